@@ -0,0 +1,312 @@
+// file: src/api.rs
+// version: 1.1.0
+// guid: 8b1d4e73-2c69-4f05-a7e8-5d3c9f0a6b21
+
+//! High-level API for embedding the agent in another program
+//!
+//! [`Agent`] bundles what the CLI sets up around a run: a cancellation token, the event bus the
+//! installers publish to and a config loader. Progress arrives as typed [`InstallEvent`]s on a
+//! [`ProgressStream`], so an orchestrator never has to parse logs. The agent works in a base
+//! directory laid out like the CLI's working directory (`presets/`, `locks/`, `logs/` and
+//! `protected.yaml`), and installs go through the same target lock, protection check and secret
+//! redaction as `ssh-install`.
+//!
+//! ```no_run
+//! # async fn run() -> ubuntu_autoinstall_agent::Result<()> {
+//! use ubuntu_autoinstall_agent::{Agent, InstallTarget};
+//!
+//! let agent = Agent::builder("/srv/autoinstall").build();
+//! let mut progress = agent.progress();
+//! tokio::spawn(async move {
+//!     while let Some(event) = progress.next().await {
+//!         println!("{:?}", event);
+//!     }
+//! });
+//! let config = agent.install_config(Some("web"), Some("configs/web-01.yaml"))?;
+//! agent.install(&InstallTarget::new("192.0.2.10", config)).await?;
+//! # Ok(())
+//! # }
+//! ```
+//!
+//! Everything re-exported from the crate root is covered by semver: it only changes
+//! incompatibly with a major version bump (a minor bump while the crate is 0.x). The modules
+//! behind it are internal to the crate.
+
+use crate::config::{loader::ConfigLoader, ImageSpec, TargetConfig};
+use crate::image::ImageBuilder;
+use crate::logging::redact;
+use crate::network::events::{EventBus, EventSubscription, InstallEvent, DEFAULT_CAPACITY};
+use crate::network::progress::ProgressReporter;
+use crate::network::ssh_installer::lock::{LockHolder, TargetLock};
+use crate::network::ssh_installer::{protection, PresetStore};
+use crate::network::{InstallationConfig, SshInstaller};
+use crate::utils::CancellationToken;
+use crate::Result;
+use std::path::{Path, PathBuf};
+
+/// Version of this API; follows the crate version
+pub const API_VERSION: &str = env!("CARGO_PKG_VERSION");
+
+/// Builder for [`Agent`]
+#[derive(Debug)]
+pub struct AgentBuilder {
+    base_dir: PathBuf,
+    cancel: Option<CancellationToken>,
+    event_capacity: Option<usize>,
+    cache_dir: Option<PathBuf>,
+}
+
+impl AgentBuilder {
+    /// Share a token with the caller so it can stop runs at their next safe point
+    pub fn cancellation_token(mut self, token: CancellationToken) -> Self {
+        self.cancel = Some(token);
+        self
+    }
+
+    /// Events a progress stream may fall behind by before it loses the oldest
+    pub fn event_capacity(mut self, capacity: usize) -> Self {
+        self.event_capacity = Some(capacity);
+        self
+    }
+
+    /// Working and cache directory for image builds
+    pub fn cache_dir<P: AsRef<Path>>(mut self, dir: P) -> Self {
+        self.cache_dir = Some(dir.as_ref().to_path_buf());
+        self
+    }
+
+    pub fn build(self) -> Agent {
+        Agent {
+            base_dir: self.base_dir,
+            cancel: self.cancel.unwrap_or_default(),
+            events: EventBus::new(self.event_capacity.unwrap_or(DEFAULT_CAPACITY)),
+            cache_dir: self.cache_dir,
+        }
+    }
+}
+
+/// A target machine to install, booted into a live or rescue system reachable over SSH
+#[derive(Debug, Clone)]
+pub struct InstallTarget {
+    pub host: String,
+    pub username: String,
+    pub config: InstallationConfig,
+    /// Host or environment name allowing the install of a protected host, like
+    /// `--i-know-what-i-am-doing`
+    pub protection_token: Option<String>,
+    /// Take over a target lock held by another run, like `--steal-lock`
+    pub steal_lock: bool,
+}
+
+impl InstallTarget {
+    /// Target reached as the live image's `ubuntu` user
+    pub fn new(host: impl Into<String>, config: InstallationConfig) -> Self {
+        Self {
+            host: host.into(),
+            username: "ubuntu".to_string(),
+            config,
+            protection_token: None,
+            steal_lock: false,
+        }
+    }
+
+    pub fn with_username(mut self, username: impl Into<String>) -> Self {
+        self.username = username.into();
+        self
+    }
+
+    pub fn with_protection_token(mut self, token: impl Into<String>) -> Self {
+        self.protection_token = Some(token.into());
+        self
+    }
+
+    pub fn with_steal_lock(mut self, steal: bool) -> Self {
+        self.steal_lock = steal;
+        self
+    }
+}
+
+/// Typed progress of the runs of one [`Agent`]
+pub struct ProgressStream {
+    subscription: EventSubscription,
+}
+
+impl ProgressStream {
+    /// Next event; waits for one, `None` once the agent is dropped
+    pub async fn next(&mut self) -> Option<InstallEvent> {
+        self.subscription.next().await
+    }
+
+    /// Next event if one is waiting
+    pub fn try_next(&mut self) -> Option<InstallEvent> {
+        self.subscription.try_next()
+    }
+}
+
+/// Entry point for library consumers
+#[derive(Debug, Clone)]
+pub struct Agent {
+    base_dir: PathBuf,
+    cancel: CancellationToken,
+    events: EventBus,
+    cache_dir: Option<PathBuf>,
+}
+
+impl Agent {
+    /// Builder for an agent working in `base_dir`
+    pub fn builder<P: AsRef<Path>>(base_dir: P) -> AgentBuilder {
+        AgentBuilder {
+            base_dir: base_dir.as_ref().to_path_buf(),
+            cancel: None,
+            event_capacity: None,
+            cache_dir: None,
+        }
+    }
+
+    /// Directory holding presets, locks, logs and `protected.yaml`
+    pub fn base_dir(&self) -> &Path {
+        &self.base_dir
+    }
+
+    /// Token that cancels this agent's runs
+    pub fn cancellation_token(&self) -> CancellationToken {
+        self.cancel.clone()
+    }
+
+    /// Stream of every event published after this call
+    pub fn progress(&self) -> ProgressStream {
+        ProgressStream {
+            subscription: self.events.subscribe(),
+        }
+    }
+
+    /// Load and validate a target config, expanding `${VAR}`s from the environment
+    pub fn load_target_config<P: AsRef<Path>>(&self, path: P) -> Result<TargetConfig> {
+        ConfigLoader::new().load_target_config(path)
+    }
+
+    /// Load and validate an image spec
+    pub fn load_image_spec<P: AsRef<Path>>(&self, path: P) -> Result<ImageSpec> {
+        ConfigLoader::new().load_image_spec(path)
+    }
+
+    /// Installation config as `ssh-install --preset --target-config` builds it: the preset
+    /// (from `presets/` in the base directory, or the built-in default) with the install
+    /// sections of the target config
+    pub fn install_config(
+        &self,
+        preset: Option<&str>,
+        target_config: Option<&str>,
+    ) -> Result<InstallationConfig> {
+        let mut config = PresetStore::in_base_dir(&self.base_dir)
+            .resolve(preset, None)?
+            .into_config();
+        config.apply_target_sections(&ConfigLoader::new(), target_config)?;
+        Ok(config)
+    }
+
+    /// Build a golden image; returns its path
+    pub async fn build_image(&self, spec: ImageSpec, output: Option<PathBuf>) -> Result<PathBuf> {
+        let mut builder = match &self.cache_dir {
+            Some(dir) => ImageBuilder::with_cache_dir(dir),
+            None => ImageBuilder::new(),
+        };
+        builder.set_cancellation_token(self.cancel.clone());
+        builder.set_event_bus(self.events.clone());
+        builder
+            .create_image(spec, output.map(|p| p.display().to_string()))
+            .await
+    }
+
+    /// Install Ubuntu on `target` over SSH
+    ///
+    /// Like `ssh-install`, this refuses protected hosts without a token or second approver,
+    /// holds the target lock locally and on the target for the whole run, and keeps the
+    /// config's secrets out of the logs.
+    pub async fn install(&self, target: &InstallTarget) -> Result<()> {
+        const COMMAND: &str = "ssh-install";
+        let names = [target.host.as_str(), target.config.hostname.as_str()];
+        protection::check(
+            &self.base_dir,
+            &names,
+            COMMAND,
+            target.protection_token.as_deref(),
+        )
+        .await?;
+        let _lock = TargetLock::acquire(&self.base_dir, &target.host, COMMAND, target.steal_lock)?;
+        redact::register(&target.config.luks_key);
+        redact::register(&target.config.root_password);
+
+        let mut installer = SshInstaller::new();
+        installer.set_cancellation_token(self.cancel.clone());
+        installer.set_event_bus(self.events.clone());
+        installer.set_progress(
+            ProgressReporter::new(&Default::default())?.with_bus(self.events.clone()),
+        );
+        installer.connect(&target.host, &target.username).await?;
+        installer
+            .acquire_target_lock(&LockHolder::current(COMMAND), target.steal_lock)
+            .await?;
+        let result = installer.perform_installation(&target.config).await;
+        if let Err(e) = installer.release_target_lock().await {
+            tracing::warn!("Could not remove the target lock marker: {}", e);
+        }
+        result
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn test_progress_stream_receives_agent_events() {
+        let dir = tempfile::TempDir::new().unwrap();
+        let agent = Agent::builder(dir.path()).event_capacity(8).build();
+        let mut progress = agent.progress();
+        agent.events.publish(InstallEvent::Vm {
+            message: "booted".to_string(),
+        });
+        match progress.next().await {
+            Some(InstallEvent::Vm { message }) => assert_eq!(message, "booted"),
+            other => panic!("unexpected event {:?}", other),
+        }
+        assert!(progress.try_next().is_none());
+
+        let target = InstallTarget::new("192.0.2.10", InstallationConfig::for_len_serv_003())
+            .with_username("root");
+        assert_eq!(target.username, "root");
+        assert!(!agent.cancellation_token().is_cancelled());
+        assert_eq!(agent.base_dir(), dir.path());
+    }
+
+    #[tokio::test]
+    async fn test_install_refuses_protected_and_locked_targets() {
+        let dir = tempfile::TempDir::new().unwrap();
+        std::fs::write(
+            dir.path().join("protected.yaml"),
+            "hosts:\n  db-01:\n    protected: true\n",
+        )
+        .unwrap();
+        let agent = Agent::builder(dir.path()).build();
+        let mut config = InstallationConfig::for_len_serv_003();
+        config.hostname = "db-01".to_string();
+        let err = agent
+            .install(&InstallTarget::new("192.0.2.10", config.clone()))
+            .await
+            .unwrap_err();
+        assert!(
+            err.to_string().contains("Refusing `ssh-install`"),
+            "{}",
+            err
+        );
+
+        // Another run holding the target lock stops the install before it connects
+        config.hostname = "web-01".to_string();
+        let _held = TargetLock::acquire(dir.path(), "192.0.2.10", "deploy", false).unwrap();
+        assert!(agent
+            .install(&InstallTarget::new("192.0.2.10", config))
+            .await
+            .is_err());
+    }
+}
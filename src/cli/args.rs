@@ -1,10 +1,11 @@
 // file: src/cli/args.rs
-// version: 1.4.0
+// version: 1.60.0
 // guid: f6g7h8i9-j0k1-2345-6789-012345fghijk
 
 //! Command line argument definitions
 
 use crate::config::Architecture;
+use crate::network::ssh_installer::install_report::InstallReportFormat;
 use clap::{Parser, Subcommand};
 
 #[derive(Parser)]
@@ -20,6 +21,21 @@ pub struct Cli {
 
     #[arg(short, long, global = true)]
     pub quiet: bool,
+
+    #[arg(
+        long,
+        global = true,
+        help = "Take over a target lock held by another operator or process"
+    )]
+    pub steal_lock: bool,
+
+    #[arg(
+        long = "i-know-what-i-am-doing",
+        value_name = "TOKEN",
+        global = true,
+        help = "Run a destructive command against a host in protected.yaml; TOKEN is the protected host's or environment's name"
+    )]
+    pub protection_token: Option<String>,
 }
 
 #[derive(Subcommand)]
@@ -40,6 +56,72 @@ pub enum Commands {
 
         #[arg(short, long, help = "Directory for caching ISOs and temporary files")]
         cache_dir: Option<String>,
+
+        #[arg(
+            long,
+            help = "CPU cores for the build VM (default: host CPUs minus one, up to 8)"
+        )]
+        vm_cpus: Option<u32>,
+
+        #[arg(
+            long,
+            value_name = "MB",
+            help = "Memory for the build VM (default: available memory minus 2 GB, up to 8 GB)"
+        )]
+        vm_mem: Option<u32>,
+
+        #[arg(long, help = "Build under software emulation when KVM is unavailable")]
+        allow_tcg: bool,
+
+        #[arg(
+            long,
+            value_name = "SECS",
+            default_value_t = 30,
+            help = "Seconds between build VM screenshots kept for failures (0: final screenshot only)"
+        )]
+        screenshot_interval: u64,
+
+        #[arg(
+            long,
+            help = "Leave the build VM paused on failure for inspection over VNC instead of killing it"
+        )]
+        pause_on_failure: bool,
+
+        #[arg(
+            long,
+            value_name = "LOCKFILE",
+            help = "Refuse to build unless the host matches this build environment lockfile"
+        )]
+        reproduce: Option<String>,
+    },
+
+    /// Capture a golden image from an existing reference machine over SSH
+    CaptureImage {
+        #[arg(short = 'H', long, help = "Reference machine IP address or hostname")]
+        host: String,
+
+        #[arg(short, long, default_value = "root", help = "SSH username")]
+        username: String,
+
+        #[arg(short, long)]
+        output: Option<String>,
+
+        #[arg(short, long, help = "Directory for caching ISOs and temporary files")]
+        cache_dir: Option<String>,
+
+        #[arg(
+            long,
+            value_name = "PATH",
+            help = "Additional path or glob to leave out of the image (repeatable)"
+        )]
+        exclude: Vec<String>,
+
+        #[arg(
+            long,
+            default_value_t = 2,
+            help = "Free space added to the image on top of the captured data, in GiB"
+        )]
+        extra_space_gb: u32,
     },
 
     /// Deploy image to target machine
@@ -58,6 +140,62 @@ pub enum Commands {
 
         #[arg(long)]
         dry_run: bool,
+
+        #[arg(
+            long,
+            value_name = "DIR",
+            help = "Copy this directory tree into the deployed filesystem before first boot (ownership and modes from DIR/.overlay.yaml)"
+        )]
+        overlay: Option<String>,
+
+        #[arg(
+            long,
+            help = "Write the whole disk image to the target disk in chunks, reading each back and rewriting mismatches"
+        )]
+        verify_writes: bool,
+
+        #[arg(
+            long,
+            value_name = "MB",
+            default_value_t = 64,
+            requires = "verify_writes",
+            help = "Chunk size for --verify-writes"
+        )]
+        write_chunk_mb: u32,
+
+        #[arg(
+            long,
+            help = "Before customizing, compare file hashes of the deployed root with the golden image's content manifest (<image>.content.json, generated when missing)"
+        )]
+        verify_content: bool,
+
+        #[arg(
+            long,
+            value_name = "FILES",
+            default_value_t = 256,
+            requires = "verify_content",
+            help = "Files sampled by --verify-content; 0 hashes every file and compares the root hash"
+        )]
+        verify_sample: usize,
+    },
+
+    /// Deploy an image as a VM on vCenter/ESXi (uses the config's vsphere: section)
+    DeployVsphere {
+        #[arg(short, long)]
+        config: String,
+
+        #[arg(
+            short = 'i',
+            long,
+            help = "Golden image: qcow2 (converted), stream-optimized VMDK or OVA"
+        )]
+        image: String,
+
+        #[arg(long, help = "VM name (default: the config's hostname)")]
+        name: Option<String>,
+
+        #[arg(long)]
+        dry_run: bool,
     },
 
     /// Validate image integrity
@@ -66,6 +204,27 @@ pub enum Commands {
         image: String,
     },
 
+    /// Validate every target config, image spec and inventory under a directory
+    ValidateConfig {
+        #[arg(
+            long,
+            help = "Directory searched recursively for *.yaml and *.yml files"
+        )]
+        dir: String,
+
+        #[arg(long, value_enum, default_value = "text", help = "Report format")]
+        format: ValidationFormatArg,
+
+        #[arg(short, long, help = "Write the report to this file instead of stdout")]
+        output: Option<String>,
+
+        #[arg(
+            long,
+            help = "Count files that could not be checked (missing env vars, fact templates) as failures"
+        )]
+        strict: bool,
+    },
+
     /// Check system prerequisites
     CheckPrereqs,
 
@@ -95,6 +254,13 @@ pub enum Commands {
         #[arg(short = 'n', long, help = "Target hostname for the installation")]
         hostname: Option<String>,
 
+        #[arg(
+            long,
+            value_name = "NAME",
+            help = "Install preset from presets/<NAME>.yaml or the built-ins (defaults to one named after --hostname, then len-serv-003)"
+        )]
+        preset: Option<String>,
+
         #[arg(short, long, default_value = "ubuntu", help = "SSH username")]
         username: Option<String>,
 
@@ -110,141 +276,1772 @@ pub enum Commands {
         )]
         hold_on_failure: bool,
 
+        #[arg(
+            long,
+            value_name = "MINUTES",
+            default_value_t = 240,
+            help = "Clean up a held session (unmount, export pools, close LUKS) after this many minutes; 0 holds until `sessions release`"
+        )]
+        hold_timeout: u64,
+
         #[arg(
             long,
             help = "Pause after storage setup (partitioning, formatting, LUKS, ZFS pools/datasets) and print next commands to run manually"
         )]
         pause_after_storage: bool,
-    },
 
-    /// Install Ubuntu locally (on current live system)
-    LocalInstall {
-        #[arg(short = 'n', long, help = "Hostname for the new installation")]
-        hostname: Option<String>,
+        #[arg(
+            long,
+            value_name = "PHASE",
+            help = "Wait for approval before this phase (phase_0 to phase_6; repeatable), in addition to the target config's `confirmation.pause_before`"
+        )]
+        pause_before: Vec<String>,
 
-        #[arg(long, help = "Only investigate system, don't install")]
-        investigate_only: bool,
+        #[arg(
+            long,
+            value_name = "DEVICE",
+            help = "Additional disk to receive a mirrored ESP kept in sync with the primary (repeatable; disk is wiped)"
+        )]
+        esp_mirror: Vec<String>,
 
-        #[arg(long, help = "Show what would be done without executing")]
-        dry_run: bool,
+        #[arg(
+            long,
+            value_name = "PATH",
+            help = "Target config YAML whose `kernel:` (sysctl, modules), `hardening:` and `zfs_tuning:` sections and `apt_snapshot:` pin are applied to the install, and whose `bmc:` section adds Redfish inventory to the report"
+        )]
+        target_config: Option<String>,
 
-        #[arg(long, help = "Hold on failure for debugging")]
-        hold_on_failure: bool,
+        #[arg(
+            long,
+            value_name = "TIMESTAMP",
+            help = "Pin debootstrap and apt to a snapshot.ubuntu.com timestamp (YYYYMMDDTHHMMSSZ), `now`, or `previous` to reuse the last install's pin"
+        )]
+        apt_snapshot: Option<String>,
 
-        #[arg(long, help = "Pause after storage setup for manual verification")]
-        pause_after_storage: bool,
+        #[arg(
+            long,
+            value_name = "RULE",
+            help = "Developer: inject a failure as <exit:CODE|timeout|disconnect>@<command substring>[#nth] (repeatable)"
+        )]
+        chaos: Vec<String>,
 
         #[arg(
             long,
-            help = "Force installation even when not in live environment (use with caution)"
+            value_name = "SPEC",
+            help = "Run commands over `serial:<device>[@<baud>]` or `sol:[<user>@]<bmc>` (password in IPMI_PASSWORD) instead of SSH"
         )]
-        force: bool,
+        transport: Option<String>,
+
+        #[arg(
+            long,
+            help = "Snapshot the root and boot datasets before the package step; on failure roll back, or write the apt commands that revert it"
+        )]
+        transactional_packages: bool,
+
+        #[arg(
+            long,
+            help = "Measure the official mirrors (or the target config's `mirror_selection:` candidates) from the target and debootstrap from the fastest"
+        )]
+        select_mirror: bool,
+
+        #[arg(
+            long,
+            help = "Disposable targets only: run the configuration commands a second time and fail the install if any fails or changes files again"
+        )]
+        audit_idempotency: bool,
+
+        #[arg(
+            long,
+            help = "Log in as an unprivileged user and run each command through NOPASSWD sudo; the target config's `privilege:` section can set a password instead"
+        )]
+        sudo: bool,
+
+        #[arg(
+            long,
+            help = "Opt in to sending anonymized phase durations, failure category and hardware class to the target config's `telemetry.endpoint`"
+        )]
+        telemetry: bool,
+
+        #[arg(
+            long,
+            help = "Reinstall onto the pools of an earlier install: replace only the ROOT and BOOT datasets and keep the rest, relinking the target config's `reinstall.preserve` datasets"
+        )]
+        reinstall: bool,
     },
-}
 
-/// Architecture argument for CLI
-#[derive(clap::ValueEnum, Clone, Debug)]
-pub enum ArchArg {
-    Amd64,
-    Arm64,
-}
+    /// Investigate a target over SSH and export a structured report
+    Investigate {
+        #[arg(short = 'H', long, help = "Target machine IP address or hostname")]
+        host: String,
 
-impl From<ArchArg> for Architecture {
-    fn from(arch: ArchArg) -> Self {
-        match arch {
-            ArchArg::Amd64 => Architecture::Amd64,
-            ArchArg::Arm64 => Architecture::Arm64,
-        }
-    }
-}
+        #[arg(short, long, default_value = "ubuntu", help = "SSH username")]
+        username: String,
 
-impl From<Architecture> for ArchArg {
-    fn from(arch: Architecture) -> Self {
-        match arch {
-            Architecture::Amd64 => ArchArg::Amd64,
-            Architecture::Arm64 => ArchArg::Arm64,
-        }
-    }
-}
+        #[arg(
+            short,
+            long,
+            value_enum,
+            default_value = "text",
+            help = "Report format"
+        )]
+        format: ReportFormatArg,
 
-#[cfg(test)]
-mod tests {
-    use super::*;
-    use clap::Parser;
+        #[arg(short, long, help = "Write the report to this file instead of stdout")]
+        output: Option<String>,
 
-    #[test]
-    fn test_arch_arg_from_architecture() {
-        // Arrange
-        let amd64_arch = Architecture::Amd64;
-        let arm64_arch = Architecture::Arm64;
+        #[arg(
+            long,
+            value_name = "PATH",
+            help = "Target config YAML whose `bmc:` section is used to add Redfish hardware inventory"
+        )]
+        target_config: Option<String>,
 
-        // Act
-        let amd64_arg: ArchArg = amd64_arch.into();
-        let arm64_arg: ArchArg = arm64_arch.into();
+        #[arg(
+            long,
+            help = "Name the hardware baseline is kept under in logs/<hostname>/ (default: the target's own hostname)"
+        )]
+        hostname: Option<String>,
 
-        // Assert
-        assert!(matches!(amd64_arg, ArchArg::Amd64));
-        assert!(matches!(arm64_arg, ArchArg::Arm64));
-    }
+        #[arg(
+            long,
+            help = "Replace the stored hardware baseline with this run's hardware"
+        )]
+        rebaseline: bool,
+    },
 
-    #[test]
-    fn test_architecture_from_arch_arg() {
-        // Arrange
-        let amd64_arg = ArchArg::Amd64;
-        let arm64_arg = ArchArg::Arm64;
+    /// Render the installation report for a host from its last session record
+    Report {
+        #[arg(
+            short = 'n',
+            long,
+            help = "Hostname whose logs/<hostname>/session.json is read"
+        )]
+        hostname: String,
 
-        // Act
-        let amd64_arch: Architecture = amd64_arg.into();
-        let arm64_arch: Architecture = arm64_arg.into();
+        #[arg(
+            short,
+            long,
+            value_enum,
+            default_value = "markdown",
+            help = "Report format"
+        )]
+        format: InstallReportFormatArg,
 
-        // Assert
-        assert_eq!(amd64_arch, Architecture::Amd64);
-        assert_eq!(arm64_arch, Architecture::Arm64);
-    }
+        #[arg(short, long, help = "Write the report to this file instead of stdout")]
+        output: Option<String>,
+    },
 
-    #[test]
-    fn test_cli_parsing_create_image_minimal() {
-        // Arrange
-        let args = vec!["ubuntu-autoinstall-agent", "create-image"];
+    /// Pack a session's logs, reports, redacted config and plan into a .tar.gz for an issue
+    SupportBundle {
+        #[arg(help = "Session id (or a unique prefix of it), or a hostname for its last install")]
+        session: String,
 
-        // Act
-        let cli = Cli::try_parse_from(args).unwrap();
+        #[arg(
+            short,
+            long,
+            help = "Archive path (defaults to support-bundle-<hostname>-<id>.tar.gz here)"
+        )]
+        output: Option<String>,
 
-        // Assert
-        match cli.command {
-            Commands::CreateImage {
-                arch,
-                version,
-                output,
-                spec,
-                cache_dir,
-            } => {
-                assert!(matches!(arch, ArchArg::Amd64));
-                assert_eq!(version, "24.04");
-                assert!(output.is_none());
-                assert!(spec.is_none());
-                assert!(cache_dir.is_none());
-            }
-            _ => panic!("Expected CreateImage command"),
-        }
-    }
+        #[arg(
+            long,
+            value_name = "NAME",
+            help = "Preset the install used (defaults to one named after the host)"
+        )]
+        preset: Option<String>,
 
-    #[test]
+        #[arg(long, value_name = "PATH", help = "Target config the install used")]
+        target_config: Option<String>,
+    },
+
+    /// Kexec a running host into the Ubuntu live environment (no PXE or media needed)
+    KexecBoot {
+        #[arg(short = 'H', long, help = "Target machine IP address or hostname")]
+        host: String,
+
+        #[arg(short, long, default_value = "root", help = "SSH username")]
+        username: String,
+
+        #[arg(
+            long,
+            default_value = "24.04",
+            help = "Ubuntu release of the live environment"
+        )]
+        version: String,
+
+        #[arg(short, long, value_enum, default_value = "amd64")]
+        arch: ArchArg,
+
+        #[arg(long, help = "Autoinstall seed URL (nocloud-net datasource)")]
+        seed_url: Option<String>,
+
+        #[arg(long, help = "Override live kernel URL")]
+        kernel_url: Option<String>,
+
+        #[arg(long, help = "Override live initrd URL")]
+        initrd_url: Option<String>,
+
+        #[arg(long, help = "Override live server ISO URL")]
+        iso_url: Option<String>,
+
+        #[arg(
+            long = "cmdline",
+            help = "Extra kernel command line argument (repeatable)"
+        )]
+        extra_cmdline: Vec<String>,
+
+        #[arg(
+            long,
+            default_value = "900",
+            help = "Seconds to wait for SSH in the live environment"
+        )]
+        wait_timeout: u64,
+
+        #[arg(
+            long,
+            help = "Continue with ssh-install once the live environment is up"
+        )]
+        then_install: bool,
+
+        #[arg(short = 'n', long, help = "Target hostname for the installation")]
+        hostname: Option<String>,
+
+        #[arg(long, help = "Show the commands without executing them")]
+        dry_run: bool,
+    },
+
+    /// Copy this binary onto a target so it is available there (e.g. on first boot)
+    SelfInstall {
+        #[arg(short = 'H', long, help = "Target machine IP address or hostname")]
+        host: String,
+
+        #[arg(short, long, default_value = "root", help = "SSH username")]
+        username: String,
+
+        #[arg(
+            long,
+            default_value = crate::network::self_install::DEFAULT_PATH,
+            help = "Install path on the target"
+        )]
+        path: String,
+
+        #[arg(long, help = "Mounted target root to install into, e.g. /mnt/targetos")]
+        root: Option<String>,
+
+        #[arg(long, help = "Show the commands without executing them")]
+        dry_run: bool,
+    },
+
+    /// Compare an installed host against its recorded install baseline
+    DriftCheck {
+        #[arg(short = 'H', long, help = "Target machine IP address or hostname")]
+        host: String,
+
+        #[arg(
+            short = 'n',
+            long,
+            help = "Hostname the baseline was recorded under (defaults to --host)"
+        )]
+        hostname: Option<String>,
+
+        #[arg(short, long, default_value = "root", help = "SSH username")]
+        username: String,
+
+        #[arg(
+            long,
+            default_value = "1",
+            help = "Drift score at which the host counts as drifted"
+        )]
+        threshold: usize,
+
+        #[arg(
+            long,
+            help = "Reinstall the host (kexec-boot + ssh-install) when drift reaches the threshold"
+        )]
+        reinstall: bool,
+
+        #[arg(long, help = "Print the drift report as JSON")]
+        json: bool,
+    },
+
+    /// Run the `verification:` service probes of a target config against an installed host
+    VerifyHost {
+        #[arg(short = 'H', long, help = "Target machine IP address or hostname")]
+        host: String,
+
+        #[arg(
+            short = 'n',
+            long,
+            help = "Hostname whose install session records the results (defaults to --host)"
+        )]
+        hostname: Option<String>,
+
+        #[arg(short, long, default_value = "root", help = "SSH username")]
+        username: String,
+
+        #[arg(
+            short = 'c',
+            long,
+            help = "Target config file with the verification section"
+        )]
+        target_config: String,
+    },
+
+    /// Reconstruct a target config from an installed host
+    ExportConfig {
+        #[arg(short = 'H', long, help = "Installed host to read")]
+        host: String,
+
+        #[arg(short, long, default_value = "root", help = "SSH username")]
+        username: String,
+
+        #[arg(
+            short,
+            long,
+            help = "Write the target config to this file instead of stdout"
+        )]
+        output: Option<String>,
+    },
+
+    /// Upgrade an installed host to a new Ubuntu release with snapshot safety points
+    Upgrade {
+        #[arg(short = 'H', long, help = "Installed host to upgrade")]
+        host: String,
+
+        #[arg(
+            short = 'n',
+            long,
+            help = "Hostname the session record is kept under (defaults to --host)"
+        )]
+        hostname: Option<String>,
+
+        #[arg(short, long, default_value = "root", help = "SSH username")]
+        username: String,
+
+        #[arg(
+            long = "to",
+            value_name = "CODENAME",
+            help = "Release to upgrade to (e.g. plucky)"
+        )]
+        to_release: String,
+
+        #[arg(
+            long,
+            value_name = "UNIT",
+            help = "Unit that must be active after the upgrade (repeatable); any newly failed unit also fails it"
+        )]
+        check_service: Vec<String>,
+
+        #[arg(
+            long,
+            help = "Upgrade packages without rebooting; the new release is not verified and failures after the reboot are not rolled back"
+        )]
+        no_reboot: bool,
+
+        #[arg(
+            long,
+            default_value = "900",
+            help = "Seconds to wait for the host to come back after each reboot"
+        )]
+        reboot_timeout: u64,
+
+        #[arg(
+            long,
+            value_name = "URL",
+            help = "POST the session record as JSON when the upgrade starts and ends"
+        )]
+        webhook: Option<String>,
+
+        #[arg(
+            long,
+            help = "Skip the boot environment of the running system created before the upgrade"
+        )]
+        no_boot_env: bool,
+
+        #[arg(long, help = "Print the upgrade steps without changing the host")]
+        dry_run: bool,
+    },
+
+    /// List, create, activate and destroy ZFS boot environments on an installed host
+    BootEnv {
+        #[arg(short = 'H', long, help = "Installed host")]
+        host: String,
+
+        #[arg(
+            short = 'n',
+            long,
+            help = "Hostname whose preset gives the pool names (defaults to --host)"
+        )]
+        hostname: Option<String>,
+
+        #[arg(short, long, default_value = "root", help = "SSH username")]
+        username: String,
+
+        #[arg(long, help = "Show the commands without executing them")]
+        dry_run: bool,
+
+        #[command(subcommand)]
+        action: BootEnvAction,
+    },
+
+    /// Grow partitions, LUKS and pools of an installed host into new disk space
+    Storage {
+        #[arg(short = 'H', long, help = "Installed host")]
+        host: String,
+
+        #[arg(short, long, default_value = "root", help = "SSH username")]
+        username: String,
+
+        #[arg(
+            long,
+            help = "Show what was found and the commands without executing them"
+        )]
+        dry_run: bool,
+
+        #[command(subcommand)]
+        action: StorageAction,
+    },
+
+    /// Snapshot an installed host's ZFS pools and send them to a backup target
+    Backup {
+        #[arg(short = 'H', long, help = "Host to back up")]
+        host: String,
+
+        #[arg(
+            short = 'n',
+            long,
+            help = "Hostname the backup catalog is kept under (defaults to --host)"
+        )]
+        hostname: Option<String>,
+
+        #[arg(short, long, default_value = "root", help = "SSH username")]
+        username: String,
+
+        #[arg(
+            short,
+            long,
+            help = "Directory on the host, or ssh://[user@]backup-host/pool/dataset"
+        )]
+        target: String,
+
+        #[arg(long, help = "Send a full stream even if an earlier snapshot exists")]
+        full: bool,
+
+        #[arg(
+            long,
+            value_name = "KBPS",
+            help = "Cap the send stream at this rate in KiB/s"
+        )]
+        bwlimit: Option<u64>,
+
+        #[arg(
+            long,
+            help = "Run the send stream with idle IO priority and lowest CPU priority"
+        )]
+        io_idle: bool,
+
+        #[arg(
+            long,
+            value_name = "WEIGHT",
+            help = "cgroup IO weight for the send stream (1-10000)"
+        )]
+        io_weight: Option<u32>,
+
+        #[arg(long, help = "Show the commands without executing them")]
+        dry_run: bool,
+    },
+
+    /// Recreate pools on a fresh disk and restore a host from its ZFS backups
+    Restore {
+        #[arg(short = 'H', long, help = "Live environment to restore from")]
+        host: String,
+
+        #[arg(short = 'n', long, help = "Hostname whose backups are restored")]
+        hostname: String,
+
+        #[arg(short, long, default_value = "ubuntu", help = "SSH username")]
+        username: String,
+
+        #[arg(
+            short,
+            long,
+            help = "Directory on the live host, or ssh://[user@]backup-host/pool/dataset"
+        )]
+        target: String,
+
+        #[arg(long, help = "Snapshot to restore (defaults to the latest)")]
+        snapshot: Option<String>,
+
+        #[arg(long, value_name = "DEVICE", help = "Disk to restore onto (wiped)")]
+        disk: Option<String>,
+
+        #[arg(long, help = "Show the commands without executing them")]
+        dry_run: bool,
+    },
+
+    /// List install presets, or print one as YAML
+    Presets {
+        #[arg(help = "Preset to print")]
+        name: Option<String>,
+
+        #[arg(
+            long,
+            requires = "name",
+            help = "Write the preset to presets/<NAME>.yaml so it can be edited"
+        )]
+        save: bool,
+    },
+
+    /// Show or edit the variables kept for a host across reinstalls
+    HostVars {
+        #[arg(help = "Hostname the variables are kept under in logs/<hostname>/")]
+        hostname: String,
+
+        #[command(subcommand)]
+        action: HostVarsAction,
+    },
+
+    /// Inspect and verify signed provenance of images and deployments
+    Provenance {
+        #[command(subcommand)]
+        action: ProvenanceAction,
+    },
+
+    /// Act on every host of an inventory file
+    Fleet {
+        #[command(subcommand)]
+        action: FleetAction,
+    },
+
+    /// List or release sessions held open after a failed install
+    Sessions {
+        #[command(subcommand)]
+        action: SessionsAction,
+    },
+
+    /// Run a pipeline file: build, publish, roll out and verify as declared stages
+    RunPipeline {
+        #[arg(help = "Pipeline file")]
+        file: String,
+
+        #[arg(
+            long = "set",
+            value_name = "NAME=VALUE",
+            help = "Override a pipeline parameter (repeatable)"
+        )]
+        set: Vec<String>,
+
+        #[arg(
+            long,
+            value_name = "STAGE",
+            help = "Start at this stage, e.g. to resume a failed run; earlier stages are skipped"
+        )]
+        from_stage: Option<String>,
+
+        #[arg(
+            long,
+            help = "Print the stages with their parameters expanded without running them"
+        )]
+        dry_run: bool,
+    },
+
+    /// Approve or reject an install waiting at a confirmation gate
+    Approve {
+        #[arg(help = "Hostname of the waiting install")]
+        hostname: String,
+
+        #[arg(help = "Phase the install is waiting before (phase_2, 2 or \"Phase 2\")")]
+        phase: String,
+
+        #[arg(
+            long,
+            value_name = "REASON",
+            help = "Reject the phase instead; the install stops there"
+        )]
+        reject: Option<String>,
+    },
+
+    /// Redeem a host's one-time enrollment token (for report receivers)
+    EnrollVerify {
+        #[arg(short = 'n', long, help = "Hostname the token was issued for")]
+        hostname: String,
+
+        #[arg(
+            short,
+            long,
+            help = "Token presented by the host (read from stdin when omitted)"
+        )]
+        token: Option<String>,
+    },
+
+    /// Install Ubuntu locally (on current live system)
+    LocalInstall {
+        #[arg(short = 'n', long, help = "Hostname for the new installation")]
+        hostname: Option<String>,
+
+        #[arg(long, help = "Only investigate system, don't install")]
+        investigate_only: bool,
+
+        #[arg(long, help = "Show what would be done without executing")]
+        dry_run: bool,
+
+        #[arg(long, help = "Hold on failure for debugging")]
+        hold_on_failure: bool,
+
+        #[arg(long, help = "Pause after storage setup for manual verification")]
+        pause_after_storage: bool,
+
+        #[arg(
+            long,
+            help = "Force installation even when not in live environment (use with caution)"
+        )]
+        force: bool,
+    },
+}
+
+/// `provenance` subcommands
+#[derive(Subcommand, Debug, PartialEq, Eq)]
+pub enum ProvenanceAction {
+    /// Check the signature (and for images, the file digest) of a provenance document
+    Verify {
+        #[arg(help = "Image file, <image>.provenance.json, or logs/<hostname>/provenance.json")]
+        path: String,
+
+        #[arg(
+            long,
+            value_name = "KEY",
+            help = "Trusted public key as hex or a .pub file (repeatable; defaults to the local signing key)"
+        )]
+        key: Vec<String>,
+    },
+
+    /// Print the provenance statement without verifying it
+    Show {
+        #[arg(help = "Image file, <image>.provenance.json, or logs/<hostname>/provenance.json")]
+        path: String,
+    },
+}
+
+/// `host-vars` subcommands
+#[derive(Subcommand, Debug, PartialEq, Eq)]
+pub enum HostVarsAction {
+    /// Print every variable with its source and when it was set
+    Show,
+
+    /// Set a variable; the next install of the host uses it
+    Set {
+        #[arg(help = "Variable name, e.g. install.id or luks.uuid")]
+        key: String,
+
+        value: String,
+    },
+
+    /// Remove a variable so the next install chooses a new value
+    Unset { key: String },
+}
+
+/// `boot-env` subcommands
+#[derive(Subcommand, Debug, PartialEq, Eq)]
+pub enum BootEnvAction {
+    /// List the boot environments and which one is running and booted next
+    List,
+
+    /// Clone the running system as a new boot environment
+    Create {
+        #[arg(help = "Name of the new boot environment")]
+        name: String,
+    },
+
+    /// Boot the given environment from the next start on
+    Activate {
+        #[arg(help = "Boot environment to boot next")]
+        name: String,
+    },
+
+    /// Destroy a boot environment that is neither running nor booted next
+    Destroy {
+        #[arg(help = "Boot environment to destroy")]
+        name: String,
+    },
+}
+
+/// `storage` subcommands
+#[derive(Subcommand, Debug, PartialEq, Eq)]
+pub enum StorageAction {
+    /// Grow the LUKS partition, its mapping and the pool on it after a disk got bigger
+    Expand {
+        #[arg(long, default_value = "luks", help = "LUKS mapping the pool sits on")]
+        mapper: String,
+
+        #[arg(long, default_value = "rpool", help = "Pool to grow")]
+        pool: String,
+
+        #[arg(
+            long,
+            help = "Local file with the LUKS passphrase, needed when the volume key is in the kernel keyring"
+        )]
+        passphrase_file: Option<String>,
+    },
+}
+
+/// `fleet` subcommands
+#[derive(Subcommand, Debug, PartialEq, Eq)]
+pub enum FleetAction {
+    /// Install every inventory host: canaries first, then batches within the unavailability limit
+    Deploy {
+        #[arg(help = "Inventory file listing the hosts and the rollout policy")]
+        inventory: String,
+
+        #[arg(
+            short,
+            long,
+            help = "Continue past the canary stage without asking when the policy says `promotion: prompt`"
+        )]
+        yes: bool,
+
+        #[arg(long, help = "Print the canary stage and batches without installing")]
+        dry_run: bool,
+
+        #[arg(
+            long,
+            value_name = "SELECTOR",
+            help = "Only hosts whose labels match, e.g. 'role=worker,rack!=12'"
+        )]
+        selector: Option<String>,
+    },
+
+    /// Read facts from every inventory host and export them as one dataset
+    Facts {
+        #[arg(help = "Inventory file listing the hosts")]
+        inventory: String,
+
+        #[arg(
+            short,
+            long,
+            value_enum,
+            default_value = "json",
+            help = "Export format"
+        )]
+        format: FactsFormatArg,
+
+        #[arg(short, long, help = "Write the export to this file instead of stdout")]
+        output: Option<String>,
+
+        #[arg(
+            long,
+            value_name = "SELECTOR",
+            help = "Only hosts whose labels match, e.g. 'role=worker,rack!=12'"
+        )]
+        selector: Option<String>,
+    },
+
+    /// Show what the current configs would change on every inventory host, without connecting
+    Plan {
+        #[arg(help = "Inventory file listing the hosts")]
+        inventory: String,
+
+        #[arg(long, help = "Print the previews as JSON")]
+        json: bool,
+
+        #[arg(
+            long,
+            value_name = "SELECTOR",
+            help = "Only hosts whose labels match, e.g. 'role=worker,rack!=12'"
+        )]
+        selector: Option<String>,
+    },
+
+    /// Serve the inventory's static leases on the provisioning network until Ctrl+C
+    Dhcp {
+        #[arg(help = "Inventory file with a dhcp: section and host MAC addresses")]
+        inventory: String,
+
+        #[arg(long, help = "Listen on this interface instead of dhcp.interface")]
+        interface: Option<String>,
+    },
+
+    /// Stop a running deploy: pending hosts are skipped, hosts in flight follow the policy
+    Cancel {
+        #[arg(help = "Run id printed when the deploy started")]
+        run_id: String,
+
+        #[arg(
+            long,
+            value_enum,
+            default_value = "finish-phase",
+            help = "What hosts already installing do at their next phase boundary"
+        )]
+        policy: CancelPolicyArg,
+    },
+}
+
+/// `sessions` subcommands
+#[derive(Subcommand, Debug, PartialEq, Eq)]
+pub enum SessionsAction {
+    /// Show the targets currently held for debugging and when their holds expire
+    List,
+
+    /// End a hold: the target is unmounted, its pools exported and LUKS closed, then the session closes
+    Release {
+        #[arg(help = "Hostname of the held target")]
+        hostname: String,
+
+        #[arg(long, help = "Close the session but leave the target mounted as-is")]
+        keep_mounts: bool,
+    },
+}
+
+/// What `fleet cancel` does with the hosts in flight
+#[derive(clap::ValueEnum, Clone, Copy, Debug, PartialEq, Eq)]
+pub enum CancelPolicyArg {
+    /// Finish the running phase and leave the target as-is
+    FinishPhase,
+    /// Finish the running phase and keep the SSH session open for debugging
+    Hold,
+    /// Finish the running phase, then clean up the target and wipe its disk
+    AbortCleanup,
+}
+
+/// Output format for `fleet facts`
+#[derive(clap::ValueEnum, Clone, Copy, Debug, PartialEq, Eq)]
+pub enum FactsFormatArg {
+    Json,
+    Csv,
+}
+
+/// Output format for `validate-config`
+#[derive(clap::ValueEnum, Clone, Copy, Debug, PartialEq, Eq)]
+pub enum ValidationFormatArg {
+    Text,
+    Json,
+    Junit,
+}
+
+/// Output format for exported reports
+#[derive(clap::ValueEnum, Clone, Copy, Debug, PartialEq, Eq)]
+pub enum ReportFormatArg {
+    Text,
+    Json,
+    Html,
+}
+
+/// Output format for installation reports
+#[derive(clap::ValueEnum, Clone, Copy, Debug, PartialEq, Eq)]
+pub enum InstallReportFormatArg {
+    Markdown,
+    Text,
+    Html,
+}
+
+impl From<InstallReportFormatArg> for InstallReportFormat {
+    fn from(format: InstallReportFormatArg) -> Self {
+        match format {
+            InstallReportFormatArg::Markdown => InstallReportFormat::Markdown,
+            InstallReportFormatArg::Text => InstallReportFormat::Text,
+            InstallReportFormatArg::Html => InstallReportFormat::Html,
+        }
+    }
+}
+
+/// Architecture argument for CLI
+#[derive(clap::ValueEnum, Clone, Debug)]
+pub enum ArchArg {
+    Amd64,
+    Arm64,
+}
+
+impl From<ArchArg> for Architecture {
+    fn from(arch: ArchArg) -> Self {
+        match arch {
+            ArchArg::Amd64 => Architecture::Amd64,
+            ArchArg::Arm64 => Architecture::Arm64,
+        }
+    }
+}
+
+impl From<Architecture> for ArchArg {
+    fn from(arch: Architecture) -> Self {
+        match arch {
+            Architecture::Amd64 => ArchArg::Amd64,
+            Architecture::Arm64 => ArchArg::Arm64,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use clap::Parser;
+
+    #[test]
+    fn test_arch_arg_from_architecture() {
+        // Arrange
+        let amd64_arch = Architecture::Amd64;
+        let arm64_arch = Architecture::Arm64;
+
+        // Act
+        let amd64_arg: ArchArg = amd64_arch.into();
+        let arm64_arg: ArchArg = arm64_arch.into();
+
+        // Assert
+        assert!(matches!(amd64_arg, ArchArg::Amd64));
+        assert!(matches!(arm64_arg, ArchArg::Arm64));
+    }
+
+    #[test]
+    fn test_architecture_from_arch_arg() {
+        // Arrange
+        let amd64_arg = ArchArg::Amd64;
+        let arm64_arg = ArchArg::Arm64;
+
+        // Act
+        let amd64_arch: Architecture = amd64_arg.into();
+        let arm64_arch: Architecture = arm64_arg.into();
+
+        // Assert
+        assert_eq!(amd64_arch, Architecture::Amd64);
+        assert_eq!(arm64_arch, Architecture::Arm64);
+    }
+
+    #[test]
+    fn test_cli_parsing_create_image_minimal() {
+        // Arrange
+        let args = vec!["ubuntu-autoinstall-agent", "create-image"];
+
+        // Act
+        let cli = Cli::try_parse_from(args).unwrap();
+
+        // Assert
+        match cli.command {
+            Commands::CreateImage {
+                arch,
+                version,
+                output,
+                spec,
+                cache_dir,
+                vm_cpus,
+                vm_mem,
+                allow_tcg,
+                screenshot_interval,
+                pause_on_failure,
+                reproduce,
+            } => {
+                assert!(matches!(arch, ArchArg::Amd64));
+                assert_eq!(version, "24.04");
+                assert!(output.is_none());
+                assert!(spec.is_none());
+                assert!(cache_dir.is_none());
+                assert!(vm_cpus.is_none());
+                assert!(vm_mem.is_none());
+                assert!(!allow_tcg);
+                assert_eq!(screenshot_interval, 30);
+                assert!(!pause_on_failure);
+                assert!(reproduce.is_none());
+            }
+            _ => panic!("Expected CreateImage command"),
+        }
+    }
+
+    #[test]
     fn test_cli_parsing_create_image_full() {
         // Arrange
         let args = vec![
             "ubuntu-autoinstall-agent",
-            "create-image",
-            "--arch",
-            "arm64",
-            "--version",
-            "22.04",
+            "create-image",
+            "--arch",
+            "arm64",
+            "--version",
+            "22.04",
+            "--output",
+            "/tmp/output.iso",
+            "--spec",
+            "spec.yaml",
+            "--cache-dir",
+            "/tmp/cache",
+            "--vm-cpus",
+            "6",
+            "--vm-mem",
+            "4096",
+            "--allow-tcg",
+            "--screenshot-interval",
+            "10",
+            "--pause-on-failure",
+            "--reproduce",
+            "/tmp/web.qcow2.buildenv.yaml",
+        ];
+
+        // Act
+        let cli = Cli::try_parse_from(args).unwrap();
+
+        // Assert
+        match cli.command {
+            Commands::CreateImage {
+                arch,
+                version,
+                output,
+                spec,
+                cache_dir,
+                vm_cpus,
+                vm_mem,
+                allow_tcg,
+                screenshot_interval,
+                pause_on_failure,
+                reproduce,
+            } => {
+                assert!(matches!(arch, ArchArg::Arm64));
+                assert_eq!(version, "22.04");
+                assert_eq!(output.as_deref(), Some("/tmp/output.iso"));
+                assert_eq!(spec.as_deref(), Some("spec.yaml"));
+                assert_eq!(cache_dir.as_deref(), Some("/tmp/cache"));
+                assert_eq!(vm_cpus, Some(6));
+                assert_eq!(vm_mem, Some(4096));
+                assert!(allow_tcg);
+                assert_eq!(screenshot_interval, 10);
+                assert!(pause_on_failure);
+                assert_eq!(reproduce.as_deref(), Some("/tmp/web.qcow2.buildenv.yaml"));
+            }
+            _ => panic!("Expected CreateImage command"),
+        }
+    }
+
+    #[test]
+    fn test_cli_parsing_deploy() {
+        // Arrange
+        let args = vec![
+            "ubuntu-autoinstall-agent",
+            "deploy",
+            "--target",
+            "192.168.1.100",
+            "--config",
+            "config.yaml",
+            "--image",
+            "image.iso",
+            "--via-ssh",
+            "--dry-run",
+            "--overlay",
+            "site/",
+            "--verify-content",
+            "--verify-sample",
+            "0",
+        ];
+
+        // Act
+        let cli = Cli::try_parse_from(args).unwrap();
+
+        // Assert
+        match cli.command {
+            Commands::Deploy {
+                target,
+                config,
+                image,
+                via_ssh,
+                dry_run,
+                overlay,
+                verify_writes,
+                write_chunk_mb,
+                verify_content,
+                verify_sample,
+            } => {
+                assert_eq!(target, "192.168.1.100");
+                assert_eq!(config, "config.yaml");
+                assert_eq!(image, "image.iso");
+                assert!(via_ssh);
+                assert!(dry_run);
+                assert_eq!(overlay.as_deref(), Some("site/"));
+                assert!(!verify_writes);
+                assert_eq!(write_chunk_mb, 64);
+                assert!(verify_content);
+                assert_eq!(verify_sample, 0);
+            }
+            _ => panic!("Expected Deploy command"),
+        }
+    }
+
+    #[test]
+    fn test_cli_parsing_deploy_vsphere() {
+        let cli = Cli::try_parse_from([
+            "ubuntu-autoinstall-agent",
+            "deploy-vsphere",
+            "-c",
+            "web-01.yaml",
+            "-i",
+            "golden.qcow2",
+            "--dry-run",
+        ])
+        .unwrap();
+        match cli.command {
+            Commands::DeployVsphere {
+                config,
+                image,
+                name,
+                dry_run,
+            } => {
+                assert_eq!(config, "web-01.yaml");
+                assert_eq!(image, "golden.qcow2");
+                assert!(name.is_none());
+                assert!(dry_run);
+            }
+            _ => panic!("Expected DeployVsphere command"),
+        }
+    }
+
+    #[test]
+    fn test_cli_parsing_validate() {
+        // Arrange
+        let args = vec![
+            "ubuntu-autoinstall-agent",
+            "validate",
+            "--image",
+            "test.iso",
+        ];
+
+        // Act
+        let cli = Cli::try_parse_from(args).unwrap();
+
+        // Assert
+        match cli.command {
+            Commands::Validate { image } => {
+                assert_eq!(image, "test.iso");
+            }
+            _ => panic!("Expected Validate command"),
+        }
+    }
+
+    #[test]
+    fn test_cli_parsing_check_prereqs() {
+        // Arrange
+        let args = vec!["ubuntu-autoinstall-agent", "check-prereqs"];
+
+        // Act
+        let cli = Cli::try_parse_from(args).unwrap();
+
+        // Assert
+        assert!(matches!(cli.command, Commands::CheckPrereqs));
+    }
+
+    #[test]
+    fn test_cli_parsing_list_images() {
+        // Arrange
+        let args = vec![
+            "ubuntu-autoinstall-agent",
+            "list-images",
+            "--filter-arch",
+            "amd64",
+            "--json",
+        ];
+
+        // Act
+        let cli = Cli::try_parse_from(args).unwrap();
+
+        // Assert
+        match cli.command {
+            Commands::ListImages { filter_arch, json } => {
+                assert!(matches!(filter_arch, Some(ArchArg::Amd64)));
+                assert!(json);
+            }
+            _ => panic!("Expected ListImages command"),
+        }
+    }
+
+    #[test]
+    fn test_cli_parsing_cleanup() {
+        // Arrange
+        let args = vec![
+            "ubuntu-autoinstall-agent",
+            "cleanup",
+            "--older-than-days",
+            "60",
+            "--dry-run",
+        ];
+
+        // Act
+        let cli = Cli::try_parse_from(args).unwrap();
+
+        // Assert
+        match cli.command {
+            Commands::Cleanup {
+                older_than_days,
+                dry_run,
+            } => {
+                assert_eq!(older_than_days, 60);
+                assert!(dry_run);
+            }
+            _ => panic!("Expected Cleanup command"),
+        }
+    }
+
+    #[test]
+    fn test_cli_parsing_ssh_install_minimal() {
+        // Arrange
+        let args = vec![
+            "ubuntu-autoinstall-agent",
+            "ssh-install",
+            "--host",
+            "10.0.0.5",
+        ];
+
+        // Act
+        let cli = Cli::try_parse_from(args).unwrap();
+
+        // Assert
+        match cli.command {
+            Commands::SshInstall {
+                host,
+                hostname,
+                preset,
+                username,
+                investigate_only,
+                dry_run,
+                hold_on_failure,
+                hold_timeout,
+                pause_after_storage,
+                pause_before,
+                esp_mirror,
+                target_config,
+                apt_snapshot,
+                chaos,
+                transport,
+                transactional_packages,
+                select_mirror,
+                audit_idempotency,
+                sudo,
+                telemetry,
+                reinstall,
+            } => {
+                assert_eq!(host, "10.0.0.5");
+                assert!(hostname.is_none());
+                assert!(preset.is_none());
+                assert_eq!(username.as_deref(), Some("ubuntu"));
+                assert!(!investigate_only);
+                assert!(!dry_run);
+                assert!(!hold_on_failure);
+                assert_eq!(hold_timeout, 240);
+                assert!(!pause_after_storage);
+                assert!(pause_before.is_empty());
+                assert!(esp_mirror.is_empty());
+                assert_eq!(target_config, None);
+                assert_eq!(apt_snapshot, None);
+                assert!(chaos.is_empty());
+                assert!(transport.is_none());
+                assert!(!transactional_packages);
+                assert!(!audit_idempotency);
+                assert!(!sudo);
+                assert!(!telemetry);
+                assert!(!select_mirror);
+                assert!(!reinstall);
+            }
+            _ => panic!("Expected SshInstall command"),
+        }
+    }
+
+    #[test]
+    fn test_cli_parsing_ssh_install_full() {
+        // Arrange
+        let args = vec![
+            "ubuntu-autoinstall-agent",
+            "ssh-install",
+            "--host",
+            "server.example.com",
+            "--hostname",
+            "prod-web-01",
+            "--preset",
+            "web",
+            "--username",
+            "admin",
+            "--investigate-only",
+            "--dry-run",
+            "--hold-on-failure",
+            "--hold-timeout",
+            "0",
+            "--pause-after-storage",
+            "--pause-before",
+            "phase_2",
+            "--pause-before",
+            "6",
+            "--esp-mirror",
+            "/dev/nvme1n1",
+            "--esp-mirror",
+            "/dev/nvme2n1",
+            "--target-config",
+            "targets/host.yaml",
+            "--apt-snapshot",
+            "previous",
+            "--chaos",
+            "exit:1@zpool create",
+            "--chaos",
+            "disconnect@debootstrap#2",
+            "--transport",
+            "serial:/dev/ttyUSB0@115200",
+            "--transactional-packages",
+            "--select-mirror",
+            "--audit-idempotency",
+            "--sudo",
+            "--telemetry",
+            "--reinstall",
+        ];
+
+        // Act
+        let cli = Cli::try_parse_from(args).unwrap();
+
+        // Assert
+        match cli.command {
+            Commands::SshInstall {
+                host,
+                hostname,
+                preset,
+                username,
+                investigate_only,
+                dry_run,
+                hold_on_failure,
+                hold_timeout,
+                pause_after_storage,
+                pause_before,
+                esp_mirror,
+                target_config,
+                apt_snapshot,
+                chaos,
+                transport,
+                transactional_packages,
+                select_mirror,
+                audit_idempotency,
+                sudo,
+                telemetry,
+                reinstall,
+            } => {
+                assert_eq!(host, "server.example.com");
+                assert_eq!(hostname.as_deref(), Some("prod-web-01"));
+                assert_eq!(preset.as_deref(), Some("web"));
+                assert_eq!(username.as_deref(), Some("admin"));
+                assert!(investigate_only);
+                assert!(dry_run);
+                assert!(hold_on_failure);
+                assert_eq!(hold_timeout, 0);
+                assert!(pause_after_storage);
+                assert_eq!(pause_before, vec!["phase_2", "6"]);
+                assert_eq!(esp_mirror, vec!["/dev/nvme1n1", "/dev/nvme2n1"]);
+                assert_eq!(target_config.as_deref(), Some("targets/host.yaml"));
+                assert_eq!(apt_snapshot.as_deref(), Some("previous"));
+                assert_eq!(
+                    chaos,
+                    vec!["exit:1@zpool create", "disconnect@debootstrap#2"]
+                );
+                assert_eq!(transport.as_deref(), Some("serial:/dev/ttyUSB0@115200"));
+                assert!(transactional_packages);
+                assert!(select_mirror);
+                assert!(audit_idempotency);
+                assert!(sudo);
+                assert!(telemetry);
+                assert!(reinstall);
+            }
+            _ => panic!("Expected SshInstall command"),
+        }
+    }
+
+    #[test]
+    fn test_cli_global_flags() {
+        // Arrange
+        let args = vec![
+            "ubuntu-autoinstall-agent",
+            "--verbose",
+            "--quiet",
+            "check-prereqs",
+        ];
+
+        // Act
+        let cli = Cli::try_parse_from(args).unwrap();
+
+        // Assert
+        assert!(cli.verbose);
+        assert!(cli.quiet);
+        assert!(!cli.steal_lock);
+        assert!(matches!(cli.command, Commands::CheckPrereqs));
+
+        let cli = Cli::try_parse_from([
+            "ubuntu-autoinstall-agent",
+            "restore",
+            "-H",
+            "h",
+            "-n",
+            "a",
+            "-t",
+            "/b",
+            "--steal-lock",
+        ])
+        .unwrap();
+        assert!(cli.steal_lock);
+    }
+
+    #[test]
+    fn test_cli_parsing_local_install_minimal() {
+        // Arrange
+        let args = vec!["ubuntu-autoinstall-agent", "local-install"];
+
+        // Act
+        let cli = Cli::try_parse_from(args).unwrap();
+
+        // Assert
+        match cli.command {
+            Commands::LocalInstall {
+                hostname,
+                investigate_only,
+                dry_run,
+                hold_on_failure,
+                pause_after_storage,
+                force,
+            } => {
+                assert!(hostname.is_none());
+                assert!(!investigate_only);
+                assert!(!dry_run);
+                assert!(!hold_on_failure);
+                assert!(!pause_after_storage);
+                assert!(!force);
+                assert!(!hold_on_failure);
+                assert!(!pause_after_storage);
+            }
+            _ => panic!("Expected LocalInstall command"),
+        }
+    }
+
+    #[test]
+    fn test_cli_parsing_local_install_full() {
+        // Arrange
+        let args = vec![
+            "ubuntu-autoinstall-agent",
+            "local-install",
+            "--hostname",
+            "local-server",
+            "--investigate-only",
+            "--dry-run",
+            "--hold-on-failure",
+            "--pause-after-storage",
+        ];
+
+        // Act
+        let cli = Cli::try_parse_from(args).unwrap();
+
+        // Assert
+        match cli.command {
+            Commands::LocalInstall {
+                hostname,
+                investigate_only,
+                dry_run,
+                hold_on_failure,
+                pause_after_storage,
+                force,
+            } => {
+                assert_eq!(hostname.as_deref(), Some("local-server"));
+                assert!(investigate_only);
+                assert!(dry_run);
+                assert!(hold_on_failure);
+                assert!(pause_after_storage);
+                assert!(!force);
+            }
+            _ => panic!("Expected LocalInstall command"),
+        }
+    }
+
+    #[test]
+    fn test_cli_parsing_kexec_boot() {
+        // Arrange
+        let args = vec![
+            "ubuntu-autoinstall-agent",
+            "kexec-boot",
+            "--host",
+            "10.0.0.5",
+            "--seed-url",
+            "http://10.0.0.1/seed/",
+            "--cmdline",
+            "console=ttyS0",
+            "--then-install",
+        ];
+
+        // Act
+        let cli = Cli::try_parse_from(args).unwrap();
+
+        // Assert
+        match cli.command {
+            Commands::KexecBoot {
+                host,
+                username,
+                version,
+                seed_url,
+                extra_cmdline,
+                wait_timeout,
+                then_install,
+                dry_run,
+                ..
+            } => {
+                assert_eq!(host, "10.0.0.5");
+                assert_eq!(username, "root");
+                assert_eq!(version, "24.04");
+                assert_eq!(seed_url.as_deref(), Some("http://10.0.0.1/seed/"));
+                assert_eq!(extra_cmdline, vec!["console=ttyS0".to_string()]);
+                assert_eq!(wait_timeout, 900);
+                assert!(then_install);
+                assert!(!dry_run);
+            }
+            _ => panic!("Expected KexecBoot command"),
+        }
+    }
+
+    #[test]
+    fn test_parse_self_install() {
+        let cli = Cli::parse_from([
+            "uaa",
+            "self-install",
+            "-H",
+            "10.0.0.5",
+            "--root",
+            "/mnt/targetos",
+        ]);
+        match cli.command {
+            Commands::SelfInstall {
+                host,
+                username,
+                path,
+                root,
+                dry_run,
+            } => {
+                assert_eq!(host, "10.0.0.5");
+                assert_eq!(username, "root");
+                assert_eq!(path, "/usr/local/bin/ubuntu-autoinstall-agent");
+                assert_eq!(root.as_deref(), Some("/mnt/targetos"));
+                assert!(!dry_run);
+            }
+            _ => panic!("Expected SelfInstall command"),
+        }
+    }
+
+    #[test]
+    fn test_cli_parsing_drift_check() {
+        // Arrange
+        let args = vec![
+            "ubuntu-autoinstall-agent",
+            "drift-check",
+            "--host",
+            "10.0.0.5",
+            "--threshold",
+            "5",
+            "--reinstall",
+        ];
+
+        // Act
+        let cli = Cli::try_parse_from(args).unwrap();
+
+        // Assert
+        match cli.command {
+            Commands::DriftCheck {
+                host,
+                hostname,
+                username,
+                threshold,
+                reinstall,
+                json,
+            } => {
+                assert_eq!(host, "10.0.0.5");
+                assert_eq!(hostname, None);
+                assert_eq!(username, "root");
+                assert_eq!(threshold, 5);
+                assert!(reinstall);
+                assert!(!json);
+            }
+            _ => panic!("Expected DriftCheck command"),
+        }
+    }
+
+    #[test]
+    fn test_cli_parsing_verify_host() {
+        // Arrange
+        let args = vec![
+            "ubuntu-autoinstall-agent",
+            "verify-host",
+            "-H",
+            "10.0.0.5",
+            "-n",
+            "web-01",
+            "-c",
+            "web-01.yaml",
+        ];
+
+        // Act
+        let cli = Cli::try_parse_from(args).unwrap();
+
+        // Assert
+        match cli.command {
+            Commands::VerifyHost {
+                host,
+                hostname,
+                username,
+                target_config,
+            } => {
+                assert_eq!(host, "10.0.0.5");
+                assert_eq!(hostname.as_deref(), Some("web-01"));
+                assert_eq!(username, "root");
+                assert_eq!(target_config, "web-01.yaml");
+            }
+            _ => panic!("Expected VerifyHost command"),
+        }
+    }
+
+    #[test]
+    fn test_cli_parsing_export_config() {
+        // Arrange
+        let args = vec![
+            "ubuntu-autoinstall-agent",
+            "export-config",
+            "-H",
+            "10.0.0.5",
             "--output",
-            "/tmp/output.iso",
-            "--spec",
-            "spec.yaml",
-            "--cache-dir",
-            "/tmp/cache",
+            "web-01.yaml",
+        ];
+
+        // Act
+        let cli = Cli::try_parse_from(args).unwrap();
+
+        // Assert
+        match cli.command {
+            Commands::ExportConfig {
+                host,
+                username,
+                output,
+            } => {
+                assert_eq!(host, "10.0.0.5");
+                assert_eq!(username, "root");
+                assert_eq!(output.as_deref(), Some("web-01.yaml"));
+            }
+            _ => panic!("Expected ExportConfig command"),
+        }
+    }
+
+    #[test]
+    fn test_cli_parsing_upgrade() {
+        // Arrange
+        let args = vec![
+            "ubuntu-autoinstall-agent",
+            "upgrade",
+            "-H",
+            "10.0.0.5",
+            "--to",
+            "plucky",
+            "--check-service",
+            "nginx.service",
+            "--webhook",
+            "https://hooks.example/upgrades",
+        ];
+
+        // Act
+        let cli = Cli::try_parse_from(args).unwrap();
+
+        // Assert
+        match cli.command {
+            Commands::Upgrade {
+                host,
+                hostname,
+                username,
+                to_release,
+                check_service,
+                no_reboot,
+                reboot_timeout,
+                webhook,
+                no_boot_env,
+                dry_run,
+            } => {
+                assert_eq!(host, "10.0.0.5");
+                assert!(hostname.is_none());
+                assert_eq!(username, "root");
+                assert_eq!(to_release, "plucky");
+                assert_eq!(check_service, vec!["nginx.service"]);
+                assert!(!no_reboot);
+                assert_eq!(reboot_timeout, 900);
+                assert_eq!(webhook.as_deref(), Some("https://hooks.example/upgrades"));
+                assert!(!no_boot_env);
+                assert!(!dry_run);
+            }
+            _ => panic!("Expected Upgrade command"),
+        }
+    }
+
+    #[test]
+    fn test_cli_parsing_boot_env() {
+        let cli = Cli::try_parse_from([
+            "ubuntu-autoinstall-agent",
+            "boot-env",
+            "-H",
+            "10.0.0.5",
+            "activate",
+            "before-kernel",
+        ])
+        .unwrap();
+        match cli.command {
+            Commands::BootEnv {
+                host,
+                hostname,
+                username,
+                dry_run,
+                action,
+            } => {
+                assert_eq!(host, "10.0.0.5");
+                assert!(hostname.is_none());
+                assert_eq!(username, "root");
+                assert!(!dry_run);
+                assert_eq!(
+                    action,
+                    BootEnvAction::Activate {
+                        name: "before-kernel".to_string()
+                    }
+                );
+            }
+            _ => panic!("Expected BootEnv command"),
+        }
+    }
+
+    #[test]
+    fn test_cli_parsing_storage_expand() {
+        let cli = Cli::try_parse_from([
+            "ubuntu-autoinstall-agent",
+            "storage",
+            "-H",
+            "10.0.0.5",
+            "--dry-run",
+            "expand",
+        ])
+        .unwrap();
+        match cli.command {
+            Commands::Storage {
+                host,
+                dry_run,
+                action,
+                ..
+            } => {
+                assert_eq!(host, "10.0.0.5");
+                assert!(dry_run);
+                assert_eq!(
+                    action,
+                    StorageAction::Expand {
+                        mapper: "luks".to_string(),
+                        pool: "rpool".to_string(),
+                        passphrase_file: None,
+                    }
+                );
+            }
+            _ => panic!("Expected Storage command"),
+        }
+    }
+
+    #[test]
+    fn test_cli_parsing_backup() {
+        // Arrange
+        let args = vec![
+            "ubuntu-autoinstall-agent",
+            "backup",
+            "-H",
+            "10.0.0.5",
+            "--target",
+            "ssh://root@nas/tank/backups",
+            "--full",
+            "--bwlimit",
+            "20480",
+            "--io-idle",
         ];
 
         // Act
@@ -252,37 +2049,45 @@ mod tests {
 
         // Assert
         match cli.command {
-            Commands::CreateImage {
-                arch,
-                version,
-                output,
-                spec,
-                cache_dir,
+            Commands::Backup {
+                host,
+                hostname,
+                username,
+                target,
+                full,
+                bwlimit,
+                io_idle,
+                io_weight,
+                dry_run,
             } => {
-                assert!(matches!(arch, ArchArg::Arm64));
-                assert_eq!(version, "22.04");
-                assert_eq!(output.as_deref(), Some("/tmp/output.iso"));
-                assert_eq!(spec.as_deref(), Some("spec.yaml"));
-                assert_eq!(cache_dir.as_deref(), Some("/tmp/cache"));
+                assert_eq!(host, "10.0.0.5");
+                assert_eq!(hostname, None);
+                assert_eq!(username, "root");
+                assert_eq!(target, "ssh://root@nas/tank/backups");
+                assert!(full);
+                assert_eq!(bwlimit, Some(20480));
+                assert!(io_idle);
+                assert_eq!(io_weight, None);
+                assert!(!dry_run);
             }
-            _ => panic!("Expected CreateImage command"),
+            _ => panic!("Expected Backup command"),
         }
     }
 
     #[test]
-    fn test_cli_parsing_deploy() {
+    fn test_cli_parsing_restore() {
         // Arrange
         let args = vec![
             "ubuntu-autoinstall-agent",
-            "deploy",
+            "restore",
+            "-H",
+            "10.0.0.9",
+            "-n",
+            "host-a",
             "--target",
-            "192.168.1.100",
-            "--config",
-            "config.yaml",
-            "--image",
-            "image.iso",
-            "--via-ssh",
-            "--dry-run",
+            "/mnt/nfs/host-a",
+            "--disk",
+            "/dev/nvme1n1",
         ];
 
         // Act
@@ -290,31 +2095,44 @@ mod tests {
 
         // Assert
         match cli.command {
-            Commands::Deploy {
+            Commands::Restore {
+                host,
+                hostname,
+                username,
                 target,
-                config,
-                image,
-                via_ssh,
+                snapshot,
+                disk,
                 dry_run,
             } => {
-                assert_eq!(target, "192.168.1.100");
-                assert_eq!(config, "config.yaml");
-                assert_eq!(image, "image.iso");
-                assert!(via_ssh);
-                assert!(dry_run);
+                assert_eq!(host, "10.0.0.9");
+                assert_eq!(hostname, "host-a");
+                assert_eq!(username, "ubuntu");
+                assert_eq!(target, "/mnt/nfs/host-a");
+                assert_eq!(snapshot, None);
+                assert_eq!(disk.as_deref(), Some("/dev/nvme1n1"));
+                assert!(!dry_run);
             }
-            _ => panic!("Expected Deploy command"),
+            _ => panic!("Expected Restore command"),
         }
     }
 
     #[test]
-    fn test_cli_parsing_validate() {
+    fn test_cli_parsing_investigate() {
         // Arrange
         let args = vec![
             "ubuntu-autoinstall-agent",
-            "validate",
-            "--image",
-            "test.iso",
+            "investigate",
+            "-H",
+            "10.0.0.5",
+            "--format",
+            "html",
+            "--output",
+            "report.html",
+            "--target-config",
+            "targets/host-a.yaml",
+            "--hostname",
+            "host-a",
+            "--rebaseline",
         ];
 
         // Act
@@ -322,34 +2140,66 @@ mod tests {
 
         // Assert
         match cli.command {
-            Commands::Validate { image } => {
-                assert_eq!(image, "test.iso");
+            Commands::Investigate {
+                host,
+                username,
+                format,
+                output,
+                target_config,
+                hostname,
+                rebaseline,
+            } => {
+                assert_eq!(host, "10.0.0.5");
+                assert_eq!(username, "ubuntu");
+                assert_eq!(format, ReportFormatArg::Html);
+                assert_eq!(output.as_deref(), Some("report.html"));
+                assert_eq!(target_config.as_deref(), Some("targets/host-a.yaml"));
+                assert_eq!(hostname.as_deref(), Some("host-a"));
+                assert!(rebaseline);
             }
-            _ => panic!("Expected Validate command"),
+            _ => panic!("Expected Investigate command"),
         }
     }
 
     #[test]
-    fn test_cli_parsing_check_prereqs() {
+    fn test_cli_parsing_report() {
         // Arrange
-        let args = vec!["ubuntu-autoinstall-agent", "check-prereqs"];
+        let args = vec![
+            "ubuntu-autoinstall-agent",
+            "report",
+            "--hostname",
+            "host-a",
+            "--format",
+            "text",
+        ];
 
         // Act
         let cli = Cli::try_parse_from(args).unwrap();
 
         // Assert
-        assert!(matches!(cli.command, Commands::CheckPrereqs));
+        match cli.command {
+            Commands::Report {
+                hostname,
+                format,
+                output,
+            } => {
+                assert_eq!(hostname, "host-a");
+                assert_eq!(format, InstallReportFormatArg::Text);
+                assert!(output.is_none());
+            }
+            _ => panic!("Expected Report command"),
+        }
     }
 
     #[test]
-    fn test_cli_parsing_list_images() {
+    fn test_cli_parsing_support_bundle() {
         // Arrange
         let args = vec![
             "ubuntu-autoinstall-agent",
-            "list-images",
-            "--filter-arch",
-            "amd64",
-            "--json",
+            "support-bundle",
+            "3f2a9c1e",
+            "--target-config",
+            "targets/web-01.yaml",
         ];
 
         // Act
@@ -357,23 +2207,31 @@ mod tests {
 
         // Assert
         match cli.command {
-            Commands::ListImages { filter_arch, json } => {
-                assert!(matches!(filter_arch, Some(ArchArg::Amd64)));
-                assert!(json);
+            Commands::SupportBundle {
+                session,
+                output,
+                preset,
+                target_config,
+            } => {
+                assert_eq!(session, "3f2a9c1e");
+                assert!(output.is_none());
+                assert!(preset.is_none());
+                assert_eq!(target_config.as_deref(), Some("targets/web-01.yaml"));
             }
-            _ => panic!("Expected ListImages command"),
+            _ => panic!("Expected SupportBundle command"),
         }
     }
 
     #[test]
-    fn test_cli_parsing_cleanup() {
+    fn test_cli_parsing_approve() {
         // Arrange
         let args = vec![
             "ubuntu-autoinstall-agent",
-            "cleanup",
-            "--older-than-days",
-            "60",
-            "--dry-run",
+            "approve",
+            "web-01",
+            "phase_2",
+            "--reject",
+            "wrong disk",
         ];
 
         // Act
@@ -381,25 +2239,29 @@ mod tests {
 
         // Assert
         match cli.command {
-            Commands::Cleanup {
-                older_than_days,
-                dry_run,
+            Commands::Approve {
+                hostname,
+                phase,
+                reject,
             } => {
-                assert_eq!(older_than_days, 60);
-                assert!(dry_run);
+                assert_eq!(hostname, "web-01");
+                assert_eq!(phase, "phase_2");
+                assert_eq!(reject.as_deref(), Some("wrong disk"));
             }
-            _ => panic!("Expected Cleanup command"),
+            _ => panic!("Expected Approve command"),
         }
     }
 
     #[test]
-    fn test_cli_parsing_ssh_install_minimal() {
+    fn test_cli_parsing_enroll_verify() {
         // Arrange
         let args = vec![
             "ubuntu-autoinstall-agent",
-            "ssh-install",
-            "--host",
-            "10.0.0.5",
+            "enroll-verify",
+            "--hostname",
+            "host-a",
+            "--token",
+            "abc123",
         ];
 
         // Act
@@ -407,133 +2269,284 @@ mod tests {
 
         // Assert
         match cli.command {
-            Commands::SshInstall {
-                host,
-                hostname,
-                username,
-                investigate_only,
-                dry_run,
-                hold_on_failure,
-                pause_after_storage,
-            } => {
-                assert_eq!(host, "10.0.0.5");
-                assert!(hostname.is_none());
-                assert_eq!(username.as_deref(), Some("ubuntu"));
-                assert!(!investigate_only);
-                assert!(!dry_run);
-                assert!(!hold_on_failure);
-                assert!(!pause_after_storage);
+            Commands::EnrollVerify { hostname, token } => {
+                assert_eq!(hostname, "host-a");
+                assert_eq!(token.as_deref(), Some("abc123"));
             }
-            _ => panic!("Expected SshInstall command"),
+            _ => panic!("Expected EnrollVerify command"),
         }
     }
 
     #[test]
-    fn test_cli_parsing_ssh_install_full() {
+    fn test_cli_parsing_presets() {
         // Arrange
-        let args = vec![
+        let list = vec!["ubuntu-autoinstall-agent", "presets"];
+        let save = vec![
             "ubuntu-autoinstall-agent",
-            "ssh-install",
-            "--host",
-            "server.example.com",
-            "--hostname",
-            "prod-web-01",
-            "--username",
-            "admin",
-            "--investigate-only",
-            "--dry-run",
-            "--hold-on-failure",
-            "--pause-after-storage",
+            "presets",
+            "len-serv-003",
+            "--save",
         ];
 
         // Act
-        let cli = Cli::try_parse_from(args).unwrap();
+        let list = Cli::try_parse_from(list).unwrap();
+        let save = Cli::try_parse_from(save).unwrap();
 
         // Assert
+        assert!(matches!(
+            list.command,
+            Commands::Presets {
+                name: None,
+                save: false
+            }
+        ));
+        match save.command {
+            Commands::Presets { name, save } => {
+                assert_eq!(name.as_deref(), Some("len-serv-003"));
+                assert!(save);
+            }
+            _ => panic!("Expected Presets command"),
+        }
+        assert!(Cli::try_parse_from(["ubuntu-autoinstall-agent", "presets", "--save"]).is_err());
+    }
+
+    #[test]
+    fn test_cli_parsing_host_vars() {
+        let cli = Cli::try_parse_from([
+            "ubuntu-autoinstall-agent",
+            "host-vars",
+            "web-01",
+            "set",
+            "install.id",
+            "ab12cd",
+        ])
+        .unwrap();
         match cli.command {
-            Commands::SshInstall {
-                host,
-                hostname,
-                username,
-                investigate_only,
-                dry_run,
-                hold_on_failure,
-                pause_after_storage,
-            } => {
-                assert_eq!(host, "server.example.com");
-                assert_eq!(hostname.as_deref(), Some("prod-web-01"));
-                assert_eq!(username.as_deref(), Some("admin"));
-                assert!(investigate_only);
-                assert!(dry_run);
-                assert!(hold_on_failure);
-                assert!(pause_after_storage);
+            Commands::HostVars { hostname, action } => {
+                assert_eq!(hostname, "web-01");
+                assert_eq!(
+                    action,
+                    HostVarsAction::Set {
+                        key: "install.id".to_string(),
+                        value: "ab12cd".to_string(),
+                    }
+                );
             }
-            _ => panic!("Expected SshInstall command"),
+            _ => panic!("Expected HostVars command"),
         }
     }
 
     #[test]
-    fn test_cli_global_flags() {
+    fn test_cli_parsing_provenance() {
         // Arrange
-        let args = vec![
+        let verify = vec![
             "ubuntu-autoinstall-agent",
-            "--verbose",
-            "--quiet",
-            "check-prereqs",
+            "provenance",
+            "verify",
+            "images/ubuntu.qcow2",
+            "--key",
+            "keys/ci.pub",
+            "--key",
+            "abcd",
+        ];
+        let show = vec![
+            "ubuntu-autoinstall-agent",
+            "provenance",
+            "show",
+            "logs/host-a/provenance.json",
         ];
 
         // Act
-        let cli = Cli::try_parse_from(args).unwrap();
+        let verify = Cli::try_parse_from(verify).unwrap();
+        let show = Cli::try_parse_from(show).unwrap();
 
         // Assert
-        assert!(cli.verbose);
-        assert!(cli.quiet);
-        assert!(matches!(cli.command, Commands::CheckPrereqs));
+        match verify.command {
+            Commands::Provenance { action } => assert_eq!(
+                action,
+                ProvenanceAction::Verify {
+                    path: "images/ubuntu.qcow2".to_string(),
+                    key: vec!["keys/ci.pub".to_string(), "abcd".to_string()],
+                }
+            ),
+            _ => panic!("Expected Provenance command"),
+        }
+        match show.command {
+            Commands::Provenance { action } => assert_eq!(
+                action,
+                ProvenanceAction::Show {
+                    path: "logs/host-a/provenance.json".to_string(),
+                }
+            ),
+            _ => panic!("Expected Provenance command"),
+        }
     }
 
     #[test]
-    fn test_cli_parsing_local_install_minimal() {
+    fn test_cli_parsing_fleet_deploy() {
         // Arrange
-        let args = vec!["ubuntu-autoinstall-agent", "local-install"];
+        let args = vec![
+            "ubuntu-autoinstall-agent",
+            "fleet",
+            "deploy",
+            "inventory/web.yaml",
+            "--yes",
+            "--selector",
+            "role=worker,rack!=12",
+        ];
 
         // Act
         let cli = Cli::try_parse_from(args).unwrap();
 
         // Assert
         match cli.command {
-            Commands::LocalInstall {
-                hostname,
-                investigate_only,
+            Commands::Fleet { action } => assert_eq!(
+                action,
+                FleetAction::Deploy {
+                    inventory: "inventory/web.yaml".to_string(),
+                    yes: true,
+                    dry_run: false,
+                    selector: Some("role=worker,rack!=12".to_string()),
+                }
+            ),
+            _ => panic!("Expected Fleet command"),
+        }
+
+        let cli = Cli::try_parse_from([
+            "ubuntu-autoinstall-agent",
+            "fleet",
+            "facts",
+            "inventory/web.yaml",
+            "--format",
+            "csv",
+        ])
+        .unwrap();
+        match cli.command {
+            Commands::Fleet { action } => assert_eq!(
+                action,
+                FleetAction::Facts {
+                    inventory: "inventory/web.yaml".to_string(),
+                    format: FactsFormatArg::Csv,
+                    output: None,
+                    selector: None,
+                }
+            ),
+            _ => panic!("Expected Fleet command"),
+        }
+
+        let cli = Cli::try_parse_from([
+            "ubuntu-autoinstall-agent",
+            "fleet",
+            "plan",
+            "inventory/web.yaml",
+            "--json",
+        ])
+        .unwrap();
+        match cli.command {
+            Commands::Fleet { action } => assert_eq!(
+                action,
+                FleetAction::Plan {
+                    inventory: "inventory/web.yaml".to_string(),
+                    json: true,
+                    selector: None,
+                }
+            ),
+            _ => panic!("Expected Fleet command"),
+        }
+
+        let cli = Cli::try_parse_from([
+            "ubuntu-autoinstall-agent",
+            "fleet",
+            "cancel",
+            "3f2c9a1e",
+            "--policy",
+            "abort-cleanup",
+        ])
+        .unwrap();
+        match cli.command {
+            Commands::Fleet { action } => assert_eq!(
+                action,
+                FleetAction::Cancel {
+                    run_id: "3f2c9a1e".to_string(),
+                    policy: CancelPolicyArg::AbortCleanup,
+                }
+            ),
+            _ => panic!("Expected Fleet command"),
+        }
+    }
+
+    #[test]
+    fn test_cli_parsing_sessions() {
+        let cli = Cli::try_parse_from(["ubuntu-autoinstall-agent", "sessions", "list"]).unwrap();
+        assert!(matches!(
+            cli.command,
+            Commands::Sessions {
+                action: SessionsAction::List
+            }
+        ));
+
+        let cli = Cli::try_parse_from([
+            "ubuntu-autoinstall-agent",
+            "sessions",
+            "release",
+            "db-01",
+            "--keep-mounts",
+        ])
+        .unwrap();
+        match cli.command {
+            Commands::Sessions { action } => assert_eq!(
+                action,
+                SessionsAction::Release {
+                    hostname: "db-01".to_string(),
+                    keep_mounts: true,
+                }
+            ),
+            _ => panic!("Expected Sessions command"),
+        }
+    }
+
+    #[test]
+    fn test_cli_parsing_run_pipeline() {
+        let cli = Cli::try_parse_from([
+            "ubuntu-autoinstall-agent",
+            "run-pipeline",
+            "pipelines/weekly.yaml",
+            "--set",
+            "rollout=false",
+            "--set",
+            "image=build/web.qcow2",
+            "--from-stage",
+            "publish",
+        ])
+        .unwrap();
+        match cli.command {
+            Commands::RunPipeline {
+                file,
+                set,
+                from_stage,
                 dry_run,
-                hold_on_failure,
-                pause_after_storage,
-                force,
             } => {
-                assert!(hostname.is_none());
-                assert!(!investigate_only);
+                assert_eq!(file, "pipelines/weekly.yaml");
+                assert_eq!(set, ["rollout=false", "image=build/web.qcow2"]);
+                assert_eq!(from_stage.as_deref(), Some("publish"));
                 assert!(!dry_run);
-                assert!(!hold_on_failure);
-                assert!(!pause_after_storage);
-                assert!(!force);
-                assert!(!hold_on_failure);
-                assert!(!pause_after_storage);
             }
-            _ => panic!("Expected LocalInstall command"),
+            _ => panic!("Expected RunPipeline command"),
         }
     }
 
     #[test]
-    fn test_cli_parsing_local_install_full() {
+    fn test_cli_parsing_capture_image() {
         // Arrange
         let args = vec![
             "ubuntu-autoinstall-agent",
-            "local-install",
-            "--hostname",
-            "local-server",
-            "--investigate-only",
-            "--dry-run",
-            "--hold-on-failure",
-            "--pause-after-storage",
+            "capture-image",
+            "-H",
+            "lab-01",
+            "--exclude",
+            "/srv/scratch",
+            "--exclude",
+            "/home/*/Downloads",
         ];
 
         // Act
@@ -541,22 +2554,51 @@ mod tests {
 
         // Assert
         match cli.command {
-            Commands::LocalInstall {
-                hostname,
-                investigate_only,
-                dry_run,
-                hold_on_failure,
-                pause_after_storage,
-                force,
+            Commands::CaptureImage {
+                host,
+                username,
+                output,
+                cache_dir,
+                exclude,
+                extra_space_gb,
             } => {
-                assert_eq!(hostname.as_deref(), Some("local-server"));
-                assert!(investigate_only);
-                assert!(dry_run);
-                assert!(hold_on_failure);
-                assert!(pause_after_storage);
-                assert!(!force);
+                assert_eq!(host, "lab-01");
+                assert_eq!(username, "root");
+                assert!(output.is_none());
+                assert!(cache_dir.is_none());
+                assert_eq!(exclude, vec!["/srv/scratch", "/home/*/Downloads"]);
+                assert_eq!(extra_space_gb, 2);
             }
-            _ => panic!("Expected LocalInstall command"),
+            _ => panic!("Expected CaptureImage command"),
+        }
+    }
+
+    #[test]
+    fn test_cli_parsing_validate_config() {
+        let cli = Cli::try_parse_from([
+            "ubuntu-autoinstall-agent",
+            "validate-config",
+            "--dir",
+            "configs/",
+            "--format",
+            "junit",
+            "-o",
+            "report.xml",
+        ])
+        .unwrap();
+        match cli.command {
+            Commands::ValidateConfig {
+                dir,
+                format,
+                output,
+                strict,
+            } => {
+                assert_eq!(dir, "configs/");
+                assert_eq!(format, ValidationFormatArg::Junit);
+                assert_eq!(output.as_deref(), Some("report.xml"));
+                assert!(!strict);
+            }
+            _ => panic!("Expected ValidateConfig command"),
         }
     }
 }
@@ -1,20 +1,107 @@
 // file: src/cli/commands.rs
-// version: 1.4.0
+// version: 1.103.0
 // guid: g7h8i9j0-k1l2-3456-7890-123456ghijkl
 
 //! Command implementations for the CLI
 
 use crate::{
-    config::{loader::ConfigLoader, Architecture, ImageSpec},
+    cli::args::{
+        BootEnvAction, CancelPolicyArg, Commands, FactsFormatArg, HostVarsAction, ReportFormatArg,
+        SessionsAction, StorageAction, ValidationFormatArg,
+    },
+    config::{
+        confirmation::GatePhase,
+        inventory::{FleetInventory, HostSelector, InventoryHost},
+        loader::ConfigLoader,
+        pipeline::StageAction,
+        progress::{GithubStatusConfig, SinkTarget},
+        protection::ProtectionRegistry,
+        tenants::Tenant,
+        zfs_pools::PoolLayout,
+        AptSnapshot, Architecture, ImageFlavor, ImageSpec, MirrorSelectionConfig, TenantRegistry,
+        ThrottleConfig, VmConfig,
+    },
     image::deployer::ImageDeployer,
-    image::{builder::ImageBuilder, manager::ImageManager},
-    network::{InstallationConfig, SshInstaller, SystemInfo},
-    utils::system::SystemUtils,
+    image::{
+        builder::{environment::BuildEnvironment, CaptureOptions, ImageBuilder},
+        integrity::ContentManifest,
+        manager::ImageManager,
+        overlay::{Overlay, OverlayManifest},
+        vsphere::{VsphereDeployer, VsphereImage},
+        writer::VerifiedWriteOptions,
+    },
+    network::{
+        beacon::TargetBeacon,
+        chaos::ChaosMonkey,
+        events::{spawn_subscriber, EventBus, InstallEvent, LogSubscriber},
+        fleet::{
+            canary_decision, verify_session, wave_size, CanaryDecision, CancelPolicy,
+            CancelRequest, CancelSignal, FleetRun, HostRecord, HostStatus, RolloutPlan, RunStatus,
+            CANARY_STAGE,
+        },
+        fleet_facts::{FleetFacts, HostFacts},
+        fleet_plan::{FleetPlan, HostPlanPreview},
+        github::{GithubStatusReporter, StatusState},
+        kexec::build_kexec_commands,
+        progress::ProgressReporter,
+        redfish::{self, HardwareInventory},
+        self_install::{build_install_commands, SelfInstaller},
+        sinks::ReportDispatcher,
+        ssh::RebootWait,
+        ssh_installer::{
+            backup::{
+                build_backup_commands, build_receive_commands, check_chain_pools, layout_pools,
+                snapshot_name, BackupCatalog, BackupManager, BackupTarget,
+            },
+            boot_env::{self, BootEnvManager},
+            config_export::{ConfigExporter, RELEASE_COMMAND},
+            drift::{compare, BaselineCollector, HostBaseline},
+            facts::TargetFacts,
+            gates::{self, GateDecision},
+            hardware_baseline::{HardwareBaseline, HardwareChange, HardwareSnapshot},
+            hardware_class::HardwareProfile,
+            hold::{HeldSession, ReleaseRequest, DEFAULT_HOLD_TIMEOUT, HOLD_POLL_INTERVAL},
+            host_vars::HostVars,
+            install_report::{InstallReport, InstallReportFormat},
+            lock::{self, LockHolder, TargetLock},
+            plan::{self, InstallPlan},
+            presets::{InstallPreset, PresetStore},
+            protection,
+            session::InstallSession,
+            storage_expand::StorageExpander,
+            support_bundle::{self, SupportBundle},
+            upgrade::{self, ReleaseUpgrader, UpgradeOptions, UPGRADE_SESSION_FILE},
+        },
+        sudo::SudoPolicy,
+        transport::TransportSpec,
+        webhook::WebhookNotifier,
+        InstallationConfig, KexecBooter, KexecOptions, SshClient, SshInstaller,
+    },
+    security::{
+        enrollment,
+        provenance::{self, ArtifactKind, Provenance, ProvenanceSigner, SignedProvenance, Subject},
+    },
+    utils::{system::SystemUtils, CancellationToken, ScreenCaptureOptions, VmManager},
     Result,
 };
-use std::io::Write;
+use std::io::{IsTerminal, Write};
 use tracing::{error, info, warn};
 
+/// Build VM overrides for the `create-image` command
+#[derive(Debug, Clone, Default)]
+pub struct VmResourceOverrides {
+    /// CPU cores for the build VM
+    pub cpus: Option<u32>,
+    /// Memory for the build VM in MB
+    pub memory_mb: Option<u32>,
+    /// Build under software emulation when KVM is unavailable
+    pub allow_tcg: bool,
+    /// Build VM screenshots and pausing on failure
+    pub screen_capture: ScreenCaptureOptions,
+    /// Build environment lockfile the host must match (`--reproduce`)
+    pub reproduce: Option<String>,
+}
+
 /// Create a golden Ubuntu image
 pub async fn create_image_command(
     arch: Architecture,
@@ -22,32 +109,205 @@ pub async fn create_image_command(
     output: Option<String>,
     spec_path: Option<String>,
     cache_dir: Option<String>,
-) -> Result<()> {
+    vm: VmResourceOverrides,
+    cancel: &CancellationToken,
+) -> Result<std::path::PathBuf> {
     info!(
         "Creating Ubuntu {} image for {} architecture",
         version,
         arch.as_str()
     );
 
-    let spec = if let Some(spec_path) = spec_path {
+    let vm_manager = VmManager::new();
+    let host = vm_manager.detect_host_resources().await?;
+    info!(
+        "Build host: {} CPUs, {} MB available, KVM {}",
+        host.cpus,
+        host.memory_mb,
+        if host.kvm { "available" } else { "unavailable" }
+    );
+
+    // A spec file sizes its VM explicitly; otherwise size it for this host
+    let mut github = None;
+    let mut spec = if let Some(spec_path) = spec_path {
         let loader = ConfigLoader::new();
+        github = github_reporter(loader.load_progress_config(&spec_path)?.github.as_ref());
         loader.load_image_spec(&spec_path)?
     } else {
-        ImageSpec::minimal(version.to_string(), arch)
+        let mut spec = ImageSpec::minimal(version.to_string(), arch);
+        spec.vm_config = VmConfig::sized_for(&host, version);
+        spec
     };
 
+    if let ImageFlavor::Sbc(sbc) = &spec.flavor {
+        // SD-card images are assembled on a loop device; no build VM is started
+        sbc.validate(spec.architecture, &spec.ubuntu_version)?;
+        info!(
+            "SD-card image for {}: {} GB, {} MB firmware partition",
+            sbc.board.as_str(),
+            sbc.image_size_gb,
+            sbc.firmware_size_mb
+        );
+    } else {
+        if arch == SystemUtils::get_system_arch() {
+            if vm.allow_tcg {
+                if !host.kvm {
+                    warn!("KVM unavailable; building under software emulation (slow)");
+                }
+            } else {
+                VmManager::require_kvm(&host)?;
+            }
+        }
+        if let Some(cpus) = vm.cpus {
+            spec.vm_config.cpu_cores = cpus;
+        }
+        if let Some(memory_mb) = vm.memory_mb {
+            spec.vm_config.memory_mb = memory_mb;
+        }
+        spec.vm_config.check_minimums(&spec.ubuntu_version)?;
+        if spec.vm_config.memory_mb > host.memory_mb {
+            warn!(
+                "Build VM gets {} MB but only {} MB is available; the host may start swapping",
+                spec.vm_config.memory_mb, host.memory_mb
+            );
+        }
+        info!(
+            "Build VM: {} CPUs, {} MB memory, {} GB disk",
+            spec.vm_config.cpu_cores, spec.vm_config.memory_mb, spec.vm_config.disk_size_gb
+        );
+    }
+
+    // Captured from the effective spec, so --vm-cpus and --vm-mem are part of the checksum
+    let environment = BuildEnvironment::capture(&spec).await?;
+    if let Some(lockfile) = &vm.reproduce {
+        let locked = BuildEnvironment::load(std::path::Path::new(&lockfile))?;
+        for difference in locked.differences(&environment) {
+            if !difference.material {
+                info!(
+                    "Build environment {} changed: {} -> {}",
+                    difference.field, difference.locked, difference.current
+                );
+            }
+        }
+        locked.require_reproducible(&environment)?;
+        info!("Build environment matches {}", lockfile);
+    }
+
     let mut builder = if let Some(cache_dir) = cache_dir {
         ImageBuilder::with_cache_dir(cache_dir)
     } else {
         ImageBuilder::new()
     };
+    builder.set_cancellation_token(cancel.clone());
+    builder.set_screen_capture(vm.screen_capture);
 
-    let image_path = builder.create_image(spec, output).await?;
+    let description = format!(
+        "Ubuntu {} {} image",
+        spec.ubuntu_version,
+        spec.architecture.as_str()
+    );
+    github_start(&mut github, &format!("Building {}", description)).await;
+    let result = builder.create_image(spec, output).await;
+    if let Ok(path) = &result {
+        let lockfile = BuildEnvironment::path_for(path);
+        match environment.save(&lockfile) {
+            Ok(()) => info!("Build environment recorded in {}", lockfile.display()),
+            Err(e) => warn!("Could not write {}: {}", lockfile.display(), e),
+        }
+    }
+    let artifacts = match &result {
+        Ok(path) => vec![
+            path.display().to_string(),
+            provenance::provenance_path_for(path).display().to_string(),
+            BuildEnvironment::path_for(path).display().to_string(),
+        ],
+        Err(_) => Vec::new(),
+    };
+    github_finish(&mut github, &result, &description, &artifacts).await;
+    let image_path = result?;
 
     info!("Image created successfully: {}", image_path.display());
+    Ok(image_path)
+}
+
+/// Reporter for a `progress.github` section; one that cannot be set up is logged, not fatal
+fn github_reporter(config: Option<&GithubStatusConfig>) -> Option<GithubStatusReporter> {
+    match GithubStatusReporter::new(config?) {
+        Ok(reporter) => Some(reporter),
+        Err(e) => {
+            warn!("GitHub status reporting disabled: {}", e);
+            None
+        }
+    }
+}
+
+/// Report the start of a run to GitHub, if configured
+async fn github_start(reporter: &mut Option<GithubStatusReporter>, description: &str) {
+    if let Some(reporter) = reporter {
+        if let Err(e) = reporter.start(description).await {
+            warn!("GitHub status delivery failed: {}", e);
+        }
+    }
+}
+
+/// Report the outcome of a run to GitHub, if configured
+async fn github_finish<T>(
+    reporter: &mut Option<GithubStatusReporter>,
+    result: &Result<T>,
+    description: &str,
+    artifacts: &[String],
+) {
+    let Some(reporter) = reporter else {
+        return;
+    };
+    let (state, description) = match result {
+        Ok(_) => (StatusState::Success, format!("{} succeeded", description)),
+        Err(e) => (
+            StatusState::Failure,
+            format!("{} failed: {}", description, e),
+        ),
+    };
+    if let Err(e) = reporter.finish(state, &description, artifacts).await {
+        warn!("GitHub status delivery failed: {}", e);
+    }
+}
+
+/// Capture a golden image from a running reference machine
+pub async fn capture_image_command(
+    host: &str,
+    username: &str,
+    output: Option<String>,
+    cache_dir: Option<String>,
+    options: CaptureOptions,
+) -> Result<()> {
+    info!("Capturing golden image from {}@{}", username, host);
+
+    let mut ssh = SshClient::new();
+    ssh.connect(host, username).await?;
+
+    let mut builder = if let Some(cache_dir) = cache_dir {
+        ImageBuilder::with_cache_dir(cache_dir)
+    } else {
+        ImageBuilder::new()
+    };
+    let image_path = builder.capture_image(&mut ssh, &options, output).await?;
+    ssh.disconnect();
+
+    info!("Image captured successfully: {}", image_path.display());
     Ok(())
 }
 
+/// Options of `deploy` besides the target, config and image
+#[derive(Debug, Clone, Default)]
+pub struct DeployOptions {
+    /// Directory tree copied into the deployed filesystem before first boot
+    pub overlay_dir: Option<String>,
+    /// Write the whole disk image in verified chunks instead of extracting it
+    pub verified_write: Option<VerifiedWriteOptions>,
+    /// Files of the deployed root compared with the golden image; every file when 0
+    pub verify_content: Option<usize>,
+}
+
 /// Deploy image to target machine
 pub async fn deploy_command(
     target: &str,
@@ -55,11 +315,25 @@ pub async fn deploy_command(
     image_path: &str,
     via_ssh: bool,
     dry_run: bool,
+    options: DeployOptions,
 ) -> Result<()> {
     info!("Deploying image to target: {}", target);
+    let DeployOptions {
+        overlay_dir,
+        verified_write,
+        verify_content,
+    } = options;
 
     let loader = ConfigLoader::new();
     let config = loader.load_target_config(config_path)?;
+    // Scan before touching the target so a bad overlay fails early
+    let overlay = overlay_dir
+        .as_deref()
+        .map(|dir| Overlay::scan(std::path::Path::new(dir)))
+        .transpose()?;
+    if let Some(options) = &verified_write {
+        options.validate()?;
+    }
 
     if dry_run {
         info!(
@@ -73,12 +347,44 @@ pub async fn deploy_command(
             config.hostname,
             config.architecture.as_str()
         );
+        if let Some(options) = &verified_write {
+            info!(
+                "Would write the whole image to {} in {} MiB chunks, verified every {} chunk(s)",
+                config.disk_device, options.chunk_size_mb, options.barrier_chunks
+            );
+        }
+        match verify_content {
+            Some(0) => info!("Would hash every file of the deployed root and compare the root hash with the golden image"),
+            Some(sample) => info!("Would compare {} sampled file(s) of the deployed root with the golden image", sample),
+            None => {}
+        }
+        if let Some(overlay) = &overlay {
+            info!("Overlay {}:", overlay.root.display());
+            for entry in &overlay.entries {
+                info!(
+                    "  /{} {}:{} {}",
+                    entry.path, entry.owner, entry.group, entry.mode
+                );
+            }
+        }
         return Ok(());
     }
 
-    let deployer = ImageDeployer::new();
+    let started_at = chrono::Utc::now();
+    let mut deployer = ImageDeployer::new();
+    if let Some(overlay) = overlay {
+        deployer = deployer.with_overlay(overlay);
+    }
+    if let Some(options) = verified_write {
+        deployer = deployer.with_verified_write(options);
+    }
+    if let (Some(sample), true) = (verify_content, via_ssh) {
+        let contents = ContentManifest::for_image(std::path::Path::new(image_path)).await?;
+        deployer = deployer.with_content_check(contents, sample);
+    }
+    let mut manifest = None;
     if via_ssh {
-        deployer
+        manifest = deployer
             .deploy_via_ssh(target, &config, std::path::Path::new(image_path))
             .await?;
     } else {
@@ -86,6 +392,234 @@ pub async fn deploy_command(
     }
 
     info!("Deployment completed successfully");
+
+    let image = std::path::Path::new(image_path);
+    let mut statement = Provenance::new(
+        ArtifactKind::Deployment,
+        Subject {
+            name: config.hostname.clone(),
+            sha256: provenance::sha256_file(std::path::Path::new(config_path))?,
+        },
+        started_at,
+    )
+    .parameter("target", target)
+    .parameter("method", if via_ssh { "ssh" } else { "netboot" })
+    .parameter("architecture", config.architecture.as_str())
+    .parameter("disk_device", &config.disk_device);
+    if let (Some(sample), true) = (verify_content, via_ssh) {
+        statement = statement.parameter(
+            "content_verified",
+            if sample == 0 {
+                "all".to_string()
+            } else {
+                sample.to_string()
+            },
+        );
+    }
+    if via_ssh {
+        statement = statement.material(
+            "image",
+            Some(image.display().to_string()),
+            provenance::sha256_file(image).ok(),
+        );
+    }
+    if let Some(manifest) = &manifest {
+        statement = statement
+            .parameter("overlay", &manifest.source)
+            .parameter("overlay_paths", manifest.entries.len());
+        write_overlay_manifest(&config.hostname, manifest);
+    }
+    write_deployment_provenance(&statement);
+    Ok(())
+}
+
+/// Deploy an image as a VM on vCenter/ESXi and verify it the way bare-metal deploys are
+pub async fn deploy_vsphere_command(
+    config_path: &str,
+    image_path: &str,
+    vm_name: Option<&str>,
+    dry_run: bool,
+) -> Result<()> {
+    let loader = ConfigLoader::new();
+    let config = loader.load_target_config(config_path)?;
+    let image = std::path::Path::new(image_path);
+    let work_dir = std::env::temp_dir().join(format!(
+        "uaa-vsphere-{}",
+        vm_name.unwrap_or(&config.hostname)
+    ));
+    let deployer = VsphereDeployer::new(&config, vm_name, &work_dir)?;
+    let source = VsphereImage::from_path(image);
+    let vsphere = config
+        .vsphere
+        .as_ref()
+        .expect("checked by VsphereDeployer::new");
+
+    if dry_run {
+        info!(
+            "DRY RUN: Would deploy {} as VM {} in {}/{}",
+            image_path,
+            deployer.vm_name(),
+            vsphere.datacenter,
+            vsphere.datastore
+        );
+        for step in deployer.plan(&source) {
+            info!("  {}", step);
+        }
+        return Ok(());
+    }
+    if !image.exists() {
+        return Err(crate::error::AutoInstallError::ImageError(format!(
+            "Image not found: {}",
+            image_path
+        )));
+    }
+
+    let started_at = chrono::Utc::now();
+    let result = deployer.deploy(&source).await;
+    let _ = std::fs::remove_dir_all(&work_dir);
+    let deployment = result?;
+    info!(
+        "VM {} is up at {}; verifying",
+        deployment.vm_name, deployment.ip_address
+    );
+    deployer.verify(&deployment).await?;
+
+    let statement = Provenance::new(
+        ArtifactKind::Deployment,
+        Subject {
+            name: config.hostname.clone(),
+            sha256: provenance::sha256_file(std::path::Path::new(config_path))?,
+        },
+        started_at,
+    )
+    .parameter("method", "vsphere")
+    .parameter("vm", &deployment.vm_name)
+    .parameter("datacenter", &vsphere.datacenter)
+    .parameter("ip_address", &deployment.ip_address)
+    .parameter("architecture", config.architecture.as_str())
+    .material(
+        "image",
+        Some(image.display().to_string()),
+        provenance::sha256_file(image).ok(),
+    );
+    write_deployment_provenance(&statement);
+    info!("vSphere deployment of {} completed", config.hostname);
+    Ok(())
+}
+
+/// Keep the applied overlay's manifest next to the deployment provenance; never fatal
+fn write_overlay_manifest(hostname: &str, manifest: &OverlayManifest) {
+    let result = std::env::current_dir()
+        .map_err(crate::error::AutoInstallError::from)
+        .and_then(|base| {
+            let dir = InstallSession::host_dir(&base, hostname);
+            std::fs::create_dir_all(&dir)?;
+            let path = dir.join("overlay-manifest.json");
+            std::fs::write(&path, serde_json::to_string_pretty(manifest)?)?;
+            Ok(path)
+        });
+    match result {
+        Ok(path) => info!("Overlay manifest written to {}", path.display()),
+        Err(e) => warn!("Failed to write overlay manifest: {}", e),
+    }
+}
+
+/// Sign and store deployment provenance under `logs/<hostname>/`; never fatal
+fn write_deployment_provenance(statement: &Provenance) {
+    let path = match std::env::current_dir() {
+        Ok(base) => {
+            InstallSession::host_dir(&base, &statement.subject.name).join("provenance.json")
+        }
+        Err(e) => {
+            warn!("Failed to write deployment provenance: {}", e);
+            return;
+        }
+    };
+    match provenance::record(statement, &path) {
+        Ok(()) => info!("Provenance written to {}", path.display()),
+        Err(e) => warn!("Failed to write deployment provenance: {}", e),
+    }
+}
+
+/// Provenance document for `path` and the image it describes, if it is an image
+fn resolve_provenance_path(path: &str) -> (std::path::PathBuf, Option<std::path::PathBuf>) {
+    let path = std::path::PathBuf::from(path);
+    if let Some(image) = path
+        .to_str()
+        .and_then(|p| p.strip_suffix(provenance::PROVENANCE_SUFFIX))
+    {
+        (path.clone(), Some(std::path::PathBuf::from(image)))
+    } else if path.extension().and_then(|e| e.to_str()) == Some("json") {
+        (path, None)
+    } else {
+        (provenance::provenance_path_for(&path), Some(path))
+    }
+}
+
+/// Verify a provenance document's signature and, for images, the image digest
+pub async fn provenance_verify_command(path: &str, keys: Vec<String>) -> Result<()> {
+    let (document, image) = resolve_provenance_path(path);
+    let mut trusted = Vec::new();
+    for key in keys {
+        let key_path = std::path::Path::new(&key);
+        trusted.push(if key_path.is_file() {
+            std::fs::read_to_string(key_path)?.trim().to_string()
+        } else {
+            key
+        });
+    }
+    if trusted.is_empty() {
+        // Verification never creates a key; without --key only the local public key is trusted
+        let public = format!("{}.pub", ProvenanceSigner::default_key_path().display());
+        match std::fs::read_to_string(&public) {
+            Ok(key) => trusted.push(key.trim().to_string()),
+            Err(_) => {
+                return Err(crate::error::AutoInstallError::ValidationError(format!(
+                    "No --key given and no local public key at {}",
+                    public
+                )))
+            }
+        }
+    }
+
+    let statement = SignedProvenance::load(&document)?.verify(&trusted)?;
+    if statement.kind == ArtifactKind::Image {
+        if let Some(image) = image.filter(|p| p.exists()) {
+            let actual = provenance::sha256_file(&image)?;
+            if actual != statement.subject.sha256 {
+                return Err(crate::error::AutoInstallError::ValidationError(format!(
+                    "{} does not match its provenance: sha256 {} != {}",
+                    image.display(),
+                    actual,
+                    statement.subject.sha256
+                )));
+            }
+            info!("Image digest matches provenance");
+        } else {
+            warn!("Image file not found; only the signature was verified");
+        }
+    }
+
+    println!(
+        "verified: {:?} {} built by {} ({} {}) at {}",
+        statement.kind,
+        statement.subject.name,
+        statement.builder.id,
+        statement.builder.tool,
+        statement.builder.version,
+        statement.finished_at.to_rfc3339()
+    );
+    Ok(())
+}
+
+/// Print a provenance statement without verifying it
+pub async fn provenance_show_command(path: &str) -> Result<()> {
+    let (document, _) = resolve_provenance_path(path);
+    let signed = SignedProvenance::load(&document)?;
+    println!("{}", serde_json::to_string_pretty(&signed.statement()?)?);
+    for signature in &signed.signatures {
+        println!("signed by key {}", signature.key_id);
+    }
     Ok(())
 }
 
@@ -108,6 +642,42 @@ pub async fn validate_command(image_path: &str) -> Result<()> {
     Ok(())
 }
 
+/// Validate every config under `dir`; fails when any file does, after writing the report
+pub async fn validate_config_command(
+    dir: &str,
+    format: ValidationFormatArg,
+    output: Option<String>,
+    strict: bool,
+) -> Result<()> {
+    let report = crate::config::batch::validate_tree(
+        &ConfigLoader::new(),
+        std::path::Path::new(dir),
+        strict,
+    )?;
+    let rendered = match format {
+        ValidationFormatArg::Text => report.render_text(),
+        ValidationFormatArg::Json => report.to_json()?,
+        ValidationFormatArg::Junit => report.render_junit(),
+    };
+    match output {
+        Some(path) => {
+            std::fs::write(&path, rendered)?;
+            info!("Validation report written to {}", path);
+        }
+        None => print!("{}", rendered),
+    }
+
+    if report.is_failure() {
+        return Err(crate::error::AutoInstallError::ValidationError(format!(
+            "Config validation failed in {}: {}",
+            dir,
+            report.summary()
+        )));
+    }
+    info!("All configs in {} are valid ({})", dir, report.summary());
+    Ok(())
+}
+
 /// List available images
 pub async fn list_images_command(
     filter_arch: Option<Architecture>,
@@ -213,151 +783,2394 @@ pub async fn check_prerequisites_command() -> Result<()> {
                 );
             }
         }
-        Err(e) => error!("✗ Failed to check disk space: {}", e),
+        Err(e) => error!("✗ Failed to check disk space: {}", e),
+    }
+
+    // Check KVM support
+    if std::path::Path::new("/dev/kvm").exists() {
+        info!("✓ KVM acceleration available");
+    } else {
+        info!("⚠ KVM acceleration not available - VM operations will be slower");
+    }
+
+    if missing.is_empty() {
+        info!("System is ready for Ubuntu autoinstall operations");
+        Ok(())
+    } else {
+        Err(crate::error::AutoInstallError::SystemError(format!(
+            "Missing {} required dependencies",
+            missing.len()
+        )))
+    }
+}
+
+/// Cleanup old images
+pub async fn cleanup_command(older_than_days: u32, dry_run: bool) -> Result<()> {
+    info!("Cleaning up images older than {} days", older_than_days);
+
+    let manager = ImageManager::new();
+    let old_images = manager.find_old_images(older_than_days).await?;
+
+    if old_images.is_empty() {
+        info!("No old images found for cleanup");
+        return Ok(());
+    }
+
+    if dry_run {
+        info!("DRY RUN: Would delete {} old images:", old_images.len());
+        for image in &old_images {
+            info!(
+                "  {} - {} ({}) - {}",
+                image.id,
+                image.ubuntu_version,
+                image.architecture.as_str(),
+                image.size_human()
+            );
+        }
+        return Ok(());
+    }
+
+    let deleted_count = manager.cleanup_images(old_images).await?;
+    info!("Successfully deleted {} old images", deleted_count);
+
+    Ok(())
+}
+
+/// Options for the `ssh-install` command
+#[derive(Debug, Clone, Default)]
+pub struct SshInstallOptions {
+    /// Target hostname for the installation
+    pub hostname: Option<String>,
+    /// Install preset name; defaults to a preset named after the hostname, then the built-in default
+    pub preset: Option<String>,
+    /// SSH username (defaults to "ubuntu")
+    pub username: Option<String>,
+    /// Only investigate the target, don't install
+    pub investigate_only: bool,
+    /// Show what would be done without doing it
+    pub dry_run: bool,
+    /// Keep the session open for debugging when a phase fails
+    pub hold_on_failure: bool,
+    /// Clean up and close a held session after this long; held until released when `None`
+    pub hold_timeout: Option<std::time::Duration>,
+    /// Pause after storage setup and print the next commands
+    pub pause_after_storage: bool,
+    /// Phases (`phase_2`, `2`, ...) to wait for approval before, added to the target config's gates
+    pub pause_before: Vec<String>,
+    /// Additional disks that each receive a mirrored ESP
+    pub esp_mirrors: Vec<String>,
+    /// Target config file whose `kernel:`, `hardening:`, `zfs_tuning:`, `firewall:`, `headless:`, `ssh_ca:` and `nbde:` sections and `apt_snapshot:` pin are applied to the install, and whose `bmc:` section adds Redfish inventory
+    pub target_config: Option<String>,
+    /// Archive snapshot pin: a timestamp, `now`, or `previous` for the host's last pin
+    pub apt_snapshot: Option<String>,
+    /// Chaos rules (`<fault>@<pattern>[#nth]`) injecting failures into remote commands
+    pub chaos: Vec<String>,
+    /// Console transport spec (`serial:...` or `sol:...`); SSH when unset
+    pub transport: Option<String>,
+    /// Guard the package step with a snapshot and a recorded dpkg state
+    pub transactional_packages: bool,
+    /// Speed-test mirrors from the target and debootstrap from the fastest
+    pub select_mirror: bool,
+    /// Replay the configuration commands after applying them and fail on any that are not
+    /// idempotent (disposable targets only)
+    pub audit_idempotency: bool,
+    /// Log in as an unprivileged user and elevate each command with sudo
+    pub sudo: bool,
+    /// Send anonymized install metrics to the target config's telemetry endpoint
+    pub telemetry: bool,
+    /// Keep the pools of an earlier install and replace only its OS datasets
+    pub reinstall: bool,
+    /// Shutdown token; the install stops at the next safe point once cancelled
+    pub cancel: CancellationToken,
+    /// Replace another operator's install marker on the target
+    pub steal_lock: bool,
+    /// LUKS passphrase used when the preset has none, instead of prompting
+    pub luks_key: Option<String>,
+    /// Root password used when the preset has none, instead of prompting
+    pub root_password: Option<String>,
+    /// Cancellation of the fleet run the install belongs to
+    pub fleet_cancel: CancelSignal,
+}
+
+/// Machine a command would modify destructively, with the command name, for locking
+///
+/// Dry runs and read-only modes return `None`.
+pub fn destructive_target(command: &Commands) -> Option<(String, &'static str)> {
+    match command {
+        Commands::SshInstall {
+            host,
+            investigate_only: false,
+            dry_run: false,
+            ..
+        } => Some((host.clone(), "ssh-install")),
+        Commands::Deploy {
+            target,
+            dry_run: false,
+            ..
+        } => Some((target.clone(), "deploy")),
+        Commands::KexecBoot {
+            host,
+            dry_run: false,
+            ..
+        } => Some((host.clone(), "kexec-boot")),
+        Commands::Restore {
+            host,
+            dry_run: false,
+            ..
+        } => Some((host.clone(), "restore")),
+        Commands::DriftCheck {
+            host,
+            reinstall: true,
+            ..
+        } => Some((host.clone(), "drift-check --reinstall")),
+        Commands::Upgrade {
+            host,
+            dry_run: false,
+            ..
+        } => Some((host.clone(), "upgrade")),
+        Commands::Storage {
+            host,
+            dry_run: false,
+            action: StorageAction::Expand { .. },
+            ..
+        } => Some((host.clone(), "storage expand")),
+        Commands::BootEnv {
+            host,
+            dry_run: false,
+            action: BootEnvAction::Activate { .. },
+            ..
+        } => Some((host.clone(), "boot-env activate")),
+        Commands::BootEnv {
+            host,
+            dry_run: false,
+            action: BootEnvAction::Destroy { .. },
+            ..
+        } => Some((host.clone(), "boot-env destroy")),
+        Commands::LocalInstall {
+            investigate_only: false,
+            dry_run: false,
+            ..
+        } => Some(("localhost".to_string(), "local-install")),
+        _ => None,
+    }
+}
+
+/// Refuse a destructive command against a host in `protected.yaml` unless `token` or a second
+/// approver allows it
+pub async fn check_protection(command: &Commands, token: Option<&str>) -> Result<()> {
+    let Some((target, name)) = destructive_target(command) else {
+        return Ok(());
+    };
+    let hostname = match command {
+        Commands::SshInstall { hostname, .. }
+        | Commands::KexecBoot { hostname, .. }
+        | Commands::DriftCheck { hostname, .. }
+        | Commands::Upgrade { hostname, .. }
+        | Commands::BootEnv { hostname, .. }
+        | Commands::LocalInstall { hostname, .. } => hostname.clone(),
+        Commands::Restore { hostname, .. } => Some(hostname.clone()),
+        _ => None,
+    };
+    let mut names = vec![target.as_str()];
+    names.extend(hostname.as_deref());
+    protection::check(&std::env::current_dir()?, &names, name, token).await
+}
+
+/// Install Ubuntu via SSH to a target machine
+pub async fn ssh_install_command(host: &str, options: SshInstallOptions) -> Result<()> {
+    let SshInstallOptions {
+        hostname,
+        preset,
+        username,
+        investigate_only,
+        dry_run,
+        hold_on_failure,
+        hold_timeout,
+        pause_after_storage,
+        pause_before,
+        esp_mirrors,
+        target_config,
+        apt_snapshot,
+        chaos,
+        transport,
+        transactional_packages,
+        select_mirror,
+        audit_idempotency,
+        sudo,
+        telemetry,
+        reinstall,
+        cancel,
+        steal_lock,
+        luks_key,
+        root_password,
+        fleet_cancel,
+    } = options;
+    let username = username.unwrap_or_else(|| "ubuntu".to_string());
+
+    info!(
+        "Connecting to {}@{} for Ubuntu installation",
+        username, host
+    );
+
+    let mut installer = SshInstaller::new();
+    installer.set_cancellation_token(cancel);
+    installer.set_fleet_cancel(fleet_cancel);
+    if !chaos.is_empty() {
+        warn!("Chaos mode enabled: {}", chaos.join(", "));
+        installer.set_chaos(ChaosMonkey::from_specs(&chaos)?);
+    }
+    installer.set_hold_timeout(hold_timeout);
+    installer.set_transactional_packages(transactional_packages);
+    installer.set_idempotency_audit(audit_idempotency);
+    installer.set_reinstall(reinstall);
+    installer.set_interactive(std::io::stdin().is_terminal());
+
+    // Elevation has to be in place before the first command reaches the target
+    let privilege = match &target_config {
+        Some(path) => ConfigLoader::new().load_privilege_config(path)?,
+        None => Default::default(),
+    };
+    match SudoPolicy::from_config(&privilege)? {
+        Some(policy) => installer.set_sudo_policy(policy),
+        None if sudo => installer.set_sudo_policy(SudoPolicy::nopasswd()),
+        None => {}
+    }
+
+    // Connect to the target, over a console when SSH is not available
+    let transport = match &transport {
+        Some(spec) => TransportSpec::parse(spec)?,
+        None => TransportSpec::Ssh,
+    };
+    match transport.open()? {
+        Some(console) => installer.connect_transport(host, console),
+        None => installer.connect(host, &username).await?,
+    }
+    info!("Successfully connected to target machine");
+
+    // Always investigate the system first
+    info!("Investigating target system...");
+    let system_info = installer.investigate_system().await?;
+
+    println!("\n=== SYSTEM INVESTIGATION RESULTS ===");
+    println!("Hostname: {}", system_info.hostname);
+    println!("Kernel: {}", system_info.kernel_version);
+    println!("Available tools: {:?}", system_info.available_tools);
+    println!("\n--- OS Release ---");
+    println!("{}", system_info.os_release);
+    println!("\n--- Memory Info ---");
+    println!("{}", system_info.memory_info);
+    println!("\n--- CPU Info ---");
+    println!("{}", system_info.cpu_info);
+    println!("\n--- Disk Information ---");
+    println!("{}", system_info.disk_info);
+    println!("\n--- Network Information ---");
+    println!("{}", system_info.network_info);
+    let facts = installer.target_facts().await?;
+    let hardware_profile = HardwareProfile::detect(&facts);
+    println!("\n--- Hardware Class ---");
+    match &hardware_profile {
+        Some(profile) => println!("{}", profile.summary()),
+        None => println!("No installable disk found"),
+    }
+    // Target configs and presets may reference what was just discovered as `{{ facts.* }}`
+    let fact_vars = facts.template_vars();
+    let loader = ConfigLoader::new().with_facts(fact_vars.clone());
+    if let Some(inventory) = collect_bmc_inventory(&loader, target_config.as_deref()).await? {
+        println!("\n--- Hardware Inventory (BMC) ---");
+        for (label, value) in inventory.summary_rows() {
+            println!("{}: {}", label, value);
+        }
+        installer.set_hardware_inventory(inventory);
+    }
+
+    if investigate_only {
+        info!("Investigation complete. Exiting as requested.");
+        return Ok(());
+    }
+
+    let preset_name = preset.clone();
+    let preset = PresetStore::in_base_dir(&std::env::current_dir()?)
+        .with_facts(fact_vars)
+        .resolve(preset.as_deref(), hostname.as_deref())?;
+    let storage = match &target_config {
+        Some(path) => loader.load_storage_config(path)?,
+        None => Default::default(),
+    };
+    let progress = match &target_config {
+        Some(path) => loader.load_progress_config(path)?,
+        None => Default::default(),
+    };
+    // Off unless asked for on the command line, whatever the target config says
+    let telemetry = if telemetry {
+        let config = match &target_config {
+            Some(path) => loader.load_telemetry_config(path)?,
+            None => Default::default(),
+        };
+        Some(crate::network::telemetry::TelemetryReporter::new(&config)?)
+    } else {
+        None
+    };
+    let issues = match &target_config {
+        Some(path) => loader.load_issues_config(path)?,
+        None => Default::default(),
+    };
+    let events = EventBus::default();
+    let mut subscribers = vec![spawn_subscriber(&events, LogSubscriber::default())];
+    let mut sinks = ReportDispatcher::from_config(&progress)?;
+    // Beacon sinks post from the target; the shared session id lets receivers merge the streams
+    let beacons: Vec<_> = progress
+        .sinks
+        .iter()
+        .filter_map(|sink| match &sink.target {
+            SinkTarget::Beacon { url } => Some((sink.reports.clone(), url)),
+            _ => None,
+        })
+        .collect();
+    if !beacons.is_empty() && !matches!(transport, TransportSpec::Ssh) {
+        warn!("Beacon sinks need SSH to the target; they are skipped over a console transport");
+    } else if !beacons.is_empty() {
+        let session_id = uuid::Uuid::new_v4().to_string();
+        installer.set_session_id(&session_id);
+        for (kinds, url) in beacons {
+            let beacon = TargetBeacon::start(host, &username, url, &session_id).await?;
+            sinks.add(kinds, std::sync::Arc::new(beacon));
+        }
+    }
+    if !sinks.is_empty() {
+        subscribers.push(spawn_subscriber(&events, sinks));
+    }
+    installer.set_progress(ProgressReporter::new(&progress)?.with_bus(events.clone()));
+    installer.set_event_bus(events.clone());
+    let mut github = github_reporter(progress.github.as_ref());
+
+    // Create installation configuration
+    let mut config = preset.into_config();
+    if let Some(hostname) = hostname {
+        config.hostname = hostname;
+    }
+    if !esp_mirrors.is_empty() {
+        config.esp_mirror_devices = esp_mirrors;
+    } else if let Some(profile) = &hardware_profile {
+        // No --esp-mirror: take the layout from the target config or the detected class
+        let (layout, warnings) = profile.inherit_layout(storage.layout, &mut config);
+        if let Some(layout) = layout {
+            info!(
+                "Storage layout {} ({} for hardware class {})",
+                layout,
+                if storage.layout.is_some() {
+                    "declared"
+                } else {
+                    "default"
+                },
+                profile.class
+            );
+        }
+        for warning in warnings {
+            warn!("{}", warning);
+        }
+    } else if let Some(layout) = storage.layout {
+        warn!(
+            "No installable disks detected; storage layout {} not applied",
+            layout
+        );
+    }
+    config.apply_target_sections(&loader, target_config.as_deref())?;
+    if config.bootloader.is_systemd_boot() && !config.esp_mirror_devices.is_empty() {
+        return Err(crate::error::AutoInstallError::ValidationError(
+            "systemd-boot is not supported with mirrored ESPs; use the GRUB bootloader".to_string(),
+        ));
+    }
+    // A swapped disk or a missing DIMM is flagged before anything is wiped; it does not stop the install
+    let hardware_changes = HardwareBaseline::check_or_record(
+        &std::env::current_dir()?,
+        &config.hostname,
+        HardwareSnapshot::from_facts(&facts),
+        false,
+    )?;
+    warn_hardware_changes(&config.hostname, &hardware_changes);
+    installer.set_hardware_changes(hardware_changes);
+    for phase in &pause_before {
+        let gate: GatePhase = phase.parse()?;
+        if !config.confirmation.gates(gate) {
+            config.confirmation.pause_before.push(gate);
+        }
+    }
+    config.apt_snapshot = match apt_snapshot.as_deref() {
+        Some(value) => Some(resolve_apt_snapshot(
+            value,
+            &std::env::current_dir()?,
+            &config.hostname,
+        )?),
+        None => match &target_config {
+            Some(path) => loader.load_apt_snapshot(path)?,
+            None => None,
+        },
+    };
+
+    // Pick the fastest mirror from the target's own network; a snapshot pin already fixes the mirror
+    let mirror_selection = match &target_config {
+        Some(path) => loader.load_mirror_selection_config(path)?,
+        None => None,
+    }
+    .or_else(|| select_mirror.then(MirrorSelectionConfig::default));
+    let mirror_decision = match mirror_selection {
+        Some(_) if config.apt_snapshot.is_some() => {
+            warn!("Mirror selection skipped: the install is pinned to an apt snapshot");
+            None
+        }
+        Some(selection) => {
+            let decision = installer.select_mirror(&selection, &config).await?;
+            info!("Selected mirror: {}", decision.summary());
+            config.debootstrap_mirror = Some(decision.mirror.clone());
+            Some(decision)
+        }
+        None => None,
+    };
+
+    if dry_run {
+        info!("DRY RUN: Would perform full ZFS+LUKS installation with config:");
+        info!("  Hostname: {}", config.hostname);
+        info!("  Disk: {}", config.disk_device);
+        if !config.esp_mirror_devices.is_empty() {
+            info!("  Mirrored ESPs: {}", config.esp_mirror_devices.join(", "));
+        }
+        info!("  Timezone: {}", config.timezone);
+        info!(
+            "  Network: {} -> {}",
+            config.network_interface, config.network_address
+        );
+        for cmd in config.kernel.build_apply_commands("/mnt/targetos") {
+            info!("  Kernel tuning: {}", cmd);
+        }
+        if config.hardening.is_enabled() {
+            info!(
+                "  Hardening: {:?} ({})",
+                config.hardening.profile,
+                config
+                    .hardening
+                    .controls()
+                    .iter()
+                    .map(|c| c.as_str())
+                    .collect::<Vec<_>>()
+                    .join(", ")
+            );
+        }
+        if let Some(snapshot) = &config.apt_snapshot {
+            info!("  APT snapshot: {} ({})", snapshot, snapshot.archive_uri());
+        }
+        if let Some(decision) = &mirror_decision {
+            info!("  Mirror: {}", decision.summary());
+            for probe in &decision.probes {
+                info!("    {}: {}", probe.mirror, probe.describe());
+            }
+        }
+        if transactional_packages {
+            info!("  Package step: transactional (snapshot, rollback on failure)");
+        }
+        if reinstall {
+            info!("  Reinstall: keep the partitions, LUKS volume and pools; replace ROOT and BOOT");
+            for preserved in &config.reinstall.preserve {
+                info!(
+                    "    keep {}{}",
+                    preserved.dataset,
+                    preserved
+                        .mountpoint
+                        .as_deref()
+                        .map(|m| format!(" at {}", m))
+                        .unwrap_or_default()
+                );
+            }
+        }
+        if !config.confirmation.pause_before.is_empty() {
+            let gates: Vec<String> = config
+                .confirmation
+                .pause_before
+                .iter()
+                .map(|gate| gate.id())
+                .collect();
+            info!("  Confirmation gates before: {}", gates.join(", "));
+        }
+        if !config.late_commands.scripts.is_empty() {
+            let names: Vec<&str> = config
+                .late_commands
+                .scripts
+                .iter()
+                .map(|script| script.name.as_str())
+                .collect();
+            info!("  Late scripts: {}", names.join(", "));
+        }
+        for line in config.performance.describe() {
+            info!("  Performance {}", line);
+        }
+        if config.user_data.is_enabled() {
+            let c = &config.user_data.cloud_config;
+            info!(
+                "  First-boot user-data: {} file(s), {} package(s), {} runcmd entries",
+                c.write_files.len(),
+                c.packages.len(),
+                c.runcmd.len()
+            );
+        }
+        for repository in &config.apt_repos.repositories {
+            info!(
+                "  apt repository {}: {} {}",
+                repository.name,
+                repository.url,
+                repository.suites.join(" ")
+            );
+        }
+        if audit_idempotency {
+            let commands: usize = plan::plan_commands(&config, "/mnt/targetos")
+                .iter()
+                .map(|(_, commands)| commands.len())
+                .sum();
+            info!(
+                "  Idempotency audit: {} configuration commands run twice",
+                commands
+            );
+        }
+        let facts = installer.target_facts().await?;
+        let tuning = config.zfs_tuning.compute(facts.memory_total_mb)?;
+        for parameter in &tuning.parameters {
+            info!(
+                "  ZFS tuning: {}={} ({})",
+                parameter.name, parameter.value, parameter.reason
+            );
+        }
+        print_plan_diff(&config)?;
+        return Ok(());
+    }
+
+    // Confirm installation
+    println!("\n=== INSTALLATION CONFIGURATION ===");
+    println!("Target hostname: {}", config.hostname);
+    println!(
+        "Target disk: {} (THIS WILL BE COMPLETELY WIPED)",
+        config.disk_device
+    );
+    println!("Timezone: {}", config.timezone);
+    println!("Network interface: {}", config.network_interface);
+    println!("Network address: {}", config.network_address);
+    println!("Gateway: {}", config.network_gateway);
+    if let Some(snapshot) = &config.apt_snapshot {
+        println!("APT snapshot: {}", snapshot);
+    }
+    if let Some(decision) = &mirror_decision {
+        println!("Mirror: {}", decision.summary());
+    }
+
+    print_plan_diff(&config)?;
+
+    println!(
+        "\nWARNING: This will completely destroy all data on {}!",
+        config.disk_device
+    );
+    println!("This is a DESTRUCTIVE operation that cannot be undone!");
+
+    // In a real implementation, you might want to add a confirmation prompt here
+    // For automation purposes, we'll proceed directly
+
+    // Presets normally leave secrets out; ask for whatever the preset did not provide
+    if config.luks_key.is_empty() {
+        config.luks_key = match luks_key {
+            Some(key) => key,
+            None => prompt_for_luks_passphrase()?,
+        };
+    }
+    if config.root_password.is_empty() {
+        config.root_password = match root_password {
+            Some(password) => password,
+            None => prompt_for_root_password()?,
+        };
+    }
+    crate::logging::redact::register(&config.luks_key);
+    crate::logging::redact::register(&config.root_password);
+
+    // Claim the target so an operator on another workstation cannot start a second install
+    installer
+        .acquire_target_lock(&LockHolder::current("ssh-install"), steal_lock)
+        .await?;
+
+    info!("Starting full ZFS+LUKS Ubuntu installation...");
+    let description = format!("Install of {}", config.hostname);
+    github_start(&mut github, &format!("{} running", description)).await;
+    let started_at = chrono::Utc::now();
+    let result = installer
+        .perform_installation_with_options_and_pause(&config, hold_on_failure, pause_after_storage)
+        .await;
+    if let Err(e) = installer.release_target_lock().await {
+        warn!("Failed to remove the install marker from the target: {}", e);
+    }
+    let base_dir = std::env::current_dir()?;
+    let session = InstallSession::load(&base_dir, &config.hostname).ok();
+    if let (Some(reporter), Some(session)) = (&telemetry, &session) {
+        let payload = reporter.payload(
+            session,
+            result.as_ref().err(),
+            hardware_profile.as_ref().map(|p| p.class.as_str()),
+        );
+        match reporter.send(&payload).await {
+            Ok(()) => info!("Telemetry sent ({} phases)", payload.phases.len()),
+            Err(e) => warn!("Telemetry not sent: {}", e),
+        }
+    }
+    if let (Err(e), Some(session)) = (&result, &session) {
+        if issues.is_enabled() && !matches!(e, crate::error::AutoInstallError::CancelledError(_)) {
+            report_failure_issue(
+                &issues,
+                session,
+                e,
+                preset_name.as_deref(),
+                target_config.as_deref(),
+            )
+            .await;
+        }
+    }
+    if let Some(session) = session {
+        let event = if result.is_ok() {
+            "install.completed"
+        } else {
+            "install.failed"
+        };
+        events.publish(InstallEvent::Session {
+            name: event.to_string(),
+            session: Box::new(session),
+        });
+    }
+    // The installer holds the other handles on the bus; sinks finish delivering once it is gone
+    drop(installer);
+    events.close(subscribers).await;
+    let host_dir = InstallSession::host_dir(&base_dir, &config.hostname);
+    github_finish(
+        &mut github,
+        &result,
+        &description,
+        &[host_dir.display().to_string()],
+    )
+    .await;
+    result?;
+
+    let mut statement = Provenance::new(
+        ArtifactKind::Deployment,
+        Subject {
+            name: config.hostname.clone(),
+            sha256: config.checksum(),
+        },
+        started_at,
+    )
+    .material("debootstrap-mirror", Some(config.effective_mirror()), None)
+    .parameter("target", host)
+    .parameter("method", "ssh-install")
+    .parameter("release", config.release())
+    .parameter("architecture", config.architecture.as_str())
+    .parameter("disk_device", &config.disk_device);
+    if let Some(snapshot) = &config.apt_snapshot {
+        statement = statement.parameter("apt_snapshot", snapshot);
+    }
+    write_deployment_provenance(&statement);
+
+    info!("SSH installation completed successfully!");
+    info!("Target machine should now be ready to boot from local disk");
+
+    Ok(())
+}
+
+/// Show how the plan for `config` differs from the last successful install of the host
+fn print_plan_diff(config: &InstallationConfig) -> Result<()> {
+    let base_dir = std::env::current_dir()?;
+    let Some(previous) = InstallPlan::last_successful(&base_dir, &config.hostname) else {
+        println!(
+            "\nNo plan recorded for a successful install of {}; nothing to compare",
+            config.hostname
+        );
+        return Ok(());
+    };
+    let changes = InstallPlan::from_config(config).diff(&previous);
+    if !plan::has_changes(&changes) {
+        println!("\nPlan unchanged since the last successful install");
+        return Ok(());
+    }
+    println!("\n=== CHANGES SINCE THE LAST SUCCESSFUL INSTALL ===");
+    let color = std::io::stdout().is_terminal() && std::env::var_os("NO_COLOR").is_none();
+    for line in plan::render_diff(&changes, color) {
+        println!("{}", line);
+    }
+    Ok(())
+}
+
+/// Resolve an `--apt-snapshot` value; `previous` reuses the pin recorded for `hostname`
+fn resolve_apt_snapshot(
+    value: &str,
+    base_dir: &std::path::Path,
+    hostname: &str,
+) -> Result<AptSnapshot> {
+    match value {
+        "now" => Ok(AptSnapshot::now()),
+        "previous" => InstallSession::load(base_dir, hostname)?
+            .apt_snapshot
+            .ok_or_else(|| {
+                crate::error::AutoInstallError::ValidationError(format!(
+                    "The last install of {} was not pinned to an apt snapshot",
+                    hostname
+                ))
+            }),
+        timestamp => AptSnapshot::parse(timestamp),
+    }
+}
+
+/// Investigate a target and export a structured report (text, JSON or HTML)
+pub async fn investigate_command(
+    host: &str,
+    username: &str,
+    format: ReportFormatArg,
+    output: Option<String>,
+    target_config: Option<&str>,
+    hostname: Option<&str>,
+    rebaseline: bool,
+) -> Result<()> {
+    let mut installer = SshInstaller::new();
+    installer.connect(host, username).await?;
+    let mut report = installer.investigation_report().await?;
+    let baseline_host = hostname.unwrap_or(&report.hostname).to_string();
+    report.hardware_changes = HardwareBaseline::check_or_record(
+        &std::env::current_dir()?,
+        &baseline_host,
+        HardwareSnapshot::from_report(&report),
+        rebaseline,
+    )?;
+    warn_hardware_changes(&baseline_host, &report.hardware_changes);
+    let loader = match target_config {
+        Some(_) => ConfigLoader::new().with_facts(installer.target_facts().await?.template_vars()),
+        None => ConfigLoader::new(),
+    };
+    report.hardware_inventory = collect_bmc_inventory(&loader, target_config).await?;
+
+    let rendered = match format {
+        ReportFormatArg::Text => report.to_text(),
+        ReportFormatArg::Json => report.to_json()?,
+        ReportFormatArg::Html => report.to_html(),
+    };
+
+    match output {
+        Some(path) => {
+            std::fs::write(&path, rendered)?;
+            info!("Investigation report for {} written to {}", host, path);
+        }
+        None => println!("{}", rendered),
+    }
+    Ok(())
+}
+
+/// Log differences from the hardware baseline where they cannot be missed
+fn warn_hardware_changes(hostname: &str, changes: &[HardwareChange]) {
+    if changes.is_empty() {
+        return;
+    }
+    warn!("==================================================");
+    warn!("HARDWARE OF {} CHANGED SINCE ITS BASELINE", hostname);
+    for change in changes {
+        warn!("  {}", change.describe());
+    }
+    warn!("Run `investigate --rebaseline` once the changes are expected");
+    warn!("==================================================");
+}
+
+/// Read the target's hardware inventory from its BMC when the target config has a `bmc:` section
+///
+/// An unreachable BMC only produces a warning; the inventory is an enrichment, not a requirement.
+async fn collect_bmc_inventory(
+    loader: &ConfigLoader,
+    target_config: Option<&str>,
+) -> Result<Option<HardwareInventory>> {
+    let bmc = match target_config {
+        Some(path) => loader.load_bmc_config(path)?,
+        None => None,
+    };
+    let Some(bmc) = bmc else {
+        return Ok(None);
+    };
+    match redfish::collect_inventory(&bmc).await {
+        Ok(inventory) => {
+            for section in &inventory.unavailable {
+                warn!("BMC {}: {} not available", bmc.host, section);
+            }
+            Ok(Some(inventory))
+        }
+        Err(e) => {
+            warn!(
+                "Could not read hardware inventory from BMC {}: {}",
+                bmc.host, e
+            );
+            Ok(None)
+        }
+    }
+}
+
+/// Reconstruct a target config from the installed host `host`
+pub async fn export_config_command(
+    host: &str,
+    username: &str,
+    output: Option<String>,
+) -> Result<()> {
+    let mut ssh = SshClient::new();
+    ssh.connect(host, username).await?;
+    let exported = ConfigExporter::new(&mut ssh).export(host).await?;
+    ssh.disconnect();
+
+    for note in &exported.notes {
+        warn!("{}", note);
+    }
+    let yaml = exported.to_yaml()?;
+    match output {
+        Some(path) => {
+            std::fs::write(&path, yaml)?;
+            info!("Target config for {} written to {}", host, path);
+        }
+        None => print!("{}", yaml),
+    }
+    Ok(())
+}
+
+/// Upgrade the installed host `host` to a new release, rolling back to a snapshot on failure
+pub async fn upgrade_command(
+    host: &str,
+    hostname: Option<String>,
+    username: &str,
+    options: &UpgradeOptions,
+    webhook: Option<&str>,
+    dry_run: bool,
+    steal_lock: bool,
+) -> Result<()> {
+    let hostname = hostname.unwrap_or_else(|| host.to_string());
+    let mut sinks = ReportDispatcher::default();
+    if let Some(url) = webhook {
+        sinks.add(Vec::new(), std::sync::Arc::new(WebhookNotifier::new(url)?));
+    }
+    let base_dir = std::env::current_dir()?;
+    let layout = host_pool_layout(&base_dir, &hostname)?;
+
+    let mut ssh = SshClient::new();
+    ssh.connect(host, username).await?;
+    let events = EventBus::default();
+    let mut subscribers = vec![spawn_subscriber(&events, LogSubscriber::default())];
+    if !sinks.is_empty() {
+        subscribers.push(spawn_subscriber(&events, sinks));
+    }
+    ssh.set_progress(ProgressReporter::default().with_bus(events.clone()));
+
+    if dry_run {
+        let from = ssh.execute_with_output(RELEASE_COMMAND).await?;
+        ssh.disconnect();
+        let to = options.to_release.as_str();
+        info!(
+            "DRY RUN: Would upgrade {} from {} to {}",
+            hostname,
+            from.trim(),
+            to
+        );
+        if options.boot_environment {
+            info!(
+                "  Boot environment: {}",
+                boot_env::auto_name("upgrade", chrono::Utc::now())
+            );
+        }
+        info!("  Snapshot: @{}", upgrade::snapshot_name("pre", to));
+        for (name, commands) in upgrade::build_upgrade_commands(from.trim(), to) {
+            info!("  {}", name);
+            for command in commands {
+                info!("    {}", command);
+            }
+        }
+        if options.reboot {
+            info!("  Reboot, then check the release and that no unit newly failed");
+            for service in &options.services {
+                info!("  Require active: {}", service);
+            }
+            info!("  Snapshot: @{}", upgrade::snapshot_name("post", to));
+        }
+        return Ok(());
+    }
+
+    lock::acquire_remote(&mut ssh, &LockHolder::current("upgrade"), steal_lock).await?;
+    let mut session = InstallSession::new(&hostname);
+    events.publish(InstallEvent::Session {
+        name: "upgrade.started".to_string(),
+        session: Box::new(session.clone()),
+    });
+
+    let result = ReleaseUpgrader::new(&mut ssh, &layout)
+        .run(options, &mut session, &base_dir)
+        .await;
+    if let Err(e) = lock::release_remote(&mut ssh).await {
+        warn!("Could not remove the target lock marker: {}", e);
+    }
+    ssh.disconnect();
+    drop(ssh);
+    info!(
+        "Upgrade session recorded in {}",
+        InstallSession::host_dir(&base_dir, &hostname)
+            .join(UPGRADE_SESSION_FILE)
+            .display()
+    );
+
+    let rolled_back = session
+        .release_upgrade
+        .as_ref()
+        .is_some_and(|u| u.rolled_back);
+    let event = match (&result, rolled_back) {
+        (Ok(()), _) => "upgrade.completed",
+        (Err(_), true) => "upgrade.rolled_back",
+        (Err(_), false) => "upgrade.failed",
+    };
+    events.publish(InstallEvent::Session {
+        name: event.to_string(),
+        session: Box::new(session),
+    });
+    events.close(subscribers).await;
+    result
+}
+
+/// Inventory file a fleet command acts on, optionally narrowed by a label selector
+#[derive(Debug, Clone, Copy)]
+pub struct FleetTarget<'a> {
+    pub inventory: &'a str,
+    /// `--selector`, e.g. `role=worker,rack!=12`
+    pub selector: Option<&'a str>,
+}
+
+impl FleetTarget<'_> {
+    /// Load the inventory and its resolved hosts; with a selector, the matching hosts are
+    /// listed before the command does anything with them
+    fn load(&self) -> Result<(FleetInventory, Vec<InventoryHost>)> {
+        let inventory = ConfigLoader::new().load_inventory(self.inventory)?;
+        let Some(selector) = self.selector else {
+            let hosts = inventory.resolved_hosts();
+            return Ok((inventory, hosts));
+        };
+        let hosts = inventory.selected_hosts(&HostSelector::parse(selector)?)?;
+        info!(
+            "Selector '{}' matches {} of {} host(s):",
+            selector,
+            hosts.len(),
+            inventory.hosts.len()
+        );
+        for host in &hosts {
+            let labels: Vec<String> = host
+                .labels
+                .iter()
+                .map(|(key, value)| format!("{}={}", key, value))
+                .collect();
+            info!(
+                "  {} ({}) {}",
+                host.hostname,
+                host.address(),
+                labels.join(",")
+            );
+        }
+        Ok((inventory, hosts))
+    }
+}
+
+/// Caller's tenant when `tenants.yaml` is present in `base_dir`, once it is known to own the
+/// inventory and every `(hostname, target_config)` of the run; `None` when the agent is not
+/// shared between tenants
+fn fleet_tenant<'a>(
+    base_dir: &std::path::Path,
+    inventory: &str,
+    hosts: impl IntoIterator<Item = (&'a str, Option<&'a str>)>,
+) -> Result<Option<Tenant>> {
+    let Some(registry) = TenantRegistry::load_in(base_dir)? else {
+        return Ok(None);
+    };
+    let tenant = registry.authenticate_from_env()?;
+    tenant.check_inventory(std::path::Path::new(inventory), hosts)?;
+    info!("Acting as tenant {}", tenant.name);
+    Ok(Some(tenant.clone()))
+}
+
+/// `fleet_tenant` for the hosts of an inventory
+fn inventory_tenant(
+    base_dir: &std::path::Path,
+    inventory: &str,
+    hosts: &[InventoryHost],
+) -> Result<Option<Tenant>> {
+    fleet_tenant(
+        base_dir,
+        inventory,
+        hosts
+            .iter()
+            .map(|host| (host.hostname.as_str(), host.target_config.as_deref())),
+    )
+}
+
+/// Event posted to a tenant's webhook when one of its hosts finishes
+fn fleet_host_event(status: HostStatus) -> &'static str {
+    match status {
+        HostStatus::Completed => "install.completed",
+        HostStatus::Cancelled => "install.cancelled",
+        _ => "install.failed",
+    }
+}
+
+/// Install every host of an inventory: the canary stage first, then batches in waves
+///
+/// When the agent is shared (`tenants.yaml`), the caller's tenant must own the inventory, every
+/// target config it references and every host in it, and the tenant's webhook is told about
+/// every finished host.
+pub async fn fleet_deploy_command(
+    target: FleetTarget<'_>,
+    yes: bool,
+    dry_run: bool,
+    cancel: CancellationToken,
+    steal_lock: bool,
+    protection_token: Option<&str>,
+) -> Result<()> {
+    let inventory_path = target.inventory;
+    let (inventory, hosts) = target.load()?;
+    let base_dir = std::env::current_dir()?;
+    let tenant_webhook = inventory_tenant(&base_dir, inventory_path, &hosts)?
+        .and_then(|tenant| tenant.webhook)
+        .map(|url| WebhookNotifier::new(&url))
+        .transpose()?;
+    let policy = &inventory.rollout;
+    let plan = RolloutPlan::new(&hosts, policy);
+
+    if dry_run {
+        info!(
+            "DRY RUN: Would deploy {} hosts from {}",
+            hosts.len(),
+            inventory_path
+        );
+        for (stage, indexes) in plan.stages() {
+            let names: Vec<&str> = indexes
+                .iter()
+                .map(|&i| hosts[i].hostname.as_str())
+                .collect();
+            info!("  {}: {}", stage, names.join(", "));
+        }
+        info!(
+            "  At most {} host(s) unavailable at once; {} canary failure(s) tolerated",
+            policy.max_unavailable, policy.canary_failure_threshold
+        );
+        return Ok(());
+    }
+
+    // Protected hosts are cleared before anything is prompted for or installed
+    let registry = ProtectionRegistry::load(&base_dir)?;
+    for host in &hosts {
+        protection::authorize(
+            &base_dir,
+            &registry,
+            &[&host.hostname, host.address()],
+            "fleet deploy",
+            protection_token,
+        )
+        .await?;
+    }
+
+    // One prompt for the whole fleet; each preset's own secrets still take precedence
+    let luks_key = prompt_for_luks_passphrase()?;
+    let root_password = prompt_for_root_password()?;
+
+    let mut run = FleetRun::new(inventory_path, &hosts, &plan);
+    run.save(&base_dir)?;
+    info!(
+        "Fleet run {} started; stop it with `fleet cancel {}`",
+        run.run_id, run.run_id
+    );
+
+    // `fleet cancel` leaves a request next to the run record; the installs in flight get the signal
+    let signal = CancelSignal::default();
+    let watcher = {
+        let (signal, base_dir, run_id) = (signal.clone(), base_dir.clone(), run.run_id.clone());
+        tokio::spawn(async move {
+            loop {
+                if let Some(request) = CancelRequest::load(&base_dir, &run_id) {
+                    warn!(
+                        "Fleet run {} cancelled ({}); hosts not yet started are skipped",
+                        run_id,
+                        request.policy.as_str()
+                    );
+                    signal.request(request.policy);
+                    break;
+                }
+                tokio::time::sleep(std::time::Duration::from_secs(2)).await;
+            }
+        })
+    };
+
+    let mut stages = plan.stages().into_iter().peekable();
+    while let Some((stage, indexes)) = stages.next() {
+        // Each wave is sized to the availability budget left after earlier failures
+        let mut pending = indexes.as_slice();
+        while !pending.is_empty() {
+            if run.stop_if_cancelled(&signal, &cancel) {
+                break;
+            }
+            let size = wave_size(policy, run.failures());
+            if size == 0 {
+                run.abort(&format!(
+                    "{} host(s) failed, reaching max_unavailable {}",
+                    run.failures(),
+                    policy.max_unavailable
+                ));
+                break;
+            }
+            let (wave, rest) = pending.split_at(size.min(pending.len()));
+            pending = rest;
+            info!(
+                "Deploying {} ({})",
+                stage,
+                wave.iter()
+                    .map(|&i| hosts[i].hostname.as_str())
+                    .collect::<Vec<_>>()
+                    .join(", ")
+            );
+            let started_at = chrono::Utc::now();
+            for &i in wave {
+                run.hosts[i].status = HostStatus::Running;
+                run.hosts[i].started_at = Some(started_at);
+            }
+            run.save(&base_dir)?;
+
+            let installs = wave.iter().map(|&i| {
+                let host = &hosts[i];
+                let options = SshInstallOptions {
+                    hostname: Some(host.hostname.clone()),
+                    preset: host.preset.clone(),
+                    username: host.username.clone(),
+                    investigate_only: false,
+                    dry_run: false,
+                    hold_on_failure: false,
+                    hold_timeout: Some(DEFAULT_HOLD_TIMEOUT),
+                    pause_after_storage: false,
+                    pause_before: Vec::new(),
+                    esp_mirrors: Vec::new(),
+                    target_config: host.target_config.clone(),
+                    apt_snapshot: None,
+                    chaos: Vec::new(),
+                    transport: None,
+                    transactional_packages: false,
+                    select_mirror: false,
+                    audit_idempotency: false,
+                    sudo: false,
+                    telemetry: false,
+                    reinstall: false,
+                    cancel: cancel.clone(),
+                    steal_lock,
+                    luks_key: Some(luks_key.clone()),
+                    root_password: Some(root_password.clone()),
+                    fleet_cancel: signal.clone(),
+                };
+                let base_dir = &base_dir;
+                async move {
+                    let _lock =
+                        TargetLock::acquire(base_dir, host.address(), "fleet deploy", steal_lock)?;
+                    ssh_install_command(host.address(), options).await
+                }
+            });
+            let results = futures::future::join_all(installs).await;
+
+            for (&i, result) in wave.iter().zip(results) {
+                let record = &mut run.hosts[i];
+                record.finished_at = Some(chrono::Utc::now());
+                let verified = result
+                    .map_err(|e| match e {
+                        crate::error::AutoInstallError::CancelledError(_) => {
+                            let reason = match signal.requested() {
+                                Some(policy) => format!("{} ({})", e, policy.as_str()),
+                                None => e.to_string(),
+                            };
+                            (HostStatus::Cancelled, reason)
+                        }
+                        e => (HostStatus::Failed, e.to_string()),
+                    })
+                    .and_then(|()| {
+                        InstallSession::load(&base_dir, &record.hostname)
+                            .map_err(|e| e.to_string())
+                            .and_then(|session| verify_session(&session, policy, started_at))
+                            .map_err(|reason| (HostStatus::VerificationFailed, reason))
+                    });
+                match verified {
+                    Ok(()) => record.status = HostStatus::Completed,
+                    Err((HostStatus::Cancelled, reason)) => {
+                        warn!("{}: {}", record.hostname, reason);
+                        record.status = HostStatus::Cancelled;
+                        record.detail = Some(reason);
+                    }
+                    Err((status, reason)) => {
+                        error!("{}: {}", record.hostname, reason);
+                        record.status = status;
+                        record.detail = Some(reason);
+                    }
+                }
+                if let Some(webhook) = &tenant_webhook {
+                    let event = fleet_host_event(record.status);
+                    let delivered = match InstallSession::load(&base_dir, &record.hostname) {
+                        Ok(session) => webhook.notify(event, &session).await,
+                        Err(e) => Err(e),
+                    };
+                    if let Err(e) = delivered {
+                        warn!(
+                            "{}: could not post {} to the tenant webhook: {}",
+                            record.hostname, event, e
+                        );
+                    }
+                }
+            }
+            run.save(&base_dir)?;
+        }
+        if run.stop_if_cancelled(&signal, &cancel) {
+            break;
+        }
+
+        if stage == CANARY_STAGE && stages.peek().is_some() {
+            let canaries: Vec<&HostRecord> = run
+                .hosts
+                .iter()
+                .filter(|h| h.stage == CANARY_STAGE)
+                .collect();
+            match canary_decision(&canaries, policy) {
+                CanaryDecision::Continue => info!("Canary stage passed"),
+                CanaryDecision::Ask if yes => info!("Canary stage passed; continuing (--yes)"),
+                CanaryDecision::Ask => {
+                    for line in run.summary_lines() {
+                        println!("{}", line);
+                    }
+                    if !confirm("Canary stage passed. Continue with the remaining hosts?")? {
+                        run.abort("stopped by operator after the canary stage");
+                    }
+                }
+                CanaryDecision::Abort(reason) => run.abort(&reason),
+            }
+            if run.is_stopped() {
+                run.save(&base_dir)?;
+                break;
+            }
+        }
+
+        if policy.batch_delay_secs > 0 && stages.peek().is_some() {
+            tokio::select! {
+                _ = tokio::time::sleep(std::time::Duration::from_secs(policy.batch_delay_secs)) => {}
+                _ = cancel.cancelled() => {}
+                _ = signal.cancelled() => {}
+            }
+        }
+    }
+
+    watcher.abort();
+    CancelRequest::remove(&base_dir, &run.run_id);
+    if run.status == RunStatus::Running {
+        run.status = RunStatus::Completed;
+        run.finished_at = Some(chrono::Utc::now());
+    }
+    let path = run.save(&base_dir)?;
+
+    println!("\n=== FLEET RUN {} ===", run.run_id);
+    for line in run.summary_lines() {
+        println!("{}", line);
+    }
+    println!("{}", run.tally());
+    if let Some(policy) = run.cancel_policy {
+        println!("Hosts in flight when cancelled: {}", policy.as_str());
+    }
+    info!("Fleet run recorded in {}", path.display());
+
+    match (run.status, &run.reason, run.failures()) {
+        (RunStatus::Cancelled, reason, _) => {
+            Err(crate::error::AutoInstallError::CancelledError(format!(
+                "fleet run {} {}",
+                run.run_id,
+                reason.as_deref().unwrap_or("cancelled")
+            )))
+        }
+        (_, Some(reason), _) => Err(crate::error::AutoInstallError::InstallationError(format!(
+            "Fleet run aborted: {}",
+            reason
+        ))),
+        (_, None, 0) => Ok(()),
+        (_, None, failed) => Err(crate::error::AutoInstallError::InstallationError(format!(
+            "{} host(s) failed",
+            failed
+        ))),
+    }
+}
+
+/// Run the embedded DHCP server for the inventory's provisioning network until cancelled
+pub async fn fleet_dhcp_command(
+    inventory_path: &str,
+    interface: Option<String>,
+    cancel: &CancellationToken,
+) -> Result<()> {
+    let inventory = ConfigLoader::new().load_inventory(inventory_path)?;
+    let mut config = inventory.dhcp.clone().ok_or_else(|| {
+        crate::error::AutoInstallError::ConfigError(format!(
+            "{} has no dhcp: section",
+            inventory_path
+        ))
+    })?;
+    if let Some(interface) = interface {
+        config.interface = interface;
+    }
+    let leases = config.static_leases(&inventory.hosts)?;
+    if leases.is_empty() {
+        return Err(crate::error::AutoInstallError::ConfigError(format!(
+            "No host in {} has a mac to lease an address to",
+            inventory_path
+        )));
+    }
+    for lease in &leases {
+        info!(
+            "  {} {} -> {}",
+            lease.hostname,
+            crate::config::dhcp::format_mac(&lease.mac),
+            lease.ip
+        );
+    }
+    let base_dir = std::env::current_dir()?;
+    crate::network::dhcp::DhcpServer::new(config, leases, &base_dir)
+        .run(cancel)
+        .await
+}
+
+/// Ask the running `fleet deploy` of `run_id` to stop with `policy`
+pub fn fleet_cancel_command(run_id: &str, policy: CancelPolicyArg) -> Result<()> {
+    let policy = match policy {
+        CancelPolicyArg::FinishPhase => CancelPolicy::FinishPhase,
+        CancelPolicyArg::Hold => CancelPolicy::Hold,
+        CancelPolicyArg::AbortCleanup => CancelPolicy::AbortCleanup,
+    };
+    let base_dir = std::env::current_dir()?;
+    if TenantRegistry::load_in(&base_dir)?.is_some() {
+        let run = FleetRun::load(&base_dir, run_id)?;
+        fleet_tenant(
+            &base_dir,
+            &run.inventory,
+            run.hosts.iter().map(|host| (host.hostname.as_str(), None)),
+        )?;
+    }
+    let (run, path) = FleetRun::request_cancel(&base_dir, run_id, policy)?;
+    let count = |status| run.hosts.iter().filter(|h| h.status == status).count();
+    info!(
+        "Cancellation of fleet run {} requested in {}",
+        run_id,
+        path.display()
+    );
+    info!(
+        "  {} pending host(s) will be skipped; {} running host(s) stop at their next phase boundary ({})",
+        count(HostStatus::Pending),
+        count(HostStatus::Running),
+        policy.as_str()
+    );
+    Ok(())
+}
+
+/// List the sessions held open after failed installs, or end one of them
+pub fn sessions_command(action: SessionsAction) -> Result<()> {
+    let base_dir = std::env::current_dir()?;
+    match action {
+        SessionsAction::List => {
+            let holds = HeldSession::list(&base_dir);
+            if holds.is_empty() {
+                println!("No held sessions");
+            }
+            for hold in holds {
+                println!("{}", hold.summary());
+            }
+        }
+        SessionsAction::Release {
+            hostname,
+            keep_mounts,
+        } => {
+            let hold = ReleaseRequest::request(&base_dir, &hostname, !keep_mounts)?;
+            if !hold.is_active() {
+                warn!(
+                    "The process holding {} is gone; removed its record, but the target may still be mounted",
+                    hostname
+                );
+            } else if keep_mounts {
+                info!(
+                    "Release of {} requested; the session closes within {}s and leaves the target mounted",
+                    hostname,
+                    HOLD_POLL_INTERVAL.as_secs()
+                );
+            } else {
+                info!(
+                    "Release of {} requested; the target is unmounted, its pools exported and LUKS closed within {}s",
+                    hostname,
+                    HOLD_POLL_INTERVAL.as_secs()
+                );
+            }
+        }
+    }
+    Ok(())
+}
+
+/// Run the stages of a pipeline file in order, stopping at the first that fails
+///
+/// Stages before `from_stage` and stages whose `when:` condition does not hold are skipped.
+/// With `dry_run`, every stage is printed with its parameters expanded; build and publish
+/// stages still set `image` so later stages show what they would use.
+pub async fn run_pipeline_command(
+    path: &str,
+    overrides: &[String],
+    from_stage: Option<&str>,
+    dry_run: bool,
+    cancel: CancellationToken,
+    steal_lock: bool,
+    protection_token: Option<&str>,
+) -> Result<()> {
+    let pipeline = ConfigLoader::new().load_pipeline(path)?;
+    let mut params = pipeline.parameters_with(overrides, chrono::Utc::now().date_naive())?;
+    let start = match from_stage {
+        Some(name) => pipeline.stage_index(name)?,
+        None => 0,
+    };
+    info!(
+        "Pipeline '{}': {} stage(s){}",
+        pipeline.name,
+        pipeline.stages.len(),
+        if start > 0 {
+            format!(", starting at '{}'", pipeline.stages[start].name)
+        } else {
+            String::new()
+        }
+    );
+    for (name, value) in &params {
+        info!("  {} = {}", name, value);
+    }
+
+    let mut outcomes = Vec::new();
+    for (index, stage) in pipeline.stages.iter().enumerate() {
+        if index < start {
+            outcomes.push(format!("{}: skipped (before --from-stage)", stage.name));
+            continue;
+        }
+        if !stage.should_run(&params)? {
+            info!(
+                "Stage '{}' skipped: {} does not hold",
+                stage.name,
+                stage.when.as_deref().unwrap_or_default()
+            );
+            outcomes.push(format!(
+                "{}: skipped (when {})",
+                stage.name,
+                stage.when.as_deref().unwrap_or_default()
+            ));
+            continue;
+        }
+        if cancel.is_cancelled() {
+            return Err(crate::error::AutoInstallError::ValidationError(format!(
+                "Pipeline '{}' cancelled before stage '{}'",
+                pipeline.name, stage.name
+            )));
+        }
+
+        let action = stage.rendered(&params)?;
+        info!(
+            "Stage {}/{} '{}' ({})",
+            index + 1,
+            pipeline.stages.len(),
+            stage.name,
+            action.kind()
+        );
+        let started = std::time::Instant::now();
+        let result = if dry_run {
+            println!(
+                "{} ({}):\n{}",
+                stage.name,
+                action.kind(),
+                serde_yaml::to_string(&action)?.trim_end()
+            );
+            match &action {
+                StageAction::CreateImage(build) => {
+                    params.insert("image".to_string(), build.output.clone());
+                }
+                StageAction::Publish(publish) => {
+                    let name = std::path::Path::new(&publish.image)
+                        .file_name()
+                        .map(|n| n.to_string_lossy().into_owned())
+                        .unwrap_or_default();
+                    params.insert(
+                        "image".to_string(),
+                        std::path::Path::new(&publish.dir)
+                            .join(name)
+                            .display()
+                            .to_string(),
+                    );
+                }
+                StageAction::FleetDeploy(_) | StageAction::Verify(_) => {}
+            }
+            Ok(())
+        } else {
+            run_pipeline_stage(&action, &mut params, &cancel, steal_lock, protection_token).await
+        };
+        if let Err(e) = result {
+            outcomes.push(format!("{}: failed: {}", stage.name, e));
+            for line in &outcomes {
+                info!("  {}", line);
+            }
+            return Err(crate::error::AutoInstallError::ValidationError(format!(
+                "Pipeline '{}' stopped at stage '{}': {}; resume with --from-stage {}",
+                pipeline.name, stage.name, e, stage.name
+            )));
+        }
+        outcomes.push(format!(
+            "{}: {} in {}s",
+            stage.name,
+            if dry_run { "planned" } else { "done" },
+            started.elapsed().as_secs()
+        ));
+    }
+
+    info!("Pipeline '{}' finished:", pipeline.name);
+    for line in &outcomes {
+        info!("  {}", line);
+    }
+    Ok(())
+}
+
+/// Run one expanded pipeline stage; build and publish stages set the `image` parameter
+async fn run_pipeline_stage(
+    action: &StageAction,
+    params: &mut std::collections::BTreeMap<String, String>,
+    cancel: &CancellationToken,
+    steal_lock: bool,
+    protection_token: Option<&str>,
+) -> Result<()> {
+    match action {
+        StageAction::CreateImage(build) => {
+            let image = create_image_command(
+                build.arch,
+                &build.ubuntu_version,
+                Some(build.output.clone()),
+                build.spec.clone(),
+                build.cache_dir.clone(),
+                VmResourceOverrides {
+                    allow_tcg: build.allow_tcg,
+                    ..Default::default()
+                },
+                cancel,
+            )
+            .await?;
+            params.insert("image".to_string(), image.display().to_string());
+        }
+        StageAction::Publish(publish) => {
+            let published = ImageManager::with_images_dir(&publish.dir)
+                .publish(
+                    std::path::Path::new(&publish.image),
+                    publish.latest.as_deref(),
+                )
+                .await?;
+            params.insert("image".to_string(), published.display().to_string());
+        }
+        StageAction::FleetDeploy(deploy) => {
+            fleet_deploy_command(
+                FleetTarget {
+                    inventory: &deploy.inventory,
+                    selector: deploy.selector.as_deref(),
+                },
+                deploy.yes,
+                false,
+                cancel.clone(),
+                steal_lock,
+                protection_token,
+            )
+            .await?;
+        }
+        StageAction::Verify(verify) => {
+            let (_, hosts) = FleetTarget {
+                inventory: &verify.inventory,
+                selector: verify.selector.as_deref(),
+            }
+            .load()?;
+            let mut failed = Vec::new();
+            for host in &hosts {
+                let Some(target_config) = &host.target_config else {
+                    warn!("{} has no target_config; nothing to verify", host.hostname);
+                    failed.push(host.hostname.clone());
+                    continue;
+                };
+                let username = host.username.as_deref().unwrap_or(&verify.username);
+                if let Err(e) = verify_host_command(
+                    host.address(),
+                    Some(host.hostname.clone()),
+                    username,
+                    target_config,
+                )
+                .await
+                {
+                    warn!("Verification of {} failed: {}", host.hostname, e);
+                    failed.push(host.hostname.clone());
+                }
+            }
+            if !failed.is_empty() {
+                return Err(crate::error::AutoInstallError::ValidationError(format!(
+                    "{} of {} host(s) failed verification: {}",
+                    failed.len(),
+                    hosts.len(),
+                    failed.join(", ")
+                )));
+            }
+        }
+    }
+    Ok(())
+}
+
+/// Pool names `hostname` was installed with, from its preset
+fn host_pool_layout(base_dir: &std::path::Path, hostname: &str) -> Result<PoolLayout> {
+    let mut config = PresetStore::in_base_dir(base_dir)
+        .resolve(None, Some(hostname))?
+        .into_config();
+    config.hostname = hostname.to_string();
+    config.pool_layout()
+}
+
+fn log_boot_env_dry_run(host: &str, verb: &str, commands: &[String]) {
+    info!(
+        "DRY RUN: Would {} the boot environment on {} with:",
+        verb, host
+    );
+    for command in commands {
+        info!("  {}", command);
+    }
+}
+
+/// List or change the boot environments of an installed host
+pub async fn boot_env_command(
+    host: &str,
+    hostname: Option<String>,
+    username: &str,
+    action: BootEnvAction,
+    dry_run: bool,
+) -> Result<()> {
+    let hostname = hostname.unwrap_or_else(|| host.to_string());
+    let layout = host_pool_layout(&std::env::current_dir()?, &hostname)?;
+    let mut ssh = SshClient::new();
+    ssh.connect(host, username).await?;
+    let mut manager = BootEnvManager::new(&mut ssh, &layout);
+    let result = match &action {
+        BootEnvAction::List => manager.list().await.map(|environments| {
+            for environment in environments {
+                println!("{}", environment.summary());
+            }
+        }),
+        BootEnvAction::Create { name } if dry_run => manager
+            .create_commands(name)
+            .await
+            .map(|commands| log_boot_env_dry_run(host, "create", &commands)),
+        BootEnvAction::Activate { name } if dry_run => manager
+            .activate_commands(name)
+            .await
+            .map(|commands| log_boot_env_dry_run(host, "activate", &commands)),
+        BootEnvAction::Destroy { name } if dry_run => manager
+            .destroy_commands(name)
+            .await
+            .map(|commands| log_boot_env_dry_run(host, "destroy", &commands)),
+        BootEnvAction::Create { name } => manager.create(name).await,
+        BootEnvAction::Activate { name } => manager.activate(name).await,
+        BootEnvAction::Destroy { name } => manager.destroy(name).await,
+    };
+    ssh.disconnect();
+    result
+}
+
+/// Grow the LUKS partition, its mapping and the pool of an installed host into new disk space
+pub async fn storage_command(
+    host: &str,
+    username: &str,
+    action: StorageAction,
+    dry_run: bool,
+) -> Result<()> {
+    let StorageAction::Expand {
+        mapper,
+        pool,
+        passphrase_file,
+    } = action;
+    let passphrase = passphrase_file
+        .map(|path| std::fs::read_to_string(path).map(|key| key.trim_end().to_string()))
+        .transpose()?;
+    let mut ssh = SshClient::new();
+    ssh.connect(host, username).await?;
+    let mut expander = StorageExpander::new(&mut ssh, &mapper, &pool);
+    let plan = expander.plan().await?;
+    for line in plan.summary_lines() {
+        info!("{}", line);
+    }
+    if !plan.worth_expanding() {
+        info!(
+            "Nothing to expand on {}: {} already uses the disk",
+            host, pool
+        );
+        ssh.disconnect();
+        return Ok(());
+    }
+    if dry_run {
+        info!("DRY RUN: Would expand {} on {} with:", pool, host);
+        for command in plan.commands(passphrase.as_ref().map(|_| "<passphrase>")) {
+            info!("  {}", command);
+        }
+    } else {
+        expander.expand(&plan, passphrase.as_deref()).await?;
+        info!("Storage expand: done on {}", host);
+    }
+    ssh.disconnect();
+    Ok(())
+}
+
+/// Preview what the current presets and target configs would change on every inventory host
+pub fn fleet_plan_command(target: FleetTarget<'_>, json: bool) -> Result<()> {
+    let (_, hosts) = target.load()?;
+    let base_dir = std::env::current_dir()?;
+    inventory_tenant(&base_dir, target.inventory, &hosts)?;
+    let hosts = hosts
+        .iter()
+        .map(|host| match offline_install_config(host, &base_dir) {
+            Ok(config) => HostPlanPreview::compare(
+                &host.hostname,
+                &InstallPlan::from_config(&config),
+                InstallPlan::last_successful(&base_dir, &host.hostname).as_ref(),
+            ),
+            Err(e) => HostPlanPreview::failed(&host.hostname, e.to_string()),
+        })
+        .collect();
+    let preview = FleetPlan {
+        inventory: target.inventory.to_string(),
+        generated_at: chrono::Utc::now(),
+        hosts,
+    };
+    if json {
+        println!("{}", preview.to_json()?);
+    } else {
+        for line in preview.summary_lines() {
+            println!("{}", line);
+        }
+    }
+    Ok(())
+}
+
+/// Install config of an inventory host as far as it can be built without its facts
+fn offline_install_config(
+    host: &InventoryHost,
+    base_dir: &std::path::Path,
+) -> Result<InstallationConfig> {
+    let loader = ConfigLoader::new();
+    let mut config = PresetStore::in_base_dir(base_dir)
+        .resolve(host.preset.as_deref(), Some(&host.hostname))?
+        .into_config();
+    config.hostname = host.hostname.clone();
+    config.apply_target_sections(&loader, host.target_config.as_deref())?;
+    if let Some(path) = &host.target_config {
+        config.apt_snapshot = loader.load_apt_snapshot(path)?;
+    }
+    Ok(config)
+}
+
+/// Read facts from every inventory host and write them as one JSON or CSV dataset
+pub async fn fleet_facts_command(
+    target: FleetTarget<'_>,
+    format: FactsFormatArg,
+    output: Option<String>,
+) -> Result<()> {
+    let (_, hosts) = target.load()?;
+    let base_dir = std::env::current_dir()?;
+    inventory_tenant(&base_dir, target.inventory, &hosts)?;
+    info!("Collecting facts from {} host(s)", hosts.len());
+    let hosts = futures::future::join_all(
+        hosts
+            .iter()
+            .map(|host| HostFacts::collect(host, "ubuntu", &base_dir)),
+    )
+    .await;
+    for host in hosts.iter().filter(|h| h.error.is_some()) {
+        warn!(
+            "{}: {}",
+            host.hostname,
+            host.error.as_deref().unwrap_or_default()
+        );
+    }
+    let export = FleetFacts {
+        inventory: target.inventory.to_string(),
+        generated_at: chrono::Utc::now(),
+        hosts,
+    };
+    let rendered = match format {
+        FactsFormatArg::Json => export.to_json()?,
+        FactsFormatArg::Csv => export.to_csv(),
+    };
+
+    match output {
+        Some(path) => {
+            std::fs::write(&path, rendered)?;
+            info!(
+                "Facts for {} host(s) written to {}",
+                export.hosts.len(),
+                path
+            );
+        }
+        None => println!("{}", rendered),
+    }
+    Ok(())
+}
+
+/// Render the installation report for `hostname` from its last session record
+pub async fn report_command(
+    hostname: &str,
+    format: InstallReportFormat,
+    output: Option<String>,
+) -> Result<()> {
+    let base_dir = std::env::current_dir()?;
+    let session = InstallSession::load(&base_dir, hostname)?;
+    let rendered = InstallReport::new(&session).render(format);
+
+    match output {
+        Some(path) => {
+            std::fs::write(&path, rendered)?;
+            info!("Installation report for {} written to {}", hostname, path);
+        }
+        None => print!("{}", rendered),
+    }
+    Ok(())
+}
+
+/// Pack the artifacts of `session` (an id, id prefix or hostname) into a support bundle
+pub fn support_bundle_command(
+    session: &str,
+    output: Option<String>,
+    preset: Option<&str>,
+    target_config: Option<&str>,
+) -> Result<()> {
+    let base_dir = std::env::current_dir()?;
+    let (record, session) = support_bundle::find_session(&base_dir, session)?;
+    info!(
+        "Bundling session {} of {} ({})",
+        session.id,
+        session.hostname,
+        record.display()
+    );
+    let output = output
+        .map(std::path::PathBuf::from)
+        .unwrap_or_else(|| base_dir.join(format!("{}.tar.gz", SupportBundle::name(&session))));
+    write_support_bundle(&base_dir, &session, &output, preset, target_config)?;
+    info!("Check it before attaching it to an issue: logs are included as they were fetched");
+    Ok(())
+}
+
+/// Bundle the logs, configs and plan of `session` into `output`
+fn write_support_bundle(
+    base_dir: &std::path::Path,
+    session: &InstallSession,
+    output: &std::path::Path,
+    preset: Option<&str>,
+    target_config: Option<&str>,
+) -> Result<std::path::PathBuf> {
+    let staging = tempfile::tempdir()?;
+    let mut bundle = SupportBundle::new(staging.path(), session)?;
+    bundle.add_host_artifacts(&InstallSession::host_dir(base_dir, &session.hostname))?;
+
+    // Preset files keep `${VAR}` references unexpanded; built-ins are written out
+    let store = PresetStore::in_base_dir(base_dir);
+    let preset_name = preset.unwrap_or(&session.hostname);
+    let preset_path = store.path(preset_name)?;
+    if preset_path.is_file() {
+        bundle.add_config("preset.yaml", &std::fs::read_to_string(&preset_path)?)?;
+    } else if let Some(builtin) = InstallPreset::builtin(preset_name) {
+        bundle.add_config("preset.yaml", &serde_yaml::to_string(&builtin)?)?;
+    } else {
+        bundle.note(format!(
+            "No preset named {}; pass --preset to include one",
+            preset_name
+        ));
+    }
+    match target_config {
+        Some(path) => bundle.add_config("target.yaml", &std::fs::read_to_string(path)?)?,
+        None => bundle.note("No target config given (--target-config)".to_string()),
+    }
+    bundle.add_plan(session)?;
+    bundle.add_version(session)?;
+
+    let redacted: usize = bundle.index().files.iter().map(|f| f.redacted_values).sum();
+    let files = bundle.index().files.len();
+    let path = bundle.finish(output)?;
+    info!(
+        "Support bundle written to {} ({} files, {} secret(s) redacted)",
+        path.display(),
+        files,
+        redacted
+    );
+    Ok(path)
+}
+
+/// Open or update the failure issue of `session`'s host, with a fresh support bundle
+///
+/// Failures are logged; the install's own error is what the command returns.
+async fn report_failure_issue(
+    config: &crate::config::IssueConfig,
+    session: &InstallSession,
+    error: &crate::error::AutoInstallError,
+    preset: Option<&str>,
+    target_config: Option<&str>,
+) {
+    let reporter = match crate::network::issues::IssueReporter::new(config) {
+        Ok(reporter) => reporter,
+        Err(e) => {
+            warn!("Failure issue not opened: {}", e);
+            return;
+        }
+    };
+    let result = async {
+        let base_dir = std::env::current_dir()?;
+        let name = format!("{}.tar.gz", SupportBundle::name(session));
+        let output = InstallSession::host_dir(&base_dir, &session.hostname).join(&name);
+        let bundle = match write_support_bundle(&base_dir, session, &output, preset, target_config)
+        {
+            Ok(path) => Some(match &config.bundle_url {
+                Some(url) => url.replace("{bundle}", &name),
+                None => path.display().to_string(),
+            }),
+            Err(e) => {
+                warn!("Support bundle for the failure issue not written: {}", e);
+                None
+            }
+        };
+        let report = crate::network::issues::FailureReport::new(session, error, bundle);
+        reporter.report(&base_dir, &report).await
+    }
+    .await;
+    match result {
+        Ok(record) if record.failures > 1 => info!(
+            "Failure {} of {} added to {}",
+            record.failures, session.hostname, record.url
+        ),
+        Ok(record) => info!("Failure issue opened: {}", record.url),
+        Err(e) => warn!("Failure issue not opened: {}", e),
+    }
+}
+
+/// Kexec a running host into the Ubuntu live environment, optionally continuing with ssh-install
+pub async fn kexec_boot_command(
+    host: &str,
+    username: &str,
+    options: &KexecOptions,
+    wait_timeout_secs: u64,
+    then_install: bool,
+    hostname: Option<String>,
+    dry_run: bool,
+) -> Result<()> {
+    if dry_run {
+        info!("DRY RUN: Would run the following commands on {}:", host);
+        for cmd in build_kexec_commands(options)? {
+            info!("  {}", cmd);
+        }
+        return Ok(());
+    }
+
+    let mut ssh = SshClient::new();
+    ssh.connect(host, username).await?;
+    let wait = RebootWait {
+        timeout: std::time::Duration::from_secs(wait_timeout_secs),
+        poll_interval: std::time::Duration::from_secs(15),
+        ..Default::default()
+    };
+    KexecBooter::new(&mut ssh)
+        .boot_live_environment(options, &wait)
+        .await?;
+    ssh.disconnect();
+
+    if then_install {
+        info!("Live environment is up; continuing with ssh-install");
+        return ssh_install_command(
+            host,
+            SshInstallOptions {
+                hostname,
+                username: Some(username.to_string()),
+                ..Default::default()
+            },
+        )
+        .await;
+    }
+
+    info!(
+        "Live environment is up on {}; run ssh-install to continue",
+        host
+    );
+    Ok(())
+}
+
+/// Copy the running binary onto a target, into a mounted target root when `root` is given
+pub async fn self_install_command(
+    host: &str,
+    username: &str,
+    path: &str,
+    root: Option<&str>,
+    dry_run: bool,
+) -> Result<()> {
+    let binary = std::env::current_exe()?;
+    if dry_run {
+        info!(
+            "DRY RUN: Would upload {} to {} and run:",
+            binary.display(),
+            host
+        );
+        for cmd in build_install_commands(path, root) {
+            info!("  {}", cmd);
+        }
+        return Ok(());
     }
 
-    // Check KVM support
-    if std::path::Path::new("/dev/kvm").exists() {
-        info!("✓ KVM acceleration available");
-    } else {
-        info!("⚠ KVM acceleration not available - VM operations will be slower");
+    let mut ssh = SshClient::new();
+    ssh.connect(host, username).await?;
+    let version = SelfInstaller::new(&mut ssh)
+        .install(&binary, path, root)
+        .await;
+    ssh.disconnect();
+    info!("Installed {} on {}: {}", path, host, version?);
+    Ok(())
+}
+
+/// Run the service probes of a target config against an installed host and record the results
+/// in its install session, if there is one
+pub async fn verify_host_command(
+    host: &str,
+    hostname: Option<String>,
+    username: &str,
+    target_config: &str,
+) -> Result<()> {
+    let hostname = hostname.unwrap_or_else(|| host.to_string());
+    let verification = ConfigLoader::new().load_verification_config(target_config)?;
+    if verification.probes.is_empty() {
+        return Err(crate::error::AutoInstallError::ConfigError(format!(
+            "{} declares no verification probes",
+            target_config
+        )));
     }
 
-    if missing.is_empty() {
-        info!("System is ready for Ubuntu autoinstall operations");
-        Ok(())
-    } else {
-        Err(crate::error::AutoInstallError::SystemError(format!(
-            "Missing {} required dependencies",
-            missing.len()
-        )))
+    let mut ssh = SshClient::new();
+    ssh.connect(host, username).await?;
+    let results = crate::network::ssh_installer::probes::run_probes(&mut ssh, &verification).await;
+    ssh.disconnect();
+    let results = results?;
+
+    let base_dir = std::env::current_dir()?;
+    if let Ok(mut session) = InstallSession::load(&base_dir, &hostname) {
+        session.probe_results = results.clone();
+        session.save(&base_dir)?;
+    }
+
+    println!("Service probes for {} ({})", hostname, host);
+    for result in &results {
+        println!(
+            "  {} {} ({}, {}): {} after {} attempt(s)",
+            if result.passed { "PASS" } else { "FAIL" },
+            result.name,
+            result.kind,
+            result.role.as_str(),
+            result.detail,
+            result.attempts
+        );
     }
+    crate::network::ssh_installer::probes::require_passed(&results)
 }
 
-/// Cleanup old images
-pub async fn cleanup_command(older_than_days: u32, dry_run: bool) -> Result<()> {
-    info!("Cleaning up images older than {} days", older_than_days);
+/// Compare a host against the baseline recorded when it was installed, optionally reinstalling it
+pub async fn drift_check_command(
+    host: &str,
+    hostname: Option<String>,
+    username: &str,
+    threshold: usize,
+    reinstall: bool,
+    json: bool,
+) -> Result<()> {
+    let hostname = hostname.unwrap_or_else(|| host.to_string());
+    let base_dir = std::env::current_dir()?;
+    let baseline = HostBaseline::load(&base_dir, &hostname)?;
+
+    // The config that would be applied today; a different checksum means the host is stale
+    let config = PresetStore::in_base_dir(&base_dir)
+        .resolve(None, Some(&hostname))?
+        .into_config();
+    let recorded = InstallSession::load(&base_dir, &hostname)
+        .ok()
+        .and_then(|session| session.config_checksum)
+        .unwrap_or_else(|| baseline.config_checksum.clone());
+    if recorded != baseline.config_checksum {
+        warn!("Session and baseline config checksums disagree; using the session record");
+    }
 
-    let manager = ImageManager::new();
-    let old_images = manager.find_old_images(older_than_days).await?;
+    let mut ssh = SshClient::new();
+    ssh.connect(host, username).await?;
+    let current = BaselineCollector::new(&mut ssh)
+        .collect("/", &config.disk_device, &config.checksum())
+        .await?;
+    ssh.disconnect();
 
-    if old_images.is_empty() {
-        info!("No old images found for cleanup");
-        return Ok(());
-    }
+    let mut reference = baseline;
+    reference.config_checksum = recorded;
+    let report = compare(&reference, &current);
 
-    if dry_run {
-        info!("DRY RUN: Would delete {} old images:", old_images.len());
-        for image in &old_images {
-            info!(
-                "  {} - {} ({}) - {}",
-                image.id,
-                image.ubuntu_version,
-                image.architecture.as_str(),
-                image.size_human()
-            );
+    if json {
+        println!("{}", serde_json::to_string_pretty(&report)?);
+    } else {
+        println!("Drift report for {} ({})", hostname, host);
+        for line in report.summary_lines() {
+            println!("  {}", line);
         }
+    }
+
+    if report.score() < threshold {
+        info!(
+            "{} is within drift threshold ({} < {})",
+            hostname,
+            report.score(),
+            threshold
+        );
         return Ok(());
     }
 
-    let deleted_count = manager.cleanup_images(old_images).await?;
-    info!("Successfully deleted {} old images", deleted_count);
+    if !reinstall {
+        return Err(crate::error::AutoInstallError::ValidationError(format!(
+            "{} has drifted (score {} >= threshold {})",
+            hostname,
+            report.score(),
+            threshold
+        )));
+    }
 
-    Ok(())
+    // Boot the live image of the release and architecture the host was installed with
+    let release = config.debootstrap_release.as_deref().ok_or_else(|| {
+        crate::error::AutoInstallError::ValidationError(format!(
+            "{} has drifted, but its config names no debootstrap_release; not reinstalling",
+            hostname
+        ))
+    })?;
+    let version = crate::config::ubuntu_version(release)
+        .or_else(|| crate::config::ubuntu_codename(release).map(|_| release))
+        .ok_or_else(|| {
+            crate::error::AutoInstallError::ValidationError(format!(
+                "{} has drifted, but release '{}' has no known live image; not reinstalling",
+                hostname, release
+            ))
+        })?;
+
+    warn!(
+        "{} has drifted (score {} >= threshold {}); reinstalling {} {}",
+        hostname,
+        report.score(),
+        threshold,
+        version,
+        config.architecture.as_str()
+    );
+    kexec_boot_command(
+        host,
+        username,
+        &KexecOptions::new(version, config.architecture),
+        900,
+        true,
+        Some(hostname),
+        false,
+    )
+    .await
 }
 
-/// Install Ubuntu via SSH to a target machine
-pub async fn ssh_install_command(
+/// Snapshot a host's ZFS pools and send them to a backup target, incrementally when possible
+pub async fn backup_command(
     host: &str,
     hostname: Option<String>,
-    username: Option<String>,
-    investigate_only: bool,
+    username: &str,
+    target: &str,
+    full: bool,
+    throttle: ThrottleConfig,
     dry_run: bool,
-    hold_on_failure: bool,
-    pause_after_storage: bool,
 ) -> Result<()> {
-    let username = username.unwrap_or_else(|| "ubuntu".to_string());
-    let _hostname = hostname.unwrap_or_else(|| "len-serv-003".to_string());
-
-    info!(
-        "Connecting to {}@{} for Ubuntu installation",
-        username, host
-    );
-
-    let mut installer = SshInstaller::new();
+    throttle.validate()?;
+    let hostname = hostname.unwrap_or_else(|| host.to_string());
+    let target = BackupTarget::parse(target)?;
+    let base_dir = std::env::current_dir()?;
+    let mut catalog = BackupCatalog::load(&base_dir, &hostname)?;
+    let pools = layout_pools(&host_pool_layout(&base_dir, &hostname)?);
+    let base = if full {
+        None
+    } else {
+        catalog
+            .latest_for(&target)
+            .map(|record| record.snapshot.clone())
+    };
 
-    // Connect to the target
-    installer.connect(host, &username).await?;
-    info!("Successfully connected to target machine");
+    if dry_run {
+        info!("DRY RUN: Would run the following commands on {}:", host);
+        let snapshot = snapshot_name(chrono::Utc::now());
+        for cmd in build_backup_commands(&target, &pools, &snapshot, base.as_deref(), &throttle) {
+            info!("  {}", cmd);
+        }
+        return Ok(());
+    }
 
-    // Always investigate the system first
-    info!("Investigating target system...");
-    let system_info = installer.investigate_system().await?;
+    let mut ssh = SshClient::new();
+    ssh.connect(host, username).await?;
+    let record = BackupManager::new(&mut ssh)
+        .with_throttle(throttle)
+        .create_backup(&target, &pools, base.as_deref())
+        .await?;
+    ssh.disconnect();
 
-    println!("\n=== SYSTEM INVESTIGATION RESULTS ===");
-    println!("Hostname: {}", system_info.hostname);
-    println!("Kernel: {}", system_info.kernel_version);
-    println!("Available tools: {:?}", system_info.available_tools);
-    println!("\n--- OS Release ---");
-    println!("{}", system_info.os_release);
-    println!("\n--- Memory Info ---");
-    println!("{}", system_info.memory_info);
-    println!("\n--- CPU Info ---");
-    println!("{}", system_info.cpu_info);
-    println!("\n--- Disk Information ---");
-    println!("{}", system_info.disk_info);
-    println!("\n--- Network Information ---");
-    println!("{}", system_info.network_info);
+    info!(
+        "Backup {} of {} written to {}",
+        record.snapshot, hostname, record.target
+    );
+    catalog.backups.push(record);
+    let path = catalog.save(&base_dir, &hostname)?;
+    info!("Backup catalog updated: {}", path.display());
+    Ok(())
+}
 
-    if investigate_only {
-        info!("Investigation complete. Exiting as requested.");
-        return Ok(());
+/// Recreate a host's disk layout and pools from its recorded backups
+pub async fn restore_command(
+    host: &str,
+    hostname: &str,
+    username: &str,
+    target: &str,
+    snapshot: Option<String>,
+    disk: Option<String>,
+    dry_run: bool,
+) -> Result<()> {
+    let target = BackupTarget::parse(target)?;
+    let catalog = BackupCatalog::load(&std::env::current_dir()?, hostname)?;
+    let chain = catalog.restore_chain(&target, snapshot.as_deref())?;
+
+    let mut config = PresetStore::in_base_dir(&std::env::current_dir()?)
+        .resolve(None, Some(hostname))?
+        .into_config();
+    config.hostname = hostname.to_string();
+    if let Some(disk) = disk {
+        config.disk_device = disk;
     }
 
-    // Create installation configuration
-    let config = InstallationConfig::for_len_serv_003();
-
     if dry_run {
-        info!("DRY RUN: Would perform full ZFS+LUKS installation with config:");
-        info!("  Hostname: {}", config.hostname);
-        info!("  Disk: {}", config.disk_device);
-        info!("  Timezone: {}", config.timezone);
+        let pools = layout_pools(&config.pool_layout()?);
+        check_chain_pools(&chain, &pools)?;
         info!(
-            "  Network: {} -> {}",
-            config.network_interface, config.network_address
+            "DRY RUN: Would wipe {} on {}, recreate {} and receive:",
+            config.disk_device,
+            host,
+            pools.join("/")
         );
+        for cmd in build_receive_commands(&target, &pools, &chain, &ThrottleConfig::default()) {
+            info!("  {}", cmd);
+        }
         return Ok(());
     }
 
-    // Confirm installation
-    println!("\n=== INSTALLATION CONFIGURATION ===");
-    println!("Target hostname: {}", config.hostname);
-    println!(
-        "Target disk: {} (THIS WILL BE COMPLETELY WIPED)",
-        config.disk_device
-    );
-    println!("Timezone: {}", config.timezone);
-    println!("Network interface: {}", config.network_interface);
-    println!("Network address: {}", config.network_address);
-    println!("Gateway: {}", config.network_gateway);
+    let mut ssh = SshClient::new();
+    ssh.connect(host, username).await?;
+    BackupManager::new(&mut ssh)
+        .restore(&config, &target, &chain)
+        .await?;
+    ssh.disconnect();
+    Ok(())
+}
 
-    println!(
-        "\nWARNING: This will completely destroy all data on {}!",
-        config.disk_device
+/// Decide the confirmation gate an install of `hostname` waits at before `phase`
+pub fn approve_command(hostname: &str, phase: &str, reject: Option<String>) -> Result<()> {
+    let gate: GatePhase = phase.parse()?;
+    let decision = match reject {
+        Some(reason) => GateDecision::Rejected(reason),
+        None => GateDecision::Approved,
+    };
+    let path = gates::write_approval(&std::env::current_dir()?, hostname, gate, &decision)?;
+    match decision {
+        GateDecision::Approved => info!("Approved {} for {}", gate.id(), hostname),
+        GateDecision::Rejected(reason) => {
+            info!("Rejected {} for {}: {}", gate.id(), hostname, reason)
+        }
+    }
+    info!(
+        "Decision written to {}; the install picks it up at its next poll",
+        path.display()
     );
-    println!("This is a DESTRUCTIVE operation that cannot be undone!");
+    Ok(())
+}
 
-    // In a real implementation, you might want to add a confirmation prompt here
-    // For automation purposes, we'll proceed directly
+/// Redeem the one-time enrollment token presented by `hostname`
+pub async fn enroll_verify_command(hostname: &str, token: Option<String>) -> Result<()> {
+    let token = match token {
+        Some(token) => token,
+        None => {
+            let mut line = String::new();
+            std::io::stdin().read_line(&mut line)?;
+            line
+        }
+    };
 
-    info!("Starting full ZFS+LUKS Ubuntu installation...");
-    installer
-        .perform_installation_with_options_and_pause(&config, hold_on_failure, pause_after_storage)
-        .await?;
+    let base_dir = std::env::current_dir()?;
+    let record = enrollment::redeem(&base_dir, hostname, &token)?;
+    info!(
+        "Enrollment accepted for {} (token issued {})",
+        hostname,
+        record.issued_at.to_rfc3339()
+    );
+    println!("accepted");
+    Ok(())
+}
 
-    info!("SSH installation completed successfully!");
-    info!("Target machine should now be ready to boot from local disk");
+/// List install presets, print one as YAML, or save it for editing
+pub async fn presets_command(name: Option<String>, save: bool) -> Result<()> {
+    let store = PresetStore::in_base_dir(&std::env::current_dir()?);
+    let Some(name) = name else {
+        for name in store.list()? {
+            let source = if store.path(&name)?.is_file() {
+                "file"
+            } else {
+                "built-in"
+            };
+            println!("{:<24} {}", name, source);
+        }
+        return Ok(());
+    };
+
+    // Secrets are never written back out, even from built-ins that still carry them
+    let preset = InstallPreset {
+        luks_key: None,
+        root_password: None,
+        ..store.load(&name)?
+    };
+    if save {
+        let path = store.save(&name, &preset)?;
+        info!("Preset {} written to {}", name, path.display());
+    } else {
+        print!("{}", serde_yaml::to_string(&preset)?);
+    }
+    Ok(())
+}
 
+/// Show or edit `logs/<hostname>/host-vars.json`
+pub async fn host_vars_command(hostname: &str, action: HostVarsAction) -> Result<()> {
+    let base_dir = std::env::current_dir()?;
+    let mut vars = HostVars::load(&base_dir, hostname)?;
+    match action {
+        HostVarsAction::Show => {
+            for (key, var) in &vars.vars {
+                println!(
+                    "{:<20} {:<40} {} {}",
+                    key,
+                    var.value.replace('\n', " | "),
+                    var.source,
+                    var.updated_at.to_rfc3339()
+                );
+            }
+            return Ok(());
+        }
+        HostVarsAction::Set { key, value } => {
+            vars.set(&key, &value, "operator");
+        }
+        HostVarsAction::Unset { key } => {
+            if !vars.remove(&key) {
+                warn!("{} has no host variable {}", hostname, key);
+                return Ok(());
+            }
+        }
+    }
+    let path = vars.save(&base_dir)?;
+    info!("Host vars written to {}", path.display());
     Ok(())
 }
 
@@ -369,6 +3182,7 @@ pub async fn local_install_command(
     hold_on_failure: bool,
     pause_after_storage: bool,
     force: bool,
+    cancel: &CancellationToken,
 ) -> Result<()> {
     let hostname = hostname.unwrap_or_else(|| "ubuntu-local".to_string());
 
@@ -397,6 +3211,7 @@ pub async fn local_install_command(
     }
 
     let mut installer = SshInstaller::new();
+    installer.set_cancellation_token(cancel.clone());
 
     // "Connect" to localhost (no-op for local)
     installer.connect_local().await?;
@@ -427,12 +3242,28 @@ pub async fn local_install_command(
     }
 
     // Create installation configuration for local system
-    let config = create_local_installation_config(&hostname, &system_info)?;
+    let facts = installer.target_facts().await?;
+    let mut config = create_local_installation_config(&hostname, &facts)?;
+    if let Some(profile) = HardwareProfile::detect(&facts) {
+        let (layout, warnings) = profile.inherit_layout(None, &mut config);
+        if let Some(layout) = layout {
+            info!(
+                "Storage layout {} (default for hardware class {})",
+                layout, profile.class
+            );
+        }
+        for warning in warnings {
+            warn!("{}", warning);
+        }
+    }
 
     if dry_run {
         info!("DRY RUN: Would perform full ZFS+LUKS installation with config:");
         info!("  Hostname: {}", config.hostname);
         info!("  Disk: {}", config.disk_device);
+        if !config.esp_mirror_devices.is_empty() {
+            info!("  Mirrored ESPs: {}", config.esp_mirror_devices.join(", "));
+        }
         info!("  Timezone: {}", config.timezone);
         info!(
             "  Network: {} -> {}",
@@ -452,6 +3283,9 @@ pub async fn local_install_command(
     println!("Network interface: {}", config.network_interface);
     println!("Network address: {}", config.network_address);
     println!("Gateway: {}", config.network_gateway);
+    if let Some(snapshot) = &config.apt_snapshot {
+        println!("APT snapshot: {}", snapshot);
+    }
 
     println!(
         "\nWARNING: This will completely destroy all data on {}!",
@@ -491,13 +3325,13 @@ fn is_live_environment() -> bool {
 /// Create installation configuration for local system
 fn create_local_installation_config(
     hostname: &str,
-    system_info: &SystemInfo,
+    facts: &TargetFacts,
 ) -> Result<InstallationConfig> {
-    // Detect primary disk (usually the largest disk)
-    let disk_device = detect_primary_disk(&system_info.disk_info)?;
+    // Detect primary disk (the largest disk with nothing mounted)
+    let disk_device = detect_primary_disk(facts)?;
 
     // Detect network configuration
-    let (interface, address, gateway) = detect_network_config(&system_info.network_info)?;
+    let (interface, address, gateway) = detect_network_config(facts);
 
     // Detect timezone
     let timezone = detect_timezone().unwrap_or_else(|| "UTC".to_string());
@@ -515,37 +3349,73 @@ fn create_local_installation_config(
         network_nameservers: vec!["8.8.8.8".to_string(), "1.1.1.1".to_string()],
         debootstrap_release: Some("plucky".to_string()),
         debootstrap_mirror: Some("http://archive.ubuntu.com/ubuntu/".to_string()),
+        esp_mirror_devices: Vec::new(),
+        kernel: Default::default(),
+        apt_snapshot: None,
+        hardening: Default::default(),
+        zfs_tuning: Default::default(),
+        firewall: Default::default(),
+        headless: Default::default(),
+        ssh_ca: Default::default(),
+        nbde: Default::default(),
+        network_recovery: Default::default(),
+        late_commands: Default::default(),
+        updates: Default::default(),
+        disk_health: Default::default(),
+        apt_lock: Default::default(),
+        health_gate: Default::default(),
+        partitioning: Default::default(),
+        confirmation: Default::default(),
+        budget: Default::default(),
+        bootloader: Default::default(),
+        host_vars: Default::default(),
+        low_memory: Default::default(),
+        entropy: Default::default(),
+        apt_mirrors: Default::default(),
+        ubuntu_pro: Default::default(),
+        zfs_pools: Default::default(),
+        performance: Default::default(),
+        apt_repos: Default::default(),
+        user_data: Default::default(),
+        reinstall: Default::default(),
+        // Local installs run on the machine being installed
+        architecture: std::env::consts::ARCH
+            .parse()
+            .unwrap_or(Architecture::Amd64),
     })
 }
 
 /// Detect the primary disk for installation
-fn detect_primary_disk(disk_info: &str) -> Result<String> {
-    // Parse lsblk output to find the primary disk
-    // This is a simplified implementation - you might want to make it more robust
-    for line in disk_info.lines() {
-        if line.contains("disk") && !line.contains("loop") && !line.contains("sr") {
-            if let Some(device) = line.split_whitespace().next() {
-                if device.starts_with("nvme") || device.starts_with("sd") {
-                    return Ok(format!("/dev/{}", device));
-                }
-            }
-        }
-    }
-
-    Err(crate::error::AutoInstallError::ValidationError(
-        "Could not detect primary disk for installation".to_string(),
-    ))
+fn detect_primary_disk(facts: &TargetFacts) -> Result<String> {
+    facts
+        .primary_disk()
+        .map(|disk| disk.path.clone())
+        .ok_or_else(|| {
+            crate::error::AutoInstallError::ValidationError(
+                "Could not detect primary disk for installation".to_string(),
+            )
+        })
 }
 
-/// Detect network configuration
-fn detect_network_config(_network_info: &str) -> Result<(String, String, String)> {
-    // This is a simplified implementation
-    // In a real implementation, you'd parse the network info more carefully
-    let interface = "eth0".to_string(); // Default
-    let address = "dhcp".to_string(); // Use DHCP by default
-    let gateway = "auto".to_string(); // Auto-detect
-
-    Ok((interface, address, gateway))
+/// Detect network configuration: interface, IPv4 address and gateway of the default route
+///
+/// Falls back to DHCP on `eth0` when nothing usable was found.
+fn detect_network_config(facts: &TargetFacts) -> (String, String, String) {
+    let Some(iface) = facts.primary_interface() else {
+        return ("eth0".to_string(), "dhcp".to_string(), "auto".to_string());
+    };
+    let address = iface
+        .addresses
+        .iter()
+        .find(|a| a.contains('.'))
+        .cloned()
+        .unwrap_or_else(|| "dhcp".to_string());
+    let gateway = facts
+        .default_route
+        .as_ref()
+        .and_then(|route| route.gateway.clone())
+        .unwrap_or_else(|| "auto".to_string());
+    (iface.name.clone(), address, gateway)
 }
 
 /// Detect system timezone
@@ -569,7 +3439,24 @@ fn prompt_for_luks_passphrase() -> Result<String> {
         .read_line(&mut passphrase)
         .map_err(crate::error::AutoInstallError::IoError)?;
 
-    Ok(passphrase.trim().to_string())
+    let passphrase = passphrase.trim().to_string();
+    crate::logging::redact::register(&passphrase);
+    Ok(passphrase)
+}
+
+/// Ask a yes/no question; anything but `y` or `yes` is no
+fn confirm(question: &str) -> Result<bool> {
+    print!("{} [y/N]: ", question);
+    std::io::stdout()
+        .flush()
+        .map_err(crate::error::AutoInstallError::IoError)?;
+
+    let mut answer = String::new();
+    std::io::stdin()
+        .read_line(&mut answer)
+        .map_err(crate::error::AutoInstallError::IoError)?;
+
+    Ok(matches!(answer.trim().to_lowercase().as_str(), "y" | "yes"))
 }
 
 /// Prompt for root password
@@ -584,7 +3471,9 @@ fn prompt_for_root_password() -> Result<String> {
         .read_line(&mut password)
         .map_err(crate::error::AutoInstallError::IoError)?;
 
-    Ok(password.trim().to_string())
+    let password = password.trim().to_string();
+    crate::logging::redact::register(&password);
+    Ok(password)
 }
 
 #[cfg(test)]
@@ -615,7 +3504,19 @@ mod tests {
 
         // Act & Assert
         // Note: This will fail without actual infrastructure, but tests the function signature
-        let result = create_image_command(arch, version, None, None, Some(cache_dir_str)).await;
+        let result = create_image_command(
+            arch,
+            version,
+            None,
+            None,
+            Some(cache_dir_str),
+            VmResourceOverrides {
+                allow_tcg: true,
+                ..Default::default()
+            },
+            &CancellationToken::new(),
+        )
+        .await;
 
         // The function should at least not panic and return a Result
         // In a real test environment, we'd mock the ImageBuilder
@@ -662,6 +3563,11 @@ custom_scripts: []
             None,
             Some(spec_path_str.to_string()),
             Some(cache_dir_str),
+            VmResourceOverrides {
+                allow_tcg: true,
+                ..Default::default()
+            },
+            &CancellationToken::new(),
         )
         .await;
 
@@ -693,7 +3599,15 @@ users:
         let image_path = "/tmp/test.iso";
 
         // Act
-        let result = deploy_command(target, config_path_str, image_path, true, true).await;
+        let result = deploy_command(
+            target,
+            config_path_str,
+            image_path,
+            true,
+            true,
+            DeployOptions::default(),
+        )
+        .await;
 
         // Assert
         // Dry run may succeed or fail depending on system dependencies
@@ -708,7 +3622,15 @@ users:
         let image_path = "/tmp/test.iso";
 
         // Act
-        let result = deploy_command(target, config_path, image_path, false, false).await;
+        let result = deploy_command(
+            target,
+            config_path,
+            image_path,
+            false,
+            false,
+            DeployOptions::default(),
+        )
+        .await;
 
         // Assert
         assert!(result.is_err()); // Should fail with invalid config path
@@ -787,10 +3709,13 @@ users:
 
         // Act
         let result = ssh_install_command(
-            host, hostname, username, true,  // investigate_only
-            false, // dry_run
-            false, // hold_on_failure
-            false, // pause_after_storage
+            host,
+            SshInstallOptions {
+                hostname,
+                username,
+                investigate_only: true,
+                ..Default::default()
+            },
         )
         .await;
 
@@ -808,10 +3733,13 @@ users:
 
         // Act
         let result = ssh_install_command(
-            host, hostname, username, false, // investigate_only
-            true,  // dry_run
-            false, // hold_on_failure
-            false, // pause_after_storage
+            host,
+            SshInstallOptions {
+                hostname,
+                username,
+                dry_run: true,
+                ..Default::default()
+            },
         )
         .await;
 
@@ -842,11 +3770,13 @@ users:
 
         // Act
         let result = local_install_command(
-            hostname, true,  // investigate_only
+            hostname,
+            true,  // investigate_only
             false, // dry_run
             false, // hold_on_failure
             false, // pause_after_storage
             false, // force
+            &CancellationToken::new(),
         )
         .await;
 
@@ -862,11 +3792,13 @@ users:
 
         // Act
         let result = local_install_command(
-            hostname, false, // investigate_only
+            hostname,
+            false, // investigate_only
             true,  // dry_run
             false, // hold_on_failure
             false, // pause_after_storage
             false, // force
+            &CancellationToken::new(),
         )
         .await;
 
@@ -874,4 +3806,120 @@ users:
         // Should fail since we're not running as root in test environment
         assert!(result.is_err());
     }
+
+    #[test]
+    fn test_fleet_commands_need_a_tenant_once_the_agent_is_shared() {
+        let dir = TempDir::new().unwrap();
+        let hosts = [("web-01", None)];
+        assert!(fleet_tenant(dir.path(), "web/inventory.yaml", hosts)
+            .unwrap()
+            .is_none());
+
+        std::fs::write(
+            dir.path().join("tenants.yaml"),
+            format!(
+                "tenants:\n  - name: web\n    config_root: web\n    token_sha256: {}\n    hosts: [\"web-*\"]\n",
+                "0".repeat(64)
+            ),
+        )
+        .unwrap();
+        // No flag turns isolation on: without a valid UAA_TENANT_TOKEN nothing is listed or run
+        if std::env::var(crate::config::tenants::TENANT_TOKEN_ENV).is_err() {
+            assert!(fleet_tenant(dir.path(), "web/inventory.yaml", hosts).is_err());
+        }
+        assert_eq!(fleet_host_event(HostStatus::Completed), "install.completed");
+        assert_eq!(
+            fleet_host_event(HostStatus::VerificationFailed),
+            "install.failed"
+        );
+    }
+
+    #[test]
+    fn test_destructive_target() {
+        use crate::cli::args::Cli;
+        use clap::Parser;
+
+        let target = |args: &[&str]| {
+            let cli = Cli::try_parse_from(
+                std::iter::once("ubuntu-autoinstall-agent").chain(args.iter().copied()),
+            )
+            .unwrap();
+            destructive_target(&cli.command)
+        };
+        assert_eq!(
+            target(&["ssh-install", "-H", "10.0.0.5"]),
+            Some(("10.0.0.5".to_string(), "ssh-install"))
+        );
+        assert_eq!(
+            target(&["ssh-install", "-H", "10.0.0.5", "--dry-run"]),
+            None
+        );
+        assert_eq!(
+            target(&["restore", "-H", "live", "-n", "a", "-t", "/b"]),
+            Some(("live".to_string(), "restore"))
+        );
+        assert_eq!(target(&["drift-check", "-H", "a"]), None);
+        assert_eq!(
+            target(&["storage", "-H", "live", "expand"]),
+            Some(("live".to_string(), "storage expand"))
+        );
+        assert_eq!(
+            target(&["storage", "-H", "live", "--dry-run", "expand"]),
+            None
+        );
+        assert_eq!(
+            target(&["boot-env", "-H", "live", "destroy", "old"]),
+            Some(("live".to_string(), "boot-env destroy"))
+        );
+        assert_eq!(
+            target(&["boot-env", "-H", "live", "activate", "old"]),
+            Some(("live".to_string(), "boot-env activate"))
+        );
+        assert_eq!(target(&["boot-env", "-H", "live", "create", "new"]), None);
+        assert_eq!(target(&["check-prereqs"]), None);
+    }
+
+    #[test]
+    fn test_resolve_provenance_path() {
+        let (doc, image) = resolve_provenance_path("images/u.qcow2");
+        assert_eq!(
+            doc,
+            std::path::PathBuf::from("images/u.qcow2.provenance.json")
+        );
+        assert_eq!(image, Some(std::path::PathBuf::from("images/u.qcow2")));
+
+        let (doc, image) = resolve_provenance_path("images/u.qcow2.provenance.json");
+        assert_eq!(
+            doc,
+            std::path::PathBuf::from("images/u.qcow2.provenance.json")
+        );
+        assert_eq!(image, Some(std::path::PathBuf::from("images/u.qcow2")));
+
+        let (doc, image) = resolve_provenance_path("logs/host-a/provenance.json");
+        assert_eq!(doc, std::path::PathBuf::from("logs/host-a/provenance.json"));
+        assert!(image.is_none());
+    }
+
+    #[test]
+    fn test_resolve_apt_snapshot() {
+        // Arrange
+        let temp_dir = TempDir::new().unwrap();
+        let mut session = InstallSession::new("host-a");
+        session.apt_snapshot = Some(AptSnapshot::parse("20250301T000000Z").unwrap());
+        session.save(temp_dir.path()).unwrap();
+        InstallSession::new("host-b").save(temp_dir.path()).unwrap();
+
+        // Act & Assert
+        let previous = resolve_apt_snapshot("previous", temp_dir.path(), "host-a").unwrap();
+        assert_eq!(previous.to_string(), "20250301T000000Z");
+        assert!(resolve_apt_snapshot("previous", temp_dir.path(), "host-b").is_err());
+        assert!(resolve_apt_snapshot("previous", temp_dir.path(), "host-c").is_err());
+        assert_eq!(
+            resolve_apt_snapshot("2025-03-01", temp_dir.path(), "host-a")
+                .unwrap()
+                .to_string(),
+            "20250301T000000Z"
+        );
+        assert!(resolve_apt_snapshot("now", temp_dir.path(), "host-a").is_ok());
+    }
 }
@@ -0,0 +1,632 @@
+// file: src/cli/run.rs
+// version: 1.0.0
+// guid: 73792550-7cd1-407d-9462-2bcc0b630007
+
+//! Dispatch of a parsed command line, with Ctrl+C handling, target locks and protection checks
+//!
+//! The binary only parses the arguments and calls [`run`], so the building blocks the commands
+//! use stay internal to the crate.
+
+use super::args::{Cli, Commands, FleetAction, ProvenanceAction};
+use super::commands::*;
+use crate::config::{throttle::IoClass, ThrottleConfig};
+use crate::image::{builder::CaptureOptions, VerifiedWriteOptions};
+use crate::logging::init_logger;
+use crate::network::{
+    ssh::{HostKeyPolicy, RebootWait},
+    ssh_installer::{lock::TargetLock, upgrade::UpgradeOptions},
+    KexecOptions,
+};
+use crate::utils::{guest_agent::GUEST_AGENT_SOCKET, CancellationToken, ScreenCaptureOptions};
+use crate::Result;
+use std::time::Duration;
+use tokio::signal;
+use tracing::{info, warn};
+
+/// How long a cancelled command gets to reach a safe stopping point before exit is forced
+const SHUTDOWN_GRACE_PERIOD: Duration = Duration::from_secs(60);
+
+/// Run the command `cli` was parsed into, as the binary does
+pub async fn run(cli: Cli) -> Result<()> {
+    // Initialize logging
+    init_logger(cli.verbose, cli.quiet)?;
+
+    // Ctrl+C cancels the token; commands stop at their next safe point. The watcher runs as
+    // its own task so it still fires while a command is blocked in a remote call.
+    let cancel = CancellationToken::new();
+    tokio::spawn(watch_for_shutdown(cancel.clone()));
+
+    // Protected hosts need an explicit token or a second approver before anything else happens
+    check_protection(&cli.command, cli.protection_token.as_deref()).await?;
+
+    // Destructive commands hold a per-target lock until they finish
+    let _lock = match destructive_target(&cli.command) {
+        Some((target, command)) => Some(TargetLock::acquire(
+            &std::env::current_dir()?,
+            &target,
+            command,
+            cli.steal_lock,
+        )?),
+        None => None,
+    };
+    let steal_lock = cli.steal_lock;
+    let protection_token = cli.protection_token.clone();
+
+    // Execute command; cancellation is observed by the command itself
+    let command_future = async {
+        match cli.command {
+            Commands::CreateImage {
+                arch,
+                version,
+                output,
+                spec,
+                cache_dir,
+                vm_cpus,
+                vm_mem,
+                allow_tcg,
+                screenshot_interval,
+                pause_on_failure,
+                reproduce,
+            } => {
+                let vm = VmResourceOverrides {
+                    cpus: vm_cpus,
+                    memory_mb: vm_mem,
+                    allow_tcg,
+                    screen_capture: ScreenCaptureOptions {
+                        interval_secs: screenshot_interval,
+                        pause_on_failure,
+                        ..Default::default()
+                    },
+                    reproduce,
+                };
+                create_image_command(arch.into(), &version, output, spec, cache_dir, vm, &cancel)
+                    .await
+                    .map(drop)
+            }
+            Commands::CaptureImage {
+                host,
+                username,
+                output,
+                cache_dir,
+                exclude,
+                extra_space_gb,
+            } => {
+                capture_image_command(
+                    &host,
+                    &username,
+                    output,
+                    cache_dir,
+                    CaptureOptions {
+                        excludes: exclude,
+                        extra_space_gb,
+                    },
+                )
+                .await
+            }
+            Commands::Deploy {
+                target,
+                config,
+                image,
+                via_ssh,
+                dry_run,
+                overlay,
+                verify_writes,
+                write_chunk_mb,
+                verify_content,
+                verify_sample,
+            } => {
+                let verified_write = verify_writes.then(|| VerifiedWriteOptions {
+                    chunk_size_mb: write_chunk_mb,
+                    ..Default::default()
+                });
+                deploy_command(
+                    &target,
+                    &config,
+                    &image,
+                    via_ssh,
+                    dry_run,
+                    DeployOptions {
+                        overlay_dir: overlay,
+                        verified_write,
+                        verify_content: verify_content.then_some(verify_sample),
+                    },
+                )
+                .await
+            }
+            Commands::DeployVsphere {
+                config,
+                image,
+                name,
+                dry_run,
+            } => deploy_vsphere_command(&config, &image, name.as_deref(), dry_run).await,
+            Commands::Validate { image } => validate_command(&image).await,
+            Commands::ValidateConfig {
+                dir,
+                format,
+                output,
+                strict,
+            } => validate_config_command(&dir, format, output, strict).await,
+            Commands::CheckPrereqs => check_prerequisites_command().await,
+            Commands::ListImages { filter_arch, json } => {
+                list_images_command(filter_arch.map(Into::into), json).await
+            }
+            Commands::Cleanup {
+                older_than_days,
+                dry_run,
+            } => cleanup_command(older_than_days, dry_run).await,
+            Commands::SshInstall {
+                host,
+                hostname,
+                preset,
+                username,
+                investigate_only,
+                dry_run,
+                hold_on_failure,
+                hold_timeout,
+                pause_after_storage,
+                pause_before,
+                esp_mirror,
+                target_config,
+                apt_snapshot,
+                chaos,
+                transport,
+                transactional_packages,
+                select_mirror,
+                audit_idempotency,
+                sudo,
+                telemetry,
+                reinstall,
+            } => {
+                ssh_install_command(
+                    &host,
+                    SshInstallOptions {
+                        hostname,
+                        preset,
+                        username,
+                        investigate_only,
+                        dry_run,
+                        hold_on_failure,
+                        hold_timeout: (hold_timeout > 0)
+                            .then(|| Duration::from_secs(hold_timeout * 60)),
+                        pause_after_storage,
+                        pause_before,
+                        esp_mirrors: esp_mirror,
+                        target_config,
+                        apt_snapshot,
+                        chaos,
+                        transport,
+                        transactional_packages,
+                        select_mirror,
+                        audit_idempotency,
+                        sudo,
+                        telemetry,
+                        reinstall,
+                        cancel: cancel.clone(),
+                        steal_lock,
+                        luks_key: None,
+                        root_password: None,
+                        fleet_cancel: Default::default(),
+                    },
+                )
+                .await
+            }
+            Commands::Investigate {
+                host,
+                username,
+                format,
+                output,
+                target_config,
+                hostname,
+                rebaseline,
+            } => {
+                investigate_command(
+                    &host,
+                    &username,
+                    format,
+                    output,
+                    target_config.as_deref(),
+                    hostname.as_deref(),
+                    rebaseline,
+                )
+                .await
+            }
+            Commands::Report {
+                hostname,
+                format,
+                output,
+            } => report_command(&hostname, format.into(), output).await,
+            Commands::SupportBundle {
+                session,
+                output,
+                preset,
+                target_config,
+            } => support_bundle_command(
+                &session,
+                output,
+                preset.as_deref(),
+                target_config.as_deref(),
+            ),
+            Commands::KexecBoot {
+                host,
+                username,
+                version,
+                arch,
+                seed_url,
+                kernel_url,
+                initrd_url,
+                iso_url,
+                extra_cmdline,
+                wait_timeout,
+                then_install,
+                hostname,
+                dry_run,
+            } => {
+                let mut options = KexecOptions::new(&version, arch.into());
+                options.seed_url = seed_url;
+                options.kernel_url = kernel_url;
+                options.initrd_url = initrd_url;
+                options.iso_url = iso_url;
+                options.extra_cmdline = extra_cmdline;
+                kexec_boot_command(
+                    &host,
+                    &username,
+                    &options,
+                    wait_timeout,
+                    then_install,
+                    hostname,
+                    dry_run,
+                )
+                .await
+            }
+            Commands::SelfInstall {
+                host,
+                username,
+                path,
+                root,
+                dry_run,
+            } => self_install_command(&host, &username, &path, root.as_deref(), dry_run).await,
+            Commands::DriftCheck {
+                host,
+                hostname,
+                username,
+                threshold,
+                reinstall,
+                json,
+            } => drift_check_command(&host, hostname, &username, threshold, reinstall, json).await,
+            Commands::VerifyHost {
+                host,
+                hostname,
+                username,
+                target_config,
+            } => verify_host_command(&host, hostname, &username, &target_config).await,
+            Commands::ExportConfig {
+                host,
+                username,
+                output,
+            } => export_config_command(&host, &username, output).await,
+            Commands::Sessions { action } => sessions_command(action),
+            Commands::RunPipeline {
+                file,
+                set,
+                from_stage,
+                dry_run,
+            } => {
+                run_pipeline_command(
+                    &file,
+                    &set,
+                    from_stage.as_deref(),
+                    dry_run,
+                    cancel.clone(),
+                    steal_lock,
+                    protection_token.as_deref(),
+                )
+                .await
+            }
+            Commands::Fleet { action } => match action {
+                FleetAction::Deploy {
+                    inventory,
+                    yes,
+                    dry_run,
+                    selector,
+                } => {
+                    fleet_deploy_command(
+                        FleetTarget {
+                            inventory: &inventory,
+                            selector: selector.as_deref(),
+                        },
+                        yes,
+                        dry_run,
+                        cancel.clone(),
+                        steal_lock,
+                        protection_token.as_deref(),
+                    )
+                    .await
+                }
+                FleetAction::Facts {
+                    inventory,
+                    format,
+                    output,
+                    selector,
+                } => {
+                    fleet_facts_command(
+                        FleetTarget {
+                            inventory: &inventory,
+                            selector: selector.as_deref(),
+                        },
+                        format,
+                        output,
+                    )
+                    .await
+                }
+                FleetAction::Plan {
+                    inventory,
+                    json,
+                    selector,
+                } => fleet_plan_command(
+                    FleetTarget {
+                        inventory: &inventory,
+                        selector: selector.as_deref(),
+                    },
+                    json,
+                ),
+                FleetAction::Dhcp {
+                    inventory,
+                    interface,
+                } => fleet_dhcp_command(&inventory, interface, &cancel).await,
+                FleetAction::Cancel { run_id, policy } => fleet_cancel_command(&run_id, policy),
+            },
+            Commands::Upgrade {
+                host,
+                hostname,
+                username,
+                to_release,
+                check_service,
+                no_reboot,
+                reboot_timeout,
+                webhook,
+                no_boot_env,
+                dry_run,
+            } => {
+                let options = UpgradeOptions {
+                    to_release,
+                    reboot: !no_reboot,
+                    services: check_service,
+                    // An installed system keeps its host keys across reboots
+                    reboot_wait: RebootWait {
+                        timeout: Duration::from_secs(reboot_timeout),
+                        host_key_policy: HostKeyPolicy::Strict,
+                        ..Default::default()
+                    },
+                    boot_environment: !no_boot_env,
+                };
+                upgrade_command(
+                    &host,
+                    hostname,
+                    &username,
+                    &options,
+                    webhook.as_deref(),
+                    dry_run,
+                    steal_lock,
+                )
+                .await
+            }
+            Commands::BootEnv {
+                host,
+                hostname,
+                username,
+                dry_run,
+                action,
+            } => boot_env_command(&host, hostname, &username, action, dry_run).await,
+            Commands::Storage {
+                host,
+                username,
+                dry_run,
+                action,
+            } => storage_command(&host, &username, action, dry_run).await,
+            Commands::Backup {
+                host,
+                hostname,
+                username,
+                target,
+                full,
+                bwlimit,
+                io_idle,
+                io_weight,
+                dry_run,
+            } => {
+                let throttle = ThrottleConfig {
+                    io_class: io_idle.then_some(IoClass::Idle),
+                    nice: io_idle.then_some(19),
+                    rate_limit_kbps: bwlimit,
+                    io_weight,
+                    ..Default::default()
+                };
+                backup_command(&host, hostname, &username, &target, full, throttle, dry_run).await
+            }
+            Commands::Restore {
+                host,
+                hostname,
+                username,
+                target,
+                snapshot,
+                disk,
+                dry_run,
+            } => {
+                restore_command(
+                    &host, &hostname, &username, &target, snapshot, disk, dry_run,
+                )
+                .await
+            }
+            Commands::Presets { name, save } => presets_command(name, save).await,
+            Commands::HostVars { hostname, action } => host_vars_command(&hostname, action).await,
+            Commands::Provenance { action } => match action {
+                ProvenanceAction::Verify { path, key } => {
+                    provenance_verify_command(&path, key).await
+                }
+                ProvenanceAction::Show { path } => provenance_show_command(&path).await,
+            },
+            Commands::Approve {
+                hostname,
+                phase,
+                reject,
+            } => approve_command(&hostname, &phase, reject),
+            Commands::EnrollVerify { hostname, token } => {
+                enroll_verify_command(&hostname, token).await
+            }
+            Commands::LocalInstall {
+                hostname,
+                investigate_only,
+                dry_run,
+                hold_on_failure,
+                pause_after_storage,
+                force,
+            } => {
+                local_install_command(
+                    hostname,
+                    investigate_only,
+                    dry_run,
+                    hold_on_failure,
+                    pause_after_storage,
+                    force,
+                    &cancel,
+                )
+                .await
+            }
+        }
+    };
+
+    let result = command_future.await;
+
+    if cancel.is_cancelled() {
+        if let Err(e) = &result {
+            warn!("Stopped: {}", e);
+        }
+        warn!("Application interrupted by user");
+        cleanup_on_exit().await;
+        std::process::exit(130); // Standard exit code for Ctrl+C
+    }
+
+    result
+}
+
+/// Cancel `token` on Ctrl+C, then force exit if the command does not stop in time
+async fn watch_for_shutdown(token: CancellationToken) {
+    if signal::ctrl_c().await.is_err() {
+        warn!("Failed to install Ctrl+C handler; graceful shutdown unavailable");
+        return;
+    }
+    warn!("Received Ctrl+C, stopping at the next safe point (press Ctrl+C again to force exit)...");
+    token.cancel();
+
+    tokio::select! {
+        _ = tokio::time::sleep(SHUTDOWN_GRACE_PERIOD) => {
+            warn!(
+                "Command did not stop within {}s; forcing exit (a remote command may still be running)",
+                SHUTDOWN_GRACE_PERIOD.as_secs()
+            );
+        }
+        _ = signal::ctrl_c() => {
+            warn!("Second Ctrl+C received; forcing exit");
+        }
+    }
+    cleanup_on_exit().await;
+    std::process::exit(130);
+}
+
+/// Cleanup function called on exit
+async fn cleanup_on_exit() {
+    info!("Performing cleanup on exit...");
+
+    // Kill any running QEMU processes
+    let _ = tokio::process::Command::new("pkill")
+        .args(["-f", "qemu-system"])
+        .output()
+        .await;
+
+    // Cleanup temporary files
+    let cleanup_files = [
+        "/tmp/qemu-serial.log",
+        "/tmp/qemu-uefi.log",
+        "/tmp/qemu-monitor.sock",
+        GUEST_AGENT_SOCKET,
+        "/tmp/OVMF_VARS.fd",
+    ];
+
+    for file in &cleanup_files {
+        let _ = tokio::fs::remove_file(file).await;
+    }
+
+    info!("Cleanup completed");
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::path::Path;
+    use tempfile::TempDir;
+
+    #[tokio::test]
+    async fn test_cleanup_on_exit() {
+        // Arrange
+        let temp_dir = TempDir::new().unwrap();
+        let test_file = temp_dir.path().join("test_cleanup_file");
+        tokio::fs::write(&test_file, "test content").await.unwrap();
+
+        // Verify file exists
+        assert!(test_file.exists());
+
+        // Act
+        cleanup_on_exit().await;
+
+        // Assert
+        // The cleanup function should complete without panicking
+        // Note: We can't easily test the pkill command or file cleanup
+        // without mocking, but we can ensure the function runs
+        // Test passes if function completes without panic
+    }
+
+    #[test]
+    fn test_cleanup_file_paths() {
+        // Arrange
+        let expected_cleanup_files = [
+            "/tmp/qemu-serial.log",
+            "/tmp/qemu-uefi.log",
+            "/tmp/qemu-monitor.sock",
+            "/tmp/qemu-guest-agent.sock",
+            "/tmp/OVMF_VARS.fd",
+        ];
+
+        // Act & Assert
+        // Verify these are valid path strings
+        for file_path in expected_cleanup_files {
+            assert!(Path::new(file_path).is_absolute());
+            assert!(file_path.starts_with("/tmp/"));
+        }
+    }
+
+    #[tokio::test]
+    async fn test_cleanup_on_exit_safe_execution() {
+        // Arrange
+        // Create a controlled test environment
+
+        // Act
+        let cleanup_task = tokio::spawn(cleanup_on_exit());
+
+        // Assert
+        // The cleanup function should not panic or hang indefinitely
+        let result = tokio::time::timeout(std::time::Duration::from_secs(5), cleanup_task).await;
+
+        assert!(result.is_ok()); // Should complete within timeout
+        assert!(result.unwrap().is_ok()); // Should not panic
+    }
+
+    #[test]
+    fn test_signal_handling_setup() {
+        // This test verifies that the module includes the necessary signal handling imports
+        // If this compiles, signal handling is properly imported
+
+        // Arrange & Act & Assert
+        // Just verify the signal handling module is available at compile time
+        // Test passes if compilation succeeds
+    }
+}
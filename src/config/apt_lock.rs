@@ -0,0 +1,47 @@
+// file: src/config/apt_lock.rs
+// version: 1.1.0
+// guid: 2b7e5d19-8c4a-4f36-9d02-6a1f3e8c7b45
+
+//! Handling of held apt/dpkg locks (`apt_lock:` section of a target config)
+//!
+//! Live environments often run unattended-upgrades or apt's daily jobs right after boot. Before
+//! each apt command the installer waits for the dpkg and apt locks to be released. When they are
+//! still held after `wait_secs`, the install fails, unless `kill_holder` allows stopping the
+//! holders and repairing dpkg.
+
+use crate::error::AutoInstallError;
+use crate::Result;
+use serde::{Deserialize, Serialize};
+
+/// How long to wait for apt/dpkg locks and what to do when they stay held
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct AptLockConfig {
+    /// Seconds to wait for the locks before giving up
+    pub wait_secs: u64,
+    /// Seconds between checks
+    pub poll_secs: u64,
+    /// Stop the processes still holding the locks after `wait_secs` instead of failing
+    pub kill_holder: bool,
+}
+
+impl Default for AptLockConfig {
+    fn default() -> Self {
+        Self {
+            wait_secs: 600,
+            poll_secs: 10,
+            kill_holder: false,
+        }
+    }
+}
+
+impl AptLockConfig {
+    pub fn validate(&self) -> Result<()> {
+        if self.poll_secs == 0 {
+            return Err(AutoInstallError::ValidationError(
+                "apt_lock poll_secs must be at least 1".to_string(),
+            ));
+        }
+        Ok(())
+    }
+}
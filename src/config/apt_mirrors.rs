@@ -0,0 +1,226 @@
+// file: src/config/apt_mirrors.rs
+// version: 1.1.0
+// guid: 6d2b8f41-7a93-4c1e-b5d0-3e9f4a7c1b86
+
+//! apt mirrors of the installed system (`apt_mirrors:` section of a target config)
+//!
+//! The mirrors are written as apt mirror lists (`/etc/apt/mirrors/*.list`) and the sources use
+//! `mirror+file:` URIs, so apt tries the mirrors in priority order and moves to the next one
+//! when a mirror fails. The lowest-priority mirror is also what debootstrap uses when
+//! `debootstrap_mirror` is not set, so the policy is declared once. A pinned `apt_snapshot`
+//! takes precedence over the list.
+
+use crate::error::AutoInstallError;
+use crate::Result;
+use serde::{Deserialize, Serialize};
+
+/// Mirror list of the main archive inside the target
+pub const ARCHIVE_LIST: &str = "/etc/apt/mirrors/ubuntu.list";
+/// Mirror list of the security pocket inside the target
+pub const SECURITY_LIST: &str = "/etc/apt/mirrors/ubuntu-security.list";
+const RETRIES_FILE: &str = "/etc/apt/apt.conf.d/80-autoinstall-mirrors";
+const SECURITY_ARCHIVE: &str = "http://security.ubuntu.com/ubuntu/";
+const COMPONENTS: &str = "main restricted universe multiverse";
+const KEYRING: &str = "/usr/share/keyrings/ubuntu-archive-keyring.gpg";
+
+fn default_priority() -> u32 {
+    10
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct AptMirror {
+    pub url: String,
+    /// Lower is tried first; mirrors with equal priority are picked in list order
+    #[serde(default = "default_priority")]
+    pub priority: u32,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct AptMirrorsConfig {
+    /// Archive mirrors; the section does nothing while this is empty
+    pub mirrors: Vec<AptMirror>,
+    /// Security pocket mirrors; `mirrors` followed by security.ubuntu.com when empty
+    pub security: Vec<AptMirror>,
+    /// `Acquire::Retries` per download
+    pub retries: u32,
+    /// Connection timeout per mirror in seconds
+    pub timeout_seconds: u32,
+}
+
+impl Default for AptMirrorsConfig {
+    fn default() -> Self {
+        Self {
+            mirrors: Vec::new(),
+            security: Vec::new(),
+            retries: 3,
+            timeout_seconds: 30,
+        }
+    }
+}
+
+impl AptMirrorsConfig {
+    pub fn is_enabled(&self) -> bool {
+        !self.mirrors.is_empty()
+    }
+
+    pub fn validate(&self) -> Result<()> {
+        for mirror in self.mirrors.iter().chain(&self.security) {
+            if !(mirror.url.starts_with("http://") || mirror.url.starts_with("https://"))
+                || mirror.url.contains(char::is_whitespace)
+            {
+                return Err(AutoInstallError::ValidationError(format!(
+                    "apt_mirrors url '{}' must be an http:// or https:// URL",
+                    mirror.url
+                )));
+            }
+        }
+        if !self.security.is_empty() && self.mirrors.is_empty() {
+            return Err(AutoInstallError::ValidationError(
+                "apt_mirrors.security needs apt_mirrors.mirrors as well".to_string(),
+            ));
+        }
+        Ok(())
+    }
+
+    /// Archive mirrors in the order apt tries them
+    pub fn archive_mirrors(&self) -> Vec<AptMirror> {
+        sorted(self.mirrors.clone())
+    }
+
+    /// Security mirrors in the order apt tries them
+    pub fn security_mirrors(&self) -> Vec<AptMirror> {
+        if !self.security.is_empty() {
+            return sorted(self.security.clone());
+        }
+        let mut mirrors = self.archive_mirrors();
+        if !mirrors.iter().any(|m| same_url(&m.url, SECURITY_ARCHIVE)) {
+            let last = mirrors.iter().map(|m| m.priority).max().unwrap_or(0);
+            mirrors.push(AptMirror {
+                url: SECURITY_ARCHIVE.to_string(),
+                priority: last + 1,
+            });
+        }
+        mirrors
+    }
+
+    /// First mirror in priority order; debootstrap's mirror when none is configured
+    pub fn primary(&self) -> Option<String> {
+        self.archive_mirrors().into_iter().next().map(|m| m.url)
+    }
+
+    /// Deb822 `ubuntu.sources` reading both mirror lists
+    pub fn build_deb822_sources(&self, release: &str) -> String {
+        format!(
+            "Types: deb\nURIs: mirror+file:{archive}\nSuites: {rel} {rel}-updates\nComponents: {c}\nSigned-By: {k}\n\n\
+             Types: deb\nURIs: mirror+file:{security}\nSuites: {rel}-security\nComponents: {c}\nSigned-By: {k}\n",
+            archive = ARCHIVE_LIST,
+            security = SECURITY_LIST,
+            rel = release,
+            c = COMPONENTS,
+            k = KEYRING
+        )
+    }
+
+    /// One-line sources for apt without the mirror method: one line per mirror, best first
+    pub fn build_legacy_sources(&self, release: &str) -> String {
+        let mut lines = String::new();
+        for suite in [release.to_string(), format!("{}-updates", release)] {
+            for mirror in self.archive_mirrors() {
+                lines.push_str(&format!("deb {} {} {}\n", mirror.url, suite, COMPONENTS));
+            }
+        }
+        for mirror in self.security_mirrors() {
+            lines.push_str(&format!(
+                "deb {} {}-security {}\n",
+                mirror.url, release, COMPONENTS
+            ));
+        }
+        lines
+    }
+
+    /// Commands writing the mirror lists and the retry settings into the system at `root`
+    pub fn build_apply_commands(&self, root: &str) -> Vec<String> {
+        let root = root.trim_end_matches('/');
+        vec![
+            format!("mkdir -p {}/etc/apt/mirrors", root),
+            format!(
+                "cat > {}{} << 'EOF'\n{}EOF",
+                root,
+                ARCHIVE_LIST,
+                mirror_list(&self.archive_mirrors())
+            ),
+            format!(
+                "cat > {}{} << 'EOF'\n{}EOF",
+                root,
+                SECURITY_LIST,
+                mirror_list(&self.security_mirrors())
+            ),
+            format!(
+                "cat > {}{} << 'EOF'\nAcquire::Retries \"{}\";\nAcquire::http::Timeout \"{}\";\nAcquire::https::Timeout \"{}\";\nEOF",
+                root, RETRIES_FILE, self.retries, self.timeout_seconds, self.timeout_seconds
+            ),
+        ]
+    }
+}
+
+fn sorted(mut mirrors: Vec<AptMirror>) -> Vec<AptMirror> {
+    mirrors.sort_by_key(|m| m.priority);
+    mirrors
+}
+
+fn same_url(a: &str, b: &str) -> bool {
+    a.trim_end_matches('/') == b.trim_end_matches('/')
+}
+
+/// apt mirror list: one URL per line with its `priority:` tag
+fn mirror_list(mirrors: &[AptMirror]) -> String {
+    mirrors
+        .iter()
+        .map(|m| format!("{}\tpriority:{}\n", m.url, m.priority))
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_mirror_lists_and_sources() {
+        let config: AptMirrorsConfig = serde_yaml::from_str(
+            "mirrors:\n  - {url: \"http://archive.ubuntu.com/ubuntu/\", priority: 20}\n  - url: http://mirror.internal/ubuntu/\n    priority: 1\nretries: 5\n",
+        )
+        .unwrap();
+        config.validate().unwrap();
+        assert_eq!(
+            config.primary().as_deref(),
+            Some("http://mirror.internal/ubuntu/")
+        );
+        let security = config.security_mirrors();
+        assert_eq!(security.len(), 3);
+        assert_eq!(security[2].url, SECURITY_ARCHIVE);
+        assert_eq!(security[2].priority, 21);
+
+        let cmds = config.build_apply_commands("/mnt/targetos/");
+        assert_eq!(
+            cmds[1],
+            "cat > /mnt/targetos/etc/apt/mirrors/ubuntu.list << 'EOF'\nhttp://mirror.internal/ubuntu/\tpriority:1\nhttp://archive.ubuntu.com/ubuntu/\tpriority:20\nEOF"
+        );
+        assert!(cmds[3].contains("Acquire::Retries \"5\";"));
+        assert!(config.build_deb822_sources("noble").contains(
+            "URIs: mirror+file:/etc/apt/mirrors/ubuntu-security.list\nSuites: noble-security"
+        ));
+        assert!(config
+            .build_legacy_sources("noble")
+            .starts_with("deb http://mirror.internal/ubuntu/ noble main"));
+
+        let bad = AptMirrorsConfig {
+            mirrors: vec![AptMirror {
+                url: "ftp://mirror/".into(),
+                priority: 1,
+            }],
+            ..Default::default()
+        };
+        assert!(bad.validate().is_err());
+    }
+}
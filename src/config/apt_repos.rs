@@ -0,0 +1,325 @@
+// file: src/config/apt_repos.rs
+// version: 1.2.0
+// guid: 2e7c4a95-8b16-4d3f-a0e9-6f1b5d8c3a72
+
+//! Third-party apt repositories of the installed system (`apt_repos:` section of a target config)
+//!
+//! Every repository gets its own keyring in `/etc/apt/keyrings/<name>.gpg` and a Deb822
+//! `/etc/apt/sources.list.d/<name>.sources` whose `Signed-By` names only that keyring, so a
+//! repository's key can never sign another repository. The key comes inline (`key`), from a URL
+//! (`key_url`) or from a keyserver by fingerprint, and is checked before the source is written:
+//! the keyring must hold exactly one primary key, and it must be `fingerprint` when one is given.
+//!
+//! ```yaml
+//! apt_repos:
+//!   repositories:
+//!     - name: docker
+//!       url: https://download.docker.com/linux/ubuntu
+//!       suites: [noble]
+//!       components: [stable]
+//!       key_url: https://download.docker.com/linux/ubuntu/gpg
+//!       fingerprint: 9DC858229FC7DD38854AE2D88D81803C0EBFCD88
+//!     - name: internal
+//!       url: https://apt.internal.example/
+//!       suites: [./]                          # flat repository, no components
+//!       key: |
+//!         -----BEGIN PGP PUBLIC KEY BLOCK-----
+//!         ...
+//! ```
+//!
+//! The repositories are added right after the Ubuntu sources, so packages from them can be
+//! installed by the rest of the install.
+
+use crate::error::AutoInstallError;
+use crate::utils::shell_quote;
+use crate::Result;
+use serde::{Deserialize, Serialize};
+
+/// Directory of per-repository keyrings inside the target
+pub const KEYRING_DIR: &str = "/etc/apt/keyrings";
+const SOURCES_DIR: &str = "/etc/apt/sources.list.d";
+const DEFAULT_KEYSERVER: &str = "hkps://keyserver.ubuntu.com";
+
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct AptRepository {
+    /// File name of the keyring and the sources file; lowercase letters, digits and dashes
+    pub name: String,
+    pub url: String,
+    pub suites: Vec<String>,
+    /// Empty for flat repositories, whose suite is a path ending in `/`
+    #[serde(default)]
+    pub components: Vec<String>,
+    /// Restrict the source to these architectures, e.g. `[amd64]`
+    #[serde(default)]
+    pub architectures: Vec<String>,
+    /// ASCII-armored public key
+    #[serde(default)]
+    pub key: Option<String>,
+    /// Where the public key is downloaded from; needs `fingerprint`
+    #[serde(default)]
+    pub key_url: Option<String>,
+    /// Full 40-digit fingerprint of the signing key; spaces are ignored
+    #[serde(default)]
+    pub fingerprint: Option<String>,
+    /// Keyserver for keys given only by fingerprint
+    #[serde(default)]
+    pub keyserver: Option<String>,
+}
+
+impl AptRepository {
+    /// Fingerprint in upper case without spaces
+    pub fn normalized_fingerprint(&self) -> Option<String> {
+        self.fingerprint
+            .as_ref()
+            .map(|f| f.split_whitespace().collect::<String>().to_uppercase())
+    }
+
+    pub fn keyring_path(&self) -> String {
+        format!("{}/{}.gpg", KEYRING_DIR, self.name)
+    }
+
+    pub fn sources_path(&self) -> String {
+        format!("{}/{}.sources", SOURCES_DIR, self.name)
+    }
+
+    pub fn validate(&self) -> Result<()> {
+        let invalid = |reason: String| {
+            Err(AutoInstallError::ValidationError(format!(
+                "apt_repos '{}': {}",
+                self.name, reason
+            )))
+        };
+        let name_ok = !self.name.is_empty()
+            && self
+                .name
+                .chars()
+                .all(|c| c.is_ascii_lowercase() || c.is_ascii_digit() || c == '-');
+        if !name_ok {
+            return invalid("name must be lowercase letters, digits and dashes".to_string());
+        }
+        for url in std::iter::once(&self.url).chain(&self.key_url) {
+            if !url.starts_with("https://") && !url.starts_with("http://") {
+                return invalid(format!("'{}' must be an http(s) URL", url));
+            }
+        }
+        if self.suites.is_empty() {
+            return invalid("at least one suite is required".to_string());
+        }
+        let flat = self.suites.iter().all(|s| s.ends_with('/'));
+        if flat != self.components.is_empty() {
+            return invalid(
+                "components are required, except for flat suites ending in '/'".to_string(),
+            );
+        }
+        if let Some(fingerprint) = self.normalized_fingerprint() {
+            if fingerprint.len() != 40 || !fingerprint.chars().all(|c| c.is_ascii_hexdigit()) {
+                return invalid(format!(
+                    "fingerprint '{}' must be the full 40-digit fingerprint",
+                    fingerprint
+                ));
+            }
+        }
+        match (&self.key, &self.key_url, &self.fingerprint) {
+            (Some(_), Some(_), _) => invalid("set either key or key_url, not both".to_string()),
+            (Some(key), None, _) if !key.contains("BEGIN PGP PUBLIC KEY BLOCK") => {
+                invalid("key must be an ASCII-armored public key".to_string())
+            }
+            (None, Some(_), None) => {
+                invalid("a downloaded key needs its fingerprint to be verified".to_string())
+            }
+            (None, None, None) => invalid("needs key, key_url or fingerprint".to_string()),
+            _ => Ok(()),
+        }
+    }
+
+    /// Deb822 stanza of the repository
+    pub fn build_deb822_source(&self) -> String {
+        let mut source = format!(
+            "Types: deb\nURIs: {}\nSuites: {}\n",
+            self.url,
+            self.suites.join(" ")
+        );
+        if !self.components.is_empty() {
+            source.push_str(&format!("Components: {}\n", self.components.join(" ")));
+        }
+        if !self.architectures.is_empty() {
+            source.push_str(&format!(
+                "Architectures: {}\n",
+                self.architectures.join(" ")
+            ));
+        }
+        source.push_str(&format!("Signed-By: {}\n", self.keyring_path()));
+        source
+    }
+
+    /// One-line `sources.list` entries for targets whose apt predates Deb822
+    pub fn build_legacy_source(&self) -> String {
+        let mut options = format!("signed-by={}", self.keyring_path());
+        if !self.architectures.is_empty() {
+            options = format!("arch={} {}", self.architectures.join(","), options);
+        }
+        self.suites
+            .iter()
+            .map(|suite| {
+                format!(
+                    "deb [{}] {} {} {}\n",
+                    options,
+                    self.url,
+                    suite,
+                    self.components.join(" ")
+                )
+                .replace(" \n", "\n")
+            })
+            .collect()
+    }
+
+    /// Commands fetching the key into a staging file, verifying it and installing the keyring
+    fn build_key_commands(&self, root: &str) -> Vec<String> {
+        let staged = format!("/tmp/autoinstall-apt-key-{}", self.name);
+        let mut commands = Vec::new();
+        match (&self.key, &self.key_url, self.normalized_fingerprint()) {
+            (Some(key), _, _) => commands.push(format!(
+                "cat > {} << 'EOF'\n{}\nEOF",
+                staged,
+                key.trim_end()
+            )),
+            (None, Some(url), _) => commands.push(format!(
+                "curl -fsSL --retry 3 {} -o {}",
+                shell_quote(url),
+                staged
+            )),
+            (None, None, Some(fingerprint)) => {
+                let home = format!("{}.gnupg", staged);
+                commands.push(format!(
+                    "rm -rf {h} && mkdir -m 700 {h} && gpg --batch --homedir {h} --keyserver {s} --recv-keys {f} && gpg --batch --homedir {h} --export {f} > {k} && rm -rf {h}",
+                    h = home,
+                    s = self.keyserver.as_deref().unwrap_or(DEFAULT_KEYSERVER),
+                    f = fingerprint,
+                    k = staged
+                ));
+            }
+            (None, None, None) => return commands,
+        }
+        // Exactly one primary key, and the expected one; nothing is installed otherwise
+        let check = match self.normalized_fingerprint() {
+            Some(expected) => format!("[ \"$fprs\" = \"{}\" ]", expected),
+            None => "[ $(echo \"$fprs\" | grep -c .) -eq 1 ]".to_string(),
+        };
+        commands.push(format!(
+            "fprs=$(gpg --batch --show-keys --with-colons {k} 2>/dev/null | awk -F: '/^pub:/ {{p=1; next}} p && /^fpr:/ {{print $10; p=0}}'); \
+             if ! {check}; then echo \"apt repository {n}: unexpected signing key(s): $fprs\" >&2; rm -f {k}; exit 1; fi",
+            k = staged,
+            check = check,
+            n = self.name
+        ));
+        commands.push(format!(
+            "mkdir -p {r}{d} && if grep -q 'BEGIN PGP' {k}; then gpg --batch --yes --dearmor -o {r}{p} {k}; else cp {k} {r}{p}; fi && chmod 644 {r}{p} && rm -f {k}",
+            r = root,
+            d = KEYRING_DIR,
+            k = staged,
+            p = self.keyring_path()
+        ));
+        commands
+    }
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct AptReposConfig {
+    pub repositories: Vec<AptRepository>,
+}
+
+impl AptReposConfig {
+    pub fn is_enabled(&self) -> bool {
+        !self.repositories.is_empty()
+    }
+
+    pub fn validate(&self) -> Result<()> {
+        for (i, repository) in self.repositories.iter().enumerate() {
+            repository.validate()?;
+            if self.repositories[..i]
+                .iter()
+                .any(|other| other.name == repository.name)
+            {
+                return Err(AutoInstallError::ValidationError(format!(
+                    "apt_repos: repository name '{}' is used twice",
+                    repository.name
+                )));
+            }
+        }
+        Ok(())
+    }
+
+    /// Commands installing every repository's verified keyring and Deb822 source under `root`
+    pub fn build_apply_commands(&self, root: &str) -> Vec<String> {
+        self.build_commands(root, false)
+    }
+
+    /// Like [`Self::build_apply_commands`], with `sources.list.d/<name>.list` one-line sources
+    pub fn build_legacy_apply_commands(&self, root: &str) -> Vec<String> {
+        self.build_commands(root, true)
+    }
+
+    fn build_commands(&self, root: &str, legacy: bool) -> Vec<String> {
+        let root = root.trim_end_matches('/');
+        let mut commands = Vec::new();
+        for repository in &self.repositories {
+            commands.extend(repository.build_key_commands(root));
+            let (path, content) = if legacy {
+                (
+                    format!("{}/{}.list", SOURCES_DIR, repository.name),
+                    repository.build_legacy_source(),
+                )
+            } else {
+                (repository.sources_path(), repository.build_deb822_source())
+            };
+            commands.push(format!(
+                "mkdir -p {r}{d} && cat > {r}{p} << 'EOF'\n# Managed by ubuntu-autoinstall-agent\n{c}EOF",
+                r = root,
+                d = SOURCES_DIR,
+                p = path,
+                c = content
+            ));
+        }
+        commands
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::loader::parse_section;
+
+    #[test]
+    fn test_repositories_verify_keys_before_writing_sources() {
+        let config = parse_section::<AptReposConfig>(
+            "apt_repos:\n  repositories:\n    - name: docker\n      url: https://download.docker.com/linux/ubuntu\n      suites: [noble]\n      components: [stable]\n      architectures: [amd64]\n      key_url: https://download.docker.com/linux/ubuntu/gpg\n      fingerprint: 9DC8 5822 9FC7 DD38 854A  E2D8 8D81 803C 0EBF CD88\n",
+        "apt_repos").unwrap();
+        assert!(config.validate().is_ok());
+
+        let commands = config.build_apply_commands("/mnt/targetos/");
+        assert_eq!(commands.len(), 4);
+        assert!(commands[0].starts_with("curl -fsSL --retry 3 https://download.docker.com"));
+        assert!(
+            commands[1].contains("[ \"$fprs\" = \"9DC858229FC7DD38854AE2D88D81803C0EBFCD88\" ]")
+        );
+        assert!(commands[2].contains("--dearmor -o /mnt/targetos/etc/apt/keyrings/docker.gpg"));
+        assert!(commands[3].contains("/mnt/targetos/etc/apt/sources.list.d/docker.sources"));
+        assert!(commands[3].contains(
+            "Components: stable\nArchitectures: amd64\nSigned-By: /etc/apt/keyrings/docker.gpg\n"
+        ));
+        assert_eq!(
+            config.repositories[0].build_legacy_source(),
+            "deb [arch=amd64 signed-by=/etc/apt/keyrings/docker.gpg] https://download.docker.com/linux/ubuntu noble stable\n"
+        );
+
+        let mut unverified = config.repositories[0].clone();
+        unverified.fingerprint = None;
+        assert!(unverified.validate().is_err());
+        let mut flat = config.repositories[0].clone();
+        flat.suites = vec!["./".to_string()];
+        assert!(flat.validate().is_err());
+        flat.components.clear();
+        assert!(flat.validate().is_ok());
+    }
+}
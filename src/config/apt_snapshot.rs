@@ -0,0 +1,176 @@
+// file: src/config/apt_snapshot.rs
+// version: 1.2.0
+// guid: 7e3b9d24-1c6a-4f85-b2d7-0a9e8c5f1b63
+
+//! Pinning installs to a snapshot.ubuntu.com timestamp
+//!
+//! With a pin, debootstrap and the target's apt sources both point at the archive as it
+//! was at that moment, so reinstalling with the same pin yields the same package versions.
+
+use crate::error::AutoInstallError;
+use crate::Result;
+use chrono::{DateTime, NaiveDate, NaiveDateTime, Utc};
+use serde::{Deserialize, Serialize};
+use std::fmt;
+
+/// Snapshot service serving point-in-time copies of the Ubuntu archive
+pub const SNAPSHOT_BASE_URL: &str = "https://snapshot.ubuntu.com/ubuntu";
+
+/// Timestamp format used in snapshot URLs
+const SNAPSHOT_FORMAT: &str = "%Y%m%dT%H%M%SZ";
+
+/// An archive snapshot timestamp such as `20250301T000000Z`
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(try_from = "String", into = "String")]
+pub struct AptSnapshot(DateTime<Utc>);
+
+impl AptSnapshot {
+    /// Parse `20250301T000000Z`, an RFC 3339 timestamp or a plain `2025-03-01` date
+    pub fn parse(value: &str) -> Result<Self> {
+        let value = value.trim();
+        if let Ok(naive) = NaiveDateTime::parse_from_str(value, SNAPSHOT_FORMAT) {
+            return Ok(Self(naive.and_utc()));
+        }
+        if let Ok(parsed) = DateTime::parse_from_rfc3339(value) {
+            return Ok(Self(parsed.with_timezone(&Utc)));
+        }
+        if let Ok(date) = NaiveDate::parse_from_str(value, "%Y-%m-%d") {
+            return Ok(Self(
+                date.and_hms_opt(0, 0, 0).unwrap_or_default().and_utc(),
+            ));
+        }
+        Err(AutoInstallError::ValidationError(format!(
+            "Invalid apt snapshot '{}': expected YYYYMMDDTHHMMSSZ, RFC 3339 or YYYY-MM-DD",
+            value
+        )))
+    }
+
+    /// Pin to the archive as it is right now
+    pub fn now() -> Self {
+        Self(DateTime::from_timestamp(Utc::now().timestamp(), 0).unwrap_or_else(Utc::now))
+    }
+
+    /// Point in time the snapshot refers to
+    pub fn timestamp(&self) -> DateTime<Utc> {
+        self.0
+    }
+
+    /// Archive URI for this snapshot; the security pockets are served from the same tree
+    pub fn archive_uri(&self) -> String {
+        format!("{}/{}/", SNAPSHOT_BASE_URL, self)
+    }
+}
+
+impl fmt::Display for AptSnapshot {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.0.format(SNAPSHOT_FORMAT))
+    }
+}
+
+impl TryFrom<String> for AptSnapshot {
+    type Error = AutoInstallError;
+
+    fn try_from(value: String) -> Result<Self> {
+        Self::parse(&value)
+    }
+}
+
+impl From<AptSnapshot> for String {
+    fn from(snapshot: AptSnapshot) -> Self {
+        snapshot.to_string()
+    }
+}
+
+/// Archive and security URIs, both served from the snapshot tree when pinned
+fn source_uris(snapshot: Option<&AptSnapshot>) -> (String, String) {
+    match snapshot {
+        Some(snapshot) => (snapshot.archive_uri(), snapshot.archive_uri()),
+        None => (
+            "http://archive.ubuntu.com/ubuntu/".to_string(),
+            "http://security.ubuntu.com/ubuntu".to_string(),
+        ),
+    }
+}
+
+/// Deb822 `ubuntu.sources` for `release`, pinned to `snapshot` when given
+pub fn build_deb822_sources(release: &str, snapshot: Option<&AptSnapshot>) -> String {
+    let (archive, security) = source_uris(snapshot);
+    format!(
+        "Types: deb\nURIs: {archive}\nSuites: {rel}\nComponents: main restricted universe multiverse\nSigned-By: /usr/share/keyrings/ubuntu-archive-keyring.gpg\n\nTypes: deb\nURIs: {security}\nSuites: {rel}-security\nComponents: main restricted universe multiverse\nSigned-By: /usr/share/keyrings/ubuntu-archive-keyring.gpg\n",
+        archive = archive,
+        security = security,
+        rel = release
+    )
+}
+
+/// One-line `sources.list` equivalent of [`build_deb822_sources`] for apt older than 1.1
+pub fn build_legacy_sources(release: &str, snapshot: Option<&AptSnapshot>) -> String {
+    let (archive, security) = source_uris(snapshot);
+    format!(
+        "deb {archive} {rel} main restricted universe multiverse\ndeb {security} {rel}-security main restricted universe multiverse\n",
+        archive = archive,
+        security = security,
+        rel = release
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::loader::parse_section;
+
+    #[test]
+    fn test_parse_formats_normalize() {
+        let compact = AptSnapshot::parse("20250301T120000Z").unwrap();
+        let rfc3339 = AptSnapshot::parse("2025-03-01T13:00:00+01:00").unwrap();
+        assert_eq!(compact, rfc3339);
+        assert_eq!(compact.to_string(), "20250301T120000Z");
+        assert_eq!(
+            AptSnapshot::parse("2025-03-01").unwrap().to_string(),
+            "20250301T000000Z"
+        );
+        assert!(AptSnapshot::parse("last tuesday").is_err());
+    }
+
+    #[test]
+    fn test_serde_roundtrip_and_section() {
+        let section: Option<AptSnapshot> = parse_section(
+            "hostname: a\napt_snapshot: \"2025-03-01\"\n",
+            "apt_snapshot",
+        )
+        .unwrap();
+        let snapshot = section.unwrap();
+        assert_eq!(
+            serde_json::to_string(&snapshot).unwrap(),
+            "\"20250301T000000Z\""
+        );
+        assert!(
+            parse_section::<Option<AptSnapshot>>("apt_snapshot: nope\n", "apt_snapshot").is_err()
+        );
+        let empty: Option<AptSnapshot> = parse_section("hostname: a\n", "apt_snapshot").unwrap();
+        assert!(empty.is_none());
+    }
+
+    #[test]
+    fn test_sources_pinned_to_snapshot() {
+        let snapshot = AptSnapshot::parse("20250301T000000Z").unwrap();
+        let pinned = build_deb822_sources("noble", Some(&snapshot));
+        assert!(pinned.contains(
+            "URIs: https://snapshot.ubuntu.com/ubuntu/20250301T000000Z/\nSuites: noble\n"
+        ));
+        assert!(pinned.contains("Suites: noble-security\n"));
+        assert!(!pinned.contains("archive.ubuntu.com"));
+        assert!(!pinned.contains("security.ubuntu.com"));
+
+        let live = build_deb822_sources("noble", None);
+        assert!(live.contains("URIs: http://archive.ubuntu.com/ubuntu/"));
+        assert!(live.contains("URIs: http://security.ubuntu.com/ubuntu"));
+
+        let legacy = build_legacy_sources("noble", Some(&snapshot));
+        assert_eq!(
+            legacy.lines().next(),
+            Some("deb https://snapshot.ubuntu.com/ubuntu/20250301T000000Z/ noble main restricted universe multiverse")
+        );
+        assert!(legacy.contains(" noble-security main "));
+    }
+}
@@ -0,0 +1,323 @@
+// file: src/config/batch.rs
+// version: 1.1.0
+// guid: 9e4c2a71-3b58-4d06-8f1a-6c7d0b2e5f93
+
+//! Validation of every config file in a directory tree (`validate-config --dir`)
+//!
+//! Each `*.yaml`/`*.yml` file is classified by its top-level keys as a target config
+//! (`hostname`), an image spec (`ubuntu_version` with `vm_config`), a fleet inventory
+//! (`hosts`) or a pipeline (`stages`) and loaded the same way the command using it would load it. Other YAML files are
+//! skipped. Files whose `${VAR}` references are not set in the environment, or that use
+//! `{{ facts.* }}` templates, cannot be checked without a target; they are skipped with the
+//! reason unless `strict` counts them as failures. Hidden directories are not searched.
+
+use super::loader::ConfigLoader;
+use crate::error::AutoInstallError;
+use crate::Result;
+use serde::Serialize;
+use std::path::{Path, PathBuf};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ConfigKind {
+    Target,
+    ImageSpec,
+    Inventory,
+    Pipeline,
+    Unknown,
+}
+
+impl ConfigKind {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Self::Target => "target",
+            Self::ImageSpec => "image_spec",
+            Self::Inventory => "inventory",
+            Self::Pipeline => "pipeline",
+            Self::Unknown => "unknown",
+        }
+    }
+
+    /// Kind of a parsed YAML document, from its top-level keys
+    pub fn detect(document: &serde_yaml::Value) -> Self {
+        let Some(map) = document.as_mapping() else {
+            return Self::Unknown;
+        };
+        let has = |key: &str| map.contains_key(serde_yaml::Value::from(key));
+        if has("ubuntu_version") && has("vm_config") {
+            Self::ImageSpec
+        } else if has("hostname") {
+            Self::Target
+        } else if has("hosts") {
+            Self::Inventory
+        } else if has("stages") {
+            Self::Pipeline
+        } else {
+            Self::Unknown
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum FileStatus {
+    Passed,
+    Failed,
+    Skipped,
+}
+
+/// Result for one file
+#[derive(Debug, Clone, Serialize)]
+pub struct FileResult {
+    pub path: PathBuf,
+    pub kind: ConfigKind,
+    pub status: FileStatus,
+    /// Error for a failed file, reason for a skipped one
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub message: Option<String>,
+}
+
+/// Results for a directory tree
+#[derive(Debug, Clone, Serialize)]
+pub struct BatchReport {
+    pub root: PathBuf,
+    pub strict: bool,
+    pub files: Vec<FileResult>,
+}
+
+impl BatchReport {
+    pub fn count(&self, status: FileStatus) -> usize {
+        self.files.iter().filter(|f| f.status == status).count()
+    }
+
+    /// Whether the tree should fail a merge check
+    pub fn is_failure(&self) -> bool {
+        self.count(FileStatus::Failed) > 0 || (self.strict && self.count(FileStatus::Skipped) > 0)
+    }
+
+    pub fn summary(&self) -> String {
+        format!(
+            "{} files: {} passed, {} failed, {} skipped",
+            self.files.len(),
+            self.count(FileStatus::Passed),
+            self.count(FileStatus::Failed),
+            self.count(FileStatus::Skipped)
+        )
+    }
+
+    pub fn to_json(&self) -> Result<String> {
+        Ok(serde_json::to_string_pretty(self)?)
+    }
+
+    pub fn render_text(&self) -> String {
+        let mut out = String::new();
+        for file in &self.files {
+            let label = match file.status {
+                FileStatus::Passed => "ok  ",
+                FileStatus::Failed => "FAIL",
+                FileStatus::Skipped => "skip",
+            };
+            out.push_str(&format!(
+                "{} {} ({})",
+                label,
+                file.path.display(),
+                file.kind.as_str()
+            ));
+            if let Some(message) = &file.message {
+                out.push_str(&format!(": {}", message));
+            }
+            out.push('\n');
+        }
+        out.push_str(&self.summary());
+        out.push('\n');
+        out
+    }
+
+    /// JUnit XML with one test case per file; skipped files are failures when `strict`
+    pub fn render_junit(&self) -> String {
+        let failures = self.count(FileStatus::Failed)
+            + if self.strict {
+                self.count(FileStatus::Skipped)
+            } else {
+                0
+            };
+        let skipped = if self.strict {
+            0
+        } else {
+            self.count(FileStatus::Skipped)
+        };
+        let mut out = String::from("<?xml version=\"1.0\" encoding=\"UTF-8\"?>\n");
+        out.push_str(&format!(
+            "<testsuites name=\"validate-config\" tests=\"{n}\" failures=\"{f}\" skipped=\"{s}\">\n  <testsuite name=\"{root}\" tests=\"{n}\" failures=\"{f}\" skipped=\"{s}\">\n",
+            n = self.files.len(),
+            f = failures,
+            s = skipped,
+            root = xml_escape(&self.root.display().to_string())
+        ));
+        for file in &self.files {
+            let name = xml_escape(&file.path.display().to_string());
+            let message = xml_escape(file.message.as_deref().unwrap_or(""));
+            out.push_str(&format!(
+                "    <testcase classname=\"{}\" name=\"{}\"",
+                file.kind.as_str(),
+                name
+            ));
+            match (file.status, self.strict) {
+                (FileStatus::Passed, _) => out.push_str("/>\n"),
+                (FileStatus::Failed, _) | (FileStatus::Skipped, true) => out.push_str(&format!(
+                    ">\n      <failure message=\"{m}\">{m}</failure>\n    </testcase>\n",
+                    m = message
+                )),
+                (FileStatus::Skipped, false) => out.push_str(&format!(
+                    ">\n      <skipped message=\"{}\"/>\n    </testcase>\n",
+                    message
+                )),
+            }
+        }
+        out.push_str("  </testsuite>\n</testsuites>\n");
+        out
+    }
+}
+
+/// Validate every config file below `root`
+pub fn validate_tree(loader: &ConfigLoader, root: &Path, strict: bool) -> Result<BatchReport> {
+    if !root.is_dir() {
+        return Err(AutoInstallError::ConfigError(format!(
+            "{} is not a directory",
+            root.display()
+        )));
+    }
+    let mut paths = Vec::new();
+    collect_yaml(root, &mut paths)?;
+    paths.sort();
+    let files = paths
+        .into_iter()
+        .map(|path| validate_file(loader, &path))
+        .collect();
+    Ok(BatchReport {
+        root: root.to_path_buf(),
+        strict,
+        files,
+    })
+}
+
+fn collect_yaml(dir: &Path, paths: &mut Vec<PathBuf>) -> Result<()> {
+    for entry in std::fs::read_dir(dir)? {
+        let entry = entry?;
+        let path = entry.path();
+        if entry.file_name().to_string_lossy().starts_with('.') {
+            continue;
+        }
+        if entry.file_type()?.is_dir() {
+            collect_yaml(&path, paths)?;
+        } else if matches!(
+            path.extension().and_then(|e| e.to_str()),
+            Some("yaml" | "yml")
+        ) {
+            paths.push(path);
+        }
+    }
+    Ok(())
+}
+
+/// Classify and load one file
+pub fn validate_file(loader: &ConfigLoader, path: &Path) -> FileResult {
+    let result = |kind, status, message: Option<String>| FileResult {
+        path: path.to_path_buf(),
+        kind,
+        status,
+        message,
+    };
+    let content = match std::fs::read_to_string(path) {
+        Ok(content) => content,
+        Err(e) => return result(ConfigKind::Unknown, FileStatus::Failed, Some(e.to_string())),
+    };
+    let document: serde_yaml::Value = match serde_yaml::from_str(&content) {
+        Ok(document) => document,
+        Err(e) => {
+            return result(
+                ConfigKind::Unknown,
+                FileStatus::Failed,
+                Some(format!("YAML error: {}", e)),
+            )
+        }
+    };
+    let kind = ConfigKind::detect(&document);
+    if kind == ConfigKind::Unknown {
+        return result(
+            kind,
+            FileStatus::Skipped,
+            Some("not a target config, image spec, inventory or pipeline".to_string()),
+        );
+    }
+    if let Err(e) = loader.check_required_env_vars(&content) {
+        return result(kind, FileStatus::Skipped, Some(e.to_string()));
+    }
+    if content.contains("{{") && content.contains("facts.") {
+        return result(
+            kind,
+            FileStatus::Skipped,
+            Some(
+                "uses {{ facts.* }} templates; checked when the target is investigated".to_string(),
+            ),
+        );
+    }
+    let loaded = match kind {
+        ConfigKind::Target => loader.load_target_config(path).map(drop),
+        ConfigKind::ImageSpec => loader.load_image_spec(path).map(drop),
+        ConfigKind::Inventory => loader.load_inventory(path).map(drop),
+        ConfigKind::Pipeline => loader.load_pipeline(path).map(drop),
+        ConfigKind::Unknown => Ok(()),
+    };
+    match loaded {
+        Ok(()) => result(kind, FileStatus::Passed, None),
+        Err(e) => result(kind, FileStatus::Failed, Some(e.to_string())),
+    }
+}
+
+fn xml_escape(s: &str) -> String {
+    s.replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+        .replace('"', "&quot;")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_validate_tree_and_junit() {
+        let dir = tempfile::tempdir().unwrap();
+        let root = dir.path();
+        std::fs::create_dir_all(root.join("hosts")).unwrap();
+        std::fs::create_dir_all(root.join(".github")).unwrap();
+        std::fs::write(root.join(".github/ci.yml"), "on: push\n").unwrap();
+        std::fs::write(root.join("hosts/broken.yaml"), "hostname: [\n").unwrap();
+        std::fs::write(
+            root.join("hosts/secret.yaml"),
+            "hostname: web-01\nluks_key: ${UAA_BATCH_TEST_UNSET}\n",
+        )
+        .unwrap();
+        std::fs::write(root.join("notes.yml"), "owner: infra\n").unwrap();
+
+        let report = validate_tree(&ConfigLoader::new(), root, false).unwrap();
+        assert_eq!(report.files.len(), 3);
+        assert_eq!(report.count(FileStatus::Failed), 1);
+        assert_eq!(report.count(FileStatus::Skipped), 2);
+        assert!(report.is_failure());
+        assert_eq!(report.files[0].kind, ConfigKind::Unknown);
+        assert!(report.files[1]
+            .message
+            .as_deref()
+            .unwrap()
+            .contains("UAA_BATCH_TEST_UNSET"));
+
+        let junit = report.render_junit();
+        assert!(junit.contains("tests=\"3\" failures=\"1\" skipped=\"2\""));
+        assert!(junit.contains("<failure message=\"YAML error"));
+        assert!(report
+            .render_text()
+            .ends_with("3 files: 0 passed, 1 failed, 2 skipped\n"));
+    }
+}
@@ -0,0 +1,82 @@
+// file: src/config/bmc.rs
+// version: 1.1.0
+// guid: 7d1e4a92-3b58-4c06-a8f7-e2c9b5d0f614
+
+//! Baseboard management controller access (`bmc:` section of a target config)
+//!
+//! When present, the BMC's Redfish API is queried for the asset details the host OS cannot
+//! see reliably (chassis serial, firmware versions, DIMM slots, power supply health).
+
+use serde::{Deserialize, Serialize};
+
+/// Redfish endpoint and credentials of a target's BMC
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct BmcConfig {
+    /// BMC address, optionally with scheme and port (`https://` is assumed)
+    pub host: String,
+    pub username: String,
+    /// Password (supports environment variable substitution, e.g. `${BMC_PASSWORD}`)
+    pub password: String,
+    /// Accept self-signed BMC certificates
+    #[serde(default)]
+    pub insecure_tls: bool,
+}
+
+impl BmcConfig {
+    /// Base URL of the Redfish service, without a trailing slash
+    pub fn base_url(&self) -> String {
+        let host = self.host.trim_end_matches('/');
+        if host.starts_with("http://") || host.starts_with("https://") {
+            host.to_string()
+        } else {
+            format!("https://{}", host)
+        }
+    }
+
+    /// Check that the endpoint and credentials are filled in
+    pub fn validate(&self) -> crate::Result<()> {
+        for (field, value) in [
+            ("host", &self.host),
+            ("username", &self.username),
+            ("password", &self.password),
+        ] {
+            if value.trim().is_empty() {
+                return Err(crate::error::AutoInstallError::ValidationError(format!(
+                    "bmc.{} must not be empty",
+                    field
+                )));
+            }
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::loader::parse_section;
+
+    #[test]
+    fn test_bmc_section_and_base_url() {
+        let section: Option<BmcConfig> = parse_section(
+            "bmc:\n  host: 10.0.0.50\n  username: admin\n  password: secret\n",
+            "bmc",
+        )
+        .unwrap();
+        let bmc = section.unwrap();
+        assert!(!bmc.insecure_tls);
+        assert_eq!(bmc.base_url(), "https://10.0.0.50");
+        assert!(bmc.validate().is_ok());
+
+        let plain = BmcConfig {
+            host: "http://bmc.lab:8000/".into(),
+            password: " ".into(),
+            ..bmc
+        };
+        assert_eq!(plain.base_url(), "http://bmc.lab:8000");
+        assert!(plain.validate().is_err());
+
+        let section: Option<BmcConfig> = parse_section("hostname: a\n", "bmc").unwrap();
+        assert!(section.is_none());
+    }
+}
@@ -0,0 +1,214 @@
+// file: src/config/bootloader.rs
+// version: 1.2.0
+// guid: 9a4d2f61-3c8e-4b75-a0d9-6e1f7b2c8d34
+
+//! Bootloader of the installed system (`bootloader:` section of a target config)
+//!
+//! GRUB is the default. `kind: systemd-boot` installs systemd-boot to the ESP instead; it cannot
+//! read ZFS, so kernel-install copies every kernel and initramfs to the ESP and writes a loader
+//! entry with the command line from `/etc/kernel/cmdline`. The systemd-boot package's kernel
+//! and initramfs hooks keep the ESP copies current after the install. The GRUB and shim
+//! packages are left out, so systemd-boot hosts need Secure Boot off, and the ESP must hold
+//! the kernels being kept.
+
+use super::packages::PackageRole;
+use crate::error::AutoInstallError;
+use crate::Result;
+use serde::{Deserialize, Serialize};
+
+/// Directory of the loader entries on the ESP, relative to its mountpoint
+pub const LOADER_ENTRIES_DIR: &str = "loader/entries";
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum BootloaderKind {
+    #[default]
+    Grub,
+    SystemdBoot,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct BootloaderConfig {
+    pub kind: BootloaderKind,
+    /// Seconds systemd-boot shows its menu
+    pub timeout_seconds: u32,
+    /// Kernel parameters added after the ZFS root and console parameters (systemd-boot only;
+    /// GRUB hosts use `/etc/default/grub.d`)
+    pub cmdline: Vec<String>,
+}
+
+impl Default for BootloaderConfig {
+    fn default() -> Self {
+        Self {
+            kind: BootloaderKind::Grub,
+            timeout_seconds: 3,
+            cmdline: Vec::new(),
+        }
+    }
+}
+
+impl BootloaderConfig {
+    pub fn is_systemd_boot(&self) -> bool {
+        self.kind == BootloaderKind::SystemdBoot
+    }
+
+    pub fn validate(&self) -> Result<()> {
+        if let Some(bad) = self.cmdline.iter().find(|arg| {
+            arg.is_empty() || arg.contains(|c: char| c.is_whitespace() || "'\"".contains(c))
+        }) {
+            return Err(AutoInstallError::ValidationError(format!(
+                "bootloader.cmdline entry {:?} must be one parameter without spaces or quotes",
+                bad
+            )));
+        }
+        Ok(())
+    }
+
+    /// Package roles the base system installs; systemd-boot hosts get no GRUB or shim
+    pub fn package_roles(&self) -> Vec<PackageRole> {
+        PackageRole::BASE_SYSTEM
+            .iter()
+            .copied()
+            .filter(|role| {
+                !self.is_systemd_boot()
+                    || !matches!(
+                        role,
+                        PackageRole::Bootloader | PackageRole::SignedBootloader | PackageRole::Shim
+                    )
+            })
+            .collect()
+    }
+
+    /// Commands installing systemd-boot into the system at `root` with its ESP at `root/esp`
+    ///
+    /// `kernel_args` come from other sections (serial console, initramfs network) and go
+    /// between the root parameters and `cmdline`. The root dataset is read from the mount, so
+    /// the command line always names the dataset that was just installed.
+    pub fn build_systemd_boot_commands(
+        &self,
+        root: &str,
+        esp: &str,
+        kernel_args: &[String],
+    ) -> Vec<String> {
+        let root = root.trim_end_matches('/');
+        let args: Vec<&str> = kernel_args
+            .iter()
+            .chain(&self.cmdline)
+            .map(String::as_str)
+            .collect();
+        let extra = if args.is_empty() {
+            String::new()
+        } else {
+            format!(" {}", args.join(" "))
+        };
+        vec![
+            format!(
+                "chroot {} bash -lc 'DEBIAN_FRONTEND=noninteractive apt install -y systemd-boot'",
+                root
+            ),
+            format!(
+                "bash -lc 'mkdir -p {r}/etc/kernel && echo \"root=ZFS=$(findmnt -n -o SOURCE {r}) ro{x}\" > {r}/etc/kernel/cmdline'",
+                r = root,
+                x = extra
+            ),
+            // Like grub-install --no-nvram: firmware that refuses variable writes still boots
+            // the removable-media path
+            format!(
+                "chroot {r} bash -lc 'bootctl install --esp-path={e} || bootctl install --esp-path={e} --no-variables'",
+                r = root,
+                e = esp
+            ),
+            format!(
+                "cat > {}{}/loader/loader.conf << 'EOF'\ndefault @saved\ntimeout {}\nconsole-mode keep\neditor no\nEOF",
+                root, esp, self.timeout_seconds
+            ),
+            format!(
+                "chroot {} bash -lc 'for k in /boot/vmlinuz-*; do kernel-install add \"${{k#/boot/vmlinuz-}}\" \"$k\"; done'",
+                root
+            ),
+        ]
+    }
+}
+
+/// Number of usable loader entries in the concatenated `*.conf` files of `loader/entries`
+///
+/// An entry is usable when it has a `linux` line and its `options` name a ZFS root.
+pub fn check_loader_entries(entries: &str) -> Result<usize> {
+    let mut usable = 0;
+    let mut problems = Vec::new();
+    for entry in entries.split("\ntitle ").filter(|e| !e.trim().is_empty()) {
+        let title = entry
+            .trim_start_matches("title ")
+            .lines()
+            .next()
+            .unwrap_or("")
+            .trim();
+        let has_linux = entry.lines().any(|l| l.trim_start().starts_with("linux "));
+        let zfs_root = entry
+            .lines()
+            .any(|l| l.trim_start().starts_with("options ") && l.contains("root=ZFS="));
+        match (has_linux, zfs_root) {
+            (true, true) => usable += 1,
+            (false, _) => problems.push(format!("{}: no linux line", title)),
+            (true, false) => problems.push(format!("{}: options lack root=ZFS=", title)),
+        }
+    }
+    if usable == 0 {
+        return Err(AutoInstallError::InstallationError(format!(
+            "No usable systemd-boot loader entry{}",
+            if problems.is_empty() {
+                String::new()
+            } else {
+                format!(" ({})", problems.join("; "))
+            }
+        )));
+    }
+    Ok(usable)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_systemd_boot_commands_and_packages() {
+        let config: BootloaderConfig = serde_yaml::from_str(
+            "kind: systemd-boot\ntimeout_seconds: 5\ncmdline: [init_on_alloc=0]\n",
+        )
+        .unwrap();
+        config.validate().unwrap();
+        assert!(!config.package_roles().contains(&PackageRole::Shim));
+        assert!(BootloaderConfig::default()
+            .package_roles()
+            .contains(&PackageRole::Bootloader));
+
+        let cmds = config.build_systemd_boot_commands(
+            "/mnt/targetos/",
+            "/boot/efi",
+            &["console=ttyS1,115200n8".to_string()],
+        );
+        assert!(cmds[1].contains(
+            "root=ZFS=$(findmnt -n -o SOURCE /mnt/targetos) ro console=ttyS1,115200n8 init_on_alloc=0\" > /mnt/targetos/etc/kernel/cmdline"
+        ));
+        assert!(cmds[2].contains("--no-variables"));
+        assert!(cmds[3].starts_with("cat > /mnt/targetos/boot/efi/loader/loader.conf"));
+        assert!(cmds[3].contains("timeout 5\n"));
+
+        let bad = BootloaderConfig {
+            cmdline: vec!["quiet splash".to_string()],
+            ..config
+        };
+        assert!(bad.validate().is_err());
+    }
+
+    #[test]
+    fn test_check_loader_entries() {
+        let entries = "title Ubuntu 25.04\nversion 6.14.0-15-generic\nlinux /e3b1/6.14.0-15-generic/linux\ninitrd /e3b1/6.14.0-15-generic/initrd\noptions root=ZFS=rpool/ROOT/ubuntu_ab12 ro\n\
+                       title Ubuntu 25.04\nversion 6.14.0-10-generic\nlinux /e3b1/6.14.0-10-generic/linux\noptions ro\n";
+        assert_eq!(check_loader_entries(entries).unwrap(), 1);
+        let err = check_loader_entries("title Ubuntu\noptions ro\n").unwrap_err();
+        assert!(err.to_string().contains("Ubuntu: no linux line"));
+        assert!(check_loader_entries("").is_err());
+    }
+}
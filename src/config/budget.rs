@@ -0,0 +1,71 @@
+// file: src/config/budget.rs
+// version: 1.1.0
+// guid: 4d8b2e61-9c37-4a05-b6f1-e7a3c0d95b28
+
+//! Wall-clock budget for an installation (`budget:` section of a target config)
+//!
+//! Once `total_minutes` have passed since the install started, no further phase is started.
+//! The phase running at that point may go on for `grace_minutes`; after that the remaining
+//! commands of the phase are refused, so it stops between two commands rather than in the
+//! middle of one. The install then holds the session open with the target left as it is.
+
+use crate::error::AutoInstallError;
+use crate::Result;
+use serde::{Deserialize, Serialize};
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct BudgetConfig {
+    /// Minutes the whole install may take; unset means no limit
+    pub total_minutes: Option<u64>,
+    /// Minutes the phase running when the budget runs out may still take
+    pub grace_minutes: u64,
+}
+
+impl Default for BudgetConfig {
+    fn default() -> Self {
+        Self {
+            total_minutes: None,
+            grace_minutes: 10,
+        }
+    }
+}
+
+impl BudgetConfig {
+    /// Time after which no new phase starts
+    pub fn total(&self) -> Option<chrono::Duration> {
+        self.total_minutes
+            .map(|minutes| chrono::Duration::minutes(minutes as i64))
+    }
+
+    /// Time after which no new command starts
+    pub fn hard_limit(&self) -> Option<chrono::Duration> {
+        self.total()
+            .map(|total| total + chrono::Duration::minutes(self.grace_minutes as i64))
+    }
+
+    pub fn validate(&self) -> Result<()> {
+        if self.total_minutes == Some(0) {
+            return Err(AutoInstallError::ValidationError(
+                "budget total_minutes must be at least 1".to_string(),
+            ));
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::loader::parse_section;
+
+    #[test]
+    fn test_total_and_hard_limit() {
+        let config =
+            parse_section::<BudgetConfig>("budget:\n  total_minutes: 90\n", "budget").unwrap();
+        assert_eq!(config.total(), Some(chrono::Duration::minutes(90)));
+        assert_eq!(config.hard_limit(), Some(chrono::Duration::minutes(100)));
+        assert!(config.validate().is_ok());
+        assert_eq!(BudgetConfig::default().total(), None);
+    }
+}
@@ -0,0 +1,302 @@
+// file: src/config/build_hooks.rs
+// version: 1.1.0
+// guid: 3d8a5f21-9c64-4b07-8e1f-6a2c7b9d0e54
+
+//! Provisioner hooks of an image build (`hooks:` section of an image spec)
+//!
+//! Each stage lists provisioners in the shape of Packer's `shell` and `shell-local`
+//! provisioners, so existing Packer snippets can be pasted in unchanged: `inline`, `script` or
+//! `scripts`, `environment_vars`, `execute_command` with `{{ .Vars }}` and `{{ .Path }}`,
+//! `inline_shebang` and `expect_disconnect`. Keys Packer has but the agent does not support
+//! are rejected rather than ignored. `packer_template` imports the provisioners of a Packer
+//! JSON template into `post_install`.
+//!
+//! `pre_boot` runs `shell-local` provisioners on the build host before the installer VM starts.
+//! `post_install` and `pre_generalize` run after the install, with the installed system booted
+//! and reachable over SSH; `shell-local` provisioners in those stages run on the build host
+//! while the VM is up.
+
+use crate::error::AutoInstallError;
+use crate::utils::shell_quote;
+use crate::Result;
+use serde::{Deserialize, Serialize};
+use std::path::PathBuf;
+
+/// Packer's default for inline scripts
+const DEFAULT_SHEBANG: &str = "/bin/sh -e";
+/// Packer's default `execute_command` of the shell provisioner
+const DEFAULT_EXECUTE: &str = "chmod +x {{ .Path }}; {{ .Vars }} {{ .Path }}";
+
+/// Point in the build at which a provisioner runs
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum HookStage {
+    PreBoot,
+    PostInstall,
+    PreGeneralize,
+}
+
+impl HookStage {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            HookStage::PreBoot => "pre-boot",
+            HookStage::PostInstall => "post-install",
+            HookStage::PreGeneralize => "pre-generalize",
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum ProvisionerType {
+    /// Runs in the build VM over SSH
+    #[default]
+    Shell,
+    /// Runs on the build host
+    ShellLocal,
+}
+
+/// One Packer-style shell provisioner
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct ShellProvisioner {
+    #[serde(rename = "type")]
+    pub kind: ProvisionerType,
+    /// Commands run as one script
+    pub inline: Vec<String>,
+    pub script: Option<PathBuf>,
+    pub scripts: Vec<PathBuf>,
+    /// `KEY=value` pairs passed as `{{ .Vars }}`
+    pub environment_vars: Vec<String>,
+    /// Command running each script; `{{ .Vars }}` and `{{ .Path }}` are substituted
+    pub execute_command: Option<String>,
+    pub inline_shebang: Option<String>,
+    /// The script reboots or stops SSH; a dropped connection is not a failure
+    pub expect_disconnect: bool,
+}
+
+/// A script of a provisioner, ready to upload or run
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct HookScript {
+    /// File name the script is stored as
+    pub name: String,
+    pub content: String,
+}
+
+impl ShellProvisioner {
+    pub fn validate(&self, stage: HookStage) -> Result<()> {
+        let sources = [
+            !self.inline.is_empty(),
+            self.script.is_some(),
+            !self.scripts.is_empty(),
+        ];
+        if sources.iter().filter(|s| **s).count() != 1 {
+            return Err(AutoInstallError::ValidationError(format!(
+                "{} hook needs exactly one of inline, script or scripts",
+                stage.as_str()
+            )));
+        }
+        if stage == HookStage::PreBoot && self.kind != ProvisionerType::ShellLocal {
+            return Err(AutoInstallError::ValidationError(
+                "pre-boot hooks run before the VM exists and must be type shell-local".to_string(),
+            ));
+        }
+        if let Some(var) = self.environment_vars.iter().find(|v| !v.contains('=')) {
+            return Err(AutoInstallError::ValidationError(format!(
+                "{} hook environment_vars entry '{}' is not KEY=value",
+                stage.as_str(),
+                var
+            )));
+        }
+        Ok(())
+    }
+
+    /// Scripts in run order
+    pub fn load_scripts(&self) -> Result<Vec<HookScript>> {
+        if !self.inline.is_empty() {
+            let shebang = self.inline_shebang.as_deref().unwrap_or(DEFAULT_SHEBANG);
+            return Ok(vec![HookScript {
+                name: "inline.sh".to_string(),
+                content: format!("#!{}\n{}\n", shebang, self.inline.join("\n")),
+            }]);
+        }
+        self.script
+            .iter()
+            .chain(&self.scripts)
+            .map(|path| {
+                let content = std::fs::read_to_string(path).map_err(|e| {
+                    AutoInstallError::ConfigError(format!(
+                        "Cannot read hook script {}: {}",
+                        path.display(),
+                        e
+                    ))
+                })?;
+                let name = path
+                    .file_name()
+                    .map(|n| n.to_string_lossy().to_string())
+                    .unwrap_or_else(|| "script.sh".to_string());
+                Ok(HookScript { name, content })
+            })
+            .collect()
+    }
+
+    /// Command running the script at `path` with `extra` variables after the configured ones
+    pub fn command(&self, path: &str, extra: &[(&str, String)]) -> String {
+        let vars: Vec<String> = self
+            .environment_vars
+            .iter()
+            .map(|var| {
+                let (key, value) = var.split_once('=').unwrap_or((var, ""));
+                format!("{}={}", key, shell_quote(value))
+            })
+            .chain(
+                extra
+                    .iter()
+                    .map(|(key, value)| format!("{}={}", key, shell_quote(value))),
+            )
+            .collect();
+        let template = self.execute_command.as_deref().unwrap_or(DEFAULT_EXECUTE);
+        let mut command = template.to_string();
+        for (placeholders, value) in [
+            (["{{ .Vars }}", "{{.Vars}}"], vars.join(" ")),
+            (["{{ .Path }}", "{{.Path}}"], path.to_string()),
+        ] {
+            for placeholder in placeholders {
+                command = command.replace(placeholder, &value);
+            }
+        }
+        command.trim().to_string()
+    }
+}
+
+/// Provisioners of each build stage
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct BuildHooks {
+    pub pre_boot: Vec<ShellProvisioner>,
+    pub post_install: Vec<ShellProvisioner>,
+    pub pre_generalize: Vec<ShellProvisioner>,
+    /// Packer JSON template whose provisioners are appended to `post_install`
+    pub packer_template: Option<PathBuf>,
+}
+
+impl BuildHooks {
+    pub fn is_empty(&self) -> bool {
+        self.pre_boot.is_empty()
+            && self.post_install.is_empty()
+            && self.pre_generalize.is_empty()
+            && self.packer_template.is_none()
+    }
+
+    /// Whether the installed system has to be booted for provisioners after the install
+    pub fn needs_vm(&self) -> bool {
+        self.packer_template.is_some()
+            || !self.post_install.is_empty()
+            || !self.pre_generalize.is_empty()
+    }
+
+    pub fn validate(&self) -> Result<()> {
+        for stage in [
+            HookStage::PreBoot,
+            HookStage::PostInstall,
+            HookStage::PreGeneralize,
+        ] {
+            for provisioner in self.stage(stage)? {
+                provisioner.validate(stage)?;
+            }
+        }
+        Ok(())
+    }
+
+    /// Provisioners of `stage`, the Packer template's included
+    pub fn stage(&self, stage: HookStage) -> Result<Vec<ShellProvisioner>> {
+        match stage {
+            HookStage::PreBoot => Ok(self.pre_boot.clone()),
+            HookStage::PreGeneralize => Ok(self.pre_generalize.clone()),
+            HookStage::PostInstall => {
+                let mut provisioners = self.post_install.clone();
+                if let Some(path) = &self.packer_template {
+                    let content = std::fs::read_to_string(path).map_err(|e| {
+                        AutoInstallError::ConfigError(format!(
+                            "Cannot read Packer template {}: {}",
+                            path.display(),
+                            e
+                        ))
+                    })?;
+                    provisioners.extend(packer_provisioners(&content)?);
+                }
+                Ok(provisioners)
+            }
+        }
+    }
+}
+
+/// Shell provisioners of a Packer JSON template; other provisioner types are rejected
+pub fn packer_provisioners(template: &str) -> Result<Vec<ShellProvisioner>> {
+    let template: serde_json::Value = serde_json::from_str(template).map_err(|e| {
+        AutoInstallError::ConfigError(format!(
+            "Packer template is not JSON (HCL templates are not supported): {}",
+            e
+        ))
+    })?;
+    let Some(provisioners) = template.get("provisioners").and_then(|p| p.as_array()) else {
+        return Ok(Vec::new());
+    };
+    provisioners
+        .iter()
+        .map(|provisioner| {
+            let kind = provisioner["type"].as_str().unwrap_or_default();
+            if kind != "shell" && kind != "shell-local" {
+                return Err(AutoInstallError::ValidationError(format!(
+                    "Packer provisioner type '{}' is not supported; only shell and shell-local are",
+                    kind
+                )));
+            }
+            serde_json::from_value(provisioner.clone()).map_err(|e| {
+                AutoInstallError::ValidationError(format!("Packer {} provisioner: {}", kind, e))
+            })
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_packer_shell_provisioners() {
+        let template = r#"{
+            "builders": [{"type": "qemu"}],
+            "provisioners": [
+                {"type": "shell", "inline": ["apt-get update", "apt-get install -y nginx"],
+                 "environment_vars": ["DEBIAN_FRONTEND=noninteractive"],
+                 "execute_command": "echo 'packer' | sudo -S sh -c '{{ .Vars }} {{ .Path }}'"},
+                {"type": "shell-local", "inline": ["echo built"]}
+            ]
+        }"#;
+        let provisioners = packer_provisioners(template).unwrap();
+        assert_eq!(provisioners.len(), 2);
+        assert_eq!(provisioners[1].kind, ProvisionerType::ShellLocal);
+        provisioners[0].validate(HookStage::PostInstall).unwrap();
+        assert!(provisioners[0].validate(HookStage::PreBoot).is_err());
+
+        let scripts = provisioners[0].load_scripts().unwrap();
+        assert_eq!(
+            scripts[0].content,
+            "#!/bin/sh -e\napt-get update\napt-get install -y nginx\n"
+        );
+        assert_eq!(
+            provisioners[0].command("/tmp/uaa-hook-0.sh", &[("UAA_BUILD_STAGE", "post-install".to_string())]),
+            "echo 'packer' | sudo -S sh -c 'DEBIAN_FRONTEND=noninteractive UAA_BUILD_STAGE=post-install /tmp/uaa-hook-0.sh'"
+        );
+        assert_eq!(
+            provisioners[1].command("/tmp/x.sh", &[]),
+            "chmod +x /tmp/x.sh;  /tmp/x.sh"
+        );
+
+        assert!(packer_provisioners(r#"{"provisioners": [{"type": "ansible"}]}"#).is_err());
+        assert!(packer_provisioners(
+            r#"{"provisioners": [{"type": "shell", "inline": ["true"], "only": ["qemu"]}]}"#
+        )
+        .is_err());
+    }
+}
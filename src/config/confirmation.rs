@@ -0,0 +1,163 @@
+// file: src/config/confirmation.rs
+// version: 1.1.0
+// guid: 6c1f8e24-9a57-4d3b-b0e6-2d7a4c9f1e83
+
+//! Confirmation gates between install phases (`confirmation:` section of a target config)
+//!
+//! The install stops before every phase listed in `pause_before`, shows what that phase is about
+//! to do and waits until someone approves it: at the terminal, with `approve`, or through the
+//! `approval_url` endpoint.
+
+use crate::error::AutoInstallError;
+use crate::Result;
+use serde::{Deserialize, Serialize};
+
+/// Install phases a gate can stop before
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash, Serialize, Deserialize)]
+pub enum GatePhase {
+    #[serde(rename = "phase_0")]
+    Phase0,
+    #[serde(rename = "phase_1")]
+    Phase1,
+    #[serde(rename = "phase_2")]
+    Phase2,
+    #[serde(rename = "phase_3")]
+    Phase3,
+    #[serde(rename = "phase_4")]
+    Phase4,
+    #[serde(rename = "phase_5")]
+    Phase5,
+    #[serde(rename = "phase_6")]
+    Phase6,
+}
+
+impl GatePhase {
+    const ALL: [GatePhase; 7] = [
+        Self::Phase0,
+        Self::Phase1,
+        Self::Phase2,
+        Self::Phase3,
+        Self::Phase4,
+        Self::Phase5,
+        Self::Phase6,
+    ];
+
+    pub fn number(&self) -> u8 {
+        *self as u8
+    }
+
+    /// `phase_2`
+    pub fn id(&self) -> String {
+        format!("phase_{}", self.number())
+    }
+
+    /// Gate of the phase named like `Phase 2: Disk preparation`
+    pub fn from_label(label: &str) -> Option<Self> {
+        let number: u8 = label
+            .strip_prefix("Phase ")?
+            .split(':')
+            .next()?
+            .trim()
+            .parse()
+            .ok()?;
+        Self::ALL.get(number as usize).copied()
+    }
+}
+
+impl std::str::FromStr for GatePhase {
+    type Err = AutoInstallError;
+
+    /// `phase_2`, `2` or `Phase 2`
+    fn from_str(s: &str) -> Result<Self> {
+        let number = s
+            .trim()
+            .trim_start_matches("phase_")
+            .trim_start_matches("Phase ");
+        number
+            .parse::<usize>()
+            .ok()
+            .and_then(|n| Self::ALL.get(n).copied())
+            .ok_or_else(|| {
+                AutoInstallError::ValidationError(format!(
+                    "Unknown phase '{}': use phase_0 to phase_6",
+                    s
+                ))
+            })
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct ConfirmationConfig {
+    /// Phases to stop before until approved
+    pub pause_before: Vec<GatePhase>,
+    /// Give up and cancel the install after this many seconds; 0 waits indefinitely
+    pub timeout_secs: u64,
+    /// Polled with GET while waiting; `{hostname}` and `{phase}` are filled in. A 200 answer
+    /// with `{"approved": true}` approves, `{"approved": false, "reason": ...}` rejects, and
+    /// anything else keeps waiting.
+    pub approval_url: Option<String>,
+    pub poll_secs: u64,
+}
+
+impl Default for ConfirmationConfig {
+    fn default() -> Self {
+        Self {
+            pause_before: Vec::new(),
+            timeout_secs: 0,
+            approval_url: None,
+            poll_secs: 10,
+        }
+    }
+}
+
+impl ConfirmationConfig {
+    /// Whether the install stops before `phase`
+    pub fn gates(&self, phase: GatePhase) -> bool {
+        self.pause_before.contains(&phase)
+    }
+
+    pub fn validate(&self) -> Result<()> {
+        if self.poll_secs == 0 {
+            return Err(AutoInstallError::ValidationError(
+                "confirmation poll_secs must be at least 1".to_string(),
+            ));
+        }
+        if let Some(url) = &self.approval_url {
+            if !url.starts_with("http://") && !url.starts_with("https://") {
+                return Err(AutoInstallError::ValidationError(format!(
+                    "confirmation approval_url must start with http:// or https://: {}",
+                    url
+                )));
+            }
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::loader::parse_section;
+
+    #[test]
+    fn test_gates_and_phase_labels() {
+        let config = parse_section::<ConfirmationConfig>(
+            "confirmation:\n  pause_before: [phase_2, phase_6]\n",
+            "confirmation",
+        )
+        .unwrap();
+        assert!(config.gates(GatePhase::Phase2));
+        assert!(!config.gates(GatePhase::Phase4));
+        assert!(config.validate().is_ok());
+
+        assert_eq!(
+            GatePhase::from_label("Phase 6: Final setup"),
+            Some(GatePhase::Phase6)
+        );
+        assert_eq!(GatePhase::from_label("Capability check"), None);
+        assert_eq!("2".parse::<GatePhase>().unwrap(), GatePhase::Phase2);
+        assert_eq!(GatePhase::Phase2.id(), "phase_2");
+        assert!("phase_9".parse::<GatePhase>().is_err());
+    }
+}
@@ -0,0 +1,215 @@
+// file: src/config/dhcp.rs
+// version: 1.0.0
+// guid: 3c7f1a94-5e28-4d6b-8b03-9a2e6f4c1d75
+
+//! Embedded DHCP server for provisioning networks (`dhcp:` section of an inventory)
+//!
+//! Only inventory hosts with a `mac` get an address: their `host`, which must be an IPv4
+//! address inside the served subnet. Every other client is ignored, so the server can run on a
+//! VLAN shared with machines that are not being provisioned.
+//!
+//! ```yaml
+//! dhcp:
+//!   interface: eno2
+//!   server_ip: 10.20.0.1
+//!   netmask: 255.255.255.0
+//!   router: 10.20.0.1
+//!   dns_servers: [10.20.0.1]
+//!   boot_file: pxelinux.0
+//!   boot_file_uefi: grubnetx64.efi.signed
+//! hosts:
+//!   - hostname: web-01
+//!     host: 10.20.0.11
+//!     mac: "52:54:00:12:34:56"
+//! ```
+
+use crate::config::inventory::InventoryHost;
+use crate::error::AutoInstallError;
+use crate::Result;
+use serde::{Deserialize, Serialize};
+use std::collections::HashSet;
+use std::net::Ipv4Addr;
+
+fn default_netmask() -> Ipv4Addr {
+    Ipv4Addr::new(255, 255, 255, 0)
+}
+
+fn default_lease_secs() -> u32 {
+    3600
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct DhcpConfig {
+    /// Interface the server listens on; requests arriving elsewhere are never answered
+    pub interface: String,
+    /// Address of this machine on the provisioning network, sent as the server identifier
+    pub server_ip: Ipv4Addr,
+    #[serde(default = "default_netmask")]
+    pub netmask: Ipv4Addr,
+    #[serde(default)]
+    pub router: Option<Ipv4Addr>,
+    #[serde(default)]
+    pub dns_servers: Vec<Ipv4Addr>,
+    #[serde(default)]
+    pub domain: Option<String>,
+    #[serde(default = "default_lease_secs")]
+    pub lease_secs: u32,
+    /// TFTP server for network boot; `server_ip` when a boot file is set
+    #[serde(default)]
+    pub next_server: Option<Ipv4Addr>,
+    /// Boot file offered to BIOS PXE clients
+    #[serde(default)]
+    pub boot_file: Option<String>,
+    /// Boot file offered to UEFI clients; `boot_file` when unset
+    #[serde(default)]
+    pub boot_file_uefi: Option<String>,
+}
+
+impl DhcpConfig {
+    pub fn validate(&self) -> Result<()> {
+        if self.interface.trim().is_empty() {
+            return Err(AutoInstallError::ValidationError(
+                "dhcp.interface must not be empty".to_string(),
+            ));
+        }
+        let mask = u32::from(self.netmask);
+        if mask == 0 || mask.leading_ones() + mask.trailing_zeros() != 32 {
+            return Err(AutoInstallError::ValidationError(format!(
+                "dhcp.netmask {} is not a contiguous subnet mask",
+                self.netmask
+            )));
+        }
+        if let Some(router) = self.router {
+            if !self.contains(router) {
+                return Err(AutoInstallError::ValidationError(format!(
+                    "dhcp.router {} is outside {}/{}",
+                    router, self.server_ip, self.netmask
+                )));
+            }
+        }
+        if self.lease_secs < 60 {
+            return Err(AutoInstallError::ValidationError(
+                "dhcp.lease_secs must be at least 60".to_string(),
+            ));
+        }
+        Ok(())
+    }
+
+    /// Whether `ip` is in the subnet of `server_ip`
+    pub fn contains(&self, ip: Ipv4Addr) -> bool {
+        let mask = u32::from(self.netmask);
+        u32::from(ip) & mask == u32::from(self.server_ip) & mask
+    }
+
+    /// TFTP server sent to network-booting clients
+    pub fn tftp_server(&self) -> Option<Ipv4Addr> {
+        if self.boot_file.is_some() || self.boot_file_uefi.is_some() {
+            Some(self.next_server.unwrap_or(self.server_ip))
+        } else {
+            self.next_server
+        }
+    }
+
+    /// The fixed address of every inventory host with a `mac`
+    pub fn static_leases(&self, hosts: &[InventoryHost]) -> Result<Vec<StaticLease>> {
+        let mut macs = HashSet::new();
+        let mut ips = HashSet::new();
+        let mut leases = Vec::new();
+        for host in hosts {
+            let Some(mac) = &host.mac else { continue };
+            let mac = parse_mac(mac)?;
+            let ip: Ipv4Addr = host.address().parse().map_err(|_| {
+                AutoInstallError::ValidationError(format!(
+                    "{} has a mac but its host '{}' is not an IPv4 address to lease",
+                    host.hostname,
+                    host.address()
+                ))
+            })?;
+            if !self.contains(ip) || ip == self.server_ip {
+                return Err(AutoInstallError::ValidationError(format!(
+                    "{} ({}) is not a free address in {}/{}",
+                    host.hostname, ip, self.server_ip, self.netmask
+                )));
+            }
+            if !macs.insert(mac) || !ips.insert(ip) {
+                return Err(AutoInstallError::ValidationError(format!(
+                    "{} shares its mac or address with another inventory host",
+                    host.hostname
+                )));
+            }
+            leases.push(StaticLease {
+                hostname: host.hostname.clone(),
+                mac,
+                ip,
+            });
+        }
+        Ok(leases)
+    }
+}
+
+/// Address reserved for one inventory host
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct StaticLease {
+    pub hostname: String,
+    pub mac: [u8; 6],
+    pub ip: Ipv4Addr,
+}
+
+/// Parse `aa:bb:cc:dd:ee:ff` (or with `-` separators)
+pub fn parse_mac(mac: &str) -> Result<[u8; 6]> {
+    let parts: Vec<&str> = mac.split([':', '-']).collect();
+    let mut bytes = [0u8; 6];
+    if parts.len() != 6 {
+        return Err(invalid_mac(mac));
+    }
+    for (byte, part) in bytes.iter_mut().zip(&parts) {
+        if part.len() != 2 {
+            return Err(invalid_mac(mac));
+        }
+        *byte = u8::from_str_radix(part, 16).map_err(|_| invalid_mac(mac))?;
+    }
+    Ok(bytes)
+}
+
+/// `aa:bb:cc:dd:ee:ff` form of a hardware address
+pub fn format_mac(mac: &[u8; 6]) -> String {
+    mac.iter()
+        .map(|b| format!("{:02x}", b))
+        .collect::<Vec<_>>()
+        .join(":")
+}
+
+fn invalid_mac(mac: &str) -> AutoInstallError {
+    AutoInstallError::ValidationError(format!("'{}' is not a MAC address", mac))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::FleetInventory;
+
+    #[test]
+    fn test_static_leases_from_inventory() {
+        let inventory: FleetInventory = serde_yaml::from_str(
+            "dhcp:\n  interface: eno2\n  server_ip: 10.20.0.1\n  boot_file: pxelinux.0\nhosts:\n  - hostname: web-01\n    host: 10.20.0.11\n    mac: 52-54-00-AB-CD-EF\n  - hostname: web-02\n",
+        )
+        .unwrap();
+        assert!(inventory.validate().is_ok());
+        let dhcp = inventory.dhcp.as_ref().unwrap();
+        assert_eq!(dhcp.tftp_server(), Some(Ipv4Addr::new(10, 20, 0, 1)));
+        let leases = dhcp.static_leases(&inventory.hosts).unwrap();
+        assert_eq!(leases.len(), 1);
+        assert_eq!(format_mac(&leases[0].mac), "52:54:00:ab:cd:ef");
+        assert_eq!(leases[0].ip, Ipv4Addr::new(10, 20, 0, 11));
+
+        let mut outside = inventory.clone();
+        outside.hosts[0].host = Some("10.30.0.11".to_string());
+        assert!(outside.validate().is_err());
+        assert!(parse_mac("52:54:00:ab:cd").is_err());
+        let bad_mask = DhcpConfig {
+            netmask: Ipv4Addr::new(255, 0, 255, 0),
+            ..dhcp.clone()
+        };
+        assert!(bad_mask.validate().is_err());
+    }
+}
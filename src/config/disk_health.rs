@@ -0,0 +1,117 @@
+// file: src/config/disk_health.rs
+// version: 1.1.0
+// guid: 9c4e7a21-5b38-4d6f-a1e9-2f8d3b6c0e54
+
+//! Health limits for the install disk (`disk_health:` section of a target config)
+//!
+//! Preflight reads SMART data (smartctl, or nvme-cli for NVMe drives) from the install disk
+//! and compares it against these limits. Crossing a `warn` limit is recorded as a warning;
+//! crossing a `fail` limit stops the install before the disk is touched, unless
+//! `enforce: false` turns failures into warnings too.
+
+use crate::error::AutoInstallError;
+use crate::Result;
+use serde::{Deserialize, Serialize};
+
+/// Warning and failure limits for one counter; a counter above a limit crosses it
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct HealthLimit {
+    pub warn: Option<u64>,
+    pub fail: Option<u64>,
+}
+
+impl HealthLimit {
+    const fn new(warn: u64, fail: u64) -> Self {
+        Self {
+            warn: Some(warn),
+            fail: Some(fail),
+        }
+    }
+}
+
+/// Limits applied to the install disk in preflight
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct DiskHealthConfig {
+    /// Read SMART data in preflight
+    pub check: bool,
+    /// Stop the install when a `fail` limit is crossed; warn only when false
+    pub enforce: bool,
+    /// Fail when the drive's own overall assessment or NVMe critical warning says so
+    pub fail_on_self_assessment: bool,
+    /// Reallocated sectors (ATA attribute 5)
+    pub reallocated_sectors: HealthLimit,
+    /// Sectors waiting to be remapped (ATA attribute 197)
+    pub pending_sectors: HealthLimit,
+    /// Rated endurance used, in percent (NVMe)
+    pub percentage_used: HealthLimit,
+    /// Unrecovered media and data integrity errors (NVMe)
+    pub media_errors: HealthLimit,
+}
+
+impl Default for DiskHealthConfig {
+    fn default() -> Self {
+        Self {
+            check: true,
+            enforce: true,
+            fail_on_self_assessment: true,
+            reallocated_sectors: HealthLimit::new(0, 100),
+            pending_sectors: HealthLimit::new(0, 10),
+            percentage_used: HealthLimit::new(80, 100),
+            media_errors: HealthLimit::new(0, 100),
+        }
+    }
+}
+
+impl DiskHealthConfig {
+    /// Limits by the name used in reports
+    pub fn limits(&self) -> [(&'static str, HealthLimit); 4] {
+        [
+            ("reallocated_sectors", self.reallocated_sectors),
+            ("pending_sectors", self.pending_sectors),
+            ("percentage_used", self.percentage_used),
+            ("media_errors", self.media_errors),
+        ]
+    }
+
+    /// Check that no warning limit is above its failure limit
+    pub fn validate(&self) -> Result<()> {
+        for (name, limit) in self.limits() {
+            if let (Some(warn), Some(fail)) = (limit.warn, limit.fail) {
+                if warn > fail {
+                    return Err(AutoInstallError::ValidationError(format!(
+                        "disk_health {} warn limit {} is above its fail limit {}",
+                        name, warn, fail
+                    )));
+                }
+            }
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::loader::parse_section;
+
+    #[test]
+    fn test_validate_limits() {
+        let config = parse_section::<DiskHealthConfig>(
+            "disk_health:\n  enforce: false\n  percentage_used: {warn: 50}\n",
+            "disk_health",
+        )
+        .unwrap();
+        assert!(!config.enforce);
+        assert_eq!(config.percentage_used.warn, Some(50));
+        assert_eq!(config.percentage_used.fail, None);
+        assert!(config.validate().is_ok());
+
+        let inverted = DiskHealthConfig {
+            media_errors: HealthLimit::new(10, 5),
+            ..Default::default()
+        };
+        assert!(inverted.validate().is_err());
+    }
+}
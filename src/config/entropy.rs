@@ -0,0 +1,188 @@
+// file: src/config/entropy.rs
+// version: 1.1.0
+// guid: 2c7e4b19-6a35-4f82-9d1e-8b0a5f3c7e64
+
+//! Entropy sources of the installed system (`entropy:` section of a target config)
+//!
+//! Headless servers have no keyboard or mouse to feed the kernel's pool, and early boot is
+//! where LUKS unlock helpers and `ssh-keygen` ask for randomness. The installer looks at the
+//! live environment's `hw_random` device: with a hardware RNG (TPM, CPU, virtio-rng) it installs
+//! rng-tools to feed it to the pool, without one it installs jitterentropy-rngd. A first-boot
+//! unit ordered before sshd waits for the pool to reach `min_entropy` and logs when it does not.
+
+use crate::error::AutoInstallError;
+use crate::Result;
+use serde::{Deserialize, Serialize};
+
+/// Hardware RNG the kernel currently reads, `none` when there is none
+pub const HWRNG_CURRENT: &str = "/sys/class/misc/hw_random/rng_current";
+pub const ENTROPY_AVAIL: &str = "/proc/sys/kernel/random/entropy_avail";
+const CHECK_SCRIPT: &str = "usr/local/sbin/uaa-entropy-check";
+const CHECK_UNIT: &str = "etc/systemd/system/uaa-entropy-check.service";
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum EntropyMode {
+    /// rng-tools with a hardware RNG, jitterentropy-rngd without
+    #[default]
+    Auto,
+    RngTools,
+    Jitter,
+    Off,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct EntropyConfig {
+    pub mode: EntropyMode,
+    /// Pool level (bits) the install and first boot want before generating keys
+    pub min_entropy: u32,
+    /// Seconds the first-boot check waits before letting sshd start anyway
+    pub first_boot_timeout_seconds: u32,
+}
+
+impl Default for EntropyConfig {
+    fn default() -> Self {
+        Self {
+            mode: EntropyMode::Auto,
+            min_entropy: 256,
+            first_boot_timeout_seconds: 60,
+        }
+    }
+}
+
+/// Hardware RNG named by [`HWRNG_CURRENT`], if any
+pub fn parse_hwrng(rng_current: &str) -> Option<String> {
+    let current = rng_current.trim();
+    (!current.is_empty() && current != "none").then(|| current.to_string())
+}
+
+impl EntropyConfig {
+    pub fn is_enabled(&self) -> bool {
+        self.mode != EntropyMode::Off
+    }
+
+    pub fn validate(&self) -> Result<()> {
+        if self.min_entropy == 0 || self.min_entropy > 4096 {
+            return Err(AutoInstallError::ValidationError(format!(
+                "entropy.min_entropy must be between 1 and 4096, got {}",
+                self.min_entropy
+            )));
+        }
+        Ok(())
+    }
+
+    /// Package and service feeding the pool, given the detected hardware RNG
+    pub fn daemon(&self, hwrng: Option<&str>) -> Option<(&'static str, &'static str)> {
+        let rng_tools = ("rng-tools5", "rngd.service");
+        let jitter = ("jitterentropy-rngd", "jitterentropy.service");
+        match self.mode {
+            EntropyMode::Off => None,
+            EntropyMode::RngTools => Some(rng_tools),
+            EntropyMode::Jitter => Some(jitter),
+            EntropyMode::Auto if hwrng.is_some() => Some(rng_tools),
+            EntropyMode::Auto => Some(jitter),
+        }
+    }
+
+    /// Commands installing the daemon and the first-boot check into the system at `root`
+    pub fn build_apply_commands(&self, root: &str, hwrng: Option<&str>) -> Vec<String> {
+        let Some((package, service)) = self.daemon(hwrng) else {
+            return Vec::new();
+        };
+        let root = root.trim_end_matches('/');
+        let script = format!(
+            "#!/bin/sh\n\
+             # Installed by ubuntu-autoinstall-agent: wait for the entropy pool before sshd\n\
+             i=0\n\
+             while [ \"$(cat {avail})\" -lt {min} ]; do\n\
+             \x20 i=$((i + 1))\n\
+             \x20 if [ \"$i\" -gt {timeout} ]; then\n\
+             \x20   echo \"entropy pool at $(cat {avail}) bits after {timeout}s, wanted {min}\" >&2\n\
+             \x20   exit 0\n\
+             \x20 fi\n\
+             \x20 sleep 1\n\
+             done\n\
+             echo \"entropy pool at $(cat {avail}) bits\"\n",
+            avail = ENTROPY_AVAIL,
+            min = self.min_entropy,
+            timeout = self.first_boot_timeout_seconds
+        );
+        let unit = format!(
+            "[Unit]\n\
+             Description=Wait for the kernel entropy pool\n\
+             After={service}\n\
+             Before=ssh.service systemd-cryptsetup.target\n\
+             DefaultDependencies=no\n\n\
+             [Service]\n\
+             Type=oneshot\n\
+             ExecStart=/{script}\n\
+             TimeoutStartSec={timeout}\n\n\
+             [Install]\n\
+             WantedBy=multi-user.target\n",
+            service = service,
+            script = CHECK_SCRIPT,
+            timeout = self.first_boot_timeout_seconds + 30
+        );
+        vec![
+            format!(
+                "chroot {} bash -lc 'DEBIAN_FRONTEND=noninteractive apt install -y {}'",
+                root, package
+            ),
+            format!("chroot {} systemctl enable {}", root, service),
+            format!(
+                "cat > {}/{} << 'EOF'\n{}EOF\nchmod 755 {}/{}",
+                root, CHECK_SCRIPT, script, root, CHECK_SCRIPT
+            ),
+            format!("cat > {}/{} << 'EOF'\n{}EOF", root, CHECK_UNIT, unit),
+            format!("chroot {} systemctl enable uaa-entropy-check.service", root),
+        ]
+    }
+
+    /// Warning when the live pool (contents of [`ENTROPY_AVAIL`]) is below `min_entropy`
+    pub fn check_pool(&self, entropy_avail: &str) -> Option<String> {
+        let bits: u32 = entropy_avail.trim().parse().ok()?;
+        (bits < self.min_entropy).then(|| {
+            format!(
+                "Entropy pool at {} bits, below {}; LUKS and key generation may stall",
+                bits, self.min_entropy
+            )
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_daemon_choice_and_commands() {
+        let config = EntropyConfig::default();
+        config.validate().unwrap();
+        assert_eq!(parse_hwrng("none\n"), None);
+        let hwrng = parse_hwrng("tpm-rng-0\n");
+        assert_eq!(
+            config.daemon(hwrng.as_deref()),
+            Some(("rng-tools5", "rngd.service"))
+        );
+        assert_eq!(config.daemon(None).unwrap().0, "jitterentropy-rngd");
+
+        let cmds = config.build_apply_commands("/mnt/targetos/", None);
+        assert_eq!(
+            cmds[1],
+            "chroot /mnt/targetos systemctl enable jitterentropy.service"
+        );
+        assert!(
+            cmds[2].contains("while [ \"$(cat /proc/sys/kernel/random/entropy_avail)\" -lt 256 ]")
+        );
+        assert!(cmds[3].contains("Before=ssh.service"));
+
+        assert!(config.check_pool("31\n").is_some());
+        assert!(config.check_pool("256").is_none());
+        let off = EntropyConfig {
+            mode: EntropyMode::Off,
+            ..config
+        };
+        assert!(off.build_apply_commands("/mnt/targetos", None).is_empty());
+    }
+}
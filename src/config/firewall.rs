@@ -0,0 +1,342 @@
+// file: src/config/firewall.rs
+// version: 1.1.0
+// guid: 5e2c8a14-7f39-4d61-b0a7-2c9e4f1d6b83
+
+//! Host firewall (`firewall:` section of a target config)
+//!
+//! The rules are rendered either into `/etc/nftables.conf` or into ufw's rule files while the
+//! target is still mounted, and the service is enabled so they load on first boot. Inbound
+//! traffic is dropped unless a rule allows it; loopback, established connections and ICMP are
+//! always accepted. Validation refuses any rule set that would close the SSH port the agent
+//! manages the host through.
+
+use serde::{Deserialize, Serialize};
+
+/// Ruleset loaded by `nftables.service`
+pub const NFTABLES_CONF: &str = "etc/nftables.conf";
+/// ufw's main switch; `ufw enable` cannot run in a chroot
+pub const UFW_CONF: &str = "etc/ufw/ufw.conf";
+
+/// Tool the rules are rendered for
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum FirewallBackend {
+    /// Leave the installed system without a firewall
+    #[default]
+    None,
+    Nftables,
+    Ufw,
+}
+
+/// What happens to inbound traffic no rule matches
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum FirewallPolicy {
+    #[default]
+    Drop,
+    Accept,
+}
+
+/// Transport protocol of a rule
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum FirewallProtocol {
+    #[default]
+    Tcp,
+    Udp,
+}
+
+impl FirewallProtocol {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            FirewallProtocol::Tcp => "tcp",
+            FirewallProtocol::Udp => "udp",
+        }
+    }
+}
+
+/// One inbound allow rule
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct FirewallRule {
+    pub port: u16,
+    #[serde(default)]
+    pub protocol: FirewallProtocol,
+    /// Address or CIDR the rule is limited to; any source when absent
+    #[serde(default)]
+    pub source: Option<String>,
+    /// Note carried into the rendered rule
+    #[serde(default)]
+    pub comment: Option<String>,
+}
+
+impl FirewallRule {
+    fn is_ipv6(&self) -> bool {
+        self.source.as_deref().is_some_and(|s| s.contains(':'))
+    }
+
+    fn describe(&self) -> String {
+        match &self.source {
+            Some(source) => format!("{}/{} from {}", self.port, self.protocol.as_str(), source),
+            None => format!("{}/{}", self.port, self.protocol.as_str()),
+        }
+    }
+}
+
+/// Firewall selected for one target
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct FirewallConfig {
+    pub backend: FirewallBackend,
+    /// Inbound policy for traffic no rule allows
+    pub default_incoming: FirewallPolicy,
+    /// Port sshd listens on for the agent; an allow rule for it is required under `drop`
+    pub ssh_port: u16,
+    pub rules: Vec<FirewallRule>,
+}
+
+impl Default for FirewallConfig {
+    fn default() -> Self {
+        Self {
+            backend: FirewallBackend::None,
+            default_incoming: FirewallPolicy::Drop,
+            ssh_port: 22,
+            rules: Vec::new(),
+        }
+    }
+}
+
+impl FirewallConfig {
+    /// Whether a firewall will be installed
+    pub fn is_enabled(&self) -> bool {
+        self.backend != FirewallBackend::None
+    }
+
+    /// Check sources and that the management SSH port stays reachable
+    pub fn validate(&self) -> crate::Result<()> {
+        if !self.is_enabled() {
+            return Ok(());
+        }
+        for rule in &self.rules {
+            if rule.port == 0 {
+                return Err(crate::error::AutoInstallError::ValidationError(
+                    "firewall rule port must be between 1 and 65535".to_string(),
+                ));
+            }
+            if let Some(source) = &rule.source {
+                if source.is_empty()
+                    || !source
+                        .chars()
+                        .all(|c| c.is_ascii_hexdigit() || matches!(c, '.' | ':' | '/'))
+                {
+                    return Err(crate::error::AutoInstallError::ValidationError(format!(
+                        "firewall rule source '{}' must be an IP address or CIDR",
+                        source
+                    )));
+                }
+            }
+            if let Some(comment) = &rule.comment {
+                if comment.contains(['\'', '"', '\n']) {
+                    return Err(crate::error::AutoInstallError::ValidationError(format!(
+                        "firewall rule comment for {} must not contain quotes or newlines",
+                        rule.describe()
+                    )));
+                }
+            }
+        }
+        if self.default_incoming == FirewallPolicy::Drop && !self.allows_ssh() {
+            return Err(crate::error::AutoInstallError::ValidationError(format!(
+                "firewall drops inbound traffic but has no rule allowing SSH on {}/tcp; the host would be unreachable for management",
+                self.ssh_port
+            )));
+        }
+        Ok(())
+    }
+
+    /// Whether some rule accepts TCP on the management SSH port
+    pub fn allows_ssh(&self) -> bool {
+        self.rules
+            .iter()
+            .any(|r| r.port == self.ssh_port && r.protocol == FirewallProtocol::Tcp)
+    }
+
+    /// Commands installing the rules into the system mounted at `root` and enabling them at boot
+    ///
+    /// Package installs run in the chroot, so the chroot mounts and resolv.conf must already be
+    /// in place. Nothing is loaded into the running (live) kernel.
+    pub fn build_apply_commands(&self, root: &str) -> Vec<String> {
+        let root = root.trim_end_matches('/');
+        match self.backend {
+            FirewallBackend::None => Vec::new(),
+            FirewallBackend::Nftables => vec![
+                format!(
+                    "chroot {} bash -lc 'DEBIAN_FRONTEND=noninteractive apt-get install -y nftables'",
+                    root
+                ),
+                format!(
+                    "cat > {}/{} << 'EOF'\n{}EOF",
+                    root,
+                    NFTABLES_CONF,
+                    self.nftables_ruleset()
+                ),
+                format!("chroot {} systemctl enable nftables.service", root),
+            ],
+            FirewallBackend::Ufw => {
+                let mut commands = vec![format!(
+                    "chroot {} bash -lc 'DEBIAN_FRONTEND=noninteractive apt-get install -y ufw'",
+                    root
+                )];
+                let policy = match self.default_incoming {
+                    FirewallPolicy::Drop => "deny",
+                    FirewallPolicy::Accept => "allow",
+                };
+                commands.push(format!("chroot {} ufw default {} incoming", root, policy));
+                commands.push(format!("chroot {} ufw default allow outgoing", root));
+                for rule in &self.rules {
+                    commands.push(format!("chroot {} {}", root, ufw_allow(rule)));
+                }
+                commands.push(format!(
+                    "sed -i 's/^ENABLED=.*/ENABLED=yes/' {}/{}",
+                    root, UFW_CONF
+                ));
+                commands.push(format!("chroot {} systemctl enable ufw.service", root));
+                commands
+            }
+        }
+    }
+
+    /// `/etc/nftables.conf` contents
+    pub fn nftables_ruleset(&self) -> String {
+        let policy = match self.default_incoming {
+            FirewallPolicy::Drop => "drop",
+            FirewallPolicy::Accept => "accept",
+        };
+        let mut out = String::from("#!/usr/sbin/nft -f\n# Managed by ubuntu-autoinstall-agent\n\n");
+        out.push_str("flush ruleset\n\ntable inet filter {\n\tchain input {\n");
+        out.push_str(&format!(
+            "\t\ttype filter hook input priority filter; policy {};\n",
+            policy
+        ));
+        out.push_str("\t\tiif \"lo\" accept\n");
+        out.push_str("\t\tct state established,related accept\n");
+        out.push_str("\t\tct state invalid drop\n");
+        out.push_str("\t\tmeta l4proto { icmp, ipv6-icmp } accept\n");
+        for rule in &self.rules {
+            let mut line = String::from("\t\t");
+            if let Some(source) = &rule.source {
+                let family = if rule.is_ipv6() { "ip6" } else { "ip" };
+                line.push_str(&format!("{} saddr {} ", family, source));
+            }
+            line.push_str(&format!(
+                "{} dport {} accept",
+                rule.protocol.as_str(),
+                rule.port
+            ));
+            if let Some(comment) = &rule.comment {
+                line.push_str(&format!(" comment \"{}\"", comment));
+            }
+            out.push_str(&line);
+            out.push('\n');
+        }
+        out.push_str("\t}\n\n\tchain forward {\n");
+        out.push_str("\t\ttype filter hook forward priority filter; policy drop;\n\t}\n\n");
+        out.push_str("\tchain output {\n");
+        out.push_str("\t\ttype filter hook output priority filter; policy accept;\n\t}\n}\n");
+        out
+    }
+}
+
+/// `ufw allow ...` for `rule`
+fn ufw_allow(rule: &FirewallRule) -> String {
+    let mut command = format!(
+        "ufw allow proto {} from {} to any port {}",
+        rule.protocol.as_str(),
+        rule.source.as_deref().unwrap_or("any"),
+        rule.port
+    );
+    if let Some(comment) = &rule.comment {
+        command.push_str(&format!(" comment '{}'", comment));
+    }
+    command
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::loader::parse_section;
+
+    fn parse(yaml: &str) -> FirewallConfig {
+        parse_section::<FirewallConfig>(yaml, "firewall").unwrap()
+    }
+
+    #[test]
+    fn test_validate_requires_ssh_rule_under_drop() {
+        assert!(FirewallConfig::default().validate().is_ok());
+        assert!(FirewallConfig::default()
+            .build_apply_commands("/mnt/targetos")
+            .is_empty());
+
+        let config = parse("firewall:\n  backend: nftables\n  rules:\n    - port: 443\n");
+        let err = config.validate().unwrap_err().to_string();
+        assert!(err.contains("22/tcp"), "{}", err);
+
+        let config = parse(
+            "firewall:\n  backend: ufw\n  ssh_port: 2222\n  rules:\n    - port: 22\n    - port: 2222\n      protocol: udp\n",
+        );
+        assert!(config.validate().is_err());
+
+        let config = parse(
+            "firewall:\n  backend: ufw\n  ssh_port: 2222\n  rules:\n    - port: 2222\n      source: 10.0.0.0/8\n",
+        );
+        assert!(config.validate().is_ok());
+
+        let config = parse("firewall:\n  backend: nftables\n  default_incoming: accept\n");
+        assert!(config.validate().is_ok());
+
+        let config = parse(
+            "firewall:\n  backend: nftables\n  rules:\n    - port: 22\n      source: \"10.0.0.0/8; reboot\"\n",
+        );
+        assert!(config.validate().is_err());
+    }
+
+    #[test]
+    fn test_nftables_ruleset() {
+        let config = parse(
+            "firewall:\n  backend: nftables\n  rules:\n    - port: 22\n    - port: 9100\n      source: fd00::/8\n      comment: node exporter\n    - port: 53\n      protocol: udp\n      source: 10.0.0.0/8\n",
+        );
+        let ruleset = config.nftables_ruleset();
+        assert!(ruleset.contains("policy drop;\n\t\tiif \"lo\" accept\n"));
+        assert!(ruleset.contains("\t\ttcp dport 22 accept\n"));
+        assert!(ruleset
+            .contains("\t\tip6 saddr fd00::/8 tcp dport 9100 accept comment \"node exporter\"\n"));
+        assert!(ruleset.contains("\t\tip saddr 10.0.0.0/8 udp dport 53 accept\n"));
+
+        let commands = config.build_apply_commands("/mnt/targetos/");
+        assert_eq!(commands.len(), 3);
+        assert!(commands[1]
+            .starts_with("cat > /mnt/targetos/etc/nftables.conf << 'EOF'\n#!/usr/sbin/nft -f\n"));
+        assert_eq!(
+            commands[2],
+            "chroot /mnt/targetos systemctl enable nftables.service"
+        );
+    }
+
+    #[test]
+    fn test_ufw_commands() {
+        let config = parse(
+            "firewall:\n  backend: ufw\n  rules:\n    - port: 22\n      source: 192.0.2.0/24\n      comment: mgmt\n",
+        );
+        let commands = config.build_apply_commands("/mnt/targetos");
+        assert!(commands.contains(&"chroot /mnt/targetos ufw default deny incoming".to_string()));
+        assert!(commands.contains(
+            &"chroot /mnt/targetos ufw allow proto tcp from 192.0.2.0/24 to any port 22 comment 'mgmt'"
+                .to_string()
+        ));
+        assert!(commands.contains(
+            &"sed -i 's/^ENABLED=.*/ENABLED=yes/' /mnt/targetos/etc/ufw/ufw.conf".to_string()
+        ));
+        assert_eq!(
+            commands.last().unwrap(),
+            "chroot /mnt/targetos systemctl enable ufw.service"
+        );
+    }
+}
@@ -0,0 +1,431 @@
+// file: src/config/hardening.rs
+// version: 1.2.0
+// guid: 9c4e2a71-5d38-4b6f-a1e9-3f7b0d8c2e54
+
+//! Install-time hardening profile (`hardening:` section of a target config)
+//!
+//! The `cis-level1` profile applies a practical subset of the CIS Ubuntu Level 1 benchmark
+//! while the target is still mounted: an sshd drop-in, auditd with a small rule set, password
+//! quality and ageing, a restrictive default umask and network/kernel sysctls. Every applied
+//! setting has a matching compliance check that is run against the installed files before the
+//! first boot, so the report shows which controls actually landed.
+
+use super::write_managed_file;
+use serde::{Deserialize, Serialize};
+
+/// sshd drop-in; Ubuntu's sshd_config includes `sshd_config.d/*.conf` before its own settings
+pub const SSHD_DROPIN_FILE: &str = "etc/ssh/sshd_config.d/60-autoinstall-hardening.conf";
+/// audit rules loaded by augenrules
+pub const AUDIT_RULES_FILE: &str = "etc/audit/rules.d/60-autoinstall-hardening.rules";
+/// libpwquality drop-in
+pub const PWQUALITY_FILE: &str = "etc/security/pwquality.conf.d/60-autoinstall-hardening.conf";
+/// Login shell umask
+pub const UMASK_FILE: &str = "etc/profile.d/60-autoinstall-umask.sh";
+/// Hardening sysctls; numbered below the `kernel:` tuning file so explicit tuning wins
+pub const SYSCTL_FILE: &str = "etc/sysctl.d/60-autoinstall-hardening.conf";
+
+/// sshd directives written to the drop-in
+const SSHD_SETTINGS: &[(&str, &str)] = &[
+    ("PermitRootLogin", "no"),
+    ("PermitEmptyPasswords", "no"),
+    ("HostbasedAuthentication", "no"),
+    ("IgnoreRhosts", "yes"),
+    ("X11Forwarding", "no"),
+    ("AllowTcpForwarding", "no"),
+    ("MaxAuthTries", "4"),
+    ("LoginGraceTime", "60"),
+    ("ClientAliveInterval", "300"),
+    ("ClientAliveCountMax", "3"),
+    ("LogLevel", "VERBOSE"),
+];
+
+/// Kernel and network sysctls
+const SYSCTL_SETTINGS: &[(&str, &str)] = &[
+    ("kernel.randomize_va_space", "2"),
+    ("kernel.yama.ptrace_scope", "1"),
+    ("fs.suid_dumpable", "0"),
+    ("net.ipv4.conf.all.send_redirects", "0"),
+    ("net.ipv4.conf.default.send_redirects", "0"),
+    ("net.ipv4.conf.all.accept_redirects", "0"),
+    ("net.ipv4.conf.default.accept_redirects", "0"),
+    ("net.ipv4.conf.all.secure_redirects", "0"),
+    ("net.ipv4.conf.default.secure_redirects", "0"),
+    ("net.ipv4.conf.all.accept_source_route", "0"),
+    ("net.ipv4.conf.default.accept_source_route", "0"),
+    ("net.ipv4.conf.all.log_martians", "1"),
+    ("net.ipv4.conf.all.rp_filter", "1"),
+    ("net.ipv4.icmp_echo_ignore_broadcasts", "1"),
+    ("net.ipv4.icmp_ignore_bogus_error_responses", "1"),
+    ("net.ipv4.tcp_syncookies", "1"),
+    ("net.ipv6.conf.all.accept_redirects", "0"),
+    ("net.ipv6.conf.default.accept_redirects", "0"),
+];
+
+/// libpwquality settings
+const PWQUALITY_SETTINGS: &[(&str, &str)] = &[("minlen", "14"), ("minclass", "4"), ("retry", "3")];
+
+/// `/etc/login.defs` keys rewritten in place
+const LOGIN_DEFS_SETTINGS: &[(&str, &str)] = &[
+    ("PASS_MAX_DAYS", "365"),
+    ("PASS_MIN_DAYS", "1"),
+    ("PASS_WARN_AGE", "7"),
+];
+
+/// Default umask for login shells and `login.defs`
+const UMASK: &str = "027";
+
+/// Audit rules covering identity files, sudoers, time changes and login records
+const AUDIT_RULES: &[&str] = &[
+    "-w /etc/passwd -p wa -k identity",
+    "-w /etc/group -p wa -k identity",
+    "-w /etc/shadow -p wa -k identity",
+    "-w /etc/gshadow -p wa -k identity",
+    "-w /etc/sudoers -p wa -k scope",
+    "-w /etc/sudoers.d -p wa -k scope",
+    "-w /etc/ssh/sshd_config -p wa -k sshd",
+    "-w /var/log/lastlog -p wa -k logins",
+    "-w /var/run/faillock -p wa -k logins",
+    "-a always,exit -F arch=b64 -S adjtimex,settimeofday,clock_settime -k time-change",
+    "-w /etc/localtime -p wa -k time-change",
+];
+
+/// Named hardening profile
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum HardeningProfile {
+    /// Leave the installed system as debootstrap produced it
+    #[default]
+    None,
+    /// CIS Ubuntu Linux benchmark, Level 1 (server) subset
+    CisLevel1,
+}
+
+/// Individually skippable part of a profile
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum HardeningControl {
+    Ssh,
+    Auditd,
+    PasswordPolicy,
+    Umask,
+    Sysctl,
+}
+
+impl HardeningControl {
+    /// Every control, in the order they are applied
+    pub const ALL: &'static [HardeningControl] = &[
+        HardeningControl::Ssh,
+        HardeningControl::Auditd,
+        HardeningControl::PasswordPolicy,
+        HardeningControl::Umask,
+        HardeningControl::Sysctl,
+    ];
+
+    /// Name used in config files and reports
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            HardeningControl::Ssh => "ssh",
+            HardeningControl::Auditd => "auditd",
+            HardeningControl::PasswordPolicy => "password_policy",
+            HardeningControl::Umask => "umask",
+            HardeningControl::Sysctl => "sysctl",
+        }
+    }
+}
+
+/// Hardening selected for one target
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct HardeningConfig {
+    /// Profile to apply
+    pub profile: HardeningProfile,
+    /// Controls of the profile to leave out, e.g. `ssh` for hosts that still need root logins
+    pub skip: Vec<HardeningControl>,
+}
+
+/// One item of the post-install compliance checklist
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ComplianceCheck {
+    pub control: HardeningControl,
+    /// What is being verified, e.g. `sshd: PermitRootLogin no`
+    pub title: String,
+    /// Shell command that exits 0 when the installed system complies
+    pub command: String,
+}
+
+/// Outcome of a compliance check, kept in the session record
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ComplianceResult {
+    pub control: HardeningControl,
+    pub title: String,
+    pub passed: bool,
+}
+
+impl HardeningConfig {
+    /// Whether any control will be applied
+    pub fn is_enabled(&self) -> bool {
+        !self.controls().is_empty()
+    }
+
+    /// Controls the profile applies after `skip` is taken into account
+    pub fn controls(&self) -> Vec<HardeningControl> {
+        match self.profile {
+            HardeningProfile::None => Vec::new(),
+            HardeningProfile::CisLevel1 => HardeningControl::ALL
+                .iter()
+                .copied()
+                .filter(|c| !self.skip.contains(c))
+                .collect(),
+        }
+    }
+
+    /// Commands applying the selected controls to the system mounted at `root`
+    ///
+    /// Package installs run in the chroot, so the chroot mounts and resolv.conf must already be
+    /// in place; everything else writes files directly under `root`.
+    pub fn build_apply_commands(&self, root: &str) -> Vec<String> {
+        let root = root.trim_end_matches('/');
+        let mut commands = Vec::new();
+        for control in self.controls() {
+            match control {
+                HardeningControl::Ssh => {
+                    commands.push(write_managed_file(root, SSHD_DROPIN_FILE, &sshd_conf()));
+                }
+                HardeningControl::Auditd => {
+                    commands.push(format!(
+                        "chroot {} bash -lc 'DEBIAN_FRONTEND=noninteractive apt-get install -y auditd && systemctl enable auditd'",
+                        root
+                    ));
+                    commands.push(write_managed_file(root, AUDIT_RULES_FILE, &audit_rules()));
+                }
+                HardeningControl::PasswordPolicy => {
+                    commands.push(format!(
+                        "chroot {} bash -lc 'DEBIAN_FRONTEND=noninteractive apt-get install -y libpam-pwquality'",
+                        root
+                    ));
+                    commands.push(write_managed_file(root, PWQUALITY_FILE, &pwquality_conf()));
+                    for (key, value) in LOGIN_DEFS_SETTINGS {
+                        commands.push(login_defs_command(root, key, value));
+                    }
+                }
+                HardeningControl::Umask => {
+                    commands.push(write_managed_file(
+                        root,
+                        UMASK_FILE,
+                        &format!("umask {}\n", UMASK),
+                    ));
+                    commands.push(login_defs_command(root, "UMASK", UMASK));
+                }
+                HardeningControl::Sysctl => {
+                    commands.push(write_managed_file(root, SYSCTL_FILE, &sysctl_conf()));
+                }
+            }
+        }
+        commands
+    }
+
+    /// Checklist verifying the selected controls against the files under `root`
+    pub fn compliance_checks(&self, root: &str) -> Vec<ComplianceCheck> {
+        let root = root.trim_end_matches('/');
+        let check = |control, title: String, command: String| ComplianceCheck {
+            control,
+            title,
+            command,
+        };
+        let mut checks = Vec::new();
+        for control in self.controls() {
+            match control {
+                HardeningControl::Ssh => {
+                    for (key, value) in SSHD_SETTINGS {
+                        checks.push(check(
+                            control,
+                            format!("sshd: {} {}", key, value),
+                            format!(
+                                "grep -qxF '{} {}' {}/{}",
+                                key, value, root, SSHD_DROPIN_FILE
+                            ),
+                        ));
+                    }
+                }
+                HardeningControl::Auditd => {
+                    checks.push(check(
+                        control,
+                        "auditd installed and enabled".to_string(),
+                        format!(
+                            "test -x {0}/sbin/auditd && test -e {0}/etc/systemd/system/multi-user.target.wants/auditd.service",
+                            root
+                        ),
+                    ));
+                    checks.push(check(
+                        control,
+                        "audit rules for identity, sudoers and time changes".to_string(),
+                        format!("grep -q -- '-k identity' {}/{}", root, AUDIT_RULES_FILE),
+                    ));
+                }
+                HardeningControl::PasswordPolicy => {
+                    checks.push(check(
+                        control,
+                        "pam_pwquality enabled for password changes".to_string(),
+                        format!(
+                            "grep -q pam_pwquality.so {}/etc/pam.d/common-password",
+                            root
+                        ),
+                    ));
+                    for (key, value) in PWQUALITY_SETTINGS {
+                        checks.push(check(
+                            control,
+                            format!("pwquality: {} = {}", key, value),
+                            format!(
+                                "grep -qxF '{} = {}' {}/{}",
+                                key, value, root, PWQUALITY_FILE
+                            ),
+                        ));
+                    }
+                    for (key, value) in LOGIN_DEFS_SETTINGS {
+                        checks.push(login_defs_check(control, root, key, value));
+                    }
+                }
+                HardeningControl::Umask => {
+                    checks.push(check(
+                        control,
+                        format!("login shells: umask {}", UMASK),
+                        format!("grep -qxF 'umask {}' {}/{}", UMASK, root, UMASK_FILE),
+                    ));
+                    checks.push(login_defs_check(control, root, "UMASK", UMASK));
+                }
+                HardeningControl::Sysctl => {
+                    for (key, value) in SYSCTL_SETTINGS {
+                        checks.push(check(
+                            control,
+                            format!("sysctl: {} = {}", key, value),
+                            format!("grep -qxF '{} = {}' {}/{}", key, value, root, SYSCTL_FILE),
+                        ));
+                    }
+                }
+            }
+        }
+        checks
+    }
+}
+
+fn sshd_conf() -> String {
+    SSHD_SETTINGS
+        .iter()
+        .map(|(k, v)| format!("{} {}\n", k, v))
+        .collect()
+}
+
+fn sysctl_conf() -> String {
+    SYSCTL_SETTINGS
+        .iter()
+        .map(|(k, v)| format!("{} = {}\n", k, v))
+        .collect()
+}
+
+fn pwquality_conf() -> String {
+    PWQUALITY_SETTINGS
+        .iter()
+        .map(|(k, v)| format!("{} = {}\n", k, v))
+        .collect()
+}
+
+fn audit_rules() -> String {
+    AUDIT_RULES.iter().map(|r| format!("{}\n", r)).collect()
+}
+
+/// Replace `key` in login.defs, appending it when the distribution default is missing
+fn login_defs_command(root: &str, key: &str, value: &str) -> String {
+    let file = format!("{}/etc/login.defs", root);
+    format!(
+        "if grep -qE '^{key}[[:space:]]' {file}; then sed -i -E 's/^{key}[[:space:]].*/{key}\\t{value}/' {file}; else printf '{key}\\t{value}\\n' >> {file}; fi",
+        key = key,
+        value = value,
+        file = file
+    )
+}
+
+fn login_defs_check(
+    control: HardeningControl,
+    root: &str,
+    key: &str,
+    value: &str,
+) -> ComplianceCheck {
+    ComplianceCheck {
+        control,
+        title: format!("login.defs: {} {}", key, value),
+        command: format!(
+            "grep -qE '^{}[[:space:]]+{}$' {}/etc/login.defs",
+            key, value, root
+        ),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::loader::parse_section;
+
+    #[test]
+    fn test_profile_and_skip_select_controls() {
+        assert!(!HardeningConfig::default().is_enabled());
+        assert!(HardeningConfig::default()
+            .build_apply_commands("/mnt/targetos")
+            .is_empty());
+
+        let section: HardeningConfig = parse_section(
+            "hostname: a\nhardening:\n  profile: cis-level1\n  skip: [ssh, auditd]\n",
+            "hardening",
+        )
+        .unwrap();
+        let config = section;
+        assert_eq!(
+            config.controls(),
+            vec![
+                HardeningControl::PasswordPolicy,
+                HardeningControl::Umask,
+                HardeningControl::Sysctl
+            ]
+        );
+        let commands = config.build_apply_commands("/mnt/targetos/").join("\n");
+        assert!(!commands.contains("sshd_config.d"));
+        assert!(!commands.contains("auditd"));
+        assert!(commands.contains("/mnt/targetos/etc/sysctl.d/60-autoinstall-hardening.conf"));
+        assert!(commands.contains("kernel.randomize_va_space = 2\n"));
+        assert!(commands.contains("umask 027\n"));
+        assert!(parse_section::<HardeningConfig>(
+            "hardening:\n  profile: cis-level9\n",
+            "hardening"
+        )
+        .is_err());
+    }
+
+    #[test]
+    fn test_every_control_has_checks() {
+        let config = HardeningConfig {
+            profile: HardeningProfile::CisLevel1,
+            skip: Vec::new(),
+        };
+        let checks = config.compliance_checks("/mnt/targetos");
+        for control in HardeningControl::ALL {
+            assert!(
+                checks.iter().any(|c| c.control == *control),
+                "no checks for {}",
+                control.as_str()
+            );
+        }
+        assert!(checks.iter().any(|c| c.title == "sshd: PermitRootLogin no"
+            && c.command
+                == "grep -qxF 'PermitRootLogin no' /mnt/targetos/etc/ssh/sshd_config.d/60-autoinstall-hardening.conf"));
+        assert!(checks.iter().all(|c| c.command.contains("/mnt/targetos/")));
+    }
+
+    #[test]
+    fn test_login_defs_command_replaces_or_appends() {
+        let cmd = login_defs_command("/mnt/targetos", "PASS_MAX_DAYS", "365");
+        assert!(
+            cmd.starts_with("if grep -qE '^PASS_MAX_DAYS[[:space:]]' /mnt/targetos/etc/login.defs")
+        );
+        assert!(cmd.contains("s/^PASS_MAX_DAYS[[:space:]].*/PASS_MAX_DAYS\\t365/"));
+        assert!(cmd.contains(">> /mnt/targetos/etc/login.defs"));
+    }
+}
@@ -0,0 +1,268 @@
+// file: src/config/headless.rs
+// version: 1.3.0
+// guid: 7d1f4b92-3c68-4e05-a9b2-8e6c0f5a1d37
+
+//! Headless server settings (`headless:` section of a target config)
+//!
+//! Serial console (GRUB and kernel output plus a getty), hardware watchdog and whether the RTC
+//! keeps UTC or local time. Every part is optional; an empty section changes nothing.
+
+use super::write_managed_file;
+use serde::{Deserialize, Serialize};
+
+/// GRUB defaults snippet, sourced by `update-grub` after `/etc/default/grub`
+pub const GRUB_SERIAL_FILE: &str = "etc/default/grub.d/60-autoinstall-serial.cfg";
+/// systemd manager drop-in carrying the watchdog timeouts
+pub const WATCHDOG_FILE: &str = "etc/systemd/system.conf.d/60-autoinstall-watchdog.conf";
+/// Watchdog driver loaded at boot
+pub const WATCHDOG_MODULE_FILE: &str = "etc/modules-load.d/60-autoinstall-watchdog.conf";
+/// Read by hwclock and timedated to decide whether the RTC is UTC
+pub const ADJTIME_FILE: &str = "etc/adjtime";
+
+/// Baud rates GRUB's `serial` command and the kernel both accept
+const BAUD_RATES: &[u32] = &[9600, 19200, 38400, 57600, 115200];
+
+/// Serial console on a `ttyS*` port (IPMI and AMT Serial-over-LAN expose one too)
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct SerialConsole {
+    /// Port name, e.g. `ttyS0`; AMT SOL is usually `ttyS4` and IPMI SOL `ttyS1`
+    #[serde(default = "default_serial_device")]
+    pub device: String,
+    #[serde(default = "default_baud")]
+    pub baud: u32,
+}
+
+fn default_serial_device() -> String {
+    "ttyS0".to_string()
+}
+
+fn default_baud() -> u32 {
+    115200
+}
+
+impl SerialConsole {
+    /// GRUB `--unit` for the port, i.e. the number after `ttyS`
+    pub fn unit(&self) -> Option<u32> {
+        self.device.strip_prefix("ttyS")?.parse().ok()
+    }
+
+    /// Kernel parameters for the port; the last `console=` gets /dev/console
+    pub fn kernel_args(&self) -> Vec<String> {
+        vec![
+            "console=tty0".to_string(),
+            format!("console={},{}n8", self.device, self.baud),
+        ]
+    }
+}
+
+/// Hardware watchdog driven by systemd
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct WatchdogConfig {
+    /// Driver to load at boot, e.g. `iTCO_wdt` or `ipmi_watchdog`; left to udev when unset
+    #[serde(default)]
+    pub module: Option<String>,
+    /// Seconds without a ping from systemd before the hardware resets the machine
+    #[serde(default = "default_runtime_secs")]
+    pub runtime_secs: u32,
+    /// Seconds a reboot may take before the watchdog forces it
+    #[serde(default = "default_reboot_secs")]
+    pub reboot_secs: u32,
+}
+
+fn default_runtime_secs() -> u32 {
+    30
+}
+
+fn default_reboot_secs() -> u32 {
+    600
+}
+
+/// Time base of the hardware clock
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum RtcMode {
+    Utc,
+    /// Only for machines that dual-boot Windows
+    Local,
+}
+
+/// Serial console, watchdog and RTC settings for one target
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct HeadlessConfig {
+    pub serial_console: Option<SerialConsole>,
+    pub watchdog: Option<WatchdogConfig>,
+    pub rtc: Option<RtcMode>,
+}
+
+impl HeadlessConfig {
+    /// Whether there is nothing to write
+    pub fn is_empty(&self) -> bool {
+        self.serial_console.is_none() && self.watchdog.is_none() && self.rtc.is_none()
+    }
+
+    /// Kernel parameters for bootloaders that do not read `/etc/default/grub.d`
+    pub fn kernel_args(&self) -> Vec<String> {
+        self.serial_console
+            .as_ref()
+            .map(SerialConsole::kernel_args)
+            .unwrap_or_default()
+    }
+
+    /// Check the serial port, baud rate, watchdog module and timeouts
+    pub fn validate(&self) -> crate::Result<()> {
+        if let Some(serial) = &self.serial_console {
+            if serial.unit().is_none() {
+                return Err(crate::error::AutoInstallError::ValidationError(format!(
+                    "headless.serial_console.device '{}' must be a ttyS<N> port",
+                    serial.device
+                )));
+            }
+            if !BAUD_RATES.contains(&serial.baud) {
+                return Err(crate::error::AutoInstallError::ValidationError(format!(
+                    "headless.serial_console.baud {} is not one of {:?}",
+                    serial.baud, BAUD_RATES
+                )));
+            }
+        }
+        if let Some(watchdog) = &self.watchdog {
+            if let Some(module) = &watchdog.module {
+                if module.is_empty()
+                    || !module
+                        .chars()
+                        .all(|c| c.is_ascii_alphanumeric() || c == '_' || c == '-')
+                {
+                    return Err(crate::error::AutoInstallError::ValidationError(format!(
+                        "headless.watchdog.module '{}' is not a valid module name",
+                        module
+                    )));
+                }
+            }
+            if watchdog.runtime_secs == 0 || watchdog.reboot_secs == 0 {
+                return Err(crate::error::AutoInstallError::ValidationError(
+                    "headless.watchdog.runtime_secs and reboot_secs must be at least 1".to_string(),
+                ));
+            }
+        }
+        Ok(())
+    }
+
+    /// Commands writing the settings into the system mounted at `root`
+    ///
+    /// The GRUB snippet only takes effect on the next `update-grub`, so these must run before
+    /// the bootloader is configured.
+    pub fn build_apply_commands(&self, root: &str) -> Vec<String> {
+        let root = root.trim_end_matches('/');
+        let mut commands = Vec::new();
+        if let Some(serial) = &self.serial_console {
+            commands.push(write_managed_file(
+                root,
+                GRUB_SERIAL_FILE,
+                &grub_serial_cfg(serial),
+            ));
+            commands.push(format!(
+                "chroot {} systemctl enable serial-getty@{}.service",
+                root, serial.device
+            ));
+        }
+        if let Some(watchdog) = &self.watchdog {
+            commands.push(write_managed_file(
+                root,
+                WATCHDOG_FILE,
+                &format!(
+                    "[Manager]\nRuntimeWatchdogSec={}\nRebootWatchdogSec={}\n",
+                    watchdog.runtime_secs, watchdog.reboot_secs
+                ),
+            ));
+            if let Some(module) = &watchdog.module {
+                commands.push(write_managed_file(
+                    root,
+                    WATCHDOG_MODULE_FILE,
+                    &format!("{}\n", module),
+                ));
+            }
+        }
+        if let Some(rtc) = self.rtc {
+            let mode = match rtc {
+                RtcMode::Utc => "UTC",
+                RtcMode::Local => "LOCAL",
+            };
+            // Same content `timedatectl set-local-rtc` writes; timedatectl needs a running systemd
+            commands.push(format!(
+                "printf '0.0 0 0.0\\n0\\n{}\\n' > {}/{}",
+                mode, root, ADJTIME_FILE
+            ));
+        }
+        commands
+    }
+}
+
+/// GRUB terminal and kernel console settings for `serial`
+fn grub_serial_cfg(serial: &SerialConsole) -> String {
+    format!(
+        "GRUB_CMDLINE_LINUX=\"$GRUB_CMDLINE_LINUX {args}\"\n\
+         GRUB_TERMINAL=\"console serial\"\n\
+         GRUB_SERIAL_COMMAND=\"serial --unit={unit} --speed={baud} --word=8 --parity=no --stop=1\"\n",
+        args = serial.kernel_args().join(" "),
+        baud = serial.baud,
+        unit = serial.unit().unwrap_or(0)
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::loader::parse_section;
+
+    fn parse(yaml: &str) -> HeadlessConfig {
+        parse_section::<HeadlessConfig>(yaml, "headless").unwrap()
+    }
+
+    #[test]
+    fn test_empty_section_writes_nothing() {
+        let config = parse("hostname: a\nheadless: {}\n");
+        assert!(config.is_empty());
+        assert!(config.validate().is_ok());
+        assert!(config.build_apply_commands("/mnt/targetos").is_empty());
+    }
+
+    #[test]
+    fn test_apply_commands() {
+        let config = parse(
+            "headless:\n  serial_console:\n    device: ttyS4\n  watchdog:\n    module: iTCO_wdt\n  rtc: utc\n",
+        );
+        assert!(config.validate().is_ok());
+        let commands = config.build_apply_commands("/mnt/targetos/");
+        assert_eq!(commands.len(), 5);
+        assert!(commands[0].starts_with(
+            "mkdir -p /mnt/targetos/etc/default/grub.d && cat > /mnt/targetos/etc/default/grub.d/60-autoinstall-serial.cfg"
+        ));
+        assert!(commands[0].contains("console=tty0 console=ttyS4,115200n8"));
+        assert!(commands[0].contains("serial --unit=4 --speed=115200"));
+        assert_eq!(
+            commands[1],
+            "chroot /mnt/targetos systemctl enable serial-getty@ttyS4.service"
+        );
+        assert!(commands[2].contains("RuntimeWatchdogSec=30\nRebootWatchdogSec=600\n"));
+        assert!(commands[3].contains("60-autoinstall-watchdog.conf"));
+        assert!(commands[3].contains("\niTCO_wdt\n"));
+        assert_eq!(
+            commands[4],
+            "printf '0.0 0 0.0\\n0\\nUTC\\n' > /mnt/targetos/etc/adjtime"
+        );
+    }
+
+    #[test]
+    fn test_validate_rejects_bad_values() {
+        let bad = [
+            "headless:\n  serial_console:\n    device: ttyUSB0\n",
+            "headless:\n  serial_console:\n    baud: 115201\n",
+            "headless:\n  watchdog:\n    module: \"iTCO_wdt; reboot\"\n",
+            "headless:\n  watchdog:\n    runtime_secs: 0\n",
+        ];
+        for yaml in bad {
+            assert!(parse(yaml).validate().is_err(), "{}", yaml);
+        }
+        assert!(parse_section::<HeadlessConfig>("headless:\n  rtc: gmt\n", "headless").is_err());
+    }
+}
@@ -0,0 +1,119 @@
+// file: src/config/health_gate.rs
+// version: 1.1.0
+// guid: 8d3f1b62-7e4c-4a95-b2d8-5c0e9f6a1d37
+
+//! Go/no-go gate before the install disk is wiped (`health_gate:` section of a target config)
+//!
+//! Network, disk health, clock skew, mirror reachability and memory are each scored from 0 to 1
+//! and combined into a score out of 100 using these weights. An install whose score is below
+//! `min_score` stops before Phase 2 instead of failing part-way through with the disk wiped.
+
+use crate::error::AutoInstallError;
+use crate::Result;
+use serde::{Deserialize, Serialize};
+
+/// Relative weight of each signal; a weight of 0 leaves the signal out of the score
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct HealthWeights {
+    pub network: u32,
+    pub disk_health: u32,
+    pub clock_skew: u32,
+    pub mirror: u32,
+    pub memory: u32,
+}
+
+impl Default for HealthWeights {
+    fn default() -> Self {
+        Self {
+            network: 30,
+            disk_health: 25,
+            clock_skew: 10,
+            mirror: 25,
+            memory: 10,
+        }
+    }
+}
+
+impl HealthWeights {
+    pub fn total(&self) -> u32 {
+        self.network + self.disk_health + self.clock_skew + self.mirror + self.memory
+    }
+}
+
+/// Score threshold and the limits the signals are scored against
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct HealthGateConfig {
+    /// Score the target before Phase 2
+    pub check: bool,
+    /// Lowest score (0-100) allowed to continue
+    pub min_score: u8,
+    /// Stop the install below `min_score`; warn only when false
+    pub enforce: bool,
+    pub weights: HealthWeights,
+    /// Clock difference to the controller, in seconds, that scores 0
+    pub max_clock_skew_secs: u64,
+    /// Memory, in MiB, that scores 1; less scores proportionally
+    pub min_memory_mb: u64,
+}
+
+impl Default for HealthGateConfig {
+    fn default() -> Self {
+        Self {
+            check: true,
+            min_score: 70,
+            enforce: true,
+            weights: HealthWeights::default(),
+            max_clock_skew_secs: 300,
+            min_memory_mb: 4096,
+        }
+    }
+}
+
+impl HealthGateConfig {
+    pub fn validate(&self) -> Result<()> {
+        if self.min_score > 100 {
+            return Err(AutoInstallError::ValidationError(format!(
+                "health_gate min_score {} is above 100",
+                self.min_score
+            )));
+        }
+        if self.check && self.weights.total() == 0 {
+            return Err(AutoInstallError::ValidationError(
+                "health_gate weights are all 0".to_string(),
+            ));
+        }
+        if self.max_clock_skew_secs == 0 || self.min_memory_mb == 0 {
+            return Err(AutoInstallError::ValidationError(
+                "health_gate max_clock_skew_secs and min_memory_mb must be at least 1".to_string(),
+            ));
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::loader::parse_section;
+
+    #[test]
+    fn test_weights_and_validate() {
+        let config = parse_section::<HealthGateConfig>(
+            "health_gate:\n  min_score: 85\n  weights: {clock_skew: 0}\n",
+            "health_gate",
+        )
+        .unwrap();
+        assert_eq!(config.min_score, 85);
+        assert_eq!(config.weights.clock_skew, 0);
+        assert_eq!(config.weights.total(), 90);
+        assert!(config.validate().is_ok());
+
+        let too_high = HealthGateConfig {
+            min_score: 101,
+            ..Default::default()
+        };
+        assert!(too_high.validate().is_err());
+    }
+}
@@ -0,0 +1,42 @@
+// file: src/config/host_vars.rs
+// version: 1.1.0
+// guid: 5f2a8d63-1e9c-4b07-9a4e-c3d6b8e1f729
+
+//! Per-host variables store (`host_vars:` section of a target config)
+//!
+//! The store itself always lives in `logs/<hostname>/host-vars.json`; this section only decides
+//! whether a copy is written into the installed system, for agents running on the host.
+
+use crate::error::AutoInstallError;
+use crate::Result;
+use serde::{Deserialize, Serialize};
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct HostVarsConfig {
+    /// Copy the variables into the target at the end of the install
+    pub push: bool,
+    /// Absolute path of the copy in the target
+    pub target_path: String,
+}
+
+impl Default for HostVarsConfig {
+    fn default() -> Self {
+        Self {
+            push: false,
+            target_path: "/etc/ubuntu-autoinstall-agent/host-vars.json".to_string(),
+        }
+    }
+}
+
+impl HostVarsConfig {
+    pub fn validate(&self) -> Result<()> {
+        if !self.target_path.starts_with('/') || self.target_path.contains(char::is_whitespace) {
+            return Err(AutoInstallError::ValidationError(format!(
+                "host_vars.target_path must be an absolute path without spaces: {}",
+                self.target_path
+            )));
+        }
+        Ok(())
+    }
+}
@@ -1,9 +1,10 @@
 // file: src/config/image.rs
-// version: 1.0.1
+// version: 1.5.0
 // guid: c3d4e5f6-g7h8-9012-3456-789012cdefgh
 
 //! Image specification and metadata structures
 
+use super::build_hooks::BuildHooks;
 use super::Architecture;
 use serde::{Deserialize, Serialize};
 use sha2::{Digest, Sha256};
@@ -17,12 +18,180 @@ pub struct ImageSpec {
     pub ubuntu_version: String,
     /// Target architecture
     pub architecture: Architecture,
-    /// Base packages to install in the image
+    /// Base packages to install in the image; `@role` entries map per architecture/release
     pub base_packages: Vec<String>,
     /// Custom scripts to run during image creation
     pub custom_scripts: Vec<PathBuf>,
     /// VM configuration for image building
     pub vm_config: VmConfig,
+    /// What kind of machine the image boots on
+    #[serde(default)]
+    pub flavor: ImageFlavor,
+    /// Provisioners run before boot, after the install and before generalization
+    #[serde(default, skip_serializing_if = "BuildHooks::is_empty")]
+    pub hooks: BuildHooks,
+}
+
+/// Boot flavor of a golden image
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum ImageFlavor {
+    /// UEFI/GRUB qcow2 image installed in a build VM
+    #[default]
+    Uefi,
+    /// Raw SD-card image for a Raspberry Pi or another single-board computer
+    Sbc(SbcConfig),
+}
+
+/// Single-board computers with a known boot layout
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum SbcBoard {
+    Rpi3,
+    Rpi4,
+    Rpi5,
+    /// Any board booting U-Boot from the SD card (`u_boot` required)
+    Generic,
+}
+
+impl SbcBoard {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Self::Rpi3 => "rpi3",
+            Self::Rpi4 => "rpi4",
+            Self::Rpi5 => "rpi5",
+            Self::Generic => "generic",
+        }
+    }
+
+    /// Raspberry Pi boards boot the kernel straight from the firmware partition
+    pub fn is_raspberry_pi(&self) -> bool {
+        !matches!(self, Self::Generic)
+    }
+}
+
+/// U-Boot written to the raw device of a generic board
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct UBootConfig {
+    /// Package that ships the board's U-Boot, e.g. `u-boot-sunxi`
+    pub package: String,
+    /// Image inside the root filesystem, e.g. `/usr/lib/u-boot/orangepi_zero2/u-boot-sunxi-with-spl.bin`
+    pub image: PathBuf,
+    /// Offset of the image on the device in KiB (8 for Allwinner boards)
+    #[serde(default = "default_u_boot_offset_kb")]
+    pub offset_kb: u64,
+    /// Device tree the kernel is started with, relative to the kernel's dtbs directory
+    pub fdt: String,
+}
+
+fn default_u_boot_offset_kb() -> u64 {
+    8
+}
+
+/// Layout and boot files of an SD-card image
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct SbcConfig {
+    pub board: SbcBoard,
+    /// Size of the raw image; the root filesystem grows to fill the card on first boot
+    pub image_size_gb: u32,
+    /// Size of the FAT firmware partition (`system-boot`)
+    pub firmware_size_mb: u32,
+    /// Kernel arguments appended to the defaults in `cmdline.txt`
+    pub cmdline: Vec<String>,
+    /// Lines appended to the `[all]` section of `config.txt`
+    pub config_txt: Vec<String>,
+    pub u_boot: Option<UBootConfig>,
+}
+
+impl Default for SbcConfig {
+    fn default() -> Self {
+        Self {
+            board: SbcBoard::Rpi4,
+            image_size_gb: 4,
+            firmware_size_mb: 512,
+            cmdline: Vec::new(),
+            config_txt: Vec::new(),
+            u_boot: None,
+        }
+    }
+}
+
+impl SbcConfig {
+    pub fn validate(&self, architecture: Architecture, ubuntu_version: &str) -> crate::Result<()> {
+        if architecture != Architecture::Arm64 {
+            return Err(crate::error::AutoInstallError::ValidationError(format!(
+                "SBC images are arm64 only, not {}",
+                architecture.as_str()
+            )));
+        }
+        if super::ubuntu_codename(ubuntu_version).is_none() {
+            return Err(crate::error::AutoInstallError::ValidationError(format!(
+                "SBC images need a known Ubuntu release, not {}",
+                ubuntu_version
+            )));
+        }
+        if self.image_size_gb < 2 {
+            return Err(crate::error::AutoInstallError::ValidationError(
+                "SBC image_size_gb must be at least 2".to_string(),
+            ));
+        }
+        if self.firmware_size_mb < 128 || self.firmware_size_mb >= self.image_size_gb * 512 {
+            return Err(crate::error::AutoInstallError::ValidationError(format!(
+                "SBC firmware_size_mb must be between 128 and half the image, not {}",
+                self.firmware_size_mb
+            )));
+        }
+        match (self.board, &self.u_boot) {
+            (SbcBoard::Generic, None) => Err(crate::error::AutoInstallError::ValidationError(
+                "SBC board 'generic' needs a u_boot section".to_string(),
+            )),
+            (board, Some(_)) if board.is_raspberry_pi() => {
+                Err(crate::error::AutoInstallError::ValidationError(format!(
+                    "SBC board '{}' boots from the Raspberry Pi firmware; u_boot is for 'generic' boards",
+                    board.as_str()
+                )))
+            }
+            _ => Ok(()),
+        }
+    }
+}
+
+/// QEMU machine type and firmware of a build VM
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum MachineProfile {
+    /// x86_64 q35 machine with OVMF (UEFI)
+    Q35Ovmf,
+    /// aarch64 virt machine with AAVMF (UEFI)
+    VirtAavmf,
+    /// x86_64 pc machine with QEMU's built-in SeaBIOS
+    LegacyBios,
+}
+
+impl MachineProfile {
+    /// Profile used when `VmConfig::machine` is not set
+    pub fn default_for(architecture: Architecture) -> Self {
+        match architecture {
+            Architecture::Amd64 => Self::Q35Ovmf,
+            Architecture::Arm64 => Self::VirtAavmf,
+        }
+    }
+
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Self::Q35Ovmf => "q35-ovmf",
+            Self::VirtAavmf => "virt-aavmf",
+            Self::LegacyBios => "legacy-bios",
+        }
+    }
+
+    pub fn architecture(&self) -> Architecture {
+        match self {
+            Self::Q35Ovmf | Self::LegacyBios => Architecture::Amd64,
+            Self::VirtAavmf => Architecture::Arm64,
+        }
+    }
 }
 
 /// Virtual machine configuration for image building
@@ -34,6 +203,12 @@ pub struct VmConfig {
     pub disk_size_gb: u32,
     /// Number of CPU cores
     pub cpu_cores: u32,
+    /// Machine profile; defaults to the UEFI profile of the image architecture
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub machine: Option<MachineProfile>,
+    /// Firmware code image to use instead of the one found on the host
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub firmware: Option<PathBuf>,
 }
 
 /// Metadata for a created golden image
@@ -61,10 +236,101 @@ impl Default for VmConfig {
             memory_mb: 2048,
             disk_size_gb: 20,
             cpu_cores: 2,
+            machine: None,
+            firmware: None,
         }
     }
 }
 
+/// Host capacity kept free for everything else while a build VM runs
+pub const RESERVED_HOST_CPUS: u32 = 1;
+/// Host memory (MB) kept free while a build VM runs
+pub const RESERVED_HOST_MEMORY_MB: u32 = 2048;
+/// Upper bound for automatically sized VMs; more does not speed up an install
+const MAX_AUTO_CPUS: u32 = 8;
+const MAX_AUTO_MEMORY_MB: u32 = 8192;
+
+/// Resources of the machine running the build
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct HostResources {
+    /// Logical CPUs
+    pub cpus: u32,
+    /// Available memory in MB
+    pub memory_mb: u32,
+    /// Whether `/dev/kvm` is usable
+    pub kvm: bool,
+}
+
+impl VmConfig {
+    /// Smallest VM the installer for `ubuntu_version` reliably completes in
+    pub fn minimum_for(ubuntu_version: &str) -> Self {
+        let release = ubuntu_version.split_once('.').and_then(|(major, minor)| {
+            Some((major.parse::<u32>().ok()?, minor.parse::<u32>().ok()?))
+        });
+        let (memory_mb, disk_size_gb) = match release {
+            Some(release) if release < (22, 4) => (1024, 10),
+            Some(release) if release < (24, 4) => (1536, 15),
+            // 24.04 and later, and anything unrecognised, get the current requirements
+            _ => (2048, 20),
+        };
+        Self {
+            memory_mb,
+            disk_size_gb,
+            cpu_cores: 1,
+            machine: None,
+            firmware: None,
+        }
+    }
+
+    /// Size a build VM for `host`, leaving [`RESERVED_HOST_CPUS`] and
+    /// [`RESERVED_HOST_MEMORY_MB`] free but never going below [`VmConfig::minimum_for`]
+    pub fn sized_for(host: &HostResources, ubuntu_version: &str) -> Self {
+        let minimum = Self::minimum_for(ubuntu_version);
+        Self {
+            memory_mb: host
+                .memory_mb
+                .saturating_sub(RESERVED_HOST_MEMORY_MB)
+                .clamp(minimum.memory_mb, MAX_AUTO_MEMORY_MB.max(minimum.memory_mb)),
+            disk_size_gb: minimum.disk_size_gb.max(Self::default().disk_size_gb),
+            cpu_cores: host
+                .cpus
+                .saturating_sub(RESERVED_HOST_CPUS)
+                .clamp(minimum.cpu_cores, MAX_AUTO_CPUS),
+            machine: None,
+            firmware: None,
+        }
+    }
+
+    /// Machine profile for a VM of `architecture`
+    pub fn machine_profile(&self, architecture: Architecture) -> MachineProfile {
+        self.machine
+            .unwrap_or_else(|| MachineProfile::default_for(architecture))
+    }
+
+    /// Ensure this configuration meets the minimums for `ubuntu_version`
+    pub fn check_minimums(&self, ubuntu_version: &str) -> crate::Result<()> {
+        let minimum = Self::minimum_for(ubuntu_version);
+        if self.memory_mb < minimum.memory_mb {
+            return Err(crate::error::AutoInstallError::ValidationError(format!(
+                "VM memory must be at least {} MB for Ubuntu {}",
+                minimum.memory_mb, ubuntu_version
+            )));
+        }
+        if self.disk_size_gb < minimum.disk_size_gb {
+            return Err(crate::error::AutoInstallError::ValidationError(format!(
+                "VM disk size must be at least {} GB for Ubuntu {}",
+                minimum.disk_size_gb, ubuntu_version
+            )));
+        }
+        if self.cpu_cores < minimum.cpu_cores {
+            return Err(crate::error::AutoInstallError::ValidationError(
+                "VM must have at least 1 CPU core".to_string(),
+            ));
+        }
+        Ok(())
+    }
+}
+
 impl ImageSpec {
     /// Validate image specification
     pub fn validate(&self) -> crate::Result<()> {
@@ -77,23 +343,23 @@ impl ImageSpec {
             )));
         }
 
-        // Validate VM configuration
-        if self.vm_config.memory_mb < 1024 {
-            return Err(crate::error::AutoInstallError::ValidationError(
-                "VM memory must be at least 1024 MB".to_string(),
-            ));
+        // Validate VM configuration; SD-card images are built without a VM
+        match &self.flavor {
+            ImageFlavor::Uefi => self.vm_config.check_minimums(&self.ubuntu_version)?,
+            ImageFlavor::Sbc(sbc) => sbc.validate(self.architecture, &self.ubuntu_version)?,
         }
 
-        if self.vm_config.disk_size_gb < 10 {
-            return Err(crate::error::AutoInstallError::ValidationError(
-                "VM disk size must be at least 10 GB".to_string(),
-            ));
-        }
+        // Validate package role references
+        self.resolved_packages()?;
 
-        if self.vm_config.cpu_cores == 0 {
-            return Err(crate::error::AutoInstallError::ValidationError(
-                "VM must have at least 1 CPU core".to_string(),
-            ));
+        if !self.hooks.is_empty() {
+            if let ImageFlavor::Sbc(_) = &self.flavor {
+                return Err(crate::error::AutoInstallError::ValidationError(
+                    "Build hooks need a build VM and are not supported for SD-card images"
+                        .to_string(),
+                ));
+            }
+            self.hooks.validate()?;
         }
 
         // Validate custom scripts exist
@@ -109,6 +375,15 @@ impl ImageSpec {
         Ok(())
     }
 
+    /// Base packages with `@role` entries resolved for this architecture and version
+    pub fn resolved_packages(&self) -> crate::Result<Vec<String>> {
+        super::packages::resolve_package_list(
+            &self.base_packages,
+            self.architecture,
+            &self.ubuntu_version,
+        )
+    }
+
     /// Create a minimal Ubuntu image specification
     pub fn minimal(ubuntu_version: String, architecture: Architecture) -> Self {
         Self {
@@ -123,6 +398,8 @@ impl ImageSpec {
             ],
             custom_scripts: vec![],
             vm_config: VmConfig::default(),
+            flavor: ImageFlavor::Uefi,
+            hooks: Default::default(),
         }
     }
 }
@@ -209,6 +486,19 @@ mod tests {
     use super::*;
     use std::path::PathBuf;
 
+    #[test]
+    fn test_resolved_packages_maps_roles() {
+        let mut spec = ImageSpec::minimal("24.04".to_string(), Architecture::Arm64);
+        spec.base_packages = vec!["@bootloader".to_string(), "vim".to_string()];
+        assert_eq!(
+            spec.resolved_packages().unwrap(),
+            vec!["grub-efi-arm64".to_string(), "vim".to_string()]
+        );
+
+        spec.base_packages.push("@unknown".to_string());
+        assert!(spec.validate().is_err());
+    }
+
     #[test]
     fn test_validate_image_spec_valid() {
         let spec = ImageSpec {
@@ -220,7 +510,11 @@ mod tests {
                 memory_mb: 2048,
                 disk_size_gb: 20,
                 cpu_cores: 2,
+                machine: None,
+                firmware: None,
             },
+            flavor: Default::default(),
+            hooks: Default::default(),
         };
         assert!(spec.validate().is_ok());
     }
@@ -233,6 +527,8 @@ mod tests {
             base_packages: vec![],
             custom_scripts: vec![],
             vm_config: VmConfig::default(),
+            flavor: Default::default(),
+            hooks: Default::default(),
         };
         let err = spec.validate().unwrap_err();
         assert!(err.to_string().contains("Invalid Ubuntu version format"));
@@ -249,12 +545,64 @@ mod tests {
                 memory_mb: 512,
                 disk_size_gb: 5,
                 cpu_cores: 0,
+                machine: None,
+                firmware: None,
             },
+            flavor: Default::default(),
+            hooks: Default::default(),
         };
         // Any of the constraints can fail; ensure we get an error
         assert!(spec.validate().is_err());
     }
 
+    #[test]
+    fn test_vm_minimums_per_release() {
+        assert_eq!(VmConfig::minimum_for("20.04").memory_mb, 1024);
+        assert_eq!(VmConfig::minimum_for("22.04").memory_mb, 1536);
+        assert_eq!(VmConfig::minimum_for("24.04").memory_mb, 2048);
+        assert_eq!(VmConfig::minimum_for("99.99").disk_size_gb, 20);
+
+        let small = VmConfig {
+            memory_mb: 1536,
+            disk_size_gb: 15,
+            cpu_cores: 1,
+            machine: None,
+            firmware: None,
+        };
+        assert!(small.check_minimums("22.04").is_ok());
+        assert!(small.check_minimums("24.04").is_err());
+    }
+
+    #[test]
+    fn test_vm_sized_for_host() {
+        let big = HostResources {
+            cpus: 32,
+            memory_mb: 64000,
+            kvm: true,
+        };
+        let sized = VmConfig::sized_for(&big, "24.04");
+        assert_eq!((sized.cpu_cores, sized.memory_mb), (8, 8192));
+
+        let laptop = HostResources {
+            cpus: 4,
+            memory_mb: 6144,
+            kvm: true,
+        };
+        let sized = VmConfig::sized_for(&laptop, "24.04");
+        assert_eq!((sized.cpu_cores, sized.memory_mb), (3, 4096));
+
+        let tiny = HostResources {
+            cpus: 1,
+            memory_mb: 1024,
+            kvm: false,
+        };
+        let sized = VmConfig::sized_for(&tiny, "24.04");
+        assert_eq!(
+            (sized.cpu_cores, sized.memory_mb, sized.disk_size_gb),
+            (1, 2048, 20)
+        );
+    }
+
     #[test]
     fn test_image_info_size_human() {
         let info = ImageInfo::new(
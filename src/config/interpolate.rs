@@ -0,0 +1,351 @@
+// file: src/config/interpolate.rs
+// version: 1.0.0
+// guid: 2b7e9c43-5a16-4f8d-8e21-d04c6b3f9a75
+
+//! `{{ facts.* }}` templates in target configs and presets
+//!
+//! Values discovered about the target at preflight can be referenced instead of being copied
+//! into every host's file, e.g. `disk_device: "{{ facts.largest_nvme }}"` or
+//! `arc_max_mb: "{{ facts.memory_mb / 2 }}"`. An expression is fact names and integer literals
+//! joined by `+ - * /` with parentheses; division truncates. Any reference to a fact that was
+//! not discovered is an error rather than an empty value.
+
+use crate::error::AutoInstallError;
+use crate::Result;
+use regex::{Captures, Regex};
+use std::collections::BTreeMap;
+use std::fmt;
+
+/// Prefix every template variable carries
+const FACTS_PREFIX: &str = "facts.";
+
+/// A discovered fact
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum FactValue {
+    Int(i64),
+    Text(String),
+}
+
+impl fmt::Display for FactValue {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            FactValue::Int(n) => write!(f, "{}", n),
+            FactValue::Text(s) => f.write_str(s),
+        }
+    }
+}
+
+/// Facts available to templates, keyed by name without the `facts.` prefix
+pub type FactVars = BTreeMap<String, FactValue>;
+
+#[derive(Debug, Clone, PartialEq)]
+enum Token {
+    Fact(String),
+    Int(i64),
+    Op(char),
+    Open,
+    Close,
+}
+
+fn template_regex() -> Result<Regex> {
+    // A template that is an entire quoted YAML scalar is matched with its quotes so an integer
+    // result can be written as a YAML number
+    Regex::new(r#""\{\{([^}]*)\}\}"|'\{\{([^}]*)\}\}'|\{\{([^}]*)\}\}"#)
+        .map_err(|e| AutoInstallError::ConfigError(format!("Invalid regex pattern: {}", e)))
+}
+
+/// Expand every template in `content`
+///
+/// Without `facts` (the target has not been investigated yet) any template is an error, as is
+/// a reference to a fact missing from `facts`; all missing names are reported together.
+pub fn render(content: &str, facts: Option<&FactVars>) -> Result<String> {
+    let re = template_regex()?;
+    if !re.is_match(content) {
+        return Ok(content.to_string());
+    }
+    let Some(facts) = facts else {
+        return Err(AutoInstallError::ConfigError(
+            "Config uses {{ facts.* }} templates, which are only available once the target has been investigated".to_string(),
+        ));
+    };
+
+    let mut undefined = Vec::new();
+    for cap in re.captures_iter(content) {
+        for token in tokenize(expression(&cap))? {
+            if let Token::Fact(name) = token {
+                if !facts.contains_key(&name) && !undefined.contains(&name) {
+                    undefined.push(name);
+                }
+            }
+        }
+    }
+    if !undefined.is_empty() {
+        return Err(AutoInstallError::ConfigError(format!(
+            "Undefined facts in config templates: {} (known: {})",
+            undefined
+                .iter()
+                .map(|n| format!("{}{}", FACTS_PREFIX, n))
+                .collect::<Vec<_>>()
+                .join(", "),
+            facts.keys().cloned().collect::<Vec<_>>().join(", ")
+        )));
+    }
+
+    let mut error = None;
+    let rendered = re.replace_all(content, |cap: &Captures| {
+        match evaluate(expression(cap), facts) {
+            Ok(value) => quote_like(cap, &value),
+            Err(e) => {
+                error.get_or_insert(e);
+                String::new()
+            }
+        }
+    });
+    match error {
+        Some(e) => Err(e),
+        None => Ok(rendered.into_owned()),
+    }
+}
+
+/// Evaluate one template expression, e.g. `facts.memory_mb / 2`
+pub fn evaluate(expression: &str, facts: &FactVars) -> Result<FactValue> {
+    let tokens = tokenize(expression)?;
+    let mut parser = Parser {
+        tokens: &tokens,
+        pos: 0,
+        facts,
+        expression,
+    };
+    let value = parser.sum()?;
+    if parser.pos != tokens.len() {
+        return Err(parser.invalid("unexpected trailing input"));
+    }
+    Ok(value)
+}
+
+fn expression<'c>(cap: &'c Captures) -> &'c str {
+    cap.get(1)
+        .or_else(|| cap.get(2))
+        .or_else(|| cap.get(3))
+        .map(|m| m.as_str())
+        .unwrap_or("")
+}
+
+/// Replacement text keeping a quoted scalar quoted, except for integers
+fn quote_like(cap: &Captures, value: &FactValue) -> String {
+    match (value, cap.get(1).is_some(), cap.get(2).is_some()) {
+        (FactValue::Int(n), _, _) => n.to_string(),
+        (FactValue::Text(s), true, _) => {
+            format!("\"{}\"", s.replace('\\', "\\\\").replace('"', "\\\""))
+        }
+        (FactValue::Text(s), _, true) => format!("'{}'", s.replace('\'', "''")),
+        (FactValue::Text(s), _, _) => s.clone(),
+    }
+}
+
+fn tokenize(expression: &str) -> Result<Vec<Token>> {
+    let invalid = |msg: String| {
+        AutoInstallError::ConfigError(format!(
+            "Invalid config template '{{{{{}}}}}': {}",
+            expression, msg
+        ))
+    };
+    let mut tokens = Vec::new();
+    let mut chars = expression.chars().peekable();
+    while let Some(&c) = chars.peek() {
+        match c {
+            ' ' | '\t' => {
+                chars.next();
+            }
+            '+' | '-' | '*' | '/' => {
+                tokens.push(Token::Op(c));
+                chars.next();
+            }
+            '(' => {
+                tokens.push(Token::Open);
+                chars.next();
+            }
+            ')' => {
+                tokens.push(Token::Close);
+                chars.next();
+            }
+            '0'..='9' => {
+                let mut digits = String::new();
+                while let Some(&d) = chars.peek().filter(|d| d.is_ascii_digit()) {
+                    digits.push(d);
+                    chars.next();
+                }
+                let n = digits
+                    .parse()
+                    .map_err(|_| invalid(format!("number {} is too large", digits)))?;
+                tokens.push(Token::Int(n));
+            }
+            c if c.is_ascii_alphabetic() || c == '_' => {
+                let mut name = String::new();
+                while let Some(&d) = chars
+                    .peek()
+                    .filter(|d| d.is_ascii_alphanumeric() || matches!(d, '_' | '.'))
+                {
+                    name.push(d);
+                    chars.next();
+                }
+                match name.strip_prefix(FACTS_PREFIX) {
+                    Some(fact) if !fact.is_empty() => tokens.push(Token::Fact(fact.to_string())),
+                    _ => {
+                        return Err(invalid(format!(
+                            "'{}' is not a fact; variables are written facts.<name>",
+                            name
+                        )))
+                    }
+                }
+            }
+            other => return Err(invalid(format!("unexpected '{}'", other))),
+        }
+    }
+    if tokens.is_empty() {
+        return Err(invalid("empty expression".to_string()));
+    }
+    Ok(tokens)
+}
+
+/// Recursive-descent evaluator; `*` and `/` bind tighter than `+` and `-`
+struct Parser<'t> {
+    tokens: &'t [Token],
+    pos: usize,
+    facts: &'t FactVars,
+    expression: &'t str,
+}
+
+impl Parser<'_> {
+    fn invalid(&self, msg: &str) -> AutoInstallError {
+        AutoInstallError::ConfigError(format!(
+            "Invalid config template '{{{{{}}}}}': {}",
+            self.expression, msg
+        ))
+    }
+
+    fn sum(&mut self) -> Result<FactValue> {
+        let mut left = self.product()?;
+        while let Some(Token::Op(op @ ('+' | '-'))) = self.tokens.get(self.pos) {
+            self.pos += 1;
+            let right = self.product()?;
+            left = self.arithmetic(*op, left, right)?;
+        }
+        Ok(left)
+    }
+
+    fn product(&mut self) -> Result<FactValue> {
+        let mut left = self.operand()?;
+        while let Some(Token::Op(op @ ('*' | '/'))) = self.tokens.get(self.pos) {
+            self.pos += 1;
+            let right = self.operand()?;
+            left = self.arithmetic(*op, left, right)?;
+        }
+        Ok(left)
+    }
+
+    fn operand(&mut self) -> Result<FactValue> {
+        let token = self
+            .tokens
+            .get(self.pos)
+            .ok_or_else(|| self.invalid("expression ends early"))?;
+        self.pos += 1;
+        match token {
+            Token::Int(n) => Ok(FactValue::Int(*n)),
+            Token::Fact(name) => self.facts.get(name).cloned().ok_or_else(|| {
+                AutoInstallError::ConfigError(format!("Undefined fact {}{}", FACTS_PREFIX, name))
+            }),
+            Token::Open => {
+                let value = self.sum()?;
+                match self.tokens.get(self.pos) {
+                    Some(Token::Close) => {
+                        self.pos += 1;
+                        Ok(value)
+                    }
+                    _ => Err(self.invalid("missing ')'")),
+                }
+            }
+            Token::Op(op) => Err(self.invalid(&format!("unexpected '{}'", op))),
+            Token::Close => Err(self.invalid("unexpected ')'")),
+        }
+    }
+
+    fn arithmetic(&self, op: char, left: FactValue, right: FactValue) -> Result<FactValue> {
+        let (FactValue::Int(a), FactValue::Int(b)) = (&left, &right) else {
+            return Err(self.invalid(&format!(
+                "'{}' needs numbers, got '{}' and '{}'",
+                op, left, right
+            )));
+        };
+        let result = match op {
+            '+' => a.checked_add(*b),
+            '-' => a.checked_sub(*b),
+            '*' => a.checked_mul(*b),
+            _ if *b == 0 => return Err(self.invalid("division by zero")),
+            _ => a.checked_div(*b),
+        };
+        result
+            .map(FactValue::Int)
+            .ok_or_else(|| self.invalid("integer overflow"))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn facts() -> FactVars {
+        let mut facts = FactVars::new();
+        facts.insert("memory_mb".into(), FactValue::Int(16384));
+        facts.insert("cpus".into(), FactValue::Int(8));
+        facts.insert(
+            "largest_nvme".into(),
+            FactValue::Text("/dev/nvme1n1".into()),
+        );
+        facts
+    }
+
+    #[test]
+    fn test_evaluate_arithmetic_and_precedence() {
+        let facts = facts();
+        assert_eq!(
+            evaluate("facts.memory_mb / 2", &facts).unwrap(),
+            FactValue::Int(8192)
+        );
+        assert_eq!(
+            evaluate("facts.cpus - 2 * 3", &facts).unwrap(),
+            FactValue::Int(2)
+        );
+        assert_eq!(
+            evaluate("(facts.cpus - 2) * 3", &facts).unwrap(),
+            FactValue::Int(18)
+        );
+        assert!(evaluate("facts.cpus / 0", &facts).is_err());
+        assert!(evaluate("facts.largest_nvme + 1", &facts).is_err());
+        assert!(evaluate("(facts.cpus", &facts).is_err());
+        assert!(evaluate("env.HOME", &facts).is_err());
+    }
+
+    #[test]
+    fn test_render_keeps_yaml_types() {
+        let content = "disk_device: \"{{ facts.largest_nvme }}\"\nzfs_tuning:\n  arc_max_mb: \"{{ facts.memory_mb / 2 }}\"\nnote: 'cpus={{facts.cpus}}'\n";
+        let rendered = render(content, Some(&facts())).unwrap();
+        assert_eq!(
+            rendered,
+            "disk_device: \"/dev/nvme1n1\"\nzfs_tuning:\n  arc_max_mb: 8192\nnote: 'cpus=8'\n"
+        );
+        assert_eq!(render("plain: value\n", None).unwrap(), "plain: value\n");
+    }
+
+    #[test]
+    fn test_render_is_strict() {
+        let err = render(
+            "a: \"{{ facts.largest_hdd }}\"\nb: \"{{ facts.gpu }}\"\n",
+            Some(&facts()),
+        )
+        .unwrap_err()
+        .to_string();
+        assert!(err.contains("facts.largest_hdd, facts.gpu"), "{}", err);
+        assert!(render("a: \"{{ facts.cpus }}\"\n", None).is_err());
+    }
+}
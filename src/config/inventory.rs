@@ -0,0 +1,339 @@
+// file: src/config/inventory.rs
+// version: 1.3.0
+// guid: 6a3d8f25-1e74-4b9c-92d0-c5b7e4a1f608
+
+//! Fleet inventory: the hosts `fleet` commands act on and how a rollout proceeds
+//!
+//! ```yaml
+//! defaults:
+//!   username: ubuntu
+//!   target_config: targets/common.yaml
+//! hosts:
+//!   - hostname: web-01
+//!     host: 10.0.0.11
+//!     canary: true
+//!     labels: { role: web, rack: "12" }
+//!   - hostname: web-02
+//!     preset: web-large
+//!     labels: { role: worker, rack: "14" }
+//! rollout:
+//!   canaries: 1
+//!   batch_size: 4
+//!   max_unavailable: 2
+//! ```
+//!
+//! An optional `dhcp:` section configures `fleet dhcp` (see [`crate::config::dhcp`]).
+//!
+//! Labels let fleet commands act on a subset of the hosts: `--selector 'role=worker,rack!=12'`
+//! keeps the hosts matching every requirement (see [`HostSelector`]).
+
+use crate::config::dhcp::DhcpConfig;
+use crate::error::AutoInstallError;
+use serde::{Deserialize, Serialize};
+use std::collections::{BTreeMap, HashSet};
+
+/// Settings every host inherits unless it sets its own
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct HostDefaults {
+    pub username: Option<String>,
+    pub preset: Option<String>,
+    pub target_config: Option<String>,
+    /// Labels of every host; a host's own labels win
+    pub labels: BTreeMap<String, String>,
+}
+
+/// One machine in the inventory
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct InventoryHost {
+    /// Hostname the machine is installed as; also names its `logs/<hostname>` records
+    pub hostname: String,
+    /// Address to connect to; the hostname when absent
+    #[serde(default)]
+    pub host: Option<String>,
+    #[serde(default)]
+    pub username: Option<String>,
+    #[serde(default)]
+    pub preset: Option<String>,
+    #[serde(default)]
+    pub target_config: Option<String>,
+    /// Deploy this host in the canary stage
+    #[serde(default)]
+    pub canary: bool,
+    /// Hardware address `fleet dhcp` leases `host` to
+    #[serde(default)]
+    pub mac: Option<String>,
+    /// Free-form `key: value` pairs matched by `--selector`, e.g. `role: worker`
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    pub labels: BTreeMap<String, String>,
+}
+
+impl InventoryHost {
+    /// Address commands connect to
+    pub fn address(&self) -> &str {
+        self.host.as_deref().unwrap_or(&self.hostname)
+    }
+}
+
+/// One requirement of a [`HostSelector`]
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum LabelRequirement {
+    Equals(String, String),
+    NotEquals(String, String),
+    Exists(String),
+    Absent(String),
+}
+
+impl LabelRequirement {
+    fn matches(&self, labels: &BTreeMap<String, String>) -> bool {
+        match self {
+            Self::Equals(key, value) => labels.get(key) == Some(value),
+            // Like Kubernetes selectors, a host without the label matches `key!=value`
+            Self::NotEquals(key, value) => labels.get(key) != Some(value),
+            Self::Exists(key) => labels.contains_key(key),
+            Self::Absent(key) => !labels.contains_key(key),
+        }
+    }
+}
+
+/// Label selector of fleet commands: comma-separated `key=value`, `key!=value`, `key` and `!key`
+/// requirements, all of which must hold
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct HostSelector {
+    pub requirements: Vec<LabelRequirement>,
+}
+
+impl HostSelector {
+    pub fn parse(selector: &str) -> crate::Result<Self> {
+        let invalid = |part: &str| {
+            AutoInstallError::ValidationError(format!(
+                "Invalid selector requirement '{}' in '{}'",
+                part, selector
+            ))
+        };
+        let valid_key = |key: &str| {
+            !key.is_empty()
+                && key
+                    .chars()
+                    .all(|c| c.is_ascii_alphanumeric() || "-_./".contains(c))
+        };
+        let mut requirements = Vec::new();
+        for part in selector.split(',').map(str::trim) {
+            let requirement = if let Some((key, value)) = part.split_once("!=") {
+                LabelRequirement::NotEquals(key.trim().to_string(), value.trim().to_string())
+            } else if let Some((key, value)) = part.split_once("==").or(part.split_once('=')) {
+                LabelRequirement::Equals(key.trim().to_string(), value.trim().to_string())
+            } else if let Some(key) = part.strip_prefix('!') {
+                LabelRequirement::Absent(key.trim().to_string())
+            } else {
+                LabelRequirement::Exists(part.to_string())
+            };
+            let key = match &requirement {
+                LabelRequirement::Equals(key, _)
+                | LabelRequirement::NotEquals(key, _)
+                | LabelRequirement::Exists(key)
+                | LabelRequirement::Absent(key) => key,
+            };
+            if !valid_key(key) {
+                return Err(invalid(part));
+            }
+            requirements.push(requirement);
+        }
+        Ok(Self { requirements })
+    }
+
+    pub fn matches(&self, host: &InventoryHost) -> bool {
+        self.matches_labels(&host.labels)
+    }
+
+    /// Whether `labels` meet every requirement; also used for pipeline stage conditions
+    pub fn matches_labels(&self, labels: &BTreeMap<String, String>) -> bool {
+        self.requirements.iter().all(|r| r.matches(labels))
+    }
+}
+
+/// What happens once the canaries are through
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Promotion {
+    /// Continue when canary failures stay within `canary_failure_threshold`
+    #[default]
+    Auto,
+    /// Show the canary results and ask before continuing
+    Prompt,
+}
+
+/// Canary stage, batching and availability limits for a fleet deployment
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct RolloutPolicy {
+    /// Hosts deployed first when none are marked `canary: true`; 0 skips the canary stage
+    pub canaries: usize,
+    pub promotion: Promotion,
+    /// Canaries that may fail before the rollout is aborted
+    pub canary_failure_threshold: usize,
+    /// Hosts per batch after the canaries
+    pub batch_size: usize,
+    /// Pause between batches, in seconds
+    pub batch_delay_secs: u64,
+    /// Hosts that may be down at once: installing plus failed; the rollout stops when failures reach it
+    pub max_unavailable: usize,
+    /// Count a host as failed when any hardening compliance check failed
+    pub require_compliance: bool,
+}
+
+impl Default for RolloutPolicy {
+    fn default() -> Self {
+        Self {
+            canaries: 1,
+            promotion: Promotion::Auto,
+            canary_failure_threshold: 0,
+            batch_size: 1,
+            batch_delay_secs: 0,
+            max_unavailable: 1,
+            require_compliance: true,
+        }
+    }
+}
+
+/// Hosts and rollout policy read from an inventory file
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct FleetInventory {
+    #[serde(default)]
+    pub defaults: HostDefaults,
+    pub hosts: Vec<InventoryHost>,
+    #[serde(default)]
+    pub rollout: RolloutPolicy,
+    /// Embedded DHCP server for the provisioning network
+    #[serde(default)]
+    pub dhcp: Option<DhcpConfig>,
+}
+
+impl FleetInventory {
+    /// Check for an empty or ambiguous host list and an unusable rollout policy
+    pub fn validate(&self) -> crate::Result<()> {
+        if self.hosts.is_empty() {
+            return Err(AutoInstallError::ValidationError(
+                "Inventory lists no hosts".to_string(),
+            ));
+        }
+        let mut seen = HashSet::new();
+        for host in &self.hosts {
+            if host.hostname.is_empty() {
+                return Err(AutoInstallError::ValidationError(
+                    "Inventory host with an empty hostname".to_string(),
+                ));
+            }
+            if !seen.insert(host.hostname.as_str()) {
+                return Err(AutoInstallError::ValidationError(format!(
+                    "Inventory lists {} more than once",
+                    host.hostname
+                )));
+            }
+        }
+        if self.rollout.batch_size == 0 || self.rollout.max_unavailable == 0 {
+            return Err(AutoInstallError::ValidationError(
+                "rollout.batch_size and rollout.max_unavailable must be at least 1".to_string(),
+            ));
+        }
+        if let Some(dhcp) = &self.dhcp {
+            dhcp.validate()?;
+            dhcp.static_leases(&self.hosts)?;
+        }
+        Ok(())
+    }
+
+    /// Every host with the inventory defaults filled in
+    pub fn resolved_hosts(&self) -> Vec<InventoryHost> {
+        self.hosts
+            .iter()
+            .map(|host| InventoryHost {
+                username: host.username.clone().or(self.defaults.username.clone()),
+                preset: host.preset.clone().or(self.defaults.preset.clone()),
+                target_config: host
+                    .target_config
+                    .clone()
+                    .or(self.defaults.target_config.clone()),
+                labels: self
+                    .defaults
+                    .labels
+                    .clone()
+                    .into_iter()
+                    .chain(host.labels.clone())
+                    .collect(),
+                ..host.clone()
+            })
+            .collect()
+    }
+
+    /// Resolved hosts matching `selector`; errors when none do
+    pub fn selected_hosts(&self, selector: &HostSelector) -> crate::Result<Vec<InventoryHost>> {
+        let hosts: Vec<InventoryHost> = self
+            .resolved_hosts()
+            .into_iter()
+            .filter(|host| selector.matches(host))
+            .collect();
+        if hosts.is_empty() {
+            return Err(AutoInstallError::ValidationError(
+                "No inventory host matches the selector".to_string(),
+            ));
+        }
+        Ok(hosts)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_and_resolve_defaults() {
+        let inventory: FleetInventory = serde_yaml::from_str(
+            "defaults:\n  username: ubuntu\n  preset: web\nhosts:\n  - hostname: web-01\n    host: 10.0.0.11\n  - hostname: web-02\n    preset: web-large\n    canary: true\n",
+        )
+        .unwrap();
+        assert!(inventory.validate().is_ok());
+        assert_eq!(inventory.rollout, RolloutPolicy::default());
+
+        let hosts = inventory.resolved_hosts();
+        assert_eq!(hosts[0].address(), "10.0.0.11");
+        assert_eq!(hosts[0].preset.as_deref(), Some("web"));
+        assert_eq!(hosts[1].address(), "web-02");
+        assert_eq!(hosts[1].username.as_deref(), Some("ubuntu"));
+        assert_eq!(hosts[1].preset.as_deref(), Some("web-large"));
+        assert!(hosts[1].canary);
+    }
+
+    #[test]
+    fn test_selector_matches_resolved_labels() {
+        let inventory: FleetInventory = serde_yaml::from_str(
+            "defaults:\n  labels: { role: worker }\nhosts:\n  - hostname: a\n    labels: { rack: \"12\" }\n  - hostname: b\n    labels: { rack: \"14\", gpu: \"yes\" }\n  - hostname: c\n    labels: { role: db }\n",
+        )
+        .unwrap();
+        let names = |selector: &str| {
+            inventory
+                .selected_hosts(&HostSelector::parse(selector).unwrap())
+                .map(|hosts| hosts.into_iter().map(|h| h.hostname).collect::<Vec<_>>())
+        };
+        assert_eq!(names("role=worker,rack!=12").unwrap(), ["b"]);
+        assert_eq!(names("role==worker").unwrap(), ["a", "b"]);
+        assert_eq!(names("!gpu").unwrap(), ["a", "c"]);
+        assert_eq!(names("gpu,role=worker").unwrap(), ["b"]);
+        assert!(names("role=web").is_err());
+        assert!(HostSelector::parse("role=worker,").is_err());
+        assert!(HostSelector::parse("=x").is_err());
+    }
+
+    #[test]
+    fn test_validate_rejects_duplicates_and_zero_limits() {
+        let mut inventory: FleetInventory =
+            serde_yaml::from_str("hosts:\n  - hostname: a\n  - hostname: a\n").unwrap();
+        assert!(inventory.validate().is_err());
+        inventory.hosts.pop();
+        assert!(inventory.validate().is_ok());
+        inventory.rollout.max_unavailable = 0;
+        assert!(inventory.validate().is_err());
+        let empty: FleetInventory = serde_yaml::from_str("hosts: []\n").unwrap();
+        assert!(empty.validate().is_err());
+    }
+}
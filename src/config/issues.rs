@@ -0,0 +1,154 @@
+// file: src/config/issues.rs
+// version: 1.1.0
+// guid: 4b8e1d73-6a29-4f05-9c3e-8d2a7f6b1e54
+
+//! Issue tracker for failed installs (`issues:` section of a target config)
+//!
+//! When `repository` is set, a failed `ssh-install` opens an issue there with the redacted
+//! failure summary and a link to the support bundle, or comments on the issue it opened for
+//! the same host earlier while that one is still open:
+//!
+//! ```yaml
+//! issues:
+//!   provider: gitlab                  # github (default) or gitlab
+//!   repository: infra/installs        # owner/name, or the GitLab project path
+//!   token_env: INSTALL_ISSUES_TOKEN   # default GITHUB_TOKEN or GITLAB_TOKEN
+//!   labels: [install-failure]
+//!   bundle_url: https://artifacts.example.com/bundles/{bundle}
+//! ```
+
+use crate::error::AutoInstallError;
+use crate::Result;
+use serde::{Deserialize, Serialize};
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum IssueProvider {
+    #[default]
+    Github,
+    Gitlab,
+}
+
+impl IssueProvider {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            IssueProvider::Github => "github",
+            IssueProvider::Gitlab => "gitlab",
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct IssueConfig {
+    pub provider: IssueProvider,
+    /// Repository or project issues are opened in; nothing is opened without one
+    pub repository: Option<String>,
+    /// Environment variable holding the API token
+    pub token_env: Option<String>,
+    /// REST API root, for GitHub Enterprise Server or a self-hosted GitLab
+    pub api_url: Option<String>,
+    pub labels: Vec<String>,
+    /// Where the support bundle can be downloaded; `{bundle}` is its file name. Without it
+    /// the issue names the bundle's path on the controller.
+    pub bundle_url: Option<String>,
+}
+
+impl Default for IssueConfig {
+    fn default() -> Self {
+        Self {
+            provider: IssueProvider::default(),
+            repository: None,
+            token_env: None,
+            api_url: None,
+            labels: vec!["install-failure".to_string()],
+            bundle_url: None,
+        }
+    }
+}
+
+impl IssueConfig {
+    pub fn is_enabled(&self) -> bool {
+        self.repository.is_some()
+    }
+
+    /// Variable the token is read from
+    pub fn token_variable(&self) -> &str {
+        match (&self.token_env, self.provider) {
+            (Some(name), _) => name,
+            (None, IssueProvider::Github) => "GITHUB_TOKEN",
+            (None, IssueProvider::Gitlab) => "GITLAB_TOKEN",
+        }
+    }
+
+    /// API root without a trailing slash
+    pub fn api_root(&self) -> String {
+        let root = match (&self.api_url, self.provider) {
+            (Some(url), _) => url.as_str(),
+            (None, IssueProvider::Github) => "https://api.github.com",
+            (None, IssueProvider::Gitlab) => "https://gitlab.com/api/v4",
+        };
+        root.trim_end_matches('/').to_string()
+    }
+
+    pub fn validate(&self) -> Result<()> {
+        if let Some(repository) = &self.repository {
+            let parts: Vec<&str> = repository.split('/').collect();
+            let nested_ok = self.provider == IssueProvider::Gitlab || parts.len() == 2;
+            if parts.len() < 2 || !nested_ok || parts.iter().any(|p| p.is_empty()) {
+                return Err(AutoInstallError::ValidationError(format!(
+                    "issues.repository '{}' must be owner/name (GitLab also takes group/sub/name)",
+                    repository
+                )));
+            }
+        }
+        for url in self.api_url.iter().chain(&self.bundle_url) {
+            if !url.starts_with("https://") && !url.starts_with("http://") {
+                return Err(AutoInstallError::ValidationError(format!(
+                    "issues URL '{}' must be an http(s) URL",
+                    url
+                )));
+            }
+        }
+        if let Some(url) = &self.bundle_url {
+            if !url.contains("{bundle}") {
+                return Err(AutoInstallError::ValidationError(
+                    "issues.bundle_url must contain {bundle}".to_string(),
+                ));
+            }
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::loader::parse_section;
+
+    #[test]
+    fn test_provider_defaults_and_validation() {
+        let config = parse_section::<IssueConfig>(
+            "issues:\n  provider: gitlab\n  repository: infra/ops/installs\n",
+            "issues",
+        )
+        .unwrap();
+        assert!(config.validate().is_ok());
+        assert!(config.is_enabled());
+        assert_eq!(config.token_variable(), "GITLAB_TOKEN");
+        assert_eq!(config.api_root(), "https://gitlab.com/api/v4");
+        assert_eq!(config.labels, ["install-failure"]);
+
+        let github = IssueConfig {
+            repository: Some("infra/ops/installs".to_string()),
+            ..IssueConfig::default()
+        };
+        assert!(github.validate().is_err());
+        let bad_link = IssueConfig {
+            bundle_url: Some("https://artifacts.example.com/x.tar.gz".to_string()),
+            ..IssueConfig::default()
+        };
+        assert!(bad_link.validate().is_err());
+        assert!(!IssueConfig::default().is_enabled());
+    }
+}
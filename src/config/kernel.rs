@@ -0,0 +1,251 @@
+// file: src/config/kernel.rs
+// version: 1.1.0
+// guid: 3e7c1a95-8b42-4d06-9f1e-6a2d5b8c4e07
+
+//! Kernel tuning configuration (`kernel:` section of a target config)
+//!
+//! Rendered into `/etc/sysctl.d`, `/etc/modules-load.d` and `/etc/modprobe.d` on the
+//! installed system so the first boot already runs with the tuned settings.
+
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+
+/// sysctl key prefixes accepted in `kernel.sysctl`
+const KNOWN_SYSCTL_PREFIXES: &[&str] = &[
+    "kernel.",
+    "vm.",
+    "fs.",
+    "net.core.",
+    "net.ipv4.",
+    "net.ipv6.",
+    "net.netfilter.",
+    "net.bridge.",
+    "user.",
+];
+
+/// File written under `/etc/sysctl.d`
+pub const SYSCTL_FILE: &str = "etc/sysctl.d/90-autoinstall.conf";
+/// File written under `/etc/modules-load.d`
+pub const MODULES_LOAD_FILE: &str = "etc/modules-load.d/autoinstall.conf";
+/// File written under `/etc/modprobe.d`
+pub const MODPROBE_FILE: &str = "etc/modprobe.d/autoinstall.conf";
+
+/// Kernel parameters and module configuration for the installed system
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct KernelConfig {
+    /// sysctl settings, e.g. `vm.swappiness: "10"`
+    pub sysctl: BTreeMap<String, String>,
+    /// Modules loaded at boot
+    pub modules: Vec<String>,
+    /// Modules that must never load
+    pub blacklist: Vec<String>,
+    /// Module options, e.g. `zfs: "zfs_arc_max=4294967296"`
+    pub module_options: BTreeMap<String, String>,
+}
+
+impl KernelConfig {
+    /// Whether there is nothing to write
+    pub fn is_empty(&self) -> bool {
+        self.sysctl.is_empty()
+            && self.modules.is_empty()
+            && self.blacklist.is_empty()
+            && self.module_options.is_empty()
+    }
+
+    /// Validate sysctl keys against the known prefixes and module names against the kernel's naming rules
+    pub fn validate(&self) -> crate::Result<()> {
+        for (key, value) in &self.sysctl {
+            let well_formed = !key.is_empty()
+                && key
+                    .chars()
+                    .all(|c| c.is_ascii_alphanumeric() || matches!(c, '.' | '_' | '-'));
+            if !well_formed || !KNOWN_SYSCTL_PREFIXES.iter().any(|p| key.starts_with(p)) {
+                return Err(crate::error::AutoInstallError::ValidationError(format!(
+                    "Unknown sysctl key: {}",
+                    key
+                )));
+            }
+            if value.trim().is_empty() || value.contains('\n') {
+                return Err(crate::error::AutoInstallError::ValidationError(format!(
+                    "Invalid value for sysctl {}: {:?}",
+                    key, value
+                )));
+            }
+        }
+
+        let modules = self
+            .modules
+            .iter()
+            .chain(&self.blacklist)
+            .chain(self.module_options.keys());
+        for module in modules {
+            if module.is_empty()
+                || !module
+                    .chars()
+                    .all(|c| c.is_ascii_alphanumeric() || matches!(c, '_' | '-'))
+            {
+                return Err(crate::error::AutoInstallError::ValidationError(format!(
+                    "Invalid kernel module name: {}",
+                    module
+                )));
+            }
+        }
+        if let Some(module) = self.modules.iter().find(|m| self.blacklist.contains(m)) {
+            return Err(crate::error::AutoInstallError::ValidationError(format!(
+                "Kernel module {} is both loaded and blacklisted",
+                module
+            )));
+        }
+        for (module, options) in &self.module_options {
+            if options.trim().is_empty() || options.contains('\n') {
+                return Err(crate::error::AutoInstallError::ValidationError(format!(
+                    "Invalid options for module {}: {:?}",
+                    module, options
+                )));
+            }
+        }
+        Ok(())
+    }
+
+    /// Contents of the sysctl.d file
+    pub fn sysctl_conf(&self) -> String {
+        self.sysctl
+            .iter()
+            .map(|(k, v)| format!("{} = {}\n", k, v.trim()))
+            .collect()
+    }
+
+    /// Contents of the modules-load.d file
+    pub fn modules_load_conf(&self) -> String {
+        self.modules.iter().map(|m| format!("{}\n", m)).collect()
+    }
+
+    /// Contents of the modprobe.d file (blacklists and module options)
+    pub fn modprobe_conf(&self) -> String {
+        let mut conf: String = self
+            .blacklist
+            .iter()
+            .map(|m| format!("blacklist {}\ninstall {} /bin/false\n", m, m))
+            .collect();
+        for (module, options) in &self.module_options {
+            conf.push_str(&format!("options {} {}\n", module, options.trim()));
+        }
+        conf
+    }
+
+    /// Commands writing the config files under `root` and rebuilding the initramfs in its chroot
+    ///
+    /// Module options and blacklists must be in the initramfs to take effect for modules
+    /// loaded early (e.g. `zfs`), so the initramfs is regenerated when they change.
+    pub fn build_apply_commands(&self, root: &str) -> Vec<String> {
+        let root = root.trim_end_matches('/');
+        let files = [
+            (SYSCTL_FILE, self.sysctl_conf()),
+            (MODULES_LOAD_FILE, self.modules_load_conf()),
+            (MODPROBE_FILE, self.modprobe_conf()),
+        ];
+
+        let mut commands = Vec::new();
+        for (path, content) in files.iter().filter(|(_, c)| !c.is_empty()) {
+            let full = format!("{}/{}", root, path);
+            let dir = &full[..full.rfind('/').unwrap_or(0)];
+            commands.push(format!(
+                "mkdir -p {} && cat > {} << 'EOF'\n# Managed by ubuntu-autoinstall-agent\n{}EOF",
+                dir, full, content
+            ));
+        }
+        if !self.blacklist.is_empty() || !self.module_options.is_empty() {
+            commands.push(format!(
+                "chroot {} bash -lc 'update-initramfs -u -k all'",
+                root
+            ));
+        }
+        commands
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::loader::parse_section;
+
+    fn sample() -> KernelConfig {
+        let mut config = KernelConfig::default();
+        config
+            .sysctl
+            .insert("vm.swappiness".to_string(), "10".to_string());
+        config
+            .sysctl
+            .insert("net.core.somaxconn".to_string(), "4096".to_string());
+        config.modules.push("br_netfilter".to_string());
+        config.blacklist.push("floppy".to_string());
+        config
+            .module_options
+            .insert("zfs".to_string(), "zfs_arc_max=4294967296".to_string());
+        config
+    }
+
+    #[test]
+    fn test_validate_accepts_known_keys() {
+        assert!(sample().validate().is_ok());
+        assert!(KernelConfig::default().validate().is_ok());
+    }
+
+    #[test]
+    fn test_validate_rejects_bad_entries() {
+        let mut config = sample();
+        config
+            .sysctl
+            .insert("dev.raid.speed_limit_min".to_string(), "1".to_string());
+        assert!(config.validate().is_err());
+
+        let mut config = sample();
+        config.blacklist.push("br_netfilter".to_string());
+        assert!(config.validate().is_err());
+
+        let mut config = sample();
+        config.modules.push("evil; rm -rf /".to_string());
+        assert!(config.validate().is_err());
+    }
+
+    #[test]
+    fn test_rendered_files_and_commands() {
+        let config = sample();
+        assert_eq!(
+            config.sysctl_conf(),
+            "net.core.somaxconn = 4096\nvm.swappiness = 10\n"
+        );
+        assert_eq!(
+            config.modprobe_conf(),
+            "blacklist floppy\ninstall floppy /bin/false\noptions zfs zfs_arc_max=4294967296\n"
+        );
+
+        let cmds = config.build_apply_commands("/mnt/targetos/");
+        assert_eq!(cmds.len(), 4);
+        assert!(cmds[0].starts_with(
+            "mkdir -p /mnt/targetos/etc/sysctl.d && cat > /mnt/targetos/etc/sysctl.d/90-autoinstall.conf"
+        ));
+        assert_eq!(
+            cmds[3],
+            "chroot /mnt/targetos bash -lc 'update-initramfs -u -k all'"
+        );
+
+        let mut sysctl_only = KernelConfig::default();
+        sysctl_only
+            .sysctl
+            .insert("vm.swappiness".to_string(), "10".to_string());
+        assert_eq!(sysctl_only.build_apply_commands("/mnt/targetos").len(), 1);
+    }
+
+    #[test]
+    fn test_kernel_section_parses_from_target_yaml() {
+        let yaml = "hostname: host-a\nkernel:\n  sysctl:\n    vm.swappiness: \"10\"\n  blacklist: [floppy]\n";
+        let section: KernelConfig = parse_section(yaml, "kernel").unwrap();
+        assert_eq!(section.blacklist, vec!["floppy".to_string()]);
+        assert_eq!(section.sysctl.get("vm.swappiness").unwrap(), "10");
+
+        let empty: KernelConfig = parse_section("hostname: host-a\n", "kernel").unwrap();
+        assert!(empty.is_empty());
+    }
+}
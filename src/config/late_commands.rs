@@ -0,0 +1,354 @@
+// file: src/config/late_commands.rs
+// version: 1.3.0
+// guid: 8f1c5a39-2d74-4e6b-a0c8-5b9e3d7f2a16
+
+//! Operator scripts run in the target at the end of an install (`late_commands:` section)
+//!
+//! Each script runs in the chroot under `timeout`, with an environment reduced to a fixed
+//! `PATH`, `HOME` and `LANG` plus the variables it declares, from a chosen working directory
+//! and under `ulimit` limits. Scripts fetched from a URL must match `allowed_urls` or be marked
+//! `approved`, and are checked against `sha256` when one is given.
+
+use crate::error::AutoInstallError;
+use crate::utils::shell_quote;
+use crate::Result;
+use reqwest::Url;
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+
+/// Directory inside the target the scripts are written to
+pub const SCRIPT_DIR: &str = "root/.late-commands";
+
+/// Environment every script starts from
+pub const BASE_ENV: &[(&str, &str)] = &[
+    (
+        "PATH",
+        "/usr/local/sbin:/usr/local/bin:/usr/sbin:/usr/bin:/sbin:/bin",
+    ),
+    ("HOME", "/root"),
+    ("LANG", "C.UTF-8"),
+    ("DEBIAN_FRONTEND", "noninteractive"),
+];
+
+/// Exit status `timeout` reports when it had to stop the script
+pub const TIMEOUT_EXIT_CODE: i32 = 124;
+
+/// `ulimit` limits for one script; unset values are inherited
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct ScriptLimits {
+    /// Virtual memory in MiB (`ulimit -v`)
+    pub memory_mb: Option<u64>,
+    /// CPU time in seconds (`ulimit -t`)
+    pub cpu_secs: Option<u64>,
+    /// Open file descriptors (`ulimit -n`)
+    pub open_files: Option<u64>,
+    /// Processes of the user (`ulimit -u`)
+    pub processes: Option<u64>,
+}
+
+impl ScriptLimits {
+    /// `ulimit` commands applying the limits, joined with `;`
+    pub fn ulimit_commands(&self) -> String {
+        [
+            ("-v", self.memory_mb.map(|mb| mb * 1024)),
+            ("-t", self.cpu_secs),
+            ("-n", self.open_files),
+            ("-u", self.processes),
+        ]
+        .iter()
+        .filter_map(|(flag, value)| value.map(|v| format!("ulimit {} {}; ", flag, v)))
+        .collect()
+    }
+}
+
+/// One script and how it is confined
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct LateScript {
+    /// Name used for the script file and in the session record
+    pub name: String,
+    /// Inline bash script
+    pub run: Option<String>,
+    /// URL the script is downloaded from on the live system
+    pub url: Option<String>,
+    /// Expected SHA256 of the downloaded script
+    pub sha256: Option<String>,
+    /// Allow a URL outside `allowed_urls`
+    pub approved: bool,
+    /// Seconds before the script is stopped; the section's `timeout_secs` when unset
+    pub timeout_secs: Option<u64>,
+    /// Working directory inside the target
+    pub workdir: Option<String>,
+    /// Variables added to the reduced environment
+    pub env: BTreeMap<String, String>,
+    pub limits: ScriptLimits,
+    /// Carry on with the install when the script fails
+    pub continue_on_error: bool,
+}
+
+impl LateScript {
+    /// Where the script is written, relative to the target root
+    pub fn path(&self) -> String {
+        format!("{}/{}.sh", SCRIPT_DIR, self.name)
+    }
+
+    /// Command running the script at `root`, confined as configured
+    pub fn build_run_command(&self, root: &str, default_timeout: u64) -> String {
+        let root = root.trim_end_matches('/');
+        let env: Vec<String> = BASE_ENV
+            .iter()
+            .map(|(k, v)| (k.to_string(), v.to_string()))
+            .chain(self.env.clone())
+            .map(|(k, v)| format!("{}={}", k, shell_quote(&v)))
+            .collect();
+        let inner = format!(
+            "{}cd {} && exec bash /{}",
+            self.limits.ulimit_commands(),
+            self.workdir.as_deref().unwrap_or("/root"),
+            self.path()
+        );
+        format!(
+            "timeout --kill-after=10 {} chroot {} /usr/bin/env -i {} bash -c {}",
+            self.timeout_secs.unwrap_or(default_timeout),
+            root,
+            env.join(" "),
+            shell_quote(&inner)
+        )
+    }
+}
+
+/// Scripts to run and where remote scripts may come from
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct LateCommandsConfig {
+    /// Run in order after the system is configured
+    pub scripts: Vec<LateScript>,
+    /// URLs scripts may be downloaded from, or from below, without `approved: true`
+    pub allowed_urls: Vec<String>,
+    /// Timeout for scripts that set none
+    pub timeout_secs: u64,
+}
+
+impl Default for LateCommandsConfig {
+    fn default() -> Self {
+        Self {
+            scripts: Vec::new(),
+            allowed_urls: Vec::new(),
+            timeout_secs: 600,
+        }
+    }
+}
+
+impl LateCommandsConfig {
+    /// Whether `url` lies under one of `allowed_urls`
+    ///
+    /// Both are parsed so the scheme, host and port must match exactly and the path must equal
+    /// the allowed path or continue below it; a plain string prefix would also accept
+    /// `https://scripts.example.attacker.net/` or `https://scripts.example@attacker/`.
+    pub fn is_allowed(&self, url: &str) -> bool {
+        let Ok(url) = Url::parse(url) else {
+            return false;
+        };
+        self.allowed_urls
+            .iter()
+            .filter_map(|allowed| Url::parse(allowed).ok())
+            .any(|allowed| {
+                let base = allowed.path().trim_end_matches('/');
+                url.scheme() == allowed.scheme()
+                    && url.host() == allowed.host()
+                    && url.port_or_known_default() == allowed.port_or_known_default()
+                    && (url.path() == base || url.path().starts_with(&format!("{}/", base)))
+            })
+    }
+
+    /// Check names, sources, approvals and limits
+    pub fn validate(&self) -> Result<()> {
+        let invalid = |message: String| Err(AutoInstallError::ValidationError(message));
+        if !(1..=86_400).contains(&self.timeout_secs) {
+            return invalid(format!(
+                "late_commands timeout_secs must be 1-86400, got {}",
+                self.timeout_secs
+            ));
+        }
+        for allowed in &self.allowed_urls {
+            match Url::parse(allowed) {
+                Ok(url) if matches!(url.scheme(), "http" | "https") && url.has_host() => {}
+                _ => {
+                    return invalid(format!(
+                        "late_commands allowed_urls entry '{}' must be an http:// or https:// URL",
+                        allowed
+                    ))
+                }
+            }
+        }
+        let mut names = std::collections::HashSet::new();
+        for script in &self.scripts {
+            let name = &script.name;
+            if name.is_empty()
+                || !name
+                    .chars()
+                    .all(|c| c.is_ascii_alphanumeric() || c == '-' || c == '_')
+            {
+                return invalid(format!(
+                    "late_commands script name '{}' must be letters, digits, '-' or '_'",
+                    name
+                ));
+            }
+            if !names.insert(name) {
+                return invalid(format!("late_commands script '{}' is listed twice", name));
+            }
+            match (&script.run, &script.url) {
+                (Some(_), None) => {}
+                (None, Some(url)) => {
+                    if !(url.starts_with("https://") || url.starts_with("http://"))
+                        || url.contains(|c: char| c.is_whitespace() || "'\"`".contains(c))
+                    {
+                        return invalid(format!(
+                            "late_commands script '{}' url must be a plain http:// or https:// URL",
+                            name
+                        ));
+                    }
+                    if !script.approved && !self.is_allowed(url) {
+                        return invalid(format!(
+                            "late_commands script '{}' comes from {}, which is not in allowed_urls; add the prefix or set approved: true",
+                            name, url
+                        ));
+                    }
+                }
+                _ => {
+                    return invalid(format!(
+                        "late_commands script '{}' needs exactly one of run or url",
+                        name
+                    ))
+                }
+            }
+            if let Some(sum) = &script.sha256 {
+                if sum.len() != 64 || !sum.chars().all(|c| c.is_ascii_hexdigit()) {
+                    return invalid(format!(
+                        "late_commands script '{}' sha256 must be 64 hex digits",
+                        name
+                    ));
+                }
+            }
+            if let Some(timeout) = script.timeout_secs {
+                if !(1..=86_400).contains(&timeout) {
+                    return invalid(format!(
+                        "late_commands script '{}' timeout_secs must be 1-86400, got {}",
+                        name, timeout
+                    ));
+                }
+            }
+            if let Some(dir) = &script.workdir {
+                if !dir.starts_with('/')
+                    || dir.contains(|c: char| c.is_whitespace() || "'\"`$;&|\\".contains(c))
+                {
+                    return invalid(format!(
+                        "late_commands script '{}' workdir must be a plain absolute path",
+                        name
+                    ));
+                }
+            }
+            for key in script.env.keys() {
+                let valid = key
+                    .chars()
+                    .next()
+                    .is_some_and(|c| c.is_ascii_uppercase() || c == '_')
+                    && key
+                        .chars()
+                        .all(|c| c.is_ascii_uppercase() || c.is_ascii_digit() || c == '_');
+                if !valid {
+                    return invalid(format!(
+                        "late_commands script '{}' env name '{}' must be upper-case letters, digits and '_'",
+                        name, key
+                    ));
+                }
+            }
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::loader::parse_section;
+
+    fn parse(yaml: &str) -> LateCommandsConfig {
+        parse_section::<LateCommandsConfig>(yaml, "late_commands").unwrap()
+    }
+
+    #[test]
+    fn test_validation() {
+        let config = parse(
+            "late_commands:\n  allowed_urls: [https://scripts.example/]\n  scripts:\n    - name: motd\n      run: echo hi > /etc/motd\n    - name: agent\n      url: https://scripts.example/agent.sh\n",
+        );
+        assert!(config.validate().is_ok());
+        assert!(parse("hostname: a\n").validate().is_ok());
+
+        let bad = [
+            "late_commands:\n  scripts:\n    - name: x\n      url: https://elsewhere.example/x.sh\n",
+            "late_commands:\n  allowed_urls: [scripts.example]\n",
+            "late_commands:\n  scripts:\n    - name: x\n",
+            "late_commands:\n  scripts:\n    - name: x\n      run: a\n      url: https://a/\n",
+            "late_commands:\n  scripts:\n    - name: ../x\n      run: a\n",
+            "late_commands:\n  scripts:\n    - name: x\n      run: a\n    - name: x\n      run: b\n",
+            "late_commands:\n  scripts:\n    - name: x\n      run: a\n      workdir: /tmp; reboot\n",
+            "late_commands:\n  scripts:\n    - name: x\n      run: a\n      env: {lower: 1}\n",
+            "late_commands:\n  scripts:\n    - name: x\n      run: a\n      sha256: abc\n",
+        ];
+        for yaml in bad {
+            assert!(parse(yaml).validate().is_err(), "{}", yaml);
+        }
+        // Approval lets an operator run a script from anywhere
+        assert!(parse(
+            "late_commands:\n  scripts:\n    - name: x\n      url: https://elsewhere.example/x.sh\n      approved: true\n"
+        )
+        .validate()
+        .is_ok());
+    }
+
+    #[test]
+    fn test_allowed_urls_match_host_and_path() {
+        let config = parse(
+            "late_commands:\n  allowed_urls: [https://scripts.example, https://files.example/team/]\n",
+        );
+        assert!(config.is_allowed("https://scripts.example/x.sh"));
+        assert!(config.is_allowed("https://scripts.example:443/a/b.sh"));
+        assert!(config.is_allowed("https://files.example/team/x.sh"));
+        assert!(config.is_allowed("https://files.example/team"));
+
+        assert!(!config.is_allowed("https://scripts.example.attacker.net/x.sh"));
+        assert!(!config.is_allowed("https://scripts.example@evil/x.sh"));
+        assert!(!config.is_allowed("https://scripts.example:8443/x.sh"));
+        assert!(!config.is_allowed("http://scripts.example/x.sh"));
+        assert!(!config.is_allowed("https://files.example/teammate/x.sh"));
+        assert!(!config.is_allowed("https://files.example/team/../other/x.sh"));
+        assert!(!config.is_allowed("not a url"));
+    }
+
+    #[test]
+    fn test_run_command_is_confined() {
+        let script = LateScript {
+            name: "agent".to_string(),
+            run: Some("true".to_string()),
+            timeout_secs: Some(30),
+            workdir: Some("/opt".to_string()),
+            env: BTreeMap::from([("TOKEN".to_string(), "a b'c".to_string())]),
+            limits: ScriptLimits {
+                memory_mb: Some(512),
+                open_files: Some(1024),
+                ..Default::default()
+            },
+            ..Default::default()
+        };
+        let command = script.build_run_command("/mnt/targetos/", 600);
+        assert!(command
+            .starts_with("timeout --kill-after=10 30 chroot /mnt/targetos /usr/bin/env -i PATH="));
+        assert!(command.contains("HOME=/root"));
+        assert!(command.contains("TOKEN='a b'\\''c'"));
+        assert!(command.contains(
+            "bash -c 'ulimit -v 524288; ulimit -n 1024; cd /opt && exec bash /root/.late-commands/agent.sh'"
+        ));
+    }
+}
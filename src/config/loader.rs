@@ -1,19 +1,34 @@
 // file: src/config/loader.rs
-// version: 1.0.0
+// version: 1.41.0
 // guid: d4e5f6g7-h8i9-0123-4567-890123defghi
 
 //! Configuration file loading and environment variable substitution
 
-use super::{ImageSpec, TargetConfig};
+use super::interpolate::{self, FactVars};
+use super::{
+    AptLockConfig, AptMirrorsConfig, AptReposConfig, AptSnapshot, BmcConfig, BootloaderConfig,
+    BudgetConfig, ConfirmationConfig, DiskHealthConfig, EntropyConfig, FirewallConfig,
+    FleetInventory, HardeningConfig, HeadlessConfig, HealthGateConfig, HostVarsConfig, ImageSpec,
+    IssueConfig, KernelConfig, LateCommandsConfig, LowMemoryConfig, MirrorSelectionConfig,
+    NbdeConfig, NetworkRecoveryConfig, PartitioningConfig, PerformanceConfig, PipelineConfig,
+    PrivilegeConfig, ProgressConfig, ReinstallConfig, SshCaConfig, StorageConfig, TargetConfig,
+    TelemetryConfig, UbuntuProConfig, UpdatesConfig, UserDataConfig, VerificationConfig,
+    ZfsPoolsConfig, ZfsTuningConfig,
+};
 use crate::Result;
 use regex::Regex;
+use serde::de::{self, DeserializeOwned, IgnoredAny, MapAccess, Visitor};
+use serde::Deserializer;
 use std::collections::HashMap;
+use std::fmt;
 use std::fs;
+use std::marker::PhantomData;
 use std::path::Path;
 
-/// Configuration loader with environment variable substitution
+/// Configuration loader with environment variable and target fact substitution
 pub struct ConfigLoader {
     env_vars: HashMap<String, String>,
+    facts: Option<FactVars>,
 }
 
 impl ConfigLoader {
@@ -21,21 +36,20 @@ impl ConfigLoader {
     pub fn new() -> Self {
         Self {
             env_vars: std::env::vars().collect(),
+            facts: None,
         }
     }
 
+    /// Resolve `{{ facts.* }}` templates from `facts`; without them any template is an error
+    pub fn with_facts(mut self, facts: FactVars) -> Self {
+        self.facts = Some(facts);
+        self
+    }
+
     /// Load target configuration from YAML file
     pub fn load_target_config<P: AsRef<Path>>(&self, path: P) -> Result<TargetConfig> {
-        let content = fs::read_to_string(&path).map_err(|e| {
-            crate::error::AutoInstallError::ConfigError(format!(
-                "Failed to read target config file {}: {}",
-                path.as_ref().display(),
-                e
-            ))
-        })?;
-
-        let expanded = self.expand_env_vars(&content)?;
-        let config: TargetConfig = serde_yaml::from_str(&expanded)?;
+        let content = self.read_expanded(path.as_ref(), "target config")?;
+        let config: TargetConfig = serde_yaml::from_str(&content)?;
 
         // Validate configuration
         config.validate()?;
@@ -43,18 +57,285 @@ impl ConfigLoader {
         Ok(config)
     }
 
+    /// Load only the `kernel:` section of a target configuration file
+    pub fn load_kernel_config<P: AsRef<Path>>(&self, path: P) -> Result<KernelConfig> {
+        let kernel: KernelConfig = self.load_section(path.as_ref(), "kernel")?;
+        kernel.validate()?;
+        Ok(kernel)
+    }
+
+    /// Load only the `apt_snapshot:` pin of a target configuration file
+    pub fn load_apt_snapshot<P: AsRef<Path>>(&self, path: P) -> Result<Option<AptSnapshot>> {
+        self.load_section(path.as_ref(), "apt_snapshot")
+    }
+
+    /// Load only the `hardening:` section of a target configuration file
+    pub fn load_hardening_config<P: AsRef<Path>>(&self, path: P) -> Result<HardeningConfig> {
+        self.load_section(path.as_ref(), "hardening")
+    }
+
+    /// Load only the `firewall:` section of a target configuration file
+    pub fn load_firewall_config<P: AsRef<Path>>(&self, path: P) -> Result<FirewallConfig> {
+        let firewall: FirewallConfig = self.load_section(path.as_ref(), "firewall")?;
+        firewall.validate()?;
+        Ok(firewall)
+    }
+
+    /// Load only the `headless:` section of a target configuration file
+    pub fn load_headless_config<P: AsRef<Path>>(&self, path: P) -> Result<HeadlessConfig> {
+        let headless: HeadlessConfig = self.load_section(path.as_ref(), "headless")?;
+        headless.validate()?;
+        Ok(headless)
+    }
+
+    /// Load only the `ssh_ca:` section of a target configuration file
+    pub fn load_ssh_ca_config<P: AsRef<Path>>(&self, path: P) -> Result<SshCaConfig> {
+        let ssh_ca: SshCaConfig = self.load_section(path.as_ref(), "ssh_ca")?;
+        ssh_ca.validate()?;
+        Ok(ssh_ca)
+    }
+
+    /// Load only the `nbde:` section of a target configuration file
+    pub fn load_nbde_config<P: AsRef<Path>>(&self, path: P) -> Result<NbdeConfig> {
+        let nbde: NbdeConfig = self.load_section(path.as_ref(), "nbde")?;
+        nbde.validate()?;
+        Ok(nbde)
+    }
+
+    /// Load only the `network_recovery:` section of a target configuration file
+    pub fn load_network_recovery_config<P: AsRef<Path>>(
+        &self,
+        path: P,
+    ) -> Result<NetworkRecoveryConfig> {
+        let network_recovery: NetworkRecoveryConfig =
+            self.load_section(path.as_ref(), "network_recovery")?;
+        network_recovery.validate()?;
+        Ok(network_recovery)
+    }
+
+    /// Load only the `late_commands:` section of a target configuration file
+    pub fn load_late_commands_config<P: AsRef<Path>>(&self, path: P) -> Result<LateCommandsConfig> {
+        let late_commands: LateCommandsConfig =
+            self.load_section(path.as_ref(), "late_commands")?;
+        late_commands.validate()?;
+        Ok(late_commands)
+    }
+
+    /// Load only the `updates:` section of a target configuration file
+    pub fn load_updates_config<P: AsRef<Path>>(&self, path: P) -> Result<UpdatesConfig> {
+        let updates: UpdatesConfig = self.load_section(path.as_ref(), "updates")?;
+        updates.validate()?;
+        Ok(updates)
+    }
+
+    /// Load only the `disk_health:` section of a target configuration file
+    pub fn load_disk_health_config<P: AsRef<Path>>(&self, path: P) -> Result<DiskHealthConfig> {
+        let disk_health: DiskHealthConfig = self.load_section(path.as_ref(), "disk_health")?;
+        disk_health.validate()?;
+        Ok(disk_health)
+    }
+
+    /// Load only the `apt_lock:` section of a target configuration file
+    pub fn load_apt_lock_config<P: AsRef<Path>>(&self, path: P) -> Result<AptLockConfig> {
+        let apt_lock: AptLockConfig = self.load_section(path.as_ref(), "apt_lock")?;
+        apt_lock.validate()?;
+        Ok(apt_lock)
+    }
+
+    /// Load only the `health_gate:` section of a target configuration file
+    pub fn load_health_gate_config<P: AsRef<Path>>(&self, path: P) -> Result<HealthGateConfig> {
+        let health_gate: HealthGateConfig = self.load_section(path.as_ref(), "health_gate")?;
+        health_gate.validate()?;
+        Ok(health_gate)
+    }
+
+    /// Load only the `partitioning:` section of a target configuration file
+    pub fn load_partitioning_config<P: AsRef<Path>>(&self, path: P) -> Result<PartitioningConfig> {
+        let partitioning: PartitioningConfig = self.load_section(path.as_ref(), "partitioning")?;
+        partitioning.validate()?;
+        Ok(partitioning)
+    }
+
+    /// Load only the `confirmation:` section of a target configuration file
+    pub fn load_confirmation_config<P: AsRef<Path>>(&self, path: P) -> Result<ConfirmationConfig> {
+        let confirmation: ConfirmationConfig = self.load_section(path.as_ref(), "confirmation")?;
+        confirmation.validate()?;
+        Ok(confirmation)
+    }
+
+    /// Load only the `privilege:` section of a target configuration file
+    pub fn load_privilege_config<P: AsRef<Path>>(&self, path: P) -> Result<PrivilegeConfig> {
+        let privilege: PrivilegeConfig = self.load_section(path.as_ref(), "privilege")?;
+        privilege.validate()?;
+        Ok(privilege)
+    }
+
+    /// Load only the `budget:` section of a target configuration file
+    pub fn load_budget_config<P: AsRef<Path>>(&self, path: P) -> Result<BudgetConfig> {
+        let budget: BudgetConfig = self.load_section(path.as_ref(), "budget")?;
+        budget.validate()?;
+        Ok(budget)
+    }
+
+    /// Load only the `bootloader:` section of a target configuration file
+    pub fn load_bootloader_config<P: AsRef<Path>>(&self, path: P) -> Result<BootloaderConfig> {
+        let bootloader: BootloaderConfig = self.load_section(path.as_ref(), "bootloader")?;
+        bootloader.validate()?;
+        Ok(bootloader)
+    }
+
+    /// Load only the `host_vars:` section of a target configuration file
+    pub fn load_host_vars_config<P: AsRef<Path>>(&self, path: P) -> Result<HostVarsConfig> {
+        let host_vars: HostVarsConfig = self.load_section(path.as_ref(), "host_vars")?;
+        host_vars.validate()?;
+        Ok(host_vars)
+    }
+
+    /// Load only the `low_memory:` section of a target configuration file
+    pub fn load_low_memory_config<P: AsRef<Path>>(&self, path: P) -> Result<LowMemoryConfig> {
+        let low_memory: LowMemoryConfig = self.load_section(path.as_ref(), "low_memory")?;
+        low_memory.validate()?;
+        Ok(low_memory)
+    }
+
+    /// Load only the `entropy:` section of a target configuration file
+    pub fn load_entropy_config<P: AsRef<Path>>(&self, path: P) -> Result<EntropyConfig> {
+        let entropy: EntropyConfig = self.load_section(path.as_ref(), "entropy")?;
+        entropy.validate()?;
+        Ok(entropy)
+    }
+
+    /// Load only the `apt_mirrors:` section of a target configuration file
+    pub fn load_apt_mirrors_config<P: AsRef<Path>>(&self, path: P) -> Result<AptMirrorsConfig> {
+        let apt_mirrors: AptMirrorsConfig = self.load_section(path.as_ref(), "apt_mirrors")?;
+        apt_mirrors.validate()?;
+        Ok(apt_mirrors)
+    }
+
+    /// Load only the `ubuntu_pro:` section of a target configuration file
+    pub fn load_ubuntu_pro_config<P: AsRef<Path>>(&self, path: P) -> Result<UbuntuProConfig> {
+        let ubuntu_pro: UbuntuProConfig = self.load_section(path.as_ref(), "ubuntu_pro")?;
+        ubuntu_pro.validate()?;
+        Ok(ubuntu_pro)
+    }
+
+    /// Load only the `zfs_pools:` section of a target configuration file
+    pub fn load_zfs_pools_config<P: AsRef<Path>>(&self, path: P) -> Result<ZfsPoolsConfig> {
+        let zfs_pools: ZfsPoolsConfig = self.load_section(path.as_ref(), "zfs_pools")?;
+        zfs_pools.validate()?;
+        Ok(zfs_pools)
+    }
+
+    /// Load only the `telemetry:` section of a target configuration file
+    pub fn load_telemetry_config<P: AsRef<Path>>(&self, path: P) -> Result<TelemetryConfig> {
+        let telemetry: TelemetryConfig = self.load_section(path.as_ref(), "telemetry")?;
+        telemetry.validate()?;
+        Ok(telemetry)
+    }
+
+    /// Load only the `verification:` section of a target configuration file
+    pub fn load_verification_config<P: AsRef<Path>>(&self, path: P) -> Result<VerificationConfig> {
+        let verification: VerificationConfig = self.load_section(path.as_ref(), "verification")?;
+        verification.validate()?;
+        Ok(verification)
+    }
+
+    /// Load only the `performance:` section of a target configuration file
+    pub fn load_performance_config<P: AsRef<Path>>(&self, path: P) -> Result<PerformanceConfig> {
+        let performance: PerformanceConfig = self.load_section(path.as_ref(), "performance")?;
+        performance.validate()?;
+        Ok(performance)
+    }
+
+    /// Load only the `issues:` section of a target configuration file
+    pub fn load_issues_config<P: AsRef<Path>>(&self, path: P) -> Result<IssueConfig> {
+        let issues: IssueConfig = self.load_section(path.as_ref(), "issues")?;
+        issues.validate()?;
+        Ok(issues)
+    }
+
+    /// Load only the `apt_repos:` section of a target configuration file
+    pub fn load_apt_repos_config<P: AsRef<Path>>(&self, path: P) -> Result<AptReposConfig> {
+        let apt_repos: AptReposConfig = self.load_section(path.as_ref(), "apt_repos")?;
+        apt_repos.validate()?;
+        Ok(apt_repos)
+    }
+
+    /// Load only the `user_data:` section of a target configuration file
+    pub fn load_user_data_config<P: AsRef<Path>>(&self, path: P) -> Result<UserDataConfig> {
+        let user_data: UserDataConfig = self.load_section(path.as_ref(), "user_data")?;
+        user_data.validate()?;
+        Ok(user_data)
+    }
+
+    /// Load only the `reinstall:` section of a target configuration file
+    pub fn load_reinstall_config<P: AsRef<Path>>(&self, path: P) -> Result<ReinstallConfig> {
+        let reinstall: ReinstallConfig = self.load_section(path.as_ref(), "reinstall")?;
+        reinstall.validate()?;
+        Ok(reinstall)
+    }
+
+    /// Load only the `progress:` section of a target configuration file
+    pub fn load_progress_config<P: AsRef<Path>>(&self, path: P) -> Result<ProgressConfig> {
+        let progress: ProgressConfig = self.load_section(path.as_ref(), "progress")?;
+        progress.validate()?;
+        Ok(progress)
+    }
+
+    /// Load and validate a fleet inventory file
+    pub fn load_inventory<P: AsRef<Path>>(&self, path: P) -> Result<FleetInventory> {
+        let content = self.read_expanded(path.as_ref(), "inventory")?;
+        let inventory: FleetInventory = serde_yaml::from_str(&content)?;
+        inventory.validate()?;
+        Ok(inventory)
+    }
+
+    /// Load a `run-pipeline` file
+    pub fn load_pipeline<P: AsRef<Path>>(&self, path: P) -> Result<PipelineConfig> {
+        let content = self.read_expanded(path.as_ref(), "pipeline")?;
+        let pipeline: PipelineConfig = serde_yaml::from_str(&content)?;
+        pipeline.validate()?;
+        Ok(pipeline)
+    }
+
+    /// Load only the `zfs_tuning:` section of a target configuration file
+    pub fn load_zfs_tuning_config<P: AsRef<Path>>(&self, path: P) -> Result<ZfsTuningConfig> {
+        let zfs_tuning: ZfsTuningConfig = self.load_section(path.as_ref(), "zfs_tuning")?;
+        zfs_tuning.validate()?;
+        Ok(zfs_tuning)
+    }
+
+    /// Load only the `storage:` section of a target configuration file
+    pub fn load_storage_config<P: AsRef<Path>>(&self, path: P) -> Result<StorageConfig> {
+        self.load_section(path.as_ref(), "storage")
+    }
+
+    /// Load only the `bmc:` section of a target config file
+    pub fn load_bmc_config<P: AsRef<Path>>(&self, path: P) -> Result<Option<BmcConfig>> {
+        let bmc: Option<BmcConfig> = self.load_section(path.as_ref(), "bmc")?;
+        if let Some(bmc) = &bmc {
+            bmc.validate()?;
+        }
+        Ok(bmc)
+    }
+
+    /// Load only the `mirror_selection:` section of a target config file
+    pub fn load_mirror_selection_config<P: AsRef<Path>>(
+        &self,
+        path: P,
+    ) -> Result<Option<MirrorSelectionConfig>> {
+        let mirror_selection: Option<MirrorSelectionConfig> =
+            self.load_section(path.as_ref(), "mirror_selection")?;
+        if let Some(selection) = &mirror_selection {
+            selection.validate()?;
+        }
+        Ok(mirror_selection)
+    }
+
     /// Load image specification from YAML file
     pub fn load_image_spec<P: AsRef<Path>>(&self, path: P) -> Result<ImageSpec> {
-        let content = fs::read_to_string(&path).map_err(|e| {
-            crate::error::AutoInstallError::ConfigError(format!(
-                "Failed to read image spec file {}: {}",
-                path.as_ref().display(),
-                e
-            ))
-        })?;
-
-        let expanded = self.expand_env_vars(&content)?;
-        let spec: ImageSpec = serde_yaml::from_str(&expanded)?;
+        let content = self.read_expanded(path.as_ref(), "image spec")?;
+        let spec: ImageSpec = serde_yaml::from_str(&content)?;
 
         // Validate specification
         spec.validate()?;
@@ -62,8 +343,26 @@ impl ConfigLoader {
         Ok(spec)
     }
 
-    /// Expand environment variables in configuration content
-    fn expand_env_vars(&self, content: &str) -> Result<String> {
+    /// Read `path` and expand its variables; `what` names the file in the read error
+    fn read_expanded(&self, path: &Path, what: &str) -> Result<String> {
+        let content = fs::read_to_string(path).map_err(|e| {
+            crate::error::AutoInstallError::ConfigError(format!(
+                "Failed to read {} file {}: {}",
+                what,
+                path.display(),
+                e
+            ))
+        })?;
+        self.expand_env_vars(&content)
+    }
+
+    /// Load only the `key:` section of a target configuration file
+    fn load_section<T: DeserializeOwned + Default>(&self, path: &Path, key: &str) -> Result<T> {
+        parse_section(&self.read_expanded(path, "target config")?, key)
+    }
+
+    /// Expand environment variables, then `{{ facts.* }}` templates, in configuration content
+    pub(crate) fn expand_env_vars(&self, content: &str) -> Result<String> {
         let re = Regex::new(r"\$\{([^}]+)\}").map_err(|e| {
             crate::error::AutoInstallError::ConfigError(format!("Invalid regex pattern: {}", e))
         })?;
@@ -89,7 +388,7 @@ impl ConfigLoader {
             )));
         }
 
-        Ok(result)
+        interpolate::render(&result, self.facts.as_ref())
     }
 
     /// Set environment variable for substitution
@@ -133,6 +432,46 @@ impl Default for ConfigLoader {
     }
 }
 
+/// Parse the `key:` section of a target config document; a missing section is the default
+pub(crate) fn parse_section<T: DeserializeOwned + Default>(content: &str, key: &str) -> Result<T> {
+    let document = serde_yaml::Deserializer::from_str(content);
+    Ok(document.deserialize_map(SectionVisitor {
+        key,
+        section: PhantomData,
+    })?)
+}
+
+/// Deserializes one top-level key straight from the document, so scalars keep the same
+/// typing they get when the whole config is parsed, and skips every other key
+struct SectionVisitor<'a, T> {
+    key: &'a str,
+    section: PhantomData<T>,
+}
+
+impl<'de, T: DeserializeOwned + Default> Visitor<'de> for SectionVisitor<'_, T> {
+    type Value = T;
+
+    fn expecting(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str("a target config mapping")
+    }
+
+    fn visit_unit<E: de::Error>(self) -> std::result::Result<T, E> {
+        Ok(T::default())
+    }
+
+    fn visit_map<A: MapAccess<'de>>(self, mut map: A) -> std::result::Result<T, A::Error> {
+        let mut section = None;
+        while let Some(key) = map.next_key::<String>()? {
+            if key == self.key {
+                section = Some(map.next_value()?);
+            } else {
+                map.next_value::<IgnoredAny>()?;
+            }
+        }
+        Ok(section.unwrap_or_default())
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -162,6 +501,73 @@ mod tests {
             .contains("Missing environment variables"));
     }
 
+    #[test]
+    fn test_partial_sections_keep_defaults() {
+        let disk: DiskHealthConfig =
+            parse_section("disk_health:\n  enforce: false\n", "disk_health").unwrap();
+        assert!(!disk.enforce);
+        assert!(disk.check);
+        assert_eq!(
+            disk.pending_sectors,
+            DiskHealthConfig::default().pending_sectors
+        );
+
+        let budget: BudgetConfig =
+            parse_section("budget:\n  total_minutes: 90\n", "budget").unwrap();
+        assert_eq!(budget.grace_minutes, BudgetConfig::default().grace_minutes);
+
+        let privilege: PrivilegeConfig =
+            parse_section("privilege:\n  mode: sudo\n", "privilege").unwrap();
+        assert!(privilege.unprivileged.is_empty());
+
+        let gate: HealthGateConfig =
+            parse_section("health_gate:\n  weights: {clock_skew: 0}\n", "health_gate").unwrap();
+        assert_eq!(
+            gate.weights.network,
+            HealthGateConfig::default().weights.network
+        );
+
+        let confirmation: ConfirmationConfig =
+            parse_section("confirmation:\n  pause_before: [phase_2]\n", "confirmation").unwrap();
+        assert_eq!(
+            confirmation.poll_secs,
+            ConfirmationConfig::default().poll_secs
+        );
+
+        let partitioning: PartitioningConfig =
+            parse_section("partitioning:\n  mode: existing\n", "partitioning").unwrap();
+        assert!(partitioning.format);
+
+        // A missing section is the whole default, and a bad one is still an error
+        let kernel: KernelConfig = parse_section("hostname: a\n", "kernel").unwrap();
+        assert!(kernel.is_empty());
+        assert!(parse_section::<KernelConfig>("", "kernel")
+            .unwrap()
+            .is_empty());
+        let bmc: Option<BmcConfig> = parse_section("hostname: a\n", "bmc").unwrap();
+        assert!(bmc.is_none());
+        assert!(parse_section::<StorageConfig>("storage:\n  layout: raid5\n", "storage").is_err());
+    }
+
+    #[test]
+    fn test_fact_templates() {
+        use crate::config::interpolate::FactValue;
+
+        let content = "disk_device: \"{{ facts.largest_nvme }}\"";
+        assert!(ConfigLoader::new().expand_env_vars(content).is_err());
+
+        let mut facts = FactVars::new();
+        facts.insert(
+            "largest_nvme".to_string(),
+            FactValue::Text("/dev/nvme0n1".to_string()),
+        );
+        let loader = ConfigLoader::new().with_facts(facts);
+        assert_eq!(
+            loader.expand_env_vars(content).unwrap(),
+            "disk_device: \"/dev/nvme0n1\""
+        );
+    }
+
     #[test]
     fn test_load_target_config() -> Result<()> {
         let mut file = NamedTempFile::new().unwrap();
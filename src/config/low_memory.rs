@@ -0,0 +1,194 @@
+// file: src/config/low_memory.rs
+// version: 1.1.0
+// guid: 8c2e5a91-4f7d-4b36-a1e8-d9b3c6f0e247
+
+//! Low-memory mode for the live environment (`low_memory:` section of a target config)
+//!
+//! The live system runs from RAM, and debootstrap, dpkg and the ZFS ARC all compete for what is
+//! left. On small machines the install can be killed by the OOM killer halfway through. In
+//! low-memory mode the installer adds compressed swap in RAM (zram) and optionally a swapfile
+//! on persistent storage, caps the ARC for the duration of the install, installs the base
+//! packages one role at a time and drops the page cache before the heavy phases. The live
+//! environment is put back when the install finishes.
+
+use crate::error::AutoInstallError;
+use crate::Result;
+use serde::{Deserialize, Serialize};
+
+/// zram device the installer creates; the live system normally has none
+const ZRAM_DEVICE: &str = "/dev/zram0";
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum LowMemoryMode {
+    /// On when the target has less than `threshold_mb`
+    #[default]
+    Auto,
+    On,
+    Off,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct LowMemoryConfig {
+    pub mode: LowMemoryMode,
+    /// `auto` turns the mode on below this much RAM
+    pub threshold_mb: u64,
+    /// Installs below this much RAM get a warning in the report
+    pub recommended_mb: u64,
+    /// zram size as a share of RAM
+    pub zram_percent: u32,
+    /// Swapfile on persistent storage of the live system (a USB stick, not the install disk
+    /// and not the live system's own tmpfs)
+    pub swapfile: Option<String>,
+    pub swapfile_mb: u64,
+    /// ZFS ARC limit in the live environment while installing
+    pub arc_max_mb: u64,
+}
+
+impl Default for LowMemoryConfig {
+    fn default() -> Self {
+        Self {
+            mode: LowMemoryMode::Auto,
+            threshold_mb: 3072,
+            recommended_mb: 4096,
+            zram_percent: 50,
+            swapfile: None,
+            swapfile_mb: 2048,
+            arc_max_mb: 256,
+        }
+    }
+}
+
+impl LowMemoryConfig {
+    /// Whether the mode applies to a target with `memory_mb` of RAM (unknown counts as enough)
+    pub fn enabled_for(&self, memory_mb: Option<u64>) -> bool {
+        match self.mode {
+            LowMemoryMode::On => true,
+            LowMemoryMode::Off => false,
+            LowMemoryMode::Auto => memory_mb.is_some_and(|mb| mb < self.threshold_mb),
+        }
+    }
+
+    /// Warning for a target below the recommended RAM
+    pub fn check_recommended(&self, memory_mb: Option<u64>) -> Option<String> {
+        let mb = memory_mb?;
+        (mb < self.recommended_mb).then(|| {
+            format!(
+                "Target has {} MiB of RAM; {} MiB or more is recommended for a ZFS install",
+                mb, self.recommended_mb
+            )
+        })
+    }
+
+    pub fn validate(&self) -> Result<()> {
+        if !(1..=150).contains(&self.zram_percent) {
+            return Err(AutoInstallError::ValidationError(format!(
+                "low_memory.zram_percent must be between 1 and 150, got {}",
+                self.zram_percent
+            )));
+        }
+        if self.arc_max_mb < 64 {
+            return Err(AutoInstallError::ValidationError(
+                "low_memory.arc_max_mb must be at least 64".to_string(),
+            ));
+        }
+        if let Some(path) = &self.swapfile {
+            if !path.starts_with('/') || path.contains(|c: char| c.is_whitespace() || c == '\'') {
+                return Err(AutoInstallError::ValidationError(format!(
+                    "low_memory.swapfile must be an absolute path without spaces: {}",
+                    path
+                )));
+            }
+        }
+        Ok(())
+    }
+
+    /// Commands adding swap to the live environment of a target with `memory_mb` of RAM
+    pub fn build_enable_commands(&self, memory_mb: u64) -> Vec<String> {
+        let zram_mb = (memory_mb * self.zram_percent as u64 / 100).max(256);
+        let mut commands = vec![
+            "modprobe zram num_devices=1".to_string(),
+            format!(
+                "zramctl {d} --algorithm zstd --size {m}M || zramctl {d} --size {m}M",
+                d = ZRAM_DEVICE,
+                m = zram_mb
+            ),
+            format!("mkswap {}", ZRAM_DEVICE),
+            format!("swapon --priority 100 {}", ZRAM_DEVICE),
+        ];
+        if let Some(path) = &self.swapfile {
+            commands.extend([
+                format!(
+                    "fallocate -l {}M {p} || dd if=/dev/zero of={p} bs=1M count={}",
+                    self.swapfile_mb,
+                    self.swapfile_mb,
+                    p = path
+                ),
+                format!("chmod 600 {}", path),
+                format!("mkswap {}", path),
+                format!("swapon --priority 10 {}", path),
+            ]);
+        }
+        commands
+    }
+
+    /// Command capping the ARC of the live environment's ZFS module
+    pub fn build_arc_command(&self) -> String {
+        format!(
+            "modprobe zfs && echo {} > /sys/module/zfs/parameters/zfs_arc_max",
+            self.arc_max_mb * 1024 * 1024
+        )
+    }
+
+    /// Commands removing the swap again; each one is allowed to fail
+    pub fn build_disable_commands(&self) -> Vec<String> {
+        let mut commands = Vec::new();
+        if let Some(path) = &self.swapfile {
+            commands.push(format!("swapoff {p} && rm -f {p}", p = path));
+        }
+        commands.push(format!(
+            "swapoff {d} && zramctl --reset {d}",
+            d = ZRAM_DEVICE
+        ));
+        commands
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_mode_and_commands() {
+        let config: LowMemoryConfig =
+            serde_yaml::from_str("swapfile: /media/usb/uaa.swap\nswapfile_mb: 1024\n").unwrap();
+        config.validate().unwrap();
+        assert!(config.enabled_for(Some(2000)));
+        assert!(!config.enabled_for(Some(8192)));
+        assert!(!config.enabled_for(None));
+        assert!(config.check_recommended(Some(2000)).is_some());
+        assert!(config.check_recommended(Some(4096)).is_none());
+
+        let commands = config.build_enable_commands(2000);
+        assert_eq!(
+            commands[1],
+            "zramctl /dev/zram0 --algorithm zstd --size 1000M || zramctl /dev/zram0 --size 1000M"
+        );
+        assert_eq!(
+            commands.last().unwrap(),
+            "swapon --priority 10 /media/usb/uaa.swap"
+        );
+        assert_eq!(
+            config.build_arc_command(),
+            "modprobe zfs && echo 268435456 > /sys/module/zfs/parameters/zfs_arc_max"
+        );
+        assert_eq!(config.build_disable_commands().len(), 2);
+
+        let off = LowMemoryConfig {
+            mode: LowMemoryMode::Off,
+            ..config
+        };
+        assert!(!off.enabled_for(Some(1024)));
+    }
+}
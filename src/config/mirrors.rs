@@ -0,0 +1,141 @@
+// file: src/config/mirrors.rs
+// version: 1.1.0
+// guid: 3a8f1c67-d2e9-4b50-9c14-7e6b0a2d58f3
+
+//! Automatic mirror selection (`mirror_selection:` section of a target config)
+//!
+//! Candidates are measured from the target itself before debootstrap, so the choice reflects
+//! the network the machine is actually on.
+
+use super::Architecture;
+use serde::{Deserialize, Serialize};
+
+/// mirrors.txt list of archive mirrors close to the requester
+pub const UBUNTU_MIRRORS_TXT: &str = "mirror://mirrors.ubuntu.com/mirrors.txt";
+/// Primary archive (amd64)
+pub const UBUNTU_ARCHIVE: &str = "http://archive.ubuntu.com/ubuntu/";
+/// Ports archive (arm64 and the other non-x86 architectures)
+pub const UBUNTU_PORTS: &str = "http://ports.ubuntu.com/ubuntu-ports/";
+
+/// Mirrors to measure and how long the winner is reused
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct MirrorSelectionConfig {
+    /// Mirror base URLs, or `mirror://` lists in mirrors.txt format; the official archives when empty
+    #[serde(default)]
+    pub candidates: Vec<String>,
+    /// Most mirrors measured per run, after lists are expanded
+    #[serde(default = "default_max_probes")]
+    pub max_probes: usize,
+    /// Per-mirror download timeout in seconds
+    #[serde(default = "default_timeout_secs")]
+    pub timeout_secs: u64,
+    /// How long a choice is reused on the same network, in hours (0 always measures)
+    #[serde(default = "default_cache_hours")]
+    pub cache_hours: u64,
+}
+
+fn default_max_probes() -> usize {
+    8
+}
+
+fn default_timeout_secs() -> u64 {
+    10
+}
+
+fn default_cache_hours() -> u64 {
+    24
+}
+
+impl Default for MirrorSelectionConfig {
+    fn default() -> Self {
+        Self {
+            candidates: Vec::new(),
+            max_probes: default_max_probes(),
+            timeout_secs: default_timeout_secs(),
+            cache_hours: default_cache_hours(),
+        }
+    }
+}
+
+impl MirrorSelectionConfig {
+    /// Candidates to probe for `architecture`, falling back to the official archives
+    pub fn candidates_for(&self, architecture: Architecture) -> Vec<String> {
+        if !self.candidates.is_empty() {
+            return self.candidates.clone();
+        }
+        match architecture {
+            Architecture::Amd64 => vec![UBUNTU_MIRRORS_TXT.to_string(), UBUNTU_ARCHIVE.to_string()],
+            Architecture::Arm64 => vec![UBUNTU_PORTS.to_string()],
+        }
+    }
+
+    /// Check candidate URLs and limits
+    pub fn validate(&self) -> crate::Result<()> {
+        for candidate in &self.candidates {
+            if !["http://", "https://", "mirror://"]
+                .iter()
+                .any(|scheme| candidate.starts_with(scheme))
+            {
+                return Err(crate::error::AutoInstallError::ValidationError(format!(
+                    "mirror_selection candidate '{}' must be an http://, https:// or mirror:// URL",
+                    candidate
+                )));
+            }
+        }
+        if self.max_probes == 0 || self.timeout_secs == 0 {
+            return Err(crate::error::AutoInstallError::ValidationError(
+                "mirror_selection.max_probes and timeout_secs must be at least 1".to_string(),
+            ));
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::loader::parse_section;
+
+    #[test]
+    fn test_section_defaults_and_candidates() {
+        let section: Option<MirrorSelectionConfig> =
+            parse_section("hostname: a\nmirror_selection: {}\n", "mirror_selection").unwrap();
+        let selection = section.unwrap();
+        assert_eq!(selection, MirrorSelectionConfig::default());
+        assert_eq!(
+            selection.candidates_for(Architecture::Amd64),
+            vec![UBUNTU_MIRRORS_TXT, UBUNTU_ARCHIVE]
+        );
+        assert_eq!(
+            selection.candidates_for(Architecture::Arm64),
+            vec![UBUNTU_PORTS]
+        );
+        assert!(selection.validate().is_ok());
+
+        let section: Option<MirrorSelectionConfig> = parse_section(
+            "mirror_selection:\n  candidates: [\"http://mirror.lab/ubuntu/\"]\n  cache_hours: 0\n",
+            "mirror_selection",
+        )
+        .unwrap();
+        let selection = section.unwrap();
+        assert_eq!(
+            selection.candidates_for(Architecture::Arm64),
+            vec!["http://mirror.lab/ubuntu/"]
+        );
+        assert_eq!(selection.cache_hours, 0);
+    }
+
+    #[test]
+    fn test_validate_rejects_bad_candidates() {
+        let selection = MirrorSelectionConfig {
+            candidates: vec!["ftp://mirror.lab/ubuntu/".into()],
+            ..Default::default()
+        };
+        assert!(selection.validate().is_err());
+        let selection = MirrorSelectionConfig {
+            max_probes: 0,
+            ..Default::default()
+        };
+        assert!(selection.validate().is_err());
+    }
+}
@@ -1,17 +1,112 @@
 // file: src/config/mod.rs
-// version: 1.0.1
+// version: 1.52.0
 // guid: a1b2c3d4-e5f6-7a8b-9c0d-1e2f3a4b5c6d
 
 //! Configuration module for Ubuntu AutoInstall Agent
 //!
 //! Handles loading and validation of target configurations and image specifications.
 
+pub mod apt_lock;
+pub mod apt_mirrors;
+pub mod apt_repos;
+pub mod apt_snapshot;
+pub mod batch;
+pub mod bmc;
+pub mod bootloader;
+pub mod budget;
+pub mod build_hooks;
+pub mod confirmation;
+pub mod dhcp;
+pub mod disk_health;
+pub mod entropy;
+pub mod firewall;
+pub mod hardening;
+pub mod headless;
+pub mod health_gate;
+pub mod host_vars;
 pub mod image;
+pub mod interpolate;
+pub mod inventory;
+pub mod issues;
+pub mod kernel;
+pub mod late_commands;
 pub mod loader;
+pub mod low_memory;
+pub mod mirrors;
+pub mod nbde;
+pub mod network_recovery;
+pub mod packages;
+pub mod partitioning;
+pub mod performance;
+pub mod pipeline;
+pub mod privilege;
+pub mod progress;
+pub mod protection;
+pub mod reinstall;
+pub mod secrets;
+pub mod ssh_ca;
+pub mod storage;
 pub mod target;
+pub mod telemetry;
+pub mod tenants;
+pub mod throttle;
+pub mod ubuntu_pro;
+pub mod updates;
+pub mod user_data;
+pub mod verification;
+pub mod vsphere;
+pub mod zfs_pools;
+pub mod zfs_tuning;
 
-pub use image::{ImageInfo, ImageSpec, VmConfig};
+pub use apt_lock::AptLockConfig;
+pub use apt_mirrors::AptMirrorsConfig;
+pub use apt_repos::AptReposConfig;
+pub use apt_snapshot::AptSnapshot;
+pub use bmc::BmcConfig;
+pub use bootloader::BootloaderConfig;
+pub use budget::BudgetConfig;
+pub use build_hooks::BuildHooks;
+pub use confirmation::ConfirmationConfig;
+pub use dhcp::DhcpConfig;
+pub use disk_health::DiskHealthConfig;
+pub use entropy::EntropyConfig;
+pub use firewall::FirewallConfig;
+pub use hardening::HardeningConfig;
+pub use headless::HeadlessConfig;
+pub use health_gate::HealthGateConfig;
+pub use host_vars::HostVarsConfig;
+pub use image::{
+    HostResources, ImageFlavor, ImageInfo, ImageSpec, MachineProfile, SbcBoard, SbcConfig, VmConfig,
+};
+pub use inventory::FleetInventory;
+pub use issues::IssueConfig;
+pub use kernel::KernelConfig;
+pub use late_commands::LateCommandsConfig;
+pub use low_memory::LowMemoryConfig;
+pub use mirrors::MirrorSelectionConfig;
+pub use nbde::NbdeConfig;
+pub use network_recovery::NetworkRecoveryConfig;
+pub use packages::PackageRole;
+pub use partitioning::PartitioningConfig;
+pub use performance::PerformanceConfig;
+pub use pipeline::PipelineConfig;
+pub use privilege::PrivilegeConfig;
+pub use progress::ProgressConfig;
+pub use reinstall::ReinstallConfig;
+pub use secrets::SecretRef;
+pub use ssh_ca::SshCaConfig;
+pub use storage::{StorageConfig, StorageLayout};
 pub use target::{LuksConfig, NetworkConfig, TargetConfig, UserConfig};
+pub use telemetry::TelemetryConfig;
+pub use tenants::TenantRegistry;
+pub use throttle::ThrottleConfig;
+pub use ubuntu_pro::UbuntuProConfig;
+pub use updates::UpdatesConfig;
+pub use user_data::UserDataConfig;
+pub use verification::VerificationConfig;
+pub use vsphere::VsphereConfig;
+pub use zfs_pools::ZfsPoolsConfig;
+pub use zfs_tuning::ZfsTuningConfig;
 
 use serde::{Deserialize, Serialize};
 
@@ -42,6 +137,50 @@ impl Architecture {
     }
 }
 
+/// Known Ubuntu releases as (version, codename)
+const UBUNTU_RELEASES: &[(&str, &str)] = &[
+    ("25.04", "plucky"),
+    ("24.10", "oracular"),
+    ("24.04", "noble"),
+    ("23.10", "mantic"),
+    ("23.04", "lunar"),
+];
+
+/// Map an Ubuntu release version (e.g., "24.04") to its codename
+pub fn ubuntu_codename(version: &str) -> Option<&'static str> {
+    UBUNTU_RELEASES
+        .iter()
+        .find(|(v, _)| *v == version)
+        .map(|(_, codename)| *codename)
+}
+
+/// Map an Ubuntu codename (e.g., "noble") to its release version
+pub fn ubuntu_version(codename: &str) -> Option<&'static str> {
+    UBUNTU_RELEASES
+        .iter()
+        .find(|(_, c)| *c == codename)
+        .map(|(version, _)| *version)
+}
+
+/// Heredoc writing `content` to `root/path`, creating the parent directory
+pub(crate) fn write_file(root: &str, path: &str, content: &str) -> String {
+    let full = format!("{}/{}", root, path);
+    let dir = &full[..full.rfind('/').unwrap_or(0)];
+    format!(
+        "mkdir -p {} && cat > {} << 'EOF'\n{}EOF",
+        dir, full, content
+    )
+}
+
+/// [`write_file`] for files taking `#` comments, headed by a note that the agent manages them
+pub(crate) fn write_managed_file(root: &str, path: &str, content: &str) -> String {
+    write_file(
+        root,
+        path,
+        &format!("# Managed by ubuntu-autoinstall-agent\n{}", content),
+    )
+}
+
 impl std::str::FromStr for Architecture {
     type Err = crate::error::AutoInstallError;
 
@@ -59,7 +198,7 @@ impl std::str::FromStr for Architecture {
 
 #[cfg(test)]
 mod tests {
-    use super::Architecture;
+    use super::{ubuntu_codename, ubuntu_version, write_managed_file, Architecture};
     use std::str::FromStr;
 
     #[test]
@@ -90,4 +229,21 @@ mod tests {
         ));
         assert!(Architecture::from_str("mips").is_err());
     }
+
+    #[test]
+    fn test_ubuntu_codename() {
+        assert_eq!(ubuntu_codename("24.04"), Some("noble"));
+        assert_eq!(ubuntu_codename("25.04"), Some("plucky"));
+        assert_eq!(ubuntu_codename("18.04"), None);
+        assert_eq!(ubuntu_version("noble"), Some("24.04"));
+        assert_eq!(ubuntu_version("bionic"), None);
+    }
+
+    #[test]
+    fn test_write_managed_file() {
+        assert_eq!(
+            write_managed_file("/mnt/targetos", "etc/sysctl.d/60-x.conf", "vm.swappiness = 10\n"),
+            "mkdir -p /mnt/targetos/etc/sysctl.d && cat > /mnt/targetos/etc/sysctl.d/60-x.conf << 'EOF'\n# Managed by ubuntu-autoinstall-agent\nvm.swappiness = 10\nEOF"
+        );
+    }
 }
@@ -0,0 +1,250 @@
+// file: src/config/nbde.rs
+// version: 1.2.0
+// guid: 6c3e9a27-1b54-4f8d-a2c6-0e7d5b9f3a41
+
+//! Network-bound disk encryption (`nbde:` section of a target config)
+//!
+//! For machines without a TPM, the LUKS volume is bound with Clevis to one or more Tang
+//! servers through an `sss` pin, so the root filesystem unlocks unattended whenever at least
+//! `threshold` of them are reachable during boot. The passphrase keyslot is kept as a fallback.
+//! The initramfs brings the network up from the kernel `ip=` parameter, and the binding is
+//! checked against the Tang servers before the target is unmounted.
+
+use serde::{Deserialize, Serialize};
+
+/// GRUB defaults snippet adding the initramfs network parameter
+pub const GRUB_NBDE_FILE: &str = "etc/default/grub.d/60-autoinstall-nbde.cfg";
+
+/// Packages providing the Clevis LUKS binding and its initramfs unlocker
+const CLEVIS_PACKAGES: &[&str] = &["clevis", "clevis-luks", "clevis-initramfs"];
+
+/// One Tang server
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct TangServer {
+    /// Base URL, e.g. `http://tang1.example.com:7500`
+    pub url: String,
+    /// Thumbprint of the server's signing key (`tang-show-keys`); the advertisement is trusted
+    /// on first use when absent
+    #[serde(default)]
+    pub thumbprint: Option<String>,
+}
+
+/// Tang servers the root volume is bound to
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct NbdeConfig {
+    pub servers: Vec<TangServer>,
+    /// How many servers must answer to unlock
+    pub threshold: u32,
+    /// Kernel `ip=` value the initramfs configures the network from
+    pub initramfs_ip: String,
+}
+
+impl Default for NbdeConfig {
+    fn default() -> Self {
+        Self {
+            servers: Vec::new(),
+            threshold: 1,
+            initramfs_ip: "dhcp".to_string(),
+        }
+    }
+}
+
+impl NbdeConfig {
+    /// Whether the volume gets a Clevis binding
+    pub fn is_enabled(&self) -> bool {
+        !self.servers.is_empty()
+    }
+
+    /// Kernel parameters for bootloaders that do not read `/etc/default/grub.d`
+    pub fn kernel_args(&self) -> Vec<String> {
+        if self.is_enabled() {
+            vec![format!("ip={}", self.initramfs_ip)]
+        } else {
+            Vec::new()
+        }
+    }
+
+    /// Check server URLs, thumbprints and that the threshold can be met
+    pub fn validate(&self) -> crate::Result<()> {
+        if !self.is_enabled() {
+            return Ok(());
+        }
+        if self.threshold == 0 || self.threshold as usize > self.servers.len() {
+            return Err(crate::error::AutoInstallError::ValidationError(format!(
+                "nbde threshold must be between 1 and the number of Tang servers ({})",
+                self.servers.len()
+            )));
+        }
+        for server in &self.servers {
+            let valid_url = (server.url.starts_with("http://")
+                || server.url.starts_with("https://"))
+                && !server
+                    .url
+                    .contains(|c: char| c.is_whitespace() || matches!(c, '\'' | '"' | '\\'));
+            if !valid_url {
+                return Err(crate::error::AutoInstallError::ValidationError(format!(
+                    "Tang server URL '{}' must be an http:// or https:// URL",
+                    server.url
+                )));
+            }
+            if let Some(thumbprint) = &server.thumbprint {
+                if thumbprint.is_empty()
+                    || !thumbprint
+                        .chars()
+                        .all(|c| c.is_ascii_alphanumeric() || matches!(c, '-' | '_'))
+                {
+                    return Err(crate::error::AutoInstallError::ValidationError(format!(
+                        "Tang thumbprint for {} must be base64url",
+                        server.url
+                    )));
+                }
+            }
+        }
+        if self.initramfs_ip.is_empty()
+            || !self.initramfs_ip.chars().all(|c| {
+                c.is_ascii_alphanumeric() || matches!(c, '.' | ':' | '-' | '_' | '[' | ']')
+            })
+        {
+            return Err(crate::error::AutoInstallError::ValidationError(format!(
+                "nbde initramfs_ip '{}' is not a valid kernel ip= value",
+                self.initramfs_ip
+            )));
+        }
+        Ok(())
+    }
+
+    /// `sss` pin configuration passed to `clevis luks bind`
+    pub fn pin_config(&self) -> String {
+        let tang: Vec<serde_json::Value> = self
+            .servers
+            .iter()
+            .map(|server| match &server.thumbprint {
+                Some(thp) => serde_json::json!({ "url": server.url, "thp": thp }),
+                None => serde_json::json!({ "url": server.url }),
+            })
+            .collect();
+        serde_json::json!({ "t": self.threshold, "pins": { "tang": tang } }).to_string()
+    }
+
+    /// Commands installing Clevis and enabling initramfs networking in the system at `root`
+    ///
+    /// The chroot mounts and resolv.conf must already be in place.
+    pub fn build_install_commands(&self, root: &str) -> Vec<String> {
+        if !self.is_enabled() {
+            return Vec::new();
+        }
+        let root = root.trim_end_matches('/');
+        vec![
+            format!(
+                "chroot {} bash -lc 'DEBIAN_FRONTEND=noninteractive apt-get install -y {}'",
+                root,
+                CLEVIS_PACKAGES.join(" ")
+            ),
+            format!(
+                "mkdir -p {r}/etc/default/grub.d && cat > {r}/{f} << 'EOF'\n# Managed by ubuntu-autoinstall-agent: network for Clevis/Tang unlock in the initramfs\nGRUB_CMDLINE_LINUX=\"$GRUB_CMDLINE_LINUX ip={ip}\"\nEOF",
+                r = root,
+                f = GRUB_NBDE_FILE,
+                ip = self.initramfs_ip
+            ),
+            // systemd-boot hosts have no GRUB; their command line already carries `ip=`
+            format!(
+                "[ ! -x {r}/usr/sbin/update-grub ] || chroot {r} update-grub",
+                r = root
+            ),
+        ]
+    }
+
+    /// Command binding `device` to the Tang servers, authorised by the existing passphrase
+    ///
+    /// The command carries the passphrase; callers must not log it.
+    pub fn build_bind_command(&self, root: &str, device: &str, passphrase: &str) -> String {
+        format!(
+            "printf '%s' '{}' | chroot {} clevis luks bind -y -k - -d {} sss '{}'",
+            passphrase,
+            root.trim_end_matches('/'),
+            device,
+            self.pin_config()
+        )
+    }
+
+    /// Command that succeeds when some Clevis binding on `device` recovers a valid passphrase
+    ///
+    /// This contacts the Tang servers exactly as the initramfs will on boot.
+    pub fn build_verify_command(&self, root: &str, device: &str) -> String {
+        format!(
+            "chroot {} bash -lc 'for slot in $(clevis luks list -d {d} | cut -d: -f1); do clevis luks pass -d {d} -s \"$slot\" | cryptsetup open --test-passphrase --key-file=- {d} && exit 0; done; exit 1'",
+            root.trim_end_matches('/'),
+            d = device
+        )
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::loader::parse_section;
+
+    fn parse(yaml: &str) -> NbdeConfig {
+        parse_section::<NbdeConfig>(yaml, "nbde").unwrap()
+    }
+
+    #[test]
+    fn test_validate_threshold_and_servers() {
+        assert!(NbdeConfig::default().validate().is_ok());
+        assert!(NbdeConfig::default()
+            .build_install_commands("/mnt/targetos")
+            .is_empty());
+
+        let config = parse(
+            "nbde:\n  threshold: 2\n  servers:\n    - url: http://tang1:7500\n    - url: http://tang2:7500\n      thumbprint: x4c5N-pF_aB\n",
+        );
+        assert!(config.validate().is_ok());
+
+        let mut bad = config.clone();
+        bad.threshold = 3;
+        assert!(bad.validate().is_err());
+
+        let mut bad = config.clone();
+        bad.servers[0].url = "tang1:7500".to_string();
+        assert!(bad.validate().is_err());
+
+        let mut bad = config.clone();
+        bad.servers[1].thumbprint = Some("abc'; reboot".to_string());
+        assert!(bad.validate().is_err());
+
+        let mut bad = config;
+        bad.initramfs_ip = "dhcp quiet".to_string();
+        assert!(bad.validate().is_err());
+    }
+
+    #[test]
+    fn test_pin_config_and_commands() {
+        let config = parse(
+            "nbde:\n  servers:\n    - url: http://tang1:7500\n      thumbprint: abc\n    - url: https://tang2\n",
+        );
+        let pin: serde_json::Value = serde_json::from_str(&config.pin_config()).unwrap();
+        assert_eq!(pin["t"], 1);
+        assert_eq!(pin["pins"]["tang"][0]["thp"], "abc");
+        assert_eq!(pin["pins"]["tang"][1]["url"], "https://tang2");
+        assert!(pin["pins"]["tang"][1].get("thp").is_none());
+
+        let commands = config.build_install_commands("/mnt/targetos/");
+        assert!(commands[0].contains("apt-get install -y clevis clevis-luks clevis-initramfs"));
+        assert!(commands[1].contains("GRUB_CMDLINE_LINUX=\"$GRUB_CMDLINE_LINUX ip=dhcp\"\n"));
+        assert_eq!(
+            commands[2],
+            "[ ! -x /mnt/targetos/usr/sbin/update-grub ] || chroot /mnt/targetos update-grub"
+        );
+        assert_eq!(config.kernel_args(), vec!["ip=dhcp".to_string()]);
+
+        let bind = config.build_bind_command("/mnt/targetos", "/dev/sda4", "secret");
+        assert!(bind.starts_with(
+            "printf '%s' 'secret' | chroot /mnt/targetos clevis luks bind -y -k - -d /dev/sda4 sss '{"
+        ));
+
+        let verify = config.build_verify_command("/mnt/targetos", "/dev/sda4");
+        assert!(verify.contains("clevis luks pass -d /dev/sda4 -s \"$slot\""));
+        assert!(verify.contains("--test-passphrase --key-file=- /dev/sda4 && exit 0"));
+    }
+}
@@ -0,0 +1,113 @@
+// file: src/config/network_recovery.rs
+// version: 1.2.0
+// guid: 5c2e8a71-3f94-4b06-9d1e-a7b4c6e2f083
+
+//! Recovery from network failures during downloads (`network_recovery:` section of a target config)
+//!
+//! When debootstrap or a chroot apt command fails, its output says whether the problem was name
+//! resolution, routing or the mirror itself. Resolution failures switch resolv.conf to
+//! `resolvers`, routing failures restart networking on the live system, and mirror failures move
+//! on to the next of `alternate_mirrors`; the failed command is then run again. A download that
+//! prints nothing for `stall_timeout_secs` is stopped and counts as a mirror failure.
+
+use serde::{Deserialize, Serialize};
+
+/// Resolvers, alternate mirrors and how often a failed download is retried
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct NetworkRecoveryConfig {
+    /// Diagnose and repair DNS and routing failures; mirror fallback happens either way
+    pub enabled: bool,
+    /// Nameservers written to resolv.conf when resolution fails
+    pub resolvers: Vec<String>,
+    /// Mirrors tried in order after the configured one fails; when empty, debootstrap falls
+    /// back to old-releases
+    pub alternate_mirrors: Vec<String>,
+    /// Runs of one command, including the first
+    pub max_attempts: u32,
+    /// Stop a download that prints nothing for this long; 0 waits forever
+    pub stall_timeout_secs: u64,
+}
+
+impl Default for NetworkRecoveryConfig {
+    fn default() -> Self {
+        Self {
+            enabled: true,
+            resolvers: vec!["1.1.1.1".to_string(), "9.9.9.9".to_string()],
+            alternate_mirrors: Vec::new(),
+            max_attempts: 3,
+            stall_timeout_secs: 600,
+        }
+    }
+}
+
+impl NetworkRecoveryConfig {
+    /// Check resolver addresses, mirror URLs and the attempt count
+    pub fn validate(&self) -> crate::Result<()> {
+        for resolver in &self.resolvers {
+            if resolver.parse::<std::net::IpAddr>().is_err() {
+                return Err(crate::error::AutoInstallError::ValidationError(format!(
+                    "network_recovery resolver '{}' must be an IP address",
+                    resolver
+                )));
+            }
+        }
+        if self.enabled && self.resolvers.is_empty() {
+            return Err(crate::error::AutoInstallError::ValidationError(
+                "network_recovery needs at least one resolver".to_string(),
+            ));
+        }
+        for mirror in &self.alternate_mirrors {
+            let valid = (mirror.starts_with("http://") || mirror.starts_with("https://"))
+                && !mirror.contains(|c: char| c.is_whitespace() || matches!(c, '\'' | '"' | '|'));
+            if !valid {
+                return Err(crate::error::AutoInstallError::ValidationError(format!(
+                    "network_recovery alternate mirror '{}' must be an http:// or https:// URL",
+                    mirror
+                )));
+            }
+        }
+        if !(1..=10).contains(&self.max_attempts) {
+            return Err(crate::error::AutoInstallError::ValidationError(format!(
+                "network_recovery max_attempts must be between 1 and 10, got {}",
+                self.max_attempts
+            )));
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::loader::parse_section;
+
+    fn parse(yaml: &str) -> NetworkRecoveryConfig {
+        parse_section::<NetworkRecoveryConfig>(yaml, "network_recovery").unwrap()
+    }
+
+    #[test]
+    fn test_defaults_and_validation() {
+        let config = parse("hostname: a\n");
+        assert_eq!(config, NetworkRecoveryConfig::default());
+        assert!(config.validate().is_ok());
+
+        let config = parse(
+            "network_recovery:\n  resolvers: [192.168.1.1, '2606:4700:4700::1111']\n  alternate_mirrors:\n    - http://mirror.example/ubuntu/\n",
+        );
+        assert!(config.validate().is_ok());
+        assert_eq!(config.max_attempts, 3);
+        assert_eq!(config.stall_timeout_secs, 600);
+
+        let bad = [
+            "network_recovery:\n  resolvers: [dns.example]\n",
+            "network_recovery:\n  resolvers: []\n",
+            "network_recovery:\n  alternate_mirrors: [ftp://mirror.example/]\n",
+            "network_recovery:\n  alternate_mirrors: ['http://m/ | reboot']\n",
+            "network_recovery:\n  max_attempts: 0\n",
+        ];
+        for yaml in bad {
+            assert!(parse(yaml).validate().is_err(), "{}", yaml);
+        }
+    }
+}
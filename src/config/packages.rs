@@ -0,0 +1,259 @@
+// file: src/config/packages.rs
+// version: 1.1.0
+// guid: 9d4b7e26-1c83-4f5a-a6e9-0b2f8d5c7a14
+
+//! Package role mapping
+//!
+//! Package names differ between architectures and Ubuntu releases (`grub-efi-arm64` on
+//! arm64, `zsys` no longer shipped after noble). Callers ask for abstract roles such as
+//! `bootloader` or `zfs-tools` and get the concrete package names for their target.
+//! Package lists may reference a role with an `@` prefix, e.g. `@zfs-tools`.
+
+use super::{ubuntu_codename, Architecture};
+use crate::Result;
+use serde::{Deserialize, Serialize};
+
+/// Ubuntu release codenames from oldest to newest, used for "since"/"until" rules
+const RELEASE_ORDER: &[&str] = &[
+    "focal", "jammy", "lunar", "mantic", "noble", "oracular", "plucky",
+];
+
+/// Last release that still ships `zsys`
+const LAST_ZSYS_RELEASE: &str = "noble";
+
+/// Abstract package role resolved per architecture and release
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum PackageRole {
+    /// GRUB EFI bootloader
+    Bootloader,
+    /// Signed GRUB EFI binaries for Secure Boot
+    SignedBootloader,
+    /// Secure Boot shim
+    Shim,
+    /// Generic kernel image
+    Kernel,
+    /// Kernel headers matching the generic kernel
+    KernelHeaders,
+    /// ZFS userland tools
+    ZfsTools,
+    /// ZFS support in the initramfs
+    ZfsInitramfs,
+    /// ZFS system snapshot manager (zsys), where still available
+    ZfsSnapshots,
+    /// EFI boot entry management
+    EfiTools,
+    /// LUKS tools
+    Encryption,
+    /// LUKS unlock support in the initramfs
+    EncryptionInitramfs,
+    /// FAT tools for the ESP
+    EspTools,
+}
+
+impl PackageRole {
+    /// Roles the base system installs, in install order
+    pub const BASE_SYSTEM: &'static [PackageRole] = &[
+        PackageRole::Bootloader,
+        PackageRole::SignedBootloader,
+        PackageRole::Kernel,
+        PackageRole::Shim,
+        PackageRole::ZfsInitramfs,
+        PackageRole::ZfsTools,
+        PackageRole::ZfsSnapshots,
+        PackageRole::EfiTools,
+        PackageRole::Encryption,
+        PackageRole::EncryptionInitramfs,
+        PackageRole::EspTools,
+    ];
+
+    /// Every role: the base system's, then the ones installed only on request
+    pub const ALL: &'static [PackageRole] = &[
+        PackageRole::Bootloader,
+        PackageRole::SignedBootloader,
+        PackageRole::Kernel,
+        PackageRole::Shim,
+        PackageRole::ZfsInitramfs,
+        PackageRole::ZfsTools,
+        PackageRole::ZfsSnapshots,
+        PackageRole::EfiTools,
+        PackageRole::Encryption,
+        PackageRole::EncryptionInitramfs,
+        PackageRole::EspTools,
+        PackageRole::KernelHeaders,
+    ];
+
+    /// Role name as used in package lists (without the `@` prefix)
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            PackageRole::Bootloader => "bootloader",
+            PackageRole::SignedBootloader => "signed-bootloader",
+            PackageRole::Shim => "shim",
+            PackageRole::Kernel => "kernel",
+            PackageRole::KernelHeaders => "kernel-headers",
+            PackageRole::ZfsTools => "zfs-tools",
+            PackageRole::ZfsInitramfs => "zfs-initramfs",
+            PackageRole::ZfsSnapshots => "zfs-snapshots",
+            PackageRole::EfiTools => "efi-tools",
+            PackageRole::Encryption => "encryption",
+            PackageRole::EncryptionInitramfs => "encryption-initramfs",
+            PackageRole::EspTools => "esp-tools",
+        }
+    }
+
+    /// Concrete package names for this role; empty when the role has no package there
+    pub fn packages(&self, arch: Architecture, release: &str) -> Vec<&'static str> {
+        let release = release_codename(release);
+        match self {
+            PackageRole::Bootloader => match arch {
+                Architecture::Amd64 => vec!["grub-efi-amd64"],
+                Architecture::Arm64 => vec!["grub-efi-arm64"],
+            },
+            PackageRole::SignedBootloader => match arch {
+                Architecture::Amd64 => vec!["grub-efi-amd64-signed"],
+                Architecture::Arm64 => vec!["grub-efi-arm64-signed"],
+            },
+            PackageRole::Shim => vec!["shim-signed"],
+            PackageRole::Kernel => vec!["linux-image-generic"],
+            PackageRole::KernelHeaders => vec!["linux-headers-generic"],
+            PackageRole::ZfsTools => vec!["zfsutils-linux"],
+            PackageRole::ZfsInitramfs => vec!["zfs-initramfs"],
+            PackageRole::ZfsSnapshots => {
+                if release_at_most(release, LAST_ZSYS_RELEASE) {
+                    vec!["zsys"]
+                } else {
+                    vec![]
+                }
+            }
+            PackageRole::EfiTools => vec!["efibootmgr"],
+            PackageRole::Encryption => vec!["cryptsetup"],
+            PackageRole::EncryptionInitramfs => vec!["cryptsetup-initramfs"],
+            PackageRole::EspTools => vec!["dosfstools"],
+        }
+    }
+}
+
+impl std::str::FromStr for PackageRole {
+    type Err = crate::error::AutoInstallError;
+
+    fn from_str(s: &str) -> std::result::Result<Self, Self::Err> {
+        PackageRole::ALL
+            .iter()
+            .find(|role| role.as_str() == s)
+            .copied()
+            .ok_or_else(|| {
+                crate::error::AutoInstallError::ValidationError(format!(
+                    "Unknown package role: {}",
+                    s
+                ))
+            })
+    }
+}
+
+/// Normalize a version ("24.04") or codename ("noble") to a codename
+fn release_codename(release: &str) -> &str {
+    ubuntu_codename(release).unwrap_or(release)
+}
+
+/// Whether `release` is `limit` or older; unknown releases are treated as newer
+fn release_at_most(release: &str, limit: &str) -> bool {
+    let position = |r: &str| RELEASE_ORDER.iter().position(|known| *known == r);
+    match (position(release), position(limit)) {
+        (Some(r), Some(l)) => r <= l,
+        _ => false,
+    }
+}
+
+/// Packages for `roles`, deduplicated in order
+pub fn packages_for_roles(roles: &[PackageRole], arch: Architecture, release: &str) -> Vec<String> {
+    let mut packages: Vec<String> = Vec::new();
+    for role in roles {
+        for pkg in role.packages(arch, release) {
+            if !packages.iter().any(|p| p == pkg) {
+                packages.push(pkg.to_string());
+            }
+        }
+    }
+    packages
+}
+
+/// Expand `@role` entries in a package list, passing other names through unchanged
+pub fn resolve_package_list(
+    entries: &[String],
+    arch: Architecture,
+    release: &str,
+) -> Result<Vec<String>> {
+    let mut packages: Vec<String> = Vec::new();
+    for entry in entries {
+        let expanded = match entry.strip_prefix('@') {
+            Some(role) => packages_for_roles(&[role.parse()?], arch, release),
+            None => vec![entry.clone()],
+        };
+        for pkg in expanded {
+            if !packages.contains(&pkg) {
+                packages.push(pkg);
+            }
+        }
+    }
+    Ok(packages)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_bootloader_follows_architecture() {
+        assert_eq!(
+            PackageRole::Bootloader.packages(Architecture::Amd64, "noble"),
+            vec!["grub-efi-amd64"]
+        );
+        assert_eq!(
+            PackageRole::SignedBootloader.packages(Architecture::Arm64, "24.04"),
+            vec!["grub-efi-arm64-signed"]
+        );
+    }
+
+    #[test]
+    fn test_zsys_dropped_after_noble() {
+        let role = PackageRole::ZfsSnapshots;
+        assert_eq!(role.packages(Architecture::Amd64, "jammy"), vec!["zsys"]);
+        assert_eq!(role.packages(Architecture::Amd64, "24.04"), vec!["zsys"]);
+        assert!(role.packages(Architecture::Amd64, "plucky").is_empty());
+        assert!(role.packages(Architecture::Amd64, "25.04").is_empty());
+        assert!(role.packages(Architecture::Amd64, "future").is_empty());
+    }
+
+    #[test]
+    fn test_resolve_package_list_expands_roles() {
+        let entries = vec![
+            "openssh-server".to_string(),
+            "@zfs-tools".to_string(),
+            "@bootloader".to_string(),
+            "zfsutils-linux".to_string(),
+        ];
+        let resolved = resolve_package_list(&entries, Architecture::Arm64, "plucky").unwrap();
+        assert_eq!(
+            resolved,
+            vec!["openssh-server", "zfsutils-linux", "grub-efi-arm64"]
+        );
+        assert!(
+            resolve_package_list(&["@nope".to_string()], Architecture::Amd64, "noble").is_err()
+        );
+    }
+
+    #[test]
+    fn test_role_names_roundtrip() {
+        for role in PackageRole::ALL {
+            assert_eq!(role.as_str().parse::<PackageRole>().unwrap(), *role);
+        }
+        assert_eq!(
+            "kernel-headers".parse::<PackageRole>().unwrap(),
+            PackageRole::KernelHeaders
+        );
+        assert!(PackageRole::BASE_SYSTEM
+            .iter()
+            .all(|role| PackageRole::ALL.contains(role)));
+        assert!(!PackageRole::BASE_SYSTEM.contains(&PackageRole::KernelHeaders));
+    }
+}
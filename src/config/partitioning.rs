@@ -0,0 +1,171 @@
+// file: src/config/partitioning.rs
+// version: 1.1.0
+// guid: 2f6a9d41-c7e3-4b18-9a05-e8d1b3c76f29
+
+//! Bring-your-own partitioning (`partitioning:` section of a target config)
+//!
+//! By default the installer wipes the install disk and creates its own four partitions. With
+//! `mode: existing` the partitions are left as they are; with `mode: commands` the operator's
+//! commands (typically `sgdisk`) run instead of the built-in ones. Either way the layout found
+//! on the disk is checked against `expect` before LUKS, ZFS and debootstrap touch it. The rest
+//! of the install addresses partitions by number, so the expected roles keep their numbers:
+//! 1 ESP, 2 RESET, 3 boot pool, 4 LUKS.
+
+use crate::error::AutoInstallError;
+use crate::Result;
+use serde::{Deserialize, Serialize};
+
+/// Who lays out the install disk
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum PartitioningMode {
+    /// Wipe the disk and create the standard layout
+    #[default]
+    Auto,
+    /// Use the partitions already on the disk
+    Existing,
+    /// Run `commands` instead of the built-in partitioning
+    Commands,
+}
+
+impl PartitioningMode {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Self::Auto => "auto",
+            Self::Existing => "existing",
+            Self::Commands => "commands",
+        }
+    }
+}
+
+/// A partition the install needs
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ExpectedPartition {
+    pub number: u32,
+    /// sgdisk type code, e.g. `EF00`
+    #[serde(rename = "type")]
+    pub type_code: String,
+    #[serde(default)]
+    pub min_size_mb: Option<u64>,
+    #[serde(default)]
+    pub max_size_mb: Option<u64>,
+    /// Filesystem that must already be on the partition (`blkid` TYPE), e.g. `vfat`
+    #[serde(default)]
+    pub fstype: Option<String>,
+}
+
+impl ExpectedPartition {
+    fn new(number: u32, type_code: &str, min_size_mb: u64) -> Self {
+        Self {
+            number,
+            type_code: type_code.to_string(),
+            min_size_mb: Some(min_size_mb),
+            max_size_mb: None,
+            fstype: None,
+        }
+    }
+}
+
+/// The roles the rest of the install addresses by partition number
+pub fn standard_layout() -> Vec<ExpectedPartition> {
+    vec![
+        ExpectedPartition::new(1, "EF00", 256),
+        ExpectedPartition::new(2, "8300", 1024),
+        ExpectedPartition::new(3, "BE00", 1024),
+        ExpectedPartition::new(4, "8309", 8192),
+    ]
+}
+
+/// Who partitions the install disk and what it must look like afterwards
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct PartitioningConfig {
+    pub mode: PartitioningMode,
+    /// Commands run on the live system in `commands` mode, in order
+    pub commands: Vec<String>,
+    /// Layout the disk must have before the install continues
+    pub expect: Vec<ExpectedPartition>,
+    /// Create fresh filesystems on the ESP and RESET partitions; turn off to keep existing ones
+    pub format: bool,
+}
+
+impl Default for PartitioningConfig {
+    fn default() -> Self {
+        Self {
+            mode: PartitioningMode::Auto,
+            commands: Vec::new(),
+            expect: standard_layout(),
+            format: true,
+        }
+    }
+}
+
+impl PartitioningConfig {
+    /// Whether the installer partitions the disk itself
+    pub fn is_auto(&self) -> bool {
+        self.mode == PartitioningMode::Auto
+    }
+
+    pub fn validate(&self) -> Result<()> {
+        if self.mode == PartitioningMode::Commands && self.commands.is_empty() {
+            return Err(AutoInstallError::ValidationError(
+                "partitioning mode 'commands' needs at least one command".to_string(),
+            ));
+        }
+        for role in standard_layout() {
+            if !self.expect.iter().any(|p| p.number == role.number) {
+                return Err(AutoInstallError::ValidationError(format!(
+                    "partitioning expect must describe partition {} (the install uses partitions 1-4)",
+                    role.number
+                )));
+            }
+        }
+        for partition in &self.expect {
+            if let (Some(min), Some(max)) = (partition.min_size_mb, partition.max_size_mb) {
+                if min > max {
+                    return Err(AutoInstallError::ValidationError(format!(
+                        "partitioning expect {}: min_size_mb {} is above max_size_mb {}",
+                        partition.number, min, max
+                    )));
+                }
+            }
+        }
+        if !self.format
+            && !self
+                .expect
+                .iter()
+                .filter(|p| p.number <= 2)
+                .all(|p| p.fstype.is_some())
+        {
+            return Err(AutoInstallError::ValidationError(
+                "partitioning format: false needs the fstype of partitions 1 and 2 in expect"
+                    .to_string(),
+            ));
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::loader::parse_section;
+
+    #[test]
+    fn test_validate_expected_layout() {
+        let config = parse_section::<PartitioningConfig>(
+            "partitioning:\n  mode: existing\n",
+            "partitioning",
+        )
+        .unwrap();
+        assert_eq!(config.mode, PartitioningMode::Existing);
+        assert_eq!(config.expect, standard_layout());
+        assert!(config.validate().is_ok());
+
+        let commands = parse_section::<PartitioningConfig>(
+            "partitioning:\n  mode: commands\n  format: false\n  expect:\n    - {number: 1, type: EF00, fstype: vfat}\n    - {number: 2, type: '8300', fstype: ext4}\n    - {number: 3, type: BE00}\n",
+        "partitioning").unwrap();
+        // No commands and no partition 4
+        assert!(commands.validate().is_err());
+    }
+}
@@ -0,0 +1,273 @@
+// file: src/config/performance.rs
+// version: 1.2.0
+// guid: 6c4a9e21-5f83-4b7d-a0e6-2d8f1b3c7a94
+
+//! Performance presets for the installed system (`performance:` section of a target config)
+//!
+//! A preset expands into a curated set of kernel command line parameters, sysctls and
+//! compressed swap settings:
+//!
+//! - `latency`: full preemption, shallow C-states, busy polling, no zswap
+//! - `throughput`: no preemption, always-on transparent hugepages, larger network and dirty
+//!   page budgets, zswap in front of the disk swap
+//! - `low-memory`: zram swap sized from RAM (systemd-zram-generator), zswap off so pages are
+//!   not compressed twice, aggressive swapping into zram
+//!
+//! ```yaml
+//! performance:
+//!   preset: low-memory
+//!   zram_percent: 75    # overrides the preset's zram size
+//! ```
+//!
+//! The sysctl file sorts before the `kernel.sysctl` file, so explicit kernel tuning wins. This
+//! is unrelated to `low_memory:`, which only affects the live system during the install.
+
+use super::write_managed_file;
+use crate::error::AutoInstallError;
+use crate::Result;
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+
+/// GRUB defaults snippet carrying the preset's kernel parameters
+pub const GRUB_PERFORMANCE_FILE: &str = "etc/default/grub.d/70-autoinstall-performance.cfg";
+/// sysctl file; sorts after the hardening file and before `kernel.sysctl`'s
+pub const SYSCTL_FILE: &str = "etc/sysctl.d/70-autoinstall-performance.conf";
+/// Read by systemd-zram-generator at boot
+pub const ZRAM_GENERATOR_FILE: &str = "etc/systemd/zram-generator.conf";
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum PerformancePreset {
+    Latency,
+    Throughput,
+    LowMemory,
+}
+
+impl PerformancePreset {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            PerformancePreset::Latency => "latency",
+            PerformancePreset::Throughput => "throughput",
+            PerformancePreset::LowMemory => "low-memory",
+        }
+    }
+}
+
+/// zram swap device created at boot
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ZramSwap {
+    /// Size as a share of RAM
+    pub percent: u32,
+    pub algorithm: &'static str,
+}
+
+/// Everything a preset applies
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct PresetExpansion {
+    pub kernel_args: Vec<String>,
+    pub sysctl: BTreeMap<String, String>,
+    pub zram: Option<ZramSwap>,
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct PerformanceConfig {
+    pub preset: Option<PerformancePreset>,
+    /// zram size as a share of RAM, for presets that set up zram
+    pub zram_percent: Option<u32>,
+}
+
+impl PerformanceConfig {
+    pub fn validate(&self) -> Result<()> {
+        if let Some(percent) = self.zram_percent {
+            if !(1..=150).contains(&percent) {
+                return Err(AutoInstallError::ValidationError(format!(
+                    "performance.zram_percent must be between 1 and 150, got {}",
+                    percent
+                )));
+            }
+            if self.preset != Some(PerformancePreset::LowMemory) {
+                return Err(AutoInstallError::ValidationError(
+                    "performance.zram_percent only applies to the low-memory preset".to_string(),
+                ));
+            }
+        }
+        Ok(())
+    }
+
+    /// Parameters, sysctls and swap settings of the chosen preset; empty without one
+    pub fn expand(&self) -> PresetExpansion {
+        let Some(preset) = self.preset else {
+            return PresetExpansion::default();
+        };
+        let (args, sysctl, zram): (&[&str], &[(&str, &str)], _) = match preset {
+            PerformancePreset::Latency => (
+                &[
+                    "preempt=full",
+                    "processor.max_cstate=1",
+                    "intel_idle.max_cstate=1",
+                    "transparent_hugepage=madvise",
+                    "zswap.enabled=0",
+                ],
+                &[
+                    ("net.core.busy_poll", "50"),
+                    ("net.core.busy_read", "50"),
+                    ("vm.stat_interval", "10"),
+                    ("vm.swappiness", "10"),
+                ],
+                None,
+            ),
+            PerformancePreset::Throughput => (
+                &[
+                    "preempt=none",
+                    "transparent_hugepage=always",
+                    "zswap.enabled=1",
+                    "zswap.compressor=zstd",
+                    "zswap.max_pool_percent=20",
+                ],
+                &[
+                    ("net.core.netdev_max_backlog", "16384"),
+                    ("net.core.somaxconn", "8192"),
+                    ("net.ipv4.tcp_slow_start_after_idle", "0"),
+                    ("vm.dirty_background_ratio", "10"),
+                    ("vm.dirty_ratio", "40"),
+                    ("vm.swappiness", "10"),
+                ],
+                None,
+            ),
+            PerformancePreset::LowMemory => (
+                &["zswap.enabled=0", "transparent_hugepage=never"],
+                &[
+                    ("vm.page-cluster", "0"),
+                    ("vm.swappiness", "180"),
+                    ("vm.vfs_cache_pressure", "50"),
+                    ("vm.watermark_boost_factor", "0"),
+                    ("vm.watermark_scale_factor", "125"),
+                ],
+                Some(ZramSwap {
+                    percent: self.zram_percent.unwrap_or(50),
+                    algorithm: "zstd",
+                }),
+            ),
+        };
+        PresetExpansion {
+            kernel_args: args.iter().map(|a| a.to_string()).collect(),
+            sysctl: sysctl
+                .iter()
+                .map(|(k, v)| (k.to_string(), v.to_string()))
+                .collect(),
+            zram,
+        }
+    }
+
+    /// Kernel parameters for bootloaders that do not read `/etc/default/grub.d`
+    pub fn kernel_args(&self) -> Vec<String> {
+        self.expand().kernel_args
+    }
+
+    /// The expansion as plan lines, e.g. `sysctl vm.swappiness=180`
+    pub fn describe(&self) -> Vec<String> {
+        let Some(preset) = self.preset else {
+            return Vec::new();
+        };
+        let expansion = self.expand();
+        let mut lines = vec![
+            format!("preset: {}", preset.as_str()),
+            format!("cmdline: {}", expansion.kernel_args.join(" ")),
+        ];
+        lines.extend(
+            expansion
+                .sysctl
+                .iter()
+                .map(|(k, v)| format!("sysctl {}={}", k, v)),
+        );
+        if let Some(zram) = &expansion.zram {
+            lines.push(format!(
+                "zram: {}% of RAM, {}",
+                zram.percent, zram.algorithm
+            ));
+        }
+        lines
+    }
+
+    /// Commands writing the preset into the system under `root`; the GRUB snippet only takes
+    /// effect on the next `update-grub`, so these must run before the bootloader is configured
+    pub fn build_apply_commands(&self, root: &str) -> Vec<String> {
+        if self.preset.is_none() {
+            return Vec::new();
+        }
+        let root = root.trim_end_matches('/');
+        let expansion = self.expand();
+        let sysctl: String = expansion
+            .sysctl
+            .iter()
+            .map(|(k, v)| format!("{} = {}\n", k, v))
+            .collect();
+        let mut commands = vec![
+            write_managed_file(
+                root,
+                GRUB_PERFORMANCE_FILE,
+                &format!(
+                    "GRUB_CMDLINE_LINUX_DEFAULT=\"$GRUB_CMDLINE_LINUX_DEFAULT {}\"\n",
+                    expansion.kernel_args.join(" ")
+                ),
+            ),
+            write_managed_file(root, SYSCTL_FILE, &sysctl),
+        ];
+        if let Some(zram) = &expansion.zram {
+            commands.push(format!(
+                "chroot {} bash -lc 'DEBIAN_FRONTEND=noninteractive apt-get install -y systemd-zram-generator'",
+                root
+            ));
+            commands.push(write_managed_file(
+                root,
+                ZRAM_GENERATOR_FILE,
+                &format!(
+                    "[zram0]\nzram-size = ram * {} / 100\ncompression-algorithm = {}\nswap-priority = 100\n",
+                    zram.percent, zram.algorithm
+                ),
+            ));
+        }
+        commands
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::loader::parse_section;
+
+    #[test]
+    fn test_low_memory_preset_expands_to_zram() {
+        let config = parse_section::<PerformanceConfig>(
+            "performance:\n  preset: low-memory\n  zram_percent: 75\n",
+            "performance",
+        )
+        .unwrap();
+        assert!(config.validate().is_ok());
+        assert_eq!(
+            config.kernel_args(),
+            ["zswap.enabled=0", "transparent_hugepage=never"]
+        );
+        assert!(config
+            .describe()
+            .contains(&"zram: 75% of RAM, zstd".to_string()));
+
+        let commands = config.build_apply_commands("/mnt/targetos/");
+        assert_eq!(commands.len(), 4);
+        assert!(commands[0].contains(
+            "GRUB_CMDLINE_LINUX_DEFAULT=\"$GRUB_CMDLINE_LINUX_DEFAULT zswap.enabled=0 transparent_hugepage=never\""
+        ));
+        assert!(commands[1].contains("vm.swappiness = 180\n"));
+        assert!(commands[3].contains("zram-size = ram * 75 / 100\n"));
+
+        let latency = PerformanceConfig {
+            preset: Some(PerformancePreset::Latency),
+            zram_percent: Some(50),
+        };
+        assert!(latency.validate().is_err());
+        assert!(PerformanceConfig::default()
+            .build_apply_commands("/mnt/targetos")
+            .is_empty());
+    }
+}
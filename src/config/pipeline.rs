@@ -0,0 +1,390 @@
+// file: src/config/pipeline.rs
+// version: 1.1.0
+// guid: 8e4c1f72-6b39-4d05-a2e8-3f9d7b1c5a64
+
+//! Pipelines: image build, publish, fleet rollout and verification as one file (`run-pipeline`)
+//!
+//! ```yaml
+//! name: weekly-web
+//! parameters:
+//!   inventory: inventory/web.yaml
+//!   rollout: "true"
+//! stages:
+//!   - name: build
+//!     create_image:
+//!       spec: specs/web.yaml
+//!       output: build/web-{{ params.date }}.qcow2
+//!   - name: publish
+//!     publish:
+//!       dir: /srv/images/web
+//!       latest: web-latest.qcow2
+//!   - name: rollout
+//!     when: rollout=true
+//!     fleet_deploy:
+//!       inventory: "{{ params.inventory }}"
+//!       selector: role=web
+//!       yes: true
+//!   - name: verify
+//!     when: rollout=true
+//!     verify:
+//!       inventory: "{{ params.inventory }}"
+//! ```
+//!
+//! Strings may reference `{{ params.<name> }}`: the declared parameters, `--set` overrides,
+//! the built-in `pipeline` and `date` (UTC `YYYYMMDD`), and `image`, which `create_image` and
+//! `publish` set to the image they produced. A `when:` condition uses the selector syntax of
+//! fleet commands against the parameters (see [`HostSelector`]); a stage whose condition does
+//! not hold is skipped.
+
+use crate::config::inventory::HostSelector;
+use crate::config::Architecture;
+use crate::error::AutoInstallError;
+use crate::Result;
+use regex::Regex;
+use serde::{Deserialize, Serialize};
+use std::collections::{BTreeMap, BTreeSet};
+
+/// Parameters every pipeline has without declaring them
+pub const BUILTIN_PARAMETERS: [&str; 3] = ["pipeline", "date", "image"];
+
+/// A named sequence of stages sharing parameters
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct PipelineConfig {
+    pub name: String,
+    #[serde(default)]
+    pub parameters: BTreeMap<String, String>,
+    pub stages: Vec<PipelineStage>,
+}
+
+/// One step of a pipeline
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct PipelineStage {
+    pub name: String,
+    /// Parameter selector, e.g. `rollout=true,env!=staging`; the stage always runs without one
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub when: Option<String>,
+    #[serde(flatten)]
+    pub action: StageAction,
+}
+
+/// What a stage does; each corresponds to a CLI command
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum StageAction {
+    /// `create-image`
+    CreateImage(CreateImageStage),
+    /// Copy the image and its sidecars into a published directory
+    Publish(PublishStage),
+    /// `fleet deploy`
+    FleetDeploy(FleetDeployStage),
+    /// `verify-host` for every selected inventory host
+    Verify(VerifyStage),
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct CreateImageStage {
+    /// Image spec; a minimal image is built without one
+    #[serde(default)]
+    pub spec: Option<String>,
+    #[serde(default = "default_ubuntu_version")]
+    pub ubuntu_version: String,
+    #[serde(default = "default_arch")]
+    pub arch: Architecture,
+    pub output: String,
+    #[serde(default)]
+    pub cache_dir: Option<String>,
+    /// Build under software emulation when KVM is unavailable
+    #[serde(default)]
+    pub allow_tcg: bool,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct PublishStage {
+    /// Image to publish; the image of the previous build by default
+    #[serde(default = "default_image")]
+    pub image: String,
+    pub dir: String,
+    /// Symlink in `dir` pointed at the published image
+    #[serde(default)]
+    pub latest: Option<String>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct FleetDeployStage {
+    pub inventory: String,
+    #[serde(default)]
+    pub selector: Option<String>,
+    /// Skip the confirmation prompt, for unattended runs
+    #[serde(default)]
+    pub yes: bool,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct VerifyStage {
+    pub inventory: String,
+    #[serde(default)]
+    pub selector: Option<String>,
+    #[serde(default = "default_username")]
+    pub username: String,
+}
+
+fn default_ubuntu_version() -> String {
+    "24.04".to_string()
+}
+
+fn default_arch() -> Architecture {
+    Architecture::Amd64
+}
+
+fn default_image() -> String {
+    "{{ params.image }}".to_string()
+}
+
+fn default_username() -> String {
+    "ubuntu".to_string()
+}
+
+impl StageAction {
+    pub fn kind(&self) -> &'static str {
+        match self {
+            Self::CreateImage(_) => "create_image",
+            Self::Publish(_) => "publish",
+            Self::FleetDeploy(_) => "fleet_deploy",
+            Self::Verify(_) => "verify",
+        }
+    }
+
+    /// Whether the stage sets the `image` parameter
+    pub fn produces_image(&self) -> bool {
+        matches!(self, Self::CreateImage(_) | Self::Publish(_))
+    }
+}
+
+impl PipelineStage {
+    /// Whether the stage runs with `params`
+    pub fn should_run(&self, params: &BTreeMap<String, String>) -> Result<bool> {
+        match &self.when {
+            Some(when) => Ok(HostSelector::parse(when)?.matches_labels(params)),
+            None => Ok(true),
+        }
+    }
+
+    /// The stage's action with every `{{ params.* }}` template expanded
+    pub fn rendered(&self, params: &BTreeMap<String, String>) -> Result<StageAction> {
+        let re = params_regex()?;
+        let mut undefined = BTreeSet::new();
+        let mut value = serde_yaml::to_value(&self.action)?;
+        render_value(&mut value, &re, params, &mut undefined);
+        if !undefined.is_empty() {
+            return Err(AutoInstallError::ConfigError(format!(
+                "Stage '{}' references undefined parameter(s): {}",
+                self.name,
+                undefined.into_iter().collect::<Vec<_>>().join(", ")
+            )));
+        }
+        Ok(serde_yaml::from_value(value)?)
+    }
+}
+
+fn params_regex() -> Result<Regex> {
+    Regex::new(r"\{\{\s*params\.([A-Za-z0-9_.-]+)\s*\}\}")
+        .map_err(|e| AutoInstallError::ConfigError(format!("Invalid regex pattern: {}", e)))
+}
+
+fn render_value(
+    value: &mut serde_yaml::Value,
+    re: &Regex,
+    params: &BTreeMap<String, String>,
+    undefined: &mut BTreeSet<String>,
+) {
+    match value {
+        serde_yaml::Value::String(text) => {
+            let rendered = re.replace_all(text, |cap: &regex::Captures| {
+                params.get(&cap[1]).cloned().unwrap_or_else(|| {
+                    undefined.insert(cap[1].to_string());
+                    String::new()
+                })
+            });
+            *text = rendered.into_owned();
+        }
+        serde_yaml::Value::Sequence(items) => {
+            for item in items {
+                render_value(item, re, params, undefined);
+            }
+        }
+        serde_yaml::Value::Mapping(map) => {
+            for (_, item) in map.iter_mut() {
+                render_value(item, re, params, undefined);
+            }
+        }
+        serde_yaml::Value::Tagged(tagged) => render_value(&mut tagged.value, re, params, undefined),
+        _ => {}
+    }
+}
+
+impl PipelineConfig {
+    pub fn validate(&self) -> Result<()> {
+        if self.name.trim().is_empty() {
+            return Err(AutoInstallError::ValidationError(
+                "Pipeline name must not be empty".to_string(),
+            ));
+        }
+        if self.stages.is_empty() {
+            return Err(AutoInstallError::ValidationError(format!(
+                "Pipeline '{}' has no stages",
+                self.name
+            )));
+        }
+
+        // Every parameter that can exist gets a placeholder, so templates are checked before
+        // anything runs
+        let mut placeholders = self.parameters.clone();
+        for name in BUILTIN_PARAMETERS {
+            placeholders.entry(name.to_string()).or_default();
+        }
+        let mut names = BTreeSet::new();
+        for stage in &self.stages {
+            if stage.name.trim().is_empty() {
+                return Err(AutoInstallError::ValidationError(format!(
+                    "Pipeline '{}' has a stage without a name",
+                    self.name
+                )));
+            }
+            if !names.insert(stage.name.as_str()) {
+                return Err(AutoInstallError::ValidationError(format!(
+                    "Stage name '{}' is used more than once",
+                    stage.name
+                )));
+            }
+            if let Some(when) = &stage.when {
+                HostSelector::parse(when)?;
+            }
+            let empty = match stage.rendered(&placeholders)? {
+                StageAction::CreateImage(s) => s.output.trim().is_empty().then_some("output"),
+                StageAction::Publish(s) => s.dir.trim().is_empty().then_some("dir"),
+                StageAction::FleetDeploy(s) => s.inventory.trim().is_empty().then_some("inventory"),
+                StageAction::Verify(s) => s.inventory.trim().is_empty().then_some("inventory"),
+            };
+            if let Some(field) = empty {
+                return Err(AutoInstallError::ValidationError(format!(
+                    "Stage '{}' ({}) needs {}",
+                    stage.name,
+                    stage.action.kind(),
+                    field
+                )));
+            }
+        }
+        Ok(())
+    }
+
+    /// Parameters of a run: the declared ones and the built-ins, then `overrides` (`name=value`)
+    ///
+    /// Overrides may only set declared parameters or `image`, so a typo is not silently ignored.
+    pub fn parameters_with(
+        &self,
+        overrides: &[String],
+        today: chrono::NaiveDate,
+    ) -> Result<BTreeMap<String, String>> {
+        let mut params = self.parameters.clone();
+        params
+            .entry("pipeline".to_string())
+            .or_insert_with(|| self.name.clone());
+        params
+            .entry("date".to_string())
+            .or_insert_with(|| today.format("%Y%m%d").to_string());
+        for assignment in overrides {
+            let Some((name, value)) = assignment.split_once('=') else {
+                return Err(AutoInstallError::ValidationError(format!(
+                    "Expected name=value, got '{}'",
+                    assignment
+                )));
+            };
+            let name = name.trim();
+            if !params.contains_key(name) && name != "image" {
+                return Err(AutoInstallError::ValidationError(format!(
+                    "Pipeline '{}' has no parameter '{}'",
+                    self.name, name
+                )));
+            }
+            params.insert(name.to_string(), value.to_string());
+        }
+        Ok(params)
+    }
+
+    /// Index of the stage named `name`, to resume a run from
+    pub fn stage_index(&self, name: &str) -> Result<usize> {
+        self.stages
+            .iter()
+            .position(|stage| stage.name == name)
+            .ok_or_else(|| {
+                AutoInstallError::ValidationError(format!(
+                    "Pipeline '{}' has no stage '{}'",
+                    self.name, name
+                ))
+            })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const PIPELINE: &str = r#"
+name: weekly-web
+parameters:
+  inventory: inventory/web.yaml
+  rollout: "true"
+stages:
+  - name: build
+    create_image:
+      spec: specs/web.yaml
+      output: build/web-{{ params.date }}.qcow2
+  - name: publish
+    publish:
+      dir: /srv/images/web
+      latest: web-latest.qcow2
+  - name: rollout
+    when: rollout=true
+    fleet_deploy:
+      inventory: "{{ params.inventory }}"
+      selector: role=web
+"#;
+
+    #[test]
+    fn test_pipeline_parameters_conditions_and_templates() {
+        let pipeline: PipelineConfig = serde_yaml::from_str(PIPELINE).unwrap();
+        pipeline.validate().unwrap();
+        assert_eq!(pipeline.stage_index("rollout").unwrap(), 2);
+        assert!(pipeline.stage_index("deploy").is_err());
+
+        let today = chrono::NaiveDate::from_ymd_opt(2026, 10, 12).unwrap();
+        let params = pipeline
+            .parameters_with(&["rollout=false".to_string()], today)
+            .unwrap();
+        assert_eq!(params["pipeline"], "weekly-web");
+        assert!(!pipeline.stages[2].should_run(&params).unwrap());
+        assert!(pipeline.stages[0].should_run(&params).unwrap());
+        assert!(pipeline
+            .parameters_with(&["rolout=false".to_string()], today)
+            .is_err());
+
+        match pipeline.stages[0].rendered(&params).unwrap() {
+            StageAction::CreateImage(stage) => {
+                assert_eq!(stage.output, "build/web-20261012.qcow2");
+                assert_eq!(stage.arch, Architecture::Amd64);
+            }
+            other => panic!("unexpected stage {:?}", other),
+        }
+        // `image` only exists once a build or publish stage has run
+        let err = pipeline.stages[1].rendered(&params).unwrap_err();
+        assert!(err.to_string().contains("undefined parameter(s): image"));
+
+        let mut typo = pipeline.clone();
+        typo.stages[2].action = StageAction::Verify(VerifyStage {
+            inventory: "{{ params.inventroy }}".to_string(),
+            selector: None,
+            username: default_username(),
+        });
+        assert!(typo.validate().is_err());
+    }
+}
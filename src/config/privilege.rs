@@ -0,0 +1,87 @@
+// file: src/config/privilege.rs
+// version: 1.1.0
+// guid: 3f7a1c95-6e28-4b04-9d6a-e2b8c51f7a49
+
+//! How the installer gets root on the target (`privilege:` section of a target config)
+//!
+//! By default the SSH user is expected to be root. With `mode: sudo` the installer logs in as
+//! an unprivileged user and runs each command through `sudo`, except commands starting with
+//! one of the `unprivileged` prefixes. Without a `password`, sudo must be NOPASSWD for the
+//! user; this is checked right after connecting. Which commands ran elevated is written to
+//! `logs/<hostname>/privilege-audit.json`.
+//!
+//! The section is read before the target is investigated, so it cannot use `{{ facts.* }}`.
+
+use super::secrets::SecretRef;
+use crate::error::AutoInstallError;
+use crate::Result;
+use serde::{Deserialize, Serialize};
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum PrivilegeMode {
+    /// The SSH user is root; commands run as they are
+    #[default]
+    Root,
+    /// Elevate each command with sudo
+    Sudo,
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct PrivilegeConfig {
+    pub mode: PrivilegeMode,
+    /// sudo password; unset means the user must have NOPASSWD
+    pub password: Option<SecretRef>,
+    /// Commands starting with one of these run as the SSH user, e.g. `uname`, `cat /proc/`
+    pub unprivileged: Vec<String>,
+}
+
+impl PrivilegeConfig {
+    pub fn uses_sudo(&self) -> bool {
+        self.mode == PrivilegeMode::Sudo
+    }
+
+    pub fn validate(&self) -> Result<()> {
+        if !self.uses_sudo() && (self.password.is_some() || !self.unprivileged.is_empty()) {
+            return Err(AutoInstallError::ValidationError(
+                "privilege password and unprivileged only apply with mode: sudo".to_string(),
+            ));
+        }
+        if self.unprivileged.iter().any(|p| p.trim().is_empty()) {
+            return Err(AutoInstallError::ValidationError(
+                "privilege unprivileged prefixes must not be empty".to_string(),
+            ));
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::loader::parse_section;
+
+    #[test]
+    fn test_sudo_password_and_validate() {
+        let config = parse_section::<PrivilegeConfig>(
+            "privilege:\n  mode: sudo\n  password:\n    env: TARGET_SUDO_PASSWORD\n",
+            "privilege",
+        )
+        .unwrap();
+        assert!(config.uses_sudo());
+        assert_eq!(
+            config.password,
+            Some(SecretRef::Env {
+                env: "TARGET_SUDO_PASSWORD".to_string()
+            })
+        );
+        assert!(config.validate().is_ok());
+
+        let root = PrivilegeConfig {
+            unprivileged: vec!["uname".to_string()],
+            ..Default::default()
+        };
+        assert!(root.validate().is_err());
+    }
+}
@@ -0,0 +1,393 @@
+// file: src/config/progress.rs
+// version: 1.4.0
+// guid: 9c2e7a41-6b3d-4f18-a5e0-d8f14b6c3a92
+
+//! Progress reporting for long remote commands (`progress:` section of a target config)
+//!
+//! debootstrap, apt, `dd status=progress` and `zfs send -v` output is parsed without any
+//! configuration. `patterns` adds parsers for custom scripts: a regex selecting the command
+//! and a regex with a `percent` group, or `current` and `total` groups, matched per output line.
+//! `github` posts the overall build or install state to a commit status or check run, so the
+//! CI job that builds an image for a pull request shows up in its checks. `sinks` sends the
+//! same reports to JSONL files, S3, syslog or journald as well, each filtered by report type. A
+//! `beacon` sink has the target post them to a webhook the controller cannot reach.
+
+use crate::error::AutoInstallError;
+use regex::Regex;
+use serde::{Deserialize, Serialize};
+
+/// Parser for the output of a custom command
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ProgressPattern {
+    /// Label used in progress events; `custom` when unset
+    #[serde(default)]
+    pub name: Option<String>,
+    /// Regex matched against the remote command line
+    pub command: String,
+    /// Regex matched against each output line
+    pub pattern: String,
+    /// Also parse stderr (merged into stdout while the command runs)
+    #[serde(default)]
+    pub stderr: bool,
+}
+
+impl ProgressPattern {
+    /// Label used in progress events
+    pub fn label(&self) -> &str {
+        self.name.as_deref().unwrap_or("custom")
+    }
+}
+
+/// Commit the state of a build or install is reported against on GitHub
+///
+/// Unset fields fall back to the variables GitHub Actions provides (`GITHUB_REPOSITORY`,
+/// `GITHUB_SHA`, `GITHUB_API_URL` and the run URL), so a workflow only needs the token.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct GithubStatusConfig {
+    /// `owner/name`
+    pub repository: Option<String>,
+    /// Commit the status is attached to
+    pub sha: Option<String>,
+    /// Environment variable holding the GitHub App installation token (or a PAT)
+    pub token_env: String,
+    /// Status context, or check run name
+    pub context: String,
+    /// Link shown next to the status, e.g. where the artifacts are uploaded
+    pub target_url: Option<String>,
+    /// Report a check run instead of a commit status
+    pub check_run: bool,
+    /// REST API root, for GitHub Enterprise Server
+    pub api_url: Option<String>,
+}
+
+impl Default for GithubStatusConfig {
+    fn default() -> Self {
+        Self {
+            repository: None,
+            sha: None,
+            token_env: "GITHUB_TOKEN".to_string(),
+            context: "ubuntu-autoinstall-agent".to_string(),
+            target_url: None,
+            check_run: false,
+            api_url: None,
+        }
+    }
+}
+
+impl GithubStatusConfig {
+    /// Check the repository, commit, URLs and token variable name
+    pub fn validate(&self) -> crate::Result<()> {
+        if let Some(repository) = &self.repository {
+            let valid = repository.split_once('/').is_some_and(|(owner, name)| {
+                !owner.is_empty()
+                    && !name.is_empty()
+                    && repository
+                        .chars()
+                        .all(|c| c.is_ascii_alphanumeric() || matches!(c, '-' | '_' | '.' | '/'))
+                    && !name.contains('/')
+            });
+            if !valid {
+                return Err(AutoInstallError::ValidationError(format!(
+                    "progress.github.repository '{}' must be owner/name",
+                    repository
+                )));
+            }
+        }
+        if let Some(sha) = &self.sha {
+            if sha.len() != 40 || !sha.chars().all(|c| c.is_ascii_hexdigit()) {
+                return Err(AutoInstallError::ValidationError(format!(
+                    "progress.github.sha '{}' must be a full 40-character commit hash",
+                    sha
+                )));
+            }
+        }
+        for (field, url) in [("target_url", &self.target_url), ("api_url", &self.api_url)] {
+            if let Some(url) = url {
+                if !url.starts_with("http://") && !url.starts_with("https://") {
+                    return Err(AutoInstallError::ValidationError(format!(
+                        "progress.github.{} '{}' must be an http:// or https:// URL",
+                        field, url
+                    )));
+                }
+            }
+        }
+        if self.token_env.is_empty()
+            || !self
+                .token_env
+                .chars()
+                .all(|c| c.is_ascii_alphanumeric() || c == '_')
+        {
+            return Err(AutoInstallError::ValidationError(format!(
+                "progress.github.token_env '{}' is not an environment variable name",
+                self.token_env
+            )));
+        }
+        if self.context.is_empty() {
+            return Err(AutoInstallError::ValidationError(
+                "progress.github.context must not be empty".to_string(),
+            ));
+        }
+        Ok(())
+    }
+}
+
+/// Kind of report a sink can be limited to
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ReportKind {
+    /// Lifecycle events carrying the session record (`install.completed`, ...)
+    Session,
+    /// Percentages parsed from long remote commands
+    Progress,
+}
+
+/// Destination of reports
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum SinkTarget {
+    /// HTTP endpoint receiving each report as a JSON POST
+    Webhook { url: String },
+    /// Local file each report is appended to as one JSON line
+    File { path: String },
+    /// S3 (or compatible) bucket receiving one object per report; credentials come from
+    /// `AWS_ACCESS_KEY_ID`, `AWS_SECRET_ACCESS_KEY` and optionally `AWS_SESSION_TOKEN`
+    S3 {
+        bucket: String,
+        #[serde(default)]
+        prefix: String,
+        #[serde(default = "default_s3_region")]
+        region: String,
+        /// Endpoint of an S3-compatible store (path-style requests); AWS when unset
+        #[serde(default)]
+        endpoint: Option<String>,
+    },
+    /// RFC 5424 messages to a local socket path or a `host:port` UDP collector
+    Syslog {
+        #[serde(default = "default_syslog_address")]
+        address: String,
+    },
+    /// Structured entries through journald's native socket
+    Journald,
+    /// HTTP endpoint the target posts to itself, for receivers the controller cannot reach
+    /// (ssh-install only)
+    Beacon { url: String },
+}
+
+fn default_s3_region() -> String {
+    "us-east-1".to_string()
+}
+
+fn default_syslog_address() -> String {
+    "/dev/log".to_string()
+}
+
+/// One report destination and the report types it receives
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct SinkConfig {
+    #[serde(flatten)]
+    pub target: SinkTarget,
+    /// Report types sent to this sink; all of them when empty
+    #[serde(default)]
+    pub reports: Vec<ReportKind>,
+}
+
+impl SinkConfig {
+    /// Whether reports of `kind` go to this sink
+    pub fn accepts(&self, kind: ReportKind) -> bool {
+        self.reports.is_empty() || self.reports.contains(&kind)
+    }
+
+    /// Check URLs, paths and names
+    pub fn validate(&self) -> crate::Result<()> {
+        let invalid = |message: String| Err(AutoInstallError::ValidationError(message));
+        match &self.target {
+            SinkTarget::Webhook { url } | SinkTarget::Beacon { url } => {
+                if !url.starts_with("http://") && !url.starts_with("https://") {
+                    let kind = match self.target {
+                        SinkTarget::Beacon { .. } => "beacon",
+                        _ => "webhook",
+                    };
+                    return invalid(format!(
+                        "{} sink '{}' must be an http:// or https:// URL",
+                        kind, url
+                    ));
+                }
+            }
+            SinkTarget::File { path } => {
+                if path.is_empty() {
+                    return invalid("file sink needs a path".to_string());
+                }
+            }
+            SinkTarget::S3 {
+                bucket,
+                region,
+                endpoint,
+                ..
+            } => {
+                let valid_bucket = (3..=63).contains(&bucket.len())
+                    && bucket.chars().all(|c| {
+                        c.is_ascii_lowercase() || c.is_ascii_digit() || matches!(c, '-' | '.')
+                    });
+                if !valid_bucket {
+                    return invalid(format!("S3 sink bucket '{}' is not a valid name", bucket));
+                }
+                if region.is_empty()
+                    || !region
+                        .chars()
+                        .all(|c| c.is_ascii_lowercase() || c.is_ascii_digit() || c == '-')
+                {
+                    return invalid(format!("S3 sink region '{}' is not valid", region));
+                }
+                if let Some(endpoint) = endpoint {
+                    if !endpoint.starts_with("http://") && !endpoint.starts_with("https://") {
+                        return invalid(format!(
+                            "S3 sink endpoint '{}' must be an http:// or https:// URL",
+                            endpoint
+                        ));
+                    }
+                }
+            }
+            SinkTarget::Syslog { address } => {
+                let valid = address.starts_with('/')
+                    || address.rsplit_once(':').is_some_and(|(host, port)| {
+                        !host.is_empty() && port.parse::<u16>().is_ok()
+                    });
+                if !valid {
+                    return invalid(format!(
+                        "syslog sink address '{}' must be a socket path or host:port",
+                        address
+                    ));
+                }
+            }
+            SinkTarget::Journald => {}
+        }
+        Ok(())
+    }
+}
+
+/// Where progress events go and how custom commands are parsed
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct ProgressConfig {
+    /// URL receiving each progress event as JSON
+    pub webhook: Option<String>,
+    pub patterns: Vec<ProgressPattern>,
+    /// Commit status or check run tracking the whole build or install
+    pub github: Option<GithubStatusConfig>,
+    /// Further destinations for session and progress reports
+    pub sinks: Vec<SinkConfig>,
+}
+
+impl ProgressConfig {
+    /// Check the webhook URL and that every regex compiles with usable groups
+    pub fn validate(&self) -> crate::Result<()> {
+        if let Some(url) = &self.webhook {
+            if !url.starts_with("http://") && !url.starts_with("https://") {
+                return Err(AutoInstallError::ValidationError(format!(
+                    "progress.webhook '{}' must be an http:// or https:// URL",
+                    url
+                )));
+            }
+        }
+        if let Some(github) = &self.github {
+            github.validate()?;
+        }
+        for sink in &self.sinks {
+            sink.validate()?;
+        }
+        for pattern in &self.patterns {
+            compile(&pattern.command, "command")?;
+            let line = compile(&pattern.pattern, "pattern")?;
+            let groups: Vec<&str> = line.capture_names().flatten().collect();
+            let usable = groups.contains(&"percent")
+                || (groups.contains(&"current") && groups.contains(&"total"));
+            if !usable {
+                return Err(AutoInstallError::ValidationError(format!(
+                    "progress pattern '{}' needs a (?P<percent>...) group or (?P<current>...) and (?P<total>...) groups",
+                    pattern.pattern
+                )));
+            }
+        }
+        Ok(())
+    }
+}
+
+fn compile(regex: &str, field: &str) -> crate::Result<Regex> {
+    Regex::new(regex).map_err(|e| {
+        AutoInstallError::ValidationError(format!(
+            "progress pattern {} '{}' is not a valid regex: {}",
+            field, regex, e
+        ))
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::loader::parse_section;
+
+    fn parse(yaml: &str) -> ProgressConfig {
+        parse_section::<ProgressConfig>(yaml, "progress").unwrap()
+    }
+
+    #[test]
+    fn test_parse_patterns() {
+        let config = parse(
+            "progress:\n  webhook: https://hooks.example/progress\n  patterns:\n    - name: provision\n      command: ^/root/provision\\.sh\n      pattern: 'step (?P<current>\\d+)/(?P<total>\\d+)'\n",
+        );
+        assert!(config.validate().is_ok());
+        assert_eq!(config.patterns[0].label(), "provision");
+        assert!(!config.patterns[0].stderr);
+        assert_eq!(parse("hostname: a\n"), ProgressConfig::default());
+
+        let config =
+            parse("progress:\n  github:\n    repository: acme/images\n    check_run: true\n");
+        assert!(config.validate().is_ok());
+        let github = config.github.unwrap();
+        assert_eq!(github.token_env, "GITHUB_TOKEN");
+        assert_eq!(github.context, "ubuntu-autoinstall-agent");
+        assert!(github.check_run);
+    }
+
+    #[test]
+    fn test_parse_sinks() {
+        let config = parse(
+            "progress:\n  sinks:\n    - type: file\n      path: logs/reports.jsonl\n    - type: s3\n      bucket: install-reports\n      prefix: lab\n      reports: [session]\n    - type: syslog\n      address: logs.example:514\n      reports: [progress]\n    - type: journald\n",
+        );
+        assert!(config.validate().is_ok());
+        assert_eq!(config.sinks.len(), 4);
+        assert!(config.sinks[0].accepts(ReportKind::Progress));
+        assert_eq!(
+            config.sinks[1].target,
+            SinkTarget::S3 {
+                bucket: "install-reports".to_string(),
+                prefix: "lab".to_string(),
+                region: "us-east-1".to_string(),
+                endpoint: None,
+            }
+        );
+        assert!(config.sinks[1].accepts(ReportKind::Session));
+        assert!(!config.sinks[1].accepts(ReportKind::Progress));
+        assert_eq!(config.sinks[3].target, SinkTarget::Journald);
+    }
+
+    #[test]
+    fn test_validate_rejects_bad_patterns() {
+        let bad = [
+            "progress:\n  webhook: hooks.example\n",
+            "progress:\n  patterns:\n    - command: '('\n      pattern: '(?P<percent>\\d+)%'\n",
+            "progress:\n  patterns:\n    - command: sync\n      pattern: '(\\d+)%'\n",
+            "progress:\n  patterns:\n    - command: sync\n      pattern: '(?P<current>\\d+)'\n",
+            "progress:\n  github:\n    repository: just-a-name\n",
+            "progress:\n  github:\n    sha: abc123\n",
+            "progress:\n  github:\n    token_env: 'TOKEN; reboot'\n",
+            "progress:\n  sinks:\n    - type: s3\n      bucket: Reports\n",
+            "progress:\n  sinks:\n    - type: syslog\n      address: collector\n",
+            "progress:\n  sinks:\n    - type: beacon\n      url: hooks.example\n",
+        ];
+        for yaml in bad {
+            assert!(parse(yaml).validate().is_err(), "{}", yaml);
+        }
+    }
+}
@@ -0,0 +1,196 @@
+// file: src/config/protection.rs
+// version: 1.0.0
+// guid: 8b2e5f16-3d94-4a7c-9e01-7f6c2a8d4b53
+
+//! Protected hosts and environments (`protected.yaml` in the working directory)
+//!
+//! Destructive commands refuse to run against a protected machine unless the operator passes
+//! `--i-know-what-i-am-doing <TOKEN>`, where the token is the protected host's name (as listed)
+//! or its environment's name, or a second person approves the run through `approval_url`:
+//!
+//! ```yaml
+//! hosts:
+//!   db-01:
+//!     protected: true
+//!     reason: primary PostgreSQL
+//! environments:
+//!   production:
+//!     protected: true
+//!     hosts: ["prod-*", "10.0.3.*"]
+//! approval_url: https://approvals.example.com/protected?target={target}&command={command}
+//! ```
+//!
+//! Hosts are matched by the hostname and the address a command is given; `*` in environment
+//! patterns matches any run of characters.
+
+use crate::config::tenants::glob_match;
+use crate::error::AutoInstallError;
+use crate::Result;
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::path::Path;
+
+/// Registry file name under the working directory
+pub const PROTECTION_FILE: &str = "protected.yaml";
+
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ProtectedHost {
+    #[serde(default)]
+    pub protected: bool,
+    #[serde(default)]
+    pub reason: Option<String>,
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ProtectedEnvironment {
+    #[serde(default)]
+    pub protected: bool,
+    /// Hostnames or addresses belonging to the environment
+    pub hosts: Vec<String>,
+    #[serde(default)]
+    pub reason: Option<String>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct ProtectionRegistry {
+    pub hosts: BTreeMap<String, ProtectedHost>,
+    pub environments: BTreeMap<String, ProtectedEnvironment>,
+    /// Polled with GET while a run waits for a second approver; `{target}` and `{command}`
+    /// are filled in. A 200 answer with `{"approved": true, "approver": "<name>"}` approves,
+    /// `{"approved": false, "reason": ...}` rejects, and anything else keeps waiting.
+    pub approval_url: Option<String>,
+    /// Give up waiting for an approver after this many seconds
+    pub approval_timeout_secs: u64,
+    pub poll_secs: u64,
+}
+
+impl Default for ProtectionRegistry {
+    fn default() -> Self {
+        Self {
+            hosts: BTreeMap::new(),
+            environments: BTreeMap::new(),
+            approval_url: None,
+            approval_timeout_secs: 900,
+            poll_secs: 10,
+        }
+    }
+}
+
+/// Why a machine is protected
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Protection {
+    /// Registry entry that matched: the host's name, or the name the command used for it
+    pub target: String,
+    pub environment: Option<String>,
+    pub reason: Option<String>,
+}
+
+impl Protection {
+    /// Whether `token` unlocks this protection
+    pub fn accepts(&self, token: &str) -> bool {
+        token == self.target || self.environment.as_deref() == Some(token)
+    }
+
+    pub fn describe(&self) -> String {
+        let mut text = match &self.environment {
+            Some(environment) => format!("{} (environment {})", self.target, environment),
+            None => self.target.clone(),
+        };
+        if let Some(reason) = &self.reason {
+            text.push_str(&format!(": {}", reason));
+        }
+        text
+    }
+}
+
+impl ProtectionRegistry {
+    /// Read `protected.yaml` under `base_dir`; no file means nothing is protected
+    pub fn load(base_dir: &Path) -> Result<Self> {
+        let path = base_dir.join(PROTECTION_FILE);
+        if !path.exists() {
+            return Ok(Self::default());
+        }
+        let registry: Self = serde_yaml::from_str(&std::fs::read_to_string(&path)?)?;
+        registry.validate()?;
+        Ok(registry)
+    }
+
+    pub fn validate(&self) -> Result<()> {
+        if let Some(url) = &self.approval_url {
+            if !url.starts_with("http://") && !url.starts_with("https://") {
+                return Err(AutoInstallError::ValidationError(format!(
+                    "protection approval_url must start with http:// or https://: {}",
+                    url
+                )));
+            }
+        }
+        if self.poll_secs == 0 {
+            return Err(AutoInstallError::ValidationError(
+                "protection poll_secs must be at least 1".to_string(),
+            ));
+        }
+        Ok(())
+    }
+
+    /// Protection of the machine known by `names` (hostname, address), if any
+    pub fn protection(&self, names: &[&str]) -> Option<Protection> {
+        for name in names {
+            if let Some(host) = self.hosts.get(*name).filter(|h| h.protected) {
+                return Some(Protection {
+                    target: name.to_string(),
+                    environment: self.environment_of(name),
+                    reason: host.reason.clone(),
+                });
+            }
+        }
+        for (environment, entry) in &self.environments {
+            if !entry.protected {
+                continue;
+            }
+            if let Some(name) = names
+                .iter()
+                .find(|name| entry.hosts.iter().any(|p| glob_match(p, name)))
+            {
+                return Some(Protection {
+                    target: name.to_string(),
+                    environment: Some(environment.clone()),
+                    reason: entry.reason.clone(),
+                });
+            }
+        }
+        None
+    }
+
+    fn environment_of(&self, name: &str) -> Option<String> {
+        self.environments
+            .iter()
+            .find(|(_, entry)| entry.hosts.iter().any(|p| glob_match(p, name)))
+            .map(|(environment, _)| environment.clone())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_hosts_and_environments_match() {
+        let registry: ProtectionRegistry = serde_yaml::from_str(
+            "hosts:\n  db-01:\n    protected: true\n    reason: primary PostgreSQL\n  web-09:\n    protected: false\nenvironments:\n  production:\n    protected: true\n    hosts: [\"prod-*\", \"10.0.3.*\"]\n",
+        )
+        .unwrap();
+        assert!(registry.validate().is_ok());
+
+        let db = registry.protection(&["10.0.1.5", "db-01"]).unwrap();
+        assert_eq!(db.target, "db-01");
+        assert!(db.accepts("db-01"));
+        assert!(!db.accepts("10.0.1.5"));
+
+        let prod = registry.protection(&["10.0.3.7"]).unwrap();
+        assert_eq!(prod.environment.as_deref(), Some("production"));
+        assert!(prod.accepts("production"));
+        assert!(registry.protection(&["web-09"]).is_none());
+        assert!(registry.protection(&["staging-01"]).is_none());
+    }
+}
@@ -0,0 +1,116 @@
+// file: src/config/reinstall.rs
+// version: 1.1.0
+// guid: 4f7a2c91-8d36-4e1b-b5f0-2a9c6e3d8b17
+
+//! User datasets kept by `ssh-install --reinstall` (`reinstall:` section of a target config)
+//!
+//! A reinstall reuses the partitions, LUKS volume and pools of an earlier install. Only the OS
+//! datasets under `<root pool>/ROOT` and `<boot pool>/BOOT` are destroyed and recreated; every
+//! other dataset stays. The datasets listed here must exist before anything is destroyed, and
+//! are linked into the new system afterwards: `canmount=on`, the mountpoint, and an fstab entry
+//! for `legacy` mounts.
+//!
+//! ```yaml
+//! reinstall:
+//!   preserve:
+//!     - dataset: rpool/home
+//!     - dataset: rpool/data
+//!       mountpoint: /srv/data
+//!       legacy: true
+//! ```
+
+use crate::error::AutoInstallError;
+use serde::{Deserialize, Serialize};
+
+/// A dataset that survives a reinstall
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct PreservedDataset {
+    /// Full dataset name, e.g. `rpool/home`
+    pub dataset: String,
+    /// Where the new system mounts it; the dataset's current mountpoint when absent
+    #[serde(default)]
+    pub mountpoint: Option<String>,
+    /// Mount from `/etc/fstab` (`mountpoint=legacy`) instead of by ZFS
+    #[serde(default)]
+    pub legacy: bool,
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct ReinstallConfig {
+    pub preserve: Vec<PreservedDataset>,
+}
+
+impl ReinstallConfig {
+    pub fn validate(&self) -> crate::Result<()> {
+        for preserved in &self.preserve {
+            let mut parts = preserved.dataset.split('/');
+            let (pool, child) = (parts.next().unwrap_or_default(), parts.next());
+            let valid_name = !pool.is_empty()
+                && child.is_some_and(|c| !c.is_empty())
+                && preserved
+                    .dataset
+                    .chars()
+                    .all(|c| c.is_ascii_alphanumeric() || "-_.:/".contains(c));
+            if !valid_name {
+                return Err(AutoInstallError::ValidationError(format!(
+                    "reinstall.preserve: '{}' is not a dataset below a pool, e.g. rpool/home",
+                    preserved.dataset
+                )));
+            }
+            if matches!(child, Some("ROOT" | "BOOT")) {
+                return Err(AutoInstallError::ValidationError(format!(
+                    "reinstall.preserve: {} holds the OS datasets, which a reinstall replaces",
+                    preserved.dataset
+                )));
+            }
+            match &preserved.mountpoint {
+                Some(mountpoint)
+                    if !mountpoint.starts_with('/') || mountpoint.contains(char::is_whitespace) =>
+                {
+                    return Err(AutoInstallError::ValidationError(format!(
+                        "reinstall.preserve: mountpoint '{}' of {} must be an absolute path",
+                        mountpoint, preserved.dataset
+                    )));
+                }
+                None if preserved.legacy => {
+                    return Err(AutoInstallError::ValidationError(format!(
+                        "reinstall.preserve: {} is mounted from fstab and needs a mountpoint",
+                        preserved.dataset
+                    )));
+                }
+                _ => {}
+            }
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::loader::parse_section;
+
+    fn parse(yaml: &str) -> ReinstallConfig {
+        parse_section::<ReinstallConfig>(yaml, "reinstall").unwrap()
+    }
+
+    #[test]
+    fn test_validate_preserved_datasets() {
+        assert!(ReinstallConfig::default().validate().is_ok());
+        let config = parse(
+            "reinstall:\n  preserve:\n    - dataset: rpool/home\n    - dataset: rpool/data\n      mountpoint: /srv/data\n      legacy: true\n",
+        );
+        assert_eq!(config.preserve.len(), 2);
+        assert!(config.validate().is_ok());
+
+        for yaml in [
+            "reinstall:\n  preserve:\n    - dataset: home\n",
+            "reinstall:\n  preserve:\n    - dataset: rpool/ROOT/ubuntu_abc123\n",
+            "reinstall:\n  preserve:\n    - dataset: rpool/data\n      mountpoint: srv/data\n",
+            "reinstall:\n  preserve:\n    - dataset: rpool/data\n      legacy: true\n",
+        ] {
+            assert!(parse(yaml).validate().is_err(), "{}", yaml);
+        }
+    }
+}
@@ -0,0 +1,103 @@
+// file: src/config/secrets.rs
+// version: 1.1.0
+// guid: 8b3e6f12-4a7c-4d95-a1e8-7c2d9f0b5e36
+
+//! References to secrets kept outside target configs
+//!
+//! A target config never holds a secret itself; it names where to fetch it on the operator's
+//! machine when the install starts: an environment variable, a file, or the output of a
+//! command such as `pass show` or `vault kv get -field=password`.
+
+use crate::error::AutoInstallError;
+use crate::Result;
+use serde::{Deserialize, Serialize};
+use std::path::PathBuf;
+
+/// Where to read a secret from, e.g. `{env: TARGET_SUDO_PASSWORD}`
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(untagged)]
+pub enum SecretRef {
+    Env {
+        env: String,
+    },
+    File {
+        file: PathBuf,
+    },
+    /// Run with `sh -c`; the secret is its standard output
+    Command {
+        command: String,
+    },
+}
+
+impl SecretRef {
+    /// Where the secret comes from, without the secret
+    pub fn describe(&self) -> String {
+        match self {
+            Self::Env { env } => format!("environment variable {}", env),
+            Self::File { file } => format!("file {}", file.display()),
+            Self::Command { command } => format!("command `{}`", command),
+        }
+    }
+
+    /// Fetch the secret; a trailing newline is dropped
+    pub fn resolve(&self) -> Result<String> {
+        let value = match self {
+            Self::Env { env } => std::env::var(env).map_err(|_| {
+                AutoInstallError::ConfigError(format!("Secret variable {} is not set", env))
+            })?,
+            Self::File { file } => std::fs::read_to_string(file).map_err(|e| {
+                AutoInstallError::ConfigError(format!(
+                    "Failed to read secret file {}: {}",
+                    file.display(),
+                    e
+                ))
+            })?,
+            Self::Command { command } => {
+                let output = std::process::Command::new("sh")
+                    .args(["-c", command])
+                    .output()?;
+                if !output.status.success() {
+                    return Err(AutoInstallError::ProcessError {
+                        command: command.clone(),
+                        exit_code: output.status.code(),
+                        stderr: String::from_utf8_lossy(&output.stderr).to_string(),
+                    });
+                }
+                String::from_utf8_lossy(&output.stdout).to_string()
+            }
+        };
+        let value = value.trim_end_matches(['\r', '\n']).to_string();
+        crate::logging::redact::register(&value);
+        if value.is_empty() {
+            return Err(AutoInstallError::ConfigError(format!(
+                "Secret from {} is empty",
+                self.describe()
+            )));
+        }
+        Ok(value)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_resolve() {
+        let secret: SecretRef = serde_yaml::from_str("command: printf 'hunter2\\n'").unwrap();
+        assert_eq!(secret.resolve().unwrap(), "hunter2");
+
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("password");
+        std::fs::write(&path, "from-file\n").unwrap();
+        assert_eq!(
+            SecretRef::File { file: path }.resolve().unwrap(),
+            "from-file"
+        );
+        assert!(SecretRef::Env {
+            env: "UAA_TEST_UNSET_SECRET".to_string()
+        }
+        .resolve()
+        .is_err());
+    }
+}
@@ -0,0 +1,63 @@
+// file: src/config/storage.rs
+// version: 1.1.0
+// guid: 2a7c4e91-6d3b-4f08-9e15-b8c0d4a6f273
+
+//! Storage layout templates (`storage:` section of a target config)
+//!
+//! A layout says how the install disks found on the target are used. When a target config
+//! does not name one, the installer picks the default for the hardware class it detected
+//! during investigation (see `ssh_installer::hardware_class`).
+
+use serde::{Deserialize, Serialize};
+
+/// How the target's disks are laid out
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum StorageLayout {
+    /// Everything on the install disk; other disks are left alone
+    Single,
+    /// Pools on the install disk, with a mirrored ESP on every other disk of the class so
+    /// the machine still boots when the first disk dies
+    MirroredEsp,
+}
+
+impl StorageLayout {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            StorageLayout::Single => "single",
+            StorageLayout::MirroredEsp => "mirrored-esp",
+        }
+    }
+}
+
+impl std::fmt::Display for StorageLayout {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(self.as_str())
+    }
+}
+
+/// Storage settings for one target
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct StorageConfig {
+    /// Layout template; inherited from the detected hardware class when unset
+    pub layout: Option<StorageLayout>,
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::loader::parse_section;
+
+    #[test]
+    fn test_storage_section_parsing() {
+        let section: StorageConfig =
+            parse_section("storage:\n  layout: mirrored-esp\n", "storage").unwrap();
+        assert_eq!(section.layout, Some(StorageLayout::MirroredEsp));
+        assert_eq!(StorageLayout::MirroredEsp.to_string(), "mirrored-esp");
+
+        let section: StorageConfig = parse_section("hostname: a\n", "storage").unwrap();
+        assert_eq!(section.layout, None);
+        assert!(parse_section::<StorageConfig>("storage:\n  layout: raid5\n", "storage").is_err());
+    }
+}
@@ -1,10 +1,19 @@
 // file: src/config/target.rs
-// version: 1.0.1
+// version: 1.38.0
 // guid: b2c3d4e5-f6g7-8901-2345-678901bcdefg
 
 //! Target machine configuration structures
 
-use super::Architecture;
+use super::{
+    AptLockConfig, AptMirrorsConfig, AptReposConfig, AptSnapshot, Architecture, BmcConfig,
+    BootloaderConfig, BudgetConfig, ConfirmationConfig, DiskHealthConfig, EntropyConfig,
+    FirewallConfig, HardeningConfig, HeadlessConfig, HealthGateConfig, HostVarsConfig, IssueConfig,
+    KernelConfig, LateCommandsConfig, LowMemoryConfig, MirrorSelectionConfig, NbdeConfig,
+    NetworkRecoveryConfig, PartitioningConfig, PerformanceConfig, PrivilegeConfig, ProgressConfig,
+    ReinstallConfig, SshCaConfig, StorageConfig, TelemetryConfig, ThrottleConfig, UbuntuProConfig,
+    UpdatesConfig, UserDataConfig, VerificationConfig, VsphereConfig, ZfsPoolsConfig,
+    ZfsTuningConfig,
+};
 use serde::{Deserialize, Serialize};
 
 /// Configuration for target machine deployment
@@ -26,6 +35,120 @@ pub struct TargetConfig {
     pub luks_config: LuksConfig,
     /// Additional packages to install
     pub packages: Vec<String>,
+    /// sysctl and kernel module settings applied to the installed system
+    #[serde(default)]
+    pub kernel: KernelConfig,
+    /// IO/CPU/bandwidth limits for bulk transfers to a host that is serving traffic
+    #[serde(default)]
+    pub throttle: ThrottleConfig,
+    /// snapshot.ubuntu.com timestamp the installed system's apt sources are pinned to
+    #[serde(default)]
+    pub apt_snapshot: Option<AptSnapshot>,
+    /// Hardening profile applied during system configuration
+    #[serde(default)]
+    pub hardening: HardeningConfig,
+    /// ZFS ARC sizing and module parameters; computed from RAM unless overridden
+    #[serde(default)]
+    pub zfs_tuning: ZfsTuningConfig,
+    /// Storage layout template; inherited from the detected hardware class when unset
+    #[serde(default)]
+    pub storage: StorageConfig,
+    /// BMC Redfish access used to enrich hardware inventory in reports
+    #[serde(default)]
+    pub bmc: Option<BmcConfig>,
+    /// vCenter placement, resources and networks for `deploy-vsphere`
+    #[serde(default)]
+    pub vsphere: Option<VsphereConfig>,
+    /// Mirrors measured from the target before debootstrap; the fastest one is used
+    #[serde(default)]
+    pub mirror_selection: Option<MirrorSelectionConfig>,
+    /// Inbound firewall installed into the target and enabled at boot
+    #[serde(default)]
+    pub firewall: FirewallConfig,
+    /// Serial console, hardware watchdog and RTC time base
+    #[serde(default)]
+    pub headless: HeadlessConfig,
+    /// Progress webhook and parsers for custom commands
+    #[serde(default)]
+    pub progress: ProgressConfig,
+    /// SSH CA that signs host and user certificates instead of trusting raw keys
+    #[serde(default)]
+    pub ssh_ca: SshCaConfig,
+    /// Tang servers the LUKS volume is bound to with Clevis for unattended unlock
+    #[serde(default)]
+    pub nbde: NbdeConfig,
+    /// Resolvers and alternate mirrors used when downloads fail during the install
+    #[serde(default)]
+    pub network_recovery: NetworkRecoveryConfig,
+    /// Scripts run in the target at the end of the install, with timeouts and limits
+    #[serde(default)]
+    pub late_commands: LateCommandsConfig,
+    /// unattended-upgrades, periodic apt tasks and pinning written into the target
+    #[serde(default)]
+    pub updates: UpdatesConfig,
+    /// Health limits for the install disk
+    #[serde(default)]
+    pub disk_health: DiskHealthConfig,
+    /// Waiting for apt/dpkg locks held in the live environment
+    #[serde(default)]
+    pub apt_lock: AptLockConfig,
+    /// Weighted go/no-go score checked before the install disk is wiped
+    #[serde(default)]
+    pub health_gate: HealthGateConfig,
+    /// Built-in partitioning, or the operator-provided layout the install disk must have
+    #[serde(default)]
+    pub partitioning: PartitioningConfig,
+    /// Phases the install stops before until someone approves them
+    #[serde(default)]
+    pub confirmation: ConfirmationConfig,
+    /// How the installer gets root on the target
+    #[serde(default)]
+    pub privilege: PrivilegeConfig,
+    /// Wall-clock limit for the installation
+    #[serde(default)]
+    pub budget: BudgetConfig,
+    /// Bootloader of the installed system: GRUB (default) or systemd-boot
+    #[serde(default)]
+    pub bootloader: BootloaderConfig,
+    /// Per-host variables store; whether a copy is written into the installed system
+    #[serde(default)]
+    pub host_vars: HostVarsConfig,
+    /// Swap, ARC cap and serialized package steps for small machines
+    #[serde(default)]
+    pub low_memory: LowMemoryConfig,
+    /// Hardware RNG daemon and first-boot entropy check
+    #[serde(default)]
+    pub entropy: EntropyConfig,
+    /// apt mirrors of the installed system, tried in priority order
+    #[serde(default)]
+    pub apt_mirrors: AptMirrorsConfig,
+    /// Ubuntu Pro attachment and services of the installed system
+    #[serde(default)]
+    pub ubuntu_pro: UbuntuProConfig,
+    /// Pool names and properties, and the node's storage role
+    #[serde(default)]
+    pub zfs_pools: ZfsPoolsConfig,
+    /// Endpoint for anonymized install metrics, used with `--telemetry`
+    #[serde(default)]
+    pub telemetry: TelemetryConfig,
+    /// Service probes checked once the installed host has booted (`verify`, `deploy-vsphere`)
+    #[serde(default)]
+    pub verification: VerificationConfig,
+    /// Kernel parameter, sysctl and zram/zswap preset for the installed system
+    #[serde(default)]
+    pub performance: PerformanceConfig,
+    /// Issue tracker that failed installs are reported to
+    #[serde(default)]
+    pub issues: IssueConfig,
+    /// Third-party apt repositories with verified keyrings
+    #[serde(default)]
+    pub apt_repos: AptReposConfig,
+    /// cloud-init style user-data run on first boot
+    #[serde(default)]
+    pub user_data: UserDataConfig,
+    /// User datasets kept by `ssh-install --reinstall`
+    #[serde(default)]
+    pub reinstall: ReinstallConfig,
 }
 
 /// Network interface configuration
@@ -115,6 +238,89 @@ impl TargetConfig {
         // Validate network configuration
         self.network.validate()?;
 
+        // Validate kernel tuning
+        self.kernel.validate()?;
+        self.zfs_tuning.validate()?;
+
+        // Validate transfer throttling
+        self.throttle.validate()?;
+
+        // Validate BMC access
+        if let Some(bmc) = &self.bmc {
+            bmc.validate()?;
+        }
+        if let Some(vsphere) = &self.vsphere {
+            vsphere.validate()?;
+        }
+
+        // Validate mirror candidates
+        if let Some(selection) = &self.mirror_selection {
+            selection.validate()?;
+        }
+
+        // Validate firewall rules, including that management SSH stays open
+        self.firewall.validate()?;
+
+        // Validate serial console and watchdog settings
+        self.headless.validate()?;
+
+        // Validate progress webhook and patterns
+        self.progress.validate()?;
+
+        // Validate SSH CA principals and user keys
+        self.ssh_ca.validate()?;
+
+        // Validate Tang servers and unlock threshold
+        self.nbde.validate()?;
+
+        self.network_recovery.validate()?;
+
+        self.late_commands.validate()?;
+
+        self.updates.validate()?;
+
+        self.disk_health.validate()?;
+
+        self.apt_lock.validate()?;
+
+        self.health_gate.validate()?;
+
+        self.partitioning.validate()?;
+
+        self.confirmation.validate()?;
+
+        self.privilege.validate()?;
+
+        self.budget.validate()?;
+
+        self.bootloader.validate()?;
+
+        self.host_vars.validate()?;
+
+        self.low_memory.validate()?;
+
+        self.entropy.validate()?;
+
+        self.apt_mirrors.validate()?;
+
+        self.ubuntu_pro.validate()?;
+
+        self.zfs_pools.validate()?;
+
+        self.telemetry.validate()?;
+
+        self.verification.validate()?;
+
+        self.performance.validate()?;
+
+        self.issues.validate()?;
+
+        self.apt_repos.validate()?;
+
+        self.user_data.validate()?;
+
+        self.reinstall.validate()?;
+
         Ok(())
     }
 }
@@ -171,6 +377,44 @@ mod tests {
             }],
             luks_config: LuksConfig::default(),
             packages: vec![],
+            kernel: KernelConfig::default(),
+            throttle: ThrottleConfig::default(),
+            apt_snapshot: None,
+            hardening: HardeningConfig::default(),
+            zfs_tuning: ZfsTuningConfig::default(),
+            storage: StorageConfig::default(),
+            bmc: None,
+            vsphere: None,
+            mirror_selection: None,
+            firewall: FirewallConfig::default(),
+            headless: HeadlessConfig::default(),
+            progress: ProgressConfig::default(),
+            ssh_ca: SshCaConfig::default(),
+            reinstall: ReinstallConfig::default(),
+            user_data: UserDataConfig::default(),
+            apt_repos: AptReposConfig::default(),
+            issues: IssueConfig::default(),
+            performance: PerformanceConfig::default(),
+            verification: VerificationConfig::default(),
+            telemetry: TelemetryConfig::default(),
+            zfs_pools: ZfsPoolsConfig::default(),
+            ubuntu_pro: UbuntuProConfig::default(),
+            apt_mirrors: AptMirrorsConfig::default(),
+            entropy: EntropyConfig::default(),
+            low_memory: LowMemoryConfig::default(),
+            host_vars: HostVarsConfig::default(),
+            bootloader: BootloaderConfig::default(),
+            budget: BudgetConfig::default(),
+            privilege: PrivilegeConfig::default(),
+            confirmation: ConfirmationConfig::default(),
+            partitioning: PartitioningConfig::default(),
+            health_gate: HealthGateConfig::default(),
+            apt_lock: AptLockConfig::default(),
+            disk_health: DiskHealthConfig::default(),
+            updates: UpdatesConfig::default(),
+            late_commands: LateCommandsConfig::default(),
+            network_recovery: NetworkRecoveryConfig::default(),
+            nbde: NbdeConfig::default(),
         }
     }
 
@@ -180,6 +424,17 @@ mod tests {
         assert!(t.validate().is_ok());
     }
 
+    #[test]
+    fn test_target_validate_firewall_keeps_ssh_open() {
+        let mut t = valid_target();
+        t.firewall = serde_yaml::from_str("backend: nftables\nrules:\n  - port: 443\n").unwrap();
+        assert!(t.validate().is_err());
+        t.firewall
+            .rules
+            .push(serde_yaml::from_str("port: 22").unwrap());
+        assert!(t.validate().is_ok());
+    }
+
     #[test]
     fn test_target_validate_empty_hostname() {
         let mut t = valid_target();
@@ -0,0 +1,52 @@
+// file: src/config/telemetry.rs
+// version: 1.1.0
+// guid: 7a3e9c15-2d84-4b6f-a0c7-5e1b8d4f2c93
+
+//! Telemetry endpoint (`telemetry:` section of a target config)
+//!
+//! The section only says where anonymized install metrics would go. Nothing is sent unless
+//! `ssh-install` is also run with `--telemetry`; see `network::telemetry` for the payload.
+
+use crate::error::AutoInstallError;
+use crate::Result;
+use serde::{Deserialize, Serialize};
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct TelemetryConfig {
+    /// URL the metrics are POSTed to as JSON
+    pub endpoint: Option<String>,
+    /// Label grouping installs for aggregation, e.g. a datacenter name; sent as given
+    pub site: Option<String>,
+    /// Upper bound for the upload
+    pub timeout_secs: u64,
+}
+
+impl Default for TelemetryConfig {
+    fn default() -> Self {
+        Self {
+            endpoint: None,
+            site: None,
+            timeout_secs: 10,
+        }
+    }
+}
+
+impl TelemetryConfig {
+    pub fn validate(&self) -> Result<()> {
+        if let Some(endpoint) = &self.endpoint {
+            if !endpoint.starts_with("https://") && !endpoint.starts_with("http://") {
+                return Err(AutoInstallError::ValidationError(format!(
+                    "telemetry endpoint '{}' must be an http(s) URL",
+                    endpoint
+                )));
+            }
+        }
+        if self.timeout_secs == 0 {
+            return Err(AutoInstallError::ValidationError(
+                "telemetry timeout_secs must be at least 1".to_string(),
+            ));
+        }
+        Ok(())
+    }
+}
@@ -0,0 +1,403 @@
+// file: src/config/tenants.rs
+// version: 1.2.0
+// guid: 4d7b1e93-0a62-4c85-b9f4-2e8c6a3d5f17
+
+//! Tenant namespaces for an agent shared by several teams
+//!
+//! `tenants.yaml` in the working directory lists each tenant with its own config root
+//! (inventories, target configs, presets), webhook endpoint, API token and the hostnames it may
+//! deploy:
+//!
+//! ```yaml
+//! tenants:
+//!   - name: web
+//!     config_root: tenants/web
+//!     webhook: https://hooks.example/web
+//!     token_sha256: 9f86d081884c7d659a2feaa0c55ad015a3bf4f1b2b0b822cd15d6c15b0f00a08
+//!     hosts: ["web-*"]
+//! ```
+//!
+//! While the file exists, every fleet command needs a tenant token. Relative paths are
+//! resolved against the working directory. Only the SHA-256 of a token is stored. A caller
+//! authenticated as one tenant can only use files under that tenant's root and only act on
+//! hosts matching its patterns, and no two tenants may claim the same root or overlapping host
+//! patterns.
+
+use crate::error::AutoInstallError;
+use crate::Result;
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use std::path::{Component, Path, PathBuf};
+
+/// Environment variable carrying the caller's tenant token
+pub const TENANT_TOKEN_ENV: &str = "UAA_TENANT_TOKEN";
+/// Registry file name under the working directory
+pub const TENANTS_FILE: &str = "tenants.yaml";
+
+/// One tenant's namespace
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Tenant {
+    pub name: String,
+    /// Directory holding the tenant's inventories, target configs and presets
+    pub config_root: PathBuf,
+    /// Endpoint the tenant's fleet lifecycle events go to
+    #[serde(default)]
+    pub webhook: Option<String>,
+    /// Hex SHA-256 of the tenant's API token
+    pub token_sha256: String,
+    /// Hostnames the tenant may act on; `*` matches any run of characters
+    pub hosts: Vec<String>,
+}
+
+impl Tenant {
+    /// Whether `hostname` belongs to this tenant
+    pub fn owns_host(&self, hostname: &str) -> bool {
+        self.hosts
+            .iter()
+            .any(|pattern| glob_match(pattern, hostname))
+    }
+
+    /// Whether `path` lies under the tenant's config root
+    pub fn owns_path(&self, path: &Path) -> bool {
+        normalize(path).starts_with(normalize(&self.config_root))
+    }
+
+    /// Refuse `path` unless it belongs to this tenant
+    pub fn check_path(&self, path: &Path) -> Result<()> {
+        if self.owns_path(path) {
+            Ok(())
+        } else {
+            Err(AutoInstallError::ValidationError(format!(
+                "{} is outside tenant {}'s namespace",
+                path.display(),
+                self.name
+            )))
+        }
+    }
+
+    /// Refuse `hostname` unless it belongs to this tenant
+    pub fn check_host(&self, hostname: &str) -> Result<()> {
+        if self.owns_host(hostname) {
+            Ok(())
+        } else {
+            Err(AutoInstallError::ValidationError(format!(
+                "Host {} does not belong to tenant {}",
+                hostname, self.name
+            )))
+        }
+    }
+
+    /// Refuse an inventory run unless this tenant owns the inventory file and every host with
+    /// its target config, given as `(hostname, target_config)`
+    pub fn check_inventory<'a>(
+        &self,
+        inventory: &Path,
+        hosts: impl IntoIterator<Item = (&'a str, Option<&'a str>)>,
+    ) -> Result<()> {
+        self.check_path(inventory)?;
+        for (hostname, target_config) in hosts {
+            self.check_host(hostname)?;
+            if let Some(target_config) = target_config {
+                self.check_path(Path::new(target_config))?;
+            }
+        }
+        Ok(())
+    }
+
+    /// `items` restricted to the hosts this tenant may see
+    pub fn visible_hosts<'a, T>(
+        &self,
+        items: &'a [T],
+        hostname: impl Fn(&T) -> &str,
+    ) -> Vec<&'a T> {
+        items
+            .iter()
+            .filter(|item| self.owns_host(hostname(item)))
+            .collect()
+    }
+}
+
+/// Every tenant the agent serves
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct TenantRegistry {
+    pub tenants: Vec<Tenant>,
+}
+
+impl TenantRegistry {
+    /// The registry in `base_dir`, or `None` when the agent is not shared between tenants
+    pub fn load_in(base_dir: &Path) -> Result<Option<Self>> {
+        let path = base_dir.join(TENANTS_FILE);
+        if !path.exists() {
+            return Ok(None);
+        }
+        Self::load(&path).map(Some)
+    }
+
+    /// Read and validate a registry file, resolving relative paths against its directory
+    pub fn load(path: &Path) -> Result<Self> {
+        let content = std::fs::read_to_string(path).map_err(|e| {
+            AutoInstallError::ConfigError(format!(
+                "Failed to read tenant registry {}: {}",
+                path.display(),
+                e
+            ))
+        })?;
+        let mut registry: TenantRegistry = serde_yaml::from_str(&content)?;
+        let base = path.parent().unwrap_or(Path::new("."));
+        for tenant in &mut registry.tenants {
+            tenant.config_root = base.join(&tenant.config_root);
+        }
+        registry.validate()?;
+        Ok(registry)
+    }
+
+    /// Check names, tokens and that no two tenants share a root, a token or a host pattern
+    pub fn validate(&self) -> Result<()> {
+        for (index, tenant) in self.tenants.iter().enumerate() {
+            if tenant.name.is_empty()
+                || !tenant
+                    .name
+                    .chars()
+                    .all(|c| c.is_ascii_alphanumeric() || matches!(c, '-' | '_'))
+            {
+                return Err(AutoInstallError::ValidationError(format!(
+                    "Tenant name '{}' may only contain letters, digits, '-' and '_'",
+                    tenant.name
+                )));
+            }
+            if tenant.token_sha256.len() != 64
+                || !tenant.token_sha256.chars().all(|c| c.is_ascii_hexdigit())
+            {
+                return Err(AutoInstallError::ValidationError(format!(
+                    "Tenant {} token_sha256 must be 64 hex characters",
+                    tenant.name
+                )));
+            }
+            if let Some(url) = &tenant.webhook {
+                if !url.starts_with("http://") && !url.starts_with("https://") {
+                    return Err(AutoInstallError::ValidationError(format!(
+                        "Tenant {} webhook must start with http:// or https://: {}",
+                        tenant.name, url
+                    )));
+                }
+            }
+            if tenant.hosts.is_empty() {
+                return Err(AutoInstallError::ValidationError(format!(
+                    "Tenant {} lists no host patterns",
+                    tenant.name
+                )));
+            }
+            for other in &self.tenants[index + 1..] {
+                if other.name == tenant.name {
+                    return Err(AutoInstallError::ValidationError(format!(
+                        "Tenant {} is listed more than once",
+                        tenant.name
+                    )));
+                }
+                if other
+                    .token_sha256
+                    .eq_ignore_ascii_case(&tenant.token_sha256)
+                {
+                    return Err(AutoInstallError::ValidationError(format!(
+                        "Tenants {} and {} share an API token",
+                        tenant.name, other.name
+                    )));
+                }
+                let (a, b) = (
+                    normalize(&tenant.config_root),
+                    normalize(&other.config_root),
+                );
+                if a.starts_with(&b) || b.starts_with(&a) {
+                    return Err(AutoInstallError::ValidationError(format!(
+                        "Tenants {} and {} have overlapping config roots",
+                        tenant.name, other.name
+                    )));
+                }
+                for pattern in &tenant.hosts {
+                    if let Some(clash) = other.hosts.iter().find(|p| patterns_overlap(pattern, p)) {
+                        return Err(AutoInstallError::ValidationError(format!(
+                            "Host pattern '{}' of tenant {} overlaps '{}' of tenant {}",
+                            pattern, tenant.name, clash, other.name
+                        )));
+                    }
+                }
+            }
+        }
+        Ok(())
+    }
+
+    /// Tenant whose token is `token`
+    pub fn authenticate(&self, token: &str) -> Result<&Tenant> {
+        let digest: String = Sha256::digest(token.trim().as_bytes())
+            .iter()
+            .map(|b| format!("{:02x}", b))
+            .collect();
+        self.tenants
+            .iter()
+            .find(|t| t.token_sha256.eq_ignore_ascii_case(&digest))
+            .ok_or_else(|| AutoInstallError::ValidationError("Unknown tenant token".to_string()))
+    }
+
+    /// Tenant for the token in `UAA_TENANT_TOKEN`
+    pub fn authenticate_from_env(&self) -> Result<&Tenant> {
+        let token = std::env::var(TENANT_TOKEN_ENV).map_err(|_| {
+            AutoInstallError::ValidationError(format!(
+                "{} must be set when a tenant registry is in use",
+                TENANT_TOKEN_ENV
+            ))
+        })?;
+        self.authenticate(&token)
+    }
+}
+
+/// Lexically absolute, `.`/`..`-free form of `path`; symlinks are resolved when it exists
+fn normalize(path: &Path) -> PathBuf {
+    if let Ok(canonical) = path.canonicalize() {
+        return canonical;
+    }
+    let absolute = if path.is_absolute() {
+        path.to_path_buf()
+    } else {
+        std::env::current_dir()
+            .unwrap_or_else(|_| PathBuf::from("/"))
+            .join(path)
+    };
+    let mut out = PathBuf::new();
+    for component in absolute.components() {
+        match component {
+            Component::CurDir => {}
+            Component::ParentDir => {
+                out.pop();
+            }
+            other => out.push(other),
+        }
+    }
+    out
+}
+
+/// `*`-only glob match
+pub(crate) fn glob_match(pattern: &str, text: &str) -> bool {
+    match pattern.split_once('*') {
+        None => pattern == text,
+        Some((prefix, rest)) => {
+            let Some(tail) = text.strip_prefix(prefix) else {
+                return false;
+            };
+            if rest.is_empty() {
+                return true;
+            }
+            (0..=tail.len())
+                .filter(|&i| tail.is_char_boundary(i))
+                .any(|i| glob_match(rest, &tail[i..]))
+        }
+    }
+}
+
+/// Conservative check whether two patterns could match the same hostname
+///
+/// Compares the literal text before the first `*` and after the last one; patterns that agree
+/// on both are treated as overlapping.
+fn patterns_overlap(a: &str, b: &str) -> bool {
+    let prefix = |p: &str| p.split('*').next().unwrap_or("").to_string();
+    let suffix = |p: &str| p.rsplit('*').next().unwrap_or("").to_string();
+    let (pa, pb) = (prefix(a), prefix(b));
+    let (sa, sb) = (suffix(a), suffix(b));
+    let prefixes_agree = pa.starts_with(&pb) || pb.starts_with(&pa);
+    let suffixes_agree = sa.ends_with(&sb) || sb.ends_with(&sa);
+    match (a.contains('*'), b.contains('*')) {
+        (false, false) => a == b,
+        (false, true) => glob_match(b, a),
+        (true, false) => glob_match(a, b),
+        (true, true) => prefixes_agree && suffixes_agree,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::TempDir;
+
+    const TOKEN_A: &str = "token-a";
+    const TOKEN_B: &str = "token-b";
+
+    fn hash(token: &str) -> String {
+        Sha256::digest(token.as_bytes())
+            .iter()
+            .map(|b| format!("{:02x}", b))
+            .collect()
+    }
+
+    fn write_registry(dir: &Path, b_hosts: &str) -> PathBuf {
+        let path = dir.join(TENANTS_FILE);
+        std::fs::write(
+            &path,
+            format!(
+                "tenants:\n  - name: web\n    config_root: web\n    webhook: https://hooks.example/web\n    token_sha256: {}\n    hosts: [\"web-*\"]\n  - name: db\n    config_root: db\n    token_sha256: {}\n    hosts: {}\n",
+                hash(TOKEN_A),
+                hash(TOKEN_B),
+                b_hosts
+            ),
+        )
+        .unwrap();
+        path
+    }
+
+    #[test]
+    fn test_authenticate_and_isolation() {
+        let dir = TempDir::new().unwrap();
+        assert!(TenantRegistry::load_in(dir.path()).unwrap().is_none());
+        write_registry(dir.path(), "[\"db-*\"]");
+        let registry = TenantRegistry::load_in(dir.path()).unwrap().unwrap();
+
+        let web = registry.authenticate(TOKEN_A).unwrap();
+        assert_eq!(web.name, "web");
+        assert!(registry.authenticate("nope").is_err());
+
+        assert!(web.check_host("web-01").is_ok());
+        assert!(web.check_host("db-01").is_err());
+        assert!(web
+            .check_path(&dir.path().join("web/inventory.yaml"))
+            .is_ok());
+        assert!(web
+            .check_path(&dir.path().join("db/inventory.yaml"))
+            .is_err());
+        assert!(web
+            .check_path(&dir.path().join("web/../db/inventory.yaml"))
+            .is_err());
+
+        let inventory = dir.path().join("web/inventory.yaml");
+        let target_config = dir.path().join("web/web-01.yaml");
+        let target_config = target_config.to_str().unwrap();
+        assert!(web
+            .check_inventory(&inventory, [("web-01", Some(target_config))])
+            .is_ok());
+        assert!(web
+            .check_inventory(&inventory, [("web-01", None), ("db-01", None)])
+            .is_err());
+        assert!(web
+            .check_inventory(&dir.path().join("db/inventory.yaml"), [("web-01", None)])
+            .is_err());
+
+        let hosts = ["web-01", "db-01", "web-02"];
+        let visible = web.visible_hosts(&hosts, |h| h);
+        assert_eq!(visible, vec![&"web-01", &"web-02"]);
+    }
+
+    #[test]
+    fn test_validate_rejects_overlaps() {
+        let dir = TempDir::new().unwrap();
+        assert!(TenantRegistry::load(&write_registry(dir.path(), "[\"web-db-*\"]")).is_err());
+        assert!(TenantRegistry::load(&write_registry(dir.path(), "[web-07]")).is_err());
+        assert!(TenantRegistry::load(&write_registry(dir.path(), "[\"*\"]")).is_err());
+        assert!(TenantRegistry::load(&write_registry(dir.path(), "[db-01, cache-*]")).is_ok());
+    }
+
+    #[test]
+    fn test_glob_match() {
+        assert!(glob_match("web-*", "web-01"));
+        assert!(glob_match("*-01", "web-01"));
+        assert!(glob_match("w*-*1", "web-01"));
+        assert!(!glob_match("web-*", "db-01"));
+        assert!(glob_match("db-01", "db-01"));
+    }
+}
@@ -0,0 +1,264 @@
+// file: src/config/throttle.rs
+// version: 1.1.0
+// guid: 5a9c3e17-6b2d-4f80-9d41-e8b7c2a6f053
+
+//! IO, CPU and bandwidth throttling for bulk transfers on live hosts
+//!
+//! Heavy operations (image copies, `zfs send`/`recv` streams) are wrapped in a transient
+//! systemd scope with cgroup limits, `ionice` and `nice`, and rate-limited with `pv` or
+//! `rsync --bwlimit` so production workloads on the same host keep their share.
+
+use crate::error::AutoInstallError;
+use crate::utils::shell_quote;
+use crate::Result;
+use serde::{Deserialize, Serialize};
+
+/// `ionice` scheduling class
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum IoClass {
+    /// Only gets disk time when nothing else wants it
+    Idle,
+    /// Normal class; combine with `io_level` 0 (highest) to 7 (lowest)
+    BestEffort,
+}
+
+/// Throttling knobs; everything is off by default
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct ThrottleConfig {
+    /// `ionice` class
+    pub io_class: Option<IoClass>,
+    /// `ionice` level for the best-effort class (0-7)
+    pub io_level: Option<u8>,
+    /// `nice` adjustment (0-19)
+    pub nice: Option<i32>,
+    /// Transfer rate cap in KiB/s
+    pub rate_limit_kbps: Option<u64>,
+    /// cgroup v2 IO weight (1-10000, default 100)
+    pub io_weight: Option<u32>,
+    /// CPU quota in percent of one CPU
+    pub cpu_quota_percent: Option<u32>,
+}
+
+impl ThrottleConfig {
+    /// Whether no throttling is configured
+    pub fn is_empty(&self) -> bool {
+        *self == Self::default()
+    }
+
+    /// Reject values the underlying tools would refuse
+    pub fn validate(&self) -> Result<()> {
+        if let Some(level) = self.io_level {
+            if level > 7 {
+                return Err(AutoInstallError::ValidationError(format!(
+                    "io_level must be 0-7, got {}",
+                    level
+                )));
+            }
+            if self.io_class != Some(IoClass::BestEffort) {
+                return Err(AutoInstallError::ValidationError(
+                    "io_level requires io_class: best-effort".to_string(),
+                ));
+            }
+        }
+        if let Some(nice) = self.nice {
+            if !(0..=19).contains(&nice) {
+                return Err(AutoInstallError::ValidationError(format!(
+                    "nice must be 0-19, got {}",
+                    nice
+                )));
+            }
+        }
+        if self.rate_limit_kbps == Some(0) {
+            return Err(AutoInstallError::ValidationError(
+                "rate_limit_kbps must be greater than 0".to_string(),
+            ));
+        }
+        if let Some(weight) = self.io_weight {
+            if !(1..=10000).contains(&weight) {
+                return Err(AutoInstallError::ValidationError(format!(
+                    "io_weight must be 1-10000, got {}",
+                    weight
+                )));
+            }
+        }
+        if self.cpu_quota_percent == Some(0) {
+            return Err(AutoInstallError::ValidationError(
+                "cpu_quota_percent must be greater than 0".to_string(),
+            ));
+        }
+        Ok(())
+    }
+
+    /// Program and arguments that run a command under the configured scope, ionice and nice
+    pub fn prefix_args(&self) -> Vec<String> {
+        let mut args = Vec::new();
+        if self.io_weight.is_some() || self.cpu_quota_percent.is_some() {
+            args.extend(["systemd-run", "--scope", "--quiet"].map(String::from));
+            if let Some(weight) = self.io_weight {
+                args.push("-p".to_string());
+                args.push(format!("IOWeight={}", weight));
+            }
+            if let Some(quota) = self.cpu_quota_percent {
+                args.push("-p".to_string());
+                args.push(format!("CPUQuota={}%", quota));
+            }
+            args.push("--".to_string());
+        }
+        match self.io_class {
+            Some(IoClass::Idle) => args.extend(["ionice", "-c3"].map(String::from)),
+            Some(IoClass::BestEffort) => {
+                args.extend(["ionice", "-c2"].map(String::from));
+                args.push(format!("-n{}", self.io_level.unwrap_or(7)));
+            }
+            None => {}
+        }
+        if let Some(nice) = self.nice {
+            args.push("nice".to_string());
+            args.push(format!("-n{}", nice));
+        }
+        args
+    }
+
+    /// `program args...` with the throttling prefix and, for rsync, the bandwidth cap
+    pub fn wrap_argv(&self, argv: &[&str]) -> Vec<String> {
+        let mut wrapped = self.prefix_args();
+        wrapped.extend(argv.iter().map(|a| a.to_string()));
+        if let (Some(rate), Some(&"rsync")) = (self.rate_limit_kbps, argv.first()) {
+            wrapped.insert(
+                wrapped.len() - argv.len() + 1,
+                format!("--bwlimit={}", rate),
+            );
+        }
+        wrapped
+    }
+
+    /// Run a shell command line (pipes allowed) under the throttling prefix
+    pub fn wrap_command(&self, command: &str) -> String {
+        let prefix = self.prefix_args();
+        if prefix.is_empty() {
+            return command.to_string();
+        }
+        let prefix: Vec<String> = prefix.iter().map(|a| shell_quote(a)).collect();
+        format!("{} bash -c {}", prefix.join(" "), shell_quote(command))
+    }
+
+    /// Join a producer and consumer with a rate-limiting `pv` stage when a cap is set
+    pub fn pipe(&self, producer: &str, consumer: &str) -> String {
+        match self.rate_limit_kbps {
+            Some(rate) => format!("{} | pv -q -L {}k | {}", producer, rate, consumer),
+            None => format!("{} | {}", producer, consumer),
+        }
+    }
+
+    /// Redirect a producer into `path`, rate-limited when a cap is set
+    pub fn redirect(&self, producer: &str, path: &str) -> String {
+        match self.rate_limit_kbps {
+            Some(rate) => format!("{} | pv -q -L {}k > {}", producer, rate, path),
+            None => format!("{} > {}", producer, path),
+        }
+    }
+
+    /// Feed `path` into a consumer, rate-limited when a cap is set
+    pub fn feed(&self, path: &str, consumer: &str) -> String {
+        match self.rate_limit_kbps {
+            Some(rate) => format!("pv -q -L {}k {} | {}", rate, path, consumer),
+            None => format!("{} < {}", consumer, path),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn throttled() -> ThrottleConfig {
+        ThrottleConfig {
+            io_class: Some(IoClass::Idle),
+            nice: Some(10),
+            rate_limit_kbps: Some(20480),
+            io_weight: Some(10),
+            cpu_quota_percent: Some(50),
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn test_default_is_passthrough() {
+        let none = ThrottleConfig::default();
+        assert!(none.is_empty());
+        assert_eq!(
+            none.wrap_command("zfs send -R rpool@s | zfs recv x"),
+            "zfs send -R rpool@s | zfs recv x"
+        );
+        assert_eq!(none.pipe("a", "b"), "a | b");
+        assert_eq!(none.feed("/f", "zfs recv x"), "zfs recv x < /f");
+        assert_eq!(
+            none.wrap_argv(&["rsync", "-a", "s/", "d"]),
+            vec!["rsync", "-a", "s/", "d"]
+        );
+    }
+
+    #[test]
+    fn test_wrap_command_and_pipes() {
+        let t = throttled();
+        assert_eq!(
+            t.wrap_command("set -o pipefail; a | b"),
+            "systemd-run --scope --quiet -p IOWeight=10 -p CPUQuota=50% -- ionice -c3 nice -n10 bash -c 'set -o pipefail; a | b'"
+        );
+        assert_eq!(t.pipe("a", "b"), "a | pv -q -L 20480k | b");
+        assert_eq!(t.redirect("a", "/f"), "a | pv -q -L 20480k > /f");
+        assert_eq!(t.feed("/f", "b"), "pv -q -L 20480k /f | b");
+    }
+
+    #[test]
+    fn test_wrap_argv_adds_rsync_bwlimit() {
+        let t = ThrottleConfig {
+            io_class: Some(IoClass::BestEffort),
+            io_level: Some(6),
+            rate_limit_kbps: Some(1000),
+            ..Default::default()
+        };
+        assert_eq!(
+            t.wrap_argv(&["rsync", "-a", "s/", "d"]),
+            vec![
+                "ionice",
+                "-c2",
+                "-n6",
+                "rsync",
+                "--bwlimit=1000",
+                "-a",
+                "s/",
+                "d"
+            ]
+        );
+    }
+
+    #[test]
+    fn test_validate() {
+        assert!(throttled().validate().is_ok());
+        let bad = |t: ThrottleConfig| assert!(t.validate().is_err());
+        bad(ThrottleConfig {
+            nice: Some(-5),
+            ..Default::default()
+        });
+        bad(ThrottleConfig {
+            io_level: Some(3),
+            ..Default::default()
+        });
+        bad(ThrottleConfig {
+            io_class: Some(IoClass::BestEffort),
+            io_level: Some(8),
+            ..Default::default()
+        });
+        bad(ThrottleConfig {
+            rate_limit_kbps: Some(0),
+            ..Default::default()
+        });
+        bad(ThrottleConfig {
+            io_weight: Some(0),
+            ..Default::default()
+        });
+    }
+}
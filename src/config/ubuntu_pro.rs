@@ -0,0 +1,237 @@
+// file: src/config/ubuntu_pro.rs
+// version: 1.1.0
+// guid: 4a9c2e67-1d83-4b5f-a0e6-8f7b3d1c5e92
+
+//! Ubuntu Pro attachment of the installed system (`ubuntu_pro:` section of a target config)
+//!
+//! The contract token is a [`SecretRef`] resolved on the operator's machine. It reaches the
+//! target only inside a `pro attach --attach-config` file, which is removed as soon as the
+//! client has read it. With `attach: install` the system is attached from the chroot and the
+//! post-install verification checks `pro status`; services that need a running system, such as
+//! livepatch, require `attach: first-boot`, where a oneshot unit attaches once the network is up.
+
+use super::secrets::SecretRef;
+use crate::error::AutoInstallError;
+use crate::Result;
+use serde::{Deserialize, Serialize};
+
+/// Attach config read by `pro attach --attach-config`, path inside the target
+pub const ATTACH_CONFIG: &str = "/var/lib/ubuntu-autoinstall-agent/pro-attach.yaml";
+const ATTACH_UNIT: &str = "etc/systemd/system/uaa-pro-attach.service";
+/// Services that need snapd or a booted kernel and cannot be enabled from a chroot
+const RUNTIME_SERVICES: &[&str] = &["livepatch", "landscape", "anbox-cloud"];
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum ProAttachTiming {
+    /// Attach from the chroot and verify before the install finishes
+    #[default]
+    Install,
+    /// Attach from a oneshot unit on first boot
+    FirstBoot,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct UbuntuProConfig {
+    /// Contract token; the section does nothing without it
+    pub token: Option<SecretRef>,
+    pub attach: ProAttachTiming,
+    /// Services to enable, e.g. `esm-infra`, `esm-apps`, `livepatch`
+    pub services: Vec<String>,
+    /// Fail the install when verification finds the system unattached; warn only when false
+    pub required: bool,
+}
+
+impl Default for UbuntuProConfig {
+    fn default() -> Self {
+        Self {
+            token: None,
+            attach: ProAttachTiming::Install,
+            services: vec!["esm-infra".to_string()],
+            required: true,
+        }
+    }
+}
+
+impl UbuntuProConfig {
+    pub fn is_enabled(&self) -> bool {
+        self.token.is_some()
+    }
+
+    pub fn validate(&self) -> Result<()> {
+        for service in &self.services {
+            if service.is_empty()
+                || !service
+                    .chars()
+                    .all(|c| c.is_ascii_lowercase() || c.is_ascii_digit() || c == '-')
+            {
+                return Err(AutoInstallError::ValidationError(format!(
+                    "ubuntu_pro service '{}' is not a Pro service name",
+                    service
+                )));
+            }
+        }
+        if self.has("livepatch") && (self.has("fips") || self.has("fips-updates")) {
+            return Err(AutoInstallError::ValidationError(
+                "ubuntu_pro livepatch cannot be combined with fips".to_string(),
+            ));
+        }
+        if self.attach == ProAttachTiming::Install {
+            if let Some(service) = self
+                .services
+                .iter()
+                .find(|s| RUNTIME_SERVICES.contains(&s.as_str()))
+            {
+                return Err(AutoInstallError::ValidationError(format!(
+                    "ubuntu_pro service {} needs a running system; set attach: first-boot",
+                    service
+                )));
+            }
+        }
+        Ok(())
+    }
+
+    fn has(&self, service: &str) -> bool {
+        self.services.iter().any(|s| s == service)
+    }
+
+    /// Contents of the attach config for `token`
+    pub fn attach_config(&self, token: &str) -> String {
+        if self.services.is_empty() {
+            return format!("token: \"{}\"\nenable_services: []\n", token);
+        }
+        let mut config = format!("token: \"{}\"\nenable_services:\n", token);
+        for service in &self.services {
+            config.push_str(&format!("  - {}\n", service));
+        }
+        config
+    }
+
+    /// Commands attaching the system at `root`; they carry the token and must not be logged
+    pub fn build_attach_commands(&self, root: &str, token: &str) -> Vec<String> {
+        let root = root.trim_end_matches('/');
+        let mut cmds = vec![
+            format!(
+                "chroot {} bash -lc 'DEBIAN_FRONTEND=noninteractive apt install -y ubuntu-advantage-tools'",
+                root
+            ),
+            format!(
+                "install -d -m 0700 {r}/var/lib/ubuntu-autoinstall-agent && (umask 077; cat > {r}{f} << 'EOF'\n{c}EOF\n)",
+                r = root,
+                f = ATTACH_CONFIG,
+                c = self.attach_config(token)
+            ),
+        ];
+        match self.attach {
+            ProAttachTiming::Install => cmds.push(format!(
+                "chroot {r} pro attach --attach-config {f}; status=$?; rm -f {r}{f}; exit $status",
+                r = root,
+                f = ATTACH_CONFIG
+            )),
+            ProAttachTiming::FirstBoot => {
+                let unit = format!(
+                    "[Unit]\n\
+                     Description=Attach to Ubuntu Pro\n\
+                     Wants=network-online.target\n\
+                     After=network-online.target\n\
+                     ConditionPathExists={f}\n\n\
+                     [Service]\n\
+                     Type=oneshot\n\
+                     ExecStart=/usr/bin/pro attach --attach-config {f}\n\
+                     ExecStartPost=/bin/rm -f {f}\n\n\
+                     [Install]\n\
+                     WantedBy=multi-user.target\n",
+                    f = ATTACH_CONFIG
+                );
+                cmds.push(format!(
+                    "cat > {}/{} << 'EOF'\n{}EOF",
+                    root, ATTACH_UNIT, unit
+                ));
+                cmds.push(format!(
+                    "chroot {} systemctl enable uaa-pro-attach.service",
+                    root
+                ));
+            }
+        }
+        cmds
+    }
+
+    /// Status command run for the post-install verification
+    pub fn status_command(&self, root: &str) -> String {
+        format!(
+            "chroot {} pro status --format json",
+            root.trim_end_matches('/')
+        )
+    }
+
+    /// Problems in `pro status --format json` output: not attached or a service not enabled
+    pub fn check_status(&self, status_json: &str) -> Vec<String> {
+        let status: serde_json::Value = match serde_json::from_str(status_json) {
+            Ok(status) => status,
+            Err(e) => return vec![format!("pro status output is not JSON: {}", e)],
+        };
+        if status["attached"].as_bool() != Some(true) {
+            return vec!["system is not attached to Ubuntu Pro".to_string()];
+        }
+        let services = status["services"].as_array().cloned().unwrap_or_default();
+        self.services
+            .iter()
+            .filter_map(|wanted| {
+                let state = services
+                    .iter()
+                    .find(|s| s["name"].as_str() == Some(wanted.as_str()))
+                    .and_then(|s| s["status"].as_str())
+                    .unwrap_or("missing");
+                (state != "enabled").then(|| format!("Ubuntu Pro service {} is {}", wanted, state))
+            })
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_attach_commands_and_status_check() {
+        let config: UbuntuProConfig = serde_yaml::from_str(
+            "token: {env: UBUNTU_PRO_TOKEN}\nservices: [esm-infra, esm-apps]\n",
+        )
+        .unwrap();
+        config.validate().unwrap();
+        assert!(config.is_enabled());
+
+        let cmds = config.build_attach_commands("/mnt/targetos/", "C1234");
+        assert!(cmds[1]
+            .contains("token: \"C1234\"\nenable_services:\n  - esm-infra\n  - esm-apps\nEOF"));
+        assert!(cmds[2].starts_with(
+            "chroot /mnt/targetos pro attach --attach-config /var/lib/ubuntu-autoinstall-agent/pro-attach.yaml;"
+        ));
+
+        let status = r#"{"attached": true, "services": [
+            {"name": "esm-infra", "status": "enabled"},
+            {"name": "esm-apps", "status": "disabled"}]}"#;
+        assert_eq!(
+            config.check_status(status),
+            vec!["Ubuntu Pro service esm-apps is disabled"]
+        );
+        assert_eq!(config.check_status(r#"{"attached": false}"#).len(), 1);
+
+        let livepatch = UbuntuProConfig {
+            services: vec!["livepatch".to_string()],
+            ..config
+        };
+        assert!(livepatch.validate().is_err());
+        let first_boot = UbuntuProConfig {
+            attach: ProAttachTiming::FirstBoot,
+            ..livepatch
+        };
+        first_boot.validate().unwrap();
+        assert!(
+            first_boot.build_attach_commands("/mnt/targetos", "C1234")[2].contains(
+                "ExecStartPost=/bin/rm -f /var/lib/ubuntu-autoinstall-agent/pro-attach.yaml"
+            )
+        );
+    }
+}
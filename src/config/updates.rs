@@ -0,0 +1,299 @@
+// file: src/config/updates.rs
+// version: 1.2.0
+// guid: 4b8e2f61-d7a3-4c95-8e0b-1f6c9a3d5e27
+
+//! Patching posture of the installed system (`updates:` section of a target config)
+//!
+//! unattended-upgrades, the `APT::Periodic` timers and apt pinning are written into the target
+//! while it is still mounted, so a machine is patched the intended way from its first boot.
+//! Settings go into files of their own that sort after the package defaults; allowed origins
+//! replace the distribution's list instead of adding to it.
+
+use super::write_file;
+use crate::error::AutoInstallError;
+use crate::Result;
+use serde::{Deserialize, Serialize};
+
+/// `APT::Periodic` overrides; sorts after the distribution's `20auto-upgrades`
+pub const PERIODIC_FILE: &str = "etc/apt/apt.conf.d/21autoinstall-periodic";
+/// unattended-upgrades overrides; sorts after the package's `50unattended-upgrades`
+pub const UNATTENDED_FILE: &str = "etc/apt/apt.conf.d/52autoinstall-unattended-upgrades";
+/// Pinning preferences
+pub const PREFERENCES_FILE: &str = "etc/apt/preferences.d/autoinstall-pins";
+
+/// First line of the apt.conf files; preference stanzas carry an `Explanation:` instead
+const MANAGED_HEADER: &str = "// Managed by ubuntu-autoinstall-agent\n";
+
+/// How often apt's daily job does each task, in days; 0 turns the task off
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct PeriodicConfig {
+    pub update_package_lists: u32,
+    pub download_upgradeable_packages: u32,
+    pub autoclean_interval: u32,
+}
+
+impl Default for PeriodicConfig {
+    fn default() -> Self {
+        Self {
+            update_package_lists: 1,
+            download_upgradeable_packages: 0,
+            autoclean_interval: 0,
+        }
+    }
+}
+
+/// An apt preference stanza
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct AptPin {
+    /// Package name or glob, e.g. `linux-*`
+    pub package: String,
+    /// Pin expression, e.g. `version 6.8.*` or `release a=noble-security`
+    pub pin: String,
+    /// 1000 and above allows downgrades, negative values block the package
+    pub priority: i32,
+}
+
+/// unattended-upgrades, periodic apt tasks and pins
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct UpdatesConfig {
+    /// Install and turn on unattended-upgrades, or turn it off; unset keeps the image's setup
+    pub unattended_upgrades: Option<bool>,
+    /// Origin patterns replacing the distribution's list, e.g. `{distro_id}:{distro_codename}-security`;
+    /// `${...}` would be taken for an environment variable by the config loader
+    pub allowed_origins: Vec<String>,
+    /// Reboot on its own when an upgrade needs it
+    pub automatic_reboot: bool,
+    /// Time of day (`HH:MM`) for automatic reboots; immediately when unset
+    pub reboot_time: Option<String>,
+    /// Also reboot while users are logged in
+    pub reboot_with_users: bool,
+    /// Remove dependencies upgrades no longer need
+    pub remove_unused_dependencies: bool,
+    /// apt's daily tasks; unset keeps the image's schedule
+    pub periodic: Option<PeriodicConfig>,
+    pub pins: Vec<AptPin>,
+}
+
+impl UpdatesConfig {
+    /// Whether anything is written to the target
+    pub fn is_enabled(&self) -> bool {
+        *self != Self::default()
+    }
+
+    /// Check origins, the reboot time and pins
+    pub fn validate(&self) -> Result<()> {
+        let invalid = |message: String| Err(AutoInstallError::ValidationError(message));
+        let plain = |s: &str| !s.is_empty() && !s.contains(['"', '\n', '\r']);
+        if self.unattended_upgrades == Some(false)
+            && (self.automatic_reboot || !self.allowed_origins.is_empty())
+        {
+            return invalid(
+                "updates: allowed_origins and automatic_reboot need unattended_upgrades"
+                    .to_string(),
+            );
+        }
+        for origin in &self.allowed_origins {
+            if !plain(origin) {
+                return invalid(format!("updates: invalid allowed origin '{}'", origin));
+            }
+        }
+        if let Some(time) = &self.reboot_time {
+            let valid = time
+                .split_once(':')
+                .and_then(|(h, m)| {
+                    (h.len() == 2 && m.len() == 2)
+                        .then_some((h.parse::<u8>().ok()?, m.parse::<u8>().ok()?))
+                })
+                .is_some_and(|(h, m)| h < 24 && m < 60);
+            if !valid {
+                return invalid(format!("updates: reboot_time '{}' must be HH:MM", time));
+            }
+        }
+        for pin in &self.pins {
+            if !plain(&pin.package) || pin.package.contains(char::is_whitespace) {
+                return invalid(format!("updates: invalid pinned package '{}'", pin.package));
+            }
+            if !plain(&pin.pin) {
+                return invalid(format!(
+                    "updates: invalid pin '{}' for {}",
+                    pin.pin, pin.package
+                ));
+            }
+        }
+        Ok(())
+    }
+
+    /// Whether any unattended-upgrades option differs from the package defaults
+    fn has_unattended_settings(&self) -> bool {
+        self.unattended_upgrades == Some(true)
+            || !self.allowed_origins.is_empty()
+            || self.automatic_reboot
+            || self.reboot_time.is_some()
+            || self.reboot_with_users
+            || self.remove_unused_dependencies
+    }
+
+    /// `APT::Periodic` overrides; only what was configured
+    pub fn periodic_conf(&self) -> String {
+        let mut out = MANAGED_HEADER.to_string();
+        if let Some(periodic) = &self.periodic {
+            out.push_str(&format!(
+                "APT::Periodic::Update-Package-Lists \"{}\";\nAPT::Periodic::Download-Upgradeable-Packages \"{}\";\nAPT::Periodic::AutocleanInterval \"{}\";\n",
+                periodic.update_package_lists,
+                periodic.download_upgradeable_packages,
+                periodic.autoclean_interval
+            ));
+        }
+        if let Some(enabled) = self.unattended_upgrades {
+            out.push_str(&format!(
+                "APT::Periodic::Unattended-Upgrade \"{}\";\n",
+                u8::from(enabled)
+            ));
+        }
+        out
+    }
+
+    /// unattended-upgrades overrides
+    pub fn unattended_conf(&self) -> String {
+        let mut out = MANAGED_HEADER.to_string();
+        if !self.allowed_origins.is_empty() {
+            out.push_str("#clear Unattended-Upgrade::Allowed-Origins;\nUnattended-Upgrade::Allowed-Origins {\n");
+            for origin in &self.allowed_origins {
+                out.push_str(&format!("    \"{}\";\n", unattended_variables(origin)));
+            }
+            out.push_str("};\n");
+        }
+        out.push_str(&format!(
+            "Unattended-Upgrade::Automatic-Reboot \"{}\";\n",
+            self.automatic_reboot
+        ));
+        if let Some(time) = &self.reboot_time {
+            out.push_str(&format!(
+                "Unattended-Upgrade::Automatic-Reboot-Time \"{}\";\n",
+                time
+            ));
+        }
+        out.push_str(&format!(
+            "Unattended-Upgrade::Automatic-Reboot-WithUsers \"{}\";\n",
+            self.reboot_with_users
+        ));
+        out.push_str(&format!(
+            "Unattended-Upgrade::Remove-Unused-Dependencies \"{}\";\n",
+            self.remove_unused_dependencies
+        ));
+        out
+    }
+
+    /// apt preferences, one stanza per pin
+    pub fn preferences(&self) -> String {
+        self.pins
+            .iter()
+            .map(|pin| {
+                format!(
+                    "Explanation: Managed by ubuntu-autoinstall-agent\nPackage: {}\nPin: {}\nPin-Priority: {}\n",
+                    pin.package, pin.pin, pin.priority
+                )
+            })
+            .collect::<Vec<_>>()
+            .join("\n")
+    }
+
+    /// Commands writing the settings into the target mounted at `root`
+    pub fn build_apply_commands(&self, root: &str) -> Vec<String> {
+        let root = root.trim_end_matches('/');
+        if !self.is_enabled() {
+            return Vec::new();
+        }
+        let mut commands = Vec::new();
+        if self.unattended_upgrades == Some(true) {
+            commands.push(format!(
+                "chroot {} bash -lc 'DEBIAN_FRONTEND=noninteractive apt-get install -y unattended-upgrades'",
+                root
+            ));
+        }
+        if self.periodic.is_some() || self.unattended_upgrades.is_some() {
+            commands.push(write_file(root, PERIODIC_FILE, &self.periodic_conf()));
+        }
+        if self.unattended_upgrades != Some(false) && self.has_unattended_settings() {
+            commands.push(write_file(root, UNATTENDED_FILE, &self.unattended_conf()));
+        }
+        if !self.pins.is_empty() {
+            commands.push(write_file(root, PREFERENCES_FILE, &self.preferences()));
+        }
+        commands
+    }
+}
+
+/// Turn `{distro_id}` and `{distro_codename}` into the `${...}` variables unattended-upgrades expands
+fn unattended_variables(origin: &str) -> String {
+    ["distro_id", "distro_codename"]
+        .iter()
+        .fold(origin.to_string(), |origin, name| {
+            origin
+                .replace(&format!("${{{}}}", name), &format!("{{{}}}", name))
+                .replace(&format!("{{{}}}", name), &format!("${{{}}}", name))
+        })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::loader::parse_section;
+
+    fn parse(yaml: &str) -> UpdatesConfig {
+        parse_section::<UpdatesConfig>(yaml, "updates").unwrap()
+    }
+
+    #[test]
+    fn test_validation() {
+        assert!(!parse("hostname: a\n").is_enabled());
+        let bad = [
+            "updates:\n  unattended_upgrades: false\n  automatic_reboot: true\n",
+            "updates:\n  allowed_origins: ['a\"b']\n",
+            "updates:\n  reboot_time: '25:00'\n",
+            "updates:\n  reboot_time: '2:00'\n",
+            "updates:\n  pins:\n    - {package: 'linux image', pin: 'version 6.8.*', priority: 1001}\n",
+        ];
+        for yaml in bad {
+            assert!(parse(yaml).validate().is_err(), "{}", yaml);
+        }
+    }
+
+    #[test]
+    fn test_apply_commands() {
+        let config = parse(
+            "updates:\n  unattended_upgrades: true\n  allowed_origins: ['{distro_id}:${distro_codename}-security']\n  automatic_reboot: true\n  reboot_time: '03:30'\n  periodic:\n    autoclean_interval: 7\n  pins:\n    - {package: 'linux-*', pin: 'version 6.8.*', priority: 1001}\n",
+        );
+        assert!(config.validate().is_ok());
+        let commands = config.build_apply_commands("/mnt/targetos/");
+        assert_eq!(commands.len(), 4);
+        assert!(commands[0].contains("apt-get install -y unattended-upgrades"));
+        assert!(
+            commands[1].contains("cat > /mnt/targetos/etc/apt/apt.conf.d/21autoinstall-periodic")
+        );
+        assert!(commands[1].contains("APT::Periodic::AutocleanInterval \"7\";"));
+        assert!(commands[1].contains("APT::Periodic::Unattended-Upgrade \"1\";"));
+        assert!(commands[2].contains(
+            "#clear Unattended-Upgrade::Allowed-Origins;\nUnattended-Upgrade::Allowed-Origins {\n    \"${distro_id}:${distro_codename}-security\";\n};"
+        ));
+        assert!(commands[2].contains("Unattended-Upgrade::Automatic-Reboot-Time \"03:30\";"));
+        assert!(commands[3].contains(
+            "Explanation: Managed by ubuntu-autoinstall-agent\nPackage: linux-*\nPin: version 6.8.*\nPin-Priority: 1001\n"
+        ));
+
+        // Turning it off only writes the periodic switch
+        let off = parse("updates:\n  unattended_upgrades: false\n");
+        let commands = off.build_apply_commands("/mnt/targetos");
+        assert_eq!(commands.len(), 1);
+        assert!(commands[0].contains("APT::Periodic::Unattended-Upgrade \"0\";"));
+        assert!(!commands[0].contains("Update-Package-Lists"));
+
+        // Pins alone leave the update schedule alone
+        let pins = parse(
+            "updates:\n  pins:\n    - {package: nginx, pin: 'origin nginx.org', priority: 900}\n",
+        );
+        assert_eq!(pins.build_apply_commands("/mnt/targetos").len(), 1);
+    }
+}
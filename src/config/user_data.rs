@@ -0,0 +1,391 @@
+// file: src/config/user_data.rs
+// version: 1.2.0
+// guid: 9b3e6d21-4f78-4a5c-8d19-2e7a5c1f8b46
+
+//! cloud-init style user-data applied on first boot (`user_data:` section of a target config)
+//!
+//! Bare-metal installs do not run cloud-init, but teams already describe last-mile changes as
+//! `#cloud-config`. The supported subset is `write_files`, `package_update`, `package_upgrade`,
+//! `packages` and `runcmd`; any other module is rejected instead of being silently ignored. The
+//! section takes the document as a mapping or as the original text:
+//!
+//! ```yaml
+//! user_data: |
+//!   #cloud-config
+//!   packages: [nginx, [jq, 1.7.1-3build1]]
+//!   write_files:
+//!     - path: /etc/nginx/conf.d/status.conf
+//!       content: "server { listen 8080; location /status { stub_status; } }"
+//!   runcmd:
+//!     - systemctl reload nginx
+//!     - [sh, -c, "echo done > /run/first-boot"]
+//! ```
+//!
+//! The install stages a script and a oneshot unit that runs it once the network is up on first
+//! boot, in cloud-init's order: `write_files`, package update/upgrade/install, `write_files`
+//! with `defer: true`, then `runcmd`. Like cloud-init it runs once per install, also when a
+//! step fails; the output goes to `/var/log/autoinstall-user-data.log`.
+
+use crate::error::AutoInstallError;
+use crate::utils::shell_quote;
+use crate::Result;
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+
+/// Directory the staged document and scripts live in on the target
+pub const USER_DATA_DIR: &str = "/var/lib/ubuntu-autoinstall-agent/user-data";
+/// Log of the first-boot run on the target
+pub const USER_DATA_LOG: &str = "/var/log/autoinstall-user-data.log";
+const UNIT_NAME: &str = "autoinstall-user-data.service";
+const SUPPORTED_MODULES: &str = "write_files, package_update, package_upgrade, packages, runcmd";
+
+/// One `write_files` entry
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct WriteFile {
+    pub path: String,
+    #[serde(default)]
+    pub content: String,
+    /// `text/plain` (default), `b64`/`base64` or `gz+b64`/`gzip+base64`
+    #[serde(default)]
+    pub encoding: Option<String>,
+    #[serde(default)]
+    pub owner: Option<String>,
+    /// Octal mode, e.g. `'0600'`
+    #[serde(default)]
+    pub permissions: Option<String>,
+    #[serde(default)]
+    pub append: bool,
+    /// Write after packages are installed, for owners created by a package
+    #[serde(default)]
+    pub defer: bool,
+}
+
+/// A package name, or `[name, version]`
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(untagged)]
+pub enum PackageSpec {
+    Name(String),
+    Pinned(Vec<String>),
+}
+
+/// A `runcmd` entry: a shell line, or an argument list run without a shell
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(untagged)]
+pub enum RunCommand {
+    Shell(String),
+    Argv(Vec<String>),
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct CloudConfig {
+    pub write_files: Vec<WriteFile>,
+    pub package_update: bool,
+    pub package_upgrade: bool,
+    pub packages: Vec<PackageSpec>,
+    pub runcmd: Vec<RunCommand>,
+    /// Modules outside the supported subset; rejected by validation
+    #[serde(flatten, skip_serializing)]
+    pub unsupported: BTreeMap<String, serde_yaml::Value>,
+}
+
+/// How the section may be written: the `#cloud-config` text or a mapping
+#[derive(Deserialize)]
+#[serde(untagged)]
+enum RawUserData {
+    Document(String),
+    Mapping(CloudConfig),
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+#[serde(try_from = "RawUserData", into = "CloudConfig")]
+pub struct UserDataConfig {
+    pub cloud_config: CloudConfig,
+}
+
+impl TryFrom<RawUserData> for UserDataConfig {
+    type Error = String;
+
+    fn try_from(raw: RawUserData) -> std::result::Result<Self, Self::Error> {
+        let cloud_config = match raw {
+            RawUserData::Mapping(config) => config,
+            RawUserData::Document(text) => {
+                let body = text.trim_start().strip_prefix("#cloud-config").ok_or(
+                    "user_data text must be a #cloud-config document; other user-data formats are not supported",
+                )?;
+                if body.trim().is_empty() {
+                    CloudConfig::default()
+                } else {
+                    serde_yaml::from_str(body).map_err(|e| format!("user_data: {}", e))?
+                }
+            }
+        };
+        Ok(Self { cloud_config })
+    }
+}
+
+impl From<UserDataConfig> for CloudConfig {
+    fn from(config: UserDataConfig) -> Self {
+        config.cloud_config
+    }
+}
+
+impl UserDataConfig {
+    pub fn is_enabled(&self) -> bool {
+        let c = &self.cloud_config;
+        !c.write_files.is_empty()
+            || c.package_update
+            || c.package_upgrade
+            || !c.packages.is_empty()
+            || !c.runcmd.is_empty()
+    }
+
+    pub fn validate(&self) -> Result<()> {
+        let config = &self.cloud_config;
+        if !config.unsupported.is_empty() {
+            let keys: Vec<&str> = config.unsupported.keys().map(String::as_str).collect();
+            return Err(AutoInstallError::ValidationError(format!(
+                "user_data uses unsupported cloud-config modules {}; supported are {}",
+                keys.join(", "),
+                SUPPORTED_MODULES
+            )));
+        }
+        for file in &config.write_files {
+            let invalid = |reason: &str| {
+                Err(AutoInstallError::ValidationError(format!(
+                    "user_data write_files {}: {}",
+                    file.path, reason
+                )))
+            };
+            if !file.path.starts_with('/') {
+                return invalid("path must be absolute");
+            }
+            if decoder(file.encoding.as_deref()).is_none() {
+                return invalid("encoding must be text/plain, b64 or gz+b64");
+            }
+            if let Some(mode) = &file.permissions {
+                let digits = mode.trim_start_matches("0o");
+                if !(3..=4).contains(&digits.len())
+                    || !digits.chars().all(|c| ('0'..='7').contains(&c))
+                {
+                    return invalid("permissions must be an octal mode such as '0644'");
+                }
+            }
+        }
+        for package in &config.packages {
+            let ok = match package {
+                PackageSpec::Name(name) => !name.trim().is_empty(),
+                PackageSpec::Pinned(pair) => pair.len() == 2 && pair.iter().all(|p| !p.is_empty()),
+            };
+            if !ok {
+                return Err(AutoInstallError::ValidationError(format!(
+                    "user_data package {:?} must be a name or [name, version]",
+                    package
+                )));
+            }
+        }
+        if config
+            .runcmd
+            .iter()
+            .any(|cmd| matches!(cmd, RunCommand::Argv(argv) if argv.is_empty()))
+        {
+            return Err(AutoInstallError::ValidationError(
+                "user_data runcmd entries must not be empty lists".to_string(),
+            ));
+        }
+        Ok(())
+    }
+
+    /// Script the first-boot unit runs, in cloud-init's module order
+    pub fn build_script(&self) -> String {
+        let config = &self.cloud_config;
+        let mut script = String::from(
+            "#!/bin/sh\n# Managed by ubuntu-autoinstall-agent\nset -eu\nexport DEBIAN_FRONTEND=noninteractive\n",
+        );
+        for file in config.write_files.iter().filter(|f| !f.defer) {
+            script.push_str(&write_file_command(file));
+        }
+        if config.package_update || config.package_upgrade || !config.packages.is_empty() {
+            script.push_str("apt-get update\n");
+        }
+        if config.package_upgrade {
+            script.push_str("apt-get -y upgrade\n");
+        }
+        if !config.packages.is_empty() {
+            let packages: Vec<String> = config
+                .packages
+                .iter()
+                .map(|package| match package {
+                    PackageSpec::Name(name) => shell_quote(name),
+                    PackageSpec::Pinned(pair) => shell_quote(&pair.join("=")),
+                })
+                .collect();
+            script.push_str(&format!("apt-get install -y {}\n", packages.join(" ")));
+        }
+        for file in config.write_files.iter().filter(|f| f.defer) {
+            script.push_str(&write_file_command(file));
+        }
+        if !config.runcmd.is_empty() {
+            script.push_str(&format!("sh {}/runcmd.sh\n", USER_DATA_DIR));
+        }
+        script
+    }
+
+    /// `runcmd` as the shell script cloud-init would write for it
+    pub fn build_runcmd(&self) -> String {
+        let mut script = String::from("#!/bin/sh\n");
+        for command in &self.cloud_config.runcmd {
+            match command {
+                RunCommand::Shell(line) => script.push_str(line),
+                RunCommand::Argv(argv) => {
+                    let quoted: Vec<String> = argv.iter().map(|a| shell_quote(a)).collect();
+                    script.push_str(&quoted.join(" "));
+                }
+            }
+            script.push('\n');
+        }
+        script
+    }
+
+    /// Commands staging the document, scripts and first-boot unit in the system at `root`
+    pub fn build_apply_commands(&self, root: &str) -> Vec<String> {
+        if !self.is_enabled() {
+            return Vec::new();
+        }
+        let root = root.trim_end_matches('/');
+        let document = serde_yaml::to_string(&self.cloud_config).unwrap_or_default();
+        let unit = format!(
+            "[Unit]\n\
+             Description=Apply autoinstall user-data\n\
+             Wants=network-online.target\n\
+             After=network-online.target\n\
+             ConditionPathExists=!{d}/done\n\n\
+             [Service]\n\
+             Type=oneshot\n\
+             ExecStart={d}/run.sh\n\
+             ExecStopPost=/bin/touch {d}/done\n\
+             StandardOutput=append:{l}\n\
+             StandardError=inherit\n\
+             TimeoutStartSec=3600\n\n\
+             [Install]\n\
+             WantedBy=multi-user.target\n",
+            d = USER_DATA_DIR,
+            l = USER_DATA_LOG
+        );
+        let mut commands = vec![
+            format!("install -d -m 0700 {}{}", root, USER_DATA_DIR),
+            heredoc(
+                &format!("{}{}/user-data.yaml", root, USER_DATA_DIR),
+                &format!("#cloud-config\n{}", document),
+            ),
+            heredoc(
+                &format!("{}{}/run.sh", root, USER_DATA_DIR),
+                &self.build_script(),
+            ),
+        ];
+        if !self.cloud_config.runcmd.is_empty() {
+            commands.push(heredoc(
+                &format!("{}{}/runcmd.sh", root, USER_DATA_DIR),
+                &self.build_runcmd(),
+            ));
+        }
+        commands.push(format!("chmod 0700 {}{}/run.sh", root, USER_DATA_DIR));
+        commands.push(heredoc(
+            &format!("{}/etc/systemd/system/{}", root, UNIT_NAME),
+            &unit,
+        ));
+        commands.push(format!("chroot {} systemctl enable {}", root, UNIT_NAME));
+        commands
+    }
+}
+
+/// Shell pipeline decoding content of `encoding`; `None` for unsupported encodings
+fn decoder(encoding: Option<&str>) -> Option<&'static str> {
+    match encoding.unwrap_or("text/plain") {
+        "text/plain" => Some(""),
+        "b64" | "base64" => Some(" | base64 -d"),
+        "gz+b64" | "gzip+base64" | "gz+base64" | "gzip+b64" => Some(" | base64 -d | gunzip"),
+        _ => None,
+    }
+}
+
+fn write_file_command(file: &WriteFile) -> String {
+    let path = shell_quote(&file.path);
+    let mut command = format!(
+        "mkdir -p \"$(dirname {p})\"\nprintf '%s' {c}{d} {r} {p}\n",
+        p = path,
+        c = shell_quote(&file.content),
+        d = decoder(file.encoding.as_deref()).unwrap_or_default(),
+        r = if file.append { ">>" } else { ">" }
+    );
+    command.push_str(&format!(
+        "chmod {} {}\n",
+        file.permissions.as_deref().unwrap_or("0644"),
+        path
+    ));
+    command.push_str(&format!(
+        "chown {} {}\n",
+        shell_quote(file.owner.as_deref().unwrap_or("root:root")),
+        path
+    ));
+    command
+}
+
+/// Heredoc writing `content` to `path` verbatim
+fn heredoc(path: &str, content: &str) -> String {
+    format!(
+        "cat > {} << 'AUTOINSTALL_USER_DATA_EOF'\n{}AUTOINSTALL_USER_DATA_EOF",
+        path, content
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::loader::parse_section;
+
+    #[test]
+    fn test_cloud_config_document_becomes_first_boot_script() {
+        let config = parse_section::<UserDataConfig>(
+            "user_data: |\n  #cloud-config\n  packages: [nginx, [jq, 1.7.1-3build1]]\n  write_files:\n    - path: /etc/motd\n      content: \"it's managed\\n\"\n    - path: /etc/nginx/conf.d/x.conf\n      content: c2VydmVy\n      encoding: b64\n      owner: www-data:www-data\n      permissions: '0640'\n      defer: true\n  runcmd:\n    - systemctl reload nginx\n    - [sh, -c, echo done]\n",
+        "user_data").unwrap();
+        assert!(config.validate().is_ok());
+
+        let script = config.build_script();
+        let motd = script
+            .find("printf '%s' 'it'\\''s managed\n' > /etc/motd")
+            .unwrap();
+        let install = script
+            .find("apt-get install -y nginx jq=1.7.1-3build1")
+            .unwrap();
+        let deferred = script
+            .find("printf '%s' c2VydmVy | base64 -d > /etc/nginx/conf.d/x.conf")
+            .unwrap();
+        assert!(motd < install && install < deferred);
+        assert!(script.contains("chown www-data:www-data /etc/nginx/conf.d/x.conf"));
+        assert!(script.ends_with("sh /var/lib/ubuntu-autoinstall-agent/user-data/runcmd.sh\n"));
+        assert_eq!(
+            config.build_runcmd(),
+            "#!/bin/sh\nsystemctl reload nginx\nsh -c 'echo done'\n"
+        );
+        assert!(config
+            .build_apply_commands("/mnt/targetos")
+            .last()
+            .unwrap()
+            .ends_with("systemctl enable autoinstall-user-data.service"));
+
+        let users = parse_section::<UserDataConfig>(
+            "user_data:\n  users: [deploy]\n  runcmd: [reboot]\n",
+            "user_data",
+        );
+        assert!(users.unwrap().validate().is_err());
+        assert!(parse_section::<UserDataConfig>(
+            "user_data: \"#!/bin/sh\\necho hi\"\n",
+            "user_data"
+        )
+        .is_err());
+        assert!(UserDataConfig::default()
+            .build_apply_commands("/mnt/targetos")
+            .is_empty());
+    }
+}
@@ -0,0 +1,202 @@
+// file: src/config/verification.rs
+// version: 1.1.0
+// guid: 2a8d5c17-9f36-4e0b-a4c1-6e3b7d9f2a58
+
+//! Service probes run against an installed host once it has booted (`verification:` section
+//! of a target config)
+//!
+//! Readiness probes are retried until they pass or `ready_timeout_secs` runs out, since
+//! services take a while to come up after first boot. Once every readiness probe passed,
+//! liveness probes are sampled `liveness_samples` times, `interval_secs` apart, and must pass
+//! every time, which catches services that start and then crash-loop.
+//!
+//! ```yaml
+//! verification:
+//!   probes:
+//!     - name: api
+//!       type: http
+//!       url: http://127.0.0.1:8080/healthz
+//!       expect_status: 200
+//!     - name: postgres
+//!       type: tcp
+//!       port: 5432
+//!     - name: nginx
+//!       type: systemd
+//!       unit: nginx.service
+//!       role: liveness
+//!     - name: migrations
+//!       type: command
+//!       command: test -f /var/lib/app/migrated
+//!       timeout_secs: 30
+//! ```
+
+use crate::error::AutoInstallError;
+use crate::Result;
+use serde::{Deserialize, Serialize};
+use std::collections::HashSet;
+
+fn default_host() -> String {
+    "127.0.0.1".to_string()
+}
+
+fn default_status() -> u16 {
+    200
+}
+
+fn default_timeout_secs() -> u64 {
+    10
+}
+
+/// When a probe is checked
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum ProbeRole {
+    /// Retried until it passes or the readiness timeout runs out
+    #[default]
+    Readiness,
+    /// Sampled repeatedly after readiness; every sample must pass
+    Liveness,
+}
+
+impl ProbeRole {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            ProbeRole::Readiness => "readiness",
+            ProbeRole::Liveness => "liveness",
+        }
+    }
+}
+
+/// What a probe checks; every check runs on the installed host
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(tag = "type", rename_all = "lowercase")]
+pub enum ProbeCheck {
+    /// A TCP connection to `host:port` succeeds
+    Tcp {
+        #[serde(default = "default_host")]
+        host: String,
+        port: u16,
+    },
+    /// A GET of `url` answers `expect_status`, optionally with `contains` in the body
+    Http {
+        url: String,
+        #[serde(default = "default_status")]
+        expect_status: u16,
+        #[serde(default)]
+        contains: Option<String>,
+    },
+    /// `systemctl is-active` reports the unit active
+    Systemd { unit: String },
+    /// A shell command exits with `expect_exit`
+    Command {
+        command: String,
+        #[serde(default)]
+        expect_exit: i32,
+    },
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Probe {
+    pub name: String,
+    #[serde(flatten)]
+    pub check: ProbeCheck,
+    #[serde(default)]
+    pub role: ProbeRole,
+    /// Upper bound for one attempt
+    #[serde(default = "default_timeout_secs")]
+    pub timeout_secs: u64,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct VerificationConfig {
+    pub probes: Vec<Probe>,
+    /// How long readiness probes may take to pass in total
+    pub ready_timeout_secs: u64,
+    /// Delay between readiness retries and between liveness samples
+    pub interval_secs: u64,
+    /// Number of times each liveness probe is checked
+    pub liveness_samples: u32,
+}
+
+impl Default for VerificationConfig {
+    fn default() -> Self {
+        Self {
+            probes: Vec::new(),
+            ready_timeout_secs: 300,
+            interval_secs: 5,
+            liveness_samples: 3,
+        }
+    }
+}
+
+impl VerificationConfig {
+    pub fn validate(&self) -> Result<()> {
+        let mut names = HashSet::new();
+        for probe in &self.probes {
+            if probe.name.trim().is_empty() || !names.insert(probe.name.as_str()) {
+                return Err(AutoInstallError::ValidationError(format!(
+                    "verification probe names must be unique and non-empty ('{}')",
+                    probe.name
+                )));
+            }
+            if probe.timeout_secs == 0 {
+                return Err(AutoInstallError::ValidationError(format!(
+                    "verification probe {} needs a timeout_secs of at least 1",
+                    probe.name
+                )));
+            }
+            match &probe.check {
+                ProbeCheck::Http { url, .. }
+                    if !url.starts_with("http://") && !url.starts_with("https://") =>
+                {
+                    return Err(AutoInstallError::ValidationError(format!(
+                        "verification probe {}: '{}' is not an http(s) URL",
+                        probe.name, url
+                    )));
+                }
+                ProbeCheck::Systemd { unit } if unit.trim().is_empty() => {
+                    return Err(AutoInstallError::ValidationError(format!(
+                        "verification probe {} names no unit",
+                        probe.name
+                    )));
+                }
+                _ => {}
+            }
+        }
+        if self.interval_secs == 0 || self.liveness_samples == 0 {
+            return Err(AutoInstallError::ValidationError(
+                "verification interval_secs and liveness_samples must be at least 1".to_string(),
+            ));
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::loader::parse_section;
+
+    #[test]
+    fn test_probes_parse_with_defaults() {
+        let config = parse_section::<VerificationConfig>(
+            "verification:\n  probes:\n    - name: pg\n      type: tcp\n      port: 5432\n    - name: api\n      type: http\n      url: http://127.0.0.1/healthz\n      role: liveness\n",
+        "verification").unwrap();
+        assert!(config.validate().is_ok());
+        assert_eq!(
+            config.probes[0].check,
+            ProbeCheck::Tcp {
+                host: "127.0.0.1".to_string(),
+                port: 5432
+            }
+        );
+        assert_eq!(config.probes[0].role, ProbeRole::Readiness);
+        assert_eq!(config.probes[1].role, ProbeRole::Liveness);
+        assert_eq!(config.probes[1].timeout_secs, 10);
+
+        let mut duplicate = config.clone();
+        duplicate.probes[1].name = "pg".to_string();
+        assert!(duplicate.validate().is_err());
+    }
+}
@@ -0,0 +1,124 @@
+// file: src/config/vsphere.rs
+// version: 1.0.0
+// guid: 5b9e2c74-8a16-4f3d-b7e0-1c6a4d8f2e59
+
+//! vCenter/ESXi placement of a VM deployment (`vsphere:` section of a target config)
+//!
+//! Used by `deploy-vsphere`, which imports the golden image, creates the VM with these
+//! resources and networks and hands the target config to cloud-init through guestinfo.
+
+use serde::{Deserialize, Serialize};
+
+fn default_cpus() -> u32 {
+    2
+}
+
+fn default_memory_mb() -> u64 {
+    4096
+}
+
+fn default_guest_id() -> String {
+    "ubuntu64Guest".to_string()
+}
+
+/// vSphere endpoint, credentials and the VM to create
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct VsphereConfig {
+    /// vCenter or ESXi address, optionally with scheme (`https://` is assumed)
+    pub url: String,
+    pub username: String,
+    /// Password (supports environment variable substitution, e.g. `${VSPHERE_PASSWORD}`)
+    pub password: String,
+    /// Accept self-signed vCenter certificates
+    #[serde(default)]
+    pub insecure_tls: bool,
+    pub datacenter: String,
+    /// Datastore the disk is uploaded to
+    pub datastore: String,
+    /// Resource pool or cluster path; the datacenter's default pool when unset
+    #[serde(default)]
+    pub resource_pool: Option<String>,
+    /// VM folder; the datacenter's VM folder when unset
+    #[serde(default)]
+    pub folder: Option<String>,
+    #[serde(default = "default_cpus")]
+    pub cpus: u32,
+    #[serde(default = "default_memory_mb")]
+    pub memory_mb: u64,
+    /// Grow the imported disk to this size
+    #[serde(default)]
+    pub disk_gb: Option<u64>,
+    /// Port groups, one NIC each; the first carries the target config's network settings
+    pub networks: Vec<String>,
+    #[serde(default = "default_guest_id")]
+    pub guest_id: String,
+}
+
+impl VsphereConfig {
+    /// SDK URL of the endpoint
+    pub fn sdk_url(&self) -> String {
+        let url = self.url.trim_end_matches('/');
+        let url = if url.starts_with("http://") || url.starts_with("https://") {
+            url.to_string()
+        } else {
+            format!("https://{}", url)
+        };
+        if url.ends_with("/sdk") {
+            url
+        } else {
+            format!("{}/sdk", url)
+        }
+    }
+
+    pub fn validate(&self) -> crate::Result<()> {
+        for (field, value) in [
+            ("url", &self.url),
+            ("username", &self.username),
+            ("password", &self.password),
+            ("datacenter", &self.datacenter),
+            ("datastore", &self.datastore),
+        ] {
+            if value.trim().is_empty() {
+                return Err(crate::error::AutoInstallError::ValidationError(format!(
+                    "vsphere.{} must not be empty",
+                    field
+                )));
+            }
+        }
+        if self.networks.is_empty() {
+            return Err(crate::error::AutoInstallError::ValidationError(
+                "vsphere.networks needs at least one port group".to_string(),
+            ));
+        }
+        if self.cpus == 0 || self.memory_mb < 1024 {
+            return Err(crate::error::AutoInstallError::ValidationError(format!(
+                "vsphere VM needs at least 1 CPU and 1024 MB of memory (got {} CPUs, {} MB)",
+                self.cpus, self.memory_mb
+            )));
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_vsphere_defaults_and_url() {
+        let config: VsphereConfig = serde_yaml::from_str(
+            "url: vc.lab\nusername: deploy@vsphere.local\npassword: secret\ndatacenter: dc1\ndatastore: ssd-01\nnetworks: [VM Network]\n",
+        )
+        .unwrap();
+        assert_eq!(config.cpus, 2);
+        assert_eq!(config.guest_id, "ubuntu64Guest");
+        assert_eq!(config.sdk_url(), "https://vc.lab/sdk");
+        assert!(config.validate().is_ok());
+
+        let bad = VsphereConfig {
+            networks: Vec::new(),
+            ..config
+        };
+        assert!(bad.validate().is_err());
+    }
+}
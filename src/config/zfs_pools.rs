@@ -0,0 +1,412 @@
+// file: src/config/zfs_pools.rs
+// version: 1.2.0
+// guid: 7c3e1a58-9b24-4d6f-a8e0-2f5b7d9c1e43
+
+//! Pool names and properties (`zfs_pools:` section of a target config)
+//!
+//! Nodes of a storage cluster replicate to and import each other's pools, so every node's pools
+//! need a name of their own: `name: "rpool-{hostname}"` gives each host unique pool names. Pool
+//! (`-o`) and root dataset (`-O`) properties override the installer's defaults, and `role`
+//! adds a dataset tuned for what the node stores. Everything is checked against the OpenZFS
+//! version of the live system before a pool is created, since an unsupported property or feature
+//! only fails at `zpool create`, after the disk has been partitioned.
+
+use crate::error::AutoInstallError;
+use crate::Result;
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+
+/// What the node's bulk storage is for
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum StorageRole {
+    /// System datasets only
+    #[default]
+    Standalone,
+    /// VM and container images: 64K records
+    ComputeNode,
+    /// Large files served to others: 1M records, zstd
+    StorageNode,
+    /// Received backup streams: 1M records, zstd-9
+    BackupNode,
+}
+
+/// Overrides for one pool
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct PoolSpec {
+    /// Pool name; `{hostname}` is replaced with the target's hostname
+    pub name: Option<String>,
+    pub ashift: Option<u8>,
+    pub autotrim: Option<bool>,
+    /// `compatibility` property, e.g. `openzfs-2.1-linux`; the boot pool needs `grub2`
+    pub compatibility: Option<String>,
+    /// Features enabled at creation, without the `feature@` prefix
+    pub features: Vec<String>,
+    /// Properties of the pool's root dataset (`zpool create -O`)
+    pub properties: BTreeMap<String, String>,
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct ZfsPoolsConfig {
+    pub role: StorageRole,
+    /// Pool on the LUKS mapping holding the system
+    pub root_pool: PoolSpec,
+    /// GRUB-readable pool holding `/boot`
+    pub boot_pool: PoolSpec,
+}
+
+/// A pool as it will be created
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct PoolPlan {
+    pub name: String,
+    /// `-o` properties in command order
+    pub pool_properties: Vec<(String, String)>,
+    /// `-O` properties in command order
+    pub dataset_properties: Vec<(String, String)>,
+}
+
+impl PoolPlan {
+    /// `zpool create` for `device`, with the pool's datasets mounted below `altroot`
+    pub fn create_command(&self, device: &str, altroot: &str) -> String {
+        let mut cmd = String::from("zpool create");
+        for (key, value) in &self.pool_properties {
+            cmd.push_str(&format!(" -o {}={}", key, value));
+        }
+        for (key, value) in &self.dataset_properties {
+            cmd.push_str(&format!(" -O {}={}", key, value));
+        }
+        cmd.push_str(&format!(" -m none -R {} {} {}", altroot, self.name, device));
+        cmd
+    }
+
+    /// Value of a `-o` or `-O` property
+    pub fn property(&self, key: &str) -> Option<&str> {
+        self.pool_properties
+            .iter()
+            .chain(&self.dataset_properties)
+            .find(|(k, _)| k == key)
+            .map(|(_, v)| v.as_str())
+    }
+}
+
+/// Dataset added for the node's role
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct RoleDataset {
+    /// Name below the root pool
+    pub name: &'static str,
+    pub mountpoint: &'static str,
+    pub properties: Vec<(&'static str, &'static str)>,
+}
+
+impl RoleDataset {
+    pub fn create_command(&self, pool: &str) -> String {
+        let mut cmd = format!(
+            "zfs create -o canmount=on -o mountpoint={}",
+            self.mountpoint
+        );
+        for (key, value) in &self.properties {
+            cmd.push_str(&format!(" -o {}={}", key, value));
+        }
+        cmd.push_str(&format!(" {}/{}", pool, self.name));
+        cmd
+    }
+}
+
+/// Pools and role datasets of one install
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct PoolLayout {
+    pub root: PoolPlan,
+    pub boot: PoolPlan,
+    pub role_dataset: Option<RoleDataset>,
+}
+
+impl Default for PoolLayout {
+    /// The installer's `rpool`/`bpool` layout
+    fn default() -> Self {
+        ZfsPoolsConfig::default()
+            .resolve("")
+            .expect("default pool layout is valid")
+    }
+}
+
+impl PoolLayout {
+    /// Parent of the root datasets, `rpool/ROOT` by default
+    pub fn root_container(&self) -> String {
+        format!("{}/ROOT", self.root.name)
+    }
+
+    /// Parent of the boot datasets, `bpool/BOOT` by default
+    pub fn boot_container(&self) -> String {
+        format!("{}/BOOT", self.boot.name)
+    }
+}
+
+/// Names `zpool` reserves for vdev types
+const RESERVED_POOL_NAMES: &[&str] = &["mirror", "raidz", "draid", "spare", "log", "cache"];
+
+impl ZfsPoolsConfig {
+    pub fn validate(&self) -> Result<()> {
+        for spec in [&self.root_pool, &self.boot_pool] {
+            if let Some(ashift) = spec.ashift {
+                if !(9..=16).contains(&ashift) {
+                    return Err(invalid(format!(
+                        "zfs_pools ashift {} is outside 9-16",
+                        ashift
+                    )));
+                }
+            }
+            for name in spec.features.iter().chain(spec.properties.keys()) {
+                if name.is_empty()
+                    || !name.chars().all(|c| {
+                        c.is_ascii_lowercase() || c.is_ascii_digit() || matches!(c, '_' | ':' | '.')
+                    })
+                {
+                    return Err(invalid(format!(
+                        "zfs_pools property or feature '{}' is not a ZFS name",
+                        name
+                    )));
+                }
+            }
+            if spec
+                .properties
+                .values()
+                .any(|v| v.is_empty() || v.contains(char::is_whitespace))
+            {
+                return Err(invalid(
+                    "zfs_pools property values must be single words".to_string(),
+                ));
+            }
+        }
+        if let Some(compat) = &self.boot_pool.compatibility {
+            if !compat.split(',').any(|c| c == "grub2") {
+                return Err(invalid(format!(
+                    "zfs_pools boot_pool compatibility '{}' must include grub2 so GRUB can read /boot",
+                    compat
+                )));
+            }
+        }
+        // Names with `{hostname}` are checked once the hostname is known
+        self.resolve("host").map(drop)
+    }
+
+    /// Pools for the target `hostname`
+    pub fn resolve(&self, hostname: &str) -> Result<PoolLayout> {
+        let root = plan(
+            &self.root_pool,
+            "rpool",
+            hostname,
+            &[("ashift", "12"), ("autotrim", "on")],
+            &[
+                ("acltype", "posixacl"),
+                ("xattr", "sa"),
+                ("dnodesize", "auto"),
+                ("compression", "lz4"),
+                ("normalization", "formD"),
+                ("relatime", "on"),
+                ("canmount", "off"),
+                ("mountpoint", "none"),
+            ],
+        )?;
+        let boot = plan(
+            &self.boot_pool,
+            "bpool",
+            hostname,
+            &[
+                ("ashift", "12"),
+                ("autotrim", "on"),
+                ("cachefile", "/etc/zfs/zpool.cache"),
+                ("compatibility", "grub2"),
+                ("feature@livelist", "enabled"),
+                ("feature@zpool_checkpoint", "enabled"),
+            ],
+            &[
+                ("devices", "off"),
+                ("acltype", "posixacl"),
+                ("xattr", "sa"),
+                ("compression", "lz4"),
+                ("normalization", "formD"),
+                ("relatime", "on"),
+                ("canmount", "off"),
+                ("mountpoint", "none"),
+            ],
+        )?;
+        if root.name == boot.name {
+            return Err(invalid(format!(
+                "zfs_pools root_pool and boot_pool are both named {}",
+                root.name
+            )));
+        }
+        Ok(PoolLayout {
+            root,
+            boot,
+            role_dataset: role_dataset(self.role),
+        })
+    }
+}
+
+fn plan(
+    spec: &PoolSpec,
+    default_name: &str,
+    hostname: &str,
+    pool_defaults: &[(&str, &str)],
+    dataset_defaults: &[(&str, &str)],
+) -> Result<PoolPlan> {
+    let name = spec
+        .name
+        .as_deref()
+        .unwrap_or(default_name)
+        .replace("{hostname}", hostname);
+    check_pool_name(&name)?;
+
+    let owned = |pairs: &[(&str, &str)]| -> Vec<(String, String)> {
+        pairs
+            .iter()
+            .map(|(k, v)| (k.to_string(), v.to_string()))
+            .collect()
+    };
+    let mut pool_properties = owned(pool_defaults);
+    if let Some(ashift) = spec.ashift {
+        set(&mut pool_properties, "ashift", ashift.to_string());
+    }
+    if let Some(autotrim) = spec.autotrim {
+        set(
+            &mut pool_properties,
+            "autotrim",
+            if autotrim { "on" } else { "off" }.to_string(),
+        );
+    }
+    if let Some(compat) = &spec.compatibility {
+        set(&mut pool_properties, "compatibility", compat.clone());
+    }
+    for feature in &spec.features {
+        set(
+            &mut pool_properties,
+            &format!("feature@{}", feature),
+            "enabled".to_string(),
+        );
+    }
+    let mut dataset_properties = owned(dataset_defaults);
+    for (key, value) in &spec.properties {
+        set(&mut dataset_properties, key, value.clone());
+    }
+    Ok(PoolPlan {
+        name,
+        pool_properties,
+        dataset_properties,
+    })
+}
+
+/// Replace `key` in place, or append it
+fn set(properties: &mut Vec<(String, String)>, key: &str, value: String) {
+    match properties.iter_mut().find(|(k, _)| k == key) {
+        Some(entry) => entry.1 = value,
+        None => properties.push((key.to_string(), value)),
+    }
+}
+
+fn check_pool_name(name: &str) -> Result<()> {
+    let valid = name.starts_with(|c: char| c.is_ascii_alphabetic())
+        && name
+            .chars()
+            .all(|c| c.is_ascii_alphanumeric() || matches!(c, '_' | '-' | '.' | ':'))
+        && !RESERVED_POOL_NAMES.iter().any(|r| name.starts_with(r))
+        && !(name.starts_with('c') && name[1..].starts_with(|c: char| c.is_ascii_digit()));
+    if valid {
+        Ok(())
+    } else {
+        Err(invalid(format!("'{}' is not a valid ZFS pool name", name)))
+    }
+}
+
+fn role_dataset(role: StorageRole) -> Option<RoleDataset> {
+    match role {
+        StorageRole::Standalone => None,
+        StorageRole::ComputeNode => Some(RoleDataset {
+            name: "images",
+            mountpoint: "/var/lib/libvirt/images",
+            properties: vec![("recordsize", "64K"), ("compression", "lz4")],
+        }),
+        StorageRole::StorageNode => Some(RoleDataset {
+            name: "data",
+            mountpoint: "/srv/data",
+            properties: vec![
+                ("recordsize", "1M"),
+                ("compression", "zstd"),
+                ("atime", "off"),
+            ],
+        }),
+        StorageRole::BackupNode => Some(RoleDataset {
+            name: "backup",
+            mountpoint: "/srv/backup",
+            properties: vec![
+                ("recordsize", "1M"),
+                ("compression", "zstd-9"),
+                ("atime", "off"),
+                ("sync", "disabled"),
+            ],
+        }),
+    }
+}
+
+fn invalid(message: String) -> AutoInstallError {
+    AutoInstallError::ValidationError(message)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_resolve_cluster_names_and_version_checks() {
+        let config: ZfsPoolsConfig = serde_yaml::from_str(
+            "role: backup-node\n\
+             root_pool:\n  name: \"rpool-{hostname}\"\n  ashift: 13\n  features: [block_cloning]\n  properties: {compression: zstd}\n\
+             boot_pool:\n  name: \"bpool-{hostname}\"\n  autotrim: false\n",
+        )
+        .unwrap();
+        config.validate().unwrap();
+        let layout = config.resolve("stor-02").unwrap();
+        assert_eq!(
+            layout
+                .root
+                .create_command("/dev/mapper/luks", "/mnt/targetos"),
+            "zpool create -o ashift=13 -o autotrim=on -o feature@block_cloning=enabled \
+             -O acltype=posixacl -O xattr=sa -O dnodesize=auto -O compression=zstd \
+             -O normalization=formD -O relatime=on -O canmount=off -O mountpoint=none \
+             -m none -R /mnt/targetos rpool-stor-02 /dev/mapper/luks"
+        );
+        assert!(layout
+            .boot
+            .create_command("/dev/sda3", "/mnt/targetos")
+            .contains("-o autotrim=off -o cachefile=/etc/zfs/zpool.cache -o compatibility=grub2"));
+        assert_eq!(
+            layout
+                .role_dataset
+                .as_ref()
+                .unwrap()
+                .create_command(&layout.root.name),
+            "zfs create -o canmount=on -o mountpoint=/srv/backup -o recordsize=1M \
+             -o compression=zstd-9 -o atime=off -o sync=disabled rpool-stor-02/backup"
+        );
+
+        let clash = ZfsPoolsConfig {
+            boot_pool: PoolSpec {
+                name: Some("rpool".to_string()),
+                ..Default::default()
+            },
+            ..Default::default()
+        };
+        assert!(clash.validate().is_err());
+        let reserved = ZfsPoolsConfig {
+            root_pool: PoolSpec {
+                name: Some("mirror0".to_string()),
+                ..Default::default()
+            },
+            ..Default::default()
+        };
+        assert!(reserved.validate().is_err());
+        assert_eq!(PoolLayout::default().root.name, "rpool");
+    }
+}
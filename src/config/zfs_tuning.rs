@@ -0,0 +1,401 @@
+// file: src/config/zfs_tuning.rs
+// version: 1.1.0
+// guid: 5f2d8a16-7c93-4e0b-b4a7-1e6c9d3f0a82
+
+//! ZFS module parameter tuning (`zfs_tuning:` section of a target config)
+//!
+//! ZFS lets the ARC grow to most of RAM by default, which starves desktops and small VMs. The
+//! installer sizes `zfs_arc_max` from the target's memory and role, writes the result to
+//! `/etc/modprobe.d` in the chroot and records it in the install report. Explicit values in the
+//! config always win over computed ones.
+
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+
+/// modprobe.d file for the computed options; sorts before the `kernel:` tuning file so an
+/// explicit `kernel.module_options.zfs` entry still wins
+pub const MODPROBE_FILE: &str = "etc/modprobe.d/60-autoinstall-zfs.conf";
+
+const MIB: u64 = 1024 * 1024;
+
+/// Below this much RAM the target is treated as memory-constrained
+const LOW_MEMORY_MB: u64 = 4096;
+
+/// RAM left outside the ARC on servers, as long as the ARC keeps at least a quarter of RAM
+const SERVER_RESERVED_MB: u64 = 4096;
+
+/// Smallest ARC the installer will configure
+const MIN_ARC_MB: u64 = 256;
+
+/// What the installed machine is used for
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum MachineRole {
+    /// Storage-heavy host; the ARC may use up to three quarters of RAM
+    #[default]
+    Server,
+    /// Interactive machine; the ARC is kept to a quarter of RAM
+    Desktop,
+}
+
+/// ZFS tuning requested for one target
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct ZfsTuningConfig {
+    /// Compute and write ZFS module options
+    pub enabled: bool,
+    /// Role used to size the ARC
+    pub role: MachineRole,
+    /// Fixed ARC ceiling in MiB instead of the computed one
+    pub arc_max_mb: Option<u64>,
+    /// ARC floor in MiB; left at the ZFS default when unset
+    pub arc_min_mb: Option<u64>,
+    /// Additional `zfs` module parameters, e.g. `zfs_txg_timeout: "10"`
+    pub parameters: BTreeMap<String, String>,
+}
+
+impl Default for ZfsTuningConfig {
+    fn default() -> Self {
+        Self {
+            enabled: true,
+            role: MachineRole::default(),
+            arc_max_mb: None,
+            arc_min_mb: None,
+            parameters: BTreeMap::new(),
+        }
+    }
+}
+
+/// Where a chosen parameter value came from
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum TuningSource {
+    Computed,
+    Config,
+}
+
+impl TuningSource {
+    /// Name used in reports
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            TuningSource::Computed => "computed",
+            TuningSource::Config => "config",
+        }
+    }
+}
+
+/// One `zfs` module parameter chosen for the install
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ZfsParameter {
+    pub name: String,
+    pub value: String,
+    pub source: TuningSource,
+    /// Why this value was chosen
+    pub reason: String,
+}
+
+/// Parameters chosen for one install, as recorded in the session and report
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ZfsTuning {
+    /// Target memory the values were computed from, when it was known
+    pub memory_total_mb: Option<u64>,
+    pub role: MachineRole,
+    pub parameters: Vec<ZfsParameter>,
+}
+
+impl ZfsTuningConfig {
+    /// Validate parameter names and values and the ARC bounds
+    pub fn validate(&self) -> crate::Result<()> {
+        for (name, value) in &self.parameters {
+            let well_formed = !name.is_empty()
+                && name
+                    .chars()
+                    .all(|c| c.is_ascii_lowercase() || c.is_ascii_digit() || c == '_');
+            if !well_formed {
+                return Err(crate::error::AutoInstallError::ValidationError(format!(
+                    "Invalid ZFS module parameter name: {}",
+                    name
+                )));
+            }
+            if value.trim().is_empty() || value.chars().any(char::is_whitespace) {
+                return Err(crate::error::AutoInstallError::ValidationError(format!(
+                    "Invalid value for ZFS module parameter {}: {:?}",
+                    name, value
+                )));
+            }
+        }
+        if let (Some(min), Some(max)) = (self.arc_min_mb, self.arc_max_mb) {
+            if min > max {
+                return Err(crate::error::AutoInstallError::ValidationError(format!(
+                    "zfs_tuning.arc_min_mb ({}) is larger than arc_max_mb ({})",
+                    min, max
+                )));
+            }
+        }
+        Ok(())
+    }
+
+    /// Choose parameters for a target with `memory_total_mb` of RAM
+    ///
+    /// Without a memory figure only explicitly configured values are used.
+    pub fn compute(&self, memory_total_mb: Option<u64>) -> crate::Result<ZfsTuning> {
+        self.validate()?;
+        let mut tuning = ZfsTuning {
+            memory_total_mb,
+            role: self.role,
+            parameters: Vec::new(),
+        };
+        if !self.enabled {
+            return Ok(tuning);
+        }
+
+        match (self.arc_max_mb, memory_total_mb) {
+            (Some(max), _) => tuning.push(
+                "zfs_arc_max",
+                max * MIB,
+                TuningSource::Config,
+                format!("{} MiB from config", max),
+            ),
+            (None, Some(memory)) => {
+                let (max, reason) = Self::computed_arc_max_mb(self.role, memory);
+                tuning.push("zfs_arc_max", max * MIB, TuningSource::Computed, reason);
+            }
+            (None, None) => {}
+        }
+        if let Some(min) = self.arc_min_mb {
+            tuning.push(
+                "zfs_arc_min",
+                min * MIB,
+                TuningSource::Config,
+                format!("{} MiB from config", min),
+            );
+        }
+        if let Some(memory) = memory_total_mb.filter(|m| *m < LOW_MEMORY_MB) {
+            if !self.parameters.contains_key("zfs_prefetch_disable") {
+                tuning.push(
+                    "zfs_prefetch_disable",
+                    1,
+                    TuningSource::Computed,
+                    format!("prefetch wastes ARC with only {} MiB of RAM", memory),
+                );
+            }
+        }
+        for (name, value) in &self.parameters {
+            tuning.parameters.retain(|p| &p.name != name);
+            tuning.parameters.push(ZfsParameter {
+                name: name.clone(),
+                value: value.clone(),
+                source: TuningSource::Config,
+                reason: "set in config".to_string(),
+            });
+        }
+        Ok(tuning)
+    }
+
+    /// ARC ceiling in MiB for `role` on a machine with `memory_mb` of RAM, with the rule used
+    fn computed_arc_max_mb(role: MachineRole, memory_mb: u64) -> (u64, String) {
+        let (max, rule) = match role {
+            MachineRole::Desktop => (memory_mb / 4, "a quarter of RAM for a desktop".to_string()),
+            MachineRole::Server if memory_mb < LOW_MEMORY_MB => (
+                memory_mb / 4,
+                "a quarter of RAM on a low-memory server".to_string(),
+            ),
+            MachineRole::Server => (
+                (memory_mb * 3 / 4)
+                    .min(memory_mb.saturating_sub(SERVER_RESERVED_MB))
+                    .max(memory_mb / 4),
+                format!(
+                    "three quarters of RAM for a server, leaving {} MiB for the system where possible",
+                    SERVER_RESERVED_MB
+                ),
+            ),
+        };
+        let max = max.max(MIN_ARC_MB);
+        (max, format!("{} MiB: {} of {} MiB", max, rule, memory_mb))
+    }
+}
+
+impl ZfsTuning {
+    fn push(&mut self, name: &str, value: u64, source: TuningSource, reason: String) {
+        self.parameters.push(ZfsParameter {
+            name: name.to_string(),
+            value: value.to_string(),
+            source,
+            reason,
+        });
+    }
+
+    /// Whether there is nothing to write
+    pub fn is_empty(&self) -> bool {
+        self.parameters.is_empty()
+    }
+
+    /// One-line description, e.g. `zfs_arc_max=8589934592 (computed)`
+    pub fn summary(&self) -> String {
+        self.parameters
+            .iter()
+            .map(|p| format!("{}={} ({})", p.name, p.value, p.source.as_str()))
+            .collect::<Vec<_>>()
+            .join(", ")
+    }
+
+    /// Contents of the modprobe.d file
+    pub fn modprobe_conf(&self) -> String {
+        if self.is_empty() {
+            return String::new();
+        }
+        let options: Vec<String> = self
+            .parameters
+            .iter()
+            .map(|p| format!("{}={}", p.name, p.value))
+            .collect();
+        format!("options zfs {}\n", options.join(" "))
+    }
+
+    /// Commands writing the modprobe.d file under `root` and rebuilding the initramfs
+    ///
+    /// `zfs` is loaded from the initramfs to import the root pool, so the options only take
+    /// effect once they are in the initramfs.
+    pub fn build_apply_commands(&self, root: &str) -> Vec<String> {
+        if self.is_empty() {
+            return Vec::new();
+        }
+        let root = root.trim_end_matches('/');
+        let full = format!("{}/{}", root, MODPROBE_FILE);
+        let dir = &full[..full.rfind('/').unwrap_or(0)];
+        vec![
+            format!(
+                "mkdir -p {} && cat > {} << 'EOF'\n# Managed by ubuntu-autoinstall-agent\n{}EOF",
+                dir,
+                full,
+                self.modprobe_conf()
+            ),
+            format!("chroot {} bash -lc 'update-initramfs -u -k all'", root),
+        ]
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::loader::parse_section;
+
+    fn value(tuning: &ZfsTuning, name: &str) -> Option<String> {
+        tuning
+            .parameters
+            .iter()
+            .find(|p| p.name == name)
+            .map(|p| p.value.clone())
+    }
+
+    #[test]
+    fn test_arc_max_follows_memory_and_role() {
+        let server = ZfsTuningConfig::default();
+        let tuning = server.compute(Some(32768)).unwrap();
+        assert_eq!(
+            value(&tuning, "zfs_arc_max"),
+            Some((24576 * MIB).to_string())
+        );
+        assert_eq!(value(&tuning, "zfs_prefetch_disable"), None);
+
+        // Three quarters of 8 GiB would eat into the system reserve
+        let tuning = server.compute(Some(8192)).unwrap();
+        assert_eq!(
+            value(&tuning, "zfs_arc_max"),
+            Some((4096 * MIB).to_string())
+        );
+        // The reserve never pushes the ARC below a quarter of RAM
+        let tuning = server.compute(Some(4096)).unwrap();
+        assert_eq!(
+            value(&tuning, "zfs_arc_max"),
+            Some((1024 * MIB).to_string())
+        );
+
+        let desktop = ZfsTuningConfig {
+            role: MachineRole::Desktop,
+            ..Default::default()
+        };
+        let tuning = desktop.compute(Some(16384)).unwrap();
+        assert_eq!(
+            value(&tuning, "zfs_arc_max"),
+            Some((4096 * MIB).to_string())
+        );
+
+        let tuning = server.compute(Some(768)).unwrap();
+        assert_eq!(value(&tuning, "zfs_arc_max"), Some((256 * MIB).to_string()));
+        assert_eq!(value(&tuning, "zfs_prefetch_disable").as_deref(), Some("1"));
+    }
+
+    #[test]
+    fn test_config_values_override_computed_ones() {
+        let mut config = ZfsTuningConfig {
+            arc_max_mb: Some(1024),
+            arc_min_mb: Some(512),
+            ..Default::default()
+        };
+        config
+            .parameters
+            .insert("zfs_prefetch_disable".to_string(), "0".to_string());
+        config
+            .parameters
+            .insert("zfs_txg_timeout".to_string(), "10".to_string());
+
+        let tuning = config.compute(Some(2048)).unwrap();
+        assert_eq!(
+            tuning.modprobe_conf(),
+            "options zfs zfs_arc_max=1073741824 zfs_arc_min=536870912 zfs_prefetch_disable=0 zfs_txg_timeout=10\n"
+        );
+        assert!(tuning
+            .parameters
+            .iter()
+            .all(|p| p.source == TuningSource::Config));
+
+        // Unknown memory: only explicit values
+        let tuning = ZfsTuningConfig::default().compute(None).unwrap();
+        assert!(tuning.is_empty());
+        assert!(tuning.build_apply_commands("/mnt/targetos").is_empty());
+
+        let disabled = ZfsTuningConfig {
+            enabled: false,
+            ..Default::default()
+        };
+        assert!(disabled.compute(Some(65536)).unwrap().is_empty());
+    }
+
+    #[test]
+    fn test_validate_rejects_bad_input() {
+        let mut config = ZfsTuningConfig {
+            arc_max_mb: Some(512),
+            arc_min_mb: Some(1024),
+            ..Default::default()
+        };
+        assert!(config.validate().is_err());
+        config.arc_min_mb = None;
+        config
+            .parameters
+            .insert("zfs_arc_max; reboot".to_string(), "1".to_string());
+        assert!(config.validate().is_err());
+    }
+
+    #[test]
+    fn test_apply_commands_write_modprobe_file() {
+        let tuning = ZfsTuningConfig::default().compute(Some(8192)).unwrap();
+        assert_eq!(
+            tuning.summary(),
+            format!("zfs_arc_max={} (computed)", 4096 * MIB)
+        );
+        let commands = tuning.build_apply_commands("/mnt/targetos/");
+        assert_eq!(commands.len(), 2);
+        assert!(commands[0].contains("cat > /mnt/targetos/etc/modprobe.d/60-autoinstall-zfs.conf"));
+        assert!(commands[0].contains("options zfs zfs_arc_max=4294967296\n"));
+        assert!(commands[1].contains("update-initramfs"));
+
+        let section: ZfsTuningConfig = parse_section(
+            "zfs_tuning:\n  role: desktop\n  arc_max_mb: 2048\n",
+            "zfs_tuning",
+        )
+        .unwrap();
+        assert_eq!(section.role, MachineRole::Desktop);
+        assert!(section.enabled);
+    }
+}
@@ -1,5 +1,5 @@
 // file: src/error.rs
-// version: 1.0.0
+// version: 1.3.0
 // guid: 57b83a63-07b6-4534-aa6c-51e8797254e0
 
 use thiserror::Error;
@@ -40,7 +40,17 @@ pub enum AutoInstallError {
     #[error("System error: {0}")]
     SystemError(String),
 
-    #[error("Process failed: {command} (exit code: {exit_code:?}): {stderr}")]
+    #[error("Operation cancelled: {0}")]
+    CancelledError(String),
+
+    #[error("Operation timed out: {0}")]
+    TimeoutError(String),
+
+    #[error(
+        "Process failed: {} (exit code: {exit_code:?}): {}",
+        crate::logging::redact::scrub(.command),
+        crate::logging::redact::scrub(.stderr)
+    )]
     ProcessError {
         command: String,
         exit_code: Option<i32>,
@@ -59,3 +69,27 @@ pub enum AutoInstallError {
     #[error("HTTP error: {0}")]
     HttpError(#[from] reqwest::Error),
 }
+
+impl AutoInstallError {
+    /// Kind of failure without its message, for metrics that must not carry host details
+    pub fn category(&self) -> &'static str {
+        match self {
+            AutoInstallError::VmError(_) => "vm",
+            AutoInstallError::DiskError(_) => "disk",
+            AutoInstallError::NetworkError(_) => "network",
+            AutoInstallError::LuksError(_) => "luks",
+            AutoInstallError::ConfigError(_) => "config",
+            AutoInstallError::ImageError(_) => "image",
+            AutoInstallError::SshError(_) => "ssh",
+            AutoInstallError::InstallationError(_) => "installation",
+            AutoInstallError::ValidationError(_) => "validation",
+            AutoInstallError::SystemError(_) => "system",
+            AutoInstallError::CancelledError(_) => "cancelled",
+            AutoInstallError::TimeoutError(_) => "timeout",
+            AutoInstallError::ProcessError { .. } => "process",
+            AutoInstallError::IoError(_) => "io",
+            AutoInstallError::SerdeError(_) | AutoInstallError::JsonError(_) => "parse",
+            AutoInstallError::HttpError(_) => "http",
+        }
+    }
+}
@@ -0,0 +1,236 @@
+// file: src/image/builder/cache.rs
+// version: 1.0.0
+// guid: 9e2b7d41-3a65-4c8f-b1d0-7f4e6a2c9b38
+
+//! Coordination of one image cache between several agent processes on the same host
+//!
+//! CI often runs a few builds side by side against `~/.cache/ubuntu-autoinstall`. Downloaded
+//! artifacts live in a shared store that is only written while holding an exclusive `flock`
+//! on `locks/<key>.lock`; a second process asking for the same ISO waits for the first and
+//! then reuses its download. Finished artifacts are listed in `manifest.json` with their
+//! digest and made read-only, so an artifact without a manifest entry is an interrupted one
+//! and gets rebuilt. Every process builds in its own `work/<pid>` directory.
+//!
+//! `flock` locks belong to the open file, so a crashed process never leaves a stale lock.
+
+use crate::security::provenance::BuilderIdentity;
+use crate::Result;
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::fs::File;
+use std::path::{Path, PathBuf};
+use std::time::Duration;
+use tracing::{debug, info, warn};
+
+/// Manifest of finished artifacts under the cache root
+pub const MANIFEST_FILE: &str = "manifest.json";
+
+/// How often a waiting process retries a held lock
+const LOCK_POLL: Duration = Duration::from_millis(500);
+
+/// One finished artifact in the shared store
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct CacheEntry {
+    pub path: PathBuf,
+    pub sha256: Option<String>,
+    pub completed_at: DateTime<Utc>,
+    /// `user@host` and pid of the process that produced it
+    pub producer: String,
+    pub pid: u32,
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct CacheManifest {
+    pub entries: BTreeMap<String, CacheEntry>,
+}
+
+/// Exclusive lock on one cache key, released when dropped
+#[derive(Debug)]
+pub struct CacheLock {
+    _file: File,
+}
+
+/// The shared artifact store under a cache root
+#[derive(Debug, Clone)]
+pub struct SharedCache {
+    root: PathBuf,
+}
+
+impl SharedCache {
+    pub fn new(root: PathBuf) -> Self {
+        Self { root }
+    }
+
+    /// Private work directory of process `pid`
+    pub fn work_dir(root: &Path, pid: u32) -> PathBuf {
+        root.join("work").join(pid.to_string())
+    }
+
+    /// Wait for the exclusive lock on `key`
+    pub async fn lock(&self, key: &str) -> Result<CacheLock> {
+        let path = self.root.join("locks").join(format!("{}.lock", key));
+        if let Some(parent) = path.parent() {
+            std::fs::create_dir_all(parent)?;
+        }
+        let file = std::fs::OpenOptions::new()
+            .create(true)
+            .truncate(false)
+            .write(true)
+            .open(&path)?;
+        let mut announced = false;
+        while !try_lock(&file)? {
+            if !announced {
+                info!("Waiting for another agent process to finish {}", key);
+                announced = true;
+            }
+            tokio::time::sleep(LOCK_POLL).await;
+        }
+        debug!("Locked cache key {}", key);
+        Ok(CacheLock { _file: file })
+    }
+
+    /// Whether any artifact was ever recorded; caches from older versions have no manifest
+    pub fn has_manifest(&self) -> bool {
+        self.root.join(MANIFEST_FILE).exists()
+    }
+
+    /// Manifest as last written; missing or unreadable means empty
+    pub fn manifest(&self) -> CacheManifest {
+        std::fs::read_to_string(self.root.join(MANIFEST_FILE))
+            .ok()
+            .and_then(|content| serde_json::from_str(&content).ok())
+            .unwrap_or_default()
+    }
+
+    /// The finished artifact for `key`, if it is recorded and still on disk
+    pub fn lookup(&self, key: &str) -> Option<CacheEntry> {
+        self.manifest()
+            .entries
+            .remove(key)
+            .filter(|entry| entry.path.exists())
+    }
+
+    /// Record `path` as the finished artifact for `key` and make it read-only
+    pub async fn record(&self, key: &str, path: &Path, sha256: Option<String>) -> Result<()> {
+        if let Err(e) = make_read_only(path) {
+            warn!("Could not make {} read-only: {}", path.display(), e);
+        }
+        let _lock = self.lock("manifest").await?;
+        let mut manifest = self.manifest();
+        manifest.entries.insert(
+            key.to_string(),
+            CacheEntry {
+                path: path.to_path_buf(),
+                sha256,
+                completed_at: Utc::now(),
+                producer: BuilderIdentity::current().id,
+                pid: std::process::id(),
+            },
+        );
+        // Readers never lock the manifest, so it is replaced in one rename
+        let target = self.root.join(MANIFEST_FILE);
+        let staged = self
+            .root
+            .join(format!("{}.{}", MANIFEST_FILE, std::process::id()));
+        std::fs::write(&staged, serde_json::to_string_pretty(&manifest)?)?;
+        std::fs::rename(&staged, &target)?;
+        Ok(())
+    }
+
+    /// Remove work directories of processes that no longer exist; returns how many
+    pub fn sweep_stale_work_dirs(&self) -> usize {
+        if !Path::new("/proc").is_dir() {
+            return 0;
+        }
+        let Ok(entries) = std::fs::read_dir(self.root.join("work")) else {
+            return 0;
+        };
+        let mut removed = 0;
+        for entry in entries.flatten() {
+            let name = entry.file_name().to_string_lossy().to_string();
+            let Ok(pid) = name.parse::<u32>() else {
+                continue;
+            };
+            if Path::new(&format!("/proc/{}", pid)).exists() {
+                continue;
+            }
+            match std::fs::remove_dir_all(entry.path()) {
+                Ok(()) => removed += 1,
+                Err(e) => warn!("Could not remove stale work directory {}: {}", name, e),
+            }
+        }
+        removed
+    }
+}
+
+/// Take an exclusive `flock` without blocking; `false` while another process holds it
+#[cfg(unix)]
+fn try_lock(file: &File) -> Result<bool> {
+    use std::os::fd::AsRawFd;
+    // SAFETY: the fd stays open for the duration of the call
+    let rc = unsafe { libc::flock(file.as_raw_fd(), libc::LOCK_EX | libc::LOCK_NB) };
+    if rc == 0 {
+        return Ok(true);
+    }
+    let error = std::io::Error::last_os_error();
+    if error.kind() == std::io::ErrorKind::WouldBlock {
+        Ok(false)
+    } else {
+        Err(error.into())
+    }
+}
+
+#[cfg(not(unix))]
+fn try_lock(_file: &File) -> Result<bool> {
+    Ok(true)
+}
+
+/// Clear the write bits of `path` and, for a directory, of every file below it
+fn make_read_only(path: &Path) -> std::io::Result<()> {
+    let metadata = std::fs::metadata(path)?;
+    if metadata.is_dir() {
+        for entry in std::fs::read_dir(path)? {
+            make_read_only(&entry?.path())?;
+        }
+        return Ok(());
+    }
+    let mut permissions = metadata.permissions();
+    permissions.set_readonly(true);
+    std::fs::set_permissions(path, permissions)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn test_lock_excludes_and_manifest_records() {
+        let dir = tempfile::TempDir::new().unwrap();
+        let cache = SharedCache::new(dir.path().to_path_buf());
+
+        let held = cache.lock("ubuntu-24.04-amd64").await.unwrap();
+        let second = std::fs::OpenOptions::new()
+            .write(true)
+            .open(dir.path().join("locks/ubuntu-24.04-amd64.lock"))
+            .unwrap();
+        assert!(!try_lock(&second).unwrap());
+        drop(held);
+        assert!(try_lock(&second).unwrap());
+
+        let iso = dir.path().join("ubuntu.iso");
+        std::fs::write(&iso, b"iso").unwrap();
+        assert!(cache.lookup("iso").is_none());
+        cache
+            .record("iso", &iso, Some("abc".to_string()))
+            .await
+            .unwrap();
+        let entry = cache.lookup("iso").unwrap();
+        assert_eq!(entry.sha256.as_deref(), Some("abc"));
+        assert_eq!(entry.pid, std::process::id());
+        assert!(std::fs::metadata(&iso).unwrap().permissions().readonly());
+
+        std::fs::remove_file(&iso).unwrap();
+        assert!(cache.lookup("iso").is_none());
+    }
+}
@@ -0,0 +1,254 @@
+// file: src/image/builder/capture.rs
+// version: 1.0.0
+// guid: 8c2f5a1e-3d94-4b67-a0e8-6f1b9d7c4e25
+
+//! Capture a golden image from an existing reference machine
+//!
+//! The root filesystem of the running machine is archived over SSH with machine-specific
+//! data (machine-id, SSH host keys, logs, shell history) left out, then turned into a
+//! qcow2 disk locally and finalized like a freshly built image.
+
+use crate::config::Architecture;
+use crate::network::SshClient;
+use crate::Result;
+use std::path::{Path, PathBuf};
+use tokio::process::Command;
+use tracing::info;
+
+/// Archive path on the reference machine while capturing
+pub const REMOTE_ARCHIVE: &str = "/var/tmp/uaa-capture.tar.gz";
+
+/// Paths never captured: pseudo filesystems, mounts and scratch space
+const SYSTEM_EXCLUDES: &[&str] = &[
+    "/proc",
+    "/sys",
+    "/dev",
+    "/run",
+    "/tmp",
+    "/var/tmp",
+    "/mnt",
+    "/media",
+    "/lost+found",
+    "/swap.img",
+    "/swapfile",
+];
+
+/// Machine-specific files removed so every deployed copy gets its own identity
+const GENERALIZE_EXCLUDES: &[&str] = &[
+    "/etc/machine-id",
+    "/var/lib/dbus/machine-id",
+    "/etc/ssh/ssh_host_*",
+    "/var/log/*",
+    "/root/.bash_history",
+    "/home/*/.bash_history",
+    "/var/cache/apt/archives/*.deb",
+    "/var/lib/cloud/instances",
+    "/etc/netplan/50-cloud-init.yaml",
+];
+
+/// Package manifest query, one `name<TAB>version<TAB>arch` line per installed package
+pub const PACKAGE_MANIFEST_COMMAND: &str =
+    "dpkg-query -W -f='${Package}\\t${Version}\\t${Architecture}\\n'";
+
+/// Options for capturing a reference machine
+#[derive(Debug, Clone, Default)]
+pub struct CaptureOptions {
+    /// Additional paths (or globs) to leave out of the image
+    pub excludes: Vec<String>,
+    /// Free space added on top of the captured data, in GiB
+    pub extra_space_gb: u32,
+}
+
+/// Build the tar command that archives the reference machine's root filesystem
+pub fn build_archive_command(extra_excludes: &[String]) -> String {
+    let excludes: Vec<String> = SYSTEM_EXCLUDES
+        .iter()
+        .chain(GENERALIZE_EXCLUDES)
+        .map(|p| p.to_string())
+        .chain(extra_excludes.iter().cloned())
+        .chain(std::iter::once(REMOTE_ARCHIVE.to_string()))
+        .map(|p| format!("--exclude='.{}'", p))
+        .collect();
+    format!(
+        "tar --numeric-owner --xattrs --acls --one-file-system -czpf {} {} -C / .",
+        REMOTE_ARCHIVE,
+        excludes.join(" ")
+    )
+}
+
+/// Arguments for `virt-make-fs` turning the archive into a partitioned qcow2 disk
+pub fn build_make_fs_args(archive: &Path, disk: &Path, extra_space_gb: u32) -> Vec<String> {
+    vec![
+        "--format=qcow2".to_string(),
+        "--type=ext4".to_string(),
+        "--partition=gpt".to_string(),
+        format!("--size=+{}G", extra_space_gb.max(1)),
+        archive.display().to_string(),
+        disk.display().to_string(),
+    ]
+}
+
+/// Parse `VERSION_ID` from `/etc/os-release`
+pub fn parse_version_id(os_release: &str) -> Option<String> {
+    os_release.lines().find_map(|line| {
+        line.strip_prefix("VERSION_ID=")
+            .map(|v| v.trim().trim_matches('"').to_string())
+    })
+}
+
+/// What was learned about the reference machine during capture
+#[derive(Debug, Clone)]
+pub struct CapturedSystem {
+    pub ubuntu_version: String,
+    pub architecture: Architecture,
+    /// Output of [`PACKAGE_MANIFEST_COMMAND`]
+    pub package_manifest: String,
+    /// Local copy of the root filesystem archive
+    pub archive: PathBuf,
+}
+
+/// Captures a reference machine over an existing SSH session
+pub struct CaptureManager<'a> {
+    ssh: &'a mut SshClient,
+    work_dir: PathBuf,
+}
+
+impl<'a> CaptureManager<'a> {
+    pub fn new(ssh: &'a mut SshClient, work_dir: PathBuf) -> Self {
+        Self { ssh, work_dir }
+    }
+
+    async fn log_and_execute(&mut self, description: &str, cmd: &str) -> Result<()> {
+        info!("Executing: {} -> {}", description, cmd);
+        self.ssh.execute(cmd).await
+    }
+
+    /// Record the release, architecture and package manifest, then archive and fetch the root filesystem
+    pub async fn capture(&mut self, options: &CaptureOptions) -> Result<CapturedSystem> {
+        let os_release = self.ssh.execute_with_output("cat /etc/os-release").await?;
+        let ubuntu_version = parse_version_id(&os_release).ok_or_else(|| {
+            crate::error::AutoInstallError::ImageError(
+                "Reference machine is not Ubuntu (no VERSION_ID in /etc/os-release)".to_string(),
+            )
+        })?;
+        let architecture: Architecture = self
+            .ssh
+            .execute_with_output("dpkg --print-architecture")
+            .await?
+            .trim()
+            .parse()?;
+        let package_manifest = self
+            .ssh
+            .execute_with_output(PACKAGE_MANIFEST_COMMAND)
+            .await?;
+        info!(
+            "Reference machine runs Ubuntu {} ({}) with {} packages",
+            ubuntu_version,
+            architecture.as_str(),
+            package_manifest.lines().count()
+        );
+
+        self.log_and_execute(
+            "Archive root filesystem",
+            &build_archive_command(&options.excludes),
+        )
+        .await?;
+        let archive = self.work_dir.join("rootfs.tar.gz");
+        let download = self
+            .ssh
+            .download_file(REMOTE_ARCHIVE, &archive.display().to_string())
+            .await;
+        self.log_and_execute(
+            "Remove remote archive",
+            &format!("rm -f {}", REMOTE_ARCHIVE),
+        )
+        .await?;
+        download?;
+
+        Ok(CapturedSystem {
+            ubuntu_version,
+            architecture,
+            package_manifest,
+            archive,
+        })
+    }
+}
+
+/// Build a qcow2 disk at `disk` from a captured root filesystem archive
+pub async fn make_disk(archive: &Path, disk: &Path, extra_space_gb: u32) -> Result<()> {
+    info!(
+        "Building disk {} from {}",
+        disk.display(),
+        archive.display()
+    );
+    let output = Command::new("virt-make-fs")
+        .args(build_make_fs_args(archive, disk, extra_space_gb))
+        .output()
+        .await
+        .map_err(|e| {
+            crate::error::AutoInstallError::ImageError(format!("Failed to run virt-make-fs: {}", e))
+        })?;
+    if !output.status.success() {
+        return Err(crate::error::AutoInstallError::ImageError(format!(
+            "virt-make-fs failed: {}",
+            String::from_utf8_lossy(&output.stderr)
+        )));
+    }
+
+    // systemd regenerates an empty machine-id on first boot
+    let output = Command::new("guestfish")
+        .args(["-a", &disk.display().to_string(), "-m", "/dev/sda1"])
+        .args(["touch", "/etc/machine-id"])
+        .output()
+        .await
+        .map_err(|e| {
+            crate::error::AutoInstallError::ImageError(format!("Failed to start guestfish: {}", e))
+        })?;
+    if !output.status.success() {
+        return Err(crate::error::AutoInstallError::ImageError(format!(
+            "Failed to reset machine-id: {}",
+            String::from_utf8_lossy(&output.stderr)
+        )));
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_archive_command_excludes_identity_and_itself() {
+        let cmd = build_archive_command(&["/srv/scratch".to_string()]);
+        assert!(cmd.starts_with("tar --numeric-owner --xattrs --acls --one-file-system -czpf /var/tmp/uaa-capture.tar.gz"));
+        assert!(cmd.contains("--exclude='./etc/ssh/ssh_host_*'"));
+        assert!(cmd.contains("--exclude='./etc/machine-id'"));
+        assert!(cmd.contains("--exclude='./proc'"));
+        assert!(cmd.contains("--exclude='./srv/scratch'"));
+        assert!(cmd.contains("--exclude='./var/tmp/uaa-capture.tar.gz'"));
+        assert!(cmd.ends_with("-C / ."));
+    }
+
+    #[test]
+    fn test_make_fs_args() {
+        let args = build_make_fs_args(Path::new("/w/rootfs.tar.gz"), Path::new("/w/disk.qcow2"), 0);
+        assert_eq!(
+            args,
+            vec![
+                "--format=qcow2",
+                "--type=ext4",
+                "--partition=gpt",
+                "--size=+1G",
+                "/w/rootfs.tar.gz",
+                "/w/disk.qcow2"
+            ]
+        );
+    }
+
+    #[test]
+    fn test_parse_version_id() {
+        let os_release = "NAME=\"Ubuntu\"\nVERSION_ID=\"24.04\"\nID=ubuntu\n";
+        assert_eq!(parse_version_id(os_release).as_deref(), Some("24.04"));
+        assert_eq!(parse_version_id("ID=debian\n"), None);
+    }
+}
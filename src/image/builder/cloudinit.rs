@@ -1,24 +1,44 @@
 // file: src/image/builder/cloudinit.rs
-// version: 1.1.0
+// version: 1.5.0
 // guid: c1c2c3c4-d5d6-7890-1234-567890cdefgh
 
 //! Cloud-init configuration generation
 
 use crate::config::ImageSpec;
+use crate::utils::guest_agent::INSTALL_STATUS_FILE;
 use crate::Result;
 use std::path::PathBuf;
 use tokio::fs;
 use tracing::debug;
 
+/// DHCP on the wired port of an SD-card image, like Ubuntu's own Raspberry Pi images
+const SBC_NETWORK_CONFIG: &str =
+    "version: 2\nethernets:\n  eth0:\n    dhcp4: true\n    optional: true\n";
+
+/// Placeholder key of builds without provisioner hooks; removed again by a late-command
+const TEMP_AUTHORIZED_KEY: &str =
+    "ssh-rsa AAAAB3NzaC1yc2EAAAADAQABAAABgQDHQGvTZ8nZ8/temp-key-for-image-creation";
+
 /// Cloud-init configuration manager
 pub struct CloudInitManager {
     work_dir: PathBuf,
+    build_key: Option<String>,
 }
 
 impl CloudInitManager {
     /// Create a new cloud-init manager
     pub fn new(work_dir: PathBuf) -> Self {
-        Self { work_dir }
+        Self {
+            work_dir,
+            build_key: None,
+        }
+    }
+
+    /// Authorize `public_key` for the ubuntu user and keep it after the install, so
+    /// provisioner hooks can log in; generalization removes it
+    pub fn with_build_key(mut self, public_key: String) -> Self {
+        self.build_key = Some(public_key);
+        self
     }
 
     /// Create cloud-init configuration for automated installation
@@ -40,9 +60,65 @@ impl CloudInitManager {
         Ok(cloud_init_dir)
     }
 
+    /// Create the NoCloud seed an SD-card image configures itself from on first boot
+    ///
+    /// The files are copied onto the image's `system-boot` partition, where cloud-init finds
+    /// them by label, so they can still be edited on the card before it is first booted.
+    pub async fn create_sbc_seed(&self, spec: &ImageSpec) -> Result<PathBuf> {
+        let seed_dir = self.work_dir.join("sbc-seed");
+        fs::create_dir_all(&seed_dir).await?;
+
+        fs::write(
+            seed_dir.join("user-data"),
+            self.generate_sbc_user_data(spec)?,
+        )
+        .await?;
+        let meta_data = format!("instance-id: ubuntu-autoinstall-{}\n", uuid::Uuid::new_v4());
+        fs::write(seed_dir.join("meta-data"), meta_data).await?;
+        fs::write(seed_dir.join("network-config"), SBC_NETWORK_CONFIG).await?;
+
+        debug!("Created SBC cloud-init seed in: {}", seed_dir.display());
+        Ok(seed_dir)
+    }
+
+    /// First-boot user-data of an SD-card image: the same user and packages as the VM
+    /// install, without the autoinstall wrapper and the GRUB settings
+    fn generate_sbc_user_data(&self, spec: &ImageSpec) -> Result<String> {
+        let packages = spec.resolved_packages()?.join("\n  - ");
+        Ok(format!(
+            r#"#cloud-config
+hostname: ubuntu-autoinstall
+locale: en_US.UTF-8
+timezone: UTC
+ssh_pwauth: false
+users:
+  - name: ubuntu
+    gecos: Ubuntu User
+    groups: [adm, sudo, video, dialout]
+    shell: /bin/bash
+    sudo: ALL=(ALL) NOPASSWD:ALL
+    lock_passwd: true
+package_update: true
+packages:
+  - {}
+growpart:
+  mode: auto
+  devices: ["/"]
+runcmd:
+  - echo "Image first boot completed at $(date)" > /var/log/autoinstall.log
+"#,
+            packages
+        ))
+    }
+
     /// Generate cloud-init user-data for automated installation
     fn generate_user_data(&self, spec: &ImageSpec) -> Result<String> {
-        let packages = spec.base_packages.join("\n    - ");
+        let mut packages = spec.resolved_packages()?;
+        // Keep the agent in the image so VMs built from it can be managed the same way
+        if !packages.iter().any(|p| p == "qemu-guest-agent") {
+            packages.push("qemu-guest-agent".to_string());
+        }
+        let packages = packages.join("\n    - ");
 
         // Generate a password hash for the ubuntu user (password: 'ubuntu')
         // In production, this should be configurable or use key-based auth only
@@ -72,7 +148,7 @@ autoinstall:
     install-server: true
     allow-pw: false
     authorized-keys:
-      - ssh-rsa AAAAB3NzaC1yc2EAAAADAQABAAABgQDHQGvTZ8nZ8/temp-key-for-image-creation
+      - {authorized_key}
   identity:
     realname: Ubuntu User
     username: ubuntu
@@ -83,6 +159,10 @@ autoinstall:
   timezone: UTC
   updates: security
   shutdown: reboot
+  early-commands:
+    # Let the build host follow the install through the QEMU guest agent
+    - echo running > {status_file}
+    - systemctl start qemu-guest-agent || (apt-get update && apt-get install -y qemu-guest-agent && systemctl start qemu-guest-agent) || true
   late-commands:
     # Configure GRUB for serial console
     - echo 'GRUB_TERMINAL="console serial"' >> /target/etc/default/grub
@@ -91,16 +171,28 @@ autoinstall:
     # Configure sudoers for passwordless sudo
     - echo 'ubuntu ALL=(ALL) NOPASSWD:ALL' >> /target/etc/sudoers.d/ubuntu-nopasswd
     - chmod 440 /target/etc/sudoers.d/ubuntu-nopasswd
-    # Remove temporary SSH key - it will be replaced during VM provisioning
-    - rm -f /target/home/ubuntu/.ssh/authorized_keys
+{key_cleanup}
     - echo "Image creation completed at $(date)" > /target/var/log/autoinstall.log
     # Update GRUB configuration
     - chroot /target update-grub
+    # Tell the build host the install is finished; the delay keeps the installer from
+    # rebooting before the host has seen the marker and stopped the VM
+    - echo done > {status_file}
+    - sleep 60
   error-commands:
+    - echo failed > {status_file}
     - echo "Installation failed at $(date)" > /target/var/log/autoinstall-error.log
     - journalctl -b > /target/var/log/autoinstall-journal.log
 "#,
-            packages, password_hash
+            packages,
+            password_hash,
+            status_file = INSTALL_STATUS_FILE,
+            authorized_key = self.build_key.as_deref().unwrap_or(TEMP_AUTHORIZED_KEY),
+            key_cleanup = if self.build_key.is_some() {
+                "    # Build key stays for the provisioner hooks; generalization removes it"
+            } else {
+                "    # Remove temporary SSH key - it will be replaced during VM provisioning\n    - rm -f /target/home/ubuntu/.ssh/authorized_keys"
+            },
         );
 
         Ok(config)
@@ -126,8 +218,12 @@ mod tests {
                 memory_mb: 2048,
                 disk_size_gb: 20,
                 cpu_cores: 2,
+                machine: None,
+                firmware: None,
             },
             custom_scripts: vec![],
+            flavor: Default::default(),
+            hooks: Default::default(),
         }
     }
 
@@ -185,6 +281,23 @@ mod tests {
         assert!(user_data_content.contains("locale: en_US.UTF-8"));
     }
 
+    #[test]
+    fn test_build_key_is_kept_for_hooks() {
+        let manager = CloudInitManager::new(PathBuf::from("/tmp/work"));
+        let user_data = manager
+            .generate_user_data(&create_test_image_spec())
+            .unwrap();
+        assert!(user_data.contains("rm -f /target/home/ubuntu/.ssh/authorized_keys"));
+
+        let manager = manager.with_build_key("ssh-ed25519 AAAAC3Nz uaa-build".to_string());
+        let user_data = manager
+            .generate_user_data(&create_test_image_spec())
+            .unwrap();
+        assert!(user_data.contains("      - ssh-ed25519 AAAAC3Nz uaa-build\n"));
+        assert!(!user_data.contains("rm -f /target/home/ubuntu/.ssh/authorized_keys"));
+        assert!(!user_data.contains("temp-key-for-image-creation"));
+    }
+
     #[tokio::test]
     async fn test_meta_data_content() {
         // Arrange
@@ -299,4 +412,48 @@ mod tests {
         assert!(user_data.contains("autoinstall.log"));
         assert!(user_data.contains("autoinstall-error.log"));
     }
+
+    #[tokio::test]
+    async fn test_create_sbc_seed() {
+        // Arrange
+        let temp_dir = TempDir::new().unwrap();
+        let manager = CloudInitManager::new(temp_dir.path().to_path_buf());
+        let spec = create_test_image_spec();
+
+        // Act
+        let seed_dir = manager.create_sbc_seed(&spec).await.unwrap();
+        let user_data = fs::read_to_string(seed_dir.join("user-data"))
+            .await
+            .unwrap();
+
+        // Assert
+        assert!(seed_dir.join("meta-data").exists());
+        assert!(seed_dir.join("network-config").exists());
+        assert!(user_data.starts_with("#cloud-config\n"));
+        assert!(user_data.contains("  - openssh-server\n"));
+        assert!(!user_data.contains("autoinstall:"));
+        assert!(!user_data.contains("grub"));
+    }
+
+    #[test]
+    fn test_generate_user_data_reports_status_to_guest_agent() {
+        // Arrange
+        let temp_dir = TempDir::new().unwrap();
+        let manager = CloudInitManager::new(temp_dir.path().to_path_buf());
+        let spec = create_test_image_spec();
+
+        // Act
+        let user_data = manager.generate_user_data(&spec).unwrap();
+
+        // Assert
+        assert!(user_data.contains("early-commands:"));
+        assert!(user_data.contains("systemctl start qemu-guest-agent"));
+        assert!(user_data.contains("    - qemu-guest-agent\n"));
+        assert!(user_data.contains(&format!("echo done > {}", INSTALL_STATUS_FILE)));
+        assert!(user_data.contains(&format!("echo failed > {}", INSTALL_STATUS_FILE)));
+        let late = user_data.find("late-commands:").unwrap();
+        let errors = user_data.find("error-commands:").unwrap();
+        let done = user_data.find("echo done").unwrap();
+        assert!(late < done && done < errors);
+    }
 }
@@ -0,0 +1,330 @@
+// file: src/image/builder/environment.rs
+// version: 1.0.0
+// guid: 7a1d5e38-2c94-4b6f-9e07-4f8b3a6d2c19
+
+//! Build environment lockfiles for reproducibility audits
+//!
+//! Every image build writes `<image>.buildenv.yaml` next to the image: the agent version, host
+//! kernel, QEMU binary and version, the UEFI firmware with its digest and package version, the
+//! spec checksum and the command line. `create-image --reproduce <lockfile>` captures the same
+//! facts before building and refuses to start when a material one differs. The command line,
+//! builder and kernel patch level are recorded for the audit trail but never block a build.
+
+use crate::config::ImageSpec;
+use crate::error::AutoInstallError;
+use crate::security::provenance::{self, BuilderIdentity};
+use crate::utils::qemu_profile::QemuMachine;
+use crate::Result;
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use std::path::{Path, PathBuf};
+use tokio::process::Command;
+
+/// Suffix of the lockfile written next to an image
+pub const BUILD_ENV_SUFFIX: &str = ".buildenv.yaml";
+
+/// Everything about the build host and inputs that can change the image
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct BuildEnvironment {
+    pub captured_at: DateTime<Utc>,
+    pub builder: BuilderIdentity,
+    /// Host kernel release, e.g. `6.8.0-45-generic`
+    pub host_kernel: String,
+    pub host_arch: String,
+    pub ubuntu_version: String,
+    pub architecture: String,
+    /// SHA-256 of the effective spec after command-line overrides
+    pub spec_sha256: String,
+    pub qemu_binary: Option<String>,
+    /// First line of `qemu-system-* --version`
+    pub qemu_version: Option<String>,
+    pub firmware: Option<PathBuf>,
+    pub firmware_sha256: Option<String>,
+    /// Version of the package owning the firmware file
+    pub firmware_package: Option<String>,
+    /// Arguments of the build, without `--reproduce`
+    pub command_line: Vec<String>,
+}
+
+/// One fact that differs between a lockfile and the current host
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct EnvironmentDifference {
+    pub field: &'static str,
+    pub locked: String,
+    pub current: String,
+    /// Whether the difference blocks a reproduction
+    pub material: bool,
+}
+
+impl BuildEnvironment {
+    /// Capture the environment building `spec` on this host
+    pub async fn capture(spec: &ImageSpec) -> Result<Self> {
+        let profile = spec.vm_config.machine_profile(spec.architecture);
+        let machine = QemuMachine::resolve(&spec.vm_config, profile).ok();
+        let qemu_binary = machine.as_ref().map(|m| m.qemu_binary().to_string());
+        let qemu_version = match &qemu_binary {
+            Some(binary) => first_line_of(binary, &["--version"]).await,
+            None => None,
+        };
+        let firmware = machine.and_then(|m| m.firmware).map(|f| f.code);
+        let firmware_sha256 = firmware
+            .as_deref()
+            .and_then(|path| provenance::sha256_file(path).ok());
+        let firmware_package = match &firmware {
+            Some(path) => firmware_package_version(path).await,
+            None => None,
+        };
+        Ok(Self {
+            captured_at: Utc::now(),
+            builder: BuilderIdentity::current(),
+            host_kernel: std::fs::read_to_string("/proc/sys/kernel/osrelease")
+                .map(|r| r.trim().to_string())
+                .unwrap_or_else(|_| "unknown".to_string()),
+            host_arch: std::env::consts::ARCH.to_string(),
+            ubuntu_version: spec.ubuntu_version.clone(),
+            architecture: spec.architecture.as_str().to_string(),
+            spec_sha256: format!(
+                "{:x}",
+                Sha256::digest(serde_yaml::to_string(spec)?.as_bytes())
+            ),
+            qemu_binary,
+            qemu_version,
+            firmware,
+            firmware_sha256,
+            firmware_package,
+            command_line: command_line(std::env::args()),
+        })
+    }
+
+    /// Lockfile path for an image file
+    pub fn path_for(image: &Path) -> PathBuf {
+        PathBuf::from(format!("{}{}", image.display(), BUILD_ENV_SUFFIX))
+    }
+
+    pub fn load(path: &Path) -> Result<Self> {
+        let content = std::fs::read_to_string(path).map_err(|e| {
+            AutoInstallError::ConfigError(format!(
+                "Cannot read build lockfile {}: {}",
+                path.display(),
+                e
+            ))
+        })?;
+        Ok(serde_yaml::from_str(&content)?)
+    }
+
+    pub fn save(&self, path: &Path) -> Result<()> {
+        std::fs::write(path, serde_yaml::to_string(self)?)?;
+        Ok(())
+    }
+
+    /// Facts of `current` that differ from this locked environment
+    pub fn differences(&self, current: &Self) -> Vec<EnvironmentDifference> {
+        let show = |value: &Option<String>| value.clone().unwrap_or_else(|| "none".to_string());
+        let path = |value: &Option<PathBuf>| {
+            value
+                .as_ref()
+                .map(|p| p.display().to_string())
+                .unwrap_or_else(|| "none".to_string())
+        };
+        let facts = [
+            (
+                "agent version",
+                self.builder.version.clone(),
+                current.builder.version.clone(),
+                true,
+            ),
+            (
+                "host architecture",
+                self.host_arch.clone(),
+                current.host_arch.clone(),
+                true,
+            ),
+            (
+                "host kernel series",
+                kernel_series(&self.host_kernel),
+                kernel_series(&current.host_kernel),
+                true,
+            ),
+            (
+                "host kernel",
+                self.host_kernel.clone(),
+                current.host_kernel.clone(),
+                false,
+            ),
+            (
+                "Ubuntu version",
+                self.ubuntu_version.clone(),
+                current.ubuntu_version.clone(),
+                true,
+            ),
+            (
+                "architecture",
+                self.architecture.clone(),
+                current.architecture.clone(),
+                true,
+            ),
+            (
+                "spec checksum",
+                self.spec_sha256.clone(),
+                current.spec_sha256.clone(),
+                true,
+            ),
+            (
+                "QEMU version",
+                show(&self.qemu_version),
+                show(&current.qemu_version),
+                true,
+            ),
+            (
+                "firmware checksum",
+                show(&self.firmware_sha256),
+                show(&current.firmware_sha256),
+                true,
+            ),
+            (
+                "firmware path",
+                path(&self.firmware),
+                path(&current.firmware),
+                false,
+            ),
+            (
+                "firmware package",
+                show(&self.firmware_package),
+                show(&current.firmware_package),
+                false,
+            ),
+            (
+                "command line",
+                self.command_line.join(" "),
+                current.command_line.join(" "),
+                false,
+            ),
+        ];
+        facts
+            .into_iter()
+            .filter(|(_, locked, current, _)| locked != current)
+            .map(|(field, locked, current, material)| EnvironmentDifference {
+                field,
+                locked,
+                current,
+                material,
+            })
+            .collect()
+    }
+
+    /// `Err` listing the material differences from `current`, if there are any
+    pub fn require_reproducible(&self, current: &Self) -> Result<()> {
+        let material: Vec<String> = self
+            .differences(current)
+            .into_iter()
+            .filter(|d| d.material)
+            .map(|d| format!("{}: locked {}, found {}", d.field, d.locked, d.current))
+            .collect();
+        if material.is_empty() {
+            return Ok(());
+        }
+        Err(AutoInstallError::ValidationError(format!(
+            "Build environment differs from the lockfile: {}",
+            material.join("; ")
+        )))
+    }
+}
+
+/// `major.minor` of a kernel release; patch-level updates do not change a build
+fn kernel_series(release: &str) -> String {
+    release.split('.').take(2).collect::<Vec<_>>().join(".")
+}
+
+/// Process arguments without the program name and any `--reproduce` option
+fn command_line(args: impl Iterator<Item = String>) -> Vec<String> {
+    let mut kept = Vec::new();
+    let mut skip_value = false;
+    for arg in args.skip(1) {
+        if skip_value {
+            skip_value = false;
+        } else if arg == "--reproduce" {
+            skip_value = true;
+        } else if !arg.starts_with("--reproduce=") {
+            kept.push(arg);
+        }
+    }
+    kept
+}
+
+async fn first_line_of(program: &str, args: &[&str]) -> Option<String> {
+    let output = Command::new(program).args(args).output().await.ok()?;
+    if !output.status.success() {
+        return None;
+    }
+    String::from_utf8_lossy(&output.stdout)
+        .lines()
+        .next()
+        .map(|line| line.trim().to_string())
+}
+
+/// `package version` of the dpkg or rpm package owning `path`
+async fn firmware_package_version(path: &Path) -> Option<String> {
+    let path = path.to_string_lossy();
+    if let Some(owner) = first_line_of("dpkg-query", &["-S", &path]).await {
+        let package = owner.split(':').next()?.trim().to_string();
+        let version = first_line_of("dpkg-query", &["-W", "-f=${Version}", &package]).await?;
+        return Some(format!("{} {}", package, version));
+    }
+    first_line_of("rpm", &["-qf", &path]).await
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_only_material_differences_block_reproduction() {
+        let locked = BuildEnvironment {
+            captured_at: Utc::now(),
+            builder: BuilderIdentity::current(),
+            host_kernel: "6.8.0-45-generic".to_string(),
+            host_arch: "x86_64".to_string(),
+            ubuntu_version: "24.04".to_string(),
+            architecture: "amd64".to_string(),
+            spec_sha256: "abc".to_string(),
+            qemu_binary: Some("qemu-system-x86_64".to_string()),
+            qemu_version: Some("QEMU emulator version 8.2.2".to_string()),
+            firmware: Some(PathBuf::from("/usr/share/OVMF/OVMF_CODE_4M.fd")),
+            firmware_sha256: Some("f00".to_string()),
+            firmware_package: Some("ovmf 2024.02-2".to_string()),
+            command_line: command_line(
+                [
+                    "agent",
+                    "create-image",
+                    "--reproduce",
+                    "old.yaml",
+                    "-s",
+                    "web.yaml",
+                ]
+                .into_iter()
+                .map(String::from),
+            ),
+        };
+        assert_eq!(locked.command_line, ["create-image", "-s", "web.yaml"]);
+
+        let mut current = locked.clone();
+        current.host_kernel = "6.8.0-47-generic".to_string();
+        current.command_line.push("--allow-tcg".to_string());
+        assert_eq!(locked.differences(&current).len(), 2);
+        assert!(locked.require_reproducible(&current).is_ok());
+
+        current.qemu_version = Some("QEMU emulator version 9.0.0".to_string());
+        let err = locked
+            .require_reproducible(&current)
+            .unwrap_err()
+            .to_string();
+        assert!(err.contains("QEMU version: locked QEMU emulator version 8.2.2"));
+
+        let dir = tempfile::TempDir::new().unwrap();
+        let path = BuildEnvironment::path_for(&dir.path().join("web.qcow2"));
+        assert!(path.ends_with("web.qcow2.buildenv.yaml"));
+        locked.save(&path).unwrap();
+        assert_eq!(BuildEnvironment::load(&path).unwrap(), locked);
+    }
+}
@@ -0,0 +1,291 @@
+// file: src/image/builder/hooks.rs
+// version: 1.0.0
+// guid: 8e4b2d71-5a96-4c3f-b0e8-1d7a9c6f3e25
+
+//! Runs the provisioner hooks of an image build
+//!
+//! `shell-local` provisioners run through `sh -c` on the build host. `shell` provisioners are
+//! copied into the booted build VM with `scp` and started over `ssh` as the `ubuntu` user,
+//! authenticating with a key generated for the build. The VM's SSH port is forwarded to a free
+//! port on the host's loopback address. Every script sees `UAA_BUILD_STAGE`; local scripts
+//! also see `UAA_BUILD_DISK`, the build VM's disk.
+
+use crate::config::build_hooks::{
+    BuildHooks, HookScript, HookStage, ProvisionerType, ShellProvisioner,
+};
+use crate::error::AutoInstallError;
+use crate::security::ssh_ca::SshKey;
+use crate::Result;
+use std::path::{Path, PathBuf};
+use std::time::{Duration, Instant};
+use tokio::fs;
+use tokio::process::Command;
+use tracing::{debug, info, warn};
+
+/// User the build VM is installed with
+const BUILD_USER: &str = "ubuntu";
+/// How long the booted build VM may take to accept SSH
+const SSH_WAIT: Duration = Duration::from_secs(300);
+/// Exit status of `ssh` when the connection drops
+const SSH_DISCONNECTED: i32 = 255;
+
+pub struct HookRunner {
+    work_dir: PathBuf,
+    disk: PathBuf,
+    key_path: PathBuf,
+    ssh_port: u16,
+}
+
+impl HookRunner {
+    pub fn new(work_dir: PathBuf, disk: PathBuf) -> Self {
+        Self {
+            key_path: work_dir.join("hooks").join("build_key"),
+            work_dir,
+            disk,
+            ssh_port: 0,
+        }
+    }
+
+    /// Generate the build key and pick the host port forwarded to the VM's SSH port;
+    /// returns the public key to authorize in the VM
+    pub async fn prepare_ssh(&mut self) -> Result<String> {
+        let key = SshKey::generate()?;
+        let dir = self.work_dir.join("hooks");
+        fs::create_dir_all(&dir).await?;
+        fs::write(&self.key_path, key.private_openssh("uaa-build")).await?;
+        #[cfg(unix)]
+        {
+            use std::os::unix::fs::PermissionsExt;
+            fs::set_permissions(&self.key_path, std::fs::Permissions::from_mode(0o600)).await?;
+        }
+        self.ssh_port = std::net::TcpListener::bind("127.0.0.1:0")?
+            .local_addr()?
+            .port();
+        Ok(key.public_openssh("uaa-build"))
+    }
+
+    pub fn ssh_port(&self) -> u16 {
+        self.ssh_port
+    }
+
+    /// Run the provisioners of `stage` in order; the first failure stops the build
+    pub async fn run_stage(&self, hooks: &BuildHooks, stage: HookStage) -> Result<()> {
+        let provisioners = hooks.stage(stage)?;
+        if provisioners.is_empty() {
+            return Ok(());
+        }
+        info!("Running {} {} hook(s)", provisioners.len(), stage.as_str());
+        for (index, provisioner) in provisioners.iter().enumerate() {
+            match provisioner.kind {
+                ProvisionerType::ShellLocal => self.run_local(provisioner, stage, index).await?,
+                ProvisionerType::Shell => self.run_remote(provisioner, stage, index).await?,
+            }
+        }
+        Ok(())
+    }
+
+    /// Wait until the booted build VM accepts the build key
+    pub async fn wait_for_ssh(&self) -> Result<()> {
+        let started = Instant::now();
+        loop {
+            let status = Command::new("ssh")
+                .args(ssh_args(&self.key_path, self.ssh_port, "-p"))
+                .arg(format!("{}@127.0.0.1", BUILD_USER))
+                .arg("true")
+                .output()
+                .await?
+                .status;
+            if status.success() {
+                debug!("Build VM reachable over SSH on port {}", self.ssh_port);
+                return Ok(());
+            }
+            if started.elapsed() >= SSH_WAIT {
+                return Err(AutoInstallError::TimeoutError(format!(
+                    "Build VM did not accept SSH on 127.0.0.1:{} within {}s",
+                    self.ssh_port,
+                    SSH_WAIT.as_secs()
+                )));
+            }
+            tokio::time::sleep(Duration::from_secs(5)).await;
+        }
+    }
+
+    /// Store script `n` of provisioner `index` in the work directory
+    async fn write_script(
+        &self,
+        stage: HookStage,
+        index: usize,
+        n: usize,
+        script: &HookScript,
+    ) -> Result<PathBuf> {
+        let dir = self.work_dir.join("hooks");
+        fs::create_dir_all(&dir).await?;
+        let path = dir.join(format!(
+            "{}-{}-{}-{}",
+            stage.as_str(),
+            index,
+            n,
+            script.name
+        ));
+        fs::write(&path, &script.content).await?;
+        Ok(path)
+    }
+
+    async fn run_local(
+        &self,
+        provisioner: &ShellProvisioner,
+        stage: HookStage,
+        index: usize,
+    ) -> Result<()> {
+        for (n, script) in provisioner.load_scripts()?.into_iter().enumerate() {
+            let path = self.write_script(stage, index, n, &script).await?;
+            let command = provisioner.command(
+                &path.display().to_string(),
+                &[
+                    ("UAA_BUILD_STAGE", stage.as_str().to_string()),
+                    ("UAA_BUILD_DISK", self.disk.display().to_string()),
+                ],
+            );
+            info!("{} hook (local): {}", stage.as_str(), script.name);
+            let output = Command::new("sh").args(["-c", &command]).output().await?;
+            log_output(&output.stdout);
+            if !output.status.success() {
+                return Err(hook_failed(stage, &script.name, &command, &output));
+            }
+        }
+        Ok(())
+    }
+
+    async fn run_remote(
+        &self,
+        provisioner: &ShellProvisioner,
+        stage: HookStage,
+        index: usize,
+    ) -> Result<()> {
+        for (n, script) in provisioner.load_scripts()?.into_iter().enumerate() {
+            let local = self.write_script(stage, index, n, &script).await?;
+            let remote = format!("/tmp/uaa-hook-{}-{}.sh", index, n);
+
+            let upload = Command::new("scp")
+                .args(ssh_args(&self.key_path, self.ssh_port, "-P"))
+                .arg(&local)
+                .arg(format!("{}@127.0.0.1:{}", BUILD_USER, remote))
+                .output()
+                .await?;
+            if !upload.status.success() {
+                return Err(hook_failed(stage, &script.name, "scp", &upload));
+            }
+
+            let command =
+                provisioner.command(&remote, &[("UAA_BUILD_STAGE", stage.as_str().to_string())]);
+            info!("{} hook (build VM): {}", stage.as_str(), script.name);
+            let output = Command::new("ssh")
+                .args(ssh_args(&self.key_path, self.ssh_port, "-p"))
+                .arg(format!("{}@127.0.0.1", BUILD_USER))
+                .arg(&command)
+                .output()
+                .await?;
+            log_output(&output.stdout);
+            if output.status.code() == Some(SSH_DISCONNECTED) && provisioner.expect_disconnect {
+                warn!(
+                    "{} hook {} dropped the SSH connection as expected; waiting for the VM",
+                    stage.as_str(),
+                    script.name
+                );
+                tokio::time::sleep(Duration::from_secs(10)).await;
+                self.wait_for_ssh().await?;
+            } else if !output.status.success() {
+                return Err(hook_failed(stage, &script.name, &command, &output));
+            }
+        }
+        Ok(())
+    }
+}
+
+/// Non-interactive `ssh`/`scp` options for the build VM; `port_flag` is `-p` or `-P`
+fn ssh_args(key: &Path, port: u16, port_flag: &str) -> Vec<String> {
+    vec![
+        "-i".to_string(),
+        key.display().to_string(),
+        port_flag.to_string(),
+        port.to_string(),
+        "-o".to_string(),
+        "StrictHostKeyChecking=no".to_string(),
+        "-o".to_string(),
+        "UserKnownHostsFile=/dev/null".to_string(),
+        "-o".to_string(),
+        "BatchMode=yes".to_string(),
+        "-o".to_string(),
+        "ConnectTimeout=10".to_string(),
+        "-o".to_string(),
+        "LogLevel=ERROR".to_string(),
+    ]
+}
+
+fn log_output(stdout: &[u8]) {
+    for line in String::from_utf8_lossy(stdout).lines() {
+        debug!("  | {}", line);
+    }
+}
+
+fn hook_failed(
+    stage: HookStage,
+    script: &str,
+    command: &str,
+    output: &std::process::Output,
+) -> AutoInstallError {
+    warn!("{} hook {} failed", stage.as_str(), script);
+    AutoInstallError::ProcessError {
+        command: command.to_string(),
+        exit_code: output.status.code(),
+        stderr: String::from_utf8_lossy(&output.stderr).trim().to_string(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn test_local_hooks_run_in_order() {
+        let dir = tempfile::TempDir::new().unwrap();
+        let out = dir.path().join("out.txt");
+        let hooks: BuildHooks = serde_yaml::from_str(&format!(
+            r#"
+pre_boot:
+  - type: shell-local
+    inline: ["echo \"$UAA_BUILD_STAGE $GREETING\" >> {out}"]
+    environment_vars: ["GREETING=hello world"]
+  - type: shell-local
+    inline: ["echo second >> {out}"]
+"#,
+            out = out.display()
+        ))
+        .unwrap();
+        hooks.validate().unwrap();
+        let runner = HookRunner::new(dir.path().to_path_buf(), dir.path().join("disk.qcow2"));
+        runner.run_stage(&hooks, HookStage::PreBoot).await.unwrap();
+        assert_eq!(
+            std::fs::read_to_string(&out).unwrap(),
+            "pre-boot hello world\nsecond\n"
+        );
+
+        let failing: BuildHooks =
+            serde_yaml::from_str("pre_boot:\n  - type: shell-local\n    inline: [\"exit 3\"]\n")
+                .unwrap();
+        let err = runner
+            .run_stage(&failing, HookStage::PreBoot)
+            .await
+            .unwrap_err();
+        assert!(matches!(
+            err,
+            AutoInstallError::ProcessError {
+                exit_code: Some(3),
+                ..
+            }
+        ));
+
+        let args = ssh_args(Path::new("/w/key"), 2222, "-P").join(" ");
+        assert!(args.starts_with("-i /w/key -P 2222 -o StrictHostKeyChecking=no"));
+    }
+}
@@ -1,17 +1,21 @@
 // file: src/image/builder/iso.rs
-// version: 1.0.1
+// version: 1.4.0
 // guid: a1a2a3a4-b5b6-7890-1234-567890abcdef
 
 //! ISO management and download utilities
 
+use super::cache::SharedCache;
 use crate::{
     config::{Architecture, ImageSpec},
-    network::download::NetworkDownloader,
+    network::download_pipeline::{
+        check_digest, parse_sha256sums, verify_signature, Artifact, DownloadPipeline,
+        SignatureStatus,
+    },
     Result,
 };
 use std::path::{Path, PathBuf};
 use tokio::fs;
-use tracing::info;
+use tracing::{info, warn};
 
 /// ISO download and caching manager
 pub struct IsoManager {
@@ -25,7 +29,34 @@ impl IsoManager {
     }
 
     /// Download Ubuntu Server ISO if not cached and extract kernel/initrd for direct boot
+    ///
+    /// Other agent processes sharing the cache wait on the same lock instead of downloading
+    /// the ISO a second time; the extracted files only count once they are in the manifest.
     pub async fn get_ubuntu_iso(&self, spec: &ImageSpec) -> Result<PathBuf> {
+        let key = format!(
+            "ubuntu-{}-{}",
+            spec.ubuntu_version,
+            spec.architecture.as_str()
+        );
+        let cache = SharedCache::new(self.cache_dir.clone());
+        if let Some(entry) = cache.lookup(&key) {
+            info!(
+                "Using cached Ubuntu Server ISO files: {}",
+                entry.path.display()
+            );
+            return Ok(entry.path);
+        }
+        let _lock = cache.lock(&key).await?;
+        // Another process may have finished it while this one waited
+        if let Some(entry) = cache.lookup(&key) {
+            info!(
+                "Using Ubuntu Server ISO files prepared by {}: {}",
+                entry.producer,
+                entry.path.display()
+            );
+            return Ok(entry.path);
+        }
+
         // For autoinstall, we need the full Ubuntu Server ISO, not netboot
         let iso_dir = self.cache_dir.join("isos").join(format!(
             "ubuntu-{}-{}",
@@ -47,13 +78,25 @@ impl IsoManager {
             .await
             .map_err(crate::error::AutoInstallError::IoError)?;
 
-        // Check if kernel files already extracted
+        // Files extracted before the cache had a manifest are adopted as they are; once it
+        // has one, unrecorded files are the leftovers of an interrupted run
+        let iso_path = self.iso_path(spec);
         let kernel_path = extract_dir.join("casper").join("vmlinuz");
-        if kernel_path.exists() {
+        if kernel_path.exists() && !cache.has_manifest() {
             info!(
                 "Using cached Ubuntu Server ISO files: {}",
                 extract_dir.display()
             );
+            let digest = fs::read_to_string(format!("{}.sha256", iso_path.display()))
+                .await
+                .ok()
+                .map(|d| d.trim().to_string());
+            if iso_path.exists() {
+                cache
+                    .record(&format!("{}.iso", key), &iso_path, digest)
+                    .await?;
+            }
+            cache.record(&key, &extract_dir, None).await?;
             return Ok(extract_dir);
         }
 
@@ -62,24 +105,41 @@ impl IsoManager {
             extract_dir.display()
         );
 
-        // Download Ubuntu Server ISO
-        let iso_url = self.get_ubuntu_server_iso_url(spec)?;
-        let iso_path = iso_dir.join(format!(
-            "ubuntu-{}-live-server-{}.iso",
-            spec.ubuntu_version,
-            spec.architecture.as_str()
-        ));
-
-        info!("Downloading Ubuntu Server ISO from: {}", iso_url);
-        self.download_file(&iso_url, &iso_path).await?;
+        // Download Ubuntu Server ISO unless an earlier run already verified it
+        let iso_key = format!("{}.iso", key);
+        if cache.lookup(&iso_key).is_none() {
+            let digest = self.download_iso(spec).await?;
+            cache.record(&iso_key, &iso_path, Some(digest)).await?;
+        }
 
-        // Extract kernel and initrd from ISO
+        // Extract kernel and initrd from ISO; leftovers of an interrupted extraction go first
+        if extract_dir.join("casper").exists() {
+            fs::remove_dir_all(&extract_dir).await?;
+            fs::create_dir_all(&extract_dir).await?;
+        }
         self.extract_iso_boot_files(&iso_path, &extract_dir).await?;
+        cache.record(&key, &extract_dir, None).await?;
 
         Ok(extract_dir)
     }
+    /// Cached location of the Ubuntu Server ISO for `spec`
+    pub fn iso_path(&self, spec: &ImageSpec) -> PathBuf {
+        self.cache_dir
+            .join("isos")
+            .join(format!(
+                "ubuntu-{}-{}",
+                spec.ubuntu_version,
+                spec.architecture.as_str()
+            ))
+            .join(format!(
+                "ubuntu-{}-live-server-{}.iso",
+                spec.ubuntu_version,
+                spec.architecture.as_str()
+            ))
+    }
+
     /// Get Ubuntu Server ISO download URL
-    fn get_ubuntu_server_iso_url(&self, spec: &ImageSpec) -> Result<String> {
+    pub fn get_ubuntu_server_iso_url(&self, spec: &ImageSpec) -> Result<String> {
         // Ubuntu Server ISO URLs follow this pattern:
         // https://releases.ubuntu.com/{codename}/ubuntu-{version}-live-server-{arch}.iso
         let arch_suffix = match spec.architecture {
@@ -88,19 +148,12 @@ impl IsoManager {
         };
 
         // Convert version to codename for releases URL
-        let codename = match spec.ubuntu_version.as_str() {
-            "25.04" => "plucky",
-            "24.10" => "oracular",
-            "24.04" => "noble",
-            "23.10" => "mantic",
-            "23.04" => "lunar",
-            _ => {
-                return Err(crate::error::AutoInstallError::ConfigError(format!(
-                    "Unsupported Ubuntu version: {}",
-                    spec.ubuntu_version
-                )));
-            }
-        };
+        let codename = crate::config::ubuntu_codename(&spec.ubuntu_version).ok_or_else(|| {
+            crate::error::AutoInstallError::ConfigError(format!(
+                "Unsupported Ubuntu version: {}",
+                spec.ubuntu_version
+            ))
+        })?;
 
         Ok(format!(
             "https://releases.ubuntu.com/{}/ubuntu-{}-live-server-{}.iso",
@@ -185,10 +238,69 @@ echo "ISO boot files extracted successfully"
         Ok(())
     }
 
-    /// Download file with progress
-    async fn download_file(&self, url: &str, dest: &Path) -> Result<()> {
-        let downloader = NetworkDownloader::new();
-        downloader.download_with_progress(url, dest).await
+    /// `SHA256SUMS` list published next to the ISO at `iso_url`
+    pub fn checksums_url(iso_url: &str) -> String {
+        let base = iso_url.rsplit_once('/').map_or(iso_url, |(base, _)| base);
+        format!("{}/SHA256SUMS", base)
+    }
+
+    /// Download the ISO and its signed checksum list concurrently and verify the ISO; returns
+    /// its sha256
+    ///
+    /// The ISO is hashed while it streams; the checksum list and its signature are fetched and
+    /// checked alongside it, so verification is a string comparison once the ISO completes.
+    async fn download_iso(&self, spec: &ImageSpec) -> Result<String> {
+        let iso_url = self.get_ubuntu_server_iso_url(spec)?;
+        let iso_path = self.iso_path(spec);
+        let iso_dir = iso_path.parent().unwrap_or(&self.cache_dir).to_path_buf();
+        let iso_name = iso_url.rsplit('/').next().unwrap_or_default().to_string();
+        let sums_url = Self::checksums_url(&iso_url);
+
+        let pipeline = DownloadPipeline::new()?;
+        let checksum_artifacts = [
+            Artifact::new("SHA256SUMS", &sums_url, iso_dir.join("SHA256SUMS")),
+            Artifact::new(
+                "SHA256SUMS.gpg",
+                &format!("{}.gpg", sums_url),
+                iso_dir.join("SHA256SUMS.gpg"),
+            ),
+        ];
+        let iso = Artifact::new("ISO", &iso_url, iso_path.clone());
+
+        let expected_digest = async {
+            pipeline.fetch_all(&checksum_artifacts).await?;
+            let (sums, signature) = (&checksum_artifacts[0].dest, &checksum_artifacts[1].dest);
+            match verify_signature(sums, signature).await? {
+                SignatureStatus::Verified => info!("Verified signature of {}", sums_url),
+                SignatureStatus::Unverifiable(reason) => {
+                    warn!("Signature of {} not checked: {}", sums_url, reason)
+                }
+            }
+            parse_sha256sums(&fs::read_to_string(sums).await?)
+                .remove(&iso_name)
+                .ok_or_else(|| {
+                    crate::error::AutoInstallError::ValidationError(format!(
+                        "{} is not listed in {}",
+                        iso_name, sums_url
+                    ))
+                })
+        };
+
+        info!("Downloading Ubuntu Server ISO from: {}", iso_url);
+        let (expected, downloaded) = tokio::try_join!(expected_digest, pipeline.fetch(&iso))?;
+        if let Err(e) = check_digest(&iso_name, &expected, &downloaded.sha256) {
+            let _ = fs::remove_file(&iso_path).await;
+            return Err(e);
+        }
+        info!("Verified {} (sha256 {})", iso_name, downloaded.sha256);
+
+        // Seed the digest cache read by provenance so the ISO is never hashed a second time
+        fs::write(
+            format!("{}.sha256", iso_path.display()),
+            format!("{}\n", downloaded.sha256),
+        )
+        .await?;
+        Ok(downloaded.sha256)
     }
 }
 
@@ -225,8 +337,12 @@ mod tests {
                 memory_mb: 2048,
                 disk_size_gb: 20,
                 cpu_cores: 2,
+                machine: None,
+                firmware: None,
             },
             custom_scripts: vec![],
+            flavor: Default::default(),
+            hooks: Default::default(),
         };
 
         // Act
@@ -257,8 +373,12 @@ mod tests {
                 memory_mb: 2048,
                 disk_size_gb: 20,
                 cpu_cores: 2,
+                machine: None,
+                firmware: None,
             },
             custom_scripts: vec![],
+            flavor: Default::default(),
+            hooks: Default::default(),
         };
 
         // Act
@@ -300,8 +420,12 @@ mod tests {
                     memory_mb: 2048,
                     disk_size_gb: 20,
                     cpu_cores: 2,
+                    machine: None,
+                    firmware: None,
                 },
                 custom_scripts: vec![],
+                flavor: Default::default(),
+                hooks: Default::default(),
             };
 
             // Act
@@ -335,8 +459,12 @@ mod tests {
                 memory_mb: 2048,
                 disk_size_gb: 20,
                 cpu_cores: 2,
+                machine: None,
+                firmware: None,
             },
             custom_scripts: vec![],
+            flavor: Default::default(),
+            hooks: Default::default(),
         };
 
         // Act
@@ -366,8 +494,12 @@ mod tests {
                 memory_mb: 2048,
                 disk_size_gb: 20,
                 cpu_cores: 2,
+                machine: None,
+                firmware: None,
             },
             custom_scripts: vec![],
+            flavor: Default::default(),
+            hooks: Default::default(),
         };
 
         // Seed cache with expected kernel/initrd so download path is skipped in tests
@@ -418,8 +550,12 @@ mod tests {
                     memory_mb: 2048,
                     disk_size_gb: 20,
                     cpu_cores: 2,
+                    machine: None,
+                    firmware: None,
                 },
                 custom_scripts: vec![],
+                flavor: Default::default(),
+                hooks: Default::default(),
             };
 
             // Act
@@ -433,27 +569,15 @@ mod tests {
         }
     }
 
-    #[tokio::test]
-    async fn test_download_file_method_structure() {
+    #[test]
+    fn test_checksums_url() {
         // Arrange
-        let temp_dir = TempDir::new().unwrap();
-        let iso_manager = IsoManager::new(temp_dir.path().to_path_buf());
-        let test_file = temp_dir.path().join("test_download.txt");
-
-        // Use mocked downloader to simulate a failure without making real network calls
-        crate::network::download::set_mock_download_with_progress(Err(
-            crate::error::AutoInstallError::NetworkError("Simulated download failure".to_string()),
-        ));
+        let iso_url = "https://releases.ubuntu.com/noble/ubuntu-24.04-live-server-amd64.iso";
 
         // Act
-        let result = iso_manager
-            .download_file("http://unused.test/does-not-exist", &test_file)
-            .await;
+        let sums_url = IsoManager::checksums_url(iso_url);
 
         // Assert
-        // Should fail gracefully with network error
-        assert!(result.is_err());
-        // File should not be created for failed download
-        assert!(!test_file.exists());
+        assert_eq!(sums_url, "https://releases.ubuntu.com/noble/SHA256SUMS");
     }
 }
@@ -1,25 +1,43 @@
 // file: src/image/builder/mod.rs
-// version: 1.0.1
+// version: 1.10.0
 // guid: e1e2e3e4-f5f6-7890-1234-567890efghij
 
 //! Modular image builder implementation
+//!
+//! The cache directory may be shared by several agent processes; `cache.rs` describes how
+//! downloads are coordinated. Each process builds in its own work directory.
 
-use crate::config::ImageSpec;
-use crate::utils::VmManager;
+use crate::config::build_hooks::HookStage;
+use crate::config::{ImageFlavor, ImageSpec, SbcConfig};
+use crate::network::{EventBus, SshClient};
+use crate::security::provenance::{self, ArtifactKind, Provenance, Subject};
+use crate::utils::{CancellationToken, ScreenCaptureOptions, VmManager};
 use crate::Result;
-use std::path::PathBuf;
+use chrono::{DateTime, Utc};
+use sha2::{Digest, Sha256};
+use std::path::{Path, PathBuf};
 use tokio::fs;
-use tracing::{debug, info};
+use tracing::{debug, info, warn};
 
+mod cache;
+mod capture;
 mod cloudinit;
 mod disk;
+pub mod environment;
+mod hooks;
 mod iso;
 mod postprocess;
+mod sbc;
 
+use cache::SharedCache;
+use capture::CaptureManager;
+pub use capture::CaptureOptions;
 use cloudinit::CloudInitManager;
 use disk::DiskManager;
+use hooks::HookRunner;
 use iso::IsoManager;
 use postprocess::PostProcessor;
+use sbc::SbcImageBuilder;
 
 /// Golden image builder using QEMU/KVM
 pub struct ImageBuilder {
@@ -35,23 +53,36 @@ impl ImageBuilder {
             .unwrap_or_else(|| PathBuf::from("/tmp"))
             .join("ubuntu-autoinstall");
 
-        Self {
-            vm_manager: VmManager::new(),
-            work_dir: default_cache.join("work"),
-            cache_dir: default_cache,
-        }
+        Self::with_cache_dir(default_cache)
     }
 
     /// Create a new image builder with custom cache directory
     pub fn with_cache_dir<P: AsRef<std::path::Path>>(cache_dir: P) -> Self {
         let cache_path = cache_dir.as_ref().to_path_buf();
+        let mut vm_manager = VmManager::new();
+        vm_manager.set_failures_dir(cache_path.join("failures"));
         Self {
-            vm_manager: VmManager::new(),
-            work_dir: cache_path.join("work"),
+            vm_manager,
+            work_dir: SharedCache::work_dir(&cache_path, std::process::id()),
             cache_dir: cache_path,
         }
     }
 
+    /// Stop the VM installation cleanly once `token` is cancelled
+    pub fn set_cancellation_token(&mut self, token: CancellationToken) {
+        self.vm_manager.set_cancellation_token(token);
+    }
+
+    /// Screenshot interval of the build VM and whether a failed install stays paused
+    pub fn set_screen_capture(&mut self, options: ScreenCaptureOptions) {
+        self.vm_manager.set_screen_capture(options);
+    }
+
+    /// Publish the build VM's status to `bus`
+    pub fn set_event_bus(&mut self, bus: EventBus) {
+        self.vm_manager.set_event_bus(bus);
+    }
+
     /// Create a golden image from specification
     pub async fn create_image(
         &mut self,
@@ -63,14 +94,21 @@ impl ImageBuilder {
             spec.ubuntu_version,
             spec.architecture.as_str()
         );
+        let started_at = Utc::now();
 
         // Create working directory
         self.setup_work_dir().await?;
 
+        if let ImageFlavor::Sbc(sbc) = &spec.flavor {
+            return self
+                .create_sbc_image(&spec, sbc, output_path, started_at)
+                .await;
+        }
+
         // Initialize managers
         let iso_manager = IsoManager::new(self.cache_dir.clone());
         let disk_manager = DiskManager::new(self.work_dir.clone());
-        let cloudinit_manager = CloudInitManager::new(self.work_dir.clone());
+        let mut cloudinit_manager = CloudInitManager::new(self.work_dir.clone());
         let postprocessor = PostProcessor::new(self.work_dir.clone(), self.cache_dir.clone());
 
         // Download Ubuntu netboot files
@@ -82,31 +120,30 @@ impl ImageBuilder {
             .create_qemu_disk(&vm_disk, spec.vm_config.disk_size_gb)
             .await?;
 
+        let mut hook_runner = HookRunner::new(self.work_dir.clone(), vm_disk.clone());
+        hook_runner
+            .run_stage(&spec.hooks, HookStage::PreBoot)
+            .await?;
+        if spec.hooks.needs_vm() {
+            let build_key = hook_runner.prepare_ssh().await?;
+            cloudinit_manager = cloudinit_manager.with_build_key(build_key);
+        }
+
         // Create cloud-init config for automated installation
         let cloud_init_path = cloudinit_manager.create_cloud_init_config(&spec).await?;
 
         // Start VM and perform installation with signal handling
         info!("Creating VM and installing Ubuntu");
 
-        let vm_installation = self.vm_manager.install_ubuntu_in_vm(
-            &vm_disk,
-            &netboot_dir,
-            &cloud_init_path,
-            spec.vm_config.memory_mb,
-        );
-
-        let installation_result = tokio::select! {
-            result = vm_installation => result,
-            _ = tokio::signal::ctrl_c() => {
-                // If interrupted, cleanup VM and return error
-                let _ = self.vm_manager.kill_qemu().await;
-                return Err(crate::error::AutoInstallError::VmError(
-                    "VM installation interrupted by user".to_string()
-                ));
-            }
-        };
+        // Cancellation (Ctrl+C) is handled inside the VM manager, which kills QEMU itself
+        self.vm_manager
+            .install_ubuntu_in_vm(&vm_disk, &netboot_dir, &cloud_init_path, &spec.vm_config)
+            .await?;
 
-        installation_result?;
+        if spec.hooks.needs_vm() {
+            self.run_provisioner_hooks(&spec, &hook_runner, &vm_disk)
+                .await?;
+        }
 
         // Generalize the image (remove machine-specific data)
         postprocessor.generalize_image(&vm_disk).await?;
@@ -119,10 +156,176 @@ impl ImageBuilder {
         // Cleanup
         self.cleanup_work_dir().await?;
 
+        // Inputs that define the image: the spec and the installer ISO it was built from
+        let iso_path = iso_manager.iso_path(&spec);
+        let spec_yaml = serde_yaml::to_string(&spec)?;
+        let iso_digest = if iso_path.exists() {
+            provenance::sha256_file_cached(&iso_path).ok()
+        } else {
+            None
+        };
+        let statement = Self::image_provenance(&final_path, started_at)?
+            .material(
+                "spec",
+                None,
+                Some(format!("{:x}", Sha256::digest(spec_yaml.as_bytes()))),
+            )
+            .material(
+                "iso",
+                iso_manager.get_ubuntu_server_iso_url(&spec).ok(),
+                iso_digest,
+            )
+            .parameter("ubuntu_version", &spec.ubuntu_version)
+            .parameter("architecture", spec.architecture.as_str())
+            .parameter("base_packages", spec.base_packages.join(","))
+            .parameter("vm_cpu_cores", spec.vm_config.cpu_cores)
+            .parameter("vm_memory_mb", spec.vm_config.memory_mb);
+        Self::write_provenance(&statement, &final_path);
+
         info!("Image creation completed: {}", final_path.display());
         Ok(final_path)
     }
 
+    /// Boot the installed system, run the post-install and pre-generalize hooks and power
+    /// the VM off again
+    async fn run_provisioner_hooks(
+        &self,
+        spec: &ImageSpec,
+        hook_runner: &HookRunner,
+        vm_disk: &Path,
+    ) -> Result<()> {
+        self.vm_manager
+            .boot_for_provisioning(vm_disk, &spec.vm_config, hook_runner.ssh_port())
+            .await?;
+        let provisioned = async {
+            hook_runner.wait_for_ssh().await?;
+            hook_runner
+                .run_stage(&spec.hooks, HookStage::PostInstall)
+                .await?;
+            hook_runner
+                .run_stage(&spec.hooks, HookStage::PreGeneralize)
+                .await
+        }
+        .await;
+        if let Err(e) = provisioned {
+            self.vm_manager.kill_qemu().await?;
+            return Err(e);
+        }
+        self.vm_manager.power_off().await
+    }
+
+    /// Build a raw SD-card image without a VM; the UEFI/GRUB steps do not apply
+    async fn create_sbc_image(
+        &self,
+        spec: &ImageSpec,
+        sbc: &SbcConfig,
+        output_path: Option<String>,
+        started_at: DateTime<Utc>,
+    ) -> Result<PathBuf> {
+        let seed_dir = CloudInitManager::new(self.work_dir.clone())
+            .create_sbc_seed(spec)
+            .await?;
+        let raw_image = SbcImageBuilder::new(self.work_dir.clone())
+            .build(spec, sbc, &seed_dir)
+            .await?;
+        let final_path = PostProcessor::new(self.work_dir.clone(), self.cache_dir.clone())
+            .finalize_raw_image(&raw_image, output_path, spec, sbc.board.as_str())
+            .await?;
+        self.cleanup_work_dir().await?;
+
+        let spec_yaml = serde_yaml::to_string(spec)?;
+        let statement = Self::image_provenance(&final_path, started_at)?
+            .material(
+                "spec",
+                None,
+                Some(format!("{:x}", Sha256::digest(spec_yaml.as_bytes()))),
+            )
+            .parameter("ubuntu_version", &spec.ubuntu_version)
+            .parameter("architecture", spec.architecture.as_str())
+            .parameter("flavor", "sbc")
+            .parameter("board", sbc.board.as_str())
+            .parameter("base_packages", spec.base_packages.join(","));
+        Self::write_provenance(&statement, &final_path);
+
+        info!("SD-card image completed: {}", final_path.display());
+        Ok(final_path)
+    }
+
+    /// Capture a golden image from a running reference machine
+    ///
+    /// The package manifest of the reference machine is written next to the image as
+    /// `<image>.packages.txt`.
+    pub async fn capture_image(
+        &mut self,
+        ssh: &mut SshClient,
+        options: &CaptureOptions,
+        output_path: Option<String>,
+    ) -> Result<PathBuf> {
+        let started_at = Utc::now();
+        self.setup_work_dir().await?;
+
+        let captured = CaptureManager::new(ssh, self.work_dir.clone())
+            .capture(options)
+            .await?;
+
+        let vm_disk = self.work_dir.join("captured.qcow2");
+        capture::make_disk(&captured.archive, &vm_disk, options.extra_space_gb).await?;
+
+        let spec = ImageSpec::minimal(captured.ubuntu_version.clone(), captured.architecture);
+        let postprocessor = PostProcessor::new(self.work_dir.clone(), self.cache_dir.clone());
+        let final_path = postprocessor
+            .finalize_image(&vm_disk, output_path, &spec)
+            .await?;
+
+        let manifest_path = PathBuf::from(format!("{}.packages.txt", final_path.display()));
+        fs::write(&manifest_path, &captured.package_manifest).await?;
+        info!("Package manifest written to {}", manifest_path.display());
+
+        self.cleanup_work_dir().await?;
+
+        let statement = Self::image_provenance(&final_path, started_at)?
+            .material(
+                "package-manifest",
+                Some(manifest_path.display().to_string()),
+                Some(format!(
+                    "{:x}",
+                    Sha256::digest(captured.package_manifest.as_bytes())
+                )),
+            )
+            .parameter("source", "capture")
+            .parameter("ubuntu_version", &captured.ubuntu_version)
+            .parameter("architecture", captured.architecture.as_str());
+        Self::write_provenance(&statement, &final_path);
+
+        info!("Image capture completed: {}", final_path.display());
+        Ok(final_path)
+    }
+
+    /// Provenance statement whose subject is the finished image file
+    fn image_provenance(image: &Path, started_at: DateTime<Utc>) -> Result<Provenance> {
+        let name = image
+            .file_name()
+            .map(|n| n.to_string_lossy().to_string())
+            .unwrap_or_default();
+        Ok(Provenance::new(
+            ArtifactKind::Image,
+            Subject {
+                name,
+                sha256: provenance::sha256_file(image)?,
+            },
+            started_at,
+        ))
+    }
+
+    /// Sign and store provenance next to the image; a failure leaves the image usable
+    fn write_provenance(statement: &Provenance, image: &Path) {
+        let path = provenance::provenance_path_for(image);
+        match provenance::record(statement, &path) {
+            Ok(()) => info!("Provenance written to {}", path.display()),
+            Err(e) => warn!("Failed to write provenance for {}: {}", image.display(), e),
+        }
+    }
+
     /// Set up working directory
     async fn setup_work_dir(&self) -> Result<()> {
         // Create both work and cache directories
@@ -132,6 +335,10 @@ impl ImageBuilder {
         fs::create_dir_all(&self.cache_dir)
             .await
             .map_err(crate::error::AutoInstallError::IoError)?;
+        let swept = SharedCache::new(self.cache_dir.clone()).sweep_stale_work_dirs();
+        if swept > 0 {
+            debug!("Removed {} work directories of finished processes", swept);
+        }
 
         debug!("Work directory created: {}", self.work_dir.display());
         debug!("Cache directory: {}", self.cache_dir.display());
@@ -1,5 +1,5 @@
 // file: src/image/builder/postprocess.rs
-// version: 1.0.1
+// version: 1.2.0
 // guid: d1d2d3d4-e5e6-7890-1234-567890defghi
 
 //! Image post-processing: generalization and finalization
@@ -51,6 +51,7 @@ rm-rf /tmp/*
 rm-rf /var/tmp/*
 rm-rf /root/.bash_history
 rm-rf /home/ubuntu/.bash_history
+rm-rf /home/ubuntu/.ssh/authorized_keys
 
 # Clear package cache
 rm-rf /var/cache/apt/archives/*.deb
@@ -156,12 +157,53 @@ umount-all
             )));
         }
 
+        self.register_final_image(&final_path, spec).await?;
+        Ok(final_path)
+    }
+
+    /// Move a raw SD-card image to its final place; it stays raw so it can be written to a card
+    pub async fn finalize_raw_image(
+        &self,
+        raw_image: &Path,
+        output_path: Option<String>,
+        spec: &ImageSpec,
+        board: &str,
+    ) -> Result<PathBuf> {
+        info!("Finalizing SD-card image");
+
+        let final_path = match output_path {
+            Some(output) => PathBuf::from(output),
+            None => self.cache_dir.join("images").join(format!(
+                "ubuntu-{}-{}-{}-{}.img",
+                spec.ubuntu_version,
+                spec.architecture.as_str(),
+                board,
+                chrono::Utc::now().format("%Y%m%d-%H%M%S")
+            )),
+        };
+        if let Some(parent) = final_path.parent() {
+            fs::create_dir_all(parent)
+                .await
+                .map_err(crate::error::AutoInstallError::IoError)?;
+        }
+        // The work directory may be on another filesystem than the output
+        if fs::rename(raw_image, &final_path).await.is_err() {
+            fs::copy(raw_image, &final_path).await?;
+            fs::remove_file(raw_image).await?;
+        }
+
+        self.register_final_image(&final_path, spec).await?;
+        Ok(final_path)
+    }
+
+    /// Checksum the finished image and record it in the image database
+    async fn register_final_image(&self, final_path: &Path, spec: &ImageSpec) -> Result<()> {
         // Calculate checksum for integrity verification
-        let checksum = self.calculate_image_checksum(&final_path).await?;
+        let checksum = self.calculate_image_checksum(final_path).await?;
         info!("Image checksum (SHA256): {}", checksum);
 
         // Get image size
-        let metadata = fs::metadata(&final_path)
+        let metadata = fs::metadata(final_path)
             .await
             .map_err(crate::error::AutoInstallError::IoError)?;
         let size_bytes = metadata.len();
@@ -173,7 +215,7 @@ umount-all
             spec.architecture,
             size_bytes,
             checksum,
-            final_path.clone(),
+            final_path.to_path_buf(),
         );
 
         if let Err(e) = manager.register_image(image_info).await {
@@ -181,11 +223,11 @@ umount-all
         }
 
         info!(
-            "Image compressed to: {} ({})",
+            "Image written to: {} ({})",
             final_path.display(),
             Self::format_size(size_bytes)
         );
-        Ok(final_path)
+        Ok(())
     }
 
     /// Calculate SHA256 checksum of image file
@@ -386,8 +428,12 @@ mod tests {
                 memory_mb: 2048,
                 disk_size_gb: 20,
                 cpu_cores: 2,
+                machine: None,
+                firmware: None,
             },
             custom_scripts: vec![],
+            flavor: Default::default(),
+            hooks: Default::default(),
         };
 
         // Act
@@ -432,8 +478,12 @@ mod tests {
                 memory_mb: 2048,
                 disk_size_gb: 20,
                 cpu_cores: 2,
+                machine: None,
+                firmware: None,
             },
             custom_scripts: vec![],
+            flavor: Default::default(),
+            hooks: Default::default(),
         };
 
         // Act
@@ -480,8 +530,12 @@ mod tests {
                     memory_mb: 2048,
                     disk_size_gb: 20,
                     cpu_cores: 2,
+                    machine: None,
+                    firmware: None,
                 },
                 custom_scripts: vec![],
+                flavor: Default::default(),
+                hooks: Default::default(),
             };
 
             // Act
@@ -0,0 +1,337 @@
+// file: src/image/builder/sbc.rs
+// version: 1.1.0
+// guid: 5e8c1a3f-2d94-4b67-a0f5-c7b9e2d41a86
+
+//! Raw SD-card images for Raspberry Pi and other single-board computers
+//!
+//! There is no build VM, installer or GRUB here. The image file is partitioned on a loop
+//! device using the layout of Ubuntu's own Raspberry Pi images: an MBR with a FAT
+//! `system-boot` partition and an ext4 `writable` root. The root is debootstrapped from the
+//! ports archive and given the board's boot files. Building on an amd64 host needs
+//! `qemu-user-static` with binfmt support for the arm64 chroot, and root for the loop device.
+
+use crate::config::image::{SbcBoard, SbcConfig};
+use crate::config::mirrors::UBUNTU_PORTS;
+use crate::config::{ubuntu_codename, ImageSpec};
+use crate::error::AutoInstallError;
+use crate::utils::shell_quote;
+use crate::Result;
+use std::path::{Path, PathBuf};
+use tokio::fs;
+use tokio::process::Command;
+use tracing::info;
+
+/// Kernel arguments every Raspberry Pi image boots with
+const RPI_CMDLINE: &str =
+    "console=serial0,115200 console=tty1 root=LABEL=writable rootfstype=ext4 rootwait fixrtc";
+
+/// Lets cloud-init find the seed on the firmware partition by its label
+const NOCLOUD_DATASOURCE: &str =
+    "datasource_list: [NoCloud, None]\ndatasource:\n  NoCloud:\n    fs_label: system-boot\n";
+
+/// Builder of raw SD-card images
+pub struct SbcImageBuilder {
+    work_dir: PathBuf,
+}
+
+impl SbcImageBuilder {
+    pub fn new(work_dir: PathBuf) -> Self {
+        Self { work_dir }
+    }
+
+    /// Raw image written by [`build`](Self::build)
+    pub fn image_path(&self) -> PathBuf {
+        self.work_dir.join("sbc.img")
+    }
+
+    /// Build the image, copying the cloud-init seed in `seed_dir` onto its firmware partition
+    pub async fn build(
+        &self,
+        spec: &ImageSpec,
+        sbc: &SbcConfig,
+        seed_dir: &Path,
+    ) -> Result<PathBuf> {
+        let image = self.image_path();
+        let script = self.build_script(spec, sbc, &image, seed_dir)?;
+        let script_path = self.work_dir.join("sbc-build.sh");
+        fs::write(&script_path, script).await?;
+
+        info!(
+            "Building {} SD-card image for Ubuntu {}",
+            sbc.board.as_str(),
+            spec.ubuntu_version
+        );
+        let output = Command::new("bash")
+            .arg(&script_path)
+            .output()
+            .await
+            .map_err(|e| {
+                AutoInstallError::ImageError(format!("Failed to start the SBC image build: {}", e))
+            })?;
+        if !output.status.success() {
+            let stderr = String::from_utf8_lossy(&output.stderr);
+            let tail: Vec<&str> = stderr.lines().rev().take(20).collect();
+            return Err(AutoInstallError::ImageError(format!(
+                "SBC image build failed (script kept at {}):\n{}",
+                script_path.display(),
+                tail.into_iter().rev().collect::<Vec<_>>().join("\n")
+            )));
+        }
+        Ok(image)
+    }
+
+    /// Shell script that builds the image; it runs as root and detaches the loop device on exit
+    pub fn build_script(
+        &self,
+        spec: &ImageSpec,
+        sbc: &SbcConfig,
+        image: &Path,
+        seed_dir: &Path,
+    ) -> Result<String> {
+        let codename = ubuntu_codename(&spec.ubuntu_version).ok_or_else(|| {
+            AutoInstallError::ValidationError(format!(
+                "No codename known for Ubuntu {}",
+                spec.ubuntu_version
+            ))
+        })?;
+        let mut packages = vec![
+            "cloud-init".to_string(),
+            "cloud-guest-utils".to_string(),
+            "openssh-server".to_string(),
+        ];
+        packages.extend(board_packages(sbc));
+        for package in spec.resolved_packages()? {
+            if !packages.contains(&package) {
+                packages.push(package);
+            }
+        }
+
+        // Generic boards keep U-Boot in the gap before the first partition
+        let firmware_start = if sbc.board.is_raspberry_pi() { 1 } else { 16 };
+        let firmware_end = firmware_start + sbc.firmware_size_mb;
+        let boot_partition = if sbc.board.is_raspberry_pi() { 1 } else { 2 };
+
+        let mut lines = vec![
+            "set -euo pipefail".to_string(),
+            format!("IMAGE={}", shell_quote(&image.display().to_string())),
+            format!(
+                "ROOT={}",
+                shell_quote(&self.work_dir.join("sbc-root").display().to_string())
+            ),
+            format!("SEED={}", shell_quote(&seed_dir.display().to_string())),
+            "rm -f \"$IMAGE\"".to_string(),
+            format!("truncate -s {}G \"$IMAGE\"", sbc.image_size_gb),
+            format!(
+                "parted -s \"$IMAGE\" mklabel msdos mkpart primary fat32 {}MiB {}MiB mkpart primary ext4 {}MiB 100% set {} boot on",
+                firmware_start, firmware_end, firmware_end, boot_partition
+            ),
+            "LOOP=$(losetup --find --show --partscan \"$IMAGE\")".to_string(),
+            "cleanup() { umount -R \"$ROOT\" 2>/dev/null || true; losetup -d \"$LOOP\" || true; }"
+                .to_string(),
+            "trap cleanup EXIT".to_string(),
+            "mkfs.vfat -F 32 -n system-boot \"${LOOP}p1\"".to_string(),
+            "mkfs.ext4 -q -L writable \"${LOOP}p2\"".to_string(),
+            "mkdir -p \"$ROOT\"".to_string(),
+            "mount \"${LOOP}p2\" \"$ROOT\"".to_string(),
+            format!(
+                "debootstrap --arch=arm64 --components=main,restricted,universe {} \"$ROOT\" {}",
+                codename, UBUNTU_PORTS
+            ),
+            "mkdir -p \"$ROOT/boot/firmware\"".to_string(),
+            "mount \"${LOOP}p1\" \"$ROOT/boot/firmware\"".to_string(),
+            "for fs in dev dev/pts proc sys; do mount --bind \"/$fs\" \"$ROOT/$fs\"; done"
+                .to_string(),
+            heredoc(
+                "$ROOT/etc/fstab",
+                "LABEL=writable / ext4 defaults,discard,x-systemd.growfs 0 1\nLABEL=system-boot /boot/firmware vfat defaults 0 1\n",
+            ),
+            heredoc(
+                "$ROOT/etc/apt/sources.list",
+                &format!(
+                    "deb {ports} {rel} main restricted universe multiverse\ndeb {ports} {rel}-updates main restricted universe multiverse\ndeb {ports} {rel}-security main restricted universe multiverse\n",
+                    ports = UBUNTU_PORTS,
+                    rel = codename
+                ),
+            ),
+            "chroot \"$ROOT\" apt-get update".to_string(),
+            format!(
+                "chroot \"$ROOT\" env DEBIAN_FRONTEND=noninteractive apt-get install -y {}",
+                packages.join(" ")
+            ),
+            "KVER=$(ls \"$ROOT/lib/modules\" | sort -V | tail -n 1)".to_string(),
+        ];
+        lines.extend(boot_file_commands(sbc));
+
+        // The same cloud-init customization the VM images get, read from the card on first boot
+        lines.push("cp \"$SEED\"/user-data \"$SEED\"/meta-data \"$SEED\"/network-config \"$ROOT/boot/firmware/\"".to_string());
+        lines.push(heredoc(
+            "$ROOT/etc/cloud/cloud.cfg.d/99-sbc-seed.cfg",
+            NOCLOUD_DATASOURCE,
+        ));
+
+        // Generalize like the VM images: first boot creates the machine identity
+        lines.push("truncate -s 0 \"$ROOT/etc/machine-id\"".to_string());
+        lines.push("rm -f \"$ROOT\"/etc/ssh/ssh_host_*".to_string());
+        lines.push("chroot \"$ROOT\" apt-get clean".to_string());
+        lines.push("sync".to_string());
+        Ok(lines.join("\n") + "\n")
+    }
+}
+
+/// Kernel and boot packages of `sbc`'s board
+fn board_packages(sbc: &SbcConfig) -> Vec<String> {
+    match &sbc.u_boot {
+        Some(u_boot) if !sbc.board.is_raspberry_pi() => vec![
+            "linux-generic".to_string(),
+            "u-boot-menu".to_string(),
+            u_boot.package.clone(),
+        ],
+        _ => vec![
+            "linux-raspi".to_string(),
+            "linux-firmware-raspi".to_string(),
+            "flash-kernel".to_string(),
+        ],
+    }
+}
+
+/// Commands that put the board's boot files in place; `$KVER` is the installed kernel
+fn boot_file_commands(sbc: &SbcConfig) -> Vec<String> {
+    let mut cmdline = RPI_CMDLINE.to_string();
+    for arg in &sbc.cmdline {
+        cmdline.push(' ');
+        cmdline.push_str(arg);
+    }
+    match (sbc.board, &sbc.u_boot) {
+        (SbcBoard::Generic, Some(u_boot)) => {
+            // U-Boot's distro boot reads /boot/extlinux/extlinux.conf from the bootable root;
+            // u-boot-menu regenerates it on every kernel update
+            let parameters = cmdline
+                .replace("console=serial0,115200 console=tty1 ", "")
+                .replace(" fixrtc", "");
+            vec![
+                heredoc(
+                    "$ROOT/etc/default/u-boot",
+                    &format!(
+                        "U_BOOT_PARAMETERS=\"{}\"\nU_BOOT_FDT=\"{}\"\n",
+                        parameters, u_boot.fdt
+                    ),
+                ),
+                "chroot \"$ROOT\" u-boot-update".to_string(),
+                format!(
+                    "dd if=\"$ROOT\"{} of=\"$LOOP\" bs=1024 seek={} conv=notrunc,fsync",
+                    shell_quote(&u_boot.image.display().to_string()),
+                    u_boot.offset_kb
+                ),
+            ]
+        }
+        (board, _) => {
+            let mut config_txt = vec![
+                "[all]",
+                "arm_64bit=1",
+                "kernel=vmlinuz",
+                "cmdline=cmdline.txt",
+                "initramfs initrd.img followkernel",
+            ]
+            .into_iter()
+            .map(str::to_string)
+            .collect::<Vec<_>>();
+            config_txt.extend(sbc.config_txt.iter().cloned());
+            vec![
+                // flash-kernel keeps the firmware partition current on kernel updates
+                heredoc(
+                    "$ROOT/etc/flash-kernel/machine",
+                    &format!("{}\n", rpi_model(board)),
+                ),
+                "cp \"$ROOT\"/usr/lib/linux-firmware-raspi/* \"$ROOT/boot/firmware/\"".to_string(),
+                "cp \"$ROOT/boot/vmlinuz-$KVER\" \"$ROOT/boot/firmware/vmlinuz\"".to_string(),
+                "cp \"$ROOT/boot/initrd.img-$KVER\" \"$ROOT/boot/firmware/initrd.img\"".to_string(),
+                "cp \"$ROOT/lib/firmware/$KVER/device-tree/broadcom/\"bcm27*.dtb \"$ROOT/boot/firmware/\""
+                    .to_string(),
+                "cp -r \"$ROOT/lib/firmware/$KVER/device-tree/overlays\" \"$ROOT/boot/firmware/\""
+                    .to_string(),
+                heredoc("$ROOT/boot/firmware/cmdline.txt", &format!("{}\n", cmdline)),
+                heredoc(
+                    "$ROOT/boot/firmware/config.txt",
+                    &(config_txt.join("\n") + "\n"),
+                ),
+            ]
+        }
+    }
+}
+
+/// Model string flash-kernel knows the board by
+fn rpi_model(board: SbcBoard) -> &'static str {
+    match board {
+        SbcBoard::Rpi3 => "Raspberry Pi 3 Model B",
+        SbcBoard::Rpi5 => "Raspberry Pi 5 Model B Rev 1.0",
+        SbcBoard::Rpi4 | SbcBoard::Generic => "Raspberry Pi 4 Model B",
+    }
+}
+
+/// `cat` of `content` into `path`, verbatim
+fn heredoc(path: &str, content: &str) -> String {
+    format!(
+        "mkdir -p \"$(dirname \"{path}\")\"\ncat > \"{path}\" <<'SBC_EOF'\n{content}SBC_EOF",
+        path = path,
+        content = content
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::image::UBootConfig;
+    use crate::config::{Architecture, ImageFlavor};
+
+    #[test]
+    fn test_build_scripts() {
+        let mut spec = ImageSpec::minimal("24.04".to_string(), Architecture::Arm64);
+        let mut sbc = SbcConfig {
+            cmdline: vec!["cgroup_enable=memory".to_string()],
+            config_txt: vec!["dtoverlay=disable-bt".to_string()],
+            ..Default::default()
+        };
+        spec.flavor = ImageFlavor::Sbc(sbc.clone());
+        assert!(spec.validate().is_ok());
+
+        let builder = SbcImageBuilder::new(PathBuf::from("/work"));
+        let script = builder
+            .build_script(&spec, &sbc, &builder.image_path(), Path::new("/work/seed"))
+            .unwrap();
+        assert!(script.contains("IMAGE=/work/sbc.img\n"));
+        assert!(script.contains(
+            "mkpart primary fat32 1MiB 513MiB mkpart primary ext4 513MiB 100% set 1 boot on"
+        ));
+        assert!(script.contains("debootstrap --arch=arm64 --components=main,restricted,universe noble \"$ROOT\" http://ports.ubuntu.com/ubuntu-ports/"));
+        assert!(script.contains("apt-get install -y cloud-init cloud-guest-utils openssh-server linux-raspi linux-firmware-raspi flash-kernel curl"));
+        assert!(script.contains("rootwait fixrtc cgroup_enable=memory\nSBC_EOF"));
+        assert!(script.contains("initramfs initrd.img followkernel\ndtoverlay=disable-bt\nSBC_EOF"));
+        assert!(script.contains("fs_label: system-boot"));
+        assert!(!script.contains("grub"));
+
+        sbc.board = SbcBoard::Generic;
+        sbc.u_boot = Some(UBootConfig {
+            package: "u-boot-sunxi".to_string(),
+            image: PathBuf::from("/usr/lib/u-boot/orangepi_zero2/u-boot-sunxi-with-spl.bin"),
+            offset_kb: 8,
+            fdt: "allwinner/sun50i-h616-orangepi-zero2.dtb".to_string(),
+        });
+        let script = builder
+            .build_script(&spec, &sbc, &builder.image_path(), Path::new("/work/seed"))
+            .unwrap();
+        assert!(script.contains("fat32 16MiB 528MiB mkpart primary ext4 528MiB 100% set 2 boot on"));
+        assert!(script.contains("U_BOOT_PARAMETERS=\"root=LABEL=writable rootfstype=ext4 rootwait cgroup_enable=memory\""));
+        assert!(script.contains("dd if=\"$ROOT\"/usr/lib/u-boot/orangepi_zero2/u-boot-sunxi-with-spl.bin of=\"$LOOP\" bs=1024 seek=8"));
+        assert!(!script.contains("cmdline.txt"));
+
+        // A generic board without U-Boot, or an amd64 SD card, is refused
+        spec.flavor = ImageFlavor::Sbc(SbcConfig {
+            board: SbcBoard::Generic,
+            ..Default::default()
+        });
+        assert!(spec.validate().is_err());
+        spec.flavor = ImageFlavor::Sbc(SbcConfig::default());
+        spec.architecture = Architecture::Amd64;
+        assert!(spec.validate().is_err());
+    }
+}
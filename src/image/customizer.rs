@@ -1,42 +0,0 @@
-// file: src/image/customizer.rs
-// version: 1.0.0
-// guid: o5p6q7r8-s9t0-1234-5678-901234opqrst
-
-//! Image customization for target-specific modifications
-
-use crate::{config::TargetConfig, Result};
-use std::path::Path;
-
-/// Customizer for applying target-specific modifications to images
-pub struct ImageCustomizer;
-
-impl ImageCustomizer {
-    /// Create a new image customizer
-    pub fn new() -> Self {
-        Self
-    }
-
-    /// Apply target-specific customizations to an image
-    pub async fn customize_image<P: AsRef<Path>>(
-        &self,
-        _image_path: P,
-        config: &TargetConfig,
-    ) -> Result<()> {
-        // This is a placeholder for image customization logic
-        // In a full implementation, this would:
-        // 1. Mount the image
-        // 2. Apply hostname, network, user configurations
-        // 3. Install target-specific packages
-        // 4. Run custom scripts
-        // 5. Unmount the image
-
-        tracing::info!("Customizing image for target: {}", config.hostname);
-        Ok(())
-    }
-}
-
-impl Default for ImageCustomizer {
-    fn default() -> Self {
-        Self::new()
-    }
-}
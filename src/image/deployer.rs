@@ -1,20 +1,31 @@
 // file: src/image/deployer.rs
-// version: 1.0.0
+// version: 1.6.0
 // guid: m3n4o5p6-q7r8-9012-3456-789012mnopqr
 
 //! Image deployment via SSH and netboot
 
+use super::integrity::{self, ContentManifest};
+use super::overlay::{Overlay, OverlayManifest};
+use super::writer::{VerifiedWriteOptions, VerifiedWriter};
 use crate::config::TargetConfig;
 use crate::network::SshClient;
 use crate::security::LuksManager;
+use crate::utils::block_device::partition_path;
 use crate::utils::QemuUtils;
 use crate::Result;
 use std::path::Path;
-use tracing::{debug, info};
+use tracing::{debug, error, info, warn};
+
+/// Root filesystem of a deployment extracted into a new LUKS volume
+const LUKS_ROOT_DEVICE: &str = "/dev/mapper/ubuntu-root";
 
 /// Deployer for golden images to target machines
 pub struct ImageDeployer {
     luks_manager: LuksManager,
+    overlay: Option<Overlay>,
+    verified_write: Option<VerifiedWriteOptions>,
+    /// Golden image contents to compare the written root filesystem with, and how many files
+    content_check: Option<(ContentManifest, usize)>,
 }
 
 impl ImageDeployer {
@@ -22,16 +33,39 @@ impl ImageDeployer {
     pub fn new() -> Self {
         Self {
             luks_manager: LuksManager::new(),
+            overlay: None,
+            verified_write: None,
+            content_check: None,
         }
     }
 
-    /// Deploy image via SSH to target machine
+    /// Copy `overlay` into the deployed filesystem after the image and customizations
+    pub fn with_overlay(mut self, overlay: Overlay) -> Self {
+        self.overlay = Some(overlay);
+        self
+    }
+
+    /// Write the whole golden disk image to the target disk in verified chunks instead of
+    /// extracting it into a new LUKS volume
+    pub fn with_verified_write(mut self, options: VerifiedWriteOptions) -> Self {
+        self.verified_write = Some(options);
+        self
+    }
+
+    /// Compare `sample` files of the written root filesystem (every file when 0) with
+    /// `manifest` before customizing it, and fail the deploy on any difference
+    pub fn with_content_check(mut self, manifest: ContentManifest, sample: usize) -> Self {
+        self.content_check = Some((manifest, sample));
+        self
+    }
+
+    /// Deploy image via SSH to target machine; returns the manifest of the applied overlay
     pub async fn deploy_via_ssh(
         &self,
         target: &str,
         config: &TargetConfig,
         golden_image_path: &Path,
-    ) -> Result<()> {
+    ) -> Result<Option<OverlayManifest>> {
         info!("Deploying via SSH to: {}", target);
 
         // Connect to target machine
@@ -54,21 +88,34 @@ impl ImageDeployer {
         // Verify we're in a rescue environment
         self.verify_rescue_environment(&mut ssh).await?;
 
-        // Setup LUKS encryption on target disk
-        self.setup_luks_disk(&mut ssh, config).await?;
+        let root_device = match &self.verified_write {
+            // The image carries its own partitions and bootloader; root is its first partition
+            Some(options) => {
+                self.write_raw_image(&mut ssh, config, golden_image_path, options)
+                    .await?;
+                partition_path(&config.disk_device, 1)
+            }
+            None => {
+                // Setup LUKS encryption on target disk
+                self.setup_luks_disk(&mut ssh, config).await?;
 
-        // Download and deploy image
-        self.deploy_image_to_disk(&mut ssh, config, golden_image_path)
-            .await?;
+                // Download and deploy image
+                self.deploy_image_to_disk(&mut ssh, config, golden_image_path)
+                    .await?;
 
-        // Configure bootloader
-        self.configure_bootloader(&mut ssh, config).await?;
+                // Configure bootloader
+                self.configure_bootloader(&mut ssh, config).await?;
+                LUKS_ROOT_DEVICE.to_string()
+            }
+        };
 
-        // Apply target-specific customizations
-        self.apply_customizations(&mut ssh, config).await?;
+        // Apply target-specific customizations and the overlay
+        let manifest = self
+            .apply_customizations(&mut ssh, config, &root_device)
+            .await?;
 
         info!("SSH deployment completed successfully. Target is ready for reboot.");
-        Ok(())
+        Ok(manifest)
     }
 
     /// Deploy image via netboot/PXE
@@ -138,7 +185,7 @@ impl ImageDeployer {
     async fn deploy_image_to_disk(
         &self,
         ssh: &mut SshClient,
-        _config: &TargetConfig,
+        config: &TargetConfig,
         golden_image_path: &Path,
     ) -> Result<()> {
         info!("Deploying golden image to encrypted disk");
@@ -152,7 +199,7 @@ impl ImageDeployer {
             .await?;
 
         // Extract golden image to target
-        self.extract_golden_image(ssh, golden_image_path, mount_point)
+        self.extract_golden_image(ssh, golden_image_path, mount_point, config)
             .await?;
 
         // Unmount
@@ -162,17 +209,59 @@ impl ImageDeployer {
         Ok(())
     }
 
+    /// Convert the golden image to raw and write it to the target disk with verification
+    async fn write_raw_image(
+        &self,
+        ssh: &mut SshClient,
+        config: &TargetConfig,
+        golden_image: &Path,
+        options: &VerifiedWriteOptions,
+    ) -> Result<()> {
+        info!(
+            "Writing golden image to {} with verified writes",
+            config.disk_device
+        );
+        let temp_dir = tempfile::tempdir()?;
+        let raw_path = temp_dir.path().join("image.raw");
+        QemuUtils::convert_to_raw(golden_image, raw_path.as_path()).await?;
+
+        ssh.execute(&format!("wipefs -a {}", config.disk_device))
+            .await?;
+        let report = VerifiedWriter::new(options.clone())
+            .write(ssh, &raw_path, &config.disk_device)
+            .await?;
+        if !report.retried_chunks.is_empty() {
+            warn!(
+                "{} chunk(s) of {} needed rewriting; check the disk and its cabling",
+                report.retried_chunks.len(),
+                config.disk_device
+            );
+        }
+        ssh.execute(&format!("partprobe {} || true", config.disk_device))
+            .await?;
+        Ok(())
+    }
+
     /// Extract golden image to target filesystem
     async fn extract_golden_image(
         &self,
         ssh: &mut SshClient,
         golden_image: &Path,
         target_mount: &str,
+        config: &TargetConfig,
     ) -> Result<()> {
         info!("Extracting golden image to target");
+        if !config.throttle.is_empty() {
+            info!("Throttling image copy: {:?}", config.throttle);
+        }
 
         // Use QemuUtils for efficient image extraction
-        QemuUtils::extract_image_contents(golden_image, std::path::Path::new(target_mount)).await?;
+        QemuUtils::extract_image_contents_throttled(
+            golden_image,
+            std::path::Path::new(target_mount),
+            &config.throttle,
+        )
+        .await?;
 
         // Set up proper ownership and permissions
         ssh.execute(&format!("chown -R root:root {}", target_mount))
@@ -244,16 +333,34 @@ impl ImageDeployer {
     }
 
     /// Apply target-specific customizations
-    async fn apply_customizations(&self, ssh: &mut SshClient, config: &TargetConfig) -> Result<()> {
+    async fn apply_customizations(
+        &self,
+        ssh: &mut SshClient,
+        config: &TargetConfig,
+        root_device: &str,
+    ) -> Result<Option<OverlayManifest>> {
         info!("Applying target customizations");
 
         let mount_point = "/mnt/target";
-        let luks_device = "/dev/mapper/ubuntu-root";
 
         // Remount for customization
-        ssh.execute(&format!("mount {} {}", luks_device, mount_point))
+        ssh.execute(&format!("mkdir -p {}", mount_point)).await?;
+        ssh.execute(&format!("mount {} {}", root_device, mount_point))
             .await?;
 
+        // Checked before anything below changes files of the image
+        if let Some((manifest, sample)) = &self.content_check {
+            let report = integrity::verify_deployed(ssh, mount_point, manifest, *sample).await?;
+            for line in report.summary_lines() {
+                if report.is_clean() {
+                    info!("{}", line);
+                } else {
+                    error!("{}", line);
+                }
+            }
+            report.require_clean()?;
+        }
+
         // Set hostname
         ssh.execute(&format!(
             "echo '{}' > {}/etc/hostname",
@@ -279,10 +386,21 @@ impl ImageDeployer {
         ))
         .await?;
 
+        // Kernel tuning (sysctl.d, modules-load.d, modprobe.d)
+        for cmd in config.kernel.build_apply_commands(mount_point) {
+            ssh.execute(&cmd).await?;
+        }
+
+        // Site files go last so they win over anything the customizations wrote
+        let manifest = match &self.overlay {
+            Some(overlay) => Some(overlay.apply(ssh, mount_point).await?),
+            None => None,
+        };
+
         ssh.execute(&format!("umount {}", mount_point)).await?;
 
         info!("Target customizations completed");
-        Ok(())
+        Ok(manifest)
     }
 
     /// Configure network settings
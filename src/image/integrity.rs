@@ -0,0 +1,363 @@
+// file: src/image/integrity.rs
+// version: 1.1.0
+// guid: 9d4b7e21-6a35-4c8f-b1e0-3f7a5c2d8e64
+
+//! Content verification of a deployed image against the golden image
+//!
+//! `<image>.content.json` lists the SHA-256 of every regular file under [`HASHED_DIRS`] of the
+//! golden image, plus a root hash over the whole list in `sha256sum` format. `deploy
+//! --verify-content` hashes files of the freshly written root filesystem on the target before
+//! any customization touches it and compares them with the manifest. A sampled check picks files
+//! with a fresh random seed on every run, so a tampered transfer cannot know which files are
+//! looked at; `--verify-sample 0` hashes every file and also compares the root hash, which
+//! catches added files as well.
+
+use crate::error::AutoInstallError;
+use crate::network::SshClient;
+use crate::security::provenance;
+use crate::utils::shell_quote;
+use crate::utils::QemuUtils;
+use crate::Result;
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use std::collections::BTreeMap;
+use std::path::{Path, PathBuf};
+use tracing::info;
+
+/// Suffix of the manifest written next to an image
+pub const CONTENT_MANIFEST_SUFFIX: &str = ".content.json";
+
+/// Directories of the root filesystem whose files are hashed; deploys customize `/etc`, `/boot`
+/// and `/var`, so only these stay byte-identical to the golden image
+pub const HASHED_DIRS: &[&str] = &["usr", "opt"];
+
+/// Files hashed per remote command in a sampled check
+const SAMPLE_BATCH: usize = 100;
+
+/// File digests of a golden image's root filesystem
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ContentManifest {
+    /// SHA-256 of the image file the manifest was generated from
+    pub image_sha256: String,
+    pub generated_at: DateTime<Utc>,
+    /// SHA-256 of the `sha256sum` lines of every file, in path order
+    pub root_hash: String,
+    /// Path relative to the root filesystem, e.g. `usr/bin/ls`, to its SHA-256
+    pub files: BTreeMap<String, String>,
+}
+
+impl ContentManifest {
+    pub fn new(image_sha256: String, files: BTreeMap<String, String>) -> Self {
+        Self {
+            image_sha256,
+            generated_at: Utc::now(),
+            root_hash: root_hash(&files),
+            files,
+        }
+    }
+
+    /// Manifest path for an image file
+    pub fn path_for(image: &Path) -> PathBuf {
+        PathBuf::from(format!("{}{}", image.display(), CONTENT_MANIFEST_SUFFIX))
+    }
+
+    pub fn load(path: &Path) -> Result<Self> {
+        let content = std::fs::read_to_string(path)?;
+        Ok(serde_json::from_str(&content)?)
+    }
+
+    pub fn save(&self, path: &Path) -> Result<()> {
+        std::fs::write(path, serde_json::to_string_pretty(self)?)?;
+        Ok(())
+    }
+
+    /// The manifest of `image`, generated and saved next to it when missing or stale
+    pub async fn for_image(image: &Path) -> Result<Self> {
+        let image_sha256 = provenance::sha256_file_cached(image)?;
+        let path = Self::path_for(image);
+        if let Ok(manifest) = Self::load(&path) {
+            if manifest.image_sha256 == image_sha256 {
+                return Ok(manifest);
+            }
+        }
+        info!("Hashing the contents of {}", image.display());
+        let temp_dir = tempfile::tempdir()?;
+        let raw_path = temp_dir.path().join("image.raw");
+        let mount_point = temp_dir.path().join("mount");
+        std::fs::create_dir_all(&mount_point)?;
+        QemuUtils::convert_to_raw(image, raw_path.as_path()).await?;
+        let loop_device = QemuUtils::mount_raw_image(&raw_path, &mount_point).await?;
+        let files = hash_tree(&mount_point);
+        let _ = QemuUtils::unmount_image(&mount_point, &loop_device).await;
+        let manifest = Self::new(image_sha256, files?);
+        manifest.save(&path)?;
+        info!(
+            "Content manifest of {} file(s) written to {}",
+            manifest.files.len(),
+            path.display()
+        );
+        Ok(manifest)
+    }
+
+    /// `count` paths picked by `seed`; every path when `count` is 0 or covers them all
+    pub fn sample(&self, count: usize, seed: &str) -> Vec<&str> {
+        let mut paths: Vec<&str> = self.files.keys().map(String::as_str).collect();
+        if count == 0 || count >= paths.len() {
+            return paths;
+        }
+        paths.sort_by_cached_key(|path| Sha256::digest(format!("{}{}", seed, path).as_bytes()));
+        paths.truncate(count);
+        paths.sort_unstable();
+        paths
+    }
+
+    /// Compare digests `found` on the target with the manifest for `checked` paths; with
+    /// `full`, `found` is every file on the target and the root hashes are compared too
+    pub fn verify(
+        &self,
+        checked: &[&str],
+        found: &BTreeMap<String, String>,
+        full: bool,
+        seed: &str,
+    ) -> ContentReport {
+        let mut report = ContentReport {
+            seed: seed.to_string(),
+            checked: checked.len(),
+            ..Default::default()
+        };
+        for path in checked {
+            match (self.files.get(*path), found.get(*path)) {
+                (Some(expected), Some(actual)) if expected == actual => report.matched += 1,
+                (_, Some(_)) => report.mismatched.push(path.to_string()),
+                (_, None) => report.missing.push(path.to_string()),
+            }
+        }
+        if full {
+            report.unexpected = found
+                .keys()
+                .filter(|path| !self.files.contains_key(*path))
+                .cloned()
+                .collect();
+            report.root_hash_matches = Some(root_hash(found) == self.root_hash);
+        }
+        report
+    }
+}
+
+/// Outcome of comparing a deployed root filesystem with its manifest
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ContentReport {
+    /// Seed the sample was picked with
+    pub seed: String,
+    pub checked: usize,
+    pub matched: usize,
+    /// Files whose content differs from the golden image
+    pub mismatched: Vec<String>,
+    pub missing: Vec<String>,
+    /// Files on the target that the golden image does not have; full checks only
+    pub unexpected: Vec<String>,
+    /// Whether the target's root hash equals the manifest's; full checks only
+    pub root_hash_matches: Option<bool>,
+}
+
+impl ContentReport {
+    pub fn is_clean(&self) -> bool {
+        self.mismatched.is_empty()
+            && self.missing.is_empty()
+            && self.unexpected.is_empty()
+            && self.root_hash_matches != Some(false)
+    }
+
+    pub fn summary_lines(&self) -> Vec<String> {
+        let mut lines = vec![format!(
+            "{} of {} file(s) match the golden image (seed {})",
+            self.matched, self.checked, self.seed
+        )];
+        for (label, paths) in [
+            ("differs", &self.mismatched),
+            ("missing", &self.missing),
+            ("not in the image", &self.unexpected),
+        ] {
+            lines.extend(paths.iter().map(|path| format!("  {}: /{}", label, path)));
+        }
+        match self.root_hash_matches {
+            Some(true) => lines.push("Root hash matches".to_string()),
+            Some(false) => lines.push("Root hash differs".to_string()),
+            None => {}
+        }
+        lines
+    }
+
+    /// `Err` naming the first differences when the check found any
+    pub fn require_clean(&self) -> Result<()> {
+        if self.is_clean() {
+            return Ok(());
+        }
+        let problems: Vec<String> = self
+            .mismatched
+            .iter()
+            .chain(&self.missing)
+            .chain(&self.unexpected)
+            .take(5)
+            .map(|path| format!("/{}", path))
+            .collect();
+        Err(AutoInstallError::ValidationError(format!(
+            "Deployed content does not match the golden image: {} differ, {} missing, {} unexpected ({})",
+            self.mismatched.len(),
+            self.missing.len(),
+            self.unexpected.len(),
+            if problems.is_empty() {
+                "root hash differs".to_string()
+            } else {
+                problems.join(", ")
+            }
+        )))
+    }
+}
+
+/// Verify the root filesystem mounted at `mount` on the target against `manifest`, hashing
+/// `sample` files picked with a fresh seed, or every file when `sample` is 0
+pub async fn verify_deployed(
+    ssh: &mut SshClient,
+    mount: &str,
+    manifest: &ContentManifest,
+    sample: usize,
+) -> Result<ContentReport> {
+    let seed = uuid::Uuid::new_v4().simple().to_string();
+    let full = sample == 0;
+    let checked = manifest.sample(sample, &seed);
+    let mut found = BTreeMap::new();
+    if full {
+        let output = ssh
+            .execute_with_output(&build_full_hash_command(mount))
+            .await?;
+        found.extend(parse_sha256sum(&output));
+    } else {
+        for batch in checked.chunks(SAMPLE_BATCH) {
+            let output = ssh
+                .execute_with_output(&build_hash_command(mount, batch))
+                .await?;
+            found.extend(parse_sha256sum(&output));
+        }
+    }
+    Ok(manifest.verify(&checked, &found, full, &seed))
+}
+
+/// Command hashing `paths` relative to `mount`; missing files print nothing
+pub fn build_hash_command(mount: &str, paths: &[&str]) -> String {
+    let quoted: Vec<String> = paths.iter().map(|path| shell_quote(path)).collect();
+    format!(
+        "cd {} && sha256sum -- {} 2>/dev/null; true",
+        shell_quote(mount),
+        quoted.join(" ")
+    )
+}
+
+/// Command hashing every regular file under [`HASHED_DIRS`] of `mount`
+pub fn build_full_hash_command(mount: &str) -> String {
+    format!(
+        "cd {} && find {} -xdev -type f -print0 2>/dev/null | xargs -0 -r sha256sum",
+        shell_quote(mount),
+        HASHED_DIRS.join(" ")
+    )
+}
+
+/// Path to digest of `sha256sum` output lines
+pub fn parse_sha256sum(output: &str) -> BTreeMap<String, String> {
+    output
+        .lines()
+        .filter_map(|line| {
+            let (hash, path) = line.trim_start_matches('\\').split_once(' ')?;
+            let path = path.strip_prefix(' ').or(path.strip_prefix('*'))?;
+            Some((path.to_string(), hash.to_string()))
+        })
+        .collect()
+}
+
+/// Digests of the regular files under [`HASHED_DIRS`] of a root filesystem at `root`
+pub fn hash_tree(root: &Path) -> Result<BTreeMap<String, String>> {
+    let mut files = BTreeMap::new();
+    for dir in HASHED_DIRS {
+        for entry in walkdir::WalkDir::new(root.join(dir)).same_file_system(true) {
+            let entry = match entry {
+                Ok(entry) => entry,
+                // A directory the image does not have
+                Err(e) if e.depth() == 0 => break,
+                Err(e) => return Err(AutoInstallError::IoError(e.into())),
+            };
+            if !entry.file_type().is_file() {
+                continue;
+            }
+            let relative = entry
+                .path()
+                .strip_prefix(root)
+                .unwrap_or(entry.path())
+                .to_string_lossy()
+                .into_owned();
+            files.insert(relative, provenance::sha256_file(entry.path())?);
+        }
+    }
+    Ok(files)
+}
+
+/// SHA-256 of `sha256sum`-style lines for `files` in path order
+fn root_hash(files: &BTreeMap<String, String>) -> String {
+    let mut hasher = Sha256::new();
+    for (path, hash) in files {
+        hasher.update(format!("{}  {}\n", hash, path).as_bytes());
+    }
+    format!("{:x}", hasher.finalize())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_manifest_sampling_and_verification() {
+        let dir = tempfile::TempDir::new().unwrap();
+        std::fs::create_dir_all(dir.path().join("usr/bin")).unwrap();
+        std::fs::create_dir_all(dir.path().join("etc")).unwrap();
+        for name in ["ls", "cat", "cp"] {
+            std::fs::write(dir.path().join("usr/bin").join(name), name).unwrap();
+        }
+        std::fs::write(dir.path().join("etc/hostname"), "web-01").unwrap();
+        let manifest = ContentManifest::new("abc".to_string(), hash_tree(dir.path()).unwrap());
+        assert_eq!(
+            manifest.files.keys().collect::<Vec<_>>(),
+            ["usr/bin/cat", "usr/bin/cp", "usr/bin/ls"]
+        );
+
+        let sample = manifest.sample(2, "seed");
+        assert_eq!(sample.len(), 2);
+        assert_eq!(sample, manifest.sample(2, "seed"));
+        assert_eq!(manifest.sample(0, "seed").len(), 3);
+        assert!(build_hash_command("/mnt/target", &sample)
+            .starts_with("cd /mnt/target && sha256sum -- usr/bin/"));
+
+        // What sha256sum prints on the target, with one file altered and one added
+        let mut output = String::new();
+        for (path, hash) in &manifest.files {
+            output.push_str(&format!("{}  {}\n", hash, path));
+        }
+        let clean = parse_sha256sum(&output);
+        let report = manifest.verify(&manifest.sample(0, "s"), &clean, true, "s");
+        assert!(report.is_clean());
+        assert_eq!(report.root_hash_matches, Some(true));
+
+        let mut tampered = clean.clone();
+        tampered.insert("usr/bin/ls".to_string(), "f00".to_string());
+        tampered.insert("usr/bin/nc".to_string(), "ba5".to_string());
+        tampered.remove("usr/bin/cp");
+        let report = manifest.verify(&manifest.sample(0, "s"), &tampered, true, "s");
+        assert_eq!(report.mismatched, ["usr/bin/ls"]);
+        assert_eq!(report.missing, ["usr/bin/cp"]);
+        assert_eq!(report.unexpected, ["usr/bin/nc"]);
+        assert!(report
+            .require_clean()
+            .unwrap_err()
+            .to_string()
+            .contains("1 differ, 1 missing, 1 unexpected (/usr/bin/ls"));
+    }
+}
@@ -1,5 +1,5 @@
 // file: src/image/manager.rs
-// version: 1.0.1
+// version: 1.3.0
 // guid: n4o5p6q7-r8s9-0123-4567-890123nopqrs
 
 //! Image lifecycle management
@@ -12,6 +12,15 @@ use std::path::{Path, PathBuf};
 use tokio::fs;
 use tracing::{debug, info, warn};
 
+/// Files written next to an image that are published with it
+const PUBLISHED_SIDECARS: [&str; 5] = [
+    crate::security::provenance::PROVENANCE_SUFFIX,
+    crate::image::builder::environment::BUILD_ENV_SUFFIX,
+    crate::image::integrity::CONTENT_MANIFEST_SUFFIX,
+    ".packages.txt",
+    ".sha256",
+];
+
 /// Manager for golden image lifecycle
 pub struct ImageManager {
     images_dir: PathBuf,
@@ -62,7 +71,7 @@ impl ImageManager {
         }
 
         // Sort by creation date (newest first)
-        images.sort_by(|a, b| b.created_at.cmp(&a.created_at));
+        images.sort_by_key(|image| std::cmp::Reverse(image.created_at));
 
         Ok(images)
     }
@@ -185,23 +194,6 @@ impl ImageManager {
         Ok(deleted_count)
     }
 
-    /// Calculate total disk usage of all images
-    pub async fn calculate_total_usage(&self) -> Result<u64> {
-        let images = self.list_images(None).await?;
-        Ok(images.iter().map(|img| img.size_bytes).sum())
-    }
-
-    /// Get image by ID
-    pub async fn get_image(&self, image_id: &str) -> Result<Option<ImageInfo>> {
-        let info_path = self.images_dir.join(format!("{}.json", image_id));
-
-        if info_path.exists() {
-            Ok(Some(self.load_image_info(&info_path).await?))
-        } else {
-            Ok(None)
-        }
-    }
-
     /// Load image info from JSON file
     async fn load_image_info<P: AsRef<Path>>(&self, path: P) -> Result<ImageInfo> {
         let content = fs::read_to_string(path)
@@ -229,32 +221,62 @@ impl ImageManager {
         Ok(())
     }
 
-    /// Calculate checksum of image file
-    pub async fn calculate_checksum<P: AsRef<Path>>(&self, path: P) -> Result<String> {
-        use sha2::{Digest, Sha256};
-        use tokio::io::AsyncReadExt;
-
-        let mut file = fs::File::open(path)
-            .await
-            .map_err(crate::error::AutoInstallError::IoError)?;
-
-        let mut hasher = Sha256::new();
-        let mut buffer = [0u8; 8192];
+    /// Copy `image` into the images directory with the provenance, build lockfile, content
+    /// manifest, package list and checksum sidecars found next to it
+    ///
+    /// Every file is copied under a temporary name and renamed, so a reader never sees a
+    /// partial image. With `latest`, a symlink of that name is pointed at the published image.
+    pub async fn publish(&self, image: &Path, latest: Option<&str>) -> Result<PathBuf> {
+        let file_name = image.file_name().ok_or_else(|| {
+            crate::error::AutoInstallError::ImageError(format!(
+                "Not an image file: {}",
+                image.display()
+            ))
+        })?;
+        self.ensure_images_dir().await?;
 
-        loop {
-            let bytes_read = file
-                .read(&mut buffer)
+        let published = self.images_dir.join(file_name);
+        let mut files = vec![(image.to_path_buf(), published.clone())];
+        for suffix in PUBLISHED_SIDECARS {
+            let sidecar = PathBuf::from(format!("{}{}", image.display(), suffix));
+            if sidecar.exists() {
+                files.push((
+                    sidecar,
+                    PathBuf::from(format!("{}{}", published.display(), suffix)),
+                ));
+            }
+        }
+        for (from, to) in &files {
+            let partial = PathBuf::from(format!("{}.partial", to.display()));
+            fs::copy(from, &partial)
                 .await
                 .map_err(crate::error::AutoInstallError::IoError)?;
+            fs::rename(&partial, to)
+                .await
+                .map_err(crate::error::AutoInstallError::IoError)?;
+            debug!("Published {}", to.display());
+        }
 
-            if bytes_read == 0 {
-                break;
-            }
-
-            hasher.update(&buffer[..bytes_read]);
+        if let Some(latest) = latest {
+            let link = self.images_dir.join(latest);
+            let partial = PathBuf::from(format!("{}.partial", link.display()));
+            let _ = fs::remove_file(&partial).await;
+            fs::symlink(file_name, &partial)
+                .await
+                .map_err(crate::error::AutoInstallError::IoError)?;
+            fs::rename(&partial, &link)
+                .await
+                .map_err(crate::error::AutoInstallError::IoError)?;
+            info!("{} now points at {}", link.display(), published.display());
         }
 
-        Ok(format!("{:x}", hasher.finalize()))
+        info!(
+            "Published {} to {} ({} file(s))",
+            image.display(),
+            published.display(),
+            files.len()
+        );
+        Ok(published)
     }
 }
 
@@ -339,6 +361,40 @@ mod tests {
         assert_eq!(arm64_images.len(), 1);
         assert_eq!(arm64_images[0].architecture, Architecture::Arm64);
 
+        // Both images were just created, so neither is old enough to clean up
+        assert!(manager.find_old_images(1).await?.is_empty());
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_publish_copies_sidecars_and_links_latest() -> Result<()> {
+        let build_dir = TempDir::new().unwrap();
+        let images_dir = TempDir::new().unwrap();
+        let image = build_dir.path().join("web-20261012.qcow2");
+        std::fs::write(&image, b"qcow2")?;
+        std::fs::write(
+            build_dir.path().join("web-20261012.qcow2.buildenv.yaml"),
+            b"env",
+        )?;
+
+        let manager = ImageManager::with_images_dir(images_dir.path());
+        let published = manager.publish(&image, Some("web-latest.qcow2")).await?;
+        assert_eq!(published, images_dir.path().join("web-20261012.qcow2"));
+        assert!(images_dir
+            .path()
+            .join("web-20261012.qcow2.buildenv.yaml")
+            .exists());
+        assert!(!images_dir
+            .path()
+            .join("web-20261012.qcow2.provenance.json")
+            .exists());
+        let latest = images_dir.path().join("web-latest.qcow2");
+        assert_eq!(std::fs::read(&latest)?, b"qcow2");
+        assert_eq!(
+            std::fs::read_link(&latest)?,
+            PathBuf::from("web-20261012.qcow2")
+        );
         Ok(())
     }
 }
@@ -1,15 +1,16 @@
 // file: src/image/mod.rs
-// version: 1.0.0
+// version: 1.6.0
 // guid: k1l2m3n4-o5p6-7890-1234-567890klmnop
 
 //! Image management module for Ubuntu AutoInstall Agent
 
 pub mod builder;
-pub mod customizer;
 pub mod deployer;
+pub mod integrity;
 pub mod manager;
+pub mod overlay;
+pub mod vsphere;
+pub mod writer;
 
 pub use builder::ImageBuilder;
-pub use customizer::ImageCustomizer;
-pub use deployer::ImageDeployer;
-pub use manager::ImageManager;
+pub use writer::VerifiedWriteOptions;
@@ -0,0 +1,355 @@
+// file: src/image/overlay.rs
+// version: 1.1.0
+// guid: 7f3a9d52-1e8b-4c64-a5d7-2b9e6c0f4a13
+
+//! Deploy-time overlay of site-specific files
+//!
+//! `deploy --overlay DIR` copies the tree under `DIR` into the deployed filesystem after the
+//! image is written and before the first boot, for files that do not belong in the golden
+//! image (site certificates, monitoring config, MOTD). Files keep their mode from the overlay
+//! directory and are owned by root unless `DIR/.overlay.yaml` says otherwise:
+//!
+//! ```yaml
+//! defaults: {owner: root, group: root}
+//! paths:
+//!   etc/ssl/private/: {mode: "0600"}
+//!   home/ops/: {owner: ops, group: ops}
+//! ```
+//!
+//! A key ending in `/` applies to everything below it; the longest matching key wins. Owners
+//! are resolved by `chown` inside the target, so users created by the deployment can be used.
+//! The applied files with their checksums are recorded in a manifest written to the target and
+//! next to the deployment's provenance.
+
+use crate::error::AutoInstallError;
+use crate::network::SshClient;
+use crate::security::provenance;
+use crate::utils::shell_quote;
+use crate::Result;
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::os::unix::fs::PermissionsExt;
+use std::path::{Path, PathBuf};
+use tracing::info;
+
+/// Ownership and mode rules inside the overlay directory; never copied to the target
+pub const OVERLAY_RULES_FILE: &str = ".overlay.yaml";
+/// Manifest location inside the deployed system
+pub const TARGET_MANIFEST_PATH: &str = "/var/lib/ubuntu-autoinstall-agent/overlay-manifest.json";
+
+/// Owner, group and mode for matching paths; unset fields fall through to the defaults
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct OverlayRule {
+    pub owner: Option<String>,
+    pub group: Option<String>,
+    /// Octal mode such as `"0640"`
+    pub mode: Option<String>,
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct OverlayRules {
+    pub defaults: OverlayRule,
+    pub paths: BTreeMap<String, OverlayRule>,
+}
+
+impl OverlayRules {
+    /// Owner, group and mode for `path`, with `fallback_mode` from the source file
+    fn resolve(
+        &self,
+        path: &str,
+        is_dir: bool,
+        fallback_mode: u32,
+    ) -> Result<(String, String, u32)> {
+        let rule = self
+            .paths
+            .iter()
+            .filter(|(key, _)| {
+                let key = key.trim_start_matches('/');
+                match key.strip_suffix('/') {
+                    Some(dir) => path == dir || path.starts_with(key),
+                    None => path == key,
+                }
+            })
+            .max_by_key(|(key, _)| key.len())
+            .map(|(_, rule)| rule);
+        let pick = |f: fn(&OverlayRule) -> &Option<String>| {
+            rule.and_then(|r| f(r).clone())
+                .or_else(|| f(&self.defaults).clone())
+        };
+        // A directory rule's mode is meant for the files below it, not the directory itself
+        let mode = match pick(|r| &r.mode) {
+            Some(_) if is_dir => fallback_mode,
+            Some(mode) => u32::from_str_radix(&mode, 8).map_err(|_| {
+                AutoInstallError::ValidationError(format!(
+                    "{}: mode {:?} for {} is not octal",
+                    OVERLAY_RULES_FILE, mode, path
+                ))
+            })?,
+            None => fallback_mode,
+        };
+        Ok((
+            pick(|r| &r.owner).unwrap_or_else(|| "root".to_string()),
+            pick(|r| &r.group).unwrap_or_else(|| "root".to_string()),
+            mode & 0o7777,
+        ))
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum OverlayKind {
+    Directory,
+    File,
+    Symlink,
+}
+
+/// One path of the overlay as it is applied
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct OverlayEntry {
+    /// Path relative to the target root, without a leading `/`
+    pub path: String,
+    pub kind: OverlayKind,
+    pub owner: String,
+    pub group: String,
+    /// Octal, e.g. `0644`
+    pub mode: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub sha256: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub link_target: Option<String>,
+}
+
+/// Record of an applied overlay
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct OverlayManifest {
+    pub source: String,
+    pub applied_at: DateTime<Utc>,
+    pub entries: Vec<OverlayEntry>,
+}
+
+/// A scanned overlay directory
+#[derive(Debug, Clone)]
+pub struct Overlay {
+    pub root: PathBuf,
+    /// Parents come before their children
+    pub entries: Vec<OverlayEntry>,
+}
+
+impl Overlay {
+    /// Scan `root` and resolve every path against its `.overlay.yaml`
+    pub fn scan(root: &Path) -> Result<Self> {
+        if !root.is_dir() {
+            return Err(AutoInstallError::ValidationError(format!(
+                "Overlay {} is not a directory",
+                root.display()
+            )));
+        }
+        let rules_path = root.join(OVERLAY_RULES_FILE);
+        let rules: OverlayRules = if rules_path.exists() {
+            serde_yaml::from_str(&std::fs::read_to_string(&rules_path)?)?
+        } else {
+            OverlayRules::default()
+        };
+        let mut entries = Vec::new();
+        Self::walk(root, root, &rules, &mut entries)?;
+        Ok(Self {
+            root: root.to_path_buf(),
+            entries,
+        })
+    }
+
+    fn walk(
+        root: &Path,
+        dir: &Path,
+        rules: &OverlayRules,
+        entries: &mut Vec<OverlayEntry>,
+    ) -> Result<()> {
+        let mut children: Vec<_> = std::fs::read_dir(dir)?.collect::<std::io::Result<_>>()?;
+        children.sort_by_key(|c| c.file_name());
+        for child in children {
+            let path = child.path();
+            let relative = path
+                .strip_prefix(root)
+                .unwrap_or(&path)
+                .to_string_lossy()
+                .to_string();
+            if relative == OVERLAY_RULES_FILE {
+                continue;
+            }
+            if relative.contains(|c: char| c.is_control()) {
+                return Err(AutoInstallError::ValidationError(format!(
+                    "Overlay path {:?} contains control characters",
+                    relative
+                )));
+            }
+            let metadata = std::fs::symlink_metadata(&path)?;
+            let file_type = metadata.file_type();
+            let kind = if file_type.is_symlink() {
+                OverlayKind::Symlink
+            } else if file_type.is_dir() {
+                OverlayKind::Directory
+            } else if file_type.is_file() {
+                OverlayKind::File
+            } else {
+                return Err(AutoInstallError::ValidationError(format!(
+                    "Overlay path {} is not a file, directory or symlink",
+                    relative
+                )));
+            };
+            let (owner, group, mode) = rules.resolve(
+                &relative,
+                kind == OverlayKind::Directory,
+                metadata.permissions().mode(),
+            )?;
+            entries.push(OverlayEntry {
+                path: relative.clone(),
+                kind,
+                owner,
+                group,
+                mode: format!("{:04o}", mode),
+                sha256: match kind {
+                    OverlayKind::File => Some(provenance::sha256_file(&path)?),
+                    _ => None,
+                },
+                link_target: match kind {
+                    OverlayKind::Symlink => {
+                        Some(std::fs::read_link(&path)?.to_string_lossy().to_string())
+                    }
+                    _ => None,
+                },
+            });
+            if kind == OverlayKind::Directory {
+                Self::walk(root, &path, rules, entries)?;
+            }
+        }
+        Ok(())
+    }
+
+    /// Commands run for `entry` in the filesystem mounted at `mount`; files are uploaded
+    /// before these run
+    pub fn build_entry_commands(entry: &OverlayEntry, mount: &str) -> Vec<String> {
+        let mount = mount.trim_end_matches('/');
+        let target = shell_quote(&format!("{}/{}", mount, entry.path));
+        let inside = shell_quote(&format!("/{}", entry.path));
+        let mut commands = Vec::new();
+        match entry.kind {
+            OverlayKind::Directory => commands.push(format!("mkdir -p {}", target)),
+            OverlayKind::File => {}
+            OverlayKind::Symlink => commands.push(format!(
+                "ln -sfn {} {}",
+                shell_quote(entry.link_target.as_deref().unwrap_or("")),
+                target
+            )),
+        }
+        commands.push(format!(
+            "chroot {} chown -h {}:{} {}",
+            mount,
+            shell_quote(&entry.owner),
+            shell_quote(&entry.group),
+            inside
+        ));
+        if entry.kind != OverlayKind::Symlink {
+            commands.push(format!("chmod {} {}", entry.mode, target));
+        }
+        commands
+    }
+
+    /// Copy the overlay into the filesystem mounted at `mount` and write the manifest there
+    pub async fn apply(&self, ssh: &mut SshClient, mount: &str) -> Result<OverlayManifest> {
+        info!(
+            "Applying overlay {} ({} paths)",
+            self.root.display(),
+            self.entries.len()
+        );
+        let mount = mount.trim_end_matches('/');
+        for entry in &self.entries {
+            if entry.kind == OverlayKind::File {
+                let local = self.root.join(&entry.path);
+                ssh.upload_file(
+                    &local.to_string_lossy(),
+                    &format!("{}/{}", mount, entry.path),
+                )
+                .await?;
+            }
+            for cmd in Self::build_entry_commands(entry, mount) {
+                ssh.execute(&cmd).await?;
+            }
+        }
+        let manifest = OverlayManifest {
+            source: self.root.display().to_string(),
+            applied_at: Utc::now(),
+            entries: self.entries.clone(),
+        };
+        let full = format!("{}{}", mount, TARGET_MANIFEST_PATH);
+        ssh.execute(&format!(
+            "mkdir -p {} && cat > {} << 'EOF'\n{}\nEOF",
+            &full[..full.rfind('/').unwrap_or(0)],
+            full,
+            serde_json::to_string_pretty(&manifest)?
+        ))
+        .await?;
+        Ok(manifest)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_scan_applies_rules() {
+        let dir = tempfile::tempdir().unwrap();
+        let root = dir.path();
+        std::fs::create_dir_all(root.join("etc/ssl/private")).unwrap();
+        std::fs::create_dir_all(root.join("home/ops")).unwrap();
+        std::fs::write(root.join("etc/motd"), "site\n").unwrap();
+        std::fs::write(root.join("etc/ssl/private/site.key"), "key").unwrap();
+        std::fs::write(root.join("home/ops/.bashrc"), "alias l=ls\n").unwrap();
+        std::fs::set_permissions(
+            root.join("etc/motd"),
+            std::fs::Permissions::from_mode(0o644),
+        )
+        .unwrap();
+        std::os::unix::fs::symlink("motd", root.join("etc/issue.net")).unwrap();
+        std::fs::write(
+            root.join(OVERLAY_RULES_FILE),
+            "paths:\n  etc/ssl/private/: {mode: \"0600\"}\n  home/ops/: {owner: ops, group: ops}\n",
+        )
+        .unwrap();
+
+        let overlay = Overlay::scan(root).unwrap();
+        let paths: Vec<&str> = overlay.entries.iter().map(|e| e.path.as_str()).collect();
+        assert_eq!(
+            paths,
+            [
+                "etc",
+                "etc/issue.net",
+                "etc/motd",
+                "etc/ssl",
+                "etc/ssl/private",
+                "etc/ssl/private/site.key",
+                "home",
+                "home/ops",
+                "home/ops/.bashrc"
+            ]
+        );
+        let by_path = |p: &str| overlay.entries.iter().find(|e| e.path == p).unwrap();
+        assert_eq!(by_path("etc/motd").mode, "0644");
+        assert_eq!(by_path("etc/ssl/private/site.key").mode, "0600");
+        assert_eq!(by_path("home/ops/.bashrc").owner, "ops");
+        assert_eq!(by_path("home").owner, "root");
+        assert!(by_path("etc/motd").sha256.is_some());
+
+        let cmds = Overlay::build_entry_commands(by_path("etc/issue.net"), "/mnt/target/");
+        assert_eq!(
+            cmds,
+            [
+                "ln -sfn motd /mnt/target/etc/issue.net",
+                "chroot /mnt/target chown -h root:root /etc/issue.net"
+            ]
+        );
+    }
+}
@@ -0,0 +1,523 @@
+// file: src/image/vsphere.rs
+// version: 1.1.0
+// guid: 9d4a7e12-6c58-4b3f-a2e1-8f5c0b7d3a64
+
+//! Deployment of a golden image as a VM on vCenter/ESXi
+//!
+//! The vSphere API is driven through `govc`, which handles the NFC upload of disks and OVAs.
+//! A qcow2 golden image is converted to a stream-optimized VMDK first; a `.vmdk` or `.ova` is
+//! imported as it is. The VM gets the resources and port groups of the target config's
+//! `vsphere:` section, and the rest of the target config (hostname, network, users, packages)
+//! reaches cloud-init's VMware datasource as `guestinfo.metadata` and `guestinfo.userdata`.
+//! Once the VM reports an address, the deployment is verified over SSH like a bare-metal one:
+//! cloud-init must have finished cleanly and the hostname must match.
+
+use crate::config::{TargetConfig, VsphereConfig};
+use crate::error::AutoInstallError;
+use crate::network::ssh_installer::probes;
+use crate::network::transport::base64_encode;
+use crate::network::SshClient;
+use crate::Result;
+use serde_json::json;
+use sha2::{Digest, Sha256};
+use std::path::{Path, PathBuf};
+use tokio::process::Command;
+use tracing::{debug, info, warn};
+
+/// How long the VM may take to boot and report an address through VMware Tools
+const IP_WAIT: &str = "10m";
+
+/// Label vSphere gives the first disk of a VM
+const FIRST_DISK: &str = "Hard disk 1";
+
+/// Form in which the golden image is handed to vSphere
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum VsphereImage {
+    /// qcow2 image converted to a stream-optimized VMDK before the upload
+    Qcow2(PathBuf),
+    /// Stream-optimized VMDK uploaded as the VM's disk
+    Vmdk(PathBuf),
+    /// Appliance whose descriptor creates the VM
+    Ova(PathBuf),
+}
+
+impl VsphereImage {
+    pub fn from_path(path: &Path) -> Self {
+        match path.extension().and_then(|e| e.to_str()) {
+            Some("vmdk") => VsphereImage::Vmdk(path.to_path_buf()),
+            Some("ova") => VsphereImage::Ova(path.to_path_buf()),
+            _ => VsphereImage::Qcow2(path.to_path_buf()),
+        }
+    }
+}
+
+/// Where a deployment ended up
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct VsphereDeployment {
+    pub vm_name: String,
+    pub ip_address: String,
+}
+
+/// Creates and verifies one VM from a target config
+pub struct VsphereDeployer<'a> {
+    config: &'a TargetConfig,
+    vsphere: &'a VsphereConfig,
+    vm_name: String,
+    work_dir: PathBuf,
+}
+
+impl<'a> VsphereDeployer<'a> {
+    /// Deployer for `config`, which must have a `vsphere:` section
+    pub fn new(config: &'a TargetConfig, vm_name: Option<&str>, work_dir: &Path) -> Result<Self> {
+        let vsphere = config.vsphere.as_ref().ok_or_else(|| {
+            AutoInstallError::ConfigError(format!(
+                "Target config for {} has no vsphere: section",
+                config.hostname
+            ))
+        })?;
+        crate::logging::redact::register(&vsphere.password);
+        Ok(Self {
+            config,
+            vsphere,
+            vm_name: vm_name.unwrap_or(&config.hostname).to_string(),
+            work_dir: work_dir.to_path_buf(),
+        })
+    }
+
+    pub fn vm_name(&self) -> &str {
+        &self.vm_name
+    }
+
+    /// `govc` invocations the deployment of `image` runs, in order; the guestinfo values
+    /// are abbreviated
+    pub fn plan(&self, image: &VsphereImage) -> Vec<String> {
+        let mut plan = Vec::new();
+        if let VsphereImage::Qcow2(path) = image {
+            plan.push(format!(
+                "qemu-img convert -O vmdk -o subformat=streamOptimized {} {}",
+                path.display(),
+                self.vmdk_path().display()
+            ));
+        }
+        plan.extend(
+            self.commands(image)
+                .iter()
+                .map(|args| format!("govc {}", args.join(" "))),
+        );
+        plan.push(format!("govc vm.ip -wait={} -v4 {}", IP_WAIT, self.vm_name));
+        plan
+    }
+
+    /// Import the image, create and configure the VM and power it on; returns once the VM
+    /// reports an IPv4 address
+    pub async fn deploy(&self, image: &VsphereImage) -> Result<VsphereDeployment> {
+        let image = match image {
+            VsphereImage::Qcow2(path) => {
+                self.convert_to_vmdk(path).await?;
+                VsphereImage::Vmdk(self.vmdk_path())
+            }
+            other => other.clone(),
+        };
+
+        let commands = self.commands(&image);
+        let (import, configure) = commands.split_at(1);
+        info!(
+            "Uploading the image to datastore {}",
+            self.vsphere.datastore
+        );
+        self.govc(&import[0]).await?;
+
+        // From here on a VM exists; leave it for inspection when a step fails
+        let configured = async {
+            for args in configure {
+                self.govc(args).await?;
+            }
+            info!("VM {} powered on; waiting for an address", self.vm_name);
+            let output = self
+                .govc(&[
+                    "vm.ip".to_string(),
+                    format!("-wait={}", IP_WAIT),
+                    "-v4".to_string(),
+                    self.vm_name.clone(),
+                ])
+                .await?;
+            parse_ip(&output).ok_or_else(|| {
+                AutoInstallError::NetworkError(format!(
+                    "VM {} reported no IPv4 address",
+                    self.vm_name
+                ))
+            })
+        }
+        .await;
+        match configured {
+            Ok(ip_address) => Ok(VsphereDeployment {
+                vm_name: self.vm_name.clone(),
+                ip_address,
+            }),
+            Err(e) => {
+                warn!(
+                    "VM {} was left in place for inspection; remove it with `govc vm.destroy {}`",
+                    self.vm_name, self.vm_name
+                );
+                Err(e)
+            }
+        }
+    }
+
+    /// Check the booted VM over SSH as the first sudo user: cloud-init finished without
+    /// errors, the hostname is the configured one and the `verification:` probes pass
+    pub async fn verify(&self, deployment: &VsphereDeployment) -> Result<()> {
+        let user = self
+            .config
+            .users
+            .iter()
+            .find(|u| u.sudo)
+            .unwrap_or(&self.config.users[0]);
+        let mut ssh = SshClient::new();
+        ssh.connect(&deployment.ip_address, &user.name).await?;
+
+        let status = ssh
+            .execute_with_output("cloud-init status --wait >/dev/null 2>&1; cloud-init status")
+            .await?;
+        if !status.contains("status: done") {
+            ssh.disconnect();
+            return Err(AutoInstallError::InstallationError(format!(
+                "cloud-init did not finish cleanly on {}: {}",
+                self.vm_name,
+                status.trim()
+            )));
+        }
+        let hostname = ssh.execute_with_output("hostname").await?;
+        let probe_results = if self.config.verification.probes.is_empty() {
+            Vec::new()
+        } else {
+            probes::run_probes(&mut ssh, &self.config.verification).await?
+        };
+        ssh.disconnect();
+        if hostname.trim() != self.config.hostname {
+            return Err(AutoInstallError::InstallationError(format!(
+                "VM {} came up as {} instead of {}",
+                self.vm_name,
+                hostname.trim(),
+                self.config.hostname
+            )));
+        }
+        probes::require_passed(&probe_results)?;
+        info!(
+            "VM {} verified: cloud-init done, hostname {}",
+            self.vm_name, self.config.hostname
+        );
+        Ok(())
+    }
+
+    fn vmdk_path(&self) -> PathBuf {
+        self.work_dir.join(format!("{}.vmdk", self.vm_name))
+    }
+
+    async fn convert_to_vmdk(&self, qcow2: &Path) -> Result<()> {
+        tokio::fs::create_dir_all(&self.work_dir).await?;
+        info!("Converting {} to a stream-optimized VMDK", qcow2.display());
+        let output = Command::new("qemu-img")
+            .args(["convert", "-O", "vmdk", "-o", "subformat=streamOptimized"])
+            .arg(qcow2)
+            .arg(self.vmdk_path())
+            .output()
+            .await?;
+        if !output.status.success() {
+            return Err(AutoInstallError::ImageError(format!(
+                "Converting {} to VMDK failed: {}",
+                qcow2.display(),
+                String::from_utf8_lossy(&output.stderr).trim()
+            )));
+        }
+        Ok(())
+    }
+
+    /// Arguments of each `govc` call: the import first, then everything up to power-on
+    fn commands(&self, image: &VsphereImage) -> Vec<Vec<String>> {
+        let vm = self.vm_name.clone();
+        let mac = guest_mac(&self.config.hostname);
+        let primary = self.vsphere.networks[0].clone();
+        let mut commands = Vec::new();
+        match image {
+            VsphereImage::Ova(path) => {
+                commands.push(vec![
+                    "import.ova".to_string(),
+                    format!("-name={}", vm),
+                    path.display().to_string(),
+                ]);
+                commands.push(vec![
+                    "vm.change".to_string(),
+                    format!("-vm={}", vm),
+                    format!("-c={}", self.vsphere.cpus),
+                    format!("-m={}", self.vsphere.memory_mb),
+                    format!("-g={}", self.vsphere.guest_id),
+                ]);
+                commands.push(vec![
+                    "vm.network.change".to_string(),
+                    format!("-vm={}", vm),
+                    format!("-net={}", primary),
+                    "-net.adapter=vmxnet3".to_string(),
+                    format!("-net.address={}", mac),
+                    "ethernet-0".to_string(),
+                ]);
+            }
+            VsphereImage::Vmdk(_) | VsphereImage::Qcow2(_) => {
+                // A qcow2 image is uploaded as the VMDK it is converted to
+                let path = match image {
+                    VsphereImage::Vmdk(path) => path.clone(),
+                    _ => self.vmdk_path(),
+                };
+                let disk = path
+                    .file_name()
+                    .map(|n| n.to_string_lossy().to_string())
+                    .unwrap_or_else(|| format!("{}.vmdk", vm));
+                commands.push(vec![
+                    "import.vmdk".to_string(),
+                    "-force".to_string(),
+                    path.display().to_string(),
+                    vm.clone(),
+                ]);
+                commands.push(vec![
+                    "vm.create".to_string(),
+                    "-on=false".to_string(),
+                    format!("-c={}", self.vsphere.cpus),
+                    format!("-m={}", self.vsphere.memory_mb),
+                    format!("-g={}", self.vsphere.guest_id),
+                    "-firmware=efi".to_string(),
+                    format!("-net={}", primary),
+                    "-net.adapter=vmxnet3".to_string(),
+                    format!("-net.address={}", mac),
+                    "-disk.controller=pvscsi".to_string(),
+                    format!("-disk={}/{}", vm, disk),
+                    vm.clone(),
+                ]);
+            }
+        }
+        for network in &self.vsphere.networks[1..] {
+            commands.push(vec![
+                "vm.network.add".to_string(),
+                format!("-vm={}", vm),
+                format!("-net={}", network),
+                "-net.adapter=vmxnet3".to_string(),
+            ]);
+        }
+        if let Some(size) = self.vsphere.disk_gb {
+            commands.push(vec![
+                "vm.disk.change".to_string(),
+                format!("-vm={}", vm),
+                format!("-disk.label={}", FIRST_DISK),
+                format!("-size={}G", size),
+            ]);
+        }
+        let mut guestinfo = vec!["vm.change".to_string(), format!("-vm={}", vm)];
+        for (key, value) in [
+            ("metadata", cloud_init_metadata(self.config, &vm, &mac)),
+            ("userdata", cloud_init_userdata(self.config)),
+        ] {
+            guestinfo.push("-e".to_string());
+            guestinfo.push(format!(
+                "guestinfo.{}={}",
+                key,
+                base64_encode(value.as_bytes())
+            ));
+            guestinfo.push("-e".to_string());
+            guestinfo.push(format!("guestinfo.{}.encoding=base64", key));
+        }
+        commands.push(guestinfo);
+        commands.push(vec!["vm.power".to_string(), "-on".to_string(), vm]);
+        commands
+    }
+
+    async fn govc(&self, args: &[String]) -> Result<String> {
+        debug!("govc {}", args[0]);
+        let mut command = Command::new("govc");
+        command.args(args);
+        for (key, value) in govc_env(self.vsphere) {
+            command.env(key, value);
+        }
+        let output = command.output().await.map_err(|e| {
+            AutoInstallError::SystemError(format!(
+                "Cannot run govc ({}); install it from https://github.com/vmware/govmomi",
+                e
+            ))
+        })?;
+        if !output.status.success() {
+            return Err(AutoInstallError::ProcessError {
+                command: format!("govc {}", args[0]),
+                exit_code: output.status.code(),
+                stderr: String::from_utf8_lossy(&output.stderr).trim().to_string(),
+            });
+        }
+        Ok(String::from_utf8_lossy(&output.stdout).to_string())
+    }
+}
+
+/// Connection and placement settings passed to `govc` through its environment
+pub fn govc_env(vsphere: &VsphereConfig) -> Vec<(&'static str, String)> {
+    let mut env = vec![
+        ("GOVC_URL", vsphere.sdk_url()),
+        ("GOVC_USERNAME", vsphere.username.clone()),
+        ("GOVC_PASSWORD", vsphere.password.clone()),
+        ("GOVC_INSECURE", vsphere.insecure_tls.to_string()),
+        ("GOVC_DATACENTER", vsphere.datacenter.clone()),
+        ("GOVC_DATASTORE", vsphere.datastore.clone()),
+    ];
+    if let Some(pool) = &vsphere.resource_pool {
+        env.push(("GOVC_RESOURCE_POOL", pool.clone()));
+    }
+    if let Some(folder) = &vsphere.folder {
+        env.push(("GOVC_FOLDER", folder.clone()));
+    }
+    env
+}
+
+/// MAC of the primary NIC, stable per hostname, in VMware's range for static addresses
+/// (00:50:56:00:00:00 to 00:50:56:3f:ff:ff), so the network config can match on it
+pub fn guest_mac(hostname: &str) -> String {
+    let digest = Sha256::digest(hostname.as_bytes());
+    format!(
+        "00:50:56:{:02x}:{:02x}:{:02x}",
+        digest[0] & 0x3f,
+        digest[1],
+        digest[2]
+    )
+}
+
+/// cloud-init metadata: instance id, hostname and the netplan config of the primary NIC
+pub fn cloud_init_metadata(config: &TargetConfig, vm_name: &str, mac: &str) -> String {
+    let network = &config.network;
+    let mut primary = json!({ "match": { "macaddress": mac } });
+    if network.dhcp {
+        primary["dhcp4"] = json!(true);
+    } else {
+        if let Some(address) = &network.ip_address {
+            primary["addresses"] = json!([address]);
+        }
+        if let Some(gateway) = &network.gateway {
+            primary["routes"] = json!([{ "to": "default", "via": gateway }]);
+        }
+    }
+    if !network.dns_servers.is_empty() {
+        primary["nameservers"] = json!({ "addresses": network.dns_servers });
+    }
+    let metadata = json!({
+        "instance-id": format!("{}-{}", vm_name, uuid::Uuid::new_v4()),
+        "local-hostname": config.hostname,
+        "network": {
+            "version": 2,
+            "ethernets": { "primary": primary },
+        },
+    });
+    serde_yaml::to_string(&metadata).unwrap_or_default()
+}
+
+/// cloud-init user data: users with their keys, timezone and packages
+pub fn cloud_init_userdata(config: &TargetConfig) -> String {
+    let users: Vec<serde_json::Value> = config
+        .users
+        .iter()
+        .map(|user| {
+            let mut entry = json!({
+                "name": user.name,
+                "shell": user.shell.as_deref().unwrap_or("/bin/bash"),
+                "lock_passwd": true,
+                "ssh_authorized_keys": user.ssh_keys,
+            });
+            if user.sudo {
+                entry["groups"] = json!("sudo");
+                entry["sudo"] = json!("ALL=(ALL) NOPASSWD:ALL");
+            }
+            entry
+        })
+        .collect();
+    let mut userdata = json!({
+        "hostname": config.hostname,
+        "timezone": config.timezone,
+        "users": users,
+    });
+    if !config.packages.is_empty() {
+        userdata["package_update"] = json!(true);
+        userdata["packages"] = json!(config.packages);
+    }
+    format!(
+        "#cloud-config\n{}",
+        serde_yaml::to_string(&userdata).unwrap_or_default()
+    )
+}
+
+/// First IPv4 address in `govc vm.ip` output
+fn parse_ip(output: &str) -> Option<String> {
+    output
+        .split(|c: char| c == ',' || c.is_whitespace())
+        .find(|part| part.parse::<std::net::Ipv4Addr>().is_ok())
+        .map(str::to_string)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_vsphere_plan_and_guestinfo() {
+        let config: TargetConfig = serde_yaml::from_str(
+            r#"
+hostname: web-01
+architecture: amd64
+disk_device: /dev/sda
+timezone: UTC
+network:
+  interface: ens192
+  ip_address: 10.1.0.5/24
+  gateway: 10.1.0.1
+  dns_servers: [10.1.0.2]
+  dhcp: false
+users:
+  - name: admin
+    sudo: true
+    ssh_keys: ["ssh-ed25519 AAAA admin"]
+luks_config:
+  passphrase: x
+  cipher: aes-xts-plain64
+  key_size: 512
+  hash: sha256
+packages: [htop]
+vsphere:
+  url: vc.lab
+  username: deploy
+  password: s3cret-vc
+  datacenter: dc1
+  datastore: ssd-01
+  networks: [prod, backup]
+  disk_gb: 40
+"#,
+        )
+        .unwrap();
+
+        let deployer = VsphereDeployer::new(&config, None, Path::new("/w")).unwrap();
+        let plan = deployer.plan(&VsphereImage::from_path(Path::new("/img/golden.qcow2")));
+        assert!(plan[0].ends_with("/img/golden.qcow2 /w/web-01.vmdk"));
+        assert_eq!(plan[1], "govc import.vmdk -force /w/web-01.vmdk web-01");
+        assert!(plan[2].contains("-net=prod -net.adapter=vmxnet3 -net.address=00:50:56:"));
+        assert!(plan[2].ends_with("-disk=web-01/web-01.vmdk web-01"));
+        assert_eq!(
+            plan[3],
+            "govc vm.network.add -vm=web-01 -net=backup -net.adapter=vmxnet3"
+        );
+        assert!(plan[4].contains("-disk.label=Hard disk 1 -size=40G"));
+        assert!(plan[5].contains("guestinfo.userdata.encoding=base64"));
+        assert_eq!(plan[6], "govc vm.power -on web-01");
+
+        let mac = guest_mac("web-01");
+        assert_eq!(mac, guest_mac("web-01"));
+        assert!(u8::from_str_radix(&mac[9..11], 16).unwrap() <= 0x3f);
+        let metadata = cloud_init_metadata(&config, "web-01", &mac);
+        assert!(metadata.contains(&format!("macaddress: {}", mac)));
+        assert!(metadata.contains("via: 10.1.0.1"));
+        assert!(cloud_init_userdata(&config).starts_with("#cloud-config\n"));
+        assert!(govc_env(config.vsphere.as_ref().unwrap())
+            .contains(&("GOVC_URL", "https://vc.lab/sdk".to_string())));
+
+        assert_eq!(parse_ip("fe80::1,10.1.0.5\n").as_deref(), Some("10.1.0.5"));
+    }
+}
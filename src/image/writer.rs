@@ -0,0 +1,290 @@
+// file: src/image/writer.rs
+// version: 1.0.0
+// guid: 3f8a1c62-5d94-4e07-b2a6-9c1e7d4f0b38
+
+//! Verified raw image writes to a target's block device
+//!
+//! The image is sent in fixed-size chunks and written with `dd` at the chunk's offset. Every
+//! `barrier_chunks` chunks the device's buffers are flushed (`blockdev --flushbufs`, which also
+//! drops the cached pages), and each chunk written since the previous barrier is read back from
+//! the disk and compared with the SHA-256 of the source chunk. A mismatching chunk is written
+//! again, up to `max_retries` times, before the deployment fails. This catches USB-SATA bridges
+//! that acknowledge writes they never complete.
+
+use crate::error::AutoInstallError;
+use crate::network::SshClient;
+use crate::Result;
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use std::io::{Read, Seek, SeekFrom};
+use std::path::Path;
+use tracing::{debug, info, warn};
+
+const MIB: u64 = 1024 * 1024;
+const STAGING: &str = "/tmp/.uaa-chunk";
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct VerifiedWriteOptions {
+    /// Chunk size in MiB; the unit of verification and retry
+    pub chunk_size_mb: u32,
+    /// Chunks written between fsync barriers
+    pub barrier_chunks: u32,
+    /// Rewrites of a chunk whose read-back does not match before giving up
+    pub max_retries: u32,
+}
+
+impl Default for VerifiedWriteOptions {
+    fn default() -> Self {
+        Self {
+            chunk_size_mb: 64,
+            barrier_chunks: 4,
+            max_retries: 3,
+        }
+    }
+}
+
+impl VerifiedWriteOptions {
+    pub fn validate(&self) -> Result<()> {
+        if self.chunk_size_mb == 0 || self.barrier_chunks == 0 {
+            return Err(AutoInstallError::ValidationError(
+                "Verified writes need a chunk size and barrier interval of at least 1".to_string(),
+            ));
+        }
+        Ok(())
+    }
+
+    pub fn chunk_bytes(&self) -> u64 {
+        u64::from(self.chunk_size_mb) * MIB
+    }
+
+    /// Chunks covering an image of `image_size` bytes
+    pub fn plan(&self, image_size: u64) -> Vec<Chunk> {
+        let size = self.chunk_bytes();
+        (0..image_size.div_ceil(size))
+            .map(|index| Chunk {
+                index,
+                offset: index * size,
+                len: size.min(image_size - index * size),
+            })
+            .collect()
+    }
+}
+
+/// One region of the image
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Chunk {
+    pub index: u64,
+    /// Byte offset, always a multiple of 1 MiB
+    pub offset: u64,
+    pub len: u64,
+}
+
+impl Chunk {
+    /// Write the staged chunk at its offset on `device`
+    pub fn write_command(&self, staging: &str, device: &str) -> String {
+        format!(
+            "dd if={} of={} bs=1M seek={} conv=notrunc status=none && rm -f {}",
+            staging,
+            device,
+            self.offset / MIB,
+            staging
+        )
+    }
+
+    /// SHA-256 of the chunk as it is on `device`
+    pub fn read_back_command(&self, device: &str) -> String {
+        format!(
+            "dd if={} bs=1M skip={} count={} iflag=count_bytes status=none | sha256sum | cut -d' ' -f1",
+            device,
+            self.offset / MIB,
+            self.len
+        )
+    }
+}
+
+/// Fsync barrier: flush the device and drop its cached pages so read-back hits the disk
+pub fn barrier_command(device: &str) -> String {
+    format!("sync && blockdev --flushbufs {}", device)
+}
+
+/// What a verified write did
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize)]
+pub struct WriteReport {
+    pub bytes: u64,
+    pub chunks: u64,
+    pub barriers: u64,
+    /// Indexes of chunks that had to be rewritten
+    pub retried_chunks: Vec<u64>,
+}
+
+/// Writes a raw image to a block device on the target with per-chunk verification
+pub struct VerifiedWriter {
+    options: VerifiedWriteOptions,
+}
+
+impl VerifiedWriter {
+    pub fn new(options: VerifiedWriteOptions) -> Self {
+        Self { options }
+    }
+
+    /// Write `raw_image` to `device`; fails if any chunk still mismatches after its retries
+    pub async fn write(
+        &self,
+        ssh: &mut SshClient,
+        raw_image: &Path,
+        device: &str,
+    ) -> Result<WriteReport> {
+        self.options.validate()?;
+        let image_size = std::fs::metadata(raw_image)?.len();
+        let device_size: u64 = ssh
+            .execute_with_output(&format!("blockdev --getsize64 {}", device))
+            .await?
+            .trim()
+            .parse()
+            .map_err(|_| {
+                AutoInstallError::ImageError(format!("Could not read the size of {}", device))
+            })?;
+        if device_size < image_size {
+            return Err(AutoInstallError::ImageError(format!(
+                "{} is {} bytes, the image needs {}",
+                device, device_size, image_size
+            )));
+        }
+
+        let chunks = self.options.plan(image_size);
+        info!(
+            "Writing {} to {} in {} chunk(s) of {} MiB with verification",
+            raw_image.display(),
+            device,
+            chunks.len(),
+            self.options.chunk_size_mb
+        );
+        let mut image = std::fs::File::open(raw_image)?;
+        let mut report = WriteReport {
+            bytes: image_size,
+            chunks: chunks.len() as u64,
+            ..Default::default()
+        };
+        let mut pending: Vec<(Chunk, String)> = Vec::new();
+        for (position, chunk) in chunks.iter().enumerate() {
+            let data = read_chunk(&mut image, chunk)?;
+            self.write_chunk(ssh, chunk, &data, device).await?;
+            pending.push((*chunk, sha256_hex(&data)));
+            if pending.len() as u32 >= self.options.barrier_chunks || position + 1 == chunks.len() {
+                ssh.execute(&barrier_command(device)).await?;
+                report.barriers += 1;
+                for (chunk, expected) in pending.drain(..) {
+                    self.verify_chunk(ssh, &mut image, &chunk, &expected, device, &mut report)
+                        .await?;
+                }
+                debug!("Verified {} of {} chunk(s)", position + 1, report.chunks);
+            }
+        }
+        info!(
+            "Image written and verified: {} chunk(s), {} rewritten",
+            report.chunks,
+            report.retried_chunks.len()
+        );
+        Ok(report)
+    }
+
+    async fn write_chunk(
+        &self,
+        ssh: &mut SshClient,
+        chunk: &Chunk,
+        data: &[u8],
+        device: &str,
+    ) -> Result<()> {
+        let local = tempfile::NamedTempFile::new()?;
+        std::fs::write(local.path(), data)?;
+        ssh.upload_file(&local.path().to_string_lossy(), STAGING)
+            .await?;
+        ssh.execute(&chunk.write_command(STAGING, device)).await
+    }
+
+    /// Read `chunk` back and rewrite it until it matches or the retries run out
+    async fn verify_chunk(
+        &self,
+        ssh: &mut SshClient,
+        image: &mut std::fs::File,
+        chunk: &Chunk,
+        expected: &str,
+        device: &str,
+        report: &mut WriteReport,
+    ) -> Result<()> {
+        let mut attempt = 0;
+        loop {
+            let actual = ssh
+                .execute_with_output(&chunk.read_back_command(device))
+                .await?;
+            if actual.trim() == expected {
+                return Ok(());
+            }
+            if attempt == self.options.max_retries {
+                return Err(AutoInstallError::ImageError(format!(
+                    "Chunk {} at offset {} of {} still differs from the image after {} rewrite(s); the disk or its bridge is not storing writes",
+                    chunk.index, chunk.offset, device, attempt
+                )));
+            }
+            attempt += 1;
+            warn!(
+                "Chunk {} at offset {} of {} failed verification, rewriting (attempt {}/{})",
+                chunk.index, chunk.offset, device, attempt, self.options.max_retries
+            );
+            if !report.retried_chunks.contains(&chunk.index) {
+                report.retried_chunks.push(chunk.index);
+            }
+            let data = read_chunk(image, chunk)?;
+            self.write_chunk(ssh, chunk, &data, device).await?;
+            ssh.execute(&barrier_command(device)).await?;
+            report.barriers += 1;
+        }
+    }
+}
+
+fn read_chunk(image: &mut std::fs::File, chunk: &Chunk) -> Result<Vec<u8>> {
+    let mut data = vec![0u8; chunk.len as usize];
+    image.seek(SeekFrom::Start(chunk.offset))?;
+    image.read_exact(&mut data)?;
+    Ok(data)
+}
+
+fn sha256_hex(data: &[u8]) -> String {
+    format!("{:x}", Sha256::digest(data))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_plan_and_commands() {
+        let options = VerifiedWriteOptions {
+            chunk_size_mb: 4,
+            ..Default::default()
+        };
+        let chunks = options.plan(10 * MIB + 512);
+        assert_eq!(chunks.len(), 3);
+        assert_eq!(chunks[2].offset, 8 * MIB);
+        assert_eq!(chunks[2].len, 2 * MIB + 512);
+        assert!(options.plan(0).is_empty());
+
+        assert_eq!(
+            chunks[1].write_command("/tmp/c", "/dev/sdb"),
+            "dd if=/tmp/c of=/dev/sdb bs=1M seek=4 conv=notrunc status=none && rm -f /tmp/c"
+        );
+        assert!(chunks[2]
+            .read_back_command("/dev/sdb")
+            .starts_with("dd if=/dev/sdb bs=1M skip=8 count=2097664 iflag=count_bytes"));
+        assert_eq!(
+            sha256_hex(b""),
+            "e3b0c44298fc1c149afbf4c8996fb92427ae41e4649b934ca495991b7852b855"
+        );
+        assert!(VerifiedWriteOptions {
+            barrier_chunks: 0,
+            ..Default::default()
+        }
+        .validate()
+        .is_err());
+    }
+}
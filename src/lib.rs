@@ -1,5 +1,5 @@
 // file: src/lib.rs
-// version: 1.0.0
+// version: 1.4.0
 // guid: d82472d1-7f0f-4eb4-b0a3-6e1547103eb4
 
 //! # Ubuntu AutoInstall Agent
@@ -7,14 +7,27 @@
 //! Automated Ubuntu server deployment with golden images and LUKS encryption.
 //! This system provides zero manual intervention deployment using VM-based golden
 //! images that can be deployed via SSH or netboot.
+//!
+//! Programs embedding the agent should start from [`Agent`] and the other items re-exported
+//! here; they are the supported API and follow semver. The building blocks stay internal to the
+//! crate; the binary only parses its arguments and hands them to `cli::run`.
 
+pub mod api;
+#[doc(hidden)]
 pub mod cli;
 pub mod config;
 pub mod error;
-pub mod image;
-pub mod logging;
-pub mod network;
-pub mod security;
-pub mod utils;
+pub(crate) mod image;
+pub(crate) mod logging;
+pub(crate) mod network;
+pub(crate) mod security;
+pub(crate) mod utils;
 
+pub use api::{Agent, AgentBuilder, InstallTarget, ProgressStream, API_VERSION};
+pub use config::{Architecture, ImageSpec, TargetConfig};
 pub use error::{AutoInstallError, Result};
+pub use network::{InstallEvent, InstallationConfig};
+pub use utils::CancellationToken;
+
+#[cfg(not(any(feature = "rustls", feature = "native-tls")))]
+compile_error!("enable a TLS backend: the `rustls` or the `native-tls` feature");
@@ -1,9 +1,10 @@
 // file: src/logging/logger.rs
-// version: 1.1.0
+// version: 1.4.0
 // guid: j0k1l2m3-n4o5-6789-0123-456789jklmno
 
 //! Logger initialization and configuration
 
+use crate::logging::redact::Redacting;
 use crate::Result;
 use tracing_subscriber::{fmt, layer::SubscriberExt, util::SubscriberInitExt, EnvFilter};
 
@@ -25,6 +26,7 @@ pub fn init_logger(verbose: bool, quiet: bool) -> Result<()> {
                 .with_thread_ids(false)
                 .with_file(false)
                 .with_line_number(false)
+                .with_writer(Redacting::new(std::io::stdout))
                 .compact(),
         )
         .try_init()
@@ -38,49 +40,6 @@ pub fn init_logger(verbose: bool, quiet: bool) -> Result<()> {
     Ok(())
 }
 
-/// Initialize structured JSON logging (for services)
-pub fn init_json_logger() -> Result<()> {
-    let filter = EnvFilter::try_from_default_env().unwrap_or_else(|_| EnvFilter::new("info"));
-
-    tracing_subscriber::registry()
-        .with(filter)
-        .with(fmt::layer())
-        .try_init()
-        .map_err(|e| {
-            crate::error::AutoInstallError::ConfigError(format!(
-                "Failed to initialize JSON logger: {}",
-                e
-            ))
-        })?;
-
-    Ok(())
-}
-
-/// Create a scoped logger for operations
-pub fn with_operation_span<F, R>(operation: &str, f: F) -> R
-where
-    F: FnOnce() -> R,
-{
-    let span = tracing::info_span!("operation", name = operation);
-    let _enter = span.enter();
-    f()
-}
-
-/// Create an async scoped logger for operations
-pub async fn with_async_operation_span<F, Fut, R>(operation: &str, f: F) -> R
-where
-    F: FnOnce() -> Fut,
-    Fut: std::future::Future<Output = R>,
-{
-    let span = tracing::info_span!("operation", name = operation);
-    async move { f().await }.instrument(span).await
-}
-
-// Re-export tracing macros for convenience
-pub use tracing::{debug, error, info, trace, warn};
-
-use tracing::Instrument;
-
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -130,79 +89,6 @@ mod tests {
         assert!(result.is_ok() || result.is_err());
     }
 
-    #[test]
-    fn test_init_json_logger() {
-        // Act
-        let result = init_json_logger();
-
-        // Assert
-        // Should handle initialization gracefully
-        assert!(result.is_ok() || result.is_err());
-    }
-
-    #[test]
-    fn test_with_operation_span() {
-        // Arrange
-        let operation = "test_operation";
-        let mut executed = false;
-
-        // Act
-        let result = with_operation_span(operation, || {
-            executed = true;
-            "test_result"
-        });
-
-        // Assert
-        assert!(executed);
-        assert_eq!(result, "test_result");
-    }
-
-    #[test]
-    fn test_with_operation_span_with_return_value() {
-        // Arrange
-        let operation = "math_operation";
-
-        // Act
-        let result = with_operation_span(operation, || 2 + 2);
-
-        // Assert
-        assert_eq!(result, 4);
-    }
-
-    #[tokio::test]
-    async fn test_with_async_operation_span() {
-        // Arrange
-        let operation = "async_test_operation";
-        let mut executed = false;
-
-        // Act
-        let result = with_async_operation_span(operation, || async {
-            executed = true;
-            "async_result"
-        })
-        .await;
-
-        // Assert
-        assert!(executed);
-        assert_eq!(result, "async_result");
-    }
-
-    #[tokio::test]
-    async fn test_with_async_operation_span_with_delay() {
-        // Arrange
-        let operation = "delayed_operation";
-
-        // Act
-        let result = with_async_operation_span(operation, || async {
-            tokio::time::sleep(std::time::Duration::from_millis(1)).await;
-            42
-        })
-        .await;
-
-        // Assert
-        assert_eq!(result, 42);
-    }
-
     #[test]
     fn test_tracing_macros_availability() {
         // This test verifies that the re-exported macros are available
@@ -1,9 +1,10 @@
 // file: src/logging/mod.rs
-// version: 1.0.0
+// version: 1.1.0
 // guid: i9j0k1l2-m3n4-5678-9012-345678ijklmn
 
 //! Logging system for Ubuntu AutoInstall Agent
 
 pub mod logger;
+pub mod redact;
 
 pub use logger::init_logger;
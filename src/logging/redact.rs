@@ -0,0 +1,155 @@
+// file: src/logging/redact.rs
+// version: 1.0.0
+// guid: 6c1f8e42-3b97-4d5a-a0e2-9d7b4f1c8e35
+
+//! Redaction of secrets from everything the agent writes
+//!
+//! LUKS passphrases and root passwords are embedded in the commands sent to the target, so they
+//! would otherwise reach the log, the session record, error messages and report sinks. Secrets
+//! are registered once, where they are loaded or prompted for, and [`scrub`] replaces every
+//! registered value with [`REDACTED`]. The log writer, the SSH client, report dispatch, webhook
+//! and GitHub payloads and the saved session, report, runbook, audit and fleet records all pass
+//! through it. Values shorter than [`MIN_SECRET_LEN`] are not registered, since redacting them
+//! would garble unrelated output.
+
+use std::borrow::Cow;
+use std::io::Write;
+use std::sync::RwLock;
+use tracing_subscriber::fmt::MakeWriter;
+
+/// Text that replaces a secret
+pub const REDACTED: &str = "<redacted>";
+/// Shortest value that is registered as a secret
+pub const MIN_SECRET_LEN: usize = 4;
+
+static SECRETS: RwLock<Vec<String>> = RwLock::new(Vec::new());
+
+/// Treat `secret` as sensitive from now on; also covers its JSON-escaped form
+pub fn register(secret: &str) {
+    if secret.chars().count() < MIN_SECRET_LEN {
+        return;
+    }
+    let mut forms = vec![secret.to_string()];
+    if let Ok(quoted) = serde_json::to_string(secret) {
+        let escaped = &quoted[1..quoted.len() - 1];
+        if escaped != secret {
+            forms.push(escaped.to_string());
+        }
+    }
+    let mut secrets = SECRETS.write().unwrap_or_else(|e| e.into_inner());
+    for form in forms {
+        if !secrets.contains(&form) {
+            secrets.push(form);
+        }
+    }
+    // Longest first, so a secret containing another is replaced whole
+    secrets.sort_by_key(|s| std::cmp::Reverse(s.len()));
+}
+
+/// `text` with every registered secret replaced by [`REDACTED`]
+pub fn scrub(text: &str) -> Cow<'_, str> {
+    let secrets = SECRETS.read().unwrap_or_else(|e| e.into_inner());
+    let mut text = Cow::Borrowed(text);
+    for secret in secrets.iter() {
+        if text.contains(secret.as_str()) {
+            text = Cow::Owned(text.replace(secret.as_str(), REDACTED));
+        }
+    }
+    text
+}
+
+/// Scrub every string in `value`, keys included
+pub fn scrub_json(value: &mut serde_json::Value) {
+    match value {
+        serde_json::Value::String(s) => {
+            if let Cow::Owned(clean) = scrub(s) {
+                *s = clean;
+            }
+        }
+        serde_json::Value::Array(items) => items.iter_mut().for_each(scrub_json),
+        serde_json::Value::Object(map) => {
+            let entries = std::mem::take(map);
+            for (key, mut value) in entries {
+                scrub_json(&mut value);
+                map.insert(scrub(&key).into_owned(), value);
+            }
+        }
+        _ => {}
+    }
+}
+
+/// Log writer factory scrubbing each formatted event before it reaches `inner`
+pub struct Redacting<M> {
+    inner: M,
+}
+
+impl<M> Redacting<M> {
+    pub fn new(inner: M) -> Self {
+        Self { inner }
+    }
+}
+
+impl<'a, M: MakeWriter<'a>> MakeWriter<'a> for Redacting<M> {
+    type Writer = RedactingWriter<M::Writer>;
+
+    fn make_writer(&'a self) -> Self::Writer {
+        RedactingWriter {
+            inner: self.inner.make_writer(),
+        }
+    }
+}
+
+/// Writer scrubbing each buffer; the fmt layer writes one whole event per call
+pub struct RedactingWriter<W> {
+    inner: W,
+}
+
+impl<W: Write> Write for RedactingWriter<W> {
+    fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+        match std::str::from_utf8(buf) {
+            Ok(text) => self.inner.write_all(scrub(text).as_bytes())?,
+            Err(_) => self.inner.write_all(buf)?,
+        }
+        Ok(buf.len())
+    }
+
+    fn flush(&mut self) -> std::io::Result<()> {
+        self.inner.flush()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_registered_secrets_are_scrubbed() {
+        register("s3cr\"et-luks-7f41");
+        register("abc");
+        let command = "echo 's3cr\"et-luks-7f41' | cryptsetup open /dev/sda4 luks";
+        assert_eq!(
+            scrub(command),
+            "echo '<redacted>' | cryptsetup open /dev/sda4 luks"
+        );
+        assert!(matches!(scrub("abc is too short"), Cow::Borrowed(_)));
+
+        let mut payload = serde_json::json!({
+            "last_command": command,
+            "failed_phases": [format!("Phase 2: {}", command)],
+        });
+        scrub_json(&mut payload);
+        assert!(!payload.to_string().contains("et-luks-7f41"));
+        let saved = serde_json::to_string(&serde_json::json!({ "c": command })).unwrap();
+        assert!(!scrub(&saved).contains("et-luks-7f41"), "{}", scrub(&saved));
+
+        let mut out = Vec::new();
+        let mut writer = RedactingWriter { inner: &mut out };
+        writer
+            .write_all(b"INFO Executing: Opening LUKS device -> echo 's3cr\"et-luks-7f41'\n")
+            .unwrap();
+        assert_eq!(
+            String::from_utf8(out).unwrap(),
+            "INFO Executing: Opening LUKS device -> echo '<redacted>'\n"
+        );
+    }
+}
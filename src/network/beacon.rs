@@ -0,0 +1,225 @@
+// file: src/network/beacon.rs
+// version: 1.2.0
+// guid: 5c8e2a17-4f93-4d60-b1a7-9e3d6c0f2b84
+
+//! Progress beacon posting reports from the target itself
+//!
+//! For targets behind NAT whose webhook receiver the controller cannot reach. A `beacon` sink
+//! opens its own SSH session to the target, installs a small shell loop there and spools every
+//! report it accepts into `/run/uaa-beacon/spool`. The loop POSTs the files in order with curl
+//! and deletes each one once the receiver accepted it, so reports queued while the target's
+//! network is down are delivered later. Every report carries the `session_id` the controller
+//! gave the install, which is also the `id` of the session record, so receivers can merge the
+//! beacon's reports with those from the controller's own sinks. Once the install has written
+//! the host's one-time enrollment token, posts carry it in an `X-Uaa-Enrollment-Token` header
+//! for the receiver to redeem before it trusts the completion report.
+
+use crate::error::AutoInstallError;
+use crate::network::sinks::{Report, ReportSink};
+use crate::network::SshClient;
+use crate::security::enrollment::TARGET_TOKEN_PATH;
+use crate::utils::shell_quote;
+use crate::Result;
+use async_trait::async_trait;
+use std::sync::atomic::{AtomicU64, Ordering};
+use tokio::sync::Mutex;
+use tracing::info;
+
+/// Working directory of the beacon on the target
+pub const BEACON_DIR: &str = "/run/uaa-beacon";
+/// Where the install mounts the target, under which the enrollment token is written
+const TARGET_ROOT: &str = "/mnt/targetos";
+/// Seconds between delivery rounds
+const INTERVAL_SECS: u64 = 2;
+/// Seconds to wait for the spool to drain when the install is done
+const DRAIN_SECS: u64 = 60;
+
+/// Shell loop run on the target
+pub fn build_script(url: &str, session_id: &str) -> String {
+    format!(
+        r#"#!/bin/sh
+# Progress beacon of ubuntu-autoinstall-agent; stops once {dir}/stop exists and the spool is empty
+dir={dir}
+rounds=0
+while :; do
+  for f in "$dir"/spool/*.json; do
+    [ -e "$f" ] || break
+    token=$(cat {root}/{token} 2>/dev/null)
+    curl -fsS -m 15 -X POST -H 'Content-Type: application/json' -H {session} ${{token:+-H X-Uaa-Enrollment-Token:$token}} --data-binary @"$f" {url} >/dev/null || break
+    rm -f "$f"
+  done
+  if [ -e "$dir/stop" ]; then
+    ls "$dir"/spool/*.json >/dev/null 2>&1 || exit 0
+    rounds=$((rounds + 1))
+    [ "$rounds" -gt {rounds} ] && exit 1
+  fi
+  sleep {interval}
+done
+"#,
+        dir = BEACON_DIR,
+        root = TARGET_ROOT,
+        token = TARGET_TOKEN_PATH,
+        session = shell_quote(&format!("X-Uaa-Session: {}", session_id)),
+        url = shell_quote(url),
+        rounds = DRAIN_SECS / INTERVAL_SECS,
+        interval = INTERVAL_SECS,
+    )
+}
+
+/// Commands writing the script and starting it detached from the SSH session
+pub fn build_start_commands(url: &str, session_id: &str) -> Vec<String> {
+    vec![
+        format!("mkdir -p {0}/spool && rm -f {0}/stop", BEACON_DIR),
+        format!(
+            "printf '%s' {} > {}/beacon.sh && chmod 0755 {}/beacon.sh",
+            shell_quote(&build_script(url, session_id)),
+            BEACON_DIR,
+            BEACON_DIR
+        ),
+        format!(
+            "command -v curl >/dev/null && (setsid sh {0}/beacon.sh >{0}/beacon.log 2>&1 </dev/null &)",
+            BEACON_DIR
+        ),
+    ]
+}
+
+/// Command queueing `payload` as spool entry `sequence`; the rename makes it visible whole
+pub fn build_spool_command(sequence: u64, payload: &serde_json::Value) -> Result<String> {
+    let file = format!("{}/spool/{:010}.json", BEACON_DIR, sequence);
+    Ok(format!(
+        "printf '%s\\n' {} > {file}.tmp && mv {file}.tmp {file}",
+        shell_quote(&serde_json::to_string(payload)?),
+        file = file
+    ))
+}
+
+/// Command asking the beacon to stop and waiting until the spool is empty
+pub fn build_drain_command() -> String {
+    format!(
+        "touch {0}/stop; for i in $(seq {1}); do ls {0}/spool/*.json >/dev/null 2>&1 || exit 0; sleep 1; done; exit 1",
+        BEACON_DIR, DRAIN_SECS
+    )
+}
+
+/// Report payload with the install's `session_id` added
+pub fn tag_payload(payload: &serde_json::Value, session_id: &str) -> serde_json::Value {
+    let mut payload = payload.clone();
+    if let Some(object) = payload.as_object_mut() {
+        object.insert("session_id".to_string(), session_id.into());
+        object.insert("via".to_string(), "beacon".into());
+    }
+    payload
+}
+
+/// Report sink delivering through the target's own network
+pub struct TargetBeacon {
+    ssh: Mutex<SshClient>,
+    host: String,
+    url: String,
+    session_id: String,
+    sequence: AtomicU64,
+}
+
+impl TargetBeacon {
+    /// Connect to `host` and start the beacon posting to `url`
+    pub async fn start(host: &str, username: &str, url: &str, session_id: &str) -> Result<Self> {
+        if !url.starts_with("http://") && !url.starts_with("https://") {
+            return Err(AutoInstallError::ValidationError(format!(
+                "Beacon URL must start with http:// or https://: {}",
+                url
+            )));
+        }
+        let mut ssh = SshClient::new();
+        ssh.connect(host, username).await?;
+        for command in build_start_commands(url, session_id) {
+            ssh.execute(&command).await.map_err(|e| {
+                AutoInstallError::NetworkError(format!(
+                    "Failed to start the progress beacon on {} (is curl installed?): {}",
+                    host, e
+                ))
+            })?;
+        }
+        info!("Progress beacon started on {} for {}", host, url);
+        Ok(Self {
+            ssh: Mutex::new(ssh),
+            host: host.to_string(),
+            url: url.to_string(),
+            session_id: session_id.to_string(),
+            sequence: AtomicU64::new(0),
+        })
+    }
+}
+
+#[async_trait]
+impl ReportSink for TargetBeacon {
+    fn describe(&self) -> String {
+        format!("beacon {} on {}", self.url, self.host)
+    }
+
+    async fn deliver(&self, report: &Report) -> Result<()> {
+        let sequence = self.sequence.fetch_add(1, Ordering::SeqCst);
+        let command =
+            build_spool_command(sequence, &tag_payload(&report.payload, &self.session_id))?;
+        self.ssh.lock().await.execute(&command).await
+    }
+
+    /// Stop the beacon once it has posted everything queued
+    async fn finish(&self) -> Result<()> {
+        let mut ssh = self.ssh.lock().await;
+        let drained = ssh.execute(&build_drain_command()).await;
+        ssh.disconnect();
+        drained.map_err(|_| {
+            AutoInstallError::NetworkError(format!(
+                "reports were still queued after {}s; see {}/beacon.log on the target",
+                DRAIN_SECS, BEACON_DIR
+            ))
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    #[test]
+    fn test_spool_entries_are_ordered_and_tagged() {
+        let payload = tag_payload(
+            &json!({"event": "progress", "hostname": "o'hara"}),
+            "abc-123",
+        );
+        assert_eq!(payload["session_id"], "abc-123");
+        assert_eq!(payload["via"], "beacon");
+
+        let command = build_spool_command(7, &payload).unwrap();
+        assert!(command.starts_with("printf '%s\\n' '{"));
+        assert!(command.contains("o'\\''hara"));
+        assert!(command.ends_with(
+            "> /run/uaa-beacon/spool/0000000007.json.tmp && mv /run/uaa-beacon/spool/0000000007.json.tmp /run/uaa-beacon/spool/0000000007.json"
+        ));
+    }
+
+    #[test]
+    fn test_script_posts_to_receiver() {
+        let script = build_script("https://hooks.example/install", "abc-123");
+        assert!(script.contains("-H 'X-Uaa-Session: abc-123'"));
+        assert!(script.contains("--data-binary @\"$f\" https://hooks.example/install"));
+
+        // Whatever the controller passes stays one word for the target's shell
+        let script = build_script("https://hooks.example/x?a=1&b=2", "abc'; reboot; '");
+        assert!(script.contains("-H 'X-Uaa-Session: abc'\\''; reboot; '\\'''"));
+        assert!(script.contains("'https://hooks.example/x?a=1&b=2'"));
+        assert!(script.contains("[ \"$rounds\" -gt 30 ] && exit 1"));
+        assert!(script.contains(
+            "token=$(cat /mnt/targetos/etc/ubuntu-autoinstall-agent/enrollment-token 2>/dev/null)"
+        ));
+        assert!(script.contains("${token:+-H X-Uaa-Enrollment-Token:$token}"));
+
+        let start = build_start_commands("https://hooks.example/install", "abc-123");
+        assert_eq!(
+            start[0],
+            "mkdir -p /run/uaa-beacon/spool && rm -f /run/uaa-beacon/stop"
+        );
+        assert!(start[2].contains("setsid sh /run/uaa-beacon/beacon.sh"));
+    }
+}
@@ -0,0 +1,172 @@
+// file: src/network/chaos.rs
+// version: 1.1.0
+// guid: d2a6f8c1-7b39-4e05-a1c4-58e9b0d3f672
+
+//! Failure injection for exercising retry, hold and recovery paths
+//!
+//! Chaos rules are written as `<fault>@<pattern>[#<nth>]`:
+//!
+//! - `exit:<code>@zpool create` makes the first command containing `zpool create` exit with `<code>`
+//! - `timeout@debootstrap#2` makes the second `debootstrap` command time out
+//! - `disconnect@*` drops the SSH session on the very next command
+//!
+//! Each rule fires once. Faults are injected before the command reaches the target, so the
+//! target is never touched by a command that is reported as failed.
+
+use crate::error::AutoInstallError;
+use crate::Result;
+
+/// Fault injected in place of running a command
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ChaosFault {
+    /// Report the command as exited with this code
+    ExitCode(i32),
+    /// Report the command as timed out
+    Timeout,
+    /// Drop the SSH connection
+    Disconnect,
+}
+
+/// One injection rule
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ChaosRule {
+    /// Substring the command must contain; `*` matches every command
+    pub pattern: String,
+    /// Fault to inject
+    pub fault: ChaosFault,
+    /// Fire on the nth matching command (1-based)
+    pub nth: usize,
+}
+
+impl ChaosRule {
+    /// Parse `<fault>@<pattern>[#<nth>]`
+    pub fn parse(spec: &str) -> Result<Self> {
+        let invalid = |reason: &str| {
+            AutoInstallError::ConfigError(format!("Invalid chaos rule '{}': {}", spec, reason))
+        };
+
+        let (fault, target) = spec
+            .split_once('@')
+            .ok_or_else(|| invalid("expected <fault>@<pattern>"))?;
+        let fault = match fault.trim() {
+            "timeout" => ChaosFault::Timeout,
+            "disconnect" => ChaosFault::Disconnect,
+            other => {
+                let code = other
+                    .strip_prefix("exit:")
+                    .ok_or_else(|| invalid("fault must be exit:<code>, timeout or disconnect"))?;
+                ChaosFault::ExitCode(
+                    code.trim()
+                        .parse()
+                        .map_err(|_| invalid("exit code must be an integer"))?,
+                )
+            }
+        };
+
+        let (pattern, nth) = match target.rsplit_once('#') {
+            Some((pattern, nth)) => (
+                pattern,
+                nth.trim()
+                    .parse::<usize>()
+                    .ok()
+                    .filter(|n| *n > 0)
+                    .ok_or_else(|| invalid("#<nth> must be a positive integer"))?,
+            ),
+            None => (target, 1),
+        };
+        if pattern.trim().is_empty() {
+            return Err(invalid("pattern must not be empty"));
+        }
+
+        Ok(Self {
+            pattern: pattern.trim().to_string(),
+            fault,
+            nth,
+        })
+    }
+
+    fn matches(&self, command: &str) -> bool {
+        self.pattern == "*" || command.contains(&self.pattern)
+    }
+}
+
+/// Tracks rules and how often each has matched
+#[derive(Debug, Clone, Default)]
+pub struct ChaosMonkey {
+    rules: Vec<(ChaosRule, usize)>,
+}
+
+impl ChaosMonkey {
+    /// Build from rule specs; an empty list disables injection
+    pub fn from_specs(specs: &[String]) -> Result<Self> {
+        let rules = specs
+            .iter()
+            .map(|spec| ChaosRule::parse(spec).map(|rule| (rule, 0)))
+            .collect::<Result<Vec<_>>>()?;
+        Ok(Self { rules })
+    }
+
+    /// Record `command` and return the fault to inject, if a rule fires
+    pub fn on_command(&mut self, command: &str) -> Option<ChaosFault> {
+        let mut fired = None;
+        for (rule, seen) in self.rules.iter_mut() {
+            if *seen >= rule.nth || !rule.matches(command) {
+                continue;
+            }
+            *seen += 1;
+            if *seen == rule.nth && fired.is_none() {
+                fired = Some(rule.fault.clone());
+            }
+        }
+        fired
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_rules() {
+        assert_eq!(
+            ChaosRule::parse("exit:2@zpool create").unwrap(),
+            ChaosRule {
+                pattern: "zpool create".into(),
+                fault: ChaosFault::ExitCode(2),
+                nth: 1
+            }
+        );
+        let rule = ChaosRule::parse("timeout@debootstrap#3").unwrap();
+        assert_eq!(rule.fault, ChaosFault::Timeout);
+        assert_eq!(rule.nth, 3);
+        assert_eq!(
+            ChaosRule::parse("disconnect@*").unwrap().fault,
+            ChaosFault::Disconnect
+        );
+
+        assert!(ChaosRule::parse("explode@x").is_err());
+        assert!(ChaosRule::parse("exit:abc@x").is_err());
+        assert!(ChaosRule::parse("timeout@x#0").is_err());
+        assert!(ChaosRule::parse("timeout@").is_err());
+        assert!(ChaosRule::parse("timeout").is_err());
+    }
+
+    #[test]
+    fn test_rules_fire_once_on_nth_match() {
+        let mut monkey =
+            ChaosMonkey::from_specs(&["exit:1@apt install#2".into(), "disconnect@grub".into()])
+                .unwrap();
+        assert_eq!(monkey.on_command("apt install -y vim"), None);
+        assert_eq!(monkey.on_command("ls"), None);
+        assert_eq!(
+            monkey.on_command("apt install -y curl"),
+            Some(ChaosFault::ExitCode(1))
+        );
+        assert_eq!(monkey.on_command("apt install -y htop"), None);
+        assert_eq!(
+            monkey.on_command("grub-install"),
+            Some(ChaosFault::Disconnect)
+        );
+        assert_eq!(monkey.on_command("grub-install"), None);
+    }
+}
@@ -0,0 +1,610 @@
+// file: src/network/dhcp.rs
+// version: 1.2.0
+// guid: 6e2b9d47-1f83-4a5c-b7d0-4c8a3e1f9b26
+
+//! Minimal DHCP server for provisioning networks without one
+//!
+//! Serves only the static leases of the inventory (see [`crate::config::dhcp`]): DISCOVER is
+//! answered with an OFFER of the host's fixed address, REQUEST for that address with an ACK
+//! and any other REQUEST with a NAK. Clients without a lease get no answer. Network-booting
+//! clients also get the TFTP server and the BIOS or UEFI boot file.
+//!
+//! Every offer, ack, nak, release and decline is appended to `logs/<hostname>/dhcp-leases.jsonl`
+//! with the id of the server run and, while the host is being installed, the id of its
+//! install session.
+
+use crate::config::dhcp::{format_mac, DhcpConfig, StaticLease};
+use crate::error::AutoInstallError;
+use crate::network::ssh_installer::session::{InstallSession, SessionStatus};
+use crate::utils::CancellationToken;
+use crate::Result;
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use std::collections::{HashMap, HashSet};
+use std::io::Write;
+use std::net::{Ipv4Addr, SocketAddr, SocketAddrV4};
+use std::path::{Path, PathBuf};
+use tokio::net::UdpSocket;
+use tracing::{debug, info, warn};
+
+const SERVER_PORT: u16 = 67;
+const CLIENT_PORT: u16 = 68;
+const MAGIC_COOKIE: [u8; 4] = [99, 130, 83, 99];
+/// Fixed BOOTP header before the options
+const HEADER_LEN: usize = 240;
+/// Smallest BOOTP message; some PXE ROMs drop shorter replies
+const MIN_REPLY_LEN: usize = 300;
+/// Clients without a lease remembered for logging them once; forgotten past this many so
+/// spoofed MACs cannot grow the set without bound
+const MAX_UNKNOWN_CLIENTS: usize = 4096;
+
+const OPT_PAD: u8 = 0;
+const OPT_SUBNET_MASK: u8 = 1;
+const OPT_ROUTER: u8 = 3;
+const OPT_DNS: u8 = 6;
+const OPT_HOSTNAME: u8 = 12;
+const OPT_DOMAIN: u8 = 15;
+const OPT_REQUESTED_IP: u8 = 50;
+const OPT_LEASE_TIME: u8 = 51;
+const OPT_MESSAGE_TYPE: u8 = 53;
+const OPT_SERVER_ID: u8 = 54;
+const OPT_VENDOR_CLASS: u8 = 60;
+const OPT_TFTP_SERVER: u8 = 66;
+const OPT_BOOT_FILE: u8 = 67;
+const OPT_CLIENT_ARCH: u8 = 93;
+const OPT_END: u8 = 255;
+
+/// DHCP message types (option 53)
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum MessageType {
+    Discover,
+    Offer,
+    Request,
+    Decline,
+    Ack,
+    Nak,
+    Release,
+    Inform,
+}
+
+impl MessageType {
+    fn from_u8(value: u8) -> Option<Self> {
+        Some(match value {
+            1 => MessageType::Discover,
+            2 => MessageType::Offer,
+            3 => MessageType::Request,
+            4 => MessageType::Decline,
+            5 => MessageType::Ack,
+            6 => MessageType::Nak,
+            7 => MessageType::Release,
+            8 => MessageType::Inform,
+            _ => return None,
+        })
+    }
+
+    fn as_u8(self) -> u8 {
+        match self {
+            MessageType::Discover => 1,
+            MessageType::Offer => 2,
+            MessageType::Request => 3,
+            MessageType::Decline => 4,
+            MessageType::Ack => 5,
+            MessageType::Nak => 6,
+            MessageType::Release => 7,
+            MessageType::Inform => 8,
+        }
+    }
+
+    pub fn as_str(self) -> &'static str {
+        match self {
+            MessageType::Discover => "discover",
+            MessageType::Offer => "offer",
+            MessageType::Request => "request",
+            MessageType::Decline => "decline",
+            MessageType::Ack => "ack",
+            MessageType::Nak => "nak",
+            MessageType::Release => "release",
+            MessageType::Inform => "inform",
+        }
+    }
+}
+
+/// The parts of a client message the server looks at
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct DhcpRequest {
+    pub message_type: MessageType,
+    pub xid: [u8; 4],
+    pub flags: [u8; 2],
+    pub ciaddr: Ipv4Addr,
+    pub giaddr: Ipv4Addr,
+    pub chaddr: [u8; 6],
+    pub requested_ip: Option<Ipv4Addr>,
+    pub server_id: Option<Ipv4Addr>,
+    pub vendor_class: Option<String>,
+    /// Client system architecture (option 93): 0 is BIOS, 6, 7, 9, 10 and 11 are UEFI
+    pub client_arch: Option<u16>,
+}
+
+impl DhcpRequest {
+    /// Parse a BOOTREQUEST from an Ethernet client; anything else is an error
+    pub fn parse(packet: &[u8]) -> Result<Self> {
+        if packet.len() < HEADER_LEN || packet[236..240] != MAGIC_COOKIE {
+            return Err(malformed("too short or missing the DHCP magic cookie"));
+        }
+        if packet[0] != 1 || packet[1] != 1 || packet[2] != 6 {
+            return Err(malformed("not a BOOTREQUEST from an Ethernet client"));
+        }
+        let mut message_type = None;
+        let mut requested_ip = None;
+        let mut server_id = None;
+        let mut vendor_class = None;
+        let mut client_arch = None;
+        let mut i = HEADER_LEN;
+        while i < packet.len() {
+            let code = packet[i];
+            if code == OPT_END {
+                break;
+            }
+            if code == OPT_PAD {
+                i += 1;
+                continue;
+            }
+            let len = *packet
+                .get(i + 1)
+                .ok_or_else(|| malformed("truncated option"))? as usize;
+            let value = packet
+                .get(i + 2..i + 2 + len)
+                .ok_or_else(|| malformed("truncated option"))?;
+            match (code, len) {
+                (OPT_MESSAGE_TYPE, 1) => message_type = MessageType::from_u8(value[0]),
+                (OPT_REQUESTED_IP, 4) => requested_ip = Some(ipv4(value)),
+                (OPT_SERVER_ID, 4) => server_id = Some(ipv4(value)),
+                (OPT_VENDOR_CLASS, _) => {
+                    vendor_class = Some(String::from_utf8_lossy(value).to_string())
+                }
+                (OPT_CLIENT_ARCH, 2) => {
+                    client_arch = Some(u16::from_be_bytes([value[0], value[1]]))
+                }
+                _ => {}
+            }
+            i += 2 + len;
+        }
+        let mut chaddr = [0u8; 6];
+        chaddr.copy_from_slice(&packet[28..34]);
+        Ok(Self {
+            message_type: message_type.ok_or_else(|| malformed("no message type"))?,
+            xid: [packet[4], packet[5], packet[6], packet[7]],
+            flags: [packet[10], packet[11]],
+            ciaddr: ipv4(&packet[12..16]),
+            giaddr: ipv4(&packet[24..28]),
+            chaddr,
+            requested_ip,
+            server_id,
+            vendor_class,
+            client_arch,
+        })
+    }
+
+    fn is_uefi(&self) -> bool {
+        matches!(self.client_arch, Some(6 | 7 | 9 | 10 | 11))
+    }
+}
+
+/// A reply and where it goes
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct DhcpReply {
+    pub message_type: MessageType,
+    pub packet: Vec<u8>,
+    pub destination: SocketAddrV4,
+}
+
+/// Lease log entry, one JSON line in `logs/<hostname>/dhcp-leases.jsonl`
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct LeaseEvent {
+    pub time: DateTime<Utc>,
+    /// Id of the `fleet dhcp` run
+    pub run_id: String,
+    /// Install session of the host, when one is running
+    pub install_session: Option<String>,
+    pub hostname: String,
+    pub mac: String,
+    pub ip: Ipv4Addr,
+    /// offer, ack, nak, release or decline
+    pub event: String,
+    pub vendor_class: Option<String>,
+    pub client_arch: Option<u16>,
+}
+
+pub struct DhcpServer {
+    config: DhcpConfig,
+    leases: HashMap<[u8; 6], StaticLease>,
+    base_dir: PathBuf,
+    run_id: String,
+    unknown_clients: HashSet<[u8; 6]>,
+}
+
+impl DhcpServer {
+    pub fn new(config: DhcpConfig, leases: Vec<StaticLease>, base_dir: &Path) -> Self {
+        Self {
+            config,
+            leases: leases.into_iter().map(|l| (l.mac, l)).collect(),
+            base_dir: base_dir.to_path_buf(),
+            run_id: uuid::Uuid::new_v4().to_string(),
+            unknown_clients: HashSet::new(),
+        }
+    }
+
+    /// Answer requests on the configured interface until `cancel` fires
+    pub async fn run(&mut self, cancel: &CancellationToken) -> Result<()> {
+        let socket = bind_socket(&self.config.interface)?;
+        info!(
+            "DHCP server {} on {} ({}), {} static lease(s)",
+            self.run_id,
+            self.config.interface,
+            self.config.server_ip,
+            self.leases.len()
+        );
+        let mut buf = [0u8; 1500];
+        loop {
+            let (len, from) = tokio::select! {
+                _ = cancel.cancelled() => break,
+                received = socket.recv_from(&mut buf) => received?,
+            };
+            let request = match DhcpRequest::parse(&buf[..len]) {
+                Ok(request) => request,
+                Err(e) => {
+                    debug!("Ignoring packet from {}: {}", from, e);
+                    continue;
+                }
+            };
+            if let Some(reply) = self.handle(&request) {
+                // A failed send (e.g. the interface flapping) is logged; the server keeps answering
+                if let Err(e) = socket
+                    .send_to(&reply.packet, SocketAddr::V4(reply.destination))
+                    .await
+                {
+                    warn!(
+                        "Failed to send {} to {}: {}",
+                        reply.message_type.as_str(),
+                        format_mac(&request.chaddr),
+                        e
+                    );
+                }
+            }
+        }
+        info!("DHCP server {} stopped", self.run_id);
+        Ok(())
+    }
+
+    /// Decide on the answer to `request` and log the lease event
+    pub fn handle(&mut self, request: &DhcpRequest) -> Option<DhcpReply> {
+        let Some(lease) = self.leases.get(&request.chaddr).cloned() else {
+            if self.unknown_clients.len() >= MAX_UNKNOWN_CLIENTS {
+                self.unknown_clients.clear();
+            }
+            if self.unknown_clients.insert(request.chaddr) {
+                info!(
+                    "Ignoring {} from {}, which has no lease in the inventory",
+                    request.message_type.as_str(),
+                    format_mac(&request.chaddr)
+                );
+            }
+            return None;
+        };
+        let reply_type = match request.message_type {
+            MessageType::Discover => MessageType::Offer,
+            MessageType::Request => {
+                if request
+                    .server_id
+                    .is_some_and(|id| id != self.config.server_ip)
+                {
+                    // The client took another server's offer
+                    return None;
+                }
+                let wanted = request.requested_ip.unwrap_or(request.ciaddr);
+                if wanted == lease.ip {
+                    MessageType::Ack
+                } else {
+                    MessageType::Nak
+                }
+            }
+            MessageType::Inform => MessageType::Ack,
+            MessageType::Release | MessageType::Decline => {
+                if request.message_type == MessageType::Decline {
+                    warn!(
+                        "{} declined {}; another machine may be using the address",
+                        lease.hostname, lease.ip
+                    );
+                }
+                self.log_event(&lease, request, request.message_type);
+                return None;
+            }
+            MessageType::Offer | MessageType::Ack | MessageType::Nak => return None,
+        };
+        if request.message_type != MessageType::Inform {
+            info!(
+                "DHCP {} {} -> {} ({})",
+                reply_type.as_str(),
+                format_mac(&lease.mac),
+                lease.ip,
+                lease.hostname
+            );
+            self.log_event(&lease, request, reply_type);
+        }
+        Some(DhcpReply {
+            message_type: reply_type,
+            packet: self.build_reply(request, &lease, reply_type),
+            destination: reply_destination(request, reply_type),
+        })
+    }
+
+    fn build_reply(
+        &self,
+        request: &DhcpRequest,
+        lease: &StaticLease,
+        reply_type: MessageType,
+    ) -> Vec<u8> {
+        let config = &self.config;
+        let mut packet = vec![0u8; HEADER_LEN];
+        packet[0] = 2;
+        packet[1] = 1;
+        packet[2] = 6;
+        packet[4..8].copy_from_slice(&request.xid);
+        packet[10..12].copy_from_slice(&request.flags);
+        packet[12..16].copy_from_slice(&request.ciaddr.octets());
+        if reply_type != MessageType::Nak && request.message_type != MessageType::Inform {
+            packet[16..20].copy_from_slice(&lease.ip.octets());
+        }
+        packet[24..28].copy_from_slice(&request.giaddr.octets());
+        packet[28..34].copy_from_slice(&request.chaddr);
+        packet[236..240].copy_from_slice(&MAGIC_COOKIE);
+
+        push_option(&mut packet, OPT_MESSAGE_TYPE, &[reply_type.as_u8()]);
+        push_option(&mut packet, OPT_SERVER_ID, &config.server_ip.octets());
+        if reply_type != MessageType::Nak {
+            if request.message_type != MessageType::Inform {
+                push_option(
+                    &mut packet,
+                    OPT_LEASE_TIME,
+                    &config.lease_secs.to_be_bytes(),
+                );
+            }
+            push_option(&mut packet, OPT_SUBNET_MASK, &config.netmask.octets());
+            if let Some(router) = config.router {
+                push_option(&mut packet, OPT_ROUTER, &router.octets());
+            }
+            if !config.dns_servers.is_empty() {
+                let dns: Vec<u8> = config
+                    .dns_servers
+                    .iter()
+                    .flat_map(|ip| ip.octets())
+                    .collect();
+                push_option(&mut packet, OPT_DNS, &dns);
+            }
+            push_option(&mut packet, OPT_HOSTNAME, lease.hostname.as_bytes());
+            if let Some(domain) = &config.domain {
+                push_option(&mut packet, OPT_DOMAIN, domain.as_bytes());
+            }
+            let boot_file = if request.is_uefi() {
+                config.boot_file_uefi.as_ref().or(config.boot_file.as_ref())
+            } else {
+                config.boot_file.as_ref()
+            };
+            if let (Some(tftp), Some(file)) = (config.tftp_server(), boot_file) {
+                packet[20..24].copy_from_slice(&tftp.octets());
+                let name = file.as_bytes();
+                let len = name.len().min(127);
+                packet[108..108 + len].copy_from_slice(&name[..len]);
+                push_option(&mut packet, OPT_TFTP_SERVER, tftp.to_string().as_bytes());
+                push_option(&mut packet, OPT_BOOT_FILE, name);
+            }
+        }
+        packet.push(OPT_END);
+        if packet.len() < MIN_REPLY_LEN {
+            packet.resize(MIN_REPLY_LEN, OPT_PAD);
+        }
+        packet
+    }
+
+    /// Append to the host's lease log; never fatal
+    fn log_event(&self, lease: &StaticLease, request: &DhcpRequest, event: MessageType) {
+        let install_session = InstallSession::load(&self.base_dir, &lease.hostname)
+            .ok()
+            .filter(|session| session.status == SessionStatus::Running)
+            .map(|session| session.id);
+        let entry = LeaseEvent {
+            time: Utc::now(),
+            run_id: self.run_id.clone(),
+            install_session,
+            hostname: lease.hostname.clone(),
+            mac: format_mac(&lease.mac),
+            ip: lease.ip,
+            event: event.as_str().to_string(),
+            vendor_class: request.vendor_class.clone(),
+            client_arch: request.client_arch,
+        };
+        let result = (|| -> Result<()> {
+            let dir = InstallSession::host_dir(&self.base_dir, &lease.hostname);
+            std::fs::create_dir_all(&dir)?;
+            let mut file = std::fs::OpenOptions::new()
+                .create(true)
+                .append(true)
+                .open(dir.join("dhcp-leases.jsonl"))?;
+            writeln!(file, "{}", serde_json::to_string(&entry)?)?;
+            Ok(())
+        })();
+        if let Err(e) = result {
+            warn!("Failed to log DHCP lease of {}: {}", lease.hostname, e);
+        }
+    }
+}
+
+/// Relay agents get the reply on the server port; configured clients by unicast; everyone
+/// else by broadcast, since a client without an address cannot answer ARP for it
+fn reply_destination(request: &DhcpRequest, reply_type: MessageType) -> SocketAddrV4 {
+    if !request.giaddr.is_unspecified() {
+        SocketAddrV4::new(request.giaddr, SERVER_PORT)
+    } else if !request.ciaddr.is_unspecified() && reply_type != MessageType::Nak {
+        SocketAddrV4::new(request.ciaddr, CLIENT_PORT)
+    } else {
+        SocketAddrV4::new(Ipv4Addr::BROADCAST, CLIENT_PORT)
+    }
+}
+
+/// UDP socket on port 67 that only sees traffic of `interface`
+fn bind_socket(interface: &str) -> Result<UdpSocket> {
+    let socket = std::net::UdpSocket::bind((Ipv4Addr::UNSPECIFIED, SERVER_PORT)).map_err(|e| {
+        AutoInstallError::NetworkError(format!(
+            "Cannot listen on UDP port {} ({}); run as root or grant CAP_NET_BIND_SERVICE",
+            SERVER_PORT, e
+        ))
+    })?;
+    socket.set_broadcast(true)?;
+    #[cfg(target_os = "linux")]
+    {
+        use std::os::fd::AsRawFd;
+        let name = interface.as_bytes();
+        // SAFETY: the fd is open for the call and `name` outlives it
+        let rc = unsafe {
+            libc::setsockopt(
+                socket.as_raw_fd(),
+                libc::SOL_SOCKET,
+                libc::SO_BINDTODEVICE,
+                name.as_ptr() as *const libc::c_void,
+                name.len() as libc::socklen_t,
+            )
+        };
+        if rc != 0 {
+            return Err(AutoInstallError::NetworkError(format!(
+                "Cannot bind the DHCP socket to {}: {}",
+                interface,
+                std::io::Error::last_os_error()
+            )));
+        }
+    }
+    #[cfg(not(target_os = "linux"))]
+    warn!(
+        "Binding to {} is only supported on Linux; answering on every interface",
+        interface
+    );
+    socket.set_nonblocking(true)?;
+    Ok(UdpSocket::from_std(socket)?)
+}
+
+fn push_option(packet: &mut Vec<u8>, code: u8, value: &[u8]) {
+    let len = value.len().min(255);
+    packet.push(code);
+    packet.push(len as u8);
+    packet.extend_from_slice(&value[..len]);
+}
+
+fn ipv4(bytes: &[u8]) -> Ipv4Addr {
+    Ipv4Addr::new(bytes[0], bytes[1], bytes[2], bytes[3])
+}
+
+fn malformed(reason: &str) -> AutoInstallError {
+    AutoInstallError::NetworkError(format!("Malformed DHCP packet: {}", reason))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn client_packet(message_type: u8, mac: [u8; 6], extra: &[(u8, Vec<u8>)]) -> Vec<u8> {
+        let mut packet = vec![0u8; HEADER_LEN];
+        packet[0] = 1;
+        packet[1] = 1;
+        packet[2] = 6;
+        packet[4..8].copy_from_slice(&[1, 2, 3, 4]);
+        packet[28..34].copy_from_slice(&mac);
+        packet[236..240].copy_from_slice(&MAGIC_COOKIE);
+        push_option(&mut packet, OPT_MESSAGE_TYPE, &[message_type]);
+        for (code, value) in extra {
+            push_option(&mut packet, *code, value);
+        }
+        packet.push(OPT_END);
+        packet
+    }
+
+    #[test]
+    fn test_static_lease_exchange_is_logged() {
+        let dir = tempfile::TempDir::new().unwrap();
+        let config: DhcpConfig = serde_yaml::from_str(
+            "interface: eno2\nserver_ip: 10.20.0.1\nboot_file: pxelinux.0\nboot_file_uefi: grubx64.efi\n",
+        )
+        .unwrap();
+        let mac = [0x52, 0x54, 0, 0x12, 0x34, 0x56];
+        let lease = StaticLease {
+            hostname: "web-01".to_string(),
+            mac,
+            ip: Ipv4Addr::new(10, 20, 0, 11),
+        };
+        let mut session = InstallSession::new("web-01");
+        session.save(dir.path()).unwrap();
+        let mut server = DhcpServer::new(config, vec![lease], dir.path());
+
+        let discover = DhcpRequest::parse(&client_packet(
+            1,
+            mac,
+            &[
+                (OPT_VENDOR_CLASS, b"PXEClient:Arch:00007".to_vec()),
+                (OPT_CLIENT_ARCH, vec![0, 7]),
+            ],
+        ))
+        .unwrap();
+        let offer = server.handle(&discover).unwrap();
+        assert_eq!(offer.message_type, MessageType::Offer);
+        assert_eq!(offer.destination, "255.255.255.255:68".parse().unwrap());
+        assert_eq!(&offer.packet[16..20], &[10, 20, 0, 11]);
+        assert_eq!(&offer.packet[108..119], b"grubx64.efi");
+
+        let request = DhcpRequest::parse(&client_packet(
+            3,
+            mac,
+            &[
+                (OPT_REQUESTED_IP, vec![10, 20, 0, 11]),
+                (OPT_SERVER_ID, vec![10, 20, 0, 1]),
+            ],
+        ))
+        .unwrap();
+        assert_eq!(
+            server.handle(&request).unwrap().message_type,
+            MessageType::Ack
+        );
+        let wrong = DhcpRequest::parse(&client_packet(
+            3,
+            mac,
+            &[(OPT_REQUESTED_IP, vec![10, 20, 0, 99])],
+        ))
+        .unwrap();
+        assert_eq!(
+            server.handle(&wrong).unwrap().message_type,
+            MessageType::Nak
+        );
+        let stranger = DhcpRequest::parse(&client_packet(1, [2; 6], &[])).unwrap();
+        assert!(server.handle(&stranger).is_none());
+        for i in 0..=MAX_UNKNOWN_CLIENTS as u32 {
+            let [a, b, c, d] = i.to_be_bytes();
+            let spoofed = DhcpRequest::parse(&client_packet(1, [6, 0, a, b, c, d], &[])).unwrap();
+            assert!(server.handle(&spoofed).is_none());
+        }
+        assert!(server.unknown_clients.len() <= MAX_UNKNOWN_CLIENTS);
+
+        let log = std::fs::read_to_string(
+            InstallSession::host_dir(dir.path(), "web-01").join("dhcp-leases.jsonl"),
+        )
+        .unwrap();
+        let events: Vec<LeaseEvent> = log
+            .lines()
+            .map(|line| serde_json::from_str(line).unwrap())
+            .collect();
+        let kinds: Vec<&str> = events.iter().map(|e| e.event.as_str()).collect();
+        assert_eq!(kinds, ["offer", "ack", "nak"]);
+        assert_eq!(
+            events[0].install_session.as_deref(),
+            Some(session.id.as_str())
+        );
+        assert_eq!(events[0].run_id, server.run_id);
+        assert_eq!(events[0].client_arch, Some(7));
+    }
+}
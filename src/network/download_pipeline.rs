@@ -0,0 +1,415 @@
+// file: src/network/download_pipeline.rs
+// version: 1.1.0
+// guid: 8c4f1e69-d27a-4b35-9e80-a3b6f5c2d917
+
+//! Streaming download pipeline for large artifacts
+//!
+//! Each artifact is hashed while it streams to disk, so verifying a multi-gigabyte ISO needs
+//! no second pass over it. Data goes to `<dest>.part` first; an interrupted download resumes
+//! from there with an HTTP range request and only a complete, verified file is renamed into
+//! place. Several artifacts download concurrently under one shared progress display, which
+//! lets a small signed checksum list be fetched and checked while the ISO is still in flight.
+
+use crate::error::AutoInstallError;
+use crate::Result;
+use futures::{StreamExt, TryStreamExt};
+use indicatif::{MultiProgress, ProgressBar, ProgressStyle};
+use reqwest::header::RANGE;
+use reqwest::StatusCode;
+use sha2::{Digest, Sha256};
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+use tokio::fs::{self, File, OpenOptions};
+use tokio::io::{AsyncReadExt, AsyncWriteExt};
+use tracing::{debug, info, warn};
+
+/// Suffix of the file a download is written to until it is complete and verified
+pub const PART_SUFFIX: &str = ".part";
+
+/// Artifacts downloaded at the same time by [`DownloadPipeline::fetch_all`]
+pub const DEFAULT_CONCURRENCY: usize = 4;
+
+/// Keyrings `gpgv` checks Ubuntu checksum list signatures against; missing ones are skipped
+pub const UBUNTU_KEYRINGS: &[&str] = &[
+    "/usr/share/keyrings/ubuntu-cdimage-keyring.gpg",
+    "/usr/share/keyrings/ubuntu-archive-keyring.gpg",
+];
+
+/// One file to download
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Artifact {
+    /// Short label for the progress display and errors
+    pub name: String,
+    pub url: String,
+    pub dest: PathBuf,
+}
+
+impl Artifact {
+    pub fn new(name: &str, url: &str, dest: PathBuf) -> Self {
+        Self {
+            name: name.to_string(),
+            url: url.to_string(),
+            dest,
+        }
+    }
+}
+
+/// A completed download
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Downloaded {
+    pub name: String,
+    pub dest: PathBuf,
+    /// Hex SHA-256 of the whole file, computed while streaming
+    pub sha256: String,
+    pub bytes: u64,
+    /// Bytes reused from an earlier partial download
+    pub resumed_from: u64,
+}
+
+/// Result of checking a detached signature
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum SignatureStatus {
+    Verified,
+    /// The signature could not be checked on this machine (reason given); it was not found bad
+    Unverifiable(String),
+}
+
+/// Where `dest` is written while downloading
+pub fn part_path(dest: &Path) -> PathBuf {
+    PathBuf::from(format!("{}{}", dest.display(), PART_SUFFIX))
+}
+
+/// Parse a `SHA256SUMS` file into file name -> lowercase hex digest
+///
+/// Accepts both text (`<digest>  <name>`) and binary (`<digest> *<name>`) entries.
+pub fn parse_sha256sums(text: &str) -> HashMap<String, String> {
+    text.lines()
+        .filter_map(|line| {
+            let (digest, name) = line.trim().split_once(char::is_whitespace)?;
+            let name = name.trim_start().trim_start_matches('*');
+            (digest.len() == 64
+                && digest.chars().all(|c| c.is_ascii_hexdigit())
+                && !name.is_empty())
+            .then(|| (name.to_string(), digest.to_lowercase()))
+        })
+        .collect()
+}
+
+/// Fail unless `actual` matches `expected`
+pub fn check_digest(name: &str, expected: &str, actual: &str) -> Result<()> {
+    if expected.eq_ignore_ascii_case(actual) {
+        Ok(())
+    } else {
+        Err(AutoInstallError::ValidationError(format!(
+            "Checksum mismatch for {}: expected {}, got {}",
+            name, expected, actual
+        )))
+    }
+}
+
+/// Check `signature` over `data` with `gpgv` and the installed Ubuntu keyrings
+///
+/// A bad signature is an error. A machine without `gpgv` or the keyrings yields
+/// [`SignatureStatus::Unverifiable`] so the caller can decide whether checksums alone suffice.
+pub async fn verify_signature(data: &Path, signature: &Path) -> Result<SignatureStatus> {
+    let keyrings: Vec<&str> = UBUNTU_KEYRINGS
+        .iter()
+        .copied()
+        .filter(|k| Path::new(k).exists())
+        .collect();
+    if keyrings.is_empty() {
+        return Ok(SignatureStatus::Unverifiable(
+            "no Ubuntu keyring installed (package ubuntu-keyring)".to_string(),
+        ));
+    }
+
+    let mut command = tokio::process::Command::new("gpgv");
+    for keyring in &keyrings {
+        command.args(["--keyring", keyring]);
+    }
+    let output = match command.arg(signature).arg(data).output().await {
+        Ok(output) => output,
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => {
+            return Ok(SignatureStatus::Unverifiable(
+                "gpgv is not installed".to_string(),
+            ))
+        }
+        Err(e) => return Err(e.into()),
+    };
+    if output.status.success() {
+        Ok(SignatureStatus::Verified)
+    } else {
+        Err(AutoInstallError::ValidationError(format!(
+            "Signature check of {} failed: {}",
+            data.display(),
+            String::from_utf8_lossy(&output.stderr).trim()
+        )))
+    }
+}
+
+/// Feed an existing partial download into `hasher`, returning its length
+async fn hash_existing(path: &Path, hasher: &mut Sha256) -> Result<u64> {
+    let mut file = File::open(path).await?;
+    let mut buffer = vec![0u8; 1 << 20];
+    let mut total = 0u64;
+    loop {
+        let read = file.read(&mut buffer).await?;
+        if read == 0 {
+            return Ok(total);
+        }
+        hasher.update(&buffer[..read]);
+        total += read as u64;
+    }
+}
+
+/// Concurrent, resumable, hash-while-downloading fetcher with a shared progress display
+pub struct DownloadPipeline {
+    client: reqwest::Client,
+    progress: MultiProgress,
+}
+
+impl DownloadPipeline {
+    pub fn new() -> Result<Self> {
+        let client = reqwest::Client::builder().build().map_err(|e| {
+            AutoInstallError::NetworkError(format!("Failed to create HTTP client: {}", e))
+        })?;
+        Ok(Self {
+            client,
+            progress: MultiProgress::new(),
+        })
+    }
+
+    /// Download every artifact, [`DEFAULT_CONCURRENCY`] at a time, in input order
+    ///
+    /// The first failure stops the others; their partial files stay behind for the next run
+    /// to resume.
+    pub async fn fetch_all(&self, artifacts: &[Artifact]) -> Result<Vec<Downloaded>> {
+        futures::stream::iter(artifacts)
+            .map(|artifact| self.fetch(artifact))
+            .buffered(DEFAULT_CONCURRENCY)
+            .try_collect()
+            .await
+    }
+
+    /// Download one artifact, resuming `<dest>.part` if an earlier attempt left one
+    pub async fn fetch(&self, artifact: &Artifact) -> Result<Downloaded> {
+        let part = part_path(&artifact.dest);
+        if let Some(parent) = artifact.dest.parent() {
+            fs::create_dir_all(parent).await?;
+        }
+
+        let (response, resumed_from, mut hasher) = loop {
+            let mut hasher = Sha256::new();
+            let offset = if part.exists() {
+                hash_existing(&part, &mut hasher).await?
+            } else {
+                0
+            };
+            let mut request = self.client.get(&artifact.url);
+            if offset > 0 {
+                request = request.header(RANGE, format!("bytes={}-", offset));
+            }
+            let response = request.send().await.map_err(|e| {
+                AutoInstallError::NetworkError(format!(
+                    "Download of {} failed: {}",
+                    artifact.url, e
+                ))
+            })?;
+            match response.status() {
+                StatusCode::PARTIAL_CONTENT if offset > 0 => break (response, offset, hasher),
+                // The partial file is already complete or longer than the remote file
+                StatusCode::RANGE_NOT_SATISFIABLE if offset > 0 => {
+                    warn!("Discarding stale partial download {}", part.display());
+                    fs::remove_file(&part).await?;
+                }
+                status if status.is_success() => {
+                    if offset > 0 {
+                        debug!(
+                            "{} does not support range requests; restarting",
+                            artifact.url
+                        );
+                    }
+                    break (response, 0, Sha256::new());
+                }
+                status => {
+                    return Err(AutoInstallError::NetworkError(format!(
+                        "Download of {} failed with status: {}",
+                        artifact.url, status
+                    )))
+                }
+            }
+        };
+
+        let mut file = if resumed_from > 0 {
+            info!("Resuming {} at {} bytes", artifact.name, resumed_from);
+            OpenOptions::new().append(true).open(&part).await?
+        } else {
+            File::create(&part).await?
+        };
+
+        let total = response.content_length().map(|len| len + resumed_from);
+        let bar = self.progress.add(ProgressBar::new(total.unwrap_or(0)));
+        bar.set_style(
+            ProgressStyle::default_bar()
+                .template("{prefix:>14} [{bar:30.cyan/blue}] {bytes}/{total_bytes} ({eta})")
+                .unwrap()
+                .progress_chars("#>-"),
+        );
+        bar.set_prefix(artifact.name.clone());
+        bar.set_position(resumed_from);
+
+        let mut written = resumed_from;
+        let mut stream = response.bytes_stream();
+        while let Some(chunk) = stream.next().await {
+            let chunk = chunk?;
+            file.write_all(&chunk).await?;
+            hasher.update(&chunk);
+            written += chunk.len() as u64;
+            bar.set_position(written);
+        }
+        file.flush().await?;
+        drop(file);
+
+        let sha256 = format!("{:x}", hasher.finalize());
+        fs::rename(&part, &artifact.dest).await?;
+        bar.finish();
+        info!(
+            "Downloaded {} ({} bytes) to {}",
+            artifact.name,
+            written,
+            artifact.dest.display()
+        );
+
+        Ok(Downloaded {
+            name: artifact.name.clone(),
+            dest: artifact.dest.clone(),
+            sha256,
+            bytes: written,
+            resumed_from,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::TempDir;
+    use tokio::net::TcpListener;
+
+    /// Minimal HTTP server for `body`; honours `Range: bytes=N-` unless `ranges` is false
+    async fn serve(body: Vec<u8>, ranges: bool) -> String {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let addr = listener.local_addr().unwrap();
+        tokio::spawn(async move {
+            loop {
+                let Ok((mut stream, _)) = listener.accept().await else {
+                    return;
+                };
+                let mut request = Vec::new();
+                let mut buf = [0u8; 1024];
+                while !request.ends_with(b"\r\n\r\n") {
+                    let n = stream.read(&mut buf).await.unwrap();
+                    if n == 0 {
+                        break;
+                    }
+                    request.extend_from_slice(&buf[..n]);
+                }
+                let request = String::from_utf8_lossy(&request).to_lowercase();
+                let offset = request
+                    .lines()
+                    .find_map(|l| l.strip_prefix("range: bytes="))
+                    .and_then(|r| r.trim().trim_end_matches('-').parse::<usize>().ok())
+                    .filter(|_| ranges);
+                let (status, slice) = match offset {
+                    Some(o) if o >= body.len() => ("416 Range Not Satisfiable", &body[..0]),
+                    Some(o) => ("206 Partial Content", &body[o..]),
+                    None => ("200 OK", &body[..]),
+                };
+                let head = format!(
+                    "HTTP/1.1 {}\r\nContent-Length: {}\r\nConnection: close\r\n\r\n",
+                    status,
+                    slice.len()
+                );
+                stream.write_all(head.as_bytes()).await.unwrap();
+                stream.write_all(slice).await.unwrap();
+            }
+        });
+        format!("http://{}/artifact", addr)
+    }
+
+    fn digest(data: &[u8]) -> String {
+        format!("{:x}", Sha256::digest(data))
+    }
+
+    #[tokio::test]
+    async fn test_fetch_resumes_partial_download() {
+        let body: Vec<u8> = (0..200_000u32).map(|i| (i % 251) as u8).collect();
+        let url = serve(body.clone(), true).await;
+        let dir = TempDir::new().unwrap();
+        let dest = dir.path().join("installer.iso");
+        std::fs::write(part_path(&dest), &body[..70_000]).unwrap();
+
+        let pipeline = DownloadPipeline::new().unwrap();
+        let artifact = Artifact::new("ISO", &url, dest.clone());
+        let downloaded = pipeline.fetch(&artifact).await.unwrap();
+
+        assert_eq!(downloaded.resumed_from, 70_000);
+        assert_eq!(downloaded.bytes, body.len() as u64);
+        assert_eq!(downloaded.sha256, digest(&body));
+        assert_eq!(std::fs::read(&dest).unwrap(), body);
+        assert!(!part_path(&dest).exists());
+
+        // A part file as long as the artifact is discarded and fetched again
+        std::fs::write(part_path(&dest), &body).unwrap();
+        assert_eq!(pipeline.fetch(&artifact).await.unwrap().resumed_from, 0);
+    }
+
+    #[tokio::test]
+    async fn test_fetch_restarts_without_ranges() {
+        let body = b"kernel image".to_vec();
+        let url = serve(body.clone(), false).await;
+        let dir = TempDir::new().unwrap();
+        let dest = dir.path().join("vmlinuz");
+        std::fs::write(part_path(&dest), b"garbage").unwrap();
+
+        let pipeline = DownloadPipeline::new().unwrap();
+        let kernel = Artifact::new("kernel", &url, dest.clone());
+        let initrd = Artifact::new("initrd", &url, dir.path().join("initrd"));
+
+        let downloaded = pipeline.fetch_all(&[kernel, initrd.clone()]).await.unwrap();
+        assert_eq!(downloaded[0].resumed_from, 0);
+        assert_eq!(downloaded[1].name, "initrd");
+        assert_eq!(std::fs::read(&dest).unwrap(), body);
+        assert_eq!(std::fs::read(&initrd.dest).unwrap(), body);
+    }
+
+    #[tokio::test]
+    async fn test_failed_download_creates_no_file() {
+        let dir = TempDir::new().unwrap();
+        let dest = dir.path().join("missing.iso");
+        let pipeline = DownloadPipeline::new().unwrap();
+        let result = pipeline
+            .fetch(&Artifact::new(
+                "ISO",
+                "http://127.0.0.1:1/missing.iso",
+                dest.clone(),
+            ))
+            .await;
+        assert!(result.is_err());
+        assert!(!dest.exists());
+    }
+
+    #[test]
+    fn test_parse_sha256sums() {
+        let sums = parse_sha256sums(&format!(
+            "{}  ubuntu-24.04-live-server-amd64.iso\n{} *ubuntu-24.04-desktop-amd64.iso\nnot a digest\n",
+            "A".repeat(64),
+            "b".repeat(64)
+        ));
+        assert_eq!(sums.len(), 2);
+        assert_eq!(sums["ubuntu-24.04-live-server-amd64.iso"], "a".repeat(64));
+        assert_eq!(sums["ubuntu-24.04-desktop-amd64.iso"], "b".repeat(64));
+        assert!(check_digest("x", &"A".repeat(64), &"a".repeat(64)).is_ok());
+        assert!(check_digest("x", &"a".repeat(64), &"b".repeat(64)).is_err());
+    }
+}
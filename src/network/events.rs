@@ -0,0 +1,240 @@
+// file: src/network/events.rs
+// version: 1.2.0
+// guid: 7d3b9f52-4a18-4e6c-b0d7-1e5c8a2f6d93
+
+//! Installation event bus
+//!
+//! `SshInstaller`, the progress trackers of the SSH client and `VmManager` publish what happens
+//! to an [`EventBus`] instead of calling reporters directly. Consumers (logging, report sinks,
+//! and whatever comes later) subscribe with [`spawn_subscriber`] and receive every event
+//! published after they subscribed. A subscriber that falls more than the bus capacity behind
+//! loses the oldest events and is told how many.
+
+use crate::network::progress::{log_progress, ProgressEvent};
+use crate::network::ssh_installer::session::InstallSession;
+use async_trait::async_trait;
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use tokio::sync::broadcast::{self, error::RecvError, error::TryRecvError};
+use tokio::task::JoinHandle;
+use tracing::{debug, warn};
+
+/// Events a subscriber may fall behind by before it loses the oldest
+pub const DEFAULT_CAPACITY: usize = 1024;
+
+/// Something that happened during an installation
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(tag = "event", rename_all = "snake_case")]
+pub enum InstallEvent {
+    /// An installation phase started on `host`
+    PhaseStarted { host: String, phase: String },
+    /// A non-fatal problem kept for the installation report
+    Warning { host: String, message: String },
+    /// Progress of a long remote command
+    Progress(ProgressEvent),
+    /// Lifecycle event such as `install.completed`, with the session record at that point
+    Session {
+        name: String,
+        session: Box<InstallSession>,
+    },
+    /// Status of the installer running inside a build VM
+    Vm { message: String },
+}
+
+/// Broadcast channel the installers publish to; clones share the channel
+#[derive(Debug, Clone)]
+pub struct EventBus {
+    sender: broadcast::Sender<InstallEvent>,
+}
+
+impl Default for EventBus {
+    fn default() -> Self {
+        Self::new(DEFAULT_CAPACITY)
+    }
+}
+
+impl EventBus {
+    pub fn new(capacity: usize) -> Self {
+        let (sender, _) = broadcast::channel(capacity);
+        Self { sender }
+    }
+
+    /// Send `event` to every subscriber; without subscribers it is dropped
+    pub fn publish(&self, event: InstallEvent) {
+        let _ = self.sender.send(event);
+    }
+
+    /// Receive the events published from now on
+    pub fn subscribe(&self) -> EventSubscription {
+        EventSubscription {
+            receiver: self.sender.subscribe(),
+        }
+    }
+
+    /// Drop this handle and wait for `subscribers` to handle every event
+    ///
+    /// Subscribers stop once every clone of the bus is gone, so the installers holding clones
+    /// must be dropped first.
+    pub async fn close(self, subscribers: Vec<JoinHandle<()>>) {
+        drop(self);
+        for subscriber in subscribers {
+            if let Err(e) = subscriber.await {
+                warn!("Event subscriber stopped abnormally: {}", e);
+            }
+        }
+    }
+}
+
+/// Receiving end of one subscriber
+pub struct EventSubscription {
+    receiver: broadcast::Receiver<InstallEvent>,
+}
+
+impl EventSubscription {
+    /// Next event, or `None` once the bus is closed
+    pub async fn next(&mut self) -> Option<InstallEvent> {
+        loop {
+            match self.receiver.recv().await {
+                Ok(event) => return Some(event),
+                Err(RecvError::Lagged(missed)) => {
+                    warn!("Event subscriber fell behind; {} events dropped", missed)
+                }
+                Err(RecvError::Closed) => return None,
+            }
+        }
+    }
+
+    /// Next event if one is waiting
+    pub fn try_next(&mut self) -> Option<InstallEvent> {
+        loop {
+            match self.receiver.try_recv() {
+                Ok(event) => return Some(event),
+                Err(TryRecvError::Lagged(missed)) => {
+                    warn!("Event subscriber fell behind; {} events dropped", missed)
+                }
+                Err(_) => return None,
+            }
+        }
+    }
+}
+
+/// A consumer of installation events
+#[async_trait]
+pub trait EventSubscriber: Send + 'static {
+    async fn handle(&mut self, event: &InstallEvent);
+
+    /// Called after the last event, once the bus is closed
+    async fn close(&mut self) {}
+}
+
+/// Feed the events of `bus` to `subscriber` on a background task until the bus is closed
+pub fn spawn_subscriber<S: EventSubscriber>(bus: &EventBus, mut subscriber: S) -> JoinHandle<()> {
+    let mut events = bus.subscribe();
+    tokio::spawn(async move {
+        while let Some(event) = events.next().await {
+            subscriber.handle(&event).await;
+        }
+        subscriber.close().await;
+    })
+}
+
+/// Writes progress to the log, every 10% per command
+///
+/// Phases and warnings are logged where they happen; they only appear here at debug level.
+#[derive(Debug, Default)]
+pub struct LogSubscriber {
+    last: HashMap<(String, String), u8>,
+}
+
+#[async_trait]
+impl EventSubscriber for LogSubscriber {
+    async fn handle(&mut self, event: &InstallEvent) {
+        match event {
+            InstallEvent::Progress(progress) => {
+                let key = (progress.host.clone(), progress.tool.clone());
+                // A lower percentage means the tool started over for a new command
+                let previous = self
+                    .last
+                    .get(&key)
+                    .copied()
+                    .filter(|last| *last < progress.percent);
+                log_progress(progress, previous);
+                self.last.insert(key, progress.percent);
+            }
+            InstallEvent::PhaseStarted { host, phase } => debug!("{}: {} started", host, phase),
+            InstallEvent::Warning { host, message } => debug!("{}: warning: {}", host, message),
+            InstallEvent::Session { name, session } => debug!("{}: {}", session.hostname, name),
+            InstallEvent::Vm { message } => debug!("VM: {}", message),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::{Arc, Mutex};
+
+    struct Collect(Arc<Mutex<Vec<String>>>);
+
+    #[async_trait]
+    impl EventSubscriber for Collect {
+        async fn handle(&mut self, event: &InstallEvent) {
+            let json = serde_json::to_value(event).unwrap();
+            self.0
+                .lock()
+                .unwrap()
+                .push(json["event"].as_str().unwrap().to_string());
+        }
+    }
+
+    #[tokio::test]
+    async fn test_subscribers_receive_every_event_until_closed() {
+        let bus = EventBus::default();
+        let seen = Arc::new(Mutex::new(Vec::new()));
+        let subscribers = vec![
+            spawn_subscriber(&bus, Collect(seen.clone())),
+            spawn_subscriber(&bus, LogSubscriber::default()),
+        ];
+
+        let installer = bus.clone();
+        installer.publish(InstallEvent::PhaseStarted {
+            host: "web-01".into(),
+            phase: "Phase 1: Packages".into(),
+        });
+        installer.publish(InstallEvent::Progress(ProgressEvent {
+            host: "web-01".into(),
+            tool: "apt".into(),
+            percent: 40,
+            detail: String::new(),
+        }));
+        installer.publish(InstallEvent::Session {
+            name: "install.completed".into(),
+            session: Box::new(InstallSession::new("web-01")),
+        });
+        drop(installer);
+        bus.close(subscribers).await;
+
+        assert_eq!(
+            *seen.lock().unwrap(),
+            vec!["phase_started", "progress", "session"]
+        );
+    }
+
+    #[test]
+    fn test_slow_subscriber_loses_oldest_events() {
+        let bus = EventBus::new(2);
+        let mut events = bus.subscribe();
+        for message in ["a", "b", "c"] {
+            bus.publish(InstallEvent::Vm {
+                message: message.into(),
+            });
+        }
+        let messages: Vec<String> = std::iter::from_fn(|| events.try_next())
+            .map(|event| match event {
+                InstallEvent::Vm { message } => message,
+                other => panic!("unexpected {:?}", other),
+            })
+            .collect();
+        assert_eq!(messages, vec!["b", "c"]);
+    }
+}
@@ -0,0 +1,616 @@
+// file: src/network/fleet.rs
+// version: 1.4.0
+// guid: 4f9b2d68-c13e-4a70-8d5f-b6e1a7c3092d
+
+//! Fleet rollouts: canary stage, batches and the run record
+//!
+//! A deployment installs the canaries first and checks them before anything else is touched.
+//! The remaining hosts follow in batches, each batch split into waves no larger than the
+//! `max_unavailable` budget left after failures, so a bad config cannot take down more hosts
+//! than the policy allows. Progress is written to `logs/fleet/<run-id>.json` after every host.
+//!
+//! `fleet cancel <run-id>` writes a [`CancelRequest`] next to the record. The running deploy
+//! picks it up, skips every host that has not started and passes the request's
+//! [`CancelPolicy`] to the installs in flight through a [`CancelSignal`]; they act on it at
+//! their next phase boundary.
+
+use crate::config::inventory::{InventoryHost, Promotion, RolloutPolicy};
+use crate::logging::redact;
+use crate::network::ssh_installer::session::{InstallSession, SessionStatus};
+use crate::utils::CancellationToken;
+use crate::Result;
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, OnceLock};
+
+/// Stage label of the canary hosts
+pub const CANARY_STAGE: &str = "canary";
+
+/// Where one host stands in a rollout
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum HostStatus {
+    Pending,
+    Running,
+    Completed,
+    Failed,
+    /// Installed, but the post-install checks did not pass
+    VerificationFailed,
+    /// Never started because the rollout stopped first
+    Skipped,
+    /// Started, then stopped by `fleet cancel` or a shutdown
+    Cancelled,
+}
+
+impl HostStatus {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            HostStatus::Pending => "pending",
+            HostStatus::Running => "running",
+            HostStatus::Completed => "completed",
+            HostStatus::Failed => "failed",
+            HostStatus::VerificationFailed => "verification failed",
+            HostStatus::Skipped => "skipped",
+            HostStatus::Cancelled => "cancelled",
+        }
+    }
+
+    /// Failed to install or to verify; counts against the availability budget
+    pub fn is_failure(&self) -> bool {
+        matches!(self, HostStatus::Failed | HostStatus::VerificationFailed)
+    }
+}
+
+/// Outcome of one host in a rollout
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct HostRecord {
+    pub hostname: String,
+    pub address: String,
+    /// `canary` or `batch N`
+    pub stage: String,
+    pub status: HostStatus,
+    /// Error or skip reason
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub detail: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub started_at: Option<DateTime<Utc>>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub finished_at: Option<DateTime<Utc>>,
+}
+
+/// Overall state of a rollout
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum RunStatus {
+    Running,
+    Completed,
+    /// Stopped by a failed canary stage, an exhausted availability budget or the operator
+    Aborted,
+    /// Stopped by `fleet cancel` or a shutdown
+    Cancelled,
+}
+
+/// What a cancelled run does with the hosts that are installing
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum CancelPolicy {
+    /// Let the running phase finish, then stop; the target is left as-is and can be resumed
+    #[default]
+    FinishPhase,
+    /// Stop after the running phase and keep the SSH session open for debugging
+    Hold,
+    /// Stop after the running phase, then unmount, export the pools, close LUKS and wipe the disk
+    AbortCleanup,
+}
+
+impl CancelPolicy {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            CancelPolicy::FinishPhase => "finish-phase",
+            CancelPolicy::Hold => "hold",
+            CancelPolicy::AbortCleanup => "abort-cleanup",
+        }
+    }
+}
+
+/// Request to cancel a run, written by `fleet cancel` to `logs/fleet/<run-id>.cancel.json`
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct CancelRequest {
+    pub policy: CancelPolicy,
+    pub requested_at: DateTime<Utc>,
+}
+
+impl CancelRequest {
+    pub fn path(base_dir: &Path, run_id: &str) -> PathBuf {
+        base_dir
+            .join("logs")
+            .join("fleet")
+            .join(format!("{}.cancel.json", run_id))
+    }
+
+    /// The pending request for `run_id`, if any
+    pub fn load(base_dir: &Path, run_id: &str) -> Option<Self> {
+        let content = std::fs::read_to_string(Self::path(base_dir, run_id)).ok()?;
+        serde_json::from_str(&content).ok()
+    }
+
+    pub fn remove(base_dir: &Path, run_id: &str) {
+        let _ = std::fs::remove_file(Self::path(base_dir, run_id));
+    }
+}
+
+/// Cancellation of a fleet run shared with its installs; the first requested policy wins
+#[derive(Debug, Clone, Default)]
+pub struct CancelSignal {
+    policy: Arc<OnceLock<CancelPolicy>>,
+    token: CancellationToken,
+}
+
+impl CancelSignal {
+    pub fn request(&self, policy: CancelPolicy) {
+        let _ = self.policy.set(policy);
+        self.token.cancel();
+    }
+
+    /// Policy of the cancellation, `None` while the run goes on
+    pub fn requested(&self) -> Option<CancelPolicy> {
+        self.policy.get().copied()
+    }
+
+    /// Wait until the run is cancelled
+    pub async fn cancelled(&self) {
+        self.token.cancelled().await
+    }
+}
+
+/// Order in which hosts are deployed, as indexes into the inventory host list
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct RolloutPlan {
+    pub canaries: Vec<usize>,
+    pub batches: Vec<Vec<usize>>,
+}
+
+impl RolloutPlan {
+    /// Hosts marked `canary: true` go first, else the first `policy.canaries`; the rest are batched
+    pub fn new(hosts: &[InventoryHost], policy: &RolloutPolicy) -> Self {
+        let marked: Vec<usize> = (0..hosts.len()).filter(|&i| hosts[i].canary).collect();
+        let canaries = if marked.is_empty() {
+            (0..hosts.len().min(policy.canaries)).collect()
+        } else {
+            marked
+        };
+        let rest: Vec<usize> = (0..hosts.len()).filter(|i| !canaries.contains(i)).collect();
+        let batches = rest
+            .chunks(policy.batch_size.max(1))
+            .map(|chunk| chunk.to_vec())
+            .collect();
+        Self { canaries, batches }
+    }
+
+    /// Stage label and hosts of each stage, canaries first
+    pub fn stages(&self) -> Vec<(String, Vec<usize>)> {
+        let mut stages = Vec::new();
+        if !self.canaries.is_empty() {
+            stages.push((CANARY_STAGE.to_string(), self.canaries.clone()));
+        }
+        for (i, batch) in self.batches.iter().enumerate() {
+            stages.push((format!("batch {}", i + 1), batch.clone()));
+        }
+        stages
+    }
+}
+
+/// Whether to go on after the canary stage
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum CanaryDecision {
+    Continue,
+    /// Within the threshold, but the policy asks the operator first
+    Ask,
+    Abort(String),
+}
+
+/// Decide on the canary stage from its host records
+pub fn canary_decision(canaries: &[&HostRecord], policy: &RolloutPolicy) -> CanaryDecision {
+    let failed: Vec<&str> = canaries
+        .iter()
+        .filter(|r| r.status.is_failure())
+        .map(|r| r.hostname.as_str())
+        .collect();
+    if failed.len() > policy.canary_failure_threshold {
+        return CanaryDecision::Abort(format!(
+            "{} of {} canaries failed ({}); threshold is {}",
+            failed.len(),
+            canaries.len(),
+            failed.join(", "),
+            policy.canary_failure_threshold
+        ));
+    }
+    match policy.promotion {
+        Promotion::Auto => CanaryDecision::Continue,
+        Promotion::Prompt => CanaryDecision::Ask,
+    }
+}
+
+/// Hosts that may start together when `failed` hosts are already down
+pub fn wave_size(policy: &RolloutPolicy, failed: usize) -> usize {
+    policy.max_unavailable.saturating_sub(failed)
+}
+
+/// Check the session record of an install started at `since`; `Err` carries the reason it does not pass
+pub fn verify_session(
+    session: &InstallSession,
+    policy: &RolloutPolicy,
+    since: DateTime<Utc>,
+) -> std::result::Result<(), String> {
+    if session.started_at < since {
+        return Err("no session record from this run".to_string());
+    }
+    if session.status != SessionStatus::Completed {
+        return Err(format!("session ended as {:?}", session.status));
+    }
+    if policy.require_compliance {
+        let failed: Vec<&str> = session
+            .compliance
+            .iter()
+            .filter(|c| !c.passed)
+            .map(|c| c.title.as_str())
+            .collect();
+        if !failed.is_empty() {
+            return Err(format!("compliance checks failed: {}", failed.join("; ")));
+        }
+    }
+    Ok(())
+}
+
+/// Record of one fleet deployment, rewritten after every host
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct FleetRun {
+    pub run_id: String,
+    /// Inventory file the run was started from
+    pub inventory: String,
+    pub status: RunStatus,
+    /// Why the run was aborted or cancelled
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub reason: Option<String>,
+    /// Policy applied to the hosts in flight when the run was cancelled
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub cancel_policy: Option<CancelPolicy>,
+    pub started_at: DateTime<Utc>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub finished_at: Option<DateTime<Utc>>,
+    pub hosts: Vec<HostRecord>,
+}
+
+impl FleetRun {
+    /// New run over `hosts`, every host pending in its planned stage
+    pub fn new(inventory: &str, hosts: &[InventoryHost], plan: &RolloutPlan) -> Self {
+        let mut records: Vec<HostRecord> = hosts
+            .iter()
+            .map(|host| HostRecord {
+                hostname: host.hostname.clone(),
+                address: host.address().to_string(),
+                stage: String::new(),
+                status: HostStatus::Pending,
+                detail: None,
+                started_at: None,
+                finished_at: None,
+            })
+            .collect();
+        for (stage, indexes) in plan.stages() {
+            for i in indexes {
+                records[i].stage = stage.clone();
+            }
+        }
+        Self {
+            run_id: uuid::Uuid::new_v4().to_string(),
+            inventory: inventory.to_string(),
+            status: RunStatus::Running,
+            reason: None,
+            cancel_policy: None,
+            started_at: Utc::now(),
+            finished_at: None,
+            hosts: records,
+        }
+    }
+
+    /// Path of the record for `run_id` under `base_dir`
+    pub fn path(base_dir: &Path, run_id: &str) -> PathBuf {
+        base_dir
+            .join("logs")
+            .join("fleet")
+            .join(format!("{}.json", run_id))
+    }
+
+    pub fn save(&self, base_dir: &Path) -> Result<PathBuf> {
+        let path = Self::path(base_dir, &self.run_id);
+        if let Some(parent) = path.parent() {
+            std::fs::create_dir_all(parent)?;
+        }
+        std::fs::write(
+            &path,
+            redact::scrub(&serde_json::to_string_pretty(self)?).as_bytes(),
+        )?;
+        Ok(path)
+    }
+
+    pub fn load(base_dir: &Path, run_id: &str) -> Result<Self> {
+        let path = Self::path(base_dir, run_id);
+        let content = std::fs::read_to_string(&path).map_err(|e| {
+            crate::error::AutoInstallError::ConfigError(format!(
+                "No fleet run record at {}: {}",
+                path.display(),
+                e
+            ))
+        })?;
+        Ok(serde_json::from_str(&content)?)
+    }
+
+    /// Ask the deploy running `run_id` to stop; fails when the run has already ended
+    pub fn request_cancel(
+        base_dir: &Path,
+        run_id: &str,
+        policy: CancelPolicy,
+    ) -> Result<(Self, PathBuf)> {
+        let run = Self::load(base_dir, run_id)?;
+        if run.status != RunStatus::Running {
+            return Err(crate::error::AutoInstallError::ValidationError(format!(
+                "Fleet run {} has already ended ({:?})",
+                run_id, run.status
+            )));
+        }
+        let path = CancelRequest::path(base_dir, run_id);
+        let request = CancelRequest {
+            policy,
+            requested_at: Utc::now(),
+        };
+        std::fs::write(&path, serde_json::to_string_pretty(&request)?)?;
+        Ok((run, path))
+    }
+
+    /// Hosts that failed to install or verify so far
+    pub fn failures(&self) -> usize {
+        self.hosts.iter().filter(|h| h.status.is_failure()).count()
+    }
+
+    /// Mark every host that has not started as skipped and end the run as aborted
+    pub fn abort(&mut self, reason: &str) {
+        for host in &mut self.hosts {
+            if host.status == HostStatus::Pending {
+                host.status = HostStatus::Skipped;
+                host.detail = Some(reason.to_string());
+            }
+        }
+        self.status = RunStatus::Aborted;
+        self.reason = Some(reason.to_string());
+        self.finished_at = Some(Utc::now());
+    }
+
+    /// Like [`FleetRun::abort`], but the run ends as cancelled; `policy` is `None` on shutdown
+    pub fn cancel(&mut self, reason: &str, policy: Option<CancelPolicy>) {
+        self.abort(reason);
+        self.status = RunStatus::Cancelled;
+        self.cancel_policy = policy;
+    }
+
+    /// Cancel the run once `signal` or the shutdown `token` fires; returns whether it has stopped
+    pub fn stop_if_cancelled(&mut self, signal: &CancelSignal, token: &CancellationToken) -> bool {
+        if self.status == RunStatus::Running {
+            if let Some(policy) = signal.requested() {
+                self.cancel("cancelled with fleet cancel", Some(policy));
+            } else if token.is_cancelled() {
+                self.cancel("cancelled by operator", None);
+            }
+        }
+        self.is_stopped()
+    }
+
+    /// Whether the run has stopped early, by failures or cancellation
+    pub fn is_stopped(&self) -> bool {
+        matches!(self.status, RunStatus::Aborted | RunStatus::Cancelled)
+    }
+
+    /// Host counts per outcome, e.g. `3 completed, 1 failed, 2 cancelled, 4 skipped`
+    pub fn tally(&self) -> String {
+        let count = |wanted: &[HostStatus]| {
+            self.hosts
+                .iter()
+                .filter(|h| wanted.contains(&h.status))
+                .count()
+        };
+        let mut parts = vec![
+            format!("{} completed", count(&[HostStatus::Completed])),
+            format!(
+                "{} failed",
+                count(&[HostStatus::Failed, HostStatus::VerificationFailed])
+            ),
+        ];
+        for status in [
+            HostStatus::Cancelled,
+            HostStatus::Skipped,
+            HostStatus::Running,
+            HostStatus::Pending,
+        ] {
+            let n = count(&[status]);
+            if n > 0 {
+                parts.push(format!("{} {}", n, status.as_str()));
+            }
+        }
+        parts.join(", ")
+    }
+
+    /// One line per host: hostname, stage, status and detail
+    pub fn summary_lines(&self) -> Vec<String> {
+        let width = self
+            .hosts
+            .iter()
+            .map(|h| h.hostname.len())
+            .max()
+            .unwrap_or(0);
+        self.hosts
+            .iter()
+            .map(|h| {
+                let mut line = format!(
+                    "{:width$}  {:8}  {}",
+                    h.hostname,
+                    h.stage,
+                    h.status.as_str(),
+                    width = width
+                );
+                if let Some(detail) = &h.detail {
+                    line.push_str(&format!(" ({})", detail));
+                }
+                line
+            })
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::hardening::{ComplianceResult, HardeningControl};
+
+    fn hosts(names: &[&str]) -> Vec<InventoryHost> {
+        names
+            .iter()
+            .map(|n| InventoryHost {
+                hostname: n.to_string(),
+                host: None,
+                username: None,
+                preset: None,
+                target_config: None,
+                canary: false,
+                mac: None,
+                labels: Default::default(),
+            })
+            .collect()
+    }
+
+    #[test]
+    fn test_plan_canaries_and_batches() {
+        let policy = RolloutPolicy {
+            canaries: 2,
+            batch_size: 2,
+            ..Default::default()
+        };
+        let mut inventory = hosts(&["a", "b", "c", "d", "e"]);
+        let plan = RolloutPlan::new(&inventory, &policy);
+        assert_eq!(plan.canaries, vec![0, 1]);
+        assert_eq!(plan.batches, vec![vec![2, 3], vec![4]]);
+
+        inventory[3].canary = true;
+        let plan = RolloutPlan::new(&inventory, &policy);
+        assert_eq!(plan.canaries, vec![3]);
+        assert_eq!(plan.batches, vec![vec![0, 1], vec![2, 4]]);
+        let run = FleetRun::new("fleet.yaml", &inventory, &plan);
+        assert_eq!(run.hosts[3].stage, "canary");
+        assert_eq!(run.hosts[4].stage, "batch 2");
+
+        let policy = RolloutPolicy {
+            canaries: 0,
+            ..Default::default()
+        };
+        let plan = RolloutPlan::new(&hosts(&["a", "b"]), &policy);
+        assert!(plan.canaries.is_empty());
+        assert_eq!(plan.stages()[0].0, "batch 1");
+    }
+
+    #[test]
+    fn test_canary_decision_and_budget() {
+        let record = |name: &str, status| HostRecord {
+            hostname: name.to_string(),
+            address: name.to_string(),
+            stage: CANARY_STAGE.to_string(),
+            status,
+            detail: None,
+            started_at: None,
+            finished_at: None,
+        };
+        let ok = record("a", HostStatus::Completed);
+        let bad = record("b", HostStatus::VerificationFailed);
+        let mut policy = RolloutPolicy::default();
+        assert_eq!(canary_decision(&[&ok], &policy), CanaryDecision::Continue);
+        assert!(matches!(
+            canary_decision(&[&ok, &bad], &policy),
+            CanaryDecision::Abort(reason) if reason.contains("1 of 2 canaries failed (b)")
+        ));
+        policy.canary_failure_threshold = 1;
+        policy.promotion = Promotion::Prompt;
+        assert_eq!(canary_decision(&[&ok, &bad], &policy), CanaryDecision::Ask);
+
+        policy.max_unavailable = 3;
+        assert_eq!(wave_size(&policy, 1), 2);
+        assert_eq!(wave_size(&policy, 4), 0);
+    }
+
+    #[test]
+    fn test_verify_session_and_abort() {
+        let policy = RolloutPolicy::default();
+        let since = Utc::now() - chrono::Duration::minutes(1);
+        let mut session = InstallSession::new("a");
+        assert!(verify_session(&session, &policy, since).is_err());
+        session.status = SessionStatus::Completed;
+        session.compliance.push(ComplianceResult {
+            control: HardeningControl::Ssh,
+            title: "sshd: PermitRootLogin no".to_string(),
+            passed: false,
+        });
+        let err = verify_session(&session, &policy, since).unwrap_err();
+        assert!(err.contains("PermitRootLogin"), "{}", err);
+        let lenient = RolloutPolicy {
+            require_compliance: false,
+            ..Default::default()
+        };
+        assert!(verify_session(&session, &lenient, since).is_ok());
+        assert!(verify_session(&session, &lenient, Utc::now()).is_err());
+
+        let inventory = hosts(&["a", "b"]);
+        let mut run = FleetRun::new(
+            "fleet.yaml",
+            &inventory,
+            &RolloutPlan::new(&inventory, &policy),
+        );
+        run.hosts[0].status = HostStatus::Failed;
+        run.abort("canary stage failed");
+        assert_eq!(run.status, RunStatus::Aborted);
+        assert_eq!(run.failures(), 1);
+        assert_eq!(run.hosts[1].status, HostStatus::Skipped);
+        assert!(run.summary_lines()[1].ends_with("skipped (canary stage failed)"));
+    }
+
+    #[test]
+    fn test_cancel_request_and_tally() {
+        let dir = tempfile::TempDir::new().unwrap();
+        let inventory = hosts(&["a", "b", "c", "d"]);
+        let policy = RolloutPolicy::default();
+        let mut run = FleetRun::new(
+            "fleet.yaml",
+            &inventory,
+            &RolloutPlan::new(&inventory, &policy),
+        );
+        run.save(dir.path()).unwrap();
+
+        let signal = CancelSignal::default();
+        let token = CancellationToken::new();
+        assert!(!run.stop_if_cancelled(&signal, &token));
+        let (_, path) =
+            FleetRun::request_cancel(dir.path(), &run.run_id, CancelPolicy::Hold).unwrap();
+        assert!(path.ends_with(format!("{}.cancel.json", run.run_id)));
+        let request = CancelRequest::load(dir.path(), &run.run_id).unwrap();
+        signal.request(request.policy);
+        signal.request(CancelPolicy::AbortCleanup);
+        assert_eq!(signal.requested(), Some(CancelPolicy::Hold));
+
+        run.hosts[0].status = HostStatus::Completed;
+        run.hosts[1].status = HostStatus::Failed;
+        run.hosts[2].status = HostStatus::Cancelled;
+        assert!(run.stop_if_cancelled(&signal, &token));
+        assert_eq!(run.status, RunStatus::Cancelled);
+        assert_eq!(run.cancel_policy, Some(CancelPolicy::Hold));
+        assert_eq!(run.tally(), "1 completed, 1 failed, 1 cancelled, 1 skipped");
+
+        run.save(dir.path()).unwrap();
+        assert!(FleetRun::request_cancel(dir.path(), &run.run_id, CancelPolicy::Hold).is_err());
+    }
+}
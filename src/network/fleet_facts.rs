@@ -0,0 +1,346 @@
+// file: src/network/fleet_facts.rs
+// version: 1.0.0
+// guid: 1e6b4f82-a39d-4c57-b0e8-7d2c5a9f3e16
+
+//! Facts export across an inventory
+//!
+//! `fleet facts` connects to every inventory host, reads the same typed facts the installer
+//! uses plus the OS release and ZFS pools, and joins them with what the agent recorded about
+//! the host under `logs/<hostname>/`. The combined dataset is written as JSON, or as CSV with
+//! one row per host for spreadsheets. Unreachable hosts stay in the export with their error.
+
+use crate::config::inventory::InventoryHost;
+use crate::network::ssh_installer::drift::HostBaseline;
+use crate::network::ssh_installer::facts::{FactsCollector, TargetFacts};
+use crate::network::ssh_installer::runbook::{self, PoolInfo};
+use crate::network::ssh_installer::session::{InstallSession, SessionStatus};
+use crate::network::SshClient;
+use crate::Result;
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use std::path::Path;
+
+/// Distribution identification
+pub const OS_RELEASE_COMMAND: &str = "cat /etc/os-release";
+/// Running kernel
+pub const KERNEL_COMMAND: &str = "uname -r";
+
+/// Operating system of a host
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct OsFacts {
+    /// `PRETTY_NAME`, e.g. `Ubuntu 24.04.1 LTS`
+    pub name: Option<String>,
+    /// `VERSION_ID`, e.g. `24.04`
+    pub version: Option<String>,
+    pub codename: Option<String>,
+    pub kernel: Option<String>,
+}
+
+/// Parse `/etc/os-release`
+pub fn parse_os_release(text: &str) -> OsFacts {
+    let value = |key: &str| {
+        text.lines().find_map(|line| {
+            let rest = line.strip_prefix(key)?.strip_prefix('=')?;
+            Some(rest.trim().trim_matches('"').to_string())
+        })
+    };
+    OsFacts {
+        name: value("PRETTY_NAME"),
+        version: value("VERSION_ID"),
+        codename: value("VERSION_CODENAME"),
+        kernel: None,
+    }
+}
+
+/// What the agent recorded about a host
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ManagedState {
+    /// Outcome of the last install session
+    pub last_install: Option<SessionStatus>,
+    pub last_install_at: Option<DateTime<Utc>>,
+    pub config_checksum: Option<String>,
+    /// Archive snapshot the last install was pinned to
+    pub apt_snapshot: Option<String>,
+    /// A drift baseline exists for the host
+    pub baseline: bool,
+}
+
+impl ManagedState {
+    /// Read the session and baseline records of `hostname` under `base_dir`
+    pub fn from_records(base_dir: &Path, hostname: &str) -> Self {
+        let mut state = Self {
+            baseline: HostBaseline::record_path(base_dir, hostname).exists(),
+            ..Default::default()
+        };
+        if let Ok(session) = InstallSession::load(base_dir, hostname) {
+            state.last_install = Some(session.status);
+            state.last_install_at = Some(session.updated_at);
+            state.config_checksum = session.config_checksum;
+            state.apt_snapshot = session.apt_snapshot.map(|s| s.to_string());
+        }
+        state
+    }
+}
+
+/// Everything exported for one host
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct HostFacts {
+    pub hostname: String,
+    pub address: String,
+    /// Why the host could not be read; the live facts are empty then
+    pub error: Option<String>,
+    pub os: OsFacts,
+    pub facts: TargetFacts,
+    pub pools: Vec<PoolInfo>,
+    pub managed: ManagedState,
+}
+
+impl HostFacts {
+    /// Connect to `host` and read its facts; connection failures are recorded, not returned
+    pub async fn collect(host: &InventoryHost, default_username: &str, base_dir: &Path) -> Self {
+        let mut record = Self {
+            hostname: host.hostname.clone(),
+            address: host.address().to_string(),
+            error: None,
+            os: OsFacts::default(),
+            facts: TargetFacts::default(),
+            pools: Vec::new(),
+            managed: ManagedState::from_records(base_dir, &host.hostname),
+        };
+        let username = host.username.as_deref().unwrap_or(default_username);
+        let mut ssh = SshClient::new();
+        if let Err(e) = ssh.connect(host.address(), username).await {
+            record.error = Some(e.to_string());
+            return record;
+        }
+        record.facts = FactsCollector::new(&mut ssh).collect().await;
+        if let Ok(text) = ssh.execute_with_output(OS_RELEASE_COMMAND).await {
+            record.os = parse_os_release(&text);
+        }
+        record.os.kernel = ssh
+            .execute_with_output(KERNEL_COMMAND)
+            .await
+            .ok()
+            .map(|k| k.trim().to_string())
+            .filter(|k| !k.is_empty());
+        if let Ok(text) = ssh.execute_with_output(runbook::POOLS_COMMAND).await {
+            record.pools = runbook::parse_pools(&text);
+        }
+        ssh.disconnect();
+        record
+    }
+}
+
+/// Facts of every host in an inventory
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct FleetFacts {
+    pub inventory: String,
+    pub generated_at: DateTime<Utc>,
+    pub hosts: Vec<HostFacts>,
+}
+
+/// Columns of the CSV export, in order
+pub const CSV_COLUMNS: &[&str] = &[
+    "hostname",
+    "address",
+    "reachable",
+    "os",
+    "os_version",
+    "kernel",
+    "architecture",
+    "cpu_model",
+    "cpus",
+    "memory_mb",
+    "disks",
+    "disk_total_gb",
+    "interfaces",
+    "addresses",
+    "pools",
+    "last_install",
+    "last_install_at",
+    "config_checksum",
+    "apt_snapshot",
+    "baseline",
+    "error",
+];
+
+impl FleetFacts {
+    pub fn to_json(&self) -> Result<String> {
+        Ok(serde_json::to_string_pretty(self)?)
+    }
+
+    /// One row per host; list-valued columns are joined with `;`
+    pub fn to_csv(&self) -> String {
+        let mut out = CSV_COLUMNS.join(",");
+        out.push('\n');
+        for host in &self.hosts {
+            let facts = &host.facts;
+            let cpu = facts.cpu.as_ref();
+            let text = |value: &Option<String>| value.clone().unwrap_or_default();
+            let row = [
+                host.hostname.clone(),
+                host.address.clone(),
+                host.error.is_none().to_string(),
+                text(&host.os.name),
+                text(&host.os.version),
+                text(&host.os.kernel),
+                cpu.and_then(|c| c.architecture.clone()).unwrap_or_default(),
+                cpu.and_then(|c| c.model_name.clone()).unwrap_or_default(),
+                cpu.map(|c| c.cpus.to_string()).unwrap_or_default(),
+                facts
+                    .memory_total_mb
+                    .map(|mb| mb.to_string())
+                    .unwrap_or_default(),
+                facts
+                    .disks
+                    .iter()
+                    .map(|d| format!("{}:{}", d.path, d.size_bytes))
+                    .collect::<Vec<_>>()
+                    .join(";"),
+                if facts.disks.is_empty() {
+                    String::new()
+                } else {
+                    let bytes: u64 = facts.disks.iter().map(|d| d.size_bytes).sum();
+                    format!("{:.1}", bytes as f64 / 1e9)
+                },
+                facts
+                    .interfaces
+                    .iter()
+                    .filter(|i| i.name != "lo")
+                    .map(|i| i.name.clone())
+                    .collect::<Vec<_>>()
+                    .join(";"),
+                facts
+                    .interfaces
+                    .iter()
+                    .filter(|i| i.name != "lo")
+                    .flat_map(|i| i.addresses.iter().cloned())
+                    .collect::<Vec<_>>()
+                    .join(";"),
+                host.pools
+                    .iter()
+                    .map(|p| format!("{}:{}:{}", p.name, p.size, p.health))
+                    .collect::<Vec<_>>()
+                    .join(";"),
+                host.managed
+                    .last_install
+                    .map(|s| {
+                        serde_json::to_value(s)
+                            .ok()
+                            .and_then(|v| v.as_str().map(str::to_string))
+                            .unwrap_or_default()
+                    })
+                    .unwrap_or_default(),
+                host.managed
+                    .last_install_at
+                    .map(|t| t.to_rfc3339())
+                    .unwrap_or_default(),
+                text(&host.managed.config_checksum),
+                text(&host.managed.apt_snapshot),
+                host.managed.baseline.to_string(),
+                text(&host.error),
+            ];
+            let cells: Vec<String> = row.iter().map(|cell| csv_field(cell)).collect();
+            out.push_str(&cells.join(","));
+            out.push('\n');
+        }
+        out
+    }
+}
+
+/// Quote a CSV field when it contains a separator, quote or line break
+fn csv_field(value: &str) -> String {
+    if value.contains([',', '"', '\n', '\r']) {
+        format!("\"{}\"", value.replace('"', "\"\""))
+    } else {
+        value.to_string()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::network::ssh_installer::facts::CpuFacts;
+    use crate::network::ssh_installer::investigation_report::DiskReport;
+
+    #[test]
+    fn test_parse_os_release() {
+        let os = parse_os_release(
+            "PRETTY_NAME=\"Ubuntu 24.04.1 LTS\"\nNAME=\"Ubuntu\"\nVERSION_ID=\"24.04\"\nVERSION_CODENAME=noble\n",
+        );
+        assert_eq!(os.name.as_deref(), Some("Ubuntu 24.04.1 LTS"));
+        assert_eq!(os.version.as_deref(), Some("24.04"));
+        assert_eq!(os.codename.as_deref(), Some("noble"));
+    }
+
+    #[test]
+    fn test_csv_export() {
+        let reachable = HostFacts {
+            hostname: "web-01".to_string(),
+            address: "10.0.0.5".to_string(),
+            error: None,
+            os: OsFacts {
+                name: Some("Ubuntu 24.04.1 LTS".to_string()),
+                version: Some("24.04".to_string()),
+                codename: None,
+                kernel: Some("6.8.0-45-generic".to_string()),
+            },
+            facts: TargetFacts {
+                disks: vec![DiskReport {
+                    name: "nvme0n1".to_string(),
+                    path: "/dev/nvme0n1".to_string(),
+                    size_bytes: 512_000_000_000,
+                    model: None,
+                    serial: None,
+                    rotational: false,
+                    transport: Some("nvme".to_string()),
+                    partitions: Vec::new(),
+                }],
+                cpu: Some(CpuFacts {
+                    model_name: Some("EPYC 7302, 16-Core".to_string()),
+                    cpus: 32,
+                    ..Default::default()
+                }),
+                memory_total_mb: Some(65536),
+                ..Default::default()
+            },
+            pools: vec![PoolInfo {
+                name: "rpool".to_string(),
+                size: "464G".to_string(),
+                health: "ONLINE".to_string(),
+            }],
+            managed: ManagedState {
+                last_install: Some(SessionStatus::Completed),
+                baseline: true,
+                ..Default::default()
+            },
+        };
+        let unreachable = HostFacts {
+            hostname: "web-02".to_string(),
+            address: "web-02".to_string(),
+            error: Some("Connection refused".to_string()),
+            os: OsFacts::default(),
+            facts: TargetFacts::default(),
+            pools: Vec::new(),
+            managed: ManagedState::default(),
+        };
+        let export = FleetFacts {
+            inventory: "inventory/web.yaml".to_string(),
+            generated_at: Utc::now(),
+            hosts: vec![reachable, unreachable],
+        };
+        let csv = export.to_csv();
+        let lines: Vec<&str> = csv.lines().collect();
+        assert_eq!(lines.len(), 3);
+        assert_eq!(lines[0].split(',').count(), CSV_COLUMNS.len());
+        assert_eq!(
+            lines[1],
+            "web-01,10.0.0.5,true,Ubuntu 24.04.1 LTS,24.04,6.8.0-45-generic,,\"EPYC 7302, 16-Core\",32,65536,/dev/nvme0n1:512000000000,512.0,,,rpool:464G:ONLINE,completed,,,,true,"
+        );
+        assert!(lines[2].starts_with("web-02,web-02,false,"));
+        assert!(lines[2].ends_with(",false,Connection refused"));
+
+        let json: serde_json::Value = serde_json::from_str(&export.to_json().unwrap()).unwrap();
+        assert_eq!(json["hosts"][0]["managed"]["last_install"], "completed");
+    }
+}
@@ -0,0 +1,212 @@
+// file: src/network/fleet_plan.rs
+// version: 1.1.0
+// guid: 4b7d2e91-8c3a-4f65-a0d9-6e1f3b8c2a57
+
+//! Offline plan preview across an inventory
+//!
+//! `fleet plan` builds the install plan every inventory host would get from its preset and
+//! target config as they are now, and compares it with the plan of the host's last completed
+//! install under `logs/<hostname>/`. Nothing connects to the targets, so settings the installer
+//! derives from live facts (a storage layout picked from the hardware class, `{{ facts.* }}`
+//! templates) are not part of the preview; hosts whose configs need facts are listed with the
+//! error instead.
+
+use crate::network::ssh_installer::plan::{self, InstallPlan};
+use crate::Result;
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+
+/// How a host's plan compares with its last completed install
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum PreviewStatus {
+    Unchanged,
+    Changed,
+    /// No completed install is recorded; the whole plan would run
+    New,
+    /// The plan could not be built
+    Error,
+}
+
+/// Plan preview of one host
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct HostPlanPreview {
+    pub hostname: String,
+    pub status: PreviewStatus,
+    /// Phases whose steps differ from the last completed install
+    pub phases: Vec<String>,
+    /// Changed steps, rendered like `plan` diffs
+    pub changes: Vec<String>,
+    /// Number of steps in the new plan
+    pub steps: usize,
+    pub error: Option<String>,
+}
+
+impl HostPlanPreview {
+    /// Compare `plan` with the plan of the last completed install, if any
+    pub fn compare(hostname: &str, plan: &InstallPlan, previous: Option<&InstallPlan>) -> Self {
+        let mut preview = Self {
+            hostname: hostname.to_string(),
+            status: PreviewStatus::New,
+            phases: Vec::new(),
+            changes: Vec::new(),
+            steps: plan.steps.len(),
+            error: None,
+        };
+        if let Some(previous) = previous {
+            let changes = plan.diff(previous);
+            preview.status = if plan::has_changes(&changes) {
+                PreviewStatus::Changed
+            } else {
+                PreviewStatus::Unchanged
+            };
+            preview.phases = plan::phases_to_rerun(&changes)
+                .into_iter()
+                .map(str::to_string)
+                .collect();
+            preview.changes = plan::render_diff(&changes, false);
+        }
+        preview
+    }
+
+    /// Preview of a host whose plan could not be built
+    pub fn failed(hostname: &str, error: String) -> Self {
+        Self {
+            hostname: hostname.to_string(),
+            status: PreviewStatus::Error,
+            phases: Vec::new(),
+            changes: Vec::new(),
+            steps: 0,
+            error: Some(error),
+        }
+    }
+}
+
+/// Plan previews of every host in an inventory
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct FleetPlan {
+    pub inventory: String,
+    pub generated_at: DateTime<Utc>,
+    pub hosts: Vec<HostPlanPreview>,
+}
+
+impl FleetPlan {
+    pub fn to_json(&self) -> Result<String> {
+        Ok(serde_json::to_string_pretty(self)?)
+    }
+
+    /// Hosts with `status`
+    pub fn count(&self, status: PreviewStatus) -> usize {
+        self.hosts.iter().filter(|h| h.status == status).count()
+    }
+
+    /// Per-host listing followed by a one-line tally, for reading out in a review
+    pub fn summary_lines(&self) -> Vec<String> {
+        let mut lines = Vec::new();
+        for host in &self.hosts {
+            match host.status {
+                PreviewStatus::Unchanged => lines.push(format!("{}: unchanged", host.hostname)),
+                PreviewStatus::New => lines.push(format!(
+                    "{}: new, no completed install recorded ({} steps)",
+                    host.hostname, host.steps
+                )),
+                PreviewStatus::Error => lines.push(format!(
+                    "{}: error: {}",
+                    host.hostname,
+                    host.error.as_deref().unwrap_or_default()
+                )),
+                PreviewStatus::Changed => {
+                    lines.push(format!(
+                        "{}: changed, reruns {}",
+                        host.hostname,
+                        host.phases.join(", ")
+                    ));
+                    lines.extend(host.changes.iter().map(|line| format!("    {}", line)));
+                }
+            }
+        }
+        lines.push(format!(
+            "{} host(s): {} changed, {} unchanged, {} new, {} error(s)",
+            self.hosts.len(),
+            self.count(PreviewStatus::Changed),
+            self.count(PreviewStatus::Unchanged),
+            self.count(PreviewStatus::New),
+            self.count(PreviewStatus::Error)
+        ));
+        lines
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::network::ssh_installer::plan::PlanStep;
+
+    fn plan(steps: &[(&str, &str)]) -> InstallPlan {
+        InstallPlan {
+            steps: steps
+                .iter()
+                .map(|(section, line)| PlanStep {
+                    section: section.to_string(),
+                    line: line.to_string(),
+                })
+                .collect(),
+        }
+    }
+
+    #[test]
+    fn test_previews_and_summary() {
+        let previous = plan(&[
+            ("system", "hostname: web-01"),
+            ("kernel", "sysctl vm.swappiness=10"),
+        ]);
+        let next = plan(&[
+            ("system", "hostname: web-01"),
+            ("kernel", "sysctl vm.swappiness=1"),
+        ]);
+        let changed = HostPlanPreview::compare("web-01", &next, Some(&previous));
+        assert_eq!(changed.status, PreviewStatus::Changed);
+        assert_eq!(changed.phases, vec!["Phase 5: System configuration"]);
+        assert_eq!(
+            changed.changes,
+            vec![
+                "- [kernel] sysctl vm.swappiness=10",
+                "+ [kernel] sysctl vm.swappiness=1",
+            ]
+        );
+        let unchanged = HostPlanPreview::compare("web-02", &previous, Some(&previous));
+        assert_eq!(unchanged.status, PreviewStatus::Unchanged);
+        assert!(unchanged.phases.is_empty());
+
+        let fleet = FleetPlan {
+            inventory: "inventory/web.yaml".to_string(),
+            generated_at: Utc::now(),
+            hosts: vec![
+                changed,
+                unchanged,
+                HostPlanPreview::compare("web-03", &next, None),
+                HostPlanPreview::failed("web-04", "Preset not found: db".to_string()),
+            ],
+        };
+        let lines = fleet.summary_lines();
+        assert_eq!(
+            lines[0],
+            "web-01: changed, reruns Phase 5: System configuration"
+        );
+        assert_eq!(lines[1], "    - [kernel] sysctl vm.swappiness=10");
+        assert_eq!(lines[3], "web-02: unchanged");
+        assert_eq!(
+            lines[4],
+            "web-03: new, no completed install recorded (2 steps)"
+        );
+        assert_eq!(lines[5], "web-04: error: Preset not found: db");
+        assert_eq!(
+            lines[6],
+            "4 host(s): 1 changed, 1 unchanged, 1 new, 1 error(s)"
+        );
+
+        let json: serde_json::Value = serde_json::from_str(&fleet.to_json().unwrap()).unwrap();
+        assert_eq!(json["hosts"][0]["status"], "changed");
+        assert_eq!(json["hosts"][3]["status"], "error");
+    }
+}
@@ -0,0 +1,343 @@
+// file: src/network/github.rs
+// version: 1.1.0
+// guid: 2f8c4a61-7d3e-4b95-a0c2-6e1d9b5f7a38
+
+//! Build and install state posted to GitHub as a commit status or check run
+//!
+//! A run is reported `pending` (or an `in_progress` check run) when it starts and `success` or
+//! `failure` when it ends, linking to where its artifacts are. Deliveries never decide the
+//! outcome of the run; callers log failures and carry on.
+
+use crate::config::progress::GithubStatusConfig;
+use crate::error::AutoInstallError;
+use crate::logging::redact;
+use crate::Result;
+use serde::{Deserialize, Serialize};
+use serde_json::json;
+use std::time::Duration;
+
+/// Upper bound for one API request
+const REQUEST_TIMEOUT: Duration = Duration::from_secs(15);
+
+/// GitHub rejects commit status descriptions longer than this
+const MAX_DESCRIPTION: usize = 140;
+
+/// State of a commit status
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum StatusState {
+    Pending,
+    Success,
+    Failure,
+    Error,
+}
+
+impl StatusState {
+    /// Check run `conclusion` matching a final state
+    fn conclusion(self) -> &'static str {
+        match self {
+            StatusState::Success => "success",
+            StatusState::Pending => "neutral",
+            StatusState::Failure | StatusState::Error => "failure",
+        }
+    }
+}
+
+/// Repository, commit and credentials one run reports against
+#[derive(Clone)]
+pub struct GithubStatusReporter {
+    client: reqwest::Client,
+    api_url: String,
+    repository: String,
+    sha: String,
+    token: String,
+    context: String,
+    target_url: Option<String>,
+    check_run: bool,
+    check_run_id: Option<u64>,
+}
+
+impl GithubStatusReporter {
+    /// Reporter for `config`, filling unset fields from the GitHub Actions environment
+    ///
+    /// Errors when the repository, commit or token cannot be determined.
+    pub fn new(config: &GithubStatusConfig) -> Result<Self> {
+        Self::resolve(config, |name| std::env::var(name).ok())
+    }
+
+    fn resolve(config: &GithubStatusConfig, env: impl Fn(&str) -> Option<String>) -> Result<Self> {
+        let missing = |what: &str, variable: &str| {
+            AutoInstallError::ConfigError(format!(
+                "GitHub status reporting needs {} (set it in progress.github or {})",
+                what, variable
+            ))
+        };
+        let repository = config
+            .repository
+            .clone()
+            .or_else(|| env("GITHUB_REPOSITORY"))
+            .ok_or_else(|| missing("a repository", "GITHUB_REPOSITORY"))?;
+        let sha = config
+            .sha
+            .clone()
+            .or_else(|| env("GITHUB_SHA"))
+            .ok_or_else(|| missing("a commit", "GITHUB_SHA"))?;
+        let token = env(&config.token_env)
+            .filter(|token| !token.is_empty())
+            .ok_or_else(|| missing("a token", &config.token_env))?;
+        let api_url = config
+            .api_url
+            .clone()
+            .or_else(|| env("GITHUB_API_URL"))
+            .unwrap_or_else(|| "https://api.github.com".to_string());
+        // Inside Actions, link to the workflow run, whose page lists the uploaded artifacts
+        let target_url = config.target_url.clone().or_else(|| {
+            Some(format!(
+                "{}/{}/actions/runs/{}",
+                env("GITHUB_SERVER_URL")?,
+                env("GITHUB_REPOSITORY")?,
+                env("GITHUB_RUN_ID")?
+            ))
+        });
+        let client = reqwest::Client::builder()
+            .timeout(REQUEST_TIMEOUT)
+            .user_agent(concat!(
+                "ubuntu-autoinstall-agent/",
+                env!("CARGO_PKG_VERSION")
+            ))
+            .build()
+            .map_err(|e| {
+                AutoInstallError::NetworkError(format!("Failed to create GitHub client: {}", e))
+            })?;
+        Ok(Self {
+            client,
+            api_url: api_url.trim_end_matches('/').to_string(),
+            repository,
+            sha,
+            token,
+            context: config.context.clone(),
+            target_url,
+            check_run: config.check_run,
+            check_run_id: None,
+        })
+    }
+
+    /// Mark the run as started
+    pub async fn start(&mut self, description: &str) -> Result<()> {
+        if !self.check_run {
+            return self.set_status(StatusState::Pending, description).await;
+        }
+        let url = format!("{}/repos/{}/check-runs", self.api_url, self.repository);
+        let body = self.check_run_start_payload(description);
+        let response: serde_json::Value = self.send(self.client.post(&url), &body).await?;
+        self.check_run_id = response["id"].as_u64();
+        Ok(())
+    }
+
+    /// Mark the run as finished in `state`, listing `artifacts` in the check run summary
+    pub async fn finish(
+        &mut self,
+        state: StatusState,
+        description: &str,
+        artifacts: &[String],
+    ) -> Result<()> {
+        if !self.check_run {
+            return self.set_status(state, description).await;
+        }
+        let body = self.check_run_finish_payload(state, description, artifacts);
+        let request = match self.check_run_id {
+            Some(id) => self.client.patch(format!(
+                "{}/repos/{}/check-runs/{}",
+                self.api_url, self.repository, id
+            )),
+            // The start was never delivered; create the check run already completed
+            None => self.client.post(format!(
+                "{}/repos/{}/check-runs",
+                self.api_url, self.repository
+            )),
+        };
+        self.send::<serde_json::Value>(request, &body).await?;
+        Ok(())
+    }
+
+    async fn set_status(&self, state: StatusState, description: &str) -> Result<()> {
+        let url = format!(
+            "{}/repos/{}/statuses/{}",
+            self.api_url, self.repository, self.sha
+        );
+        let body = self.status_payload(state, description);
+        self.send::<serde_json::Value>(self.client.post(&url), &body)
+            .await?;
+        Ok(())
+    }
+
+    /// Body of `POST /repos/{repo}/statuses/{sha}`
+    pub fn status_payload(&self, state: StatusState, description: &str) -> serde_json::Value {
+        json!({
+            "state": state,
+            "context": self.context,
+            "description": truncate(description),
+            "target_url": self.target_url,
+        })
+    }
+
+    /// Body creating an `in_progress` check run
+    pub fn check_run_start_payload(&self, description: &str) -> serde_json::Value {
+        json!({
+            "name": self.context,
+            "head_sha": self.sha,
+            "status": "in_progress",
+            "details_url": self.target_url,
+            "output": { "title": truncate(description), "summary": description },
+        })
+    }
+
+    /// Body completing a check run; also valid for creating one already completed
+    pub fn check_run_finish_payload(
+        &self,
+        state: StatusState,
+        description: &str,
+        artifacts: &[String],
+    ) -> serde_json::Value {
+        let mut summary = description.to_string();
+        if !artifacts.is_empty() {
+            summary.push_str("\n\n**Artifacts**\n");
+            for artifact in artifacts {
+                summary.push_str(&format!("- `{}`\n", artifact));
+            }
+        }
+        if let Some(url) = &self.target_url {
+            summary.push_str(&format!("\n[Run details]({})\n", url));
+        }
+        json!({
+            "name": self.context,
+            "head_sha": self.sha,
+            "status": "completed",
+            "conclusion": state.conclusion(),
+            "details_url": self.target_url,
+            "output": { "title": truncate(description), "summary": summary },
+        })
+    }
+
+    async fn send<T: serde::de::DeserializeOwned>(
+        &self,
+        request: reqwest::RequestBuilder,
+        body: &serde_json::Value,
+    ) -> Result<T> {
+        let mut body = body.clone();
+        redact::scrub_json(&mut body);
+        let response = request
+            .bearer_auth(&self.token)
+            .header("Accept", "application/vnd.github+json")
+            .header("X-GitHub-Api-Version", "2022-11-28")
+            .json(&body)
+            .send()
+            .await?;
+        let status = response.status();
+        if !status.is_success() {
+            let message = response.text().await.unwrap_or_default();
+            return Err(AutoInstallError::NetworkError(format!(
+                "GitHub answered {} for {}: {}",
+                status,
+                self.repository,
+                message.trim()
+            )));
+        }
+        Ok(response.json().await?)
+    }
+}
+
+/// Status descriptions are limited to 140 characters
+fn truncate(description: &str) -> String {
+    if description.chars().count() <= MAX_DESCRIPTION {
+        return description.to_string();
+    }
+    let mut short: String = description.chars().take(MAX_DESCRIPTION - 1).collect();
+    short.push('…');
+    short
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::collections::HashMap;
+
+    const SHA: &str = "0123456789abcdef0123456789abcdef01234567";
+
+    fn resolve_with(
+        config: &GithubStatusConfig,
+        vars: &[(&str, &str)],
+    ) -> Result<GithubStatusReporter> {
+        let vars: HashMap<String, String> = vars
+            .iter()
+            .map(|(k, v)| (k.to_string(), v.to_string()))
+            .collect();
+        GithubStatusReporter::resolve(config, |name| vars.get(name).cloned())
+    }
+
+    #[test]
+    fn test_resolve_from_actions_environment() {
+        let actions = [
+            ("GITHUB_REPOSITORY", "acme/images"),
+            ("GITHUB_SHA", SHA),
+            ("GITHUB_TOKEN", "ghs_token"),
+            ("GITHUB_SERVER_URL", "https://github.com"),
+            ("GITHUB_RUN_ID", "42"),
+        ];
+        let reporter = resolve_with(&GithubStatusConfig::default(), &actions).unwrap();
+        assert_eq!(reporter.repository, "acme/images");
+        assert_eq!(reporter.api_url, "https://api.github.com");
+        assert_eq!(
+            reporter.target_url.as_deref(),
+            Some("https://github.com/acme/images/actions/runs/42")
+        );
+
+        let payload = reporter.status_payload(StatusState::Pending, "Building 24.04 amd64");
+        assert_eq!(payload["state"], "pending");
+        assert_eq!(payload["context"], "ubuntu-autoinstall-agent");
+        assert_eq!(
+            payload["target_url"],
+            "https://github.com/acme/images/actions/runs/42"
+        );
+
+        // The token is required; everything else can come from the config
+        assert!(resolve_with(&GithubStatusConfig::default(), &actions[..2]).is_err());
+        let config = GithubStatusConfig {
+            repository: Some("acme/images".to_string()),
+            sha: Some(SHA.to_string()),
+            token_env: "APP_TOKEN".to_string(),
+            ..Default::default()
+        };
+        let reporter = resolve_with(&config, &[("APP_TOKEN", "t")]).unwrap();
+        assert!(reporter.target_url.is_none());
+    }
+
+    #[test]
+    fn test_check_run_payloads() {
+        let config = GithubStatusConfig {
+            repository: Some("acme/images".to_string()),
+            sha: Some(SHA.to_string()),
+            target_url: Some("https://ci.example/build/7".to_string()),
+            check_run: true,
+            ..Default::default()
+        };
+        let reporter = resolve_with(&config, &[("GITHUB_TOKEN", "t")]).unwrap();
+
+        let start = reporter.check_run_start_payload("Building image");
+        assert_eq!(start["status"], "in_progress");
+        assert_eq!(start["head_sha"], SHA);
+
+        let done = reporter.check_run_finish_payload(
+            StatusState::Failure,
+            "Image build failed",
+            &["images/ubuntu-24.04-amd64.qcow2".to_string()],
+        );
+        assert_eq!(done["conclusion"], "failure");
+        let summary = done["output"]["summary"].as_str().unwrap();
+        assert!(summary.contains("- `images/ubuntu-24.04-amd64.qcow2`"));
+        assert!(summary.contains("[Run details](https://ci.example/build/7)"));
+
+        let long = "x".repeat(200);
+        assert_eq!(truncate(&long).chars().count(), MAX_DESCRIPTION);
+    }
+}
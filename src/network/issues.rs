@@ -0,0 +1,352 @@
+// file: src/network/issues.rs
+// version: 1.0.0
+// guid: 8f3a6c25-1e94-4d7b-b0a2-5c7e9d1f4a36
+
+//! Issues opened on GitHub or GitLab for failed installs
+//!
+//! The issue carries the redacted failure summary, the failed phase, the error category and a
+//! link to the support bundle. The issue opened for a host is remembered in
+//! `logs/<hostname>/failure-issue.json`; while it stays open, later failures of the host are
+//! added to it as comments instead of opening another one. Deliveries never decide the outcome
+//! of the run; callers log failures and carry on.
+
+use crate::config::issues::{IssueConfig, IssueProvider};
+use crate::error::AutoInstallError;
+use crate::logging::redact;
+use crate::network::ssh_installer::session::InstallSession;
+use crate::Result;
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use serde_json::json;
+use std::path::{Path, PathBuf};
+use std::time::Duration;
+
+/// Upper bound for one API request
+const REQUEST_TIMEOUT: Duration = Duration::from_secs(15);
+
+/// Issue opened for a host's failures
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct IssueRecord {
+    pub provider: IssueProvider,
+    pub repository: String,
+    /// GitHub issue number or GitLab `iid`
+    pub number: u64,
+    pub url: String,
+    /// Failures reported on the issue, including the one that opened it
+    pub failures: u32,
+    pub last_failure: DateTime<Utc>,
+}
+
+impl IssueRecord {
+    pub fn path(base_dir: &Path, hostname: &str) -> PathBuf {
+        InstallSession::host_dir(base_dir, hostname).join("failure-issue.json")
+    }
+
+    pub fn load(base_dir: &Path, hostname: &str) -> Option<Self> {
+        let content = std::fs::read_to_string(Self::path(base_dir, hostname)).ok()?;
+        serde_json::from_str(&content).ok()
+    }
+
+    pub fn save(&self, base_dir: &Path, hostname: &str) -> Result<()> {
+        let path = Self::path(base_dir, hostname);
+        if let Some(parent) = path.parent() {
+            std::fs::create_dir_all(parent)?;
+        }
+        std::fs::write(path, serde_json::to_string_pretty(self)?)?;
+        Ok(())
+    }
+}
+
+/// What an issue says about one failed install
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct FailureReport {
+    pub hostname: String,
+    pub session_id: String,
+    pub failed_phase: Option<String>,
+    /// Error kind, e.g. `ssh` or `timeout`
+    pub category: String,
+    pub error: String,
+    /// Where the session stopped, as in the shutdown summary
+    pub summary: Vec<String>,
+    /// Link to the support bundle, or its path on the controller
+    pub bundle: Option<String>,
+}
+
+impl FailureReport {
+    /// Report for `session` failing with `error`; secrets are scrubbed from every line
+    pub fn new(session: &InstallSession, error: &AutoInstallError, bundle: Option<String>) -> Self {
+        let failed_phase = session
+            .failed_phases
+            .last()
+            .or(session.current_phase.as_ref())
+            .map(|phase| redact::scrub(phase).into_owned());
+        Self {
+            hostname: session.hostname.clone(),
+            session_id: session.id.clone(),
+            failed_phase,
+            category: error.category().to_string(),
+            error: redact::scrub(&error.to_string()).into_owned(),
+            summary: session
+                .shutdown_summary()
+                .iter()
+                .map(|line| redact::scrub(line).into_owned())
+                .collect(),
+            bundle,
+        }
+    }
+
+    pub fn title(&self) -> String {
+        format!(
+            "Install of {} failed: {} error{}",
+            self.hostname,
+            self.category,
+            self.failed_phase
+                .as_ref()
+                .map(|phase| format!(" in {}", phase))
+                .unwrap_or_default()
+        )
+    }
+
+    /// Markdown body of a new issue
+    pub fn body(&self) -> String {
+        let mut body = format!(
+            "**Host:** {}\n**Session:** `{}`\n**Failed phase:** {}\n**Error category:** `{}`\n\n```\n{}\n```\n\n**Summary**\n",
+            self.hostname,
+            self.session_id,
+            self.failed_phase.as_deref().unwrap_or("unknown"),
+            self.category,
+            self.error
+        );
+        for line in &self.summary {
+            body.push_str(&format!("- {}\n", line));
+        }
+        match &self.bundle {
+            Some(bundle) if bundle.starts_with("http") => {
+                body.push_str(&format!("\n[Support bundle]({})\n", bundle))
+            }
+            Some(bundle) => body.push_str(&format!(
+                "\nSupport bundle on the controller: `{}`\n",
+                bundle
+            )),
+            None => body.push_str("\nNo support bundle could be written.\n"),
+        }
+        body.push_str("\n_Opened by ubuntu-autoinstall-agent_\n");
+        body
+    }
+
+    /// Comment added to an open issue for a repeated failure
+    pub fn comment(&self, failures: u32) -> String {
+        format!("Failed again (failure {}).\n\n{}", failures, self.body())
+    }
+}
+
+/// Opens and comments on failure issues in one repository
+pub struct IssueReporter {
+    client: reqwest::Client,
+    provider: IssueProvider,
+    api_root: String,
+    repository: String,
+    token: String,
+    labels: Vec<String>,
+}
+
+impl IssueReporter {
+    /// Reporter for `config`; errors when no repository is set or the token is missing
+    pub fn new(config: &IssueConfig) -> Result<Self> {
+        let repository = config.repository.clone().ok_or_else(|| {
+            AutoInstallError::ConfigError("issues.repository is not set".to_string())
+        })?;
+        let token = std::env::var(config.token_variable())
+            .ok()
+            .filter(|token| !token.is_empty())
+            .ok_or_else(|| {
+                AutoInstallError::ConfigError(format!(
+                    "Opening failure issues needs a token in {}",
+                    config.token_variable()
+                ))
+            })?;
+        let client = reqwest::Client::builder()
+            .timeout(REQUEST_TIMEOUT)
+            .user_agent(concat!(
+                "ubuntu-autoinstall-agent/",
+                env!("CARGO_PKG_VERSION")
+            ))
+            .build()
+            .map_err(|e| {
+                AutoInstallError::NetworkError(format!("Failed to create issue client: {}", e))
+            })?;
+        Ok(Self {
+            client,
+            provider: config.provider,
+            api_root: config.api_root(),
+            repository,
+            token,
+            labels: config.labels.clone(),
+        })
+    }
+
+    /// Open an issue for `report`, or comment on the host's open one; returns the issue
+    pub async fn report(&self, base_dir: &Path, report: &FailureReport) -> Result<IssueRecord> {
+        let previous = IssueRecord::load(base_dir, &report.hostname)
+            .filter(|r| r.provider == self.provider && r.repository == self.repository);
+        let record = match previous {
+            Some(mut record) if self.is_open(record.number).await? => {
+                record.failures += 1;
+                record.last_failure = Utc::now();
+                self.comment(record.number, &report.comment(record.failures))
+                    .await?;
+                record
+            }
+            _ => {
+                let (number, url) = self.open(report).await?;
+                IssueRecord {
+                    provider: self.provider,
+                    repository: self.repository.clone(),
+                    number,
+                    url,
+                    failures: 1,
+                    last_failure: Utc::now(),
+                }
+            }
+        };
+        record.save(base_dir, &report.hostname)?;
+        Ok(record)
+    }
+
+    /// Issues endpoint of the repository
+    fn issues_url(&self) -> String {
+        match self.provider {
+            IssueProvider::Github => format!("{}/repos/{}/issues", self.api_root, self.repository),
+            IssueProvider::Gitlab => format!(
+                "{}/projects/{}/issues",
+                self.api_root,
+                self.repository.replace('/', "%2F")
+            ),
+        }
+    }
+
+    /// Body creating an issue for `report`
+    pub fn open_payload(&self, report: &FailureReport) -> serde_json::Value {
+        match self.provider {
+            IssueProvider::Github => json!({
+                "title": report.title(),
+                "body": report.body(),
+                "labels": self.labels,
+            }),
+            IssueProvider::Gitlab => json!({
+                "title": report.title(),
+                "description": report.body(),
+                "labels": self.labels.join(","),
+            }),
+        }
+    }
+
+    async fn open(&self, report: &FailureReport) -> Result<(u64, String)> {
+        let answer = self
+            .send(
+                self.client.post(self.issues_url()),
+                Some(&self.open_payload(report)),
+            )
+            .await?;
+        let (number, url) = match self.provider {
+            IssueProvider::Github => (answer["number"].as_u64(), answer["html_url"].as_str()),
+            IssueProvider::Gitlab => (answer["iid"].as_u64(), answer["web_url"].as_str()),
+        };
+        match (number, url) {
+            (Some(number), Some(url)) => Ok((number, url.to_string())),
+            _ => Err(AutoInstallError::NetworkError(format!(
+                "{} did not return the new issue's number",
+                self.provider.as_str()
+            ))),
+        }
+    }
+
+    async fn is_open(&self, number: u64) -> Result<bool> {
+        let url = format!("{}/{}", self.issues_url(), number);
+        let answer = self.send(self.client.get(url), None).await?;
+        Ok(matches!(answer["state"].as_str(), Some("open" | "opened")))
+    }
+
+    async fn comment(&self, number: u64, body: &str) -> Result<()> {
+        let (path, payload) = match self.provider {
+            IssueProvider::Github => ("comments", json!({ "body": body })),
+            IssueProvider::Gitlab => ("notes", json!({ "body": body })),
+        };
+        let url = format!("{}/{}/{}", self.issues_url(), number, path);
+        self.send(self.client.post(url), Some(&payload)).await?;
+        Ok(())
+    }
+
+    async fn send(
+        &self,
+        request: reqwest::RequestBuilder,
+        body: Option<&serde_json::Value>,
+    ) -> Result<serde_json::Value> {
+        let mut request = match self.provider {
+            IssueProvider::Github => request
+                .bearer_auth(&self.token)
+                .header("Accept", "application/vnd.github+json")
+                .header("X-GitHub-Api-Version", "2022-11-28"),
+            IssueProvider::Gitlab => request.header("PRIVATE-TOKEN", &self.token),
+        };
+        if let Some(body) = body {
+            let mut body = body.clone();
+            redact::scrub_json(&mut body);
+            request = request.json(&body);
+        }
+        let response = request.send().await?;
+        let status = response.status();
+        if !status.is_success() {
+            let message = response.text().await.unwrap_or_default();
+            return Err(AutoInstallError::NetworkError(format!(
+                "{} answered {} for {}: {}",
+                self.provider.as_str(),
+                status,
+                self.repository,
+                message.trim()
+            )));
+        }
+        Ok(response.json().await?)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_failure_report_is_redacted_and_records_roundtrip() {
+        redact::register("hunter2-luks-passphrase");
+        let mut session = InstallSession::new("db-01");
+        session
+            .failed_phases
+            .push("Phase 3: Storage: cryptsetup hunter2-luks-passphrase failed".to_string());
+        let error = AutoInstallError::LuksError(
+            "luksFormat with hunter2-luks-passphrase failed".to_string(),
+        );
+        let report = FailureReport::new(
+            &session,
+            &error,
+            Some("https://artifacts.example.com/b.tar.gz".to_string()),
+        );
+        assert!(report
+            .title()
+            .starts_with("Install of db-01 failed: luks error in Phase 3"));
+        let body = report.body();
+        assert!(!body.contains("hunter2-luks-passphrase"));
+        assert!(body.contains("**Error category:** `luks`"));
+        assert!(body.contains("[Support bundle](https://artifacts.example.com/b.tar.gz)"));
+
+        let dir = tempfile::TempDir::new().unwrap();
+        let record = IssueRecord {
+            provider: IssueProvider::Gitlab,
+            repository: "infra/installs".to_string(),
+            number: 7,
+            url: "https://gitlab.com/infra/installs/-/issues/7".to_string(),
+            failures: 2,
+            last_failure: Utc::now(),
+        };
+        record.save(dir.path(), "db-01").unwrap();
+        assert_eq!(IssueRecord::load(dir.path(), "db-01"), Some(record));
+    }
+}
@@ -0,0 +1,232 @@
+// file: src/network/kexec.rs
+// version: 1.1.0
+// guid: 935455a0-29b3-4fa6-a369-1c45c13cb193
+
+//! Boot the Ubuntu live environment on a running host via kexec
+//!
+//! For hosts without PXE or media access, the live kernel/initrd are downloaded onto
+//! the running system, loaded with kexec, and executed. Once the live environment is
+//! reachable over SSH again, the normal ssh-install flow can take over.
+
+use crate::config::{ubuntu_codename, Architecture};
+use crate::network::ssh::RebootWait;
+use crate::network::SshClient;
+use crate::Result;
+use tracing::info;
+
+/// Directory on the running system where live boot files are staged
+const KEXEC_STAGING_DIR: &str = "/var/tmp/uaa-kexec";
+
+/// Options describing which live environment to kexec into
+#[derive(Debug, Clone)]
+pub struct KexecOptions {
+    /// Ubuntu release version (e.g., "24.04")
+    pub ubuntu_version: String,
+    /// Target architecture
+    pub architecture: Architecture,
+    /// Autoinstall seed URL (nocloud-net datasource); omit to boot the plain live environment
+    pub seed_url: Option<String>,
+    /// Override for the live kernel URL
+    pub kernel_url: Option<String>,
+    /// Override for the live initrd URL
+    pub initrd_url: Option<String>,
+    /// Override for the live server ISO URL fetched by casper
+    pub iso_url: Option<String>,
+    /// Kernel `ip=` setting for the live environment
+    pub ip: String,
+    /// Additional kernel command line arguments
+    pub extra_cmdline: Vec<String>,
+}
+
+impl KexecOptions {
+    /// Create options for the given release using the default Ubuntu download locations
+    pub fn new(ubuntu_version: &str, architecture: Architecture) -> Self {
+        Self {
+            ubuntu_version: ubuntu_version.to_string(),
+            architecture,
+            seed_url: None,
+            kernel_url: None,
+            initrd_url: None,
+            iso_url: None,
+            ip: "dhcp".to_string(),
+            extra_cmdline: Vec::new(),
+        }
+    }
+
+    /// Resolve the kernel, initrd and ISO URLs, applying overrides
+    pub fn resolve_urls(&self) -> Result<(String, String, String)> {
+        let codename = ubuntu_codename(&self.ubuntu_version).ok_or_else(|| {
+            crate::error::AutoInstallError::ConfigError(format!(
+                "Unsupported Ubuntu version: {}",
+                self.ubuntu_version
+            ))
+        })?;
+        let arch = self.architecture.as_str();
+        let base = format!("https://releases.ubuntu.com/{}", codename);
+
+        let kernel = self
+            .kernel_url
+            .clone()
+            .unwrap_or_else(|| format!("{}/netboot/{}/linux", base, arch));
+        let initrd = self
+            .initrd_url
+            .clone()
+            .unwrap_or_else(|| format!("{}/netboot/{}/initrd", base, arch));
+        let iso = self.iso_url.clone().unwrap_or_else(|| {
+            format!(
+                "{}/ubuntu-{}-live-server-{}.iso",
+                base, self.ubuntu_version, arch
+            )
+        });
+
+        Ok((kernel, initrd, iso))
+    }
+
+    /// Build the kernel command line for the live environment
+    pub fn build_cmdline(&self, iso_url: &str) -> String {
+        let mut args = vec![format!("ip={}", self.ip), format!("url={}", iso_url)];
+        if let Some(seed) = &self.seed_url {
+            args.push("autoinstall".to_string());
+            // Trailing slash is required by the nocloud-net datasource
+            let seed = if seed.ends_with('/') {
+                seed.clone()
+            } else {
+                format!("{}/", seed)
+            };
+            args.push(format!("ds=nocloud-net;s={}", seed));
+        }
+        args.extend(self.extra_cmdline.iter().cloned());
+        args.push("---".to_string());
+        args.join(" ")
+    }
+}
+
+/// Build the `kexec -l` command that loads the staged live kernel
+pub fn build_kexec_load_command(kernel: &str, initrd: &str, cmdline: &str) -> String {
+    format!(
+        "kexec -l {} --initrd={} --command-line=\"{}\"",
+        kernel,
+        initrd,
+        cmdline.replace('"', "\\\"")
+    )
+}
+
+/// Build the full list of commands that stage, load and execute the live kernel
+pub fn build_kexec_commands(options: &KexecOptions) -> Result<Vec<String>> {
+    let (kernel_url, initrd_url, iso_url) = options.resolve_urls()?;
+    let kernel = format!("{}/linux", KEXEC_STAGING_DIR);
+    let initrd = format!("{}/initrd", KEXEC_STAGING_DIR);
+    let cmdline = options.build_cmdline(&iso_url);
+
+    Ok(vec![
+        "command -v kexec >/dev/null 2>&1 || DEBIAN_FRONTEND=noninteractive apt-get install -y kexec-tools".to_string(),
+        format!("mkdir -p {}", KEXEC_STAGING_DIR),
+        format!("curl -fsSL -o {} '{}'", kernel, kernel_url),
+        format!("curl -fsSL -o {} '{}'", initrd, initrd_url),
+        build_kexec_load_command(&kernel, &initrd, &cmdline),
+        // Detach so the SSH channel returns before the kernel is replaced
+        "nohup bash -c 'sleep 2; systemctl kexec || kexec -e' >/dev/null 2>&1 &".to_string(),
+    ])
+}
+
+/// Drives a kexec into the live environment over an existing SSH session
+pub struct KexecBooter<'a> {
+    ssh: &'a mut SshClient,
+}
+
+impl<'a> KexecBooter<'a> {
+    pub fn new(ssh: &'a mut SshClient) -> Self {
+        Self { ssh }
+    }
+
+    /// Stage the live kernel/initrd, kexec into the live environment and reconnect to it
+    ///
+    /// On success the borrowed client is connected to the live environment.
+    pub async fn boot_live_environment(
+        &mut self,
+        options: &KexecOptions,
+        wait: &RebootWait,
+    ) -> Result<()> {
+        info!(
+            "Kexec into Ubuntu {} live environment ({})",
+            options.ubuntu_version,
+            options.architecture.as_str()
+        );
+
+        let boot_id = self.ssh.boot_id().await?;
+        for cmd in build_kexec_commands(options)? {
+            info!("Executing: {}", cmd);
+            self.ssh.execute(&cmd).await?;
+        }
+
+        info!("Kexec triggered; waiting for the live environment");
+        self.ssh.wait_for_reboot(&boot_id, wait).await?;
+        info!("Live environment is reachable");
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_resolve_urls_defaults() {
+        let opts = KexecOptions::new("24.04", Architecture::Amd64);
+        let (kernel, initrd, iso) = opts.resolve_urls().unwrap();
+        assert_eq!(
+            kernel,
+            "https://releases.ubuntu.com/noble/netboot/amd64/linux"
+        );
+        assert_eq!(
+            initrd,
+            "https://releases.ubuntu.com/noble/netboot/amd64/initrd"
+        );
+        assert_eq!(
+            iso,
+            "https://releases.ubuntu.com/noble/ubuntu-24.04-live-server-amd64.iso"
+        );
+    }
+
+    #[test]
+    fn test_resolve_urls_overrides_and_unknown_version() {
+        let mut opts = KexecOptions::new("24.04", Architecture::Amd64);
+        opts.kernel_url = Some("http://mirror.local/linux".to_string());
+        let (kernel, _, _) = opts.resolve_urls().unwrap();
+        assert_eq!(kernel, "http://mirror.local/linux");
+
+        let opts = KexecOptions::new("18.04", Architecture::Amd64);
+        assert!(opts.resolve_urls().is_err());
+    }
+
+    #[test]
+    fn test_build_cmdline_with_seed() {
+        let mut opts = KexecOptions::new("24.04", Architecture::Amd64);
+        opts.seed_url = Some("http://10.0.0.1:8080/seed".to_string());
+        opts.extra_cmdline = vec!["console=ttyS0,115200".to_string()];
+        let cmdline = opts.build_cmdline("http://iso");
+        assert_eq!(
+            cmdline,
+            "ip=dhcp url=http://iso autoinstall ds=nocloud-net;s=http://10.0.0.1:8080/seed/ console=ttyS0,115200 ---"
+        );
+    }
+
+    #[test]
+    fn test_build_cmdline_without_seed() {
+        let opts = KexecOptions::new("24.04", Architecture::Amd64);
+        let cmdline = opts.build_cmdline("http://iso");
+        assert!(!cmdline.contains("autoinstall"));
+        assert!(cmdline.ends_with("---"));
+    }
+
+    #[test]
+    fn test_build_kexec_commands_order() {
+        let opts = KexecOptions::new("24.04", Architecture::Arm64);
+        let cmds = build_kexec_commands(&opts).unwrap();
+        let load = cmds.iter().position(|c| c.starts_with("kexec -l")).unwrap();
+        let exec = cmds.iter().position(|c| c.contains("kexec -e")).unwrap();
+        assert!(cmds[2].contains("netboot/arm64/linux"));
+        assert!(load < exec);
+        assert!(cmds[load].contains("--initrd=/var/tmp/uaa-kexec/initrd"));
+    }
+}
@@ -1,17 +1,39 @@
 // file: src/network/mod.rs
-// version: 1.2.0
+// version: 1.24.0
 // guid: s9t0u1v2-w3x4-5678-9012-345678stuvwx
 
 //! Network operations module
 
-pub mod download;
+pub mod beacon;
+pub mod chaos;
+pub mod dhcp;
+pub mod download_pipeline;
+pub mod events;
 pub mod executor;
+pub mod fleet;
+pub mod fleet_facts;
+pub mod fleet_plan;
+pub mod github;
+pub mod issues;
+pub mod kexec;
 pub mod local;
+pub mod progress;
+pub mod redfish;
+pub mod self_install;
+pub mod serial;
+pub mod sinks;
 pub mod ssh;
 pub mod ssh_installer;
+pub mod stream;
+pub mod sudo;
+pub mod telemetry;
+pub mod transport;
+pub mod webhook;
 
-pub use download::NetworkDownloader;
+pub use events::{EventBus, InstallEvent};
 pub use executor::CommandExecutor;
+pub use kexec::{KexecBooter, KexecOptions};
 pub use local::LocalClient;
 pub use ssh::SshClient;
-pub use ssh_installer::{InstallationConfig, SshInstaller, SystemInfo};
+pub use ssh_installer::{InstallationConfig, SshInstaller};
+pub use transport::Transport;
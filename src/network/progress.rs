@@ -0,0 +1,569 @@
+// file: src/network/progress.rs
+// version: 1.2.0
+// guid: 5e1a9d37-8c42-4b6f-9073-a2d6c8f4e1b5
+
+//! Progress events parsed from the output of long remote commands
+//!
+//! The SSH client streams a command's output through the parser chosen for it and emits an
+//! event whenever the whole percentage goes up. With an event bus set, events are published
+//! to it and its subscribers log and forward them; without one the tracker logs them itself. Tools that report on stderr are run with stderr merged into
+//! stdout. Commands sent over a console transport produce no events.
+
+use crate::config::progress::{ProgressConfig, ProgressPattern};
+use crate::network::events::{EventBus, InstallEvent};
+use crate::Result;
+use regex::Regex;
+use serde::{Deserialize, Serialize};
+use tracing::{debug, info};
+
+/// Progress of one remote command
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ProgressEvent {
+    pub host: String,
+    /// Parser that produced the event, e.g. `debootstrap` or `apt`
+    pub tool: String,
+    pub percent: u8,
+    /// Output line the percentage was taken from
+    pub detail: String,
+}
+
+/// Turns output lines of one command into percentages
+pub trait ProgressParser: Send {
+    /// Label carried by the events
+    fn tool(&self) -> &str;
+
+    /// Whether the tool reports on stderr
+    fn reads_stderr(&self) -> bool {
+        false
+    }
+
+    /// Percentage (0-100) reached at `line`, if the line says anything about it
+    fn parse_line(&mut self, line: &str) -> Option<f64>;
+}
+
+/// debootstrap stage messages and the share of the run completed when each starts
+const DEBOOTSTRAP_STAGES: &[(&str, f64)] = &[
+    ("I: Retrieving InRelease", 1.0),
+    ("I: Retrieving Release", 1.0),
+    ("I: Retrieving Packages", 3.0),
+    ("I: Resolving dependencies", 6.0),
+    ("I: Checking component", 8.0),
+    ("I: Retrieving ", 10.0),
+    ("I: Validating ", 10.0),
+    ("I: Extracting ", 45.0),
+    ("I: Installing core packages", 55.0),
+    ("I: Unpacking required packages", 60.0),
+    ("I: Configuring required packages", 70.0),
+    ("I: Unpacking the base system", 80.0),
+    ("I: Configuring the base system", 90.0),
+    ("I: Base system installed successfully", 100.0),
+];
+
+/// debootstrap: stage messages, with per-package steps inside retrieval and unpacking
+#[derive(Debug, Default)]
+pub struct DebootstrapParser {
+    reached: f64,
+}
+
+impl ProgressParser for DebootstrapParser {
+    fn tool(&self) -> &str {
+        "debootstrap"
+    }
+
+    fn reads_stderr(&self) -> bool {
+        true
+    }
+
+    fn parse_line(&mut self, line: &str) -> Option<f64> {
+        let (_, stage) = DEBOOTSTRAP_STAGES
+            .iter()
+            .find(|(prefix, _)| line.starts_with(prefix))?;
+        // Package lines repeat a stage; creep towards the next stage instead of standing still
+        let next = DEBOOTSTRAP_STAGES
+            .iter()
+            .map(|(_, p)| *p)
+            .find(|p| p > stage)
+            .unwrap_or(100.0);
+        self.reached = if *stage > self.reached {
+            *stage
+        } else {
+            self.reached + (next - self.reached) / 50.0
+        };
+        Some(self.reached)
+    }
+}
+
+/// apt-get and apt: package count from the summary line, then unpack and setup steps;
+/// also reads `APT::Status-Fd` lines (`dlstatus:` and `pmstatus:`) when the command enables them
+#[derive(Debug)]
+pub struct AptParser {
+    summary: Regex,
+    packages: u64,
+    steps: u64,
+}
+
+impl AptParser {
+    pub fn new() -> Result<Self> {
+        Ok(Self {
+            summary: regex(r"(\d+) (upgraded|newly installed|downgraded|reinstalled)")?,
+            packages: 0,
+            steps: 0,
+        })
+    }
+}
+
+impl ProgressParser for AptParser {
+    fn tool(&self) -> &str {
+        "apt"
+    }
+
+    fn parse_line(&mut self, line: &str) -> Option<f64> {
+        for prefix in ["pmstatus:", "dlstatus:"] {
+            if let Some(rest) = line.strip_prefix(prefix) {
+                return rest.split(':').nth(1)?.parse().ok();
+            }
+        }
+        if line.contains("newly installed") {
+            self.packages = self
+                .summary
+                .captures_iter(line)
+                .filter_map(|c| c[1].parse::<u64>().ok())
+                .sum();
+            return None;
+        }
+        if self.packages == 0
+            || !(line.starts_with("Unpacking ") || line.starts_with("Setting up "))
+        {
+            return None;
+        }
+        self.steps += 1;
+        Some(self.steps as f64 * 100.0 / (self.packages * 2) as f64)
+    }
+}
+
+/// `dd status=progress`: bytes copied against `bs` times `count` from the command line
+#[derive(Debug)]
+pub struct DdParser {
+    total: u64,
+}
+
+impl DdParser {
+    /// Parser for `command`, when its size can be read from `bs=` and `count=`
+    pub fn for_command(command: &str) -> Option<Self> {
+        let operand = |name: &str| {
+            command
+                .split_whitespace()
+                .find_map(|word| word.strip_prefix(name))
+                .and_then(parse_size)
+        };
+        let total = operand("bs=").unwrap_or(512) * operand("count=")?;
+        (total > 0).then_some(Self { total })
+    }
+}
+
+impl ProgressParser for DdParser {
+    fn tool(&self) -> &str {
+        "dd"
+    }
+
+    fn reads_stderr(&self) -> bool {
+        true
+    }
+
+    fn parse_line(&mut self, line: &str) -> Option<f64> {
+        let (bytes, rest) = line.split_once(' ')?;
+        if !rest.starts_with("bytes") {
+            return None;
+        }
+        Some(bytes.parse::<u64>().ok()? as f64 * 100.0 / self.total as f64)
+    }
+}
+
+/// `zfs send -v` or `-P` statistics: the estimated stream size, then bytes sent per second
+#[derive(Debug, Default)]
+pub struct ZfsSendParser {
+    total: u64,
+}
+
+impl ProgressParser for ZfsSendParser {
+    fn tool(&self) -> &str {
+        "zfs send"
+    }
+
+    fn reads_stderr(&self) -> bool {
+        true
+    }
+
+    fn parse_line(&mut self, line: &str) -> Option<f64> {
+        let fields: Vec<&str> = line.split_whitespace().collect();
+        if let Some(size) = line.strip_prefix("total estimated size is ") {
+            self.total = parse_size(size.trim()).unwrap_or(0);
+            return None;
+        }
+        match fields.as_slice() {
+            ["size", size] => {
+                self.total = parse_size(size).unwrap_or(0);
+                None
+            }
+            [time, sent, _snapshot] if self.total > 0 && time.contains(':') => {
+                Some(parse_size(sent)? as f64 * 100.0 / self.total as f64)
+            }
+            _ => None,
+        }
+    }
+}
+
+/// Custom command output matched with a `progress.patterns` entry
+#[derive(Debug)]
+pub struct RegexParser {
+    name: String,
+    pattern: Regex,
+    stderr: bool,
+}
+
+impl RegexParser {
+    pub fn new(pattern: &ProgressPattern) -> Result<Self> {
+        Ok(Self {
+            name: pattern.label().to_string(),
+            pattern: regex(&pattern.pattern)?,
+            stderr: pattern.stderr,
+        })
+    }
+}
+
+impl ProgressParser for RegexParser {
+    fn tool(&self) -> &str {
+        &self.name
+    }
+
+    fn reads_stderr(&self) -> bool {
+        self.stderr
+    }
+
+    fn parse_line(&mut self, line: &str) -> Option<f64> {
+        let caps = self.pattern.captures(line)?;
+        if let Some(percent) = caps.name("percent") {
+            return percent.as_str().parse().ok();
+        }
+        let current: f64 = caps.name("current")?.as_str().parse().ok()?;
+        let total: f64 = caps.name("total")?.as_str().parse().ok()?;
+        (total > 0.0).then_some(current * 100.0 / total)
+    }
+}
+
+/// Chooses a parser per command and carries the event bus
+#[derive(Debug, Default)]
+pub struct ProgressReporter {
+    custom: Vec<(Regex, ProgressPattern)>,
+    bus: Option<EventBus>,
+}
+
+impl ProgressReporter {
+    /// Reporter using the custom patterns of `config`
+    pub fn new(config: &ProgressConfig) -> Result<Self> {
+        let custom = config
+            .patterns
+            .iter()
+            .map(|p| Ok((regex(&p.command)?, p.clone())))
+            .collect::<Result<_>>()?;
+        Ok(Self { custom, bus: None })
+    }
+
+    /// Publish every event to `bus`; its subscribers take over logging
+    pub fn with_bus(mut self, bus: EventBus) -> Self {
+        self.bus = Some(bus);
+        self
+    }
+
+    /// Parser for `command`: a matching custom pattern first, then the built-in tools
+    pub fn parser_for(&self, command: &str) -> Option<Box<dyn ProgressParser>> {
+        if let Some((_, pattern)) = self.custom.iter().find(|(re, _)| re.is_match(command)) {
+            return RegexParser::new(pattern)
+                .ok()
+                .map(|p| Box::new(p) as Box<dyn ProgressParser>);
+        }
+        let words: Vec<&str> = command.split_whitespace().collect();
+        let has = |tool: &str| runs(&words, tool);
+        if has("debootstrap") {
+            Some(Box::new(DebootstrapParser::default()))
+        } else if (has("apt-get") || has("apt")) && words.contains(&"-y") {
+            AptParser::new()
+                .ok()
+                .map(|p| Box::new(p) as Box<dyn ProgressParser>)
+        } else if has("dd") && command.contains("status=progress") {
+            DdParser::for_command(command).map(|p| Box::new(p) as Box<dyn ProgressParser>)
+        } else if regex(r"zfs send(\s+-\S+)*\s+-\S*[vP]").is_ok_and(|re| re.is_match(command)) {
+            Some(Box::new(ZfsSendParser::default()))
+        } else {
+            None
+        }
+    }
+
+    /// Tracker feeding `command`'s output to its parser, if it has one
+    pub fn track(&self, host: &str, command: &str) -> Option<ProgressTracker> {
+        self.parser_for(command).map(|parser| ProgressTracker {
+            parser,
+            host: host.to_string(),
+            bus: self.bus.clone(),
+            last: None,
+            partial: Vec::new(),
+        })
+    }
+}
+
+/// Splits streamed output into lines and emits events when the percentage rises
+pub struct ProgressTracker {
+    parser: Box<dyn ProgressParser>,
+    host: String,
+    bus: Option<EventBus>,
+    last: Option<u8>,
+    partial: Vec<u8>,
+}
+
+impl ProgressTracker {
+    /// Whether the command must run with stderr merged into stdout
+    pub fn reads_stderr(&self) -> bool {
+        self.parser.reads_stderr()
+    }
+
+    /// Feed a chunk of output; `\r` ends a line too, as progress meters redraw with it
+    pub fn feed(&mut self, chunk: &[u8]) {
+        for &byte in chunk {
+            if byte == b'\n' || byte == b'\r' {
+                let line = String::from_utf8_lossy(&self.partial).into_owned();
+                self.partial.clear();
+                self.line(&line);
+            } else {
+                self.partial.push(byte);
+            }
+        }
+    }
+
+    /// Parse the last line when the output did not end with a newline
+    pub fn finish(&mut self) {
+        if !self.partial.is_empty() {
+            self.feed(b"\n");
+        }
+    }
+
+    fn line(&mut self, line: &str) {
+        let line = line.trim();
+        let Some(percent) = self.parser.parse_line(line) else {
+            return;
+        };
+        let percent = percent.clamp(0.0, 100.0) as u8;
+        if self.last.is_some_and(|last| percent <= last) {
+            return;
+        }
+        let event = ProgressEvent {
+            host: self.host.clone(),
+            tool: self.parser.tool().to_string(),
+            percent,
+            detail: line.to_string(),
+        };
+        match &self.bus {
+            Some(bus) => bus.publish(InstallEvent::Progress(event)),
+            None => log_progress(&event, self.last),
+        }
+        self.last = Some(percent);
+    }
+}
+
+/// Log `event` at info level each time it crosses a 10% step since `previous`, else at debug
+pub fn log_progress(event: &ProgressEvent, previous: Option<u8>) {
+    if previous.map(|last| last / 10) != Some(event.percent / 10) {
+        info!("{}: {} {}%", event.host, event.tool, event.percent);
+    } else {
+        debug!(
+            "{}: {} {}% ({})",
+            event.host, event.tool, event.percent, event.detail
+        );
+    }
+}
+
+/// Whether `tool` is run by the command in `words`, rather than merely mentioned
+///
+/// A command position is the start, after a separator, an environment assignment, `sudo`,
+/// `chroot <dir>` or a shell's `-c`.
+fn runs(words: &[&str], tool: &str) -> bool {
+    words.iter().enumerate().any(|(i, word)| {
+        word.trim_start_matches(['(', '\'', '"']) == tool
+            && (i == 0
+                || matches!(
+                    words[i - 1],
+                    "&&" | "||" | ";" | "|" | "sudo" | "-c" | "-lc"
+                )
+                || is_assignment(words[i - 1])
+                || (i >= 2 && words[i - 2] == "chroot"))
+    })
+}
+
+/// `NAME=value` environment assignment, possibly opening a quoted shell script
+fn is_assignment(word: &str) -> bool {
+    word.trim_start_matches(['\'', '"'])
+        .split_once('=')
+        .is_some_and(|(name, _)| {
+            !name.is_empty()
+                && name
+                    .chars()
+                    .all(|c| c.is_ascii_uppercase() || c.is_ascii_digit() || c == '_')
+        })
+}
+
+/// Byte count with an optional K/M/G/T suffix (powers of 1024), e.g. `1.5G` or `4M`
+pub fn parse_size(size: &str) -> Option<u64> {
+    let size = size.trim().trim_end_matches(['B', 'b']);
+    let (number, unit) = match size.char_indices().last()? {
+        (i, c) if c.is_ascii_alphabetic() => (&size[..i], c.to_ascii_uppercase()),
+        _ => (size, ' '),
+    };
+    let factor: u64 = match unit {
+        ' ' => 1,
+        'K' => 1 << 10,
+        'M' => 1 << 20,
+        'G' => 1 << 30,
+        'T' => 1 << 40,
+        _ => return None,
+    };
+    let value: f64 = number.parse().ok()?;
+    Some((value * factor as f64) as u64)
+}
+
+fn regex(pattern: &str) -> Result<Regex> {
+    Regex::new(pattern).map_err(|e| {
+        crate::error::AutoInstallError::ConfigError(format!("Invalid regex pattern: {}", e))
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn run(reporter: &ProgressReporter, command: &str, output: &str) -> Vec<ProgressEvent> {
+        let bus = EventBus::default();
+        let mut subscription = bus.subscribe();
+        let reporter = ProgressReporter {
+            custom: reporter.custom.clone(),
+            bus: Some(bus),
+        };
+        let mut tracker = reporter.track("web-01", command).expect("parser");
+        tracker.feed(output.as_bytes());
+        tracker.finish();
+        std::iter::from_fn(|| subscription.try_next())
+            .filter_map(|event| match event {
+                InstallEvent::Progress(progress) => Some(progress),
+                _ => None,
+            })
+            .collect()
+    }
+
+    fn percents(events: &[ProgressEvent]) -> Vec<u8> {
+        events.iter().map(|e| e.percent).collect()
+    }
+
+    #[test]
+    fn test_parser_selection() {
+        let reporter = ProgressReporter::default();
+        let tool = |command: &str| reporter.parser_for(command).map(|p| p.tool().to_string());
+        assert_eq!(
+            tool("debootstrap noble /mnt/targetos http://archive.ubuntu.com/ubuntu"),
+            Some("debootstrap".into())
+        );
+        assert_eq!(
+            tool("chroot /mnt/targetos bash -lc 'DEBIAN_FRONTEND=noninteractive apt install -y rsync'"),
+            Some("apt".into())
+        );
+        assert_eq!(tool("apt-get update"), None);
+        assert_eq!(tool("dpkg-query -W -f='${Version}' debootstrap"), None);
+        assert_eq!(tool("dd if=/dev/zero of=/dev/sda bs=1M count=10"), None);
+        assert_eq!(
+            tool("dd if=/dev/zero of=/dev/sda bs=1M count=10 status=progress"),
+            Some("dd".into())
+        );
+        assert_eq!(
+            tool("zfs send -v -R rpool@s1 | zfs recv -u -F tank/rpool"),
+            Some("zfs send".into())
+        );
+        assert_eq!(tool("zfs send -R rpool@s1 > /d/f"), None);
+        assert_eq!(tool("zpool status"), None);
+    }
+
+    #[test]
+    fn test_debootstrap_stages() {
+        let output = "I: Retrieving InRelease \nI: Checking Release signature\nI: Retrieving Packages \nI: Resolving dependencies of required packages...\nI: Retrieving adduser 3.137\nI: Retrieving apt 2.9\nI: Extracting adduser...\nI: Installing core packages...\nI: Unpacking required packages...\nI: Configuring required packages...\nI: Unpacking the base system...\nI: Configuring the base system...\nI: Base system installed successfully.\n";
+        let events = run(
+            &ProgressReporter::default(),
+            "debootstrap noble /mnt",
+            output,
+        );
+        assert_eq!(
+            percents(&events),
+            vec![1, 3, 6, 10, 45, 55, 60, 70, 80, 90, 100]
+        );
+        assert_eq!(events[0].tool, "debootstrap");
+        assert_eq!(events[0].host, "web-01");
+        assert_eq!(events[10].detail, "I: Base system installed successfully.");
+    }
+
+    #[test]
+    fn test_apt_counts_and_status_fd() {
+        let output = "Reading package lists...\n0 upgraded, 2 newly installed, 0 to remove and 3 not upgraded.\nGet:1 http://archive/ubuntu noble/main amd64 rsync 3.2\nUnpacking rsync (3.2) ...\nUnpacking htop (3.3) ...\nSetting up rsync (3.2) ...\nSetting up htop (3.3) ...\n";
+        let events = run(
+            &ProgressReporter::default(),
+            "apt-get install -y rsync htop",
+            output,
+        );
+        assert_eq!(percents(&events), vec![25, 50, 75, 100]);
+
+        let output = "dlstatus:1:12.5:Retrieving file 1 of 8\npmstatus:rsync:40.0:Installing rsync\npmstatus:rsync:40.0:Installing rsync\n";
+        let events = run(
+            &ProgressReporter::default(),
+            "apt-get -y -o APT::Status-Fd=1 install rsync",
+            output,
+        );
+        assert_eq!(percents(&events), vec![12, 40]);
+    }
+
+    #[test]
+    fn test_dd_and_zfs_send() {
+        let output = "5242880 bytes (5.2 MB, 5.0 MiB) copied, 1 s, 5.2 MB/s\r10485760 bytes (10 MB, 10 MiB) copied, 2 s, 5.2 MB/s\n10+0 records in\n";
+        let events = run(
+            &ProgressReporter::default(),
+            "dd if=/dev/zero of=/dev/sdb bs=1M count=10 status=progress",
+            output,
+        );
+        assert_eq!(percents(&events), vec![50, 100]);
+
+        let output = "full send of rpool@s1 estimated size is 2.00G\ntotal estimated size is 2.00G\nTIME        SENT   SNAPSHOT rpool@s1\n12:00:01    512M   rpool@s1\n12:00:02   1.50G   rpool@s1\n";
+        let events = run(
+            &ProgressReporter::default(),
+            "zfs send -v -R rpool@s1 > /d/f",
+            output,
+        );
+        assert_eq!(percents(&events), vec![25, 75]);
+
+        assert_eq!(parse_size("1.5K"), Some(1536));
+        assert_eq!(parse_size("4MiB"), None);
+        assert_eq!(parse_size("123"), Some(123));
+    }
+
+    #[test]
+    fn test_custom_pattern_takes_precedence() {
+        let config: ProgressConfig = serde_yaml::from_str(
+            "patterns:\n  - name: provision\n    command: provision\\.sh\n    pattern: 'step (?P<current>\\d+)/(?P<total>\\d+)'\n    stderr: true\n",
+        )
+        .unwrap();
+        let reporter = ProgressReporter::new(&config).unwrap();
+        assert!(reporter
+            .track("h", "/root/provision.sh --all")
+            .unwrap()
+            .reads_stderr());
+        let events = run(
+            &reporter,
+            "/root/provision.sh --all",
+            "step 1/4\nnoise\nstep 3/4",
+        );
+        assert_eq!(percents(&events), vec![25, 75]);
+        assert_eq!(events[0].tool, "provision");
+    }
+}
@@ -0,0 +1,515 @@
+// file: src/network/redfish.rs
+// version: 1.0.0
+// guid: 5b9e2c47-8a13-4f6d-b0e2-c7d4a1f95836
+
+//! Hardware inventory from a BMC's Redfish API
+//!
+//! The installed OS only sees what its drivers expose. The BMC knows the chassis serial,
+//! firmware versions, every DIMM slot (populated or not) and power supply health, which is
+//! what asset records need. Only the system resource is required; every other section is
+//! collected best-effort because BMC implementations differ widely.
+
+use crate::config::BmcConfig;
+use crate::error::AutoInstallError;
+use crate::Result;
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use std::time::Duration;
+use tracing::{debug, info};
+
+/// Per-request timeout; BMCs are slow but should never take this long
+const REQUEST_TIMEOUT: Duration = Duration::from_secs(30);
+
+/// A firmware component and its version
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct FirmwareEntry {
+    pub name: String,
+    pub version: String,
+}
+
+/// One memory slot
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct DimmSlot {
+    pub locator: String,
+    pub populated: bool,
+    #[serde(default)]
+    pub capacity_mib: Option<u64>,
+    #[serde(default)]
+    pub part_number: Option<String>,
+    #[serde(default)]
+    pub speed_mhz: Option<u64>,
+    #[serde(default)]
+    pub health: Option<String>,
+}
+
+/// One power supply
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct PowerSupply {
+    pub name: String,
+    #[serde(default)]
+    pub model: Option<String>,
+    #[serde(default)]
+    pub serial_number: Option<String>,
+    #[serde(default)]
+    pub capacity_watts: Option<u64>,
+    /// Redfish `Status.Health` (`OK`, `Warning`, `Critical`), or `Absent` for an empty bay
+    #[serde(default)]
+    pub health: Option<String>,
+}
+
+/// Asset data collected from the BMC
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct HardwareInventory {
+    #[serde(default)]
+    pub manufacturer: Option<String>,
+    #[serde(default)]
+    pub model: Option<String>,
+    #[serde(default)]
+    pub serial_number: Option<String>,
+    #[serde(default)]
+    pub sku: Option<String>,
+    #[serde(default)]
+    pub bios_version: Option<String>,
+    #[serde(default)]
+    pub bmc_firmware: Option<String>,
+    #[serde(default)]
+    pub firmware: Vec<FirmwareEntry>,
+    #[serde(default)]
+    pub memory: Vec<DimmSlot>,
+    #[serde(default)]
+    pub power_supplies: Vec<PowerSupply>,
+    /// Sections that could not be read from this BMC
+    #[serde(default)]
+    pub unavailable: Vec<String>,
+}
+
+impl HardwareInventory {
+    /// Populated DIMM slots out of all slots reported
+    pub fn dimm_population(&self) -> (usize, usize) {
+        let populated = self.memory.iter().filter(|d| d.populated).count();
+        (populated, self.memory.len())
+    }
+
+    /// Total installed memory according to the BMC
+    pub fn total_memory_mib(&self) -> u64 {
+        self.memory.iter().filter_map(|d| d.capacity_mib).sum()
+    }
+
+    /// Power supplies whose health is anything other than OK
+    pub fn unhealthy_power_supplies(&self) -> Vec<&PowerSupply> {
+        self.power_supplies
+            .iter()
+            .filter(|p| p.health.as_deref().is_some_and(|h| h != "OK"))
+            .collect()
+    }
+
+    /// Field/value rows for reports, skipping unknown values
+    pub fn summary_rows(&self) -> Vec<(&'static str, String)> {
+        let mut rows = Vec::new();
+        for (label, value) in [
+            ("Manufacturer", &self.manufacturer),
+            ("Model", &self.model),
+            ("Serial number", &self.serial_number),
+            ("SKU", &self.sku),
+            ("BIOS", &self.bios_version),
+            ("BMC firmware", &self.bmc_firmware),
+        ] {
+            if let Some(value) = value {
+                rows.push((label, value.clone()));
+            }
+        }
+        if !self.memory.is_empty() {
+            let (populated, slots) = self.dimm_population();
+            rows.push((
+                "Memory",
+                format!(
+                    "{} of {} DIMM slots populated, {} MiB",
+                    populated,
+                    slots,
+                    self.total_memory_mib()
+                ),
+            ));
+        }
+        if !self.power_supplies.is_empty() {
+            let unhealthy = self.unhealthy_power_supplies();
+            let health = if unhealthy.is_empty() {
+                "all OK".to_string()
+            } else {
+                unhealthy
+                    .iter()
+                    .map(|p| format!("{} {}", p.name, p.health.as_deref().unwrap_or("?")))
+                    .collect::<Vec<_>>()
+                    .join(", ")
+            };
+            rows.push((
+                "Power supplies",
+                format!("{} ({})", self.power_supplies.len(), health),
+            ));
+        }
+        rows
+    }
+
+    /// One-line summary for console output
+    pub fn summary(&self) -> String {
+        let mut parts = Vec::new();
+        match (&self.manufacturer, &self.model) {
+            (Some(m), Some(model)) => parts.push(format!("{} {}", m, model)),
+            (None, Some(model)) => parts.push(model.clone()),
+            (Some(m), None) => parts.push(m.clone()),
+            (None, None) => {}
+        }
+        if let Some(serial) = &self.serial_number {
+            parts.push(format!("S/N {}", serial));
+        }
+        if !self.memory.is_empty() {
+            let (populated, slots) = self.dimm_population();
+            parts.push(format!("{}/{} DIMMs", populated, slots));
+        }
+        if !self.power_supplies.is_empty() {
+            parts.push(format!(
+                "{} PSUs, {} unhealthy",
+                self.power_supplies.len(),
+                self.unhealthy_power_supplies().len()
+            ));
+        }
+        parts.join(", ")
+    }
+}
+
+fn string_field(value: &Value, key: &str) -> Option<String> {
+    value
+        .get(key)
+        .and_then(Value::as_str)
+        .map(str::trim)
+        .filter(|s| !s.is_empty())
+        .map(str::to_string)
+}
+
+fn u64_field(value: &Value, key: &str) -> Option<u64> {
+    value
+        .get(key)
+        .and_then(|v| v.as_u64().or_else(|| v.as_f64().map(|f| f as u64)))
+        .filter(|n| *n > 0)
+}
+
+fn status_field(value: &Value, key: &str) -> Option<String> {
+    value.get("Status").and_then(|s| string_field(s, key))
+}
+
+/// `@odata.id` of each entry of a collection's `Members`
+pub fn member_links(collection: &Value) -> Vec<String> {
+    collection
+        .get("Members")
+        .and_then(Value::as_array)
+        .map(|members| {
+            members
+                .iter()
+                .filter_map(|m| string_field(m, "@odata.id"))
+                .collect()
+        })
+        .unwrap_or_default()
+}
+
+/// `@odata.id` of a linked resource, e.g. `link(system, "Memory")`
+pub fn link(value: &Value, key: &str) -> Option<String> {
+    value.get(key).and_then(|v| string_field(v, "@odata.id"))
+}
+
+/// Fill the identity fields from a `ComputerSystem` resource
+pub fn parse_system(system: &Value, inventory: &mut HardwareInventory) {
+    inventory.manufacturer = string_field(system, "Manufacturer");
+    inventory.model = string_field(system, "Model");
+    inventory.serial_number = string_field(system, "SerialNumber");
+    inventory.sku = string_field(system, "SKU");
+    inventory.bios_version = string_field(system, "BiosVersion");
+}
+
+/// A `Memory` resource
+pub fn parse_dimm(memory: &Value) -> DimmSlot {
+    let state = status_field(memory, "State");
+    let capacity_mib = u64_field(memory, "CapacityMiB");
+    let populated = match state.as_deref() {
+        Some("Absent") => false,
+        Some(_) => true,
+        None => capacity_mib.is_some(),
+    };
+    let locator = memory
+        .get("DeviceLocator")
+        .and_then(Value::as_str)
+        .or_else(|| {
+            memory
+                .get("Location")
+                .and_then(|l| l.get("PartLocation"))
+                .and_then(|p| p.get("ServiceLabel"))
+                .and_then(Value::as_str)
+        })
+        .or_else(|| memory.get("Id").and_then(Value::as_str))
+        .unwrap_or("unknown")
+        .to_string();
+    DimmSlot {
+        locator,
+        populated,
+        capacity_mib: capacity_mib.filter(|_| populated),
+        part_number: string_field(memory, "PartNumber").filter(|_| populated),
+        speed_mhz: u64_field(memory, "OperatingSpeedMhz").filter(|_| populated),
+        health: status_field(memory, "Health"),
+    }
+}
+
+/// A `SoftwareInventory` resource; `None` when it carries no version
+pub fn parse_firmware(entry: &Value) -> Option<FirmwareEntry> {
+    Some(FirmwareEntry {
+        name: string_field(entry, "Name").or_else(|| string_field(entry, "Id"))?,
+        version: string_field(entry, "Version")?,
+    })
+}
+
+/// A power supply object, from either the legacy `Power` resource or `PowerSubsystem`
+pub fn parse_power_supply(psu: &Value) -> PowerSupply {
+    let health = match status_field(psu, "State").as_deref() {
+        Some("Absent") => Some("Absent".to_string()),
+        _ => status_field(psu, "Health"),
+    };
+    PowerSupply {
+        name: string_field(psu, "Name")
+            .or_else(|| string_field(psu, "MemberId"))
+            .unwrap_or_else(|| "PSU".to_string()),
+        model: string_field(psu, "Model"),
+        serial_number: string_field(psu, "SerialNumber"),
+        capacity_watts: u64_field(psu, "PowerCapacityWatts"),
+        health,
+    }
+}
+
+/// Power supplies embedded in a legacy `Chassis/Power` resource
+pub fn parse_legacy_power(power: &Value) -> Vec<PowerSupply> {
+    power
+        .get("PowerSupplies")
+        .and_then(Value::as_array)
+        .map(|psus| psus.iter().map(parse_power_supply).collect())
+        .unwrap_or_default()
+}
+
+/// Read-only client for a BMC's Redfish service
+pub struct RedfishClient {
+    client: reqwest::Client,
+    base_url: String,
+    username: String,
+    password: String,
+}
+
+impl RedfishClient {
+    pub fn new(bmc: &BmcConfig) -> Result<Self> {
+        bmc.validate()?;
+        let client = reqwest::Client::builder()
+            .timeout(REQUEST_TIMEOUT)
+            .danger_accept_invalid_certs(bmc.insecure_tls)
+            .build()
+            .map_err(|e| {
+                AutoInstallError::NetworkError(format!("Failed to create Redfish client: {}", e))
+            })?;
+        Ok(Self {
+            client,
+            base_url: bmc.base_url(),
+            username: bmc.username.clone(),
+            password: bmc.password.clone(),
+        })
+    }
+
+    /// GET a resource by its `@odata.id` path
+    pub async fn get(&self, path: &str) -> Result<Value> {
+        let url = format!("{}{}", self.base_url, path);
+        debug!("Redfish GET {}", url);
+        let response = self
+            .client
+            .get(&url)
+            .basic_auth(&self.username, Some(&self.password))
+            .header("Accept", "application/json")
+            .send()
+            .await
+            .map_err(|e| AutoInstallError::NetworkError(format!("Redfish GET {}: {}", url, e)))?;
+        let status = response.status();
+        if !status.is_success() {
+            return Err(AutoInstallError::NetworkError(format!(
+                "Redfish GET {} returned {}",
+                url, status
+            )));
+        }
+        Ok(response.json().await?)
+    }
+
+    /// GET every member of a collection
+    async fn members(&self, path: &str) -> Result<Vec<Value>> {
+        let collection = self.get(path).await?;
+        let mut members = Vec::new();
+        for member in member_links(&collection) {
+            members.push(self.get(&member).await?);
+        }
+        Ok(members)
+    }
+
+    /// Collect the inventory of the first system the BMC manages
+    pub async fn collect_inventory(&self) -> Result<HardwareInventory> {
+        info!("Collecting hardware inventory from BMC {}", self.base_url);
+        let systems = self.get("/redfish/v1/Systems").await?;
+        let system_path = member_links(&systems).into_iter().next().ok_or_else(|| {
+            AutoInstallError::NetworkError("BMC reports no computer systems".to_string())
+        })?;
+        let system = self.get(&system_path).await?;
+
+        let mut inventory = HardwareInventory::default();
+        parse_system(&system, &mut inventory);
+
+        match self.collect_memory(&system).await {
+            Ok(memory) => inventory.memory = memory,
+            Err(e) => inventory.unavailable.push(format!("memory: {}", e)),
+        }
+        match self.collect_bmc_firmware().await {
+            Ok(version) => inventory.bmc_firmware = version,
+            Err(e) => inventory.unavailable.push(format!("manager: {}", e)),
+        }
+        match self
+            .members("/redfish/v1/UpdateService/FirmwareInventory")
+            .await
+        {
+            Ok(entries) => inventory.firmware = entries.iter().filter_map(parse_firmware).collect(),
+            Err(e) => inventory.unavailable.push(format!("firmware: {}", e)),
+        }
+        match self.collect_power_supplies(&system).await {
+            Ok(psus) => inventory.power_supplies = psus,
+            Err(e) => inventory.unavailable.push(format!("power: {}", e)),
+        }
+        Ok(inventory)
+    }
+
+    async fn collect_memory(&self, system: &Value) -> Result<Vec<DimmSlot>> {
+        let path = link(system, "Memory").ok_or_else(|| {
+            AutoInstallError::NetworkError("system has no Memory collection".to_string())
+        })?;
+        Ok(self.members(&path).await?.iter().map(parse_dimm).collect())
+    }
+
+    async fn collect_bmc_firmware(&self) -> Result<Option<String>> {
+        let managers = self.members("/redfish/v1/Managers").await?;
+        Ok(managers
+            .iter()
+            .find_map(|m| string_field(m, "FirmwareVersion")))
+    }
+
+    async fn collect_power_supplies(&self, system: &Value) -> Result<Vec<PowerSupply>> {
+        let chassis_path = system
+            .get("Links")
+            .and_then(|l| l.get("Chassis"))
+            .and_then(Value::as_array)
+            .and_then(|c| c.first())
+            .and_then(|c| string_field(c, "@odata.id"))
+            .ok_or_else(|| {
+                AutoInstallError::NetworkError("system links to no chassis".to_string())
+            })?;
+        let chassis = self.get(&chassis_path).await?;
+
+        // Newer BMCs expose PowerSubsystem; Power is deprecated but far more common
+        if let Some(subsystem) = link(&chassis, "PowerSubsystem") {
+            let subsystem = self.get(&subsystem).await?;
+            if let Some(collection) = link(&subsystem, "PowerSupplies") {
+                let psus = self.members(&collection).await?;
+                return Ok(psus.iter().map(parse_power_supply).collect());
+            }
+        }
+        let power = link(&chassis, "Power").ok_or_else(|| {
+            AutoInstallError::NetworkError("chassis has no power resource".to_string())
+        })?;
+        Ok(parse_legacy_power(&self.get(&power).await?))
+    }
+}
+
+/// Collect the inventory for `bmc`; failures are returned for the caller to report as warnings
+pub async fn collect_inventory(bmc: &BmcConfig) -> Result<HardwareInventory> {
+    RedfishClient::new(bmc)?.collect_inventory().await
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    #[test]
+    fn test_parse_system_and_dimms() {
+        let mut inventory = HardwareInventory::default();
+        parse_system(
+            &json!({
+                "Manufacturer": "Lenovo",
+                "Model": "ThinkSystem SR630",
+                "SerialNumber": "J30012AB",
+                "SKU": "",
+                "BiosVersion": "IVE176K-3.10",
+                "Memory": {"@odata.id": "/redfish/v1/Systems/1/Memory"}
+            }),
+            &mut inventory,
+        );
+        assert_eq!(inventory.model.as_deref(), Some("ThinkSystem SR630"));
+        assert_eq!(inventory.sku, None);
+
+        inventory.memory = vec![
+            parse_dimm(&json!({
+                "Id": "DIMM1", "DeviceLocator": "DIMM 1", "CapacityMiB": 32768,
+                "PartNumber": "M393A4K40DB3-CWE", "OperatingSpeedMhz": 3200,
+                "Status": {"State": "Enabled", "Health": "OK"}
+            })),
+            parse_dimm(&json!({
+                "Id": "DIMM2", "CapacityMiB": 0, "Status": {"State": "Absent"}
+            })),
+        ];
+        assert_eq!(inventory.memory[1].locator, "DIMM2");
+        assert!(!inventory.memory[1].populated);
+        assert_eq!(inventory.dimm_population(), (1, 2));
+        assert_eq!(inventory.total_memory_mib(), 32768);
+        assert_eq!(
+            inventory.summary(),
+            "Lenovo ThinkSystem SR630, S/N J30012AB, 1/2 DIMMs"
+        );
+    }
+
+    #[test]
+    fn test_parse_power_and_firmware() {
+        let psus = parse_legacy_power(&json!({
+            "PowerSupplies": [
+                {"MemberId": "0", "Name": "PSU1", "PowerCapacityWatts": 750,
+                 "Status": {"State": "Enabled", "Health": "OK"}},
+                {"MemberId": "1", "Name": "PSU2", "Status": {"State": "Enabled", "Health": "Critical"}},
+                {"MemberId": "2", "Status": {"State": "Absent"}}
+            ]
+        }));
+        assert_eq!(psus.len(), 3);
+        assert_eq!(psus[0].capacity_watts, Some(750));
+        assert_eq!(psus[2].name, "2");
+        assert_eq!(psus[2].health.as_deref(), Some("Absent"));
+
+        let inventory = HardwareInventory {
+            power_supplies: psus,
+            ..Default::default()
+        };
+        let names: Vec<&str> = inventory
+            .unhealthy_power_supplies()
+            .iter()
+            .map(|p| p.name.as_str())
+            .collect();
+        assert_eq!(names, vec!["PSU2", "2"]);
+        assert!(inventory
+            .summary_rows()
+            .contains(&("Power supplies", "3 (PSU2 Critical, 2 Absent)".to_string())));
+
+        assert_eq!(
+            parse_firmware(&json!({"Id": "BMC", "Name": "XCC Primary", "Version": "4.20"})),
+            Some(FirmwareEntry {
+                name: "XCC Primary".into(),
+                version: "4.20".into()
+            })
+        );
+        assert_eq!(parse_firmware(&json!({"Name": "Slot 3"})), None);
+        assert_eq!(
+            member_links(&json!({"Members": [{"@odata.id": "/a"}, {"@odata.id": "/b"}]})),
+            vec!["/a".to_string(), "/b".to_string()]
+        );
+    }
+}
@@ -0,0 +1,202 @@
+// file: src/network/self_install.rs
+// version: 1.1.0
+// guid: 4f8a2c71-6d39-4b05-9e1a-c7b3d5f08e62
+
+//! Copying the running agent onto a target (`self-install`)
+//!
+//! Live ISOs ship different glibc versions, so the binary copied is meant to be a static musl
+//! build. A dynamically linked binary is still copied, with a warning naming the loader it
+//! needs. The binary must match the target's architecture. It is uploaded to a staging path,
+//! put in place with `install -D` on the live system or inside a mounted target root (so it is
+//! there on first boot), and run with `--version` to prove it starts.
+
+use crate::error::AutoInstallError;
+use crate::network::SshClient;
+use crate::Result;
+use std::path::Path;
+use tracing::{info, warn};
+
+/// Where the agent is installed unless told otherwise
+pub const DEFAULT_PATH: &str = "/usr/local/bin/ubuntu-autoinstall-agent";
+/// Upload location before the binary is moved into place
+pub const STAGING_PATH: &str = "/tmp/.uaa-self-install";
+
+/// `PT_INTERP` program header: the dynamic loader a binary needs
+const PT_INTERP: u32 = 3;
+
+/// What the ELF header says about a binary
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ElfInfo {
+    /// Architecture as `uname -m` names it, when known
+    pub machine: Option<&'static str>,
+    /// Dynamic loader; `None` for a static binary
+    pub interpreter: Option<String>,
+}
+
+/// Read the architecture and loader of a 64-bit little-endian ELF file
+pub fn inspect_elf(bytes: &[u8]) -> Option<ElfInfo> {
+    if bytes.get(..4)? != b"\x7fELF" || bytes.get(4)? != &2 || bytes.get(5)? != &1 {
+        return None;
+    }
+    let u16_at = |at: usize| Some(u16::from_le_bytes(bytes.get(at..at + 2)?.try_into().ok()?));
+    let u32_at = |at: usize| Some(u32::from_le_bytes(bytes.get(at..at + 4)?.try_into().ok()?));
+    let u64_at =
+        |at: usize| Some(u64::from_le_bytes(bytes.get(at..at + 8)?.try_into().ok()?) as usize);
+    let machine = match u16_at(18)? {
+        62 => Some("x86_64"),
+        183 => Some("aarch64"),
+        243 => Some("riscv64"),
+        _ => None,
+    };
+    let (phoff, phentsize, phnum) = (u64_at(32)?, u16_at(54)? as usize, u16_at(56)? as usize);
+    let mut interpreter = None;
+    for i in 0..phnum {
+        let header = phoff + i * phentsize;
+        if u32_at(header)? == PT_INTERP {
+            let (offset, size) = (u64_at(header + 8)?, u64_at(header + 32)?);
+            let path = bytes.get(offset..offset + size)?;
+            interpreter = Some(
+                String::from_utf8_lossy(path)
+                    .trim_end_matches('\0')
+                    .to_string(),
+            );
+        }
+    }
+    Some(ElfInfo {
+        machine,
+        interpreter,
+    })
+}
+
+/// Commands moving the staged binary to `path`, under `root` when given, and running it
+pub fn build_install_commands(path: &str, root: Option<&str>) -> Vec<String> {
+    let (destination, verify) = match root {
+        Some(root) => {
+            let root = root.trim_end_matches('/');
+            (
+                format!("{}{}", root, path),
+                format!("chroot {} {} --version", root, path),
+            )
+        }
+        None => (path.to_string(), format!("{} --version", path)),
+    };
+    vec![
+        format!("install -D -m 0755 {} {}", STAGING_PATH, destination),
+        format!("rm -f {}", STAGING_PATH),
+        verify,
+    ]
+}
+
+/// Copies a binary onto a connected target
+pub struct SelfInstaller<'a> {
+    ssh: &'a mut SshClient,
+}
+
+impl<'a> SelfInstaller<'a> {
+    pub fn new(ssh: &'a mut SshClient) -> Self {
+        Self { ssh }
+    }
+
+    /// Install `binary` at `path` (inside `root` when given); returns its `--version` output
+    pub async fn install(
+        &mut self,
+        binary: &Path,
+        path: &str,
+        root: Option<&str>,
+    ) -> Result<String> {
+        let bytes = std::fs::read(binary)?;
+        let elf = inspect_elf(&bytes).ok_or_else(|| {
+            AutoInstallError::ValidationError(format!(
+                "{} is not a 64-bit Linux executable",
+                binary.display()
+            ))
+        })?;
+        let target_machine = self.ssh.execute_with_output("uname -m").await?;
+        let target_machine = target_machine.trim();
+        if let Some(machine) = elf.machine {
+            if machine != target_machine {
+                return Err(AutoInstallError::ValidationError(format!(
+                    "{} is built for {} but the target is {}",
+                    binary.display(),
+                    machine,
+                    target_machine
+                )));
+            }
+        }
+        match &elf.interpreter {
+            Some(loader) => warn!(
+                "{} is dynamically linked ({}); it may not start on the target's glibc. Use a static musl build",
+                binary.display(),
+                loader
+            ),
+            None => info!("{} is statically linked", binary.display()),
+        }
+
+        let source = binary.to_string_lossy();
+        self.ssh.upload_file(&source, STAGING_PATH).await?;
+        let commands = build_install_commands(path, root);
+        let (verify, setup) = commands.split_last().unwrap_or((&commands[0], &[]));
+        for command in setup {
+            self.ssh.execute(command).await?;
+        }
+        let version = self.ssh.execute_with_output(verify).await?;
+        Ok(version.trim().to_string())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// ELF header and one program header, followed by the loader path when given
+    fn elf(machine: u16, interpreter: Option<&str>) -> Vec<u8> {
+        let mut bytes = vec![0u8; 64 + 56];
+        bytes[..6].copy_from_slice(b"\x7fELF\x02\x01");
+        bytes[18..20].copy_from_slice(&machine.to_le_bytes());
+        bytes[32..40].copy_from_slice(&64u64.to_le_bytes());
+        bytes[54..56].copy_from_slice(&56u16.to_le_bytes());
+        bytes[56..58].copy_from_slice(&1u16.to_le_bytes());
+        if let Some(path) = interpreter {
+            let data = format!("{}\0", path);
+            bytes[64..68].copy_from_slice(&PT_INTERP.to_le_bytes());
+            bytes[72..80].copy_from_slice(&120u64.to_le_bytes());
+            bytes[96..104].copy_from_slice(&(data.len() as u64).to_le_bytes());
+            bytes.extend_from_slice(data.as_bytes());
+        }
+        bytes
+    }
+
+    #[test]
+    fn test_inspect_elf() {
+        let dynamic = inspect_elf(&elf(62, Some("/lib64/ld-linux-x86-64.so.2"))).unwrap();
+        assert_eq!(dynamic.machine, Some("x86_64"));
+        assert_eq!(
+            dynamic.interpreter.as_deref(),
+            Some("/lib64/ld-linux-x86-64.so.2")
+        );
+        assert!(dynamic.interpreter.is_some());
+
+        let musl = inspect_elf(&elf(183, None)).unwrap();
+        assert_eq!(musl.machine, Some("aarch64"));
+        assert!(musl.interpreter.is_none());
+
+        assert!(inspect_elf(b"#!/bin/sh\n").is_none());
+        assert!(inspect_elf(&elf(62, None)[..40]).is_none());
+    }
+
+    #[test]
+    fn test_install_commands() {
+        assert_eq!(
+            build_install_commands(DEFAULT_PATH, Some("/mnt/targetos/")),
+            vec![
+                "install -D -m 0755 /tmp/.uaa-self-install /mnt/targetos/usr/local/bin/ubuntu-autoinstall-agent",
+                "rm -f /tmp/.uaa-self-install",
+                "chroot /mnt/targetos /usr/local/bin/ubuntu-autoinstall-agent --version",
+            ]
+        );
+        assert_eq!(
+            build_install_commands("/opt/uaa", None)[2],
+            "/opt/uaa --version"
+        );
+    }
+}
@@ -0,0 +1,467 @@
+// file: src/network/serial.rs
+// version: 1.1.0
+// guid: c2a5e871-9d3f-4b60-8e14-5f7b0d9a3c26
+
+//! Command transport over a serial console or IPMI Serial-over-LAN
+//!
+//! The target must present a logged-in root shell on the console (the live installer's
+//! autologin on `ttyS0`, for example). Commands and their output are framed so they survive
+//! an interactive terminal full of kernel messages and prompts:
+//!
+//! - the command is uploaded as base64 in chunks of [`UPLOAD_CHUNK_SIZE`], each acknowledged
+//!   before the next is sent so the console's input buffer never overflows;
+//! - stdout and stderr are captured to files, then printed back as numbered base64 lines
+//!   tagged `@@uaa-<id> o|e <seq> <data>`, followed by `@@uaa-<id> x <exit code>`;
+//! - the tag is written split across shell quotes, so an echoed command line never looks like
+//!   a frame, and anything without the current tag is ignored.
+
+use crate::error::AutoInstallError;
+use crate::network::transport::UPLOAD_CHUNK_SIZE;
+use crate::network::transport::{base64_decode, base64_encode, CommandOutput, Transport};
+use crate::Result;
+use std::io::{BufRead, BufReader, Read, Write};
+use std::process::{Child, Command, Stdio};
+use std::sync::mpsc::{Receiver, RecvTimeoutError};
+use std::time::{Duration, Instant};
+use tracing::{debug, info};
+
+/// How long the console gets to answer the first framed command
+pub const HANDSHAKE_TIMEOUT: Duration = Duration::from_secs(30);
+
+/// Upper bound for a single command; debootstrap over a slow console can take a long time
+pub const DEFAULT_COMMAND_TIMEOUT: Duration = Duration::from_secs(4 * 3600);
+
+/// Width of the base64 lines the target prints back
+const OUTPUT_LINE_WIDTH: usize = 512;
+
+/// Builds the shell lines that run one command over the console
+#[derive(Debug, Clone)]
+pub struct FrameEncoder {
+    id: String,
+}
+
+impl FrameEncoder {
+    pub fn new(id: &str) -> Self {
+        Self { id: id.to_string() }
+    }
+
+    /// Tag prefixing every frame of this command
+    pub fn tag(&self) -> String {
+        format!("@@uaa-{}", self.id)
+    }
+
+    /// The tag as shell words that print it without containing it literally
+    fn quoted_tag(&self) -> String {
+        format!("'@@uaa'-{}", self.id)
+    }
+
+    fn file(&self, suffix: &str) -> String {
+        format!("/tmp/.uaa-{}.{}", self.id, suffix)
+    }
+
+    /// Lines uploading `command`; line `n` (1-based) is acknowledged with an `a n` frame
+    pub fn upload_lines(&self, command: &str) -> Vec<String> {
+        base64_encode(command.as_bytes())
+            .as_bytes()
+            .chunks(UPLOAD_CHUNK_SIZE)
+            .enumerate()
+            .map(|(i, chunk)| {
+                format!(
+                    "printf '%s' '{}' >> {}; echo {} a {}",
+                    String::from_utf8_lossy(chunk),
+                    self.file("cmd"),
+                    self.quoted_tag(),
+                    i + 1
+                )
+            })
+            .collect()
+    }
+
+    /// Line running the uploaded command and printing its framed output and exit code
+    pub fn run_line(&self) -> String {
+        format!(
+            "touch {cmd}; base64 -d {cmd} > {sh}; bash {sh} > {o} 2> {e} < /dev/null; rc=$?; \
+             for s in o e; do base64 -w {width} /tmp/.uaa-{id}.$s | awk -v p={tag}\" $s\" '{{print p, NR, $0}}'; done; \
+             echo {tag} x $rc; rm -f /tmp/.uaa-{id}.*",
+            cmd = self.file("cmd"),
+            sh = self.file("sh"),
+            o = self.file("o"),
+            e = self.file("e"),
+            width = OUTPUT_LINE_WIDTH,
+            id = self.id,
+            tag = self.quoted_tag(),
+        )
+    }
+}
+
+/// Reassembles one command's output from console lines
+#[derive(Debug)]
+pub struct FrameDecoder {
+    tag: String,
+    acked: usize,
+    stdout: String,
+    stderr: String,
+    seq: [u64; 2],
+    exit_code: Option<i32>,
+}
+
+impl FrameDecoder {
+    pub fn new(tag: &str) -> Self {
+        Self {
+            tag: format!("{} ", tag),
+            acked: 0,
+            stdout: String::new(),
+            stderr: String::new(),
+            seq: [0, 0],
+            exit_code: None,
+        }
+    }
+
+    /// Number of upload lines the target has acknowledged
+    pub fn acked(&self) -> usize {
+        self.acked
+    }
+
+    /// Whether the exit frame has arrived
+    pub fn is_complete(&self) -> bool {
+        self.exit_code.is_some()
+    }
+
+    /// Consume one console line; lines without this command's tag are ignored
+    pub fn feed(&mut self, line: &str) -> Result<()> {
+        // The prompt may share a line with the first frame, so search rather than match a prefix
+        let Some(start) = line.find(&self.tag) else {
+            return Ok(());
+        };
+        let frame = line[start + self.tag.len()..].trim_end();
+        let mut parts = frame.splitn(3, ' ');
+        let kind = parts.next().unwrap_or_default();
+        let malformed =
+            || AutoInstallError::SshError(format!("Malformed console frame: {}", line.trim_end()));
+        match kind {
+            "a" => {
+                self.acked = parts
+                    .next()
+                    .and_then(|n| n.parse().ok())
+                    .ok_or_else(malformed)?
+            }
+            "x" => {
+                self.exit_code = Some(
+                    parts
+                        .next()
+                        .and_then(|n| n.parse().ok())
+                        .ok_or_else(malformed)?,
+                )
+            }
+            "o" | "e" => {
+                let stream = usize::from(kind == "e");
+                let seq: u64 = parts
+                    .next()
+                    .and_then(|n| n.parse().ok())
+                    .ok_or_else(malformed)?;
+                if seq != self.seq[stream] + 1 {
+                    return Err(AutoInstallError::SshError(format!(
+                        "Lost output from the console: expected frame {} of {}, got {}",
+                        self.seq[stream] + 1,
+                        if stream == 0 { "stdout" } else { "stderr" },
+                        seq
+                    )));
+                }
+                self.seq[stream] = seq;
+                let data = parts.next().unwrap_or_default();
+                if stream == 0 {
+                    self.stdout.push_str(data);
+                } else {
+                    self.stderr.push_str(data);
+                }
+            }
+            _ => return Err(malformed()),
+        }
+        Ok(())
+    }
+
+    /// Decoded output once the exit frame has arrived
+    pub fn finish(self) -> Result<CommandOutput> {
+        let exit_code = self.exit_code.ok_or_else(|| {
+            AutoInstallError::SshError("Console output ended before the exit frame".to_string())
+        })?;
+        Ok(CommandOutput {
+            exit_code,
+            stdout: String::from_utf8_lossy(&base64_decode(&self.stdout)?).into_owned(),
+            stderr: String::from_utf8_lossy(&base64_decode(&self.stderr)?).into_owned(),
+        })
+    }
+}
+
+/// Shell on a serial line, reached through a device file or a child process such as `ipmitool`
+pub struct SerialTransport {
+    endpoint: String,
+    writer: Box<dyn Write + Send>,
+    lines: Receiver<String>,
+    child: Option<Child>,
+    session: String,
+    counter: u64,
+    timeout: Duration,
+}
+
+impl SerialTransport {
+    /// Open a local serial device at `baud`
+    pub fn open_device(device: &str, baud: u32) -> Result<Self> {
+        info!("Opening serial console {} at {} baud", device, baud);
+        let status = Command::new("stty")
+            .args(["-F", device, &baud.to_string(), "raw", "-echo"])
+            .status()?;
+        if !status.success() {
+            return Err(AutoInstallError::SshError(format!(
+                "Failed to configure serial device {} (stty exited with {})",
+                device, status
+            )));
+        }
+        let file = std::fs::OpenOptions::new()
+            .read(true)
+            .write(true)
+            .open(device)?;
+        let reader = file.try_clone()?;
+        Self::from_streams(&format!("serial {}", device), reader, file, None)
+    }
+
+    /// Open Serial-over-LAN on `bmc`; the BMC password is read from `IPMI_PASSWORD`
+    pub fn open_ipmi_sol(bmc: &str, username: &str) -> Result<Self> {
+        if std::env::var_os("IPMI_PASSWORD").is_none() {
+            return Err(AutoInstallError::ConfigError(
+                "IPMI_PASSWORD must be set to use the sol: transport".to_string(),
+            ));
+        }
+        info!("Activating IPMI Serial-over-LAN on {} as {}", bmc, username);
+        let base = ["-I", "lanplus", "-H", bmc, "-U", username, "-E"];
+        // A session left active by an earlier run blocks activation
+        let _ = Command::new("ipmitool")
+            .args(base)
+            .args(["sol", "deactivate"])
+            .output();
+        let mut args: Vec<&str> = base.to_vec();
+        args.extend(["sol", "activate"]);
+        Self::spawn(&format!("sol {}", bmc), "ipmitool", &args)
+    }
+
+    /// Use the stdin/stdout of `program` as the console
+    pub fn spawn(endpoint: &str, program: &str, args: &[&str]) -> Result<Self> {
+        let mut child = Command::new(program)
+            .args(args)
+            .stdin(Stdio::piped())
+            .stdout(Stdio::piped())
+            .stderr(Stdio::null())
+            .spawn()
+            .map_err(|e| {
+                AutoInstallError::SshError(format!("Failed to start {}: {}", program, e))
+            })?;
+        let writer = child
+            .stdin
+            .take()
+            .ok_or_else(|| AutoInstallError::SshError(format!("No stdin for {}", program)))?;
+        let reader = child
+            .stdout
+            .take()
+            .ok_or_else(|| AutoInstallError::SshError(format!("No stdout for {}", program)))?;
+        Self::from_streams(endpoint, reader, writer, Some(child))
+    }
+
+    fn from_streams(
+        endpoint: &str,
+        reader: impl Read + Send + 'static,
+        writer: impl Write + Send + 'static,
+        child: Option<Child>,
+    ) -> Result<Self> {
+        let (sender, lines) = std::sync::mpsc::channel();
+        std::thread::spawn(move || {
+            let mut reader = BufReader::new(reader);
+            let mut buf = Vec::new();
+            loop {
+                buf.clear();
+                match reader.read_until(b'\n', &mut buf) {
+                    Ok(0) | Err(_) => break,
+                    Ok(_) => {
+                        let line = String::from_utf8_lossy(&buf)
+                            .trim_end_matches(['\r', '\n'])
+                            .to_string();
+                        if sender.send(line).is_err() {
+                            break;
+                        }
+                    }
+                }
+            }
+        });
+
+        let mut transport = Self {
+            endpoint: endpoint.to_string(),
+            writer: Box::new(writer),
+            lines,
+            child,
+            session: uuid::Uuid::new_v4().simple().to_string()[..8].to_string(),
+            counter: 0,
+            timeout: DEFAULT_COMMAND_TIMEOUT,
+        };
+        transport.handshake()?;
+        Ok(transport)
+    }
+
+    /// Quiet the console and check that a shell answers
+    fn handshake(&mut self) -> Result<()> {
+        self.send_line("")?;
+        self.send_line("stty -echo 2>/dev/null; export PS1= PS2= TERM=dumb HISTFILE=/dev/null")?;
+        let timeout = std::mem::replace(&mut self.timeout, HANDSHAKE_TIMEOUT);
+        let result = self.run("true");
+        self.timeout = timeout;
+        match result {
+            Ok(_) => {
+                info!("Shell ready on {}", self.endpoint);
+                Ok(())
+            }
+            Err(e) => Err(AutoInstallError::SshError(format!(
+                "No shell answered on {} ({}); log in as root on the console or enable autologin first",
+                self.endpoint, e
+            ))),
+        }
+    }
+
+    fn send_line(&mut self, line: &str) -> Result<()> {
+        self.writer
+            .write_all(format!("{}\n", line).as_bytes())
+            .and_then(|_| self.writer.flush())
+            .map_err(|e| {
+                AutoInstallError::SshError(format!("Failed to write to {}: {}", self.endpoint, e))
+            })
+    }
+
+    /// Feed console lines to `decoder` until `done` holds or the deadline passes
+    fn read_until(
+        &mut self,
+        decoder: &mut FrameDecoder,
+        deadline: Instant,
+        done: impl Fn(&FrameDecoder) -> bool,
+    ) -> Result<()> {
+        while !done(decoder) {
+            let remaining = deadline.saturating_duration_since(Instant::now());
+            match self.lines.recv_timeout(remaining) {
+                Ok(line) => decoder.feed(&line)?,
+                Err(RecvTimeoutError::Timeout) => {
+                    return Err(AutoInstallError::TimeoutError(format!(
+                        "No response from {} within {}s",
+                        self.endpoint,
+                        self.timeout.as_secs()
+                    )))
+                }
+                Err(RecvTimeoutError::Disconnected) => {
+                    return Err(AutoInstallError::SshError(format!(
+                        "Console {} closed",
+                        self.endpoint
+                    )))
+                }
+            }
+        }
+        Ok(())
+    }
+}
+
+impl Transport for SerialTransport {
+    fn describe(&self) -> String {
+        self.endpoint.clone()
+    }
+
+    fn run(&mut self, command: &str) -> Result<CommandOutput> {
+        self.counter += 1;
+        let encoder = FrameEncoder::new(&format!("{}-{}", self.session, self.counter));
+        let mut decoder = FrameDecoder::new(&encoder.tag());
+        let deadline = Instant::now() + self.timeout;
+        debug!("Running over {}: {}", self.endpoint, command);
+
+        for (i, line) in encoder.upload_lines(command).iter().enumerate() {
+            self.send_line(line)?;
+            self.read_until(&mut decoder, deadline, |d| d.acked() > i)?;
+        }
+        self.send_line(&encoder.run_line())?;
+        self.read_until(&mut decoder, deadline, FrameDecoder::is_complete)?;
+        decoder.finish()
+    }
+
+    fn close(&mut self) {
+        if let Some(mut child) = self.child.take() {
+            let _ = child.kill();
+            let _ = child.wait();
+        }
+    }
+}
+
+impl Drop for SerialTransport {
+    fn drop(&mut self) {
+        self.close();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_decoder_ignores_noise_and_reassembles_output() {
+        let encoder = FrameEncoder::new("abc-1");
+        let mut decoder = FrameDecoder::new(&encoder.tag());
+        // Echoed command lines carry the split tag and must not match
+        decoder.feed(&encoder.run_line()).unwrap();
+        decoder
+            .feed("[  12.345] kernel: usb 1-1: new device")
+            .unwrap();
+        decoder.feed("root@ubuntu:~# @@uaa-abc-1 o 1 aGVs").unwrap();
+        decoder.feed("@@uaa-abc-1 o 2 bG8K\r").unwrap();
+        decoder.feed("@@uaa-abc-2 o 1 bm9pc2U=").unwrap();
+        decoder.feed("@@uaa-abc-1 e 1 b29wcwo=").unwrap();
+        assert!(!decoder.is_complete());
+        decoder.feed("@@uaa-abc-1 x 2").unwrap();
+        assert!(decoder.is_complete());
+
+        let output = decoder.finish().unwrap();
+        assert_eq!(output.exit_code, 2);
+        assert_eq!(output.stdout, "hello\n");
+        assert_eq!(output.stderr, "oops\n");
+    }
+
+    #[test]
+    fn test_decoder_detects_lost_frames() {
+        let mut decoder = FrameDecoder::new("@@uaa-x");
+        decoder.feed("@@uaa-x o 1 YQ==").unwrap();
+        assert!(decoder.feed("@@uaa-x o 3 YQ==").is_err());
+        assert!(FrameDecoder::new("@@uaa-x").finish().is_err());
+    }
+
+    #[test]
+    fn test_upload_lines_are_chunked_and_acknowledged() {
+        let encoder = FrameEncoder::new("s-7");
+        let command = "x".repeat(UPLOAD_CHUNK_SIZE * 2);
+        let lines = encoder.upload_lines(&command);
+        assert_eq!(lines.len(), 3);
+        assert!(lines[0].ends_with("; echo '@@uaa'-s-7 a 1"));
+        assert!(lines.iter().all(|l| l.len() < 4096));
+        assert!(!encoder.run_line().contains(&encoder.tag()));
+    }
+
+    #[test]
+    fn test_commands_run_over_a_shell_pipe() {
+        // A plain bash on a pipe stands in for a console
+        let mut transport = SerialTransport::spawn("test shell", "bash", &[]).unwrap();
+
+        let output = transport
+            .run("echo 'it''s here'; printf 'multi\\nline\\n' >&2; exit 3")
+            .unwrap();
+        assert_eq!(output.exit_code, 3);
+        assert_eq!(output.stdout, "its here\n");
+        assert_eq!(output.stderr, "multi\nline\n");
+
+        let dir = tempfile::TempDir::new().unwrap();
+        let path = dir.path().join("blob");
+        let data: Vec<u8> = (0..5000u32).map(|i| (i % 251) as u8).collect();
+        transport.write_file(path.to_str().unwrap(), &data).unwrap();
+        assert_eq!(std::fs::read(&path).unwrap(), data);
+        assert_eq!(transport.read_file(path.to_str().unwrap()).unwrap(), data);
+        transport.close();
+    }
+}
@@ -0,0 +1,785 @@
+// file: src/network/sinks.rs
+// version: 1.3.0
+// guid: 0d6a3f84-9c21-4e57-b8f3-5a7e2c1d9b46
+
+//! Destinations for session and progress reports
+//!
+//! A [`ReportDispatcher`] sends every report to each configured sink that accepts its type:
+//! webhooks, JSONL files, S3 objects, syslog and journald. All sinks receive the same JSON
+//! payload the webhook does. A failing sink is logged and never stops the others or the run.
+//! The dispatcher subscribes to the installation event bus for progress and lifecycle events.
+
+use crate::config::progress::{ProgressConfig, ReportKind, SinkConfig, SinkTarget};
+use crate::error::AutoInstallError;
+use crate::logging::redact;
+use crate::network::events::{EventSubscriber, InstallEvent};
+use crate::network::progress::ProgressEvent;
+use crate::network::ssh_installer::session::InstallSession;
+use crate::network::webhook::WebhookNotifier;
+use crate::Result;
+use async_trait::async_trait;
+use ring::hmac;
+use sha2::{Digest, Sha256};
+use std::path::PathBuf;
+use std::sync::Arc;
+use std::time::Duration;
+use tokio::io::AsyncWriteExt;
+use tracing::warn;
+
+/// Name reports are logged under in syslog and journald
+const IDENTIFIER: &str = "ubuntu-autoinstall-agent";
+
+/// Upper bound for one S3 upload
+const S3_TIMEOUT: Duration = Duration::from_secs(30);
+
+/// journald's native protocol socket
+const JOURNALD_SOCKET: &str = "/run/systemd/journal/socket";
+
+/// One session or progress report
+#[derive(Debug, Clone, PartialEq)]
+pub struct Report {
+    pub kind: ReportKind,
+    /// e.g. `install.completed` or `progress`
+    pub event: String,
+    pub hostname: String,
+    /// Body shared by every sink
+    pub payload: serde_json::Value,
+}
+
+impl Report {
+    /// Lifecycle `event` carrying `session`
+    pub fn session(event: &str, session: &InstallSession) -> Self {
+        Self {
+            kind: ReportKind::Session,
+            event: event.to_string(),
+            hostname: session.hostname.clone(),
+            payload: WebhookNotifier::payload(event, session),
+        }
+    }
+
+    /// Progress of one remote command
+    pub fn progress(progress: &ProgressEvent) -> Self {
+        Self {
+            kind: ReportKind::Progress,
+            event: "progress".to_string(),
+            hostname: progress.host.clone(),
+            payload: WebhookNotifier::progress_payload(progress),
+        }
+    }
+
+    /// Whether the event reports a failed or rolled back run
+    fn is_failure(&self) -> bool {
+        self.event.ends_with(".failed") || self.event.ends_with(".rolled_back")
+    }
+}
+
+/// A destination for reports
+#[async_trait]
+pub trait ReportSink: Send + Sync {
+    /// Short description used in log messages
+    fn describe(&self) -> String;
+
+    async fn deliver(&self, report: &Report) -> Result<()>;
+
+    /// Called once no more reports will come
+    async fn finish(&self) -> Result<()> {
+        Ok(())
+    }
+}
+
+#[async_trait]
+impl ReportSink for WebhookNotifier {
+    fn describe(&self) -> String {
+        format!("webhook {}", self.url())
+    }
+
+    async fn deliver(&self, report: &Report) -> Result<()> {
+        self.post(&report.event, &report.payload).await
+    }
+}
+
+/// Appends each report as one JSON line
+pub struct JsonlFileSink {
+    path: PathBuf,
+}
+
+impl JsonlFileSink {
+    pub fn new(path: impl Into<PathBuf>) -> Self {
+        Self { path: path.into() }
+    }
+}
+
+#[async_trait]
+impl ReportSink for JsonlFileSink {
+    fn describe(&self) -> String {
+        format!("file {}", self.path.display())
+    }
+
+    async fn deliver(&self, report: &Report) -> Result<()> {
+        if let Some(parent) = self.path.parent().filter(|p| !p.as_os_str().is_empty()) {
+            tokio::fs::create_dir_all(parent).await?;
+        }
+        let mut line = serde_json::to_vec(&report.payload)?;
+        line.push(b'\n');
+        let mut file = tokio::fs::OpenOptions::new()
+            .create(true)
+            .append(true)
+            .open(&self.path)
+            .await?;
+        // One write per line so concurrent runs appending to the same file do not interleave
+        file.write_all(&line).await?;
+        file.flush().await?;
+        Ok(())
+    }
+}
+
+/// Credentials for signing S3 requests
+#[derive(Clone)]
+pub struct AwsCredentials {
+    pub access_key_id: String,
+    pub secret_access_key: String,
+    pub session_token: Option<String>,
+}
+
+impl AwsCredentials {
+    /// Credentials from the standard AWS environment variables
+    pub fn from_env() -> Result<Self> {
+        let var = |name: &str| std::env::var(name).ok().filter(|v| !v.is_empty());
+        match (var("AWS_ACCESS_KEY_ID"), var("AWS_SECRET_ACCESS_KEY")) {
+            (Some(access_key_id), Some(secret_access_key)) => Ok(Self {
+                access_key_id,
+                secret_access_key,
+                session_token: var("AWS_SESSION_TOKEN"),
+            }),
+            _ => Err(AutoInstallError::ConfigError(
+                "S3 sink needs AWS_ACCESS_KEY_ID and AWS_SECRET_ACCESS_KEY".to_string(),
+            )),
+        }
+    }
+}
+
+/// Stores each report as an object `<prefix>/<hostname>/<timestamp>-<event>.json`
+pub struct S3Sink {
+    client: reqwest::Client,
+    bucket: String,
+    prefix: String,
+    region: String,
+    endpoint: Option<String>,
+    credentials: AwsCredentials,
+}
+
+impl S3Sink {
+    pub fn new(
+        bucket: &str,
+        prefix: &str,
+        region: &str,
+        endpoint: Option<&str>,
+        credentials: AwsCredentials,
+    ) -> Result<Self> {
+        let client = reqwest::Client::builder()
+            .timeout(S3_TIMEOUT)
+            .build()
+            .map_err(|e| {
+                AutoInstallError::NetworkError(format!("Failed to create S3 client: {}", e))
+            })?;
+        Ok(Self {
+            client,
+            bucket: bucket.to_string(),
+            prefix: prefix.trim_matches('/').to_string(),
+            region: region.to_string(),
+            endpoint: endpoint.map(|e| e.trim_end_matches('/').to_string()),
+            credentials,
+        })
+    }
+
+    /// Object key for `report`, unique per delivery
+    pub fn object_key(&self, report: &Report, now: chrono::DateTime<chrono::Utc>) -> String {
+        let safe = |s: &str| -> String {
+            s.chars()
+                .map(|c| {
+                    if c.is_ascii_alphanumeric() || matches!(c, '-' | '_' | '.') {
+                        c
+                    } else {
+                        '_'
+                    }
+                })
+                .collect()
+        };
+        let name = format!(
+            "{}/{}-{}-{}.json",
+            safe(&report.hostname),
+            now.format("%Y%m%dT%H%M%S%.3fZ"),
+            safe(&report.event),
+            &uuid::Uuid::new_v4().simple().to_string()[..8]
+        );
+        if self.prefix.is_empty() {
+            name
+        } else {
+            format!("{}/{}", self.prefix, name)
+        }
+    }
+
+    /// Host and path of `key`: virtual-hosted on AWS, path-style on a custom endpoint
+    fn location(&self, key: &str) -> (String, String, String) {
+        let path = |parts: &[&str]| {
+            parts
+                .iter()
+                .flat_map(|part| part.split('/'))
+                .map(uri_encode)
+                .collect::<Vec<_>>()
+                .join("/")
+        };
+        match &self.endpoint {
+            Some(endpoint) => {
+                let (scheme, host) = endpoint.split_once("://").unwrap_or(("https", endpoint));
+                (
+                    scheme.to_string(),
+                    host.to_string(),
+                    format!("/{}", path(&[&self.bucket, key])),
+                )
+            }
+            None => (
+                "https".to_string(),
+                format!("{}.s3.{}.amazonaws.com", self.bucket, self.region),
+                format!("/{}", path(&[key])),
+            ),
+        }
+    }
+}
+
+#[async_trait]
+impl ReportSink for S3Sink {
+    fn describe(&self) -> String {
+        format!("s3://{}/{}", self.bucket, self.prefix)
+    }
+
+    async fn deliver(&self, report: &Report) -> Result<()> {
+        let now = chrono::Utc::now();
+        let key = self.object_key(report, now);
+        let (scheme, host, path) = self.location(&key);
+        let body = serde_json::to_vec(&report.payload)?;
+        let headers = sigv4_headers(
+            &self.credentials,
+            &self.region,
+            "s3",
+            "PUT",
+            &host,
+            &path,
+            &body,
+            now,
+        );
+        let mut request = self
+            .client
+            .put(format!("{}://{}{}", scheme, host, path))
+            .header("Content-Type", "application/json")
+            .body(body);
+        for (name, value) in headers {
+            request = request.header(name, value);
+        }
+        let response = request.send().await?;
+        if !response.status().is_success() {
+            return Err(AutoInstallError::NetworkError(format!(
+                "S3 answered {} for {}",
+                response.status(),
+                key
+            )));
+        }
+        Ok(())
+    }
+}
+
+/// Headers signing a request with AWS Signature Version 4, `Authorization` last
+#[allow(clippy::too_many_arguments)]
+pub fn sigv4_headers(
+    credentials: &AwsCredentials,
+    region: &str,
+    service: &str,
+    method: &str,
+    host: &str,
+    path: &str,
+    body: &[u8],
+    now: chrono::DateTime<chrono::Utc>,
+) -> Vec<(String, String)> {
+    let amz_date = now.format("%Y%m%dT%H%M%SZ").to_string();
+    let date = now.format("%Y%m%d").to_string();
+    let payload_hash = format!("{:x}", Sha256::digest(body));
+
+    let mut headers = vec![
+        ("host".to_string(), host.to_string()),
+        ("x-amz-content-sha256".to_string(), payload_hash.clone()),
+        ("x-amz-date".to_string(), amz_date.clone()),
+    ];
+    if let Some(token) = &credentials.session_token {
+        headers.push(("x-amz-security-token".to_string(), token.clone()));
+    }
+    let signed_headers = headers
+        .iter()
+        .map(|(name, _)| name.as_str())
+        .collect::<Vec<_>>()
+        .join(";");
+    let canonical_headers: String = headers
+        .iter()
+        .map(|(name, value)| format!("{}:{}\n", name, value.trim()))
+        .collect();
+    let canonical_request = format!(
+        "{}\n{}\n\n{}\n{}\n{}",
+        method, path, canonical_headers, signed_headers, payload_hash
+    );
+    let scope = format!("{}/{}/{}/aws4_request", date, region, service);
+    let string_to_sign = format!(
+        "AWS4-HMAC-SHA256\n{}\n{}\n{:x}",
+        amz_date,
+        scope,
+        Sha256::digest(canonical_request.as_bytes())
+    );
+    let key = signing_key(&credentials.secret_access_key, &date, region, service);
+    let signature = hex(hmac::sign(
+        &hmac::Key::new(hmac::HMAC_SHA256, &key),
+        string_to_sign.as_bytes(),
+    )
+    .as_ref());
+
+    headers.retain(|(name, _)| name != "host");
+    headers.push((
+        "Authorization".to_string(),
+        format!(
+            "AWS4-HMAC-SHA256 Credential={}/{}, SignedHeaders={}, Signature={}",
+            credentials.access_key_id, scope, signed_headers, signature
+        ),
+    ));
+    headers
+}
+
+/// SigV4 key derived from the secret for one day, region and service
+fn signing_key(secret: &str, date: &str, region: &str, service: &str) -> Vec<u8> {
+    let step = |key: &[u8], data: &str| {
+        hmac::sign(&hmac::Key::new(hmac::HMAC_SHA256, key), data.as_bytes())
+    };
+    let k_date = step(format!("AWS4{}", secret).as_bytes(), date);
+    let k_region = step(k_date.as_ref(), region);
+    let k_service = step(k_region.as_ref(), service);
+    step(k_service.as_ref(), "aws4_request").as_ref().to_vec()
+}
+
+/// Percent-encode everything but RFC 3986 unreserved characters
+fn uri_encode(segment: &str) -> String {
+    segment
+        .bytes()
+        .map(|b| match b {
+            b'A'..=b'Z' | b'a'..=b'z' | b'0'..=b'9' | b'-' | b'_' | b'.' | b'~' => {
+                (b as char).to_string()
+            }
+            _ => format!("%{:02X}", b),
+        })
+        .collect()
+}
+
+fn hex(bytes: &[u8]) -> String {
+    bytes.iter().map(|b| format!("{:02x}", b)).collect()
+}
+
+/// Sends RFC 5424 messages to a local syslog socket or a UDP collector
+pub struct SyslogSink {
+    address: String,
+}
+
+impl SyslogSink {
+    pub fn new(address: &str) -> Self {
+        Self {
+            address: address.to_string(),
+        }
+    }
+
+    /// RFC 5424 message for `report` from facility local0; failures are logged as errors
+    pub fn format(report: &Report, now: chrono::DateTime<chrono::Utc>) -> String {
+        let severity = if report.is_failure() { 3 } else { 6 };
+        format!(
+            "<{}>1 {} {} {} - {} - {}",
+            16 * 8 + severity,
+            now.to_rfc3339_opts(chrono::SecondsFormat::Millis, true),
+            nil_or(&report.hostname),
+            IDENTIFIER,
+            nil_or(&report.event),
+            report.payload
+        )
+    }
+}
+
+/// RFC 5424 header fields are printable ASCII without spaces, or `-`
+fn nil_or(value: &str) -> String {
+    let value: String = value.chars().filter(|c| c.is_ascii_graphic()).collect();
+    if value.is_empty() {
+        "-".to_string()
+    } else {
+        value
+    }
+}
+
+#[async_trait]
+impl ReportSink for SyslogSink {
+    fn describe(&self) -> String {
+        format!("syslog {}", self.address)
+    }
+
+    async fn deliver(&self, report: &Report) -> Result<()> {
+        let message = Self::format(report, chrono::Utc::now());
+        if self.address.starts_with('/') {
+            let socket = tokio::net::UnixDatagram::unbound()?;
+            socket.send_to(message.as_bytes(), &self.address).await?;
+        } else {
+            let socket = tokio::net::UdpSocket::bind("0.0.0.0:0").await?;
+            socket.send_to(message.as_bytes(), &self.address).await?;
+        }
+        Ok(())
+    }
+}
+
+/// Writes structured entries through journald's native protocol
+pub struct JournaldSink {
+    socket: PathBuf,
+}
+
+impl Default for JournaldSink {
+    fn default() -> Self {
+        Self {
+            socket: PathBuf::from(JOURNALD_SOCKET),
+        }
+    }
+}
+
+impl JournaldSink {
+    /// Datagram carrying `report` as journal fields
+    ///
+    /// Values containing a newline use the length-prefixed form of the protocol.
+    pub fn encode(report: &Report) -> Vec<u8> {
+        let priority = if report.is_failure() { "3" } else { "6" };
+        let message = match report.kind {
+            ReportKind::Session => format!("{} {}", report.hostname, report.event),
+            ReportKind::Progress => format!(
+                "{} {} {}%",
+                report.hostname,
+                report.payload["progress"]["tool"].as_str().unwrap_or(""),
+                report.payload["progress"]["percent"]
+            ),
+        };
+        let kind = match report.kind {
+            ReportKind::Session => "session",
+            ReportKind::Progress => "progress",
+        };
+        let fields = [
+            ("MESSAGE", message),
+            ("PRIORITY", priority.to_string()),
+            ("SYSLOG_IDENTIFIER", IDENTIFIER.to_string()),
+            ("UAA_KIND", kind.to_string()),
+            ("UAA_EVENT", report.event.clone()),
+            ("UAA_HOSTNAME", report.hostname.clone()),
+            ("UAA_PAYLOAD", report.payload.to_string()),
+        ];
+        let mut datagram = Vec::new();
+        for (name, value) in fields {
+            datagram.extend_from_slice(name.as_bytes());
+            if value.contains('\n') {
+                datagram.push(b'\n');
+                datagram.extend_from_slice(&(value.len() as u64).to_le_bytes());
+            } else {
+                datagram.push(b'=');
+            }
+            datagram.extend_from_slice(value.as_bytes());
+            datagram.push(b'\n');
+        }
+        datagram
+    }
+}
+
+#[async_trait]
+impl ReportSink for JournaldSink {
+    fn describe(&self) -> String {
+        "journald".to_string()
+    }
+
+    async fn deliver(&self, report: &Report) -> Result<()> {
+        let socket = tokio::net::UnixDatagram::unbound()?;
+        socket.send_to(&Self::encode(report), &self.socket).await?;
+        Ok(())
+    }
+}
+
+/// Fans reports out to every sink that accepts their type
+#[derive(Clone, Default)]
+pub struct ReportDispatcher {
+    sinks: Vec<(Vec<ReportKind>, Arc<dyn ReportSink>)>,
+}
+
+impl ReportDispatcher {
+    /// Sinks of a `progress:` section; `webhook` is a webhook sink for every report type
+    pub fn from_config(config: &ProgressConfig) -> Result<Self> {
+        let mut dispatcher = Self::default();
+        if let Some(url) = &config.webhook {
+            dispatcher.add(Vec::new(), Arc::new(WebhookNotifier::new(url)?));
+        }
+        for sink in &config.sinks {
+            // Beacons need the connected target; ssh-install adds them itself
+            if matches!(sink.target, SinkTarget::Beacon { .. }) {
+                continue;
+            }
+            dispatcher.add(sink.reports.clone(), Self::build(sink)?);
+        }
+        Ok(dispatcher)
+    }
+
+    fn build(sink: &SinkConfig) -> Result<Arc<dyn ReportSink>> {
+        Ok(match &sink.target {
+            SinkTarget::Webhook { url } => Arc::new(WebhookNotifier::new(url)?),
+            SinkTarget::File { path } => Arc::new(JsonlFileSink::new(path)),
+            SinkTarget::S3 {
+                bucket,
+                prefix,
+                region,
+                endpoint,
+            } => Arc::new(S3Sink::new(
+                bucket,
+                prefix,
+                region,
+                endpoint.as_deref(),
+                AwsCredentials::from_env()?,
+            )?),
+            SinkTarget::Syslog { address } => Arc::new(SyslogSink::new(address)),
+            SinkTarget::Journald => Arc::new(JournaldSink::default()),
+            SinkTarget::Beacon { url } => {
+                return Err(AutoInstallError::ConfigError(format!(
+                    "beacon sink {} is started against a connected target",
+                    url
+                )))
+            }
+        })
+    }
+
+    /// Add `sink` for the report types in `kinds` (all when empty)
+    pub fn add(&mut self, kinds: Vec<ReportKind>, sink: Arc<dyn ReportSink>) {
+        self.sinks.push((kinds, sink));
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.sinks.is_empty()
+    }
+
+    /// Deliver `report` to each accepting sink in turn, logging failures
+    pub async fn dispatch(&self, report: &Report) {
+        let mut report = report.clone();
+        redact::scrub_json(&mut report.payload);
+        let report = &report;
+        for (kinds, sink) in &self.sinks {
+            if !kinds.is_empty() && !kinds.contains(&report.kind) {
+                continue;
+            }
+            if let Err(e) = sink.deliver(report).await {
+                warn!(
+                    "Report delivery to {} failed for {}: {}",
+                    sink.describe(),
+                    report.event,
+                    e
+                );
+            }
+        }
+    }
+
+    /// Deliver lifecycle `event` for `session`
+    pub async fn notify(&self, event: &str, session: &InstallSession) {
+        self.dispatch(&Report::session(event, session)).await;
+    }
+
+    /// Let every sink finish delivering, logging failures
+    pub async fn finish(&self) {
+        for (_, sink) in &self.sinks {
+            if let Err(e) = sink.finish().await {
+                warn!("Report sink {} did not finish: {}", sink.describe(), e);
+            }
+        }
+    }
+}
+
+#[async_trait]
+impl EventSubscriber for ReportDispatcher {
+    /// Dispatch progress and lifecycle events; the rest has no report type
+    async fn handle(&mut self, event: &InstallEvent) {
+        match event {
+            InstallEvent::Progress(progress) => self.dispatch(&Report::progress(progress)).await,
+            InstallEvent::Session { name, session } => self.notify(name, session).await,
+            _ => {}
+        }
+    }
+
+    async fn close(&mut self) {
+        self.finish().await;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::network::ssh_installer::session::SessionStatus;
+    use chrono::TimeZone;
+
+    fn progress_report() -> Report {
+        Report::progress(&ProgressEvent {
+            host: "web-01".to_string(),
+            tool: "apt".to_string(),
+            percent: 40,
+            detail: "pmstatus:rsync:40.0:Installing rsync".to_string(),
+        })
+    }
+
+    #[tokio::test]
+    async fn test_dispatch_filters_by_kind() {
+        let dir = tempfile::tempdir().unwrap();
+        let all = dir.path().join("all.jsonl");
+        let sessions = dir.path().join("nested/sessions.jsonl");
+        let mut dispatcher = ReportDispatcher::default();
+        dispatcher.add(Vec::new(), Arc::new(JsonlFileSink::new(&all)));
+        dispatcher.add(
+            vec![ReportKind::Session],
+            Arc::new(JsonlFileSink::new(&sessions)),
+        );
+
+        let mut session = InstallSession::new("web-01");
+        session.status = SessionStatus::Completed;
+        dispatcher.dispatch(&progress_report()).await;
+        dispatcher.notify("install.completed", &session).await;
+
+        let all = std::fs::read_to_string(&all).unwrap();
+        let lines: Vec<serde_json::Value> = all
+            .lines()
+            .map(|l| serde_json::from_str(l).unwrap())
+            .collect();
+        assert_eq!(lines.len(), 2);
+        assert_eq!(lines[0]["event"], "progress");
+        assert_eq!(lines[1]["status"], "completed");
+        let sessions = std::fs::read_to_string(&sessions).unwrap();
+        assert_eq!(sessions.lines().count(), 1);
+        assert!(sessions.contains("install.completed"));
+    }
+
+    #[tokio::test]
+    async fn test_reports_and_session_records_are_redacted() {
+        let secret = "hunter2-luks-9c3e";
+        redact::register(secret);
+        let command = format!("echo '{}' | cryptsetup open /dev/sda4 luks", secret);
+        let error = AutoInstallError::ProcessError {
+            command: command.clone(),
+            exit_code: Some(2),
+            stderr: format!("No key available with this passphrase: {}", secret),
+        };
+        let mut session = InstallSession::new("web-01");
+        session.status = SessionStatus::Failed;
+        session.last_command = Some(command);
+        session.failed_phases = vec![format!("Phase 2: Disk setup - {}", error)];
+
+        let dir = tempfile::tempdir().unwrap();
+        let jsonl = dir.path().join("reports.jsonl");
+        let mut dispatcher = ReportDispatcher::default();
+        dispatcher.add(Vec::new(), Arc::new(JsonlFileSink::new(&jsonl)));
+        dispatcher.notify("install.failed", &session).await;
+        let record = session.save(dir.path()).unwrap();
+
+        for path in [jsonl, record] {
+            let written = std::fs::read_to_string(&path).unwrap();
+            assert!(!written.contains(secret), "{}", written);
+            assert!(written.contains(redact::REDACTED));
+        }
+        assert!(!error.to_string().contains(secret));
+    }
+
+    #[test]
+    fn test_syslog_and_journald_formats() {
+        let now = chrono::Utc.with_ymd_and_hms(2026, 3, 1, 12, 0, 0).unwrap();
+        let message = SyslogSink::format(&progress_report(), now);
+        assert!(message.starts_with(
+            "<134>1 2026-03-01T12:00:00.000Z web-01 ubuntu-autoinstall-agent - progress - {"
+        ));
+
+        let mut report = progress_report();
+        report.event = "upgrade.failed".to_string();
+        assert!(SyslogSink::format(&report, now).starts_with("<131>1 "));
+
+        let datagram = JournaldSink::encode(&progress_report());
+        let text = String::from_utf8(datagram).unwrap();
+        assert!(text.contains("MESSAGE=web-01 apt 40%\n"));
+        assert!(text.contains("PRIORITY=6\n"));
+        assert!(text.contains("UAA_KIND=progress\n"));
+
+        report.hostname = "two\nlines".to_string();
+        let datagram = JournaldSink::encode(&report);
+        let marker = b"UAA_HOSTNAME\n";
+        let at = datagram
+            .windows(marker.len())
+            .position(|w| w == marker)
+            .unwrap();
+        let start = at + marker.len();
+        let len = u64::from_le_bytes(datagram[start..start + 8].try_into().unwrap());
+        assert_eq!(len, report.hostname.len() as u64);
+    }
+
+    #[test]
+    fn test_sigv4_signing_key_matches_aws_example() {
+        // Example from the AWS Signature Version 4 documentation
+        let key = signing_key(
+            "wJalrXUtnFEMI/K7MDENG+bPxRfiCYEXAMPLEKEY",
+            "20150830",
+            "us-east-1",
+            "iam",
+        );
+        assert_eq!(
+            hex(&key),
+            "c4afb1cc5771d871763a393e44b703571b55cc28424d1a5e86da6ed3c154a4b9"
+        );
+    }
+
+    #[test]
+    fn test_s3_object_key_and_headers() {
+        let credentials = AwsCredentials {
+            access_key_id: "AKIDEXAMPLE".to_string(),
+            secret_access_key: "secret".to_string(),
+            session_token: Some("token".to_string()),
+        };
+        let sink = S3Sink::new(
+            "install-reports",
+            "/lab/",
+            "eu-west-1",
+            Some("http://minio:9000/"),
+            credentials.clone(),
+        )
+        .unwrap();
+        let now = chrono::Utc.with_ymd_and_hms(2026, 3, 1, 12, 0, 0).unwrap();
+        let key = sink.object_key(&progress_report(), now);
+        assert!(key.starts_with("lab/web-01/20260301T120000.000Z-progress-"));
+        let (scheme, host, path) = sink.location("lab/web 01.json");
+        assert_eq!(
+            (scheme.as_str(), host.as_str(), path.as_str()),
+            ("http", "minio:9000", "/install-reports/lab/web%2001.json")
+        );
+
+        let headers = sigv4_headers(
+            &credentials,
+            "eu-west-1",
+            "s3",
+            "PUT",
+            &host,
+            &path,
+            b"{}",
+            now,
+        );
+        let names: Vec<&str> = headers.iter().map(|(n, _)| n.as_str()).collect();
+        assert_eq!(
+            names,
+            vec![
+                "x-amz-content-sha256",
+                "x-amz-date",
+                "x-amz-security-token",
+                "Authorization"
+            ]
+        );
+        assert!(headers[3].1.starts_with(
+            "AWS4-HMAC-SHA256 Credential=AKIDEXAMPLE/20260301/eu-west-1/s3/aws4_request, SignedHeaders=host;x-amz-content-sha256;x-amz-date;x-amz-security-token, Signature="
+        ));
+    }
+}
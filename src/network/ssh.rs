@@ -1,18 +1,110 @@
 // file: src/network/ssh.rs
-// version: 1.3.0
+// version: 1.15.0
 // guid: t0u1v2w3-x4y5-6789-0123-456789tuvwxy
 
 //! SSH client for remote deployment operations
 
+use crate::logging::redact;
+use crate::network::chaos::{ChaosFault, ChaosMonkey};
+use crate::network::progress::{ProgressReporter, ProgressTracker};
+use crate::network::stream::{LineCallback, LineSplitter, OutputLine, StreamKind, StreamOptions};
+use crate::network::sudo::{ElevationRecord, SudoPolicy};
+use crate::network::transport::Transport;
+use crate::utils::CancellationToken;
 use crate::Result;
 use ssh2::Session;
-use std::net::TcpStream;
-use tracing::{debug, error, info};
+use std::net::{TcpStream, ToSocketAddrs};
+use std::time::Duration;
+use tracing::{debug, error, info, warn};
+
+/// Command printing the kernel's per-boot random ID, which changes on every reboot
+const BOOT_ID_COMMAND: &str = "cat /proc/sys/kernel/random/boot_id";
+
+/// How often a streamed command is polled for output
+const STREAM_POLL: Duration = Duration::from_millis(20);
+
+/// How to treat a host key that changed across a reboot
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum HostKeyPolicy {
+    /// Refuse to reconnect when the key changed
+    Strict,
+    /// Accept the new key with a warning (live environments generate fresh keys on every boot)
+    #[default]
+    AcceptNew,
+}
+
+/// Options for [`SshClient::wait_for_reboot`]
+#[derive(Debug, Clone)]
+pub struct RebootWait {
+    /// Give up when the host has not come back within this time
+    pub timeout: Duration,
+    /// Delay between reconnect attempts; also the TCP connect timeout for each attempt
+    pub poll_interval: Duration,
+    /// What to do when the host key changed
+    pub host_key_policy: HostKeyPolicy,
+}
+
+impl Default for RebootWait {
+    fn default() -> Self {
+        Self {
+            timeout: Duration::from_secs(600),
+            poll_interval: Duration::from_secs(10),
+            host_key_policy: HostKeyPolicy::AcceptNew,
+        }
+    }
+}
+
+/// Check `current` against the fingerprint seen before the reboot
+///
+/// Returns whether the key changed; fails when it changed under [`HostKeyPolicy::Strict`].
+pub fn verify_host_key(
+    host: &str,
+    previous: Option<&str>,
+    current: Option<&str>,
+    policy: HostKeyPolicy,
+) -> Result<bool> {
+    let changed = matches!((previous, current), (Some(old), Some(new)) if old != new);
+    if changed {
+        match policy {
+            HostKeyPolicy::Strict => {
+                return Err(crate::error::AutoInstallError::SshError(format!(
+                    "Host key for {} changed across reboot ({} -> {}); refusing to reconnect",
+                    host,
+                    previous.unwrap_or_default(),
+                    current.unwrap_or_default()
+                )))
+            }
+            HostKeyPolicy::AcceptNew => warn!(
+                "Host key for {} changed across reboot; accepting new key {}",
+                host,
+                current.unwrap_or_default()
+            ),
+        }
+    }
+    Ok(changed)
+}
+
+/// SHA-256 fingerprint of the server's host key, hex-encoded
+fn host_key_fingerprint(session: &Session) -> Option<String> {
+    session
+        .host_key_hash(ssh2::HashType::Sha256)
+        .map(|hash| hash.iter().map(|b| format!("{:02x}", b)).collect())
+}
 
 /// SSH client for remote operations
 pub struct SshClient {
     session: Option<Session>,
     host: String,
+    username: String,
+    host_key: Option<String>,
+    cancel: Option<CancellationToken>,
+    last_command: Option<String>,
+    chaos: Option<ChaosMonkey>,
+    transport: Option<Box<dyn Transport>>,
+    progress: ProgressReporter,
+    sudo: Option<SudoPolicy>,
+    elevation_log: Vec<ElevationRecord>,
+    deadline: Option<std::time::Instant>,
 }
 
 impl SshClient {
@@ -21,6 +113,136 @@ impl SshClient {
         Self {
             session: None,
             host: String::new(),
+            username: String::new(),
+            host_key: None,
+            cancel: None,
+            last_command: None,
+            chaos: None,
+            transport: None,
+            progress: ProgressReporter::default(),
+            sudo: None,
+            elevation_log: Vec::new(),
+            deadline: None,
+        }
+    }
+
+    /// Refuse to start new remote commands once `token` is cancelled
+    ///
+    /// A short command that is already running is allowed to finish so the target is never left
+    /// half-way through a single step; only subsequent commands are rejected. Streamed commands
+    /// (those with a progress parser, like debootstrap and apt, and [`Self::execute_streaming`])
+    /// can run for a long time, so they are stopped on the target instead.
+    pub fn set_cancellation_token(&mut self, token: CancellationToken) {
+        self.cancel = Some(token);
+    }
+
+    /// Host this client connects to
+    pub fn host(&self) -> &str {
+        &self.host
+    }
+
+    /// The most recent remote command started on this client
+    pub fn last_command(&self) -> Option<&str> {
+        self.last_command.as_deref()
+    }
+
+    /// Parse progress from long commands with `reporter`'s custom patterns and event bus
+    pub fn set_progress(&mut self, reporter: ProgressReporter) {
+        self.progress = reporter;
+    }
+
+    /// Run commands through sudo as `policy` says; console transports run them unchanged
+    pub fn set_sudo_policy(&mut self, policy: SudoPolicy) {
+        self.sudo = Some(policy);
+    }
+
+    /// User the SSH session logs in as
+    pub fn username(&self) -> &str {
+        &self.username
+    }
+
+    /// Commands run under the sudo policy so far and whether each was elevated
+    pub fn elevation_log(&self) -> &[ElevationRecord] {
+        &self.elevation_log
+    }
+
+    /// Check that sudo works for the logged-in user without an interactive prompt
+    pub async fn verify_sudo(&mut self) -> Result<()> {
+        let Some(policy) = self.sudo.clone() else {
+            return Ok(());
+        };
+        if self.transport.is_some() {
+            return Ok(());
+        }
+        let (exit_status, _, stderr) = self.run_session("true", true)?;
+        if exit_status != 0 {
+            return Err(policy.check_failed(&self.host, &self.username, &stderr));
+        }
+        info!("sudo works for {} on {}", self.username, self.host);
+        Ok(())
+    }
+
+    /// Refuse to start new remote commands after `deadline`; `None` lifts the limit
+    ///
+    /// A command already running is allowed to finish.
+    pub fn set_command_deadline(&mut self, deadline: Option<std::time::Instant>) {
+        self.deadline = deadline;
+    }
+
+    /// Inject faults from `chaos` instead of running matching commands (developer mode)
+    pub fn set_chaos(&mut self, chaos: ChaosMonkey) {
+        self.chaos = Some(chaos);
+    }
+
+    /// Check for cancellation and remember `command` as the one in flight
+    ///
+    /// Returns the exit code to report when chaos mode injects a command failure; injected
+    /// timeouts and dropped connections are returned as errors.
+    fn begin_command(&mut self, command: &str) -> Result<Option<i32>> {
+        if let Some(token) = &self.cancel {
+            token.check(&format!("refusing to start remote command: {}", command))?;
+        }
+        if self
+            .deadline
+            .is_some_and(|deadline| std::time::Instant::now() >= deadline)
+        {
+            return Err(crate::error::AutoInstallError::TimeoutError(format!(
+                "installation budget exceeded; refusing to start remote command: {}",
+                command
+            )));
+        }
+        self.last_command = Some(redact::scrub(command).into_owned());
+
+        match self.chaos.as_mut().and_then(|c| c.on_command(command)) {
+            None => Ok(None),
+            Some(ChaosFault::ExitCode(code)) => {
+                warn!("Chaos: injecting exit code {} for: {}", code, command);
+                Ok(Some(code))
+            }
+            Some(ChaosFault::Timeout) => {
+                warn!("Chaos: injecting timeout for: {}", command);
+                Err(crate::error::AutoInstallError::TimeoutError(format!(
+                    "Command timed out (injected by chaos mode): {}",
+                    command
+                )))
+            }
+            Some(ChaosFault::Disconnect) => {
+                warn!("Chaos: dropping SSH connection before: {}", command);
+                self.disconnect();
+                Err(crate::error::AutoInstallError::SshError(format!(
+                    "Connection to {} lost (injected by chaos mode)",
+                    self.host
+                )))
+            }
+        }
+    }
+
+    /// Error reported for a command failed by chaos mode
+    fn injected_failure(command: &str, exit_code: i32) -> crate::error::AutoInstallError {
+        crate::error::AutoInstallError::ProcessError {
+            command: command.to_string(),
+            exit_code: Some(exit_code),
+            stderr: "injected by chaos mode".to_string(),
         }
     }
 
@@ -28,12 +250,54 @@ impl SshClient {
     pub async fn connect(&mut self, host: &str, username: &str) -> Result<()> {
         info!("Connecting to {} as {}", host, username);
 
-        let tcp = TcpStream::connect(format!("{}:22", host)).map_err(|e| {
+        let session = Self::open_session(host, username, None)?;
+        self.host_key = host_key_fingerprint(&session);
+        self.session = Some(session);
+        self.host = host.to_string();
+        self.username = username.to_string();
+
+        info!("SSH connection established to {}", host);
+        Ok(())
+    }
+
+    /// Run commands for `host` over `transport` instead of an SSH session
+    ///
+    /// `host` only labels logs and reports; every command goes through the transport.
+    pub fn connect_transport(&mut self, host: &str, transport: Box<dyn Transport>) {
+        info!("Using {} for {}", transport.describe(), host);
+        self.disconnect();
+        self.transport = Some(transport);
+        self.host = host.to_string();
+    }
+
+    /// Open and authenticate a session; `connect_timeout` bounds the TCP connect
+    fn open_session(
+        host: &str,
+        username: &str,
+        connect_timeout: Option<Duration>,
+    ) -> Result<Session> {
+        let connect_error = |e: std::io::Error| {
             crate::error::AutoInstallError::SshError(format!(
                 "Failed to connect to {}: {}",
                 host, e
             ))
-        })?;
+        };
+        let tcp = match connect_timeout {
+            Some(timeout) => {
+                let addr = (host, 22)
+                    .to_socket_addrs()
+                    .map_err(connect_error)?
+                    .next()
+                    .ok_or_else(|| {
+                        crate::error::AutoInstallError::SshError(format!(
+                            "Failed to resolve {}",
+                            host
+                        ))
+                    })?;
+                TcpStream::connect_timeout(&addr, timeout).map_err(connect_error)?
+            }
+            None => TcpStream::connect(format!("{}:22", host)).map_err(connect_error)?,
+        };
 
         let mut session = Session::new().map_err(|e| {
             crate::error::AutoInstallError::SshError(format!("Failed to create SSH session: {}", e))
@@ -58,17 +322,145 @@ impl SshClient {
             ));
         }
 
-        self.session = Some(session);
-        self.host = host.to_string();
+        Ok(session)
+    }
 
-        info!("SSH connection established to {}", host);
-        Ok(())
+    /// The target's current boot ID; compare before and after a reboot to confirm it happened
+    pub async fn boot_id(&mut self) -> Result<String> {
+        Ok(self
+            .execute_with_output(BOOT_ID_COMMAND)
+            .await?
+            .trim()
+            .to_string())
     }
 
-    /// Execute command on remote host
-    pub async fn execute(&mut self, command: &str) -> Result<()> {
-        debug!("Executing command: {}", command);
+    /// Reboot the target and wait for it to come back on this client
+    pub async fn reboot_and_wait(&mut self, options: &RebootWait) -> Result<()> {
+        self.ensure_reconnectable()?;
+        let boot_id = self.boot_id().await?;
+        // Detach so the reboot does not kill the channel before the command returns
+        self.execute("nohup sh -c 'sleep 2 && systemctl reboot' >/dev/null 2>&1 &")
+            .await?;
+        self.wait_for_reboot(&boot_id, options).await
+    }
 
+    /// Reconnecting after a reboot needs SSH; a console transport cannot tell when it is back
+    fn ensure_reconnectable(&self) -> Result<()> {
+        match &self.transport {
+            Some(transport) => Err(crate::error::AutoInstallError::SshError(format!(
+                "Rebooting the target is not supported over {}",
+                transport.describe()
+            ))),
+            None => Ok(()),
+        }
+    }
+
+    /// Wait for a target that is rebooting to come back, then re-establish this session
+    ///
+    /// The current session is dropped and the host is polled until SSH accepts a session whose
+    /// boot ID differs from `previous_boot_id`, so reconnecting to the old system before it goes
+    /// down is not mistaken for a completed reboot. Managers borrowing this client keep working
+    /// on the new session afterwards.
+    pub async fn wait_for_reboot(
+        &mut self,
+        previous_boot_id: &str,
+        options: &RebootWait,
+    ) -> Result<()> {
+        if self.host.is_empty() {
+            return Err(crate::error::AutoInstallError::SshError(
+                "wait_for_reboot requires a previously connected client".to_string(),
+            ));
+        }
+        self.ensure_reconnectable()?;
+        info!(
+            "Waiting up to {}s for {} to reboot",
+            options.timeout.as_secs(),
+            self.host
+        );
+        self.disconnect();
+        let deadline = tokio::time::Instant::now() + options.timeout;
+
+        loop {
+            tokio::time::sleep(options.poll_interval).await;
+            if let Some(token) = &self.cancel {
+                token.check(&format!("stopped waiting for {} to reboot", self.host))?;
+            }
+
+            match Self::open_session(&self.host, &self.username, Some(options.poll_interval)) {
+                Ok(session) => {
+                    let fingerprint = host_key_fingerprint(&session);
+                    verify_host_key(
+                        &self.host,
+                        self.host_key.as_deref(),
+                        fingerprint.as_deref(),
+                        options.host_key_policy,
+                    )?;
+                    self.session = Some(session);
+                    match self.boot_id().await {
+                        Ok(id) if id != previous_boot_id => {
+                            self.host_key = fingerprint;
+                            info!("{} is back after reboot (boot id {})", self.host, id);
+                            return Ok(());
+                        }
+                        Ok(_) => debug!("{} has not gone down yet", self.host),
+                        Err(e) => debug!(
+                            "Reconnected to {} but boot id unavailable: {}",
+                            self.host, e
+                        ),
+                    }
+                    self.disconnect();
+                }
+                Err(e) => debug!("{} not reachable yet: {}", self.host, e),
+            }
+
+            if tokio::time::Instant::now() >= deadline {
+                return Err(crate::error::AutoInstallError::TimeoutError(format!(
+                    "{} did not come back within {}s after reboot",
+                    self.host,
+                    options.timeout.as_secs()
+                )));
+            }
+        }
+    }
+
+    /// Run `command` on the active transport, returning exit status, stdout and stderr
+    async fn run_remote(&mut self, command: &str) -> Result<(i32, String, String)> {
+        if let Some(transport) = self.transport.as_mut() {
+            let output = transport.run(command)?;
+            return Ok((output.exit_code, output.stdout, output.stderr));
+        }
+
+        let elevated = self.record_elevation(command);
+        // Commands with a progress parser are streamed, so they report while running and can
+        // be interrupted
+        if self.progress.parser_for(command).is_some() {
+            let host = self.host.clone();
+            let mut log_line = |line: &OutputLine| debug!("{}: {}", host, line.line);
+            return self
+                .stream_session(command, elevated, &StreamOptions::default(), &mut log_line)
+                .await;
+        }
+        self.run_session(command, elevated)
+    }
+
+    /// Whether the sudo policy elevates `command`, noted in the elevation log
+    fn record_elevation(&mut self, command: &str) -> bool {
+        match &self.sudo {
+            Some(policy) => {
+                let elevated = policy.needs_elevation(command);
+                self.elevation_log.push(ElevationRecord {
+                    at: chrono::Utc::now(),
+                    command: redact::scrub(command).into_owned(),
+                    elevated,
+                });
+                elevated
+            }
+            None => false,
+        }
+    }
+
+    /// Open a channel running `exec`, through sudo when `elevated`
+    fn exec_channel(&mut self, exec: &str, elevated: bool) -> Result<ssh2::Channel> {
         let session = self.session.as_mut().ok_or_else(|| {
             crate::error::AutoInstallError::SshError("No active SSH session".to_string())
         })?;
@@ -77,17 +469,37 @@ impl SshClient {
             crate::error::AutoInstallError::SshError(format!("Failed to create SSH channel: {}", e))
         })?;
 
-        channel.exec(command).map_err(|e| {
+        let policy = self.sudo.as_ref().filter(|_| elevated);
+        let exec = match policy {
+            Some(policy) => policy.wrap(exec),
+            None => exec.to_string(),
+        };
+        channel.exec(&exec).map_err(|e| {
             crate::error::AutoInstallError::SshError(format!("Failed to execute command: {}", e))
         })?;
+        if let Some(password) = policy.and_then(|p| p.stdin()) {
+            channel.write_all(password.as_bytes()).map_err(|e| {
+                crate::error::AutoInstallError::SshError(format!(
+                    "Failed to pass the sudo password: {}",
+                    e
+                ))
+            })?;
+        }
+        Ok(channel)
+    }
 
-        let mut stdout = String::new();
-        let mut stderr = String::new();
+    /// Run `command` on the SSH session, through sudo when `elevated`
+    fn run_session(&mut self, command: &str, elevated: bool) -> Result<(i32, String, String)> {
+        let mut channel = self.exec_channel(command, elevated)?;
 
-        // Read stdout and stderr
-        channel.read_to_string(&mut stdout).map_err(|e| {
+        let read_error = |e: String| {
             crate::error::AutoInstallError::SshError(format!("Failed to read stdout: {}", e))
-        })?;
+        };
+        let mut stdout = String::new();
+        let mut stderr = String::new();
+        channel
+            .read_to_string(&mut stdout)
+            .map_err(|e| read_error(e.to_string()))?;
         channel.stderr().read_to_string(&mut stderr).map_err(|e| {
             crate::error::AutoInstallError::SshError(format!("Failed to read stderr: {}", e))
         })?;
@@ -100,58 +512,222 @@ impl SshClient {
             crate::error::AutoInstallError::SshError(format!("Failed to get exit status: {}", e))
         })?;
 
-        if exit_status != 0 {
-            error!("Command failed with exit code {}", exit_status);
-            if !stdout.trim().is_empty() {
-                error!("STDOUT: {}", stdout);
+        Ok((exit_status, stdout, stderr))
+    }
+
+    /// Run `command` on the SSH session, handing output lines to `on_line` as they arrive;
+    /// returns exit status, stdout and stderr
+    ///
+    /// The command records its process group in a marker file, so that it can be killed on the
+    /// target when it goes silent for too long or the run is cancelled.
+    async fn stream_session(
+        &mut self,
+        command: &str,
+        elevated: bool,
+        options: &StreamOptions,
+        on_line: LineCallback<'_>,
+    ) -> Result<(i32, String, String)> {
+        let mut tracker = self.progress.track(&self.host, command);
+        let marker = format!("/tmp/uaa-stream-{}.pgid", uuid::Uuid::new_v4().simple());
+        let exec = format!(
+            "ps -o pgid= -p $$ > {marker}; trap 'rm -f {marker}' EXIT\n{command}",
+            marker = marker,
+            command = command
+        );
+        let mut channel = self.exec_channel(&exec, elevated)?;
+        let session = self.session.clone().ok_or_else(|| {
+            crate::error::AutoInstallError::SshError("No active SSH session".to_string())
+        })?;
+
+        // Non-blocking reads let stdout and stderr interleave and the watchdogs fire
+        session.set_blocking(false);
+        let pumped = Self::pump(
+            &mut channel,
+            tracker.as_mut(),
+            command,
+            options,
+            self.cancel.as_ref(),
+            on_line,
+        )
+        .await;
+        session.set_blocking(true);
+        let (stdout, stderr) = match pumped {
+            Ok(output) => output,
+            Err(e) => {
+                let _ = channel.close();
+                let kill = format!(
+                    "kill -TERM -- -$(tr -d ' ' < {marker}) 2>/dev/null; rm -f {marker}",
+                    marker = marker
+                );
+                if let Err(kill_error) = self.run_session(&kill, elevated) {
+                    warn!(
+                        "Could not stop the abandoned command on {}: {}",
+                        self.host, kill_error
+                    );
+                }
+                return Err(e);
             }
-            if !stderr.trim().is_empty() {
-                error!("STDERR: {}", stderr);
+        };
+
+        channel.wait_close().map_err(|e| {
+            crate::error::AutoInstallError::SshError(format!("Failed to close SSH channel: {}", e))
+        })?;
+        let exit_status = channel.exit_status().map_err(|e| {
+            crate::error::AutoInstallError::SshError(format!("Failed to get exit status: {}", e))
+        })?;
+        Ok((exit_status, stdout, stderr))
+    }
+
+    /// Read a non-blocking channel until EOF, splitting both streams into lines; returns the
+    /// raw stdout and stderr
+    ///
+    /// Fails when `options.silence_timeout` passes without output or `cancel` fires.
+    async fn pump(
+        channel: &mut ssh2::Channel,
+        mut tracker: Option<&mut ProgressTracker>,
+        command: &str,
+        options: &StreamOptions,
+        cancel: Option<&CancellationToken>,
+        on_line: LineCallback<'_>,
+    ) -> Result<(String, String)> {
+        let mut splitters = [
+            LineSplitter::new(StreamKind::Stdout),
+            LineSplitter::new(StreamKind::Stderr),
+        ];
+        let mut output = [Vec::new(), Vec::new()];
+        let mut buf = [0u8; 8192];
+        let mut last_output = std::time::Instant::now();
+        loop {
+            let mut read_any = false;
+            for (index, splitter) in splitters.iter_mut().enumerate() {
+                let read = if index == 0 {
+                    channel.read(&mut buf)
+                } else {
+                    channel.stderr().read(&mut buf)
+                };
+                let n = match read {
+                    Ok(n) => n,
+                    Err(e) if e.kind() == std::io::ErrorKind::WouldBlock => 0,
+                    Err(e) => {
+                        return Err(crate::error::AutoInstallError::SshError(format!(
+                            "Failed to read command output: {}",
+                            e
+                        )))
+                    }
+                };
+                if n == 0 {
+                    continue;
+                }
+                read_any = true;
+                let chunk = &buf[..n];
+                if let Some(tracker) = tracker.as_mut() {
+                    if index == 0 || tracker.reads_stderr() {
+                        tracker.feed(chunk);
+                    }
+                }
+                output[index].extend_from_slice(chunk);
+                for line in splitter.push(chunk) {
+                    on_line(&line);
+                }
+            }
+            if read_any {
+                last_output = std::time::Instant::now();
+                continue;
             }
+            if channel.eof() {
+                break;
+            }
+            if let Some(limit) = options.silence_timeout {
+                if last_output.elapsed() >= limit {
+                    return Err(crate::error::AutoInstallError::TimeoutError(format!(
+                        "No output for {}s from: {}",
+                        limit.as_secs(),
+                        redact::scrub(command)
+                    )));
+                }
+            }
+            match cancel {
+                Some(token) => {
+                    tokio::select! {
+                        _ = tokio::time::sleep(STREAM_POLL) => {}
+                        _ = token.cancelled() => {
+                            return Err(crate::error::AutoInstallError::CancelledError(format!(
+                                "interrupted remote command: {}",
+                                redact::scrub(command)
+                            )));
+                        }
+                    }
+                }
+                None => tokio::time::sleep(STREAM_POLL).await,
+            }
+        }
+        for splitter in &mut splitters {
+            if let Some(line) = splitter.finish() {
+                on_line(&line);
+            }
+        }
+        if let Some(tracker) = tracker.as_mut() {
+            tracker.finish();
+        }
+        let [stdout, stderr] = output;
+        Ok((
+            String::from_utf8_lossy(&stdout).into_owned(),
+            String::from_utf8_lossy(&stderr).into_owned(),
+        ))
+    }
+
+    /// Execute `command`, handing each stdout and stderr line to `on_line` as it appears
+    ///
+    /// Fails like [`Self::execute_with_output`] on a non-zero exit, and with a timeout error
+    /// once `options.silence_timeout` passes without any output. Over a non-SSH transport the
+    /// command runs to completion and its lines are handed over afterwards.
+    pub async fn execute_streaming(
+        &mut self,
+        command: &str,
+        options: &StreamOptions,
+        on_line: LineCallback<'_>,
+    ) -> Result<()> {
+        debug!("Executing streamed command: {}", command);
+        if let Some(code) = self.begin_command(command)? {
+            return Err(Self::injected_failure(command, code));
+        }
+
+        let (exit_status, stderr) = if let Some(transport) = self.transport.as_mut() {
+            let output = transport.run(command)?;
+            crate::network::stream::replay(&output.stdout, &output.stderr, on_line);
+            (output.exit_code, output.stderr)
+        } else {
+            let elevated = self.record_elevation(command);
+            let (exit_status, _, stderr) = self
+                .stream_session(command, elevated, options, on_line)
+                .await?;
+            (exit_status, stderr)
+        };
+
+        if exit_status != 0 {
+            error!("Command failed with exit code {}", exit_status);
             return Err(crate::error::AutoInstallError::ProcessError {
                 command: command.to_string(),
                 exit_code: Some(exit_status),
-                stderr: if stderr.is_empty() { stdout } else { stderr },
+                stderr,
             });
         }
-
-        debug!("Command executed successfully");
         Ok(())
     }
 
+    /// Execute command on remote host
+    pub async fn execute(&mut self, command: &str) -> Result<()> {
+        self.execute_with_output(command).await.map(|_| ())
+    }
+
     /// Execute command and return output
     pub async fn execute_with_output(&mut self, command: &str) -> Result<String> {
         debug!("Executing command with output: {}", command);
+        if let Some(code) = self.begin_command(command)? {
+            return Err(Self::injected_failure(command, code));
+        }
 
-        let session = self.session.as_mut().ok_or_else(|| {
-            crate::error::AutoInstallError::SshError("No active SSH session".to_string())
-        })?;
-
-        let mut channel = session.channel_session().map_err(|e| {
-            crate::error::AutoInstallError::SshError(format!("Failed to create SSH channel: {}", e))
-        })?;
-
-        channel.exec(command).map_err(|e| {
-            crate::error::AutoInstallError::SshError(format!("Failed to execute command: {}", e))
-        })?;
-
-        let mut stdout = String::new();
-        let mut stderr = String::new();
-
-        channel.read_to_string(&mut stdout).map_err(|e| {
-            crate::error::AutoInstallError::SshError(format!("Failed to read stdout: {}", e))
-        })?;
-        channel.stderr().read_to_string(&mut stderr).map_err(|e| {
-            crate::error::AutoInstallError::SshError(format!("Failed to read stderr: {}", e))
-        })?;
-
-        channel.wait_close().map_err(|e| {
-            crate::error::AutoInstallError::SshError(format!("Failed to close SSH channel: {}", e))
-        })?;
-
-        let exit_status = channel.exit_status().map_err(|e| {
-            crate::error::AutoInstallError::SshError(format!("Failed to get exit status: {}", e))
-        })?;
+        let (exit_status, stdout, stderr) = self.run_remote(command).await?;
 
         if exit_status != 0 {
             error!("Command failed with exit code {}", exit_status);
@@ -179,39 +755,11 @@ impl SshClient {
         description: &str,
     ) -> Result<(i32, String, String)> {
         info!("Executing: {} -> {}", description, command);
+        if let Some(code) = self.begin_command(command)? {
+            return Ok((code, String::new(), "injected by chaos mode".to_string()));
+        }
 
-        let session = self.session.as_mut().ok_or_else(|| {
-            crate::error::AutoInstallError::SshError("No active SSH session".to_string())
-        })?;
-
-        let mut channel = session.channel_session().map_err(|e| {
-            crate::error::AutoInstallError::SshError(format!("Failed to create SSH channel: {}", e))
-        })?;
-
-        channel.exec(command).map_err(|e| {
-            crate::error::AutoInstallError::SshError(format!("Failed to execute command: {}", e))
-        })?;
-
-        let mut stdout = String::new();
-        let mut stderr = String::new();
-
-        // Read stdout
-        channel.read_to_string(&mut stdout).map_err(|e| {
-            crate::error::AutoInstallError::SshError(format!("Failed to read stdout: {}", e))
-        })?;
-
-        // Read stderr
-        channel.stderr().read_to_string(&mut stderr).map_err(|e| {
-            crate::error::AutoInstallError::SshError(format!("Failed to read stderr: {}", e))
-        })?;
-
-        channel.wait_close().map_err(|e| {
-            crate::error::AutoInstallError::SshError(format!("Failed to close SSH channel: {}", e))
-        })?;
-
-        let exit_status = channel.exit_status().map_err(|e| {
-            crate::error::AutoInstallError::SshError(format!("Failed to get exit status: {}", e))
-        })?;
+        let (exit_status, stdout, stderr) = self.run_remote(command).await?;
 
         if exit_status != 0 {
             error!(
@@ -231,27 +779,10 @@ impl SshClient {
     /// Execute a command intended as a boolean check without emitting error logs.
     /// Returns Ok(true) if the command exits with 0, Ok(false) if non-zero, Err on transport issues.
     pub async fn check_silent(&mut self, command: &str) -> Result<bool> {
-        let session = self.session.as_mut().ok_or_else(|| {
-            crate::error::AutoInstallError::SshError("No active SSH session".to_string())
-        })?;
-
-        let mut channel = session.channel_session().map_err(|e| {
-            crate::error::AutoInstallError::SshError(format!("Failed to create SSH channel: {}", e))
-        })?;
-
-        channel.exec(command).map_err(|e| {
-            crate::error::AutoInstallError::SshError(format!("Failed to execute command: {}", e))
-        })?;
-
-        // We don't care about output here; just wait for status
-        channel.wait_close().map_err(|e| {
-            crate::error::AutoInstallError::SshError(format!("Failed to close SSH channel: {}", e))
-        })?;
-
-        let exit_status = channel.exit_status().map_err(|e| {
-            crate::error::AutoInstallError::SshError(format!("Failed to get exit status: {}", e))
-        })?;
-
+        if self.begin_command(command)?.is_some() {
+            return Ok(false);
+        }
+        let (exit_status, _, _) = self.run_remote(command).await?;
         Ok(exit_status == 0)
     }
 
@@ -301,6 +832,41 @@ impl SshClient {
     pub async fn upload_file(&mut self, local_path: &str, remote_path: &str) -> Result<()> {
         info!("Uploading {} to {}:{}", local_path, self.host, remote_path);
 
+        if let Some(transport) = self.transport.as_mut() {
+            let contents =
+                std::fs::read(local_path).map_err(crate::error::AutoInstallError::IoError)?;
+            transport.write_file(remote_path, &contents)?;
+            info!("File upload completed");
+            return Ok(());
+        }
+
+        // scp writes as the SSH user; stage the file and move it into place with sudo
+        if self.sudo.is_some() {
+            let staging = Self::staging_path();
+            self.scp_upload(local_path, &staging)?;
+            self.execute(&format!(
+                "install -m 0644 {0} {1}; status=$?; rm -f {0}; exit $status",
+                staging, remote_path
+            ))
+            .await?;
+        } else {
+            self.scp_upload(local_path, remote_path)?;
+        }
+
+        info!("File upload completed");
+        Ok(())
+    }
+
+    /// Temporary file on the target for transfers under a sudo policy
+    fn staging_path() -> String {
+        format!(
+            "/tmp/.uaa-transfer-{}",
+            &uuid::Uuid::new_v4().simple().to_string()[..8]
+        )
+    }
+
+    /// Copy `local_path` to `remote_path` with scp
+    fn scp_upload(&mut self, local_path: &str, remote_path: &str) -> Result<()> {
         let session = self.session.as_mut().ok_or_else(|| {
             crate::error::AutoInstallError::SshError("No active SSH session".to_string())
         })?;
@@ -345,7 +911,6 @@ impl SshClient {
             crate::error::AutoInstallError::SshError(format!("Failed to wait for close: {}", e))
         })?;
 
-        info!("File upload completed");
         Ok(())
     }
 
@@ -356,6 +921,35 @@ impl SshClient {
             self.host, remote_path, local_path
         );
 
+        if let Some(transport) = self.transport.as_mut() {
+            let contents = transport.read_file(remote_path)?;
+            std::fs::write(local_path, contents)
+                .map_err(crate::error::AutoInstallError::IoError)?;
+            info!("File download completed");
+            return Ok(());
+        }
+
+        // Root-owned files are copied to a staging file the SSH user can read first
+        if self.sudo.is_some() {
+            let staging = Self::staging_path();
+            self.execute(&format!(
+                "install -m 0600 -o {} {} {}",
+                self.username, remote_path, staging
+            ))
+            .await?;
+            let result = self.scp_download(&staging, local_path);
+            let _ = self.execute(&format!("rm -f {}", staging)).await;
+            result?;
+        } else {
+            self.scp_download(remote_path, local_path)?;
+        }
+
+        info!("File download completed");
+        Ok(())
+    }
+
+    /// Copy `remote_path` to `local_path` with scp
+    fn scp_download(&mut self, remote_path: &str, local_path: &str) -> Result<()> {
         let session = self.session.as_mut().ok_or_else(|| {
             crate::error::AutoInstallError::SshError("No active SSH session".to_string())
         })?;
@@ -399,12 +993,15 @@ impl SshClient {
             crate::error::AutoInstallError::SshError(format!("Failed to wait for close: {}", e))
         })?;
 
-        info!("File download completed");
         Ok(())
     }
 
     /// Disconnect SSH session
     pub fn disconnect(&mut self) {
+        if let Some(mut transport) = self.transport.take() {
+            transport.close();
+            info!("Closed {}", transport.describe());
+        }
         if let Some(session) = self.session.take() {
             let _ = session.disconnect(None, "", None);
             info!("SSH session disconnected");
@@ -425,3 +1022,140 @@ impl Default for SshClient {
 }
 
 use std::io::{Read, Write};
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn test_execute_refused_after_cancellation() {
+        let token = CancellationToken::new();
+        let mut client = SshClient::new();
+        client.set_cancellation_token(token.clone());
+        token.cancel();
+
+        let err = client.execute("echo hi").await.unwrap_err();
+        assert!(matches!(
+            err,
+            crate::error::AutoInstallError::CancelledError(_)
+        ));
+        assert!(client.last_command().is_none());
+    }
+
+    #[tokio::test]
+    async fn test_privilege_audit_keeps_secrets_out() {
+        let secret = "elevation-audit-passphrase";
+        redact::register(secret);
+        let mut client = SshClient::new();
+        client.set_sudo_policy(SudoPolicy::nopasswd());
+
+        // No session: the command fails, but only after it was noted in the elevation log
+        let command = format!("echo '{}' | cryptsetup luksOpen /dev/sda4 luks", secret);
+        assert!(client.execute(&command).await.is_err());
+        assert_eq!(client.elevation_log().len(), 1);
+        assert!(!client.elevation_log()[0].command.contains(secret));
+
+        // Records built elsewhere are scrubbed when written
+        let mut records = client.elevation_log().to_vec();
+        records.push(ElevationRecord {
+            at: chrono::Utc::now(),
+            command: format!("echo 'root:{}' | chpasswd", secret),
+            elevated: true,
+        });
+        let dir = tempfile::TempDir::new().unwrap();
+        let audit = crate::network::sudo::PrivilegeAudit::new("10.0.0.5", "deploy", records);
+        let written = std::fs::read_to_string(audit.write(dir.path()).unwrap()).unwrap();
+        assert!(!written.contains(secret), "{}", written);
+        assert_eq!(written.matches(redact::REDACTED).count(), 2);
+    }
+
+    #[tokio::test]
+    async fn test_chaos_injects_faults_without_session() {
+        let mut client = SshClient::new();
+        client.set_chaos(
+            ChaosMonkey::from_specs(&[
+                "exit:3@zpool".to_string(),
+                "timeout@debootstrap".to_string(),
+                "exit:1@test -d".to_string(),
+            ])
+            .unwrap(),
+        );
+
+        match client.execute("zpool create rpool").await.unwrap_err() {
+            crate::error::AutoInstallError::ProcessError { exit_code, .. } => {
+                assert_eq!(exit_code, Some(3))
+            }
+            other => panic!("unexpected error: {:?}", other),
+        }
+        assert!(matches!(
+            client.execute_with_output("debootstrap noble /mnt").await,
+            Err(crate::error::AutoInstallError::TimeoutError(_))
+        ));
+        assert!(!client.check_silent("test -d /mnt").await.unwrap());
+        assert_eq!(client.last_command(), Some("test -d /mnt"));
+    }
+
+    struct ScriptedTransport;
+
+    impl Transport for ScriptedTransport {
+        fn describe(&self) -> String {
+            "scripted".to_string()
+        }
+
+        fn run(&mut self, command: &str) -> Result<crate::network::transport::CommandOutput> {
+            Ok(crate::network::transport::CommandOutput {
+                exit_code: if command.contains("fail") { 2 } else { 0 },
+                stdout: "unpacking\r50%\r100%\n".to_string(),
+                stderr: "warning: slow mirror\n".to_string(),
+            })
+        }
+    }
+
+    #[tokio::test]
+    async fn test_execute_streaming_hands_over_lines() {
+        let mut client = SshClient::new();
+        client.connect_transport("console", Box::new(ScriptedTransport));
+        let mut lines = Vec::new();
+        client
+            .execute_streaming(
+                "debootstrap noble /mnt",
+                &StreamOptions::default(),
+                &mut |l| lines.push(l.clone()),
+            )
+            .await
+            .unwrap();
+        let text: Vec<&str> = lines.iter().map(|l| l.line.as_str()).collect();
+        assert_eq!(text, ["unpacking", "50%", "100%", "warning: slow mirror"]);
+        assert_eq!(lines[3].stream, StreamKind::Stderr);
+
+        let err = client
+            .execute_streaming("fail", &StreamOptions::default(), &mut |_| {})
+            .await
+            .unwrap_err();
+        assert!(matches!(
+            err,
+            crate::error::AutoInstallError::ProcessError {
+                exit_code: Some(2),
+                ..
+            }
+        ));
+    }
+
+    #[test]
+    fn test_verify_host_key_policy() {
+        assert!(!verify_host_key("h", Some("aa"), Some("aa"), HostKeyPolicy::Strict).unwrap());
+        assert!(!verify_host_key("h", None, Some("bb"), HostKeyPolicy::Strict).unwrap());
+        assert!(verify_host_key("h", Some("aa"), Some("bb"), HostKeyPolicy::AcceptNew).unwrap());
+        assert!(verify_host_key("h", Some("aa"), Some("bb"), HostKeyPolicy::Strict).is_err());
+    }
+
+    #[tokio::test]
+    async fn test_wait_for_reboot_requires_prior_connection() {
+        let mut client = SshClient::new();
+        let err = client
+            .wait_for_reboot("old-boot-id", &RebootWait::default())
+            .await
+            .unwrap_err();
+        assert!(matches!(err, crate::error::AutoInstallError::SshError(_)));
+    }
+}
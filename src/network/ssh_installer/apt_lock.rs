@@ -0,0 +1,201 @@
+// file: src/network/ssh_installer/apt_lock.rs
+// version: 1.0.0
+// guid: 6e1c9a48-3d7b-4b52-8f0e-d4a2c7b91e36
+
+//! Waiting for apt/dpkg locks before package commands
+//!
+//! Holders are found by scanning `/proc/*/fd` for the lock files, which works in minimal live
+//! environments without `fuser` or `lsof`. Paths are resolved from the live system, so a process
+//! running inside the target chroot shows up under the target root.
+
+use crate::config::AptLockConfig;
+use crate::error::AutoInstallError;
+use crate::network::SshClient;
+use crate::Result;
+use std::time::{Duration, Instant};
+use tracing::{info, warn};
+
+/// Locks taken by apt and dpkg, relative to the system root
+pub const LOCK_FILES: [&str; 4] = [
+    "/var/lib/dpkg/lock-frontend",
+    "/var/lib/dpkg/lock",
+    "/var/lib/apt/lists/lock",
+    "/var/cache/apt/archives/lock",
+];
+
+/// apt.conf snippet making apt itself wait for the frontend lock as well
+const LOCK_TIMEOUT_CONF: &str = "/etc/apt/apt.conf.d/90uaa-lock-timeout";
+
+/// A process holding one of the locks
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct AptLockHolder {
+    pub pid: u32,
+    pub lock: String,
+    pub command: String,
+}
+
+/// List `pid lock command` for every open lock file under `root` ("" for the live system)
+pub fn build_holders_command(root: &str) -> String {
+    let locks: Vec<String> = LOCK_FILES
+        .iter()
+        .map(|lock| format!("{}{}", root, lock))
+        .collect();
+    format!(
+        "for fd in /proc/[0-9]*/fd/*; do lock=$(readlink \"$fd\" 2>/dev/null); case \"$lock\" in {}) \
+         pid=${{fd#/proc/}}; pid=${{pid%%/*}}; echo \"$pid $lock $(cat /proc/$pid/comm 2>/dev/null)\";; \
+         esac; done | sort -u; true",
+        locks.join("|")
+    )
+}
+
+/// Parse the output of [`build_holders_command`]
+pub fn parse_holders(output: &str) -> Vec<AptLockHolder> {
+    output
+        .lines()
+        .filter_map(|line| {
+            let mut fields = line.split_whitespace();
+            let pid = fields.next()?.parse().ok()?;
+            let lock = fields.next()?.to_string();
+            Some(AptLockHolder {
+                pid,
+                lock,
+                command: fields.collect::<Vec<_>>().join(" "),
+            })
+        })
+        .collect()
+}
+
+/// `unattended-upgr (pid 812) holds /var/lib/dpkg/lock-frontend, ...`
+fn describe(holders: &[AptLockHolder]) -> String {
+    holders
+        .iter()
+        .map(|h| format!("{} (pid {}) holds {}", h.command, h.pid, h.lock))
+        .collect::<Vec<_>>()
+        .join(", ")
+}
+
+/// Commands stopping the holders and repairing dpkg after an interrupted run
+pub fn build_release_commands(root: &str, holders: &[AptLockHolder]) -> Vec<String> {
+    let mut pids: Vec<String> = holders.iter().map(|h| h.pid.to_string()).collect();
+    pids.dedup();
+    let pids = pids.join(" ");
+    let mut commands = Vec::new();
+    if root.is_empty() {
+        commands.push(
+            "systemctl stop unattended-upgrades.service apt-daily.service apt-daily-upgrade.service 2>/dev/null || true"
+                .to_string(),
+        );
+    }
+    commands.push(format!("kill -TERM {} 2>/dev/null || true", pids));
+    commands.push(format!("sleep 5; kill -KILL {} 2>/dev/null || true", pids));
+    let configure = "DEBIAN_FRONTEND=noninteractive dpkg --configure -a";
+    commands.push(if root.is_empty() {
+        configure.to_string()
+    } else {
+        format!("chroot {} bash -lc '{}'", root, configure)
+    });
+    commands
+}
+
+/// Commands keeping apt's own jobs from starting mid-install in the live environment
+pub fn build_live_preparation_commands(config: &AptLockConfig) -> Vec<String> {
+    vec![
+        "systemctl stop apt-daily.timer apt-daily-upgrade.timer 2>/dev/null || true".to_string(),
+        format!(
+            "mkdir -p /etc/apt/apt.conf.d && echo 'DPkg::Lock::Timeout \"{}\";' > {}",
+            config.wait_secs, LOCK_TIMEOUT_CONF
+        ),
+    ]
+}
+
+/// Wait until no process holds an apt/dpkg lock under `root` ("" for the live system)
+///
+/// Fails when the locks are still held after `wait_secs`, unless `kill_holder` is set, in which
+/// case the holders are stopped and dpkg is repaired.
+pub async fn wait_for_apt_locks(
+    ssh: &mut SshClient,
+    config: &AptLockConfig,
+    root: &str,
+) -> Result<()> {
+    let started = Instant::now();
+    let command = build_holders_command(root);
+    loop {
+        let holders = parse_holders(&ssh.execute_with_output(&command).await?);
+        if holders.is_empty() {
+            return Ok(());
+        }
+        if started.elapsed() >= Duration::from_secs(config.wait_secs) {
+            if !config.kill_holder {
+                return Err(AutoInstallError::SystemError(format!(
+                    "apt/dpkg locks still held after {}s: {}; set apt_lock.kill_holder to stop the holders",
+                    config.wait_secs,
+                    describe(&holders)
+                )));
+            }
+            warn!("Stopping apt/dpkg lock holders: {}", describe(&holders));
+            for release in build_release_commands(root, &holders) {
+                ssh.execute(&release).await?;
+            }
+            let remaining = parse_holders(&ssh.execute_with_output(&command).await?);
+            if remaining.is_empty() {
+                return Ok(());
+            }
+            return Err(AutoInstallError::SystemError(format!(
+                "apt/dpkg locks still held after stopping their holders: {}",
+                describe(&remaining)
+            )));
+        }
+        info!(
+            "Waiting for apt/dpkg locks ({}s elapsed): {}",
+            started.elapsed().as_secs(),
+            describe(&holders)
+        );
+        tokio::time::sleep(Duration::from_secs(config.poll_secs)).await;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_holders() {
+        let holders = parse_holders(
+            "812 /var/lib/dpkg/lock-frontend unattended-upgr\n\
+             812 /var/lib/dpkg/lock unattended-upgr\n\
+             garbage\n",
+        );
+        assert_eq!(holders.len(), 2);
+        assert_eq!(holders[0].pid, 812);
+        assert_eq!(holders[0].command, "unattended-upgr");
+        assert_eq!(
+            describe(&holders[..1]),
+            "unattended-upgr (pid 812) holds /var/lib/dpkg/lock-frontend"
+        );
+        assert!(parse_holders("").is_empty());
+    }
+
+    #[test]
+    fn test_commands_follow_the_root() {
+        let live = build_holders_command("");
+        assert!(live.contains("/var/lib/dpkg/lock-frontend|/var/lib/dpkg/lock|"));
+        let target = build_holders_command("/mnt/targetos");
+        assert!(target.contains("/mnt/targetos/var/cache/apt/archives/lock)"));
+
+        let holders =
+            parse_holders("812 /var/lib/dpkg/lock apt-get\n812 /var/lib/apt/lists/lock apt-get\n");
+        let live = build_release_commands("", &holders);
+        assert!(live[0].starts_with("systemctl stop unattended-upgrades.service"));
+        assert_eq!(live[1], "kill -TERM 812 2>/dev/null || true");
+        assert_eq!(
+            live[3],
+            "DEBIAN_FRONTEND=noninteractive dpkg --configure -a"
+        );
+        let target = build_release_commands("/mnt/targetos", &holders);
+        assert_eq!(target.len(), 3);
+        assert_eq!(
+            target[2],
+            "chroot /mnt/targetos bash -lc 'DEBIAN_FRONTEND=noninteractive dpkg --configure -a'"
+        );
+    }
+}
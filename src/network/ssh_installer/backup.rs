@@ -0,0 +1,661 @@
+// file: src/network/ssh_installer/backup.rs
+// version: 1.4.0
+// guid: 5c8e2b71-3f94-4a6d-b0e7-19d4c6a82f35
+
+//! ZFS send/receive backups of installed systems
+//!
+//! Backups take a recursive snapshot of each pool and stream it with `zfs send -R`, either
+//! into files in a directory or into `zfs recv` on a backup host over SSH. Every backup is
+//! recorded in `logs/<hostname>/backups.json` so later runs send incrementals from the last
+//! snapshot and restores know which streams to replay, in which order.
+
+use super::config::InstallationConfig;
+use super::disk_ops::DiskManager;
+use super::session::InstallSession;
+use super::system_setup::SystemConfigurator;
+use super::zfs_ops::ZfsManager;
+use crate::config::zfs_pools::PoolLayout;
+use crate::config::ThrottleConfig;
+use crate::network::SshClient;
+use crate::utils::shell_quote;
+use crate::Result;
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use std::path::{Path, PathBuf};
+use tracing::{info, warn};
+
+/// Pools of an install, in the order they are backed up and restored
+pub fn layout_pools(layout: &PoolLayout) -> Vec<String> {
+    vec![layout.boot.name.clone(), layout.root.name.clone()]
+}
+
+/// Check that `chain` holds a stream of every pool in `pools`
+pub fn check_chain_pools(chain: &[&BackupRecord], pools: &[String]) -> Result<()> {
+    match pools
+        .iter()
+        .find(|pool| !chain.iter().any(|record| record.pools.contains(pool)))
+    {
+        Some(pool) => Err(crate::error::AutoInstallError::ValidationError(format!(
+            "The backups hold no stream of pool {}; the host's zfs_pools must name the pools \
+             the backups were taken from",
+            pool
+        ))),
+        None => Ok(()),
+    }
+}
+
+/// Where backup streams are written to or read from
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum BackupTarget {
+    /// Directory (local disk, NFS, ...) on the host being backed up or restored
+    Directory(String),
+    /// `zfs recv` into `dataset/<pool>` on a backup host reached with `ssh destination`
+    Remote {
+        destination: String,
+        dataset: String,
+    },
+}
+
+impl BackupTarget {
+    /// Parse `ssh://[user@]host/pool/dataset` or a directory path
+    pub fn parse(spec: &str) -> Result<Self> {
+        if let Some(rest) = spec.strip_prefix("ssh://") {
+            let (destination, dataset) = rest.split_once('/').ok_or_else(|| {
+                crate::error::AutoInstallError::ConfigError(format!(
+                    "Remote backup target '{}' must be ssh://[user@]host/pool/dataset",
+                    spec
+                ))
+            })?;
+            if destination.is_empty() || dataset.trim_matches('/').is_empty() {
+                return Err(crate::error::AutoInstallError::ConfigError(format!(
+                    "Remote backup target '{}' must be ssh://[user@]host/pool/dataset",
+                    spec
+                )));
+            }
+            // ssh would take a leading '-' as an option
+            if destination.starts_with('-') || destination.contains(char::is_whitespace) {
+                return Err(crate::error::AutoInstallError::ConfigError(format!(
+                    "Remote backup target '{}' has an invalid host '{}'",
+                    spec, destination
+                )));
+            }
+            return Ok(Self::Remote {
+                destination: destination.to_string(),
+                dataset: dataset.trim_matches('/').to_string(),
+            });
+        }
+        if spec.trim().is_empty() {
+            return Err(crate::error::AutoInstallError::ConfigError(
+                "Backup target must not be empty".to_string(),
+            ));
+        }
+        Ok(Self::Directory(spec.trim_end_matches('/').to_string()))
+    }
+
+    /// Canonical string form, used to match catalog entries to a target
+    pub fn spec(&self) -> String {
+        match self {
+            Self::Directory(dir) => dir.clone(),
+            Self::Remote {
+                destination,
+                dataset,
+            } => format!("ssh://{}/{}", destination, dataset),
+        }
+    }
+
+    /// Stream file for `pool@snapshot` (directory targets only), quoted for the shell
+    fn stream_path(dir: &str, pool: &str, snapshot: &str) -> String {
+        shell_quote(&format!("{}/{}@{}.zfs", dir, pool, snapshot))
+    }
+}
+
+/// One completed backup
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct BackupRecord {
+    /// Snapshot name (without the pool prefix)
+    pub snapshot: String,
+    /// Snapshot the incremental stream was based on; `None` for a full stream
+    pub base: Option<String>,
+    /// Target spec the streams were written to
+    pub target: String,
+    /// Pools included in the backup
+    pub pools: Vec<String>,
+    /// When the snapshot was taken
+    pub created_at: DateTime<Utc>,
+}
+
+/// Snapshot chain bookkeeping for one host
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct BackupCatalog {
+    /// Backups in the order they were taken
+    pub backups: Vec<BackupRecord>,
+}
+
+impl BackupCatalog {
+    /// Path of the catalog for `hostname`
+    pub fn record_path(base_dir: &Path, hostname: &str) -> PathBuf {
+        InstallSession::host_dir(base_dir, hostname).join("backups.json")
+    }
+
+    /// Load the catalog for `hostname`, or an empty one if none exists yet
+    pub fn load(base_dir: &Path, hostname: &str) -> Result<Self> {
+        let path = Self::record_path(base_dir, hostname);
+        if !path.exists() {
+            return Ok(Self::default());
+        }
+        Ok(serde_json::from_str(&std::fs::read_to_string(path)?)?)
+    }
+
+    /// Write the catalog to `logs/<hostname>/backups.json` under `base_dir`
+    pub fn save(&self, base_dir: &Path, hostname: &str) -> Result<PathBuf> {
+        let path = Self::record_path(base_dir, hostname);
+        if let Some(parent) = path.parent() {
+            std::fs::create_dir_all(parent)?;
+        }
+        std::fs::write(&path, serde_json::to_string_pretty(self)?)?;
+        Ok(path)
+    }
+
+    /// Latest snapshot sent to `target`, the base for the next incremental
+    pub fn latest_for(&self, target: &BackupTarget) -> Option<&BackupRecord> {
+        let spec = target.spec();
+        self.backups.iter().rev().find(|b| b.target == spec)
+    }
+
+    /// Records needed to restore `snapshot` (or the latest) from `target`, oldest first
+    ///
+    /// For directory targets this is the last full stream followed by every incremental up
+    /// to the requested snapshot. Remote targets hold the whole chain in the received
+    /// datasets, so only the requested record is returned.
+    pub fn restore_chain(
+        &self,
+        target: &BackupTarget,
+        snapshot: Option<&str>,
+    ) -> Result<Vec<&BackupRecord>> {
+        let spec = target.spec();
+        let records: Vec<&BackupRecord> =
+            self.backups.iter().filter(|b| b.target == spec).collect();
+        let end = match snapshot {
+            Some(name) => records
+                .iter()
+                .position(|b| b.snapshot == name)
+                .ok_or_else(|| {
+                    crate::error::AutoInstallError::ConfigError(format!(
+                        "No backup '{}' recorded for {}",
+                        name, spec
+                    ))
+                })?,
+            None => records.len().checked_sub(1).ok_or_else(|| {
+                crate::error::AutoInstallError::ConfigError(format!(
+                    "No backups recorded for {}",
+                    spec
+                ))
+            })?,
+        };
+
+        if matches!(target, BackupTarget::Remote { .. }) {
+            return Ok(vec![records[end]]);
+        }
+
+        let start = records[..=end]
+            .iter()
+            .rposition(|b| b.base.is_none())
+            .ok_or_else(|| {
+                crate::error::AutoInstallError::ConfigError(format!(
+                    "Backup chain for {} has no full stream before '{}'",
+                    spec, records[end].snapshot
+                ))
+            })?;
+        Ok(records[start..=end].to_vec())
+    }
+}
+
+/// `ssh` invocation running `command` on the backup host; the command is quoted once more
+/// because the remote shell parses it again
+fn remote_command(destination: &str, command: &str) -> String {
+    format!(
+        "ssh -o BatchMode=yes {} {}",
+        shell_quote(destination),
+        shell_quote(command)
+    )
+}
+
+/// Snapshot name for a backup taken at `time`
+pub fn snapshot_name(time: DateTime<Utc>) -> String {
+    format!("autoinstall-{}", time.format("%Y%m%dT%H%M%SZ"))
+}
+
+/// Commands that snapshot `pools` and send them to `target`, streams throttled per `throttle`
+pub fn build_backup_commands(
+    target: &BackupTarget,
+    pools: &[String],
+    snapshot: &str,
+    base: Option<&str>,
+    throttle: &ThrottleConfig,
+) -> Vec<String> {
+    let mut commands: Vec<String> = pools
+        .iter()
+        .map(|pool| {
+            format!(
+                "zfs snapshot -r {}",
+                shell_quote(&format!("{}@{}", pool, snapshot))
+            )
+        })
+        .collect();
+
+    match target {
+        BackupTarget::Directory(dir) => commands.push(format!("mkdir -p {}", shell_quote(dir))),
+        BackupTarget::Remote {
+            destination,
+            dataset,
+        } => commands.push(remote_command(
+            destination,
+            &format!("zfs create -p {}", shell_quote(dataset)),
+        )),
+    }
+
+    for pool in pools {
+        let incremental = base
+            .map(|b| format!("-I {} ", shell_quote(&format!("@{}", b))))
+            .unwrap_or_default();
+        let send = format!(
+            "zfs send -R {}{}",
+            incremental,
+            shell_quote(&format!("{}@{}", pool, snapshot))
+        );
+        let stream = match target {
+            BackupTarget::Directory(dir) => {
+                throttle.redirect(&send, &BackupTarget::stream_path(dir, pool, snapshot))
+            }
+            BackupTarget::Remote {
+                destination,
+                dataset,
+            } => throttle.pipe(
+                &send,
+                &remote_command(
+                    destination,
+                    &format!(
+                        "zfs recv -u -F {}",
+                        shell_quote(&format!("{}/{}", dataset, pool))
+                    ),
+                ),
+            ),
+        };
+        commands.push(throttle.wrap_command(&format!("set -o pipefail; {}", stream)));
+    }
+    commands
+}
+
+/// Commands that replay `chain` into freshly created pools, streams throttled per `throttle`
+pub fn build_receive_commands(
+    target: &BackupTarget,
+    pools: &[String],
+    chain: &[&BackupRecord],
+    throttle: &ThrottleConfig,
+) -> Vec<String> {
+    let mut commands = Vec::new();
+    for record in chain {
+        for pool in pools.iter().filter(|p| record.pools.contains(p)) {
+            let receive = format!("zfs recv -u -F {}", shell_quote(pool));
+            let stream = match target {
+                BackupTarget::Directory(dir) => {
+                    let fed = throttle.feed(
+                        &BackupTarget::stream_path(dir, pool, &record.snapshot),
+                        &receive,
+                    );
+                    if throttle.rate_limit_kbps.is_some() {
+                        format!("set -o pipefail; {}", fed)
+                    } else {
+                        fed
+                    }
+                }
+                BackupTarget::Remote {
+                    destination,
+                    dataset,
+                } => format!(
+                    "set -o pipefail; {}",
+                    throttle.pipe(
+                        &remote_command(
+                            destination,
+                            &format!(
+                                "zfs send -R {}",
+                                shell_quote(&format!("{}/{}@{}", dataset, pool, record.snapshot))
+                            ),
+                        ),
+                        &receive
+                    )
+                ),
+            };
+            commands.push(throttle.wrap_command(&stream));
+        }
+    }
+    commands
+}
+
+/// Command that mounts the restored root dataset and the rest of the pools under /mnt/targetos
+pub fn build_mount_restored_root_command(layout: &PoolLayout) -> String {
+    format!(
+        "ROOTFS=$(zfs list -H -o name,mountpoint -r {} | awk '$2==\"/\"{{print $1; exit}}'); \
+         [ -n \"$ROOTFS\" ] && zfs mount \"$ROOTFS\" && zfs mount -a",
+        layout.root_container()
+    )
+}
+
+/// Takes and restores ZFS send/receive backups over SSH
+pub struct BackupManager<'a> {
+    ssh: &'a mut SshClient,
+    throttle: ThrottleConfig,
+}
+
+impl<'a> BackupManager<'a> {
+    pub fn new(ssh: &'a mut SshClient) -> Self {
+        Self {
+            ssh,
+            throttle: ThrottleConfig::default(),
+        }
+    }
+
+    /// Throttle the send/receive streams so a live host keeps serving traffic
+    pub fn with_throttle(mut self, throttle: ThrottleConfig) -> Self {
+        self.throttle = throttle;
+        self
+    }
+
+    /// Snapshot and send `pools`, incrementally from `base` when that snapshot still exists
+    pub async fn create_backup(
+        &mut self,
+        target: &BackupTarget,
+        pools: &[String],
+        base: Option<&str>,
+    ) -> Result<BackupRecord> {
+        let created_at = Utc::now();
+        let snapshot = snapshot_name(created_at);
+
+        let mut base = base.map(str::to_string);
+        if let Some(b) = &base {
+            for pool in pools {
+                let present = self
+                    .ssh
+                    .check_silent(&format!(
+                        "zfs list -H -t snapshot {}@{} >/dev/null 2>&1",
+                        pool, b
+                    ))
+                    .await
+                    .unwrap_or(false);
+                if !present {
+                    warn!(
+                        "Base snapshot {}@{} no longer exists; sending a full stream",
+                        pool, b
+                    );
+                    base = None;
+                    break;
+                }
+            }
+        }
+
+        info!(
+            "Backing up {} to {} as {} ({})",
+            pools.join(", "),
+            target.spec(),
+            snapshot,
+            base.as_deref()
+                .map(|b| format!("incremental from {}", b))
+                .unwrap_or_else(|| "full".to_string())
+        );
+        for cmd in build_backup_commands(target, pools, &snapshot, base.as_deref(), &self.throttle)
+        {
+            self.log_and_execute("Backup", &cmd).await?;
+        }
+
+        Ok(BackupRecord {
+            snapshot,
+            base,
+            target: target.spec(),
+            pools: pools.to_vec(),
+            created_at,
+        })
+    }
+
+    /// Recreate disk layout and pools on `config.disk_device`, then replay `chain`
+    ///
+    /// The disk is wiped. After the streams are received GRUB and crypttab are regenerated
+    /// for the new partitions, since their UUIDs differ from the backed-up system. The pools
+    /// are recreated under the names of the host's `zfs_pools` layout, which backups are taken
+    /// under as well.
+    pub async fn restore(
+        &mut self,
+        config: &InstallationConfig,
+        target: &BackupTarget,
+        chain: &[&BackupRecord],
+    ) -> Result<()> {
+        let layout = config.pool_layout()?;
+        let pools = layout_pools(&layout);
+        check_chain_pools(chain, &pools)?;
+        info!(
+            "Restoring {} onto {} from {}",
+            config.hostname,
+            config.disk_device,
+            target.spec()
+        );
+
+        DiskManager::new(self.ssh).prepare_disk(config).await?;
+        self.log_and_execute("Creating target directory", "mkdir -p /mnt/targetos")
+            .await?;
+        self.log_and_execute(
+            &format!("Creating {}", layout.boot.name),
+            &ZfsManager::build_bpool_create_command(&layout.boot, &config.disk_device),
+        )
+        .await?;
+        self.log_and_execute(
+            &format!("Creating {}", layout.root.name),
+            &ZfsManager::build_rpool_create_command(&layout.root),
+        )
+        .await?;
+
+        for cmd in build_receive_commands(target, &pools, chain, &self.throttle) {
+            self.log_and_execute("Receiving stream", &cmd).await?;
+        }
+        self.log_and_execute(
+            "Mounting restored datasets",
+            &build_mount_restored_root_command(&layout),
+        )
+        .await?;
+
+        let mut configurator = SystemConfigurator::new(self.ssh);
+        configurator.setup_luks_key_in_chroot(config).await?;
+        configurator.configure_grub_in_chroot(config).await?;
+        configurator.final_cleanup(config).await?;
+
+        info!("Restore of {} completed", config.hostname);
+        Ok(())
+    }
+
+    async fn log_and_execute(&mut self, description: &str, command: &str) -> Result<()> {
+        info!("Executing: {} -> {}", description, command);
+        self.ssh.execute(command).await
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn record(snapshot: &str, base: Option<&str>, target: &str) -> BackupRecord {
+        BackupRecord {
+            snapshot: snapshot.into(),
+            base: base.map(str::to_string),
+            target: target.into(),
+            pools: vec!["bpool".into(), "rpool".into()],
+            created_at: Utc::now(),
+        }
+    }
+
+    #[test]
+    fn test_parse_targets() {
+        assert_eq!(
+            BackupTarget::parse("/srv/backups/").unwrap(),
+            BackupTarget::Directory("/srv/backups".into())
+        );
+        let remote = BackupTarget::parse("ssh://root@nas/tank/backups/host-a").unwrap();
+        assert_eq!(
+            remote,
+            BackupTarget::Remote {
+                destination: "root@nas".into(),
+                dataset: "tank/backups/host-a".into()
+            }
+        );
+        assert_eq!(remote.spec(), "ssh://root@nas/tank/backups/host-a");
+        assert!(BackupTarget::parse("ssh://nas").is_err());
+        assert!(BackupTarget::parse("ssh://-oProxyCommand=reboot/tank/b").is_err());
+        assert!(BackupTarget::parse("ssh://nas x/tank/b").is_err());
+        assert!(BackupTarget::parse("").is_err());
+    }
+
+    #[test]
+    fn test_backup_commands_incremental_to_remote() {
+        let target = BackupTarget::parse("ssh://nas/tank/b").unwrap();
+        let pools = vec!["rpool".to_string()];
+        let cmds = build_backup_commands(
+            &target,
+            &pools,
+            "autoinstall-2",
+            Some("autoinstall-1"),
+            &ThrottleConfig::default(),
+        );
+        assert_eq!(cmds[0], "zfs snapshot -r rpool@autoinstall-2");
+        assert_eq!(cmds[1], "ssh -o BatchMode=yes nas 'zfs create -p tank/b'");
+        assert!(cmds[2].contains("zfs send -R -I @autoinstall-1 rpool@autoinstall-2 | ssh"));
+        assert!(cmds[2].ends_with("'zfs recv -u -F tank/b/rpool'"));
+    }
+
+    #[test]
+    fn test_backup_commands_throttled() {
+        let throttle = ThrottleConfig {
+            io_class: Some(crate::config::throttle::IoClass::Idle),
+            rate_limit_kbps: Some(10240),
+            ..Default::default()
+        };
+        let target = BackupTarget::parse("/mnt/nfs").unwrap();
+        let cmds = build_backup_commands(
+            &target,
+            &["rpool".to_string()],
+            "autoinstall-1",
+            None,
+            &throttle,
+        );
+        assert_eq!(
+            cmds[2],
+            "ionice -c3 bash -c 'set -o pipefail; zfs send -R rpool@autoinstall-1 | pv -q -L 10240k > /mnt/nfs/rpool@autoinstall-1.zfs'"
+        );
+    }
+
+    #[test]
+    fn test_backup_commands_full_to_directory() {
+        let target = BackupTarget::parse("/mnt/nfs").unwrap();
+        let pools = vec!["bpool".to_string(), "rpool".to_string()];
+        let cmds = build_backup_commands(
+            &target,
+            &pools,
+            "autoinstall-1",
+            None,
+            &ThrottleConfig::default(),
+        );
+        assert_eq!(cmds.len(), 5);
+        assert_eq!(
+            cmds[4],
+            "set -o pipefail; zfs send -R rpool@autoinstall-1 > /mnt/nfs/rpool@autoinstall-1.zfs"
+        );
+    }
+
+    #[test]
+    fn test_backup_commands_quote_targets() {
+        let pools = vec!["rpool".to_string()];
+        let dir = BackupTarget::Directory("/mnt/my backups".to_string());
+        let cmds = build_backup_commands(&dir, &pools, "s1", None, &ThrottleConfig::default());
+        assert_eq!(cmds[1], "mkdir -p '/mnt/my backups'");
+        assert!(cmds[2].ends_with("> '/mnt/my backups/rpool@s1.zfs'"));
+
+        let remote = BackupTarget::parse("ssh://nas/tank/b;reboot").unwrap();
+        let cmds = build_backup_commands(&remote, &pools, "s1", None, &ThrottleConfig::default());
+        assert_eq!(
+            cmds[1],
+            "ssh -o BatchMode=yes nas 'zfs create -p '\\''tank/b;reboot'\\'''"
+        );
+    }
+
+    #[test]
+    fn test_restore_chain_starts_at_last_full() {
+        let catalog = BackupCatalog {
+            backups: vec![
+                record("s1", None, "/d"),
+                record("s2", Some("s1"), "/d"),
+                record("r1", None, "ssh://nas/tank"),
+                record("s3", None, "/d"),
+                record("s4", Some("s3"), "/d"),
+                record("s5", Some("s4"), "/d"),
+            ],
+        };
+        let dir = BackupTarget::parse("/d").unwrap();
+        let chain = catalog.restore_chain(&dir, None).unwrap();
+        let names: Vec<&str> = chain.iter().map(|b| b.snapshot.as_str()).collect();
+        assert_eq!(names, vec!["s3", "s4", "s5"]);
+
+        let chain = catalog.restore_chain(&dir, Some("s2")).unwrap();
+        assert_eq!(chain.len(), 2);
+        let cmds = build_receive_commands(
+            &dir,
+            &["rpool".to_string()],
+            &chain,
+            &ThrottleConfig::default(),
+        );
+        assert_eq!(
+            cmds,
+            vec![
+                "zfs recv -u -F rpool < /d/rpool@s1.zfs",
+                "zfs recv -u -F rpool < /d/rpool@s2.zfs"
+            ]
+        );
+
+        let remote = BackupTarget::parse("ssh://nas/tank").unwrap();
+        assert_eq!(catalog.restore_chain(&remote, None).unwrap().len(), 1);
+        assert_eq!(
+            catalog.latest_for(&remote).map(|b| b.snapshot.as_str()),
+            Some("r1")
+        );
+        assert!(catalog.restore_chain(&dir, Some("missing")).is_err());
+
+        // Restores need a stream of every pool the host is configured with
+        let mut layout = PoolLayout::default();
+        assert_eq!(layout_pools(&layout), ["bpool", "rpool"]);
+        assert!(check_chain_pools(&chain, &layout_pools(&layout)).is_ok());
+        layout.root.name = "tank".to_string();
+        assert!(check_chain_pools(&chain, &layout_pools(&layout)).is_err());
+        assert!(build_mount_restored_root_command(&layout).contains("-r tank/ROOT |"));
+    }
+
+    #[test]
+    fn test_catalog_roundtrip() {
+        let dir = tempfile::TempDir::new().unwrap();
+        assert!(BackupCatalog::load(dir.path(), "host-a")
+            .unwrap()
+            .backups
+            .is_empty());
+        let catalog = BackupCatalog {
+            backups: vec![record("s1", None, "/d")],
+        };
+        let path = catalog.save(dir.path(), "host-a").unwrap();
+        assert!(path.ends_with("logs/host-a/backups.json"));
+        assert_eq!(
+            BackupCatalog::load(dir.path(), "host-a").unwrap().backups,
+            catalog.backups
+        );
+    }
+
+    #[test]
+    fn test_snapshot_name_format() {
+        let time = DateTime::parse_from_rfc3339("2025-03-04T05:06:07Z")
+            .unwrap()
+            .with_timezone(&Utc);
+        assert_eq!(snapshot_name(time), "autoinstall-20250304T050607Z");
+    }
+}
@@ -0,0 +1,526 @@
+// file: src/network/ssh_installer/boot_env.rs
+// version: 1.2.0
+// guid: 9a4e1c73-2b6d-4f08-8e95-d3c7a1f0b264
+
+//! ZFS boot environments on installed hosts (`boot-env`)
+//!
+//! A boot environment is a clone of a root dataset tree under `<root pool>/ROOT`, with a
+//! matching clone of its boot dataset under `<boot pool>/BOOT`; the installer's own
+//! `ubuntu_<id>` datasets are the first one. Pool names come from the host's `zfs_pools`
+//! layout. Ubuntu's GRUB script lists every root dataset and defaults to the one whose
+//! `com.ubuntu.zsys:last-used` is newest, so activating an environment marks it last-used,
+//! hands the automatic mounts over to it and regenerates the boot menu. On hosts with zsys the
+//! snapshot behind a new environment is saved with `zsysctl` so it shows up in zsys' history;
+//! elsewhere plain `zfs snapshot` is used.
+
+use crate::config::zfs_pools::PoolLayout;
+use crate::error::AutoInstallError;
+use crate::network::SshClient;
+use crate::Result;
+use chrono::{DateTime, TimeZone, Utc};
+use serde::{Deserialize, Serialize};
+use tracing::info;
+
+/// User property keeping a dataset's own `canmount` while its environment is not the default
+pub const CANMOUNT_PROPERTY: &str = "org.ubuntu-autoinstall:canmount";
+/// Property GRUB's ZFS script picks the default entry by
+const LAST_USED_PROPERTY: &str = "com.ubuntu.zsys:last-used";
+
+/// Command listing the root datasets with their origin, creation time, space used and
+/// last-used stamp
+pub fn list_command(layout: &PoolLayout) -> String {
+    format!(
+        "zfs list -H -p -o name,origin,creation,used,{} -d 1 {}",
+        LAST_USED_PROPERTY,
+        layout.root_container()
+    )
+}
+/// Dataset mounted at `/`
+pub const RUNNING_COMMAND: &str = "findmnt -n -o SOURCE /";
+const ZSYS_CHECK: &str = "command -v zsysctl >/dev/null 2>&1";
+
+/// One boot environment
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct BootEnvironment {
+    pub name: String,
+    /// Snapshot the environment was cloned from; `None` for the installed one
+    pub origin: Option<String>,
+    pub created: Option<DateTime<Utc>>,
+    pub used_bytes: u64,
+    /// Mounted at `/` right now
+    pub running: bool,
+    /// Booted by default on the next start
+    pub default: bool,
+}
+
+impl BootEnvironment {
+    /// `ubuntu_x1y2z3  running,default  1.2 GiB  2026-10-16 09:30`
+    pub fn summary(&self) -> String {
+        let mut flags = Vec::new();
+        if self.running {
+            flags.push("running");
+        }
+        if self.default {
+            flags.push("default");
+        }
+        format!(
+            "{}  {}  {:.1} GiB  {}",
+            self.name,
+            if flags.is_empty() {
+                "-".to_string()
+            } else {
+                flags.join(",")
+            },
+            self.used_bytes as f64 / (1u64 << 30) as f64,
+            self.created
+                .map(|t| t.format("%Y-%m-%d %H:%M").to_string())
+                .unwrap_or_default()
+        )
+    }
+}
+
+/// Check that `name` can be used as a dataset and snapshot name
+pub fn validate_name(name: &str) -> Result<()> {
+    let valid = !name.is_empty()
+        && !name.starts_with('-')
+        && name
+            .chars()
+            .all(|c| c.is_ascii_alphanumeric() || matches!(c, '_' | '-' | '.' | ':'));
+    if valid {
+        Ok(())
+    } else {
+        Err(AutoInstallError::ValidationError(format!(
+            "Invalid boot environment name '{}': use letters, digits, '_', '-', '.' and ':'",
+            name
+        )))
+    }
+}
+
+/// Name of the environment created automatically before `operation`
+pub fn auto_name(operation: &str, now: DateTime<Utc>) -> String {
+    format!("uaa-pre-{}-{}", operation, now.format("%Y%m%d-%H%M%S"))
+}
+
+/// Parse [`list_command`] output; `running` is the output of [`RUNNING_COMMAND`]
+///
+/// The default environment is the one with the newest last-used stamp, or the running one
+/// when none carries a stamp.
+pub fn parse_list(output: &str, running: &str, layout: &PoolLayout) -> Vec<BootEnvironment> {
+    let running = running.trim();
+    let root_container = layout.root_container();
+    let mut last_used = Vec::new();
+    let mut environments: Vec<BootEnvironment> = output
+        .lines()
+        .filter_map(|line| {
+            let fields: Vec<&str> = line.split('\t').collect();
+            let name = fields
+                .first()?
+                .strip_prefix(root_container.as_str())?
+                .strip_prefix('/')?;
+            let value = |i: usize| fields.get(i).copied().filter(|v| *v != "-");
+            last_used.push(value(4).and_then(|v| v.parse::<i64>().ok()));
+            Some(BootEnvironment {
+                name: name.to_string(),
+                origin: value(1).map(str::to_string),
+                created: value(2)
+                    .and_then(|v| v.parse().ok())
+                    .and_then(|secs| Utc.timestamp_opt(secs, 0).single()),
+                used_bytes: value(3).and_then(|v| v.parse().ok()).unwrap_or(0),
+                running: fields[0] == running,
+                default: false,
+            })
+        })
+        .collect();
+    let newest = last_used
+        .iter()
+        .enumerate()
+        .filter_map(|(i, stamp)| stamp.map(|s| (s, i)))
+        .max()
+        .map(|(_, i)| i)
+        .or_else(|| environments.iter().position(|e| e.running));
+    if let Some(i) = newest {
+        environments[i].default = true;
+    }
+    environments
+}
+
+/// Commands snapshotting environment `source` as `name`
+pub fn build_snapshot_commands(
+    layout: &PoolLayout,
+    source: &str,
+    name: &str,
+    zsys: bool,
+) -> Vec<String> {
+    if zsys {
+        return vec![format!("zsysctl save --system {}", name)];
+    }
+    vec![
+        format!(
+            "zfs snapshot -r {}/{}@{}",
+            layout.root_container(),
+            source,
+            name
+        ),
+        format!(
+            "if zfs list -H {0}/{1} >/dev/null 2>&1; then zfs snapshot -r {0}/{1}@{2}; fi",
+            layout.boot_container(),
+            source,
+            name
+        ),
+    ]
+}
+
+/// Command cloning the tree `container/source@name` to `container/name`
+///
+/// Clones never mount automatically; each keeps its source's `canmount` in
+/// [`CANMOUNT_PROPERTY`] until the environment is activated.
+fn build_clone_command(container: &str, source: &str, name: &str, mountpoint: &str) -> String {
+    format!(
+        "zfs list -H -o name -r {c}/{s} | while read -r ds; do \
+         canmount=$(zfs get -H -o value canmount \"$ds\"); \
+         bootfs=$(zfs get -H -o value com.ubuntu.zsys:bootfs \"$ds\"); \
+         [ \"$canmount\" = off ] && mode=off || mode=noauto; \
+         [ \"$bootfs\" = - ] && extra= || extra=\"-o com.ubuntu.zsys:bootfs=$bootfs\"; \
+         zfs clone -o canmount=$mode -o {p}=$canmount $extra \"$ds@{n}\" \"{c}/{n}${{ds#{c}/{s}}}\" || exit 1; \
+         done && zfs set mountpoint={m} {c}/{n}",
+        c = container,
+        s = source,
+        n = name,
+        m = mountpoint,
+        p = CANMOUNT_PROPERTY
+    )
+}
+
+/// Commands creating environment `name` from the running environment `source`
+pub fn build_create_commands(
+    layout: &PoolLayout,
+    source: &str,
+    name: &str,
+    zsys: bool,
+) -> Vec<String> {
+    let boot_container = layout.boot_container();
+    let mut commands = build_snapshot_commands(layout, source, name, zsys);
+    commands.push(build_clone_command(
+        &layout.root_container(),
+        source,
+        name,
+        "/",
+    ));
+    commands.push(format!(
+        "if zfs list -H {c}/{s}@{n} >/dev/null 2>&1; then {clone}; fi",
+        c = boot_container,
+        s = source,
+        n = name,
+        clone = build_clone_command(&boot_container, source, name, "/boot")
+    ));
+    commands.push("update-grub".to_string());
+    commands
+}
+
+/// Commands making `target` the default environment instead of `others`
+pub fn build_activate_commands(
+    layout: &PoolLayout,
+    target: &str,
+    others: &[String],
+    now: DateTime<Utc>,
+) -> Vec<String> {
+    let (root_container, boot_container) = (layout.root_container(), layout.boot_container());
+    let mut commands = Vec::new();
+    for container in [&root_container, &boot_container] {
+        for other in others {
+            commands.push(format!(
+                "zfs list -H -o name -r {c}/{o} 2>/dev/null | while read -r ds; do \
+                 [ \"$(zfs get -H -o value canmount \"$ds\")\" = on ] || continue; \
+                 zfs set {p}=on \"$ds\" && zfs set canmount=noauto \"$ds\" || exit 1; done",
+                c = container,
+                o = other,
+                p = CANMOUNT_PROPERTY
+            ));
+        }
+        commands.push(format!(
+            "zfs list -H -o name -r {c}/{t} 2>/dev/null | while read -r ds; do \
+             [ \"$(zfs get -H -o value {p} \"$ds\")\" = on ] || continue; \
+             zfs set canmount=on \"$ds\" || exit 1; done",
+            c = container,
+            t = target,
+            p = CANMOUNT_PROPERTY
+        ));
+    }
+    commands.push(format!(
+        "zfs set {}={} {}/{}",
+        LAST_USED_PROPERTY,
+        now.timestamp(),
+        root_container,
+        target
+    ));
+    commands.push(format!(
+        "if zfs list -H {c}/{t} >/dev/null 2>&1; then zfs set {p}={s} {c}/{t}; fi",
+        c = boot_container,
+        t = target,
+        p = LAST_USED_PROPERTY,
+        s = now.timestamp()
+    ));
+    commands.push("update-grub".to_string());
+    commands
+}
+
+/// Commands destroying `environment` and, when nothing else uses it, the snapshot it came from
+pub fn build_destroy_commands(layout: &PoolLayout, environment: &BootEnvironment) -> Vec<String> {
+    let (root_container, boot_container) = (layout.root_container(), layout.boot_container());
+    let mut commands = vec![
+        format!("zfs destroy -r {}/{}", root_container, environment.name),
+        format!(
+            "if zfs list -H {0}/{1} >/dev/null 2>&1; then zfs destroy -r {0}/{1}; fi",
+            boot_container, environment.name
+        ),
+    ];
+    if let Some((source, snapshot)) = environment
+        .origin
+        .as_deref()
+        .and_then(|origin| {
+            origin
+                .strip_prefix(root_container.as_str())?
+                .strip_prefix('/')
+        })
+        .and_then(|origin| origin.split_once('@'))
+    {
+        for container in [&root_container, &boot_container] {
+            commands.push(format!(
+                "zfs destroy -r {}/{}@{} 2>/dev/null || true",
+                container, source, snapshot
+            ));
+        }
+    }
+    commands.push("update-grub".to_string());
+    commands
+}
+
+/// Manages the boot environments of a host over an SSH session connected as root
+pub struct BootEnvManager<'a> {
+    ssh: &'a mut SshClient,
+    layout: &'a PoolLayout,
+}
+
+impl<'a> BootEnvManager<'a> {
+    pub fn new(ssh: &'a mut SshClient, layout: &'a PoolLayout) -> Self {
+        Self { ssh, layout }
+    }
+
+    pub async fn list(&mut self) -> Result<Vec<BootEnvironment>> {
+        let output = self
+            .ssh
+            .execute_with_output(&list_command(self.layout))
+            .await?;
+        let running = self.ssh.execute_with_output(RUNNING_COMMAND).await?;
+        Ok(parse_list(&output, &running, self.layout))
+    }
+
+    async fn running(&mut self) -> Result<BootEnvironment> {
+        self.list()
+            .await?
+            .into_iter()
+            .find(|e| e.running)
+            .ok_or_else(|| {
+                AutoInstallError::ValidationError(format!(
+                    "The running root is not a dataset under {}",
+                    self.layout.root_container()
+                ))
+            })
+    }
+
+    async fn find(&mut self, name: &str) -> Result<BootEnvironment> {
+        self.list()
+            .await?
+            .into_iter()
+            .find(|e| e.name == name)
+            .ok_or_else(|| {
+                AutoInstallError::ValidationError(format!("No boot environment named '{}'", name))
+            })
+    }
+
+    /// Commands [`Self::create`] would run
+    pub async fn create_commands(&mut self, name: &str) -> Result<Vec<String>> {
+        validate_name(name)?;
+        if self.list().await?.iter().any(|e| e.name == name) {
+            return Err(AutoInstallError::ValidationError(format!(
+                "Boot environment '{}' already exists",
+                name
+            )));
+        }
+        let source = self.running().await?;
+        let zsys = self.ssh.check_silent(ZSYS_CHECK).await?;
+        Ok(build_create_commands(self.layout, &source.name, name, zsys))
+    }
+
+    /// Clone the running environment as `name`; it is listed in the boot menu but not default
+    pub async fn create(&mut self, name: &str) -> Result<()> {
+        for command in self.create_commands(name).await? {
+            self.ssh.execute(&command).await?;
+        }
+        info!("Created boot environment {}", name);
+        Ok(())
+    }
+
+    /// Commands [`Self::activate`] would run
+    pub async fn activate_commands(&mut self, name: &str) -> Result<Vec<String>> {
+        let environments = self.list().await?;
+        if !environments.iter().any(|e| e.name == name) {
+            return Err(AutoInstallError::ValidationError(format!(
+                "No boot environment named '{}'",
+                name
+            )));
+        }
+        let others: Vec<String> = environments
+            .into_iter()
+            .filter(|e| e.name != name)
+            .map(|e| e.name)
+            .collect();
+        Ok(build_activate_commands(
+            self.layout,
+            name,
+            &others,
+            Utc::now(),
+        ))
+    }
+
+    /// Make `name` the environment booted next
+    pub async fn activate(&mut self, name: &str) -> Result<()> {
+        for command in self.activate_commands(name).await? {
+            self.ssh.execute(&command).await?;
+        }
+        info!("Boot environment {} is booted next", name);
+        Ok(())
+    }
+
+    /// Commands [`Self::destroy`] would run
+    pub async fn destroy_commands(&mut self, name: &str) -> Result<Vec<String>> {
+        let environment = self.find(name).await?;
+        if environment.running || environment.default {
+            return Err(AutoInstallError::ValidationError(format!(
+                "Boot environment '{}' is running or booted next; activate another one first",
+                name
+            )));
+        }
+        Ok(build_destroy_commands(self.layout, &environment))
+    }
+
+    /// Destroy environment `name`, which must be neither running nor the default
+    pub async fn destroy(&mut self, name: &str) -> Result<()> {
+        for command in self.destroy_commands(name).await? {
+            self.ssh.execute(&command).await?;
+        }
+        info!("Destroyed boot environment {}", name);
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::zfs_pools::{PoolSpec, ZfsPoolsConfig};
+
+    const LIST: &str = "rpool/ROOT\t-\t1760000000\t98304\t-\n\
+                        rpool/ROOT/ubuntu_x1y2z3\t-\t1760000000\t4294967296\t1760000100\n\
+                        rpool/ROOT/uaa-pre-upgrade-1\trpool/ROOT/ubuntu_x1y2z3@uaa-pre-upgrade-1\t1760500000\t1048576\t1760600000\n";
+
+    #[test]
+    fn test_parse_list() {
+        let layout = PoolLayout::default();
+        let environments = parse_list(LIST, "rpool/ROOT/ubuntu_x1y2z3\n", &layout);
+        assert_eq!(environments.len(), 2);
+        assert_eq!(environments[0].name, "ubuntu_x1y2z3");
+        assert!(environments[0].running);
+        assert!(!environments[0].default);
+        assert!(environments[1].default);
+        assert_eq!(
+            environments[1].origin.as_deref(),
+            Some("rpool/ROOT/ubuntu_x1y2z3@uaa-pre-upgrade-1")
+        );
+        assert!(environments[0]
+            .summary()
+            .starts_with("ubuntu_x1y2z3  running  4.0 GiB  "));
+
+        // Without stamps the running environment is the default
+        let environments = parse_list(
+            "rpool/ROOT/ubuntu_x1y2z3\t-\t1760000000\t4294967296\t-\n",
+            "rpool/ROOT/ubuntu_x1y2z3",
+            &layout,
+        );
+        assert!(environments[0].default);
+
+        assert!(validate_name("pre-upgrade_2.0").is_ok());
+        assert!(validate_name("-rf").is_err());
+        assert!(validate_name("a b").is_err());
+    }
+
+    #[test]
+    fn test_create_and_destroy_commands() {
+        let layout = PoolLayout::default();
+        let raw = build_create_commands(&layout, "ubuntu_x1y2z3", "before-kernel", false);
+        assert_eq!(
+            raw[0],
+            "zfs snapshot -r rpool/ROOT/ubuntu_x1y2z3@before-kernel"
+        );
+        assert!(raw[2].starts_with("zfs list -H -o name -r rpool/ROOT/ubuntu_x1y2z3 |"));
+        assert!(raw[2].contains(
+            "\"$ds@before-kernel\" \"rpool/ROOT/before-kernel${ds#rpool/ROOT/ubuntu_x1y2z3}\""
+        ));
+        assert!(raw[2].ends_with("zfs set mountpoint=/ rpool/ROOT/before-kernel"));
+        assert!(raw[3].ends_with("zfs set mountpoint=/boot bpool/BOOT/before-kernel; fi"));
+        assert_eq!(raw.last().unwrap(), "update-grub");
+
+        let zsys = build_create_commands(&layout, "ubuntu_x1y2z3", "before-kernel", true);
+        assert_eq!(zsys[0], "zsysctl save --system before-kernel");
+
+        let environments = parse_list(LIST, "rpool/ROOT/ubuntu_x1y2z3", &layout);
+        let destroy = build_destroy_commands(&layout, &environments[1]);
+        assert_eq!(destroy[0], "zfs destroy -r rpool/ROOT/uaa-pre-upgrade-1");
+        assert_eq!(
+            destroy[2],
+            "zfs destroy -r rpool/ROOT/ubuntu_x1y2z3@uaa-pre-upgrade-1 2>/dev/null || true"
+        );
+        assert_eq!(destroy.len(), 5);
+
+        // Custom pool names from zfs_pools
+        let layout = ZfsPoolsConfig {
+            root_pool: PoolSpec {
+                name: Some("tank-{hostname}".to_string()),
+                ..Default::default()
+            },
+            boot_pool: PoolSpec {
+                name: Some("boot-{hostname}".to_string()),
+                ..Default::default()
+            },
+            ..Default::default()
+        }
+        .resolve("stor-02")
+        .unwrap();
+        assert!(list_command(&layout).ends_with(" -d 1 tank-stor-02/ROOT"));
+        let raw = build_create_commands(&layout, "ubuntu_x1y2z3", "before-kernel", false);
+        assert_eq!(
+            raw[0],
+            "zfs snapshot -r tank-stor-02/ROOT/ubuntu_x1y2z3@before-kernel"
+        );
+        assert!(raw[3].ends_with("zfs set mountpoint=/boot boot-stor-02/BOOT/before-kernel; fi"));
+    }
+
+    #[test]
+    fn test_activate_hands_over_mounts() {
+        let now = Utc.timestamp_opt(1760700000, 0).unwrap();
+        let commands = build_activate_commands(
+            &PoolLayout::default(),
+            "before-kernel",
+            &["ubuntu_x1y2z3".to_string()],
+            now,
+        );
+        assert!(commands[0].starts_with("zfs list -H -o name -r rpool/ROOT/ubuntu_x1y2z3 "));
+        assert!(commands[0].contains("zfs set canmount=noauto"));
+        assert!(commands[1].contains("zfs set canmount=on"));
+        assert!(commands[2].contains("bpool/BOOT/ubuntu_x1y2z3"));
+        assert_eq!(
+            commands[4],
+            "zfs set com.ubuntu.zsys:last-used=1760700000 rpool/ROOT/before-kernel"
+        );
+        assert_eq!(auto_name("upgrade", now), "uaa-pre-upgrade-20251017-112000");
+    }
+}
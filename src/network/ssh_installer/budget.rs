@@ -0,0 +1,127 @@
+// file: src/network/ssh_installer/budget.rs
+// version: 1.0.0
+// guid: 7b1e4c92-3a6d-4f58-8e07-d2c9a5f1b643
+
+//! Where the time of a budgeted installation went
+//!
+//! Recorded in the session when the install finishes or the budget runs out, so a run that
+//! was stopped can be told apart from one that failed, and the slowest phases are the first
+//! thing in the report.
+
+use super::session::InstallSession;
+use chrono::Utc;
+use serde::{Deserialize, Serialize};
+
+/// Time spent in one phase
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct PhaseUsage {
+    pub name: String,
+    pub seconds: i64,
+    /// Share of the budget, in percent
+    pub percent_of_budget: u32,
+}
+
+/// Budget and how much of it each phase took
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct BudgetUsage {
+    pub limit_minutes: u64,
+    pub elapsed_seconds: i64,
+    /// The budget ran out and the install was stopped
+    pub exceeded: bool,
+    /// Phase that was not started, or `during <phase>` when one was cut short
+    pub stopped: Option<String>,
+    /// Most expensive first
+    pub phases: Vec<PhaseUsage>,
+}
+
+impl BudgetUsage {
+    /// Usage of `session` so far against `limit_minutes`
+    pub fn from_session(
+        session: &InstallSession,
+        limit_minutes: u64,
+        stopped: Option<String>,
+    ) -> Self {
+        let now = Utc::now();
+        let limit_seconds = (limit_minutes * 60).max(1) as i64;
+        let mut phases: Vec<PhaseUsage> = session
+            .phase_timings
+            .iter()
+            .map(|timing| {
+                let seconds = (timing.finished_at.unwrap_or(now) - timing.started_at)
+                    .num_seconds()
+                    .max(0);
+                PhaseUsage {
+                    name: timing.name.clone(),
+                    seconds,
+                    percent_of_budget: (seconds * 100 / limit_seconds) as u32,
+                }
+            })
+            .collect();
+        phases.sort_by_key(|phase| std::cmp::Reverse(phase.seconds));
+        let elapsed_seconds = (now - session.started_at).num_seconds().max(0);
+        Self {
+            limit_minutes,
+            elapsed_seconds,
+            exceeded: stopped.is_some(),
+            stopped,
+            phases,
+        }
+    }
+
+    /// `Budget exceeded: 97m of 90m, stopped before Phase 5: ...`, then one line per phase
+    pub fn summary_lines(&self) -> Vec<String> {
+        let mut lines = vec![format!(
+            "Budget {}: {}m of {}m{}",
+            if self.exceeded { "exceeded" } else { "used" },
+            self.elapsed_seconds / 60,
+            self.limit_minutes,
+            self.stopped
+                .as_ref()
+                .map(|s| format!(", stopped {}", s))
+                .unwrap_or_default()
+        )];
+        lines.extend(self.phases.iter().map(|phase| {
+            format!(
+                "  {:>5}s {:>3}%  {}",
+                phase.seconds, phase.percent_of_budget, phase.name
+            )
+        }));
+        lines
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::super::session::PhaseTiming;
+    use super::*;
+
+    #[test]
+    fn test_usage_orders_phases_by_cost() {
+        let mut session = InstallSession::new("web-01");
+        let start = Utc::now() - chrono::Duration::minutes(70);
+        session.started_at = start;
+        let timing = |name: &str, from: i64, to: i64| PhaseTiming {
+            name: name.to_string(),
+            started_at: start + chrono::Duration::minutes(from),
+            finished_at: Some(start + chrono::Duration::minutes(to)),
+        };
+        session.phase_timings = vec![
+            timing("Phase 1: Package installation", 0, 6),
+            timing("Phase 4: Base system", 6, 66),
+        ];
+
+        let usage = BudgetUsage::from_session(
+            &session,
+            60,
+            Some("before Phase 5: System configuration".to_string()),
+        );
+        assert!(usage.exceeded);
+        assert_eq!(usage.phases[0].name, "Phase 4: Base system");
+        assert_eq!(usage.phases[0].percent_of_budget, 100);
+        assert_eq!(usage.phases[1].percent_of_budget, 10);
+        assert_eq!(
+            usage.summary_lines()[0],
+            "Budget exceeded: 70m of 60m, stopped before Phase 5: System configuration"
+        );
+    }
+}
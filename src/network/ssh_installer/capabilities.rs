@@ -0,0 +1,366 @@
+// file: src/network/ssh_installer/capabilities.rs
+// version: 1.0.0
+// guid: sshcap01-2345-6789-abcd-ef0123456789
+
+//! Version-aware capability detection for the live environment
+//!
+//! Live ISOs and vendor rescue images ship very different tool versions. The probe records
+//! the versions the installer depends on, selects command variants the target understands and
+//! refuses to touch the disk when a capability has no known-good fallback.
+
+use crate::network::SshClient;
+use crate::Result;
+use regex::Regex;
+use std::fmt;
+use std::sync::OnceLock;
+
+/// `lsblk -o PATH` first appeared in util-linux 2.33
+pub const LSBLK_PATH_COLUMN: ToolVersion = ToolVersion::new(2, 33, 0);
+/// `lsblk -p` and the PARTTYPE column first appeared in util-linux 2.23
+pub const LSBLK_PARTTYPE_COLUMN: ToolVersion = ToolVersion::new(2, 23, 0);
+/// cryptsetup formats LUKS2 by default from 2.1; 2.0 needs `--type luks2`
+pub const CRYPTSETUP_LUKS2_DEFAULT: ToolVersion = ToolVersion::new(2, 1, 0);
+/// First cryptsetup release able to write LUKS2 headers
+pub const CRYPTSETUP_LUKS2: ToolVersion = ToolVersion::new(2, 0, 0);
+/// `zpool create -o compatibility=grub2` (used for bpool) needs OpenZFS 2.1
+pub const ZFS_COMPATIBILITY_PROPERTY: ToolVersion = ToolVersion::new(2, 1, 0);
+/// Deb822 `.sources` files are read by apt 1.1 and newer
+pub const APT_DEB822: ToolVersion = ToolVersion::new(1, 1, 0);
+/// Every Ubuntu suite script in debootstrap is a link to this one
+pub const DEBOOTSTRAP_FALLBACK_SCRIPT: &str = "/usr/share/debootstrap/scripts/gutsy";
+
+/// Dotted tool version, compared component by component
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub struct ToolVersion {
+    pub major: u32,
+    pub minor: u32,
+    pub patch: u32,
+}
+
+impl ToolVersion {
+    pub const fn new(major: u32, minor: u32, patch: u32) -> Self {
+        Self {
+            major,
+            minor,
+            patch,
+        }
+    }
+
+    /// First dotted version in `text`, e.g. `2.39.3` in `lsblk from util-linux 2.39.3`
+    pub fn parse(text: &str) -> Option<Self> {
+        static VERSION: OnceLock<Regex> = OnceLock::new();
+        let re = VERSION.get_or_init(|| Regex::new(r"(\d+)\.(\d+)(?:\.(\d+))?").unwrap());
+        let caps = re.captures(text)?;
+        let part = |i: usize| caps.get(i).and_then(|m| m.as_str().parse().ok());
+        Some(Self::new(part(1)?, part(2)?, part(3).unwrap_or(0)))
+    }
+
+    /// Lowest version found across the lines of `text`
+    ///
+    /// `zfs version` prints both the userland and the kernel module; pools are created by the
+    /// module, so the older of the two decides what is supported.
+    pub fn parse_lowest(text: &str) -> Option<Self> {
+        text.lines().filter_map(Self::parse).min()
+    }
+}
+
+impl fmt::Display for ToolVersion {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}.{}.{}", self.major, self.minor, self.patch)
+    }
+}
+
+/// How the ESP is located by partition type GUID
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum EspDetection {
+    /// `lsblk -rP -o PATH,PARTTYPE` (util-linux 2.33+)
+    LsblkPath,
+    /// `lsblk -rpP -o NAME,PARTTYPE`, where `-p` makes NAME a full device path
+    LsblkName,
+    /// `blkid` partition-entry lookup for images without a usable lsblk
+    Blkid,
+}
+
+impl EspDetection {
+    /// Command printing the first partition whose type matches `guid`
+    pub fn command(self, guid: &str) -> String {
+        // Safe quoting: outer bash uses single quotes; grep and sed patterns use double quotes.
+        match self {
+            EspDetection::LsblkPath => format!(
+                "bash -lc 'lsblk -rP -o PATH,PARTTYPE | grep -i \"PARTTYPE=\\\"{0}\\\"\" | head -n1 | sed -n \"s/.*PATH=\\\"\\([^\\\" ]*\\)\\\".*/\\1/p\"'",
+                guid
+            ),
+            EspDetection::LsblkName => format!(
+                "bash -lc 'lsblk -rpP -o NAME,PARTTYPE | grep -i \"PARTTYPE=\\\"{0}\\\"\" | head -n1 | sed -n \"s/.*NAME=\\\"\\([^\\\" ]*\\)\\\".*/\\1/p\"'",
+                guid
+            ),
+            EspDetection::Blkid => format!(
+                "blkid -o device -t PART_ENTRY_TYPE={} | head -n1",
+                guid
+            ),
+        }
+    }
+}
+
+/// Tool versions found in the live environment; `None` means the tool is not installed
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct TargetCapabilities {
+    pub util_linux: Option<ToolVersion>,
+    pub cryptsetup: Option<ToolVersion>,
+    pub zfs: Option<ToolVersion>,
+    pub sgdisk: Option<ToolVersion>,
+    pub debootstrap: Option<ToolVersion>,
+    /// Whether debootstrap ships a script for the requested release
+    pub debootstrap_release_script: bool,
+    /// Whether the generic Ubuntu script exists for releases newer than debootstrap
+    pub debootstrap_fallback_script: bool,
+}
+
+impl TargetCapabilities {
+    /// Probe the live environment; run after the required packages are installed
+    pub async fn probe(ssh: &mut SshClient, release: &str) -> Result<Self> {
+        let util_linux = probe_version(ssh, "lsblk --version").await?;
+        let cryptsetup = probe_version(ssh, "cryptsetup --version").await?;
+        let zfs = ToolVersion::parse_lowest(
+            &probe_output(
+                ssh,
+                "zfs version 2>/dev/null || cat /sys/module/zfs/version 2>/dev/null",
+            )
+            .await?,
+        );
+        let sgdisk = probe_version(ssh, "sgdisk --version").await?;
+        let debootstrap = probe_version(ssh, "dpkg-query -W -f='${Version}' debootstrap").await?;
+        let debootstrap_release_script = ssh
+            .check_silent(&format!(
+                "test -e /usr/share/debootstrap/scripts/{}",
+                release
+            ))
+            .await?;
+        let debootstrap_fallback_script = ssh
+            .check_silent(&format!("test -e {}", DEBOOTSTRAP_FALLBACK_SCRIPT))
+            .await?;
+        Ok(Self {
+            util_linux,
+            cryptsetup,
+            zfs,
+            sgdisk,
+            debootstrap,
+            debootstrap_release_script,
+            debootstrap_fallback_script,
+        })
+    }
+
+    /// ESP detection variant supported by the installed util-linux
+    pub fn esp_detection(&self) -> EspDetection {
+        match self.util_linux {
+            Some(v) if v >= LSBLK_PATH_COLUMN => EspDetection::LsblkPath,
+            Some(v) if v >= LSBLK_PARTTYPE_COLUMN => EspDetection::LsblkName,
+            _ => EspDetection::Blkid,
+        }
+    }
+
+    /// Extra `cryptsetup luksFormat` options that make the header LUKS2
+    pub fn luks_format_options(&self) -> &'static str {
+        match self.cryptsetup {
+            Some(v) if v < CRYPTSETUP_LUKS2_DEFAULT => "--type luks2 ",
+            _ => "",
+        }
+    }
+
+    /// Script argument appended to debootstrap when it predates the requested release
+    pub fn debootstrap_script(&self) -> Option<&'static str> {
+        if self.debootstrap_release_script {
+            None
+        } else {
+            Some(DEBOOTSTRAP_FALLBACK_SCRIPT)
+        }
+    }
+
+    /// Actionable messages for every capability that has no fallback
+    pub fn missing(&self, release: &str) -> Vec<String> {
+        let mut missing = Vec::new();
+        match self.zfs {
+            None => missing.push(
+                "zfs is not available; install zfsutils-linux and load the zfs module (modprobe zfs)"
+                    .to_string(),
+            ),
+            Some(v) if v < ZFS_COMPATIBILITY_PROPERTY => missing.push(format!(
+                "OpenZFS {} cannot create bpool with -o compatibility=grub2 (needs {} or newer); boot an Ubuntu 22.04 or newer live ISO",
+                v, ZFS_COMPATIBILITY_PROPERTY
+            )),
+            Some(_) => {}
+        }
+        match self.cryptsetup {
+            None => missing.push(
+                "cryptsetup is not installed; apt-get install cryptsetup".to_string(),
+            ),
+            Some(v) if v < CRYPTSETUP_LUKS2 => missing.push(format!(
+                "cryptsetup {} cannot write LUKS2 headers (needs {} or newer); boot an Ubuntu 18.10 or newer live ISO",
+                v, CRYPTSETUP_LUKS2
+            )),
+            Some(_) => {}
+        }
+        if self.sgdisk.is_none() {
+            missing.push("sgdisk is not installed; apt-get install gdisk".to_string());
+        }
+        if self.debootstrap.is_none() {
+            missing.push("debootstrap is not installed; apt-get install debootstrap".to_string());
+        } else if !self.debootstrap_release_script && !self.debootstrap_fallback_script {
+            missing.push(format!(
+                "debootstrap {} has no script for '{}' and no generic Ubuntu script; install a newer debootstrap",
+                self.debootstrap.map(|v| v.to_string()).unwrap_or_default(),
+                release
+            ));
+        }
+        missing
+    }
+
+    /// Fail with every missing capability listed, before any disk is modified
+    pub fn require(&self, release: &str) -> Result<()> {
+        let missing = self.missing(release);
+        if missing.is_empty() {
+            return Ok(());
+        }
+        Err(crate::error::AutoInstallError::ValidationError(format!(
+            "Live environment lacks required capabilities:\n  - {}",
+            missing.join("\n  - ")
+        )))
+    }
+
+    /// One-line summary of the detected versions for the install log
+    pub fn summary(&self) -> String {
+        let show =
+            |v: Option<ToolVersion>| v.map(|v| v.to_string()).unwrap_or_else(|| "missing".into());
+        format!(
+            "util-linux {}, cryptsetup {}, zfs {}, sgdisk {}, debootstrap {}",
+            show(self.util_linux),
+            show(self.cryptsetup),
+            show(self.zfs),
+            show(self.sgdisk),
+            show(self.debootstrap)
+        )
+    }
+}
+
+/// Whether the apt in the target reads Deb822 `.sources` files; unknown versions assume yes
+pub fn supports_deb822(apt: Option<ToolVersion>) -> bool {
+    apt.is_none_or(|v| v >= APT_DEB822)
+}
+
+/// Version of apt installed in the target chroot, if it can be determined
+pub async fn probe_target_apt(ssh: &mut SshClient) -> Result<Option<ToolVersion>> {
+    probe_version(ssh, "chroot /mnt/targetos apt-get --version").await
+}
+
+async fn probe_output(ssh: &mut SshClient, command: &str) -> Result<String> {
+    // Missing tools are an answer, not an error
+    ssh.execute_with_output(&format!("{{ {}; }} 2>/dev/null || true", command))
+        .await
+}
+
+async fn probe_version(ssh: &mut SshClient, command: &str) -> Result<Option<ToolVersion>> {
+    Ok(probe_output(ssh, command)
+        .await?
+        .lines()
+        .next()
+        .and_then(ToolVersion::parse))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn modern() -> TargetCapabilities {
+        TargetCapabilities {
+            util_linux: ToolVersion::parse("lsblk from util-linux 2.39.3"),
+            cryptsetup: ToolVersion::parse("cryptsetup 2.7.0 flags: UDEV BLKID KEYRING"),
+            zfs: ToolVersion::parse_lowest("zfs-2.2.2-0ubuntu9\nzfs-kmod-2.2.2-0ubuntu9\n"),
+            sgdisk: ToolVersion::parse("GPT fdisk (sgdisk) version 1.0.10"),
+            debootstrap: ToolVersion::parse("1.0.134ubuntu1"),
+            debootstrap_release_script: true,
+            debootstrap_fallback_script: true,
+        }
+    }
+
+    #[test]
+    fn test_parse_versions() {
+        assert_eq!(
+            ToolVersion::parse("apt 2.7.14 (amd64)"),
+            Some(ToolVersion::new(2, 7, 14))
+        );
+        assert_eq!(
+            ToolVersion::parse("lsblk from util-linux 2.34"),
+            Some(ToolVersion::new(2, 34, 0))
+        );
+        assert_eq!(
+            ToolVersion::parse_lowest("zfs-2.2.0-1\nzfs-kmod-0.8.3-1ubuntu12\n"),
+            Some(ToolVersion::new(0, 8, 3))
+        );
+        assert_eq!(ToolVersion::parse("bash: lsblk: command not found"), None);
+        assert!(ToolVersion::new(2, 10, 0) > ToolVersion::new(2, 9, 9));
+    }
+
+    #[test]
+    fn test_modern_live_iso_uses_default_variants() {
+        let caps = modern();
+        assert!(caps.require("noble").is_ok());
+        assert_eq!(caps.esp_detection(), EspDetection::LsblkPath);
+        assert_eq!(caps.luks_format_options(), "");
+        assert_eq!(caps.debootstrap_script(), None);
+    }
+
+    #[test]
+    fn test_old_rescue_image_selects_fallbacks() {
+        let caps = TargetCapabilities {
+            util_linux: Some(ToolVersion::new(2, 31, 1)),
+            cryptsetup: Some(ToolVersion::new(2, 0, 2)),
+            debootstrap_release_script: false,
+            ..modern()
+        };
+        assert!(caps.require("plucky").is_ok());
+        assert_eq!(caps.esp_detection(), EspDetection::LsblkName);
+        assert_eq!(caps.luks_format_options(), "--type luks2 ");
+        assert_eq!(caps.debootstrap_script(), Some(DEBOOTSTRAP_FALLBACK_SCRIPT));
+
+        let no_lsblk = TargetCapabilities {
+            util_linux: None,
+            ..modern()
+        };
+        assert_eq!(no_lsblk.esp_detection(), EspDetection::Blkid);
+    }
+
+    #[test]
+    fn test_missing_capabilities_are_actionable() {
+        let caps = TargetCapabilities {
+            zfs: Some(ToolVersion::new(0, 8, 3)),
+            cryptsetup: Some(ToolVersion::new(1, 7, 3)),
+            sgdisk: None,
+            debootstrap_release_script: false,
+            debootstrap_fallback_script: false,
+            ..modern()
+        };
+        let missing = caps.missing("plucky");
+        assert_eq!(missing.len(), 4);
+        assert!(missing[0].contains("compatibility=grub2"));
+        assert!(missing[1].contains("LUKS2"));
+        assert!(missing[2].contains("apt-get install gdisk"));
+        assert!(missing[3].contains("no script for 'plucky'"));
+        let err = caps.require("plucky").unwrap_err().to_string();
+        assert!(err.contains("OpenZFS 0.8.3"));
+    }
+
+    #[test]
+    fn test_esp_detection_commands_and_deb822() {
+        let guid = "c12a7328-f81f-11d2-ba4b-00a0c93ec93b";
+        assert!(EspDetection::LsblkName
+            .command(guid)
+            .contains("lsblk -rpP -o NAME,PARTTYPE"));
+        assert_eq!(
+            EspDetection::Blkid.command(guid),
+            format!("blkid -o device -t PART_ENTRY_TYPE={} | head -n1", guid)
+        );
+        assert!(supports_deb822(None));
+        assert!(supports_deb822(Some(ToolVersion::new(1, 6, 12))));
+        assert!(!supports_deb822(Some(ToolVersion::new(1, 0, 1))));
+    }
+}
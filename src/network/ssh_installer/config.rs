@@ -1,9 +1,25 @@
 // file: src/network/ssh_installer/config.rs
-// version: 1.2.0
+// version: 1.36.0
 // guid: sshcfg01-2345-6789-abcd-ef0123456789
 
 //! Configuration structures for SSH installation
 
+use super::presets::{InstallPreset, DEFAULT_PRESET};
+use crate::config::loader::ConfigLoader;
+use crate::config::zfs_pools::PoolLayout;
+use crate::config::{
+    AptLockConfig, AptMirrorsConfig, AptReposConfig, AptSnapshot, Architecture, BootloaderConfig,
+    BudgetConfig, ConfirmationConfig, DiskHealthConfig, EntropyConfig, FirewallConfig,
+    HardeningConfig, HeadlessConfig, HealthGateConfig, HostVarsConfig, KernelConfig,
+    LateCommandsConfig, LowMemoryConfig, NbdeConfig, NetworkRecoveryConfig, PartitioningConfig,
+    PerformanceConfig, ReinstallConfig, SshCaConfig, UbuntuProConfig, UpdatesConfig,
+    UserDataConfig, ZfsPoolsConfig, ZfsTuningConfig,
+};
+use sha2::{Digest, Sha256};
+
+/// Release installed when a config names no `debootstrap_release`
+pub const DEFAULT_RELEASE: &str = "plucky";
+
 #[derive(Debug, Clone)]
 pub struct InstallationConfig {
     pub hostname: String,
@@ -18,26 +34,222 @@ pub struct InstallationConfig {
     pub network_nameservers: Vec<String>,
     pub debootstrap_release: Option<String>,
     pub debootstrap_mirror: Option<String>,
+    /// Additional disks that each receive a mirrored ESP (mounted at /boot/efi2, ...)
+    pub esp_mirror_devices: Vec<String>,
+    /// Architecture of the target, used to pick bootloader and kernel packages
+    pub architecture: Architecture,
+    /// sysctl and kernel module settings written into the target
+    pub kernel: KernelConfig,
+    /// Archive snapshot that debootstrap and the target's apt sources are pinned to
+    pub apt_snapshot: Option<AptSnapshot>,
+    /// Hardening profile applied during system configuration and checked afterwards
+    pub hardening: HardeningConfig,
+    /// ZFS ARC sizing; the chosen values are computed from the target's RAM at install time
+    pub zfs_tuning: ZfsTuningConfig,
+    /// Inbound firewall written into the target and enabled at boot
+    pub firewall: FirewallConfig,
+    /// Serial console, watchdog and RTC settings; written before GRUB is configured
+    pub headless: HeadlessConfig,
+    /// SSH CA signing the target's host certificate and the listed users' certificates
+    pub ssh_ca: SshCaConfig,
+    /// Tang servers the LUKS volume is bound to; checked before the target is unmounted
+    pub nbde: NbdeConfig,
+    /// Resolvers and alternate mirrors used when debootstrap or chroot apt fails
+    pub network_recovery: NetworkRecoveryConfig,
+    /// Operator scripts run in the target after configuration
+    pub late_commands: LateCommandsConfig,
+    /// unattended-upgrades, periodic apt tasks and pinning for the installed system
+    pub updates: UpdatesConfig,
+    /// Health limits checked against the install disk's SMART data in preflight
+    pub disk_health: DiskHealthConfig,
+    /// Waiting for apt/dpkg locks before package steps
+    pub apt_lock: AptLockConfig,
+    /// Weighted go/no-go score checked before Phase 2
+    pub health_gate: HealthGateConfig,
+    /// Who partitions the install disk and the layout it must have
+    pub partitioning: PartitioningConfig,
+    /// Phases the install stops before until approved
+    pub confirmation: ConfirmationConfig,
+    /// Wall-clock limit for the installation
+    pub budget: BudgetConfig,
+    /// GRUB or systemd-boot; systemd-boot also takes its kernel command line from here
+    pub bootloader: BootloaderConfig,
+    /// Whether the per-host variables are copied into the installed system
+    pub host_vars: HostVarsConfig,
+    /// Swap, ARC cap and serialized package steps in the live environment of small machines
+    pub low_memory: LowMemoryConfig,
+    /// Hardware RNG daemon and first-boot entropy check
+    pub entropy: EntropyConfig,
+    /// apt mirrors of the installed system, tried in priority order
+    pub apt_mirrors: AptMirrorsConfig,
+    /// Ubuntu Pro attachment, with the token as a secret reference
+    pub ubuntu_pro: UbuntuProConfig,
+    /// Pool names, properties and storage role
+    pub zfs_pools: ZfsPoolsConfig,
+    /// Kernel parameter, sysctl and zram/zswap preset applied during system configuration
+    pub performance: PerformanceConfig,
+    /// Third-party apt repositories added to the installed system
+    pub apt_repos: AptReposConfig,
+    /// cloud-init style user-data staged for the first boot
+    pub user_data: UserDataConfig,
+    /// User datasets kept and relinked by a reinstall
+    pub reinstall: ReinstallConfig,
 }
 
 impl InstallationConfig {
-    /// Create configuration for len-serv-003
+    /// Apply the install sections of the optional target config, as `ssh-install
+    /// --target-config` does
+    pub fn apply_target_sections(
+        &mut self,
+        loader: &ConfigLoader,
+        target_config: Option<&str>,
+    ) -> crate::Result<()> {
+        self.kernel = match target_config {
+            Some(path) => loader.load_kernel_config(path)?,
+            None => Default::default(),
+        };
+        self.hardening = match target_config {
+            Some(path) => loader.load_hardening_config(path)?,
+            None => Default::default(),
+        };
+        if let Some(path) = target_config {
+            self.zfs_tuning = loader.load_zfs_tuning_config(path)?;
+            self.firewall = loader.load_firewall_config(path)?;
+            self.headless = loader.load_headless_config(path)?;
+            self.ssh_ca = loader.load_ssh_ca_config(path)?;
+            self.reinstall = loader.load_reinstall_config(path)?;
+            self.user_data = loader.load_user_data_config(path)?;
+            self.apt_repos = loader.load_apt_repos_config(path)?;
+            self.performance = loader.load_performance_config(path)?;
+            self.zfs_pools = loader.load_zfs_pools_config(path)?;
+            self.ubuntu_pro = loader.load_ubuntu_pro_config(path)?;
+            self.apt_mirrors = loader.load_apt_mirrors_config(path)?;
+            self.entropy = loader.load_entropy_config(path)?;
+            self.low_memory = loader.load_low_memory_config(path)?;
+            self.host_vars = loader.load_host_vars_config(path)?;
+            self.bootloader = loader.load_bootloader_config(path)?;
+            self.budget = loader.load_budget_config(path)?;
+            self.confirmation = loader.load_confirmation_config(path)?;
+            self.partitioning = loader.load_partitioning_config(path)?;
+            self.health_gate = loader.load_health_gate_config(path)?;
+            self.apt_lock = loader.load_apt_lock_config(path)?;
+            self.disk_health = loader.load_disk_health_config(path)?;
+            self.updates = loader.load_updates_config(path)?;
+            self.late_commands = loader.load_late_commands_config(path)?;
+            self.network_recovery = loader.load_network_recovery_config(path)?;
+            self.nbde = loader.load_nbde_config(path)?;
+        }
+        Ok(())
+    }
+
+    /// Configuration of the built-in `len-serv-003` preset
+    ///
+    /// Kept for existing callers; new code should resolve presets through `PresetStore`.
     pub fn for_len_serv_003() -> Self {
-        Self {
-            hostname: "len-serv-003".to_string(),
-            disk_device: "/dev/nvme0n1".to_string(),
-            timezone: "America/New_York".to_string(),
-            luks_key: "changeme123!@#".to_string(),
-            root_password: "changeme123!@#".to_string(),
-            network_interface: "eno1".to_string(),
-            network_address: "172.16.3.96/23".to_string(),
-            network_gateway: "172.16.2.1".to_string(),
-            network_search: "local.jdfalk.com".to_string(),
-            network_nameservers: vec!["172.16.2.1".to_string(), "8.8.8.8".to_string()],
-            debootstrap_release: Some("plucky".to_string()),
-            debootstrap_mirror: Some("http://archive.ubuntu.com/ubuntu/".to_string()),
+        InstallPreset::builtin(DEFAULT_PRESET)
+            .expect("built-in default preset")
+            .into_config()
+    }
+
+    /// Release debootstrap installs: `debootstrap_release`, or [`DEFAULT_RELEASE`] when unset
+    pub fn release(&self) -> &str {
+        self.debootstrap_release
+            .as_deref()
+            .unwrap_or(DEFAULT_RELEASE)
+    }
+
+    /// Pools of this install, named for its hostname
+    pub fn pool_layout(&self) -> crate::Result<PoolLayout> {
+        self.zfs_pools.resolve(&self.hostname)
+    }
+
+    /// Mirror debootstrap pulls from: the pinned snapshot, the configured mirror, the first
+    /// `apt_mirrors` entry or the archive
+    pub fn effective_mirror(&self) -> String {
+        match (&self.apt_snapshot, &self.debootstrap_mirror) {
+            (Some(snapshot), _) => snapshot.archive_uri(),
+            (None, Some(mirror)) => mirror.clone(),
+            (None, None) => self
+                .apt_mirrors
+                .primary()
+                .unwrap_or_else(|| "http://archive.ubuntu.com/ubuntu/".to_string()),
         }
     }
+
+    /// Whether the target's sources come from `apt_mirrors` (a pinned snapshot wins)
+    pub fn uses_apt_mirrors(&self) -> bool {
+        self.apt_snapshot.is_none() && self.apt_mirrors.is_enabled()
+    }
+
+    /// SHA256 over the settings that shape the installed system.
+    ///
+    /// Secrets (LUKS key, root password) are excluded so the checksum can be stored in logs.
+    pub fn checksum(&self) -> String {
+        let canonical = [
+            format!("hostname={}", self.hostname),
+            format!("disk_device={}", self.disk_device),
+            format!("timezone={}", self.timezone),
+            format!("network_interface={}", self.network_interface),
+            format!("network_address={}", self.network_address),
+            format!("network_gateway={}", self.network_gateway),
+            format!("network_search={}", self.network_search),
+            format!("network_nameservers={}", self.network_nameservers.join(",")),
+            format!(
+                "debootstrap_release={}",
+                self.debootstrap_release.as_deref().unwrap_or("")
+            ),
+            format!(
+                "debootstrap_mirror={}",
+                self.debootstrap_mirror.as_deref().unwrap_or("")
+            ),
+            format!("esp_mirror_devices={}", self.esp_mirror_devices.join(",")),
+            format!("architecture={}", self.architecture.as_str()),
+            format!("kernel.sysctl={:?}", self.kernel.sysctl),
+            format!("kernel.modules={}", self.kernel.modules.join(",")),
+            format!("kernel.blacklist={}", self.kernel.blacklist.join(",")),
+            format!("kernel.module_options={:?}", self.kernel.module_options),
+            format!(
+                "apt_snapshot={}",
+                self.apt_snapshot.map(|s| s.to_string()).unwrap_or_default()
+            ),
+            format!("hardening.profile={:?}", self.hardening.profile),
+            format!(
+                "hardening.skip={}",
+                self.hardening
+                    .skip
+                    .iter()
+                    .map(|c| c.as_str())
+                    .collect::<Vec<_>>()
+                    .join(",")
+            ),
+            format!("zfs_tuning={:?}", self.zfs_tuning),
+            format!("firewall={:?}", self.firewall),
+            format!("headless={:?}", self.headless),
+            format!("ssh_ca={:?}", self.ssh_ca),
+            format!("reinstall={:?}", self.reinstall),
+            format!("user_data={:?}", self.user_data),
+            format!("apt_repos={:?}", self.apt_repos),
+            format!("performance={:?}", self.performance),
+            format!("zfs_pools={:?}", self.zfs_pools),
+            format!("ubuntu_pro={:?}", self.ubuntu_pro),
+            format!("apt_mirrors={:?}", self.apt_mirrors),
+            format!("entropy={:?}", self.entropy),
+            format!("low_memory={:?}", self.low_memory),
+            format!("host_vars={:?}", self.host_vars),
+            format!("bootloader={:?}", self.bootloader),
+            format!("budget={:?}", self.budget),
+            format!("confirmation={:?}", self.confirmation),
+            format!("partitioning={:?}", self.partitioning),
+            format!("health_gate={:?}", self.health_gate),
+            format!("apt_lock={:?}", self.apt_lock),
+            format!("disk_health={:?}", self.disk_health),
+            format!("updates={:?}", self.updates),
+            format!("late_commands={:?}", self.late_commands),
+            format!("nbde={:?}", self.nbde),
+        ]
+        .join("\n");
+        format!("{:x}", Sha256::digest(canonical.as_bytes()))
+    }
 }
 
 #[derive(Debug, Default)]
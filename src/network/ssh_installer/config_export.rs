@@ -0,0 +1,564 @@
+// file: src/network/ssh_installer/config_export.rs
+// version: 1.32.0
+// guid: 3f6b9d14-a82e-4c57-b1d0-7e5c2a9f8e63
+
+//! Golden config export (`export-config`)
+//!
+//! Reads an installed host over SSH and reconstructs a best-effort [`TargetConfig`] from it:
+//! install disk, netplan, local users with their keys, LUKS parameters and the packages
+//! installed on top of the base system. ZFS datasets have no place in a target config and are
+//! listed as comments. Anything that had to be guessed is noted at the top of the output.
+
+use crate::config::packages::packages_for_roles;
+use crate::config::{
+    Architecture, LuksConfig, NetworkConfig, PackageRole, TargetConfig, UserConfig,
+};
+use crate::network::SshClient;
+use crate::Result;
+use serde::Deserialize;
+use serde_yaml::Value;
+use std::collections::{BTreeMap, BTreeSet};
+use std::str::FromStr;
+use tracing::info;
+
+pub const HOSTNAME_COMMAND: &str = "hostname";
+pub const ARCH_COMMAND: &str = "dpkg --print-architecture";
+pub const RELEASE_COMMAND: &str = ". /etc/os-release && echo \"$VERSION_CODENAME\"";
+pub const TIMEZONE_COMMAND: &str =
+    "cat /etc/timezone 2>/dev/null || timedatectl show -p Timezone --value 2>/dev/null || true";
+/// Parent disk of the ESP, which the installer puts on the install disk
+pub const INSTALL_DISK_COMMAND: &str =
+    "lsblk -ndo PKNAME \"$(findmnt -no SOURCE /boot/efi)\" 2>/dev/null || true";
+/// Every netplan file as a separate YAML document
+pub const NETPLAN_COMMAND: &str =
+    "for f in /etc/netplan/*.yaml; do [ -f \"$f\" ] && echo '---' && cat \"$f\"; done; true";
+/// `user <name> <shell>` and `key <key>` lines for regular accounts, then `sudo <members>`
+pub const USERS_COMMAND: &str = "getent passwd | awk -F: '$3>=1000 && $3<65534 {print $1\":\"$6\":\"$7}' | \
+     while IFS=: read -r name home shell; do echo \"user $name $shell\"; \
+     sed -n 's/^\\(ssh-\\|ecdsa-\\|sk-\\)/key &/p' \"$home/.ssh/authorized_keys\" 2>/dev/null; done; \
+     getent group sudo admin | cut -d: -f4 | sed 's/^/sudo /'";
+pub const MANUAL_PACKAGES_COMMAND: &str = "apt-mark showmanual";
+pub const PACKAGE_PRIORITY_COMMAND: &str = "dpkg-query -W -f='${Package} ${Priority}\\n'";
+/// Header of the first LUKS device
+pub const LUKS_COMMAND: &str =
+    "for d in $(lsblk -nrpo NAME,FSTYPE | awk '$2==\"crypto_LUKS\"{print $1}'); \
+     do cryptsetup luksDump \"$d\"; break; done 2>/dev/null; true";
+pub const DATASETS_COMMAND: &str =
+    "zfs list -H -o name,mountpoint,compression,encryption 2>/dev/null || true";
+
+/// Package priorities debootstrap and the standard task install; never exported
+const BASE_PRIORITIES: &[&str] = &["required", "important", "standard"];
+
+/// A ZFS dataset on the source host
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct DatasetInfo {
+    pub name: String,
+    pub mountpoint: String,
+    pub compression: String,
+    pub encryption: String,
+}
+
+/// A reconstructed target config with what could not be inferred
+#[derive(Debug, Clone)]
+pub struct ExportedConfig {
+    /// Host the config was read from
+    pub source: String,
+    pub config: TargetConfig,
+    pub datasets: Vec<DatasetInfo>,
+    /// Values that were guessed or defaulted
+    pub notes: Vec<String>,
+}
+
+impl ExportedConfig {
+    /// Target config YAML with the notes and datasets as comments
+    pub fn to_yaml(&self) -> Result<String> {
+        let mut out = format!(
+            "# Exported from {} by export-config on {}\n\
+             # Best-effort reconstruction of an installed host; review before use.\n",
+            self.source,
+            chrono::Utc::now().format("%Y-%m-%d")
+        );
+        for note in &self.notes {
+            out.push_str(&format!("#   - {}\n", note));
+        }
+        out.push_str(&serde_yaml::to_string(&self.config)?);
+        if !self.datasets.is_empty() {
+            out.push_str(
+                "\n# ZFS datasets on the source host (created by the installer layout):\n",
+            );
+            for dataset in &self.datasets {
+                out.push_str(&format!(
+                    "#   {} mountpoint={} compression={} encryption={}\n",
+                    dataset.name, dataset.mountpoint, dataset.compression, dataset.encryption
+                ));
+            }
+        }
+        Ok(out)
+    }
+}
+
+/// First ethernet of the netplan documents in `text`
+pub fn parse_netplan(text: &str) -> Option<NetworkConfig> {
+    for document in serde_yaml::Deserializer::from_str(text) {
+        let Ok(value) = Value::deserialize(document) else {
+            continue;
+        };
+        let Some(ethernets) = value
+            .get("network")
+            .and_then(|n| n.get("ethernets"))
+            .and_then(Value::as_mapping)
+        else {
+            continue;
+        };
+        let Some((name, iface)) = ethernets.iter().next() else {
+            continue;
+        };
+
+        let ip_address = iface
+            .get("addresses")
+            .and_then(Value::as_sequence)
+            .and_then(|a| a.first())
+            .and_then(Value::as_str)
+            .map(str::to_string);
+        let gateway = iface
+            .get("gateway4")
+            .and_then(Value::as_str)
+            .map(str::to_string)
+            .or_else(|| {
+                iface
+                    .get("routes")
+                    .and_then(Value::as_sequence)?
+                    .iter()
+                    .find(|r| {
+                        matches!(
+                            r.get("to").and_then(Value::as_str),
+                            Some("default" | "0.0.0.0/0")
+                        )
+                    })?
+                    .get("via")
+                    .and_then(Value::as_str)
+                    .map(str::to_string)
+            });
+        let dns_servers = iface
+            .get("nameservers")
+            .and_then(|n| n.get("addresses"))
+            .and_then(Value::as_sequence)
+            .map(|a| {
+                a.iter()
+                    .filter_map(Value::as_str)
+                    .map(str::to_string)
+                    .collect()
+            })
+            .unwrap_or_default();
+        let dhcp = iface
+            .get("dhcp4")
+            .and_then(Value::as_bool)
+            .unwrap_or(ip_address.is_none());
+
+        return Some(NetworkConfig {
+            interface: name.as_str().unwrap_or("eth0").to_string(),
+            ip_address: ip_address.filter(|_| !dhcp),
+            gateway: gateway.filter(|_| !dhcp),
+            dns_servers,
+            dhcp,
+        });
+    }
+    None
+}
+
+/// Parse [`USERS_COMMAND`] output
+pub fn parse_users(text: &str) -> Vec<UserConfig> {
+    let mut users: Vec<UserConfig> = Vec::new();
+    let mut sudoers = BTreeSet::new();
+    for line in text.lines() {
+        if let Some(rest) = line.strip_prefix("user ") {
+            let mut fields = rest.split_whitespace();
+            let Some(name) = fields.next() else {
+                continue;
+            };
+            users.push(UserConfig {
+                name: name.to_string(),
+                sudo: false,
+                ssh_keys: Vec::new(),
+                shell: fields
+                    .next()
+                    .filter(|s| *s != "/bin/bash")
+                    .map(str::to_string),
+            });
+        } else if let Some(key) = line.strip_prefix("key ") {
+            if let Some(user) = users.last_mut() {
+                user.ssh_keys.push(key.trim().to_string());
+            }
+        } else if let Some(members) = line.strip_prefix("sudo ") {
+            sudoers.extend(members.split(',').map(str::trim).map(str::to_string));
+        }
+    }
+    for user in &mut users {
+        user.sudo = sudoers.contains(&user.name);
+    }
+    users
+}
+
+/// LUKS parameters from `cryptsetup luksDump` (LUKS1 or LUKS2); the passphrase stays a variable
+pub fn parse_luks_dump(text: &str) -> Option<LuksConfig> {
+    let field = |key: &str| {
+        text.lines().find_map(|line| {
+            let (k, v) = line.split_once(':')?;
+            (k.trim() == key).then(|| v.trim().to_string())
+        })
+    };
+    let bits = |value: String| value.split_whitespace().next()?.parse::<u32>().ok();
+
+    let defaults = LuksConfig::default();
+    let (cipher, key_size, hash) = if let Some(name) = field("Cipher name") {
+        // LUKS1: cipher split into name and mode
+        let cipher = match field("Cipher mode") {
+            Some(mode) => format!("{}-{}", name, mode),
+            None => name,
+        };
+        (
+            Some(cipher),
+            field("MK bits").and_then(bits),
+            field("Hash spec"),
+        )
+    } else {
+        (
+            field("cipher"),
+            field("Cipher key").and_then(bits),
+            field("Hash").or_else(|| field("hash")),
+        )
+    };
+    let cipher = cipher?;
+    Some(LuksConfig {
+        cipher,
+        key_size: key_size.unwrap_or(defaults.key_size),
+        hash: hash.unwrap_or(defaults.hash),
+        ..defaults
+    })
+}
+
+/// Parse [`DATASETS_COMMAND`] output
+pub fn parse_datasets(text: &str) -> Vec<DatasetInfo> {
+    text.lines()
+        .filter_map(|line| {
+            let mut fields = line.split('\t');
+            Some(DatasetInfo {
+                name: fields.next()?.to_string(),
+                mountpoint: fields.next()?.to_string(),
+                compression: fields.next()?.to_string(),
+                encryption: fields.next()?.trim().to_string(),
+            })
+        })
+        .collect()
+}
+
+/// Manually installed packages that are neither base system nor installed by the installer
+pub fn extra_packages(
+    manual: &str,
+    priorities: &str,
+    installer_packages: &[String],
+) -> Vec<String> {
+    let base: BTreeMap<&str, &str> = priorities
+        .lines()
+        .filter_map(|line| line.split_once(' '))
+        .collect();
+    let installer: BTreeSet<&str> = installer_packages.iter().map(String::as_str).collect();
+    manual
+        .lines()
+        .map(str::trim)
+        .filter(|name| !name.is_empty())
+        .filter(|name| !installer.contains(name))
+        .filter(|name| {
+            !base
+                .get(name)
+                .is_some_and(|priority| BASE_PRIORITIES.contains(&priority.trim()))
+        })
+        .filter(|name| !name.starts_with("linux-") && !name.starts_with("ubuntu-"))
+        .map(str::to_string)
+        .collect::<BTreeSet<_>>()
+        .into_iter()
+        .collect()
+}
+
+/// Reads an installed host and reconstructs its target config
+pub struct ConfigExporter<'a> {
+    ssh: &'a mut SshClient,
+}
+
+impl<'a> ConfigExporter<'a> {
+    pub fn new(ssh: &'a mut SshClient) -> Self {
+        Self { ssh }
+    }
+
+    async fn read(&mut self, command: &str) -> Result<String> {
+        Ok(self
+            .ssh
+            .execute_with_output(command)
+            .await?
+            .trim()
+            .to_string())
+    }
+
+    /// Export the connected host; `source` labels the output
+    pub async fn export(&mut self, source: &str) -> Result<ExportedConfig> {
+        info!("Exporting configuration of {}", source);
+        let mut notes = Vec::new();
+
+        let hostname = self.read(HOSTNAME_COMMAND).await?;
+        let architecture = match Architecture::from_str(&self.read(ARCH_COMMAND).await?) {
+            Ok(arch) => arch,
+            Err(e) => {
+                notes.push(format!("{}; assumed amd64", e));
+                Architecture::Amd64
+            }
+        };
+        let release = self.read(RELEASE_COMMAND).await?;
+
+        let timezone = match self.read(TIMEZONE_COMMAND).await? {
+            tz if tz.is_empty() => {
+                notes.push("Timezone not found; set to UTC".to_string());
+                "UTC".to_string()
+            }
+            tz => tz,
+        };
+
+        let disk_device = match self.read(INSTALL_DISK_COMMAND).await? {
+            disk if disk.is_empty() => {
+                notes.push("No ESP mounted at /boot/efi; disk_device is a placeholder".to_string());
+                "/dev/sda".to_string()
+            }
+            disk => format!("/dev/{}", disk.lines().next().unwrap_or_default()),
+        };
+
+        let network = parse_netplan(&self.read(NETPLAN_COMMAND).await?).unwrap_or_else(|| {
+            notes.push("No netplan ethernet found; network set to DHCP on eth0".to_string());
+            NetworkConfig {
+                interface: "eth0".to_string(),
+                ip_address: None,
+                gateway: None,
+                dns_servers: Vec::new(),
+                dhcp: true,
+            }
+        });
+
+        let users = parse_users(&self.read(USERS_COMMAND).await?);
+        if users.is_empty() {
+            notes.push("No regular user accounts found".to_string());
+        } else if users.iter().all(|u| u.ssh_keys.is_empty()) {
+            notes.push(
+                "No authorized_keys were readable; connect as root to export keys".to_string(),
+            );
+        }
+
+        let luks_config = parse_luks_dump(&self.read(LUKS_COMMAND).await?).unwrap_or_else(|| {
+            notes.push("No LUKS header readable; LUKS settings are defaults".to_string());
+            LuksConfig::default()
+        });
+
+        let installer_packages = packages_for_roles(PackageRole::ALL, architecture, &release);
+        let packages = extra_packages(
+            &self.read(MANUAL_PACKAGES_COMMAND).await?,
+            &self.read(PACKAGE_PRIORITY_COMMAND).await?,
+            &installer_packages,
+        );
+
+        let datasets = parse_datasets(&self.read(DATASETS_COMMAND).await?);
+
+        let config = TargetConfig {
+            hostname,
+            architecture,
+            disk_device,
+            timezone,
+            network,
+            users,
+            luks_config,
+            packages,
+            kernel: Default::default(),
+            throttle: Default::default(),
+            apt_snapshot: None,
+            hardening: Default::default(),
+            zfs_tuning: Default::default(),
+            storage: Default::default(),
+            bmc: None,
+            vsphere: None,
+            mirror_selection: None,
+            firewall: Default::default(),
+            headless: Default::default(),
+            progress: Default::default(),
+            ssh_ca: Default::default(),
+            reinstall: Default::default(),
+            user_data: Default::default(),
+            apt_repos: Default::default(),
+            issues: Default::default(),
+            performance: Default::default(),
+            verification: Default::default(),
+            telemetry: Default::default(),
+            zfs_pools: Default::default(),
+            ubuntu_pro: Default::default(),
+            apt_mirrors: Default::default(),
+            entropy: Default::default(),
+            low_memory: Default::default(),
+            host_vars: Default::default(),
+            bootloader: Default::default(),
+            budget: Default::default(),
+            privilege: Default::default(),
+            confirmation: Default::default(),
+            partitioning: Default::default(),
+            health_gate: Default::default(),
+            apt_lock: Default::default(),
+            disk_health: Default::default(),
+            updates: Default::default(),
+            late_commands: Default::default(),
+            network_recovery: Default::default(),
+            nbde: Default::default(),
+        };
+        if let Err(e) = config.validate() {
+            notes.push(format!("Exported config does not validate yet: {}", e));
+        }
+
+        Ok(ExportedConfig {
+            source: source.to_string(),
+            config,
+            datasets,
+            notes,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_netplan() {
+        let static_cfg = parse_netplan(
+            "---\nnetwork:\n  version: 2\n  ethernets:\n    eno1:\n      addresses: [10.0.0.5/24]\n      routes:\n        - to: default\n          via: 10.0.0.1\n      nameservers:\n        addresses: [1.1.1.1, 9.9.9.9]\n",
+        )
+        .unwrap();
+        assert_eq!(static_cfg.interface, "eno1");
+        assert!(!static_cfg.dhcp);
+        assert_eq!(static_cfg.ip_address.as_deref(), Some("10.0.0.5/24"));
+        assert_eq!(static_cfg.gateway.as_deref(), Some("10.0.0.1"));
+        assert_eq!(static_cfg.dns_servers, vec!["1.1.1.1", "9.9.9.9"]);
+
+        // The first document without ethernets is skipped
+        let dhcp = parse_netplan(
+            "---\nnetwork:\n  wifis: {}\n---\nnetwork:\n  ethernets:\n    enp1s0:\n      dhcp4: true\n",
+        )
+        .unwrap();
+        assert!(dhcp.dhcp);
+        assert_eq!(dhcp.interface, "enp1s0");
+        assert!(parse_netplan("").is_none());
+    }
+
+    #[test]
+    fn test_parse_users_luks_and_datasets() {
+        let users = parse_users(
+            "user admin /bin/bash\nkey ssh-ed25519 AAAAC3Nz admin@laptop\nuser svc /usr/sbin/nologin\nsudo admin,ops\n",
+        );
+        assert_eq!(users.len(), 2);
+        assert!(users[0].sudo);
+        assert_eq!(users[0].shell, None);
+        assert_eq!(users[0].ssh_keys, vec!["ssh-ed25519 AAAAC3Nz admin@laptop"]);
+        assert!(!users[1].sudo);
+        assert_eq!(users[1].shell.as_deref(), Some("/usr/sbin/nologin"));
+
+        let luks2 = parse_luks_dump(
+            "LUKS header information\nVersion:       \t2\nKeyslots:\n  0: luks2\n\tKey:        512 bits\n\tCipher:     aes-xts-plain64\nData segments:\n  0: crypt\n\tcipher: aes-xts-plain64\n\tsector: 512 [bytes]\nDigests:\n  0: pbkdf2\n\tHash:       sha256\n",
+        )
+        .unwrap();
+        assert_eq!(luks2.cipher, "aes-xts-plain64");
+        assert_eq!(luks2.hash, "sha256");
+        assert_eq!(luks2.passphrase, "${LUKS_PASSPHRASE}");
+
+        let luks1 = parse_luks_dump(
+            "Version:       \t1\nCipher name:   \taes\nCipher mode:   \tcbc-essiv:sha256\nHash spec:     \tsha1\nMK bits:       \t256\n",
+        )
+        .unwrap();
+        assert_eq!(luks1.cipher, "aes-cbc-essiv:sha256");
+        assert_eq!(luks1.key_size, 256);
+        assert_eq!(luks1.hash, "sha1");
+        assert!(parse_luks_dump("").is_none());
+
+        let datasets =
+            parse_datasets("rpool/ROOT/ubuntu\t/\tlz4\taes-256-gcm\nbpool\t/boot\tlz4\toff\n");
+        assert_eq!(datasets[0].mountpoint, "/");
+        assert_eq!(datasets[1].encryption, "off");
+    }
+
+    #[test]
+    fn test_extra_packages_skip_base_and_installer() {
+        let packages = extra_packages(
+            "bash\nzfsutils-linux\nlinux-generic\nnginx\nhtop\nubuntu-server\nhtop\n",
+            "bash required\nnginx optional\nhtop optional\nzfsutils-linux optional\n",
+            &["zfsutils-linux".to_string()],
+        );
+        assert_eq!(packages, vec!["htop", "nginx"]);
+    }
+
+    #[test]
+    fn test_exported_yaml_round_trips() {
+        let exported = ExportedConfig {
+            source: "10.0.0.5".to_string(),
+            config: TargetConfig {
+                hostname: "web-01".to_string(),
+                architecture: Architecture::Amd64,
+                disk_device: "/dev/nvme0n1".to_string(),
+                timezone: "Europe/Berlin".to_string(),
+                network: parse_netplan("network:\n  ethernets:\n    eno1:\n      dhcp4: true\n")
+                    .unwrap(),
+                users: parse_users("user admin /bin/bash\nsudo admin\n"),
+                luks_config: LuksConfig::default(),
+                packages: vec!["nginx".to_string()],
+                kernel: Default::default(),
+                throttle: Default::default(),
+                apt_snapshot: None,
+                hardening: Default::default(),
+                zfs_tuning: Default::default(),
+                storage: Default::default(),
+                bmc: None,
+                vsphere: None,
+                mirror_selection: None,
+                firewall: Default::default(),
+                headless: Default::default(),
+                progress: Default::default(),
+                ssh_ca: Default::default(),
+                reinstall: Default::default(),
+                user_data: Default::default(),
+                apt_repos: Default::default(),
+                issues: Default::default(),
+                performance: Default::default(),
+                verification: Default::default(),
+                telemetry: Default::default(),
+                zfs_pools: Default::default(),
+                ubuntu_pro: Default::default(),
+                apt_mirrors: Default::default(),
+                entropy: Default::default(),
+                low_memory: Default::default(),
+                host_vars: Default::default(),
+                bootloader: Default::default(),
+                budget: Default::default(),
+                privilege: Default::default(),
+                confirmation: Default::default(),
+                partitioning: Default::default(),
+                health_gate: Default::default(),
+                apt_lock: Default::default(),
+                disk_health: Default::default(),
+                updates: Default::default(),
+                late_commands: Default::default(),
+                network_recovery: Default::default(),
+                nbde: Default::default(),
+            },
+            datasets: parse_datasets("rpool/ROOT/ubuntu\t/\tlz4\taes-256-gcm\n"),
+            notes: vec!["Timezone not found; set to UTC".to_string()],
+        };
+        let yaml = exported.to_yaml().unwrap();
+        assert!(yaml.contains("#   - Timezone not found; set to UTC\n"));
+        assert!(yaml.contains("#   rpool/ROOT/ubuntu mountpoint=/ compression=lz4"));
+
+        let parsed: TargetConfig = serde_yaml::from_str(&yaml).unwrap();
+        assert_eq!(parsed.hostname, "web-01");
+        assert_eq!(parsed.disk_device, "/dev/nvme0n1");
+        assert!(parsed.validate().is_ok());
+    }
+}
@@ -0,0 +1,379 @@
+// file: src/network/ssh_installer/disk_health.rs
+// version: 1.0.0
+// guid: 2b7f4d19-8e63-4a0c-9d25-6c1a8f3e7b40
+
+//! SMART health of the target's disks
+//!
+//! `smartctl -j -a` is read when smartmontools is on the live system, `nvme smart-log -o json`
+//! otherwise. The counters that predict a failing drive (reallocated and pending sectors, NVMe
+//! endurance used and media errors) and the drive's own assessment are compared against the
+//! `disk_health:` limits; the result gates preflight and is kept for the reports.
+
+use crate::config::disk_health::DiskHealthConfig;
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+
+/// Command printing SMART data of `device` as JSON; prints nothing when neither tool exists
+pub fn health_command(device: &str) -> String {
+    format!(
+        "if command -v smartctl >/dev/null 2>&1; then smartctl -j -a '{0}'; \
+         elif command -v nvme >/dev/null 2>&1; then nvme smart-log -o json '{0}'; fi; true",
+        device
+    )
+}
+
+/// Tool the health data came from
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum HealthSource {
+    Smartctl,
+    NvmeCli,
+}
+
+/// Health counters of one drive; counters the drive does not report are `None`
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct DiskHealth {
+    pub source: HealthSource,
+    pub model: Option<String>,
+    pub serial: Option<String>,
+    /// SMART overall-health self-assessment
+    pub smart_passed: Option<bool>,
+    /// NVMe critical warning bit field; 0 when nothing is wrong
+    pub critical_warning: Option<u64>,
+    pub reallocated_sectors: Option<u64>,
+    pub pending_sectors: Option<u64>,
+    pub percentage_used: Option<u64>,
+    pub media_errors: Option<u64>,
+    pub temperature_c: Option<i64>,
+    pub power_on_hours: Option<u64>,
+}
+
+impl DiskHealth {
+    /// Counter by the name used in `disk_health:` limits
+    pub fn counter(&self, name: &str) -> Option<u64> {
+        match name {
+            "reallocated_sectors" => self.reallocated_sectors,
+            "pending_sectors" => self.pending_sectors,
+            "percentage_used" => self.percentage_used,
+            "media_errors" => self.media_errors,
+            _ => None,
+        }
+    }
+}
+
+/// Parse the output of [`health_command`]; `None` when it holds no health data
+pub fn parse_health(output: &str) -> Option<DiskHealth> {
+    let json: Value = serde_json::from_str(output.trim()).ok()?;
+    let health = if json.get("smartctl").is_some() {
+        parse_smartctl(&json)
+    } else if json.get("critical_warning").is_some() {
+        parse_nvme_cli(&json)
+    } else {
+        return None;
+    };
+    let reported = health.smart_passed.is_some()
+        || health.critical_warning.is_some()
+        || [
+            "reallocated_sectors",
+            "pending_sectors",
+            "percentage_used",
+            "media_errors",
+        ]
+        .iter()
+        .any(|name| health.counter(name).is_some());
+    reported.then_some(health)
+}
+
+fn parse_smartctl(json: &Value) -> DiskHealth {
+    let text = |key: &str| json.get(key).and_then(Value::as_str).map(str::to_string);
+    let attribute = |id: u64| {
+        json.pointer("/ata_smart_attributes/table")?
+            .as_array()?
+            .iter()
+            .find(|a| a.get("id").and_then(Value::as_u64) == Some(id))?
+            .pointer("/raw/value")?
+            .as_u64()
+    };
+    let nvme = |key: &str| {
+        json.get("nvme_smart_health_information_log")?
+            .get(key)?
+            .as_u64()
+    };
+    DiskHealth {
+        source: HealthSource::Smartctl,
+        model: text("model_name"),
+        serial: text("serial_number"),
+        smart_passed: json
+            .pointer("/smart_status/passed")
+            .and_then(Value::as_bool),
+        critical_warning: nvme("critical_warning"),
+        reallocated_sectors: attribute(5),
+        pending_sectors: attribute(197),
+        percentage_used: nvme("percentage_used"),
+        media_errors: nvme("media_errors"),
+        temperature_c: json.pointer("/temperature/current").and_then(Value::as_i64),
+        power_on_hours: json.pointer("/power_on_time/hours").and_then(Value::as_u64),
+    }
+}
+
+fn parse_nvme_cli(json: &Value) -> DiskHealth {
+    let number = |key: &str| json.get(key).and_then(Value::as_u64);
+    DiskHealth {
+        source: HealthSource::NvmeCli,
+        model: None,
+        serial: None,
+        smart_passed: None,
+        critical_warning: number("critical_warning"),
+        reallocated_sectors: None,
+        pending_sectors: None,
+        // Older nvme-cli releases call it percent_used
+        percentage_used: number("percentage_used").or_else(|| number("percent_used")),
+        media_errors: number("media_errors"),
+        // nvme-cli reports Kelvin
+        temperature_c: number("temperature").map(|k| k as i64 - 273),
+        power_on_hours: number("power_on_hours"),
+    }
+}
+
+/// Overall result of a health check
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum HealthVerdict {
+    Healthy,
+    Warning,
+    Failing,
+    /// No SMART data could be read
+    Unknown,
+}
+
+impl HealthVerdict {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            HealthVerdict::Healthy => "healthy",
+            HealthVerdict::Warning => "warning",
+            HealthVerdict::Failing => "failing",
+            HealthVerdict::Unknown => "unknown",
+        }
+    }
+}
+
+/// Health of one disk measured against the configured limits
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct DiskHealthCheck {
+    pub device: String,
+    pub checked_at: DateTime<Utc>,
+    pub health: Option<DiskHealth>,
+    /// Limits crossed that only warn
+    pub warnings: Vec<String>,
+    /// Limits crossed that fail the check
+    pub failures: Vec<String>,
+}
+
+impl DiskHealthCheck {
+    /// Compare `health` of `device` against the limits in `config`
+    pub fn evaluate(device: &str, health: Option<DiskHealth>, config: &DiskHealthConfig) -> Self {
+        let mut check = Self {
+            device: device.to_string(),
+            checked_at: Utc::now(),
+            health: None,
+            warnings: Vec::new(),
+            failures: Vec::new(),
+        };
+        let Some(health) = health else {
+            check.warnings.push(
+                "no SMART data; smartmontools and nvme-cli are missing or the device reports none"
+                    .to_string(),
+            );
+            return check;
+        };
+
+        let mut assessment = Vec::new();
+        if health.smart_passed == Some(false) {
+            assessment.push("drive reports SMART overall-health FAILED".to_string());
+        }
+        if let Some(warning) = health.critical_warning.filter(|w| *w != 0) {
+            assessment.push(format!("NVMe critical warning 0x{:02x}", warning));
+        }
+        if config.fail_on_self_assessment {
+            check.failures.extend(assessment);
+        } else {
+            check.warnings.extend(assessment);
+        }
+
+        for (name, limit) in config.limits() {
+            let Some(value) = health.counter(name) else {
+                continue;
+            };
+            if let Some(fail) = limit.fail.filter(|fail| value > *fail) {
+                check.failures.push(format!(
+                    "{} is {}, above the failure limit {}",
+                    name, value, fail
+                ));
+            } else if let Some(warn) = limit.warn.filter(|warn| value > *warn) {
+                check.warnings.push(format!(
+                    "{} is {}, above the warning limit {}",
+                    name, value, warn
+                ));
+            }
+        }
+        check.health = Some(health);
+        check
+    }
+
+    pub fn verdict(&self) -> HealthVerdict {
+        if !self.failures.is_empty() {
+            HealthVerdict::Failing
+        } else if self.health.is_none() {
+            HealthVerdict::Unknown
+        } else if !self.warnings.is_empty() {
+            HealthVerdict::Warning
+        } else {
+            HealthVerdict::Healthy
+        }
+    }
+
+    /// `/dev/sda: warning (reallocated_sectors is 3, above the warning limit 0)`
+    pub fn summary(&self) -> String {
+        let notes: Vec<&str> = self
+            .failures
+            .iter()
+            .chain(&self.warnings)
+            .map(String::as_str)
+            .collect();
+        if notes.is_empty() {
+            format!("{}: {}", self.device, self.verdict().as_str())
+        } else {
+            format!(
+                "{}: {} ({})",
+                self.device,
+                self.verdict().as_str(),
+                notes.join("; ")
+            )
+        }
+    }
+
+    /// Label and value pairs of the counters that were read, for reports
+    pub fn summary_rows(&self) -> Vec<(&'static str, String)> {
+        let mut rows = vec![
+            ("Device", self.device.clone()),
+            ("Verdict", self.verdict().as_str().to_string()),
+        ];
+        let Some(health) = &self.health else {
+            return rows;
+        };
+        let mut push = |label: &'static str, value: Option<String>| {
+            if let Some(value) = value {
+                rows.push((label, value));
+            }
+        };
+        push("Model", health.model.clone());
+        push("Serial", health.serial.clone());
+        push(
+            "Self-assessment",
+            health
+                .smart_passed
+                .map(|p| if p { "passed" } else { "FAILED" }.to_string()),
+        );
+        push(
+            "Critical warning",
+            health.critical_warning.map(|w| format!("0x{:02x}", w)),
+        );
+        push(
+            "Reallocated sectors",
+            health.reallocated_sectors.map(|v| v.to_string()),
+        );
+        push(
+            "Pending sectors",
+            health.pending_sectors.map(|v| v.to_string()),
+        );
+        push(
+            "Endurance used",
+            health.percentage_used.map(|v| format!("{}%", v)),
+        );
+        push("Media errors", health.media_errors.map(|v| v.to_string()));
+        push(
+            "Temperature",
+            health.temperature_c.map(|t| format!("{} C", t)),
+        );
+        push(
+            "Power-on hours",
+            health.power_on_hours.map(|h| h.to_string()),
+        );
+        rows
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const SMARTCTL_ATA: &str = r#"{
+        "smartctl": {"version": [7, 4], "exit_status": 0},
+        "model_name": "Samsung SSD 870 EVO 1TB",
+        "serial_number": "S5Y1NX0R",
+        "smart_status": {"passed": true},
+        "ata_smart_attributes": {"table": [
+            {"id": 5, "name": "Reallocated_Sector_Ct", "raw": {"value": 3}},
+            {"id": 9, "name": "Power_On_Hours", "raw": {"value": 21000}},
+            {"id": 197, "name": "Current_Pending_Sector", "raw": {"value": 0}}
+        ]},
+        "temperature": {"current": 34},
+        "power_on_time": {"hours": 21000}
+    }"#;
+
+    #[test]
+    fn test_parse_smartctl_and_nvme_cli() {
+        let health = parse_health(SMARTCTL_ATA).unwrap();
+        assert_eq!(health.source, HealthSource::Smartctl);
+        assert_eq!(health.smart_passed, Some(true));
+        assert_eq!(health.reallocated_sectors, Some(3));
+        assert_eq!(health.pending_sectors, Some(0));
+        assert_eq!(health.percentage_used, None);
+        assert_eq!(health.temperature_c, Some(34));
+
+        let nvme = parse_health(
+            r#"{"critical_warning": 0, "temperature": 310, "percent_used": 12, "media_errors": 0, "power_on_hours": 800}"#,
+        )
+        .unwrap();
+        assert_eq!(nvme.source, HealthSource::NvmeCli);
+        assert_eq!(nvme.percentage_used, Some(12));
+        assert_eq!(nvme.temperature_c, Some(37));
+
+        assert!(parse_health("").is_none());
+        assert!(parse_health(r#"{"smartctl": {"exit_status": 2}}"#).is_none());
+    }
+
+    #[test]
+    fn test_evaluate_against_limits() {
+        let config = DiskHealthConfig::default();
+        let health = parse_health(SMARTCTL_ATA).unwrap();
+        let check = DiskHealthCheck::evaluate("/dev/sda", Some(health.clone()), &config);
+        assert_eq!(check.verdict(), HealthVerdict::Warning);
+        assert_eq!(
+            check.summary(),
+            "/dev/sda: warning (reallocated_sectors is 3, above the warning limit 0)"
+        );
+
+        let worn = DiskHealth {
+            smart_passed: Some(false),
+            reallocated_sectors: Some(500),
+            ..health
+        };
+        let check = DiskHealthCheck::evaluate("/dev/sda", Some(worn.clone()), &config);
+        assert_eq!(check.verdict(), HealthVerdict::Failing);
+        assert_eq!(check.failures.len(), 2);
+
+        let lenient = DiskHealthConfig {
+            fail_on_self_assessment: false,
+            ..Default::default()
+        };
+        let check = DiskHealthCheck::evaluate("/dev/sda", Some(worn), &lenient);
+        assert_eq!(check.failures.len(), 1);
+        assert!(check.warnings[0].contains("FAILED"));
+
+        let unknown = DiskHealthCheck::evaluate("/dev/vda", None, &config);
+        assert_eq!(unknown.verdict(), HealthVerdict::Unknown);
+        assert_eq!(unknown.summary_rows().len(), 2);
+    }
+}
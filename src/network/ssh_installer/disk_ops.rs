@@ -1,21 +1,130 @@
 // file: src/network/ssh_installer/disk_ops.rs
-// version: 1.4.0
+// version: 1.11.0
 // guid: sshdisk1-2345-6789-abcd-ef0123456789
 
 //! Disk operations for SSH installation
 
+use super::capabilities::TargetCapabilities;
 use super::config::InstallationConfig;
+use crate::config::partitioning::{ExpectedPartition, PartitioningMode};
+use crate::error::AutoInstallError;
 use crate::network::SshClient;
+use crate::utils::block_device::partition_path;
 use crate::Result;
 use tracing::info;
 
+/// A partition as listed by `sgdisk -p`
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct DiskPartition {
+    pub number: u32,
+    pub size_mb: u64,
+    /// sgdisk type code, e.g. `EF00`
+    pub type_code: String,
+    pub name: String,
+}
+
+/// Parse the partition table printed by `sgdisk -p`
+pub fn parse_sgdisk_print(output: &str) -> Vec<DiskPartition> {
+    output
+        .lines()
+        .filter_map(|line| {
+            let fields: Vec<&str> = line.split_whitespace().collect();
+            let number = fields.first()?.parse().ok()?;
+            let size: f64 = fields.get(3)?.parse().ok()?;
+            let scale = match *fields.get(4)? {
+                "KiB" => 1.0 / 1024.0,
+                "MiB" => 1.0,
+                "GiB" => 1024.0,
+                "TiB" => 1024.0 * 1024.0,
+                _ => return None,
+            };
+            Some(DiskPartition {
+                number,
+                size_mb: (size * scale).round() as u64,
+                type_code: fields.get(5)?.to_string(),
+                name: fields.get(6..).unwrap_or_default().join(" "),
+            })
+        })
+        .collect()
+}
+
+/// Differences between the partitions found and those expected; empty when the layout fits
+///
+/// `fstypes` holds the `blkid` TYPE of every expected partition that declares an `fstype`.
+pub fn check_layout(
+    found: &[DiskPartition],
+    expect: &[ExpectedPartition],
+    fstypes: &[(u32, String)],
+) -> Vec<String> {
+    let mut problems = Vec::new();
+    for expected in expect {
+        let Some(partition) = found.iter().find(|p| p.number == expected.number) else {
+            problems.push(format!("partition {} is missing", expected.number));
+            continue;
+        };
+        if !partition
+            .type_code
+            .eq_ignore_ascii_case(&expected.type_code)
+        {
+            problems.push(format!(
+                "partition {} has type {} instead of {}",
+                expected.number, partition.type_code, expected.type_code
+            ));
+        }
+        if let Some(min) = expected.min_size_mb.filter(|min| partition.size_mb < *min) {
+            problems.push(format!(
+                "partition {} is {} MiB, below the {} MiB expected",
+                expected.number, partition.size_mb, min
+            ));
+        }
+        if let Some(max) = expected.max_size_mb.filter(|max| partition.size_mb > *max) {
+            problems.push(format!(
+                "partition {} is {} MiB, above the {} MiB expected",
+                expected.number, partition.size_mb, max
+            ));
+        }
+        if let Some(fstype) = &expected.fstype {
+            let actual = fstypes
+                .iter()
+                .find(|(number, _)| *number == expected.number)
+                .map(|(_, t)| t.as_str())
+                .unwrap_or_default();
+            if actual != fstype {
+                problems.push(format!(
+                    "partition {} has filesystem '{}' instead of {}",
+                    expected.number, actual, fstype
+                ));
+            }
+        }
+    }
+    problems
+}
+
 pub struct DiskManager<'a> {
     ssh: &'a mut SshClient,
+    capabilities: Option<TargetCapabilities>,
+    luks_uuid: Option<String>,
 }
 
 impl<'a> DiskManager<'a> {
     pub fn new(ssh: &'a mut SshClient) -> Self {
-        Self { ssh }
+        Self {
+            ssh,
+            capabilities: None,
+            luks_uuid: None,
+        }
+    }
+
+    /// Pick command variants for the probed live environment instead of assuming current tools
+    pub fn with_capabilities(mut self, capabilities: Option<TargetCapabilities>) -> Self {
+        self.capabilities = capabilities;
+        self
+    }
+
+    /// UUID for the new LUKS header, so a reinstall keeps the one the host had
+    pub fn with_luks_uuid(mut self, uuid: Option<String>) -> Self {
+        self.luks_uuid = uuid;
+        self
     }
 
     /// Perform complete disk preparation and partitioning
@@ -28,16 +137,77 @@ impl<'a> DiskManager<'a> {
         // Destroy existing ZFS pools
         self.destroy_existing_zfs_pools().await?;
 
-        // Wipe and partition disk
-        self.wipe_disk(config).await?;
-        self.create_partitions(config).await?;
-        self.format_partitions(config).await?;
+        // Wipe and partition disk, unless the operator brings the layout
+        match config.partitioning.mode {
+            PartitioningMode::Auto => {
+                self.wipe_disk(config).await?;
+                self.create_partitions(config).await?;
+            }
+            PartitioningMode::Existing => {
+                info!("Keeping the existing partitions on {}", config.disk_device);
+            }
+            PartitioningMode::Commands => self.run_partitioning_commands(config).await?,
+        }
+        if !config.partitioning.is_auto() {
+            self.verify_layout(config).await?;
+        }
+        if config.partitioning.format {
+            self.format_partitions(config).await?;
+        }
         self.setup_luks_encryption(config).await?;
 
         info!("Disk preparation completed successfully");
         Ok(())
     }
 
+    /// Reopen the LUKS volume of an earlier install and reformat only its ESP, for a reinstall
+    /// that keeps the pools (see [`super::reinstall`])
+    pub async fn reopen_for_reinstall(&mut self, config: &InstallationConfig) -> Result<()> {
+        info!(
+            "Reopening the existing storage on {} for a reinstall",
+            config.disk_device
+        );
+
+        // Pools go before their LUKS mapping can be closed
+        let _ = self
+            .log_and_execute(
+                "Exporting imported pools",
+                "zpool export -a 2>/dev/null || true",
+            )
+            .await;
+        self.cleanup_existing_mounts(config).await?;
+
+        let luks = partition_path(&config.disk_device, 4);
+        if !self
+            .ssh
+            .check_silent(&format!("cryptsetup isLuks {}", luks))
+            .await
+            .unwrap_or(false)
+        {
+            return Err(AutoInstallError::ValidationError(format!(
+                "{} is not a LUKS volume; a reinstall needs the storage of an earlier install",
+                luks
+            )));
+        }
+        self.log_and_execute(
+            "Opening existing LUKS device",
+            &format!("echo '{}' | cryptsetup open {} luks", config.luks_key, luks),
+        )
+        .await?;
+
+        if config.partitioning.is_auto() || config.partitioning.format {
+            self.log_and_execute(
+                "Formatting ESP (vfat)",
+                &format!(
+                    "mkfs.vfat -F32 -n ESP {}",
+                    partition_path(&config.disk_device, 1)
+                ),
+            )
+            .await?;
+        }
+        Ok(())
+    }
+
     /// Perform a robust recovery cleanup and wipe in case of prior failures
     ///
     /// This will:
@@ -52,6 +222,24 @@ impl<'a> DiskManager<'a> {
     ) -> Result<()> {
         info!("Recovery: cleaning up mounts, closing LUKS, exporting ZFS, and wiping disk");
 
+        // As an extra measure, the install's pools are destroyed if they linger after the export
+        let layout = config.pool_layout().unwrap_or_default();
+        self.release_storage(&[&layout.boot.name, &layout.root.name])
+            .await;
+
+        // Finally wipe the disk and GPT; an operator-provided layout is left alone
+        if config.partitioning.is_auto() {
+            self.wipe_disk(config).await?;
+        }
+
+        Ok(())
+    }
+
+    /// Unmount everything the install mounted, export the pools and close LUKS, best-effort
+    ///
+    /// Nothing is destroyed except `lingering_pools` still imported after the export; used on
+    /// its own to end a held session safely.
+    pub async fn release_storage(&mut self, lingering_pools: &[&str]) {
         // 1) Unmount common chroot bind mounts and EFI if present
         let _ = self
             .log_and_execute(
@@ -98,19 +286,14 @@ impl<'a> DiskManager<'a> {
             )
             .await;
 
-        // As an extra measure, try to destroy common pools if they linger
-        let _ = self
-            .log_and_execute(
-                "Recovery: destroy bpool",
-                "zpool destroy bpool 2>/dev/null || true",
-            )
-            .await;
-        let _ = self
-            .log_and_execute(
-                "Recovery: destroy rpool",
-                "zpool destroy rpool 2>/dev/null || true",
-            )
-            .await;
+        for pool in lingering_pools {
+            let _ = self
+                .log_and_execute(
+                    &format!("Recovery: destroy {}", pool),
+                    &format!("zpool destroy {} 2>/dev/null || true", pool),
+                )
+                .await;
+        }
 
         // 4) Unmount /mnt/luks if mounted
         let _ = self
@@ -132,11 +315,6 @@ impl<'a> DiskManager<'a> {
             "Recovery: close any crypt mappers",
             "for m in $(ls /dev/mapper 2>/dev/null | grep -E '^(luks|crypt)' || true); do cryptsetup close \"$m\" 2>/dev/null || true; done"
         ).await;
-
-        // 6) Finally wipe the disk and GPT
-        self.wipe_disk(config).await?;
-
-        Ok(())
     }
 
     /// Clean up existing mounts and filesystem structures
@@ -292,6 +470,56 @@ impl<'a> DiskManager<'a> {
         Ok(())
     }
 
+    /// Run the operator's partitioning commands in place of the built-in layout
+    async fn run_partitioning_commands(&mut self, config: &InstallationConfig) -> Result<()> {
+        info!("Running the configured partitioning commands");
+        for command in &config.partitioning.commands {
+            self.log_and_execute("Partitioning command", command)
+                .await?;
+        }
+        self.log_and_execute(
+            "Reload partition table",
+            &format!("partprobe {} || true", config.disk_device),
+        )
+        .await?;
+        self.log_and_execute("Settle udev", "udevadm settle || true")
+            .await?;
+        Ok(())
+    }
+
+    /// Check the layout on the disk against `partitioning.expect`
+    async fn verify_layout(&mut self, config: &InstallationConfig) -> Result<()> {
+        info!("Validating the partition layout of {}", config.disk_device);
+        let found = parse_sgdisk_print(
+            &self
+                .ssh
+                .execute_with_output(&format!("sgdisk -p {}", config.disk_device))
+                .await?,
+        );
+        let mut fstypes = Vec::new();
+        for expected in config.partitioning.expect.iter() {
+            if expected.fstype.is_some() {
+                let fstype = self
+                    .ssh
+                    .execute_with_output(&format!(
+                        "blkid -o value -s TYPE {} || true",
+                        partition_path(&config.disk_device, expected.number)
+                    ))
+                    .await?;
+                fstypes.push((expected.number, fstype.trim().to_string()));
+            }
+        }
+        let problems = check_layout(&found, &config.partitioning.expect, &fstypes);
+        if !problems.is_empty() {
+            return Err(AutoInstallError::ValidationError(format!(
+                "Partition layout of {} does not match partitioning.expect: {}",
+                config.disk_device,
+                problems.join("; ")
+            )));
+        }
+        Ok(())
+    }
+
     /// Format partitions
     async fn format_partitions(&mut self, config: &InstallationConfig) -> Result<()> {
         info!("Formatting partitions");
@@ -299,12 +527,18 @@ impl<'a> DiskManager<'a> {
         // Format ESP and RESET partitions
         self.log_and_execute(
             "Formatting ESP (vfat)",
-            &format!("mkfs.vfat -F32 -n ESP {}p1", config.disk_device),
+            &format!(
+                "mkfs.vfat -F32 -n ESP {}",
+                partition_path(&config.disk_device, 1)
+            ),
         )
         .await?;
         self.log_and_execute(
             "Formatting RESET (ext4)",
-            &format!("mkfs.ext4 -F -L RESET {}p2", config.disk_device),
+            &format!(
+                "mkfs.ext4 -F -L RESET {}",
+                partition_path(&config.disk_device, 2)
+            ),
         )
         .await?;
 
@@ -316,19 +550,31 @@ impl<'a> DiskManager<'a> {
         info!("Setting up LUKS encryption");
 
         // Setup LUKS encryption
+        let options = self
+            .capabilities
+            .as_ref()
+            .map(TargetCapabilities::luks_format_options)
+            .unwrap_or_default();
+        let options = match &self.luks_uuid {
+            Some(uuid) => format!("{}--uuid={} ", options, uuid),
+            None => options.to_string(),
+        };
         self.log_and_execute(
             "Setting up LUKS encryption",
             &format!(
-                "echo '{}' | cryptsetup luksFormat --batch-mode {}p4",
-                config.luks_key, config.disk_device
+                "echo '{}' | cryptsetup luksFormat --batch-mode {}{}",
+                config.luks_key,
+                options,
+                partition_path(&config.disk_device, 4)
             ),
         )
         .await?;
         self.log_and_execute(
             "Opening LUKS device",
             &format!(
-                "echo '{}' | cryptsetup open {}p4 luks",
-                config.luks_key, config.disk_device
+                "echo '{}' | cryptsetup open {} luks",
+                config.luks_key,
+                partition_path(&config.disk_device, 4)
             ),
         )
         .await?;
@@ -369,18 +615,19 @@ impl<'a> DiskManager<'a> {
 
     #[cfg(test)]
     fn build_mkfs_esp(disk: &str) -> String {
-        format!("mkfs.vfat -F32 -n ESP {}p1", disk)
+        format!("mkfs.vfat -F32 -n ESP {}", partition_path(disk, 1))
     }
 
     #[cfg(test)]
     fn build_mkfs_reset(disk: &str) -> String {
-        format!("mkfs.ext4 -F -L RESET {}p2", disk)
+        format!("mkfs.ext4 -F -L RESET {}", partition_path(disk, 2))
     }
 }
 
 #[cfg(test)]
 mod tests {
-    use super::DiskManager;
+    use super::*;
+    use crate::config::partitioning::standard_layout;
 
     #[test]
     fn test_sgdisk_partition_commands() {
@@ -401,4 +648,35 @@ mod tests {
             "mkfs.ext4 -F -L RESET /dev/nvme0n1p2"
         );
     }
+
+    #[test]
+    fn test_layout_check() {
+        let output = "Disk /dev/nvme0n1: 1000215216 sectors, 476.9 GiB\n\
+                      Number  Start (sector)    End (sector)  Size       Code  Name\n\
+                      1            2048         1050623   512.0 MiB   EF00  EFI System Partition\n\
+                      2         1050624         9439231   4.0 GiB     8300  RESET\n\
+                      3         9439232        13633535   2.0 GiB     BE00  BPOOL\n\
+                      4        13633536        17827839   2.0 GiB     8300  data\n";
+        let found = parse_sgdisk_print(output);
+        assert_eq!(found.len(), 4);
+        assert_eq!(found[0].size_mb, 512);
+        assert_eq!(found[0].name, "EFI System Partition");
+        assert_eq!(found[1].size_mb, 4096);
+
+        let mut expect = standard_layout();
+        expect[0].fstype = Some("vfat".to_string());
+        let problems = check_layout(&found, &expect, &[(1, "vfat".to_string())]);
+        assert_eq!(
+            problems,
+            vec![
+                "partition 4 has type 8300 instead of 8309",
+                "partition 4 is 2048 MiB, below the 8192 MiB expected",
+            ]
+        );
+        let problems = check_layout(&found[..3], &expect[..1], &[]);
+        assert_eq!(
+            problems,
+            vec!["partition 1 has filesystem '' instead of vfat"]
+        );
+    }
 }
@@ -0,0 +1,350 @@
+// file: src/network/ssh_installer/drift.rs
+// version: 1.1.0
+// guid: a47c3e91-5d28-4f6b-8e10-9b2d6c7f4a83
+
+//! Drift detection for installed hosts
+//!
+//! At the end of an installation a [`HostBaseline`] (installed packages, checksums of the
+//! files the installer manages, and the partition table) is written to
+//! `logs/<hostname>/baseline.json`. `drift-check` collects the same data from the running
+//! host and reports what changed, so hosts can be reinstalled instead of reconciled.
+
+use super::session::InstallSession;
+use crate::network::SshClient;
+use crate::Result;
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::path::{Path, PathBuf};
+use tracing::info;
+
+/// Files written by the installer, relative to the target root
+pub const MANAGED_FILES: &[&str] = &[
+    "etc/hostname",
+    "etc/hosts",
+    "etc/netplan/01-netcfg.yaml",
+    "etc/fstab",
+    "etc/crypttab",
+    "etc/apt/sources.list.d/ubuntu.sources",
+];
+
+/// Recorded state of a freshly installed host
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct HostBaseline {
+    /// Checksum of the installation config used
+    pub config_checksum: String,
+    /// Installed packages mapped to their versions
+    pub packages: BTreeMap<String, String>,
+    /// SHA256 of each managed file, keyed by path relative to the root
+    pub managed_files: BTreeMap<String, String>,
+    /// Partition table of the install disk (`sgdisk -p` table rows)
+    pub disk_layout: String,
+}
+
+impl HostBaseline {
+    /// Path of the baseline record for `hostname`
+    pub fn record_path(base_dir: &Path, hostname: &str) -> PathBuf {
+        InstallSession::host_dir(base_dir, hostname).join("baseline.json")
+    }
+
+    /// Write the baseline to `logs/<hostname>/baseline.json` under `base_dir`
+    pub fn save(&self, base_dir: &Path, hostname: &str) -> Result<PathBuf> {
+        let path = Self::record_path(base_dir, hostname);
+        if let Some(parent) = path.parent() {
+            std::fs::create_dir_all(parent)?;
+        }
+        std::fs::write(&path, serde_json::to_string_pretty(self)?)?;
+        Ok(path)
+    }
+
+    /// Load the baseline recorded for `hostname` under `base_dir`
+    pub fn load(base_dir: &Path, hostname: &str) -> Result<Self> {
+        let path = Self::record_path(base_dir, hostname);
+        let content = std::fs::read_to_string(&path).map_err(|e| {
+            crate::error::AutoInstallError::ConfigError(format!(
+                "No baseline recorded at {}: {}",
+                path.display(),
+                e
+            ))
+        })?;
+        Ok(serde_json::from_str(&content)?)
+    }
+}
+
+/// A single package difference
+#[derive(Debug, Clone, PartialEq, Serialize)]
+#[serde(tag = "change", rename_all = "snake_case")]
+pub enum PackageDrift {
+    Added {
+        name: String,
+        version: String,
+    },
+    Removed {
+        name: String,
+        version: String,
+    },
+    Changed {
+        name: String,
+        from: String,
+        to: String,
+    },
+}
+
+/// Differences between a baseline and the current host state
+#[derive(Debug, Clone, Default, PartialEq, Serialize)]
+pub struct DriftReport {
+    /// Recorded config checksum differs from the config that would be applied now
+    pub config_changed: bool,
+    /// Package additions, removals and version changes
+    pub packages: Vec<PackageDrift>,
+    /// Managed files that were modified or removed
+    pub files: Vec<String>,
+    /// Whether the partition table differs
+    pub disk_layout_changed: bool,
+}
+
+impl DriftReport {
+    /// Number of drifted items; the config checksum and disk layout count as one each
+    pub fn score(&self) -> usize {
+        self.packages.len()
+            + self.files.len()
+            + usize::from(self.config_changed)
+            + usize::from(self.disk_layout_changed)
+    }
+
+    /// Human-readable summary lines
+    pub fn summary_lines(&self) -> Vec<String> {
+        let mut lines = vec![format!("Drift score: {}", self.score())];
+        if self.config_changed {
+            lines.push("Config: checksum differs from recorded session".to_string());
+        }
+        if self.disk_layout_changed {
+            lines.push("Disk layout: partition table changed".to_string());
+        }
+        for file in &self.files {
+            lines.push(format!("File: {}", file));
+        }
+        for pkg in &self.packages {
+            lines.push(match pkg {
+                PackageDrift::Added { name, version } => {
+                    format!("Package added: {} {}", name, version)
+                }
+                PackageDrift::Removed { name, version } => {
+                    format!("Package removed: {} {}", name, version)
+                }
+                PackageDrift::Changed { name, from, to } => {
+                    format!("Package changed: {} {} -> {}", name, from, to)
+                }
+            });
+        }
+        lines
+    }
+}
+
+/// Build the dpkg query listing installed packages under `root`
+pub fn build_package_query_command(root: &str) -> String {
+    let query = "dpkg-query -W -f='${Package} ${Version}\\n'";
+    if root == "/" {
+        query.to_string()
+    } else {
+        format!("chroot {} {}", root, query)
+    }
+}
+
+/// Build the command that checksums all managed files under `root`
+pub fn build_managed_files_command(root: &str) -> String {
+    format!(
+        "cd {} && sha256sum {} 2>/dev/null || true",
+        root,
+        MANAGED_FILES.join(" ")
+    )
+}
+
+/// Build the command that prints the partition table rows of `disk`
+pub fn build_disk_layout_command(disk: &str) -> String {
+    format!("sgdisk -p {} | sed -n '/^Number/,$p'", disk)
+}
+
+/// Parse `<package> <version>` lines
+pub fn parse_package_list(output: &str) -> BTreeMap<String, String> {
+    output
+        .lines()
+        .filter_map(|line| {
+            let mut parts = line.split_whitespace();
+            Some((parts.next()?.to_string(), parts.next()?.to_string()))
+        })
+        .collect()
+}
+
+/// Parse `sha256sum` output into path -> checksum
+pub fn parse_checksums(output: &str) -> BTreeMap<String, String> {
+    output
+        .lines()
+        .filter_map(|line| {
+            let (sum, path) = line.split_once(char::is_whitespace)?;
+            Some((path.trim().to_string(), sum.to_string()))
+        })
+        .collect()
+}
+
+/// Compare a recorded baseline with the current state
+pub fn compare(baseline: &HostBaseline, current: &HostBaseline) -> DriftReport {
+    let mut packages = Vec::new();
+    for (name, version) in &baseline.packages {
+        match current.packages.get(name) {
+            None => packages.push(PackageDrift::Removed {
+                name: name.clone(),
+                version: version.clone(),
+            }),
+            Some(now) if now != version => packages.push(PackageDrift::Changed {
+                name: name.clone(),
+                from: version.clone(),
+                to: now.clone(),
+            }),
+            Some(_) => {}
+        }
+    }
+    for (name, version) in &current.packages {
+        if !baseline.packages.contains_key(name) {
+            packages.push(PackageDrift::Added {
+                name: name.clone(),
+                version: version.clone(),
+            });
+        }
+    }
+
+    let files = baseline
+        .managed_files
+        .iter()
+        .filter_map(|(path, sum)| match current.managed_files.get(path) {
+            None => Some(format!("{} (missing)", path)),
+            Some(now) if now != sum => Some(format!("{} (modified)", path)),
+            Some(_) => None,
+        })
+        .collect();
+
+    DriftReport {
+        config_changed: baseline.config_checksum != current.config_checksum,
+        packages,
+        files,
+        disk_layout_changed: baseline.disk_layout.trim() != current.disk_layout.trim(),
+    }
+}
+
+/// Collects a [`HostBaseline`] from a target over SSH
+pub struct BaselineCollector<'a> {
+    ssh: &'a mut SshClient,
+}
+
+impl<'a> BaselineCollector<'a> {
+    pub fn new(ssh: &'a mut SshClient) -> Self {
+        Self { ssh }
+    }
+
+    /// Collect packages and managed files under `root` and the partition table of `disk`
+    pub async fn collect(
+        &mut self,
+        root: &str,
+        disk: &str,
+        config_checksum: &str,
+    ) -> Result<HostBaseline> {
+        info!("Collecting host state under {} (disk {})", root, disk);
+        let packages = self
+            .ssh
+            .execute_with_output(&build_package_query_command(root))
+            .await?;
+        let files = self
+            .ssh
+            .execute_with_output(&build_managed_files_command(root))
+            .await?;
+        let layout = self
+            .ssh
+            .execute_with_output(&build_disk_layout_command(disk))
+            .await?;
+
+        Ok(HostBaseline {
+            config_checksum: config_checksum.to_string(),
+            packages: parse_package_list(&packages),
+            managed_files: parse_checksums(&files),
+            disk_layout: layout,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn baseline() -> HostBaseline {
+        HostBaseline {
+            config_checksum: "abc".into(),
+            packages: parse_package_list("zfsutils-linux 2.2.2\nopenssh-server 1:9.6\n"),
+            managed_files: parse_checksums("1111  etc/hostname\n2222  etc/fstab\n"),
+            disk_layout: "Number  Start\n 1  2048\n".into(),
+        }
+    }
+
+    #[test]
+    fn test_commands_respect_root() {
+        assert!(build_package_query_command("/").starts_with("dpkg-query"));
+        assert!(build_package_query_command("/mnt/targetos").starts_with("chroot /mnt/targetos"));
+        assert!(build_managed_files_command("/mnt/targetos").contains("etc/netplan/01-netcfg.yaml"));
+        assert!(build_disk_layout_command("/dev/sda").starts_with("sgdisk -p /dev/sda"));
+    }
+
+    #[test]
+    fn test_compare_identical_is_clean() {
+        let b = baseline();
+        let report = compare(&b, &b.clone());
+        assert_eq!(report.score(), 0);
+    }
+
+    #[test]
+    fn test_compare_detects_all_kinds_of_drift() {
+        let b = baseline();
+        let mut current = b.clone();
+        current.config_checksum = "def".into();
+        current.packages.remove("openssh-server");
+        current
+            .packages
+            .insert("zfsutils-linux".into(), "2.3.0".into());
+        current.packages.insert("nginx".into(), "1.24".into());
+        current
+            .managed_files
+            .insert("etc/fstab".into(), "9999".into());
+        current.managed_files.remove("etc/hostname");
+        current.disk_layout = "Number  Start\n 1  4096\n".into();
+
+        let report = compare(&b, &current);
+        assert!(report.config_changed);
+        assert!(report.disk_layout_changed);
+        assert_eq!(report.files.len(), 2);
+        assert_eq!(report.packages.len(), 3);
+        assert_eq!(report.score(), 7);
+        assert!(report
+            .summary_lines()
+            .iter()
+            .any(|l| l == "Package changed: zfsutils-linux 2.2.2 -> 2.3.0"));
+    }
+
+    #[test]
+    fn test_config_checksum_ignores_secrets() {
+        let config = crate::network::InstallationConfig::for_len_serv_003();
+        let mut rekeyed = config.clone();
+        rekeyed.luks_key = "different".into();
+        rekeyed.root_password = "different".into();
+        assert_eq!(config.checksum(), rekeyed.checksum());
+
+        let mut moved = config.clone();
+        moved.network_address = "172.16.3.97/23".into();
+        assert_ne!(config.checksum(), moved.checksum());
+    }
+
+    #[test]
+    fn test_baseline_save_and_load() {
+        let dir = tempfile::TempDir::new().unwrap();
+        let b = baseline();
+        b.save(dir.path(), "host-a").unwrap();
+        assert_eq!(HostBaseline::load(dir.path(), "host-a").unwrap(), b);
+        assert!(HostBaseline::load(dir.path(), "other").is_err());
+    }
+}
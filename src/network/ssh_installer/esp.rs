@@ -0,0 +1,332 @@
+// file: src/network/ssh_installer/esp.rs
+// version: 1.1.0
+// guid: 5b0f2c8e-7d41-4e9a-b3c6-2a8f91d04e37
+
+//! Redundant EFI system partitions for mirrored-boot servers
+//!
+//! The primary ESP lives on `config.disk_device` and is mounted at `/boot/efi`. Each disk
+//! listed in `config.esp_mirror_devices` receives its own ESP, mounted at `/boot/efi2`,
+//! `/boot/efi3`, ... in the target. GRUB is installed to every ESP, each one gets its own
+//! NVRAM boot entry, and a systemd path unit plus kernel hook keep the copies in sync.
+
+use super::config::InstallationConfig;
+use crate::network::SshClient;
+use crate::utils::block_device::partition_path;
+use crate::Result;
+use tracing::{info, warn};
+
+/// Boot entry label used for the primary ESP
+const PRIMARY_BOOT_LABEL: &str = "ubuntu";
+
+/// Script installed in the target that copies the primary ESP to all secondaries
+const ESP_SYNC_SCRIPT: &str = "/usr/local/sbin/uaa-esp-sync";
+
+/// Mountpoint (inside the target) of the secondary ESP at `index` (0-based)
+pub fn secondary_esp_mountpoint(index: usize) -> String {
+    format!("/boot/efi{}", index + 2)
+}
+
+/// NVRAM boot entry label of the secondary ESP at `index` (0-based)
+pub fn secondary_boot_label(index: usize) -> String {
+    format!("{}-esp{}", PRIMARY_BOOT_LABEL, index + 2)
+}
+
+/// Build the commands that partition and format a secondary ESP on `disk`
+pub fn build_secondary_esp_partition_commands(disk: &str, index: usize) -> Vec<String> {
+    vec![
+        format!("wipefs -a {}", disk),
+        format!("sgdisk --zap-all {}", disk),
+        format!("sgdisk -o {}", disk),
+        format!(
+            "sgdisk -n 1:2048:+512M -t 1:EF00 -c 1:'EFI System Partition {}' {}",
+            index + 2,
+            disk
+        ),
+        format!("partprobe {} || true", disk),
+        "udevadm settle || true".to_string(),
+        format!(
+            "mkfs.vfat -F32 -n ESP{} {}",
+            index + 2,
+            partition_path(disk, 1)
+        ),
+    ]
+}
+
+/// Build the commands that mount a secondary ESP and register it in the target fstab
+pub fn build_secondary_esp_mount_commands(disk: &str, index: usize) -> Vec<String> {
+    let mountpoint = secondary_esp_mountpoint(index);
+    vec![
+        format!("mkdir -p /mnt/targetos{}", mountpoint),
+        format!(
+            "mountpoint -q /mnt/targetos{m} || mount {p} /mnt/targetos{m}",
+            m = mountpoint,
+            p = partition_path(disk, 1)
+        ),
+        // nofail so a dead mirror disk never blocks boot
+        format!(
+            "bash -lc 'UUID=$(blkid -s UUID -o value {p} 2>/dev/null || true); if [ -n \"$UUID\" ] && ! grep -q \" {m} \" /mnt/targetos/etc/fstab; then echo \"UUID=$UUID {m} vfat umask=0077,nofail 0 1\" >> /mnt/targetos/etc/fstab; fi'",
+            p = partition_path(disk, 1),
+            m = mountpoint
+        ),
+    ]
+}
+
+/// Build the commands that install GRUB to a secondary ESP and add its NVRAM entry
+///
+/// GRUB is installed with `--no-nvram` so the primary `ubuntu` entry is left untouched;
+/// the entry for this disk is then created explicitly with efibootmgr.
+pub fn build_secondary_grub_commands(disk: &str, index: usize) -> Vec<String> {
+    let mountpoint = secondary_esp_mountpoint(index);
+    let label = secondary_boot_label(index);
+    vec![
+        format!(
+            "chroot /mnt/targetos bash -lc 'grub-install --target=x86_64-efi --efi-directory={} --bootloader-id={} --recheck --no-nvram'",
+            mountpoint, PRIMARY_BOOT_LABEL
+        ),
+        format!(
+            "chroot /mnt/targetos bash -lc 'efibootmgr | grep -qw {l} || efibootmgr -c -d {d} -p 1 -L {l} -l \"\\\\EFI\\\\{b}\\\\shimx64.efi\"'",
+            l = label,
+            d = disk,
+            b = PRIMARY_BOOT_LABEL
+        ),
+    ]
+}
+
+/// Render the sync script that mirrors the primary ESP onto each secondary ESP
+pub fn build_esp_sync_script(mountpoints: &[String]) -> String {
+    format!(
+        "#!/bin/sh\n# Managed by ubuntu-autoinstall-agent: keep secondary ESPs in sync with /boot/efi\nset -e\nfor mp in {}; do\n  mountpoint -q \"$mp\" || mount \"$mp\" || {{ echo \"skipping $mp (not mounted)\" >&2; continue; }}\n  rsync -rt --delete /boot/efi/ \"$mp\"/\ndone\n",
+        mountpoints.join(" ")
+    )
+}
+
+/// Render the systemd path unit that triggers a sync when the primary ESP changes
+pub fn build_esp_sync_path_unit() -> String {
+    format!(
+        "[Unit]\nDescription=Watch the primary ESP for bootloader changes\n\n[Path]\nPathChanged=/boot/efi/EFI/{}\nPathChanged=/boot/efi/EFI/BOOT\nUnit=uaa-esp-sync.service\n\n[Install]\nWantedBy=multi-user.target\n",
+        PRIMARY_BOOT_LABEL
+    )
+}
+
+/// Render the oneshot service that runs the sync script
+pub fn build_esp_sync_service_unit() -> String {
+    format!(
+        "[Unit]\nDescription=Sync the primary ESP to secondary ESPs\nRequiresMountsFor=/boot/efi\n\n[Service]\nType=oneshot\nExecStart={}\n",
+        ESP_SYNC_SCRIPT
+    )
+}
+
+/// Render the kernel postinst hook so kernel/GRUB updates are mirrored immediately
+pub fn build_kernel_postinst_hook() -> String {
+    format!("#!/bin/sh\n{} || true\n", ESP_SYNC_SCRIPT)
+}
+
+/// Return the expected boot labels that are missing from `efibootmgr` output
+pub fn find_missing_boot_entries(efibootmgr_output: &str, expected: &[String]) -> Vec<String> {
+    expected
+        .iter()
+        .filter(|label| {
+            !efibootmgr_output.lines().any(|line| {
+                // Entries look like: "Boot0003* ubuntu-esp2\tHD(1,GPT,...)"
+                line.starts_with("Boot")
+                    && line
+                        .split_once(' ')
+                        .map(|(_, rest)| {
+                            rest.split('\t').next().unwrap_or("").trim() == label.as_str()
+                        })
+                        .unwrap_or(false)
+            })
+        })
+        .cloned()
+        .collect()
+}
+
+/// Sets up and verifies redundant ESPs on mirror disks
+pub struct RedundantEspManager<'a> {
+    ssh: &'a mut SshClient,
+}
+
+impl<'a> RedundantEspManager<'a> {
+    pub fn new(ssh: &'a mut SshClient) -> Self {
+        Self { ssh }
+    }
+
+    /// Partition and format an ESP on every mirror disk
+    pub async fn prepare_secondary_esps(&mut self, config: &InstallationConfig) -> Result<()> {
+        for (index, disk) in config.esp_mirror_devices.iter().enumerate() {
+            info!("Creating secondary ESP {} on {}", index + 2, disk);
+            for cmd in build_secondary_esp_partition_commands(disk, index) {
+                self.log_and_execute("Prepare secondary ESP", &cmd).await?;
+            }
+        }
+        Ok(())
+    }
+
+    /// Mount the secondary ESPs, install GRUB to each and install the sync hook
+    pub async fn install_bootloaders(&mut self, config: &InstallationConfig) -> Result<()> {
+        if config.esp_mirror_devices.is_empty() {
+            return Ok(());
+        }
+
+        let mut mountpoints = Vec::new();
+        for (index, disk) in config.esp_mirror_devices.iter().enumerate() {
+            for cmd in build_secondary_esp_mount_commands(disk, index) {
+                self.log_and_execute("Mount secondary ESP", &cmd).await?;
+            }
+            for cmd in build_secondary_grub_commands(disk, index) {
+                self.log_and_execute("Install GRUB to secondary ESP", &cmd)
+                    .await?;
+            }
+            mountpoints.push(secondary_esp_mountpoint(index));
+        }
+
+        self.install_sync_hook(&mountpoints).await
+    }
+
+    /// Write the sync script, systemd units and kernel hook into the target
+    async fn install_sync_hook(&mut self, mountpoints: &[String]) -> Result<()> {
+        info!("Installing ESP sync hook for {}", mountpoints.join(", "));
+
+        self.log_and_execute(
+            "Install rsync for ESP sync",
+            "chroot /mnt/targetos bash -lc 'DEBIAN_FRONTEND=noninteractive apt install -y rsync'",
+        )
+        .await?;
+
+        let files = [
+            (
+                ESP_SYNC_SCRIPT.to_string(),
+                build_esp_sync_script(mountpoints),
+            ),
+            (
+                "/etc/systemd/system/uaa-esp-sync.path".to_string(),
+                build_esp_sync_path_unit(),
+            ),
+            (
+                "/etc/systemd/system/uaa-esp-sync.service".to_string(),
+                build_esp_sync_service_unit(),
+            ),
+            (
+                "/etc/kernel/postinst.d/zz-uaa-esp-sync".to_string(),
+                build_kernel_postinst_hook(),
+            ),
+        ];
+        for (path, content) in files {
+            self.ssh
+                .execute(&format!(
+                    "mkdir -p $(dirname /mnt/targetos{p}) && cat > /mnt/targetos{p} << 'EOF'\n{c}EOF",
+                    p = path,
+                    c = content
+                ))
+                .await?;
+        }
+
+        self.log_and_execute(
+            "Make ESP sync hooks executable",
+            &format!(
+                "chmod 0755 /mnt/targetos{} /mnt/targetos/etc/kernel/postinst.d/zz-uaa-esp-sync",
+                ESP_SYNC_SCRIPT
+            ),
+        )
+        .await?;
+        self.log_and_execute(
+            "Enable ESP sync path unit",
+            "chroot /mnt/targetos bash -lc 'systemctl enable uaa-esp-sync.path'",
+        )
+        .await?;
+        // Seed the secondaries once so they match the primary right after install
+        self.log_and_execute(
+            "Initial ESP sync",
+            &format!("chroot /mnt/targetos {}", ESP_SYNC_SCRIPT),
+        )
+        .await?;
+
+        Ok(())
+    }
+
+    /// Verify an NVRAM boot entry exists for the primary and every secondary ESP
+    pub async fn verify_boot_entries(&mut self, config: &InstallationConfig) -> Result<()> {
+        if config.esp_mirror_devices.is_empty() {
+            return Ok(());
+        }
+
+        let output = self
+            .ssh
+            .execute_with_output("efibootmgr 2>/dev/null || true")
+            .await?;
+        let mut expected = vec![PRIMARY_BOOT_LABEL.to_string()];
+        expected.extend((0..config.esp_mirror_devices.len()).map(secondary_boot_label));
+
+        let missing = find_missing_boot_entries(&output, &expected);
+        if missing.is_empty() {
+            info!("Boot entries present for all ESPs: {}", expected.join(", "));
+            Ok(())
+        } else {
+            warn!("efibootmgr output:\n{}", output);
+            Err(crate::error::AutoInstallError::InstallationError(format!(
+                "Missing UEFI boot entries: {}",
+                missing.join(", ")
+            )))
+        }
+    }
+
+    /// Helper method to log and execute commands
+    async fn log_and_execute(&mut self, description: &str, command: &str) -> Result<()> {
+        info!("Executing: {} -> {}", description, command);
+        self.ssh.execute(command).await
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_secondary_names() {
+        assert_eq!(secondary_esp_mountpoint(0), "/boot/efi2");
+        assert_eq!(secondary_boot_label(1), "ubuntu-esp3");
+    }
+
+    #[test]
+    fn test_secondary_partition_commands() {
+        let cmds = build_secondary_esp_partition_commands("/dev/nvme1n1", 0);
+        assert!(cmds
+            .iter()
+            .any(|c| c.contains("-t 1:EF00") && c.ends_with("/dev/nvme1n1")));
+        assert_eq!(
+            cmds.last().unwrap(),
+            "mkfs.vfat -F32 -n ESP2 /dev/nvme1n1p1"
+        );
+    }
+
+    #[test]
+    fn test_secondary_grub_commands_keep_primary_nvram_entry() {
+        let cmds = build_secondary_grub_commands("/dev/nvme1n1", 0);
+        assert!(cmds[0].contains("--efi-directory=/boot/efi2"));
+        assert!(cmds[0].contains("--no-nvram"));
+        assert!(cmds[1].contains("efibootmgr -c -d /dev/nvme1n1 -p 1 -L ubuntu-esp2"));
+    }
+
+    #[test]
+    fn test_sync_script_lists_all_mountpoints() {
+        let script = build_esp_sync_script(&["/boot/efi2".to_string(), "/boot/efi3".to_string()]);
+        assert!(script.starts_with("#!/bin/sh"));
+        assert!(script.contains("for mp in /boot/efi2 /boot/efi3; do"));
+        assert!(script.contains("rsync -rt --delete /boot/efi/"));
+        assert!(build_esp_sync_path_unit().contains("Unit=uaa-esp-sync.service"));
+    }
+
+    #[test]
+    fn test_find_missing_boot_entries() {
+        let output = "BootCurrent: 0001\nBootOrder: 0001,0002\nBoot0001* ubuntu\tHD(1,GPT,aaaa)\nBoot0002* ubuntu-esp2\tHD(1,GPT,bbbb)\n";
+        let expected = vec![
+            "ubuntu".to_string(),
+            "ubuntu-esp2".to_string(),
+            "ubuntu-esp3".to_string(),
+        ];
+        assert_eq!(
+            find_missing_boot_entries(output, &expected),
+            vec!["ubuntu-esp3".to_string()]
+        );
+    }
+}
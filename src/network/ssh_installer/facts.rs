@@ -0,0 +1,637 @@
+// file: src/network/ssh_installer/facts.rs
+// version: 1.1.0
+// guid: 3d8f1b52-7e4a-4c19-a6d0-9b2e5f7c8a41
+
+//! Typed facts about a target, read from structured tool output
+//!
+//! `lsblk -J`, `ip -j` and `lscpu -J` are preferred. Older util-linux and iproute2 releases
+//! without JSON output fall back to `lsblk -P`, `ip -o` and plain `lscpu`, so preflight,
+//! validation and reports see the same typed data on every live image.
+
+use super::investigation_report::{
+    parse_ip_json, parse_lsblk_json, DiskReport, InterfaceReport, PartitionReport, IP_ADDR_COMMAND,
+    LSBLK_COMMAND,
+};
+use crate::config::interpolate::{FactValue, FactVars};
+use crate::error::AutoInstallError;
+use crate::Result;
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use std::collections::HashMap;
+use tracing::{debug, warn};
+
+/// CPU summary as JSON
+pub const LSCPU_COMMAND: &str = "lscpu -J";
+/// Default route as JSON
+pub const IP_ROUTE_COMMAND: &str = "ip -j route show default";
+/// Memory totals; present on every kernel
+pub const MEMINFO_COMMAND: &str = "cat /proc/meminfo";
+
+/// Fallbacks for tools that predate JSON output
+const LSBLK_PAIRS_COMMAND: &str =
+    "lsblk -P -b -o NAME,TYPE,SIZE,MODEL,SERIAL,ROTA,TRAN,FSTYPE,MOUNTPOINT,PKNAME";
+const IP_ADDR_ONELINE_COMMAND: &str = "ip -o addr show";
+const IP_LINK_ONELINE_COMMAND: &str = "ip -o link show";
+const IP_ROUTE_TEXT_COMMAND: &str = "ip route show default";
+const LSCPU_TEXT_COMMAND: &str = "lscpu";
+
+/// Processor summary
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct CpuFacts {
+    pub architecture: Option<String>,
+    pub model_name: Option<String>,
+    /// Logical CPUs
+    pub cpus: u32,
+    pub sockets: Option<u32>,
+    pub threads_per_core: Option<u32>,
+    /// Hardware virtualization extension (VT-x, AMD-V)
+    pub virtualization: Option<String>,
+    /// Hypervisor vendor when running as a guest
+    pub hypervisor: Option<String>,
+}
+
+/// The route used for traffic leaving the local network
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct DefaultRoute {
+    pub interface: String,
+    pub gateway: Option<String>,
+}
+
+/// Everything known about a target's hardware and network
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct TargetFacts {
+    pub disks: Vec<DiskReport>,
+    pub interfaces: Vec<InterfaceReport>,
+    pub default_route: Option<DefaultRoute>,
+    pub cpu: Option<CpuFacts>,
+    pub memory_total_mb: Option<u64>,
+}
+
+impl TargetFacts {
+    /// Disk by device path (`/dev/nvme0n1`) or kernel name (`nvme0n1`)
+    pub fn disk(&self, device: &str) -> Option<&DiskReport> {
+        let name = device.trim_start_matches("/dev/");
+        self.disks
+            .iter()
+            .find(|d| d.path == device || d.name == name)
+    }
+
+    /// Interface by name
+    pub fn interface(&self, name: &str) -> Option<&InterfaceReport> {
+        self.interfaces.iter().find(|i| i.name == name)
+    }
+
+    /// Largest disk with nothing mounted
+    pub fn primary_disk(&self) -> Option<&DiskReport> {
+        self.disks
+            .iter()
+            .filter(|d| !d.in_use())
+            .max_by_key(|d| d.size_bytes)
+    }
+
+    /// Interface carrying the default route, else the first non-loopback interface with an address
+    pub fn primary_interface(&self) -> Option<&InterfaceReport> {
+        self.default_route
+            .as_ref()
+            .and_then(|route| self.interface(&route.interface))
+            .or_else(|| {
+                self.interfaces
+                    .iter()
+                    .find(|i| i.name != "lo" && !i.addresses.is_empty())
+            })
+    }
+
+    /// Variables for `{{ facts.* }}` config templates; facts that were not discovered are left out
+    pub fn template_vars(&self) -> FactVars {
+        let mut vars = FactVars::new();
+        let mut text = |name: &str, value: &str| {
+            vars.insert(name.to_string(), FactValue::Text(value.to_string()));
+        };
+        let free: Vec<&DiskReport> = self.disks.iter().filter(|d| !d.in_use()).collect();
+        let largest = |keep: &dyn Fn(&DiskReport) -> bool| {
+            free.iter()
+                .filter(|d| keep(d))
+                .max_by_key(|d| d.size_bytes)
+                .map(|d| d.path.clone())
+        };
+        let is_nvme =
+            |d: &DiskReport| d.transport.as_deref() == Some("nvme") || d.name.starts_with("nvme");
+        if let Some(path) = largest(&|_| true) {
+            text("largest_disk", &path);
+        }
+        if let Some(path) = largest(&is_nvme) {
+            text("largest_nvme", &path);
+        }
+        if let Some(path) = largest(&|d| !d.rotational) {
+            text("largest_ssd", &path);
+        }
+        if let Some(path) = largest(&|d| d.rotational) {
+            text("largest_hdd", &path);
+        }
+        if let Some(iface) = self.primary_interface() {
+            text("primary_interface", &iface.name);
+            if let Some(mac) = &iface.mac {
+                text("primary_mac", mac);
+            }
+            if let Some(address) = iface.addresses.first() {
+                text("primary_address", address);
+                text("primary_ip", address.split('/').next().unwrap_or(address));
+            }
+        }
+        if let Some(gateway) = self
+            .default_route
+            .as_ref()
+            .and_then(|r| r.gateway.as_deref())
+        {
+            text("gateway", gateway);
+        }
+        if let Some(architecture) = self.cpu.as_ref().and_then(|c| c.architecture.as_deref()) {
+            text("architecture", architecture);
+        }
+        vars.insert("disk_count".to_string(), FactValue::Int(free.len() as i64));
+        if let Some(cpu) = &self.cpu {
+            vars.insert("cpus".to_string(), FactValue::Int(cpu.cpus as i64));
+        }
+        if let Some(memory_mb) = self.memory_total_mb {
+            vars.insert("memory_mb".to_string(), FactValue::Int(memory_mb as i64));
+        }
+        vars
+    }
+
+    /// Check that `disk_device` and `interface` exist on the target
+    ///
+    /// Returns warnings for suspicious but non-fatal findings. Facts that could not be
+    /// collected at all are not held against the target.
+    pub fn check_install_target(&self, disk_device: &str, interface: &str) -> Result<Vec<String>> {
+        let mut warnings = Vec::new();
+        if !self.disks.is_empty() {
+            let disk = self.disk(disk_device).ok_or_else(|| {
+                AutoInstallError::ValidationError(format!(
+                    "Install disk {} not found on target (disks: {})",
+                    disk_device,
+                    self.disks
+                        .iter()
+                        .map(|d| d.path.as_str())
+                        .collect::<Vec<_>>()
+                        .join(", ")
+                ))
+            })?;
+            for part in &disk.partitions {
+                if let Some(mountpoint) = part
+                    .mountpoint
+                    .as_deref()
+                    .filter(|m| !m.starts_with("/mnt/targetos"))
+                {
+                    warnings.push(format!(
+                        "{} is mounted at {} and will be wiped",
+                        part.name, mountpoint
+                    ));
+                }
+            }
+        }
+        if !self.interfaces.is_empty() && self.interface(interface).is_none() {
+            return Err(AutoInstallError::ValidationError(format!(
+                "Network interface {} not found on target (interfaces: {})",
+                interface,
+                self.interfaces
+                    .iter()
+                    .map(|i| i.name.as_str())
+                    .collect::<Vec<_>>()
+                    .join(", ")
+            )));
+        }
+        Ok(warnings)
+    }
+}
+
+/// Split a `lsblk -P` line into its `KEY="value"` pairs
+fn parse_pairs(line: &str) -> HashMap<String, String> {
+    let mut pairs = HashMap::new();
+    let mut rest = line.trim();
+    while let Some(eq) = rest.find("=\"") {
+        let key = rest[..eq].trim().to_string();
+        let after = &rest[eq + 2..];
+        let Some(end) = after.find('"') else { break };
+        // lsblk escapes embedded quotes and spaces as \x22 and \x20
+        let value = after[..end].replace("\\x22", "\"").replace("\\x20", " ");
+        pairs.insert(key, value.trim().to_string());
+        rest = &after[end + 1..];
+    }
+    pairs
+}
+
+/// Parse `lsblk -P` output (util-linux without `-J`) into disks with their partitions
+pub fn parse_lsblk_pairs(output: &str) -> Vec<DiskReport> {
+    let rows: Vec<HashMap<String, String>> = output
+        .lines()
+        .map(parse_pairs)
+        .filter(|row| !row.is_empty())
+        .collect();
+    let field =
+        |row: &HashMap<String, String>, key: &str| row.get(key).filter(|v| !v.is_empty()).cloned();
+
+    rows.iter()
+        .filter(|row| field(row, "TYPE").as_deref() == Some("disk"))
+        .map(|disk| {
+            let name = field(disk, "NAME").unwrap_or_default();
+            let partitions = rows
+                .iter()
+                .filter(|row| field(row, "PKNAME").as_deref() == Some(name.as_str()))
+                .map(|row| PartitionReport {
+                    name: field(row, "NAME").unwrap_or_default(),
+                    size_bytes: field(row, "SIZE").and_then(|s| s.parse().ok()).unwrap_or(0),
+                    fstype: field(row, "FSTYPE"),
+                    mountpoint: field(row, "MOUNTPOINT"),
+                })
+                .collect();
+            DiskReport {
+                path: format!("/dev/{}", name),
+                size_bytes: field(disk, "SIZE")
+                    .and_then(|s| s.parse().ok())
+                    .unwrap_or(0),
+                model: field(disk, "MODEL"),
+                serial: field(disk, "SERIAL"),
+                rotational: field(disk, "ROTA").as_deref() == Some("1"),
+                transport: field(disk, "TRAN"),
+                name,
+                partitions,
+            }
+        })
+        .collect()
+}
+
+/// Parse `ip -o link show` and `ip -o addr show` (iproute2 without `-j`)
+pub fn parse_ip_oneline(link_output: &str, addr_output: &str) -> Vec<InterfaceReport> {
+    let iface_name = |token: &str| {
+        let name = token.trim_end_matches(':');
+        name.split('@').next().unwrap_or(name).to_string()
+    };
+    let mut interfaces: Vec<InterfaceReport> = link_output
+        .lines()
+        .filter_map(|line| {
+            let tokens: Vec<&str> = line.split_whitespace().collect();
+            let after = |key: &str| {
+                tokens
+                    .iter()
+                    .position(|t| *t == key)
+                    .and_then(|i| tokens.get(i + 1))
+                    .map(|v| v.to_string())
+            };
+            Some(InterfaceReport {
+                name: iface_name(tokens.get(1)?),
+                mac: after("link/ether"),
+                state: after("state"),
+                addresses: Vec::new(),
+            })
+        })
+        .collect();
+
+    for line in addr_output.lines() {
+        let tokens: Vec<&str> = line.split_whitespace().collect();
+        let (Some(name), Some(family), Some(address)) =
+            (tokens.get(1), tokens.get(2), tokens.get(3))
+        else {
+            continue;
+        };
+        if !family.starts_with("inet") {
+            continue;
+        }
+        let name = iface_name(name);
+        match interfaces.iter_mut().find(|i| i.name == name) {
+            Some(iface) => iface.addresses.push(address.to_string()),
+            None => interfaces.push(InterfaceReport {
+                name,
+                mac: None,
+                state: None,
+                addresses: vec![address.to_string()],
+            }),
+        }
+    }
+    interfaces
+}
+
+/// Parse `ip -j route show default`
+pub fn parse_route_json(output: &str) -> Result<Option<DefaultRoute>> {
+    let parsed: Value = serde_json::from_str(output.trim())?;
+    Ok(parsed.as_array().and_then(|routes| {
+        routes.iter().find_map(|route| {
+            Some(DefaultRoute {
+                interface: route.get("dev")?.as_str()?.to_string(),
+                gateway: route
+                    .get("gateway")
+                    .and_then(Value::as_str)
+                    .map(str::to_string),
+            })
+        })
+    }))
+}
+
+/// Parse `ip route show default` (`default via 10.0.0.1 dev eno1 ...`)
+pub fn parse_route_text(output: &str) -> Option<DefaultRoute> {
+    output.lines().find_map(|line| {
+        let tokens: Vec<&str> = line.split_whitespace().collect();
+        let after = |key: &str| {
+            tokens
+                .iter()
+                .position(|t| *t == key)
+                .and_then(|i| tokens.get(i + 1))
+                .map(|v| v.to_string())
+        };
+        Some(DefaultRoute {
+            interface: after("dev")?,
+            gateway: after("via"),
+        })
+    })
+}
+
+/// Parse `lscpu -J` (flat or nested) or plain `lscpu` output
+pub fn parse_lscpu(output: &str) -> CpuFacts {
+    fn flatten(entries: &[Value], fields: &mut HashMap<String, String>) {
+        for entry in entries {
+            if let (Some(field), Some(data)) = (
+                entry.get("field").and_then(Value::as_str),
+                entry.get("data").and_then(Value::as_str),
+            ) {
+                fields
+                    .entry(field.trim_end_matches(':').trim().to_string())
+                    .or_insert_with(|| data.trim().to_string());
+            }
+            if let Some(children) = entry.get("children").and_then(Value::as_array) {
+                flatten(children, fields);
+            }
+        }
+    }
+
+    let mut fields = HashMap::new();
+    match serde_json::from_str::<Value>(output.trim()) {
+        Ok(json) => {
+            if let Some(entries) = json.get("lscpu").and_then(Value::as_array) {
+                flatten(entries, &mut fields);
+            }
+        }
+        Err(_) => {
+            for line in output.lines() {
+                if let Some((key, value)) = line.split_once(':') {
+                    fields
+                        .entry(key.trim().to_string())
+                        .or_insert_with(|| value.trim().to_string());
+                }
+            }
+        }
+    }
+
+    let text = |key: &str| fields.get(key).filter(|v| !v.is_empty()).cloned();
+    let number = |key: &str| fields.get(key).and_then(|v| v.parse::<u32>().ok());
+    CpuFacts {
+        architecture: text("Architecture"),
+        model_name: text("Model name"),
+        cpus: number("CPU(s)").unwrap_or(0),
+        sockets: number("Socket(s)"),
+        threads_per_core: number("Thread(s) per core"),
+        virtualization: text("Virtualization"),
+        hypervisor: text("Hypervisor vendor"),
+    }
+}
+
+/// Total memory in MiB from `/proc/meminfo`
+pub fn parse_meminfo_total_mb(output: &str) -> Option<u64> {
+    output.lines().find_map(|line| {
+        let kb: u64 = line
+            .strip_prefix("MemTotal:")?
+            .split_whitespace()
+            .next()?
+            .parse()
+            .ok()?;
+        Some(kb / 1024)
+    })
+}
+
+/// Collects [`TargetFacts`] through any command executor
+pub struct FactsCollector<'a, T> {
+    executor: &'a mut T,
+}
+
+impl<'a, T> FactsCollector<'a, T>
+where
+    T: crate::network::CommandExecutor,
+{
+    pub fn new(executor: &'a mut T) -> Self {
+        Self { executor }
+    }
+
+    /// Output of `command`, or `None` when it fails
+    async fn output(&mut self, command: &str) -> Option<String> {
+        match self.executor.execute_with_output(command).await {
+            Ok(out) => Some(out),
+            Err(e) => {
+                debug!("{} failed: {}", command, e);
+                None
+            }
+        }
+    }
+
+    /// Collect all facts; anything that cannot be read is left empty
+    pub async fn collect(&mut self) -> TargetFacts {
+        TargetFacts {
+            disks: self.disks().await,
+            interfaces: self.interfaces().await,
+            default_route: self.default_route().await,
+            cpu: self.cpu().await,
+            memory_total_mb: self
+                .output(MEMINFO_COMMAND)
+                .await
+                .and_then(|out| parse_meminfo_total_mb(&out)),
+        }
+    }
+
+    async fn disks(&mut self) -> Vec<DiskReport> {
+        if let Some(out) = self.output(LSBLK_COMMAND).await {
+            match parse_lsblk_json(&out) {
+                Ok(disks) => return disks,
+                Err(e) => debug!("lsblk -J output not usable: {}", e),
+            }
+        }
+        match self.output(LSBLK_PAIRS_COMMAND).await {
+            Some(out) => parse_lsblk_pairs(&out),
+            None => {
+                warn!("Could not list block devices on target");
+                Vec::new()
+            }
+        }
+    }
+
+    async fn interfaces(&mut self) -> Vec<InterfaceReport> {
+        if let Some(out) = self.output(IP_ADDR_COMMAND).await {
+            match parse_ip_json(&out) {
+                Ok(interfaces) => return interfaces,
+                Err(e) => debug!("ip -j output not usable: {}", e),
+            }
+        }
+        match (
+            self.output(IP_LINK_ONELINE_COMMAND).await,
+            self.output(IP_ADDR_ONELINE_COMMAND).await,
+        ) {
+            (Some(links), Some(addrs)) => parse_ip_oneline(&links, &addrs),
+            _ => {
+                warn!("Could not list network interfaces on target");
+                Vec::new()
+            }
+        }
+    }
+
+    async fn default_route(&mut self) -> Option<DefaultRoute> {
+        if let Some(out) = self.output(IP_ROUTE_COMMAND).await {
+            if let Ok(route) = parse_route_json(&out) {
+                return route;
+            }
+        }
+        self.output(IP_ROUTE_TEXT_COMMAND)
+            .await
+            .and_then(|out| parse_route_text(&out))
+    }
+
+    async fn cpu(&mut self) -> Option<CpuFacts> {
+        let out = match self.output(LSCPU_COMMAND).await {
+            Some(out) => out,
+            None => self.output(LSCPU_TEXT_COMMAND).await?,
+        };
+        Some(parse_lscpu(&out))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_template_vars() {
+        let out = concat!(
+            "NAME=\"sda\" TYPE=\"disk\" SIZE=\"4000000000000\" MODEL=\"\" SERIAL=\"\" ROTA=\"1\" TRAN=\"sata\" FSTYPE=\"\" MOUNTPOINT=\"\" PKNAME=\"\"\n",
+            "NAME=\"nvme0n1\" TYPE=\"disk\" SIZE=\"512000000000\" MODEL=\"\" SERIAL=\"\" ROTA=\"0\" TRAN=\"nvme\" FSTYPE=\"\" MOUNTPOINT=\"\" PKNAME=\"\"\n",
+            "NAME=\"nvme1n1\" TYPE=\"disk\" SIZE=\"1024000000000\" MODEL=\"\" SERIAL=\"\" ROTA=\"0\" TRAN=\"nvme\" FSTYPE=\"\" MOUNTPOINT=\"\" PKNAME=\"\"\n",
+            "NAME=\"nvme1n1p1\" TYPE=\"part\" SIZE=\"536870912\" MODEL=\"\" SERIAL=\"\" ROTA=\"0\" TRAN=\"\" FSTYPE=\"vfat\" MOUNTPOINT=\"/boot/efi\" PKNAME=\"nvme1n1\"\n",
+        );
+        let facts = TargetFacts {
+            disks: parse_lsblk_pairs(out),
+            memory_total_mb: Some(32768),
+            ..Default::default()
+        };
+        let vars = facts.template_vars();
+        let text = |name: &str| vars.get(name).map(|v| v.to_string());
+        assert_eq!(text("largest_disk").as_deref(), Some("/dev/sda"));
+        // nvme1n1 is larger but mounted
+        assert_eq!(text("largest_nvme").as_deref(), Some("/dev/nvme0n1"));
+        assert_eq!(text("largest_ssd").as_deref(), Some("/dev/nvme0n1"));
+        assert_eq!(text("largest_hdd").as_deref(), Some("/dev/sda"));
+        assert_eq!(vars.get("memory_mb"), Some(&FactValue::Int(32768)));
+        assert_eq!(vars.get("disk_count"), Some(&FactValue::Int(2)));
+        assert!(!vars.contains_key("cpus"));
+        assert!(!vars.contains_key("primary_interface"));
+    }
+
+    #[test]
+    fn test_lsblk_pairs_fallback() {
+        let out = concat!(
+            "NAME=\"sda\" TYPE=\"disk\" SIZE=\"2000398934016\" MODEL=\"WDC\\x20WD20\" SERIAL=\"WD-123\" ROTA=\"1\" TRAN=\"sata\" FSTYPE=\"\" MOUNTPOINT=\"\" PKNAME=\"\"\n",
+            "NAME=\"sda1\" TYPE=\"part\" SIZE=\"536870912\" MODEL=\"\" SERIAL=\"\" ROTA=\"1\" TRAN=\"\" FSTYPE=\"vfat\" MOUNTPOINT=\"/boot/efi\" PKNAME=\"sda\"\n",
+            "NAME=\"loop0\" TYPE=\"loop\" SIZE=\"1000\" MODEL=\"\" SERIAL=\"\" ROTA=\"0\" TRAN=\"\" FSTYPE=\"squashfs\" MOUNTPOINT=\"/rofs\" PKNAME=\"\"\n",
+        );
+        let disks = parse_lsblk_pairs(out);
+        assert_eq!(disks.len(), 1);
+        assert_eq!(disks[0].path, "/dev/sda");
+        assert_eq!(disks[0].model.as_deref(), Some("WDC WD20"));
+        assert!(disks[0].rotational);
+        assert_eq!(
+            disks[0].partitions[0].mountpoint.as_deref(),
+            Some("/boot/efi")
+        );
+        assert!(disks[0].in_use());
+    }
+
+    #[test]
+    fn test_ip_oneline_fallback_and_routes() {
+        let links = "1: lo: <LOOPBACK,UP,LOWER_UP> mtu 65536 qdisc noqueue state UNKNOWN mode DEFAULT group default qlen 1000\\    link/loopback 00:00:00:00:00:00 brd 00:00:00:00:00:00\n\
+                     2: eno1: <BROADCAST,MULTICAST,UP,LOWER_UP> mtu 1500 qdisc fq_codel state UP mode DEFAULT group default qlen 1000\\    link/ether aa:bb:cc:dd:ee:ff brd ff:ff:ff:ff:ff:ff\n";
+        let addrs = "1: lo    inet 127.0.0.1/8 scope host lo\\       valid_lft forever preferred_lft forever\n\
+                     2: eno1    inet 172.16.3.96/23 brd 172.16.3.255 scope global eno1\\       valid_lft forever preferred_lft forever\n";
+        let ifaces = parse_ip_oneline(links, addrs);
+        assert_eq!(ifaces.len(), 2);
+        assert_eq!(ifaces[1].name, "eno1");
+        assert_eq!(ifaces[1].mac.as_deref(), Some("aa:bb:cc:dd:ee:ff"));
+        assert_eq!(ifaces[1].state.as_deref(), Some("UP"));
+        assert_eq!(ifaces[1].addresses, vec!["172.16.3.96/23".to_string()]);
+
+        let expected = Some(DefaultRoute {
+            interface: "eno1".into(),
+            gateway: Some("172.16.2.1".into()),
+        });
+        assert_eq!(
+            parse_route_json(r#"[{"dst":"default","gateway":"172.16.2.1","dev":"eno1","protocol":"static","flags":[]}]"#).unwrap(),
+            expected
+        );
+        assert_eq!(
+            parse_route_text("default via 172.16.2.1 dev eno1 proto static\n"),
+            expected
+        );
+        assert_eq!(parse_route_json("[]").unwrap(), None);
+    }
+
+    #[test]
+    fn test_lscpu_json_nested_and_text() {
+        let nested = r#"{"lscpu":[{"field":"Architecture:","data":"x86_64","children":[{"field":"CPU op-mode(s):","data":"32-bit, 64-bit"}]},
+            {"field":"CPU(s):","data":"16"},
+            {"field":"Vendor ID:","data":"GenuineIntel","children":[{"field":"Model name:","data":"Intel(R) Xeon(R) E-2278G","children":[{"field":"Thread(s) per core:","data":"2"},{"field":"Socket(s):","data":"1"}]}]},
+            {"field":"Virtualization features:","data":null,"children":[{"field":"Virtualization:","data":"VT-x"}]}]}"#;
+        let cpu = parse_lscpu(nested);
+        assert_eq!(cpu.architecture.as_deref(), Some("x86_64"));
+        assert_eq!(cpu.cpus, 16);
+        assert_eq!(cpu.model_name.as_deref(), Some("Intel(R) Xeon(R) E-2278G"));
+        assert_eq!(cpu.threads_per_core, Some(2));
+        assert_eq!(cpu.virtualization.as_deref(), Some("VT-x"));
+
+        let text = "Architecture:        aarch64\nCPU(s):              4\nModel name:          Cortex-A72\nHypervisor vendor:   KVM\n";
+        let cpu = parse_lscpu(text);
+        assert_eq!(cpu.architecture.as_deref(), Some("aarch64"));
+        assert_eq!(cpu.cpus, 4);
+        assert_eq!(cpu.hypervisor.as_deref(), Some("KVM"));
+
+        assert_eq!(
+            parse_meminfo_total_mb("MemTotal:       16314624 kB\nMemFree: 1 kB\n"),
+            Some(15932)
+        );
+    }
+
+    #[test]
+    fn test_check_install_target() {
+        let facts = TargetFacts {
+            disks: parse_lsblk_pairs(
+                "NAME=\"nvme0n1\" TYPE=\"disk\" SIZE=\"512110190592\" PKNAME=\"\"\n\
+                 NAME=\"nvme0n1p1\" TYPE=\"part\" SIZE=\"1\" MOUNTPOINT=\"/mnt/targetos/boot/efi\" PKNAME=\"nvme0n1\"\n\
+                 NAME=\"sda\" TYPE=\"disk\" SIZE=\"32000000000\" PKNAME=\"\"\n\
+                 NAME=\"sda1\" TYPE=\"part\" SIZE=\"1\" MOUNTPOINT=\"/cdrom\" PKNAME=\"sda\"\n\
+                 NAME=\"sdb\" TYPE=\"disk\" SIZE=\"64000000000\" PKNAME=\"\"\n",
+            ),
+            interfaces: parse_ip_oneline("2: eno1: <UP> mtu 1500 state UP\n", ""),
+            ..Default::default()
+        };
+        assert!(facts
+            .check_install_target("/dev/nvme0n1", "eno1")
+            .unwrap()
+            .is_empty());
+        assert_eq!(
+            facts
+                .check_install_target("/dev/sda", "eno1")
+                .unwrap()
+                .len(),
+            1
+        );
+        assert!(facts.check_install_target("/dev/sdc", "eno1").is_err());
+        assert!(facts.check_install_target("/dev/nvme0n1", "eth0").is_err());
+        assert_eq!(facts.primary_disk().unwrap().name, "sdb");
+
+        // Nothing collected: nothing to hold against the target
+        assert!(TargetFacts::default()
+            .check_install_target("/dev/sdb", "eth0")
+            .is_ok());
+    }
+}
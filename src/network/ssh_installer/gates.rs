@@ -0,0 +1,202 @@
+// file: src/network/ssh_installer/gates.rs
+// version: 1.1.0
+// guid: 0d5b7e39-4f82-4c1a-9e63-a8f2c6d1b957
+
+//! Approvals for the confirmation gates between install phases
+//!
+//! A waiting install checks `logs/<hostname>/approvals/<phase>` (written by `approve`) and the
+//! configured `approval_url` until one of them decides, and also asks at the terminal when it
+//! runs interactively. The decision file is removed once read, so every gate of every install
+//! needs its own approval.
+
+use super::config::InstallationConfig;
+use super::plan::{phase_for_section, InstallPlan};
+use super::session::InstallSession;
+use crate::config::confirmation::GatePhase;
+use crate::Result;
+use std::path::{Path, PathBuf};
+use std::time::Duration;
+
+/// Upper bound for one poll of the approval endpoint
+const REQUEST_TIMEOUT: Duration = Duration::from_secs(15);
+
+/// Outcome of a gate
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum GateDecision {
+    Approved,
+    Rejected(String),
+}
+
+/// What the phase behind a gate is about to do
+pub fn phase_summary(config: &InstallationConfig, phase: &str) -> Vec<String> {
+    let mut lines = Vec::new();
+    if GatePhase::from_label(phase) == Some(GatePhase::Phase2) {
+        lines.push(if config.partitioning.is_auto() {
+            format!("wipe and partition {}", config.disk_device)
+        } else {
+            format!(
+                "use the {} partitions on {}",
+                config.partitioning.mode.as_str(),
+                config.disk_device
+            )
+        });
+    }
+    lines.extend(
+        InstallPlan::from_config(config)
+            .steps
+            .into_iter()
+            .filter(|step| phase_for_section(&step.section) == phase)
+            .map(|step| format!("[{}] {}", step.section, step.line)),
+    );
+    lines
+}
+
+/// Decision file `approve` writes for `gate` of `hostname`
+pub fn approval_path(base_dir: &Path, hostname: &str, gate: GatePhase) -> PathBuf {
+    InstallSession::host_dir(base_dir, hostname)
+        .join("approvals")
+        .join(gate.id())
+}
+
+/// Record a decision for a waiting install
+pub fn write_approval(
+    base_dir: &Path,
+    hostname: &str,
+    gate: GatePhase,
+    decision: &GateDecision,
+) -> Result<PathBuf> {
+    let path = approval_path(base_dir, hostname, gate);
+    if let Some(parent) = path.parent() {
+        std::fs::create_dir_all(parent)?;
+    }
+    let text = match decision {
+        GateDecision::Approved => "approve\n".to_string(),
+        GateDecision::Rejected(reason) => format!("reject {}\n", reason),
+    };
+    std::fs::write(&path, text)?;
+    Ok(path)
+}
+
+/// Parse a decision file: `approve`, or `reject` followed by an optional reason
+pub fn parse_decision(text: &str) -> Option<GateDecision> {
+    let text = text.trim();
+    if text == "approve" {
+        return Some(GateDecision::Approved);
+    }
+    let reason = text.strip_prefix("reject")?.trim();
+    Some(GateDecision::Rejected(if reason.is_empty() {
+        "rejected".to_string()
+    } else {
+        reason.to_string()
+    }))
+}
+
+/// Take the decision file for `gate` if one was written
+pub fn take_file_decision(
+    base_dir: &Path,
+    hostname: &str,
+    gate: GatePhase,
+) -> Option<GateDecision> {
+    let path = approval_path(base_dir, hostname, gate);
+    let text = std::fs::read_to_string(&path).ok()?;
+    let _ = std::fs::remove_file(&path);
+    parse_decision(&text)
+}
+
+/// `url` with `{hostname}` and `{phase}` filled in
+pub fn approval_url(url: &str, hostname: &str, gate: GatePhase) -> String {
+    url.replace("{hostname}", hostname)
+        .replace("{phase}", &gate.id())
+}
+
+/// Decision carried by an approval endpoint's JSON answer
+pub fn parse_endpoint_decision(body: &serde_json::Value) -> Option<GateDecision> {
+    match body.get("approved")?.as_bool()? {
+        true => Some(GateDecision::Approved),
+        false => Some(GateDecision::Rejected(
+            body.get("reason")
+                .and_then(|r| r.as_str())
+                .unwrap_or("rejected by the approval endpoint")
+                .to_string(),
+        )),
+    }
+}
+
+/// Ask the approval endpoint once; errors and undecided answers are `None`
+///
+/// The decision comes with the whole JSON answer, for callers that need more of it, such as
+/// the approver of a protected run.
+pub async fn poll_endpoint(url: &str) -> Option<(GateDecision, serde_json::Value)> {
+    let client = reqwest::Client::builder()
+        .timeout(REQUEST_TIMEOUT)
+        .build()
+        .ok()?;
+    let response = client.get(url).send().await.ok()?;
+    if !response.status().is_success() {
+        return None;
+    }
+    let body: serde_json::Value = response.json().await.ok()?;
+    Some((parse_endpoint_decision(&body)?, body))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    #[test]
+    fn test_decisions() {
+        let dir = tempfile::tempdir().unwrap();
+        assert_eq!(
+            take_file_decision(dir.path(), "web-01", GatePhase::Phase2),
+            None
+        );
+        let path = write_approval(
+            dir.path(),
+            "web-01",
+            GatePhase::Phase2,
+            &GateDecision::Rejected("wrong disk".to_string()),
+        )
+        .unwrap();
+        assert!(path.ends_with("logs/web-01/approvals/phase_2"));
+        assert_eq!(
+            take_file_decision(dir.path(), "web-01", GatePhase::Phase2),
+            Some(GateDecision::Rejected("wrong disk".to_string()))
+        );
+        // Read once
+        assert!(!path.exists());
+
+        assert_eq!(parse_decision("approve\n"), Some(GateDecision::Approved));
+        assert_eq!(parse_decision("maybe"), None);
+        assert_eq!(
+            parse_endpoint_decision(&json!({"approved": true})),
+            Some(GateDecision::Approved)
+        );
+        assert_eq!(parse_endpoint_decision(&json!({"pending": true})), None);
+        assert_eq!(
+            approval_url(
+                "https://cab.example/gates/{hostname}/{phase}",
+                "web-01",
+                GatePhase::Phase6
+            ),
+            "https://cab.example/gates/web-01/phase_6"
+        );
+    }
+
+    #[test]
+    fn test_phase_summary() {
+        let config = InstallationConfig::for_len_serv_003();
+        let summary = phase_summary(&config, "Phase 2: Disk preparation");
+        assert_eq!(
+            summary[0],
+            format!("wipe and partition {}", config.disk_device)
+        );
+        assert!(summary
+            .iter()
+            .any(|line| line == &format!("[storage] disk: {}", config.disk_device)));
+        let summary = phase_summary(&config, "Phase 4: Base system");
+        assert!(summary
+            .iter()
+            .any(|line| line == &format!("[system] hostname: {}", config.hostname)));
+    }
+}
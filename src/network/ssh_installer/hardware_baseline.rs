@@ -0,0 +1,466 @@
+// file: src/network/ssh_installer/hardware_baseline.rs
+// version: 1.0.0
+// guid: 1c8f5a27-6d94-4e3b-a0b7-9e2d4c6f8a15
+
+//! Hardware baseline of each host and what changed since
+//!
+//! The first investigation of a host is stored as `logs/<hostname>/hardware-baseline.json`.
+//! Later investigations and installs compare the disks, NICs, memory and CPU count against it,
+//! so a swapped disk or a DIMM that dropped out is reported before anything is installed. The
+//! baseline is never replaced on its own; `investigate --rebaseline` records a new one.
+//!
+//! Other tools read the file, so it carries a `schema_version` with the same policy as session
+//! records: minor versions only add optional fields, and a newer major is refused.
+
+use super::facts::{CpuFacts, TargetFacts};
+use super::investigation_report::{DiskReport, InterfaceReport, InvestigationReport};
+use super::session::InstallSession;
+use crate::error::AutoInstallError;
+use crate::Result;
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use std::path::{Path, PathBuf};
+
+/// Current `schema_version` of baseline files
+pub const BASELINE_SCHEMA_VERSION: &str = "1.0";
+/// Memory may differ by this share of the baseline before it counts as a change; the kernel's
+/// own reservations move `MemTotal` slightly between boots and kernels
+const MEMORY_TOLERANCE_PERCENT: u64 = 2;
+
+/// A disk as far as identity goes
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct BaselineDisk {
+    pub path: String,
+    pub serial: Option<String>,
+    pub model: Option<String>,
+    pub size_bytes: u64,
+}
+
+/// A network interface as far as identity goes
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct BaselineNic {
+    pub name: String,
+    pub mac: Option<String>,
+}
+
+/// The hardware that is compared between runs
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct HardwareSnapshot {
+    pub disks: Vec<BaselineDisk>,
+    pub nics: Vec<BaselineNic>,
+    pub memory_total_mb: Option<u64>,
+    pub cpus: Option<u32>,
+    pub cpu_model: Option<String>,
+}
+
+impl HardwareSnapshot {
+    fn new(
+        disks: &[DiskReport],
+        interfaces: &[InterfaceReport],
+        cpu: Option<&CpuFacts>,
+        memory_total_mb: Option<u64>,
+    ) -> Self {
+        Self {
+            disks: disks
+                .iter()
+                .map(|d| BaselineDisk {
+                    path: d.path.clone(),
+                    serial: d.serial.clone().filter(|s| !s.is_empty()),
+                    model: d.model.clone(),
+                    size_bytes: d.size_bytes,
+                })
+                .collect(),
+            nics: interfaces
+                .iter()
+                .filter(|i| i.name != "lo")
+                .map(|i| BaselineNic {
+                    name: i.name.clone(),
+                    mac: i.mac.clone(),
+                })
+                .collect(),
+            memory_total_mb,
+            cpus: cpu.map(|c| c.cpus),
+            cpu_model: cpu.and_then(|c| c.model_name.clone()),
+        }
+    }
+
+    pub fn from_facts(facts: &TargetFacts) -> Self {
+        Self::new(
+            &facts.disks,
+            &facts.interfaces,
+            facts.cpu.as_ref(),
+            facts.memory_total_mb,
+        )
+    }
+
+    pub fn from_report(report: &InvestigationReport) -> Self {
+        Self::new(
+            &report.disks,
+            &report.network,
+            report.cpu.as_ref(),
+            report.memory_total_mb,
+        )
+    }
+}
+
+/// One difference from the baseline
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(tag = "kind", rename_all = "snake_case")]
+pub enum HardwareChange {
+    DiskRemoved {
+        path: String,
+        serial: Option<String>,
+    },
+    DiskAdded {
+        path: String,
+        serial: Option<String>,
+    },
+    /// Another disk answers at the same path
+    DiskSwapped {
+        path: String,
+        old_serial: Option<String>,
+        new_serial: Option<String>,
+    },
+    DiskResized {
+        path: String,
+        old_bytes: u64,
+        new_bytes: u64,
+    },
+    NicRemoved {
+        name: String,
+        mac: Option<String>,
+    },
+    NicAdded {
+        name: String,
+        mac: Option<String>,
+    },
+    MacChanged {
+        name: String,
+        old_mac: Option<String>,
+        new_mac: Option<String>,
+    },
+    MemoryChanged {
+        old_mb: u64,
+        new_mb: u64,
+    },
+    CpuCountChanged {
+        old: u32,
+        new: u32,
+    },
+}
+
+impl HardwareChange {
+    pub fn describe(&self) -> String {
+        let show = |value: &Option<String>| value.clone().unwrap_or_else(|| "-".to_string());
+        match self {
+            Self::DiskRemoved { path, serial } => {
+                format!("disk {} (serial {}) is gone", path, show(serial))
+            }
+            Self::DiskAdded { path, serial } => {
+                format!("new disk {} (serial {})", path, show(serial))
+            }
+            Self::DiskSwapped {
+                path,
+                old_serial,
+                new_serial,
+            } => format!(
+                "disk at {} was swapped: serial {} -> {}",
+                path,
+                show(old_serial),
+                show(new_serial)
+            ),
+            Self::DiskResized {
+                path,
+                old_bytes,
+                new_bytes,
+            } => format!(
+                "disk {} changed size: {} -> {} bytes",
+                path, old_bytes, new_bytes
+            ),
+            Self::NicRemoved { name, mac } => {
+                format!("interface {} ({}) is gone", name, show(mac))
+            }
+            Self::NicAdded { name, mac } => format!("new interface {} ({})", name, show(mac)),
+            Self::MacChanged {
+                name,
+                old_mac,
+                new_mac,
+            } => format!(
+                "interface {} has a new MAC: {} -> {}",
+                name,
+                show(old_mac),
+                show(new_mac)
+            ),
+            Self::MemoryChanged { old_mb, new_mb } => {
+                format!("memory changed: {} MiB -> {} MiB", old_mb, new_mb)
+            }
+            Self::CpuCountChanged { old, new } => {
+                format!("CPU count changed: {} -> {}", old, new)
+            }
+        }
+    }
+}
+
+/// Differences of `current` from `baseline`
+pub fn compare(baseline: &HardwareSnapshot, current: &HardwareSnapshot) -> Vec<HardwareChange> {
+    let mut changes = Vec::new();
+    let mut matched = vec![false; current.disks.len()];
+    for old in &baseline.disks {
+        // Disks are the same disk when their serials match, whatever path they enumerate at
+        let by_serial = old.serial.as_ref().and_then(|serial| {
+            current
+                .disks
+                .iter()
+                .position(|d| d.serial.as_ref() == Some(serial))
+        });
+        let found = by_serial.or_else(|| {
+            current
+                .disks
+                .iter()
+                .position(|d| d.path == old.path && (old.serial.is_none() || d.serial.is_none()))
+        });
+        match found {
+            Some(i) => {
+                matched[i] = true;
+                if current.disks[i].size_bytes != old.size_bytes {
+                    changes.push(HardwareChange::DiskResized {
+                        path: current.disks[i].path.clone(),
+                        old_bytes: old.size_bytes,
+                        new_bytes: current.disks[i].size_bytes,
+                    });
+                }
+            }
+            None => match current.disks.iter().position(|d| d.path == old.path) {
+                Some(i) if !matched[i] => {
+                    matched[i] = true;
+                    changes.push(HardwareChange::DiskSwapped {
+                        path: old.path.clone(),
+                        old_serial: old.serial.clone(),
+                        new_serial: current.disks[i].serial.clone(),
+                    });
+                }
+                _ => changes.push(HardwareChange::DiskRemoved {
+                    path: old.path.clone(),
+                    serial: old.serial.clone(),
+                }),
+            },
+        }
+    }
+    for (disk, _) in current.disks.iter().zip(&matched).filter(|(_, m)| !**m) {
+        changes.push(HardwareChange::DiskAdded {
+            path: disk.path.clone(),
+            serial: disk.serial.clone(),
+        });
+    }
+
+    for old in &baseline.nics {
+        match current.nics.iter().find(|n| n.name == old.name) {
+            Some(new) if new.mac != old.mac => changes.push(HardwareChange::MacChanged {
+                name: old.name.clone(),
+                old_mac: old.mac.clone(),
+                new_mac: new.mac.clone(),
+            }),
+            Some(_) => {}
+            None => changes.push(HardwareChange::NicRemoved {
+                name: old.name.clone(),
+                mac: old.mac.clone(),
+            }),
+        }
+    }
+    for new in &current.nics {
+        if !baseline.nics.iter().any(|n| n.name == new.name) {
+            changes.push(HardwareChange::NicAdded {
+                name: new.name.clone(),
+                mac: new.mac.clone(),
+            });
+        }
+    }
+
+    if let (Some(old_mb), Some(new_mb)) = (baseline.memory_total_mb, current.memory_total_mb) {
+        if old_mb.abs_diff(new_mb) * 100 > old_mb * MEMORY_TOLERANCE_PERCENT {
+            changes.push(HardwareChange::MemoryChanged { old_mb, new_mb });
+        }
+    }
+    if let (Some(old), Some(new)) = (baseline.cpus, current.cpus) {
+        if old != new {
+            changes.push(HardwareChange::CpuCountChanged { old, new });
+        }
+    }
+    changes
+}
+
+/// Version assumed when the field is missing
+fn legacy_schema_version() -> String {
+    "1.0".to_string()
+}
+
+fn schema_major(version: &str) -> Option<u32> {
+    version.split('.').next()?.parse().ok()
+}
+
+/// `logs/<hostname>/hardware-baseline.json`
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct HardwareBaseline {
+    #[serde(default = "legacy_schema_version")]
+    pub schema_version: String,
+    pub hostname: String,
+    pub recorded_at: DateTime<Utc>,
+    pub hardware: HardwareSnapshot,
+}
+
+impl HardwareBaseline {
+    pub fn new(hostname: &str, hardware: HardwareSnapshot) -> Self {
+        Self {
+            schema_version: BASELINE_SCHEMA_VERSION.to_string(),
+            hostname: hostname.to_string(),
+            recorded_at: Utc::now(),
+            hardware,
+        }
+    }
+
+    pub fn path(base_dir: &Path, hostname: &str) -> PathBuf {
+        InstallSession::host_dir(base_dir, hostname).join("hardware-baseline.json")
+    }
+
+    /// The stored baseline of `hostname`, if there is one
+    pub fn load(base_dir: &Path, hostname: &str) -> Result<Option<Self>> {
+        let path = Self::path(base_dir, hostname);
+        if !path.exists() {
+            return Ok(None);
+        }
+        let baseline: Self = serde_json::from_str(&std::fs::read_to_string(&path)?)?;
+        let supported = schema_major(BASELINE_SCHEMA_VERSION).unwrap_or(1);
+        match schema_major(&baseline.schema_version) {
+            Some(major) if major <= supported => Ok(Some(baseline)),
+            _ => Err(AutoInstallError::ConfigError(format!(
+                "Hardware baseline {} uses schema {}, this build reads up to {}.x",
+                path.display(),
+                baseline.schema_version,
+                supported
+            ))),
+        }
+    }
+
+    pub fn save(&self, base_dir: &Path) -> Result<PathBuf> {
+        let path = Self::path(base_dir, &self.hostname);
+        if let Some(parent) = path.parent() {
+            std::fs::create_dir_all(parent)?;
+        }
+        std::fs::write(&path, serde_json::to_string_pretty(self)?)?;
+        Ok(path)
+    }
+
+    /// Compare `current` with the stored baseline, or store it as the baseline when there is
+    /// none yet (or `rebaseline` is set)
+    pub fn check_or_record(
+        base_dir: &Path,
+        hostname: &str,
+        current: HardwareSnapshot,
+        rebaseline: bool,
+    ) -> Result<Vec<HardwareChange>> {
+        match Self::load(base_dir, hostname)? {
+            Some(baseline) if !rebaseline => Ok(compare(&baseline.hardware, &current)),
+            _ => {
+                let path = Self::new(hostname, current).save(base_dir)?;
+                tracing::info!(
+                    "Hardware baseline for {} written to {}",
+                    hostname,
+                    path.display()
+                );
+                Ok(Vec::new())
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn disk(path: &str, serial: &str, size_bytes: u64) -> BaselineDisk {
+        BaselineDisk {
+            path: path.to_string(),
+            serial: Some(serial.to_string()),
+            model: None,
+            size_bytes,
+        }
+    }
+
+    fn snapshot() -> HardwareSnapshot {
+        HardwareSnapshot {
+            disks: vec![
+                disk("/dev/sda", "S1", 500),
+                disk("/dev/sdb", "S2", 500),
+                disk("/dev/sdc", "S3", 500),
+            ],
+            nics: vec![BaselineNic {
+                name: "eno1".to_string(),
+                mac: Some("aa:bb:cc:00:00:01".to_string()),
+            }],
+            memory_total_mb: Some(65536),
+            cpus: Some(16),
+            cpu_model: None,
+        }
+    }
+
+    #[test]
+    fn test_compare_flags_swaps_and_lost_memory() {
+        let baseline = snapshot();
+        assert!(compare(&baseline, &baseline).is_empty());
+
+        let mut current = snapshot();
+        // sda and sdb enumerate the other way round: not a change
+        current.disks.swap(0, 1);
+        current.disks[0].path = "/dev/sda".to_string();
+        current.disks[1].path = "/dev/sdb".to_string();
+        // sdc was replaced, a DIMM dropped out and the NIC board was swapped
+        current.disks[2] = disk("/dev/sdc", "S9", 500);
+        current.memory_total_mb = Some(57344);
+        current.nics[0].mac = Some("aa:bb:cc:00:00:99".to_string());
+        let changes = compare(&baseline, &current);
+        assert_eq!(
+            changes,
+            vec![
+                HardwareChange::DiskSwapped {
+                    path: "/dev/sdc".to_string(),
+                    old_serial: Some("S3".to_string()),
+                    new_serial: Some("S9".to_string()),
+                },
+                HardwareChange::MacChanged {
+                    name: "eno1".to_string(),
+                    old_mac: Some("aa:bb:cc:00:00:01".to_string()),
+                    new_mac: Some("aa:bb:cc:00:00:99".to_string()),
+                },
+                HardwareChange::MemoryChanged {
+                    old_mb: 65536,
+                    new_mb: 57344
+                },
+            ]
+        );
+        // Within the tolerance for kernel reservations
+        current = snapshot();
+        current.memory_total_mb = Some(65000);
+        assert!(compare(&baseline, &current).is_empty());
+    }
+
+    #[test]
+    fn test_first_run_records_and_later_runs_compare() {
+        let dir = tempfile::tempdir().unwrap();
+        let changes =
+            HardwareBaseline::check_or_record(dir.path(), "web-01", snapshot(), false).unwrap();
+        assert!(changes.is_empty());
+        let mut current = snapshot();
+        current.disks.pop();
+        let changes =
+            HardwareBaseline::check_or_record(dir.path(), "web-01", current, false).unwrap();
+        assert_eq!(changes.len(), 1);
+        assert_eq!(changes[0].describe(), "disk /dev/sdc (serial S3) is gone");
+
+        let path = HardwareBaseline::path(dir.path(), "web-01");
+        let text = std::fs::read_to_string(&path)
+            .unwrap()
+            .replace("\"1.0\"", "\"2.0\"");
+        std::fs::write(&path, text).unwrap();
+        assert!(HardwareBaseline::load(dir.path(), "web-01").is_err());
+    }
+}
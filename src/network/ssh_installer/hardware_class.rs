@@ -0,0 +1,299 @@
+// file: src/network/ssh_installer/hardware_class.rs
+// version: 1.0.0
+// guid: 9c3f5b28-1e7a-4d64-8b90-f2a6d1c8e437
+
+//! Hardware classes detected from target facts
+//!
+//! Investigation sorts a target into a class by its installable disks. Each class carries a
+//! default storage layout, which is used when the target config does not declare one, so a
+//! rack of identical machines needs no per-host storage settings.
+
+use super::config::InstallationConfig;
+use super::facts::TargetFacts;
+use super::investigation_report::DiskReport;
+use crate::config::storage::StorageLayout;
+use serde::{Deserialize, Serialize};
+
+/// Disks smaller than this (USB sticks, SD cards, BMC virtual media) are never install disks
+const MIN_INSTALL_DISK_BYTES: u64 = 16 * 1024 * 1024 * 1024;
+
+/// Disks within this percentage of the largest one are treated as a matched set
+const MATCHED_SIZE_PERCENT: u64 = 90;
+
+/// Storage shape of a target
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum HardwareClass {
+    /// One NVMe drive
+    NvmeSingle,
+    /// One SATA, SAS or virtual disk
+    SingleDisk,
+    /// Two matched NVMe drives
+    DualNvme,
+    /// Two matched SATA or SAS drives
+    DualSata,
+    /// Three or more matched drives
+    RaidCapable,
+}
+
+impl HardwareClass {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            HardwareClass::NvmeSingle => "nvme-single",
+            HardwareClass::SingleDisk => "single-disk",
+            HardwareClass::DualNvme => "dual-nvme",
+            HardwareClass::DualSata => "dual-sata",
+            HardwareClass::RaidCapable => "raid-capable",
+        }
+    }
+
+    /// Layout used for this class when the target config does not name one
+    pub fn default_layout(&self) -> StorageLayout {
+        match self {
+            HardwareClass::NvmeSingle | HardwareClass::SingleDisk => StorageLayout::Single,
+            HardwareClass::DualNvme | HardwareClass::DualSata | HardwareClass::RaidCapable => {
+                StorageLayout::MirroredEsp
+            }
+        }
+    }
+}
+
+impl std::fmt::Display for HardwareClass {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(self.as_str())
+    }
+}
+
+/// Class of a target together with the disks that put it there
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct HardwareProfile {
+    pub class: HardwareClass,
+    /// Device paths of the matched disks, in device order
+    pub disks: Vec<String>,
+}
+
+impl HardwareProfile {
+    /// Classify a target; `None` when no installable disk was found
+    pub fn detect(facts: &TargetFacts) -> Option<Self> {
+        let eligible: Vec<&DiskReport> = facts.disks.iter().filter(|d| installable(d)).collect();
+        let largest = eligible.iter().max_by_key(|d| d.size_bytes)?;
+
+        // Only disks of the same kind and a similar size as the largest can share a layout
+        let mut matched: Vec<&DiskReport> = eligible
+            .iter()
+            .filter(|d| {
+                is_nvme(d) == is_nvme(largest)
+                    && d.size_bytes * 100 >= largest.size_bytes * MATCHED_SIZE_PERCENT
+            })
+            .copied()
+            .collect();
+        matched.sort_by(|a, b| a.path.cmp(&b.path));
+
+        let class = match (matched.len(), is_nvme(largest)) {
+            (1, true) => HardwareClass::NvmeSingle,
+            (1, false) => HardwareClass::SingleDisk,
+            (2, true) => HardwareClass::DualNvme,
+            (2, false) => HardwareClass::DualSata,
+            _ => HardwareClass::RaidCapable,
+        };
+        Some(Self {
+            class,
+            disks: matched.iter().map(|d| d.path.clone()).collect(),
+        })
+    }
+
+    /// One-line summary for logs and investigation output
+    pub fn summary(&self) -> String {
+        format!(
+            "{} ({}) -> {} layout",
+            self.class,
+            self.disks.join(", "),
+            self.class.default_layout()
+        )
+    }
+
+    /// Pick the layout for `config` and apply it
+    ///
+    /// A layout declared in the target config wins. Otherwise ESP mirrors already set by the
+    /// preset are kept as they are, and only a config without any falls back to the class
+    /// default. Returns the layout applied, if any, and warnings from applying it.
+    pub fn inherit_layout(
+        &self,
+        declared: Option<StorageLayout>,
+        config: &mut InstallationConfig,
+    ) -> (Option<StorageLayout>, Vec<String>) {
+        let layout = match declared {
+            Some(layout) => layout,
+            None if !config.esp_mirror_devices.is_empty() => return (None, Vec::new()),
+            None => self.class.default_layout(),
+        };
+        (Some(layout), self.apply_layout(layout, config))
+    }
+
+    /// Apply `layout` to `config` using this profile's disks
+    ///
+    /// Returns warnings when the layout cannot be realised as asked, e.g. because the install
+    /// disk is not one of the matched disks.
+    pub fn apply_layout(
+        &self,
+        layout: StorageLayout,
+        config: &mut InstallationConfig,
+    ) -> Vec<String> {
+        let mut warnings = Vec::new();
+        match layout {
+            StorageLayout::Single => config.esp_mirror_devices.clear(),
+            StorageLayout::MirroredEsp => {
+                if !self.disks.contains(&config.disk_device) {
+                    warnings.push(format!(
+                        "Install disk {} is not one of the {} disks ({}); ESP mirrors not added",
+                        config.disk_device,
+                        self.class,
+                        self.disks.join(", ")
+                    ));
+                    return warnings;
+                }
+                config.esp_mirror_devices = self
+                    .disks
+                    .iter()
+                    .filter(|d| **d != config.disk_device)
+                    .cloned()
+                    .collect();
+                if config.esp_mirror_devices.is_empty() {
+                    warnings.push(format!(
+                        "Layout {} needs a second disk but {} has only {}",
+                        layout, self.class, config.disk_device
+                    ));
+                }
+            }
+        }
+        warnings
+    }
+}
+
+fn is_nvme(disk: &DiskReport) -> bool {
+    disk.transport.as_deref() == Some("nvme") || disk.name.starts_with("nvme")
+}
+
+/// Large enough, not removable media, and not mounted outside an earlier install attempt
+fn installable(disk: &DiskReport) -> bool {
+    disk.size_bytes >= MIN_INSTALL_DISK_BYTES
+        && disk.transport.as_deref() != Some("usb")
+        && !disk.partitions.iter().any(|p| {
+            p.mountpoint
+                .as_deref()
+                .is_some_and(|m| !m.starts_with("/mnt/targetos"))
+        })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::network::ssh_installer::facts::parse_lsblk_pairs;
+
+    const GB: u64 = 1_000_000_000;
+
+    fn facts(disks: &[(&str, u64, &str)]) -> TargetFacts {
+        let out: String = disks
+            .iter()
+            .map(|(name, size, tran)| {
+                format!(
+                    "NAME=\"{}\" TYPE=\"disk\" SIZE=\"{}\" TRAN=\"{}\" PKNAME=\"\"\n",
+                    name, size, tran
+                )
+            })
+            .collect();
+        TargetFacts {
+            disks: parse_lsblk_pairs(&out),
+            ..Default::default()
+        }
+    }
+
+    fn config(disk: &str) -> InstallationConfig {
+        let mut config = InstallationConfig::for_len_serv_003();
+        config.disk_device = disk.to_string();
+        config
+    }
+
+    #[test]
+    fn test_detect_classes() {
+        let single = HardwareProfile::detect(&facts(&[
+            ("nvme0n1", 512 * GB, "nvme"),
+            ("sda", 8 * GB, "usb"),
+        ]))
+        .unwrap();
+        assert_eq!(single.class, HardwareClass::NvmeSingle);
+        assert_eq!(single.disks, vec!["/dev/nvme0n1".to_string()]);
+
+        let dual = HardwareProfile::detect(&facts(&[
+            ("sdb", 2000 * GB, "sata"),
+            ("sda", 1960 * GB, "sata"),
+            ("nvme0n1", 256 * GB, "nvme"),
+        ]))
+        .unwrap();
+        assert_eq!(dual.class, HardwareClass::DualSata);
+        assert_eq!(
+            dual.disks,
+            vec!["/dev/sda".to_string(), "/dev/sdb".to_string()]
+        );
+        assert_eq!(
+            dual.summary(),
+            "dual-sata (/dev/sda, /dev/sdb) -> mirrored-esp layout"
+        );
+
+        let raid = HardwareProfile::detect(&facts(&[
+            ("sda", 4000 * GB, "sas"),
+            ("sdb", 4000 * GB, "sas"),
+            ("sdc", 4000 * GB, "sas"),
+            ("sdd", 1000 * GB, "sas"),
+        ]))
+        .unwrap();
+        assert_eq!(raid.class, HardwareClass::RaidCapable);
+        assert_eq!(raid.disks.len(), 3);
+
+        // A smaller second drive does not make a pair
+        let mixed = HardwareProfile::detect(&facts(&[("vda", 100 * GB, ""), ("vdb", 50 * GB, "")]))
+            .unwrap();
+        assert_eq!(mixed.class, HardwareClass::SingleDisk);
+
+        assert!(HardwareProfile::detect(&facts(&[("sda", 8 * GB, "usb")])).is_none());
+    }
+
+    #[test]
+    fn test_apply_layout() {
+        let profile = HardwareProfile {
+            class: HardwareClass::DualNvme,
+            disks: vec!["/dev/nvme0n1".to_string(), "/dev/nvme1n1".to_string()],
+        };
+        let mut cfg = config("/dev/nvme0n1");
+        assert!(profile
+            .apply_layout(StorageLayout::MirroredEsp, &mut cfg)
+            .is_empty());
+        assert_eq!(cfg.esp_mirror_devices, vec!["/dev/nvme1n1".to_string()]);
+
+        assert!(profile
+            .apply_layout(StorageLayout::Single, &mut cfg)
+            .is_empty());
+        assert!(cfg.esp_mirror_devices.is_empty());
+
+        // The class default only fills in what the preset left open
+        let mut cfg = config("/dev/nvme0n1");
+        assert_eq!(
+            profile.inherit_layout(None, &mut cfg).0,
+            Some(StorageLayout::MirroredEsp)
+        );
+        cfg.esp_mirror_devices = vec!["/dev/sdz".to_string()];
+        assert_eq!(profile.inherit_layout(None, &mut cfg).0, None);
+        assert_eq!(cfg.esp_mirror_devices, vec!["/dev/sdz".to_string()]);
+        profile.inherit_layout(Some(StorageLayout::Single), &mut cfg);
+        assert!(cfg.esp_mirror_devices.is_empty());
+
+        let mut cfg = config("/dev/sda");
+        assert_eq!(
+            profile
+                .apply_layout(StorageLayout::MirroredEsp, &mut cfg)
+                .len(),
+            1
+        );
+        assert!(cfg.esp_mirror_devices.is_empty());
+    }
+}
@@ -0,0 +1,228 @@
+// file: src/network/ssh_installer/health_score.rs
+// version: 1.0.0
+// guid: 3a9c6e07-1f5d-4b28-a4e3-7d2b8f0c5e91
+
+//! Weighted health score deciding whether an install may wipe the disk
+//!
+//! Each signal is scored from 0 (bad) to 1 (good) and weighted by the `health_gate:` weights.
+//! Signals that could not be measured score 0.5, so a gap in the data lowers the score without
+//! deciding it alone.
+
+use super::disk_health::HealthVerdict;
+use crate::config::HealthGateConfig;
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+
+/// Which debootstrap mirror answered
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum MirrorReach {
+    Primary,
+    /// Only old-releases.ubuntu.com serves the release
+    FallbackOnly,
+    Unreachable,
+}
+
+/// What was measured on the target before Phase 2
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct HealthInputs {
+    pub network: bool,
+    /// `None` when the disk health check is turned off
+    pub disk_health: Option<HealthVerdict>,
+    /// Target clock minus controller clock, when the target's clock could be read
+    pub clock_skew_secs: Option<i64>,
+    pub mirror: MirrorReach,
+    pub memory_mb: Option<u64>,
+}
+
+/// One weighted signal
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct HealthSignal {
+    pub name: String,
+    pub weight: u32,
+    /// 0.0 (bad) to 1.0 (good)
+    pub score: f64,
+    pub detail: String,
+}
+
+/// The combined score and the signals behind it
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct HealthScore {
+    pub checked_at: DateTime<Utc>,
+    /// Weighted score out of 100
+    pub score: u8,
+    pub min_score: u8,
+    pub signals: Vec<HealthSignal>,
+}
+
+impl HealthScore {
+    /// Score `inputs` with the weights and limits of `config`
+    pub fn evaluate(inputs: &HealthInputs, config: &HealthGateConfig) -> Self {
+        let weights = &config.weights;
+        let (disk, disk_detail) = match inputs.disk_health {
+            Some(HealthVerdict::Healthy) => (1.0, "healthy".to_string()),
+            Some(HealthVerdict::Warning) => (0.5, "warning limits crossed".to_string()),
+            Some(HealthVerdict::Failing) => (0.0, "failure limits crossed".to_string()),
+            Some(HealthVerdict::Unknown) => (0.5, "no SMART data".to_string()),
+            None => (0.5, "not checked".to_string()),
+        };
+        let (clock, clock_detail) = match inputs.clock_skew_secs {
+            Some(skew) => {
+                let ratio = skew.unsigned_abs() as f64 / config.max_clock_skew_secs as f64;
+                ((1.0 - ratio).max(0.0), format!("{}s off", skew))
+            }
+            None => (0.5, "target clock unreadable".to_string()),
+        };
+        let (mirror, mirror_detail) = match inputs.mirror {
+            MirrorReach::Primary => (1.0, "reachable"),
+            MirrorReach::FallbackOnly => (0.5, "only old-releases reachable"),
+            MirrorReach::Unreachable => (0.0, "unreachable"),
+        };
+        let (memory, memory_detail) = match inputs.memory_mb {
+            Some(mb) => (
+                (mb as f64 / config.min_memory_mb as f64).min(1.0),
+                format!("{} MiB of {} MiB wanted", mb, config.min_memory_mb),
+            ),
+            None => (0.5, "unknown".to_string()),
+        };
+        let signal = |name: &str, weight: u32, score: f64, detail: String| HealthSignal {
+            name: name.to_string(),
+            weight,
+            score,
+            detail,
+        };
+        let signals: Vec<HealthSignal> = vec![
+            signal(
+                "network",
+                weights.network,
+                if inputs.network { 1.0 } else { 0.0 },
+                if inputs.network {
+                    "online"
+                } else {
+                    "no ICMP reply"
+                }
+                .to_string(),
+            ),
+            signal("disk_health", weights.disk_health, disk, disk_detail),
+            signal("clock_skew", weights.clock_skew, clock, clock_detail),
+            signal("mirror", weights.mirror, mirror, mirror_detail.to_string()),
+            signal("memory", weights.memory, memory, memory_detail),
+        ]
+        .into_iter()
+        .filter(|s| s.weight > 0)
+        .collect();
+
+        let total: u32 = signals.iter().map(|s| s.weight).sum();
+        let weighted: f64 = signals.iter().map(|s| s.weight as f64 * s.score).sum();
+        let score = if total == 0 {
+            100
+        } else {
+            (weighted / total as f64 * 100.0).round() as u8
+        };
+        Self {
+            checked_at: Utc::now(),
+            score,
+            min_score: config.min_score,
+            signals,
+        }
+    }
+
+    pub fn passed(&self) -> bool {
+        self.score >= self.min_score
+    }
+
+    /// `55/100 (minimum 70): network 1.00, mirror 0.00 (unreachable), ...`
+    pub fn summary(&self) -> String {
+        let signals: Vec<String> = self
+            .signals
+            .iter()
+            .map(|s| format!("{} {:.2} ({})", s.name, s.score, s.detail))
+            .collect();
+        format!(
+            "{}/100 (minimum {}): {}",
+            self.score,
+            self.min_score,
+            signals.join(", ")
+        )
+    }
+
+    /// Label/value pairs for reports
+    pub fn summary_rows(&self) -> Vec<(String, String)> {
+        let mut rows = vec![
+            (
+                "Score".to_string(),
+                format!("{}/100 (minimum {})", self.score, self.min_score),
+            ),
+            (
+                "Decision".to_string(),
+                if self.passed() { "go" } else { "no-go" }.to_string(),
+            ),
+        ];
+        rows.extend(self.signals.iter().map(|s| {
+            (
+                s.name.clone(),
+                format!("{:.2} x {} ({})", s.score, s.weight, s.detail),
+            )
+        }));
+        rows
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn healthy() -> HealthInputs {
+        HealthInputs {
+            network: true,
+            disk_health: Some(HealthVerdict::Healthy),
+            clock_skew_secs: Some(2),
+            mirror: MirrorReach::Primary,
+            memory_mb: Some(16384),
+        }
+    }
+
+    #[test]
+    fn test_healthy_target_passes() {
+        let score = HealthScore::evaluate(&healthy(), &HealthGateConfig::default());
+        assert_eq!(score.score, 100);
+        assert!(score.passed());
+        assert_eq!(score.signals.len(), 5);
+        assert!(score
+            .summary()
+            .starts_with("100/100 (minimum 70): network 1.00"));
+    }
+
+    #[test]
+    fn test_marginal_target_is_no_go() {
+        let inputs = HealthInputs {
+            disk_health: Some(HealthVerdict::Warning),
+            mirror: MirrorReach::FallbackOnly,
+            memory_mb: Some(1024),
+            clock_skew_secs: None,
+            ..healthy()
+        };
+        let score = HealthScore::evaluate(&inputs, &HealthGateConfig::default());
+        // 30 + 12.5 + 5 + 12.5 + 2.5 out of 100
+        assert_eq!(score.score, 63);
+        assert!(!score.passed());
+        assert_eq!(score.summary_rows()[1].1, "no-go");
+        assert_eq!(
+            score.summary_rows()[6].1,
+            "0.25 x 10 (1024 MiB of 4096 MiB wanted)"
+        );
+    }
+
+    #[test]
+    fn test_zero_weight_leaves_signal_out() {
+        let mut config = HealthGateConfig::default();
+        config.weights.clock_skew = 0;
+        let inputs = HealthInputs {
+            clock_skew_secs: Some(-3600),
+            ..healthy()
+        };
+        let score = HealthScore::evaluate(&inputs, &config);
+        assert_eq!(score.score, 100);
+        assert!(score.signals.iter().all(|s| s.name != "clock_skew"));
+    }
+}
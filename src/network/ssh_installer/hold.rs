@@ -0,0 +1,227 @@
+// file: src/network/ssh_installer/hold.rs
+// version: 1.0.0
+// guid: 5b9e2d47-3c18-4a6f-8d07-e1f4a2c6b938
+
+//! Held sessions: failed targets kept mounted for debugging
+//!
+//! While an install holds its session open it records the hold in `logs/<hostname>/hold.json`.
+//! `sessions list` shows these records; `sessions release <hostname>` writes a
+//! `hold.release.json` next to the record, which the holding process picks up within
+//! [`HOLD_POLL_INTERVAL`]. A released hold, or one whose timeout has passed, is cleaned up
+//! safely (unmounts, pool export, LUKS close; the disk is never wiped) before the session is
+//! closed. `--keep-mounts` and Ctrl+C leave the target as-is.
+
+use super::session::InstallSession;
+use crate::error::AutoInstallError;
+use crate::Result;
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use std::path::{Path, PathBuf};
+use std::time::Duration;
+
+/// How long a session is held when no timeout is given
+pub const DEFAULT_HOLD_TIMEOUT: Duration = Duration::from_secs(4 * 60 * 60);
+
+/// How often the holding process looks for a release request
+pub const HOLD_POLL_INTERVAL: Duration = Duration::from_secs(5);
+
+/// Record of a session held open after a failure
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct HeldSession {
+    pub hostname: String,
+    pub session_id: String,
+    /// Address the held SSH session is connected to
+    pub address: String,
+    /// Why the install stopped
+    pub reason: String,
+    /// Process holding the session
+    pub pid: u32,
+    pub held_since: DateTime<Utc>,
+    /// When the hold is cleaned up on its own; held until released when absent
+    pub expires_at: Option<DateTime<Utc>>,
+}
+
+impl HeldSession {
+    /// Hold of `hostname` by this process, expiring after `timeout`
+    pub fn new(
+        hostname: &str,
+        session_id: &str,
+        address: &str,
+        reason: &str,
+        timeout: Option<Duration>,
+    ) -> Self {
+        let held_since = Utc::now();
+        Self {
+            hostname: hostname.to_string(),
+            session_id: session_id.to_string(),
+            address: address.to_string(),
+            reason: reason.to_string(),
+            pid: std::process::id(),
+            held_since,
+            expires_at: timeout
+                .and_then(|t| chrono::Duration::from_std(t).ok())
+                .map(|t| held_since + t),
+        }
+    }
+
+    pub fn path(base_dir: &Path, hostname: &str) -> PathBuf {
+        InstallSession::host_dir(base_dir, hostname).join("hold.json")
+    }
+
+    pub fn load(base_dir: &Path, hostname: &str) -> Option<Self> {
+        let content = std::fs::read_to_string(Self::path(base_dir, hostname)).ok()?;
+        serde_json::from_str(&content).ok()
+    }
+
+    pub fn save(&self, base_dir: &Path) -> Result<()> {
+        let path = Self::path(base_dir, &self.hostname);
+        if let Some(parent) = path.parent() {
+            std::fs::create_dir_all(parent)?;
+        }
+        std::fs::write(path, serde_json::to_string_pretty(self)?)?;
+        Ok(())
+    }
+
+    /// Remove the record of `hostname` and any release request for it
+    pub fn remove(base_dir: &Path, hostname: &str) {
+        let _ = std::fs::remove_file(Self::path(base_dir, hostname));
+        let _ = std::fs::remove_file(ReleaseRequest::path(base_dir, hostname));
+    }
+
+    /// Every hold recorded under `base_dir`, oldest first
+    pub fn list(base_dir: &Path) -> Vec<Self> {
+        let Ok(entries) = std::fs::read_dir(base_dir.join("logs")) else {
+            return Vec::new();
+        };
+        let mut holds: Vec<Self> = entries
+            .flatten()
+            .filter_map(|entry| Self::load(base_dir, &entry.file_name().to_string_lossy()))
+            .collect();
+        holds.sort_by_key(|hold| hold.held_since);
+        holds
+    }
+
+    /// Whether the process holding the session is still running; a record left behind by a
+    /// process that died says nothing about the target
+    pub fn is_active(&self) -> bool {
+        Path::new(&format!("/proc/{}", self.pid)).exists()
+    }
+
+    pub fn is_expired(&self, now: DateTime<Utc>) -> bool {
+        self.expires_at.is_some_and(|at| now >= at)
+    }
+
+    /// One line for `sessions list`
+    pub fn summary(&self) -> String {
+        let expiry = match self.expires_at {
+            Some(at) => format!("until {}", at.format("%Y-%m-%d %H:%M UTC")),
+            None => "until released".to_string(),
+        };
+        let state = if self.is_active() {
+            format!("held {}", expiry)
+        } else {
+            format!("stale (process {} is gone)", self.pid)
+        };
+        format!(
+            "{} ({}) session {} since {}, {}: {}",
+            self.hostname,
+            self.address,
+            self.session_id,
+            self.held_since.format("%Y-%m-%d %H:%M UTC"),
+            state,
+            self.reason
+        )
+    }
+}
+
+/// Request to end a hold, written by `sessions release` to `logs/<hostname>/hold.release.json`
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ReleaseRequest {
+    /// Unmount, export the pools and close LUKS before closing the session
+    pub cleanup: bool,
+    pub requested_at: DateTime<Utc>,
+}
+
+impl ReleaseRequest {
+    pub fn path(base_dir: &Path, hostname: &str) -> PathBuf {
+        InstallSession::host_dir(base_dir, hostname).join("hold.release.json")
+    }
+
+    /// The pending request for `hostname`, if any
+    pub fn load(base_dir: &Path, hostname: &str) -> Option<Self> {
+        let content = std::fs::read_to_string(Self::path(base_dir, hostname)).ok()?;
+        serde_json::from_str(&content).ok()
+    }
+
+    /// Ask the process holding `hostname` to let go; a stale record is removed instead
+    pub fn request(base_dir: &Path, hostname: &str, cleanup: bool) -> Result<HeldSession> {
+        let hold = HeldSession::load(base_dir, hostname).ok_or_else(|| {
+            AutoInstallError::ValidationError(format!("No held session for {}", hostname))
+        })?;
+        if !hold.is_active() {
+            HeldSession::remove(base_dir, hostname);
+            return Ok(hold);
+        }
+        let request = Self {
+            cleanup,
+            requested_at: Utc::now(),
+        };
+        std::fs::write(
+            Self::path(base_dir, hostname),
+            serde_json::to_string_pretty(&request)?,
+        )?;
+        Ok(hold)
+    }
+}
+
+/// Why a hold ended
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum HoldOutcome {
+    /// `sessions release`, with or without cleanup
+    Released { cleanup: bool },
+    /// The hold timeout passed
+    Expired,
+    /// Ctrl+C on the holding process
+    Interrupted,
+    /// The SSH session to the target was lost
+    Disconnected,
+}
+
+impl HoldOutcome {
+    /// Whether the target is cleaned up before the session is closed
+    pub fn cleans_up(&self) -> bool {
+        matches!(self, Self::Released { cleanup: true } | Self::Expired)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_hold_records_and_release_requests() {
+        let dir = tempfile::TempDir::new().unwrap();
+        let hold = HeldSession::new(
+            "db-01",
+            "3f2c9a1e",
+            "10.0.0.21",
+            "Phase 3 failed",
+            Some(Duration::from_secs(60)),
+        );
+        assert!(hold.is_active());
+        assert!(!hold.is_expired(Utc::now()));
+        assert!(hold.is_expired(Utc::now() + chrono::Duration::minutes(2)));
+        hold.save(dir.path()).unwrap();
+        assert_eq!(HeldSession::list(dir.path()), vec![hold.clone()]);
+        assert!(hold
+            .summary()
+            .starts_with("db-01 (10.0.0.21) session 3f2c9a1e"));
+
+        assert!(ReleaseRequest::request(dir.path(), "web-01", true).is_err());
+        ReleaseRequest::request(dir.path(), "db-01", false).unwrap();
+        assert!(!ReleaseRequest::load(dir.path(), "db-01").unwrap().cleanup);
+        HeldSession::remove(dir.path(), "db-01");
+        assert!(HeldSession::list(dir.path()).is_empty());
+        assert!(ReleaseRequest::load(dir.path(), "db-01").is_none());
+    }
+}
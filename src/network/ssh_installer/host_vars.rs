@@ -0,0 +1,257 @@
+// file: src/network/ssh_installer/host_vars.rs
+// version: 1.1.0
+// guid: 3e7b9c15-8a42-4d6f-b0e3-5c1d9a7f2e68
+
+//! Per-host variables kept across reinstalls
+//!
+//! `logs/<hostname>/host-vars.json` holds identifiers that should stay stable when a host is
+//! installed again, or that operators want to look up later. Phase 0 consults it: a stored
+//! install id keeps the dataset names (`rpool/ROOT/ubuntu_<id>`), a stored LUKS UUID is passed
+//! to `luksFormat`, and a changed address is reported. Phase 6 records the values of the new
+//! install. Only identifiers are kept, never keys or passphrases; with `host_vars.push` the
+//! file is also copied into the target.
+
+use super::session::InstallSession;
+use crate::network::SshClient;
+use crate::Result;
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::path::{Path, PathBuf};
+
+/// Suffix of `rpool/ROOT/ubuntu_<id>` and `bpool/BOOT/ubuntu_<id>`
+pub const INSTALL_ID: &str = "install.id";
+/// UUID of the LUKS header on the install disk
+pub const LUKS_UUID: &str = "luks.uuid";
+/// Occupied LUKS keyslots, comma separated
+pub const LUKS_KEYSLOTS: &str = "luks.keyslots";
+pub const RPOOL_GUID: &str = "zfs.rpool_guid";
+pub const BPOOL_GUID: &str = "zfs.bpool_guid";
+/// `ssh-keygen -l` lines of the host keys, one per key type
+pub const SSH_HOST_KEYS: &str = "ssh.host_keys";
+/// Address the host was installed with
+pub const NETWORK_ADDRESS: &str = "network.address";
+
+/// One stored value and where it came from
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct HostVar {
+    pub value: String,
+    /// `install` for values recorded by an install, `operator` for `host-vars set`
+    pub source: String,
+    pub updated_at: DateTime<Utc>,
+}
+
+/// `logs/<hostname>/host-vars.json`
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct HostVars {
+    pub hostname: String,
+    pub vars: BTreeMap<String, HostVar>,
+}
+
+impl HostVars {
+    pub fn path(base_dir: &Path, hostname: &str) -> PathBuf {
+        InstallSession::host_dir(base_dir, hostname).join("host-vars.json")
+    }
+
+    /// The stored variables of `hostname`; empty when nothing was stored yet
+    pub fn load(base_dir: &Path, hostname: &str) -> Result<Self> {
+        let path = Self::path(base_dir, hostname);
+        if !path.exists() {
+            return Ok(Self {
+                hostname: hostname.to_string(),
+                vars: BTreeMap::new(),
+            });
+        }
+        Ok(serde_json::from_str(&std::fs::read_to_string(path)?)?)
+    }
+
+    pub fn save(&self, base_dir: &Path) -> Result<PathBuf> {
+        let path = Self::path(base_dir, &self.hostname);
+        if let Some(parent) = path.parent() {
+            std::fs::create_dir_all(parent)?;
+        }
+        std::fs::write(&path, self.to_json()?)?;
+        Ok(path)
+    }
+
+    pub fn to_json(&self) -> Result<String> {
+        Ok(serde_json::to_string_pretty(self)?)
+    }
+
+    pub fn get(&self, key: &str) -> Option<&str> {
+        self.vars.get(key).map(|var| var.value.as_str())
+    }
+
+    /// Store `value`; returns the previous value when it was different
+    pub fn set(&mut self, key: &str, value: &str, source: &str) -> Option<String> {
+        let previous = self.get(key).map(str::to_string);
+        if previous.as_deref() == Some(value) {
+            return None;
+        }
+        self.vars.insert(
+            key.to_string(),
+            HostVar {
+                value: value.to_string(),
+                source: source.to_string(),
+                updated_at: Utc::now(),
+            },
+        );
+        previous
+    }
+
+    pub fn remove(&mut self, key: &str) -> bool {
+        self.vars.remove(key).is_some()
+    }
+
+    /// Command copying the variables to `target_path` in the system mounted at `root`
+    pub fn build_push_command(&self, root: &str, target_path: &str) -> Result<String> {
+        let full = format!("{}{}", root.trim_end_matches('/'), target_path);
+        let dir = &full[..full.rfind('/').unwrap_or(0)];
+        Ok(format!(
+            "mkdir -p {} && cat > {} << 'EOF'\n{}\nEOF",
+            dir,
+            full,
+            self.to_json()?
+        ))
+    }
+}
+
+/// Occupied keyslot numbers from `cryptsetup luksDump` (LUKS1 and LUKS2 formats)
+pub fn parse_luks_keyslots(dump: &str) -> Vec<u32> {
+    let mut slots = Vec::new();
+    let mut in_keyslots = false;
+    for line in dump.lines() {
+        // LUKS1: "Key Slot 0: ENABLED"
+        if let Some(rest) = line.strip_prefix("Key Slot ") {
+            if let Some((slot, state)) = rest.split_once(':') {
+                if state.trim() == "ENABLED" {
+                    slots.extend(slot.trim().parse::<u32>().ok());
+                }
+            }
+            continue;
+        }
+        // LUKS2: a "Keyslots:" section with "  0: luks2" entries
+        if !line.starts_with(char::is_whitespace) {
+            in_keyslots = line.trim_end() == "Keyslots:";
+            continue;
+        }
+        if in_keyslots {
+            if let Some((slot, _)) = line.trim().split_once(": ") {
+                if let Ok(slot) = slot.parse::<u32>() {
+                    slots.push(slot);
+                }
+            }
+        }
+    }
+    slots
+}
+
+/// Reads the identifiers of a fresh install from the target
+pub struct HostVarsCollector<'a> {
+    ssh: &'a mut SshClient,
+}
+
+impl<'a> HostVarsCollector<'a> {
+    pub fn new(ssh: &'a mut SshClient) -> Self {
+        Self { ssh }
+    }
+
+    /// Current values for the system mounted at `root` with its LUKS partition `luks_device`
+    /// and its pools `root_pool` and `boot_pool`
+    ///
+    /// Values that cannot be read are left out rather than failing the collection.
+    pub async fn collect(
+        &mut self,
+        root: &str,
+        luks_device: &str,
+        root_pool: &str,
+        boot_pool: &str,
+    ) -> Result<BTreeMap<&'static str, String>> {
+        let mut values = BTreeMap::new();
+        let queries = [
+            (LUKS_UUID, format!("cryptsetup luksUUID {}", luks_device)),
+            (
+                RPOOL_GUID,
+                format!("zpool get -H -o value guid {}", root_pool),
+            ),
+            (
+                BPOOL_GUID,
+                format!("zpool get -H -o value guid {}", boot_pool),
+            ),
+            (
+                SSH_HOST_KEYS,
+                format!(
+                    "for f in {}/etc/ssh/ssh_host_*_key.pub; do ssh-keygen -l -f \"$f\"; done",
+                    root.trim_end_matches('/')
+                ),
+            ),
+        ];
+        for (key, command) in queries {
+            if let Ok(output) = self.ssh.execute_with_output(&command).await {
+                let lines: Vec<&str> = output
+                    .lines()
+                    .map(str::trim)
+                    .filter(|l| !l.is_empty())
+                    .collect();
+                if !lines.is_empty() {
+                    values.insert(key, lines.join("\n"));
+                }
+            }
+        }
+        if let Ok(dump) = self
+            .ssh
+            .execute_with_output(&format!("cryptsetup luksDump {}", luks_device))
+            .await
+        {
+            let slots = parse_luks_keyslots(&dump);
+            if !slots.is_empty() {
+                let slots: Vec<String> = slots.iter().map(u32::to_string).collect();
+                values.insert(LUKS_KEYSLOTS, slots.join(","));
+            }
+        }
+        Ok(values)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_store_round_trip() {
+        let dir = tempfile::tempdir().unwrap();
+        let mut vars = HostVars::load(dir.path(), "web-01").unwrap();
+        assert!(vars.vars.is_empty());
+        assert_eq!(vars.set(INSTALL_ID, "ab12cd", "install"), None);
+        assert_eq!(vars.set(INSTALL_ID, "ab12cd", "install"), None);
+        vars.set(NETWORK_ADDRESS, "10.0.0.5/24", "install");
+        assert_eq!(
+            vars.set(NETWORK_ADDRESS, "10.0.0.6/24", "operator")
+                .as_deref(),
+            Some("10.0.0.5/24")
+        );
+        vars.save(dir.path()).unwrap();
+
+        let loaded = HostVars::load(dir.path(), "web-01").unwrap();
+        assert_eq!(loaded.get(INSTALL_ID), Some("ab12cd"));
+        assert_eq!(loaded.vars[NETWORK_ADDRESS].source, "operator");
+        let push = loaded
+            .build_push_command(
+                "/mnt/targetos/",
+                "/etc/ubuntu-autoinstall-agent/host-vars.json",
+            )
+            .unwrap();
+        assert!(push.starts_with(
+            "mkdir -p /mnt/targetos/etc/ubuntu-autoinstall-agent && cat > /mnt/targetos/etc/ubuntu-autoinstall-agent/host-vars.json"
+        ));
+    }
+
+    #[test]
+    fn test_parse_luks_keyslots() {
+        let luks2 = "LUKS header information\nVersion:       \t2\n\nKeyslots:\n  0: luks2\n\tKey:        512 bits\n  2: luks2\n\tKey:        512 bits\nTokens:\n  0: clevis\n";
+        assert_eq!(parse_luks_keyslots(luks2), vec![0, 2]);
+        let luks1 =
+            "Key Slot 0: ENABLED\n\tIterations: 1000\nKey Slot 1: DISABLED\nKey Slot 3: ENABLED\n";
+        assert_eq!(parse_luks_keyslots(luks1), vec![0, 3]);
+    }
+}
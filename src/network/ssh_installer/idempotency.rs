@@ -0,0 +1,225 @@
+// file: src/network/ssh_installer/idempotency.rs
+// version: 1.1.0
+// guid: 3d7a9e24-1c58-4b6f-92e0-8f4b2a6c1d57
+
+//! Idempotency audit of the configuration commands
+//!
+//! With `--audit-idempotency` the configuration commands of the plan run a second time once the
+//! install has applied them, while the target is still mounted. A command that fails on the
+//! second pass, or that changes files under the target's `/etc` again, cannot be re-run safely.
+//! Findings are written to `logs/<hostname>/idempotency-audit.json` and fail the install.
+//! The audit rewrites the target, so it is meant for disposable machines and VMs.
+
+use super::config::InstallationConfig;
+use super::plan::plan_commands;
+use super::session::InstallSession;
+use crate::logging::redact;
+use crate::network::SshClient;
+use crate::Result;
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use std::collections::{BTreeMap, BTreeSet};
+use std::path::{Path, PathBuf};
+use tracing::{info, warn};
+
+/// Longest stderr excerpt kept per failed command
+const MAX_STDERR: usize = 2000;
+
+/// Checksums of every regular file under `<root>/etc`, one `<sha256>  <path>` line each
+pub fn fingerprint_command(root: &str) -> String {
+    format!(
+        "find {}/etc -xdev -type f -print0 2>/dev/null | sort -z | xargs -0 -r sha256sum 2>/dev/null || true",
+        root.trim_end_matches('/')
+    )
+}
+
+/// Files added, removed or rewritten with different content between two fingerprints
+pub fn changed_files(before: &str, after: &str) -> Vec<String> {
+    let parse = |text: &str| -> BTreeMap<String, String> {
+        text.lines()
+            .filter_map(|line| {
+                let (sum, path) = line.split_once("  ")?;
+                Some((path.to_string(), sum.to_string()))
+            })
+            .collect()
+    };
+    let (before, after) = (parse(before), parse(after));
+    let paths: BTreeSet<&String> = before.keys().chain(after.keys()).collect();
+    paths
+        .into_iter()
+        .filter(|path| before.get(*path) != after.get(*path))
+        .cloned()
+        .collect()
+}
+
+/// What went wrong when a command ran the second time
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(tag = "kind", rename_all = "snake_case")]
+pub enum AuditIssue {
+    /// The command exited non-zero
+    Failed { exit_code: i32, stderr: String },
+    /// The command changed files the first pass had already written
+    Changed { files: Vec<String> },
+}
+
+/// A command that is not safe to re-run
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct AuditFinding {
+    /// Plan section the command belongs to
+    pub section: String,
+    pub command: String,
+    pub issue: AuditIssue,
+}
+
+impl AuditFinding {
+    /// One-line description for the log
+    pub fn describe(&self) -> String {
+        let first_line = self.command.lines().next().unwrap_or_default();
+        match &self.issue {
+            AuditIssue::Failed { exit_code, .. } => format!(
+                "[{}] exit {} on second run: {}",
+                self.section, exit_code, first_line
+            ),
+            AuditIssue::Changed { files } => format!(
+                "[{}] changed {} on second run: {}",
+                self.section,
+                files.join(", "),
+                first_line
+            ),
+        }
+    }
+}
+
+/// Result of replaying the plan's commands once
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct IdempotencyReport {
+    pub hostname: String,
+    pub audited_at: DateTime<Utc>,
+    /// Commands run on the second pass
+    pub commands: usize,
+    pub findings: Vec<AuditFinding>,
+}
+
+impl IdempotencyReport {
+    /// Whether every command could be re-run without failing or changing files
+    pub fn is_clean(&self) -> bool {
+        self.findings.is_empty()
+    }
+
+    /// Path of the audit report for `hostname`
+    pub fn record_path(base_dir: &Path, hostname: &str) -> PathBuf {
+        InstallSession::host_dir(base_dir, hostname).join("idempotency-audit.json")
+    }
+
+    /// Write the report to `logs/<hostname>/idempotency-audit.json` under `base_dir`
+    pub fn save(&self, base_dir: &Path) -> Result<PathBuf> {
+        let path = Self::record_path(base_dir, &self.hostname);
+        if let Some(parent) = path.parent() {
+            std::fs::create_dir_all(parent)?;
+        }
+        std::fs::write(
+            &path,
+            redact::scrub(&serde_json::to_string_pretty(self)?).as_bytes(),
+        )?;
+        Ok(path)
+    }
+}
+
+/// Replays configuration commands against a mounted target
+pub struct IdempotencyAuditor<'a> {
+    ssh: &'a mut SshClient,
+}
+
+impl<'a> IdempotencyAuditor<'a> {
+    pub fn new(ssh: &'a mut SshClient) -> Self {
+        Self { ssh }
+    }
+
+    /// Run every configuration command of `config` a second time against `root`
+    pub async fn audit(
+        &mut self,
+        config: &InstallationConfig,
+        root: &str,
+    ) -> Result<IdempotencyReport> {
+        let fingerprint = fingerprint_command(root);
+        let mut report = IdempotencyReport {
+            hostname: config.hostname.clone(),
+            audited_at: Utc::now(),
+            commands: 0,
+            findings: Vec::new(),
+        };
+        for (section, commands) in plan_commands(config, root) {
+            for command in commands {
+                report.commands += 1;
+                let before = self.ssh.execute_with_output(&fingerprint).await?;
+                let (exit_code, _, stderr) = self
+                    .ssh
+                    .execute_with_error_collection(
+                        &command,
+                        &format!("Idempotency audit: {}", section),
+                    )
+                    .await?;
+                let issue = if exit_code != 0 {
+                    Some(AuditIssue::Failed {
+                        exit_code,
+                        stderr: stderr.chars().take(MAX_STDERR).collect(),
+                    })
+                } else {
+                    let after = self.ssh.execute_with_output(&fingerprint).await?;
+                    let files = changed_files(&before, &after);
+                    (!files.is_empty()).then_some(AuditIssue::Changed { files })
+                };
+                if let Some(issue) = issue {
+                    let finding = AuditFinding {
+                        section: section.to_string(),
+                        command,
+                        issue,
+                    };
+                    warn!("Not idempotent: {}", finding.describe());
+                    report.findings.push(finding);
+                }
+            }
+        }
+        info!(
+            "Idempotency audit replayed {} commands, {} not re-runnable",
+            report.commands,
+            report.findings.len()
+        );
+        Ok(report)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_changed_files_and_findings() {
+        let before = "aaa  /mnt/targetos/etc/hosts\nbbb  /mnt/targetos/etc/ufw/user.rules\nccc  /mnt/targetos/etc/old\n";
+        let after = "aaa  /mnt/targetos/etc/hosts\nddd  /mnt/targetos/etc/ufw/user.rules\neee  /mnt/targetos/etc/new\n";
+        assert_eq!(
+            changed_files(before, after),
+            vec![
+                "/mnt/targetos/etc/new",
+                "/mnt/targetos/etc/old",
+                "/mnt/targetos/etc/ufw/user.rules",
+            ]
+        );
+        assert!(changed_files(before, before).is_empty());
+        assert!(fingerprint_command("/mnt/targetos/").starts_with("find /mnt/targetos/etc "));
+
+        let finding = AuditFinding {
+            section: "firewall".to_string(),
+            command: "ufw allow 22\nufw enable".to_string(),
+            issue: AuditIssue::Changed {
+                files: vec!["/mnt/targetos/etc/ufw/user.rules".to_string()],
+            },
+        };
+        assert_eq!(
+            finding.describe(),
+            "[firewall] changed /mnt/targetos/etc/ufw/user.rules on second run: ufw allow 22"
+        );
+        let json = serde_json::to_value(&finding).unwrap();
+        assert_eq!(json["issue"]["kind"], "changed");
+    }
+}
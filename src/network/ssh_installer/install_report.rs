@@ -0,0 +1,1156 @@
+// file: src/network/ssh_installer/install_report.rs
+// version: 1.8.0
+// guid: 6f1d8b3a-2c47-4e9a-b5d0-7a3e9c1f4b26
+
+//! Installation report rendering
+//!
+//! Turns a session record into a Markdown, plain-text or HTML summary (phases, durations,
+//! warnings and next steps). The plain-text form is ASCII-only and wrapped at 72 columns so
+//! it survives being pasted into email or chat notifications unchanged.
+
+use super::disk_health::DiskHealthCheck;
+use super::health_score::HealthScore;
+use super::investigation_report::html_escape;
+use super::mirror_select::MirrorDecision;
+use super::session::{InstallSession, SessionStatus};
+use crate::config::zfs_tuning::{MachineRole, ZfsTuning};
+use crate::logging::redact;
+use crate::network::redfish::HardwareInventory;
+use crate::Result;
+use std::path::{Path, PathBuf};
+
+/// Column limit for the plain-text report
+const TEXT_WIDTH: usize = 72;
+
+/// Output format of an installation report
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum InstallReportFormat {
+    Markdown,
+    Text,
+    Html,
+}
+
+impl InstallReportFormat {
+    /// Every format, in the order the report files are written
+    pub const ALL: &'static [InstallReportFormat] = &[
+        InstallReportFormat::Markdown,
+        InstallReportFormat::Text,
+        InstallReportFormat::Html,
+    ];
+
+    /// File extension for this format
+    pub fn extension(&self) -> &'static str {
+        match self {
+            InstallReportFormat::Markdown => "md",
+            InstallReportFormat::Text => "txt",
+            InstallReportFormat::Html => "html",
+        }
+    }
+}
+
+/// One row of the phase table
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct PhaseRow {
+    pub name: String,
+    /// "ok", "failed", "interrupted" or "running"
+    pub outcome: &'static str,
+    pub duration: Option<String>,
+    /// Error message for failed phases
+    pub detail: Option<String>,
+}
+
+/// Summary of an installation session ready for rendering
+#[derive(Debug, Clone)]
+pub struct InstallReport<'a> {
+    session: &'a InstallSession,
+}
+
+impl<'a> InstallReport<'a> {
+    pub fn new(session: &'a InstallSession) -> Self {
+        Self { session }
+    }
+
+    fn status_label(&self) -> &'static str {
+        match self.session.status {
+            SessionStatus::Running => "RUNNING",
+            SessionStatus::Completed => "SUCCEEDED",
+            SessionStatus::Failed => "FAILED",
+            SessionStatus::Cancelled => "CANCELLED",
+        }
+    }
+
+    /// Phases in execution order with their outcome and duration
+    pub fn phases(&self) -> Vec<PhaseRow> {
+        let session = self.session;
+        let failure_for = |name: &str| {
+            session
+                .failed_phases
+                .iter()
+                .find_map(|f| f.strip_prefix(name))
+                .map(|rest| rest.trim_start_matches(" - ").to_string())
+        };
+
+        let mut rows: Vec<PhaseRow> = session
+            .phase_timings
+            .iter()
+            .map(|timing| {
+                let detail = failure_for(&timing.name);
+                let outcome = if session.completed_phases.contains(&timing.name) {
+                    "ok"
+                } else if detail.is_some() {
+                    "failed"
+                } else if session.status == SessionStatus::Running {
+                    "running"
+                } else {
+                    "interrupted"
+                };
+                PhaseRow {
+                    name: timing.name.clone(),
+                    outcome,
+                    duration: timing.duration().map(format_duration),
+                    detail,
+                }
+            })
+            .collect();
+
+        // Records written before phase timing existed only list phase names
+        for name in &session.completed_phases {
+            if !rows.iter().any(|r| &r.name == name) {
+                rows.push(PhaseRow {
+                    name: name.clone(),
+                    outcome: "ok",
+                    duration: None,
+                    detail: None,
+                });
+            }
+        }
+        for failed in &session.failed_phases {
+            if !rows.iter().any(|r| failed.starts_with(&r.name)) {
+                let (name, detail) = failed.split_once(" - ").unwrap_or((failed, ""));
+                rows.push(PhaseRow {
+                    name: name.to_string(),
+                    outcome: "failed",
+                    duration: None,
+                    detail: (!detail.is_empty()).then(|| detail.to_string()),
+                });
+            }
+        }
+        rows
+    }
+
+    /// Suggested follow-up actions for the session outcome
+    pub fn next_steps(&self) -> Vec<String> {
+        let host = &self.session.hostname;
+        match self.session.status {
+            SessionStatus::Completed => vec![
+                format!(
+                    "Reboot {} from its local disk and unlock LUKS at the prompt",
+                    host
+                ),
+                "Confirm `zpool status` reports bpool and rpool ONLINE".to_string(),
+                format!(
+                    "Run `drift-check --hostname {}` later to detect changes from this install",
+                    host
+                ),
+            ],
+            SessionStatus::Failed => {
+                let mut steps = vec![
+                    format!(
+                        "Inspect the target over SSH; hold mode leaves {} mounted as-is",
+                        host
+                    ),
+                    format!("Review the debug logs under logs/{}/", host),
+                ];
+                if let Some(first) = self.session.failed_phases.first() {
+                    steps.push(format!("Fix the cause of: {}", first));
+                }
+                steps.push("Re-run ssh-install once the problem is resolved".to_string());
+                steps
+            }
+            SessionStatus::Cancelled => vec![
+                format!(
+                    "The target was left as-is {}",
+                    self.session
+                        .current_phase
+                        .as_deref()
+                        .unwrap_or("before start")
+                ),
+                "Re-run ssh-install to start the installation again".to_string(),
+            ],
+            SessionStatus::Running => {
+                vec!["The installation is still running; check again later".to_string()]
+            }
+        }
+    }
+
+    fn elapsed(&self) -> String {
+        format_duration(self.session.elapsed())
+    }
+
+    /// Render in `format`
+    pub fn render(&self, format: InstallReportFormat) -> String {
+        match format {
+            InstallReportFormat::Markdown => self.to_markdown(),
+            InstallReportFormat::Text => self.to_text(),
+            InstallReportFormat::Html => self.to_html(),
+        }
+    }
+
+    /// `N of M hardening checks passed`
+    pub fn probe_summary(&self) -> String {
+        let probes = &self.session.probe_results;
+        format!(
+            "{} of {} service probes passed",
+            probes.iter().filter(|p| p.passed).count(),
+            probes.len()
+        )
+    }
+
+    pub fn compliance_summary(&self) -> String {
+        let compliance = &self.session.compliance;
+        format!(
+            "{} of {} hardening checks passed",
+            compliance.iter().filter(|r| r.passed).count(),
+            compliance.len()
+        )
+    }
+
+    /// ZFS tuning written to the target, if any parameters were set
+    fn zfs_tuning(&self) -> Option<&ZfsTuning> {
+        self.session.zfs_tuning.as_ref().filter(|t| !t.is_empty())
+    }
+
+    /// Mirror chosen by the speed test, if one ran
+    fn mirror_selection(&self) -> Option<&MirrorDecision> {
+        self.session.mirror_selection.as_ref()
+    }
+
+    /// SMART health of the install disk, if preflight read it
+    fn disk_health(&self) -> Option<&DiskHealthCheck> {
+        self.session.disk_health.as_ref()
+    }
+
+    /// Go/no-go score computed before Phase 2, if the gate ran
+    fn health_score(&self) -> Option<&HealthScore> {
+        self.session.health_score.as_ref()
+    }
+
+    /// BMC inventory, if one was collected
+    fn hardware_inventory(&self) -> Option<&HardwareInventory> {
+        self.session.hardware_inventory.as_ref()
+    }
+
+    /// `Sized for a server with 32768 MiB of RAM`
+    fn zfs_tuning_basis(&self, tuning: &ZfsTuning) -> String {
+        let role = match tuning.role {
+            MachineRole::Server => "server",
+            MachineRole::Desktop => "desktop",
+        };
+        match tuning.memory_total_mb {
+            Some(memory) => format!("Sized for a {} with {} MiB of RAM", role, memory),
+            None => format!("Sized for a {}; target memory was unknown", role),
+        }
+    }
+
+    /// Markdown summary with a phase table
+    pub fn to_markdown(&self) -> String {
+        let session = self.session;
+        let mut md = format!(
+            "# Installation report: {}\n\n\
+             - **Status:** {}\n- **Session:** {}\n- **Started:** {}\n- **Duration:** {}\n",
+            session.hostname,
+            self.status_label(),
+            session.id,
+            session.started_at.to_rfc3339(),
+            self.elapsed()
+        );
+        if let Some(cmd) = &session.last_command {
+            md.push_str(&format!(
+                "- **Last command:** `{}`\n",
+                cmd.replace('`', "'")
+            ));
+        }
+
+        md.push_str("\n## Phases\n\n| Phase | Result | Duration | Details |\n|---|---|---|---|\n");
+        for row in self.phases() {
+            md.push_str(&format!(
+                "| {} | {} | {} | {} |\n",
+                markdown_cell(&row.name),
+                row.outcome,
+                row.duration.as_deref().unwrap_or("-"),
+                markdown_cell(row.detail.as_deref().unwrap_or(""))
+            ));
+        }
+
+        if !session.warnings.is_empty() {
+            md.push_str("\n## Warnings\n\n");
+            for warning in &session.warnings {
+                md.push_str(&format!("- {}\n", warning));
+            }
+        }
+
+        if !session.compliance.is_empty() {
+            md.push_str(&format!(
+                "\n## Compliance\n\n{}\n\n| Check | Control | Result |\n|---|---|---|\n",
+                self.compliance_summary()
+            ));
+            for result in &session.compliance {
+                md.push_str(&format!(
+                    "| {} | {} | {} |\n",
+                    markdown_cell(&result.title),
+                    result.control.as_str(),
+                    if result.passed { "pass" } else { "FAIL" }
+                ));
+            }
+        }
+
+        if !session.probe_results.is_empty() {
+            md.push_str(&format!(
+                "\n## Service probes\n\n{}\n\n| Probe | Role | Type | Result | Attempts | Observed |\n|---|---|---|---|---|---|\n",
+                self.probe_summary()
+            ));
+            for probe in &session.probe_results {
+                md.push_str(&format!(
+                    "| {} | {} | {} | {} | {} | {} |\n",
+                    markdown_cell(&probe.name),
+                    probe.role.as_str(),
+                    probe.kind,
+                    if probe.passed { "pass" } else { "FAIL" },
+                    probe.attempts,
+                    markdown_cell(&probe.detail)
+                ));
+            }
+        }
+
+        if let Some(decision) = self.mirror_selection() {
+            md.push_str(&format!(
+                "\n## Mirror selection\n\nUsing {} for network {}{}.\n\n| Mirror | Result |\n|---|---|\n",
+                decision.mirror,
+                markdown_cell(&decision.network),
+                if decision.cached { " (cached choice)" } else { "" }
+            ));
+            for probe in &decision.probes {
+                md.push_str(&format!(
+                    "| {} | {} |\n",
+                    markdown_cell(&probe.mirror),
+                    probe.describe()
+                ));
+            }
+        }
+
+        if let Some(tuning) = self.zfs_tuning() {
+            md.push_str(&format!(
+                "\n## ZFS tuning\n\n{}\n\n| Parameter | Value | Source | Reason |\n|---|---|---|---|\n",
+                self.zfs_tuning_basis(tuning)
+            ));
+            for parameter in &tuning.parameters {
+                md.push_str(&format!(
+                    "| {} | {} | {} | {} |\n",
+                    parameter.name,
+                    parameter.value,
+                    parameter.source.as_str(),
+                    markdown_cell(&parameter.reason)
+                ));
+            }
+        }
+
+        if let Some(check) = self.disk_health() {
+            md.push_str("\n## Disk health\n\n| Field | Value |\n|---|---|\n");
+            for (label, value) in check.summary_rows() {
+                md.push_str(&format!("| {} | {} |\n", label, markdown_cell(&value)));
+            }
+            for finding in check.failures.iter().chain(&check.warnings) {
+                md.push_str(&format!("\n- {}", finding));
+            }
+            if !check.failures.is_empty() || !check.warnings.is_empty() {
+                md.push('\n');
+            }
+        }
+
+        if let Some(score) = self.health_score() {
+            md.push_str("\n## Health gate\n\n| Field | Value |\n|---|---|\n");
+            for (label, value) in score.summary_rows() {
+                md.push_str(&format!("| {} | {} |\n", label, markdown_cell(&value)));
+            }
+        }
+
+        if let Some(inventory) = self.hardware_inventory() {
+            md.push_str("\n## Hardware inventory\n\n| Field | Value |\n|---|---|\n");
+            for (label, value) in inventory.summary_rows() {
+                md.push_str(&format!("| {} | {} |\n", label, markdown_cell(&value)));
+            }
+            for firmware in &inventory.firmware {
+                md.push_str(&format!(
+                    "| Firmware: {} | {} |\n",
+                    markdown_cell(&firmware.name),
+                    markdown_cell(&firmware.version)
+                ));
+            }
+            if !inventory.memory.is_empty() {
+                md.push_str("\n| DIMM slot | Size | Part number | Speed | Health |\n|---|---|---|---|---|\n");
+                for dimm in &inventory.memory {
+                    md.push_str(&format!(
+                        "| {} | {} | {} | {} | {} |\n",
+                        markdown_cell(&dimm.locator),
+                        dimm_size(dimm.capacity_mib, dimm.populated),
+                        markdown_cell(dimm.part_number.as_deref().unwrap_or("-")),
+                        dimm.speed_mhz
+                            .map(|s| format!("{} MHz", s))
+                            .unwrap_or_else(|| "-".to_string()),
+                        dimm.health.as_deref().unwrap_or("-")
+                    ));
+                }
+            }
+            if !inventory.power_supplies.is_empty() {
+                md.push_str("\n| Power supply | Model | Serial | Capacity | Health |\n|---|---|---|---|---|\n");
+                for psu in &inventory.power_supplies {
+                    md.push_str(&format!(
+                        "| {} | {} | {} | {} | {} |\n",
+                        markdown_cell(&psu.name),
+                        markdown_cell(psu.model.as_deref().unwrap_or("-")),
+                        markdown_cell(psu.serial_number.as_deref().unwrap_or("-")),
+                        psu.capacity_watts
+                            .map(|w| format!("{} W", w))
+                            .unwrap_or_else(|| "-".to_string()),
+                        psu.health.as_deref().unwrap_or("-")
+                    ));
+                }
+            }
+            for section in &inventory.unavailable {
+                md.push_str(&format!("\n- Not available from the BMC: {}\n", section));
+            }
+        }
+
+        md.push_str("\n## Next steps\n\n");
+        for (i, step) in self.next_steps().iter().enumerate() {
+            md.push_str(&format!("{}. {}\n", i + 1, step));
+        }
+        md
+    }
+
+    /// ASCII-only plain text wrapped at 72 columns
+    pub fn to_text(&self) -> String {
+        let session = self.session;
+        let mut lines = vec![
+            format!("INSTALLATION REPORT: {}", session.hostname),
+            "=".repeat(TEXT_WIDTH.min(21 + session.hostname.len())),
+            format!("Status:   {}", self.status_label()),
+            format!("Session:  {}", session.id),
+            format!("Started:  {}", session.started_at.to_rfc3339()),
+            format!("Duration: {}", self.elapsed()),
+        ];
+        if let Some(cmd) = &session.last_command {
+            lines.extend(wrap(&format!("Last command: {}", cmd), "  "));
+        }
+
+        lines.push(String::new());
+        lines.push("PHASES".to_string());
+        for row in self.phases() {
+            lines.extend(wrap(
+                &format!(
+                    "[{}] {} ({})",
+                    row.outcome,
+                    row.name,
+                    row.duration.as_deref().unwrap_or("-")
+                ),
+                "    ",
+            ));
+            if let Some(detail) = &row.detail {
+                lines.extend(wrap(&format!("    error: {}", detail), "      "));
+            }
+        }
+
+        if !session.warnings.is_empty() {
+            lines.push(String::new());
+            lines.push("WARNINGS".to_string());
+            for warning in &session.warnings {
+                lines.extend(wrap(&format!("  - {}", warning), "    "));
+            }
+        }
+
+        if !session.compliance.is_empty() {
+            lines.push(String::new());
+            lines.push("COMPLIANCE".to_string());
+            lines.push(format!("  {}", self.compliance_summary()));
+            for result in &session.compliance {
+                lines.extend(wrap(
+                    &format!(
+                        "  [{}] {}",
+                        if result.passed { "pass" } else { "FAIL" },
+                        result.title
+                    ),
+                    "         ",
+                ));
+            }
+        }
+
+        if !session.probe_results.is_empty() {
+            lines.push(String::new());
+            lines.push("SERVICE PROBES".to_string());
+            lines.push(format!("  {}", self.probe_summary()));
+            for probe in &session.probe_results {
+                lines.extend(wrap(
+                    &format!(
+                        "  [{}] {} ({}, {}): {}",
+                        if probe.passed { "pass" } else { "FAIL" },
+                        probe.name,
+                        probe.role.as_str(),
+                        probe.kind,
+                        probe.detail
+                    ),
+                    "         ",
+                ));
+            }
+        }
+
+        if let Some(decision) = self.mirror_selection() {
+            lines.push(String::new());
+            lines.push("MIRROR SELECTION".to_string());
+            lines.extend(wrap(&format!("  {}", decision.summary()), "    "));
+            for probe in &decision.probes {
+                lines.extend(wrap(
+                    &format!("  {}: {}", probe.mirror, probe.describe()),
+                    "    ",
+                ));
+            }
+        }
+
+        if let Some(tuning) = self.zfs_tuning() {
+            lines.push(String::new());
+            lines.push("ZFS TUNING".to_string());
+            lines.push(format!("  {}", self.zfs_tuning_basis(tuning)));
+            for parameter in &tuning.parameters {
+                lines.extend(wrap(
+                    &format!(
+                        "  {}={} ({}: {})",
+                        parameter.name,
+                        parameter.value,
+                        parameter.source.as_str(),
+                        parameter.reason
+                    ),
+                    "    ",
+                ));
+            }
+        }
+
+        if let Some(check) = self.disk_health() {
+            lines.push(String::new());
+            lines.push("DISK HEALTH".to_string());
+            for (label, value) in check.summary_rows() {
+                lines.extend(wrap(&format!("  {}: {}", label, value), "    "));
+            }
+            for finding in check.failures.iter().chain(&check.warnings) {
+                lines.extend(wrap(&format!("  - {}", finding), "    "));
+            }
+        }
+
+        if let Some(score) = self.health_score() {
+            lines.push(String::new());
+            lines.push("HEALTH GATE".to_string());
+            for (label, value) in score.summary_rows() {
+                lines.extend(wrap(&format!("  {}: {}", label, value), "    "));
+            }
+        }
+
+        if let Some(inventory) = self.hardware_inventory() {
+            lines.push(String::new());
+            lines.push("HARDWARE INVENTORY".to_string());
+            for (label, value) in inventory.summary_rows() {
+                lines.extend(wrap(&format!("  {}: {}", label, value), "    "));
+            }
+            for firmware in &inventory.firmware {
+                lines.extend(wrap(
+                    &format!("  Firmware {}: {}", firmware.name, firmware.version),
+                    "    ",
+                ));
+            }
+            for dimm in &inventory.memory {
+                lines.extend(wrap(
+                    &format!(
+                        "  [{}] {} {}",
+                        dimm_size(dimm.capacity_mib, dimm.populated),
+                        dimm.locator,
+                        dimm.part_number.as_deref().unwrap_or("")
+                    ),
+                    "    ",
+                ));
+            }
+            for psu in &inventory.power_supplies {
+                lines.extend(wrap(
+                    &format!(
+                        "  [{}] {} {}",
+                        psu.health.as_deref().unwrap_or("?"),
+                        psu.name,
+                        psu.serial_number.as_deref().unwrap_or("")
+                    ),
+                    "    ",
+                ));
+            }
+            for section in &inventory.unavailable {
+                lines.extend(wrap(&format!("  Not available: {}", section), "    "));
+            }
+        }
+
+        lines.push(String::new());
+        lines.push("NEXT STEPS".to_string());
+        for (i, step) in self.next_steps().iter().enumerate() {
+            lines.extend(wrap(&format!("  {}. {}", i + 1, step), "     "));
+        }
+
+        let mut text = lines.join("\n");
+        text.push('\n');
+        to_ascii(&text)
+    }
+
+    /// Standalone HTML page
+    pub fn to_html(&self) -> String {
+        let session = self.session;
+        let mut html = format!(
+            "<!DOCTYPE html>\n<html><head><meta charset=\"utf-8\"><title>Installation: {0}</title>\
+             <style>body{{font-family:sans-serif}}table{{border-collapse:collapse}}\
+             td,th{{border:1px solid #ccc;padding:4px 8px;text-align:left}}</style></head><body>\n\
+             <h1>{0}: {1}</h1>\n<p>Session {2} &middot; started {3} &middot; {4}</p>\n",
+            html_escape(&session.hostname),
+            self.status_label(),
+            html_escape(&session.id),
+            session.started_at.to_rfc3339(),
+            self.elapsed()
+        );
+
+        html.push_str("<h2>Phases</h2>\n<table><tr><th>Phase</th><th>Result</th><th>Duration</th><th>Details</th></tr>\n");
+        for row in self.phases() {
+            html.push_str(&format!(
+                "<tr><td>{}</td><td>{}</td><td>{}</td><td>{}</td></tr>\n",
+                html_escape(&row.name),
+                row.outcome,
+                row.duration.as_deref().unwrap_or("-"),
+                html_escape(row.detail.as_deref().unwrap_or(""))
+            ));
+        }
+        html.push_str("</table>\n");
+
+        if !session.warnings.is_empty() {
+            html.push_str("<h2>Warnings</h2>\n<ul>\n");
+            for warning in &session.warnings {
+                html.push_str(&format!("<li>{}</li>\n", html_escape(warning)));
+            }
+            html.push_str("</ul>\n");
+        }
+
+        if !session.compliance.is_empty() {
+            html.push_str(&format!(
+                "<h2>Compliance</h2>\n<p>{}</p>\n<table><tr><th>Check</th><th>Control</th><th>Result</th></tr>\n",
+                self.compliance_summary()
+            ));
+            for result in &session.compliance {
+                html.push_str(&format!(
+                    "<tr><td>{}</td><td>{}</td><td>{}</td></tr>\n",
+                    html_escape(&result.title),
+                    result.control.as_str(),
+                    if result.passed { "pass" } else { "FAIL" }
+                ));
+            }
+            html.push_str("</table>\n");
+        }
+
+        if !session.probe_results.is_empty() {
+            html.push_str(&format!(
+                "<h2>Service probes</h2>\n<p>{}</p>\n<table><tr><th>Probe</th><th>Role</th><th>Type</th><th>Result</th><th>Attempts</th><th>Observed</th></tr>\n",
+                self.probe_summary()
+            ));
+            for probe in &session.probe_results {
+                html.push_str(&format!(
+                    "<tr><td>{}</td><td>{}</td><td>{}</td><td>{}</td><td>{}</td><td>{}</td></tr>\n",
+                    html_escape(&probe.name),
+                    probe.role.as_str(),
+                    probe.kind,
+                    if probe.passed { "pass" } else { "FAIL" },
+                    probe.attempts,
+                    html_escape(&probe.detail)
+                ));
+            }
+            html.push_str("</table>\n");
+        }
+
+        if let Some(decision) = self.mirror_selection() {
+            html.push_str(&format!(
+                "<h2>Mirror selection</h2>\n<p>{}</p>\n<table><tr><th>Mirror</th><th>Result</th></tr>\n",
+                html_escape(&decision.summary())
+            ));
+            for probe in &decision.probes {
+                html.push_str(&format!(
+                    "<tr><td>{}</td><td>{}</td></tr>\n",
+                    html_escape(&probe.mirror),
+                    probe.describe()
+                ));
+            }
+            html.push_str("</table>\n");
+        }
+
+        if let Some(tuning) = self.zfs_tuning() {
+            html.push_str(&format!(
+                "<h2>ZFS tuning</h2>\n<p>{}</p>\n<table><tr><th>Parameter</th><th>Value</th><th>Source</th><th>Reason</th></tr>\n",
+                self.zfs_tuning_basis(tuning)
+            ));
+            for parameter in &tuning.parameters {
+                html.push_str(&format!(
+                    "<tr><td>{}</td><td>{}</td><td>{}</td><td>{}</td></tr>\n",
+                    html_escape(&parameter.name),
+                    html_escape(&parameter.value),
+                    parameter.source.as_str(),
+                    html_escape(&parameter.reason)
+                ));
+            }
+            html.push_str("</table>\n");
+        }
+
+        if let Some(check) = self.disk_health() {
+            html.push_str("<h2>Disk health</h2>\n<table>\n");
+            for (label, value) in check.summary_rows() {
+                html.push_str(&format!(
+                    "<tr><th>{}</th><td>{}</td></tr>\n",
+                    label,
+                    html_escape(&value)
+                ));
+            }
+            html.push_str("</table>\n");
+            if !check.failures.is_empty() || !check.warnings.is_empty() {
+                html.push_str("<ul>\n");
+                for finding in check.failures.iter().chain(&check.warnings) {
+                    html.push_str(&format!("<li>{}</li>\n", html_escape(finding)));
+                }
+                html.push_str("</ul>\n");
+            }
+        }
+
+        if let Some(score) = self.health_score() {
+            html.push_str("<h2>Health gate</h2>\n<table>\n");
+            for (label, value) in score.summary_rows() {
+                html.push_str(&format!(
+                    "<tr><th>{}</th><td>{}</td></tr>\n",
+                    html_escape(&label),
+                    html_escape(&value)
+                ));
+            }
+            html.push_str("</table>\n");
+        }
+
+        if let Some(inventory) = self.hardware_inventory() {
+            html.push_str("<h2>Hardware inventory</h2>\n<table>\n");
+            for (label, value) in inventory.summary_rows() {
+                html.push_str(&format!(
+                    "<tr><th>{}</th><td>{}</td></tr>\n",
+                    label,
+                    html_escape(&value)
+                ));
+            }
+            for firmware in &inventory.firmware {
+                html.push_str(&format!(
+                    "<tr><th>Firmware: {}</th><td>{}</td></tr>\n",
+                    html_escape(&firmware.name),
+                    html_escape(&firmware.version)
+                ));
+            }
+            html.push_str("</table>\n");
+            if !inventory.memory.is_empty() {
+                html.push_str("<table><tr><th>DIMM slot</th><th>Size</th><th>Part number</th><th>Health</th></tr>\n");
+                for dimm in &inventory.memory {
+                    html.push_str(&format!(
+                        "<tr><td>{}</td><td>{}</td><td>{}</td><td>{}</td></tr>\n",
+                        html_escape(&dimm.locator),
+                        dimm_size(dimm.capacity_mib, dimm.populated),
+                        html_escape(dimm.part_number.as_deref().unwrap_or("")),
+                        html_escape(dimm.health.as_deref().unwrap_or(""))
+                    ));
+                }
+                html.push_str("</table>\n");
+            }
+            if !inventory.power_supplies.is_empty() {
+                html.push_str("<table><tr><th>Power supply</th><th>Model</th><th>Serial</th><th>Health</th></tr>\n");
+                for psu in &inventory.power_supplies {
+                    html.push_str(&format!(
+                        "<tr><td>{}</td><td>{}</td><td>{}</td><td>{}</td></tr>\n",
+                        html_escape(&psu.name),
+                        html_escape(psu.model.as_deref().unwrap_or("")),
+                        html_escape(psu.serial_number.as_deref().unwrap_or("")),
+                        html_escape(psu.health.as_deref().unwrap_or(""))
+                    ));
+                }
+                html.push_str("</table>\n");
+            }
+        }
+
+        html.push_str("<h2>Next steps</h2>\n<ol>\n");
+        for step in self.next_steps() {
+            html.push_str(&format!("<li>{}</li>\n", html_escape(&step)));
+        }
+        html.push_str("</ol>\n</body></html>\n");
+        html
+    }
+
+    /// Write `report.md`, `report.txt` and `report.html` into `dir`
+    pub fn write_all(&self, dir: &Path) -> Result<Vec<PathBuf>> {
+        std::fs::create_dir_all(dir)?;
+        let mut paths = Vec::new();
+        for format in InstallReportFormat::ALL {
+            let path = dir.join(format!("report.{}", format.extension()));
+            std::fs::write(&path, redact::scrub(&self.render(*format)).as_bytes())?;
+            paths.push(path);
+        }
+        Ok(paths)
+    }
+}
+
+/// Compact duration such as `1h 02m 03s`, `4m 05s` or `12s`
+/// `32768 MiB`, or `empty` for an unpopulated slot
+fn dimm_size(capacity_mib: Option<u64>, populated: bool) -> String {
+    match (populated, capacity_mib) {
+        (false, _) => "empty".to_string(),
+        (true, Some(mib)) => format!("{} MiB", mib),
+        (true, None) => "populated".to_string(),
+    }
+}
+
+pub fn format_duration(duration: chrono::Duration) -> String {
+    let secs = duration.num_seconds().max(0);
+    let (h, m, s) = (secs / 3600, (secs % 3600) / 60, secs % 60);
+    if h > 0 {
+        format!("{}h {:02}m {:02}s", h, m, s)
+    } else if m > 0 {
+        format!("{}m {:02}s", m, s)
+    } else {
+        format!("{}s", s)
+    }
+}
+
+fn markdown_cell(text: &str) -> String {
+    text.replace('|', "\\|").replace('\n', " ")
+}
+
+/// Replace characters outside printable ASCII so the text is safe for any mail client
+fn to_ascii(text: &str) -> String {
+    text.chars()
+        .map(|c| match c {
+            '\n' | ' '..='~' => c,
+            '\u{2014}' | '\u{2013}' => '-',
+            '\u{2018}' | '\u{2019}' => '\'',
+            '\u{201c}' | '\u{201d}' => '"',
+            _ => '?',
+        })
+        .collect()
+}
+
+/// Word-wrap `line` at `TEXT_WIDTH`, indenting continuation lines with `indent`
+fn wrap(line: &str, indent: &str) -> Vec<String> {
+    let lead = &line[..line.len() - line.trim_start().len()];
+    let mut lines = Vec::new();
+    let mut current = lead.to_string();
+    let mut empty = true;
+    for word in line.split_whitespace() {
+        if !empty && current.chars().count() + 1 + word.chars().count() > TEXT_WIDTH {
+            lines.push(std::mem::replace(&mut current, indent.to_string()));
+            empty = true;
+        }
+        if !empty {
+            current.push(' ');
+        }
+        current.push_str(word);
+        empty = false;
+    }
+    lines.push(current);
+    lines
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::Duration;
+    use tempfile::TempDir;
+
+    fn failed_session() -> InstallSession {
+        let mut session = InstallSession::new("host-a");
+        session.start_phase("Phase 0: Setup variables");
+        session.completed_phases = vec!["Phase 0: Setup variables".into()];
+        session.start_phase("Phase 1: Package installation");
+        session.end_phase();
+        session.failed_phases =
+            vec!["Phase 1: Package installation - apt failed | exit 100".into()];
+        session.status = SessionStatus::Failed;
+        session
+            .warnings
+            .push("Failed to collect host baseline: timeout".into());
+        session
+    }
+
+    #[test]
+    fn test_phase_rows_merge_timings_and_failures() {
+        let session = failed_session();
+        let rows = InstallReport::new(&session).phases();
+        assert_eq!(rows.len(), 2);
+        assert_eq!(rows[0].outcome, "ok");
+        assert_eq!(rows[1].outcome, "failed");
+        assert_eq!(rows[1].detail.as_deref(), Some("apt failed | exit 100"));
+        assert!(rows[1].duration.is_some());
+    }
+
+    #[test]
+    fn test_markdown_and_html_escape_content() {
+        let session = failed_session();
+        let report = InstallReport::new(&session);
+
+        let md = report.to_markdown();
+        assert!(md.contains("# Installation report: host-a"));
+        assert!(md.contains("apt failed \\| exit 100"));
+        assert!(md.contains("## Warnings"));
+
+        let mut session = failed_session();
+        session.hostname = "<host>".into();
+        let html = InstallReport::new(&session).to_html();
+        assert!(html.contains("&lt;host&gt;"));
+        assert!(!html.contains("<host>"));
+    }
+
+    #[test]
+    fn test_compliance_section_lists_results() {
+        use crate::config::hardening::{ComplianceResult, HardeningControl};
+
+        let mut session = failed_session();
+        assert!(!InstallReport::new(&session)
+            .to_markdown()
+            .contains("Compliance"));
+        session.compliance = vec![
+            ComplianceResult {
+                control: HardeningControl::Ssh,
+                title: "sshd: PermitRootLogin no".into(),
+                passed: true,
+            },
+            ComplianceResult {
+                control: HardeningControl::Sysctl,
+                title: "sysctl: fs.suid_dumpable = 0".into(),
+                passed: false,
+            },
+        ];
+        let report = InstallReport::new(&session);
+        assert_eq!(
+            report.compliance_summary(),
+            "1 of 2 hardening checks passed"
+        );
+        assert!(report
+            .to_markdown()
+            .contains("| sysctl: fs.suid_dumpable = 0 | sysctl | FAIL |"));
+        assert!(report
+            .to_text()
+            .contains("  [pass] sshd: PermitRootLogin no\n"));
+        assert!(report.to_html().contains("<h2>Compliance</h2>"));
+    }
+
+    #[test]
+    fn test_probe_section_lists_each_probe() {
+        use super::super::probes::ProbeResult;
+        use crate::config::verification::ProbeRole;
+
+        let mut session = failed_session();
+        session.probe_results = vec![ProbeResult {
+            name: "api".into(),
+            role: ProbeRole::Readiness,
+            kind: "http".into(),
+            passed: false,
+            detail: "status 503".into(),
+            attempts: 12,
+            elapsed_secs: 60,
+        }];
+        let report = InstallReport::new(&session);
+        assert_eq!(report.probe_summary(), "0 of 1 service probes passed");
+        assert!(report
+            .to_markdown()
+            .contains("| api | readiness | http | FAIL | 12 | status 503 |"));
+        assert!(report
+            .to_text()
+            .contains("  [FAIL] api (readiness, http): status 503\n"));
+        assert!(report.to_html().contains("<h2>Service probes</h2>"));
+    }
+
+    #[test]
+    fn test_zfs_tuning_section_shows_chosen_values() {
+        use crate::config::ZfsTuningConfig;
+
+        let mut session = failed_session();
+        session.zfs_tuning = Some(ZfsTuningConfig::default().compute(None).unwrap());
+        assert!(!InstallReport::new(&session)
+            .to_markdown()
+            .contains("ZFS tuning"));
+
+        session.zfs_tuning = Some(ZfsTuningConfig::default().compute(Some(8192)).unwrap());
+        let report = InstallReport::new(&session);
+        let md = report.to_markdown();
+        assert!(md.contains("Sized for a server with 8192 MiB of RAM"));
+        assert!(md.contains("| zfs_arc_max | 4294967296 | computed |"));
+        assert!(report
+            .to_text()
+            .contains("  zfs_arc_max=4294967296 (computed:"));
+        assert!(report.to_html().contains("<h2>ZFS tuning</h2>"));
+    }
+
+    #[test]
+    fn test_disk_health_section() {
+        use super::super::disk_health::{DiskHealth, HealthSource};
+        use crate::config::disk_health::DiskHealthConfig;
+
+        let mut session = failed_session();
+        let health = DiskHealth {
+            source: HealthSource::NvmeCli,
+            model: None,
+            serial: None,
+            smart_passed: None,
+            critical_warning: Some(0),
+            reallocated_sectors: None,
+            pending_sectors: None,
+            percentage_used: Some(91),
+            media_errors: Some(0),
+            temperature_c: None,
+            power_on_hours: None,
+        };
+        session.disk_health = Some(DiskHealthCheck::evaluate(
+            "/dev/nvme0n1",
+            Some(health),
+            &DiskHealthConfig::default(),
+        ));
+        let report = InstallReport::new(&session);
+        let md = report.to_markdown();
+        assert!(md.contains("| Verdict | warning |"));
+        assert!(md.contains("| Endurance used | 91% |"));
+        assert!(md.contains("- percentage_used is 91, above the warning limit 80"));
+        assert!(report
+            .to_text()
+            .contains("DISK HEALTH\n  Device: /dev/nvme0n1\n"));
+        assert!(report.to_html().contains("<h2>Disk health</h2>"));
+    }
+
+    #[test]
+    fn test_health_gate_section() {
+        use super::super::disk_health::HealthVerdict;
+        use super::super::health_score::{HealthInputs, MirrorReach};
+        use crate::config::HealthGateConfig;
+
+        let mut session = failed_session();
+        let inputs = HealthInputs {
+            network: true,
+            disk_health: Some(HealthVerdict::Failing),
+            clock_skew_secs: Some(0),
+            mirror: MirrorReach::Unreachable,
+            memory_mb: Some(8192),
+        };
+        session.health_score = Some(HealthScore::evaluate(&inputs, &HealthGateConfig::default()));
+        let report = InstallReport::new(&session);
+        let md = report.to_markdown();
+        assert!(md.contains("| Score | 50/100 (minimum 70) |"));
+        assert!(md.contains("| Decision | no-go |"));
+        assert!(md.contains("| mirror | 0.00 x 25 (unreachable) |"));
+        assert!(report.to_text().contains("HEALTH GATE\n  Score: 50/100"));
+        assert!(report.to_html().contains("<h2>Health gate</h2>"));
+    }
+
+    #[test]
+    fn test_hardware_inventory_section() {
+        use crate::network::redfish::{DimmSlot, FirmwareEntry, PowerSupply};
+
+        let mut session = failed_session();
+        assert!(!InstallReport::new(&session)
+            .to_markdown()
+            .contains("Hardware inventory"));
+
+        session.hardware_inventory = Some(HardwareInventory {
+            model: Some("PowerEdge R650".into()),
+            serial_number: Some("7XK2MN3".into()),
+            firmware: vec![FirmwareEntry {
+                name: "BIOS".into(),
+                version: "1.10.2".into(),
+            }],
+            memory: vec![DimmSlot {
+                locator: "A1".into(),
+                populated: true,
+                capacity_mib: Some(16384),
+                part_number: None,
+                speed_mhz: Some(3200),
+                health: Some("OK".into()),
+            }],
+            power_supplies: vec![PowerSupply {
+                name: "PSU.Slot.1".into(),
+                model: None,
+                serial_number: None,
+                capacity_watts: Some(800),
+                health: Some("Critical".into()),
+            }],
+            ..Default::default()
+        });
+        let report = InstallReport::new(&session);
+        let md = report.to_markdown();
+        assert!(md.contains("| Serial number | 7XK2MN3 |"));
+        assert!(md.contains("| Memory | 1 of 1 DIMM slots populated, 16384 MiB |"));
+        assert!(md.contains("| PSU.Slot.1 | - | - | 800 W | Critical |"));
+        assert!(report.to_text().contains("  Firmware BIOS: 1.10.2\n"));
+        assert!(report.to_html().contains("<h2>Hardware inventory</h2>"));
+    }
+
+    #[test]
+    fn test_mirror_selection_section() {
+        use super::super::mirror_select::MirrorProbe;
+
+        let mut session = failed_session();
+        session.mirror_selection = Some(MirrorDecision {
+            mirror: "http://fast.example/ubuntu/".into(),
+            network: "10.0.0.1@aa:bb:cc:dd:ee:ff".into(),
+            decided_at: chrono::Utc::now(),
+            cached: true,
+            probes: vec![
+                MirrorProbe {
+                    mirror: "http://fast.example/ubuntu/".into(),
+                    http_status: 200,
+                    latency_ms: 9.0,
+                    bytes_per_sec: 12_500_000.0,
+                },
+                MirrorProbe {
+                    mirror: "http://archive.ubuntu.com/ubuntu/".into(),
+                    http_status: 0,
+                    latency_ms: 0.0,
+                    bytes_per_sec: 0.0,
+                },
+            ],
+        });
+        let report = InstallReport::new(&session);
+        let md = report.to_markdown();
+        assert!(md.contains("Using http://fast.example/ubuntu/ for network 10.0.0.1@aa:bb:cc:dd:ee:ff (cached choice)."));
+        assert!(md.contains("| http://archive.ubuntu.com/ubuntu/ | unreachable |"));
+        assert!(report
+            .to_text()
+            .contains("  http://fast.example/ubuntu/: 12.5 MB/s, 9 ms\n"));
+        assert!(report.to_html().contains("<h2>Mirror selection</h2>"));
+    }
+
+    #[test]
+    fn test_text_is_ascii_and_wrapped() {
+        let mut session = failed_session();
+        session
+            .warnings
+            .push(format!("{} — done", "word ".repeat(40)));
+        let text = InstallReport::new(&session).to_text();
+        assert!(text.is_ascii());
+        assert!(text.lines().all(|l| l.len() <= TEXT_WIDTH));
+        assert!(text.contains("NEXT STEPS"));
+        assert!(text.contains("[failed] Phase 1: Package installation"));
+    }
+
+    #[test]
+    fn test_write_all_and_durations() {
+        let dir = TempDir::new().unwrap();
+        let session = failed_session();
+        let paths = InstallReport::new(&session).write_all(dir.path()).unwrap();
+        assert_eq!(paths.len(), 3);
+        assert!(paths[0].ends_with("report.md"));
+        assert!(paths.iter().all(|p| p.exists()));
+
+        assert_eq!(format_duration(Duration::seconds(12)), "12s");
+        assert_eq!(format_duration(Duration::seconds(245)), "4m 05s");
+        assert_eq!(format_duration(Duration::seconds(3723)), "1h 02m 03s");
+    }
+}
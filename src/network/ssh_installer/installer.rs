@@ -1,19 +1,67 @@
 // file: src/network/ssh_installer/installer.rs
-// version: 1.11.1
+// version: 1.71.0
 // guid: sshins01-2345-6789-abcd-ef0123456789
 
 //! Main SSH installer orchestrating all installation phases
 
+use super::budget::BudgetUsage;
+use super::capabilities::TargetCapabilities;
 use super::config::{InstallationConfig, SystemInfo};
+use super::config_export;
+use super::disk_health::{health_command, parse_health, DiskHealthCheck};
 use super::disk_ops::DiskManager;
+use super::drift::BaselineCollector;
+use super::esp::RedundantEspManager;
+use super::facts::{FactsCollector, TargetFacts};
+use super::gates::{self, GateDecision};
+use super::hardware_baseline::HardwareChange;
+use super::health_score::{HealthInputs, HealthScore, MirrorReach};
+use super::hold::{
+    HeldSession, HoldOutcome, ReleaseRequest, DEFAULT_HOLD_TIMEOUT, HOLD_POLL_INTERVAL,
+};
+use super::host_vars::{self, HostVars, HostVarsCollector};
+use super::idempotency::IdempotencyAuditor;
+use super::install_report::InstallReport;
 use super::investigation::SystemInvestigator;
+use super::investigation_report::InvestigationReport;
+use super::late_commands::{LateCommandRunner, ScriptRun};
+use super::lock::{self, LockHolder};
+use super::mirror_select::{MirrorCache, MirrorDecision, MirrorSelector};
 use super::packages::PackageManager;
+use super::plan::InstallPlan;
+use super::reinstall::{ReinstallManager, ReinstallPlan};
+use super::runbook::{self, Runbook};
+use super::session::{InstallSession, SessionStatus};
 use super::system_setup::SystemConfigurator;
-use super::zfs_ops::ZfsManager;
-use crate::network::{LocalClient, SshClient};
+use super::system_setup::ZFS_LIST_CACHE_TOUCH;
+use super::zfs_ops::{unsupported_pool_settings, ZfsManager};
+use crate::config::apt_snapshot::build_deb822_sources;
+use crate::config::confirmation::GatePhase;
+use crate::config::entropy::{parse_hwrng, ENTROPY_AVAIL, HWRNG_CURRENT};
+use crate::config::hardening::ComplianceResult;
+use crate::config::mirrors::MirrorSelectionConfig;
+use crate::config::ubuntu_pro::ProAttachTiming;
+use crate::config::zfs_tuning::ZfsTuning;
+use crate::network::redfish::HardwareInventory;
+use crate::network::sudo::{PrivilegeAudit, SudoPolicy};
+use crate::network::{
+    chaos::ChaosMonkey,
+    events::{EventBus, InstallEvent},
+    fleet::{CancelPolicy, CancelSignal},
+    progress::ProgressReporter,
+    LocalClient, SshClient, Transport,
+};
+use crate::security::enrollment::{
+    build_install_token_command, EnrollmentToken, DEFAULT_TOKEN_TTL_HOURS,
+};
+use crate::security::ssh_ca::{self, CertificateKind, CertificateRequest, SshKey};
+use crate::utils::block_device::{nvme_namespace_head, partition_path, DiskProbe};
+use crate::utils::CancellationToken;
 use crate::Result;
 use std::collections::HashMap;
-use tracing::{error, info};
+use std::path::PathBuf;
+use std::time::Duration;
+use tracing::{error, info, warn};
 
 /// Execution mode for the installer
 #[derive(Debug, Clone, PartialEq)]
@@ -29,6 +77,34 @@ pub struct SshInstaller {
     mode: ExecutionMode,
     connected: bool,
     variables: HashMap<String, String>,
+    cancel: CancellationToken,
+    session: Option<InstallSession>,
+    transactional_packages: bool,
+    hardware_inventory: Option<HardwareInventory>,
+    capabilities: Option<TargetCapabilities>,
+    mirror_selection: Option<MirrorDecision>,
+    audit_idempotency: bool,
+    disk_health: Option<DiskHealthCheck>,
+    health_score: Option<HealthScore>,
+    events: Option<EventBus>,
+    session_id: Option<String>,
+    interactive: bool,
+    budget_minutes: Option<u64>,
+    hardware_changes: Vec<HardwareChange>,
+    /// Low-memory mode is active in the live environment
+    low_memory: bool,
+    /// Hardware RNG of the live environment, read before LUKS is set up
+    hwrng: Option<String>,
+    /// Ubuntu Pro token, resolved before the disk is touched
+    pro_token: Option<String>,
+    /// Cancellation of the fleet run this install belongs to
+    fleet_cancel: CancelSignal,
+    /// How long a failed session is held open before it is cleaned up; `None` holds until released
+    hold_timeout: Option<Duration>,
+    /// Keep the pools of an earlier install and replace only its OS datasets
+    reinstall: bool,
+    /// Datasets the reinstall replaced and kept, once phase 3 has planned it
+    reinstall_plan: Option<ReinstallPlan>,
 }
 
 impl SshInstaller {
@@ -40,6 +116,543 @@ impl SshInstaller {
             mode: ExecutionMode::Ssh,
             connected: false,
             variables: HashMap::new(),
+            cancel: CancellationToken::new(),
+            session: None,
+            transactional_packages: false,
+            hardware_inventory: None,
+            capabilities: None,
+            mirror_selection: None,
+            audit_idempotency: false,
+            disk_health: None,
+            health_score: None,
+            events: None,
+            session_id: None,
+            interactive: false,
+            budget_minutes: None,
+            hardware_changes: Vec::new(),
+            low_memory: false,
+            hwrng: None,
+            pro_token: None,
+            fleet_cancel: CancelSignal::default(),
+            hold_timeout: Some(DEFAULT_HOLD_TIMEOUT),
+            reinstall: false,
+            reinstall_plan: None,
+        }
+    }
+
+    /// Stop between phases and before new remote commands once `token` is cancelled
+    pub fn set_cancellation_token(&mut self, token: CancellationToken) {
+        self.ssh.set_cancellation_token(token.clone());
+        self.cancel = token;
+    }
+
+    /// Stop at the next phase boundary once the fleet run is cancelled, as its policy says
+    pub fn set_fleet_cancel(&mut self, signal: CancelSignal) {
+        self.fleet_cancel = signal;
+    }
+
+    /// Clean up a held session after `timeout`, or hold it until released when `None`
+    pub fn set_hold_timeout(&mut self, timeout: Option<Duration>) {
+        self.hold_timeout = timeout;
+    }
+
+    /// Reinstall onto the existing pools, keeping every dataset outside ROOT and BOOT
+    pub fn set_reinstall(&mut self, enabled: bool) {
+        self.reinstall = enabled;
+    }
+
+    /// Report progress of debootstrap, apt and custom commands through `reporter`
+    pub fn set_progress(&mut self, reporter: ProgressReporter) {
+        self.ssh.set_progress(reporter);
+    }
+
+    /// Log in as an unprivileged user and run commands through sudo as `policy` says
+    pub fn set_sudo_policy(&mut self, policy: SudoPolicy) {
+        self.ssh.set_sudo_policy(policy);
+    }
+
+    /// Publish phase starts and warnings to `bus`
+    ///
+    /// Progress of remote commands reaches the bus through the reporter given to
+    /// [`set_progress`](Self::set_progress).
+    pub fn set_event_bus(&mut self, bus: EventBus) {
+        self.events = Some(bus);
+    }
+
+    /// Use `id` for the session record instead of a random one, so reports sent from elsewhere
+    /// (a target-side beacon) can be matched with it
+    pub fn set_session_id(&mut self, id: &str) {
+        self.session_id = Some(id.to_string());
+    }
+
+    /// Also ask at the terminal when waiting at a confirmation gate
+    pub fn set_interactive(&mut self, interactive: bool) {
+        self.interactive = interactive;
+    }
+
+    /// New session record for `hostname`, with the id set by [`set_session_id`](Self::set_session_id)
+    fn start_session(hostname: &str, id: Option<&str>) -> InstallSession {
+        let mut session = InstallSession::new(hostname);
+        if let Some(id) = id {
+            session.id = id.to_string();
+        }
+        session
+    }
+
+    /// Host named in published events: the session's hostname once there is a session
+    fn event_host(&self) -> String {
+        match &self.session {
+            Some(session) => session.hostname.clone(),
+            None => self.ssh.host().to_string(),
+        }
+    }
+
+    /// Inject failures into remote commands to exercise hold and recovery paths (developer mode)
+    pub fn set_chaos(&mut self, chaos: ChaosMonkey) {
+        self.ssh.set_chaos(chaos);
+    }
+
+    /// Snapshot the target before the package step and roll back or plan a revert if it fails
+    pub fn set_transactional_packages(&mut self, enabled: bool) {
+        self.transactional_packages = enabled;
+    }
+
+    /// Re-run the configuration commands after applying them and fail on any that are not
+    /// idempotent; only for disposable targets
+    pub fn set_idempotency_audit(&mut self, enabled: bool) {
+        self.audit_idempotency = enabled;
+    }
+
+    /// BMC inventory recorded in the session and shown in the installation report
+    pub fn set_hardware_inventory(&mut self, inventory: HardwareInventory) {
+        self.hardware_inventory = Some(inventory);
+    }
+
+    /// Differences from the hardware baseline, recorded in the session and reported as warnings
+    pub fn set_hardware_changes(&mut self, changes: Vec<HardwareChange>) {
+        self.hardware_changes = changes;
+    }
+
+    /// Measure mirrors from the target and pick the fastest for debootstrap
+    ///
+    /// The decision is recorded in the session and shown in the installation report.
+    pub async fn select_mirror(
+        &mut self,
+        selection: &MirrorSelectionConfig,
+        config: &InstallationConfig,
+    ) -> Result<MirrorDecision> {
+        let release = config.release();
+        let cache = MirrorCache::in_base_dir(&Self::logs_base_dir());
+        let decision = MirrorSelector::new(&mut self.ssh)
+            .select(selection, release, config.architecture, &cache)
+            .await?;
+        self.mirror_selection = Some(decision.clone());
+        Ok(decision)
+    }
+
+    /// Directory under which `logs/<hostname>/` session records and debug logs are written
+    fn logs_base_dir() -> PathBuf {
+        std::env::current_dir().unwrap_or_else(|_| PathBuf::from("."))
+    }
+
+    /// Checkpoint progress before `next_phase` and stop if shutdown was requested
+    ///
+    /// Returns `Some(result)` when the caller must return immediately.
+    async fn checkpoint_phase(
+        &mut self,
+        config: &InstallationConfig,
+        next_phase: &str,
+        successful_phases: &[&str],
+        failed_phases: &[String],
+    ) -> Option<Result<()>> {
+        let session = self.session.get_or_insert_with(|| {
+            Self::start_session(&config.hostname, self.session_id.as_deref())
+        });
+        if session.hardware_inventory.is_none() {
+            session.hardware_inventory = self.hardware_inventory.clone();
+        }
+        if session.mirror_selection.is_none() {
+            session.mirror_selection = self.mirror_selection.clone();
+        }
+        if session.disk_health.is_none() {
+            session.disk_health = self.disk_health.clone();
+        }
+        if session.health_score.is_none() {
+            session.health_score = self.health_score.clone();
+        }
+        if !self.hardware_changes.is_empty() {
+            session.hardware_changes = std::mem::take(&mut self.hardware_changes);
+            session.warnings.extend(
+                session
+                    .hardware_changes
+                    .iter()
+                    .map(|c| format!("Hardware changed since baseline: {}", c.describe())),
+            );
+        }
+        session.completed_phases = successful_phases.iter().map(|p| p.to_string()).collect();
+        session.failed_phases = failed_phases.to_vec();
+        session.last_command = self.ssh.last_command().map(str::to_string);
+        if let (None, Some(hard_limit)) = (self.budget_minutes, config.budget.hard_limit()) {
+            // The running phase gets the grace period; after that its commands are refused
+            self.budget_minutes = config.budget.total_minutes;
+            let remaining = (session.started_at + hard_limit - chrono::Utc::now())
+                .to_std()
+                .unwrap_or_default();
+            self.ssh
+                .set_command_deadline(Some(std::time::Instant::now() + remaining));
+        }
+
+        if self.cancel.is_cancelled() {
+            // A phase that started but never completed was interrupted part-way through
+            let stop_point = match &session.current_phase {
+                Some(prev) if !session.completed_phases.contains(prev) => {
+                    format!("during {}", prev)
+                }
+                _ => format!("before {}", next_phase),
+            };
+            session.current_phase = Some(stop_point);
+            return Some(self.stop_for_shutdown());
+        }
+
+        if let Some(policy) = self.fleet_cancel.requested() {
+            let stop_point = match &session.current_phase {
+                Some(prev) if !session.completed_phases.contains(prev) => {
+                    format!("during {}", prev)
+                }
+                _ => format!("before {}", next_phase),
+            };
+            session.current_phase = Some(stop_point);
+            return Some(self.stop_for_fleet_cancel(config, policy).await);
+        }
+
+        if self.budget_spent() {
+            let session = self.session.as_ref()?;
+            let stop_point = match &session.current_phase {
+                Some(prev) if !session.completed_phases.contains(prev) => {
+                    format!("during {}", prev)
+                }
+                _ => format!("before {}", next_phase),
+            };
+            return Some(
+                self.exceed_budget(stop_point, successful_phases, failed_phases)
+                    .await,
+            );
+        }
+
+        if let Some(gate) = GatePhase::from_label(next_phase) {
+            if config.confirmation.gates(gate) {
+                if let Some(stop) = self.await_confirmation(config, next_phase, gate).await {
+                    return Some(stop);
+                }
+            }
+        }
+
+        let session = self.session.as_mut()?;
+        session.current_phase = Some(next_phase.to_string());
+        session.start_phase(next_phase);
+        if let Some(bus) = &self.events {
+            bus.publish(InstallEvent::PhaseStarted {
+                host: config.hostname.clone(),
+                phase: next_phase.to_string(),
+            });
+        }
+        if let Err(e) = session.save(&Self::logs_base_dir()) {
+            warn!("Failed to write session checkpoint: {}", e);
+        }
+        None
+    }
+
+    /// Wait at the confirmation gate before `phase` until someone approves it
+    ///
+    /// Returns `Some(result)` when the install must stop: the gate was rejected, timed out or
+    /// the install was cancelled while waiting.
+    async fn await_confirmation(
+        &mut self,
+        config: &InstallationConfig,
+        phase: &str,
+        gate: GatePhase,
+    ) -> Option<Result<()>> {
+        let confirmation = &config.confirmation;
+        let base_dir = Self::logs_base_dir();
+        // A decision left over from an earlier run must not approve this one
+        let _ = gates::take_file_decision(&base_dir, &config.hostname, gate);
+
+        warn!("=== CONFIRMATION REQUIRED: {} ===", phase);
+        for line in gates::phase_summary(config, phase) {
+            warn!("  {}", line);
+        }
+        warn!(
+            "  Approve with `ubuntu-autoinstall-agent approve {} {}` (or --reject <REASON>)",
+            config.hostname,
+            gate.id()
+        );
+        if let Some(session) = self.session.as_mut() {
+            session.current_phase = Some(format!("awaiting approval before {}", phase));
+            if let Err(e) = session.save(&base_dir) {
+                warn!("Failed to write session checkpoint: {}", e);
+            }
+            if let Some(bus) = &self.events {
+                bus.publish(InstallEvent::Session {
+                    name: "install.awaiting_approval".to_string(),
+                    session: Box::new(session.clone()),
+                });
+            }
+        }
+
+        // The terminal answer arrives on a blocking thread; files and the endpoint are polled
+        let mut prompt = self.interactive.then(|| {
+            println!("Continue with {}? [y/N] ", phase);
+            tokio::task::spawn_blocking(|| {
+                let mut answer = String::new();
+                std::io::stdin().read_line(&mut answer).map(|_| answer)
+            })
+        });
+        let url = confirmation
+            .approval_url
+            .as_deref()
+            .map(|url| gates::approval_url(url, &config.hostname, gate));
+        let started = std::time::Instant::now();
+        let poll = std::time::Duration::from_secs(confirmation.poll_secs);
+        let decision = loop {
+            if let Some(decision) = gates::take_file_decision(&base_dir, &config.hostname, gate) {
+                break Some(decision);
+            }
+            if let Some(url) = &url {
+                if let Some((decision, _)) = gates::poll_endpoint(url).await {
+                    break Some(decision);
+                }
+            }
+            if self.cancel.is_cancelled()
+                || (confirmation.timeout_secs > 0
+                    && started.elapsed().as_secs() >= confirmation.timeout_secs)
+            {
+                break None;
+            }
+            match prompt.as_mut() {
+                Some(answer) => {
+                    if let Ok(answer) = tokio::time::timeout(poll, answer).await {
+                        prompt = None;
+                        break Some(match answer {
+                            Ok(Ok(line)) if line.trim().eq_ignore_ascii_case("y") => {
+                                GateDecision::Approved
+                            }
+                            _ => GateDecision::Rejected("declined at the terminal".to_string()),
+                        });
+                    }
+                }
+                None => tokio::time::sleep(poll).await,
+            }
+        };
+        if let Some(answer) = prompt {
+            // The install no longer waits for the terminal
+            answer.abort();
+        }
+
+        let stop_point = match decision {
+            Some(GateDecision::Approved) => {
+                info!("✓ {} approved", phase);
+                return None;
+            }
+            Some(GateDecision::Rejected(reason)) => {
+                format!("before {} (rejected: {})", phase, reason)
+            }
+            None if self.cancel.is_cancelled() => format!("before {}", phase),
+            None => format!(
+                "before {} (no approval within {}s)",
+                phase, confirmation.timeout_secs
+            ),
+        };
+        self.session.as_mut()?.current_phase = Some(stop_point);
+        Some(self.stop_for_shutdown())
+    }
+
+    /// Whether the target config's time budget has run out
+    fn budget_spent(&self) -> bool {
+        match (self.budget_minutes, &self.session) {
+            (Some(minutes), Some(session)) => {
+                chrono::Utc::now() - session.started_at >= chrono::Duration::minutes(minutes as i64)
+            }
+            _ => false,
+        }
+    }
+
+    /// Stop the install because its time budget ran out and hold the session open
+    ///
+    /// `stop_point` is `before <phase>` when the budget ran out between phases, or
+    /// `during <phase>` when the running phase was cut short after its grace period.
+    async fn exceed_budget(
+        &mut self,
+        stop_point: String,
+        successful_phases: &[&str],
+        failed_phases: &[String],
+    ) -> Result<()> {
+        let limit = self.budget_minutes.unwrap_or_default();
+        self.ssh.set_command_deadline(None);
+        let mut failed_phases = failed_phases.to_vec();
+        failed_phases.push(format!("Budget exceeded - stopped {}", stop_point));
+        if let Some(session) = self.session.as_mut() {
+            let usage = BudgetUsage::from_session(session, limit, Some(stop_point.clone()));
+            error!(
+                "⏱ Installation budget of {} minutes exceeded; no further phases are started",
+                limit
+            );
+            for line in usage.summary_lines() {
+                error!("  {}", line);
+            }
+            session.budget = Some(usage);
+        }
+        if let Some(bus) = &self.events {
+            bus.publish(InstallEvent::Warning {
+                host: self.event_host(),
+                message: format!("installation budget exceeded; stopped {}", stop_point),
+            });
+        }
+        self.finish_session(SessionStatus::Failed, successful_phases, &failed_phases);
+        self.collect_and_log_debug_info().await;
+        self.generate_installation_report(successful_phases, &failed_phases)
+            .await;
+        self.keep_session_open("installation budget exceeded").await;
+
+        Err(crate::error::AutoInstallError::TimeoutError(format!(
+            "installation budget of {} minutes exceeded; stopped {}",
+            limit, stop_point
+        )))
+    }
+
+    /// Stop for a cancelled fleet run and apply its policy to the target
+    async fn stop_for_fleet_cancel(
+        &mut self,
+        config: &InstallationConfig,
+        policy: CancelPolicy,
+    ) -> Result<()> {
+        warn!(
+            "Fleet run cancelled; stopping {} ({})",
+            config.hostname,
+            policy.as_str()
+        );
+        match policy {
+            CancelPolicy::FinishPhase => self.stop_for_shutdown(),
+            CancelPolicy::Hold => {
+                let result = self.stop_for_shutdown();
+                self.keep_session_open("fleet run cancelled").await;
+                result
+            }
+            CancelPolicy::AbortCleanup => {
+                let mut disk_manager = DiskManager::new(&mut self.ssh);
+                let note = match disk_manager.recover_after_failure_and_wipe(config).await {
+                    Ok(()) => "The target was cleaned up and its disk wiped".to_string(),
+                    Err(e) => format!("Cleanup of the target failed: {}", e),
+                };
+                self.stop_with_note(&note)
+            }
+        }
+    }
+
+    /// Persist the cancelled session and print the shutdown report
+    fn stop_for_shutdown(&mut self) -> Result<()> {
+        self.stop_with_note("The target was left as-is; no cleanup or unmount was attempted")
+    }
+
+    /// Shutdown report ending with `note` on what was done to the target
+    fn stop_with_note(&mut self, note: &str) -> Result<()> {
+        let session = self
+            .session
+            .get_or_insert_with(|| Self::start_session("unknown-host", self.session_id.as_deref()));
+        session.status = SessionStatus::Cancelled;
+        session.end_phase();
+        if session.last_command.is_none() {
+            session.last_command = self.ssh.last_command().map(str::to_string);
+        }
+
+        warn!("=== SHUTDOWN REPORT ===");
+        for line in session.shutdown_summary() {
+            warn!("  {}", line);
+        }
+        match session.save(&Self::logs_base_dir()) {
+            Ok(path) => warn!("  Checkpoint written to {}", path.display()),
+            Err(e) => error!("  Failed to write checkpoint: {}", e),
+        }
+        warn!("  {}", note);
+        warn!("=== END SHUTDOWN REPORT ===");
+
+        let hostname = session.hostname.clone();
+        let error = crate::error::AutoInstallError::CancelledError(format!(
+            "installation of {} stopped {}",
+            session.hostname,
+            session.current_phase.as_deref().unwrap_or("before start")
+        ));
+        self.write_privilege_audit(&hostname);
+        Err(error)
+    }
+
+    /// Mark the session finished with `status` and persist it
+    fn finish_session(
+        &mut self,
+        status: SessionStatus,
+        successful_phases: &[&str],
+        failed_phases: &[String],
+    ) {
+        if let Some(session) = self.session.as_mut() {
+            session.status = status;
+            session.completed_phases = successful_phases.iter().map(|p| p.to_string()).collect();
+            session.failed_phases = failed_phases.to_vec();
+            session.current_phase = None;
+            session.last_command = self.ssh.last_command().map(str::to_string);
+            session.end_phase();
+            if let (None, Some(limit)) = (&session.budget, self.budget_minutes) {
+                let usage = BudgetUsage::from_session(session, limit, None);
+                for line in usage.summary_lines() {
+                    info!("{}", line);
+                }
+                session.budget = Some(usage);
+            }
+            if let Err(e) = session.save(&Self::logs_base_dir()) {
+                warn!("Failed to write session record: {}", e);
+            }
+            let host_dir = InstallSession::host_dir(&Self::logs_base_dir(), &session.hostname);
+            match InstallReport::new(session).write_all(&host_dir) {
+                Ok(paths) => {
+                    for path in paths {
+                        info!("Installation report written to {}", path.display());
+                    }
+                }
+                Err(e) => warn!("Failed to write installation report: {}", e),
+            }
+            let hostname = session.hostname.clone();
+            self.write_privilege_audit(&hostname);
+        }
+    }
+
+    /// Record which commands ran through sudo, when the install elevated per command
+    fn write_privilege_audit(&self, hostname: &str) {
+        let log = self.ssh.elevation_log();
+        if log.is_empty() {
+            return;
+        }
+        let audit = PrivilegeAudit::new(self.ssh.host(), self.ssh.username(), log.to_vec());
+        let host_dir = InstallSession::host_dir(&Self::logs_base_dir(), hostname);
+        match audit.write(&host_dir) {
+            Ok(path) => info!(
+                "Privilege audit written to {} ({} elevated, {} unprivileged)",
+                path.display(),
+                audit.elevated_count,
+                audit.unprivileged_count
+            ),
+            Err(e) => warn!("Failed to write privilege audit: {}", e),
+        }
+    }
+
+    /// Log a non-fatal problem and keep it for the installation report
+    fn record_warning(&mut self, message: String) {
+        warn!("{}", message);
+        if let Some(bus) = &self.events {
+            bus.publish(InstallEvent::Warning {
+                host: self.event_host(),
+                message: message.clone(),
+            });
+        }
+        if let Some(session) = self.session.as_mut() {
+            session.warnings.push(message);
         }
     }
 
@@ -64,10 +677,17 @@ impl SshInstaller {
             "Starting full ZFS + LUKS installation for {} (hold-on-failure={}, pause-after-storage={})",
             config.hostname, hold_on_failure, pause_after_storage
         );
+        let resolved = self.resolve_install_disks(config).await;
+        let config = &resolved;
 
         let mut failed_phases: Vec<String> = Vec::new();
         let mut successful_phases: Vec<&str> = Vec::new();
 
+        // A failing install disk stops the install before anything is written to it
+        if let Err(e) = self.check_disk_health(config).await {
+            return self.fail_disk_health_gate(config, e);
+        }
+
         // Preflight checks (continue for diagnostics even if failing)
         if let Err(e) = self.preflight_checks(config).await {
             error!("✗ Preflight checks failed: {}", e);
@@ -77,6 +697,17 @@ impl SshInstaller {
         }
 
         // Phase 0: Setup installation variables
+        if let Some(stop) = self
+            .checkpoint_phase(
+                config,
+                "Phase 0: Setup variables",
+                &successful_phases,
+                &failed_phases,
+            )
+            .await
+        {
+            return stop;
+        }
         if let Err(e) = self.setup_installation_variables(config).await {
             failed_phases.push(format!("Phase 0: Setup variables - {}", e));
             return self
@@ -87,7 +718,18 @@ impl SshInstaller {
         }
 
         // Phase 1: Package installation
-        if let Err(e) = self.phase_1_package_installation().await {
+        if let Some(stop) = self
+            .checkpoint_phase(
+                config,
+                "Phase 1: Package installation",
+                &successful_phases,
+                &failed_phases,
+            )
+            .await
+        {
+            return stop;
+        }
+        if let Err(e) = self.phase_1_package_installation(config).await {
             failed_phases.push(format!("Phase 1: Package installation - {}", e));
             return self
                 .enter_hold_mode("Phase 1 failed", &successful_phases, &failed_phases)
@@ -96,7 +738,35 @@ impl SshInstaller {
             successful_phases.push("Phase 1: Package installation");
         }
 
+        // Capability check: nothing has touched the disk yet
+        if let Err(e) = self.check_capabilities(config).await {
+            failed_phases.push(format!("Capability check - {}", e));
+            return self
+                .enter_hold_mode(
+                    "Capability check failed",
+                    &successful_phases,
+                    &failed_phases,
+                )
+                .await;
+        }
+
+        // Marginal targets stop here, before the disk is wiped
+        if let Err(e) = self.check_health_gate(config).await {
+            return self.fail_health_gate(e, &successful_phases, &failed_phases);
+        }
+
         // Phase 2: Disk preparation
+        if let Some(stop) = self
+            .checkpoint_phase(
+                config,
+                "Phase 2: Disk preparation",
+                &successful_phases,
+                &failed_phases,
+            )
+            .await
+        {
+            return stop;
+        }
         if let Err(e) = self.phase_2_disk_preparation(config).await {
             failed_phases.push(format!("Phase 2: Disk preparation - {}", e));
             return self
@@ -107,6 +777,17 @@ impl SshInstaller {
         }
 
         // Phase 3: ZFS pool creation
+        if let Some(stop) = self
+            .checkpoint_phase(
+                config,
+                "Phase 3: ZFS creation",
+                &successful_phases,
+                &failed_phases,
+            )
+            .await
+        {
+            return stop;
+        }
         if let Err(e) = self.phase_3_zfs_creation(config).await {
             failed_phases.push(format!("Phase 3: ZFS creation - {}", e));
             return self
@@ -129,6 +810,17 @@ impl SshInstaller {
         }
 
         // Phase 4: Base system installation
+        if let Some(stop) = self
+            .checkpoint_phase(
+                config,
+                "Phase 4: Base system",
+                &successful_phases,
+                &failed_phases,
+            )
+            .await
+        {
+            return stop;
+        }
         if let Err(e) = self.phase_4_base_system(config).await {
             failed_phases.push(format!("Phase 4: Base system - {}", e));
             return self
@@ -139,6 +831,17 @@ impl SshInstaller {
         }
 
         // Phase 5: System configuration
+        if let Some(stop) = self
+            .checkpoint_phase(
+                config,
+                "Phase 5: System configuration",
+                &successful_phases,
+                &failed_phases,
+            )
+            .await
+        {
+            return stop;
+        }
         if let Err(e) = self.phase_5_system_configuration(config).await {
             failed_phases.push(format!("Phase 5: System configuration - {}", e));
             return self
@@ -149,6 +852,17 @@ impl SshInstaller {
         }
 
         // Phase 6: Final setup — in hold mode we still want to complete when all previous phases succeeded
+        if let Some(stop) = self
+            .checkpoint_phase(
+                config,
+                "Phase 6: Final setup",
+                &successful_phases,
+                &failed_phases,
+            )
+            .await
+        {
+            return stop;
+        }
         if let Err(e) = self.phase_6_final_setup(config).await {
             failed_phases.push(format!("Phase 6: Final setup - {}", e));
             return self
@@ -159,6 +873,7 @@ impl SshInstaller {
         }
 
         // All good
+        self.finish_session(SessionStatus::Completed, &successful_phases, &failed_phases);
         self.generate_installation_report(&successful_phases, &failed_phases)
             .await;
         info!(
@@ -173,7 +888,6 @@ impl SshInstaller {
         &mut self,
         config: &InstallationConfig,
     ) -> Result<()> {
-        use tracing::warn;
         warn!("=== PAUSE AFTER STORAGE REQUESTED ===");
         warn!(
             "The installer has completed: partitioning, formatting (ESP/ext4), LUKS setup, and ZFS pools/datasets."
@@ -197,33 +911,170 @@ impl SshInstaller {
         successful_phases: &[&str],
         failed_phases: &[String],
     ) -> Result<()> {
+        // A failure caused by shutdown should stop, not hold the session open
+        if self.cancel.is_cancelled() {
+            if let Some(session) = self.session.as_mut() {
+                session.completed_phases =
+                    successful_phases.iter().map(|p| p.to_string()).collect();
+                session.failed_phases = failed_phases.to_vec();
+            }
+            return self.stop_for_shutdown();
+        }
+        // A phase cut short by the time budget is reported as such, not as a plain failure
+        if self.budget_spent() {
+            let stop_point = match self.session.as_ref().and_then(|s| s.current_phase.clone()) {
+                Some(phase) => format!("during {}", phase),
+                None => reason.to_string(),
+            };
+            return self
+                .exceed_budget(stop_point, successful_phases, failed_phases)
+                .await;
+        }
+
         error!(
             "🔒 Hold-on-failure is enabled — stopping immediately: {}",
             reason
         );
+        self.finish_session(SessionStatus::Failed, successful_phases, failed_phases);
         self.collect_and_log_debug_info().await;
         self.generate_installation_report(successful_phases, failed_phases)
             .await;
 
-        // IMPORTANT: Do NOT cleanup/unmount/export anything here — leave the system as-is
-        // Keep the SSH session alive for live debugging by running a long-lived no-op on the target
-        // We intentionally block here to keep the process and SSH session open
-        let keepalive_cmd = "bash -lc 'echo \"[uaa] Hold mode active — leaving system mounted for debugging.\"; echo \"Press Ctrl-C locally when done.\"; while true; do sleep 3600; done'";
-        let _ = self.ssh.execute(keepalive_cmd).await;
+        self.keep_session_open(reason).await;
 
         Err(crate::error::AutoInstallError::InstallationError(
             "Installation halted due to failure (hold-on-failure)".to_string(),
         ))
     }
 
+    /// Block with the SSH session open so the target can be debugged live
+    ///
+    /// The hold is recorded for `sessions list` and lasts until `sessions release`, the hold
+    /// timeout or Ctrl+C. A release or timeout unmounts, exports the pools and closes LUKS
+    /// before the session is closed; nothing is cleaned up otherwise.
+    async fn keep_session_open(&mut self, reason: &str) {
+        let base_dir = Self::logs_base_dir();
+        let (hostname, session_id) = match &self.session {
+            Some(session) => (session.hostname.clone(), session.id.clone()),
+            None => (
+                self.ssh.host().to_string(),
+                self.session_id.clone().unwrap_or_default(),
+            ),
+        };
+        let hold = HeldSession::new(
+            &hostname,
+            &session_id,
+            self.ssh.host(),
+            reason,
+            self.hold_timeout,
+        );
+        if let Err(e) = hold.save(&base_dir) {
+            warn!("Failed to record the held session: {}", e);
+        }
+        match hold.expires_at {
+            Some(at) => warn!(
+                "🔒 Holding {} mounted for debugging until {}; end it earlier with `sessions release {}`",
+                hostname,
+                at.format("%Y-%m-%d %H:%M UTC"),
+                hostname
+            ),
+            None => warn!(
+                "🔒 Holding {} mounted for debugging until `sessions release {}`",
+                hostname, hostname
+            ),
+        }
+
+        let outcome = self.wait_while_held(&hold, &base_dir).await;
+        match outcome {
+            HoldOutcome::Released { cleanup: true } => {
+                info!("Hold of {} released; cleaning up the target", hostname)
+            }
+            HoldOutcome::Expired => warn!("Hold of {} timed out; cleaning up the target", hostname),
+            HoldOutcome::Released { cleanup: false } => {
+                info!("Hold of {} released; the target was left as-is", hostname)
+            }
+            HoldOutcome::Interrupted => {
+                info!(
+                    "Hold of {} interrupted; the target was left as-is",
+                    hostname
+                )
+            }
+            HoldOutcome::Disconnected => warn!(
+                "Lost the session to {} while holding it; the target was left as-is",
+                hostname
+            ),
+        }
+        if outcome.cleans_up() {
+            DiskManager::new(&mut self.ssh).release_storage(&[]).await;
+            self.ssh.disconnect();
+            self.connected = false;
+        }
+        HeldSession::remove(&base_dir, &hostname);
+    }
+
+    /// Wait until the hold is released, expires or the process is interrupted, keeping the
+    /// SSH session alive meanwhile
+    async fn wait_while_held(
+        &mut self,
+        hold: &HeldSession,
+        base_dir: &std::path::Path,
+    ) -> HoldOutcome {
+        const KEEPALIVE_EVERY: u32 = 12;
+        let mut polls = 0;
+        loop {
+            tokio::select! {
+                _ = self.cancel.cancelled() => return HoldOutcome::Interrupted,
+                _ = tokio::time::sleep(HOLD_POLL_INTERVAL) => {}
+            }
+            if let Some(request) = ReleaseRequest::load(base_dir, &hold.hostname) {
+                return HoldOutcome::Released {
+                    cleanup: request.cleanup,
+                };
+            }
+            if hold.is_expired(chrono::Utc::now()) {
+                return HoldOutcome::Expired;
+            }
+            polls += 1;
+            if self.mode == ExecutionMode::Ssh
+                && polls % KEEPALIVE_EVERY == 0
+                && self.ssh.execute("true").await.is_err()
+            {
+                return HoldOutcome::Disconnected;
+            }
+        }
+    }
+
     /// Connect to target system
     pub async fn connect(&mut self, host: &str, username: &str) -> Result<()> {
         self.ssh.connect(host, username).await?;
+        self.ssh.verify_sudo().await?;
         self.connected = true;
         info!("Successfully connected to {}@{}", username, host);
         Ok(())
     }
 
+    /// Run the install over a console transport instead of an SSH session
+    pub fn connect_transport(&mut self, host: &str, transport: Box<dyn Transport>) {
+        self.ssh.connect_transport(host, transport);
+        self.connected = true;
+    }
+
+    /// Mark the connected target as being installed by `holder`; see `lock::acquire_remote`
+    pub async fn acquire_target_lock(&mut self, holder: &LockHolder, steal: bool) -> Result<()> {
+        if self.mode == ExecutionMode::Local {
+            return Ok(());
+        }
+        lock::acquire_remote(&mut self.ssh, holder, steal).await
+    }
+
+    /// Remove the install marker from the connected target
+    pub async fn release_target_lock(&mut self) -> Result<()> {
+        if self.mode == ExecutionMode::Local {
+            return Ok(());
+        }
+        lock::release_remote(&mut self.ssh).await
+    }
+
     /// Connect for local installation (no SSH needed)
     pub async fn connect_local(&mut self) -> Result<()> {
         // Switch to local mode
@@ -253,6 +1104,42 @@ impl SshInstaller {
         }
     }
 
+    /// Collect a structured investigation report of the target
+    pub async fn investigation_report(&mut self) -> Result<InvestigationReport> {
+        if !self.connected {
+            return Err(crate::error::AutoInstallError::SshError(
+                "Not connected to target system".to_string(),
+            ));
+        }
+
+        match self.mode {
+            ExecutionMode::Ssh => {
+                SystemInvestigator::new(&mut self.ssh)
+                    .collect_report()
+                    .await
+            }
+            ExecutionMode::Local => {
+                SystemInvestigator::new(&mut self.local)
+                    .collect_report()
+                    .await
+            }
+        }
+    }
+
+    /// Collect typed disk, network, CPU and memory facts from the target
+    pub async fn target_facts(&mut self) -> Result<TargetFacts> {
+        if !self.connected {
+            return Err(crate::error::AutoInstallError::SshError(
+                "Not connected to target system".to_string(),
+            ));
+        }
+
+        Ok(match self.mode {
+            ExecutionMode::Ssh => FactsCollector::new(&mut self.ssh).collect().await,
+            ExecutionMode::Local => FactsCollector::new(&mut self.local).collect().await,
+        })
+    }
+
     /// Perform full ZFS + LUKS installation with comprehensive error handling
     pub async fn perform_installation(&mut self, config: &InstallationConfig) -> Result<()> {
         if !self.connected {
@@ -265,9 +1152,16 @@ impl SshInstaller {
             "Starting full ZFS + LUKS installation for {}",
             config.hostname
         );
+        let resolved = self.resolve_install_disks(config).await;
+        let config = &resolved;
+
+        let mut failed_phases: Vec<String> = Vec::new();
+        let mut successful_phases: Vec<&str> = Vec::new();
 
-        let mut failed_phases = Vec::new();
-        let mut successful_phases = Vec::new();
+        // A failing install disk stops the install before anything is written to it
+        if let Err(e) = self.check_disk_health(config).await {
+            return self.fail_disk_health_gate(config, e);
+        }
 
         // Preflight checks (Phase -1)
         match self.preflight_checks(config).await {
@@ -282,6 +1176,17 @@ impl SshInstaller {
         }
 
         // Phase 0: Setup installation variables
+        if let Some(stop) = self
+            .checkpoint_phase(
+                config,
+                "Phase 0: Setup variables",
+                &successful_phases,
+                &failed_phases,
+            )
+            .await
+        {
+            return stop;
+        }
         match self.setup_installation_variables(config).await {
             Ok(_) => {
                 info!("✓ Phase 0 completed: Setup variables");
@@ -295,7 +1200,18 @@ impl SshInstaller {
         }
 
         // Phase 1: Package installation (continue even if previous phase failed)
-        match self.phase_1_package_installation().await {
+        if let Some(stop) = self
+            .checkpoint_phase(
+                config,
+                "Phase 1: Package installation",
+                &successful_phases,
+                &failed_phases,
+            )
+            .await
+        {
+            return stop;
+        }
+        match self.phase_1_package_installation(config).await {
             Ok(_) => {
                 info!("✓ Phase 1 completed: Package installation");
                 successful_phases.push("Phase 1: Package installation");
@@ -307,7 +1223,31 @@ impl SshInstaller {
             }
         }
 
+        // Capability check: unlike the phases, stop here since nothing has touched the disk yet
+        if let Err(e) = self.check_capabilities(config).await {
+            error!("✗ Capability check failed: {}", e);
+            failed_phases.push(format!("Capability check - {}", e));
+            self.finish_session(SessionStatus::Failed, &successful_phases, &failed_phases);
+            return Err(e);
+        }
+
+        // Marginal targets stop here, before the disk is wiped
+        if let Err(e) = self.check_health_gate(config).await {
+            return self.fail_health_gate(e, &successful_phases, &failed_phases);
+        }
+
         // Phase 2: Disk preparation
+        if let Some(stop) = self
+            .checkpoint_phase(
+                config,
+                "Phase 2: Disk preparation",
+                &successful_phases,
+                &failed_phases,
+            )
+            .await
+        {
+            return stop;
+        }
         match self.phase_2_disk_preparation(config).await {
             Ok(_) => {
                 info!("✓ Phase 2 completed: Disk preparation");
@@ -321,6 +1261,17 @@ impl SshInstaller {
         }
 
         // Phase 3: ZFS pool creation
+        if let Some(stop) = self
+            .checkpoint_phase(
+                config,
+                "Phase 3: ZFS creation",
+                &successful_phases,
+                &failed_phases,
+            )
+            .await
+        {
+            return stop;
+        }
         match self.phase_3_zfs_creation(config).await {
             Ok(_) => {
                 info!("✓ Phase 3 completed: ZFS creation");
@@ -335,6 +1286,17 @@ impl SshInstaller {
         }
 
         // Phase 4: Base system installation
+        if let Some(stop) = self
+            .checkpoint_phase(
+                config,
+                "Phase 4: Base system",
+                &successful_phases,
+                &failed_phases,
+            )
+            .await
+        {
+            return stop;
+        }
         match self.phase_4_base_system(config).await {
             Ok(_) => {
                 info!("✓ Phase 4 completed: Base system");
@@ -348,6 +1310,17 @@ impl SshInstaller {
         }
 
         // Phase 5: System configuration
+        if let Some(stop) = self
+            .checkpoint_phase(
+                config,
+                "Phase 5: System configuration",
+                &successful_phases,
+                &failed_phases,
+            )
+            .await
+        {
+            return stop;
+        }
         match self.phase_5_system_configuration(config).await {
             Ok(_) => {
                 info!("✓ Phase 5 completed: System configuration");
@@ -361,6 +1334,17 @@ impl SshInstaller {
         }
 
         // Phase 6: Final setup
+        if let Some(stop) = self
+            .checkpoint_phase(
+                config,
+                "Phase 6: Final setup",
+                &successful_phases,
+                &failed_phases,
+            )
+            .await
+        {
+            return stop;
+        }
         match self.phase_6_final_setup(config).await {
             Ok(_) => {
                 info!("✓ Phase 6 completed: Final setup");
@@ -373,71 +1357,281 @@ impl SshInstaller {
             }
         }
 
-        // Generate comprehensive installation report
-        self.generate_installation_report(&successful_phases, &failed_phases)
-            .await;
+        // Generate comprehensive installation report
+        self.generate_installation_report(&successful_phases, &failed_phases)
+            .await;
+
+        if self.cancel.is_cancelled() {
+            if let Some(stop) = self
+                .checkpoint_phase(config, "completion", &successful_phases, &failed_phases)
+                .await
+            {
+                return stop;
+            }
+        }
+        self.finish_session(
+            if failed_phases.is_empty() {
+                SessionStatus::Completed
+            } else {
+                SessionStatus::Failed
+            },
+            &successful_phases,
+            &failed_phases,
+        );
+
+        if failed_phases.is_empty() {
+            info!(
+                "🎉 Installation completed successfully for {}",
+                config.hostname
+            );
+            Ok(())
+        } else {
+            error!(
+                "❌ Installation completed with {} failed phases out of 6 total phases",
+                failed_phases.len()
+            );
+            error!("💡 SSH session remains active for manual debugging and investigation");
+            error!("💡 You can inspect logs, retry specific phases, or analyze the system state");
+
+            // Don't disconnect - let the user investigate
+            Err(crate::error::AutoInstallError::InstallationError(format!(
+                "Installation failed: {} phases failed",
+                failed_phases.len()
+            )))
+        }
+    }
+
+    /// Config with the install disk and ESP mirrors replaced by their stable `by-id` paths, so
+    /// every phase names the same disk even if the kernel renumbers it; NVMe controller paths
+    /// (`nvme0c0n1`) become their namespace head first
+    async fn resolve_install_disks(&mut self, config: &InstallationConfig) -> InstallationConfig {
+        let mut resolved = config.clone();
+        resolved.disk_device = self.resolve_disk(&config.disk_device).await;
+        let mut mirrors = Vec::with_capacity(config.esp_mirror_devices.len());
+        for mirror in &config.esp_mirror_devices {
+            mirrors.push(self.resolve_disk(mirror).await);
+        }
+        resolved.esp_mirror_devices = mirrors;
+        resolved
+    }
+
+    async fn resolve_disk(&mut self, disk: &str) -> String {
+        let disk = match nvme_namespace_head(disk) {
+            Some(head) => {
+                info!(
+                    "{} is an NVMe controller path; installing to namespace {}",
+                    disk, head
+                );
+                head
+            }
+            None => disk.to_string(),
+        };
+        let probe = match self
+            .ssh
+            .execute_with_output(&DiskProbe::command(&disk))
+            .await
+        {
+            Ok(output) => DiskProbe::parse(&output),
+            Err(e) => {
+                self.record_warning(format!(
+                    "Could not resolve a stable path for {}, using it as given: {}",
+                    disk, e
+                ));
+                return disk;
+            }
+        };
+        if probe.is_multipath() {
+            info!("{} is a multipath device ({})", disk, probe.device);
+        }
+        match probe.stable_path() {
+            Some(stable) => {
+                info!(
+                    "Install disk {} resolved to {} (partition 1: {})",
+                    disk,
+                    stable,
+                    partition_path(&stable, 1)
+                );
+                stable
+            }
+            None => disk,
+        }
+    }
+
+    /// Read SMART data of the install disk and compare it against the `disk_health:` limits
+    ///
+    /// The result is kept for the session and reports. Returns an error when a failure limit
+    /// is crossed and the limits are enforced.
+    async fn check_disk_health(&mut self, config: &InstallationConfig) -> Result<()> {
+        let limits = &config.disk_health;
+        if !limits.check {
+            return Ok(());
+        }
+        let output = self
+            .ssh
+            .execute_with_output(&health_command(&config.disk_device))
+            .await
+            .unwrap_or_default();
+        let check = DiskHealthCheck::evaluate(&config.disk_device, parse_health(&output), limits);
+        let failing = !check.failures.is_empty();
+        if failing || !check.warnings.is_empty() {
+            warn!("Preflight: disk health {}", check.summary());
+        } else {
+            info!("Preflight: disk health {}", check.summary());
+        }
+        let summary = check.summary();
+        self.disk_health = Some(check);
+        if failing && limits.enforce {
+            return Err(crate::error::AutoInstallError::ValidationError(format!(
+                "Install disk failed the health check: {}",
+                summary
+            )));
+        }
+        Ok(())
+    }
+
+    /// Record a session for an install stopped by the disk health gate and return `error`
+    fn fail_disk_health_gate(
+        &mut self,
+        config: &InstallationConfig,
+        error: crate::error::AutoInstallError,
+    ) -> Result<()> {
+        error!("✗ Preflight disk health gate failed: {}", error);
+        let session = self.session.get_or_insert_with(|| {
+            Self::start_session(&config.hostname, self.session_id.as_deref())
+        });
+        session.disk_health = self.disk_health.clone();
+        self.finish_session(
+            SessionStatus::Failed,
+            &[],
+            &[format!("Preflight: disk health - {}", error)],
+        );
+        Err(error)
+    }
+
+    /// Whether the target gets an ICMP reply from a public resolver
+    async fn probe_network(&mut self) -> bool {
+        self.ssh
+            .execute(
+                "ping -c 1 -w 2 1.1.1.1 >/dev/null 2>&1 || ping -c 1 -w 2 8.8.8.8 >/dev/null 2>&1",
+            )
+            .await
+            .is_ok()
+    }
 
-        if failed_phases.is_empty() {
-            info!(
-                "🎉 Installation completed successfully for {}",
-                config.hostname
-            );
-            Ok(())
+    /// Whether the debootstrap mirror, or old-releases as a fallback, serves the release
+    async fn probe_mirror(&mut self, config: &InstallationConfig) -> MirrorReach {
+        let release = config.release();
+        let mirror = config.effective_mirror();
+        let release_url = format!("{}/dists/{}/Release", mirror.trim_end_matches('/'), release);
+        let head_cmd = format!("curl -fsI '{}' >/dev/null", release_url);
+        if self.ssh.execute(&head_cmd).await.is_ok() {
+            return MirrorReach::Primary;
+        }
+        let fallback_url = format!(
+            "http://old-releases.ubuntu.com/ubuntu/dists/{}/Release",
+            release
+        );
+        let fallback_cmd = format!("curl -fsI '{}' >/dev/null", fallback_url);
+        if self.ssh.execute(&fallback_cmd).await.is_ok() {
+            MirrorReach::FallbackOnly
         } else {
-            error!(
-                "❌ Installation completed with {} failed phases out of 6 total phases",
-                failed_phases.len()
-            );
-            error!("💡 SSH session remains active for manual debugging and investigation");
-            error!("💡 You can inspect logs, retry specific phases, or analyze the system state");
+            MirrorReach::Unreachable
+        }
+    }
 
-            // Don't disconnect - let the user investigate
-            Err(crate::error::AutoInstallError::InstallationError(format!(
-                "Installation failed: {} phases failed",
-                failed_phases.len()
-            )))
+    /// Score the target against the `health_gate:` weights before the disk is wiped
+    ///
+    /// The score is kept for the session and reports. Returns an error when it is below
+    /// `min_score` and the gate is enforced.
+    async fn check_health_gate(&mut self, config: &InstallationConfig) -> Result<()> {
+        let gate = &config.health_gate;
+        if !gate.check {
+            return Ok(());
+        }
+        let network = self.probe_network().await;
+        let mirror = self.probe_mirror(config).await;
+        let clock_skew_secs = self
+            .ssh
+            .execute_with_output("date +%s")
+            .await
+            .ok()
+            .and_then(|out| out.trim().parse::<i64>().ok())
+            .map(|target| target - chrono::Utc::now().timestamp());
+        let memory_mb = self
+            .target_facts()
+            .await
+            .ok()
+            .and_then(|facts| facts.memory_total_mb);
+        let inputs = HealthInputs {
+            network,
+            disk_health: self.disk_health.as_ref().map(DiskHealthCheck::verdict),
+            clock_skew_secs,
+            mirror,
+            memory_mb,
+        };
+        let score = HealthScore::evaluate(&inputs, gate);
+        let summary = score.summary();
+        let passed = score.passed();
+        self.health_score = Some(score);
+        if passed {
+            info!("Health gate: go, {}", summary);
+            return Ok(());
+        }
+        if !gate.enforce {
+            self.record_warning(format!("Health gate: score below minimum, {}", summary));
+            return Ok(());
+        }
+        Err(crate::error::AutoInstallError::ValidationError(format!(
+            "Health gate: no-go, {}",
+            summary
+        )))
+    }
+
+    /// Record a session for an install stopped by the health gate and return `error`
+    fn fail_health_gate(
+        &mut self,
+        error: crate::error::AutoInstallError,
+        successful_phases: &[&str],
+        failed_phases: &[String],
+    ) -> Result<()> {
+        error!("✗ {}", error);
+        if let Some(session) = self.session.as_mut() {
+            session.health_score = self.health_score.clone();
         }
+        let mut failed = failed_phases.to_vec();
+        failed.push(format!("Health gate - {}", error));
+        self.finish_session(SessionStatus::Failed, successful_phases, &failed);
+        Err(error)
     }
 
     /// Preflight validation: networking, mirrors, mountpoints, and existing state
     async fn preflight_checks(&mut self, config: &InstallationConfig) -> Result<()> {
         info!("Running preflight checks");
 
+        // 0) Install disk and interface exist on the target
+        let facts = self.target_facts().await?;
+        for warning in facts.check_install_target(&config.disk_device, &config.network_interface)? {
+            self.record_warning(format!("Preflight: {}", warning));
+        }
+
         // 1) Basic network connectivity
-        let ping_status = self
-            .ssh
-            .execute(
-                "ping -c 1 -w 2 1.1.1.1 >/dev/null 2>&1 || ping -c 1 -w 2 8.8.8.8 >/dev/null 2>&1",
-            )
-            .await;
-        if ping_status.is_err() {
+        if !self.probe_network().await {
             return Err(crate::error::AutoInstallError::ValidationError(
                 "No basic network connectivity (ICMP)".to_string(),
             ));
         }
 
         // 2) Check debootstrap mirror reachability
-        let release = config.debootstrap_release.as_deref().unwrap_or("plucky");
-        let mirror = config
-            .debootstrap_mirror
-            .as_deref()
-            .unwrap_or("http://archive.ubuntu.com/ubuntu/");
-        let release_url = format!("{}/dists/{}/Release", mirror.trim_end_matches('/'), release);
-        let head_cmd = format!("curl -fsI '{}' >/dev/null", release_url);
-        if self.ssh.execute(&head_cmd).await.is_err() {
-            // Try old-releases as backup if not already
-            let fallback_url = format!(
-                "http://old-releases.ubuntu.com/ubuntu/dists/{}/Release",
-                release
-            );
-            let fallback_cmd = format!("curl -fsI '{}' >/dev/null", fallback_url);
-            if self.ssh.execute(&fallback_cmd).await.is_err() {
+        match self.probe_mirror(config).await {
+            MirrorReach::Primary => {}
+            MirrorReach::FallbackOnly => {
+                info!("Mirror check: primary unreachable; old-releases is reachable")
+            }
+            MirrorReach::Unreachable => {
                 return Err(crate::error::AutoInstallError::ValidationError(format!(
                     "Debootstrap mirror not reachable for {}",
-                    release
+                    config.release()
                 )));
-            } else {
-                info!("Mirror check: primary unreachable; old-releases is reachable");
             }
         }
 
@@ -453,20 +1647,27 @@ impl SshInstaller {
         }
 
         // 4) Detect existing pools to avoid duplicate creation
+        let layout = config.pool_layout()?;
         let has_bpool = self
             .ssh
-            .check_silent("zpool list -H bpool >/dev/null 2>&1")
+            .check_silent(&format!(
+                "zpool list -H {} >/dev/null 2>&1",
+                layout.boot.name
+            ))
             .await
             .unwrap_or(false);
         let has_rpool = self
             .ssh
-            .check_silent("zpool list -H rpool >/dev/null 2>&1")
+            .check_silent(&format!(
+                "zpool list -H {} >/dev/null 2>&1",
+                layout.root.name
+            ))
             .await
             .unwrap_or(false);
         if has_bpool || has_rpool {
             info!(
-                "Preflight: existing pools detected: bpool={} rpool={}",
-                has_bpool, has_rpool
+                "Preflight: existing pools detected: {}={} {}={}",
+                layout.boot.name, has_bpool, layout.root.name, has_rpool
             );
         }
 
@@ -482,16 +1683,7 @@ impl SshInstaller {
             .check_silent("mount | grep -q '/mnt/targetos' ")
             .await
             .unwrap_or(false);
-        let pools_exist = self
-            .ssh
-            .check_silent("zpool list -H bpool >/dev/null 2>&1")
-            .await
-            .unwrap_or(false)
-            || self
-                .ssh
-                .check_silent("zpool list -H rpool >/dev/null 2>&1")
-                .await
-                .unwrap_or(false);
+        let pools_exist = has_bpool || has_rpool;
 
         if luks_active || luks_mounted || target_has_mounts || pools_exist {
             info!(
@@ -651,26 +1843,196 @@ impl SshInstaller {
             .execute(&format!("export NET_ET_NAMESERVERS=({})", nameservers))
             .await?;
 
+        self.apply_host_vars(config)?;
+        Ok(())
+    }
+
+    /// Take the identifiers an earlier install of the host left in its host vars
+    fn apply_host_vars(&mut self, config: &InstallationConfig) -> Result<()> {
+        let vars = HostVars::load(&Self::logs_base_dir(), &config.hostname)?;
+        for (key, variable) in [
+            (host_vars::INSTALL_ID, "UUID"),
+            (host_vars::LUKS_UUID, "LUKS_UUID"),
+        ] {
+            if let Some(value) = vars.get(key) {
+                info!("Host vars: {} = {}", key, value);
+                self.variables
+                    .insert(variable.to_string(), value.to_string());
+            }
+        }
+        if let Some(previous) = vars.get(host_vars::NETWORK_ADDRESS) {
+            if previous != config.network_address {
+                self.record_warning(format!(
+                    "Address changed since the last install of {}: {} -> {}",
+                    config.hostname, previous, config.network_address
+                ));
+            }
+        }
+        Ok(())
+    }
+
+    /// Store the identifiers of this install in the host vars and push them when configured
+    async fn record_host_vars(&mut self, config: &InstallationConfig) -> Result<()> {
+        let base_dir = Self::logs_base_dir();
+        let mut vars = HostVars::load(&base_dir, &config.hostname)?;
+        let layout = config.pool_layout()?;
+        let mut values = HostVarsCollector::new(&mut self.ssh)
+            .collect(
+                "/mnt/targetos",
+                &partition_path(&config.disk_device, 4),
+                &layout.root.name,
+                &layout.boot.name,
+            )
+            .await?;
+        if let Some(uuid) = self.variables.get("UUID") {
+            values.insert(host_vars::INSTALL_ID, uuid.clone());
+        }
+        values.insert(host_vars::NETWORK_ADDRESS, config.network_address.clone());
+        for (key, value) in &values {
+            if let Some(previous) = vars.set(key, value, "install") {
+                info!("Host vars: {} changed from {} to {}", key, previous, value);
+            }
+        }
+        let path = vars.save(&base_dir)?;
+        info!("Host vars written to {}", path.display());
+        if config.host_vars.push {
+            let command =
+                vars.build_push_command("/mnt/targetos", &config.host_vars.target_path)?;
+            self.ssh.execute(&command).await?;
+        }
         Ok(())
     }
 
     /// Phase 1: Install required packages
-    async fn phase_1_package_installation(&mut self) -> Result<()> {
+    async fn phase_1_package_installation(&mut self, config: &InstallationConfig) -> Result<()> {
         info!("Phase 1: Package installation");
 
-        let mut package_manager = PackageManager::new(&mut self.ssh);
+        // A token that cannot be fetched should fail now, not after the disk is wiped
+        if let Some(token) = &config.ubuntu_pro.token {
+            self.pro_token = Some(token.resolve()?);
+        }
+
+        // Swap goes in before apt starts competing for memory
+        self.prepare_low_memory(config).await?;
+
+        let mut package_manager =
+            PackageManager::new(&mut self.ssh).with_apt_lock(config.apt_lock.clone());
         package_manager.install_required_packages().await?;
 
         info!("Phase 1 completed: Required packages installed");
         Ok(())
     }
 
+    /// Warn about a target below the recommended RAM and add swap when low-memory mode applies
+    ///
+    /// Swap that cannot be set up is a warning; the install is tried anyway.
+    async fn prepare_low_memory(&mut self, config: &InstallationConfig) -> Result<()> {
+        let memory_mb = self.target_facts().await?.memory_total_mb;
+        if let Some(warning) = config.low_memory.check_recommended(memory_mb) {
+            self.record_warning(warning);
+        }
+        if self.low_memory || !config.low_memory.enabled_for(memory_mb) {
+            return Ok(());
+        }
+        info!(
+            "Low-memory mode: zram{}, ARC capped at {} MiB, package roles installed one by one",
+            if config.low_memory.swapfile.is_some() {
+                " and swapfile"
+            } else {
+                ""
+            },
+            config.low_memory.arc_max_mb
+        );
+        for cmd in config
+            .low_memory
+            .build_enable_commands(memory_mb.unwrap_or(2048))
+        {
+            if let Err(e) = self.ssh.execute(&cmd).await {
+                self.record_warning(format!("Low-memory mode: {} failed: {}", cmd, e));
+                break;
+            }
+        }
+        self.low_memory = true;
+        Ok(())
+    }
+
+    /// Detect the hardware RNG and warn when the live entropy pool is low
+    async fn check_entropy(&mut self, config: &InstallationConfig) {
+        if !config.entropy.is_enabled() {
+            return;
+        }
+        let current = self
+            .ssh
+            .execute_with_output(&format!("cat {} 2>/dev/null || true", HWRNG_CURRENT))
+            .await
+            .unwrap_or_default();
+        self.hwrng = parse_hwrng(&current);
+        info!(
+            "Hardware RNG: {}",
+            self.hwrng.as_deref().unwrap_or("none found")
+        );
+        if let Ok(avail) = self
+            .ssh
+            .execute_with_output(&format!("cat {}", ENTROPY_AVAIL))
+            .await
+        {
+            if let Some(warning) = config.entropy.check_pool(&avail) {
+                self.record_warning(warning);
+            }
+        }
+    }
+
+    /// Give the page cache back before a memory-hungry phase in low-memory mode
+    async fn relieve_memory_pressure(&mut self) {
+        if self.low_memory {
+            let _ = self
+                .ssh
+                .execute("sync && echo 3 > /proc/sys/vm/drop_caches")
+                .await;
+        }
+    }
+
+    /// Probe tool versions in the live environment and refuse to continue without a fallback
+    async fn check_capabilities(&mut self, config: &InstallationConfig) -> Result<()> {
+        let release = config.release();
+        let capabilities = TargetCapabilities::probe(&mut self.ssh, release).await?;
+        info!("Live environment: {}", capabilities.summary());
+        capabilities.require(release)?;
+        if let Some(zfs) = capabilities.zfs {
+            let problems = unsupported_pool_settings(&config.pool_layout()?, zfs);
+            if !problems.is_empty() {
+                return Err(crate::error::AutoInstallError::ValidationError(format!(
+                    "zfs_pools settings are not supported by the live system: {}",
+                    problems.join("; ")
+                )));
+            }
+        }
+        self.capabilities = Some(capabilities);
+        Ok(())
+    }
+
     /// Phase 2: Disk preparation and partitioning
     async fn phase_2_disk_preparation(&mut self, config: &InstallationConfig) -> Result<()> {
         info!("Phase 2: Disk preparation and partitioning");
 
-        let mut disk_manager = DiskManager::new(&mut self.ssh);
-        disk_manager.prepare_disk(config).await?;
+        if self.reinstall {
+            DiskManager::new(&mut self.ssh)
+                .reopen_for_reinstall(config)
+                .await?;
+        } else {
+            // luksFormat reads the pool first
+            self.check_entropy(config).await;
+
+            let mut disk_manager = DiskManager::new(&mut self.ssh)
+                .with_capabilities(self.capabilities.clone())
+                .with_luks_uuid(self.variables.get("LUKS_UUID").cloned());
+            disk_manager.prepare_disk(config).await?;
+        }
+
+        if !config.esp_mirror_devices.is_empty() {
+            let mut esp_manager = RedundantEspManager::new(&mut self.ssh);
+            esp_manager.prepare_secondary_esps(config).await?;
+        }
 
         info!("Phase 2 completed: Disk preparation and partitioning");
         Ok(())
@@ -680,6 +2042,35 @@ impl SshInstaller {
     async fn phase_3_zfs_creation(&mut self, config: &InstallationConfig) -> Result<()> {
         info!("Phase 3: ZFS pool and dataset creation");
 
+        if self.low_memory {
+            let arc = config.low_memory.build_arc_command();
+            if let Err(e) = self.ssh.execute(&arc).await {
+                self.record_warning(format!("Low-memory mode: ARC limit not set: {}", e));
+            }
+        }
+
+        if self.reinstall {
+            let layout = config.pool_layout()?;
+            let mut manager = ReinstallManager::new(&mut self.ssh);
+            let plan = manager
+                .plan(
+                    &layout,
+                    &config.reinstall,
+                    self.variables.get("UUID").map(String::as_str),
+                )
+                .await?;
+            info!("Reinstall onto the existing pools:");
+            for line in plan.summary_lines() {
+                info!("  {}", line);
+            }
+            manager.replace_os_datasets(&plan).await?;
+            // The new OS datasets take the old id, so USERDATA/root_<id> stays attached
+            if let Some(id) = &plan.installation_id {
+                self.variables.insert("UUID".to_string(), id.clone());
+            }
+            self.reinstall_plan = Some(plan);
+        }
+
         let mut zfs_manager = ZfsManager::new(&mut self.ssh, &mut self.variables);
         zfs_manager.create_zfs_pools(config).await?;
 
@@ -690,8 +2081,12 @@ impl SshInstaller {
     /// Phase 4: Base system installation
     async fn phase_4_base_system(&mut self, config: &InstallationConfig) -> Result<()> {
         info!("Phase 4: Base system installation");
+        self.relieve_memory_pressure().await;
 
-        let mut system_configurator = SystemConfigurator::new(&mut self.ssh);
+        let mut system_configurator = SystemConfigurator::new(&mut self.ssh)
+            .with_package_transactions(self.transactional_packages)
+            .with_serialized_packages(self.low_memory)
+            .with_capabilities(self.capabilities.clone());
         system_configurator.install_base_system(config).await?;
 
         info!("Phase 4 completed: Base system installed");
@@ -701,29 +2096,421 @@ impl SshInstaller {
     /// Phase 5: System configuration
     async fn phase_5_system_configuration(&mut self, config: &InstallationConfig) -> Result<()> {
         info!("Phase 5: System configuration");
+        self.relieve_memory_pressure().await;
 
         let mut system_configurator = SystemConfigurator::new(&mut self.ssh);
 
         // Configure ZFS
         system_configurator.configure_zfs_in_chroot().await?;
 
-        // Configure GRUB
-        system_configurator.configure_grub_in_chroot(config).await?;
+        // Serial console settings and the performance preset's kernel parameters feed
+        // update-grub, so they go in before GRUB is configured
+        system_configurator.apply_headless(config).await?;
+        system_configurator.apply_performance(config).await?;
+
+        // Configure the bootloader; systemd-boot checks its loader entries instead of grub.cfg
+        if config.bootloader.is_systemd_boot() {
+            system_configurator
+                .configure_systemd_boot_in_chroot(config)
+                .await?;
+        } else {
+            system_configurator.configure_grub_in_chroot(config).await?;
+        }
+
+        // Mirror the bootloader onto any secondary ESPs and check NVRAM entries
+        let mut esp_manager = RedundantEspManager::new(&mut self.ssh);
+        esp_manager.install_bootloaders(config).await?;
+        esp_manager.verify_boot_entries(config).await?;
+
+        // ZFS module options sized from the target's RAM; explicit kernel tuning below overrides them
+        let tuning = self.choose_zfs_tuning(config).await?;
+
+        let mut system_configurator = SystemConfigurator::new(&mut self.ssh);
+        system_configurator.apply_zfs_tuning(&tuning).await?;
+
+        // Kernel tuning (sysctl, modules); written before the crypttab step regenerates the initramfs
+        system_configurator.apply_kernel_config(config).await?;
+
+        // Hardening profile; its sysctl file sorts before the kernel tuning file so tuning wins
+        system_configurator.apply_hardening(config).await?;
+
+        // Firewall rules; only loaded on first boot, the live session is not filtered
+        system_configurator.apply_firewall(config).await?;
+
+        // Entropy daemon and the first-boot wait ahead of sshd
+        system_configurator
+            .apply_entropy(config, self.hwrng.as_deref())
+            .await?;
+
+        // Patching posture: unattended-upgrades, apt's daily tasks and pins
+        system_configurator.apply_updates(config).await?;
+
+        // Ubuntu Pro once the sources are final, so ESM pockets are added on top of them
+        system_configurator
+            .apply_ubuntu_pro(config, self.pro_token.as_deref())
+            .await?;
+
+        // cloud-init style user-data, applied by a oneshot unit on first boot
+        system_configurator.apply_user_data(config).await?;
+
+        // Tang binding for unattended unlock; the crypttab step below rebuilds the initramfs
+        system_configurator.apply_nbde(config).await?;
 
         // Setup LUKS key
         system_configurator.setup_luks_key_in_chroot(config).await?;
 
+        // Host certificate and CA trust anchors; the sshd drop-in sorts before hardening's
+        self.install_ssh_certificates(config).await?;
+
+        // Datasets kept by a reinstall that mount from fstab, now that the new fstab exists
+        if let Some(plan) = &self.reinstall_plan {
+            ReinstallManager::new(&mut self.ssh)
+                .link_fstab(plan)
+                .await?;
+        }
+
         info!("Phase 5 completed: System configuration");
         Ok(())
     }
 
+    /// Compute ZFS module parameters from the target's memory and record them in the session
+    async fn choose_zfs_tuning(&mut self, config: &InstallationConfig) -> Result<ZfsTuning> {
+        let memory_total_mb = self.target_facts().await?.memory_total_mb;
+        let tuning = config.zfs_tuning.compute(memory_total_mb)?;
+        if memory_total_mb.is_none()
+            && config.zfs_tuning.enabled
+            && config.zfs_tuning.arc_max_mb.is_none()
+        {
+            self.record_warning(
+                "Target memory unknown; ZFS ARC size left at the module default".to_string(),
+            );
+        }
+        for parameter in &tuning.parameters {
+            info!(
+                "ZFS tuning: {}={} ({})",
+                parameter.name, parameter.value, parameter.reason
+            );
+        }
+        if let Some(session) = self.session.as_mut() {
+            session.zfs_tuning = Some(tuning.clone());
+        }
+        Ok(tuning)
+    }
+
+    /// Run the hardening compliance checklist against the mounted target and record the results
+    async fn run_compliance_checks(&mut self, config: &InstallationConfig) -> Result<()> {
+        let checks = config.hardening.compliance_checks("/mnt/targetos");
+        if checks.is_empty() {
+            return Ok(());
+        }
+        let mut results = Vec::with_capacity(checks.len());
+        for check in checks {
+            let passed = self.ssh.check_silent(&check.command).await?;
+            results.push(ComplianceResult {
+                control: check.control,
+                title: check.title,
+                passed,
+            });
+        }
+        let failed = results.iter().filter(|r| !r.passed).count();
+        info!(
+            "Hardening compliance: {} of {} checks passed",
+            results.len() - failed,
+            results.len()
+        );
+        if failed > 0 {
+            self.record_warning(format!(
+                "{} of {} hardening compliance checks failed; see the compliance section of the report",
+                failed,
+                results.len()
+            ));
+        }
+        if let Some(session) = self.session.as_mut() {
+            session.compliance = results;
+        }
+        Ok(())
+    }
+
+    /// Check `pro status` of an install-time attachment; a first-boot attach is not checked
+    async fn verify_ubuntu_pro(&mut self, config: &InstallationConfig) -> Result<()> {
+        let pro = &config.ubuntu_pro;
+        if !pro.is_enabled() || pro.attach != ProAttachTiming::Install {
+            return Ok(());
+        }
+        let status = self
+            .ssh
+            .execute_with_output(&pro.status_command("/mnt/targetos"))
+            .await?;
+        let problems = pro.check_status(&status);
+        if problems.is_empty() {
+            info!("Ubuntu Pro attached: {}", pro.services.join(", "));
+            return Ok(());
+        }
+        if pro.required {
+            return Err(crate::error::AutoInstallError::InstallationError(format!(
+                "Ubuntu Pro verification failed: {}",
+                problems.join("; ")
+            )));
+        }
+        for problem in problems {
+            self.record_warning(problem);
+        }
+        Ok(())
+    }
+
+    /// Issue an enrollment token, record its hash and write it onto the target
+    async fn install_enrollment_token(&mut self, hostname: &str) -> Result<()> {
+        let (record, token) =
+            EnrollmentToken::issue(hostname, chrono::Duration::hours(DEFAULT_TOKEN_TTL_HOURS))?;
+        // The command carries the token, so it is deliberately not echoed at info level
+        self.ssh
+            .execute(&build_install_token_command("/mnt/targetos", &token))
+            .await?;
+        let path = record.save(&Self::logs_base_dir())?;
+        info!(
+            "Enrollment token installed on target (expires {}), record at {}",
+            record.expires_at.to_rfc3339(),
+            path.display()
+        );
+        Ok(())
+    }
+
+    /// Sign the target's host key, install the CA trust anchors and stage the first-boot key
+    ///
+    /// User certificates are written to `logs/<hostname>/ssh/<name>-cert.pub`.
+    async fn install_ssh_certificates(&mut self, config: &InstallationConfig) -> Result<()> {
+        let settings = &config.ssh_ca;
+        if !settings.enabled {
+            return Ok(());
+        }
+        let host_ca = SshKey::load_or_create(
+            &settings
+                .host_ca_key
+                .clone()
+                .unwrap_or_else(|| SshKey::default_ca_path(CertificateKind::Host)),
+            "ubuntu-autoinstall-agent host CA",
+        )?;
+        let user_ca = SshKey::load_or_create(
+            &settings
+                .user_ca_key
+                .clone()
+                .unwrap_or_else(|| SshKey::default_ca_path(CertificateKind::User)),
+            "ubuntu-autoinstall-agent user CA",
+        )?;
+
+        let host_key_line = self
+            .ssh
+            .execute_with_output(&format!(
+                "cat /mnt/targetos/{}.pub",
+                crate::config::ssh_ca::HOST_KEY_FILE
+            ))
+            .await?;
+        let principals = settings.effective_host_principals(&config.hostname);
+        // Backdate slightly so a target clock running behind still accepts the certificate
+        let now = chrono::Utc::now() - chrono::Duration::minutes(5);
+        let install_cert = host_ca.sign_certificate(&CertificateRequest {
+            kind: CertificateKind::Host,
+            key_id: config.hostname.clone(),
+            principals: principals.clone(),
+            valid_after: now,
+            valid_before: now + chrono::Duration::hours(settings.install_validity_hours.into()),
+            public_key: ssh_ca::parse_public_key(&host_key_line)?,
+        })?;
+        info!(
+            "Installing SSH host certificate for {} (valid {} hours)",
+            principals.join(", "),
+            settings.install_validity_hours
+        );
+        for cmd in settings.build_trust_commands(
+            "/mnt/targetos",
+            &host_ca.public_openssh("host-ca"),
+            &user_ca.public_openssh("user-ca"),
+            &install_cert,
+        ) {
+            self.ssh.execute(&cmd).await?;
+        }
+
+        if settings.rotate_at_first_boot {
+            let next = SshKey::generate()?;
+            let comment = format!("root@{}", config.hostname);
+            let next_cert = host_ca.sign_certificate(&CertificateRequest {
+                kind: CertificateKind::Host,
+                key_id: config.hostname.clone(),
+                principals,
+                valid_after: now,
+                valid_before: now + chrono::Duration::days(settings.host_validity_days.into()),
+                public_key: next.public_key(),
+            })?;
+            // The staging commands carry the private key, so they are deliberately not echoed
+            for cmd in settings.build_rotation_commands(
+                "/mnt/targetos",
+                &next.private_openssh(&comment),
+                &next.public_openssh(&comment),
+                &next_cert,
+            ) {
+                self.ssh.execute(&cmd).await?;
+            }
+            info!(
+                "Staged a new SSH host key for first boot (certificate valid {} days)",
+                settings.host_validity_days
+            );
+        }
+
+        if !settings.users.is_empty() {
+            let dir =
+                InstallSession::host_dir(&Self::logs_base_dir(), &config.hostname).join("ssh");
+            std::fs::create_dir_all(&dir)?;
+            for user in &settings.users {
+                let cert = user_ca.sign_certificate(&CertificateRequest {
+                    kind: CertificateKind::User,
+                    key_id: user.name.clone(),
+                    principals: user.effective_principals(),
+                    valid_after: now,
+                    valid_before: now
+                        + chrono::Duration::hours(settings.user_validity_hours.into()),
+                    public_key: ssh_ca::parse_public_key(&user.public_key)?,
+                })?;
+                let path = dir.join(format!("{}-cert.pub", user.name));
+                std::fs::write(&path, format!("{}\n", cert))?;
+                info!("Wrote SSH user certificate {}", path.display());
+            }
+        }
+        Ok(())
+    }
+
+    /// Run the `late_commands:` scripts and record each run in the session
+    async fn run_late_commands(&mut self, config: &InstallationConfig) -> Result<()> {
+        if config.late_commands.scripts.is_empty() {
+            return Ok(());
+        }
+        let runs = LateCommandRunner::new(&mut self.ssh)
+            .run(&config.late_commands, "/mnt/targetos")
+            .await?;
+        // Only the last run can have stopped the scripts; earlier failures were allowed to
+        let stops = |run: &ScriptRun| {
+            !run.succeeded()
+                && config
+                    .late_commands
+                    .scripts
+                    .iter()
+                    .any(|script| script.name == run.name && !script.continue_on_error)
+        };
+        let error = runs.last().filter(|run| stops(run)).map(|run| {
+            crate::error::AutoInstallError::ProcessError {
+                command: format!("late script {}", run.name),
+                exit_code: Some(run.exit_code),
+                stderr: run.stderr.clone(),
+            }
+        });
+        for run in runs.iter().filter(|run| !run.succeeded() && !stops(run)) {
+            self.record_warning(format!(
+                "Late script {} failed with exit {}",
+                run.name, run.exit_code
+            ));
+        }
+        if let Some(session) = self.session.as_mut() {
+            session.late_commands = runs;
+        }
+        error.map_or(Ok(()), Err)
+    }
+
+    /// Replay the configuration commands and stop the install if any is not re-runnable
+    async fn run_idempotency_audit(&mut self, config: &InstallationConfig) -> Result<()> {
+        let report = IdempotencyAuditor::new(&mut self.ssh)
+            .audit(config, "/mnt/targetos")
+            .await?;
+        let path = report.save(&Self::logs_base_dir())?;
+        info!("Idempotency audit written to {}", path.display());
+        if report.is_clean() {
+            return Ok(());
+        }
+        for finding in &report.findings {
+            error!("✗ {}", finding.describe());
+        }
+        Err(crate::error::AutoInstallError::ValidationError(format!(
+            "{} of {} configuration commands are not idempotent (see {})",
+            report.findings.len(),
+            report.commands,
+            path.display()
+        )))
+    }
+
+    /// Read pools and datasets back from the target and write `logs/<hostname>/runbook.md`
+    async fn write_runbook(&mut self, config: &InstallationConfig) -> Result<()> {
+        let pools =
+            runbook::parse_pools(&self.ssh.execute_with_output(runbook::POOLS_COMMAND).await?);
+        let datasets = config_export::parse_datasets(
+            &self
+                .ssh
+                .execute_with_output(config_export::DATASETS_COMMAND)
+                .await?,
+        );
+        let path = Runbook::new(config, pools, datasets).save(&Self::logs_base_dir())?;
+        info!("Runbook written to {}", path.display());
+        Ok(())
+    }
+
     /// Phase 6: Final setup and cleanup
     async fn phase_6_final_setup(&mut self, config: &InstallationConfig) -> Result<()> {
         info!("Phase 6: Final setup and cleanup");
 
+        // Record the freshly installed state for later drift checks; never fatal
+        let checksum = config.checksum();
+        match BaselineCollector::new(&mut self.ssh)
+            .collect("/mnt/targetos", &config.disk_device, &checksum)
+            .await
+        {
+            Ok(baseline) => match baseline.save(&Self::logs_base_dir(), &config.hostname) {
+                Ok(path) => info!("Recorded host baseline at {}", path.display()),
+                Err(e) => self.record_warning(format!("Failed to write host baseline: {}", e)),
+            },
+            Err(e) => self.record_warning(format!("Failed to collect host baseline: {}", e)),
+        }
+        if let Some(session) = self.session.as_mut() {
+            session.config_checksum = Some(checksum);
+            session.apt_snapshot = config.apt_snapshot;
+            session.plan = Some(InstallPlan::from_config(config));
+        }
+
+        // Operator scripts go last so the checks below see what they changed
+        self.run_late_commands(config).await?;
+
+        // Verify the hardening profile landed while the target is still mounted
+        self.run_compliance_checks(config).await?;
+        self.verify_ubuntu_pro(config).await?;
+
+        if self.audit_idempotency {
+            self.run_idempotency_audit(config).await?;
+        }
+
+        // Describe the installed host for operators; never fatal
+        if let Err(e) = self.write_runbook(config).await {
+            self.record_warning(format!("Failed to write runbook: {}", e));
+        }
+
+        // Identifiers kept for the next reinstall; never fatal
+        if let Err(e) = self.record_host_vars(config).await {
+            self.record_warning(format!("Failed to record host vars: {}", e));
+        }
+
+        // One-time token the first-boot phone-home presents to prove it is this install
+        if let Err(e) = self.install_enrollment_token(&config.hostname).await {
+            self.record_warning(format!("Failed to install enrollment token: {}", e));
+        }
+
         let mut system_configurator = SystemConfigurator::new(&mut self.ssh);
+        // A broken Tang binding must stop the install while the target is still mounted
+        system_configurator.verify_nbde_unlock(config).await?;
         system_configurator.final_cleanup(config).await?;
 
+        // Put the live environment back; it only matters if the machine is not rebooted
+        if self.low_memory {
+            for cmd in config.low_memory.build_disable_commands() {
+                let _ = self.ssh.execute(&cmd).await;
+            }
+            self.low_memory = false;
+        }
+
         info!("Phase 6 completed: Final setup and cleanup");
         info!(
             "Installation of {} completed successfully!",
@@ -741,31 +2528,44 @@ impl Default for SshInstaller {
 
 /// Build the list of commands that would run after storage is prepared, for testing and pause-after-storage preview
 pub(super) fn build_next_commands_after_storage(config: &InstallationConfig) -> Vec<String> {
-    let esp_part = format!("{}p1", config.disk_device);
-    let release = config.debootstrap_release.as_deref().unwrap_or("plucky");
-    vec![
+    let esp_part = partition_path(&config.disk_device, 1);
+    let release = config.release();
+    let apt_sources = if config.uses_apt_mirrors() {
+        config.apt_mirrors.build_deb822_sources(release)
+    } else {
+        build_deb822_sources(release, config.apt_snapshot.as_ref())
+    };
+    let mut cmds = vec![
         // Mount target root and boot/EFI
         "mkdir -p /mnt/targetos/boot/efi".to_string(),
         format!("mount {} /mnt/targetos/boot/efi", esp_part),
-
         // Debootstrap base system (release), try primary mirror then old-releases
         format!(
             "debootstrap {} /mnt/targetos {}",
             release,
-            config
-                .debootstrap_mirror
-                .as_deref()
-                .unwrap_or("http://archive.ubuntu.com/ubuntu/")
+            config.effective_mirror()
         ),
         format!(
             "debootstrap {} /mnt/targetos {} # fallback if the above fails",
             release, "http://old-releases.ubuntu.com/ubuntu/"
         ),
-
+    ];
+    // Mirror lists the sources point at
+    if config.uses_apt_mirrors() {
+        cmds.extend(config.apt_mirrors.build_apply_commands("/mnt/targetos"));
+    }
+    cmds.extend(vec![
         // Configure APT Deb822 sources in target
         "mkdir -p /mnt/targetos/etc/apt/sources.list.d".to_string(),
-        format!("bash -lc 'cat > /mnt/targetos/etc/apt/sources.list.d/ubuntu.sources <<\'EOF\'\nTypes: deb\nURIs: http://archive.ubuntu.com/ubuntu/\nSuites: {rel}\nComponents: main restricted universe multiverse\nSigned-By: /usr/share/keyrings/ubuntu-archive-keyring.gpg\n\nTypes: deb\nURIs: http://security.ubuntu.com/ubuntu\nSuites: {rel}-security\nComponents: main restricted universe multiverse\nSigned-By: /usr/share/keyrings/ubuntu-archive-keyring.gpg\nEOF'", rel=release),
+        format!(
+            "bash -lc 'cat > /mnt/targetos/etc/apt/sources.list.d/ubuntu.sources <<\'EOF\'\n{}EOF'",
+            apt_sources
+        ),
         "rm -f /mnt/targetos/etc/apt/sources.list || true".to_string(),
+    ]);
+    // Third-party repositories, keys verified before their sources are written
+    cmds.extend(config.apt_repos.build_apply_commands("/mnt/targetos"));
+    cmds.extend(vec![
 
         // Prepare chroot mounts
         "mount --rbind /dev /mnt/targetos/dev".to_string(),
@@ -785,31 +2585,72 @@ pub(super) fn build_next_commands_after_storage(config: &InstallationConfig) ->
         // Ensure efivarfs and install core packages
         "chroot /mnt/targetos bash -lc '[ -d /sys/firmware/efi/efivars ] || mkdir -p /sys/firmware/efi/efivars; mountpoint -q /sys/firmware/efi/efivars || mount -t efivarfs efivarfs /sys/firmware/efi/efivars || true'".to_string(),
         "chroot /mnt/targetos bash -lc 'apt update'".to_string(),
-        "chroot /mnt/targetos bash -lc 'DEBIAN_FRONTEND=noninteractive apt install -y grub-efi-amd64 grub-efi-amd64-signed linux-image-generic shim-signed zfs-initramfs zfsutils-linux zsys efibootmgr cryptsetup cryptsetup-initramfs dosfstools'".to_string(),
+        format!("chroot /mnt/targetos bash -lc '{}'", SystemConfigurator::build_base_package_install_command(config)),
         // Optional cleanups and groups
         "chroot /mnt/targetos bash -lc 'DEBIAN_FRONTEND=noninteractive apt purge -y os-prober || true'".to_string(),
         "chroot /mnt/targetos bash -lc 'addgroup --system lpadmin || true'".to_string(),
         "chroot /mnt/targetos bash -lc 'addgroup --system lxd || true'".to_string(),
         "chroot /mnt/targetos bash -lc 'addgroup --system sambashare || true'".to_string(),
 
+    ]);
+    // ZFS options set explicitly; values computed from RAM are only known once connected
+    if let Ok(tuning) = config.zfs_tuning.compute(None) {
+        cmds.extend(tuning.build_apply_commands("/mnt/targetos"));
+    }
+    // Kernel tuning files from the target config
+    cmds.extend(config.kernel.build_apply_commands("/mnt/targetos"));
+    // Hardening profile from the target config
+    cmds.extend(config.hardening.build_apply_commands("/mnt/targetos"));
+    // Firewall from the target config
+    cmds.extend(config.firewall.build_apply_commands("/mnt/targetos"));
+    // Entropy daemon; the hardware RNG is only known once connected
+    cmds.extend(config.entropy.build_apply_commands("/mnt/targetos", None));
+    // Ubuntu Pro; the token is fetched when the install starts
+    if config.ubuntu_pro.is_enabled() {
+        cmds.extend(
+            config
+                .ubuntu_pro
+                .build_attach_commands("/mnt/targetos", "<ubuntu_pro token>"),
+        );
+    }
+    // User-data staged for the first boot
+    cmds.extend(config.user_data.build_apply_commands("/mnt/targetos"));
+    // Serial console, watchdog and RTC; ahead of the update-grub calls below
+    cmds.extend(config.headless.build_apply_commands("/mnt/targetos"));
+    // Performance preset; its GRUB snippet also needs the update-grub calls below
+    cmds.extend(config.performance.build_apply_commands("/mnt/targetos"));
+    cmds.extend(vec![
         // Configure crypttab to unlock LUKS at boot via initramfs
-        format!("bash -lc 'UUID=$(blkid -s UUID -o value {d}p4 2>/dev/null || true); DEV=\"{d}p4\"; [ -n \"$UUID\" ] && DEV=\"/dev/disk/by-uuid/$UUID\"; echo \"luks $DEV none luks,discard,initramfs\" > /mnt/targetos/etc/crypttab'", d=config.disk_device),
+        format!("bash -lc 'UUID=$(blkid -s UUID -o value {d} 2>/dev/null || true); DEV=\"{d}\"; [ -n \"$UUID\" ] && DEV=\"/dev/disk/by-uuid/$UUID\"; echo \"luks $DEV none luks,discard,initramfs\" > /mnt/targetos/etc/crypttab'", d=partition_path(&config.disk_device, 4)),
         "chroot /mnt/targetos bash -lc 'update-initramfs -u -k all'".to_string(),
 
         // ZFS cache seeding and path fix
         "mkdir -p /mnt/targetos/etc/zfs/zfs-list.cache".to_string(),
         "cp -f /etc/zfs/zpool.cache /mnt/targetos/etc/zfs/ 2>/dev/null || true".to_string(),
-        "bash -lc 'touch /mnt/targetos/etc/zfs/zfs-list.cache/bpool /mnt/targetos/etc/zfs/zfs-list.cache/rpool'".to_string(),
+        ZFS_LIST_CACHE_TOUCH.to_string(),
         "chroot /mnt/targetos bash -lc 'timeout 5 zed -F || true'".to_string(),
         "chroot /mnt/targetos bash -lc 'sed -Ei \"s|/mnt/targetos/?|/|\" /etc/zfs/zfs-list.cache/* || true'".to_string(),
         "chroot /mnt/targetos bash -lc 'update-initramfs -u -k all'".to_string(),
-
+    ]);
+    if config.bootloader.is_systemd_boot() {
+        let mut kernel_args = config.headless.kernel_args();
+        kernel_args.extend(config.nbde.kernel_args());
+        kernel_args.extend(config.performance.kernel_args());
+        cmds.extend(config.bootloader.build_systemd_boot_commands(
+            "/mnt/targetos",
+            "/boot/efi",
+            &kernel_args,
+        ));
+        return cmds;
+    }
+    cmds.extend(vec![
         // GRUB installation with fallbacks
         "chroot /mnt/targetos bash -lc 'grub-install --target=x86_64-efi --efi-directory=/boot/efi --bootloader-id=ubuntu --recheck'".to_string(),
         "chroot /mnt/targetos bash -lc 'grub-install --target=x86_64-efi --efi-directory=/boot/efi --bootloader-id=ubuntu --recheck --no-nvram' # fallback".to_string(),
         "chroot /mnt/targetos bash -lc 'grub-install --target=x86_64-efi --efi-directory=/boot/efi --bootloader-id=ubuntu --recheck --removable' # fallback".to_string(),
         "chroot /mnt/targetos bash -lc 'update-grub'".to_string(),
-    ]
+    ]);
+    cmds
 }
 
 #[cfg(test)]
@@ -830,6 +2671,36 @@ mod tests {
             network_nameservers: vec!["1.1.1.1".into(), "8.8.8.8".into()],
             debootstrap_release: release.map(|s| s.to_string()),
             debootstrap_mirror: None,
+            esp_mirror_devices: Vec::new(),
+            architecture: crate::config::Architecture::Amd64,
+            kernel: Default::default(),
+            apt_snapshot: None,
+            hardening: Default::default(),
+            zfs_tuning: Default::default(),
+            firewall: Default::default(),
+            headless: Default::default(),
+            ssh_ca: Default::default(),
+            reinstall: Default::default(),
+            user_data: Default::default(),
+            apt_repos: Default::default(),
+            performance: Default::default(),
+            zfs_pools: Default::default(),
+            ubuntu_pro: Default::default(),
+            apt_mirrors: Default::default(),
+            entropy: Default::default(),
+            low_memory: Default::default(),
+            host_vars: Default::default(),
+            bootloader: Default::default(),
+            budget: Default::default(),
+            confirmation: Default::default(),
+            partitioning: Default::default(),
+            health_gate: Default::default(),
+            apt_lock: Default::default(),
+            disk_health: Default::default(),
+            updates: Default::default(),
+            late_commands: Default::default(),
+            network_recovery: Default::default(),
+            nbde: Default::default(),
         }
     }
 
@@ -902,4 +2773,16 @@ mod tests {
             .any(|c| c.contains("ubuntu.sources") && c.contains("Suites: noble")));
         assert!(cmds.iter().any(|c| c.contains("Suites: noble-security")));
     }
+
+    #[test]
+    fn test_build_next_commands_systemd_boot_skips_grub() {
+        let mut cfg = sample_config_with_release(Some("noble"));
+        cfg.bootloader.kind = crate::config::bootloader::BootloaderKind::SystemdBoot;
+        let cmds = build_next_commands_after_storage(&cfg);
+        assert!(!cmds
+            .iter()
+            .any(|c| c.contains("grub") || c.contains("shim")));
+        assert!(cmds.iter().any(|c| c.contains("bootctl install")));
+        assert!(cmds.last().unwrap().contains("kernel-install add"));
+    }
 }
@@ -1,10 +1,17 @@
 // file: src/network/ssh_installer/investigation.rs
-// version: 1.2.0
+// version: 1.8.0
 // guid: sshinv01-2345-6789-abcd-ef0123456789
 
 //! System investigation capabilities for SSH installation
 
 use super::config::SystemInfo;
+use super::disk_health::{health_command, parse_health, DiskHealthCheck};
+use super::facts::FactsCollector;
+use super::hardware_class::HardwareProfile;
+use super::investigation_report::{
+    parse_lspci_mm, parse_sensors_json, InvestigationReport, LSPCI_COMMAND, SENSORS_COMMAND,
+};
+use crate::config::disk_health::DiskHealthConfig;
 use crate::Result;
 use tracing::{info, warn};
 
@@ -41,6 +48,72 @@ where
         Ok(system_info)
     }
 
+    /// Collect a structured report (disks with serials, network, PCI devices, sensors)
+    ///
+    /// Sections whose tool is missing or produces unparseable output are left empty.
+    pub async fn collect_report(&mut self) -> Result<InvestigationReport> {
+        info!("Collecting structured investigation report");
+
+        let facts = FactsCollector::new(&mut *self.executor).collect().await;
+        let hardware_profile = HardwareProfile::detect(&facts);
+        let pci_devices = self
+            .executor
+            .execute_with_output(LSPCI_COMMAND)
+            .await
+            .map(|out| parse_lspci_mm(&out))
+            .unwrap_or_default();
+        let mut disk_health = Vec::new();
+        for disk in &facts.disks {
+            let health = self
+                .executor
+                .execute_with_output(&health_command(&disk.path))
+                .await
+                .ok()
+                .and_then(|out| parse_health(&out));
+            disk_health.push(DiskHealthCheck::evaluate(
+                &disk.path,
+                health,
+                &DiskHealthConfig::default(),
+            ));
+        }
+        let sensors = self
+            .executor
+            .execute_with_output(SENSORS_COMMAND)
+            .await
+            .ok()
+            .and_then(|out| parse_sensors_json(&out));
+
+        Ok(InvestigationReport {
+            generated_at: chrono::Utc::now(),
+            hostname: self
+                .get_command_output("hostname")
+                .await?
+                .trim()
+                .to_string(),
+            kernel_version: self
+                .get_command_output("uname -r")
+                .await?
+                .trim()
+                .to_string(),
+            os_release: self
+                .get_command_output(". /etc/os-release && echo \"$PRETTY_NAME\"")
+                .await?
+                .trim()
+                .to_string(),
+            disks: facts.disks,
+            disk_health,
+            network: facts.interfaces,
+            cpu: facts.cpu,
+            memory_total_mb: facts.memory_total_mb,
+            hardware_changes: Vec::new(),
+            hardware_profile,
+            hardware_inventory: None,
+            pci_devices,
+            sensors,
+            available_tools: self.check_available_tools().await?,
+        })
+    }
+
     /// Investigate disk configuration
     async fn investigate_disks(&mut self) -> Result<String> {
         info!("Investigating disk configuration");
@@ -0,0 +1,655 @@
+// file: src/network/ssh_installer/investigation_report.rs
+// version: 1.6.0
+// guid: 6f1d8a37-2c94-4b5e-8e07-d3a9c5b1f248
+
+//! Structured investigation report
+//!
+//! `SystemInvestigator::investigate_system` keeps raw command output for logging. This
+//! report combines the target facts (`lsblk -J`, `ip -j`, `lscpu -J`) with `sensors -j` and
+//! `lspci -mm` into typed data that can be exported as JSON or HTML, attached to tickets,
+//! and used to pick an install disk.
+
+use super::disk_health::DiskHealthCheck;
+use super::facts::CpuFacts;
+use super::hardware_baseline::HardwareChange;
+use super::hardware_class::HardwareProfile;
+use crate::network::redfish::HardwareInventory;
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+
+/// Command listing block devices with serials as JSON (sizes in bytes)
+pub const LSBLK_COMMAND: &str =
+    "lsblk -J -b -o NAME,PATH,TYPE,SIZE,MODEL,SERIAL,ROTA,TRAN,FSTYPE,MOUNTPOINT";
+/// Command listing network interfaces and addresses as JSON
+pub const IP_ADDR_COMMAND: &str = "ip -j addr show";
+/// Command listing PCI devices in machine-readable form
+pub const LSPCI_COMMAND: &str = "lspci -mm";
+/// Command reading hardware sensors as JSON (lm-sensors may be missing)
+pub const SENSORS_COMMAND: &str = "sensors -j 2>/dev/null || true";
+
+/// A partition (or other child device) of a disk
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct PartitionReport {
+    pub name: String,
+    pub size_bytes: u64,
+    pub fstype: Option<String>,
+    pub mountpoint: Option<String>,
+}
+
+/// A physical disk
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct DiskReport {
+    pub name: String,
+    pub path: String,
+    pub size_bytes: u64,
+    pub model: Option<String>,
+    pub serial: Option<String>,
+    pub rotational: bool,
+    pub transport: Option<String>,
+    pub partitions: Vec<PartitionReport>,
+}
+
+impl DiskReport {
+    /// Whether any partition of the disk is mounted
+    pub fn in_use(&self) -> bool {
+        self.partitions.iter().any(|p| p.mountpoint.is_some())
+    }
+}
+
+/// A network interface
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct InterfaceReport {
+    pub name: String,
+    pub mac: Option<String>,
+    pub state: Option<String>,
+    /// Addresses in CIDR form
+    pub addresses: Vec<String>,
+}
+
+/// A PCI device
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct PciDevice {
+    pub slot: String,
+    pub class: String,
+    pub vendor: String,
+    pub device: String,
+}
+
+/// Structured report of a target system
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct InvestigationReport {
+    pub generated_at: DateTime<Utc>,
+    pub hostname: String,
+    pub kernel_version: String,
+    pub os_release: String,
+    pub disks: Vec<DiskReport>,
+    /// SMART health of each disk against the default `disk_health:` limits
+    #[serde(default)]
+    pub disk_health: Vec<DiskHealthCheck>,
+    pub network: Vec<InterfaceReport>,
+    #[serde(default)]
+    pub cpu: Option<CpuFacts>,
+    #[serde(default)]
+    pub memory_total_mb: Option<u64>,
+    /// Differences from the host's hardware baseline, when one was compared
+    #[serde(default)]
+    pub hardware_changes: Vec<HardwareChange>,
+    /// Storage class detected from the disks; picks the default storage layout
+    #[serde(default)]
+    pub hardware_profile: Option<HardwareProfile>,
+    /// Asset data from the BMC, when BMC access was configured
+    #[serde(default)]
+    pub hardware_inventory: Option<HardwareInventory>,
+    pub pci_devices: Vec<PciDevice>,
+    /// Raw `sensors -j` output, when lm-sensors is installed
+    pub sensors: Option<Value>,
+    pub available_tools: Vec<String>,
+}
+
+impl InvestigationReport {
+    /// Disks suitable for installation: not in use, largest first
+    pub fn candidate_disks(&self) -> Vec<&DiskReport> {
+        let mut disks: Vec<&DiskReport> = self.disks.iter().filter(|d| !d.in_use()).collect();
+        disks.sort_by_key(|d| std::cmp::Reverse(d.size_bytes));
+        disks
+    }
+
+    /// Render as pretty JSON
+    pub fn to_json(&self) -> crate::Result<String> {
+        Ok(serde_json::to_string_pretty(self)?)
+    }
+
+    /// Render as a plain-text summary
+    pub fn to_text(&self) -> String {
+        let mut out = format!(
+            "Host: {}\nKernel: {}\nOS: {}\nGenerated: {}\n",
+            self.hostname,
+            self.kernel_version,
+            self.os_release,
+            self.generated_at.to_rfc3339()
+        );
+        if !self.hardware_changes.is_empty() {
+            out.push_str("\n!!! HARDWARE CHANGED SINCE BASELINE !!!\n");
+            for change in &self.hardware_changes {
+                out.push_str(&format!("  {}\n", change.describe()));
+            }
+            out.push('\n');
+        }
+        if let Some(cpu) = &self.cpu {
+            out.push_str(&format!("CPU: {}\n", cpu_summary(cpu)));
+        }
+        if let Some(mb) = self.memory_total_mb {
+            out.push_str(&format!("Memory: {}\n", format_bytes(mb * 1024 * 1024)));
+        }
+        if let Some(profile) = &self.hardware_profile {
+            out.push_str(&format!("Hardware class: {}\n", profile.summary()));
+        }
+        if let Some(inventory) = &self.hardware_inventory {
+            out.push_str("\nHardware inventory (BMC):\n");
+            for (label, value) in inventory.summary_rows() {
+                out.push_str(&format!("  {}: {}\n", label, value));
+            }
+            for firmware in &inventory.firmware {
+                out.push_str(&format!(
+                    "  Firmware {}: {}\n",
+                    firmware.name, firmware.version
+                ));
+            }
+            for dimm in inventory.memory.iter().filter(|d| d.populated) {
+                out.push_str(&format!(
+                    "  DIMM {} {} MiB {}\n",
+                    dimm.locator,
+                    dimm.capacity_mib.unwrap_or(0),
+                    dimm.part_number.as_deref().unwrap_or("-")
+                ));
+            }
+            for section in &inventory.unavailable {
+                out.push_str(&format!("  Not available: {}\n", section));
+            }
+        }
+        out.push_str("\nDisks:\n");
+        for disk in &self.disks {
+            out.push_str(&format!(
+                "  {} {} {} serial={} {}\n",
+                disk.path,
+                format_bytes(disk.size_bytes),
+                disk.model.as_deref().unwrap_or("-"),
+                disk.serial.as_deref().unwrap_or("-"),
+                if disk.in_use() { "(in use)" } else { "" }
+            ));
+            for part in &disk.partitions {
+                out.push_str(&format!(
+                    "    {} {} {} {}\n",
+                    part.name,
+                    format_bytes(part.size_bytes),
+                    part.fstype.as_deref().unwrap_or("-"),
+                    part.mountpoint.as_deref().unwrap_or("")
+                ));
+            }
+        }
+        let candidates: Vec<&str> = self
+            .candidate_disks()
+            .into_iter()
+            .map(|d| d.path.as_str())
+            .collect();
+        if !candidates.is_empty() {
+            out.push_str(&format!(
+                "  Install candidates: {}\n",
+                candidates.join(", ")
+            ));
+        }
+        if !self.disk_health.is_empty() {
+            out.push_str("\nDisk health:\n");
+            for check in &self.disk_health {
+                out.push_str(&format!("  {}\n", check.summary()));
+            }
+        }
+        out.push_str("\nNetwork:\n");
+        for iface in &self.network {
+            out.push_str(&format!(
+                "  {} {} {} {}\n",
+                iface.name,
+                iface.state.as_deref().unwrap_or("-"),
+                iface.mac.as_deref().unwrap_or("-"),
+                iface.addresses.join(", ")
+            ));
+        }
+        out.push_str("\nPCI devices:\n");
+        for dev in &self.pci_devices {
+            out.push_str(&format!(
+                "  {} {}: {} {}\n",
+                dev.slot, dev.class, dev.vendor, dev.device
+            ));
+        }
+        out
+    }
+
+    /// Render as a self-contained HTML page
+    pub fn to_html(&self) -> String {
+        let mut html = format!(
+            "<!DOCTYPE html>\n<html><head><meta charset=\"utf-8\"><title>Investigation: {0}</title>\
+             <style>body{{font-family:sans-serif}}table{{border-collapse:collapse}}\
+             td,th{{border:1px solid #ccc;padding:4px 8px;text-align:left}}</style></head><body>\n\
+             <h1>{0}</h1>\n<p>Kernel {1} &middot; {2} &middot; generated {3}</p>\n",
+            html_escape(&self.hostname),
+            html_escape(&self.kernel_version),
+            html_escape(&self.os_release),
+            self.generated_at.to_rfc3339()
+        );
+        if !self.hardware_changes.is_empty() {
+            html.push_str("<div style=\"border:2px solid #c00;padding:4px 8px\"><h2>Hardware changed since baseline</h2>\n<ul>\n");
+            for change in &self.hardware_changes {
+                html.push_str(&format!("<li>{}</li>\n", html_escape(&change.describe())));
+            }
+            html.push_str("</ul></div>\n");
+        }
+        if let Some(cpu) = &self.cpu {
+            html.push_str(&format!("<p>CPU: {}</p>\n", html_escape(&cpu_summary(cpu))));
+        }
+        if let Some(mb) = self.memory_total_mb {
+            html.push_str(&format!(
+                "<p>Memory: {}</p>\n",
+                format_bytes(mb * 1024 * 1024)
+            ));
+        }
+        if let Some(profile) = &self.hardware_profile {
+            html.push_str(&format!(
+                "<p>Hardware class: {}</p>\n",
+                html_escape(&profile.summary())
+            ));
+        }
+        if let Some(inventory) = &self.hardware_inventory {
+            html.push_str("<h2>Hardware inventory</h2>\n<table>\n");
+            for (label, value) in inventory.summary_rows() {
+                html.push_str(&format!(
+                    "<tr><th>{}</th><td>{}</td></tr>\n",
+                    label,
+                    html_escape(&value)
+                ));
+            }
+            for firmware in &inventory.firmware {
+                html.push_str(&format!(
+                    "<tr><th>Firmware: {}</th><td>{}</td></tr>\n",
+                    html_escape(&firmware.name),
+                    html_escape(&firmware.version)
+                ));
+            }
+            html.push_str("</table>\n");
+        }
+
+        html.push_str("<h2>Disks</h2>\n<table><tr><th>Device</th><th>Size</th><th>Model</th><th>Serial</th><th>Transport</th><th>Partitions</th></tr>\n");
+        for disk in &self.disks {
+            let parts: Vec<String> = disk
+                .partitions
+                .iter()
+                .map(|p| {
+                    format!(
+                        "{} ({}{})",
+                        p.name,
+                        p.fstype.as_deref().unwrap_or("-"),
+                        p.mountpoint
+                            .as_deref()
+                            .map(|m| format!(" on {}", m))
+                            .unwrap_or_default()
+                    )
+                })
+                .collect();
+            html.push_str(&format!(
+                "<tr><td>{}</td><td>{}</td><td>{}</td><td>{}</td><td>{}</td><td>{}</td></tr>\n",
+                html_escape(&disk.path),
+                format_bytes(disk.size_bytes),
+                html_escape(disk.model.as_deref().unwrap_or("")),
+                html_escape(disk.serial.as_deref().unwrap_or("")),
+                html_escape(disk.transport.as_deref().unwrap_or("")),
+                html_escape(&parts.join(", "))
+            ));
+        }
+        html.push_str("</table>\n");
+
+        if !self.disk_health.is_empty() {
+            html.push_str("<h2>Disk health</h2>\n<ul>\n");
+            for check in &self.disk_health {
+                html.push_str(&format!("<li>{}</li>\n", html_escape(&check.summary())));
+            }
+            html.push_str("</ul>\n");
+        }
+
+        html.push_str("<h2>Network</h2>\n<table><tr><th>Interface</th><th>State</th><th>MAC</th><th>Addresses</th></tr>\n");
+        for iface in &self.network {
+            html.push_str(&format!(
+                "<tr><td>{}</td><td>{}</td><td>{}</td><td>{}</td></tr>\n",
+                html_escape(&iface.name),
+                html_escape(iface.state.as_deref().unwrap_or("")),
+                html_escape(iface.mac.as_deref().unwrap_or("")),
+                html_escape(&iface.addresses.join(", "))
+            ));
+        }
+        html.push_str("</table>\n");
+
+        html.push_str("<h2>PCI devices</h2>\n<table><tr><th>Slot</th><th>Class</th><th>Vendor</th><th>Device</th></tr>\n");
+        for dev in &self.pci_devices {
+            html.push_str(&format!(
+                "<tr><td>{}</td><td>{}</td><td>{}</td><td>{}</td></tr>\n",
+                html_escape(&dev.slot),
+                html_escape(&dev.class),
+                html_escape(&dev.vendor),
+                html_escape(&dev.device)
+            ));
+        }
+        html.push_str("</table>\n");
+
+        if let Some(sensors) = &self.sensors {
+            html.push_str(&format!(
+                "<h2>Sensors</h2>\n<pre>{}</pre>\n",
+                html_escape(&serde_json::to_string_pretty(sensors).unwrap_or_default())
+            ));
+        }
+        html.push_str("</body></html>\n");
+        html
+    }
+}
+
+/// One-line CPU description, e.g. `Intel Xeon E-2278G, 16 CPUs, x86_64, VT-x`
+fn cpu_summary(cpu: &CpuFacts) -> String {
+    let mut parts = vec![
+        cpu.model_name
+            .clone()
+            .unwrap_or_else(|| "unknown model".to_string()),
+        format!("{} CPUs", cpu.cpus),
+    ];
+    parts.extend(cpu.architecture.clone());
+    parts.extend(cpu.virtualization.clone());
+    if let Some(hypervisor) = &cpu.hypervisor {
+        parts.push(format!("guest of {}", hypervisor));
+    }
+    parts.join(", ")
+}
+
+/// Escape text for inclusion in HTML
+pub(crate) fn html_escape(text: &str) -> String {
+    text.replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+        .replace('"', "&quot;")
+}
+
+/// Human-readable size (binary units)
+fn format_bytes(bytes: u64) -> String {
+    const UNITS: &[&str] = &["B", "KiB", "MiB", "GiB", "TiB"];
+    let mut value = bytes as f64;
+    let mut unit = 0;
+    while value >= 1024.0 && unit < UNITS.len() - 1 {
+        value /= 1024.0;
+        unit += 1;
+    }
+    format!("{:.1} {}", value, UNITS[unit])
+}
+
+/// Read a string field, treating JSON null and empty strings as missing
+fn str_field(value: &Value, key: &str) -> Option<String> {
+    value
+        .get(key)
+        .and_then(Value::as_str)
+        .map(str::trim)
+        .filter(|s| !s.is_empty())
+        .map(str::to_string)
+}
+
+/// Read a number that older lsblk versions emit as a string
+fn u64_field(value: &Value, key: &str) -> u64 {
+    match value.get(key) {
+        Some(Value::Number(n)) => n.as_u64().unwrap_or(0),
+        Some(Value::String(s)) => s.trim().parse().unwrap_or(0),
+        _ => 0,
+    }
+}
+
+/// Read a boolean that older lsblk versions emit as "0"/"1"
+fn bool_field(value: &Value, key: &str) -> bool {
+    match value.get(key) {
+        Some(Value::Bool(b)) => *b,
+        Some(Value::String(s)) => s.trim() == "1",
+        Some(Value::Number(n)) => n.as_u64() == Some(1),
+        _ => false,
+    }
+}
+
+/// Parse `lsblk -J` output into disks with their partitions
+pub fn parse_lsblk_json(output: &str) -> crate::Result<Vec<DiskReport>> {
+    let parsed: Value = serde_json::from_str(output)?;
+    let devices = parsed
+        .get("blockdevices")
+        .and_then(Value::as_array)
+        .cloned()
+        .unwrap_or_default();
+
+    Ok(devices
+        .iter()
+        .filter(|d| str_field(d, "type").as_deref() == Some("disk"))
+        .map(|d| {
+            let name = str_field(d, "name").unwrap_or_default();
+            let partitions = d
+                .get("children")
+                .and_then(Value::as_array)
+                .map(|children| {
+                    children
+                        .iter()
+                        .map(|c| PartitionReport {
+                            name: str_field(c, "name").unwrap_or_default(),
+                            size_bytes: u64_field(c, "size"),
+                            fstype: str_field(c, "fstype"),
+                            mountpoint: str_field(c, "mountpoint"),
+                        })
+                        .collect()
+                })
+                .unwrap_or_default();
+            DiskReport {
+                path: str_field(d, "path").unwrap_or_else(|| format!("/dev/{}", name)),
+                name,
+                size_bytes: u64_field(d, "size"),
+                model: str_field(d, "model"),
+                serial: str_field(d, "serial"),
+                rotational: bool_field(d, "rota"),
+                transport: str_field(d, "tran"),
+                partitions,
+            }
+        })
+        .collect())
+}
+
+/// Parse `ip -j addr show` output
+pub fn parse_ip_json(output: &str) -> crate::Result<Vec<InterfaceReport>> {
+    let parsed: Value = serde_json::from_str(output)?;
+    Ok(parsed
+        .as_array()
+        .map(|ifaces| {
+            ifaces
+                .iter()
+                .map(|iface| InterfaceReport {
+                    name: str_field(iface, "ifname").unwrap_or_default(),
+                    mac: str_field(iface, "address"),
+                    state: str_field(iface, "operstate"),
+                    addresses: iface
+                        .get("addr_info")
+                        .and_then(Value::as_array)
+                        .map(|addrs| {
+                            addrs
+                                .iter()
+                                .filter_map(|a| {
+                                    Some(format!(
+                                        "{}/{}",
+                                        str_field(a, "local")?,
+                                        a.get("prefixlen")?.as_u64()?
+                                    ))
+                                })
+                                .collect()
+                        })
+                        .unwrap_or_default(),
+                })
+                .collect()
+        })
+        .unwrap_or_default())
+}
+
+/// Parse `lspci -mm` output (`slot "class" "vendor" "device" ...`)
+pub fn parse_lspci_mm(output: &str) -> Vec<PciDevice> {
+    output
+        .lines()
+        .filter_map(|line| {
+            let (slot, rest) = line.trim().split_once(' ')?;
+            let quoted: Vec<&str> = rest.split('"').skip(1).step_by(2).collect();
+            Some(PciDevice {
+                slot: slot.to_string(),
+                class: quoted.first()?.to_string(),
+                vendor: quoted.get(1)?.to_string(),
+                device: quoted.get(2)?.to_string(),
+            })
+        })
+        .collect()
+}
+
+/// Parse `sensors -j` output; empty or invalid output means no sensors
+pub fn parse_sensors_json(output: &str) -> Option<Value> {
+    serde_json::from_str(output.trim()).ok()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::network::ssh_installer::hardware_class::HardwareClass;
+
+    const LSBLK: &str = r#"{"blockdevices":[
+        {"name":"nvme0n1","path":"/dev/nvme0n1","type":"disk","size":512110190592,"model":"Samsung SSD 980","serial":"S64DNX0R","rota":false,"tran":"nvme","fstype":null,"mountpoint":null,
+         "children":[{"name":"nvme0n1p1","type":"part","size":536870912,"fstype":"vfat","mountpoint":"/boot/efi"}]},
+        {"name":"sda","type":"disk","size":"2000398934016","model":"WDC WD20","serial":"WD-123","rota":"1","tran":"sata"},
+        {"name":"loop0","type":"loop","size":1000}
+    ]}"#;
+
+    fn report() -> InvestigationReport {
+        InvestigationReport {
+            generated_at: Utc::now(),
+            hostname: "lab<1>".into(),
+            kernel_version: "6.8.0".into(),
+            os_release: "Ubuntu 24.04".into(),
+            disks: parse_lsblk_json(LSBLK).unwrap(),
+            disk_health: vec![],
+            network: vec![],
+            cpu: Some(CpuFacts {
+                model_name: Some("Xeon E-2278G".into()),
+                cpus: 16,
+                virtualization: Some("VT-x".into()),
+                ..Default::default()
+            }),
+            memory_total_mb: Some(16384),
+            hardware_changes: vec![],
+            hardware_profile: None,
+            hardware_inventory: None,
+            pci_devices: parse_lspci_mm(
+                "00:02.0 \"VGA compatible controller\" \"Intel Corporation\" \"UHD Graphics 620\" -r07 \"Lenovo\" \"ThinkPad\"\n",
+            ),
+            sensors: None,
+            available_tools: vec![],
+        }
+    }
+
+    #[test]
+    fn test_parse_lsblk_handles_old_and_new_formats() {
+        let disks = parse_lsblk_json(LSBLK).unwrap();
+        assert_eq!(disks.len(), 2);
+        assert_eq!(disks[0].serial.as_deref(), Some("S64DNX0R"));
+        assert!(!disks[0].rotational);
+        assert!(disks[0].in_use());
+        assert_eq!(disks[1].path, "/dev/sda");
+        assert_eq!(disks[1].size_bytes, 2000398934016);
+        assert!(disks[1].rotational);
+    }
+
+    #[test]
+    fn test_candidate_disks_skip_in_use() {
+        let report = report();
+        let candidates = report.candidate_disks();
+        assert_eq!(candidates.len(), 1);
+        assert_eq!(candidates[0].name, "sda");
+        assert!(report
+            .to_text()
+            .contains("  Install candidates: /dev/sda\n"));
+    }
+
+    #[test]
+    fn test_parse_ip_and_lspci() {
+        let ifaces = parse_ip_json(
+            r#"[{"ifname":"eno1","address":"aa:bb:cc:dd:ee:ff","operstate":"UP","addr_info":[{"family":"inet","local":"172.16.3.96","prefixlen":23}]}]"#,
+        )
+        .unwrap();
+        assert_eq!(ifaces[0].addresses, vec!["172.16.3.96/23".to_string()]);
+        assert_eq!(ifaces[0].state.as_deref(), Some("UP"));
+
+        let pci = &report().pci_devices;
+        assert_eq!(pci[0].slot, "00:02.0");
+        assert_eq!(pci[0].vendor, "Intel Corporation");
+        assert!(parse_sensors_json("").is_none());
+    }
+
+    #[test]
+    fn test_renderers_escape_and_include_serials() {
+        let report = report();
+        let html = report.to_html();
+        assert!(html.contains("<h1>lab&lt;1&gt;</h1>"));
+        assert!(html.contains("WD-123"));
+        assert!(report.to_text().contains("serial=S64DNX0R"));
+        assert!(report
+            .to_text()
+            .contains("CPU: Xeon E-2278G, 16 CPUs, VT-x\nMemory: 16.0 GiB\n"));
+        let json: Value = serde_json::from_str(&report.to_json().unwrap()).unwrap();
+        assert_eq!(json["disks"][1]["serial"], "WD-123");
+    }
+
+    #[test]
+    fn test_report_shows_hardware_class() {
+        let mut report = report();
+        report.hardware_profile = Some(HardwareProfile {
+            class: HardwareClass::SingleDisk,
+            disks: vec!["/dev/sda".into()],
+        });
+        assert!(report
+            .to_text()
+            .contains("Hardware class: single-disk (/dev/sda) -> single layout\n"));
+        let json: Value = serde_json::from_str(&report.to_json().unwrap()).unwrap();
+        assert_eq!(json["hardware_profile"]["class"], "single-disk");
+    }
+
+    #[test]
+    fn test_report_shows_bmc_inventory() {
+        let mut report = report();
+        report.hardware_inventory = Some(HardwareInventory {
+            manufacturer: Some("Supermicro".into()),
+            bios_version: Some("2.4".into()),
+            ..Default::default()
+        });
+        let text = report.to_text();
+        assert!(
+            text.contains("Hardware inventory (BMC):\n  Manufacturer: Supermicro\n  BIOS: 2.4\n")
+        );
+        assert!(report
+            .to_html()
+            .contains("<tr><th>BIOS</th><td>2.4</td></tr>"));
+    }
+
+    #[test]
+    fn test_hardware_changes_come_first() {
+        let mut report = report();
+        report.hardware_changes = vec![HardwareChange::MemoryChanged {
+            old_mb: 32768,
+            new_mb: 16384,
+        }];
+        let text = report.to_text();
+        let flagged = text
+            .find("HARDWARE CHANGED SINCE BASELINE !!!\n  memory changed: 32768 MiB -> 16384 MiB\n")
+            .unwrap();
+        assert!(flagged < text.find("CPU:").unwrap());
+        assert!(report
+            .to_html()
+            .contains("<li>memory changed: 32768 MiB -&gt; 16384 MiB</li>"));
+    }
+}
@@ -0,0 +1,185 @@
+// file: src/network/ssh_installer/late_commands.rs
+// version: 1.0.0
+// guid: 6a2e8c14-9b37-4f05-8d61-c3f7a0e5b928
+
+//! Running the `late_commands:` scripts in the target
+//!
+//! Scripts are staged under `/root/.late-commands/` in the target (inline ones written from the
+//! config, remote ones downloaded on the live system and checked against their `sha256`), then
+//! run one after another with the limits from [`LateScript::build_run_command`]. What each run
+//! printed and how it ended is kept for the session record.
+
+use crate::config::late_commands::{LateCommandsConfig, LateScript, TIMEOUT_EXIT_CODE};
+use crate::network::transport::base64_encode;
+use crate::network::SshClient;
+use crate::Result;
+use serde::{Deserialize, Serialize};
+use std::time::Instant;
+use tracing::{info, warn};
+
+/// Longest stdout or stderr excerpt kept per script
+const MAX_OUTPUT: usize = 4000;
+
+/// How one script ran
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ScriptRun {
+    pub name: String,
+    /// `inline` or the URL the script came from
+    pub source: String,
+    pub exit_code: i32,
+    /// Stopped by the timeout
+    pub timed_out: bool,
+    pub duration_ms: u64,
+    /// Last part of the script's output
+    pub stdout: String,
+    pub stderr: String,
+}
+
+impl ScriptRun {
+    pub fn succeeded(&self) -> bool {
+        self.exit_code == 0
+    }
+}
+
+/// Keep the end of `text`, where errors usually are
+fn tail(text: &str) -> String {
+    let count = text.chars().count();
+    text.chars()
+        .skip(count.saturating_sub(MAX_OUTPUT))
+        .collect()
+}
+
+/// Command writing or downloading `script` into the target at `root`
+pub fn build_stage_command(script: &LateScript, root: &str) -> String {
+    let root = root.trim_end_matches('/');
+    let path = format!("{}/{}", root, script.path());
+    let dir = &path[..path.rfind('/').unwrap_or(0)];
+    let fetch = match (&script.run, &script.url) {
+        (Some(body), _) => format!(
+            "echo {} | base64 -d > {}",
+            base64_encode(body.as_bytes()),
+            path
+        ),
+        (None, Some(url)) => format!("curl -fsSL --max-time 120 -o {} '{}'", path, url),
+        (None, None) => "false".to_string(),
+    };
+    let mut command = format!("mkdir -p {} && chmod 700 {} && {}", dir, dir, fetch);
+    if let Some(sum) = &script.sha256 {
+        command.push_str(&format!(
+            " && echo '{}  {}' | sha256sum -c --quiet -",
+            sum.to_lowercase(),
+            path
+        ));
+    }
+    command.push_str(&format!(" && chmod 700 {}", path));
+    command
+}
+
+/// Stages and runs late scripts over an SSH connection
+pub struct LateCommandRunner<'a> {
+    ssh: &'a mut SshClient,
+}
+
+impl<'a> LateCommandRunner<'a> {
+    pub fn new(ssh: &'a mut SshClient) -> Self {
+        Self { ssh }
+    }
+
+    /// Run the scripts in order, stopping after the first failure that may not be ignored
+    ///
+    /// The runs so far are returned either way; the caller decides whether the last one
+    /// failing ends the install.
+    pub async fn run(&mut self, config: &LateCommandsConfig, root: &str) -> Result<Vec<ScriptRun>> {
+        let mut runs = Vec::new();
+        for script in &config.scripts {
+            let source = script.url.clone().unwrap_or_else(|| "inline".to_string());
+            let started = Instant::now();
+            let (code, stdout, stderr) = self
+                .ssh
+                .execute_with_error_collection(
+                    &build_stage_command(script, root),
+                    &format!("Staging late script {}", script.name),
+                )
+                .await?;
+            let (exit_code, stdout, stderr) = if code != 0 {
+                (code, stdout, format!("staging failed: {}", stderr))
+            } else {
+                self.ssh
+                    .execute_with_error_collection(
+                        &script.build_run_command(root, config.timeout_secs),
+                        &format!("Late script {}", script.name),
+                    )
+                    .await?
+            };
+            let run = ScriptRun {
+                name: script.name.clone(),
+                source,
+                exit_code,
+                timed_out: code == 0 && exit_code == TIMEOUT_EXIT_CODE,
+                duration_ms: started.elapsed().as_millis() as u64,
+                stdout: tail(&stdout),
+                stderr: tail(&stderr),
+            };
+            let ok = run.succeeded();
+            if ok {
+                info!(
+                    "Late script {} finished in {} ms",
+                    run.name, run.duration_ms
+                );
+            } else {
+                warn!(
+                    "Late script {} failed (exit {}{})",
+                    run.name,
+                    run.exit_code,
+                    if run.timed_out { ", timed out" } else { "" }
+                );
+            }
+            runs.push(run);
+            if !ok && !script.continue_on_error {
+                break;
+            }
+        }
+        // Scripts may hold secrets; they do not stay on the installed system
+        self.ssh
+            .execute(&format!(
+                "rm -rf {}/root/.late-commands",
+                root.trim_end_matches('/')
+            ))
+            .await?;
+        Ok(runs)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_stage_commands() {
+        let inline = LateScript {
+            name: "motd".to_string(),
+            run: Some("echo hi\n".to_string()),
+            ..Default::default()
+        };
+        assert_eq!(
+            build_stage_command(&inline, "/mnt/targetos"),
+            "mkdir -p /mnt/targetos/root/.late-commands && chmod 700 /mnt/targetos/root/.late-commands && echo ZWNobyBoaQo= | base64 -d > /mnt/targetos/root/.late-commands/motd.sh && chmod 700 /mnt/targetos/root/.late-commands/motd.sh"
+        );
+
+        let remote = LateScript {
+            name: "agent".to_string(),
+            url: Some("https://scripts.example/agent.sh".to_string()),
+            sha256: Some("AB".repeat(32)),
+            ..Default::default()
+        };
+        let command = build_stage_command(&remote, "/mnt/targetos");
+        assert!(command.contains("curl -fsSL --max-time 120 -o /mnt/targetos/root/.late-commands/agent.sh 'https://scripts.example/agent.sh'"));
+        assert!(command.contains(&format!(
+            "echo '{}  /mnt/targetos/root/.late-commands/agent.sh' | sha256sum -c --quiet -",
+            "ab".repeat(32)
+        )));
+
+        let long = "x".repeat(MAX_OUTPUT + 10);
+        assert_eq!(tail(&long).len(), MAX_OUTPUT);
+    }
+}
@@ -0,0 +1,268 @@
+// file: src/network/ssh_installer/lock.rs
+// version: 1.2.0
+// guid: 1d7f3b92-6c4e-4a18-9e05-b2a8f6d3c471
+
+//! Per-target locks for destructive operations
+//!
+//! Destructive commands take `locks/<target>.lock` under the working directory before they
+//! touch a machine, so two runs from the same workstation cannot overlap. `ssh-install` also
+//! leaves a marker in the target's `/run`, which catches operators working from different
+//! machines; `/run` is a tmpfs, so the marker never survives a reboot. A lock whose process
+//! has died on this machine is taken over automatically; anything else needs `--steal-lock`.
+
+use crate::error::AutoInstallError;
+use crate::network::SshClient;
+use crate::security::provenance::BuilderIdentity;
+use crate::utils::shell_quote;
+use crate::Result;
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use std::io::Write;
+use std::path::{Path, PathBuf};
+use tracing::warn;
+
+/// Marker written on the target while an install is running
+pub const REMOTE_LOCK_PATH: &str = "/run/ubuntu-autoinstall-agent.lock";
+
+/// Who holds a lock
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct LockHolder {
+    /// `user@host` of the operator
+    pub operator: String,
+    /// Process holding the lock on the operator's machine
+    pub pid: u32,
+    /// Command that took the lock, e.g. `ssh-install`
+    pub command: String,
+    pub acquired_at: DateTime<Utc>,
+}
+
+impl LockHolder {
+    /// Holder record for this process
+    pub fn current(command: &str) -> Self {
+        Self {
+            operator: BuilderIdentity::current().id,
+            pid: std::process::id(),
+            command: command.to_string(),
+            acquired_at: Utc::now(),
+        }
+    }
+
+    /// Whether the holder is a process on this machine that no longer exists
+    pub fn is_stale(&self) -> bool {
+        self.operator == BuilderIdentity::current().id
+            && Path::new("/proc").is_dir()
+            && !Path::new(&format!("/proc/{}", self.pid)).exists()
+    }
+
+    /// Human-readable description for error messages
+    pub fn describe(&self) -> String {
+        format!(
+            "{} (pid {}, `{}`, since {})",
+            self.operator,
+            self.pid,
+            self.command,
+            self.acquired_at.to_rfc3339()
+        )
+    }
+}
+
+/// Local lock on one target, released when dropped
+#[derive(Debug)]
+pub struct TargetLock {
+    path: PathBuf,
+    holder: LockHolder,
+}
+
+impl TargetLock {
+    /// Path of the lock file for `target` under `base_dir`
+    pub fn path(base_dir: &Path, target: &str) -> PathBuf {
+        let name: String = target
+            .chars()
+            .map(|c| {
+                if c.is_ascii_alphanumeric() || matches!(c, '.' | '-' | '_') {
+                    c
+                } else {
+                    '_'
+                }
+            })
+            .collect();
+        base_dir.join("locks").join(format!("{}.lock", name))
+    }
+
+    /// Take the lock on `target` for `command`, taking over stale locks and, with `steal`, live ones
+    pub fn acquire(base_dir: &Path, target: &str, command: &str, steal: bool) -> Result<Self> {
+        let path = Self::path(base_dir, target);
+        if let Some(parent) = path.parent() {
+            std::fs::create_dir_all(parent)?;
+        }
+        let holder = LockHolder::current(command);
+        let content = serde_json::to_string_pretty(&holder)?;
+
+        match std::fs::OpenOptions::new()
+            .write(true)
+            .create_new(true)
+            .open(&path)
+        {
+            Ok(mut file) => file.write_all(content.as_bytes())?,
+            Err(e) if e.kind() == std::io::ErrorKind::AlreadyExists => {
+                let existing = Self::read_holder(&path);
+                match &existing {
+                    Some(current) if current.is_stale() => {
+                        warn!(
+                            "Taking over stale lock on {} from {}",
+                            target,
+                            current.describe()
+                        );
+                    }
+                    Some(current) if !steal => {
+                        return Err(AutoInstallError::ValidationError(format!(
+                            "{} is locked by {}; wait for it to finish or pass --steal-lock",
+                            target,
+                            current.describe()
+                        )));
+                    }
+                    Some(current) => {
+                        warn!("Stealing lock on {} from {}", target, current.describe());
+                    }
+                    None if !steal => {
+                        return Err(AutoInstallError::ValidationError(format!(
+                            "{} has an unreadable lock at {}; pass --steal-lock to replace it",
+                            target,
+                            path.display()
+                        )));
+                    }
+                    None => warn!("Replacing unreadable lock on {}", target),
+                }
+                std::fs::write(&path, content.as_bytes())?;
+            }
+            Err(e) => return Err(e.into()),
+        }
+
+        Ok(Self { path, holder })
+    }
+
+    /// Current holder of the lock file at `path`, if it can be read
+    pub fn read_holder(path: &Path) -> Option<LockHolder> {
+        serde_json::from_str(&std::fs::read_to_string(path).ok()?).ok()
+    }
+}
+
+impl Drop for TargetLock {
+    fn drop(&mut self) {
+        // Only remove the file if it is still ours; it may have been stolen meanwhile
+        if Self::read_holder(&self.path).as_ref() == Some(&self.holder) {
+            let _ = std::fs::remove_file(&self.path);
+        }
+    }
+}
+
+/// Command printing the remote marker, or nothing when there is none
+pub fn build_remote_lock_read_command() -> String {
+    format!("cat {} 2>/dev/null || true", REMOTE_LOCK_PATH)
+}
+
+/// Command writing `holder` as the remote marker
+pub fn build_remote_lock_write_command(holder: &LockHolder) -> Result<String> {
+    let json = serde_json::to_string(holder)?;
+    Ok(format!(
+        "printf '%s\\n' {} > {}",
+        shell_quote(&json),
+        REMOTE_LOCK_PATH
+    ))
+}
+
+/// Command removing the remote marker
+pub fn build_remote_lock_release_command() -> String {
+    format!("rm -f {}", REMOTE_LOCK_PATH)
+}
+
+/// Place the marker on the connected target
+///
+/// A marker left by the same operator (e.g. from an aborted run) is reused; a marker from
+/// anyone else blocks unless `steal` is set.
+pub async fn acquire_remote(ssh: &mut SshClient, holder: &LockHolder, steal: bool) -> Result<()> {
+    let existing = ssh
+        .execute_with_output(&build_remote_lock_read_command())
+        .await?;
+    if !existing.trim().is_empty() {
+        match serde_json::from_str::<LockHolder>(existing.trim()) {
+            Ok(current) if current.operator == holder.operator => {}
+            Ok(current) if !steal => {
+                return Err(AutoInstallError::ValidationError(format!(
+                    "Target is being installed by {}; pass --steal-lock to take over",
+                    current.describe()
+                )));
+            }
+            Ok(current) => warn!("Stealing target lock from {}", current.describe()),
+            Err(_) if !steal => {
+                return Err(AutoInstallError::ValidationError(format!(
+                    "Target has an unreadable lock marker at {}; pass --steal-lock to replace it",
+                    REMOTE_LOCK_PATH
+                )));
+            }
+            Err(_) => warn!("Replacing unreadable target lock marker"),
+        }
+    }
+    ssh.execute(&build_remote_lock_write_command(holder)?).await
+}
+
+/// Remove the marker from the connected target
+pub async fn release_remote(ssh: &mut SshClient) -> Result<()> {
+    ssh.execute(&build_remote_lock_release_command()).await
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::TempDir;
+
+    #[test]
+    fn test_second_acquire_blocks_until_released_or_stolen() {
+        let dir = TempDir::new().unwrap();
+        let lock = TargetLock::acquire(dir.path(), "10.0.0.5", "ssh-install", false).unwrap();
+        assert!(TargetLock::path(dir.path(), "10.0.0.5").exists());
+
+        let err = TargetLock::acquire(dir.path(), "10.0.0.5", "restore", false).unwrap_err();
+        let message = err.to_string();
+        assert!(message.contains("ssh-install"), "{}", message);
+        assert!(message.contains("--steal-lock"), "{}", message);
+
+        // Another target is independent
+        let other = TargetLock::acquire(dir.path(), "10.0.0.6", "deploy", false).unwrap();
+        drop(other);
+
+        // Stealing replaces the holder; the original guard must not delete the new lock
+        let stolen = TargetLock::acquire(dir.path(), "10.0.0.5", "restore", true).unwrap();
+        drop(lock);
+        let path = TargetLock::path(dir.path(), "10.0.0.5");
+        assert_eq!(TargetLock::read_holder(&path).unwrap().command, "restore");
+        drop(stolen);
+        assert!(!path.exists());
+    }
+
+    #[test]
+    fn test_stale_lock_is_taken_over() {
+        let dir = TempDir::new().unwrap();
+        let path = TargetLock::path(dir.path(), "host/a b");
+        assert!(path.ends_with("locks/host_a_b.lock"));
+        std::fs::create_dir_all(path.parent().unwrap()).unwrap();
+        let mut dead = LockHolder::current("ssh-install");
+        dead.pid = u32::MAX;
+        std::fs::write(&path, serde_json::to_string(&dead).unwrap()).unwrap();
+
+        assert!(TargetLock::acquire(dir.path(), "host/a b", "deploy", false).is_ok());
+    }
+
+    #[test]
+    fn test_remote_marker_commands() {
+        let mut holder = LockHolder::current("ssh-install");
+        holder.operator = "o'brien@ws1".to_string();
+        let write = build_remote_lock_write_command(&holder).unwrap();
+        assert!(write.starts_with("printf '%s\\n' '{\"operator\":\"o'\\''brien@ws1\""));
+        assert!(write.ends_with("> /run/ubuntu-autoinstall-agent.lock"));
+        assert_eq!(
+            build_remote_lock_read_command(),
+            "cat /run/ubuntu-autoinstall-agent.lock 2>/dev/null || true"
+        );
+    }
+}
@@ -0,0 +1,459 @@
+// file: src/network/ssh_installer/mirror_select.rs
+// version: 1.0.0
+// guid: 6c2e9b40-71fa-4d83-a5b6-0f3d8e1c9a27
+
+//! Speed test and selection of the fastest reachable Ubuntu mirror
+//!
+//! Each candidate's `Packages.gz` for the release and architecture is downloaded on the
+//! target, so unreachable mirrors and mirrors that do not carry the release are ruled out
+//! by the same request that measures them. The winner is cached per network (default
+//! gateway and its MAC address) in `logs/mirror-cache.json`.
+
+use crate::config::mirrors::MirrorSelectionConfig;
+use crate::config::Architecture;
+use crate::network::SshClient;
+use crate::Result;
+use chrono::{DateTime, Duration, Utc};
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::path::{Path, PathBuf};
+use tracing::{info, warn};
+
+/// File under the logs directory holding the per-network choices
+pub const MIRROR_CACHE_FILE: &str = "mirror-cache.json";
+
+/// One mirror measured from the target
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct MirrorProbe {
+    pub mirror: String,
+    /// HTTP status of the probe download; 0 when no response arrived
+    pub http_status: u16,
+    /// TCP connect time in milliseconds
+    pub latency_ms: f64,
+    /// Average download speed in bytes per second
+    pub bytes_per_sec: f64,
+}
+
+impl MirrorProbe {
+    /// Whether the mirror served the release's package index
+    pub fn reachable(&self) -> bool {
+        self.http_status == 200
+    }
+
+    /// `1.2 MB/s, 14 ms` or the failure
+    pub fn describe(&self) -> String {
+        match self.http_status {
+            200 => format!(
+                "{:.1} MB/s, {:.0} ms",
+                self.bytes_per_sec / 1_000_000.0,
+                self.latency_ms
+            ),
+            0 => "unreachable".to_string(),
+            status => format!("HTTP {}", status),
+        }
+    }
+}
+
+/// Mirror chosen for an install and the measurements behind it
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct MirrorDecision {
+    pub mirror: String,
+    /// Network the choice applies to (`<gateway>@<gateway MAC>`)
+    pub network: String,
+    pub decided_at: DateTime<Utc>,
+    /// Reused from the cache instead of measured on this run
+    #[serde(default)]
+    pub cached: bool,
+    /// Every candidate measured when the decision was made, fastest first
+    pub probes: Vec<MirrorProbe>,
+}
+
+impl MirrorDecision {
+    /// One line for the plan and logs
+    pub fn summary(&self) -> String {
+        let measured = self
+            .probes
+            .iter()
+            .find(|p| p.mirror == self.mirror)
+            .map(|p| p.describe())
+            .unwrap_or_else(|| "not measured".to_string());
+        format!(
+            "{} ({}; best of {} on {}{})",
+            self.mirror,
+            measured,
+            self.probes.len(),
+            self.network,
+            if self.cached { ", cached" } else { "" }
+        )
+    }
+}
+
+/// Base URL with exactly one trailing slash
+fn normalize(mirror: &str) -> String {
+    format!("{}/", mirror.trim().trim_end_matches('/'))
+}
+
+/// Command downloading the release's package index from `mirror` and printing curl's timings
+pub fn probe_command(
+    mirror: &str,
+    release: &str,
+    architecture: Architecture,
+    timeout_secs: u64,
+) -> String {
+    format!(
+        "curl -o /dev/null -sS --max-time {} -w '%{{http_code}} %{{time_connect}} %{{speed_download}}' '{}dists/{}/main/binary-{}/Packages.gz'",
+        timeout_secs,
+        normalize(mirror),
+        release,
+        architecture.as_str()
+    )
+}
+
+/// Parse `<http_code> <time_connect> <speed_download>` as printed by [`probe_command`]
+pub fn parse_probe(mirror: &str, output: &str) -> MirrorProbe {
+    let mut fields = output.split_whitespace();
+    let mut next = || fields.next().and_then(|f| f.parse::<f64>().ok());
+    let http_status = next().unwrap_or(0.0) as u16;
+    let latency_ms = next().unwrap_or(0.0) * 1000.0;
+    let bytes_per_sec = next().unwrap_or(0.0);
+    MirrorProbe {
+        mirror: normalize(mirror),
+        http_status,
+        latency_ms,
+        bytes_per_sec,
+    }
+}
+
+/// Mirror URLs in a mirrors.txt list; apt's optional tab-separated attributes are dropped
+pub fn parse_mirrors_txt(text: &str) -> Vec<String> {
+    text.lines()
+        .filter_map(|line| line.split_whitespace().next())
+        .filter(|url| url.starts_with("http://") || url.starts_with("https://"))
+        .map(normalize)
+        .collect()
+}
+
+/// Sort fastest first: reachable mirrors by throughput, then by latency
+pub fn rank(probes: &mut [MirrorProbe]) {
+    probes.sort_by(|a, b| {
+        b.reachable()
+            .cmp(&a.reachable())
+            .then(b.bytes_per_sec.total_cmp(&a.bytes_per_sec))
+            .then(a.latency_ms.total_cmp(&b.latency_ms))
+    });
+}
+
+/// `<gateway>@<mac>` from `ip -4 route show default` and `ip neigh show <gateway>` output
+pub fn network_key(route: &str, neigh: &str) -> String {
+    let gateway = route
+        .lines()
+        .filter_map(|line| {
+            let fields: Vec<&str> = line.split_whitespace().collect();
+            fields
+                .iter()
+                .position(|f| *f == "via")
+                .and_then(|i| fields.get(i + 1).map(|g| g.to_string()))
+        })
+        .next();
+    let mac = neigh.lines().find_map(|line| {
+        let fields: Vec<&str> = line.split_whitespace().collect();
+        fields
+            .iter()
+            .position(|f| *f == "lladdr")
+            .and_then(|i| fields.get(i + 1).map(|m| m.to_lowercase()))
+    });
+    match (gateway, mac) {
+        (Some(gateway), Some(mac)) => format!("{}@{}", gateway, mac),
+        (Some(gateway), None) => gateway,
+        (None, _) => "no-default-route".to_string(),
+    }
+}
+
+/// Mirror choices keyed by network, stored next to the session records
+pub struct MirrorCache {
+    path: PathBuf,
+}
+
+impl MirrorCache {
+    /// Cache under `<base_dir>/logs/`
+    pub fn in_base_dir(base_dir: &Path) -> Self {
+        Self {
+            path: base_dir.join("logs").join(MIRROR_CACHE_FILE),
+        }
+    }
+
+    fn load_all(&self) -> BTreeMap<String, MirrorDecision> {
+        std::fs::read_to_string(&self.path)
+            .ok()
+            .and_then(|content| serde_json::from_str(&content).ok())
+            .unwrap_or_default()
+    }
+
+    /// Decision for `network` made within `max_age`
+    pub fn get(&self, network: &str, max_age: Duration) -> Option<MirrorDecision> {
+        self.load_all()
+            .remove(network)
+            .filter(|d| Utc::now() - d.decided_at <= max_age)
+    }
+
+    /// Record `decision` for its network, replacing any earlier one
+    pub fn put(&self, decision: &MirrorDecision) -> Result<()> {
+        let mut all = self.load_all();
+        all.insert(decision.network.clone(), decision.clone());
+        if let Some(parent) = self.path.parent() {
+            std::fs::create_dir_all(parent)?;
+        }
+        std::fs::write(&self.path, serde_json::to_string_pretty(&all)?)?;
+        Ok(())
+    }
+}
+
+/// Measures candidates from the target and picks the fastest
+pub struct MirrorSelector<'a> {
+    ssh: &'a mut SshClient,
+}
+
+impl<'a> MirrorSelector<'a> {
+    pub fn new(ssh: &'a mut SshClient) -> Self {
+        Self { ssh }
+    }
+
+    /// Output of `command`, empty when it fails
+    async fn quiet_output(&mut self, command: &str) -> Result<String> {
+        self.ssh
+            .execute_with_output(&format!("{{ {}; }} 2>/dev/null || true", command))
+            .await
+    }
+
+    /// Identify the network the target is on
+    async fn network_key(&mut self) -> Result<String> {
+        let route = self.quiet_output("ip -4 route show default").await?;
+        let gateway = network_key(&route, "");
+        let neigh = match gateway.as_str() {
+            "no-default-route" => String::new(),
+            gateway => {
+                self.quiet_output(&format!("ip neigh show {}", gateway))
+                    .await?
+            }
+        };
+        Ok(network_key(&route, &neigh))
+    }
+
+    /// Expand `mirror://` lists into mirror URLs, dropping duplicates
+    async fn expand(&mut self, candidates: &[String], timeout_secs: u64) -> Result<Vec<String>> {
+        let mut mirrors: Vec<String> = Vec::new();
+        for candidate in candidates {
+            let expanded = match candidate.strip_prefix("mirror://") {
+                Some(list) => {
+                    let text = self
+                        .quiet_output(&format!(
+                            "curl -fsS --max-time {} 'http://{}'",
+                            timeout_secs, list
+                        ))
+                        .await?;
+                    let listed = parse_mirrors_txt(&text);
+                    if listed.is_empty() {
+                        warn!("Mirror list {} returned no mirrors", candidate);
+                    }
+                    listed
+                }
+                None => vec![normalize(candidate)],
+            };
+            for mirror in expanded {
+                if !mirrors.contains(&mirror) {
+                    mirrors.push(mirror);
+                }
+            }
+        }
+        Ok(mirrors)
+    }
+
+    async fn probe(
+        &mut self,
+        mirror: &str,
+        release: &str,
+        architecture: Architecture,
+        timeout_secs: u64,
+    ) -> Result<MirrorProbe> {
+        let output = self
+            .quiet_output(&probe_command(mirror, release, architecture, timeout_secs))
+            .await?;
+        Ok(parse_probe(mirror, &output))
+    }
+
+    /// Choose the mirror debootstrap should use, reusing a fresh cached choice that still answers
+    pub async fn select(
+        &mut self,
+        config: &MirrorSelectionConfig,
+        release: &str,
+        architecture: Architecture,
+        cache: &MirrorCache,
+    ) -> Result<MirrorDecision> {
+        let network = self.network_key().await?;
+
+        if config.cache_hours > 0 {
+            let max_age = Duration::hours(config.cache_hours as i64);
+            if let Some(mut decision) = cache.get(&network, max_age) {
+                let check = self
+                    .probe(&decision.mirror, release, architecture, config.timeout_secs)
+                    .await?;
+                if check.reachable() {
+                    info!(
+                        "Reusing mirror {} chosen for network {}",
+                        decision.mirror, network
+                    );
+                    decision.cached = true;
+                    return Ok(decision);
+                }
+                warn!(
+                    "Cached mirror {} is no longer usable ({}); measuring again",
+                    decision.mirror,
+                    check.describe()
+                );
+            }
+        }
+
+        let mut mirrors = self
+            .expand(&config.candidates_for(architecture), config.timeout_secs)
+            .await?;
+        mirrors.truncate(config.max_probes);
+
+        let mut probes = Vec::new();
+        for mirror in &mirrors {
+            let probe = self
+                .probe(mirror, release, architecture, config.timeout_secs)
+                .await?;
+            info!("Mirror {}: {}", probe.mirror, probe.describe());
+            probes.push(probe);
+        }
+        rank(&mut probes);
+
+        let best = probes.first().filter(|p| p.reachable()).ok_or_else(|| {
+            crate::error::AutoInstallError::NetworkError(format!(
+                "No mirror serves {} for {}: {}",
+                release,
+                architecture.as_str(),
+                probes
+                    .iter()
+                    .map(|p| format!("{} ({})", p.mirror, p.describe()))
+                    .collect::<Vec<_>>()
+                    .join(", ")
+            ))
+        })?;
+        let decision = MirrorDecision {
+            mirror: best.mirror.clone(),
+            network,
+            decided_at: Utc::now(),
+            cached: false,
+            probes: probes.clone(),
+        };
+        if config.cache_hours > 0 {
+            if let Err(e) = cache.put(&decision) {
+                warn!("Could not cache the mirror choice: {}", e);
+            }
+        }
+        Ok(decision)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn probe(mirror: &str, status: u16, speed: f64, latency: f64) -> MirrorProbe {
+        MirrorProbe {
+            mirror: mirror.to_string(),
+            http_status: status,
+            latency_ms: latency,
+            bytes_per_sec: speed,
+        }
+    }
+
+    #[test]
+    fn test_probe_command_and_parse() {
+        let cmd = probe_command("http://mirror.lab/ubuntu", "noble", Architecture::Amd64, 10);
+        assert!(cmd.contains("--max-time 10"));
+        assert!(cmd.contains("-w '%{http_code} %{time_connect} %{speed_download}'"));
+        assert!(
+            cmd.ends_with("'http://mirror.lab/ubuntu/dists/noble/main/binary-amd64/Packages.gz'")
+        );
+
+        let ok = parse_probe("http://mirror.lab/ubuntu", "200 0.014 5400000.000");
+        assert_eq!(ok.mirror, "http://mirror.lab/ubuntu/");
+        assert!(ok.reachable());
+        assert_eq!(ok.describe(), "5.4 MB/s, 14 ms");
+        assert_eq!(
+            parse_probe("http://x/", "404 0.020 120.0").describe(),
+            "HTTP 404"
+        );
+        let failed = parse_probe("http://x/", "");
+        assert!(!failed.reachable());
+        assert_eq!(failed.describe(), "unreachable");
+    }
+
+    #[test]
+    fn test_parse_mirrors_txt() {
+        let text = "http://a.example/ubuntu/\thttp://ignored\nhttps://b.example/ubuntu\n# comment\n\nftp://c.example/\n";
+        assert_eq!(
+            parse_mirrors_txt(text),
+            vec!["http://a.example/ubuntu/", "https://b.example/ubuntu/"]
+        );
+    }
+
+    #[test]
+    fn test_rank_prefers_reachable_then_speed_then_latency() {
+        let mut probes = vec![
+            probe("http://down/", 0, 0.0, 0.0),
+            probe("http://slow/", 200, 1_000_000.0, 5.0),
+            probe("http://fast-far/", 200, 9_000_000.0, 80.0),
+            probe("http://fast-near/", 200, 9_000_000.0, 10.0),
+        ];
+        rank(&mut probes);
+        let order: Vec<&str> = probes.iter().map(|p| p.mirror.as_str()).collect();
+        assert_eq!(
+            order,
+            vec![
+                "http://fast-near/",
+                "http://fast-far/",
+                "http://slow/",
+                "http://down/"
+            ]
+        );
+    }
+
+    #[test]
+    fn test_network_key() {
+        let route = "default via 10.0.0.1 dev eno1 proto static\n";
+        let neigh = "10.0.0.1 dev eno1 lladdr AA:BB:CC:DD:EE:FF REACHABLE\n";
+        assert_eq!(network_key(route, neigh), "10.0.0.1@aa:bb:cc:dd:ee:ff");
+        assert_eq!(network_key(route, ""), "10.0.0.1");
+        assert_eq!(network_key("", ""), "no-default-route");
+    }
+
+    #[test]
+    fn test_cache_roundtrip_and_expiry() {
+        let dir = tempfile::tempdir().unwrap();
+        let cache = MirrorCache::in_base_dir(dir.path());
+        assert!(cache.get("10.0.0.1", Duration::hours(24)).is_none());
+
+        let decision = MirrorDecision {
+            mirror: "http://fast/".into(),
+            network: "10.0.0.1".into(),
+            decided_at: Utc::now() - Duration::hours(2),
+            cached: false,
+            probes: vec![probe("http://fast/", 200, 2_000_000.0, 12.0)],
+        };
+        cache.put(&decision).unwrap();
+        assert!(dir.path().join("logs").join(MIRROR_CACHE_FILE).exists());
+        assert_eq!(
+            cache.get("10.0.0.1", Duration::hours(24)),
+            Some(decision.clone())
+        );
+        assert!(cache.get("10.0.0.1", Duration::hours(1)).is_none());
+        assert!(cache.get("192.168.1.1", Duration::hours(24)).is_none());
+        assert_eq!(
+            decision.summary(),
+            "http://fast/ (2.0 MB/s, 12 ms; best of 1 on 10.0.0.1)"
+        );
+    }
+}
@@ -1,5 +1,5 @@
 // file: src/network/ssh_installer/mod.rs
-// version: 1.0.0
+// version: 1.35.0
 // guid: sshmod01-2345-6789-abcd-ef0123456789
 
 //! SSH-based Ubuntu installation with ZFS and LUKS
@@ -7,13 +7,48 @@
 //! This module provides a comprehensive SSH-based installation system
 //! for Ubuntu with ZFS and LUKS encryption.
 
+pub mod apt_lock;
+pub mod backup;
+pub mod boot_env;
+pub mod budget;
+pub mod capabilities;
 pub mod config;
+pub mod config_export;
+pub mod disk_health;
 pub mod disk_ops;
+pub mod drift;
+pub mod esp;
+pub mod facts;
+pub mod gates;
+pub mod hardware_baseline;
+pub mod hardware_class;
+pub mod health_score;
+pub mod hold;
+pub mod host_vars;
+pub mod idempotency;
+pub mod install_report;
 pub mod installer;
 pub mod investigation;
+pub mod investigation_report;
+pub mod late_commands;
+pub mod lock;
+pub mod mirror_select;
+pub mod network_recovery;
+pub mod package_txn;
 pub mod packages;
+pub mod plan;
+pub mod presets;
+pub mod probes;
+pub mod protection;
+pub mod reinstall;
+pub mod runbook;
+pub mod session;
+pub mod storage_expand;
+pub mod support_bundle;
 pub mod system_setup;
+pub mod upgrade;
 pub mod zfs_ops;
 
-pub use config::{InstallationConfig, SystemInfo};
+pub use config::InstallationConfig;
 pub use installer::SshInstaller;
+pub use presets::PresetStore;
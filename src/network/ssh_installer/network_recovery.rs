@@ -0,0 +1,420 @@
+// file: src/network/ssh_installer/network_recovery.rs
+// version: 1.1.0
+// guid: 9e4b1d73-6a25-4c8f-b07e-2d5f8c3a6e19
+
+//! Diagnosis and repair of network failures while packages are downloaded
+//!
+//! A failed download is classified from the command's output. Failures that look like the
+//! network are confirmed on the live system: without a default route it is a routing problem,
+//! when the mirror's name does not resolve it is DNS, and when both work the mirror is at
+//! fault. Each kind has its own repair, after which the command runs again. Commands are
+//! streamed, so one that stops printing for the stall timeout is stopped and treated as a
+//! failing mirror.
+
+use super::system_setup::is_benign_zsys_error;
+use crate::config::NetworkRecoveryConfig;
+use crate::error::AutoInstallError;
+use crate::network::stream::{OutputLine, StreamKind, StreamOptions};
+use crate::network::SshClient;
+use crate::Result;
+use std::time::Duration;
+use tracing::{debug, info, warn};
+
+/// Release archive for end-of-life releases, debootstrap's last resort
+pub const OLD_RELEASES_MIRROR: &str = "http://old-releases.ubuntu.com/ubuntu/";
+
+/// Output fragments of name resolution failures (apt, debootstrap's wget, curl)
+const DNS_PATTERNS: &[&str] = &[
+    "temporary failure resolving",
+    "temporary failure in name resolution",
+    "could not resolve",
+    "name or service not known",
+    "unable to resolve host address",
+];
+
+/// Output fragments of routing and connectivity failures
+const ROUTING_PATTERNS: &[&str] = &[
+    "network is unreachable",
+    "no route to host",
+    "connection timed out",
+    "failed to connect",
+];
+
+/// Output fragments of a mirror that answers but cannot serve the files
+const MIRROR_PATTERNS: &[&str] = &[
+    "404  not found",
+    "404 not found",
+    "hash sum mismatch",
+    "503  service unavailable",
+    "502  bad gateway",
+    "connection refused",
+    "release file",
+    "failed getting release file",
+    "couldn't download",
+    "failed to fetch",
+];
+
+/// What made a download fail
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum NetworkFailure {
+    /// Names do not resolve
+    Dns,
+    /// The live system has no usable route
+    Routing,
+    /// The network works but the mirror does not serve the files
+    Mirror,
+}
+
+impl NetworkFailure {
+    pub fn as_str(self) -> &'static str {
+        match self {
+            NetworkFailure::Dns => "DNS failure",
+            NetworkFailure::Routing => "routing failure",
+            NetworkFailure::Mirror => "mirror failure",
+        }
+    }
+}
+
+/// Kind of failure suggested by a command's output, if it looks network related
+pub fn classify(output: &str) -> Option<NetworkFailure> {
+    let output = output.to_lowercase();
+    let has = |patterns: &[&str]| patterns.iter().any(|p| output.contains(p));
+    if has(DNS_PATTERNS) {
+        Some(NetworkFailure::Dns)
+    } else if has(ROUTING_PATTERNS) {
+        Some(NetworkFailure::Routing)
+    } else if has(MIRROR_PATTERNS) {
+        Some(NetworkFailure::Mirror)
+    } else {
+        None
+    }
+}
+
+/// Host part of a mirror URL
+fn mirror_host(mirror: &str) -> &str {
+    let rest = mirror.split_once("://").map_or(mirror, |(_, rest)| rest);
+    let authority = rest.split('/').next().unwrap_or(rest);
+    authority
+        .rsplit_once(':')
+        .map_or(authority, |(host, _)| host)
+}
+
+/// Mirrors to rotate through and how to repair the live system and the target
+#[derive(Debug, Clone)]
+pub struct NetworkRecoveryStrategy {
+    config: NetworkRecoveryConfig,
+    mirrors: Vec<String>,
+    current: usize,
+    pinned: bool,
+    /// Target root whose apt sources follow mirror changes
+    root: Option<String>,
+}
+
+impl NetworkRecoveryStrategy {
+    /// Strategy starting from `mirror`; a pinned mirror (an apt snapshot) is never rotated
+    pub fn new(config: &NetworkRecoveryConfig, mirror: &str, pinned: bool) -> Self {
+        let mut mirrors = vec![mirror.to_string()];
+        if !pinned {
+            mirrors.extend(
+                config
+                    .alternate_mirrors
+                    .iter()
+                    .filter(|m| m.as_str() != mirror)
+                    .cloned(),
+            );
+        }
+        Self {
+            config: config.clone(),
+            mirrors,
+            current: 0,
+            pinned,
+            root: None,
+        }
+    }
+
+    /// Add `mirror` as a last resort when no alternates are configured
+    pub fn with_fallback(mut self, mirror: &str) -> Self {
+        if !self.pinned && self.config.alternate_mirrors.is_empty() && self.mirrors[0] != mirror {
+            self.mirrors.push(mirror.to_string());
+        }
+        self
+    }
+
+    /// Rewrite the apt sources under `root` when the mirror changes
+    pub fn for_target(mut self, root: &str) -> Self {
+        self.root = Some(root.trim_end_matches('/').to_string());
+        self
+    }
+
+    /// Mirror commands should currently use
+    pub fn mirror(&self) -> &str {
+        &self.mirrors[self.current]
+    }
+
+    /// Command printing `routing`, `dns` or `ok` for the live system and the current mirror
+    pub fn diagnose_command(&self) -> String {
+        format!(
+            "if ! ip route show default | grep -q .; then echo routing; elif ! getent hosts {} >/dev/null; then echo dns; else echo ok; fi",
+            mirror_host(self.mirror())
+        )
+    }
+
+    /// Commands pointing resolv.conf at the fallback resolvers, in the live system and target
+    pub fn dns_commands(&self) -> Vec<String> {
+        let content: String = self
+            .config
+            .resolvers
+            .iter()
+            .map(|r| format!("nameserver {}\n", r))
+            .collect();
+        let mut commands = vec![format!(
+            "rm -f /etc/resolv.conf && printf '{}' > /etc/resolv.conf",
+            content.replace('\n', "\\n")
+        )];
+        if let Some(root) = &self.root {
+            commands.push(format!(
+                "[ -d {r}/etc ] && rm -f {r}/etc/resolv.conf && printf '{c}' > {r}/etc/resolv.conf || true",
+                r = root,
+                c = content.replace('\n', "\\n")
+            ));
+        }
+        commands
+    }
+
+    /// Commands restarting networking on the live system and waiting for a default route
+    pub fn routing_commands(&self) -> Vec<String> {
+        vec![
+            "netplan apply 2>/dev/null || systemctl restart systemd-networkd 2>/dev/null || systemctl restart NetworkManager 2>/dev/null || true".to_string(),
+            "for i in $(seq 1 30); do ip route show default | grep -q . && exit 0; sleep 1; done; exit 1".to_string(),
+        ]
+    }
+
+    /// Move to the next mirror, returning the commands that switch the target's sources to it
+    pub fn rotate(&mut self) -> Option<Vec<String>> {
+        if self.current + 1 >= self.mirrors.len() {
+            return None;
+        }
+        let old = self.mirror().to_string();
+        self.current += 1;
+        let new = self.mirror();
+        let mut commands = Vec::new();
+        if let Some(root) = &self.root {
+            commands.push(format!(
+                "sed -i 's|{old}|{new}|g' {r}/etc/apt/sources.list.d/*.sources {r}/etc/apt/sources.list 2>/dev/null; true",
+                old = old,
+                new = new,
+                r = root
+            ));
+            commands.push(format!("chroot {} bash -lc 'apt-get update'", root));
+        }
+        Some(commands)
+    }
+
+    /// Run `build(mirror)`, repairing the network and retrying while attempts remain
+    ///
+    /// Benign zsys errors from apt in a chroot count as success, as elsewhere in the install.
+    /// A run that prints nothing for the stall timeout is stopped and retried on another mirror.
+    pub async fn run(
+        &mut self,
+        ssh: &mut SshClient,
+        description: &str,
+        build: impl Fn(&str) -> String,
+    ) -> Result<()> {
+        let mut attempt = 1;
+        loop {
+            let command = build(self.mirror());
+            let (code, stdout, stderr, stalled) = self.stream(ssh, &command, description).await?;
+            if code == 0 {
+                return Ok(());
+            }
+            if is_benign_zsys_error(&stderr) {
+                warn!(
+                    "Ignoring benign zsys error for '{}': exit={} stderr={}",
+                    description, code, stderr
+                );
+                return Ok(());
+            }
+            let error = AutoInstallError::ProcessError {
+                command: command.clone(),
+                exit_code: Some(code),
+                stderr: if stderr.is_empty() {
+                    stdout.clone()
+                } else {
+                    stderr.clone()
+                },
+            };
+            if attempt >= self.config.max_attempts {
+                return Err(error);
+            }
+            let suggested = if stalled {
+                NetworkFailure::Mirror
+            } else {
+                match classify(&format!("{}\n{}", stdout, stderr)) {
+                    Some(suggested) => suggested,
+                    None => return Err(error),
+                }
+            };
+            let failure = if self.config.enabled {
+                self.confirm(ssh, suggested).await
+            } else {
+                NetworkFailure::Mirror
+            };
+            warn!(
+                "{} failed ({}), attempt {} of {}",
+                description,
+                failure.as_str(),
+                attempt,
+                self.config.max_attempts
+            );
+            let repairs = match failure {
+                NetworkFailure::Dns => self.dns_commands(),
+                NetworkFailure::Routing => self.routing_commands(),
+                NetworkFailure::Mirror => match self.rotate() {
+                    Some(commands) => {
+                        info!("Switching to mirror {}", self.mirror());
+                        commands
+                    }
+                    None => return Err(error),
+                },
+            };
+            for repair in repairs {
+                info!("Network recovery: {}", repair);
+                if let Err(e) = ssh.execute(&repair).await {
+                    warn!("Network recovery step failed: {}", e);
+                }
+            }
+            attempt += 1;
+        }
+    }
+
+    /// Stream `command` and collect its output; returns exit code, stdout, stderr and whether
+    /// it was stopped for printing nothing
+    async fn stream(
+        &self,
+        ssh: &mut SshClient,
+        command: &str,
+        description: &str,
+    ) -> Result<(i32, String, String, bool)> {
+        info!("Executing: {} -> {}", description, command);
+        let options = match self.config.stall_timeout_secs {
+            0 => StreamOptions::default(),
+            secs => StreamOptions::with_silence_timeout(Duration::from_secs(secs)),
+        };
+        let mut output = [String::new(), String::new()];
+        let mut collect = |line: &OutputLine| {
+            debug!("{}: {}", description, line.line);
+            let index = usize::from(line.stream == StreamKind::Stderr);
+            output[index].push_str(&line.line);
+            output[index].push('\n');
+        };
+        let result = ssh.execute_streaming(command, &options, &mut collect).await;
+        let [stdout, stderr] = output;
+        match result {
+            Ok(()) => Ok((0, stdout, stderr, false)),
+            Err(AutoInstallError::ProcessError {
+                exit_code,
+                stderr: reported,
+                ..
+            }) => {
+                let stderr = if stderr.is_empty() { reported } else { stderr };
+                Ok((exit_code.unwrap_or(-1), stdout, stderr, false))
+            }
+            Err(AutoInstallError::TimeoutError(message)) => {
+                warn!("{}", message);
+                Ok((-1, stdout, message, true))
+            }
+            Err(e) => Err(e),
+        }
+    }
+
+    /// Check on the live system what the output only suggested
+    async fn confirm(&self, ssh: &mut SshClient, suggested: NetworkFailure) -> NetworkFailure {
+        match ssh.execute_with_output(&self.diagnose_command()).await {
+            Ok(out) => match out.trim() {
+                "routing" => NetworkFailure::Routing,
+                "dns" => NetworkFailure::Dns,
+                "ok" => NetworkFailure::Mirror,
+                _ => suggested,
+            },
+            Err(_) => suggested,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_classify_output() {
+        assert_eq!(
+            classify("E: Failed to fetch http://archive.ubuntu.com/...  Temporary failure resolving 'archive.ubuntu.com'"),
+            Some(NetworkFailure::Dns)
+        );
+        assert_eq!(
+            classify("W: Failure trying to run: wget ... connect: Network is unreachable"),
+            Some(NetworkFailure::Routing)
+        );
+        assert_eq!(
+            classify("E: Failed to fetch http://mirror/ubuntu/pool/main/a/apt.deb  404  Not Found [IP: 10.0.0.1 80]"),
+            Some(NetworkFailure::Mirror)
+        );
+        assert_eq!(
+            classify("E: Failed getting release file http://mirror/ubuntu/dists/noble/Release"),
+            Some(NetworkFailure::Mirror)
+        );
+        assert_eq!(classify("E: Unable to locate package zfsutils"), None);
+    }
+
+    #[test]
+    fn test_rotation_and_repairs() {
+        let config = NetworkRecoveryConfig {
+            alternate_mirrors: vec![
+                "http://mirror.example/ubuntu/".to_string(),
+                "http://archive.ubuntu.com/ubuntu/".to_string(),
+            ],
+            ..Default::default()
+        };
+        let mut strategy =
+            NetworkRecoveryStrategy::new(&config, "http://archive.ubuntu.com/ubuntu/", false)
+                .with_fallback(OLD_RELEASES_MIRROR)
+                .for_target("/mnt/targetos/");
+        assert!(strategy
+            .diagnose_command()
+            .contains("getent hosts archive.ubuntu.com >/dev/null"));
+
+        let switch = strategy.rotate().unwrap();
+        assert_eq!(strategy.mirror(), "http://mirror.example/ubuntu/");
+        assert_eq!(
+            switch[0],
+            "sed -i 's|http://archive.ubuntu.com/ubuntu/|http://mirror.example/ubuntu/|g' /mnt/targetos/etc/apt/sources.list.d/*.sources /mnt/targetos/etc/apt/sources.list 2>/dev/null; true"
+        );
+        // The configured mirror is not repeated and old-releases is only a default
+        assert!(strategy.rotate().is_none());
+
+        let dns = strategy.dns_commands();
+        assert_eq!(
+            dns[0],
+            "rm -f /etc/resolv.conf && printf 'nameserver 1.1.1.1\\nnameserver 9.9.9.9\\n' > /etc/resolv.conf"
+        );
+        assert!(dns[1].contains("> /mnt/targetos/etc/resolv.conf"));
+
+        let mut debootstrap = NetworkRecoveryStrategy::new(
+            &NetworkRecoveryConfig::default(),
+            "http://mirror.example:8080/ubuntu/",
+            false,
+        )
+        .with_fallback(OLD_RELEASES_MIRROR);
+        assert!(debootstrap
+            .diagnose_command()
+            .contains("getent hosts mirror.example "));
+        assert_eq!(debootstrap.rotate(), Some(Vec::new()));
+        assert_eq!(debootstrap.mirror(), OLD_RELEASES_MIRROR);
+
+        for config in [config, NetworkRecoveryConfig::default()] {
+            let mut pinned = NetworkRecoveryStrategy::new(&config, "http://snapshot/", true)
+                .with_fallback(OLD_RELEASES_MIRROR);
+            assert!(pinned.rotate().is_none());
+        }
+    }
+}
@@ -0,0 +1,345 @@
+// file: src/network/ssh_installer/package_txn.rs
+// version: 1.1.0
+// guid: 4e8a2d17-b3c6-4f59-9a01-6d7e5c2b8f34
+
+//! Transactional package step (`ssh-install --transactional-packages`)
+//!
+//! Before the chroot package step the root and boot datasets are snapshotted and the dpkg
+//! state is recorded. If the step fails, the datasets are rolled back to the snapshot so the
+//! step can be retried on a clean tree. When the rollback itself fails, the difference between
+//! the recorded and current dpkg state is turned into the apt commands that undo it, which are
+//! logged and written into the target.
+
+use crate::config::zfs_pools::PoolLayout;
+use crate::error::AutoInstallError;
+use crate::network::SshClient;
+use crate::Result;
+use std::collections::BTreeMap;
+use tracing::{info, warn};
+
+/// Snapshot taken before the package step
+pub const SNAPSHOT_NAME: &str = "autoinstall-pre-packages";
+
+/// Datasets holding everything the package step writes: the root and boot containers
+pub fn transaction_datasets(layout: &PoolLayout) -> [String; 2] {
+    [layout.root_container(), layout.boot_container()]
+}
+
+/// Where the revert commands are written when the rollback fails
+pub const REVERT_SCRIPT_PATH: &str = "/mnt/targetos/root/package-revert.sh";
+
+/// Installed packages in the target, by package name
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct DpkgState {
+    pub packages: BTreeMap<String, String>,
+}
+
+impl DpkgState {
+    /// Parse [`build_dpkg_query_command`] output, keeping fully installed packages only
+    pub fn parse(output: &str) -> Self {
+        let packages = output
+            .lines()
+            .filter_map(|line| {
+                let mut fields = line.split('\t');
+                let status = fields.next()?;
+                let name = fields.next()?;
+                let version = fields.next()?;
+                status
+                    .starts_with("ii")
+                    .then(|| (name.to_string(), version.trim().to_string()))
+            })
+            .collect();
+        Self { packages }
+    }
+}
+
+/// dpkg-query listing status, package and version of everything in the target
+pub fn build_dpkg_query_command(root: &str) -> String {
+    format!(
+        "chroot {} dpkg-query -W -f='${{db:Status-Abbrev}}\\t${{binary:Package}}\\t${{Version}}\\n'",
+        root
+    )
+}
+
+/// Recursive snapshot of every transaction dataset
+pub fn build_snapshot_commands(layout: &PoolLayout, name: &str) -> Vec<String> {
+    transaction_datasets(layout)
+        .iter()
+        .map(|dataset| format!("zfs snapshot -r {}@{}", dataset, name))
+        .collect()
+}
+
+/// Roll every dataset below the transaction datasets back to snapshot `name`
+pub fn build_rollback_commands(layout: &PoolLayout, name: &str) -> Vec<String> {
+    transaction_datasets(layout)
+        .iter()
+        .map(|dataset| {
+            format!(
+                "zfs list -H -o name -t snapshot -r {} | grep '@{}$' | xargs -r -n1 zfs rollback -r",
+                dataset, name
+            )
+        })
+        .collect()
+}
+
+/// Remove snapshot `name` once the step it guarded has succeeded
+pub fn build_destroy_commands(layout: &PoolLayout, name: &str) -> Vec<String> {
+    transaction_datasets(layout)
+        .iter()
+        .map(|dataset| format!("zfs destroy -r {}@{}", dataset, name))
+        .collect()
+}
+
+/// apt commands turning the `after` package set back into `before`
+///
+/// New packages are purged; upgraded, downgraded and removed ones are reinstalled at their
+/// recorded version.
+pub fn build_revert_commands(before: &DpkgState, after: &DpkgState) -> Vec<String> {
+    let added: Vec<&str> = after
+        .packages
+        .keys()
+        .filter(|name| !before.packages.contains_key(*name))
+        .map(String::as_str)
+        .collect();
+    let restored: Vec<String> = before
+        .packages
+        .iter()
+        .filter(|(name, version)| after.packages.get(*name) != Some(version))
+        .map(|(name, version)| format!("{}={}", name, version))
+        .collect();
+
+    let mut commands = Vec::new();
+    if !added.is_empty() {
+        commands.push(format!(
+            "DEBIAN_FRONTEND=noninteractive apt-get purge -y {}",
+            added.join(" ")
+        ));
+    }
+    if !restored.is_empty() {
+        commands.push(format!(
+            "DEBIAN_FRONTEND=noninteractive apt-get install -y --allow-downgrades {}",
+            restored.join(" ")
+        ));
+    }
+    commands
+}
+
+/// Shell script running `commands` inside the installed system
+pub fn render_revert_script(commands: &[String]) -> String {
+    let mut script = String::from(
+        "#!/bin/sh\n# Reverts the failed package step of the autoinstall.\n\
+         # Run inside the installed system (chroot /mnt/targetos, or after boot).\nset -e\n",
+    );
+    for command in commands {
+        script.push_str(command);
+        script.push('\n');
+    }
+    script
+}
+
+/// How a failed transaction was undone
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum TransactionOutcome {
+    /// The datasets are back at the pre-package snapshot
+    RolledBack,
+    /// Rollback failed; these apt commands undo the package changes
+    RevertPlan(Vec<String>),
+}
+
+impl TransactionOutcome {
+    pub fn describe(&self) -> String {
+        match self {
+            TransactionOutcome::RolledBack => format!(
+                "datasets rolled back to @{}; the package step can be retried",
+                SNAPSHOT_NAME
+            ),
+            TransactionOutcome::RevertPlan(commands) if commands.is_empty() => {
+                "rollback failed; dpkg state is unchanged".to_string()
+            }
+            TransactionOutcome::RevertPlan(commands) => format!(
+                "rollback failed; revert with these apt commands (also in {}):\n  {}",
+                REVERT_SCRIPT_PATH,
+                commands.join("\n  ")
+            ),
+        }
+    }
+}
+
+/// A package step guarded by a snapshot and the recorded dpkg state
+pub struct PackageTransaction {
+    before: DpkgState,
+    layout: PoolLayout,
+}
+
+impl PackageTransaction {
+    /// Snapshot the datasets and record dpkg state in the target at `/mnt/targetos`
+    pub async fn begin(ssh: &mut SshClient, layout: &PoolLayout) -> Result<Self> {
+        info!("Starting package transaction (snapshot @{})", SNAPSHOT_NAME);
+        // A snapshot left by an interrupted earlier attempt would make the new one fail
+        for command in build_destroy_commands(layout, SNAPSHOT_NAME) {
+            let _ = ssh
+                .execute(&format!("{} 2>/dev/null || true", command))
+                .await;
+        }
+        for command in build_snapshot_commands(layout, SNAPSHOT_NAME) {
+            ssh.execute(&command).await?;
+        }
+        let before = DpkgState::parse(
+            &ssh.execute_with_output(&build_dpkg_query_command("/mnt/targetos"))
+                .await?,
+        );
+        info!(
+            "Recorded {} installed packages before the package step",
+            before.packages.len()
+        );
+        Ok(Self {
+            before,
+            layout: layout.clone(),
+        })
+    }
+
+    /// The step succeeded: drop the snapshot
+    pub async fn commit(self, ssh: &mut SshClient) -> Result<()> {
+        for command in build_destroy_commands(&self.layout, SNAPSHOT_NAME) {
+            ssh.execute(&command).await?;
+        }
+        info!("Package transaction committed");
+        Ok(())
+    }
+
+    /// The step failed: roll back, or write the apt commands that undo it
+    pub async fn abort(self, ssh: &mut SshClient) -> TransactionOutcome {
+        // Computed first: after a successful rollback the current state is gone
+        let after = ssh
+            .execute_with_output(&build_dpkg_query_command("/mnt/targetos"))
+            .await
+            .map(|out| DpkgState::parse(&out));
+
+        let mut rolled_back = true;
+        for command in build_rollback_commands(&self.layout, SNAPSHOT_NAME) {
+            if let Err(e) = ssh.execute(&command).await {
+                warn!("Rollback to @{} failed: {}", SNAPSHOT_NAME, e);
+                rolled_back = false;
+                break;
+            }
+        }
+        if rolled_back {
+            return TransactionOutcome::RolledBack;
+        }
+
+        let commands = match after {
+            Ok(after) => build_revert_commands(&self.before, &after),
+            Err(e) => {
+                warn!("Could not read dpkg state after the failed step: {}", e);
+                return TransactionOutcome::RevertPlan(Vec::new());
+            }
+        };
+        if !commands.is_empty() {
+            let script = render_revert_script(&commands);
+            let write = format!(
+                "cat > {0} << 'EOF'\n{1}EOF\nchmod 700 {0}",
+                REVERT_SCRIPT_PATH, script
+            );
+            if let Err(e) = ssh.execute(&write).await {
+                warn!("Could not write {}: {}", REVERT_SCRIPT_PATH, e);
+            }
+        }
+        TransactionOutcome::RevertPlan(commands)
+    }
+}
+
+/// Error for a failed package step, carrying how it was undone
+pub fn transaction_error(
+    step_error: AutoInstallError,
+    outcome: &TransactionOutcome,
+) -> AutoInstallError {
+    AutoInstallError::InstallationError(format!(
+        "Package step failed: {}; {}",
+        step_error,
+        outcome.describe()
+    ))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn state(entries: &[(&str, &str)]) -> DpkgState {
+        DpkgState {
+            packages: entries
+                .iter()
+                .map(|(n, v)| (n.to_string(), v.to_string()))
+                .collect(),
+        }
+    }
+
+    #[test]
+    fn test_parse_dpkg_state() {
+        let parsed = DpkgState::parse(
+            "ii \tbash\t5.2.21-2ubuntu4\nrc \tos-prober\t1.81ubuntu4\nii \tlibc6:amd64\t2.39-0ubuntu8\n\n",
+        );
+        assert_eq!(
+            parsed,
+            state(&[
+                ("bash", "5.2.21-2ubuntu4"),
+                ("libc6:amd64", "2.39-0ubuntu8")
+            ])
+        );
+        assert!(build_dpkg_query_command("/mnt/targetos")
+            .contains("-f='${db:Status-Abbrev}\\t${binary:Package}\\t${Version}\\n'"));
+    }
+
+    #[test]
+    fn test_revert_commands() {
+        let before = state(&[("bash", "5.2"), ("curl", "8.5"), ("os-prober", "1.81")]);
+        let after = state(&[
+            ("bash", "5.2"),
+            ("curl", "8.6"),
+            ("htop", "3.3"),
+            ("vim", "9.1"),
+        ]);
+        assert_eq!(
+            build_revert_commands(&before, &after),
+            vec![
+                "DEBIAN_FRONTEND=noninteractive apt-get purge -y htop vim".to_string(),
+                "DEBIAN_FRONTEND=noninteractive apt-get install -y --allow-downgrades curl=8.5 os-prober=1.81"
+                    .to_string(),
+            ]
+        );
+        assert!(build_revert_commands(&before, &before).is_empty());
+
+        let script = render_revert_script(&build_revert_commands(&before, &after));
+        assert!(script.starts_with("#!/bin/sh\n"));
+        assert!(script.ends_with("curl=8.5 os-prober=1.81\n"));
+    }
+
+    #[test]
+    fn test_snapshot_commands_cover_root_and_boot() {
+        let layout = PoolLayout::default();
+        assert_eq!(
+            build_snapshot_commands(&layout, "s"),
+            vec![
+                "zfs snapshot -r rpool/ROOT@s",
+                "zfs snapshot -r bpool/BOOT@s"
+            ]
+        );
+        let rollback = build_rollback_commands(&layout, "s");
+        assert!(rollback[0].contains("-r rpool/ROOT | grep '@s$' | xargs -r -n1 zfs rollback -r"));
+        assert_eq!(
+            build_destroy_commands(&layout, "s")[1],
+            "zfs destroy -r bpool/BOOT@s"
+        );
+        let mut custom = layout.clone();
+        custom.root.name = "tank".to_string();
+        assert_eq!(
+            build_snapshot_commands(&custom, "s")[0],
+            "zfs snapshot -r tank/ROOT@s"
+        );
+
+        let outcome = TransactionOutcome::RevertPlan(vec!["apt-get purge -y htop".into()]);
+        assert!(outcome.describe().contains(REVERT_SCRIPT_PATH));
+        assert!(TransactionOutcome::RolledBack
+            .describe()
+            .contains("can be retried"));
+    }
+}
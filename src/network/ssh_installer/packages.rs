@@ -1,32 +1,54 @@
 // file: src/network/ssh_installer/packages.rs
-// version: 1.0.1
+// version: 1.2.0
 // guid: sshpkg01-2345-6789-abcd-ef0123456789
 
 //! Package management for SSH installation
 
+use super::apt_lock::{build_live_preparation_commands, wait_for_apt_locks};
+use crate::config::AptLockConfig;
 use crate::network::SshClient;
 use crate::Result;
 use tracing::info;
 
 pub struct PackageManager<'a> {
     ssh: &'a mut SshClient,
+    apt_lock: AptLockConfig,
 }
 
 impl<'a> PackageManager<'a> {
     pub fn new(ssh: &'a mut SshClient) -> Self {
-        Self { ssh }
+        Self {
+            ssh,
+            apt_lock: AptLockConfig::default(),
+        }
+    }
+
+    /// How long to wait for apt/dpkg locks held by the live environment
+    pub fn with_apt_lock(mut self, apt_lock: AptLockConfig) -> Self {
+        self.apt_lock = apt_lock;
+        self
+    }
+
+    /// Run an apt command once the apt/dpkg locks are free
+    async fn run_apt(&mut self, command: &str) -> Result<()> {
+        wait_for_apt_locks(self.ssh, &self.apt_lock, "").await?;
+        self.ssh.execute(command).await
     }
 
     /// Install required packages for installation
     pub async fn install_required_packages(&mut self) -> Result<()> {
         info!("Installing required packages");
 
+        // Keep apt's daily jobs from taking the locks again between our commands
+        for command in build_live_preparation_commands(&self.apt_lock) {
+            self.ssh.execute(&command).await?;
+        }
+
         // Update package lists first
-        self.ssh.execute("apt-get update").await?;
+        self.run_apt("apt-get update").await?;
 
         // Install ZFS utilities specifically
-        self.ssh
-            .execute("DEBIAN_FRONTEND=noninteractive apt-get install -y zfsutils-linux")
+        self.run_apt("DEBIAN_FRONTEND=noninteractive apt-get install -y zfsutils-linux")
             .await?;
 
         // Install other required packages
@@ -44,27 +66,9 @@ impl<'a> PackageManager<'a> {
             "DEBIAN_FRONTEND=noninteractive apt-get install -y {}",
             packages.join(" ")
         );
-        self.ssh.execute(&install_cmd).await?;
+        self.run_apt(&install_cmd).await?;
 
         info!("Required packages installed successfully");
         Ok(())
     }
-
-    /// Check if specific tools are available
-    pub async fn check_tool_availability(&mut self, tools: &[&str]) -> Result<Vec<String>> {
-        let mut available = Vec::new();
-
-        for tool in tools {
-            match self
-                .ssh
-                .execute(&format!("command -v {} >/dev/null 2>&1", tool))
-                .await
-            {
-                Ok(_) => available.push(tool.to_string()),
-                Err(_) => continue,
-            }
-        }
-
-        Ok(available)
-    }
 }
@@ -0,0 +1,376 @@
+// file: src/network/ssh_installer/plan.rs
+// version: 1.9.0
+// guid: 7b3e9c52-4a18-4d6f-8e21-c5f0a9d3b764
+
+//! Install plans and how they changed since the last successful install
+//!
+//! A plan lists the settings an install applies and the configuration commands it runs,
+//! grouped by section, without secrets. It is recorded in the session of every install so a
+//! reinstall can show, before anything is wiped, what differs from the plan that last worked.
+
+use super::config::InstallationConfig;
+use super::session::{InstallSession, SessionStatus};
+use crate::config::partitioning::PartitioningMode;
+use serde::{Deserialize, Serialize};
+use std::path::Path;
+
+/// Root the target system is assembled under
+const TARGET_ROOT: &str = "/mnt/targetos";
+
+/// One setting or command of a plan
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct PlanStep {
+    /// Part of the install the step belongs to, e.g. `network` or `kernel`
+    pub section: String,
+    pub line: String,
+}
+
+/// Everything an install is going to apply, in order
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct InstallPlan {
+    pub steps: Vec<PlanStep>,
+}
+
+/// How one step differs between two plans
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum PlanChange {
+    Unchanged(PlanStep),
+    Added(PlanStep),
+    Removed(PlanStep),
+    /// A step replaced by another in the same section
+    Changed {
+        from: PlanStep,
+        to: PlanStep,
+    },
+}
+
+impl InstallPlan {
+    /// Plan for `config`; the LUKS key and root password never appear in it
+    pub fn from_config(config: &InstallationConfig) -> Self {
+        let mut plan = Self::default();
+        let settings = [
+            ("system", "hostname", config.hostname.clone()),
+            ("system", "timezone", config.timezone.clone()),
+            (
+                "system",
+                "architecture",
+                config.architecture.as_str().to_string(),
+            ),
+            ("storage", "disk", config.disk_device.clone()),
+            (
+                "storage",
+                "esp mirrors",
+                config.esp_mirror_devices.join(", "),
+            ),
+            (
+                "storage",
+                "partitioning",
+                if config.partitioning.is_auto() {
+                    String::new()
+                } else {
+                    config.partitioning.mode.as_str().to_string()
+                },
+            ),
+            ("network", "interface", config.network_interface.clone()),
+            ("network", "address", config.network_address.clone()),
+            ("network", "gateway", config.network_gateway.clone()),
+            ("network", "search", config.network_search.clone()),
+            (
+                "network",
+                "nameservers",
+                config.network_nameservers.join(", "),
+            ),
+            ("packages", "release", config.release().to_string()),
+            ("packages", "mirror", config.effective_mirror()),
+            (
+                "packages",
+                "apt snapshot",
+                config
+                    .apt_snapshot
+                    .map(|s| s.to_string())
+                    .unwrap_or_default(),
+            ),
+        ];
+        for (section, name, value) in settings {
+            if !value.is_empty() {
+                plan.push(section, format!("{}: {}", name, value));
+            }
+        }
+        if config.partitioning.mode == PartitioningMode::Commands {
+            for command in &config.partitioning.commands {
+                plan.push("storage", command.clone());
+            }
+        }
+
+        for (section, commands) in plan_commands(config, TARGET_ROOT) {
+            for command in commands {
+                plan.push(section, command);
+            }
+        }
+        // ARC sizes depend on the target's RAM, so the plan carries the policy, not the values
+        if config.zfs_tuning != Default::default() {
+            plan.push("zfs", format!("tuning: {:?}", config.zfs_tuning));
+        }
+        if config.ssh_ca.enabled {
+            plan.push(
+                "ssh ca",
+                format!(
+                    "host principals: {}",
+                    config
+                        .ssh_ca
+                        .effective_host_principals(&config.hostname)
+                        .join(", ")
+                ),
+            );
+            for user in &config.ssh_ca.users {
+                plan.push(
+                    "ssh ca",
+                    format!(
+                        "user certificate: {} ({})",
+                        user.name,
+                        user.principals.join(", ")
+                    ),
+                );
+            }
+        }
+        for script in &config.late_commands.scripts {
+            plan.push(
+                "late commands",
+                format!(
+                    "{}: {}",
+                    script.name,
+                    script.url.as_deref().unwrap_or("inline")
+                ),
+            );
+        }
+        plan
+    }
+
+    fn push(&mut self, section: &str, line: String) {
+        self.steps.push(PlanStep {
+            section: section.to_string(),
+            line,
+        });
+    }
+
+    /// Plan recorded by the last install of `hostname`, if that install completed
+    pub fn last_successful(base_dir: &Path, hostname: &str) -> Option<Self> {
+        let session = InstallSession::load(base_dir, hostname).ok()?;
+        if session.status != SessionStatus::Completed {
+            return None;
+        }
+        session.plan
+    }
+
+    /// Changes from `previous` to this plan, in this plan's order
+    ///
+    /// Steps are matched by their longest common subsequence; a run of removed steps followed
+    /// by added steps in the same section is reported as changed steps.
+    pub fn diff(&self, previous: &InstallPlan) -> Vec<PlanChange> {
+        let (old, new) = (&previous.steps, &self.steps);
+        let mut lcs = vec![vec![0usize; new.len() + 1]; old.len() + 1];
+        for i in (0..old.len()).rev() {
+            for j in (0..new.len()).rev() {
+                lcs[i][j] = if old[i] == new[j] {
+                    lcs[i + 1][j + 1] + 1
+                } else {
+                    lcs[i + 1][j].max(lcs[i][j + 1])
+                };
+            }
+        }
+
+        let mut changes = Vec::new();
+        let (mut removed, mut added) = (Vec::new(), Vec::new());
+        let (mut i, mut j) = (0, 0);
+        while i < old.len() || j < new.len() {
+            if i < old.len() && j < new.len() && old[i] == new[j] {
+                pair_up(&mut changes, &mut removed, &mut added);
+                changes.push(PlanChange::Unchanged(new[j].clone()));
+                i += 1;
+                j += 1;
+            } else if j < new.len() && (i == old.len() || lcs[i][j + 1] >= lcs[i + 1][j]) {
+                added.push(new[j].clone());
+                j += 1;
+            } else {
+                removed.push(old[i].clone());
+                i += 1;
+            }
+        }
+        pair_up(&mut changes, &mut removed, &mut added);
+        changes
+    }
+}
+
+/// Configuration commands an install of `config` runs against `root`, by plan section
+pub fn plan_commands(config: &InstallationConfig, root: &str) -> Vec<(&'static str, Vec<String>)> {
+    vec![
+        ("apt-repos", config.apt_repos.build_apply_commands(root)),
+        ("kernel", config.kernel.build_apply_commands(root)),
+        ("hardening", config.hardening.build_apply_commands(root)),
+        ("firewall", config.firewall.build_apply_commands(root)),
+        ("updates", config.updates.build_apply_commands(root)),
+        ("headless", config.headless.build_apply_commands(root)),
+        ("performance", config.performance.build_apply_commands(root)),
+        ("user-data", config.user_data.build_apply_commands(root)),
+        ("nbde", config.nbde.build_install_commands(root)),
+    ]
+}
+
+/// Flush pending removals and additions, pairing those of the same section as changes
+fn pair_up(changes: &mut Vec<PlanChange>, removed: &mut Vec<PlanStep>, added: &mut Vec<PlanStep>) {
+    let mut added = std::mem::take(added).into_iter().peekable();
+    for from in std::mem::take(removed) {
+        match added.next_if(|to| to.section == from.section) {
+            Some(to) => changes.push(PlanChange::Changed { from, to }),
+            None => changes.push(PlanChange::Removed(from)),
+        }
+    }
+    changes.extend(added.map(PlanChange::Added));
+}
+
+/// Whether any step differs
+pub fn has_changes(changes: &[PlanChange]) -> bool {
+    changes
+        .iter()
+        .any(|change| !matches!(change, PlanChange::Unchanged(_)))
+}
+
+/// Install phase that applies the steps of a plan section
+pub fn phase_for_section(section: &str) -> &'static str {
+    match section {
+        "storage" => "Phase 2: Disk preparation",
+        "system" | "network" | "packages" => "Phase 4: Base system",
+        "late commands" => "Phase 6: Final setup",
+        _ => "Phase 5: System configuration",
+    }
+}
+
+/// Phases whose steps differ, in install order
+pub fn phases_to_rerun(changes: &[PlanChange]) -> Vec<&'static str> {
+    let mut phases: Vec<&'static str> = changes
+        .iter()
+        .flat_map(|change| match change {
+            PlanChange::Unchanged(_) => vec![],
+            PlanChange::Added(step) | PlanChange::Removed(step) => vec![&step.section],
+            PlanChange::Changed { from, to } => vec![&from.section, &to.section],
+        })
+        .map(|section| phase_for_section(section))
+        .collect();
+    phases.sort_unstable();
+    phases.dedup();
+    phases
+}
+
+/// Unified-diff style listing of the changed steps, with ANSI colours when `color` is set
+///
+/// Unchanged steps are left out; multi-line commands keep their marker on every line.
+pub fn render_diff(changes: &[PlanChange], color: bool) -> Vec<String> {
+    let paint = |code: &str, marker: char, step: &PlanStep| -> Vec<String> {
+        step.line
+            .lines()
+            .map(|line| {
+                let text = format!("{} [{}] {}", marker, step.section, line);
+                if color {
+                    format!("\x1b[{}m{}\x1b[0m", code, text)
+                } else {
+                    text
+                }
+            })
+            .collect()
+    };
+    let mut lines = Vec::new();
+    for change in changes {
+        match change {
+            PlanChange::Unchanged(_) => {}
+            PlanChange::Added(step) => lines.extend(paint("32", '+', step)),
+            PlanChange::Removed(step) => lines.extend(paint("31", '-', step)),
+            PlanChange::Changed { from, to } => {
+                lines.extend(paint("33", '-', from));
+                lines.extend(paint("33", '+', to));
+            }
+        }
+    }
+    lines
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn step(section: &str, line: &str) -> PlanStep {
+        PlanStep {
+            section: section.to_string(),
+            line: line.to_string(),
+        }
+    }
+
+    fn plan(steps: &[(&str, &str)]) -> InstallPlan {
+        InstallPlan {
+            steps: steps.iter().map(|(s, l)| step(s, l)).collect(),
+        }
+    }
+
+    #[test]
+    fn test_plan_from_config_has_no_secrets() {
+        let mut config = InstallationConfig::for_len_serv_003();
+        config.luks_key = "luks-secret".to_string();
+        config.root_password = "root-secret".to_string();
+        let plan = InstallPlan::from_config(&config);
+        assert!(plan
+            .steps
+            .contains(&step("system", &format!("hostname: {}", config.hostname))));
+        let text = serde_json::to_string(&plan).unwrap();
+        assert!(!text.contains("luks-secret"));
+        assert!(!text.contains("root-secret"));
+    }
+
+    #[test]
+    fn test_diff_added_removed_changed() {
+        let previous = plan(&[
+            ("system", "hostname: web-01"),
+            ("network", "address: 10.0.0.5/24"),
+            ("kernel", "sysctl vm.swappiness=10"),
+            ("firewall", "ufw allow 22"),
+        ]);
+        let next = plan(&[
+            ("system", "hostname: web-01"),
+            ("network", "address: 10.0.0.6/24"),
+            ("firewall", "ufw allow 22"),
+            ("firewall", "ufw allow 443"),
+        ]);
+        let changes = next.diff(&previous);
+        assert_eq!(
+            changes,
+            vec![
+                PlanChange::Unchanged(step("system", "hostname: web-01")),
+                PlanChange::Changed {
+                    from: step("network", "address: 10.0.0.5/24"),
+                    to: step("network", "address: 10.0.0.6/24"),
+                },
+                PlanChange::Removed(step("kernel", "sysctl vm.swappiness=10")),
+                PlanChange::Unchanged(step("firewall", "ufw allow 22")),
+                PlanChange::Added(step("firewall", "ufw allow 443")),
+            ]
+        );
+        assert!(has_changes(&changes));
+        assert!(!has_changes(&next.diff(&next)));
+        assert_eq!(
+            phases_to_rerun(&changes),
+            vec!["Phase 4: Base system", "Phase 5: System configuration"]
+        );
+        assert!(phases_to_rerun(&next.diff(&next)).is_empty());
+
+        let lines = render_diff(&changes, false);
+        assert_eq!(
+            lines,
+            vec![
+                "- [network] address: 10.0.0.5/24",
+                "+ [network] address: 10.0.0.6/24",
+                "- [kernel] sysctl vm.swappiness=10",
+                "+ [firewall] ufw allow 443",
+            ]
+        );
+        let colored = render_diff(&changes, true);
+        assert_eq!(colored[3], "\x1b[32m+ [firewall] ufw allow 443\x1b[0m");
+    }
+}
@@ -0,0 +1,442 @@
+// file: src/network/ssh_installer/presets.rs
+// version: 1.28.0
+// guid: 4b8d1f62-9a3e-4c57-8e20-d6f3a9b1c745
+
+//! Named installation presets
+//!
+//! A preset holds everything about a machine's install except its secrets and lives in
+//! `presets/<name>.yaml` under the working directory. `${VAR}` references are expanded from
+//! the environment, which is how the LUKS key and root password are usually supplied; when
+//! they are missing the installer prompts for them. A few presets are built in so existing
+//! invocations keep working; a file with the same name overrides the built-in one.
+
+use super::config::InstallationConfig;
+use crate::config::interpolate::FactVars;
+use crate::config::loader::ConfigLoader;
+use crate::config::{
+    AptLockConfig, AptMirrorsConfig, AptReposConfig, AptSnapshot, Architecture, BootloaderConfig,
+    BudgetConfig, ConfirmationConfig, DiskHealthConfig, EntropyConfig, FirewallConfig,
+    HardeningConfig, HeadlessConfig, HealthGateConfig, HostVarsConfig, KernelConfig,
+    LateCommandsConfig, LowMemoryConfig, NbdeConfig, NetworkRecoveryConfig, PartitioningConfig,
+    PerformanceConfig, ReinstallConfig, SshCaConfig, UbuntuProConfig, UpdatesConfig,
+    UserDataConfig, ZfsPoolsConfig, ZfsTuningConfig,
+};
+use crate::error::AutoInstallError;
+use crate::Result;
+use serde::{Deserialize, Serialize};
+use std::path::{Path, PathBuf};
+
+/// Preset used when neither `--preset` nor a preset matching the hostname is found
+pub const DEFAULT_PRESET: &str = "len-serv-003";
+
+/// Presets compiled into the binary
+pub const BUILTIN_PRESETS: &[&str] = &["len-serv-003"];
+
+/// Installation settings for one machine, as stored in a preset file
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct InstallPreset {
+    pub hostname: String,
+    pub disk_device: String,
+    #[serde(default = "default_timezone")]
+    pub timezone: String,
+    pub network_interface: String,
+    /// Address with prefix length, e.g. `192.0.2.10/24`
+    pub network_address: String,
+    pub network_gateway: String,
+    #[serde(default)]
+    pub network_search: String,
+    #[serde(default)]
+    pub network_nameservers: Vec<String>,
+    #[serde(default)]
+    pub debootstrap_release: Option<String>,
+    #[serde(default)]
+    pub debootstrap_mirror: Option<String>,
+    #[serde(default)]
+    pub esp_mirror_devices: Vec<String>,
+    #[serde(default = "default_architecture")]
+    pub architecture: Architecture,
+    #[serde(default)]
+    pub kernel: KernelConfig,
+    #[serde(default)]
+    pub apt_snapshot: Option<AptSnapshot>,
+    #[serde(default)]
+    pub hardening: HardeningConfig,
+    #[serde(default)]
+    pub zfs_tuning: ZfsTuningConfig,
+    #[serde(default)]
+    pub firewall: FirewallConfig,
+    #[serde(default)]
+    pub headless: HeadlessConfig,
+    #[serde(default)]
+    pub ssh_ca: SshCaConfig,
+    #[serde(default)]
+    pub reinstall: ReinstallConfig,
+    #[serde(default)]
+    pub user_data: UserDataConfig,
+    #[serde(default)]
+    pub apt_repos: AptReposConfig,
+    #[serde(default)]
+    pub performance: PerformanceConfig,
+    #[serde(default)]
+    pub zfs_pools: ZfsPoolsConfig,
+    #[serde(default)]
+    pub ubuntu_pro: UbuntuProConfig,
+    #[serde(default)]
+    pub apt_mirrors: AptMirrorsConfig,
+    #[serde(default)]
+    pub entropy: EntropyConfig,
+    #[serde(default)]
+    pub low_memory: LowMemoryConfig,
+    #[serde(default)]
+    pub host_vars: HostVarsConfig,
+    #[serde(default)]
+    pub bootloader: BootloaderConfig,
+    #[serde(default)]
+    pub budget: BudgetConfig,
+    #[serde(default)]
+    pub confirmation: ConfirmationConfig,
+    #[serde(default)]
+    pub partitioning: PartitioningConfig,
+    #[serde(default)]
+    pub health_gate: HealthGateConfig,
+    #[serde(default)]
+    pub apt_lock: AptLockConfig,
+    #[serde(default)]
+    pub disk_health: DiskHealthConfig,
+    #[serde(default)]
+    pub updates: UpdatesConfig,
+    #[serde(default)]
+    pub late_commands: LateCommandsConfig,
+    #[serde(default)]
+    pub network_recovery: NetworkRecoveryConfig,
+    #[serde(default)]
+    pub nbde: NbdeConfig,
+    /// LUKS passphrase; prompted for when absent
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub luks_key: Option<String>,
+    /// Root password; prompted for when absent
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub root_password: Option<String>,
+}
+
+fn default_timezone() -> String {
+    "UTC".to_string()
+}
+
+fn default_architecture() -> Architecture {
+    Architecture::Amd64
+}
+
+impl InstallPreset {
+    /// Built-in preset by name
+    pub fn builtin(name: &str) -> Option<Self> {
+        match name {
+            "len-serv-003" => Some(Self {
+                hostname: "len-serv-003".to_string(),
+                disk_device: "/dev/nvme0n1".to_string(),
+                timezone: "America/New_York".to_string(),
+                network_interface: "eno1".to_string(),
+                network_address: "172.16.3.96/23".to_string(),
+                network_gateway: "172.16.2.1".to_string(),
+                network_search: "local.jdfalk.com".to_string(),
+                network_nameservers: vec!["172.16.2.1".to_string(), "8.8.8.8".to_string()],
+                debootstrap_release: Some("plucky".to_string()),
+                debootstrap_mirror: Some("http://archive.ubuntu.com/ubuntu/".to_string()),
+                esp_mirror_devices: Vec::new(),
+                architecture: Architecture::Amd64,
+                kernel: KernelConfig::default(),
+                apt_snapshot: None,
+                hardening: HardeningConfig::default(),
+                zfs_tuning: ZfsTuningConfig::default(),
+                firewall: FirewallConfig::default(),
+                headless: HeadlessConfig::default(),
+                ssh_ca: SshCaConfig::default(),
+                reinstall: ReinstallConfig::default(),
+                user_data: UserDataConfig::default(),
+                apt_repos: AptReposConfig::default(),
+                performance: PerformanceConfig::default(),
+                zfs_pools: ZfsPoolsConfig::default(),
+                ubuntu_pro: UbuntuProConfig::default(),
+                apt_mirrors: AptMirrorsConfig::default(),
+                entropy: EntropyConfig::default(),
+                low_memory: LowMemoryConfig::default(),
+                host_vars: HostVarsConfig::default(),
+                bootloader: BootloaderConfig::default(),
+                budget: BudgetConfig::default(),
+                confirmation: ConfirmationConfig::default(),
+                partitioning: PartitioningConfig::default(),
+                health_gate: HealthGateConfig::default(),
+                apt_lock: AptLockConfig::default(),
+                disk_health: DiskHealthConfig::default(),
+                updates: UpdatesConfig::default(),
+                late_commands: LateCommandsConfig::default(),
+                network_recovery: NetworkRecoveryConfig::default(),
+                nbde: NbdeConfig::default(),
+                luks_key: Some("changeme123!@#".to_string()),
+                root_password: Some("changeme123!@#".to_string()),
+            }),
+            _ => None,
+        }
+    }
+
+    /// Installation config for this preset; missing secrets are left empty
+    ///
+    /// The secrets it carries are registered for redaction.
+    pub fn into_config(self) -> InstallationConfig {
+        for secret in [&self.luks_key, &self.root_password].into_iter().flatten() {
+            crate::logging::redact::register(secret);
+        }
+        InstallationConfig {
+            hostname: self.hostname,
+            disk_device: self.disk_device,
+            timezone: self.timezone,
+            luks_key: self.luks_key.unwrap_or_default(),
+            root_password: self.root_password.unwrap_or_default(),
+            network_interface: self.network_interface,
+            network_address: self.network_address,
+            network_gateway: self.network_gateway,
+            network_search: self.network_search,
+            network_nameservers: self.network_nameservers,
+            debootstrap_release: self.debootstrap_release,
+            debootstrap_mirror: self.debootstrap_mirror,
+            esp_mirror_devices: self.esp_mirror_devices,
+            architecture: self.architecture,
+            kernel: self.kernel,
+            apt_snapshot: self.apt_snapshot,
+            hardening: self.hardening,
+            zfs_tuning: self.zfs_tuning,
+            firewall: self.firewall,
+            headless: self.headless,
+            ssh_ca: self.ssh_ca,
+            reinstall: self.reinstall,
+            user_data: self.user_data,
+            apt_repos: self.apt_repos,
+            performance: self.performance,
+            zfs_pools: self.zfs_pools,
+            ubuntu_pro: self.ubuntu_pro,
+            apt_mirrors: self.apt_mirrors,
+            entropy: self.entropy,
+            low_memory: self.low_memory,
+            host_vars: self.host_vars,
+            bootloader: self.bootloader,
+            budget: self.budget,
+            confirmation: self.confirmation,
+            partitioning: self.partitioning,
+            health_gate: self.health_gate,
+            apt_lock: self.apt_lock,
+            disk_health: self.disk_health,
+            updates: self.updates,
+            late_commands: self.late_commands,
+            network_recovery: self.network_recovery,
+            nbde: self.nbde,
+        }
+    }
+
+    /// Check the fields an install cannot proceed without
+    pub fn validate(&self) -> Result<()> {
+        let required = [
+            ("hostname", &self.hostname),
+            ("disk_device", &self.disk_device),
+            ("network_interface", &self.network_interface),
+            ("network_address", &self.network_address),
+            ("network_gateway", &self.network_gateway),
+        ];
+        if let Some((field, _)) = required.iter().find(|(_, v)| v.trim().is_empty()) {
+            return Err(AutoInstallError::ValidationError(format!(
+                "Preset for {} is missing {}",
+                self.hostname, field
+            )));
+        }
+        if !self.disk_device.starts_with("/dev/") {
+            return Err(AutoInstallError::ValidationError(format!(
+                "Preset disk_device must be a /dev path: {}",
+                self.disk_device
+            )));
+        }
+        if !self.network_address.contains('/') {
+            return Err(AutoInstallError::ValidationError(format!(
+                "Preset network_address needs a prefix length: {}",
+                self.network_address
+            )));
+        }
+        self.kernel.validate()?;
+        self.zfs_tuning.validate()
+    }
+}
+
+/// Directory of `<name>.yaml` preset files, backed by the built-in presets
+#[derive(Debug, Clone)]
+pub struct PresetStore {
+    dir: PathBuf,
+    facts: Option<FactVars>,
+}
+
+impl PresetStore {
+    /// Store reading presets from `dir`
+    pub fn new(dir: impl Into<PathBuf>) -> Self {
+        Self {
+            dir: dir.into(),
+            facts: None,
+        }
+    }
+
+    /// Resolve `{{ facts.* }}` templates in preset files from the target's facts
+    pub fn with_facts(mut self, facts: FactVars) -> Self {
+        self.facts = Some(facts);
+        self
+    }
+
+    /// Store in `presets/` under `base_dir`
+    pub fn in_base_dir(base_dir: &Path) -> Self {
+        Self::new(base_dir.join("presets"))
+    }
+
+    /// Path of the file for preset `name`
+    pub fn path(&self, name: &str) -> Result<PathBuf> {
+        let valid = !name.is_empty()
+            && !name.starts_with('.')
+            && name
+                .chars()
+                .all(|c| c.is_ascii_alphanumeric() || matches!(c, '-' | '_' | '.'));
+        if !valid {
+            return Err(AutoInstallError::ValidationError(format!(
+                "Invalid preset name: {}",
+                name
+            )));
+        }
+        Ok(self.dir.join(format!("{}.yaml", name)))
+    }
+
+    /// Whether a file or built-in preset called `name` exists
+    pub fn exists(&self, name: &str) -> bool {
+        self.path(name).map(|p| p.is_file()).unwrap_or(false)
+            || InstallPreset::builtin(name).is_some()
+    }
+
+    /// Names of all presets, file-backed and built-in, sorted
+    pub fn list(&self) -> Result<Vec<String>> {
+        let mut names: Vec<String> = BUILTIN_PRESETS.iter().map(|n| n.to_string()).collect();
+        if self.dir.is_dir() {
+            for entry in std::fs::read_dir(&self.dir)? {
+                let path = entry?.path();
+                if path.extension().and_then(|e| e.to_str()) == Some("yaml") {
+                    if let Some(stem) = path.file_stem().and_then(|s| s.to_str()) {
+                        names.push(stem.to_string());
+                    }
+                }
+            }
+        }
+        names.sort();
+        names.dedup();
+        Ok(names)
+    }
+
+    /// Load preset `name`, preferring the file over a built-in of the same name
+    pub fn load(&self, name: &str) -> Result<InstallPreset> {
+        let path = self.path(name)?;
+        let preset = if path.is_file() {
+            let content = std::fs::read_to_string(&path)?;
+            let loader = match &self.facts {
+                Some(facts) => ConfigLoader::new().with_facts(facts.clone()),
+                None => ConfigLoader::new(),
+            };
+            let expanded = loader.expand_env_vars(&content)?;
+            serde_yaml::from_str(&expanded).map_err(|e| {
+                AutoInstallError::ConfigError(format!("Invalid preset {}: {}", path.display(), e))
+            })?
+        } else {
+            InstallPreset::builtin(name).ok_or_else(|| {
+                AutoInstallError::ConfigError(format!(
+                    "No preset named {} (available: {})",
+                    name,
+                    self.list().unwrap_or_default().join(", ")
+                ))
+            })?
+        };
+        preset.validate()?;
+        Ok(preset)
+    }
+
+    /// Write `preset` to `<dir>/<name>.yaml`
+    pub fn save(&self, name: &str, preset: &InstallPreset) -> Result<PathBuf> {
+        preset.validate()?;
+        let path = self.path(name)?;
+        std::fs::create_dir_all(&self.dir)?;
+        std::fs::write(&path, serde_yaml::to_string(preset)?)?;
+        Ok(path)
+    }
+
+    /// Pick a preset: the explicit name, else one named after `hostname`, else the default
+    pub fn resolve(&self, name: Option<&str>, hostname: Option<&str>) -> Result<InstallPreset> {
+        match (name, hostname) {
+            (Some(name), _) => self.load(name),
+            (None, Some(hostname)) if self.exists(hostname) => self.load(hostname),
+            _ => self.load(DEFAULT_PRESET),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::TempDir;
+
+    #[test]
+    fn test_builtin_matches_legacy_config() {
+        let config = InstallationConfig::for_len_serv_003();
+        assert_eq!(config.hostname, "len-serv-003");
+        assert_eq!(config.disk_device, "/dev/nvme0n1");
+        assert_eq!(config.network_address, "172.16.3.96/23");
+        assert_eq!(config.luks_key, "changeme123!@#");
+        assert!(InstallPreset::builtin("nope").is_none());
+    }
+
+    #[test]
+    fn test_file_overrides_builtin_and_omits_secrets() {
+        let dir = TempDir::new().unwrap();
+        let store = PresetStore::new(dir.path());
+
+        let mut preset = InstallPreset {
+            luks_key: None,
+            root_password: None,
+            ..InstallPreset::builtin("len-serv-003").unwrap()
+        };
+        preset.hostname = "web-01".to_string();
+        preset.disk_device = "/dev/sda".to_string();
+        let path = store.save("web-01", &preset).unwrap();
+        assert!(!std::fs::read_to_string(&path).unwrap().contains("changeme"));
+
+        let mut default = preset.clone();
+        default.timezone = "UTC".to_string();
+        store.save(DEFAULT_PRESET, &default).unwrap();
+
+        assert_eq!(store.list().unwrap(), vec!["len-serv-003", "web-01"]);
+        assert_eq!(store.load("web-01").unwrap().disk_device, "/dev/sda");
+        assert_eq!(store.load(DEFAULT_PRESET).unwrap().timezone, "UTC");
+        assert_eq!(
+            store.resolve(None, Some("web-01")).unwrap().hostname,
+            "web-01"
+        );
+        assert_eq!(
+            store.resolve(None, Some("unknown")).unwrap().timezone,
+            "UTC"
+        );
+        assert!(store.load("missing").is_err());
+        assert!(store.path("../etc/passwd").is_err());
+    }
+
+    #[test]
+    fn test_preset_validation() {
+        let section = "hostname: db-01\ndisk_device: sda\nnetwork_interface: eth0\n\
+                       network_address: 10.0.0.5/24\nnetwork_gateway: 10.0.0.1\n";
+        let preset: InstallPreset = serde_yaml::from_str(section).unwrap();
+        assert_eq!(preset.timezone, "UTC");
+        assert!(preset.validate().is_err());
+
+        let preset: InstallPreset =
+            serde_yaml::from_str(&section.replace("disk_device: sda", "disk_device: /dev/sda"))
+                .unwrap();
+        assert!(preset.validate().is_ok());
+        assert!(preset.into_config().luks_key.is_empty());
+    }
+}
@@ -0,0 +1,289 @@
+// file: src/network/ssh_installer/probes.rs
+// version: 1.1.0
+// guid: 5d1c8e43-2b7a-4f96-8e05-3a9f6c2d7b81
+
+//! Runs the `verification:` service probes on a booted host over SSH
+//!
+//! Every probe becomes one shell command on the host that prints a single `probe-result`
+//! line, so a probe that fails, hangs or is missing a tool is reported the same way as one that
+//! passes instead of aborting the run. TCP checks use bash's `/dev/tcp`, HTTP checks use
+//! `curl`, and every attempt is bounded by `timeout`.
+
+use crate::config::verification::{Probe, ProbeCheck, ProbeRole, VerificationConfig};
+use crate::error::AutoInstallError;
+use crate::network::SshClient;
+use crate::utils::shell_quote;
+use crate::Result;
+use serde::{Deserialize, Serialize};
+use std::time::{Duration, Instant};
+use tracing::{info, warn};
+
+/// Prefix of the line a probe command prints
+const RESULT_MARKER: &str = "probe-result";
+
+/// Outcome of one probe, as kept in the session record and shown in the report
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ProbeResult {
+    pub name: String,
+    pub role: ProbeRole,
+    /// `tcp`, `http`, `systemd` or `command`
+    pub kind: String,
+    pub passed: bool,
+    /// What the last attempt observed, e.g. `status 503` or `exit 1`
+    pub detail: String,
+    pub attempts: u32,
+    pub elapsed_secs: u64,
+}
+
+/// Shell command checking `probe` once; prints `probe-result <observed>`
+pub fn probe_command(probe: &Probe) -> String {
+    let timeout = probe.timeout_secs;
+    let body = match &probe.check {
+        ProbeCheck::Tcp { host, port } => format!(
+            "if timeout {t} bash -c 'exec 3<>/dev/tcp/{h}/{p}' 2>/dev/null; then echo {m} open; else echo {m} closed; fi",
+            t = timeout,
+            h = host,
+            p = port,
+            m = RESULT_MARKER
+        ),
+        ProbeCheck::Http { url, .. } => format!(
+            "body=$(mktemp); code=$(curl -sS -o \"$body\" -w '%{{http_code}}' --max-time {t} {u} 2>/dev/null); \
+             if [ -n \"$code\" ]; then echo {m} \"$code\"; else echo {m} 000; fi; cat \"$body\" 2>/dev/null | head -c 65536; rm -f \"$body\"",
+            t = timeout,
+            u = shell_quote(url),
+            m = RESULT_MARKER
+        ),
+        ProbeCheck::Systemd { unit } => format!(
+            "echo {m} \"$(systemctl is-active {u} 2>/dev/null || true)\"",
+            u = shell_quote(unit),
+            m = RESULT_MARKER
+        ),
+        ProbeCheck::Command { command, .. } => format!(
+            "timeout {t} sh -c {c} >/dev/null 2>&1; echo {m} \"$?\"",
+            t = timeout,
+            c = shell_quote(command),
+            m = RESULT_MARKER
+        ),
+    };
+    // The outer bound also covers commands that ignore their own timeout
+    format!(
+        "timeout {} sh -c {} || echo {} timeout",
+        timeout + 5,
+        shell_quote(&body),
+        RESULT_MARKER
+    )
+}
+
+/// Judge the output of [`probe_command`]: whether it passed and what was observed
+pub fn evaluate(probe: &Probe, output: &str) -> (bool, String) {
+    let Some((index, observed)) = output.lines().enumerate().find_map(|(i, line)| {
+        line.strip_prefix(RESULT_MARKER)
+            .map(|rest| (i, rest.trim().to_string()))
+    }) else {
+        return (false, "no result".to_string());
+    };
+    match &probe.check {
+        ProbeCheck::Tcp { host, port } => (
+            observed == "open",
+            format!("{}:{} {}", host, port, observed),
+        ),
+        ProbeCheck::Http {
+            expect_status,
+            contains,
+            ..
+        } => {
+            let status_ok = observed == expect_status.to_string();
+            let body: Vec<&str> = output.lines().skip(index + 1).collect();
+            let body_ok = contains
+                .as_ref()
+                .is_none_or(|needle| body.join("\n").contains(needle.as_str()));
+            let detail = match (status_ok, body_ok) {
+                (true, false) => format!("status {}, body lacks the expected text", observed),
+                _ => format!("status {}", observed),
+            };
+            (status_ok && body_ok, detail)
+        }
+        ProbeCheck::Systemd { unit } => (observed == "active", format!("{} {}", unit, observed)),
+        ProbeCheck::Command { expect_exit, .. } => (
+            observed == expect_exit.to_string(),
+            if observed == "124" {
+                "timed out".to_string()
+            } else {
+                format!("exit {}", observed)
+            },
+        ),
+    }
+}
+
+/// Run every probe of `config`: readiness probes until they pass or time out, then the
+/// liveness samples
+pub async fn run_probes(
+    ssh: &mut SshClient,
+    config: &VerificationConfig,
+) -> Result<Vec<ProbeResult>> {
+    let interval = Duration::from_secs(config.interval_secs);
+    let deadline = Instant::now() + Duration::from_secs(config.ready_timeout_secs);
+    let mut results = Vec::new();
+
+    for probe in config
+        .probes
+        .iter()
+        .filter(|p| p.role == ProbeRole::Readiness)
+    {
+        let started = Instant::now();
+        let mut attempts = 0;
+        let result = loop {
+            attempts += 1;
+            let (passed, detail) = check(ssh, probe).await?;
+            if passed || Instant::now() + interval > deadline {
+                break result_for(probe, passed, detail, attempts, started);
+            }
+            tokio::time::sleep(interval).await;
+        };
+        log_result(&result);
+        results.push(result);
+    }
+
+    let ready = results.iter().all(|r| r.passed);
+    for probe in config
+        .probes
+        .iter()
+        .filter(|p| p.role == ProbeRole::Liveness)
+    {
+        let started = Instant::now();
+        let result = if !ready {
+            result_for(probe, false, "skipped: not ready".to_string(), 0, started)
+        } else {
+            let mut outcome = (true, String::new());
+            let mut attempts = 0;
+            while attempts < config.liveness_samples {
+                if attempts > 0 {
+                    tokio::time::sleep(interval).await;
+                }
+                attempts += 1;
+                outcome = check(ssh, probe).await?;
+                if !outcome.0 {
+                    break;
+                }
+            }
+            result_for(probe, outcome.0, outcome.1, attempts, started)
+        };
+        log_result(&result);
+        results.push(result);
+    }
+    Ok(results)
+}
+
+/// `Err` naming the probes that failed, if any did
+pub fn require_passed(results: &[ProbeResult]) -> Result<()> {
+    let failed: Vec<String> = results
+        .iter()
+        .filter(|r| !r.passed)
+        .map(|r| format!("{} ({})", r.name, r.detail))
+        .collect();
+    if failed.is_empty() {
+        return Ok(());
+    }
+    Err(AutoInstallError::InstallationError(format!(
+        "{} of {} service probes failed: {}",
+        failed.len(),
+        results.len(),
+        failed.join(", ")
+    )))
+}
+
+async fn check(ssh: &mut SshClient, probe: &Probe) -> Result<(bool, String)> {
+    let output = ssh
+        .execute_with_output(&probe_command(probe))
+        .await
+        .unwrap_or_default();
+    Ok(evaluate(probe, &output))
+}
+
+fn result_for(
+    probe: &Probe,
+    passed: bool,
+    detail: String,
+    attempts: u32,
+    started: Instant,
+) -> ProbeResult {
+    ProbeResult {
+        name: probe.name.clone(),
+        role: probe.role,
+        kind: match probe.check {
+            ProbeCheck::Tcp { .. } => "tcp",
+            ProbeCheck::Http { .. } => "http",
+            ProbeCheck::Systemd { .. } => "systemd",
+            ProbeCheck::Command { .. } => "command",
+        }
+        .to_string(),
+        passed,
+        detail,
+        attempts,
+        elapsed_secs: started.elapsed().as_secs(),
+    }
+}
+
+fn log_result(result: &ProbeResult) {
+    if result.passed {
+        info!(
+            "Probe {} ({}) passed: {}",
+            result.name,
+            result.role.as_str(),
+            result.detail
+        );
+    } else {
+        warn!(
+            "Probe {} ({}) failed after {} attempt(s): {}",
+            result.name,
+            result.role.as_str(),
+            result.attempts,
+            result.detail
+        );
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn probe(yaml: &str) -> Probe {
+        serde_yaml::from_str(yaml).unwrap()
+    }
+
+    #[test]
+    fn test_probe_commands_and_evaluation() {
+        let http =
+            probe("name: api\ntype: http\nurl: http://127.0.0.1:8080/healthz\ncontains: ok\n");
+        let command = probe_command(&http);
+        assert!(command.starts_with("timeout 15 sh -c "));
+        assert!(command.contains("--max-time 10"));
+        assert_eq!(
+            evaluate(&http, "probe-result 200\n{\"status\":\"ok\"}\n"),
+            (true, "status 200".to_string())
+        );
+        assert!(!evaluate(&http, "probe-result 200\ndegraded\n").0);
+        assert_eq!(
+            evaluate(&http, "probe-result 503\n"),
+            (false, "status 503".to_string())
+        );
+
+        let unit = probe("name: nginx\ntype: systemd\nunit: nginx.service\n");
+        assert!(evaluate(&unit, "probe-result active\n").0);
+        assert!(!evaluate(&unit, "probe-result failed\n").0);
+
+        let cmd = probe("name: m\ntype: command\ncommand: test -f /x\ntimeout_secs: 3\n");
+        assert_eq!(
+            evaluate(&cmd, "probe-result 124\n"),
+            (false, "timed out".to_string())
+        );
+        assert!(evaluate(&cmd, "probe-result 0\n").0);
+
+        let tcp = probe("name: pg\ntype: tcp\nport: 5432\n");
+        assert_eq!(
+            evaluate(&tcp, "probe-result closed\n"),
+            (false, "127.0.0.1:5432 closed".to_string())
+        );
+        assert_eq!(evaluate(&tcp, ""), (false, "no result".to_string()));
+    }
+}
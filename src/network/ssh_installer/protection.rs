@@ -0,0 +1,253 @@
+// file: src/network/ssh_installer/protection.rs
+// version: 1.2.0
+// guid: 1f6a3c82-7e49-4d05-b8c3-5a9d2e7f0c14
+
+//! Guard rail for destructive commands against protected hosts
+//!
+//! A command against a machine matched by `protected.yaml` (see
+//! [`crate::config::protection`]) goes ahead only with a matching `--i-know-what-i-am-doing`
+//! token or after a second person approves it through the registry's `approval_url`; the
+//! approver must not be the operator running the command. Every attempt, allowed or not, is
+//! appended to `logs/protection-audit.jsonl`.
+
+use super::gates::{self, GateDecision};
+use crate::config::protection::{Protection, ProtectionRegistry};
+use crate::error::AutoInstallError;
+use crate::security::provenance::BuilderIdentity;
+use crate::Result;
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use std::io::Write;
+use std::path::{Path, PathBuf};
+use std::time::{Duration, Instant};
+use tracing::{info, warn};
+
+/// One attempt to run a destructive command against a protected machine
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct AuditEntry {
+    pub time: DateTime<Utc>,
+    pub target: String,
+    pub environment: Option<String>,
+    pub command: String,
+    /// `user@host` that ran the command
+    pub operator: String,
+    /// override, bad-token, approval-requested, approved, rejected, timed-out or denied
+    pub outcome: String,
+    pub approver: Option<String>,
+    pub detail: Option<String>,
+}
+
+/// Path of the audit log under `base_dir`
+pub fn audit_path(base_dir: &Path) -> PathBuf {
+    base_dir.join("logs").join("protection-audit.jsonl")
+}
+
+/// Load `protected.yaml` from `base_dir` and allow `command` against the machine known by
+/// `names`, or explain why not
+pub async fn check(
+    base_dir: &Path,
+    names: &[&str],
+    command: &str,
+    token: Option<&str>,
+) -> Result<()> {
+    let registry = ProtectionRegistry::load(base_dir)?;
+    authorize(base_dir, &registry, names, command, token).await
+}
+
+/// Allow `command` against the machine known by `names`, or explain why not
+pub async fn authorize(
+    base_dir: &Path,
+    registry: &ProtectionRegistry,
+    names: &[&str],
+    command: &str,
+    token: Option<&str>,
+) -> Result<()> {
+    let Some(protection) = registry.protection(names) else {
+        return Ok(());
+    };
+    let operator = BuilderIdentity::current().id;
+    let record = |outcome: &str, approver: Option<String>, detail: Option<String>| {
+        audit(
+            base_dir,
+            &AuditEntry {
+                time: Utc::now(),
+                target: protection.target.clone(),
+                environment: protection.environment.clone(),
+                command: command.to_string(),
+                operator: operator.clone(),
+                outcome: outcome.to_string(),
+                approver,
+                detail,
+            },
+        )
+    };
+
+    if let Some(token) = token {
+        if protection.accepts(token) {
+            warn!(
+                "Running `{}` against protected {} on the operator's override",
+                command,
+                protection.describe()
+            );
+            return record("override", None, None);
+        }
+        record("bad-token", None, Some(token.to_string()))?;
+        return Err(AutoInstallError::ValidationError(format!(
+            "--i-know-what-i-am-doing '{}' does not match protected {}; pass the host or environment name",
+            token,
+            protection.describe()
+        )));
+    }
+
+    let Some(url) = &registry.approval_url else {
+        record("denied", None, None)?;
+        return Err(refusal(command, &protection));
+    };
+    record("approval-requested", None, None)?;
+    info!(
+        "{} is protected; waiting up to {}s for a second approver",
+        protection.describe(),
+        registry.approval_timeout_secs
+    );
+    let url = url
+        .replace("{target}", &protection.target)
+        .replace("{command}", command);
+    let started = Instant::now();
+    loop {
+        let answer = gates::poll_endpoint(&url)
+            .await
+            .and_then(|(decision, body)| with_approver(decision, &body));
+        if let Some((decision, approver)) = answer {
+            match decision {
+                GateDecision::Approved if is_second_person(&approver, &operator) => {
+                    info!("`{}` approved by {}", command, approver);
+                    return record("approved", Some(approver), None);
+                }
+                GateDecision::Approved => {
+                    warn!(
+                        "Ignoring approval by {}: the operator cannot approve their own run",
+                        approver
+                    );
+                }
+                GateDecision::Rejected(reason) => {
+                    record("rejected", Some(approver), Some(reason.clone()))?;
+                    return Err(AutoInstallError::ValidationError(format!(
+                        "`{}` against protected {} was rejected: {}",
+                        command,
+                        protection.describe(),
+                        reason
+                    )));
+                }
+            }
+        }
+        if started.elapsed() >= Duration::from_secs(registry.approval_timeout_secs) {
+            record("timed-out", None, None)?;
+            return Err(AutoInstallError::TimeoutError(format!(
+                "No second approver for `{}` against protected {} within {}s",
+                command,
+                protection.describe(),
+                registry.approval_timeout_secs
+            )));
+        }
+        tokio::time::sleep(Duration::from_secs(registry.poll_secs)).await;
+    }
+}
+
+/// Append `entry` to the audit log
+pub fn audit(base_dir: &Path, entry: &AuditEntry) -> Result<()> {
+    let path = audit_path(base_dir);
+    if let Some(parent) = path.parent() {
+        std::fs::create_dir_all(parent)?;
+    }
+    let mut file = std::fs::OpenOptions::new()
+        .create(true)
+        .append(true)
+        .open(&path)?;
+    writeln!(file, "{}", serde_json::to_string(entry)?)?;
+    Ok(())
+}
+
+fn refusal(command: &str, protection: &Protection) -> AutoInstallError {
+    AutoInstallError::ValidationError(format!(
+        "Refusing `{}` against protected {}; pass --i-know-what-i-am-doing {} or configure an approval_url for a second approver",
+        command,
+        protection.describe(),
+        protection.target
+    ))
+}
+
+/// Whether `approver` names someone other than `operator` (`user@host`)
+fn is_second_person(approver: &str, operator: &str) -> bool {
+    let user = operator.split('@').next().unwrap_or(operator);
+    !approver.is_empty() && approver != operator && approver != user
+}
+
+/// Pair an endpoint decision with the approver in its JSON answer; an approval without an
+/// approver does not count
+fn with_approver(
+    decision: GateDecision,
+    body: &serde_json::Value,
+) -> Option<(GateDecision, String)> {
+    let approver = body
+        .get("approver")
+        .and_then(|a| a.as_str())
+        .unwrap_or_default()
+        .to_string();
+    if decision == GateDecision::Approved && approver.is_empty() {
+        return None;
+    }
+    Some((decision, approver))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn test_override_and_denial_are_audited() {
+        let dir = tempfile::TempDir::new().unwrap();
+        let registry: ProtectionRegistry =
+            serde_yaml::from_str("hosts:\n  db-01:\n    protected: true\n").unwrap();
+
+        authorize(dir.path(), &registry, &["10.0.0.9"], "ssh-install", None)
+            .await
+            .unwrap();
+        assert!(
+            authorize(dir.path(), &registry, &["db-01"], "ssh-install", None)
+                .await
+                .is_err()
+        );
+        assert!(
+            authorize(dir.path(), &registry, &["db-01"], "deploy", Some("db-02"))
+                .await
+                .is_err()
+        );
+        authorize(dir.path(), &registry, &["db-01"], "deploy", Some("db-01"))
+            .await
+            .unwrap();
+
+        let outcomes: Vec<String> = std::fs::read_to_string(audit_path(dir.path()))
+            .unwrap()
+            .lines()
+            .map(|line| serde_json::from_str::<AuditEntry>(line).unwrap().outcome)
+            .collect();
+        assert_eq!(outcomes, ["denied", "bad-token", "override"]);
+
+        assert!(is_second_person("alice", "bob@ops-01"));
+        assert!(!is_second_person("bob", "bob@ops-01"));
+        assert_eq!(
+            with_approver(
+                GateDecision::Approved,
+                &serde_json::json!({"approved": true})
+            ),
+            None
+        );
+        assert_eq!(
+            with_approver(
+                GateDecision::Approved,
+                &serde_json::json!({"approved": true, "approver": "alice"})
+            ),
+            Some((GateDecision::Approved, "alice".to_string()))
+        );
+    }
+}
@@ -0,0 +1,433 @@
+// file: src/network/ssh_installer/reinstall.rs
+// version: 1.1.0
+// guid: 9c2e6b14-3f78-4a05-8d91-b7e4a0c5f263
+
+//! Incremental reinstall: new OS datasets on the pools of an earlier install
+//!
+//! `ssh-install --reinstall` keeps the partitions, the LUKS volume and both pools. Phase 2
+//! opens the existing LUKS volume and reformats only the ESP. Phase 3 imports the pools
+//! without mounting anything, checks that every dataset in `reinstall.preserve` exists, then
+//! destroys the children of `<root pool>/ROOT` and `<boot pool>/BOOT` and creates the OS
+//! datasets again under the installation id the host had, so `USERDATA/root_<id>` still
+//! belongs to the new root. Nothing is destroyed when a preserved dataset is missing.
+//!
+//! Boot environments are children of `ROOT` and `BOOT` as well, cloned from snapshots of the
+//! installed root, so they are replaced too and destroyed before the datasets they were cloned
+//! from. A clone outside the OS datasets would be destroyed along with its origin, so the
+//! reinstall refuses until it is promoted or removed.
+//!
+//! Preserved datasets are relinked in two steps: `canmount=on` and their mountpoint are set in
+//! phase 3, before phase 5 seeds the new system's zfs-list.cache, and `legacy` datasets get
+//! their fstab entries once the new system's fstab exists.
+
+use crate::config::reinstall::ReinstallConfig;
+use crate::config::zfs_pools::PoolLayout;
+use crate::error::AutoInstallError;
+use crate::network::SshClient;
+use crate::Result;
+use std::collections::BTreeMap;
+use tracing::info;
+
+/// A preserved dataset and where the new system mounts it
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct LinkedDataset {
+    pub dataset: String,
+    pub mountpoint: String,
+    /// Mounted from fstab rather than by ZFS
+    pub legacy: bool,
+}
+
+/// What a reinstall destroys and keeps, from the datasets found on the target
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ReinstallPlan {
+    /// Id of the existing `ROOT/ubuntu_<id>` dataset, reused for the new OS datasets
+    pub installation_id: Option<String>,
+    /// OS datasets destroyed recursively, clones before the datasets they were cloned from
+    pub destroy: Vec<String>,
+    /// Origin snapshot of each destroyed dataset that is a clone, i.e. a boot environment
+    pub clone_origins: BTreeMap<String, String>,
+    pub preserve: Vec<LinkedDataset>,
+}
+
+impl ReinstallPlan {
+    /// Plan from `zfs list -H -o name,mountpoint,origin` output of both pools
+    ///
+    /// `preferred_id` (from the host vars) wins when the target has a root dataset with that
+    /// id, as it may after boot environments were created.
+    pub fn from_listing(
+        listing: &str,
+        layout: &PoolLayout,
+        config: &ReinstallConfig,
+        preferred_id: Option<&str>,
+    ) -> Result<Self> {
+        let listed: Vec<(&str, &str, Option<&str>)> = listing
+            .lines()
+            .filter_map(|line| {
+                let mut fields = line.split('\t');
+                let name = fields.next()?.trim();
+                let mountpoint = fields.next().unwrap_or("-").trim();
+                let origin = fields
+                    .next()
+                    .map(str::trim)
+                    .filter(|o| !o.is_empty() && *o != "-");
+                Some((name, mountpoint, origin))
+            })
+            .filter(|(name, _, _)| !name.is_empty())
+            .collect();
+        let datasets: Vec<(&str, &str)> = listed.iter().map(|(n, m, _)| (*n, *m)).collect();
+
+        let root_container = layout.root_container();
+        let boot_container = layout.boot_container();
+        if !datasets.iter().any(|(name, _)| *name == root_container) {
+            return Err(AutoInstallError::ValidationError(format!(
+                "No {} dataset on the target; a reinstall needs the pools of an earlier install",
+                root_container
+            )));
+        }
+        let child_of = |name: &str, parent: &str| {
+            name.strip_prefix(parent)
+                .and_then(|rest| rest.strip_prefix('/'))
+                .is_some_and(|rest| !rest.contains('/'))
+        };
+        let mut destroy: Vec<String> = datasets
+            .iter()
+            .filter(|(name, _)| child_of(name, &root_container) || child_of(name, &boot_container))
+            .map(|(name, _)| name.to_string())
+            .collect();
+
+        // Clones of OS snapshots: boot environments go first, anything else blocks the reinstall
+        let tree_of = |name: &str| {
+            destroy
+                .iter()
+                .position(|os| name == os || name.starts_with(&format!("{}/", os)))
+        };
+        let mut clone_origins = BTreeMap::new();
+        let mut must_precede = Vec::new();
+        for (name, _, origin) in &listed {
+            let Some(origin) = origin else { continue };
+            let origin_dataset = origin.split('@').next().unwrap_or(origin);
+            match (tree_of(name), tree_of(origin_dataset)) {
+                (Some(clone), Some(source)) if clone != source => {
+                    if *name == destroy[clone] {
+                        clone_origins.insert(name.to_string(), origin.to_string());
+                    }
+                    must_precede.push((clone, source));
+                }
+                (None, Some(_)) => {
+                    return Err(AutoInstallError::ValidationError(format!(
+                        "{} is a clone of {}, which a reinstall destroys; promote it with \
+                         `zfs promote {}` or destroy it first. Nothing was destroyed",
+                        name, origin, name
+                    )))
+                }
+                _ => {}
+            }
+        }
+        let mut ordered = Vec::with_capacity(destroy.len());
+        let mut remaining: Vec<usize> = (0..destroy.len()).collect();
+        while !remaining.is_empty() {
+            // A dataset goes once no remaining clone was made from it; clones cannot form cycles
+            let next = remaining
+                .iter()
+                .position(|&source| {
+                    !must_precede
+                        .iter()
+                        .any(|&(clone, s)| s == source && remaining.contains(&clone))
+                })
+                .unwrap_or(0);
+            ordered.push(destroy[remaining.remove(next)].clone());
+        }
+        destroy = ordered;
+
+        let ids: Vec<&str> = destroy
+            .iter()
+            .filter_map(|name| name.strip_prefix(&root_container)?.strip_prefix("/ubuntu_"))
+            .collect();
+        let installation_id = preferred_id
+            .filter(|id| ids.contains(id))
+            .or_else(|| ids.first().copied())
+            .map(str::to_string);
+
+        let mut preserve = Vec::new();
+        for preserved in &config.preserve {
+            let Some((_, current)) = datasets.iter().find(|(name, _)| *name == preserved.dataset)
+            else {
+                return Err(AutoInstallError::ValidationError(format!(
+                    "reinstall.preserve: {} does not exist on the target; nothing was destroyed",
+                    preserved.dataset
+                )));
+            };
+            if destroy
+                .iter()
+                .any(|os| preserved.dataset.starts_with(&format!("{}/", os)))
+            {
+                return Err(AutoInstallError::ValidationError(format!(
+                    "reinstall.preserve: {} lies inside an OS dataset that a reinstall replaces",
+                    preserved.dataset
+                )));
+            }
+            let mountpoint = match &preserved.mountpoint {
+                Some(mountpoint) => mountpoint.clone(),
+                None if current.starts_with('/') => current.to_string(),
+                None => {
+                    return Err(AutoInstallError::ValidationError(format!(
+                        "reinstall.preserve: {} has mountpoint '{}'; set a mountpoint for it",
+                        preserved.dataset, current
+                    )))
+                }
+            };
+            preserve.push(LinkedDataset {
+                dataset: preserved.dataset.clone(),
+                mountpoint,
+                legacy: preserved.legacy,
+            });
+        }
+
+        Ok(Self {
+            installation_id,
+            destroy,
+            clone_origins,
+            preserve,
+        })
+    }
+
+    pub fn summary_lines(&self) -> Vec<String> {
+        let mut lines: Vec<String> = self
+            .destroy
+            .iter()
+            .map(|name| match self.clone_origins.get(name) {
+                Some(origin) => format!("replace {} (boot environment, clone of {})", name, origin),
+                None => format!("replace {}", name),
+            })
+            .collect();
+        lines.extend(self.preserve.iter().map(|linked| {
+            format!(
+                "keep {} at {}{}",
+                linked.dataset,
+                linked.mountpoint,
+                if linked.legacy { " (fstab)" } else { "" }
+            )
+        }));
+        lines
+    }
+
+    /// Commands setting the mount properties of the preserved datasets; run with the pools
+    /// imported under `/mnt/targetos`, where ZFS stores mountpoints without the altroot
+    pub fn property_commands(&self) -> Vec<String> {
+        let mut commands = Vec::new();
+        for linked in &self.preserve {
+            if linked.legacy {
+                commands.push(format!("zfs set mountpoint=legacy {}", linked.dataset));
+            } else {
+                commands.push(format!(
+                    "zfs set mountpoint={} {}",
+                    linked.mountpoint, linked.dataset
+                ));
+                commands.push(format!("zfs set canmount=on {}", linked.dataset));
+            }
+        }
+        commands
+    }
+
+    /// Commands adding the fstab entries of `legacy` datasets to the new system
+    pub fn fstab_commands(&self) -> Vec<String> {
+        self.preserve
+            .iter()
+            .filter(|linked| linked.legacy)
+            .flat_map(|linked| {
+                let line = format!("{} {} zfs defaults,nofail 0 0", linked.dataset, linked.mountpoint);
+                [
+                    format!("mkdir -p /mnt/targetos{}", linked.mountpoint),
+                    format!(
+                        "bash -lc \"grep -q '^{} ' /mnt/targetos/etc/fstab 2>/dev/null || echo '{}' >> /mnt/targetos/etc/fstab\"",
+                        linked.dataset, line
+                    ),
+                ]
+            })
+            .collect()
+    }
+}
+
+/// Imports the pools of an earlier install and replaces its OS datasets
+pub struct ReinstallManager<'a> {
+    ssh: &'a mut SshClient,
+}
+
+impl<'a> ReinstallManager<'a> {
+    pub fn new(ssh: &'a mut SshClient) -> Self {
+        Self { ssh }
+    }
+
+    /// Import both pools under `/mnt/targetos` without mounting, then plan from their datasets
+    pub async fn plan(
+        &mut self,
+        layout: &PoolLayout,
+        config: &ReinstallConfig,
+        preferred_id: Option<&str>,
+    ) -> Result<ReinstallPlan> {
+        for (pool, search) in [
+            (&layout.boot.name, ""),
+            (&layout.root.name, "-d /dev/mapper "),
+        ] {
+            let imported = self
+                .ssh
+                .check_silent(&format!("zpool list -H {} >/dev/null 2>&1", pool))
+                .await
+                .unwrap_or(false);
+            if !imported {
+                self.log_and_execute(
+                    &format!("Importing {}", pool),
+                    &format!("zpool import -f -N -R /mnt/targetos {}{}", search, pool),
+                )
+                .await?;
+            }
+        }
+        let listing = self
+            .ssh
+            .execute_with_output(&format!(
+                "zfs list -H -o name,mountpoint,origin -r {} {}",
+                layout.root.name, layout.boot.name
+            ))
+            .await?;
+        ReinstallPlan::from_listing(&listing, layout, config, preferred_id)
+    }
+
+    /// Destroy the old OS datasets, boot environments first, and set the mount properties of
+    /// the preserved ones
+    pub async fn replace_os_datasets(&mut self, plan: &ReinstallPlan) -> Result<()> {
+        for dataset in &plan.destroy {
+            self.log_and_execute(
+                &format!("Destroying OS dataset {}", dataset),
+                &format!("zfs destroy -r {}", dataset),
+            )
+            .await?;
+        }
+        for cmd in plan.property_commands() {
+            self.log_and_execute("Relinking preserved dataset", &cmd)
+                .await?;
+        }
+        Ok(())
+    }
+
+    /// Add the fstab entries of preserved `legacy` datasets to the new system
+    pub async fn link_fstab(&mut self, plan: &ReinstallPlan) -> Result<()> {
+        for cmd in plan.fstab_commands() {
+            self.log_and_execute("Adding preserved dataset to fstab", &cmd)
+                .await?;
+        }
+        Ok(())
+    }
+
+    /// Helper method to log and execute commands
+    async fn log_and_execute(&mut self, description: &str, command: &str) -> Result<()> {
+        info!("Executing: {} -> {}", description, command);
+        self.ssh.execute(command).await
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::reinstall::PreservedDataset;
+
+    const LISTING: &str = "bpool\t/boot\t-\n\
+        bpool/BOOT\tnone\t-\n\
+        bpool/BOOT/before-kernel\t/boot\tbpool/BOOT/ubuntu_k3x9q2@before-kernel\n\
+        bpool/BOOT/ubuntu_k3x9q2\t/boot\t-\n\
+        rpool\t/\t-\n\
+        rpool/ROOT\tnone\t-\n\
+        rpool/ROOT/before-kernel\t/\trpool/ROOT/ubuntu_k3x9q2@before-kernel\n\
+        rpool/ROOT/before-kernel/var\t/var\trpool/ROOT/ubuntu_k3x9q2/var@before-kernel\n\
+        rpool/ROOT/ubuntu_k3x9q2\t/\t-\n\
+        rpool/ROOT/ubuntu_k3x9q2/var\t/var\t-\n\
+        rpool/USERDATA\t/\t-\n\
+        rpool/USERDATA/root_k3x9q2\t/root\t-\n\
+        rpool/home\t/home\t-\n\
+        rpool/data\tlegacy\t-\n";
+
+    #[test]
+    fn test_plan_replaces_only_os_datasets() {
+        let layout = PoolLayout::default();
+        let mut config = ReinstallConfig {
+            preserve: vec![PreservedDataset {
+                dataset: "rpool/home".to_string(),
+                mountpoint: None,
+                legacy: false,
+            }],
+        };
+        let plan = ReinstallPlan::from_listing(LISTING, &layout, &config, None).unwrap();
+        // Boot environments are destroyed before the datasets they were cloned from
+        assert_eq!(
+            plan.destroy,
+            [
+                "bpool/BOOT/before-kernel",
+                "bpool/BOOT/ubuntu_k3x9q2",
+                "rpool/ROOT/before-kernel",
+                "rpool/ROOT/ubuntu_k3x9q2"
+            ]
+        );
+        assert_eq!(
+            plan.summary_lines()[2],
+            "replace rpool/ROOT/before-kernel (boot environment, clone of rpool/ROOT/ubuntu_k3x9q2@before-kernel)"
+        );
+        assert_eq!(plan.installation_id.as_deref(), Some("k3x9q2"));
+        assert_eq!(
+            plan.property_commands(),
+            [
+                "zfs set mountpoint=/home rpool/home",
+                "zfs set canmount=on rpool/home"
+            ]
+        );
+        assert!(plan.fstab_commands().is_empty());
+
+        // A legacy dataset needs a mountpoint to go into fstab
+        config.preserve.push(PreservedDataset {
+            dataset: "rpool/data".to_string(),
+            mountpoint: None,
+            legacy: true,
+        });
+        assert!(ReinstallPlan::from_listing(LISTING, &layout, &config, None).is_err());
+        config.preserve[1].mountpoint = Some("/srv/data".to_string());
+        let plan = ReinstallPlan::from_listing(LISTING, &layout, &config, None).unwrap();
+        assert_eq!(plan.fstab_commands().len(), 2);
+        assert!(plan.fstab_commands()[1].contains("rpool/data /srv/data zfs defaults,nofail 0 0"));
+
+        config.preserve.push(PreservedDataset {
+            dataset: "rpool/srv".to_string(),
+            mountpoint: None,
+            legacy: false,
+        });
+        let err = ReinstallPlan::from_listing(LISTING, &layout, &config, None).unwrap_err();
+        assert!(err.to_string().contains("rpool/srv does not exist"));
+        assert!(ReinstallPlan::from_listing("rpool\t/\n", &layout, &config, None).is_err());
+    }
+
+    #[test]
+    fn test_plan_refuses_clones_outside_os_datasets() {
+        let layout = PoolLayout::default();
+        let config = ReinstallConfig::default();
+        // Listed before its origin, which must not change the order
+        let listing = "rpool/ROOT\tnone\t-\n\
+            rpool/ROOT/a-clone\t/\trpool/ROOT/z-root@s\n\
+            rpool/ROOT/z-root\t/\t-\n";
+        let plan = ReinstallPlan::from_listing(listing, &layout, &config, None).unwrap();
+        assert_eq!(plan.destroy, ["rpool/ROOT/a-clone", "rpool/ROOT/z-root"]);
+        let listing = "rpool/ROOT\tnone\t-\n\
+            rpool/ROOT/z-root\t/\t-\n\
+            rpool/ROOT/a-clone\t/\trpool/ROOT/z-root@s\n";
+        let plan = ReinstallPlan::from_listing(listing, &layout, &config, None).unwrap();
+        assert_eq!(plan.destroy, ["rpool/ROOT/a-clone", "rpool/ROOT/z-root"]);
+
+        let err = ReinstallPlan::from_listing(
+            &format!(
+                "{}rpool/scratch\t/scratch\trpool/ROOT/ubuntu_k3x9q2@before-kernel\n",
+                LISTING
+            ),
+            &layout,
+            &config,
+            None,
+        )
+        .unwrap_err();
+        assert!(err.to_string().contains("zfs promote rpool/scratch"));
+    }
+}
@@ -0,0 +1,370 @@
+// file: src/network/ssh_installer/runbook.rs
+// version: 1.4.0
+// guid: 9b4f2d71-6e08-4a3c-8d15-c7a2e0f9b643
+
+//! Per-host runbook written after an install
+//!
+//! `logs/<hostname>/runbook.md` describes the machine as it was actually installed: disk
+//! layout, pools and datasets read from the target, network settings, where each credential
+//! lives (never the values) and how to get into the system from a rescue environment. It is
+//! rendered from the applied installation config plus what was read back before unmounting.
+
+use super::config::InstallationConfig;
+use super::config_export::DatasetInfo;
+use super::session::InstallSession;
+use crate::logging::redact;
+use crate::security::ssh_ca::{CertificateKind, SshKey};
+use crate::utils::block_device::partition_path;
+use crate::Result;
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use std::path::{Path, PathBuf};
+
+/// Pools as read back from the target
+pub const POOLS_COMMAND: &str = "zpool list -H -o name,size,health 2>/dev/null || true";
+
+/// A ZFS pool line from `zpool list -H`
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct PoolInfo {
+    pub name: String,
+    pub size: String,
+    pub health: String,
+}
+
+/// Parse tab-separated `zpool list -H -o name,size,health` output
+pub fn parse_pools(text: &str) -> Vec<PoolInfo> {
+    text.lines()
+        .filter_map(|line| {
+            let mut fields = line.split('\t');
+            Some(PoolInfo {
+                name: fields.next()?.to_string(),
+                size: fields.next()?.to_string(),
+                health: fields.next()?.trim().to_string(),
+            })
+        })
+        .collect()
+}
+
+/// Runbook for one installed host
+#[derive(Debug, Clone)]
+pub struct Runbook<'a> {
+    config: &'a InstallationConfig,
+    pools: Vec<PoolInfo>,
+    datasets: Vec<DatasetInfo>,
+    generated_at: DateTime<Utc>,
+}
+
+impl<'a> Runbook<'a> {
+    pub fn new(
+        config: &'a InstallationConfig,
+        pools: Vec<PoolInfo>,
+        datasets: Vec<DatasetInfo>,
+    ) -> Self {
+        Self {
+            config,
+            pools,
+            datasets,
+            generated_at: Utc::now(),
+        }
+    }
+
+    /// Location of the runbook for `hostname`
+    pub fn path(base_dir: &Path, hostname: &str) -> PathBuf {
+        InstallSession::host_dir(base_dir, hostname).join("runbook.md")
+    }
+
+    /// Write `logs/<hostname>/runbook.md` under `base_dir`
+    pub fn save(&self, base_dir: &Path) -> Result<PathBuf> {
+        let path = Self::path(base_dir, &self.config.hostname);
+        if let Some(parent) = path.parent() {
+            std::fs::create_dir_all(parent)?;
+        }
+        std::fs::write(&path, redact::scrub(&self.render_markdown()).as_bytes())?;
+        Ok(path)
+    }
+
+    /// `(credential, where it lives)` pairs; values are never included
+    pub fn credential_locations(&self) -> Vec<(String, String)> {
+        let config = self.config;
+        let mut rows = vec![
+            (
+                "LUKS passphrase".to_string(),
+                "Supplied at install time (preset `luks_key` or `LUKS_KEY`); not stored by the agent"
+                    .to_string(),
+            ),
+            (
+                "root password".to_string(),
+                "Supplied at install time (preset `root_password`); not stored by the agent"
+                    .to_string(),
+            ),
+            (
+                "Enrollment token".to_string(),
+                "`/etc/ubuntu-autoinstall-agent/` on the host; hash in `enrollment.json` next to this file"
+                    .to_string(),
+            ),
+        ];
+        if config.nbde.is_enabled() {
+            rows.push((
+                "LUKS Clevis binding".to_string(),
+                format!(
+                    "Tang servers {} (any {} unlock)",
+                    config
+                        .nbde
+                        .servers
+                        .iter()
+                        .map(|s| s.url.as_str())
+                        .collect::<Vec<_>>()
+                        .join(", "),
+                    config.nbde.threshold
+                ),
+            ));
+        }
+        if config.ssh_ca.enabled {
+            let host_ca = config
+                .ssh_ca
+                .host_ca_key
+                .clone()
+                .unwrap_or_else(|| SshKey::default_ca_path(CertificateKind::Host));
+            let user_ca = config
+                .ssh_ca
+                .user_ca_key
+                .clone()
+                .unwrap_or_else(|| SshKey::default_ca_path(CertificateKind::User));
+            rows.push((
+                "SSH host CA key".to_string(),
+                format!(
+                    "`{}` on the machine that ran the install",
+                    host_ca.display()
+                ),
+            ));
+            rows.push((
+                "SSH user CA key".to_string(),
+                format!(
+                    "`{}` on the machine that ran the install",
+                    user_ca.display()
+                ),
+            ));
+            if !config.ssh_ca.users.is_empty() {
+                rows.push((
+                    "SSH user certificates".to_string(),
+                    "`ssh/<name>-cert.pub` next to this file".to_string(),
+                ));
+            }
+        }
+        rows
+    }
+
+    /// Commands that open the installed system from a live/rescue environment
+    pub fn recovery_commands(&self) -> Vec<String> {
+        let disk = &self.config.disk_device;
+        let layout = self.config.pool_layout().unwrap_or_default();
+        vec![
+            format!("cryptsetup open {} luks", partition_path(disk, 4)),
+            format!("zpool import -N -R /mnt {}", layout.root.name),
+            format!("zpool import -N -R /mnt {}", layout.boot.name),
+            format!(
+                "zfs mount $(zfs list -H -o name -d 1 {}/ROOT | tail -n 1)",
+                layout.root.name
+            ),
+            "zfs mount -a".to_string(),
+            format!("mount {} /mnt/boot/efi", partition_path(disk, 1)),
+            "for d in dev proc sys run; do mount --rbind /$d /mnt/$d; done".to_string(),
+            "chroot /mnt /bin/bash".to_string(),
+        ]
+    }
+
+    /// Markdown runbook
+    pub fn render_markdown(&self) -> String {
+        let config = self.config;
+        let mut out = format!("# Runbook: {}\n\n", config.hostname);
+        out.push_str(&format!(
+            "Generated {} from the applied installation config (checksum `{}`).\n\n",
+            self.generated_at.to_rfc3339(),
+            config.checksum()
+        ));
+
+        out.push_str("## System\n\n");
+        out.push_str(&format!(
+            "- Architecture: {}\n",
+            config.architecture.as_str()
+        ));
+        out.push_str(&format!(
+            "- Release: {}\n",
+            config.debootstrap_release.as_deref().unwrap_or("default")
+        ));
+        out.push_str(&format!("- Mirror: {}\n", config.effective_mirror()));
+        if let Some(snapshot) = &config.apt_snapshot {
+            out.push_str(&format!("- Apt snapshot: {}\n", snapshot));
+        }
+        out.push_str(&format!("- Timezone: {}\n\n", config.timezone));
+
+        out.push_str("## Disk layout\n\n");
+        out.push_str(&format!("Primary disk `{}`:\n\n", config.disk_device));
+        out.push_str("| Partition | Size | Use |\n|---|---|---|\n");
+        let disk = &config.disk_device;
+        let layout = config.pool_layout().unwrap_or_default();
+        out.push_str(&format!(
+            "| `{}` | 512M | EFI system partition (`/boot/efi`) |\n",
+            partition_path(disk, 1)
+        ));
+        out.push_str(&format!(
+            "| `{}` | 4G | Reset/rescue partition |\n",
+            partition_path(disk, 2)
+        ));
+        out.push_str(&format!(
+            "| `{}` | 2G | ZFS `{}` (`/boot`) |\n",
+            partition_path(disk, 3),
+            layout.boot.name
+        ));
+        let unlock = if config.nbde.is_enabled() {
+            "passphrase or Clevis/Tang"
+        } else {
+            "passphrase"
+        };
+        out.push_str(&format!(
+            "| `{}` | rest | LUKS2 (`/dev/mapper/luks`, unlocked by {}) holding ZFS `{}` |\n",
+            partition_path(disk, 4),
+            unlock,
+            layout.root.name
+        ));
+        for (index, mirror) in config.esp_mirror_devices.iter().enumerate() {
+            out.push_str(&format!(
+                "| `{}` | 512M | Secondary ESP {} (synced from `/boot/efi`) |\n",
+                partition_path(mirror, 1),
+                index + 2
+            ));
+        }
+        out.push('\n');
+
+        out.push_str("## Pools and datasets\n\n");
+        if self.pools.is_empty() {
+            out.push_str("Pool state could not be read from the target.\n\n");
+        } else {
+            out.push_str("| Pool | Size | Health |\n|---|---|---|\n");
+            for pool in &self.pools {
+                out.push_str(&format!(
+                    "| {} | {} | {} |\n",
+                    pool.name, pool.size, pool.health
+                ));
+            }
+            out.push('\n');
+        }
+        if !self.datasets.is_empty() {
+            out.push_str(
+                "| Dataset | Mountpoint | Compression | Encryption |\n|---|---|---|---|\n",
+            );
+            for dataset in &self.datasets {
+                out.push_str(&format!(
+                    "| {} | {} | {} | {} |\n",
+                    dataset.name, dataset.mountpoint, dataset.compression, dataset.encryption
+                ));
+            }
+            out.push('\n');
+        }
+
+        out.push_str("## Network\n\n");
+        out.push_str(&format!("- Interface: {}\n", config.network_interface));
+        out.push_str(&format!("- Address: {}\n", config.network_address));
+        out.push_str(&format!("- Gateway: {}\n", config.network_gateway));
+        out.push_str(&format!("- Search domain: {}\n", config.network_search));
+        out.push_str(&format!(
+            "- Nameservers: {}\n",
+            config.network_nameservers.join(", ")
+        ));
+        if config.firewall.is_enabled() {
+            out.push_str(&format!(
+                "- Firewall: {:?}, SSH on {}/tcp\n",
+                config.firewall.backend, config.firewall.ssh_port
+            ));
+        }
+        out.push('\n');
+
+        out.push_str("## Credentials\n\n");
+        out.push_str("Locations only; no secret values are recorded here.\n\n");
+        out.push_str("| Credential | Location |\n|---|---|\n");
+        for (name, location) in self.credential_locations() {
+            out.push_str(&format!("| {} | {} |\n", name, location));
+        }
+        out.push('\n');
+
+        out.push_str("## Recovery\n\n");
+        out.push_str(
+            "If the host does not boot, start any Ubuntu live environment with ZFS support and run:\n\n",
+        );
+        out.push_str("```sh\n");
+        for command in self.recovery_commands() {
+            out.push_str(&command);
+            out.push('\n');
+        }
+        out.push_str("```\n\n");
+        out.push_str(
+            "Inside the chroot, `update-initramfs -u -k all` and `update-grub` repair most boot problems. \
+             Leave the chroot, `umount -R /mnt` and `zpool export -a` before rebooting.\n",
+        );
+        if config.nbde.is_enabled() {
+            out.push_str(
+                "\nIf the Tang servers are unreachable at boot, the initramfs falls back to asking for the LUKS passphrase.\n",
+            );
+        }
+        out
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::network::ssh_installer::config_export::parse_datasets;
+
+    #[test]
+    fn test_parse_pools() {
+        let pools = parse_pools("bpool\t1.88G\tONLINE\nrpool\t460G\tDEGRADED\n");
+        assert_eq!(pools.len(), 2);
+        assert_eq!(pools[1].health, "DEGRADED");
+        assert!(parse_pools("").is_empty());
+    }
+
+    #[test]
+    fn test_render_markdown_has_layout_and_no_secrets() {
+        let mut config = InstallationConfig::for_len_serv_003();
+        config.luks_key = "super-secret-luks".to_string();
+        config.root_password = "super-secret-root".to_string();
+        let runbook = Runbook::new(
+            &config,
+            parse_pools("rpool\t460G\tONLINE\n"),
+            parse_datasets("rpool/ROOT/ubuntu\t/\tlz4\taes-256-gcm\n"),
+        );
+        let text = runbook.render_markdown();
+        assert!(text.starts_with(&format!("# Runbook: {}\n", config.hostname)));
+        assert!(text.contains(&format!("| `{}p4` | rest | LUKS2", config.disk_device)));
+        assert!(text.contains("| rpool | 460G | ONLINE |\n"));
+        assert!(text.contains("| rpool/ROOT/ubuntu | / | lz4 | aes-256-gcm |\n"));
+        assert!(text.contains(&format!("cryptsetup open {}p4 luks\n", config.disk_device)));
+        assert!(!text.contains("super-secret"));
+        assert!(!text.contains("Clevis binding"));
+    }
+
+    #[test]
+    fn test_credential_locations_follow_config() {
+        let mut config = InstallationConfig::for_len_serv_003();
+        config.nbde = serde_yaml::from_str("servers:\n  - url: http://tang1:7500\n").unwrap();
+        config.ssh_ca.enabled = true;
+        let rows = Runbook::new(&config, Vec::new(), Vec::new()).credential_locations();
+        let names: Vec<&str> = rows.iter().map(|(name, _)| name.as_str()).collect();
+        assert!(names.contains(&"LUKS Clevis binding"));
+        assert!(names.contains(&"SSH host CA key"));
+        assert!(!names.contains(&"SSH user certificates"));
+    }
+
+    #[test]
+    fn test_save_writes_under_host_dir() {
+        let dir = tempfile::TempDir::new().unwrap();
+        let config = InstallationConfig::for_len_serv_003();
+        let path = Runbook::new(&config, Vec::new(), Vec::new())
+            .save(dir.path())
+            .unwrap();
+        assert_eq!(path, Runbook::path(dir.path(), &config.hostname));
+        assert!(std::fs::read_to_string(path)
+            .unwrap()
+            .contains("Pool state could not be read"));
+    }
+}
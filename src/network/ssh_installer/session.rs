@@ -0,0 +1,369 @@
+// file: src/network/ssh_installer/session.rs
+// version: 1.18.0
+// guid: 2e7a9d14-6b3f-4c85-9f0e-d1a4b8c73e52
+
+//! Persistent installation session records
+//!
+//! Each installation writes `logs/<hostname>/session.json` next to the debug logs so an
+//! interrupted or failed run leaves a checkpoint describing exactly how far it got.
+//!
+//! Other tools read these records, so they carry a `schema_version`. Minor versions only add
+//! optional fields, which older readers can ignore; a major bump renames or removes fields and
+//! records with a newer major are refused rather than misread.
+
+use super::budget::BudgetUsage;
+use super::disk_health::DiskHealthCheck;
+use super::hardware_baseline::HardwareChange;
+use super::health_score::HealthScore;
+use super::late_commands::ScriptRun;
+use super::mirror_select::MirrorDecision;
+use super::plan::InstallPlan;
+use super::probes::ProbeResult;
+use super::upgrade::ReleaseUpgrade;
+use crate::config::hardening::ComplianceResult;
+use crate::config::zfs_tuning::ZfsTuning;
+use crate::config::AptSnapshot;
+use crate::logging::redact;
+use crate::network::redfish::HardwareInventory;
+use crate::Result;
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use std::path::{Path, PathBuf};
+
+/// Current `schema_version` of session records
+pub const SESSION_SCHEMA_VERSION: &str = "1.13";
+
+/// Version assumed for records written before the field existed
+fn legacy_schema_version() -> String {
+    "1.0".to_string()
+}
+
+/// Major component of a `major.minor` schema version
+fn schema_major(version: &str) -> Option<u32> {
+    version.split('.').next()?.parse().ok()
+}
+
+/// Lifecycle state of an installation session
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum SessionStatus {
+    Running,
+    Completed,
+    Failed,
+    Cancelled,
+}
+
+/// Wall-clock timing of one installation phase
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct PhaseTiming {
+    /// Phase name as recorded in `completed_phases`
+    pub name: String,
+    /// When the phase started
+    pub started_at: DateTime<Utc>,
+    /// When the phase ended; `None` while it is still running
+    pub finished_at: Option<DateTime<Utc>>,
+}
+
+impl PhaseTiming {
+    /// Elapsed time, if the phase has ended
+    pub fn duration(&self) -> Option<chrono::Duration> {
+        self.finished_at.map(|end| end - self.started_at)
+    }
+}
+
+/// Checkpoint of an installation run
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct InstallSession {
+    /// Layout version of this record; see the module docs for the compatibility policy
+    #[serde(default = "legacy_schema_version")]
+    pub schema_version: String,
+    /// Unique session identifier
+    pub id: String,
+    /// Hostname being installed
+    pub hostname: String,
+    /// Current lifecycle state
+    pub status: SessionStatus,
+    /// When the session started
+    pub started_at: DateTime<Utc>,
+    /// When the record was last written
+    pub updated_at: DateTime<Utc>,
+    /// Phases that finished successfully, in order
+    pub completed_phases: Vec<String>,
+    /// Phases that failed, with their error message
+    pub failed_phases: Vec<String>,
+    /// Phase that was running (or about to run) when the record was written
+    pub current_phase: Option<String>,
+    /// Last remote command started before the record was written
+    pub last_command: Option<String>,
+    /// Checksum of the installation config, recorded once the install completes
+    #[serde(default)]
+    pub config_checksum: Option<String>,
+    /// Archive snapshot the install was pinned to; reuse it to reinstall the same package versions
+    #[serde(default)]
+    pub apt_snapshot: Option<AptSnapshot>,
+    /// Start and end time of each phase, in order
+    #[serde(default)]
+    pub phase_timings: Vec<PhaseTiming>,
+    /// Non-fatal problems noticed during the run
+    #[serde(default)]
+    pub warnings: Vec<String>,
+    /// Results of the hardening compliance checklist, empty when no profile was applied
+    #[serde(default)]
+    pub compliance: Vec<ComplianceResult>,
+    /// ZFS module parameters written to the target, with where each value came from
+    #[serde(default)]
+    pub zfs_tuning: Option<ZfsTuning>,
+    /// Asset data read from the target's BMC over Redfish, when BMC access was configured
+    #[serde(default)]
+    pub hardware_inventory: Option<HardwareInventory>,
+    /// Mirror picked by the speed test and the measurements behind the choice
+    #[serde(default)]
+    pub mirror_selection: Option<MirrorDecision>,
+    /// Source and target release, snapshots and rollback outcome of an `upgrade` run
+    #[serde(default)]
+    pub release_upgrade: Option<ReleaseUpgrade>,
+    /// Settings and configuration commands the install applied, for diffing a reinstall
+    #[serde(default)]
+    pub plan: Option<InstallPlan>,
+    /// Output and outcome of each `late_commands:` script, in the order they ran
+    #[serde(default)]
+    pub late_commands: Vec<ScriptRun>,
+    /// SMART health of the install disk read in preflight
+    #[serde(default)]
+    pub disk_health: Option<DiskHealthCheck>,
+    /// Weighted go/no-go score computed before the install disk was wiped
+    #[serde(default)]
+    pub health_score: Option<HealthScore>,
+    /// Time budget and the phases that used it, when the target config set one
+    #[serde(default)]
+    pub budget: Option<BudgetUsage>,
+    /// Differences from the host's hardware baseline found before the install started
+    #[serde(default)]
+    pub hardware_changes: Vec<HardwareChange>,
+    /// Service probes checked by the last `verify` of the booted host
+    #[serde(default)]
+    pub probe_results: Vec<ProbeResult>,
+}
+
+impl InstallSession {
+    /// Start a new running session for `hostname`
+    pub fn new(hostname: &str) -> Self {
+        let now = Utc::now();
+        Self {
+            schema_version: SESSION_SCHEMA_VERSION.to_string(),
+            id: uuid::Uuid::new_v4().to_string(),
+            hostname: hostname.to_string(),
+            status: SessionStatus::Running,
+            started_at: now,
+            updated_at: now,
+            completed_phases: Vec::new(),
+            failed_phases: Vec::new(),
+            current_phase: None,
+            last_command: None,
+            config_checksum: None,
+            apt_snapshot: None,
+            phase_timings: Vec::new(),
+            warnings: Vec::new(),
+            compliance: Vec::new(),
+            zfs_tuning: None,
+            hardware_inventory: None,
+            mirror_selection: None,
+            release_upgrade: None,
+            plan: None,
+            late_commands: Vec::new(),
+            disk_health: None,
+            health_score: None,
+            budget: None,
+            hardware_changes: Vec::new(),
+            probe_results: Vec::new(),
+        }
+    }
+
+    /// Close the running phase (if any) and start timing `name`
+    pub fn start_phase(&mut self, name: &str) {
+        self.end_phase();
+        self.phase_timings.push(PhaseTiming {
+            name: name.to_string(),
+            started_at: Utc::now(),
+            finished_at: None,
+        });
+    }
+
+    /// Record the end time of the running phase
+    pub fn end_phase(&mut self) {
+        if let Some(timing) = self.phase_timings.last_mut() {
+            if timing.finished_at.is_none() {
+                timing.finished_at = Some(Utc::now());
+            }
+        }
+    }
+
+    /// Total time from start until the last record update
+    pub fn elapsed(&self) -> chrono::Duration {
+        self.updated_at - self.started_at
+    }
+
+    /// Directory holding logs and session records for `hostname`
+    pub fn host_dir(base_dir: &Path, hostname: &str) -> PathBuf {
+        base_dir.join("logs").join(hostname)
+    }
+
+    /// Path of the session record for `hostname`
+    pub fn record_path(base_dir: &Path, hostname: &str) -> PathBuf {
+        Self::host_dir(base_dir, hostname).join("session.json")
+    }
+
+    /// Write the record to `logs/<hostname>/session.json` under `base_dir`
+    pub fn save(&mut self, base_dir: &Path) -> Result<PathBuf> {
+        self.save_as(base_dir, "session.json")
+    }
+
+    /// Write the record to `logs/<hostname>/<file_name>`, for runs other than the install
+    pub fn save_as(&mut self, base_dir: &Path, file_name: &str) -> Result<PathBuf> {
+        self.updated_at = Utc::now();
+        let path = Self::host_dir(base_dir, &self.hostname).join(file_name);
+        if let Some(parent) = path.parent() {
+            std::fs::create_dir_all(parent)?;
+        }
+        std::fs::write(
+            &path,
+            redact::scrub(&serde_json::to_string_pretty(self)?).as_bytes(),
+        )?;
+        Ok(path)
+    }
+
+    /// Load the last session record for `hostname` under `base_dir`
+    pub fn load(base_dir: &Path, hostname: &str) -> Result<Self> {
+        let path = Self::record_path(base_dir, hostname);
+        let content = std::fs::read_to_string(&path).map_err(|e| {
+            crate::error::AutoInstallError::ConfigError(format!(
+                "No session record at {}: {}",
+                path.display(),
+                e
+            ))
+        })?;
+        let session: Self = serde_json::from_str(&content)?;
+        session.check_schema_version()?;
+        Ok(session)
+    }
+
+    /// Refuse records written by a newer major schema version
+    pub fn check_schema_version(&self) -> Result<()> {
+        let supported = schema_major(SESSION_SCHEMA_VERSION).unwrap_or(1);
+        match schema_major(&self.schema_version) {
+            Some(major) if major <= supported => Ok(()),
+            _ => Err(crate::error::AutoInstallError::ConfigError(format!(
+                "Session record for {} uses schema {}, this build reads up to {}.x",
+                self.hostname, self.schema_version, supported
+            ))),
+        }
+    }
+
+    /// Human-readable lines describing where the session stopped
+    pub fn shutdown_summary(&self) -> Vec<String> {
+        let mut lines = vec![
+            format!("Session: {} ({:?})", self.id, self.status),
+            format!("Host: {}", self.hostname),
+            format!(
+                "Completed phases: {}",
+                if self.completed_phases.is_empty() {
+                    "none".to_string()
+                } else {
+                    self.completed_phases.join(", ")
+                }
+            ),
+        ];
+        if let Some(phase) = &self.current_phase {
+            lines.push(format!("Stopped: {}", phase));
+        }
+        if let Some(cmd) = &self.last_command {
+            lines.push(format!("Last remote command: {}", cmd));
+        }
+        for failed in &self.failed_phases {
+            lines.push(format!("Failed: {}", failed));
+        }
+        lines
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::TempDir;
+
+    #[test]
+    fn test_save_and_load_roundtrip() {
+        let dir = TempDir::new().unwrap();
+        let mut session = InstallSession::new("host-a");
+        session
+            .completed_phases
+            .push("Phase 0: Setup variables".into());
+        session.status = SessionStatus::Cancelled;
+        let path = session.save(dir.path()).unwrap();
+        assert!(path.ends_with("logs/host-a/session.json"));
+
+        let loaded = InstallSession::load(dir.path(), "host-a").unwrap();
+        assert_eq!(loaded.id, session.id);
+        assert_eq!(loaded.status, SessionStatus::Cancelled);
+        assert_eq!(loaded.completed_phases.len(), 1);
+    }
+
+    #[test]
+    fn test_shutdown_summary_reports_stop_point() {
+        let mut session = InstallSession::new("host-b");
+        session.completed_phases = vec!["Phase 0: Setup variables".into()];
+        session.current_phase = Some("during Phase 1: Package installation".into());
+        session.last_command = Some("apt install -y zfsutils-linux".into());
+        let summary = session.shutdown_summary().join("\n");
+        assert!(summary.contains("Stopped: during Phase 1: Package installation"));
+        assert!(summary.contains("Last remote command: apt install -y zfsutils-linux"));
+    }
+
+    #[test]
+    fn test_phase_timings_close_previous_phase() {
+        let mut session = InstallSession::new("host-c");
+        session.start_phase("Phase 0: Setup variables");
+        session.start_phase("Phase 1: Package installation");
+        assert!(session.phase_timings[0].duration().is_some());
+        assert!(session.phase_timings[1].duration().is_none());
+
+        session.end_phase();
+        assert!(session.phase_timings[1].duration().is_some());
+        assert_eq!(session.phase_timings.len(), 2);
+    }
+
+    #[test]
+    fn test_schema_version_compatibility() {
+        let dir = TempDir::new().unwrap();
+        let mut session = InstallSession::new("host-d");
+        assert_eq!(session.schema_version, SESSION_SCHEMA_VERSION);
+        let path = session.save(dir.path()).unwrap();
+
+        // Records from before versioning load as 1.0
+        let mut value: serde_json::Value =
+            serde_json::from_str(&std::fs::read_to_string(&path).unwrap()).unwrap();
+        value.as_object_mut().unwrap().remove("schema_version");
+        std::fs::write(&path, value.to_string()).unwrap();
+        assert_eq!(
+            InstallSession::load(dir.path(), "host-d")
+                .unwrap()
+                .schema_version,
+            "1.0"
+        );
+
+        // Unknown fields from a newer minor are ignored, a newer major is refused
+        value["schema_version"] = "1.9".into();
+        value["future_field"] = true.into();
+        std::fs::write(&path, value.to_string()).unwrap();
+        assert!(InstallSession::load(dir.path(), "host-d").is_ok());
+        value["schema_version"] = "2.0".into();
+        std::fs::write(&path, value.to_string()).unwrap();
+        assert!(InstallSession::load(dir.path(), "host-d").is_err());
+    }
+
+    #[test]
+    fn test_load_missing_record_errors() {
+        let dir = TempDir::new().unwrap();
+        assert!(InstallSession::load(dir.path(), "nope").is_err());
+    }
+}
@@ -0,0 +1,384 @@
+// file: src/network/ssh_installer/storage_expand.rs
+// version: 1.2.0
+// guid: 5e2b8c47-1d9a-4f36-b7e0-c4a19d6f2e85
+
+//! Growing the root pool of an installed host into new disk space (`storage expand`)
+//!
+//! After a disk is replaced with a bigger one or a VM disk is resized, the space sits behind
+//! the end of the GPT. Expanding moves the backup GPT header to the new end of the disk,
+//! recreates the LUKS partition with the same start, type, GUID and name but ending at the last
+//! usable sector, tells the kernel about the new size with `partx`, grows the LUKS mapping and
+//! finally lets ZFS take the space with `zpool online -e`. The LUKS partition must be the last
+//! one on the disk; nothing is moved.
+
+use crate::error::AutoInstallError;
+use crate::network::SshClient;
+use crate::utils::block_device::partition_path;
+use crate::Result;
+use tracing::info;
+
+/// Smallest growth worth expanding for
+pub const MIN_GROWTH_BYTES: u64 = 64 * 1024 * 1024;
+/// Sectors the backup GPT (128 entries and the header) takes at the end of the disk
+const BACKUP_GPT_SECTORS: u64 = 33;
+
+/// Where the LUKS mapping behind the pool lives
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct LuksBacking {
+    pub disk: String,
+    pub partition: u32,
+    /// `key location: keyring`, where `cryptsetup resize` needs the passphrase again
+    pub key_in_keyring: bool,
+}
+
+/// The parts of `sgdisk -i` a recreated partition has to keep
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct PartitionInfo {
+    pub type_guid: String,
+    pub unique_guid: String,
+    pub first_sector: u64,
+    pub last_sector: u64,
+    pub name: String,
+}
+
+/// Capacity found on the host and what expanding would do with it
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ExpansionPlan {
+    pub mapper: String,
+    pub pool: String,
+    pub backing: LuksBacking,
+    pub partition: PartitionInfo,
+    pub sector_size: u64,
+    pub disk_bytes: u64,
+    pub pool_bytes: u64,
+    /// Space past the end of the partition, less the backup GPT
+    pub growth_bytes: u64,
+}
+
+impl ExpansionPlan {
+    /// `/dev/nvme0n1p4` or `/dev/sda4`
+    pub fn partition_device(&self) -> String {
+        partition_path(&self.backing.disk, self.backing.partition)
+    }
+
+    pub fn worth_expanding(&self) -> bool {
+        self.growth_bytes >= MIN_GROWTH_BYTES
+    }
+
+    /// What was found, for the dry-run output
+    pub fn summary_lines(&self) -> Vec<String> {
+        vec![
+            format!(
+                "disk {}: {}",
+                self.backing.disk,
+                format_bytes(self.disk_bytes)
+            ),
+            format!(
+                "partition {}: sectors {}-{} ({})",
+                self.partition_device(),
+                self.partition.first_sector,
+                self.partition.last_sector,
+                format_bytes(
+                    (self.partition.last_sector + 1 - self.partition.first_sector)
+                        * self.sector_size
+                )
+            ),
+            format!("pool {}: {}", self.pool, format_bytes(self.pool_bytes)),
+            format!("unallocated: {}", format_bytes(self.growth_bytes)),
+        ]
+    }
+
+    /// Commands that grow the partition, the LUKS mapping and the pool, in order. A
+    /// passphrase is only piped to `cryptsetup resize` when the key lives in the keyring.
+    pub fn commands(&self, passphrase: Option<&str>) -> Vec<String> {
+        let disk = &self.backing.disk;
+        let n = self.backing.partition;
+        let p = &self.partition;
+        let resize = match (self.backing.key_in_keyring, passphrase) {
+            (true, Some(key)) => format!(
+                "printf '%s' '{}' | cryptsetup resize --key-file=- {}",
+                key, self.mapper
+            ),
+            _ => format!("cryptsetup resize {}", self.mapper),
+        };
+        vec![
+            format!("sgdisk -e {}", disk),
+            format!(
+                "sgdisk --delete={n} --new={n}:{}:0 --typecode={n}:{} --partition-guid={n}:{} --change-name={n}:'{}' {}",
+                p.first_sector,
+                p.type_guid,
+                p.unique_guid,
+                p.name,
+                disk,
+                n = n
+            ),
+            format!("partx --update --nr {} {}", n, disk),
+            resize,
+            format!("zpool online -e {} /dev/mapper/{}", self.pool, self.mapper),
+        ]
+    }
+}
+
+/// Split `/dev/nvme0n1p4` into `/dev/nvme0n1` and 4
+pub fn split_partition_device(device: &str) -> Option<(String, u32)> {
+    let digits = device.len() - device.trim_end_matches(|c: char| c.is_ascii_digit()).len();
+    if digits == 0 {
+        return None;
+    }
+    let (disk, number) = device.split_at(device.len() - digits);
+    let number = number.parse().ok()?;
+    let disk = match disk.strip_suffix('p') {
+        Some(stripped) if stripped.ends_with(|c: char| c.is_ascii_digit()) => stripped,
+        _ => disk,
+    };
+    (!disk.is_empty()).then(|| (disk.to_string(), number))
+}
+
+/// Backing device of a mapping from `cryptsetup status`
+pub fn parse_cryptsetup_status(output: &str) -> Option<LuksBacking> {
+    let field = |name: &str| {
+        output.lines().find_map(|line| {
+            let (key, value) = line.trim().split_once(':')?;
+            (key.trim() == name).then(|| value.trim().to_string())
+        })
+    };
+    let (disk, partition) = split_partition_device(&field("device")?)?;
+    Some(LuksBacking {
+        disk,
+        partition,
+        key_in_keyring: field("key location").as_deref() == Some("keyring"),
+    })
+}
+
+/// Partition details from `sgdisk -i N`
+pub fn parse_sgdisk_info(output: &str) -> Option<PartitionInfo> {
+    let field = |name: &str| {
+        output.lines().find_map(|line| {
+            let (key, value) = line.split_once(':')?;
+            (key.trim() == name).then(|| value.trim())
+        })
+    };
+    let first_word = |value: &str| value.split_whitespace().next().map(str::to_string);
+    let sector = |name: &str| field(name).and_then(first_word)?.parse().ok();
+    Some(PartitionInfo {
+        type_guid: field("Partition GUID code").and_then(first_word)?,
+        unique_guid: field("Partition unique GUID").and_then(first_word)?,
+        first_sector: sector("First sector")?,
+        last_sector: sector("Last sector")?,
+        name: field("Partition name")
+            .unwrap_or("")
+            .trim_matches('\'')
+            .to_string(),
+    })
+}
+
+/// Highest last sector of any partition in the `sgdisk -p` table
+pub fn parse_table_end(output: &str) -> Option<u64> {
+    output
+        .lines()
+        .filter_map(|line| {
+            let mut fields = line.split_whitespace();
+            fields.next()?.parse::<u32>().ok()?;
+            fields.next()?.parse::<u64>().ok()?;
+            fields.next()?.parse::<u64>().ok()
+        })
+        .max()
+}
+
+/// First number of a command's output, e.g. `blockdev --getsize64` or `zpool list -Hp -o size`
+pub fn parse_number(output: &str) -> Option<u64> {
+    output.split_whitespace().next()?.parse().ok()
+}
+
+/// Space past a partition ending at `last_sector`, less the backup GPT at the end of the disk
+pub fn growth_bytes(disk_bytes: u64, last_sector: u64, sector_size: u64) -> u64 {
+    disk_bytes.saturating_sub((last_sector + 1 + BACKUP_GPT_SECTORS) * sector_size)
+}
+
+fn format_bytes(bytes: u64) -> String {
+    format!("{:.1} GiB", bytes as f64 / (1024.0 * 1024.0 * 1024.0))
+}
+
+/// Reads the capacity of an installed host and grows its pool into it
+pub struct StorageExpander<'a> {
+    ssh: &'a mut SshClient,
+    mapper: String,
+    pool: String,
+}
+
+impl<'a> StorageExpander<'a> {
+    pub fn new(ssh: &'a mut SshClient, mapper: &str, pool: &str) -> Self {
+        Self {
+            ssh,
+            mapper: mapper.to_string(),
+            pool: pool.to_string(),
+        }
+    }
+
+    async fn number(&mut self, command: &str) -> Result<u64> {
+        let output = self.ssh.execute_with_output(command).await?;
+        parse_number(&output).ok_or_else(|| {
+            AutoInstallError::ValidationError(format!(
+                "Unexpected output from '{}': {}",
+                command,
+                output.trim()
+            ))
+        })
+    }
+
+    /// Pool size in bytes
+    pub async fn pool_size(&mut self) -> Result<u64> {
+        self.number(&format!("zpool list -Hp -o size {}", self.pool))
+            .await
+    }
+
+    /// Detect the disk behind the pool and how much of it is unused
+    pub async fn plan(&mut self) -> Result<ExpansionPlan> {
+        let status = self
+            .ssh
+            .execute_with_output(&format!("cryptsetup status {}", self.mapper))
+            .await?;
+        let backing = parse_cryptsetup_status(&status).ok_or_else(|| {
+            AutoInstallError::ValidationError(format!(
+                "/dev/mapper/{} is not an open LUKS mapping on a partition",
+                self.mapper
+            ))
+        })?;
+        let disk = backing.disk.clone();
+        // Re-read the disk size so a resized VM disk is seen without a reboot
+        let _ = self
+            .ssh
+            .execute(&format!("blockdev --rereadpt {} 2>/dev/null || true", disk))
+            .await;
+        let disk_bytes = self
+            .number(&format!("blockdev --getsize64 {}", disk))
+            .await?;
+        let sector_size = self.number(&format!("blockdev --getss {}", disk)).await?;
+        let info = self
+            .ssh
+            .execute_with_output(&format!("sgdisk -i {} {}", backing.partition, disk))
+            .await?;
+        let partition = parse_sgdisk_info(&info).ok_or_else(|| {
+            AutoInstallError::ValidationError(format!(
+                "Could not read partition {} of {} from sgdisk",
+                backing.partition, disk
+            ))
+        })?;
+        let table = self
+            .ssh
+            .execute_with_output(&format!("sgdisk -p {}", disk))
+            .await?;
+        if parse_table_end(&table).is_some_and(|end| end > partition.last_sector) {
+            return Err(AutoInstallError::ValidationError(format!(
+                "{} is not the last partition on {}; only the last partition can grow",
+                partition_path(&disk, backing.partition),
+                disk
+            )));
+        }
+        let pool_bytes = self.pool_size().await?;
+        let growth_bytes = growth_bytes(disk_bytes, partition.last_sector, sector_size);
+        Ok(ExpansionPlan {
+            mapper: self.mapper.clone(),
+            pool: self.pool.clone(),
+            backing,
+            partition,
+            sector_size,
+            disk_bytes,
+            pool_bytes,
+            growth_bytes,
+        })
+    }
+
+    /// Run the plan's commands and check the pool grew
+    pub async fn expand(&mut self, plan: &ExpansionPlan, passphrase: Option<&str>) -> Result<u64> {
+        if plan.backing.key_in_keyring && passphrase.is_none() {
+            return Err(AutoInstallError::ValidationError(format!(
+                "The volume key of {} is in the kernel keyring, so cryptsetup resize needs the passphrase: pass --passphrase-file",
+                plan.mapper
+            )));
+        }
+        for command in plan.commands(passphrase) {
+            self.ssh.execute(&command).await?;
+        }
+        let grown = self.pool_size().await?;
+        if grown <= plan.pool_bytes {
+            return Err(AutoInstallError::ValidationError(format!(
+                "Pool {} is still {} after expanding",
+                plan.pool,
+                format_bytes(grown)
+            )));
+        }
+        info!(
+            "Pool {} grew from {} to {}",
+            plan.pool,
+            format_bytes(plan.pool_bytes),
+            format_bytes(grown)
+        );
+        Ok(grown)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const STATUS: &str = "/dev/mapper/luks is active and is in use.\n  type:    LUKS2\n  cipher:  aes-xts-plain64\n  keysize: 512 bits\n  key location: keyring\n  device:  /dev/nvme0n1p4\n  sector size:  512\n";
+    const INFO: &str = "Partition GUID code: CA7D7CCB-63ED-4C53-861C-1742536059CC (Linux LUKS)\nPartition unique GUID: 2A1D9C4E-5B7F-4E8A-9D3C-1F6B0E2A7C45\nFirst sector: 4196352 (at 2.0 GiB)\nLast sector: 209715166 (at 100.0 GiB)\nPartition size: 205518815 sectors (98.0 GiB)\nAttribute flags: 0000000000000000\nPartition name: 'luks'\n";
+
+    #[test]
+    fn test_parsers() {
+        assert_eq!(
+            split_partition_device("/dev/nvme0n1p4"),
+            Some(("/dev/nvme0n1".to_string(), 4))
+        );
+        assert_eq!(
+            split_partition_device("/dev/sda4"),
+            Some(("/dev/sda".to_string(), 4))
+        );
+        assert_eq!(split_partition_device("/dev/sda"), None);
+        assert_eq!(partition_path("/dev/vda", 4), "/dev/vda4");
+
+        let backing = parse_cryptsetup_status(STATUS).unwrap();
+        assert_eq!(backing.disk, "/dev/nvme0n1");
+        assert!(backing.key_in_keyring);
+        let info = parse_sgdisk_info(INFO).unwrap();
+        assert_eq!(info.first_sector, 4196352);
+        assert_eq!(info.name, "luks");
+
+        let table = "Number  Start (sector)    End (sector)  Size       Code  Name\n   1            2048         1050623   512.0 MiB   EF00  EFI\n   4         4196352       209715166   98.0 GiB    8309  luks\n";
+        assert_eq!(parse_table_end(table), Some(209715166));
+        assert_eq!(parse_number("107374182400\t0\n"), Some(107374182400));
+
+        // The backup GPT takes 33 sectors of whatever size the disk uses
+        assert_eq!(growth_bytes(1000 * 512, 899, 512), 67 * 512);
+        assert_eq!(growth_bytes(1000 * 4096, 899, 4096), 67 * 4096);
+        assert_eq!(growth_bytes(900 * 4096, 899, 4096), 0);
+    }
+
+    #[test]
+    fn test_plan_commands() {
+        let plan = ExpansionPlan {
+            mapper: "luks".to_string(),
+            pool: "rpool".to_string(),
+            backing: parse_cryptsetup_status(STATUS).unwrap(),
+            partition: parse_sgdisk_info(INFO).unwrap(),
+            sector_size: 512,
+            disk_bytes: 200 * 1024 * 1024 * 1024,
+            pool_bytes: 95 * 1024 * 1024 * 1024,
+            growth_bytes: 100 * 1024 * 1024 * 1024,
+        };
+        assert!(plan.worth_expanding());
+        let commands = plan.commands(Some("secret"));
+        assert_eq!(commands[0], "sgdisk -e /dev/nvme0n1");
+        assert_eq!(
+            commands[1],
+            "sgdisk --delete=4 --new=4:4196352:0 --typecode=4:CA7D7CCB-63ED-4C53-861C-1742536059CC --partition-guid=4:2A1D9C4E-5B7F-4E8A-9D3C-1F6B0E2A7C45 --change-name=4:'luks' /dev/nvme0n1"
+        );
+        assert_eq!(
+            commands[3],
+            "printf '%s' 'secret' | cryptsetup resize --key-file=- luks"
+        );
+        assert_eq!(commands[4], "zpool online -e rpool /dev/mapper/luks");
+        assert_eq!(plan.summary_lines()[3], "unallocated: 100.0 GiB");
+    }
+}
@@ -0,0 +1,404 @@
+// file: src/network/ssh_installer/support_bundle.rs
+// version: 1.1.0
+// guid: 9a4f2c71-e6b8-4d13-8f5a-3b7d0e2c9a64
+
+//! Support bundles for failed sessions
+//!
+//! `support-bundle <session-id>` gathers what is needed to look into a failed install into one
+//! `.tar.gz`: the session records, reports and the remote debug logs fetched at failure time
+//! from `logs/<hostname>/`, the preset and target config with secrets redacted, the plan and
+//! version information. An `index.json` at the top of the archive lists every file. Host keys,
+//! SSH certificates and enrollment tokens kept next to the logs are never included.
+
+use super::session::{InstallSession, SESSION_SCHEMA_VERSION};
+use crate::error::AutoInstallError;
+use crate::Result;
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use std::path::{Path, PathBuf};
+
+/// Name of the listing at the top of a bundle
+pub const INDEX_FILE: &str = "index.json";
+/// Value that replaces secrets in bundled configs
+pub use crate::logging::redact::REDACTED;
+/// Layout version of `index.json`
+const INDEX_VERSION: &str = "1";
+
+/// Key fragments whose values are treated as secrets
+const SECRET_KEYS: &[&str] = &[
+    "password",
+    "passphrase",
+    "secret",
+    "token",
+    "luks_key",
+    "private_key",
+    "api_key",
+    "credential",
+];
+
+/// Files and directories of `logs/<hostname>/` that hold credentials
+const EXCLUDED: &[&str] = &["ssh", "approvals", "enrollment.json"];
+
+/// Extensions of host artifacts worth bundling
+const BUNDLED_EXTENSIONS: &[&str] = &["json", "log", "md", "txt", "html"];
+
+/// Whether the value under `key` is a secret
+pub fn is_secret_key(key: &str) -> bool {
+    let key = key.to_ascii_lowercase();
+    SECRET_KEYS.iter().any(|fragment| key.contains(fragment))
+}
+
+/// Replace every secret in `value` with [`REDACTED`]; returns how many were replaced
+pub fn redact_yaml(value: &mut serde_yaml::Value) -> usize {
+    match value {
+        serde_yaml::Value::Mapping(map) => {
+            let mut count = 0;
+            for (key, value) in map.iter_mut() {
+                let secret = key.as_str().map(is_secret_key).unwrap_or(false);
+                if secret && !value.is_null() && !value.is_mapping() {
+                    *value = serde_yaml::Value::String(REDACTED.to_string());
+                    count += 1;
+                } else {
+                    count += redact_yaml(value);
+                }
+            }
+            count
+        }
+        serde_yaml::Value::Sequence(items) => items.iter_mut().map(redact_yaml).sum(),
+        serde_yaml::Value::Tagged(tagged) => redact_yaml(&mut tagged.value),
+        _ => 0,
+    }
+}
+
+/// Find the session record with `id` (or a unique prefix of it) under `logs/`
+///
+/// A hostname picks that host's last install session.
+pub fn find_session(base_dir: &Path, id: &str) -> Result<(PathBuf, InstallSession)> {
+    let logs = base_dir.join("logs");
+    let mut matches = Vec::new();
+    if logs.is_dir() {
+        for host in std::fs::read_dir(&logs)? {
+            let host = host?.path();
+            if !host.is_dir() {
+                continue;
+            }
+            for entry in std::fs::read_dir(&host)? {
+                let path = entry?.path();
+                if path.extension().and_then(|e| e.to_str()) != Some("json") {
+                    continue;
+                }
+                let Ok(content) = std::fs::read_to_string(&path) else {
+                    continue;
+                };
+                if let Ok(session) = serde_json::from_str::<InstallSession>(&content) {
+                    if session.id == id || (id.len() >= 4 && session.id.starts_with(id)) {
+                        matches.push((path, session));
+                    }
+                }
+            }
+        }
+    }
+    match matches.len() {
+        1 => Ok(matches.remove(0)),
+        0 => {
+            let path = InstallSession::record_path(base_dir, id);
+            let session = InstallSession::load(base_dir, id).map_err(|_| {
+                AutoInstallError::ConfigError(format!("No session {} under {}", id, logs.display()))
+            })?;
+            Ok((path, session))
+        }
+        n => Err(AutoInstallError::ValidationError(format!(
+            "Session prefix {} matches {} sessions; give more of the id",
+            id, n
+        ))),
+    }
+}
+
+/// One file in a bundle
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct BundleEntry {
+    /// Path inside the bundle
+    pub path: String,
+    /// `session`, `report`, `log`, `record`, `config`, `plan` or `version`
+    pub kind: String,
+    pub size_bytes: u64,
+    pub sha256: String,
+    /// Secrets replaced with [`REDACTED`]
+    #[serde(default)]
+    pub redacted_values: usize,
+}
+
+/// `index.json`: what the bundle is about and what it holds
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct BundleIndex {
+    pub index_version: String,
+    pub created_at: DateTime<Utc>,
+    pub tool_version: String,
+    pub session_id: String,
+    pub hostname: String,
+    pub status: String,
+    pub failed_phases: Vec<String>,
+    pub files: Vec<BundleEntry>,
+    /// Things that could not be included
+    pub notes: Vec<String>,
+}
+
+/// A bundle being assembled in a staging directory
+pub struct SupportBundle {
+    dir: PathBuf,
+    index: BundleIndex,
+}
+
+impl SupportBundle {
+    /// Start a bundle for `session` in `staging/<name>`
+    pub fn new(staging: &Path, session: &InstallSession) -> Result<Self> {
+        let dir = staging.join(Self::name(session));
+        std::fs::create_dir_all(&dir)?;
+        Ok(Self {
+            dir,
+            index: BundleIndex {
+                index_version: INDEX_VERSION.to_string(),
+                created_at: Utc::now(),
+                tool_version: env!("CARGO_PKG_VERSION").to_string(),
+                session_id: session.id.clone(),
+                hostname: session.hostname.clone(),
+                status: format!("{:?}", session.status).to_lowercase(),
+                failed_phases: session.failed_phases.clone(),
+                files: Vec::new(),
+                notes: Vec::new(),
+            },
+        })
+    }
+
+    /// Top directory of the archive, e.g. `support-bundle-web-01-3f2a9c1e`
+    pub fn name(session: &InstallSession) -> String {
+        format!(
+            "support-bundle-{}-{}",
+            session.hostname,
+            session.id.chars().take(8).collect::<String>()
+        )
+    }
+
+    pub fn index(&self) -> &BundleIndex {
+        &self.index
+    }
+
+    /// Record something that could not be included
+    pub fn note(&mut self, note: String) {
+        self.index.notes.push(note);
+    }
+
+    /// Add `content` as `path` inside the bundle
+    pub fn add(
+        &mut self,
+        path: &str,
+        kind: &str,
+        content: &[u8],
+        redacted_values: usize,
+    ) -> Result<()> {
+        let target = self.dir.join(path);
+        if let Some(parent) = target.parent() {
+            std::fs::create_dir_all(parent)?;
+        }
+        std::fs::write(&target, content)?;
+        self.index.files.push(BundleEntry {
+            path: path.to_string(),
+            kind: kind.to_string(),
+            size_bytes: content.len() as u64,
+            sha256: format!("{:x}", Sha256::digest(content)),
+            redacted_values,
+        });
+        Ok(())
+    }
+
+    /// Session records, reports and fetched logs from `logs/<hostname>/`
+    pub fn add_host_artifacts(&mut self, host_dir: &Path) -> Result<()> {
+        if !host_dir.is_dir() {
+            self.note(format!("{} does not exist", host_dir.display()));
+            return Ok(());
+        }
+        let mut paths: Vec<PathBuf> = std::fs::read_dir(host_dir)?
+            .filter_map(|entry| entry.ok().map(|e| e.path()))
+            .filter(|path| path.is_file())
+            .collect();
+        paths.sort();
+        for path in paths {
+            let Some(name) = path.file_name().and_then(|n| n.to_str()) else {
+                continue;
+            };
+            let extension = path.extension().and_then(|e| e.to_str()).unwrap_or("");
+            if EXCLUDED.contains(&name) || !BUNDLED_EXTENSIONS.contains(&extension) {
+                continue;
+            }
+            let kind = if name.starts_with("report.") {
+                "report"
+            } else if extension == "log" {
+                "log"
+            } else if name.ends_with("session.json") {
+                "session"
+            } else {
+                "record"
+            };
+            let content = std::fs::read(&path)?;
+            self.add(&format!("logs/{}", name), kind, &content, 0)?;
+        }
+        Ok(())
+    }
+
+    /// Add a YAML config as `config/<name>` with its secrets redacted
+    pub fn add_config(&mut self, name: &str, yaml: &str) -> Result<()> {
+        let mut value: serde_yaml::Value = serde_yaml::from_str(yaml)?;
+        let redacted = redact_yaml(&mut value);
+        let content = serde_yaml::to_string(&value)?;
+        self.add(
+            &format!("config/{}", name),
+            "config",
+            content.as_bytes(),
+            redacted,
+        )
+    }
+
+    /// The plan the session recorded, one step per line
+    pub fn add_plan(&mut self, session: &InstallSession) -> Result<()> {
+        match &session.plan {
+            Some(plan) => {
+                let lines: Vec<String> = plan
+                    .steps
+                    .iter()
+                    .map(|step| format!("[{}] {}\n", step.section, step.line))
+                    .collect();
+                self.add("plan.txt", "plan", lines.concat().as_bytes(), 0)
+            }
+            None => {
+                self.note("The session recorded no plan".to_string());
+                Ok(())
+            }
+        }
+    }
+
+    /// Versions of the tool, the session schema and the controller platform
+    pub fn add_version(&mut self, session: &InstallSession) -> Result<()> {
+        let content = format!(
+            "ubuntu-autoinstall-agent {}\nsession schema {} (record written as {})\ncontroller {} {}\n",
+            env!("CARGO_PKG_VERSION"),
+            SESSION_SCHEMA_VERSION,
+            session.schema_version,
+            std::env::consts::OS,
+            std::env::consts::ARCH
+        );
+        self.add("version.txt", "version", content.as_bytes(), 0)
+    }
+
+    /// Write `index.json` and pack the bundle into the `.tar.gz` at `output`
+    pub fn finish(self, output: &Path) -> Result<PathBuf> {
+        std::fs::write(
+            self.dir.join(INDEX_FILE),
+            serde_json::to_string_pretty(&self.index)?,
+        )?;
+        let (Some(parent), Some(name)) = (self.dir.parent(), self.dir.file_name()) else {
+            return Err(AutoInstallError::SystemError(format!(
+                "Invalid bundle directory {}",
+                self.dir.display()
+            )));
+        };
+        let status = std::process::Command::new("tar")
+            .arg("-czf")
+            .arg(output)
+            .arg("-C")
+            .arg(parent)
+            .arg(name)
+            .status()
+            .map_err(|e| AutoInstallError::ProcessError {
+                command: "tar".to_string(),
+                exit_code: None,
+                stderr: e.to_string(),
+            })?;
+        if !status.success() {
+            return Err(AutoInstallError::ProcessError {
+                command: format!("tar -czf {}", output.display()),
+                exit_code: status.code(),
+                stderr: String::new(),
+            });
+        }
+        Ok(output.to_path_buf())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::network::ssh_installer::plan::{InstallPlan, PlanStep};
+
+    #[test]
+    fn test_redact_yaml() {
+        let mut value: serde_yaml::Value = serde_yaml::from_str(
+            "luks_key: hunter2\nroot_password: toor\nusers:\n  - name: ops\n    password_hash: $6$x\n    ssh_keys: [ssh-ed25519 AAAA]\nprogress:\n  github:\n    token_env: GITHUB_TOKEN\nhostname: web-01\n",
+        )
+        .unwrap();
+        assert_eq!(redact_yaml(&mut value), 4);
+        assert_eq!(value["luks_key"], REDACTED);
+        assert_eq!(value["users"][0]["password_hash"], REDACTED);
+        assert_eq!(value["users"][0]["ssh_keys"][0], "ssh-ed25519 AAAA");
+        assert_eq!(value["hostname"], "web-01");
+    }
+
+    #[test]
+    fn test_bundle_contents() {
+        let dir = tempfile::tempdir().unwrap();
+        let mut session = InstallSession::new("web-01");
+        session.failed_phases = vec!["Phase 3: ZFS creation - zpool create failed".to_string()];
+        session.plan = Some(InstallPlan {
+            steps: vec![PlanStep {
+                section: "system".to_string(),
+                line: "hostname: web-01".to_string(),
+            }],
+        });
+        session.save(dir.path()).unwrap();
+        let host_dir = InstallSession::host_dir(dir.path(), "web-01");
+        std::fs::write(host_dir.join("install-debug-1700000000.log"), "dmesg").unwrap();
+        std::fs::write(host_dir.join("enrollment.json"), "{}").unwrap();
+        std::fs::create_dir_all(host_dir.join("ssh")).unwrap();
+
+        let (path, found) = find_session(dir.path(), &session.id[..8]).unwrap();
+        assert_eq!(path, InstallSession::record_path(dir.path(), "web-01"));
+        assert_eq!(found.id, session.id);
+        assert_eq!(find_session(dir.path(), "web-01").unwrap().1.id, session.id);
+        assert!(find_session(dir.path(), "nope").is_err());
+
+        let mut bundle = SupportBundle::new(&dir.path().join("staging"), &found).unwrap();
+        bundle.add_host_artifacts(&host_dir).unwrap();
+        bundle
+            .add_config("preset.yaml", "luks_key: hunter2\n")
+            .unwrap();
+        bundle.add_plan(&found).unwrap();
+        bundle.add_version(&found).unwrap();
+        let paths: Vec<&str> = bundle
+            .index()
+            .files
+            .iter()
+            .map(|f| f.path.as_str())
+            .collect();
+        assert_eq!(
+            paths,
+            vec![
+                "logs/install-debug-1700000000.log",
+                "logs/session.json",
+                "config/preset.yaml",
+                "plan.txt",
+                "version.txt",
+            ]
+        );
+        assert_eq!(bundle.index().files[2].redacted_values, 1);
+        assert_eq!(bundle.index().status, "running");
+        assert_eq!(bundle.index().failed_phases.len(), 1);
+        let staged = dir.path().join("staging").join(SupportBundle::name(&found));
+        assert_eq!(
+            std::fs::read_to_string(staged.join("plan.txt")).unwrap(),
+            "[system] hostname: web-01\n"
+        );
+        assert!(!std::fs::read_to_string(staged.join("config/preset.yaml"))
+            .unwrap()
+            .contains("hunter2"));
+    }
+}
@@ -1,53 +1,136 @@
 // file: src/network/ssh_installer/system_setup.rs
-// version: 1.16.3
+// version: 1.42.0
 // guid: sshsys01-2345-6789-abcd-ef0123456789
 
 //! System setup and configuration for SSH installation
 
+use super::apt_lock::wait_for_apt_locks;
+use super::capabilities::{self, EspDetection, TargetCapabilities};
 use super::config::InstallationConfig;
+use super::network_recovery::{NetworkRecoveryStrategy, OLD_RELEASES_MIRROR};
+use super::package_txn::{transaction_error, PackageTransaction};
+use crate::config::apt_snapshot::{build_deb822_sources, build_legacy_sources};
+use crate::config::bootloader::{check_loader_entries, LOADER_ENTRIES_DIR};
+use crate::config::mirrors::UBUNTU_ARCHIVE;
+use crate::config::packages::{packages_for_roles, PackageRole};
+use crate::config::ubuntu_pro::ProAttachTiming;
+use crate::config::zfs_pools::PoolLayout;
+use crate::config::zfs_tuning::ZfsTuning;
+use crate::config::AptLockConfig;
+use crate::error::AutoInstallError;
 use crate::network::SshClient;
+use crate::utils::block_device::partition_path;
 use crate::Result;
 use tracing::{info, warn};
 
+/// Create a zfs-list.cache file for every imported pool, whatever the pools are named
+pub const ZFS_LIST_CACHE_TOUCH: &str =
+    "bash -lc 'for p in $(zpool list -H -o name); do touch /mnt/targetos/etc/zfs/zfs-list.cache/$p; done'";
+
 pub struct SystemConfigurator<'a> {
     ssh: &'a mut SshClient,
+    package_transactions: bool,
+    capabilities: Option<TargetCapabilities>,
+    /// Repairs and mirror rotation for the chroot apt commands
+    apt_recovery: Option<NetworkRecoveryStrategy>,
+    /// Waiting for apt/dpkg locks in the target before chroot apt commands
+    apt_lock: AptLockConfig,
+    /// Install the base packages one role at a time (low-memory mode)
+    serialized_packages: bool,
 }
 
 impl<'a> SystemConfigurator<'a> {
     pub fn new(ssh: &'a mut SshClient) -> Self {
-        Self { ssh }
+        Self {
+            ssh,
+            package_transactions: false,
+            capabilities: None,
+            apt_recovery: None,
+            apt_lock: AptLockConfig::default(),
+            serialized_packages: false,
+        }
+    }
+
+    /// Guard the chroot package step with a snapshot and dpkg state record
+    pub fn with_package_transactions(mut self, enabled: bool) -> Self {
+        self.package_transactions = enabled;
+        self
+    }
+
+    /// Install the base packages in one apt run per package role, so dpkg and the initramfs
+    /// hooks work on a few packages at a time
+    pub fn with_serialized_packages(mut self, enabled: bool) -> Self {
+        self.serialized_packages = enabled;
+        self
+    }
+
+    /// Pick command variants for the probed live environment instead of assuming current tools
+    pub fn with_capabilities(mut self, capabilities: Option<TargetCapabilities>) -> Self {
+        self.capabilities = capabilities;
+        self
     }
 
     /// Build the command used to detect the ESP partition by GUID
-    fn build_esp_detection_command(guid: &str) -> String {
-        // Use lsblk key=value format (-P) and grep/sed to extract PATH for the matching PARTTYPE.
-        // Safe quoting: outer bash uses double quotes; sed uses single quotes containing double quotes.
-        format!(
-            "bash -lc 'lsblk -rP -o PATH,PARTTYPE | grep -i \"PARTTYPE=\\\"{0}\\\"\" | head -n1 | sed -n \"s/.*PATH=\\\"\\([^\\\" ]*\\)\\\".*/\\1/p\"'",
-            guid
-        )
+    fn build_esp_detection_command(detection: EspDetection, guid: &str) -> String {
+        // lsblk key=value format (-P) with grep/sed, or blkid on images whose lsblk is too old
+        detection.command(guid)
     }
 
-    /// Build Deb822-style Ubuntu apt sources content for the given release
-    fn build_apt_deb822_sources(release: &str) -> String {
-        format!(
-            "Types: deb\nURIs: http://archive.ubuntu.com/ubuntu/\nSuites: {rel}\nComponents: main restricted universe multiverse\nSigned-By: /usr/share/keyrings/ubuntu-archive-keyring.gpg\n\nTypes: deb\nURIs: http://security.ubuntu.com/ubuntu\nSuites: {rel}-security\nComponents: main restricted universe multiverse\nSigned-By: /usr/share/keyrings/ubuntu-archive-keyring.gpg\n",
-            rel = release
-        )
+    /// Build the debootstrap command, naming the generic Ubuntu script when debootstrap predates `release`
+    fn build_debootstrap_command(release: &str, mirror: &str, script: Option<&str>) -> String {
+        match script {
+            Some(script) => format!(
+                "debootstrap {} /mnt/targetos {} {}",
+                release, mirror, script
+            ),
+            None => format!("debootstrap {} /mnt/targetos {}", release, mirror),
+        }
+    }
+
+    /// Build the apt command installing the boot, kernel, ZFS and LUKS packages for the target
+    pub(super) fn build_base_package_install_command(config: &InstallationConfig) -> String {
+        Self::build_base_package_install_commands(config, false).remove(0)
+    }
+
+    /// The base package install as one apt command, or one per package role when `serialized`
+    fn build_base_package_install_commands(
+        config: &InstallationConfig,
+        serialized: bool,
+    ) -> Vec<String> {
+        let release = config.release();
+        let roles = config.bootloader.package_roles();
+        let groups: Vec<Vec<String>> = if serialized {
+            roles
+                .iter()
+                .map(|role| packages_for_roles(&[*role], config.architecture, release))
+                .filter(|packages| !packages.is_empty())
+                .collect()
+        } else {
+            vec![packages_for_roles(&roles, config.architecture, release)]
+        };
+        groups
+            .iter()
+            .map(|packages| {
+                format!(
+                    "DEBIAN_FRONTEND=noninteractive apt install -y {}",
+                    packages.join(" ")
+                )
+            })
+            .collect()
     }
 
     /// Build a crypttab entry for the LUKS partition using either a UUID or the raw device
     /// - When `uuid_opt` is Some, use /dev/disk/by-uuid/<uuid>
-    /// - Otherwise, fall back to "{disk}p4"
+    /// - Otherwise, fall back to partition 4 of the disk
     fn build_crypttab_entry(disk_device: &str, uuid_opt: Option<&str>) -> String {
         let dev = if let Some(uuid) = uuid_opt {
             if uuid.trim().is_empty() {
-                format!("{}p4", disk_device)
+                partition_path(disk_device, 4)
             } else {
                 format!("/dev/disk/by-uuid/{}", uuid.trim())
             }
         } else {
-            format!("{}p4", disk_device)
+            partition_path(disk_device, 4)
         };
         format!("luks {} none luks,discard,initramfs", dev)
     }
@@ -56,7 +139,7 @@ impl<'a> SystemConfigurator<'a> {
     fn choose_esp_partition(detected_output: &str, default_disk: &str) -> String {
         let part = detected_output.trim();
         if part.is_empty() {
-            format!("{}p1", default_disk)
+            partition_path(default_disk, 1)
         } else {
             part.to_string()
         }
@@ -66,7 +149,12 @@ impl<'a> SystemConfigurator<'a> {
     async fn detect_esp_partition_path(&mut self, default_disk: &str) -> Result<String> {
         // EFI System Partition type GUID
         let guid = "c12a7328-f81f-11d2-ba4b-00a0c93ec93b";
-        let cmd = Self::build_esp_detection_command(guid);
+        let detection = self
+            .capabilities
+            .as_ref()
+            .map(TargetCapabilities::esp_detection)
+            .unwrap_or(EspDetection::LsblkPath);
+        let cmd = Self::build_esp_detection_command(detection, guid);
         let out = self.ssh.execute_with_output(&cmd).await.unwrap_or_default();
         Ok(Self::choose_esp_partition(&out, default_disk))
     }
@@ -89,28 +177,41 @@ impl<'a> SystemConfigurator<'a> {
         .await?;
 
         // Install base system using debootstrap (codename/mirror configurable)
-        let release = config.debootstrap_release.as_deref().unwrap_or("plucky");
-        let mirror = config
-            .debootstrap_mirror
-            .as_deref()
-            .unwrap_or("http://archive.ubuntu.com/ubuntu/");
-        let primary_cmd = format!("debootstrap {} /mnt/targetos {}", release, mirror);
-        if let Err(_e) = self
-            .log_and_execute("Running debootstrap", &primary_cmd)
-            .await
-        {
-            // Fallback to old-releases if not already using it
-            let fallback_mirror = "http://old-releases.ubuntu.com/ubuntu/";
-            if mirror != fallback_mirror {
-                let fallback_cmd =
-                    format!("debootstrap {} /mnt/targetos {}", release, fallback_mirror);
-                self.log_and_execute("Running debootstrap (fallback old-releases)", &fallback_cmd)
-                    .await?;
-            } else {
-                // Re-raise the original error
-                return Err(_e);
-            }
+        let release = config.release();
+        let mirror = config.effective_mirror();
+        if let Some(snapshot) = &config.apt_snapshot {
+            info!("Installing from archive snapshot {}", snapshot);
+        }
+        let script = self
+            .capabilities
+            .as_ref()
+            .and_then(TargetCapabilities::debootstrap_script);
+        if let Some(script) = script {
+            warn!(
+                "debootstrap has no script for {}; using {}",
+                release, script
+            );
         }
+        // Retry through DNS and routing repairs, then alternate mirrors; a pinned snapshot never
+        // changes mirror
+        let pinned = config.apt_snapshot.is_some();
+        let mut recovery = NetworkRecoveryStrategy::new(&config.network_recovery, &mirror, pinned)
+            .with_fallback(OLD_RELEASES_MIRROR);
+        recovery
+            .run(self.ssh, "Running debootstrap", |mirror| {
+                Self::build_debootstrap_command(release, mirror, script)
+            })
+            .await?;
+        // The target's sources name the archive itself (or the snapshot), not the debootstrap mirror
+        let sources_mirror = match &config.apt_snapshot {
+            Some(snapshot) => snapshot.archive_uri(),
+            None => UBUNTU_ARCHIVE.to_string(),
+        };
+        self.apt_recovery = Some(
+            NetworkRecoveryStrategy::new(&config.network_recovery, &sources_mirror, pinned)
+                .for_target("/mnt/targetos"),
+        );
+        self.apt_lock = config.apt_lock.clone();
 
         // Setup basic system files
         self.setup_basic_system_files(config).await?;
@@ -158,8 +259,41 @@ impl<'a> SystemConfigurator<'a> {
             .await?;
 
         // Configure APT Deb822 sources for Ubuntu (archive + security) inside target
-        let release = config.debootstrap_release.as_deref().unwrap_or("plucky");
-        let ubuntu_sources = Self::build_apt_deb822_sources(release);
+        let release = config.release();
+        let target_apt = capabilities::probe_target_apt(self.ssh).await?;
+        if !capabilities::supports_deb822(target_apt) {
+            let apt_version = target_apt.map(|v| v.to_string()).unwrap_or_default();
+            warn!(
+                "apt {} in the target predates Deb822 sources; writing sources.list",
+                apt_version
+            );
+            let sources = if config.uses_apt_mirrors() {
+                config.apt_mirrors.build_legacy_sources(release)
+            } else {
+                build_legacy_sources(release, config.apt_snapshot.as_ref())
+            };
+            self.ssh
+                .execute(&format!(
+                    "cat > /mnt/targetos/etc/apt/sources.list << 'EOF'\n{}EOF",
+                    sources
+                ))
+                .await?;
+            self.apply_apt_repos(config, true).await?;
+            return Ok(());
+        }
+        let ubuntu_sources = if config.uses_apt_mirrors() {
+            info!(
+                "apt sources with {} mirror(s), {} first",
+                config.apt_mirrors.mirrors.len(),
+                config.apt_mirrors.primary().unwrap_or_default()
+            );
+            for cmd in config.apt_mirrors.build_apply_commands("/mnt/targetos") {
+                self.ssh.execute(&cmd).await?;
+            }
+            config.apt_mirrors.build_deb822_sources(release)
+        } else {
+            build_deb822_sources(release, config.apt_snapshot.as_ref())
+        };
         self.ssh
             .execute("mkdir -p /mnt/targetos/etc/apt/sources.list.d")
             .await?;
@@ -174,10 +308,35 @@ impl<'a> SystemConfigurator<'a> {
             .ssh
             .execute("rm -f /mnt/targetos/etc/apt/sources.list || true")
             .await;
+        self.apply_apt_repos(config, false).await?;
 
         Ok(())
     }
 
+    /// Install the third-party repositories' verified keyrings and sources; runs before the
+    /// first `apt update` in the chroot so their packages can be installed
+    async fn apply_apt_repos(&mut self, config: &InstallationConfig, legacy: bool) -> Result<()> {
+        if !config.apt_repos.is_enabled() {
+            return Ok(());
+        }
+        info!(
+            "Adding {} third-party apt repositories",
+            config.apt_repos.repositories.len()
+        );
+        let commands = if legacy {
+            config
+                .apt_repos
+                .build_legacy_apply_commands("/mnt/targetos")
+        } else {
+            config.apt_repos.build_apply_commands("/mnt/targetos")
+        };
+        for cmd in commands {
+            self.log_and_execute("Third-party apt repository", &cmd)
+                .await?;
+        }
+        Ok(())
+    }
+
     /// Setup network configuration
     async fn setup_network_configuration(&mut self, config: &InstallationConfig) -> Result<()> {
         info!("Setting up network configuration");
@@ -319,26 +478,30 @@ impl<'a> SystemConfigurator<'a> {
         ).await;
 
         // Install essential packages
-        let chroot_commands = vec![
-            "apt update",
-            // Core UEFI + ZFS packages
-            "DEBIAN_FRONTEND=noninteractive apt install -y grub-efi-amd64 grub-efi-amd64-signed linux-image-generic shim-signed zfs-initramfs zfsutils-linux zsys efibootmgr cryptsetup cryptsetup-initramfs dosfstools",
+        let base_packages =
+            Self::build_base_package_install_commands(config, self.serialized_packages);
+        let release = config.release();
+        let headers = format!(
+            "DEBIAN_FRONTEND=noninteractive apt install -y {}",
+            packages_for_roles(&[PackageRole::KernelHeaders], config.architecture, release)
+                .join(" ")
+        );
+        let mut chroot_commands = vec!["apt update"];
+        // Core UEFI + ZFS packages
+        chroot_commands.extend(base_packages.iter().map(String::as_str));
+        chroot_commands.extend([
             // Helpful tooling
-            "DEBIAN_FRONTEND=noninteractive apt install -y linux-headers-generic",
+            headers.as_str(),
             "DEBIAN_FRONTEND=noninteractive apt install -y openssh-server vim htop curl",
             // Reduce probing noise and set up common groups (best-effort)
             "DEBIAN_FRONTEND=noninteractive apt purge -y os-prober || true",
             "addgroup --system lpadmin || true",
             "addgroup --system lxd || true",
             "addgroup --system sambashare || true",
-        ];
+        ]);
 
-        for cmd in chroot_commands {
-            let desc = format!("Chroot: {}", cmd);
-            let wrapped = format!("chroot /mnt/targetos bash -lc '{}'", cmd);
-            // Use tolerant runner to ignore benign zsys errors during apt operations
-            self.run_tolerating_zsys_errors(&desc, &wrapped).await?;
-        }
+        self.run_package_step(&config.pool_layout()?, &chroot_commands)
+            .await?;
 
         // Generate /etc/hostid to aid ZFS import on boot (prefer zgenhostid, fallback to hostid)
         let _ = self.log_and_execute(
@@ -409,7 +572,9 @@ impl<'a> SystemConfigurator<'a> {
                 "mkdir -p /mnt/targetos/etc/zfs/zfs-list.cache",
             )
             .await;
-        let _ = self.log_and_execute("Touch zfs-list.cache files", "bash -lc 'touch /mnt/targetos/etc/zfs/zfs-list.cache/bpool /mnt/targetos/etc/zfs/zfs-list.cache/rpool'").await;
+        let _ = self
+            .log_and_execute("Touch zfs-list.cache files", ZFS_LIST_CACHE_TOUCH)
+            .await;
         let _ = self
             .log_and_execute(
                 "Populate zfs-list via zed",
@@ -433,10 +598,8 @@ impl<'a> SystemConfigurator<'a> {
         Ok(())
     }
 
-    /// Configure GRUB in chroot
-    pub async fn configure_grub_in_chroot(&mut self, config: &InstallationConfig) -> Result<()> {
-        info!("Configuring GRUB in chroot");
-
+    /// Chroot mounts, ESP and efivarfs a bootloader install needs in the target
+    async fn prepare_bootloader_chroot(&mut self, config: &InstallationConfig) -> Result<()> {
         // Re-ensure chroot runtime mounts are present (in case a prior phase changed mount state)
         let _ = self.log_and_execute(
             "Rebind /dev (rbind)",
@@ -484,6 +647,50 @@ impl<'a> SystemConfigurator<'a> {
             "chroot /mnt/targetos bash -lc '[ -d /sys/firmware/efi/efivars ] || mkdir -p /sys/firmware/efi/efivars; mountpoint -q /sys/firmware/efi/efivars || mount -t efivarfs efivarfs /sys/firmware/efi/efivars || true'"
         ).await;
 
+        Ok(())
+    }
+
+    /// Install systemd-boot to the ESP, copy the kernels there and check the loader entries
+    pub async fn configure_systemd_boot_in_chroot(
+        &mut self,
+        config: &InstallationConfig,
+    ) -> Result<()> {
+        info!("Configuring systemd-boot in chroot");
+        self.prepare_bootloader_chroot(config).await?;
+
+        let mut kernel_args = config.headless.kernel_args();
+        kernel_args.extend(config.nbde.kernel_args());
+        kernel_args.extend(config.performance.kernel_args());
+        for cmd in config.bootloader.build_systemd_boot_commands(
+            "/mnt/targetos",
+            "/boot/efi",
+            &kernel_args,
+        ) {
+            self.log_and_execute("Installing systemd-boot", &cmd)
+                .await?;
+        }
+        self.verify_loader_entries().await
+    }
+
+    /// Check that systemd-boot has a loader entry booting the ZFS root
+    pub async fn verify_loader_entries(&mut self) -> Result<()> {
+        let entries = self
+            .ssh
+            .execute_with_output(&format!(
+                "cat /mnt/targetos/boot/efi/{}/*.conf 2>/dev/null || true",
+                LOADER_ENTRIES_DIR
+            ))
+            .await?;
+        let usable = check_loader_entries(&entries)?;
+        info!("systemd-boot loader entries: {} usable", usable);
+        Ok(())
+    }
+
+    /// Configure GRUB in chroot
+    pub async fn configure_grub_in_chroot(&mut self, config: &InstallationConfig) -> Result<()> {
+        info!("Configuring GRUB in chroot");
+        self.prepare_bootloader_chroot(config).await?;
+
         // Update GRUB configuration - try normal path first, then --no-nvram, then --removable as last resort
         if let Err(_e) = self.log_and_execute(
             "Installing GRUB to ESP",
@@ -511,12 +718,211 @@ impl<'a> SystemConfigurator<'a> {
         Ok(())
     }
 
+    /// Write sysctl.d, modules-load.d and modprobe.d files from the `kernel` config into the target
+    pub async fn apply_kernel_config(&mut self, config: &InstallationConfig) -> Result<()> {
+        if config.kernel.is_empty() {
+            return Ok(());
+        }
+        info!("Applying kernel tuning in chroot");
+        config.kernel.validate()?;
+        for cmd in config.kernel.build_apply_commands("/mnt/targetos") {
+            self.log_and_execute("Kernel tuning", &cmd).await?;
+        }
+        Ok(())
+    }
+
+    /// Write the chosen ZFS module parameters and rebuild the initramfs
+    pub async fn apply_zfs_tuning(&mut self, tuning: &ZfsTuning) -> Result<()> {
+        if tuning.is_empty() {
+            return Ok(());
+        }
+        info!("Applying ZFS tuning in chroot: {}", tuning.summary());
+        for cmd in tuning.build_apply_commands("/mnt/targetos") {
+            self.log_and_execute("ZFS tuning", &cmd).await?;
+        }
+        Ok(())
+    }
+
+    /// Apply the target's hardening profile (sshd, auditd, password policy, umask, sysctls)
+    pub async fn apply_hardening(&mut self, config: &InstallationConfig) -> Result<()> {
+        if !config.hardening.is_enabled() {
+            return Ok(());
+        }
+        info!(
+            "Applying {:?} hardening in chroot",
+            config.hardening.profile
+        );
+        for cmd in config.hardening.build_apply_commands("/mnt/targetos") {
+            self.log_and_execute("Hardening", &cmd).await?;
+        }
+        Ok(())
+    }
+
+    /// Write serial console, watchdog and RTC settings; must precede `configure_grub_in_chroot`
+    pub async fn apply_headless(&mut self, config: &InstallationConfig) -> Result<()> {
+        if config.headless.is_empty() {
+            return Ok(());
+        }
+        info!("Applying serial console, watchdog and RTC settings in chroot");
+        for cmd in config.headless.build_apply_commands("/mnt/targetos") {
+            self.log_and_execute("Headless", &cmd).await?;
+        }
+        Ok(())
+    }
+
+    /// Write the performance preset's GRUB snippet, sysctls and zram setup; must precede
+    /// `configure_grub_in_chroot`
+    pub async fn apply_performance(&mut self, config: &InstallationConfig) -> Result<()> {
+        let Some(preset) = config.performance.preset else {
+            return Ok(());
+        };
+        info!(
+            "Applying the {} performance preset in chroot",
+            preset.as_str()
+        );
+        for cmd in config.performance.build_apply_commands("/mnt/targetos") {
+            self.log_and_execute("Performance preset", &cmd).await?;
+        }
+        Ok(())
+    }
+
+    /// Install the entropy daemon matching `hwrng` and the first-boot entropy check
+    pub async fn apply_entropy(
+        &mut self,
+        config: &InstallationConfig,
+        hwrng: Option<&str>,
+    ) -> Result<()> {
+        let Some((package, _)) = config.entropy.daemon(hwrng) else {
+            return Ok(());
+        };
+        info!("Installing {} and the first-boot entropy check", package);
+        for cmd in config.entropy.build_apply_commands("/mnt/targetos", hwrng) {
+            self.log_and_execute("Entropy", &cmd).await?;
+        }
+        Ok(())
+    }
+
+    /// Attach the target to Ubuntu Pro now or stage the first-boot attach
+    ///
+    /// The commands carry the token, so they are run without being logged.
+    pub async fn apply_ubuntu_pro(
+        &mut self,
+        config: &InstallationConfig,
+        token: Option<&str>,
+    ) -> Result<()> {
+        let (true, Some(token)) = (config.ubuntu_pro.is_enabled(), token) else {
+            return Ok(());
+        };
+        info!(
+            "Ubuntu Pro: attaching {} ({})",
+            match config.ubuntu_pro.attach {
+                ProAttachTiming::Install => "now",
+                ProAttachTiming::FirstBoot => "on first boot",
+            },
+            config.ubuntu_pro.services.join(", ")
+        );
+        for cmd in config
+            .ubuntu_pro
+            .build_attach_commands("/mnt/targetos", token)
+        {
+            self.ssh.execute(&cmd).await?;
+        }
+        Ok(())
+    }
+
+    /// Install the target's firewall rules and enable the service for first boot
+    pub async fn apply_firewall(&mut self, config: &InstallationConfig) -> Result<()> {
+        if !config.firewall.is_enabled() {
+            return Ok(());
+        }
+        info!(
+            "Installing {:?} firewall in chroot ({} rules)",
+            config.firewall.backend,
+            config.firewall.rules.len()
+        );
+        for cmd in config.firewall.build_apply_commands("/mnt/targetos") {
+            self.log_and_execute("Firewall", &cmd).await?;
+        }
+        Ok(())
+    }
+
+    /// Write unattended-upgrades, periodic apt and pinning settings into the target
+    pub async fn apply_updates(&mut self, config: &InstallationConfig) -> Result<()> {
+        if !config.updates.is_enabled() {
+            return Ok(());
+        }
+        info!(
+            "Configuring updates in chroot ({} pin(s))",
+            config.updates.pins.len()
+        );
+        for cmd in config.updates.build_apply_commands("/mnt/targetos") {
+            self.log_and_execute("Updates", &cmd).await?;
+        }
+        Ok(())
+    }
+
+    /// Stage the user-data document and the unit applying it on first boot
+    pub async fn apply_user_data(&mut self, config: &InstallationConfig) -> Result<()> {
+        if !config.user_data.is_enabled() {
+            return Ok(());
+        }
+        info!("Staging user-data for the first boot");
+        for cmd in config.user_data.build_apply_commands("/mnt/targetos") {
+            self.log_and_execute("User-data", &cmd).await?;
+        }
+        Ok(())
+    }
+
+    /// Install Clevis and bind the LUKS volume to the configured Tang servers
+    ///
+    /// Must precede `setup_luks_key_in_chroot`, whose initramfs rebuild picks up the Clevis hook.
+    pub async fn apply_nbde(&mut self, config: &InstallationConfig) -> Result<()> {
+        if !config.nbde.is_enabled() {
+            return Ok(());
+        }
+        info!(
+            "Binding LUKS volume to {} Tang server(s), threshold {}",
+            config.nbde.servers.len(),
+            config.nbde.threshold
+        );
+        for cmd in config.nbde.build_install_commands("/mnt/targetos") {
+            self.log_and_execute("NBDE", &cmd).await?;
+        }
+        // The bind command carries the passphrase, so it is deliberately not echoed
+        let device = partition_path(&config.disk_device, 4);
+        self.ssh
+            .execute(
+                &config
+                    .nbde
+                    .build_bind_command("/mnt/targetos", &device, &config.luks_key),
+            )
+            .await
+    }
+
+    /// Check that the Tang servers unlock the LUKS volume the way the initramfs will
+    pub async fn verify_nbde_unlock(&mut self, config: &InstallationConfig) -> Result<()> {
+        if !config.nbde.is_enabled() {
+            return Ok(());
+        }
+        let device = partition_path(&config.disk_device, 4);
+        let command = config.nbde.build_verify_command("/mnt/targetos", &device);
+        info!("Verifying Clevis/Tang unlock of {}", device);
+        if !self.ssh.check_silent(&command).await? {
+            return Err(AutoInstallError::SystemError(format!(
+                "Clevis could not unlock {} through the Tang servers; the installed system would wait for a passphrase at boot",
+                device
+            )));
+        }
+        info!("Clevis/Tang unlock verified");
+        Ok(())
+    }
+
     /// Configure LUKS crypttab in chroot (no keyfile; prompt at boot via initramfs)
     pub async fn setup_luks_key_in_chroot(&mut self, config: &InstallationConfig) -> Result<()> {
         info!("Configuring LUKS crypttab in chroot");
 
         // Discover partition UUID and write crypttab using by-uuid path with recommended options
-        let part = format!("{}p4", config.disk_device);
+        let part = partition_path(&config.disk_device, 4);
         let uuid_out = self
             .ssh
             .execute_with_output(&format!(
@@ -543,7 +949,7 @@ impl<'a> SystemConfigurator<'a> {
     }
 
     /// Final cleanup and unmounting
-    pub async fn final_cleanup(&mut self, _config: &InstallationConfig) -> Result<()> {
+    pub async fn final_cleanup(&mut self, config: &InstallationConfig) -> Result<()> {
         info!("Performing final cleanup");
 
         // Unmount chroot bindings (recursive for rbind mounts)
@@ -569,15 +975,24 @@ impl<'a> SystemConfigurator<'a> {
         )
         .await?;
 
-        // Unmount filesystems
+        // Unmount filesystems (secondary ESPs first, if any)
+        self.log_and_execute(
+            "Unmounting secondary ESPs",
+            "for m in /mnt/targetos/boot/efi[0-9]*; do mountpoint -q \"$m\" && umount \"$m\"; done; true",
+        )
+        .await?;
         self.log_and_execute("Unmounting ESP", "umount /mnt/targetos/boot/efi || true")
             .await?;
 
         // Export ZFS pools
-        self.log_and_execute("Exporting bpool", "zpool export bpool || true")
-            .await?;
-        self.log_and_execute("Exporting rpool", "zpool export rpool || true")
+        let layout = config.pool_layout()?;
+        for pool in [&layout.boot.name, &layout.root.name] {
+            self.log_and_execute(
+                &format!("Exporting {}", pool),
+                &format!("zpool export {} || true", pool),
+            )
             .await?;
+        }
 
         // Unmount and close LUKS if present
         let _ = self
@@ -597,6 +1012,46 @@ impl<'a> SystemConfigurator<'a> {
         Ok(())
     }
 
+    /// Run the chroot package commands, inside a package transaction when enabled
+    async fn run_package_step(&mut self, layout: &PoolLayout, commands: &[&str]) -> Result<()> {
+        let transaction = if self.package_transactions {
+            Some(PackageTransaction::begin(self.ssh, layout).await?)
+        } else {
+            None
+        };
+
+        let mut result = Ok(());
+        for cmd in commands {
+            if cmd.contains("apt ") {
+                if let Err(e) = wait_for_apt_locks(self.ssh, &self.apt_lock, "/mnt/targetos").await
+                {
+                    result = Err(e);
+                    break;
+                }
+            }
+            let desc = format!("Chroot: {}", cmd);
+            let wrapped = format!("chroot /mnt/targetos bash -lc '{}'", cmd);
+            // Both runners ignore benign zsys errors during apt operations
+            result = match self.apt_recovery.as_mut() {
+                Some(recovery) => recovery.run(self.ssh, &desc, |_| wrapped.clone()).await,
+                None => self.run_tolerating_zsys_errors(&desc, &wrapped).await,
+            };
+            if result.is_err() {
+                break;
+            }
+        }
+
+        match (transaction, result) {
+            (None, result) => result,
+            (Some(transaction), Ok(())) => transaction.commit(self.ssh).await,
+            (Some(transaction), Err(e)) => {
+                let outcome = transaction.abort(self.ssh).await;
+                warn!("Package step failed: {}", outcome.describe());
+                Err(transaction_error(e, &outcome))
+            }
+        }
+    }
+
     /// Helper method to log and execute commands
     async fn log_and_execute(&mut self, description: &str, command: &str) -> Result<()> {
         info!("Executing: {} -> {}", description, command);
@@ -621,12 +1076,7 @@ impl<'a> SystemConfigurator<'a> {
                 if code == 0 {
                     Ok(())
                 } else {
-                    let s = stderr.to_lowercase();
-                    let has_zsys = (s.contains("zsys") && s.contains("daemon"))
-                        || s.contains("/run/zsysd.sock")
-                        || s.contains("couldn't connect to zsys daemon");
-
-                    if has_zsys {
+                    if is_benign_zsys_error(&stderr) {
                         warn!(
                             "Ignoring benign zsys error for '{}': exit={} stderr={}",
                             description, code, stderr
@@ -642,14 +1092,39 @@ impl<'a> SystemConfigurator<'a> {
     }
 }
 
+/// Whether `stderr` only complains about zsysd, which does not run in a chroot or container
+pub(super) fn is_benign_zsys_error(stderr: &str) -> bool {
+    let s = stderr.to_lowercase();
+    (s.contains("zsys") && s.contains("daemon"))
+        || s.contains("/run/zsysd.sock")
+        || s.contains("couldn't connect to zsys daemon")
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
 
+    #[test]
+    fn test_build_debootstrap_command_names_fallback_script() {
+        let mirror = "http://archive.ubuntu.com/ubuntu/";
+        assert_eq!(
+            SystemConfigurator::build_debootstrap_command("noble", mirror, None),
+            "debootstrap noble /mnt/targetos http://archive.ubuntu.com/ubuntu/"
+        );
+        assert_eq!(
+            SystemConfigurator::build_debootstrap_command(
+                "plucky",
+                mirror,
+                Some("/usr/share/debootstrap/scripts/gutsy")
+            ),
+            "debootstrap plucky /mnt/targetos http://archive.ubuntu.com/ubuntu/ /usr/share/debootstrap/scripts/gutsy"
+        );
+    }
+
     #[test]
     fn test_build_esp_detection_command_contains_expected_parts() {
         let guid = "c12a7328-f81f-11d2-ba4b-00a0c93ec93b";
-        let cmd = SystemConfigurator::build_esp_detection_command(guid);
+        let cmd = SystemConfigurator::build_esp_detection_command(EspDetection::LsblkPath, guid);
         // Basic sanity of structure
         assert!(
             cmd.starts_with("bash -lc '"),
@@ -676,7 +1151,7 @@ mod tests {
     fn test_choose_esp_partition_falls_back_when_empty() {
         let detected = "  \n\t"; // whitespace only
         let chosen = SystemConfigurator::choose_esp_partition(detected, "/dev/sda");
-        assert_eq!(chosen, "/dev/sdap1");
+        assert_eq!(chosen, "/dev/sda1");
     }
 
     #[test]
@@ -688,7 +1163,7 @@ mod tests {
 
     #[test]
     fn test_build_apt_deb822_sources_plucky() {
-        let s = SystemConfigurator::build_apt_deb822_sources("plucky");
+        let s = build_deb822_sources("plucky", None);
         assert!(s.contains("Types: deb"));
         assert!(s.contains("URIs: http://archive.ubuntu.com/ubuntu/"));
         assert!(s.contains("Suites: plucky"));
@@ -708,12 +1183,12 @@ mod tests {
     #[test]
     fn test_build_crypttab_entry_without_uuid() {
         let e = SystemConfigurator::build_crypttab_entry("/dev/sda", None);
-        assert_eq!(e, "luks /dev/sdap4 none luks,discard,initramfs");
+        assert_eq!(e, "luks /dev/sda4 none luks,discard,initramfs");
     }
 
     #[test]
     fn test_build_crypttab_entry_with_empty_uuid() {
         let e = SystemConfigurator::build_crypttab_entry("/dev/sda", Some("  "));
-        assert_eq!(e, "luks /dev/sdap4 none luks,discard,initramfs");
+        assert_eq!(e, "luks /dev/sda4 none luks,discard,initramfs");
     }
 }
@@ -0,0 +1,456 @@
+// file: src/network/ssh_installer/upgrade.rs
+// version: 1.3.0
+// guid: 1f7c3a95-4d26-4e8b-8a70-c5b9e2d41f06
+
+//! In-place release upgrade of an installed host (`upgrade`)
+//!
+//! Performs the steps of `do-release-upgrade` non-interactively: bring the current release
+//! up to date, switch the Ubuntu apt sources to the new codename, dist-upgrade, reboot and
+//! verify. The root and boot datasets are snapshotted before and after, and a failure after
+//! the first snapshot rolls them back and reboots into the previous release. A boot environment
+//! of the running system is created first, so the old release stays bootable from the GRUB menu
+//! even when a rollback is not possible. Progress is recorded with the same session model as
+//! installs, in `logs/<hostname>/upgrade-session.json`.
+
+use super::boot_env::{self, BootEnvManager};
+use super::config_export::RELEASE_COMMAND;
+use super::package_txn::{build_rollback_commands, build_snapshot_commands};
+use super::session::{InstallSession, SessionStatus};
+use crate::config::zfs_pools::PoolLayout;
+use crate::error::AutoInstallError;
+use crate::network::ssh::RebootWait;
+use crate::network::SshClient;
+use crate::Result;
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeSet;
+use std::path::Path;
+use tracing::{error, info, warn};
+
+/// Session record file for upgrades, next to the install's `session.json`
+pub const UPGRADE_SESSION_FILE: &str = "upgrade-session.json";
+
+/// Ubuntu apt source files whose suite names are switched to the new release
+pub const UBUNTU_SOURCE_FILES: &[&str] = &[
+    "/etc/apt/sources.list",
+    "/etc/apt/sources.list.d/ubuntu.sources",
+];
+
+/// Non-interactive apt-get that keeps locally modified config files
+const APT_GET: &str = "DEBIAN_FRONTEND=noninteractive apt-get -y -o Dpkg::Options::=--force-confdef -o Dpkg::Options::=--force-confold";
+
+/// Failed systemd units, one per line
+const FAILED_UNITS_COMMAND: &str = "systemctl list-units --state=failed --no-legend --plain";
+
+/// What to upgrade to and how to verify it
+#[derive(Debug, Clone)]
+pub struct UpgradeOptions {
+    /// Codename of the release to upgrade to (e.g. `plucky`)
+    pub to_release: String,
+    /// Reboot into the new release and verify it; without it only the packages are upgraded
+    pub reboot: bool,
+    /// Units that must be active after the upgrade, in addition to "no new failed units"
+    pub services: Vec<String>,
+    pub reboot_wait: RebootWait,
+    /// Create a boot environment of the running system before changing anything
+    pub boot_environment: bool,
+}
+
+/// Release upgrade details kept in the session record
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ReleaseUpgrade {
+    pub from_release: String,
+    pub to_release: String,
+    /// Boot environment created before anything changed
+    #[serde(default)]
+    pub boot_environment: Option<String>,
+    /// Snapshot taken before anything changed; the rollback target
+    pub pre_snapshot: Option<String>,
+    /// Snapshot of the verified, upgraded system
+    pub post_snapshot: Option<String>,
+    /// Whether the datasets were rolled back to `pre_snapshot`
+    pub rolled_back: bool,
+    /// Units that failed after the upgrade but not before it
+    pub new_failed_units: Vec<String>,
+}
+
+/// Snapshot name marking `stage` (`pre` or `post`) of the upgrade to `release`
+pub fn snapshot_name(stage: &str, release: &str) -> String {
+    format!("autoinstall-{}-upgrade-{}", stage, release)
+}
+
+/// Switch the Ubuntu sources from `from` to `to`, including the -updates/-security suites
+pub fn build_switch_sources_command(from: &str, to: &str) -> String {
+    let files = UBUNTU_SOURCE_FILES
+        .iter()
+        .map(|f| {
+            format!(
+                "[ ! -f {0} ] || sed -i -E 's/\\b{1}(-[a-z]+)?\\b/{2}\\1/g' {0}",
+                f, from, to
+            )
+        })
+        .collect::<Vec<_>>();
+    files.join(" && ")
+}
+
+/// Commands that upgrade the current release and then switch to `to`, in order
+pub fn build_upgrade_commands(from: &str, to: &str) -> Vec<(&'static str, Vec<String>)> {
+    vec![
+        (
+            "Upgrade: Current release updates",
+            vec![
+                "apt-get update".to_string(),
+                format!("{} dist-upgrade", APT_GET),
+            ],
+        ),
+        (
+            "Upgrade: Release switch",
+            vec![
+                build_switch_sources_command(from, to),
+                "apt-get update".to_string(),
+                format!("{} dist-upgrade", APT_GET),
+                format!("{} autoremove --purge", APT_GET),
+            ],
+        ),
+    ]
+}
+
+/// Unit names from [`FAILED_UNITS_COMMAND`] output
+pub fn parse_failed_units(output: &str) -> BTreeSet<String> {
+    output
+        .lines()
+        .filter_map(|line| {
+            line.trim_start_matches(|c: char| c == '●' || c == '*' || c.is_whitespace())
+                .split_whitespace()
+                .next()
+        })
+        .map(str::to_string)
+        .collect()
+}
+
+/// Drives the upgrade over an SSH session connected as root
+pub struct ReleaseUpgrader<'a> {
+    ssh: &'a mut SshClient,
+    layout: &'a PoolLayout,
+}
+
+impl<'a> ReleaseUpgrader<'a> {
+    pub fn new(ssh: &'a mut SshClient, layout: &'a PoolLayout) -> Self {
+        Self { ssh, layout }
+    }
+
+    async fn current_release(&mut self) -> Result<String> {
+        Ok(self
+            .ssh
+            .execute_with_output(RELEASE_COMMAND)
+            .await?
+            .trim()
+            .to_string())
+    }
+
+    async fn failed_units(&mut self) -> Result<BTreeSet<String>> {
+        Ok(parse_failed_units(
+            &self.ssh.execute_with_output(FAILED_UNITS_COMMAND).await?,
+        ))
+    }
+
+    async fn run_all(&mut self, commands: &[String]) -> Result<()> {
+        for command in commands {
+            self.ssh.execute(command).await?;
+        }
+        Ok(())
+    }
+
+    /// Mark `name` as the running phase and save the record
+    fn begin(session: &mut InstallSession, base_dir: &Path, name: &str) -> Result<()> {
+        info!("{}", name);
+        session.current_phase = Some(name.to_string());
+        session.start_phase(name);
+        session.save_as(base_dir, UPGRADE_SESSION_FILE)?;
+        Ok(())
+    }
+
+    /// Record the outcome of phase `name` and save the record
+    fn finish<T>(
+        &self,
+        session: &mut InstallSession,
+        base_dir: &Path,
+        name: &str,
+        result: Result<T>,
+    ) -> Result<T> {
+        session.end_phase();
+        session.last_command = self.ssh.last_command().map(str::to_string);
+        match &result {
+            Ok(_) => session.completed_phases.push(name.to_string()),
+            Err(e) => session.failed_phases.push(format!("{} - {}", name, e)),
+        }
+        session.save_as(base_dir, UPGRADE_SESSION_FILE)?;
+        result
+    }
+
+    /// Run `commands` in order as phase `name`
+    async fn commands_phase(
+        &mut self,
+        session: &mut InstallSession,
+        base_dir: &Path,
+        name: &str,
+        commands: &[String],
+    ) -> Result<()> {
+        Self::begin(session, base_dir, name)?;
+        let result = self.run_all(commands).await;
+        self.finish(session, base_dir, name, result)
+    }
+
+    /// Source release and the units already failing before anything changes
+    async fn preflight(&mut self, to: &str) -> Result<(String, BTreeSet<String>)> {
+        let from = self.current_release().await?;
+        if from.is_empty() {
+            return Err(AutoInstallError::ValidationError(
+                "Cannot read VERSION_CODENAME from /etc/os-release".to_string(),
+            ));
+        }
+        if from == to {
+            return Err(AutoInstallError::ValidationError(format!(
+                "Host already runs {}",
+                to
+            )));
+        }
+        let (root, boot) = (self.layout.root_container(), self.layout.boot_container());
+        if !self
+            .ssh
+            .check_silent(&format!("zfs list -H {} {} >/dev/null 2>&1", root, boot))
+            .await?
+        {
+            return Err(AutoInstallError::ValidationError(format!(
+                "{} and {} are required for upgrade safety snapshots",
+                root, boot
+            )));
+        }
+        let failed_before = self.failed_units().await?;
+        Ok((from, failed_before))
+    }
+
+    /// Check the booted release, that no unit newly failed and that `services` are active
+    async fn verify(
+        &mut self,
+        to: &str,
+        failed_before: &BTreeSet<String>,
+        services: &[String],
+    ) -> Result<()> {
+        let running = self.current_release().await?;
+        if running != to {
+            return Err(AutoInstallError::InstallationError(format!(
+                "Host booted {} instead of {}",
+                running, to
+            )));
+        }
+        let new_failed: Vec<String> = self
+            .failed_units()
+            .await?
+            .difference(failed_before)
+            .cloned()
+            .collect();
+        let mut inactive = Vec::new();
+        for service in services {
+            if !self
+                .ssh
+                .check_silent(&format!("systemctl is-active --quiet {}", service))
+                .await?
+            {
+                inactive.push(service.clone());
+            }
+        }
+        if new_failed.is_empty() && inactive.is_empty() {
+            return Ok(());
+        }
+        Err(AutoInstallError::InstallationError(format!(
+            "Service check failed: new failed units [{}], inactive services [{}]",
+            new_failed.join(", "),
+            inactive.join(", ")
+        )))
+    }
+
+    /// Roll back to `pre` and reboot into the previous release
+    async fn rollback(&mut self, pre: &str, options: &UpgradeOptions) -> Result<()> {
+        self.run_all(&build_rollback_commands(self.layout, pre))
+            .await?;
+        if options.reboot {
+            self.ssh.reboot_and_wait(&options.reboot_wait).await?;
+        }
+        Ok(())
+    }
+
+    /// Upgrade the connected host, rolling back to the pre-upgrade snapshot on failure
+    ///
+    /// The session is updated and saved after every phase; its final status is Completed or
+    /// Failed, with `release_upgrade.rolled_back` telling whether the host was restored.
+    pub async fn run(
+        &mut self,
+        options: &UpgradeOptions,
+        session: &mut InstallSession,
+        base_dir: &Path,
+    ) -> Result<()> {
+        let to = options.to_release.as_str();
+        let Err(e) = self.upgrade(options, session, base_dir).await else {
+            session.status = SessionStatus::Completed;
+            session.current_phase = None;
+            session.save_as(base_dir, UPGRADE_SESSION_FILE)?;
+            info!("Upgrade to {} completed", to);
+            return Ok(());
+        };
+
+        error!("Upgrade to {} failed: {}", to, e);
+        session.status = SessionStatus::Failed;
+        let pre = session
+            .release_upgrade
+            .as_ref()
+            .and_then(|u| u.pre_snapshot.clone());
+        let Some(pre) = pre else {
+            // Nothing was changed before the failure
+            session.save_as(base_dir, UPGRADE_SESSION_FILE)?;
+            return Err(e);
+        };
+
+        warn!("Rolling back to @{}", pre);
+        const ROLLBACK: &str = "Upgrade: Rollback";
+        Self::begin(session, base_dir, ROLLBACK)?;
+        let result = self.rollback(&pre, options).await;
+        let rollback = self.finish(session, base_dir, ROLLBACK, result);
+        if let Some(upgrade) = session.release_upgrade.as_mut() {
+            upgrade.rolled_back = rollback.is_ok();
+        }
+        session.save_as(base_dir, UPGRADE_SESSION_FILE)?;
+        match rollback {
+            Ok(()) => Err(AutoInstallError::InstallationError(format!(
+                "Upgrade to {} failed and was rolled back to @{}: {}",
+                to, pre, e
+            ))),
+            Err(rollback_error) => Err(AutoInstallError::InstallationError(format!(
+                "Upgrade to {} failed ({}) and the rollback failed too: {}",
+                to, e, rollback_error
+            ))),
+        }
+    }
+
+    async fn upgrade(
+        &mut self,
+        options: &UpgradeOptions,
+        session: &mut InstallSession,
+        base_dir: &Path,
+    ) -> Result<()> {
+        let to = options.to_release.as_str();
+
+        const PREFLIGHT: &str = "Upgrade: Preflight";
+        Self::begin(session, base_dir, PREFLIGHT)?;
+        let result = self.preflight(to).await;
+        let (from, failed_before) = self.finish(session, base_dir, PREFLIGHT, result)?;
+        session.release_upgrade = Some(ReleaseUpgrade {
+            from_release: from.clone(),
+            to_release: to.to_string(),
+            ..Default::default()
+        });
+
+        if options.boot_environment {
+            const BOOT_ENV: &str = "Upgrade: Boot environment";
+            let name = boot_env::auto_name("upgrade", chrono::Utc::now());
+            Self::begin(session, base_dir, BOOT_ENV)?;
+            let result = BootEnvManager::new(self.ssh, self.layout)
+                .create(&name)
+                .await;
+            self.finish(session, base_dir, BOOT_ENV, result)?;
+            if let Some(upgrade) = session.release_upgrade.as_mut() {
+                upgrade.boot_environment = Some(name);
+            }
+        }
+
+        let pre = snapshot_name("pre", to);
+        self.commands_phase(
+            session,
+            base_dir,
+            "Upgrade: Pre-upgrade snapshot",
+            &build_snapshot_commands(self.layout, &pre),
+        )
+        .await?;
+        if let Some(upgrade) = session.release_upgrade.as_mut() {
+            upgrade.pre_snapshot = Some(pre);
+        }
+
+        for (name, commands) in build_upgrade_commands(&from, to) {
+            self.commands_phase(session, base_dir, name, &commands)
+                .await?;
+        }
+
+        if !options.reboot {
+            warn!("Skipping reboot; the new release is verified on the next boot only");
+            return Ok(());
+        }
+        const REBOOT: &str = "Upgrade: Reboot";
+        Self::begin(session, base_dir, REBOOT)?;
+        let result = self.ssh.reboot_and_wait(&options.reboot_wait).await;
+        self.finish(session, base_dir, REBOOT, result)?;
+
+        const VERIFY: &str = "Upgrade: Service verification";
+        Self::begin(session, base_dir, VERIFY)?;
+        let result = self.verify(to, &failed_before, &options.services).await;
+        if result.is_err() {
+            // Keep the failing units for the report before the rollback clears them
+            if let (Some(upgrade), Ok(units)) =
+                (session.release_upgrade.as_mut(), self.failed_units().await)
+            {
+                upgrade.new_failed_units = units.difference(&failed_before).cloned().collect();
+            }
+        }
+        self.finish(session, base_dir, VERIFY, result)?;
+
+        let post = snapshot_name("post", to);
+        self.commands_phase(
+            session,
+            base_dir,
+            "Upgrade: Post-upgrade snapshot",
+            &build_snapshot_commands(self.layout, &post),
+        )
+        .await?;
+        if let Some(upgrade) = session.release_upgrade.as_mut() {
+            upgrade.post_snapshot = Some(post);
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_switch_sources_command() {
+        let cmd = build_switch_sources_command("noble", "plucky");
+        assert!(cmd.starts_with(
+            "[ ! -f /etc/apt/sources.list ] || sed -i -E 's/\\bnoble(-[a-z]+)?\\b/plucky\\1/g' /etc/apt/sources.list && "
+        ));
+        assert!(cmd.ends_with("/etc/apt/sources.list.d/ubuntu.sources"));
+    }
+
+    #[test]
+    fn test_upgrade_commands_update_before_switching() {
+        let steps = build_upgrade_commands("noble", "plucky");
+        assert_eq!(steps[0].0, "Upgrade: Current release updates");
+        assert_eq!(steps[0].1[0], "apt-get update");
+        assert!(steps[0].1[1].ends_with("--force-confold dist-upgrade"));
+        assert_eq!(steps[1].0, "Upgrade: Release switch");
+        assert!(steps[1].1[0].contains("s/\\bnoble(-[a-z]+)?\\b/plucky\\1/g"));
+        assert!(steps[1].1[3].ends_with("autoremove --purge"));
+        assert_eq!(
+            snapshot_name("pre", "plucky"),
+            "autoinstall-pre-upgrade-plucky"
+        );
+    }
+
+    #[test]
+    fn test_parse_failed_units() {
+        let output = "● apt-daily.service loaded failed failed Daily apt download\nnfs-server.service loaded failed failed NFS server\n\n";
+        let units = parse_failed_units(output);
+        assert_eq!(
+            units.into_iter().collect::<Vec<_>>(),
+            vec!["apt-daily.service", "nfs-server.service"]
+        );
+    }
+}
@@ -1,11 +1,14 @@
 // file: src/network/ssh_installer/zfs_ops.rs
-// version: 1.4.1
+// version: 1.10.0
 // guid: sshzfs01-2345-6789-abcd-ef0123456789
 
 //! ZFS operations for SSH installation
 
+use super::capabilities::ToolVersion;
 use super::config::InstallationConfig;
+use crate::config::zfs_pools::{PoolLayout, PoolPlan};
 use crate::network::SshClient;
+use crate::utils::block_device::partition_path;
 use crate::Result;
 use std::collections::HashMap;
 use tracing::{error, info};
@@ -28,73 +31,97 @@ impl<'a> ZfsManager<'a> {
         self.log_and_execute("Creating target directory", "mkdir -p /mnt/targetos")
             .await?;
 
-        // Generate UUID for dataset naming
-        let uuid = self.generate_installation_uuid().await?;
+        // Dataset naming id; kept from an earlier install of the host when the host vars have one
+        let uuid = match self.variables.get("UUID").cloned() {
+            Some(uuid) => {
+                info!("Reusing installation id {} from host vars", uuid);
+                self.write_installation_uuid(&uuid).await?;
+                uuid
+            }
+            None => self.generate_installation_uuid().await?,
+        };
         self.variables.insert("UUID".to_string(), uuid.clone());
 
-        // Create bpool if not present
+        let layout = config.pool_layout()?;
+        let (root, boot) = (&layout.root.name, &layout.boot.name);
+
+        // Create the boot pool if not present
         if !self
             .ssh
-            .check_silent("zpool list -H bpool >/dev/null 2>&1")
+            .check_silent(&format!("zpool list -H {} >/dev/null 2>&1", boot))
             .await
             .unwrap_or(false)
         {
-            self.create_bpool(config).await?;
+            info!("Creating boot pool {}", boot);
+            let cmd = Self::build_bpool_create_command(&layout.boot, &config.disk_device);
+            self.log_and_execute("Creating boot pool", &cmd).await?;
         } else {
-            info!("bpool already exists; skipping pool creation");
+            info!("{} already exists; skipping pool creation", boot);
         }
 
-        // Create rpool with encryption if not present
+        // Create the root pool on the LUKS mapping if not present
         if !self
             .ssh
-            .check_silent("zpool list -H rpool >/dev/null 2>&1")
+            .check_silent(&format!("zpool list -H {} >/dev/null 2>&1", root))
             .await
             .unwrap_or(false)
         {
-            self.create_rpool(config).await?;
+            info!("Creating root pool {} on the LUKS mapping", root);
+            let cmd = Self::build_rpool_create_command(&layout.root);
+            self.log_and_execute("Creating root pool", &cmd).await?;
         } else {
-            info!("rpool already exists; skipping pool creation");
+            info!("{} already exists; skipping pool creation", root);
         }
 
-        // Create bpool datasets if not present
+        // Create boot pool datasets if not present
         if !self
-            .ssh
-            .check_silent("zfs list -H bpool/BOOT >/dev/null 2>&1")
+            .dataset_exists(&format!("{}/BOOT/ubuntu_{}", boot, uuid))
             .await
-            .unwrap_or(false)
         {
-            self.create_bpool_datasets(&uuid).await?;
+            self.create_bpool_datasets(boot, &uuid).await?;
         } else {
-            info!("bpool datasets already present; skipping dataset creation");
+            info!(
+                "{} datasets already present; skipping dataset creation",
+                boot
+            );
         }
 
-        // Create rpool datasets if not present
+        // Create root pool datasets if not present
         if !self
-            .ssh
-            .check_silent(&format!(
-                "zfs list -H rpool/ROOT/ubuntu_{} >/dev/null 2>&1",
-                uuid
-            ))
+            .dataset_exists(&format!("{}/ROOT/ubuntu_{}", root, uuid))
             .await
-            .unwrap_or(false)
         {
-            self.create_rpool_datasets(&uuid).await?;
+            self.create_rpool_datasets(root, &uuid).await?;
         } else {
-            info!("rpool datasets already present; skipping dataset creation");
+            info!(
+                "{} datasets already present; skipping dataset creation",
+                root
+            );
+        }
+
+        // Dataset for the node's storage role, outside ROOT so boot environments skip it
+        if let Some(dataset) = &layout.role_dataset {
+            if !self
+                .dataset_exists(&format!("{}/{}", root, dataset.name))
+                .await
+            {
+                self.log_and_execute(
+                    &format!("Creating {} role dataset", dataset.name),
+                    &dataset.create_command(root),
+                )
+                .await?;
+            }
         }
 
         info!("ZFS pools and datasets created successfully");
         Ok(())
     }
 
-    /// Verify ZFS state after creation
-    pub async fn verify_zfs_state(&mut self) -> Result<()> {
-        info!("Verifying ZFS state");
-        self.log_and_execute("Check zpool status", "zpool status")
-            .await?;
-        self.log_and_execute("List ZFS datasets", "zfs list")
-            .await?;
-        Ok(())
+    async fn dataset_exists(&mut self, name: &str) -> bool {
+        self.ssh
+            .check_silent(&format!("zfs list -H {} >/dev/null 2>&1", name))
+            .await
+            .unwrap_or(false)
     }
 
     // Removed: prepare_zfs_key_storage - no file-based key, using passphrase-opened LUKS for rpool block device
@@ -108,8 +135,14 @@ impl<'a> ZfsManager<'a> {
             )
             .await?;
         let uuid = uuid_output.trim().to_string();
+        self.write_installation_uuid(&uuid).await?;
 
-        // Write UUID to target
+        info!("Generated installation UUID: {}", uuid);
+        Ok(uuid)
+    }
+
+    /// Write the installation id and disk to `/uuid` in the target
+    async fn write_installation_uuid(&mut self, uuid: &str) -> Result<()> {
         self.ssh
             .execute(&format!("echo 'UUID={}' > /mnt/targetos/uuid", uuid))
             .await?;
@@ -119,70 +152,45 @@ impl<'a> ZfsManager<'a> {
                 self.variables.get("DISK").unwrap_or(&"unknown".to_string())
             ))
             .await?;
-
-        info!("Generated installation UUID: {}", uuid);
-        Ok(uuid)
-    }
-
-    /// Create bpool (boot pool)
-    async fn create_bpool(&mut self, config: &InstallationConfig) -> Result<()> {
-        info!("Creating bpool");
-
-        let bpool_cmd = Self::build_bpool_create_command(&config.disk_device);
-        self.log_and_execute("Creating bpool", &bpool_cmd).await?;
-
-        Ok(())
-    }
-
-    /// Create rpool (root pool) with encryption
-    async fn create_rpool(&mut self, _config: &InstallationConfig) -> Result<()> {
-        info!("Creating rpool with encryption");
-
-        // Create rpool on the LUKS-mapped block device; encryption is provided by LUKS, so ZFS native encryption is optional and disabled here
-        let rpool_cmd = Self::build_rpool_create_command();
-        self.log_and_execute("Creating rpool", &rpool_cmd).await?;
-
         Ok(())
     }
 
-    /// Build the zpool create command for rpool using the LUKS mapper device
-    fn build_rpool_create_command() -> String {
-        String::from(
-            "zpool create -o ashift=12 -o autotrim=on \
-             -O acltype=posixacl -O xattr=sa -O dnodesize=auto -O compression=lz4 \
-             -O normalization=formD -O relatime=on -O canmount=off -O mountpoint=none \
-             -m none -R /mnt/targetos rpool /dev/mapper/luks",
-        )
+    /// Build the zpool create command for the root pool on the LUKS mapper device; encryption
+    /// is provided by LUKS, so ZFS native encryption stays off
+    pub(super) fn build_rpool_create_command(plan: &PoolPlan) -> String {
+        plan.create_command("/dev/mapper/luks", "/mnt/targetos")
     }
 
-    /// Build the zpool create command for bpool (grub-compatible)
-    fn build_bpool_create_command(disk: &str) -> String {
-        format!(
-            "zpool create -o ashift=12 -o autotrim=on -o cachefile=/etc/zfs/zpool.cache \
-             -o compatibility=grub2 -o feature@livelist=enabled -o feature@zpool_checkpoint=enabled \
-             -O devices=off -O acltype=posixacl -O xattr=sa -O compression=lz4 \
-             -O normalization=formD -O relatime=on -O canmount=off -O mountpoint=none \
-             -m none -R /mnt/targetos bpool {}p3",
-            disk
-        )
+    /// Build the zpool create command for the grub-compatible boot pool on partition 3
+    pub(super) fn build_bpool_create_command(plan: &PoolPlan, disk: &str) -> String {
+        plan.create_command(&partition_path(disk, 3), "/mnt/targetos")
     }
 
     /// Create bpool datasets
-    async fn create_bpool_datasets(&mut self, uuid: &str) -> Result<()> {
-        info!("Creating bpool datasets");
+    async fn create_bpool_datasets(&mut self, pool: &str, uuid: &str) -> Result<()> {
+        info!("Creating {} datasets", pool);
 
         // Ensure mountpoint exists for /boot
         self.log_and_execute("Ensure /boot mountpoint", "mkdir -p /mnt/targetos/boot")
             .await?;
 
+        // Containers are kept by a reinstall, which only replaces the datasets below them
+        if !self.dataset_exists(&format!("{}/BOOT", pool)).await {
+            self.log_and_execute(
+                "Creating BOOT container",
+                &format!(
+                    "zfs create -o canmount=off -o mountpoint=none {}/BOOT",
+                    pool
+                ),
+            )
+            .await?;
+        }
         self.log_and_execute(
-            "Creating bpool/BOOT",
-            "zfs create -o canmount=off -o mountpoint=none bpool/BOOT",
-        )
-        .await?;
-        self.log_and_execute(
-            "Creating bpool boot dataset",
-            &format!("zfs create -o mountpoint=/boot bpool/BOOT/ubuntu_{}", uuid),
+            "Creating boot dataset",
+            &format!(
+                "zfs create -o mountpoint=/boot {}/BOOT/ubuntu_{}",
+                pool, uuid
+            ),
         )
         .await?;
 
@@ -190,15 +198,20 @@ impl<'a> ZfsManager<'a> {
     }
 
     /// Create comprehensive rpool dataset structure
-    async fn create_rpool_datasets(&mut self, uuid: &str) -> Result<()> {
-        info!("Creating rpool dataset structure");
+    async fn create_rpool_datasets(&mut self, pool: &str, uuid: &str) -> Result<()> {
+        info!("Creating {} dataset structure", pool);
 
         // Root dataset structure
-        self.log_and_execute(
-            "Creating rpool/ROOT",
-            "zfs create -o canmount=off -o mountpoint=none rpool/ROOT",
-        )
-        .await?;
+        if !self.dataset_exists(&format!("{}/ROOT", pool)).await {
+            self.log_and_execute(
+                "Creating ROOT container",
+                &format!(
+                    "zfs create -o canmount=off -o mountpoint=none {}/ROOT",
+                    pool
+                ),
+            )
+            .await?;
+        }
 
         let current_time = std::time::SystemTime::now()
             .duration_since(std::time::UNIX_EPOCH)
@@ -206,44 +219,40 @@ impl<'a> ZfsManager<'a> {
             .as_secs();
 
         self.log_and_execute("Creating root filesystem",
-            &format!("zfs create -o mountpoint=/ -o com.ubuntu.zsys:bootfs=yes -o com.ubuntu.zsys:last-used={} rpool/ROOT/ubuntu_{}", current_time, uuid)).await?;
+            &format!("zfs create -o mountpoint=/ -o com.ubuntu.zsys:bootfs=yes -o com.ubuntu.zsys:last-used={} {}/ROOT/ubuntu_{}", current_time, pool, uuid)).await?;
 
         // System directories
         let datasets = vec![
             (
                 "usr",
-                "rpool/ROOT/ubuntu_{}/usr",
+                "ROOT/ubuntu_{}/usr",
                 "-o com.ubuntu.zsys:bootfs=no -o canmount=off",
             ),
             (
                 "var",
-                "rpool/ROOT/ubuntu_{}/var",
+                "ROOT/ubuntu_{}/var",
                 "-o com.ubuntu.zsys:bootfs=no -o canmount=off",
             ),
-            ("var/lib", "rpool/ROOT/ubuntu_{}/var/lib", ""),
-            ("var/log", "rpool/ROOT/ubuntu_{}/var/log", ""),
-            ("var/spool", "rpool/ROOT/ubuntu_{}/var/spool", ""),
-            ("var/cache", "rpool/ROOT/ubuntu_{}/var/cache", ""),
-            ("var/lib/nfs", "rpool/ROOT/ubuntu_{}/var/lib/nfs", ""),
-            ("var/tmp", "rpool/ROOT/ubuntu_{}/var/tmp", ""),
-            ("var/lib/apt", "rpool/ROOT/ubuntu_{}/var/lib/apt", ""),
-            ("var/lib/dpkg", "rpool/ROOT/ubuntu_{}/var/lib/dpkg", ""),
-            (
-                "srv",
-                "rpool/ROOT/ubuntu_{}/srv",
-                "-o com.ubuntu.zsys:bootfs=no",
-            ),
-            ("usr/local", "rpool/ROOT/ubuntu_{}/usr/local", ""),
-            ("var/games", "rpool/ROOT/ubuntu_{}/var/games", ""),
+            ("var/lib", "ROOT/ubuntu_{}/var/lib", ""),
+            ("var/log", "ROOT/ubuntu_{}/var/log", ""),
+            ("var/spool", "ROOT/ubuntu_{}/var/spool", ""),
+            ("var/cache", "ROOT/ubuntu_{}/var/cache", ""),
+            ("var/lib/nfs", "ROOT/ubuntu_{}/var/lib/nfs", ""),
+            ("var/tmp", "ROOT/ubuntu_{}/var/tmp", ""),
+            ("var/lib/apt", "ROOT/ubuntu_{}/var/lib/apt", ""),
+            ("var/lib/dpkg", "ROOT/ubuntu_{}/var/lib/dpkg", ""),
+            ("srv", "ROOT/ubuntu_{}/srv", "-o com.ubuntu.zsys:bootfs=no"),
+            ("usr/local", "ROOT/ubuntu_{}/usr/local", ""),
+            ("var/games", "ROOT/ubuntu_{}/var/games", ""),
             (
                 "var/lib/AccountsService",
-                "rpool/ROOT/ubuntu_{}/var/lib/AccountsService",
+                "ROOT/ubuntu_{}/var/lib/AccountsService",
                 "",
             ),
         ];
 
         for (name, dataset, opts) in datasets {
-            let dataset_name = dataset.replace("{}", uuid);
+            let dataset_name = format!("{}/{}", pool, dataset.replace("{}", uuid));
             self.log_and_execute(
                 &format!("Creating {}", name),
                 &format!("zfs create {} {}", opts, dataset_name),
@@ -264,14 +273,30 @@ impl<'a> ZfsManager<'a> {
         )
         .await?;
 
-        // Create USERDATA structure
-        self.log_and_execute(
-            "Creating USERDATA",
-            "zfs create -o canmount=off -o mountpoint=/ rpool/USERDATA",
-        )
-        .await?;
-        self.log_and_execute("Creating root user data",
-            &format!("zfs create -o com.ubuntu.zsys:bootfs-datasets=rpool/ROOT/ubuntu_{} -o canmount=on -o mountpoint=/root rpool/USERDATA/root_{}", uuid, uuid)).await?;
+        // Create USERDATA structure; after a reinstall root's data is already there
+        if !self.dataset_exists(&format!("{}/USERDATA", pool)).await {
+            self.log_and_execute(
+                "Creating USERDATA",
+                &format!(
+                    "zfs create -o canmount=off -o mountpoint=/ {}/USERDATA",
+                    pool
+                ),
+            )
+            .await?;
+        }
+        if self
+            .dataset_exists(&format!("{}/USERDATA/root_{}", pool, uuid))
+            .await
+        {
+            self.log_and_execute(
+                "Mounting root user data",
+                &format!("zfs mount {}/USERDATA/root_{} || true", pool, uuid),
+            )
+            .await?;
+        } else {
+            self.log_and_execute("Creating root user data",
+                &format!("zfs create -o com.ubuntu.zsys:bootfs-datasets={p}/ROOT/ubuntu_{u} -o canmount=on -o mountpoint=/root {p}/USERDATA/root_{u}", p = pool, u = uuid)).await?;
+        }
 
         Ok(())
     }
@@ -324,13 +349,72 @@ impl<'a> ZfsManager<'a> {
     }
 }
 
+/// Oldest OpenZFS accepting a feature, for features newer than 2.0
+const FEATURE_VERSIONS: &[(&str, ToolVersion)] = &[
+    ("draid", ToolVersion::new(2, 1, 0)),
+    ("blake3", ToolVersion::new(2, 2, 0)),
+    ("block_cloning", ToolVersion::new(2, 2, 0)),
+    ("head_errlog", ToolVersion::new(2, 2, 0)),
+    ("vdev_zaps_v2", ToolVersion::new(2, 2, 0)),
+    ("zilsaxattr", ToolVersion::new(2, 2, 0)),
+    ("raidz_expansion", ToolVersion::new(2, 3, 0)),
+    ("fast_dedup", ToolVersion::new(2, 3, 0)),
+    ("longname", ToolVersion::new(2, 3, 0)),
+];
+
+/// Pool settings of `layout` that OpenZFS `zfs` cannot create
+pub fn unsupported_pool_settings(layout: &PoolLayout, zfs: ToolVersion) -> Vec<String> {
+    let mut problems = Vec::new();
+    let mut require = |what: String, needed: ToolVersion| {
+        if zfs < needed {
+            problems.push(format!("{} needs OpenZFS {} (found {})", what, needed, zfs));
+        }
+    };
+    for pool in [&layout.root, &layout.boot] {
+        if pool.property("compatibility").is_some() {
+            require(
+                format!("{} compatibility property", pool.name),
+                ToolVersion::new(2, 1, 0),
+            );
+        }
+        for (key, _) in &pool.pool_properties {
+            if let Some(feature) = key.strip_prefix("feature@") {
+                if let Some((_, needed)) = FEATURE_VERSIONS.iter().find(|(f, _)| *f == feature) {
+                    require(format!("{} feature {}", pool.name, feature), *needed);
+                }
+            }
+        }
+    }
+    let role_properties = layout.role_dataset.iter().flat_map(|d| {
+        d.properties
+            .iter()
+            .map(|(k, v)| (k.to_string(), v.to_string()))
+    });
+    for (key, value) in layout
+        .root
+        .dataset_properties
+        .iter()
+        .chain(&layout.boot.dataset_properties)
+        .cloned()
+        .chain(role_properties)
+    {
+        if key == "compression" && value.starts_with("zstd") {
+            require(format!("compression={}", value), ToolVersion::new(2, 0, 0));
+        }
+        if key == "checksum" && value == "blake3" {
+            require("checksum=blake3".to_string(), ToolVersion::new(2, 2, 0));
+        }
+    }
+    problems
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
 
     #[test]
     fn test_build_rpool_create_command_uses_luks_mapper() {
-        let cmd = ZfsManager::build_rpool_create_command();
+        let cmd = ZfsManager::build_rpool_create_command(&PoolLayout::default().root);
         assert!(cmd.contains("zpool create"));
         assert!(cmd.contains(" rpool "));
         assert!(cmd.contains("/dev/mapper/luks"));
@@ -339,15 +423,36 @@ mod tests {
 
     #[test]
     fn test_build_bpool_create_command_has_expected_flags() {
-        let cmd = ZfsManager::build_bpool_create_command("/dev/sda");
+        let cmd = ZfsManager::build_bpool_create_command(&PoolLayout::default().boot, "/dev/sda");
         assert!(cmd.contains("zpool create"));
         // device should be present and appear at the end of the command
         assert!(cmd.contains(" bpool "));
-        assert!(cmd.ends_with("/dev/sdap3"));
+        assert!(cmd.ends_with("/dev/sda3"));
         assert!(cmd.contains(" -R /mnt/targetos "));
         assert!(cmd.contains("compatibility=grub2"));
         assert!(cmd.contains("cachefile=/etc/zfs/zpool.cache"));
         assert!(cmd.contains("devices=off"));
         assert!(cmd.contains("compression=lz4"));
     }
+
+    #[test]
+    fn test_unsupported_pool_settings() {
+        let config: crate::config::ZfsPoolsConfig =
+            serde_yaml::from_str("role: backup-node\nroot_pool: {features: [block_cloning]}\n")
+                .unwrap();
+        let layout = config.resolve("stor-02").unwrap();
+        assert!(unsupported_pool_settings(&layout, ToolVersion::new(2, 2, 2)).is_empty());
+        let old = unsupported_pool_settings(&layout, ToolVersion::new(2, 0, 7));
+        assert_eq!(
+            old,
+            vec![
+                "rpool feature block_cloning needs OpenZFS 2.2.0 (found 2.0.7)",
+                "bpool compatibility property needs OpenZFS 2.1.0 (found 2.0.7)",
+            ]
+        );
+        assert_eq!(
+            unsupported_pool_settings(&layout, ToolVersion::new(0, 8, 3)).len(),
+            3
+        );
+    }
 }
@@ -0,0 +1,149 @@
+// file: src/network/stream.rs
+// version: 1.1.0
+// guid: 3c8f1a64-9d27-4e5b-b6a0-7e2d4c9f1b35
+
+//! Line-by-line output of running remote commands
+//!
+//! [`SshClient::execute_streaming`](crate::network::SshClient::execute_streaming) hands every
+//! stdout and stderr line to a callback as soon as the line is complete, instead of returning
+//! the output once the command has exited. Progress bars that redraw with `\r` produce one line
+//! per redraw. Lines are scrubbed of registered secrets before callbacks see them. Network
+//! recovery streams debootstrap and chroot apt downloads this way so a stalled mirror is noticed.
+
+use crate::logging::redact;
+use serde::{Deserialize, Serialize};
+use std::time::Duration;
+
+/// Stream a line was read from
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum StreamKind {
+    Stdout,
+    Stderr,
+}
+
+/// One complete line of command output, without its terminator
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct OutputLine {
+    pub stream: StreamKind,
+    pub line: String,
+}
+
+/// Callback receiving output lines while a command runs
+pub type LineCallback<'a> = &'a mut (dyn FnMut(&OutputLine) + Send);
+
+/// Options of one streamed command
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct StreamOptions {
+    /// Give up on the command when it prints nothing for this long
+    pub silence_timeout: Option<Duration>,
+}
+
+impl StreamOptions {
+    pub fn with_silence_timeout(timeout: Duration) -> Self {
+        Self {
+            silence_timeout: Some(timeout),
+        }
+    }
+}
+
+/// Splits a byte stream into lines at `\n` and `\r`
+#[derive(Debug)]
+pub struct LineSplitter {
+    stream: StreamKind,
+    pending: Vec<u8>,
+    after_cr: bool,
+}
+
+impl LineSplitter {
+    pub fn new(stream: StreamKind) -> Self {
+        Self {
+            stream,
+            pending: Vec::new(),
+            after_cr: false,
+        }
+    }
+
+    /// Feed `bytes` and return the lines they complete
+    pub fn push(&mut self, bytes: &[u8]) -> Vec<OutputLine> {
+        let mut lines = Vec::new();
+        for &byte in bytes {
+            match byte {
+                // `\r\n` ends a single line
+                b'\n' if self.after_cr => self.after_cr = false,
+                b'\n' | b'\r' => {
+                    self.after_cr = byte == b'\r';
+                    lines.push(self.take());
+                }
+                _ => {
+                    self.after_cr = false;
+                    self.pending.push(byte);
+                }
+            }
+        }
+        lines
+    }
+
+    /// The unterminated last line, if the stream ended in the middle of one
+    pub fn finish(&mut self) -> Option<OutputLine> {
+        (!self.pending.is_empty()).then(|| self.take())
+    }
+
+    fn take(&mut self) -> OutputLine {
+        let bytes = std::mem::take(&mut self.pending);
+        OutputLine {
+            stream: self.stream,
+            line: redact::scrub(&String::from_utf8_lossy(&bytes)).into_owned(),
+        }
+    }
+}
+
+/// Hand the lines of already collected output to `on_line`, stdout first; used where the
+/// transport cannot stream
+pub fn replay(stdout: &str, stderr: &str, on_line: LineCallback<'_>) {
+    for (stream, text) in [(StreamKind::Stdout, stdout), (StreamKind::Stderr, stderr)] {
+        let mut splitter = LineSplitter::new(stream);
+        for line in splitter
+            .push(text.as_bytes())
+            .into_iter()
+            .chain(splitter.finish())
+        {
+            on_line(&line);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_splitter_handles_partial_lines_and_carriage_returns() {
+        let mut splitter = LineSplitter::new(StreamKind::Stdout);
+        assert!(splitter.push(b"Get:1 http://arch").is_empty());
+        let lines = splitter.push(b"ive noble InRelease\r\n 10%\r 55%\rdone\npart");
+        let text: Vec<&str> = lines.iter().map(|l| l.line.as_str()).collect();
+        assert_eq!(
+            text,
+            [
+                "Get:1 http://archive noble InRelease",
+                " 10%",
+                " 55%",
+                "done"
+            ]
+        );
+        assert_eq!(splitter.finish().unwrap().line, "part");
+        assert!(splitter.finish().is_none());
+
+        let mut received = Vec::new();
+        replay("a\nb\n", "oops", &mut |line| received.push(line.clone()));
+        assert_eq!(received.len(), 3);
+        assert_eq!(
+            received[2],
+            OutputLine {
+                stream: StreamKind::Stderr,
+                line: "oops".to_string()
+            }
+        );
+    }
+}
@@ -0,0 +1,195 @@
+// file: src/network/sudo.rs
+// version: 1.2.0
+// guid: c6e91a3d-7f25-4b80-8d14-5a0f2e9b7c63
+
+//! Per-command elevation with sudo for installs run as an unprivileged SSH user
+//!
+//! Every command that does not start with one of the policy's unprivileged prefixes is run as
+//! `sudo -- bash -c '<command>'`. With a password, sudo reads it from the channel's standard
+//! input (`-S`), so it never appears in a command line, a log or the session record. Every
+//! command is recorded with whether it was elevated; the installer writes the record, with
+//! registered secrets scrubbed, to `logs/<hostname>/privilege-audit.json`.
+
+use crate::config::PrivilegeConfig;
+use crate::error::AutoInstallError;
+use crate::logging::redact;
+use crate::utils::shell_quote;
+use crate::Result;
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use std::path::{Path, PathBuf};
+
+/// One command run through a sudo policy
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ElevationRecord {
+    pub at: DateTime<Utc>,
+    pub command: String,
+    pub elevated: bool,
+}
+
+/// What `logs/<hostname>/privilege-audit.json` holds
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct PrivilegeAudit {
+    pub host: String,
+    pub username: String,
+    pub elevated_count: usize,
+    pub unprivileged_count: usize,
+    pub commands: Vec<ElevationRecord>,
+}
+
+impl PrivilegeAudit {
+    pub fn new(host: &str, username: &str, commands: Vec<ElevationRecord>) -> Self {
+        let elevated_count = commands.iter().filter(|c| c.elevated).count();
+        Self {
+            host: host.to_string(),
+            username: username.to_string(),
+            elevated_count,
+            unprivileged_count: commands.len() - elevated_count,
+            commands,
+        }
+    }
+
+    pub fn path(host_dir: &Path) -> PathBuf {
+        host_dir.join("privilege-audit.json")
+    }
+
+    pub fn write(&self, host_dir: &Path) -> Result<PathBuf> {
+        std::fs::create_dir_all(host_dir)?;
+        let path = Self::path(host_dir);
+        let json = serde_json::to_string_pretty(self)?;
+        std::fs::write(&path, redact::scrub(&json).as_ref())?;
+        Ok(path)
+    }
+}
+
+/// Resolved sudo settings of one connection
+#[derive(Clone)]
+pub struct SudoPolicy {
+    password: Option<String>,
+    unprivileged: Vec<String>,
+}
+
+impl std::fmt::Debug for SudoPolicy {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("SudoPolicy")
+            .field("password", &self.password.as_ref().map(|_| "<redacted>"))
+            .field("unprivileged", &self.unprivileged)
+            .finish()
+    }
+}
+
+impl SudoPolicy {
+    /// Policy for `config`, fetching the password from its secret; `None` in root mode
+    pub fn from_config(config: &PrivilegeConfig) -> Result<Option<Self>> {
+        if !config.uses_sudo() {
+            return Ok(None);
+        }
+        let password = config
+            .password
+            .as_ref()
+            .map(|secret| secret.resolve())
+            .transpose()?;
+        Ok(Some(Self {
+            password,
+            unprivileged: config.unprivileged.clone(),
+        }))
+    }
+
+    /// NOPASSWD policy elevating everything
+    pub fn nopasswd() -> Self {
+        Self {
+            password: None,
+            unprivileged: Vec::new(),
+        }
+    }
+
+    pub fn needs_elevation(&self, command: &str) -> bool {
+        let command = command.trim_start();
+        !self
+            .unprivileged
+            .iter()
+            .any(|prefix| command.starts_with(prefix.as_str()))
+    }
+
+    /// `command` as sudo runs it
+    pub fn wrap(&self, command: &str) -> String {
+        format!("{} -- bash -c {}", self.sudo(), shell_quote(command))
+    }
+
+    /// Written to the channel before the command's own input when sudo reads a password
+    pub fn stdin(&self) -> Option<String> {
+        self.password.as_ref().map(|p| format!("{}\n", p))
+    }
+
+    /// Error for a sudo check that did not pass
+    pub fn check_failed(&self, host: &str, username: &str, stderr: &str) -> AutoInstallError {
+        let hint = match self.password {
+            Some(_) => "check the privilege password",
+            None => "grant NOPASSWD in sudoers or set privilege password",
+        };
+        AutoInstallError::SshError(format!(
+            "sudo for {} on {} failed ({}): {}",
+            username,
+            host,
+            hint,
+            stderr.trim()
+        ))
+    }
+
+    fn sudo(&self) -> &'static str {
+        match self.password {
+            // -k: always read the password, so it never reaches the command's own input
+            Some(_) => "sudo -S -k -p ''",
+            None => "sudo -n",
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::privilege::PrivilegeMode;
+    use crate::config::SecretRef;
+
+    #[test]
+    fn test_wrap_and_policy() {
+        let policy = SudoPolicy::from_config(&PrivilegeConfig {
+            mode: PrivilegeMode::Sudo,
+            password: Some(SecretRef::Command {
+                command: "echo s3cret".to_string(),
+            }),
+            unprivileged: vec!["uname".to_string()],
+        })
+        .unwrap()
+        .unwrap();
+        assert!(!policy.needs_elevation("uname -r"));
+        assert!(policy.needs_elevation("zpool list"));
+        assert_eq!(
+            policy.wrap("echo 'a' > /etc/x"),
+            "sudo -S -k -p '' -- bash -c 'echo '\\''a'\\'' > /etc/x'"
+        );
+        assert_eq!(policy.stdin().as_deref(), Some("s3cret\n"));
+        assert!(!format!("{:?}", policy).contains("s3cret"));
+
+        assert_eq!(
+            SudoPolicy::nopasswd().wrap("true"),
+            "sudo -n -- bash -c true"
+        );
+        assert!(SudoPolicy::from_config(&PrivilegeConfig::default())
+            .unwrap()
+            .is_none());
+
+        let now = Utc::now();
+        let record = |command: &str, elevated| ElevationRecord {
+            at: now,
+            command: command.to_string(),
+            elevated,
+        };
+        let audit = PrivilegeAudit::new(
+            "10.0.0.5",
+            "deploy",
+            vec![record("uname -r", false), record("zpool list", true)],
+        );
+        assert_eq!((audit.elevated_count, audit.unprivileged_count), (1, 1));
+    }
+}
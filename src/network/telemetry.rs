@@ -0,0 +1,186 @@
+// file: src/network/telemetry.rs
+// version: 1.0.0
+// guid: 2f8c4a67-1b93-4e5d-9a0e-6d3c7b5f1e48
+
+//! Opt-in telemetry of installer performance
+//!
+//! With `ssh-install --telemetry` and a `telemetry.endpoint` in the target config, one JSON
+//! document per install is POSTed to the endpoint when the install ends. It holds only what
+//! is needed to compare installs across sites: the outcome, the category of the error, the
+//! hardware class, and the duration and outcome of each phase. Hostnames, addresses, disk
+//! paths, error messages, commands and secrets are never part of it. A failed upload is logged
+//! and never fails the install.
+
+use crate::config::TelemetryConfig;
+use crate::error::AutoInstallError;
+use crate::network::ssh_installer::install_report::InstallReport;
+use crate::network::ssh_installer::session::{InstallSession, SessionStatus};
+use crate::Result;
+use serde::{Deserialize, Serialize};
+use std::time::Duration;
+
+/// Layout version of [`TelemetryPayload`]; bumped on incompatible changes
+pub const TELEMETRY_SCHEMA_VERSION: &str = "1.0";
+
+/// Duration and outcome of one phase
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct PhaseMetric {
+    pub name: String,
+    /// "ok", "failed", "interrupted" or "running"
+    pub outcome: String,
+    pub seconds: Option<i64>,
+}
+
+/// The document sent for one install
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct TelemetryPayload {
+    pub schema_version: String,
+    pub agent_version: String,
+    /// `site` from the telemetry config
+    pub site: Option<String>,
+    /// "completed", "failed" or "cancelled"
+    pub outcome: String,
+    /// Error kind of a failed install, e.g. `ssh` or `timeout`
+    pub failure_category: Option<String>,
+    /// Storage class the target was sorted into, e.g. `dual-nvme`
+    pub hardware_class: Option<String>,
+    pub total_seconds: i64,
+    pub phases: Vec<PhaseMetric>,
+}
+
+impl TelemetryPayload {
+    /// Metrics of `session`; `error` is the install's error, if it failed
+    pub fn new(
+        session: &InstallSession,
+        error: Option<&AutoInstallError>,
+        hardware_class: Option<&str>,
+        site: Option<&str>,
+    ) -> Self {
+        let outcome = match (session.status, error) {
+            (SessionStatus::Cancelled, _) | (_, Some(AutoInstallError::CancelledError(_))) => {
+                "cancelled"
+            }
+            (SessionStatus::Failed, _) | (_, Some(_)) => "failed",
+            _ => "completed",
+        };
+        let phases = InstallReport::new(session)
+            .phases()
+            .into_iter()
+            .map(|row| PhaseMetric {
+                seconds: session
+                    .phase_timings
+                    .iter()
+                    .find(|timing| timing.name == row.name)
+                    .and_then(|timing| timing.duration())
+                    .map(|duration| duration.num_seconds()),
+                name: row.name,
+                outcome: row.outcome.to_string(),
+            })
+            .collect();
+        Self {
+            schema_version: TELEMETRY_SCHEMA_VERSION.to_string(),
+            agent_version: env!("CARGO_PKG_VERSION").to_string(),
+            site: site.map(str::to_string),
+            outcome: outcome.to_string(),
+            failure_category: error.map(|e| e.category().to_string()),
+            hardware_class: hardware_class.map(str::to_string),
+            total_seconds: session.elapsed().num_seconds(),
+            phases,
+        }
+    }
+}
+
+/// Sends [`TelemetryPayload`]s to the configured endpoint
+pub struct TelemetryReporter {
+    client: reqwest::Client,
+    endpoint: String,
+    site: Option<String>,
+}
+
+impl TelemetryReporter {
+    /// Reporter for `config`; errors when no endpoint is configured, since `--telemetry`
+    /// without one would silently send nothing
+    pub fn new(config: &TelemetryConfig) -> Result<Self> {
+        let endpoint = config.endpoint.clone().ok_or_else(|| {
+            AutoInstallError::ConfigError(
+                "--telemetry needs telemetry.endpoint in the target config".to_string(),
+            )
+        })?;
+        let client = reqwest::Client::builder()
+            .timeout(Duration::from_secs(config.timeout_secs))
+            .build()
+            .map_err(|e| {
+                AutoInstallError::NetworkError(format!("Failed to build HTTP client: {}", e))
+            })?;
+        Ok(Self {
+            client,
+            endpoint,
+            site: config.site.clone(),
+        })
+    }
+
+    pub fn payload(
+        &self,
+        session: &InstallSession,
+        error: Option<&AutoInstallError>,
+        hardware_class: Option<&str>,
+    ) -> TelemetryPayload {
+        TelemetryPayload::new(session, error, hardware_class, self.site.as_deref())
+    }
+
+    pub async fn send(&self, payload: &TelemetryPayload) -> Result<()> {
+        let response = self
+            .client
+            .post(&self.endpoint)
+            .json(payload)
+            .send()
+            .await?;
+        if !response.status().is_success() {
+            return Err(AutoInstallError::NetworkError(format!(
+                "Telemetry endpoint answered {}",
+                response.status()
+            )));
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_payload_carries_no_host_details() {
+        let mut session = InstallSession::new("db-07.secret.example");
+        session.start_phase("Phase 1: Disk preparation");
+        session.end_phase();
+        session.completed_phases = vec!["Phase 1: Disk preparation".to_string()];
+        session.start_phase("Phase 2: Base system");
+        session.end_phase();
+        session.failed_phases =
+            vec!["Phase 2: Base system - debootstrap failed on 10.0.4.17 /dev/nvme0n1".to_string()];
+        session.last_command = Some("zpool create rpool /dev/nvme0n1p4".to_string());
+        session.status = SessionStatus::Failed;
+
+        let error = AutoInstallError::SshError("connection to 10.0.4.17 reset".to_string());
+        let payload = TelemetryPayload::new(&session, Some(&error), Some("dual-nvme"), Some("ams"));
+        assert_eq!(payload.outcome, "failed");
+        assert_eq!(payload.failure_category.as_deref(), Some("ssh"));
+        assert_eq!(payload.phases.len(), 2);
+        assert_eq!(payload.phases[1].outcome, "failed");
+        assert_eq!(payload.phases[0].seconds, Some(0));
+
+        let json = serde_json::to_string(&payload).unwrap();
+        for leak in [
+            "db-07",
+            "10.0.4.17",
+            "nvme0n1",
+            "debootstrap failed",
+            "zpool",
+        ] {
+            assert!(!json.contains(leak), "{} leaked: {}", leak, json);
+        }
+
+        assert!(TelemetryReporter::new(&TelemetryConfig::default()).is_err());
+    }
+}
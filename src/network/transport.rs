@@ -0,0 +1,251 @@
+// file: src/network/transport.rs
+// version: 1.0.0
+// guid: 7b3e9d14-5a2c-4f86-b1e0-c94d2a6f8e53
+
+//! Alternative command transports for targets without SSH
+//!
+//! [`SshClient`](crate::network::SshClient) normally runs commands over an SSH channel. A
+//! [`Transport`] replaces that channel while keeping the rest of the client (cancellation,
+//! chaos injection, error reporting) and therefore the whole phase pipeline unchanged.
+
+use crate::error::AutoInstallError;
+use crate::network::serial::SerialTransport;
+use crate::Result;
+
+/// Largest chunk of base64 text written to the target in one line
+///
+/// Terminal line disciplines cap canonical input at 4096 bytes; staying well below leaves room
+/// for the surrounding shell command.
+pub const UPLOAD_CHUNK_SIZE: usize = 1024;
+
+/// Result of one command run over a transport
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct CommandOutput {
+    pub exit_code: i32,
+    pub stdout: String,
+    pub stderr: String,
+}
+
+/// Byte-oriented channel able to run shell commands on the target
+pub trait Transport: Send {
+    /// Human-readable endpoint for logs, e.g. `serial /dev/ttyUSB0`
+    fn describe(&self) -> String;
+
+    /// Run `command` in a shell on the target and wait for it to finish
+    fn run(&mut self, command: &str) -> Result<CommandOutput>;
+
+    /// Write `contents` to `remote_path` on the target
+    fn write_file(&mut self, remote_path: &str, contents: &[u8]) -> Result<()> {
+        let staging = format!("{}.b64", remote_path);
+        self.run_checked(&format!("rm -f '{}'", staging))?;
+        let encoded = base64_encode(contents);
+        for chunk in encoded.as_bytes().chunks(UPLOAD_CHUNK_SIZE) {
+            // base64 output is ASCII, so any byte boundary is a char boundary
+            let chunk = std::str::from_utf8(chunk).unwrap_or_default();
+            self.run_checked(&format!("printf '%s' '{}' >> '{}'", chunk, staging))?;
+        }
+        self.run_checked(&format!(
+            "base64 -d '{0}' > '{1}' && rm -f '{0}'",
+            staging, remote_path
+        ))
+    }
+
+    /// Read `remote_path` from the target
+    fn read_file(&mut self, remote_path: &str) -> Result<Vec<u8>> {
+        let output = self.run(&format!("base64 -w 0 '{}'", remote_path))?;
+        if output.exit_code != 0 {
+            return Err(AutoInstallError::SshError(format!(
+                "Failed to read {} over {}: {}",
+                remote_path,
+                self.describe(),
+                output.stderr.trim()
+            )));
+        }
+        base64_decode(output.stdout.trim())
+    }
+
+    /// Run `command`, failing on a non-zero exit code
+    fn run_checked(&mut self, command: &str) -> Result<()> {
+        let output = self.run(command)?;
+        if output.exit_code != 0 {
+            return Err(AutoInstallError::ProcessError {
+                command: command.to_string(),
+                exit_code: Some(output.exit_code),
+                stderr: output.stderr,
+            });
+        }
+        Ok(())
+    }
+
+    /// Release the underlying device or process
+    fn close(&mut self) {}
+}
+
+/// Transport selected on the command line
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum TransportSpec {
+    /// The default: an SSH session
+    Ssh,
+    /// A local serial device, e.g. a USB console cable
+    Serial { device: String, baud: u32 },
+    /// Serial-over-LAN through the target's BMC using `ipmitool`
+    IpmiSol { bmc: String, username: String },
+}
+
+impl TransportSpec {
+    /// Baud rate used when a serial spec does not name one
+    pub const DEFAULT_BAUD: u32 = 115_200;
+
+    /// Parse `ssh`, `serial:<device>[@<baud>]` or `sol:[<user>@]<bmc>`
+    pub fn parse(spec: &str) -> Result<Self> {
+        let invalid = |reason: &str| {
+            AutoInstallError::ValidationError(format!(
+                "Invalid transport '{}': {} (expected ssh, serial:<device>[@<baud>] or sol:[<user>@]<bmc>)",
+                spec, reason
+            ))
+        };
+        if spec == "ssh" {
+            return Ok(Self::Ssh);
+        }
+        if let Some(rest) = spec.strip_prefix("serial:") {
+            let (device, baud) = match rest.split_once('@') {
+                Some((device, baud)) => (
+                    device,
+                    baud.parse()
+                        .map_err(|_| invalid("baud rate is not a number"))?,
+                ),
+                None => (rest, Self::DEFAULT_BAUD),
+            };
+            if !device.starts_with('/') {
+                return Err(invalid("serial device must be an absolute path"));
+            }
+            return Ok(Self::Serial {
+                device: device.to_string(),
+                baud,
+            });
+        }
+        if let Some(rest) = spec.strip_prefix("sol:") {
+            let (username, bmc) = rest.split_once('@').unwrap_or(("ADMIN", rest));
+            if bmc.is_empty() || username.is_empty() {
+                return Err(invalid("BMC host and user must not be empty"));
+            }
+            return Ok(Self::IpmiSol {
+                bmc: bmc.to_string(),
+                username: username.to_string(),
+            });
+        }
+        Err(invalid("unknown transport"))
+    }
+
+    /// Open the transport; `None` for SSH, which is handled by the client itself
+    pub fn open(&self) -> Result<Option<Box<dyn Transport>>> {
+        Ok(match self {
+            Self::Ssh => None,
+            Self::Serial { device, baud } => {
+                Some(Box::new(SerialTransport::open_device(device, *baud)?))
+            }
+            Self::IpmiSol { bmc, username } => {
+                Some(Box::new(SerialTransport::open_ipmi_sol(bmc, username)?))
+            }
+        })
+    }
+}
+
+const BASE64_ALPHABET: &[u8; 64] =
+    b"ABCDEFGHIJKLMNOPQRSTUVWXYZabcdefghijklmnopqrstuvwxyz0123456789+/";
+
+/// Standard padded base64, matching coreutils `base64`
+pub fn base64_encode(data: &[u8]) -> String {
+    let mut out = String::with_capacity(data.len().div_ceil(3) * 4);
+    for chunk in data.chunks(3) {
+        let b = [
+            chunk[0],
+            chunk.get(1).copied().unwrap_or(0),
+            chunk.get(2).copied().unwrap_or(0),
+        ];
+        let n = (u32::from(b[0]) << 16) | (u32::from(b[1]) << 8) | u32::from(b[2]);
+        for i in 0..4 {
+            if i <= chunk.len() {
+                out.push(BASE64_ALPHABET[((n >> (18 - 6 * i)) & 0x3f) as usize] as char);
+            } else {
+                out.push('=');
+            }
+        }
+    }
+    out
+}
+
+/// Decode standard base64, ignoring whitespace
+pub fn base64_decode(text: &str) -> Result<Vec<u8>> {
+    let mut out = Vec::with_capacity(text.len() / 4 * 3);
+    let mut acc = 0u32;
+    let mut bits = 0;
+    for c in text.bytes().filter(|c| !c.is_ascii_whitespace()) {
+        if c == b'=' {
+            break;
+        }
+        let value = BASE64_ALPHABET
+            .iter()
+            .position(|&a| a == c)
+            .ok_or_else(|| {
+                AutoInstallError::ValidationError(format!(
+                    "Invalid base64 character '{}'",
+                    c as char
+                ))
+            })?;
+        acc = (acc << 6) | value as u32;
+        bits += 6;
+        if bits >= 8 {
+            bits -= 8;
+            out.push((acc >> bits) as u8);
+            acc &= (1 << bits) - 1;
+        }
+    }
+    Ok(out)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_transport_specs() {
+        assert_eq!(TransportSpec::parse("ssh").unwrap(), TransportSpec::Ssh);
+        assert_eq!(
+            TransportSpec::parse("serial:/dev/ttyUSB0").unwrap(),
+            TransportSpec::Serial {
+                device: "/dev/ttyUSB0".to_string(),
+                baud: 115_200
+            }
+        );
+        assert_eq!(
+            TransportSpec::parse("serial:/dev/ttyS1@9600").unwrap(),
+            TransportSpec::Serial {
+                device: "/dev/ttyS1".to_string(),
+                baud: 9600
+            }
+        );
+        assert_eq!(
+            TransportSpec::parse("sol:root@10.0.0.50").unwrap(),
+            TransportSpec::IpmiSol {
+                bmc: "10.0.0.50".to_string(),
+                username: "root".to_string()
+            }
+        );
+        assert!(TransportSpec::parse("serial:ttyUSB0").is_err());
+        assert!(TransportSpec::parse("serial:/dev/ttyS0@fast").is_err());
+        assert!(TransportSpec::parse("telnet:host").is_err());
+    }
+
+    #[test]
+    fn test_base64_round_trip() {
+        assert_eq!(base64_encode(b""), "");
+        assert_eq!(base64_encode(b"f"), "Zg==");
+        assert_eq!(base64_encode(b"fo"), "Zm8=");
+        assert_eq!(base64_encode(b"foo"), "Zm9v");
+        let data: Vec<u8> = (0..=255).collect();
+        assert_eq!(base64_decode(&base64_encode(&data)).unwrap(), data);
+        assert_eq!(base64_decode("Zm9v\nYmFy\n").unwrap(), b"foobar");
+        assert!(base64_decode("Zm9v!").is_err());
+    }
+}
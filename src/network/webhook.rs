@@ -0,0 +1,131 @@
+// file: src/network/webhook.rs
+// version: 1.4.0
+// guid: 8b4d2f61-9e37-4a05-b1c8-3f7a6e0d2c94
+
+//! Webhook notifications carrying session records
+//!
+//! The payload is the session record itself, wrapped with an event name, so receivers can
+//! parse installs and upgrades with the same schema. Progress of long remote commands is
+//! posted as `progress` events carrying the parsed percentage instead of the session.
+
+use crate::error::AutoInstallError;
+use crate::logging::redact;
+use crate::network::progress::ProgressEvent;
+use crate::network::ssh_installer::session::InstallSession;
+use crate::Result;
+use serde_json::json;
+use std::time::Duration;
+
+/// Upper bound for one delivery attempt
+const REQUEST_TIMEOUT: Duration = Duration::from_secs(15);
+
+/// Posts session records as JSON to an HTTP endpoint
+#[derive(Clone)]
+pub struct WebhookNotifier {
+    client: reqwest::Client,
+    url: String,
+}
+
+impl WebhookNotifier {
+    pub fn new(url: &str) -> Result<Self> {
+        if !url.starts_with("http://") && !url.starts_with("https://") {
+            return Err(AutoInstallError::ValidationError(format!(
+                "Webhook URL must start with http:// or https://: {}",
+                url
+            )));
+        }
+        let client = reqwest::Client::builder()
+            .timeout(REQUEST_TIMEOUT)
+            .build()
+            .map_err(|e| {
+                AutoInstallError::NetworkError(format!("Failed to create webhook client: {}", e))
+            })?;
+        Ok(Self {
+            client,
+            url: url.to_string(),
+        })
+    }
+
+    /// `{"event", "hostname", "status", "session"}` body for `event`
+    pub fn payload(event: &str, session: &InstallSession) -> serde_json::Value {
+        json!({
+            "event": event,
+            "hostname": session.hostname,
+            "status": session.status,
+            "session": session,
+        })
+    }
+
+    /// `{"event": "progress", "hostname", "progress"}` body for a progress event
+    pub fn progress_payload(progress: &ProgressEvent) -> serde_json::Value {
+        json!({
+            "event": "progress",
+            "hostname": progress.host,
+            "progress": progress,
+        })
+    }
+
+    /// Deliver `event` for `session`; non-2xx responses are errors
+    pub async fn notify(&self, event: &str, session: &InstallSession) -> Result<()> {
+        self.post(event, &Self::payload(event, session)).await
+    }
+
+    /// Endpoint the notifier posts to
+    pub fn url(&self) -> &str {
+        &self.url
+    }
+
+    /// POST `payload`; non-2xx responses are errors
+    pub(crate) async fn post(&self, event: &str, payload: &serde_json::Value) -> Result<()> {
+        let mut payload = payload.clone();
+        redact::scrub_json(&mut payload);
+        let response = self.client.post(&self.url).json(&payload).send().await?;
+        if !response.status().is_success() {
+            return Err(AutoInstallError::NetworkError(format!(
+                "Webhook {} answered {} for {}",
+                self.url,
+                response.status(),
+                event
+            )));
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::network::ssh_installer::session::SessionStatus;
+
+    #[test]
+    fn test_payload_wraps_session() {
+        let mut session = InstallSession::new("web-01");
+        session.status = SessionStatus::Failed;
+        let payload = WebhookNotifier::payload("upgrade.rolled_back", &session);
+        assert_eq!(payload["event"], "upgrade.rolled_back");
+        assert_eq!(payload["hostname"], "web-01");
+        assert_eq!(payload["status"], "failed");
+        assert_eq!(payload["session"]["id"], session.id.as_str());
+    }
+
+    #[test]
+    fn test_progress_payload() {
+        let progress = ProgressEvent {
+            host: "web-01".to_string(),
+            tool: "debootstrap".to_string(),
+            percent: 45,
+            detail: "I: Extracting adduser...".to_string(),
+        };
+        let payload = WebhookNotifier::progress_payload(&progress);
+        assert_eq!(payload["event"], "progress");
+        assert_eq!(payload["hostname"], "web-01");
+        assert_eq!(payload["progress"]["percent"], 45);
+        assert_eq!(payload["progress"]["tool"], "debootstrap");
+    }
+
+    #[test]
+    fn test_rejects_non_http_url() {
+        assert!(WebhookNotifier::new("ftp://hooks.example/").is_err());
+        assert!(WebhookNotifier::new("https://hooks.example/upgrade").is_ok());
+    }
+}
@@ -0,0 +1,249 @@
+// file: src/security/enrollment.rs
+// version: 1.1.0
+// guid: 4b8e2f71-9a3c-4d56-8e10-c7f5a2d9b6e3
+
+//! One-time enrollment tokens for first-boot phone-home
+//!
+//! A random token is generated for each install, written onto the target and recorded
+//! (hashed) in `logs/<hostname>/enrollment.json`. A report receiver redeems the token
+//! exactly once before accepting the host's completion report; beacon sinks send it along in
+//! an `X-Uaa-Enrollment-Token` header once it is on the target.
+
+use crate::error::AutoInstallError;
+use crate::network::ssh_installer::session::InstallSession;
+use crate::Result;
+use chrono::{DateTime, Duration, Utc};
+use ring::rand::{SecureRandom, SystemRandom};
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use std::fs::File;
+use std::path::{Path, PathBuf};
+
+/// Token location on the installed system, relative to its root
+pub const TARGET_TOKEN_PATH: &str = "etc/ubuntu-autoinstall-agent/enrollment-token";
+
+/// How long a token stays valid when no explicit lifetime is given
+pub const DEFAULT_TOKEN_TTL_HOURS: i64 = 72;
+
+/// Random bytes per token (hex-encoded to twice this length)
+const TOKEN_BYTES: usize = 32;
+
+/// Issued enrollment token; only the SHA-256 of the token is stored
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct EnrollmentToken {
+    /// Host the token was issued for
+    pub hostname: String,
+    /// SHA-256 of the token, hex-encoded
+    pub token_sha256: String,
+    /// When the token was issued
+    pub issued_at: DateTime<Utc>,
+    /// When the token stops being accepted
+    pub expires_at: DateTime<Utc>,
+    /// When the token was redeemed; a redeemed token is never accepted again
+    pub consumed_at: Option<DateTime<Utc>>,
+}
+
+fn sha256_hex(data: &str) -> String {
+    Sha256::digest(data.as_bytes())
+        .iter()
+        .map(|b| format!("{:02x}", b))
+        .collect()
+}
+
+impl EnrollmentToken {
+    /// Issue a token for `hostname`, returning the record and the plaintext token
+    pub fn issue(hostname: &str, ttl: Duration) -> Result<(Self, String)> {
+        let mut bytes = [0u8; TOKEN_BYTES];
+        SystemRandom::new().fill(&mut bytes).map_err(|_| {
+            AutoInstallError::SystemError("Failed to generate enrollment token".to_string())
+        })?;
+        let token: String = bytes.iter().map(|b| format!("{:02x}", b)).collect();
+
+        let now = Utc::now();
+        let record = Self {
+            hostname: hostname.to_string(),
+            token_sha256: sha256_hex(&token),
+            issued_at: now,
+            expires_at: now + ttl,
+            consumed_at: None,
+        };
+        Ok((record, token))
+    }
+
+    /// Path of the enrollment record for `hostname`
+    pub fn record_path(base_dir: &Path, hostname: &str) -> PathBuf {
+        InstallSession::host_dir(base_dir, hostname).join("enrollment.json")
+    }
+
+    /// Write the record to `logs/<hostname>/enrollment.json` under `base_dir`
+    pub fn save(&self, base_dir: &Path) -> Result<PathBuf> {
+        let path = Self::record_path(base_dir, &self.hostname);
+        if let Some(parent) = path.parent() {
+            std::fs::create_dir_all(parent)?;
+        }
+        std::fs::write(&path, serde_json::to_string_pretty(self)?)?;
+        Ok(path)
+    }
+
+    /// Load the enrollment record for `hostname` under `base_dir`
+    pub fn load(base_dir: &Path, hostname: &str) -> Result<Self> {
+        let path = Self::record_path(base_dir, hostname);
+        let content = std::fs::read_to_string(&path).map_err(|e| {
+            AutoInstallError::ValidationError(format!(
+                "No enrollment token issued for {} ({}): {}",
+                hostname,
+                path.display(),
+                e
+            ))
+        })?;
+        Ok(serde_json::from_str(&content)?)
+    }
+
+    /// Check `presented` against this record at time `now` without consuming it
+    pub fn verify(&self, hostname: &str, presented: &str, now: DateTime<Utc>) -> Result<()> {
+        let reject = |reason: &str| {
+            Err(AutoInstallError::ValidationError(format!(
+                "Enrollment rejected for {}: {}",
+                hostname, reason
+            )))
+        };
+        if self.hostname != hostname {
+            return reject("token was issued for a different host");
+        }
+        if sha256_hex(presented.trim()) != self.token_sha256 {
+            return reject("token does not match");
+        }
+        if self.consumed_at.is_some() {
+            return reject("token was already used");
+        }
+        if now >= self.expires_at {
+            return reject("token expired");
+        }
+        Ok(())
+    }
+
+    /// Verify `presented` and mark the token used
+    pub fn consume(&mut self, hostname: &str, presented: &str) -> Result<()> {
+        let now = Utc::now();
+        self.verify(hostname, presented, now)?;
+        self.consumed_at = Some(now);
+        Ok(())
+    }
+}
+
+/// Redeem the token presented by `hostname`, persisting that it has been used
+///
+/// Receivers may redeem concurrently, so the record is read, consumed and written back under
+/// an exclusive lock on `enrollment.json.lock`; a token is accepted at most once.
+pub fn redeem(base_dir: &Path, hostname: &str, presented: &str) -> Result<EnrollmentToken> {
+    let path = EnrollmentToken::record_path(base_dir, hostname);
+    // Unknown hosts have no directory; loading below reports them
+    let _lock = match path.parent() {
+        Some(dir) if dir.is_dir() => Some(lock_exclusive(&dir.join("enrollment.json.lock"))?),
+        _ => None,
+    };
+    let mut record = EnrollmentToken::load(base_dir, hostname)?;
+    record.consume(hostname, presented)?;
+    record.save(base_dir)?;
+    Ok(record)
+}
+
+/// Open `path` and wait for an exclusive `flock` on it, held until the file is dropped
+fn lock_exclusive(path: &Path) -> Result<File> {
+    let file = std::fs::OpenOptions::new()
+        .create(true)
+        .truncate(false)
+        .write(true)
+        .open(path)?;
+    #[cfg(unix)]
+    {
+        use std::os::fd::AsRawFd;
+        loop {
+            // SAFETY: the fd stays open for the duration of the call
+            if unsafe { libc::flock(file.as_raw_fd(), libc::LOCK_EX) } == 0 {
+                break;
+            }
+            let error = std::io::Error::last_os_error();
+            if error.kind() != std::io::ErrorKind::Interrupted {
+                return Err(error.into());
+            }
+        }
+    }
+    Ok(file)
+}
+
+/// Command writing `token` to the installed system under `root`, readable by root only
+pub fn build_install_token_command(root: &str, token: &str) -> String {
+    let path = format!("{}/{}", root.trim_end_matches('/'), TARGET_TOKEN_PATH);
+    let dir = &path[..path.rfind('/').unwrap_or(0)];
+    format!(
+        "mkdir -p {} && chmod 700 {} && (umask 077 && printf '%s\\n' '{}' > {})",
+        dir, dir, token, path
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::TempDir;
+
+    #[test]
+    fn test_issue_stores_only_hash() {
+        let (record, token) = EnrollmentToken::issue("host-a", Duration::hours(1)).unwrap();
+        assert_eq!(token.len(), TOKEN_BYTES * 2);
+        assert_ne!(record.token_sha256, token);
+        assert!(record.verify("host-a", &token, Utc::now()).is_ok());
+
+        let (_, other) = EnrollmentToken::issue("host-a", Duration::hours(1)).unwrap();
+        assert_ne!(token, other);
+    }
+
+    #[test]
+    fn test_verify_rejects_wrong_host_token_and_expiry() {
+        let (record, token) = EnrollmentToken::issue("host-a", Duration::hours(1)).unwrap();
+        assert!(record.verify("host-b", &token, Utc::now()).is_err());
+        assert!(record.verify("host-a", "deadbeef", Utc::now()).is_err());
+        assert!(record
+            .verify("host-a", &token, Utc::now() + Duration::hours(2))
+            .is_err());
+    }
+
+    #[test]
+    fn test_redeem_is_one_time() {
+        let dir = TempDir::new().unwrap();
+        let (record, token) = EnrollmentToken::issue("host-a", Duration::hours(1)).unwrap();
+        record.save(dir.path()).unwrap();
+
+        let redeemed = redeem(dir.path(), "host-a", &format!("{}\n", token)).unwrap();
+        assert!(redeemed.consumed_at.is_some());
+        assert!(redeem(dir.path(), "host-a", &token).is_err());
+        assert!(redeem(dir.path(), "unknown", &token).is_err());
+    }
+
+    #[test]
+    fn test_concurrent_redeems_accept_one() {
+        let dir = TempDir::new().unwrap();
+        let (record, token) = EnrollmentToken::issue("host-a", Duration::hours(1)).unwrap();
+        record.save(dir.path()).unwrap();
+
+        let accepted = std::thread::scope(|scope| {
+            let attempts: Vec<_> = (0..8)
+                .map(|_| scope.spawn(|| redeem(dir.path(), "host-a", &token).is_ok()))
+                .collect();
+            attempts
+                .into_iter()
+                .map(|attempt| attempt.join().unwrap())
+                .filter(|ok| *ok)
+                .count()
+        });
+        assert_eq!(accepted, 1);
+    }
+
+    #[test]
+    fn test_install_token_command() {
+        assert_eq!(
+            build_install_token_command("/mnt/targetos/", "abc123"),
+            "mkdir -p /mnt/targetos/etc/ubuntu-autoinstall-agent && chmod 700 /mnt/targetos/etc/ubuntu-autoinstall-agent && (umask 077 && printf '%s\\n' 'abc123' > /mnt/targetos/etc/ubuntu-autoinstall-agent/enrollment-token)"
+        );
+    }
+}
@@ -1,11 +1,11 @@
 // file: src/security/luks.rs
-// version: 1.0.1
+// version: 1.1.0
 // guid: q7r8s9t0-u1v2-3456-7890-123456qrstuv
 
 //! LUKS encryption operations
 
 use crate::{config::LuksConfig, network::ssh::SshClient, Result};
-use tracing::{debug, info};
+use tracing::info;
 
 /// Manager for LUKS encryption operations
 pub struct LuksManager;
@@ -31,6 +31,7 @@ impl LuksManager {
                 "LUKS passphrase contains unresolved environment variable".to_string(),
             ));
         }
+        self.validate_config(config)?;
 
         // Create LUKS partition
         let luks_format_cmd = format!(
@@ -52,41 +53,6 @@ impl LuksManager {
         Ok(())
     }
 
-    /// Close LUKS partition
-    pub async fn close_luks_partition(&self, ssh: &mut SshClient) -> Result<()> {
-        debug!("Closing LUKS partition");
-        ssh.execute("cryptsetup luksClose ubuntu-root").await?;
-        Ok(())
-    }
-
-    /// Check if LUKS is properly configured
-    pub async fn verify_luks_setup(&self, ssh: &mut SshClient, device: &str) -> Result<bool> {
-        debug!("Verifying LUKS setup on {}", device);
-
-        let output = ssh
-            .execute_with_output(&format!("cryptsetup isLuks {}", device))
-            .await?;
-        Ok(output.trim().is_empty()) // cryptsetup isLuks returns empty on success
-    }
-
-    /// Generate secure LUKS passphrase
-    pub fn generate_passphrase(&self, length: usize) -> String {
-        use ring::rand::{SecureRandom, SystemRandom};
-
-        const CHARSET: &[u8] =
-            b"ABCDEFGHIJKLMNOPQRSTUVWXYZabcdefghijklmnopqrstuvwxyz0123456789!@#$%^&*";
-        let rng = SystemRandom::new();
-        let mut passphrase = vec![0u8; length];
-
-        for byte in passphrase.iter_mut() {
-            let mut random_byte = [0u8; 1];
-            rng.fill(&mut random_byte).unwrap();
-            *byte = CHARSET[random_byte[0] as usize % CHARSET.len()];
-        }
-
-        String::from_utf8(passphrase).unwrap()
-    }
-
     /// Validate LUKS configuration
     pub fn validate_config(&self, config: &LuksConfig) -> Result<()> {
         // Check cipher
@@ -137,15 +103,6 @@ impl Default for LuksManager {
 mod tests {
     use super::*;
 
-    #[test]
-    fn test_generate_passphrase() {
-        let manager = LuksManager::new();
-        let passphrase = manager.generate_passphrase(32);
-
-        assert_eq!(passphrase.len(), 32);
-        assert!(passphrase.is_ascii());
-    }
-
     #[test]
     fn test_validate_config() {
         let manager = LuksManager::new();
@@ -1,11 +1,12 @@
 // file: src/security/mod.rs
-// version: 1.0.0
+// version: 1.5.0
 // guid: p6q7r8s9-t0u1-2345-6789-012345pqrstu
 
-//! Security module for LUKS encryption and validation
+//! Security module for LUKS encryption, SSH certificates and validation
 
+pub mod enrollment;
 pub mod luks;
-pub mod validation;
+pub mod provenance;
+pub mod ssh_ca;
 
 pub use luks::LuksManager;
-pub use validation::ValidationUtils;
@@ -0,0 +1,437 @@
+// file: src/security/provenance.rs
+// version: 1.2.0
+// guid: 8f2a6c14-3e9b-4d70-b5c1-9e4d7a2f0b38
+
+//! Signed provenance for built images and deployments
+//!
+//! Every image gets `<image>.provenance.json` and every deployment gets
+//! `logs/<hostname>/provenance.json`, describing what was produced, from which inputs, by
+//! whom and when. The statement is stored as the exact signed JSON payload (DSSE-style) so
+//! verification never depends on re-serializing it. Signatures are Ed25519 with a key kept
+//! in the user's config directory, created on first use.
+
+use crate::error::AutoInstallError;
+use crate::Result;
+use chrono::{DateTime, Utc};
+use ring::rand::SystemRandom;
+use ring::signature::{Ed25519KeyPair, KeyPair, UnparsedPublicKey, ED25519};
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use std::collections::BTreeMap;
+use std::io::Read;
+use std::path::{Path, PathBuf};
+
+/// Suffix appended to an image path for its provenance document
+pub const PROVENANCE_SUFFIX: &str = ".provenance.json";
+
+/// Payload type recorded in the envelope
+pub const PAYLOAD_TYPE: &str = "application/vnd.ubuntu-autoinstall-agent.provenance.v1+json";
+
+/// What kind of artifact a statement describes
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ArtifactKind {
+    Image,
+    Deployment,
+}
+
+/// The artifact a statement is about
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Subject {
+    /// Image file name or deployed hostname
+    pub name: String,
+    /// SHA-256 of the image file, or the installation config checksum for deployments
+    pub sha256: String,
+}
+
+/// An input that went into the artifact
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Material {
+    /// Short role of the input, e.g. `spec`, `iso`, `apt-snapshot`
+    pub name: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub uri: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub sha256: Option<String>,
+}
+
+/// Who produced the artifact
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct BuilderIdentity {
+    /// `user@host` the tool ran as
+    pub id: String,
+    pub tool: String,
+    pub version: String,
+}
+
+impl BuilderIdentity {
+    /// Identity of the current process
+    pub fn current() -> Self {
+        let user = std::env::var("USER").unwrap_or_else(|_| "unknown".to_string());
+        let host = std::fs::read_to_string("/proc/sys/kernel/hostname")
+            .or_else(|_| std::fs::read_to_string("/etc/hostname"))
+            .map(|h| h.trim().to_string())
+            .unwrap_or_else(|_| "unknown".to_string());
+        Self {
+            id: format!("{}@{}", user, host),
+            tool: env!("CARGO_PKG_NAME").to_string(),
+            version: env!("CARGO_PKG_VERSION").to_string(),
+        }
+    }
+}
+
+/// Provenance statement for one artifact
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Provenance {
+    pub kind: ArtifactKind,
+    pub subject: Subject,
+    #[serde(default)]
+    pub materials: Vec<Material>,
+    /// Build or install settings that are not files, e.g. Ubuntu version or target disk
+    #[serde(default)]
+    pub parameters: BTreeMap<String, String>,
+    pub builder: BuilderIdentity,
+    pub started_at: DateTime<Utc>,
+    pub finished_at: DateTime<Utc>,
+}
+
+impl Provenance {
+    /// Statement for `subject` produced by this process, finished now
+    pub fn new(kind: ArtifactKind, subject: Subject, started_at: DateTime<Utc>) -> Self {
+        Self {
+            kind,
+            subject,
+            materials: Vec::new(),
+            parameters: BTreeMap::new(),
+            builder: BuilderIdentity::current(),
+            started_at,
+            finished_at: Utc::now(),
+        }
+    }
+
+    /// Add an input
+    pub fn material(mut self, name: &str, uri: Option<String>, sha256: Option<String>) -> Self {
+        self.materials.push(Material {
+            name: name.to_string(),
+            uri,
+            sha256,
+        });
+        self
+    }
+
+    /// Add a build or install parameter
+    pub fn parameter(mut self, key: &str, value: impl ToString) -> Self {
+        self.parameters.insert(key.to_string(), value.to_string());
+        self
+    }
+
+    /// Sign the statement
+    pub fn sign(&self, signer: &ProvenanceSigner) -> Result<SignedProvenance> {
+        let payload = serde_json::to_string(self)?;
+        Ok(SignedProvenance {
+            payload_type: PAYLOAD_TYPE.to_string(),
+            signatures: vec![signer.sign(&payload)],
+            payload,
+        })
+    }
+}
+
+/// Ed25519 key used to sign provenance
+pub struct ProvenanceSigner {
+    key_pair: Ed25519KeyPair,
+}
+
+impl ProvenanceSigner {
+    fn generate_pkcs8() -> Result<ring::pkcs8::Document> {
+        Ed25519KeyPair::generate_pkcs8(&SystemRandom::new())
+            .map_err(|_| key_error("failed to generate signing key"))
+    }
+
+    /// Default key location in the user's config directory
+    pub fn default_key_path() -> PathBuf {
+        dirs::config_dir()
+            .unwrap_or_else(|| PathBuf::from("/etc"))
+            .join("ubuntu-autoinstall-agent")
+            .join("provenance-key.pk8")
+    }
+
+    /// Load the PKCS#8 key at `path`, creating it (owner-readable only) if missing
+    ///
+    /// The public key is written next to it as `<path>.pub` (hex) for distribution to verifiers.
+    pub fn load_or_create(path: &Path) -> Result<Self> {
+        if !path.exists() {
+            let document = Self::generate_pkcs8()?;
+            if let Some(parent) = path.parent() {
+                std::fs::create_dir_all(parent)?;
+            }
+            write_private(path, document.as_ref())?;
+        }
+        let key_pair = Ed25519KeyPair::from_pkcs8(&std::fs::read(path)?)
+            .map_err(|e| key_error(&format!("invalid key {}: {}", path.display(), e)))?;
+        let signer = Self { key_pair };
+        let public_path = PathBuf::from(format!("{}.pub", path.display()));
+        if !public_path.exists() {
+            std::fs::write(&public_path, format!("{}\n", signer.public_key_hex()))?;
+        }
+        Ok(signer)
+    }
+
+    /// Hex-encoded public key
+    pub fn public_key_hex(&self) -> String {
+        hex(self.key_pair.public_key().as_ref())
+    }
+
+    fn sign(&self, payload: &str) -> ProvenanceSignature {
+        let public_key = self.public_key_hex();
+        ProvenanceSignature {
+            key_id: key_id(&public_key),
+            sig: hex(self.key_pair.sign(payload.as_bytes()).as_ref()),
+            public_key,
+        }
+    }
+}
+
+/// One signature over the payload
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ProvenanceSignature {
+    /// First 16 hex digits of the SHA-256 of the public key
+    pub key_id: String,
+    /// Hex-encoded Ed25519 public key
+    pub public_key: String,
+    /// Hex-encoded signature
+    pub sig: String,
+}
+
+/// Signed envelope as stored on disk
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct SignedProvenance {
+    pub payload_type: String,
+    /// The statement as the exact JSON that was signed
+    pub payload: String,
+    pub signatures: Vec<ProvenanceSignature>,
+}
+
+impl SignedProvenance {
+    /// Parse the statement without checking signatures
+    pub fn statement(&self) -> Result<Provenance> {
+        Ok(serde_json::from_str(&self.payload)?)
+    }
+
+    /// Check that a key from `trusted_keys` (hex) signed the payload and return the statement
+    pub fn verify(&self, trusted_keys: &[String]) -> Result<Provenance> {
+        if self.payload_type != PAYLOAD_TYPE {
+            return Err(AutoInstallError::ValidationError(format!(
+                "Unsupported provenance payload type: {}",
+                self.payload_type
+            )));
+        }
+        let trusted = self.signatures.iter().find(|s| {
+            trusted_keys
+                .iter()
+                .any(|k| k.trim().eq_ignore_ascii_case(&s.public_key))
+        });
+        let Some(signature) = trusted else {
+            return Err(AutoInstallError::ValidationError(format!(
+                "Provenance is not signed by a trusted key (signed by: {})",
+                self.signatures
+                    .iter()
+                    .map(|s| s.key_id.as_str())
+                    .collect::<Vec<_>>()
+                    .join(", ")
+            )));
+        };
+        let (Some(public_key), Some(sig)) = (unhex(&signature.public_key), unhex(&signature.sig))
+        else {
+            return Err(AutoInstallError::ValidationError(
+                "Malformed provenance signature".to_string(),
+            ));
+        };
+        UnparsedPublicKey::new(&ED25519, public_key)
+            .verify(self.payload.as_bytes(), &sig)
+            .map_err(|_| {
+                AutoInstallError::ValidationError(format!(
+                    "Provenance signature by {} does not match its payload",
+                    signature.key_id
+                ))
+            })?;
+        self.statement()
+    }
+
+    /// Write the envelope to `path`
+    pub fn save(&self, path: &Path) -> Result<()> {
+        if let Some(parent) = path.parent() {
+            std::fs::create_dir_all(parent)?;
+        }
+        std::fs::write(path, serde_json::to_string_pretty(self)?)?;
+        Ok(())
+    }
+
+    /// Read an envelope from `path`
+    pub fn load(path: &Path) -> Result<Self> {
+        let content = std::fs::read_to_string(path).map_err(|e| {
+            AutoInstallError::ValidationError(format!("No provenance at {}: {}", path.display(), e))
+        })?;
+        Ok(serde_json::from_str(&content)?)
+    }
+}
+
+/// Provenance document path for an image file
+pub fn provenance_path_for(artifact: &Path) -> PathBuf {
+    PathBuf::from(format!("{}{}", artifact.display(), PROVENANCE_SUFFIX))
+}
+
+/// Sign `provenance` with the default key and write it to `path`
+pub fn record(provenance: &Provenance, path: &Path) -> Result<()> {
+    let signer = ProvenanceSigner::load_or_create(&ProvenanceSigner::default_key_path())?;
+    provenance.sign(&signer)?.save(path)
+}
+
+/// Streaming SHA-256 of a file, hex-encoded
+pub fn sha256_file(path: &Path) -> Result<String> {
+    let mut file = std::fs::File::open(path)?;
+    let mut hasher = Sha256::new();
+    let mut buffer = vec![0u8; 1 << 20];
+    loop {
+        let read = file.read(&mut buffer)?;
+        if read == 0 {
+            break;
+        }
+        hasher.update(&buffer[..read]);
+    }
+    Ok(format!("{:x}", hasher.finalize()))
+}
+
+/// SHA-256 of a large input file, cached in `<path>.sha256` so it is hashed only once
+pub fn sha256_file_cached(path: &Path) -> Result<String> {
+    let cache = PathBuf::from(format!("{}.sha256", path.display()));
+    if let Ok(cached) = std::fs::read_to_string(&cache) {
+        let fresh = match (std::fs::metadata(&cache), std::fs::metadata(path)) {
+            (Ok(c), Ok(p)) => match (c.modified(), p.modified()) {
+                (Ok(c), Ok(p)) => c >= p,
+                _ => false,
+            },
+            _ => false,
+        };
+        if fresh {
+            return Ok(cached.trim().to_string());
+        }
+    }
+    let digest = sha256_file(path)?;
+    let _ = std::fs::write(&cache, format!("{}\n", digest));
+    Ok(digest)
+}
+
+fn key_id(public_key_hex: &str) -> String {
+    format!("{:x}", Sha256::digest(public_key_hex.as_bytes()))[..16].to_string()
+}
+
+fn key_error(reason: &str) -> AutoInstallError {
+    AutoInstallError::SystemError(format!("Provenance signing key: {}", reason))
+}
+
+fn hex(bytes: &[u8]) -> String {
+    bytes.iter().map(|b| format!("{:02x}", b)).collect()
+}
+
+fn unhex(text: &str) -> Option<Vec<u8>> {
+    if !text.len().is_multiple_of(2) {
+        return None;
+    }
+    (0..text.len())
+        .step_by(2)
+        .map(|i| u8::from_str_radix(text.get(i..i + 2)?, 16).ok())
+        .collect()
+}
+
+/// Create `path` readable by its owner only, failing if it exists
+#[cfg(unix)]
+pub(crate) fn write_private(path: &Path, data: &[u8]) -> Result<()> {
+    use std::io::Write;
+    use std::os::unix::fs::OpenOptionsExt;
+    let mut file = std::fs::OpenOptions::new()
+        .write(true)
+        .create_new(true)
+        .mode(0o600)
+        .open(path)?;
+    file.write_all(data)?;
+    Ok(())
+}
+
+#[cfg(not(unix))]
+pub(crate) fn write_private(path: &Path, data: &[u8]) -> Result<()> {
+    std::fs::write(path, data)?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::TempDir;
+
+    fn statement() -> Provenance {
+        Provenance::new(
+            ArtifactKind::Image,
+            Subject {
+                name: "ubuntu-24.04-amd64.qcow2".to_string(),
+                sha256: "ab".repeat(32),
+            },
+            Utc::now(),
+        )
+        .material("spec", None, Some("cd".repeat(32)))
+        .parameter("ubuntu_version", "24.04")
+    }
+
+    #[test]
+    fn test_sign_and_verify_roundtrip() {
+        let dir = TempDir::new().unwrap();
+        let signer = ProvenanceSigner::load_or_create(&dir.path().join("a.pk8")).unwrap();
+        let signed = statement().sign(&signer).unwrap();
+        let path = dir.path().join("image.qcow2.provenance.json");
+        signed.save(&path).unwrap();
+
+        let loaded = SignedProvenance::load(&path).unwrap();
+        let verified = loaded.verify(&[signer.public_key_hex()]).unwrap();
+        assert_eq!(verified.subject.name, "ubuntu-24.04-amd64.qcow2");
+        assert_eq!(verified.parameters["ubuntu_version"], "24.04");
+        assert_eq!(verified.builder.tool, env!("CARGO_PKG_NAME"));
+
+        let other = ProvenanceSigner::load_or_create(&dir.path().join("b.pk8")).unwrap();
+        assert!(loaded.verify(&[other.public_key_hex()]).is_err());
+    }
+
+    #[test]
+    fn test_tampered_payload_is_rejected() {
+        let dir = TempDir::new().unwrap();
+        let signer = ProvenanceSigner::load_or_create(&dir.path().join("a.pk8")).unwrap();
+        let mut signed = statement().sign(&signer).unwrap();
+        signed.payload = signed.payload.replace("24.04", "22.04");
+        assert!(signed.verify(&[signer.public_key_hex()]).is_err());
+        // The statement itself still parses for inspection
+        assert_eq!(
+            signed.statement().unwrap().parameters["ubuntu_version"],
+            "22.04"
+        );
+    }
+
+    #[test]
+    fn test_key_is_persisted_and_file_digests() {
+        let dir = TempDir::new().unwrap();
+        let key_path = dir.path().join("keys").join("provenance-key.pk8");
+        let first = ProvenanceSigner::load_or_create(&key_path).unwrap();
+        let second = ProvenanceSigner::load_or_create(&key_path).unwrap();
+        assert_eq!(first.public_key_hex(), second.public_key_hex());
+        let public = std::fs::read_to_string(format!("{}.pub", key_path.display())).unwrap();
+        assert_eq!(public.trim(), first.public_key_hex());
+
+        let file = dir.path().join("input.iso");
+        std::fs::write(&file, b"abc").unwrap();
+        let expected = "ba7816bf8f01cfea414140de5dae2223b00361a396177a9cb410ff61f20015ad";
+        assert_eq!(sha256_file(&file).unwrap(), expected);
+        assert_eq!(sha256_file_cached(&file).unwrap(), expected);
+        assert_eq!(sha256_file_cached(&file).unwrap(), expected);
+        assert_eq!(
+            provenance_path_for(&file),
+            dir.path().join("input.iso.provenance.json")
+        );
+    }
+}
@@ -0,0 +1,159 @@
+// file: src/utils/block_device.rs
+// version: 1.0.0
+// guid: 1e6b9d34-8f27-4c50-a3d1-7b4e2c9f0a85
+
+//! Install disks and their partitions
+//!
+//! Partition names depend on how the disk is named: `/dev/sda` + 4 is `/dev/sda4`,
+//! `/dev/nvme0n1` + 4 is `/dev/nvme0n1p4`, and udev's stable links (`/dev/disk/by-id/...`) and
+//! multipath maps (`/dev/mapper/mpatha`) use a `-part4` suffix. With NVMe native multipath the
+//! kernel also lists per-controller paths (`nvme0c0n1`) that are not usable block devices; the
+//! namespace head (`nvme0n1`) is. [`DiskProbe`] resolves whatever the config names to a stable
+//! `by-id` path on the target so that every phase refers to the same disk, whichever
+//! controller or namespace numbering the live system booted with.
+
+use regex::Regex;
+
+/// Path of partition `number` of `disk`
+pub fn partition_path(disk: &str, number: u32) -> String {
+    if disk.starts_with("/dev/disk/by-") || disk.starts_with("/dev/mapper/") {
+        format!("{}-part{}", disk, number)
+    } else if disk.ends_with(|c: char| c.is_ascii_digit()) {
+        format!("{}p{}", disk, number)
+    } else {
+        format!("{}{}", disk, number)
+    }
+}
+
+/// Namespace head of an NVMe multipath controller path: `/dev/nvme0c1n2` is `/dev/nvme0n2`
+pub fn nvme_namespace_head(path: &str) -> Option<String> {
+    let re = Regex::new(r"^(/dev/)?nvme(\d+)c\d+n(\d+)$").ok()?;
+    let caps = re.captures(path)?;
+    Some(format!(
+        "{}nvme{}n{}",
+        caps.get(1).map_or("", |m| m.as_str()),
+        &caps[2],
+        &caps[3]
+    ))
+}
+
+/// `by-id` link prefixes in order of preference; each has `-partN` links for its partitions
+const STABLE_PREFIXES: &[&str] = &[
+    "dm-name-",
+    "wwn-",
+    "nvme-eui.",
+    "nvme-uuid.",
+    "nvme-",
+    "scsi-",
+    "ata-",
+    "usb-",
+];
+
+/// What the target reports about a disk, from [`DiskProbe::command`]
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct DiskProbe {
+    /// Kernel device node the configured path resolves to
+    pub device: String,
+    /// device-mapper UUID, `mpath-...` for a multipath map
+    pub dm_uuid: Option<String>,
+    /// `by-id` links pointing at the device
+    pub links: Vec<String>,
+}
+
+impl DiskProbe {
+    /// Shell command printing the probe of `disk` as `key=value` lines
+    pub fn command(disk: &str) -> String {
+        format!(
+            "d=$(readlink -f '{}'); echo \"device=$d\"; n=${{d#/dev/}}; \
+             [ -r /sys/class/block/$n/dm/uuid ] && echo \"dm_uuid=$(cat /sys/class/block/$n/dm/uuid)\"; \
+             for l in /dev/disk/by-id/*; do case \"$l\" in *-part[0-9]*) continue;; esac; \
+             [ \"$(readlink -f \"$l\")\" = \"$d\" ] && echo \"link=$l\"; done; true",
+            disk
+        )
+    }
+
+    pub fn parse(output: &str) -> Self {
+        let mut probe = Self::default();
+        for line in output.lines() {
+            match line.trim().split_once('=') {
+                Some(("device", value)) => probe.device = value.to_string(),
+                Some(("dm_uuid", value)) if !value.is_empty() => {
+                    probe.dm_uuid = Some(value.to_string())
+                }
+                Some(("link", value)) => probe.links.push(value.to_string()),
+                _ => {}
+            }
+        }
+        probe
+    }
+
+    pub fn is_multipath(&self) -> bool {
+        self.dm_uuid
+            .as_deref()
+            .is_some_and(|uuid| uuid.starts_with("mpath-"))
+    }
+
+    /// Most stable link to the device, or `None` when udev has no `by-id` link for it
+    pub fn stable_path(&self) -> Option<String> {
+        STABLE_PREFIXES.iter().find_map(|prefix| {
+            let mut matching: Vec<&String> = self
+                .links
+                .iter()
+                .filter(|link| {
+                    link.rsplit('/')
+                        .next()
+                        .is_some_and(|name| name.starts_with(prefix))
+                })
+                .collect();
+            // Several links of one kind (e.g. nvme-<model>_<serial> and its _1 alias): shortest
+            matching.sort_by_key(|link| (link.len(), link.to_string()));
+            matching.first().map(|link| link.to_string())
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_partition_paths_and_probe() {
+        assert_eq!(partition_path("/dev/sda", 4), "/dev/sda4");
+        assert_eq!(partition_path("/dev/nvme0n2", 1), "/dev/nvme0n2p1");
+        assert_eq!(
+            partition_path("/dev/mapper/mpatha", 3),
+            "/dev/mapper/mpatha-part3"
+        );
+        assert_eq!(
+            partition_path("/dev/disk/by-id/nvme-eui.0025388b", 4),
+            "/dev/disk/by-id/nvme-eui.0025388b-part4"
+        );
+        assert_eq!(
+            nvme_namespace_head("/dev/nvme1c3n2").as_deref(),
+            Some("/dev/nvme1n2")
+        );
+        assert_eq!(nvme_namespace_head("/dev/nvme0n1"), None);
+
+        let probe = DiskProbe::parse(
+            "device=/dev/nvme0n1\n\
+             link=/dev/disk/by-id/nvme-Samsung_SSD_980_S64DNF0R\n\
+             link=/dev/disk/by-id/nvme-Samsung_SSD_980_S64DNF0R_1\n\
+             link=/dev/disk/by-id/nvme-eui.002538b911b2c3d4\n",
+        );
+        assert!(!probe.is_multipath());
+        assert_eq!(
+            probe.stable_path().as_deref(),
+            Some("/dev/disk/by-id/nvme-eui.002538b911b2c3d4")
+        );
+
+        let mpath = DiskProbe::parse(
+            "device=/dev/dm-0\ndm_uuid=mpath-3600a0980\nlink=/dev/disk/by-id/dm-uuid-mpath-3600a0980\nlink=/dev/disk/by-id/dm-name-mpatha\n",
+        );
+        assert!(mpath.is_multipath());
+        assert_eq!(
+            mpath.stable_path().as_deref(),
+            Some("/dev/disk/by-id/dm-name-mpatha")
+        );
+        assert_eq!(DiskProbe::parse("device=/dev/vda\n").stable_path(), None);
+    }
+}
@@ -0,0 +1,153 @@
+// file: src/utils/cancel.rs
+// version: 1.0.0
+// guid: 8c3e6f1a-94d2-4b7e-a05c-3f2d7e9b1c64
+
+//! Cooperative cancellation and structured timeouts
+//!
+//! A [`CancellationToken`] is created once in `main`, triggered by Ctrl+C, and cloned into
+//! long-running components (`SshClient`, `SshInstaller`, `VmManager`). Components check the
+//! token between remote commands or poll iterations so they can stop at a safe point and
+//! record where they stopped instead of being killed mid-operation.
+
+use crate::Result;
+use std::future::Future;
+use std::sync::Arc;
+use std::time::Duration;
+use tokio::sync::watch;
+
+/// Cloneable cancellation flag shared between the signal handler and workers
+#[derive(Debug, Clone)]
+pub struct CancellationToken {
+    sender: Arc<watch::Sender<bool>>,
+    receiver: watch::Receiver<bool>,
+}
+
+impl CancellationToken {
+    /// Create a new, untriggered token
+    pub fn new() -> Self {
+        let (sender, receiver) = watch::channel(false);
+        Self {
+            sender: Arc::new(sender),
+            receiver,
+        }
+    }
+
+    /// Request cancellation; all clones observe it
+    pub fn cancel(&self) {
+        self.sender.send_replace(true);
+    }
+
+    /// Whether cancellation has been requested
+    pub fn is_cancelled(&self) -> bool {
+        *self.receiver.borrow()
+    }
+
+    /// Return a `CancelledError` if cancellation has been requested
+    pub fn check(&self, context: &str) -> Result<()> {
+        if self.is_cancelled() {
+            return Err(crate::error::AutoInstallError::CancelledError(
+                context.to_string(),
+            ));
+        }
+        Ok(())
+    }
+
+    /// Wait until cancellation is requested
+    pub async fn cancelled(&self) {
+        let mut receiver = self.receiver.clone();
+        // The sender lives as long as any clone of the token, so this only ends on cancel
+        let _ = receiver.wait_for(|cancelled| *cancelled).await;
+    }
+
+    /// Run `future` bounded by an optional timeout, stopping early on cancellation
+    pub async fn run<F, T>(
+        &self,
+        description: &str,
+        timeout: Option<Duration>,
+        future: F,
+    ) -> Result<T>
+    where
+        F: Future<Output = Result<T>>,
+    {
+        self.check(description)?;
+        let bounded = async {
+            match timeout {
+                Some(limit) => tokio::time::timeout(limit, future).await.map_err(|_| {
+                    crate::error::AutoInstallError::TimeoutError(format!(
+                        "{} did not finish within {}s",
+                        description,
+                        limit.as_secs()
+                    ))
+                })?,
+                None => future.await,
+            }
+        };
+
+        tokio::select! {
+            result = bounded => result,
+            _ = self.cancelled() => Err(crate::error::AutoInstallError::CancelledError(
+                description.to_string(),
+            )),
+        }
+    }
+}
+
+impl Default for CancellationToken {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_cancel_is_shared_between_clones() {
+        let token = CancellationToken::new();
+        let clone = token.clone();
+        assert!(!clone.is_cancelled());
+        assert!(clone.check("phase").is_ok());
+
+        token.cancel();
+        assert!(clone.is_cancelled());
+        let err = clone.check("Phase 2").unwrap_err();
+        assert!(err.to_string().contains("Phase 2"));
+    }
+
+    #[tokio::test]
+    async fn test_run_times_out() {
+        let token = CancellationToken::new();
+        let result: Result<()> = token
+            .run("slow step", Some(Duration::from_millis(20)), async {
+                tokio::time::sleep(Duration::from_secs(5)).await;
+                Ok(())
+            })
+            .await;
+        assert!(matches!(
+            result,
+            Err(crate::error::AutoInstallError::TimeoutError(_))
+        ));
+    }
+
+    #[tokio::test]
+    async fn test_run_stops_on_cancel() {
+        let token = CancellationToken::new();
+        let trigger = token.clone();
+        tokio::spawn(async move {
+            tokio::time::sleep(Duration::from_millis(20)).await;
+            trigger.cancel();
+        });
+
+        let result: Result<()> = token
+            .run("long step", None, async {
+                tokio::time::sleep(Duration::from_secs(5)).await;
+                Ok(())
+            })
+            .await;
+        assert!(matches!(
+            result,
+            Err(crate::error::AutoInstallError::CancelledError(_))
+        ));
+    }
+}
@@ -0,0 +1,327 @@
+// file: src/utils/guest_agent.rs
+// version: 1.1.0
+// guid: 8e4b1c73-2f6a-4d95-a0c8-7b3d9e5f1a26
+
+//! QEMU guest agent client for the image build VM
+//!
+//! The build VM exposes `org.qemu.guest_agent.0` on a virtio-serial port backed by a unix
+//! socket on the host. The installer environment starts `qemu-guest-agent` from the
+//! autoinstall `early-commands`, and the `late-commands`/`error-commands` write
+//! [`INSTALL_STATUS_FILE`], so the host can tell exactly when the install finished or failed
+//! instead of scraping the serial log.
+
+use crate::error::AutoInstallError;
+use crate::network::transport::base64_decode;
+use crate::Result;
+use serde_json::{json, Value};
+use std::path::{Path, PathBuf};
+use std::time::Duration;
+use tokio::io::{AsyncBufReadExt, AsyncWriteExt, BufReader};
+use tokio::net::UnixStream;
+
+/// Host side of the guest agent channel
+pub const GUEST_AGENT_SOCKET: &str = "/tmp/qemu-guest-agent.sock";
+
+/// File the autoinstall config writes in the installer environment: `running`, `done` or `failed`
+pub const INSTALL_STATUS_FILE: &str = "/run/autoinstall-agent.status";
+
+/// Installer log whose last line is reported as progress
+pub const INSTALL_PROGRESS_LOG: &str = "/var/log/installer/curtin-install.log";
+
+/// How long a single agent request may take before the agent is considered unavailable
+const REQUEST_TIMEOUT: Duration = Duration::from_secs(10);
+
+/// How often `guest-exec-status` is polled while a command runs
+const EXEC_POLL_INTERVAL: Duration = Duration::from_millis(250);
+
+/// QEMU arguments attaching a guest agent channel backed by `socket`
+pub fn qemu_args(socket: &Path) -> Vec<String> {
+    vec![
+        "-chardev".to_string(),
+        format!(
+            "socket,path={},server=on,wait=off,id=qga0",
+            socket.display()
+        ),
+        "-device".to_string(),
+        "virtio-serial".to_string(),
+        "-device".to_string(),
+        "virtserialport,chardev=qga0,name=org.qemu.guest_agent.0".to_string(),
+    ]
+}
+
+/// State of the install inside the VM as reported through the agent
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum InstallStatus {
+    /// Still installing; carries the last installer log line, if any
+    Running(Option<String>),
+    Done,
+    Failed,
+}
+
+impl InstallStatus {
+    /// Interpret the status file contents and the last progress log line
+    pub fn parse(status: &str, progress: &str) -> Self {
+        match status.trim() {
+            "done" => InstallStatus::Done,
+            "failed" => InstallStatus::Failed,
+            _ => {
+                let line = progress.trim();
+                InstallStatus::Running((!line.is_empty()).then(|| line.to_string()))
+            }
+        }
+    }
+}
+
+/// Result of `guest-exec`
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct GuestExecOutput {
+    pub exit_code: i32,
+    pub stdout: String,
+    pub stderr: String,
+}
+
+/// Client for the guest agent socket of one VM
+#[derive(Debug, Clone)]
+pub struct GuestAgent {
+    socket: PathBuf,
+}
+
+impl GuestAgent {
+    pub fn new(socket: impl Into<PathBuf>) -> Self {
+        Self {
+            socket: socket.into(),
+        }
+    }
+
+    /// Run `command` through `/bin/sh -c` in the guest and wait for it to exit
+    pub async fn exec(&self, command: &str) -> Result<GuestExecOutput> {
+        let started = self
+            .request(
+                "guest-exec",
+                Some(json!({
+                    "path": "/bin/sh",
+                    "arg": ["-c", command],
+                    "capture-output": true,
+                })),
+            )
+            .await?;
+        let pid = started["pid"].as_i64().ok_or_else(|| {
+            AutoInstallError::VmError(format!("guest-exec returned no pid: {}", started))
+        })?;
+
+        loop {
+            let status = self
+                .request("guest-exec-status", Some(json!({ "pid": pid })))
+                .await?;
+            if let Some(output) = parse_exec_status(&status)? {
+                return Ok(output);
+            }
+            tokio::time::sleep(EXEC_POLL_INTERVAL).await;
+        }
+    }
+
+    /// Current install status inside the VM
+    pub async fn install_status(&self) -> Result<InstallStatus> {
+        let output = self
+            .exec(&format!(
+                "cat {} 2>/dev/null; echo; tail -n 1 {} 2>/dev/null",
+                INSTALL_STATUS_FILE, INSTALL_PROGRESS_LOG
+            ))
+            .await?;
+        let (status, progress) = output
+            .stdout
+            .split_once('\n')
+            .unwrap_or((&output.stdout, ""));
+        Ok(InstallStatus::parse(status, progress))
+    }
+
+    async fn connect(&self) -> Result<UnixStream> {
+        UnixStream::connect(&self.socket).await.map_err(|e| {
+            AutoInstallError::VmError(format!(
+                "Guest agent socket {} unavailable: {}",
+                self.socket.display(),
+                e
+            ))
+        })
+    }
+
+    /// Send one command and return its `return` value
+    ///
+    /// Each request uses a fresh connection preceded by `guest-sync`, which flushes any reply
+    /// left over from an earlier request that timed out.
+    async fn request(&self, execute: &str, arguments: Option<Value>) -> Result<Value> {
+        let exchange = async {
+            let stream = self.connect().await?;
+            let (read, mut write) = stream.into_split();
+            let mut lines = BufReader::new(read).lines();
+
+            let sync_id = u64::from(uuid::Uuid::new_v4().as_u128() as u32);
+            write
+                .write_all(build_request("guest-sync", Some(json!({ "id": sync_id }))).as_bytes())
+                .await?;
+            loop {
+                let line = lines.next_line().await?.ok_or_else(closed)?;
+                if parse_response(&line).ok() == Some(json!(sync_id)) {
+                    break;
+                }
+            }
+
+            write
+                .write_all(build_request(execute, arguments).as_bytes())
+                .await?;
+            let line = lines.next_line().await?.ok_or_else(closed)?;
+            parse_response(&line)
+        };
+        tokio::time::timeout(REQUEST_TIMEOUT, exchange)
+            .await
+            .map_err(|_| {
+                AutoInstallError::TimeoutError(format!(
+                    "Guest agent did not answer {} within {}s",
+                    execute,
+                    REQUEST_TIMEOUT.as_secs()
+                ))
+            })?
+    }
+}
+
+fn closed() -> AutoInstallError {
+    AutoInstallError::VmError("Guest agent closed the connection".to_string())
+}
+
+/// One QMP-style request line
+pub fn build_request(execute: &str, arguments: Option<Value>) -> String {
+    let mut request = json!({ "execute": execute });
+    if let Some(arguments) = arguments {
+        request["arguments"] = arguments;
+    }
+    format!("{}\n", request)
+}
+
+/// The `return` value of a response line, or the agent's error
+pub fn parse_response(line: &str) -> Result<Value> {
+    let mut response: Value = serde_json::from_str(line.trim())?;
+    if let Some(error) = response.get("error") {
+        return Err(AutoInstallError::VmError(format!(
+            "Guest agent error {}: {}",
+            error["class"].as_str().unwrap_or("unknown"),
+            error["desc"].as_str().unwrap_or("")
+        )));
+    }
+    response
+        .get_mut("return")
+        .map(Value::take)
+        .ok_or_else(|| AutoInstallError::VmError(format!("Unexpected guest agent reply: {}", line)))
+}
+
+/// Output of a finished `guest-exec`, or `None` while it is still running
+pub fn parse_exec_status(status: &Value) -> Result<Option<GuestExecOutput>> {
+    if !status["exited"].as_bool().unwrap_or(false) {
+        return Ok(None);
+    }
+    let decode = |key: &str| -> Result<String> {
+        Ok(match status[key].as_str() {
+            Some(data) => String::from_utf8_lossy(&base64_decode(data)?).into_owned(),
+            None => String::new(),
+        })
+    };
+    Ok(Some(GuestExecOutput {
+        // Killed by a signal: report it the way a shell would
+        exit_code: status["exitcode"]
+            .as_i64()
+            .or_else(|| status["signal"].as_i64().map(|s| 128 + s))
+            .unwrap_or(-1) as i32,
+        stdout: decode("out-data")?,
+        stderr: decode("err-data")?,
+    }))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tokio::net::UnixListener;
+
+    #[test]
+    fn test_request_and_response_framing() {
+        assert_eq!(
+            build_request("guest-ping", None),
+            "{\"execute\":\"guest-ping\"}\n"
+        );
+        assert_eq!(parse_response("{\"return\": {}}").unwrap(), json!({}));
+        let err = parse_response(
+            "{\"error\": {\"class\": \"GenericError\", \"desc\": \"no such file\"}}",
+        )
+        .unwrap_err();
+        assert!(err.to_string().contains("no such file"));
+        assert!(parse_response("{}").is_err());
+    }
+
+    #[test]
+    fn test_exec_status_decoding() {
+        assert_eq!(parse_exec_status(&json!({"exited": false})).unwrap(), None);
+        let output = parse_exec_status(
+            &json!({"exited": true, "exitcode": 0, "out-data": "ZG9uZQpsaW5lCg=="}),
+        )
+        .unwrap()
+        .unwrap();
+        assert_eq!(output.stdout, "done\nline\n");
+        assert_eq!(
+            parse_exec_status(&json!({"exited": true, "signal": 9}))
+                .unwrap()
+                .unwrap()
+                .exit_code,
+            137
+        );
+    }
+
+    #[test]
+    fn test_install_status_parsing() {
+        assert_eq!(InstallStatus::parse("done\n", ""), InstallStatus::Done);
+        assert_eq!(InstallStatus::parse("failed", "x"), InstallStatus::Failed);
+        assert_eq!(
+            InstallStatus::parse("running", "curtin: extracting image\n"),
+            InstallStatus::Running(Some("curtin: extracting image".to_string()))
+        );
+        assert_eq!(InstallStatus::parse("", ""), InstallStatus::Running(None));
+        assert!(qemu_args(Path::new("/tmp/qga.sock"))
+            .contains(&"socket,path=/tmp/qga.sock,server=on,wait=off,id=qga0".to_string()));
+    }
+
+    #[tokio::test]
+    async fn test_exec_against_fake_agent() {
+        let dir = tempfile::TempDir::new().unwrap();
+        let socket = dir.path().join("qga.sock");
+        let listener = UnixListener::bind(&socket).unwrap();
+
+        // Answer each connection's guest-sync, then the one request that follows it
+        tokio::spawn(async move {
+            loop {
+                let (stream, _) = listener.accept().await.unwrap();
+                let (read, mut write) = stream.into_split();
+                let mut lines = BufReader::new(read).lines();
+                while let Ok(Some(line)) = lines.next_line().await {
+                    let request: Value = serde_json::from_str(&line).unwrap();
+                    let reply = match request["execute"].as_str().unwrap() {
+                        "guest-sync" => json!({"return": request["arguments"]["id"]}),
+                        "guest-exec" => json!({"return": {"pid": 42}}),
+                        "guest-exec-status" => json!({"return": {
+                            "exited": true, "exitcode": 0, "out-data": "ZG9uZQpzdGVwIDMK"
+                        }}),
+                        _ => json!({"error": {"class": "CommandNotFound", "desc": "nope"}}),
+                    };
+                    write
+                        .write_all(format!("{}\n", reply).as_bytes())
+                        .await
+                        .unwrap();
+                }
+            }
+        });
+
+        let agent = GuestAgent::new(&socket);
+        assert_eq!(agent.install_status().await.unwrap(), InstallStatus::Done);
+        assert!(agent.request("guest-ping", None).await.is_err());
+        assert!(GuestAgent::new(dir.path().join("missing.sock"))
+            .request("guest-ping", None)
+            .await
+            .is_err());
+    }
+}
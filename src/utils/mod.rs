@@ -1,18 +1,24 @@
 // file: src/utils/mod.rs
-// version: 1.2.0
+// version: 1.10.0
 // guid: o8p7q6r5-s4t3-2u1v-0987-w5x4y3z2a1b0
 
 //! Utility modules for the Ubuntu AutoInstall Agent
 
-pub mod coreutils;
-pub mod disk;
+pub mod block_device;
+pub mod cancel;
+pub mod guest_agent;
 pub mod qemu;
+pub mod qemu_profile;
+pub mod qmp;
+pub mod screen_capture;
+pub mod shell;
 pub mod system;
 pub mod vm;
 
 // Re-export commonly used utilities
-pub use coreutils::CoreUtils;
-pub use disk::DiskUtils;
+pub use cancel::CancellationToken;
 pub use qemu::QemuUtils;
-pub use system::SystemUtils;
+pub use qemu_profile::QemuMachine;
+pub use screen_capture::ScreenCaptureOptions;
+pub use shell::shell_quote;
 pub use vm::VmManager;
@@ -0,0 +1,286 @@
+// file: src/utils/qemu_profile.rs
+// version: 1.1.0
+// guid: 5b8e2d47-9c13-4a6f-b2d0-7e4f1a9c3b58
+
+//! QEMU machine profiles and firmware discovery
+//!
+//! Each [`MachineProfile`] fixes the QEMU binary, machine type, CPU model and firmware of a
+//! build VM. UEFI firmware is packaged under different paths per distribution (`ovmf` on
+//! Debian/Ubuntu, `edk2-ovmf` on Fedora and Arch, with 2 MiB and 4 MiB variants), so the
+//! candidates are searched in order and a missing firmware is reported with every path that
+//! was tried and the package that provides it.
+
+use crate::config::{MachineProfile, VmConfig};
+use crate::error::AutoInstallError;
+use crate::Result;
+use std::path::{Path, PathBuf};
+
+/// One known location of a firmware build: code image and, for pflash, its NVRAM template
+struct FirmwareCandidate {
+    code: &'static str,
+    vars: Option<&'static str>,
+}
+
+const OVMF_CANDIDATES: &[FirmwareCandidate] = &[
+    // Debian/Ubuntu (ovmf)
+    FirmwareCandidate {
+        code: "/usr/share/OVMF/OVMF_CODE_4M.fd",
+        vars: Some("/usr/share/OVMF/OVMF_VARS_4M.fd"),
+    },
+    FirmwareCandidate {
+        code: "/usr/share/OVMF/OVMF_CODE.fd",
+        vars: Some("/usr/share/OVMF/OVMF_VARS.fd"),
+    },
+    // Fedora (edk2-ovmf)
+    FirmwareCandidate {
+        code: "/usr/share/edk2/ovmf/OVMF_CODE.fd",
+        vars: Some("/usr/share/edk2/ovmf/OVMF_VARS.fd"),
+    },
+    // Arch (edk2-ovmf), current and pre-202202 layouts
+    FirmwareCandidate {
+        code: "/usr/share/edk2/x64/OVMF_CODE.4m.fd",
+        vars: Some("/usr/share/edk2/x64/OVMF_VARS.4m.fd"),
+    },
+    FirmwareCandidate {
+        code: "/usr/share/edk2-ovmf/x64/OVMF_CODE.fd",
+        vars: Some("/usr/share/edk2-ovmf/x64/OVMF_VARS.fd"),
+    },
+];
+
+const AAVMF_CANDIDATES: &[FirmwareCandidate] = &[
+    // Debian/Ubuntu (qemu-efi-aarch64)
+    FirmwareCandidate {
+        code: "/usr/share/AAVMF/AAVMF_CODE.fd",
+        vars: Some("/usr/share/AAVMF/AAVMF_VARS.fd"),
+    },
+    // Fedora (edk2-aarch64)
+    FirmwareCandidate {
+        code: "/usr/share/edk2/aarch64/QEMU_EFI-pflash.raw",
+        vars: Some("/usr/share/edk2/aarch64/vars-template-pflash.raw"),
+    },
+    // Arch (edk2-aarch64)
+    FirmwareCandidate {
+        code: "/usr/share/edk2/aarch64/QEMU_CODE.fd",
+        vars: Some("/usr/share/edk2/aarch64/QEMU_VARS.fd"),
+    },
+    // Unpadded image loaded with -bios; no persistent variables
+    FirmwareCandidate {
+        code: "/usr/share/qemu-efi-aarch64/QEMU_EFI.fd",
+        vars: None,
+    },
+];
+
+/// Firmware files found for a profile
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct FirmwarePaths {
+    pub code: PathBuf,
+    /// NVRAM template copied next to the VM disk; `None` loads `code` with `-bios`
+    pub vars_template: Option<PathBuf>,
+}
+
+/// A resolved machine: profile plus the firmware found for it
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct QemuMachine {
+    pub profile: MachineProfile,
+    pub firmware: Option<FirmwarePaths>,
+}
+
+impl QemuMachine {
+    /// Resolve the profile of `vm_config` for the host, looking firmware up on the filesystem
+    pub fn resolve(vm_config: &VmConfig, profile: MachineProfile) -> Result<Self> {
+        Self::resolve_with(vm_config, profile, |path| path.exists())
+    }
+
+    /// [`QemuMachine::resolve`] with an injectable existence check
+    pub fn resolve_with(
+        vm_config: &VmConfig,
+        profile: MachineProfile,
+        exists: impl Fn(&Path) -> bool,
+    ) -> Result<Self> {
+        let firmware = match (profile, &vm_config.firmware) {
+            (MachineProfile::LegacyBios, _) => None,
+            (_, Some(code)) => {
+                if !exists(code) {
+                    return Err(AutoInstallError::VmError(format!(
+                        "vm_config.firmware {} does not exist",
+                        code.display()
+                    )));
+                }
+                Some(FirmwarePaths {
+                    code: code.clone(),
+                    vars_template: None,
+                })
+            }
+            (_, None) => Some(discover_firmware(profile, &exists)?),
+        };
+        Ok(Self { profile, firmware })
+    }
+
+    pub fn qemu_binary(&self) -> &'static str {
+        match self.profile {
+            MachineProfile::Q35Ovmf | MachineProfile::LegacyBios => "qemu-system-x86_64",
+            MachineProfile::VirtAavmf => "qemu-system-aarch64",
+        }
+    }
+
+    /// `-machine` value including the accelerator; KVM when usable, TCG otherwise
+    pub fn machine_arg(&self) -> &'static str {
+        match self.profile {
+            MachineProfile::Q35Ovmf => "q35,accel=kvm:tcg",
+            MachineProfile::VirtAavmf => "virt,gic-version=max,accel=kvm:tcg",
+            MachineProfile::LegacyBios => "pc,accel=kvm:tcg",
+        }
+    }
+
+    pub fn cpu_arg(&self) -> &'static str {
+        match self.profile {
+            MachineProfile::Q35Ovmf | MachineProfile::LegacyBios => "host",
+            // `host` needs KVM on an arm64 host; `max` also works under TCG
+            MachineProfile::VirtAavmf => "max",
+        }
+    }
+
+    /// Machine, CPU and firmware arguments; `vars_copy` is the VM's writable NVRAM file
+    pub fn args(&self, vars_copy: Option<&Path>) -> Vec<String> {
+        let mut args = vec![
+            "-machine".to_string(),
+            self.machine_arg().to_string(),
+            "-cpu".to_string(),
+            self.cpu_arg().to_string(),
+        ];
+        if let Some(firmware) = &self.firmware {
+            match (&firmware.vars_template, vars_copy) {
+                (Some(_), Some(vars)) => args.extend([
+                    "-drive".to_string(),
+                    format!(
+                        "if=pflash,format=raw,readonly=on,file={}",
+                        firmware.code.display()
+                    ),
+                    "-drive".to_string(),
+                    format!("if=pflash,format=raw,file={}", vars.display()),
+                ]),
+                _ => args.extend(["-bios".to_string(), firmware.code.display().to_string()]),
+            }
+        }
+        args
+    }
+
+    /// virtio-rng device fed from the host's /dev/urandom, so the guest's installer and first
+    /// boot never wait for entropy
+    pub fn rng_args(&self) -> Vec<String> {
+        vec![
+            "-object".to_string(),
+            "rng-random,filename=/dev/urandom,id=rng0".to_string(),
+            "-device".to_string(),
+            "virtio-rng-pci,rng=rng0".to_string(),
+        ]
+    }
+
+    /// Copy the NVRAM template next to `disk_path`; returns the copy, if the firmware has one
+    pub async fn prepare_vars(&self, disk_path: &Path) -> Result<Option<PathBuf>> {
+        let Some(template) = self
+            .firmware
+            .as_ref()
+            .and_then(|f| f.vars_template.as_ref())
+        else {
+            return Ok(None);
+        };
+        let copy = disk_path.with_extension("vars.fd");
+        tokio::fs::copy(template, &copy).await.map_err(|e| {
+            AutoInstallError::VmError(format!(
+                "Failed to copy firmware variables {} to {}: {}",
+                template.display(),
+                copy.display(),
+                e
+            ))
+        })?;
+        Ok(Some(copy))
+    }
+}
+
+/// First installed firmware for `profile`, or an error naming every path tried
+pub fn discover_firmware(
+    profile: MachineProfile,
+    exists: impl Fn(&Path) -> bool,
+) -> Result<FirmwarePaths> {
+    let (candidates, packages) = match profile {
+        MachineProfile::Q35Ovmf => (
+            OVMF_CANDIDATES,
+            "ovmf (Debian/Ubuntu) or edk2-ovmf (Fedora, Arch)",
+        ),
+        MachineProfile::VirtAavmf => (
+            AAVMF_CANDIDATES,
+            "qemu-efi-aarch64 (Debian/Ubuntu) or edk2-aarch64 (Fedora, Arch)",
+        ),
+        MachineProfile::LegacyBios => {
+            return Err(AutoInstallError::VmError(
+                "legacy-bios uses QEMU's built-in firmware".to_string(),
+            ))
+        }
+    };
+    for candidate in candidates {
+        let code = Path::new(candidate.code);
+        if !exists(code) {
+            continue;
+        }
+        // A code image without its template is still usable through -bios
+        let vars_template = candidate
+            .vars
+            .map(Path::new)
+            .filter(|vars| exists(vars))
+            .map(Path::to_path_buf);
+        return Ok(FirmwarePaths {
+            code: code.to_path_buf(),
+            vars_template,
+        });
+    }
+    let tried: Vec<&str> = candidates.iter().map(|c| c.code).collect();
+    Err(AutoInstallError::VmError(format!(
+        "No UEFI firmware for machine profile {} found; tried {}. Install {} or set vm_config.firmware",
+        profile.as_str(),
+        tried.join(", "),
+        packages
+    )))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_discovery_and_args() {
+        let fedora = |p: &Path| p.starts_with("/usr/share/edk2/ovmf");
+        let machine =
+            QemuMachine::resolve_with(&VmConfig::default(), MachineProfile::Q35Ovmf, fedora)
+                .unwrap();
+        assert_eq!(
+            machine.firmware.as_ref().unwrap().code,
+            Path::new("/usr/share/edk2/ovmf/OVMF_CODE.fd")
+        );
+        let args = machine.args(Some(Path::new("/var/tmp/vm.vars.fd")));
+        assert_eq!(&args[..2], ["-machine", "q35,accel=kvm:tcg"]);
+        assert_eq!(
+            args.last().unwrap(),
+            "if=pflash,format=raw,file=/var/tmp/vm.vars.fd"
+        );
+
+        let ubuntu_old = |p: &Path| p == Path::new("/usr/share/qemu-efi-aarch64/QEMU_EFI.fd");
+        let arm =
+            QemuMachine::resolve_with(&VmConfig::default(), MachineProfile::VirtAavmf, ubuntu_old)
+                .unwrap();
+        assert_eq!(arm.qemu_binary(), "qemu-system-aarch64");
+        assert_eq!(arm.args(None)[4], "-bios");
+
+        let bios =
+            QemuMachine::resolve_with(&VmConfig::default(), MachineProfile::LegacyBios, |_| false)
+                .unwrap();
+        assert_eq!(bios.args(None).len(), 4);
+
+        let err =
+            QemuMachine::resolve_with(&VmConfig::default(), MachineProfile::Q35Ovmf, |_| false)
+                .unwrap_err()
+                .to_string();
+        assert!(err.contains("/usr/share/OVMF/OVMF_CODE_4M.fd"));
+        assert!(err.contains("edk2-ovmf"));
+    }
+}
@@ -0,0 +1,191 @@
+// file: src/utils/qmp.rs
+// version: 1.0.0
+// guid: 5d2a8f16-7c43-4e9b-b1d0-3a6e9c2f7b54
+
+//! QEMU Machine Protocol client for the image build VM
+//!
+//! The build VM listens for QMP on a unix socket next to its human monitor. Each request opens
+//! a fresh connection, reads the greeting, negotiates capabilities and sends one command;
+//! asynchronous events arriving in between are skipped.
+
+use crate::error::AutoInstallError;
+use crate::utils::guest_agent::build_request;
+use crate::Result;
+use serde_json::{json, Value};
+use std::path::{Path, PathBuf};
+use std::time::Duration;
+use tokio::io::{AsyncBufReadExt, AsyncWriteExt, BufReader};
+use tokio::net::UnixStream;
+
+/// Host side of the build VM's QMP socket
+pub const QMP_SOCKET: &str = "/tmp/qemu-qmp.sock";
+
+/// How long a single QMP request may take
+const REQUEST_TIMEOUT: Duration = Duration::from_secs(10);
+
+/// QEMU arguments exposing QMP on `socket`
+pub fn qemu_args(socket: &Path) -> Vec<String> {
+    vec![
+        "-qmp".to_string(),
+        format!("unix:{},server=on,wait=off", socket.display()),
+    ]
+}
+
+/// Client for the QMP socket of one VM
+#[derive(Debug, Clone)]
+pub struct QmpClient {
+    socket: PathBuf,
+}
+
+impl QmpClient {
+    pub fn new(socket: impl Into<PathBuf>) -> Self {
+        Self {
+            socket: socket.into(),
+        }
+    }
+
+    /// Save the VM's display as a PNG at `path`; QEMU older than 7.1 only writes PPM, in which
+    /// case the screen is saved next to it with a `.ppm` extension. Returns the written file.
+    pub async fn screendump(&self, path: &Path) -> Result<PathBuf> {
+        let png = self
+            .request(
+                "screendump",
+                Some(json!({"filename": path.display().to_string(), "format": "png"})),
+            )
+            .await;
+        match png {
+            Ok(_) => Ok(path.to_path_buf()),
+            Err(AutoInstallError::VmError(_)) => {
+                let ppm = path.with_extension("ppm");
+                self.request(
+                    "screendump",
+                    Some(json!({"filename": ppm.display().to_string()})),
+                )
+                .await?;
+                Ok(ppm)
+            }
+            Err(e) => Err(e),
+        }
+    }
+
+    /// Pause the VM's CPUs, leaving it inspectable over VNC and the monitor
+    pub async fn stop(&self) -> Result<()> {
+        self.request("stop", None).await.map(drop)
+    }
+
+    async fn request(&self, execute: &str, arguments: Option<Value>) -> Result<Value> {
+        let exchange = async {
+            let stream = UnixStream::connect(&self.socket).await.map_err(|e| {
+                AutoInstallError::VmError(format!(
+                    "QMP socket {} unavailable: {}",
+                    self.socket.display(),
+                    e
+                ))
+            })?;
+            let (read, mut write) = stream.into_split();
+            let mut lines = BufReader::new(read).lines();
+
+            // Greeting, then capability negotiation
+            lines.next_line().await?.ok_or_else(closed)?;
+            write
+                .write_all(build_request("qmp_capabilities", None).as_bytes())
+                .await?;
+            next_reply(&mut lines).await?;
+
+            write
+                .write_all(build_request(execute, arguments).as_bytes())
+                .await?;
+            next_reply(&mut lines).await
+        };
+        tokio::time::timeout(REQUEST_TIMEOUT, exchange)
+            .await
+            .map_err(|_| {
+                AutoInstallError::TimeoutError(format!(
+                    "QMP did not answer {} within {}s",
+                    execute,
+                    REQUEST_TIMEOUT.as_secs()
+                ))
+            })?
+    }
+}
+
+async fn next_reply(
+    lines: &mut tokio::io::Lines<BufReader<tokio::net::unix::OwnedReadHalf>>,
+) -> Result<Value> {
+    loop {
+        let line = lines.next_line().await?.ok_or_else(closed)?;
+        if let Some(reply) = parse_reply(&line)? {
+            return Ok(reply);
+        }
+    }
+}
+
+fn closed() -> AutoInstallError {
+    AutoInstallError::VmError("QMP closed the connection".to_string())
+}
+
+/// The `return` value of a reply line, `None` for an event, or QEMU's error
+pub fn parse_reply(line: &str) -> Result<Option<Value>> {
+    let mut reply: Value = serde_json::from_str(line.trim())?;
+    if reply.get("event").is_some() {
+        return Ok(None);
+    }
+    if let Some(error) = reply.get("error") {
+        return Err(AutoInstallError::VmError(format!(
+            "QMP error {}: {}",
+            error["class"].as_str().unwrap_or("unknown"),
+            error["desc"].as_str().unwrap_or("")
+        )));
+    }
+    reply
+        .get_mut("return")
+        .map(|value| Some(value.take()))
+        .ok_or_else(|| AutoInstallError::VmError(format!("Unexpected QMP reply: {}", line)))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tokio::net::UnixListener;
+
+    #[tokio::test]
+    async fn test_screendump_falls_back_to_ppm() {
+        assert_eq!(parse_reply("{\"event\": \"STOP\"}").unwrap(), None);
+        assert!(qemu_args(Path::new("/tmp/q.sock"))
+            .contains(&"unix:/tmp/q.sock,server=on,wait=off".to_string()));
+
+        let dir = tempfile::TempDir::new().unwrap();
+        let socket = dir.path().join("qmp.sock");
+        let listener = UnixListener::bind(&socket).unwrap();
+        // A QEMU without PNG support: rejects `format`, emits an event before each reply
+        tokio::spawn(async move {
+            loop {
+                let (stream, _) = listener.accept().await.unwrap();
+                let (read, mut write) = stream.into_split();
+                let mut lines = BufReader::new(read).lines();
+                write
+                    .write_all(b"{\"QMP\": {\"version\": {}, \"capabilities\": []}}\n")
+                    .await
+                    .unwrap();
+                while let Ok(Some(line)) = lines.next_line().await {
+                    let request: Value = serde_json::from_str(&line).unwrap();
+                    let reply = if request["arguments"]["format"].is_string() {
+                        json!({"error": {"class": "GenericError", "desc": "Parameter 'format' is unexpected"}})
+                    } else {
+                        json!({"return": {}})
+                    };
+                    write
+                        .write_all(format!("{{\"event\": \"RESUME\"}}\n{}\n", reply).as_bytes())
+                        .await
+                        .unwrap();
+                }
+            }
+        });
+
+        let written = QmpClient::new(&socket)
+            .screendump(Path::new("/tmp/frame.png"))
+            .await
+            .unwrap();
+        assert_eq!(written, PathBuf::from("/tmp/frame.ppm"));
+    }
+}
@@ -0,0 +1,213 @@
+// file: src/utils/screen_capture.rs
+// version: 1.0.0
+// guid: 2b7e4c91-6a35-4f08-9d1c-8e3f5a0b7d62
+
+//! Screen recording of the image build VM and the artifact bundle kept when a build fails
+//!
+//! While the installer runs, [`ScreenRecorder`] saves the VM display through QMP every
+//! `interval_secs`, keeping the newest `max_frames`. When the install fails or times out it adds
+//! a final screenshot, the serial and firmware logs and the error to the frames, renders them
+//! into an animated GIF when ImageMagick is installed, and moves everything to
+//! `<cache_dir>/failures/<timestamp>/`. A successful build discards the frames.
+
+use crate::utils::qmp::QmpClient;
+use crate::Result;
+use chrono::Utc;
+use serde::{Deserialize, Serialize};
+use std::collections::VecDeque;
+use std::path::{Path, PathBuf};
+use std::time::{Duration, Instant};
+use tokio::fs;
+use tokio::process::Command;
+use tracing::{debug, info, warn};
+
+/// Staging directory below the failures directory for the running build
+const IN_PROGRESS: &str = "in-progress";
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ScreenCaptureOptions {
+    /// Seconds between screenshots; 0 only takes the final one
+    pub interval_secs: u64,
+    /// Periodic screenshots kept; older ones are deleted
+    pub max_frames: usize,
+    /// Pause the VM on failure instead of killing it
+    pub pause_on_failure: bool,
+}
+
+impl Default for ScreenCaptureOptions {
+    fn default() -> Self {
+        Self {
+            interval_secs: 30,
+            max_frames: 60,
+            pause_on_failure: false,
+        }
+    }
+}
+
+/// Takes periodic screenshots of one build VM
+pub struct ScreenRecorder {
+    qmp: QmpClient,
+    failures_dir: PathBuf,
+    options: ScreenCaptureOptions,
+    frames: VecDeque<PathBuf>,
+    taken: u64,
+    last: Option<Instant>,
+}
+
+impl ScreenRecorder {
+    pub fn new(qmp: QmpClient, failures_dir: &Path, options: ScreenCaptureOptions) -> Self {
+        Self {
+            qmp,
+            failures_dir: failures_dir.to_path_buf(),
+            options,
+            frames: VecDeque::new(),
+            taken: 0,
+            last: None,
+        }
+    }
+
+    fn staging(&self) -> PathBuf {
+        self.failures_dir.join(IN_PROGRESS)
+    }
+
+    /// Whether a periodic screenshot is due at `now`
+    pub fn is_due(&self, now: Instant) -> bool {
+        self.options.interval_secs > 0
+            && self.last.is_none_or(|last| {
+                now.duration_since(last) >= Duration::from_secs(self.options.interval_secs)
+            })
+    }
+
+    /// Take a periodic screenshot if one is due; the display may not be up yet, so failures
+    /// are only logged
+    pub async fn tick(&mut self) {
+        let now = Instant::now();
+        if !self.is_due(now) {
+            return;
+        }
+        self.last = Some(now);
+        let frames_dir = self.staging().join("frames");
+        if let Err(e) = fs::create_dir_all(&frames_dir).await {
+            debug!("Cannot create {}: {}", frames_dir.display(), e);
+            return;
+        }
+        let path = frames_dir.join(format!("frame-{:05}.png", self.taken));
+        match self.qmp.screendump(&path).await {
+            Ok(written) => {
+                self.taken += 1;
+                for old in self.push_frame(written) {
+                    let _ = fs::remove_file(old).await;
+                }
+            }
+            Err(e) => debug!("Screenshot of the build VM failed: {}", e),
+        }
+    }
+
+    /// Record a frame; returns the frames that fell out of the window
+    fn push_frame(&mut self, frame: PathBuf) -> Vec<PathBuf> {
+        self.frames.push_back(frame);
+        let excess = self.frames.len().saturating_sub(self.options.max_frames);
+        self.frames.drain(..excess).collect()
+    }
+
+    /// Write the failure bundle for `error` with copies of `logs`; returns its directory
+    pub async fn capture_failure(&mut self, error: &str, logs: &[&Path]) -> Result<PathBuf> {
+        let staging = self.staging();
+        fs::create_dir_all(&staging).await?;
+        let mut frames: Vec<PathBuf> = self.frames.iter().cloned().collect();
+        match self.qmp.screendump(&staging.join("final.png")).await {
+            Ok(written) => frames.push(written),
+            Err(e) => warn!("Final screenshot of the build VM failed: {}", e),
+        }
+        for log in logs {
+            if let Some(name) = log.file_name() {
+                if fs::copy(log, staging.join(name)).await.is_err() {
+                    debug!("{} not available for the failure bundle", log.display());
+                }
+            }
+        }
+        fs::write(staging.join("error.txt"), format!("{}\n", error)).await?;
+
+        if frames.len() > 1 {
+            let gif = staging.join("screen.gif");
+            match Command::new("convert")
+                .args(build_gif_args(&frames, &gif))
+                .output()
+                .await
+            {
+                Ok(output) if output.status.success() => {}
+                Ok(output) => warn!(
+                    "Could not render {}: {}",
+                    gif.display(),
+                    String::from_utf8_lossy(&output.stderr).trim()
+                ),
+                Err(_) => info!("ImageMagick not installed; keeping screenshots without a GIF"),
+            }
+        }
+
+        let bundle = self
+            .failures_dir
+            .join(Utc::now().format("%Y%m%dT%H%M%SZ").to_string());
+        fs::rename(&staging, &bundle).await?;
+        self.frames.clear();
+        Ok(bundle)
+    }
+
+    /// Drop the screenshots of a build that did not fail
+    pub async fn discard(&mut self) {
+        self.frames.clear();
+        let _ = fs::remove_dir_all(self.staging()).await;
+    }
+}
+
+/// ImageMagick `convert` arguments animating `frames` at one frame per second, holding the last
+pub fn build_gif_args(frames: &[PathBuf], output: &Path) -> Vec<String> {
+    let mut args = vec!["-delay".to_string(), "100".to_string()];
+    args.extend(frames.iter().map(|f| f.display().to_string()));
+    if let Some(last) = frames.last() {
+        args.extend([
+            "-delay".to_string(),
+            "400".to_string(),
+            last.display().to_string(),
+        ]);
+    }
+    args.extend([
+        "-loop".to_string(),
+        "0".to_string(),
+        output.display().to_string(),
+    ]);
+    args
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_frame_window_and_gif_args() {
+        let mut recorder = ScreenRecorder::new(
+            QmpClient::new("/nonexistent.sock"),
+            Path::new("/tmp/failures"),
+            ScreenCaptureOptions {
+                max_frames: 2,
+                ..Default::default()
+            },
+        );
+        assert!(recorder.is_due(Instant::now()));
+        recorder.last = Some(Instant::now());
+        assert!(!recorder.is_due(Instant::now()));
+
+        assert!(recorder.push_frame(PathBuf::from("a.png")).is_empty());
+        assert!(recorder.push_frame(PathBuf::from("b.png")).is_empty());
+        assert_eq!(
+            recorder.push_frame(PathBuf::from("c.ppm")),
+            vec![PathBuf::from("a.png")]
+        );
+
+        let frames: Vec<PathBuf> = recorder.frames.iter().cloned().collect();
+        assert_eq!(
+            build_gif_args(&frames, Path::new("out.gif")).join(" "),
+            "-delay 100 b.png c.ppm -delay 400 c.ppm -loop 0 out.gif"
+        );
+    }
+}
@@ -0,0 +1,36 @@
+// file: src/utils/shell.rs
+// version: 1.0.0
+// guid: b49e1b73-76c1-4aea-8a03-3af159cb1756
+
+//! Quoting for the shell commands the agent builds
+
+/// Quote `s` for a POSIX shell
+///
+/// Words made only of characters the shell treats literally stay bare, so generated commands
+/// stay readable; everything else is single-quoted, with embedded `'` closed, escaped and
+/// reopened.
+pub fn shell_quote(s: &str) -> String {
+    if !s.is_empty()
+        && s.chars()
+            .all(|c| c.is_ascii_alphanumeric() || "-_./=:@%+,".contains(c))
+    {
+        s.to_string()
+    } else {
+        format!("'{}'", s.replace('\'', "'\\''"))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_shell_quote() {
+        assert_eq!(shell_quote("/etc/motd"), "/etc/motd");
+        assert_eq!(shell_quote("KEY=v1,v2"), "KEY=v1,v2");
+        assert_eq!(shell_quote(""), "''");
+        assert_eq!(shell_quote("a b"), "'a b'");
+        assert_eq!(shell_quote("$HOME"), "'$HOME'");
+        assert_eq!(shell_quote("it's"), "'it'\\''s'");
+    }
+}
@@ -1,5 +1,5 @@
 // file: src/utils/system.rs
-// version: 1.1.3
+// version: 1.2.0
 // guid: w3x4y5z6-a7b8-9012-3456-789012wxyzab
 
 //! System utility functions
@@ -7,7 +7,7 @@
 use crate::Result;
 use std::process::Stdio;
 use tokio::process::Command;
-use tracing::{debug, warn};
+use tracing::warn;
 
 /// System utility functions
 pub struct SystemUtils;
@@ -81,8 +81,6 @@ impl SystemUtils {
 
     /// Get available disk space in GB for a path
     pub async fn get_available_space(path: &str) -> Result<u64> {
-        // Note: This function could be enhanced to use CoreUtils::df()
-        // for more reliable cross-platform behavior
         let output = Command::new("df")
             .args(["-BG", path])
             .output()
@@ -159,66 +157,6 @@ impl SystemUtils {
 
         Ok(output.status.success())
     }
-
-    /// Create temporary directory
-    pub async fn create_temp_dir(prefix: &str) -> Result<std::path::PathBuf> {
-        let temp_dir = tempfile::Builder::new()
-            .prefix(prefix)
-            .tempdir()
-            .map_err(crate::error::AutoInstallError::IoError)?;
-
-        let path = temp_dir.path().to_owned();
-        std::mem::forget(temp_dir); // Don't delete on drop
-        debug!("Created temporary directory: {}", path.display());
-        Ok(path)
-    }
-
-    /// Execute command with timeout
-    pub async fn execute_with_timeout(
-        command: &str,
-        args: &[&str],
-        timeout_secs: u64,
-    ) -> Result<String> {
-        let child = Command::new(command)
-            .args(args)
-            .stdout(Stdio::piped())
-            .stderr(Stdio::piped())
-            .spawn()
-            .map_err(|e| {
-                crate::error::AutoInstallError::SystemError(format!(
-                    "Failed to spawn command {}: {}",
-                    command, e
-                ))
-            })?;
-
-        let timeout = tokio::time::Duration::from_secs(timeout_secs);
-        let output = tokio::time::timeout(timeout, child.wait_with_output())
-            .await
-            .map_err(|_| {
-                crate::error::AutoInstallError::SystemError(format!(
-                    "Command {} timed out after {} seconds",
-                    command, timeout_secs
-                ))
-            })?
-            .map_err(|e| {
-                crate::error::AutoInstallError::SystemError(format!(
-                    "Command {} failed: {}",
-                    command, e
-                ))
-            })?;
-
-        if !output.status.success() {
-            let stderr = String::from_utf8_lossy(&output.stderr);
-            return Err(crate::error::AutoInstallError::SystemError(format!(
-                "Command {} failed with exit code {}: {}",
-                command,
-                output.status.code().unwrap_or(-1),
-                stderr
-            )));
-        }
-
-        Ok(String::from_utf8_lossy(&output.stdout).to_string())
-    }
 }
 
 #[cfg(test)]
@@ -250,33 +188,6 @@ mod tests {
         // Should return some memory value or error
         assert!(result.is_ok() || result.is_err());
     }
-
-    #[tokio::test]
-    async fn test_create_temp_dir() {
-        let temp_dir = SystemUtils::create_temp_dir("test").await.unwrap();
-        assert!(temp_dir.exists());
-
-        // Cleanup
-        let _ = tokio::fs::remove_dir_all(temp_dir).await;
-    }
-
-    #[tokio::test]
-    async fn test_execute_with_timeout_success() {
-        // Use a simple echo command which is widely available
-        let out = SystemUtils::execute_with_timeout("/bin/echo", &["hello"], 2)
-            .await
-            .unwrap();
-        assert!(out.contains("hello"));
-    }
-
-    #[tokio::test]
-    async fn test_execute_with_timeout_timeout() {
-        // Use a sleep that exceeds timeout to trigger timeout path
-        let res = SystemUtils::execute_with_timeout("/bin/sleep", &["2"], 1).await;
-        assert!(res.is_err());
-        let msg = res.unwrap_err().to_string();
-        assert!(msg.contains("timed out"));
-    }
 }
 
 // moved: extern crate libc is unnecessary in Rust 2018+; remove to satisfy clippy items-after-test-module
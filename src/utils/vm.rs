@@ -1,29 +1,65 @@
 // file: src/utils/vm.rs
-// version: 1.1.3
+// version: 1.10.0
 // guid: y5z6a7b8-c9d0-1234-5678-901234yzabcd
 
 //! VM management utilities
 
 use crate::{
-    config::{Architecture, VmConfig},
+    config::{Architecture, HostResources, VmConfig},
+    network::events::{EventBus, InstallEvent},
+    utils::guest_agent::{
+        self, GuestAgent, InstallStatus, GUEST_AGENT_SOCKET, INSTALL_PROGRESS_LOG,
+    },
+    utils::qmp::{self, QmpClient, QMP_SOCKET},
+    utils::screen_capture::{ScreenCaptureOptions, ScreenRecorder},
+    utils::{CancellationToken, QemuMachine},
     Result,
 };
-use std::path::Path;
+use std::path::{Path, PathBuf};
 use tokio::process::Command;
 use tracing::{debug, info, warn};
 
 /// VM manager for creating and running virtual machines
 pub struct VmManager {
-    // Using direct kernel boot approach, no UEFI required
-    pub qemu_binary: &'static str,
+    cancel: CancellationToken,
+    events: Option<EventBus>,
+    screen: ScreenCaptureOptions,
+    failures_dir: Option<PathBuf>,
 }
 
 impl VmManager {
     /// Create a new VM manager
     pub fn new() -> Self {
         Self {
-            // Default to AMD64 QEMU binary; methods may choose different binaries per architecture
-            qemu_binary: "qemu-system-x86_64",
+            cancel: CancellationToken::new(),
+            events: None,
+            screen: ScreenCaptureOptions::default(),
+            failures_dir: None,
+        }
+    }
+
+    /// Stop monitoring and kill QEMU once `token` is cancelled
+    pub fn set_cancellation_token(&mut self, token: CancellationToken) {
+        self.cancel = token;
+    }
+
+    /// Publish the installer's status inside the VM to `bus`
+    pub fn set_event_bus(&mut self, bus: EventBus) {
+        self.events = Some(bus);
+    }
+
+    /// Record the VM's screen during installs and keep failure artifacts below `dir`
+    pub fn set_failures_dir(&mut self, dir: PathBuf) {
+        self.failures_dir = Some(dir);
+    }
+
+    pub fn set_screen_capture(&mut self, options: ScreenCaptureOptions) {
+        self.screen = options;
+    }
+
+    fn publish(&self, message: String) {
+        if let Some(bus) = &self.events {
+            bus.publish(InstallEvent::Vm { message });
         }
     }
 
@@ -33,18 +69,22 @@ impl VmManager {
         disk_path: &Path,
         netboot_dir: &Path, // Now contains extracted Ubuntu Server ISO files
         cloud_init_path: &Path,
-        vm_memory_mb: u32,
+        vm_config: &VmConfig,
     ) -> Result<()> {
         info!("Starting Ubuntu installation in VM using Ubuntu Server ISO files");
 
-        // Default to AMD64 architecture (most common)
-        let architecture = Architecture::Amd64;
-
-        // Select appropriate QEMU binary for architecture
-        let qemu_cmd = match architecture {
-            Architecture::Amd64 => "qemu-system-x86_64",
-            Architecture::Arm64 => "qemu-system-aarch64",
-        };
+        // Default to AMD64 architecture (most common); vm_config.machine can pick another
+        let profile = vm_config.machine_profile(Architecture::Amd64);
+        let machine = QemuMachine::resolve(vm_config, profile)?;
+        info!(
+            "Machine profile {} ({})",
+            profile.as_str(),
+            machine
+                .firmware
+                .as_ref()
+                .map(|f| f.code.display().to_string())
+                .unwrap_or_else(|| "built-in firmware".to_string())
+        );
 
         // Create cloud-init ISO
         let cloud_init_iso = self.create_cloud_init_iso(cloud_init_path).await?;
@@ -153,17 +193,18 @@ impl VmManager {
         info!("Using kernel: {}", kernel_file.display());
         info!("Using initrd: {}", initrd_file.display());
 
-        // Build QEMU command with direct kernel boot (no UEFI needed for netboot)
-        let mut cmd = Command::new(qemu_cmd);
+        let vars_copy = machine.prepare_vars(disk_path).await?;
+
+        // Build QEMU command with direct kernel boot; the firmware is what the installed
+        // system boots with afterwards
+        let mut cmd = Command::new(machine.qemu_binary());
+        cmd.args(machine.args(vars_copy.as_deref()));
+        cmd.args(machine.rng_args());
         cmd.args([
-            "-machine",
-            "accel=kvm:tcg", // Use KVM if available, fallback to TCG
-            "-cpu",
-            "host",
             "-m",
-            &format!("{}M", vm_memory_mb),
+            &format!("{}M", vm_config.memory_mb),
             "-smp",
-            "2", // Default to 2 CPU cores
+            &vm_config.cpu_cores.to_string(),
             "-drive",
             &format!("file={},format=qcow2,if=virtio", disk_path.display()),
             "-drive",
@@ -186,21 +227,10 @@ impl VmManager {
             "unix:/tmp/qemu-monitor.sock,server,nowait", // Monitor socket
             "-daemonize",                                // Run as daemon
         ]);
-
-        // Add architecture-specific arguments
-        match architecture {
-            Architecture::Amd64 => {
-                cmd.args(["-machine", "q35"]);
-            }
-            Architecture::Arm64 => {
-                cmd.args([
-                    "-machine",
-                    "virt",
-                    "-bios",
-                    "/usr/share/qemu-efi-aarch64/QEMU_EFI.fd",
-                ]);
-            }
-        }
+        // Guest agent channel used to follow the install from inside the VM
+        cmd.args(guest_agent::qemu_args(Path::new(GUEST_AGENT_SOCKET)));
+        // QMP for screenshots and pausing a failed install
+        cmd.args(qmp::qemu_args(Path::new(QMP_SOCKET)));
 
         debug!("Starting QEMU installation with command: {:?}", cmd);
 
@@ -217,8 +247,9 @@ impl VmManager {
         }
 
         info!("QEMU started in daemon mode");
+        self.publish("QEMU started".to_string());
 
-        // Monitor installation progress via serial log and QEMU monitor
+        // Monitor installation progress via the guest agent, falling back to the serial log
         self.monitor_installation().await?;
 
         // Cleanup cloud-init ISO
@@ -227,6 +258,82 @@ impl VmManager {
         Ok(())
     }
 
+    /// Boot the installed `disk_path` with the guest's SSH port forwarded to `ssh_port` on the
+    /// host's loopback address, for provisioner hooks; stop it with [`Self::power_off`]
+    pub async fn boot_for_provisioning(
+        &self,
+        disk_path: &Path,
+        vm_config: &VmConfig,
+        ssh_port: u16,
+    ) -> Result<()> {
+        let profile = vm_config.machine_profile(Architecture::Amd64);
+        let machine = QemuMachine::resolve(vm_config, profile)?;
+        // Keep the variables the install wrote its boot entry to
+        let vars = disk_path.with_extension("vars.fd");
+        let vars = if vars.exists() {
+            Some(vars)
+        } else {
+            machine.prepare_vars(disk_path).await?
+        };
+
+        let mut cmd = Command::new(machine.qemu_binary());
+        cmd.args(machine.args(vars.as_deref()));
+        cmd.args(machine.rng_args());
+        cmd.args([
+            "-m",
+            &format!("{}M", vm_config.memory_mb),
+            "-smp",
+            &vm_config.cpu_cores.to_string(),
+            "-drive",
+            &format!("file={},format=qcow2,if=virtio", disk_path.display()),
+            "-netdev",
+            &format!("user,id=net0,hostfwd=tcp:127.0.0.1:{}-:22", ssh_port),
+            "-device",
+            "virtio-net,netdev=net0",
+            "-vnc",
+            ":1",
+            "-serial",
+            "file:/tmp/qemu-serial.log",
+            "-monitor",
+            "unix:/tmp/qemu-monitor.sock,server,nowait",
+            "-daemonize",
+        ]);
+        debug!("Booting build VM for provisioning: {:?}", cmd);
+
+        let output = cmd.output().await.map_err(|e| {
+            crate::error::AutoInstallError::VmError(format!("Failed to start QEMU: {}", e))
+        })?;
+        if !output.status.success() {
+            return Err(crate::error::AutoInstallError::VmError(format!(
+                "QEMU failed to start: {}",
+                String::from_utf8_lossy(&output.stderr)
+            )));
+        }
+        info!("Build VM booted; SSH forwarded to 127.0.0.1:{}", ssh_port);
+        self.publish("Build VM booted for provisioning".to_string());
+        Ok(())
+    }
+
+    /// Ask the guest to power off, quitting QEMU if it has not within two minutes
+    pub async fn power_off(&self) -> Result<()> {
+        self.send_monitor_command("system_powerdown").await?;
+        let deadline = std::time::Instant::now() + std::time::Duration::from_secs(120);
+        while std::time::Instant::now() < deadline {
+            tokio::time::sleep(tokio::time::Duration::from_secs(2)).await;
+            #[cfg(unix)]
+            if tokio::net::UnixStream::connect("/tmp/qemu-monitor.sock")
+                .await
+                .is_err()
+            {
+                let _ = tokio::fs::remove_file("/tmp/qemu-monitor.sock").await;
+                info!("Build VM powered off");
+                return Ok(());
+            }
+        }
+        warn!("Build VM did not power off; stopping QEMU");
+        self.shutdown_qemu().await
+    }
+
     /// Monitor QEMU installation progress and handle automation
     async fn monitor_installation(&self) -> Result<()> {
         info!("Ubuntu installation started - this may take 30-60 minutes");
@@ -240,90 +347,170 @@ impl VmManager {
         // Monitor serial output for installation progress
         let mut installation_started = false;
         let mut cloud_init_started = false;
+        let agent = GuestAgent::new(GUEST_AGENT_SOCKET);
+        let mut agent_connected = false;
+        let mut last_progress: Option<String> = None;
+        let mut recorder = self
+            .failures_dir
+            .as_ref()
+            .map(|dir| ScreenRecorder::new(QmpClient::new(QMP_SOCKET), dir, self.screen.clone()));
 
         loop {
             if start_time.elapsed() > timeout {
-                self.kill_qemu().await?;
-                return Err(crate::error::AutoInstallError::VmError(
-                    "VM installation timed out after 1 hour".to_string(),
-                ));
+                self.publish("Installation timed out after 1 hour".to_string());
+                return self
+                    .fail_installation(
+                        recorder.as_mut(),
+                        "VM installation timed out after 1 hour".to_string(),
+                    )
+                    .await;
             }
-
-            // Check both serial and UEFI logs for progress indicators
-            let mut combined_log = String::new();
-
-            if let Ok(serial_content) = tokio::fs::read_to_string("/tmp/qemu-serial.log").await {
-                combined_log.push_str(&serial_content);
+            if let Some(recorder) = recorder.as_mut() {
+                recorder.tick().await;
             }
 
-            if let Ok(uefi_content) = tokio::fs::read_to_string("/tmp/qemu-uefi.log").await {
-                combined_log.push_str(&uefi_content);
+            // The guest agent reports the install state directly once it is running
+            match agent.install_status().await {
+                Ok(status) => {
+                    if !agent_connected {
+                        info!(
+                            "Guest agent connected after {:?}; tracking installation through it",
+                            start_time.elapsed()
+                        );
+                        agent_connected = true;
+                    }
+                    match status {
+                        InstallStatus::Done => {
+                            // Flush the target disk before QEMU is told to quit
+                            let _ = agent.exec("sync").await;
+                            info!(
+                                "Installation completed successfully in {:?}",
+                                start_time.elapsed()
+                            );
+                            self.publish(format!(
+                                "Installation completed in {:?}",
+                                start_time.elapsed()
+                            ));
+                            if let Some(recorder) = recorder.as_mut() {
+                                recorder.discard().await;
+                            }
+                            self.shutdown_qemu().await?;
+                            return Ok(());
+                        }
+                        InstallStatus::Failed => {
+                            let log_tail = agent
+                                .exec(&format!("tail -n 20 {}", INSTALL_PROGRESS_LOG))
+                                .await
+                                .map(|output| output.stdout)
+                                .unwrap_or_default();
+                            self.publish("Autoinstall failed inside the VM".to_string());
+                            return self
+                                .fail_installation(
+                                    recorder.as_mut(),
+                                    format!(
+                                        "Autoinstall failed inside the VM after {:?}:\n{}",
+                                        start_time.elapsed(),
+                                        log_tail.trim_end()
+                                    ),
+                                )
+                                .await;
+                        }
+                        InstallStatus::Running(progress) => {
+                            if progress.is_some() && progress != last_progress {
+                                let line = progress.as_deref().unwrap_or_default();
+                                info!("Installer: {}", line);
+                                self.publish(format!("Installer: {}", line));
+                                last_progress = progress;
+                            }
+                        }
+                    }
+                }
+                Err(e) => debug!("Guest agent not answering yet: {}", e),
             }
 
-            if !combined_log.is_empty() {
-                // Check for cloud-init startup
-                if !cloud_init_started
-                    && (combined_log.contains("cloud-init")
-                        || combined_log.contains("Cloud-init")
-                        || combined_log.contains("Starting initial cloud-init")
-                        || combined_log.contains("cloud init"))
-                {
-                    info!("Cloud-init detected, looking for autoinstall...");
-                    cloud_init_started = true;
-                }
+            // Fall back to scraping the serial log until the agent answers
+            if !agent_connected {
+                // Check both serial and UEFI logs for progress indicators
+                let mut combined_log = String::new();
 
-                // Check for installer activity
-                if !installation_started
-                    && (combined_log.contains("autoinstall") ||
-                                           combined_log.contains("subiquity") ||  // Ubuntu Server installer
-                                           combined_log.contains("installer") ||
-                                           combined_log.contains("d-i") ||  // debian-installer
-                                           combined_log.contains("ubuntu-installer"))
+                if let Ok(serial_content) = tokio::fs::read_to_string("/tmp/qemu-serial.log").await
                 {
-                    info!("Ubuntu installer process started");
-                    installation_started = true;
+                    combined_log.push_str(&serial_content);
                 }
 
-                // Check for installation completion
-                if combined_log.contains("Installation finished")
-                    || combined_log.contains("reboot")
-                    || combined_log.contains("Installation complete")
-                    || combined_log.contains("install successful")
-                    || combined_log.contains("subiquity/Late")
-                    || combined_log.contains("The system will reboot")
-                {
-                    info!(
-                        "Installation completed successfully in {:?}",
-                        start_time.elapsed()
-                    );
-                    self.shutdown_qemu().await?;
-                    return Ok(());
+                if let Ok(uefi_content) = tokio::fs::read_to_string("/tmp/qemu-uefi.log").await {
+                    combined_log.push_str(&uefi_content);
                 }
 
-                // Check for actual installation errors (not normal kernel messages)
-                if combined_log.contains("Installation failed")
-                    || combined_log.contains("autoinstall failed")
-                    || combined_log.contains("FATAL ERROR")
-                    || combined_log.contains("cloud-init failed")
-                    || combined_log.contains("Install failed")
-                {
-                    warn!("Installation error detected in logs");
-                }
+                if !combined_log.is_empty() {
+                    // Check for cloud-init startup
+                    if !cloud_init_started
+                        && (combined_log.contains("cloud-init")
+                            || combined_log.contains("Cloud-init")
+                            || combined_log.contains("Starting initial cloud-init")
+                            || combined_log.contains("cloud init"))
+                    {
+                        info!("Cloud-init detected, looking for autoinstall...");
+                        cloud_init_started = true;
+                    }
 
-                // Log any new content for debugging (more detailed)
-                if combined_log.len() > 100 {
-                    let recent_lines: Vec<&str> = combined_log.lines().rev().take(10).collect();
-                    debug!("Recent boot activity: {:?}", recent_lines);
+                    // Check for installer activity
+                    if !installation_started
+                        && (combined_log.contains("autoinstall") ||
+                                               combined_log.contains("subiquity") ||  // Ubuntu Server installer
+                                               combined_log.contains("installer") ||
+                                               combined_log.contains("d-i") ||  // debian-installer
+                                               combined_log.contains("ubuntu-installer"))
+                    {
+                        info!("Ubuntu installer process started");
+                        installation_started = true;
+                    }
 
-                    // Look for specific userspace indicators
-                    if combined_log.contains("systemd") && !cloud_init_started {
-                        info!("Systemd started, waiting for cloud-init...");
+                    // Check for installation completion
+                    if combined_log.contains("Installation finished")
+                        || combined_log.contains("reboot")
+                        || combined_log.contains("Installation complete")
+                        || combined_log.contains("install successful")
+                        || combined_log.contains("subiquity/Late")
+                        || combined_log.contains("The system will reboot")
+                    {
+                        info!(
+                            "Installation completed successfully in {:?}",
+                            start_time.elapsed()
+                        );
+                        if let Some(recorder) = recorder.as_mut() {
+                            recorder.discard().await;
+                        }
+                        self.shutdown_qemu().await?;
+                        return Ok(());
                     }
-                    if combined_log.contains("/init as init process") {
-                        info!("Init process started, system transitioning to userspace");
+
+                    // Check for actual installation errors (not normal kernel messages)
+                    if combined_log.contains("Installation failed")
+                        || combined_log.contains("autoinstall failed")
+                        || combined_log.contains("FATAL ERROR")
+                        || combined_log.contains("cloud-init failed")
+                        || combined_log.contains("Install failed")
+                    {
+                        warn!("Installation error detected in logs");
                     }
-                    if combined_log.contains("login:") || combined_log.contains("ubuntu login:") {
-                        warn!("System reached login prompt - autoinstall may not have started");
+
+                    // Log any new content for debugging (more detailed)
+                    if combined_log.len() > 100 {
+                        let recent_lines: Vec<&str> = combined_log.lines().rev().take(10).collect();
+                        debug!("Recent boot activity: {:?}", recent_lines);
+
+                        // Look for specific userspace indicators
+                        if combined_log.contains("systemd") && !cloud_init_started {
+                            info!("Systemd started, waiting for cloud-init...");
+                        }
+                        if combined_log.contains("/init as init process") {
+                            info!("Init process started, system transitioning to userspace");
+                        }
+                        if combined_log.contains("login:") || combined_log.contains("ubuntu login:")
+                        {
+                            warn!("System reached login prompt - autoinstall may not have started");
+                        }
                     }
                 }
             }
@@ -337,8 +524,64 @@ impl VmManager {
                 );
             }
 
-            tokio::time::sleep(tokio::time::Duration::from_secs(30)).await;
+            tokio::select! {
+                _ = tokio::time::sleep(poll_interval(agent_connected)) => {}
+                _ = self.cancel.cancelled() => {
+                    warn!(
+                        "Shutdown requested after {:?}; stopping VM installation",
+                        start_time.elapsed()
+                    );
+                    if let Some(recorder) = recorder.as_mut() {
+                        recorder.discard().await;
+                    }
+                    self.kill_qemu().await?;
+                    return Err(crate::error::AutoInstallError::CancelledError(format!(
+                        "VM installation stopped after {:?} (installer_started={}, cloud_init_started={}, guest_agent={})",
+                        start_time.elapsed(),
+                        installation_started,
+                        cloud_init_started,
+                        agent_connected
+                    )));
+                }
+            }
+        }
+    }
+
+    /// Keep the failure artifacts, then pause the VM for inspection or kill it; returns the
+    /// install error, pointing at the artifacts
+    async fn fail_installation(
+        &self,
+        recorder: Option<&mut ScreenRecorder>,
+        mut message: String,
+    ) -> Result<()> {
+        if let Some(recorder) = recorder {
+            let logs = [
+                Path::new("/tmp/qemu-serial.log"),
+                Path::new("/tmp/qemu-uefi.log"),
+            ];
+            match recorder.capture_failure(&message, &logs).await {
+                Ok(dir) => {
+                    info!("Build failure artifacts written to {}", dir.display());
+                    self.publish(format!("Failure artifacts in {}", dir.display()));
+                    message.push_str(&format!("\nFailure artifacts: {}", dir.display()));
+                }
+                Err(e) => warn!("Could not write the build failure artifacts: {}", e),
+            }
+        }
+        if self.screen.pause_on_failure {
+            match QmpClient::new(QMP_SOCKET).stop().await {
+                Ok(()) => {
+                    warn!(
+                        "Build VM left paused for inspection: VNC display :1 (port 5901), monitor /tmp/qemu-monitor.sock; stop it with `pkill -f qemu-system`"
+                    );
+                    message.push_str("\nThe build VM is paused for inspection on VNC display :1");
+                    return Err(crate::error::AutoInstallError::VmError(message));
+                }
+                Err(e) => warn!("Could not pause the build VM, stopping it: {}", e),
+            }
         }
+        self.kill_qemu().await?;
+        Err(crate::error::AutoInstallError::VmError(message))
     }
 
     /// Send command to QEMU monitor
@@ -391,6 +634,8 @@ impl VmManager {
         // Cleanup files
         let _ = tokio::fs::remove_file("/tmp/qemu-serial.log").await;
         let _ = tokio::fs::remove_file("/tmp/qemu-monitor.sock").await;
+        let _ = tokio::fs::remove_file(GUEST_AGENT_SOCKET).await;
+        let _ = tokio::fs::remove_file(QMP_SOCKET).await;
 
         Ok(())
     }
@@ -412,6 +657,8 @@ impl VmManager {
         // Cleanup files
         let _ = tokio::fs::remove_file("/tmp/qemu-serial.log").await;
         let _ = tokio::fs::remove_file("/tmp/qemu-monitor.sock").await;
+        let _ = tokio::fs::remove_file(GUEST_AGENT_SOCKET).await;
+        let _ = tokio::fs::remove_file(QMP_SOCKET).await;
 
         Ok(())
     }
@@ -453,83 +700,6 @@ impl VmManager {
         Ok(iso_path)
     }
 
-    /// Test VM functionality by starting a test VM
-    pub async fn test_vm_functionality(&self, architecture: Architecture) -> Result<()> {
-        info!(
-            "Testing VM functionality for {} architecture",
-            architecture.as_str()
-        );
-
-        let qemu_cmd = match architecture {
-            Architecture::Amd64 => "qemu-system-x86_64",
-            Architecture::Arm64 => "qemu-system-aarch64",
-        };
-
-        // Create a temporary test disk
-        let temp_dir = tempfile::tempdir().map_err(crate::error::AutoInstallError::IoError)?;
-        let test_disk = temp_dir.path().join("test.qcow2");
-
-        // Create test disk
-        let output = Command::new("qemu-img")
-            .args(["create", "-f", "qcow2", test_disk.to_str().unwrap(), "1G"])
-            .output()
-            .await
-            .map_err(|e| {
-                crate::error::AutoInstallError::VmError(format!(
-                    "Failed to create test disk: {}",
-                    e
-                ))
-            })?;
-
-        if !output.status.success() {
-            return Err(crate::error::AutoInstallError::VmError(
-                "Failed to create test disk image".to_string(),
-            ));
-        }
-
-        // Test QEMU startup (without actually booting)
-        let mut cmd = Command::new(qemu_cmd);
-        cmd.args([
-            "-machine",
-            "accel=kvm:tcg",
-            "-m",
-            "512M",
-            "-drive",
-            &format!("file={},format=qcow2,if=virtio", test_disk.display()),
-            "-nographic",
-            "-serial",
-            "none",
-            "-monitor",
-            "none",
-            "-S", // Start in stopped state
-        ]);
-
-        // Add architecture-specific arguments
-        match architecture {
-            Architecture::Amd64 => {
-                cmd.args(["-machine", "q35"]);
-            }
-            Architecture::Arm64 => {
-                cmd.args(["-machine", "virt"]);
-            }
-        }
-
-        let mut child = cmd.spawn().map_err(|e| {
-            crate::error::AutoInstallError::VmError(format!("Failed to start test VM: {}", e))
-        })?;
-
-        // Give it a moment to start, then kill it
-        tokio::time::sleep(tokio::time::Duration::from_secs(2)).await;
-        child.kill().await.map_err(|e| {
-            crate::error::AutoInstallError::VmError(format!("Failed to kill test VM: {}", e))
-        })?;
-
-        let _ = child.wait().await;
-
-        info!("VM functionality test completed successfully");
-        Ok(())
-    }
-
     /// Check if KVM acceleration is available
     pub async fn check_kvm_support(&self) -> bool {
         #[cfg(unix)]
@@ -553,27 +723,45 @@ impl VmManager {
         }
     }
 
-    /// Get recommended VM configuration based on system resources
-    pub async fn get_recommended_vm_config(&self) -> Result<VmConfig> {
-        let available_memory = crate::utils::system::SystemUtils::get_available_memory().await?;
-        let available_space =
-            crate::utils::system::SystemUtils::get_available_space("/tmp").await?;
-
-        // Use 50% of available memory, but at least 1GB and at most 8GB
-        let memory_mb = (available_memory as u32 / 2).clamp(1024, 8192);
-
-        // Use 20GB disk space or 50% of available space, whichever is smaller
-        let disk_size_gb = std::cmp::min(20, available_space as u32 / 2);
-
-        // Use 2 CPU cores by default
-        let cpu_cores = 2;
-
-        Ok(VmConfig {
+    /// Detect CPUs, available memory and KVM support of this machine
+    pub async fn detect_host_resources(&self) -> Result<HostResources> {
+        let cpus = std::thread::available_parallelism()
+            .map(|n| n.get() as u32)
+            .unwrap_or(1);
+        let memory_mb = crate::utils::system::SystemUtils::get_available_memory().await? as u32;
+        Ok(HostResources {
+            cpus,
             memory_mb,
-            disk_size_gb,
-            cpu_cores,
+            kvm: self.check_kvm_support().await,
         })
     }
+
+    /// Fail with setup guidance when KVM acceleration is unavailable
+    pub fn require_kvm(host: &HostResources) -> Result<()> {
+        if host.kvm {
+            return Ok(());
+        }
+        Err(crate::error::AutoInstallError::VmError(
+            "KVM acceleration is not available (/dev/kvm missing or not a device); an install \
+             under software emulation takes hours. Enable virtualization (VT-x/AMD-V) in the \
+             firmware, load the module (`modprobe kvm_intel` or `modprobe kvm_amd`), add your \
+             user to the `kvm` group, or enable nested virtualization when building inside a VM. \
+             Pass --allow-tcg to build without KVM anyway."
+                .to_string(),
+        ))
+    }
+}
+
+/// Delay between install progress checks
+///
+/// Asking the guest agent is cheap, so completion is noticed within seconds; re-reading the
+/// whole serial log is not, so the fallback polls less often.
+fn poll_interval(agent_connected: bool) -> tokio::time::Duration {
+    if agent_connected {
+        tokio::time::Duration::from_secs(5)
+    } else {
+        tokio::time::Duration::from_secs(15)
+    }
 }
 
 impl Default for VmManager {
@@ -591,7 +779,8 @@ mod tests {
     #[tokio::test]
     async fn test_vm_manager_creation() {
         let vm_manager = VmManager::new();
-        assert_eq!(vm_manager.qemu_binary, "qemu-system-x86_64");
+        assert!(!vm_manager.cancel.is_cancelled());
+        assert!(vm_manager.events.is_none());
     }
 
     #[tokio::test]
@@ -602,17 +791,18 @@ mod tests {
         assert!(matches!(kvm_support, true | false));
     }
 
-    #[tokio::test]
-    async fn test_get_recommended_vm_config() {
-        let vm_manager = VmManager::new();
-        let result = vm_manager.get_recommended_vm_config().await;
-
-        // Should return a config or an error
-        if let Ok(config) = result {
-            assert!(config.memory_mb >= 1024);
-            assert!(config.disk_size_gb >= 1);
-            assert!(config.cpu_cores >= 1);
-        }
+    #[test]
+    fn test_require_kvm() {
+        let mut host = HostResources {
+            cpus: 4,
+            memory_mb: 8192,
+            kvm: true,
+        };
+        assert!(VmManager::require_kvm(&host).is_ok());
+        host.kvm = false;
+        let err = VmManager::require_kvm(&host).unwrap_err().to_string();
+        assert!(err.contains("modprobe kvm_intel"));
+        assert!(err.contains("--allow-tcg"));
     }
 
     #[tokio::test]
@@ -657,7 +847,12 @@ mod tests {
 
         // Act
         let result = vm_manager
-            .install_ubuntu_in_vm(&disk_path, &netboot_dir, &cloud_init_path, 2048)
+            .install_ubuntu_in_vm(
+                &disk_path,
+                &netboot_dir,
+                &cloud_init_path,
+                &VmConfig::default(),
+            )
             .await;
 
         // Assert
@@ -699,7 +894,12 @@ mod tests {
 
         // Act
         let result = vm_manager
-            .install_ubuntu_in_vm(&disk_path, &netboot_dir, &cloud_init_path, 2048)
+            .install_ubuntu_in_vm(
+                &disk_path,
+                &netboot_dir,
+                &cloud_init_path,
+                &VmConfig::default(),
+            )
             .await;
 
         // Assert
@@ -739,48 +939,6 @@ mod tests {
         }
     }
 
-    #[tokio::test]
-    async fn test_test_vm_functionality_amd64() {
-        // Arrange
-        let vm_manager = VmManager::new();
-
-        // Act
-        let result = vm_manager.test_vm_functionality(Architecture::Amd64).await;
-
-        // Assert
-        // Should handle missing qemu gracefully
-        match result {
-            Ok(_) => {
-                // QEMU is available and functional
-            }
-            Err(crate::error::AutoInstallError::VmError(_)) => {
-                // Expected when qemu-system-x86_64 is not available
-            }
-            Err(other) => panic!("Unexpected error type: {:?}", other),
-        }
-    }
-
-    #[tokio::test]
-    async fn test_test_vm_functionality_arm64() {
-        // Arrange
-        let vm_manager = VmManager::new();
-
-        // Act
-        let result = vm_manager.test_vm_functionality(Architecture::Arm64).await;
-
-        // Assert
-        // Should handle missing qemu gracefully
-        match result {
-            Ok(_) => {
-                // QEMU ARM64 is available and functional
-            }
-            Err(crate::error::AutoInstallError::VmError(_)) => {
-                // Expected when qemu-system-aarch64 is not available
-            }
-            Err(other) => panic!("Unexpected error type: {:?}", other),
-        }
-    }
-
     #[tokio::test]
     async fn test_create_cloud_init_iso_missing_files() {
         // Arrange
@@ -812,20 +970,6 @@ mod tests {
         assert!(result.is_err());
     }
 
-    #[test]
-    fn test_vm_manager_qemu_binary_field() {
-        // Arrange & Act
-        let vm_manager = VmManager::new();
-
-        // Assert
-        assert_eq!(vm_manager.qemu_binary, "qemu-system-x86_64");
-
-        // Test that the field is accessible
-        let binary = vm_manager.qemu_binary;
-        assert!(binary.contains("qemu"));
-        assert!(binary.contains("x86_64"));
-    }
-
     #[tokio::test]
     async fn test_kernel_initrd_path_discovery() {
         // This tests the kernel/initrd discovery logic in install_ubuntu_in_vm
@@ -923,7 +1067,15 @@ mod tests {
 
         for memory_mb in memory_values {
             let result = vm_manager
-                .install_ubuntu_in_vm(&disk_path, &netboot_dir, &cloud_init_path, memory_mb)
+                .install_ubuntu_in_vm(
+                    &disk_path,
+                    &netboot_dir,
+                    &cloud_init_path,
+                    &VmConfig {
+                        memory_mb,
+                        ..VmConfig::default()
+                    },
+                )
                 .await;
 
             // All should fail in test environment (no qemu), but should accept the memory parameter
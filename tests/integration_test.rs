@@ -1,14 +1,12 @@
 // file: tests/integration_test.rs
-// version: 1.0.1
+// version: 1.37.0
 // guid: z6a7b8c9-d0e1-2345-6789-012345zabcde
 
 //! Integration tests for Ubuntu AutoInstall Agent
 
-use std::path::PathBuf;
 use tempfile::TempDir;
 use ubuntu_autoinstall_agent::{
     config::{loader::ConfigLoader, Architecture, TargetConfig},
-    image::manager::ImageManager,
     Result,
 };
 
@@ -90,46 +88,6 @@ custom_scripts: []
     Ok(())
 }
 
-#[tokio::test]
-async fn test_image_manager_workflow() -> Result<()> {
-    let temp_dir = TempDir::new().unwrap();
-    let manager = ImageManager::with_images_dir(temp_dir.path());
-
-    // Test that we start with no images
-    let images = manager.list_images(None).await?;
-    assert_eq!(images.len(), 0);
-
-    // Create a mock image info
-    let image_info = ubuntu_autoinstall_agent::config::ImageInfo::new(
-        "24.04".to_string(),
-        Architecture::Amd64,
-        1024 * 1024 * 1024, // 1GB
-        "abcdef123456".to_string(),
-        PathBuf::from("/tmp/test-image.qcow2"),
-    );
-
-    // Register the image
-    manager.register_image(image_info.clone()).await?;
-
-    // Verify it was registered
-    let images = manager.list_images(None).await?;
-    assert_eq!(images.len(), 1);
-    assert_eq!(images[0].ubuntu_version, "24.04");
-
-    // Test filtering by architecture
-    let amd64_images = manager.list_images(Some(Architecture::Amd64)).await?;
-    assert_eq!(amd64_images.len(), 1);
-
-    let arm64_images = manager.list_images(Some(Architecture::Arm64)).await?;
-    assert_eq!(arm64_images.len(), 0);
-
-    // Test cleanup (this image is "new" so shouldn't be cleaned up)
-    let old_images = manager.find_old_images(1).await?;
-    assert_eq!(old_images.len(), 0);
-
-    Ok(())
-}
-
 #[tokio::test]
 async fn test_environment_variable_substitution() -> Result<()> {
     let temp_dir = TempDir::new().unwrap();
@@ -222,7 +180,16 @@ packages:
 
 #[tokio::test]
 async fn test_validation_integration() -> Result<()> {
-    use ubuntu_autoinstall_agent::config::{LuksConfig, NetworkConfig, UserConfig};
+    use ubuntu_autoinstall_agent::config::{
+        AptLockConfig, AptMirrorsConfig, AptReposConfig, BootloaderConfig, BudgetConfig,
+        ConfirmationConfig, DiskHealthConfig, EntropyConfig, FirewallConfig, HardeningConfig,
+        HeadlessConfig, HealthGateConfig, HostVarsConfig, IssueConfig, KernelConfig,
+        LateCommandsConfig, LowMemoryConfig, LuksConfig, NbdeConfig, NetworkConfig,
+        NetworkRecoveryConfig, PartitioningConfig, PerformanceConfig, PrivilegeConfig,
+        ProgressConfig, ReinstallConfig, SshCaConfig, StorageConfig, TelemetryConfig,
+        ThrottleConfig, UbuntuProConfig, UpdatesConfig, UserConfig, UserDataConfig,
+        VerificationConfig, ZfsPoolsConfig, ZfsTuningConfig,
+    };
 
     // Test valid target config validation
     let valid_config = TargetConfig {
@@ -250,6 +217,44 @@ async fn test_validation_integration() -> Result<()> {
             hash: "sha256".to_string(),
         },
         packages: vec!["openssh-server".to_string()],
+        kernel: KernelConfig::default(),
+        throttle: ThrottleConfig::default(),
+        apt_snapshot: None,
+        hardening: HardeningConfig::default(),
+        zfs_tuning: ZfsTuningConfig::default(),
+        storage: StorageConfig::default(),
+        bmc: None,
+        vsphere: None,
+        mirror_selection: None,
+        firewall: FirewallConfig::default(),
+        headless: HeadlessConfig::default(),
+        progress: ProgressConfig::default(),
+        ssh_ca: SshCaConfig::default(),
+        reinstall: ReinstallConfig::default(),
+        user_data: UserDataConfig::default(),
+        apt_repos: AptReposConfig::default(),
+        issues: IssueConfig::default(),
+        performance: PerformanceConfig::default(),
+        verification: VerificationConfig::default(),
+        telemetry: TelemetryConfig::default(),
+        zfs_pools: ZfsPoolsConfig::default(),
+        ubuntu_pro: UbuntuProConfig::default(),
+        apt_mirrors: AptMirrorsConfig::default(),
+        entropy: EntropyConfig::default(),
+        low_memory: LowMemoryConfig::default(),
+        host_vars: HostVarsConfig::default(),
+        bootloader: BootloaderConfig::default(),
+        budget: BudgetConfig::default(),
+        privilege: PrivilegeConfig::default(),
+        confirmation: ConfirmationConfig::default(),
+        partitioning: PartitioningConfig::default(),
+        health_gate: HealthGateConfig::default(),
+        apt_lock: AptLockConfig::default(),
+        disk_health: DiskHealthConfig::default(),
+        updates: UpdatesConfig::default(),
+        late_commands: LateCommandsConfig::default(),
+        network_recovery: NetworkRecoveryConfig::default(),
+        nbde: NbdeConfig::default(),
     };
 
     // Should validate successfully